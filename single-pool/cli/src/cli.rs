@@ -93,6 +93,15 @@ pub enum Command {
 
     /// Display info for one or all single-validator stake pool(s)
     Display(DisplayCli),
+
+    /// List every single-validator stake pool on the cluster, with its vote
+    /// account, available stake, and token supply
+    ListAll,
+
+    /// Check one or all single-validator stake pool(s) for pool stake accounts
+    /// in unexpected states, such as deactivated or undelegated stake, and
+    /// suggest how to recover them
+    QuarantineReport(QuarantineReportCli),
 }
 
 #[derive(Clone, Debug, Parser)]
@@ -309,6 +318,22 @@ pub struct DisplayCli {
     pub all: bool,
 }
 
+#[derive(Clone, Debug, Args)]
+#[clap(group(pool_source_group().arg("all")))]
+pub struct QuarantineReportCli {
+    /// The pool to check
+    #[clap(value_parser = |p: &str| parse_address(p, "pool_address"))]
+    pub pool_address: Option<Pubkey>,
+
+    /// The vote account corresponding to the pool to check
+    #[clap(long = "vote-account", value_parser = |p: &str| parse_address(p, "vote_account_address"))]
+    pub vote_account_address: Option<Pubkey>,
+
+    /// Check all pools
+    #[clap(long)]
+    pub all: bool,
+}
+
 fn pool_source_group() -> ArgGroup<'static> {
     ArgGroup::new("pool-source")
         .required(true)
@@ -7,12 +7,15 @@ use {
     solana_client::{
         rpc_config::RpcProgramAccountsConfig,
         rpc_filter::{Memcmp, RpcFilterType},
+        rpc_request::MAX_MULTIPLE_ACCOUNTS,
     },
     solana_sdk::{
+        account::Account,
         borsh1::try_from_slice_unchecked,
+        program_pack::Pack,
         pubkey::Pubkey,
         signature::{Keypair, Signature, Signer},
-        stake,
+        stake::{self, state::StakeStateV2},
         transaction::Transaction,
     },
     solana_vote_program::{self as vote_program, vote_state::VoteState},
@@ -20,6 +23,7 @@ use {
         self, find_default_deposit_account_address, find_pool_address, find_pool_mint_address,
         find_pool_stake_address, instruction::SinglePoolInstruction, state::SinglePool,
     },
+    spl_token::state::Mint,
     spl_token_client::token::Token,
 };
 
@@ -79,6 +83,10 @@ impl Command {
                 command_create_stake(config, command_config).await
             }
             Command::Display(command_config) => command_display(config, command_config).await,
+            Command::ListAll => command_list_all(config).await,
+            Command::QuarantineReport(command_config) => {
+                command_quarantine_report(config, command_config).await
+            }
         }
     }
 }
@@ -755,32 +763,10 @@ async fn command_create_stake(config: &Config, command_config: CreateStakeCli) -
 // display stake pool(s)
 async fn command_display(config: &Config, command_config: DisplayCli) -> CommandResult {
     if command_config.all {
-        // the filter isn't necessary now but makes the cli forward-compatible
-        let pools = config
-            .rpc_client
-            .get_program_accounts_with_config(
-                &spl_single_pool::id(),
-                RpcProgramAccountsConfig {
-                    filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
-                        0,
-                        vec![1],
-                    ))]),
-                    ..RpcProgramAccountsConfig::default()
-                },
-            )
-            .await?;
-
-        let mut displays = vec![];
-        for pool in pools {
-            let vote_account_address =
-                try_from_slice_unchecked::<SinglePool>(&pool.1.data)?.vote_account_address;
-            displays.push(get_pool_display(config, pool.0, Some(vote_account_address)).await?);
-        }
-
         Ok(format_output(
             config,
             "DisplayAll".to_string(),
-            StakePoolListOutput(displays),
+            StakePoolListOutput(get_all_pool_displays(config).await?),
         ))
     } else {
         let pool_address = pool_address_from_args(
@@ -796,25 +782,191 @@ async fn command_display(config: &Config, command_config: DisplayCli) -> Command
     }
 }
 
-async fn get_pool_display(
+// list all stake pools
+async fn command_list_all(config: &Config) -> CommandResult {
+    Ok(format_output(
+        config,
+        "ListAll".to_string(),
+        StakePoolListOutput(get_all_pool_displays(config).await?),
+    ))
+}
+
+// check stake pool(s) for stake in unexpected states
+async fn command_quarantine_report(
+    config: &Config,
+    command_config: QuarantineReportCli,
+) -> CommandResult {
+    let pools = if command_config.all {
+        get_all_pools(config).await?
+    } else {
+        let pool_address = pool_address_from_args(
+            command_config.pool_address,
+            command_config.vote_account_address,
+        );
+
+        vec![(
+            pool_address,
+            get_pool_vote_account_address(config, pool_address).await?,
+        )]
+    };
+
+    let pool_stake_addresses = pools
+        .iter()
+        .map(|(pool_address, _)| find_pool_stake_address(&spl_single_pool::id(), pool_address))
+        .collect::<Vec<_>>();
+    let vote_account_addresses = pools
+        .iter()
+        .map(|(_, vote_account_address)| *vote_account_address)
+        .collect::<Vec<_>>();
+
+    let stake_accounts = get_multiple_accounts(config, &pool_stake_addresses).await?;
+    let vote_accounts = get_multiple_accounts(config, &vote_account_addresses).await?;
+    let minimum_delegation = quarantine::get_minimum_delegation(config).await?;
+
+    let reports = pools
+        .into_iter()
+        .zip(stake_accounts)
+        .zip(vote_accounts)
+        .map(
+            |(((pool_address, vote_account_address), maybe_stake), maybe_vote)| QuarantineOutput {
+                pool_address,
+                vote_account_address,
+                issues: quarantine::check_pool_stake(
+                    &pool_address,
+                    &vote_account_address,
+                    maybe_stake.as_ref(),
+                    maybe_vote.as_ref(),
+                    minimum_delegation,
+                ),
+            },
+        )
+        .collect();
+
+    Ok(format_output(
+        config,
+        "QuarantineReport".to_string(),
+        QuarantineReportOutput(reports),
+    ))
+}
+
+// returns every pool address along with its vote account address
+async fn get_all_pools(config: &Config) -> Result<Vec<(Pubkey, Pubkey)>, Error> {
+    // the filter isn't necessary now but makes the cli forward-compatible
+    let pools = config
+        .rpc_client
+        .get_program_accounts_with_config(
+            &spl_single_pool::id(),
+            RpcProgramAccountsConfig {
+                filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                    0,
+                    vec![1],
+                ))]),
+                ..RpcProgramAccountsConfig::default()
+            },
+        )
+        .await?;
+
+    pools
+        .into_iter()
+        .map(|(pool_address, pool)| -> Result<(Pubkey, Pubkey), Error> {
+            let vote_account_address =
+                try_from_slice_unchecked::<SinglePool>(&pool.data)?.vote_account_address;
+            Ok((pool_address, vote_account_address))
+        })
+        .collect()
+}
+
+async fn get_multiple_accounts(
+    config: &Config,
+    addresses: &[Pubkey],
+) -> Result<Vec<Option<Account>>, Error> {
+    let mut accounts = Vec::with_capacity(addresses.len());
+    for chunk in addresses.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        accounts.extend(config.rpc_client.get_multiple_accounts(chunk).await?);
+    }
+
+    Ok(accounts)
+}
+
+// fetches all pool stake accounts and mints in batches rather than one pool at
+// a time. pools with missing or undelegated stake are listed with no stake
+// rather than failing the whole command; quarantine-report explains those
+async fn get_all_pool_displays(config: &Config) -> Result<Vec<StakePoolOutput>, Error> {
+    let pools = get_all_pools(config).await?;
+
+    let pool_stake_addresses = pools
+        .iter()
+        .map(|(pool_address, _)| find_pool_stake_address(&spl_single_pool::id(), pool_address))
+        .collect::<Vec<_>>();
+    let pool_mint_addresses = pools
+        .iter()
+        .map(|(pool_address, _)| find_pool_mint_address(&spl_single_pool::id(), pool_address))
+        .collect::<Vec<_>>();
+
+    let stake_accounts = get_multiple_accounts(config, &pool_stake_addresses).await?;
+    let mint_accounts = get_multiple_accounts(config, &pool_mint_addresses).await?;
+    let minimum_delegation = quarantine::get_minimum_delegation(config).await?;
+
+    Ok(pools
+        .into_iter()
+        .zip(stake_accounts)
+        .zip(mint_accounts)
+        .map(
+            |(((pool_address, vote_account_address), maybe_stake), maybe_mint)| {
+                let available_stake = match maybe_stake
+                    .and_then(|account| bincode::deserialize::<StakeStateV2>(&account.data).ok())
+                {
+                    Some(StakeStateV2::Stake(_, stake, _)) => {
+                        stake.delegation.stake.saturating_sub(minimum_delegation)
+                    }
+                    _ => 0,
+                };
+
+                let token_supply = maybe_mint
+                    .and_then(|account| Mint::unpack(&account.data).ok())
+                    .map(|mint| mint.supply)
+                    .unwrap_or(0);
+
+                StakePoolOutput {
+                    pool_address,
+                    vote_account_address,
+                    available_stake,
+                    token_supply,
+                    signature: None,
+                }
+            },
+        )
+        .collect())
+}
+
+async fn get_pool_vote_account_address(
     config: &Config,
     pool_address: Pubkey,
-    maybe_vote_account: Option<Pubkey>,
-) -> Result<StakePoolOutput, Error> {
-    let vote_account_address = if let Some(address) = maybe_vote_account {
-        address
-    } else if let Some(pool_data) = config.program_client.get_account(pool_address).await? {
+) -> Result<Pubkey, Error> {
+    if let Some(pool_data) = config.program_client.get_account(pool_address).await? {
         if let Ok(data) = try_from_slice_unchecked::<SinglePool>(&pool_data.data) {
-            data.vote_account_address
+            Ok(data.vote_account_address)
         } else {
-            return Err(format!(
+            Err(format!(
                 "Failed to parse account at {}; is this a pool?",
                 pool_address
             )
-            .into());
+            .into())
         }
     } else {
-        return Err(format!("Pool {} does not exist", pool_address).into());
+        Err(format!("Pool {} does not exist", pool_address).into())
+    }
+}
+
+async fn get_pool_display(
+    config: &Config,
+    pool_address: Pubkey,
+    maybe_vote_account: Option<Pubkey>,
+) -> Result<StakePoolOutput, Error> {
+    let vote_account_address = if let Some(address) = maybe_vote_account {
+        address
+    } else {
+        get_pool_vote_account_address(config, pool_address).await?
     };
 
     let pool_stake_address = find_pool_stake_address(&spl_single_pool::id(), &pool_address);
@@ -207,6 +207,80 @@ impl Display for StakePoolListOutput {
     }
 }
 
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuarantineOutput {
+    #[serde_as(as = "DisplayFromStr")]
+    pub pool_address: Pubkey,
+    #[serde_as(as = "DisplayFromStr")]
+    pub vote_account_address: Pubkey,
+    pub issues: Vec<String>,
+}
+
+impl QuietDisplay for QuarantineOutput {}
+impl VerboseDisplay for QuarantineOutput {}
+
+impl Display for QuarantineOutput {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        writeln!(f)?;
+        writeln!(f, "{}", style("SPL Single-Validator Stake Pool").bold())?;
+        writeln_name_value(f, "  Pool address:", &self.pool_address.to_string())?;
+        writeln_name_value(
+            f,
+            "  Vote account address:",
+            &self.vote_account_address.to_string(),
+        )?;
+
+        if self.issues.is_empty() {
+            writeln!(f, "  No issues found")?;
+        } else {
+            for issue in &self.issues {
+                writeln!(f, "  {}", style(issue).yellow())?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QuarantineReportOutput(pub Vec<QuarantineOutput>);
+
+impl QuietDisplay for QuarantineReportOutput {}
+impl VerboseDisplay for QuarantineReportOutput {
+    fn write_str(&self, w: &mut dyn Write) -> Result {
+        let flagged = self.0.iter().filter(|svsp| !svsp.issues.is_empty()).count();
+        for svsp in &self.0 {
+            write!(w, "{}", svsp)?;
+        }
+
+        writeln!(w)?;
+        writeln_name_value(w, "Pools checked:", &self.0.len().to_string())?;
+        writeln_name_value(w, "Pools with issues:", &flagged.to_string())?;
+
+        Ok(())
+    }
+}
+
+impl Display for QuarantineReportOutput {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        // healthy pools are only listed in verbose mode, or when checking just one
+        let flagged = self.0.iter().filter(|svsp| !svsp.issues.is_empty()).count();
+        for svsp in &self.0 {
+            if !svsp.issues.is_empty() || self.0.len() == 1 {
+                svsp.fmt(f)?;
+            }
+        }
+
+        writeln!(f)?;
+        writeln_name_value(f, "Pools checked:", &self.0.len().to_string())?;
+        writeln_name_value(f, "Pools with issues:", &flagged.to_string())?;
+
+        Ok(())
+    }
+}
+
 #[serde_as]
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
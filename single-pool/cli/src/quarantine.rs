@@ -4,6 +4,7 @@
 use {
     crate::config::*,
     solana_sdk::{
+        account::Account,
         instruction::Instruction,
         native_token::LAMPORTS_PER_SOL,
         pubkey::Pubkey,
@@ -13,7 +14,9 @@ use {
         },
         system_instruction,
         sysvar::{self, rent::Rent},
+        vote,
     },
+    spl_single_pool::{self, find_pool_stake_address, find_pool_stake_authority_address},
 };
 
 pub async fn get_rent(config: &Config) -> Result<Rent, Error> {
@@ -76,3 +79,102 @@ pub async fn create_uninitialized_stake_account_instruction(
         &stake::program::id(),
     ))
 }
+
+// returns a description of every way the pool stake and vote accounts differ
+// from those of a healthy pool. an empty list means nothing needs fixing
+pub fn check_pool_stake(
+    pool_address: &Pubkey,
+    vote_account_address: &Pubkey,
+    maybe_stake_account: Option<&Account>,
+    maybe_vote_account: Option<&Account>,
+    minimum_delegation: u64,
+) -> Vec<String> {
+    let mut issues = vec![];
+
+    match maybe_vote_account {
+        Some(vote_account) if vote_account.owner == vote::program::id() => (),
+        Some(_) => issues.push(format!(
+            "Vote account {} is not owned by the vote program",
+            vote_account_address
+        )),
+        None => issues.push(format!(
+            "Vote account {} does not exist",
+            vote_account_address
+        )),
+    }
+
+    let pool_stake_address = find_pool_stake_address(&spl_single_pool::id(), pool_address);
+    let stake_account = match maybe_stake_account {
+        Some(stake_account) if stake_account.owner == stake::program::id() => stake_account,
+        Some(_) => {
+            issues.push(format!(
+                "Pool stake account {} is not owned by the stake program",
+                pool_stake_address
+            ));
+            return issues;
+        }
+        None => {
+            issues.push(format!(
+                "Pool stake account {} does not exist",
+                pool_stake_address
+            ));
+            return issues;
+        }
+    };
+
+    match bincode::deserialize::<StakeStateV2>(&stake_account.data) {
+        Ok(StakeStateV2::Stake(meta, stake, _)) => {
+            let pool_stake_authority =
+                find_pool_stake_authority_address(&spl_single_pool::id(), pool_address);
+            if meta.authorized.staker != pool_stake_authority
+                || meta.authorized.withdrawer != pool_stake_authority
+            {
+                issues.push(format!(
+                    "Pool stake account {} has staker {} and withdrawer {}, expected {}",
+                    pool_stake_address,
+                    meta.authorized.staker,
+                    meta.authorized.withdrawer,
+                    pool_stake_authority,
+                ));
+            }
+
+            if stake.delegation.voter_pubkey != *vote_account_address {
+                issues.push(format!(
+                    "Pool stake account {} is delegated to {} instead of {}",
+                    pool_stake_address, stake.delegation.voter_pubkey, vote_account_address,
+                ));
+            }
+
+            if stake.delegation.deactivation_epoch != u64::MAX {
+                issues.push(format!(
+                    "Pool stake account {} was deactivated in epoch {}; once fully deactivated, \
+                     restake it with `manage reactivate-pool-stake`",
+                    pool_stake_address, stake.delegation.deactivation_epoch,
+                ));
+            } else if stake.delegation.stake < minimum_delegation {
+                issues.push(format!(
+                    "Pool stake account {} delegates {} lamports, below the minimum delegation of {}",
+                    pool_stake_address, stake.delegation.stake, minimum_delegation,
+                ));
+            }
+        }
+        Ok(StakeStateV2::Initialized(_)) => issues.push(format!(
+            "Pool stake account {} is undelegated",
+            pool_stake_address
+        )),
+        Ok(StakeStateV2::Uninitialized) => issues.push(format!(
+            "Pool stake account {} is uninitialized",
+            pool_stake_address
+        )),
+        Ok(StakeStateV2::RewardsPool) => issues.push(format!(
+            "Pool stake account {} is a rewards pool",
+            pool_stake_address
+        )),
+        Err(_) => issues.push(format!(
+            "Failed to parse pool stake account {}",
+            pool_stake_address
+        )),
+    }
+
+    issues
+}
@@ -427,3 +427,54 @@ async fn update_metadata() {
         .unwrap();
     assert!(status.success());
 }
+
+#[tokio::test]
+#[serial]
+async fn list_all() {
+    let env = setup(true).await;
+
+    let output = Command::new(SVSP_CLI)
+        .args(["list-all", "-C", &env.config_file_path, "--output", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let output: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let pools = output["commandOutput"].as_array().unwrap();
+    assert_eq!(pools.len(), 1);
+    assert_eq!(
+        pools[0]["voteAccountAddress"].as_str().unwrap(),
+        env.vote_account.to_string()
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn quarantine_report() {
+    let env = setup(true).await;
+
+    let output = Command::new(SVSP_CLI)
+        .args([
+            "quarantine-report",
+            "-C",
+            &env.config_file_path,
+            "--vote-account",
+            &env.vote_account.to_string(),
+            "--output",
+            "json",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let output: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let reports = output["commandOutput"].as_array().unwrap();
+    assert_eq!(reports.len(), 1);
+    assert!(reports[0]["issues"].as_array().unwrap().is_empty());
+
+    let status = Command::new(SVSP_CLI)
+        .args(["quarantine-report", "-C", &env.config_file_path, "--all"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
@@ -13,10 +13,15 @@ use {
     },
     solana_sdk::{compute_budget::ComputeBudgetInstruction, transaction::Transaction},
     spl_stake_pool::{
-        find_withdraw_authority_program_address,
-        state::{StakePool, ValidatorList},
+        find_fee_split_program_address, find_unstake_ticket_ledger_program_address,
+        find_validator_policy_program_address, find_withdraw_authority_program_address,
+        state::{
+            AccountType, FeeSplit, StakePool, UnstakeTicket, UnstakeTicketLedger, ValidatorList,
+            ValidatorPolicy, ValidatorRewardHistory,
+        },
     },
-    std::collections::HashSet,
+    spl_token_2022::offchain::AccountDataResult,
+    std::{collections::HashSet, future},
 };
 
 pub(crate) type Error = Box<dyn std::error::Error>;
@@ -41,6 +46,107 @@ pub fn get_validator_list(
     Ok(validator_list)
 }
 
+pub fn get_validator_policy(
+    rpc_client: &RpcClient,
+    stake_pool_address: &Pubkey,
+) -> Result<Option<ValidatorPolicy>, Error> {
+    let (validator_policy_address, _) =
+        find_validator_policy_program_address(&spl_stake_pool::id(), stake_pool_address);
+    let Some(account) = rpc_client
+        .get_account_with_commitment(&validator_policy_address, rpc_client.commitment())?
+        .value
+    else {
+        return Ok(None);
+    };
+    let validator_policy = try_from_slice_unchecked::<ValidatorPolicy>(account.data.as_slice())
+        .map_err(|err| {
+            format!(
+                "Invalid validator policy {}: {}",
+                validator_policy_address, err
+            )
+        })?;
+    Ok(Some(validator_policy))
+}
+
+/// Median of the credits earned during `epoch` by the staked vote accounts of
+/// the cluster, or `None` if no vote account earned credits in `epoch`. RPC
+/// nodes only return the credits of the last few epochs.
+pub fn get_cluster_epoch_credits(rpc_client: &RpcClient, epoch: u64) -> Result<Option<u64>, Error> {
+    let vote_accounts = rpc_client.get_vote_accounts()?;
+    let mut epoch_credits = vote_accounts
+        .current
+        .iter()
+        .chain(vote_accounts.delinquent.iter())
+        .filter(|vote_account| vote_account.activated_stake > 0)
+        .filter_map(|vote_account| {
+            vote_account
+                .epoch_credits
+                .iter()
+                .find(|(credits_epoch, _, _)| *credits_epoch == epoch)
+                .map(|(_, credits, prev_credits)| credits.saturating_sub(*prev_credits))
+        })
+        .collect::<Vec<_>>();
+    if epoch_credits.is_empty() {
+        return Ok(None);
+    }
+    let median = epoch_credits.len() / 2;
+    Ok(Some(*epoch_credits.select_nth_unstable(median).1))
+}
+
+pub fn get_fee_split(
+    rpc_client: &RpcClient,
+    stake_pool_address: &Pubkey,
+) -> Result<Option<FeeSplit>, Error> {
+    let (fee_split_address, _) =
+        find_fee_split_program_address(&spl_stake_pool::id(), stake_pool_address);
+    let Some(account) = rpc_client
+        .get_account_with_commitment(&fee_split_address, rpc_client.commitment())?
+        .value
+    else {
+        return Ok(None);
+    };
+    let fee_split = try_from_slice_unchecked::<FeeSplit>(account.data.as_slice())
+        .map_err(|err| format!("Invalid fee split {}: {}", fee_split_address, err))?;
+    Ok(Some(fee_split))
+}
+
+pub fn get_unstake_ticket_ledger(
+    rpc_client: &RpcClient,
+    stake_pool_address: &Pubkey,
+) -> Result<Option<UnstakeTicketLedger>, Error> {
+    let (unstake_ticket_ledger_address, _) =
+        find_unstake_ticket_ledger_program_address(&spl_stake_pool::id(), stake_pool_address);
+    let Some(account) = rpc_client
+        .get_account_with_commitment(&unstake_ticket_ledger_address, rpc_client.commitment())?
+        .value
+    else {
+        return Ok(None);
+    };
+    let unstake_ticket_ledger = try_from_slice_unchecked::<UnstakeTicketLedger>(
+        account.data.as_slice(),
+    )
+    .map_err(|err| {
+        format!(
+            "Invalid unstake ticket ledger {}: {}",
+            unstake_ticket_ledger_address, err
+        )
+    })?;
+    Ok(Some(unstake_ticket_ledger))
+}
+
+pub fn get_unstake_ticket(
+    rpc_client: &RpcClient,
+    unstake_ticket_address: &Pubkey,
+) -> Result<UnstakeTicket, Error> {
+    let account_data = rpc_client.get_account_data(unstake_ticket_address)?;
+    let unstake_ticket = try_from_slice_unchecked::<UnstakeTicket>(account_data.as_slice())
+        .map_err(|err| format!("Invalid unstake ticket {}: {}", unstake_ticket_address, err))?;
+    if !unstake_ticket.is_valid() {
+        return Err(format!("Invalid unstake ticket {}", unstake_ticket_address).into());
+    }
+    Ok(unstake_ticket)
+}
+
 pub fn get_token_account(
     rpc_client: &RpcClient,
     token_account_address: &Pubkey,
@@ -72,6 +178,34 @@ pub fn get_token_mint(
     Ok(token_mint)
 }
 
+/// Appends the accounts required by the pool mint's transfer hook, if any, to
+/// an instruction that transfers the withdrawal fee to the manager
+pub(crate) fn add_transfer_hook_accounts(
+    rpc_client: &RpcClient,
+    instruction: &mut Instruction,
+    stake_pool: &StakePool,
+    pool_tokens_from: &Pubkey,
+    user_transfer_authority: &Pubkey,
+    pool_tokens_fee: u64,
+) -> Result<(), Error> {
+    futures::executor::block_on(spl_stake_pool::instruction::add_transfer_hook_accounts(
+        instruction,
+        pool_tokens_from,
+        &stake_pool.manager_fee_account,
+        &stake_pool.pool_mint,
+        user_transfer_authority,
+        pool_tokens_fee,
+        |address| {
+            let account_data: AccountDataResult = rpc_client
+                .get_account_with_commitment(&address, rpc_client.commitment())
+                .map(|response| response.value.map(|account| account.data))
+                .map_err(|err| err.into());
+            future::ready(account_data)
+        },
+    ))
+    .map_err(|err| format!("Failed to resolve transfer hook accounts: {}", err).into())
+}
+
 pub(crate) fn get_stake_state(
     rpc_client: &RpcClient,
     stake_address: &Pubkey,
@@ -125,6 +259,95 @@ pub(crate) fn get_stake_pools(
         })
 }
 
+pub(crate) fn get_unstake_tickets(
+    rpc_client: &RpcClient,
+    stake_pool_address: &Pubkey,
+    owner: Option<&Pubkey>,
+) -> Result<Vec<(Pubkey, UnstakeTicket)>, ClientError> {
+    // 0 is the account type, followed by the stake pool and the owner
+    let mut filters = vec![
+        RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+            0,
+            vec![AccountType::UnstakeTicket as u8],
+        )),
+        RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+            1,
+            stake_pool_address.to_bytes().to_vec(),
+        )),
+    ];
+    if let Some(owner) = owner {
+        filters.push(RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+            33,
+            owner.to_bytes().to_vec(),
+        )));
+    }
+    rpc_client
+        .get_program_accounts_with_config(
+            &spl_stake_pool::id(),
+            RpcProgramAccountsConfig {
+                filters: Some(filters),
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    ..RpcAccountInfoConfig::default()
+                },
+                ..RpcProgramAccountsConfig::default()
+            },
+        )
+        .map(|accounts| {
+            accounts
+                .into_iter()
+                .filter_map(|(address, account)| {
+                    match try_from_slice_unchecked::<UnstakeTicket>(account.data.as_slice()) {
+                        Ok(unstake_ticket) => Some((address, unstake_ticket)),
+                        Err(err) => {
+                            eprintln!("Invalid unstake ticket data for {}: {}", address, err);
+                            None
+                        }
+                    }
+                })
+                .collect()
+        })
+}
+
+pub(crate) fn get_validator_reward_history(
+    rpc_client: &RpcClient,
+    stake_pool_address: &Pubkey,
+) -> Result<Option<(Pubkey, ValidatorRewardHistory)>, ClientError> {
+    let accounts = rpc_client.get_program_accounts_with_config(
+        &spl_stake_pool::id(),
+        RpcProgramAccountsConfig {
+            // 0 is the account type, followed by the stake pool
+            filters: Some(vec![
+                RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                    0,
+                    vec![AccountType::ValidatorRewardHistory as u8],
+                )),
+                RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                    1,
+                    stake_pool_address.to_bytes().to_vec(),
+                )),
+            ]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..RpcAccountInfoConfig::default()
+            },
+            ..RpcProgramAccountsConfig::default()
+        },
+    )?;
+    Ok(accounts.into_iter().find_map(|(address, account)| {
+        match try_from_slice_unchecked::<ValidatorRewardHistory>(account.data.as_slice()) {
+            Ok(validator_reward_history) => Some((address, validator_reward_history)),
+            Err(err) => {
+                eprintln!(
+                    "Invalid validator reward history data for {}: {}",
+                    address, err
+                );
+                None
+            }
+        }
+    }))
+}
+
 pub(crate) fn get_all_stake(
     rpc_client: &RpcClient,
     authorized_staker: &Pubkey,
@@ -151,6 +374,30 @@ pub(crate) fn get_all_stake(
         .collect())
 }
 
+/// Fetch stake accounts in batches, returning the lamports and stake state of
+/// each account, or `None` if it does not exist or is not a stake account
+pub(crate) fn get_stake_accounts(
+    rpc_client: &RpcClient,
+    stake_addresses: &[Pubkey],
+) -> Result<Vec<Option<(u64, stake::state::StakeStateV2)>>, ClientError> {
+    // maximum number of accounts accepted by `getMultipleAccounts`
+    const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+    let mut stake_accounts = Vec::with_capacity(stake_addresses.len());
+    for addresses in stake_addresses.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        for account in rpc_client.get_multiple_accounts(addresses)? {
+            stake_accounts.push(account.and_then(|account| {
+                if account.owner != stake::program::id() {
+                    return None;
+                }
+                deserialize(account.data.as_slice())
+                    .ok()
+                    .map(|stake_state| (account.lamports, stake_state))
+            }));
+        }
+    }
+    Ok(stake_accounts)
+}
+
 /// Helper function to add a compute unit limit instruction to a given set
 /// of instructions
 pub(crate) fn add_compute_unit_limit_from_simulation(
@@ -1,11 +1,24 @@
 #![allow(clippy::arithmetic_side_effects)]
 mod client;
 mod output;
+mod rebalance;
+mod rewards;
+mod verify;
 
 use {
     crate::{
         client::*,
-        output::{CliStakePool, CliStakePoolDetails, CliStakePoolStakeAccountInfo, CliStakePools},
+        output::{
+            CliRebalancePlan, CliRebalanceStep, CliRebalanceTarget, CliStakePool,
+            CliStakePoolDetails, CliStakePoolStakeAccountInfo, CliStakePools, CliUnstakeTicket,
+            CliUnstakeTicketCrank, CliUnstakeTickets, CliValidatorRewards, CliVerifyReport,
+        },
+        rebalance::{
+            batch_steps, plan_rebalance, plan_unstake_ticket_decreases, PoolBalances,
+            RebalanceOptions, RebalanceStep, TargetWeights, TransientStake, ValidatorBalance,
+        },
+        rewards::{epochs_per_year, stake_weighted_apy, validator_apy},
+        verify::{verify_pool, PoolAccounts, StakeAccount, ValidatorAccounts},
     },
     bincode::deserialize,
     clap::{
@@ -38,6 +51,7 @@ use {
         hash::Hash,
         message::Message,
         native_token::{self, Sol},
+        packet::PACKET_DATA_SIZE,
         signature::{Keypair, Signer},
         signers::Signers,
         system_instruction,
@@ -46,12 +60,16 @@ use {
     spl_associated_token_account::instruction::create_associated_token_account,
     spl_associated_token_account_client::address::get_associated_token_address,
     spl_stake_pool::{
-        self, find_stake_program_address, find_transient_stake_program_address,
-        find_withdraw_authority_program_address,
+        self, find_deposit_authority_program_address, find_ephemeral_stake_program_address,
+        find_stake_program_address, find_transient_stake_program_address,
+        find_unstake_ticket_ledger_program_address, find_withdraw_authority_program_address,
         instruction::{FundingType, PreferredValidatorType},
         minimum_delegation,
-        state::{Fee, FeeType, StakePool, ValidatorList, ValidatorStakeInfo},
-        MINIMUM_RESERVE_LAMPORTS,
+        state::{
+            Fee, FeeRecipient, FeeSplit, FeeType, StakePool, StakeStatus, UnstakeTicket,
+            ValidatorList, ValidatorRewardHistory, ValidatorStakeInfo,
+        },
+        MAX_TRANSIENT_STAKE_ACCOUNTS, MINIMUM_RESERVE_LAMPORTS,
     },
     std::{cmp::Ordering, num::NonZeroU32, process::exit, rc::Rc},
 };
@@ -143,6 +161,29 @@ where
     }
 }
 
+fn parse_fee_recipient<T>(string: T) -> Result<FeeRecipient, String>
+where
+    T: AsRef<str> + std::fmt::Display,
+{
+    let (token_account, bps) = string.as_ref().split_once('=').ok_or_else(|| {
+        format!("Invalid fee recipient {string}, expected TOKEN_ACCOUNT_ADDRESS=BPS")
+    })?;
+    Ok(FeeRecipient {
+        token_account: token_account
+            .parse::<Pubkey>()
+            .map_err(|err| format!("Invalid fee recipient address {token_account}: {err}"))?,
+        bps: bps
+            .parse::<u16>()
+            .map_err(|err| format!("Invalid fee recipient share {bps}: {err}"))?,
+    })
+}
+fn is_fee_recipient<T>(string: T) -> Result<(), String>
+where
+    T: AsRef<str> + std::fmt::Display,
+{
+    parse_fee_recipient(string).map(|_| ())
+}
+
 fn check_stake_pool_fees(
     epoch_fee: &Fee,
     withdrawal_fee: &Fee,
@@ -925,6 +966,363 @@ fn command_decrease_validator_stake(
     Ok(())
 }
 
+fn command_increase_additional_validator_stake(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    vote_account: &Pubkey,
+    amount: f64,
+    ephemeral_stake_seed: u64,
+) -> CommandResult {
+    let lamports = native_token::sol_to_lamports(amount);
+    if !config.no_update {
+        command_update(config, stake_pool_address, false, false, false)?;
+    }
+
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    let validator_list = get_validator_list(&config.rpc_client, &stake_pool.validator_list)?;
+    let validator_stake_info = validator_list
+        .find(vote_account)
+        .ok_or("Vote account not found in validator list")?;
+    let validator_seed = NonZeroU32::new(validator_stake_info.validator_seed_suffix.into());
+
+    let mut signers = vec![config.fee_payer.as_ref(), config.staker.as_ref()];
+    unique_signers!(signers);
+    let transaction = checked_transaction_with_signers(
+        config,
+        &[
+            spl_stake_pool::instruction::increase_additional_validator_stake_with_vote(
+                &spl_stake_pool::id(),
+                &stake_pool,
+                stake_pool_address,
+                vote_account,
+                lamports,
+                validator_seed,
+                validator_stake_info.transient_seed_suffix.into(),
+                ephemeral_stake_seed,
+            ),
+        ],
+        &signers,
+    )?;
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
+fn command_decrease_additional_validator_stake(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    vote_account: &Pubkey,
+    amount: f64,
+    ephemeral_stake_seed: u64,
+) -> CommandResult {
+    let lamports = native_token::sol_to_lamports(amount);
+    if !config.no_update {
+        command_update(config, stake_pool_address, false, false, false)?;
+    }
+
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    let validator_list = get_validator_list(&config.rpc_client, &stake_pool.validator_list)?;
+    let validator_stake_info = validator_list
+        .find(vote_account)
+        .ok_or("Vote account not found in validator list")?;
+    let validator_seed = NonZeroU32::new(validator_stake_info.validator_seed_suffix.into());
+
+    let mut signers = vec![config.fee_payer.as_ref(), config.staker.as_ref()];
+    unique_signers!(signers);
+    let transaction = checked_transaction_with_signers(
+        config,
+        &[
+            spl_stake_pool::instruction::decrease_additional_validator_stake_with_vote(
+                &spl_stake_pool::id(),
+                &stake_pool,
+                stake_pool_address,
+                vote_account,
+                lamports,
+                validator_seed,
+                validator_stake_info.transient_seed_suffix.into(),
+                ephemeral_stake_seed,
+            ),
+        ],
+        &signers,
+    )?;
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
+fn command_redelegate(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    source_vote_account: &Pubkey,
+    destination_vote_account: &Pubkey,
+    amount: f64,
+    ephemeral_stake_seed: u64,
+) -> CommandResult {
+    let lamports = native_token::sol_to_lamports(amount);
+    if !config.no_update {
+        command_update(config, stake_pool_address, false, false, false)?;
+    }
+
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    let validator_list = get_validator_list(&config.rpc_client, &stake_pool.validator_list)?;
+    let source = validator_list
+        .find(source_vote_account)
+        .ok_or("Source vote account not found in validator list")?;
+    let destination = validator_list
+        .find(destination_vote_account)
+        .ok_or("Destination vote account not found in validator list")?;
+
+    let mut signers = vec![config.fee_payer.as_ref(), config.staker.as_ref()];
+    unique_signers!(signers);
+    let transaction = checked_transaction_with_signers(
+        config,
+        &[redelegate_instruction(
+            &stake_pool,
+            stake_pool_address,
+            source,
+            destination,
+            lamports,
+            ephemeral_stake_seed,
+        )],
+        &signers,
+    )?;
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
+fn command_rebalance(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    weights: &TargetWeights,
+    options: &RebalanceOptions,
+) -> CommandResult {
+    if !config.no_update {
+        command_update(config, stake_pool_address, false, false, false)?;
+    }
+
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    let validator_list = get_validator_list(&config.rpc_client, &stake_pool.validator_list)?;
+    let pool = get_pool_balances(config, stake_pool_address, &stake_pool, &validator_list)?;
+
+    let plan = plan_rebalance(&pool, weights, options)?;
+    let cli_rebalance_plan = CliRebalancePlan {
+        reserve_stake_lamports: pool.reserve_lamports,
+        planned_reserve_stake_lamports: plan.reserve_lamports,
+        validators: plan.targets.iter().map(CliRebalanceTarget::from).collect(),
+        steps: plan.steps.iter().map(CliRebalanceStep::from).collect(),
+        incomplete: plan.is_incomplete(options.minimum_move_lamports),
+    };
+    println!(
+        "{}",
+        config.output_format.formatted_string(&cli_rebalance_plan)
+    );
+
+    let mut signers = vec![config.fee_payer.as_ref(), config.staker.as_ref()];
+    unique_signers!(signers);
+    for steps in batch_steps(&plan.steps, MAX_TRANSIENT_STAKE_ACCOUNTS) {
+        let mut instructions: Vec<Instruction> = vec![];
+        for step in steps {
+            let instruction =
+                rebalance_instruction(&stake_pool, stake_pool_address, &validator_list, step)?;
+            instructions.push(instruction);
+            if !fits_in_transaction(config, &instructions, signers.len()) {
+                // flush everything but the new instruction, which starts the
+                // next transaction
+                let instruction = instructions.pop().unwrap();
+                let transaction =
+                    checked_transaction_with_signers(config, &instructions, &signers)?;
+                send_transaction(config, transaction)?;
+                instructions = vec![instruction];
+            }
+        }
+        if !instructions.is_empty() {
+            let transaction = checked_transaction_with_signers(config, &instructions, &signers)?;
+            send_transaction(config, transaction)?;
+        }
+    }
+    Ok(())
+}
+
+/// Read the balances of the stake pool's reserve and active validators, with
+/// the state of their transient stake accounts
+fn get_pool_balances(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    stake_pool: &StakePool,
+    validator_list: &ValidatorList,
+) -> Result<PoolBalances, Error> {
+    let epoch_info = config.rpc_client.get_epoch_info()?;
+    let reserve_stake = config.rpc_client.get_account(&stake_pool.reserve_stake)?;
+    let stake_rent = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(STAKE_STATE_LEN)?;
+    let stake_minimum_delegation = config.rpc_client.get_stake_minimum_delegation()?;
+
+    let mut validators = vec![];
+    for validator in validator_list.validators.iter() {
+        if validator.status != StakeStatus::Active.into() {
+            continue;
+        }
+        let transient_stake_lamports = u64::from(validator.transient_stake_lamports);
+        let transient_stake = if transient_stake_lamports == 0 {
+            TransientStake::None
+        } else {
+            let (transient_stake_address, _) = find_transient_stake_program_address(
+                &spl_stake_pool::id(),
+                &validator.vote_account_address,
+                stake_pool_address,
+                validator.transient_seed_suffix.into(),
+            );
+            match get_stake_state(&config.rpc_client, &transient_stake_address)? {
+                stake::state::StakeStateV2::Stake(_, stake, _)
+                    if stake.delegation.activation_epoch == epoch_info.epoch
+                        && stake.delegation.deactivation_epoch == u64::MAX =>
+                {
+                    TransientStake::Activating
+                }
+                _ => TransientStake::Locked,
+            }
+        };
+        validators.push(ValidatorBalance {
+            vote_account_address: validator.vote_account_address,
+            active_stake_lamports: validator.active_stake_lamports.into(),
+            transient_stake_lamports,
+            transient_stake,
+        });
+    }
+    Ok(PoolBalances {
+        reserve_lamports: reserve_stake.lamports,
+        stake_rent,
+        minimum_delegation: minimum_delegation(stake_minimum_delegation),
+        validators,
+    })
+}
+
+/// Check that the instructions fit in one transaction, keeping room for the
+/// compute budget instructions added when sending it
+fn fits_in_transaction(config: &Config, instructions: &[Instruction], num_signers: usize) -> bool {
+    const COMPUTE_BUDGET_INSTRUCTIONS_SIZE: usize = 64;
+    let message = Message::new(instructions, Some(&config.fee_payer.pubkey()));
+    let size = 1 + num_signers * 64 + message.serialize().len() + COMPUTE_BUDGET_INSTRUCTIONS_SIZE;
+    size <= PACKET_DATA_SIZE
+}
+
+fn rebalance_instruction(
+    stake_pool: &StakePool,
+    stake_pool_address: &Pubkey,
+    validator_list: &ValidatorList,
+    step: &RebalanceStep,
+) -> Result<Instruction, Error> {
+    let find_validator = |vote_account_address: &Pubkey| {
+        validator_list.find(vote_account_address).ok_or_else(|| {
+            format!(
+                "Vote account {} not found in validator list",
+                vote_account_address
+            )
+        })
+    };
+    let instruction = match step {
+        RebalanceStep::Increase {
+            vote_account_address,
+            lamports,
+        } => {
+            let validator = find_validator(vote_account_address)?;
+            spl_stake_pool::instruction::increase_additional_validator_stake_with_vote(
+                &spl_stake_pool::id(),
+                stake_pool,
+                stake_pool_address,
+                vote_account_address,
+                *lamports,
+                NonZeroU32::new(validator.validator_seed_suffix.into()),
+                validator.transient_seed_suffix.into(),
+                0,
+            )
+        }
+        RebalanceStep::Decrease {
+            vote_account_address,
+            lamports,
+        } => {
+            let validator = find_validator(vote_account_address)?;
+            spl_stake_pool::instruction::decrease_validator_stake_with_vote(
+                &spl_stake_pool::id(),
+                stake_pool,
+                stake_pool_address,
+                vote_account_address,
+                *lamports,
+                NonZeroU32::new(validator.validator_seed_suffix.into()),
+                validator.transient_seed_suffix.into(),
+            )
+        }
+        RebalanceStep::Redelegate {
+            source_vote_account_address,
+            destination_vote_account_address,
+            lamports,
+        } => redelegate_instruction(
+            stake_pool,
+            stake_pool_address,
+            find_validator(source_vote_account_address)?,
+            find_validator(destination_vote_account_address)?,
+            *lamports,
+            0,
+        ),
+    };
+    Ok(instruction)
+}
+
+#[allow(deprecated)]
+fn redelegate_instruction(
+    stake_pool: &StakePool,
+    stake_pool_address: &Pubkey,
+    source: &ValidatorStakeInfo,
+    destination: &ValidatorStakeInfo,
+    lamports: u64,
+    ephemeral_stake_seed: u64,
+) -> Instruction {
+    let program_id = spl_stake_pool::id();
+    let (source_validator_stake, _) = find_stake_program_address(
+        &program_id,
+        &source.vote_account_address,
+        stake_pool_address,
+        NonZeroU32::new(source.validator_seed_suffix.into()),
+    );
+    let (source_transient_stake, _) = find_transient_stake_program_address(
+        &program_id,
+        &source.vote_account_address,
+        stake_pool_address,
+        source.transient_seed_suffix.into(),
+    );
+    let (ephemeral_stake, _) =
+        find_ephemeral_stake_program_address(&program_id, stake_pool_address, ephemeral_stake_seed);
+    let (destination_validator_stake, _) = find_stake_program_address(
+        &program_id,
+        &destination.vote_account_address,
+        stake_pool_address,
+        NonZeroU32::new(destination.validator_seed_suffix.into()),
+    );
+    let (destination_transient_stake, _) = find_transient_stake_program_address(
+        &program_id,
+        &destination.vote_account_address,
+        stake_pool_address,
+        destination.transient_seed_suffix.into(),
+    );
+    spl_stake_pool::instruction::redelegate(
+        &program_id,
+        stake_pool_address,
+        &stake_pool.staker,
+        &find_withdraw_authority_program_address(&program_id, stake_pool_address).0,
+        &stake_pool.validator_list,
+        &stake_pool.reserve_stake,
+        &source_validator_stake,
+        &source_transient_stake,
+        &ephemeral_stake,
+        &destination_transient_stake,
+        &destination_validator_stake,
+        &destination.vote_account_address,
+        lamports,
+        source.transient_seed_suffix.into(),
+        ephemeral_stake_seed,
+        destination.transient_seed_suffix.into(),
+    )
+}
+
 fn command_set_preferred_validator(
     config: &Config,
     stake_pool_address: &Pubkey,
@@ -982,6 +1380,22 @@ fn add_associated_token_account(
     account
 }
 
+fn pool_tokens_to_amount(
+    config: &Config,
+    stake_pool: &StakePool,
+    pool_amount: Option<f64>,
+) -> Result<Option<u64>, Error> {
+    pool_amount
+        .map(|pool_amount| {
+            let pool_mint = get_token_mint(&config.rpc_client, &stake_pool.pool_mint)?;
+            Ok(spl_token::ui_amount_to_amount(
+                pool_amount,
+                pool_mint.decimals,
+            ))
+        })
+        .transpose()
+}
+
 fn command_deposit_stake(
     config: &Config,
     stake_pool_address: &Pubkey,
@@ -989,12 +1403,15 @@ fn command_deposit_stake(
     withdraw_authority: Box<dyn Signer>,
     pool_token_receiver_account: &Option<Pubkey>,
     referrer_token_account: &Option<Pubkey>,
+    minimum_pool_tokens_out: Option<f64>,
 ) -> CommandResult {
     if !config.no_update {
         command_update(config, stake_pool_address, false, false, false)?;
     }
 
     let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    let minimum_pool_tokens_out =
+        pool_tokens_to_amount(config, &stake_pool, minimum_pool_tokens_out)?;
     let stake_state = get_stake_state(&config.rpc_client, stake)?;
 
     if config.verbose {
@@ -1061,11 +1478,47 @@ fn command_deposit_stake(
                 return Err(error.into());
             }
 
-            spl_stake_pool::instruction::deposit_stake_with_authority(
+            if let Some(minimum_pool_tokens_out) = minimum_pool_tokens_out {
+                spl_stake_pool::instruction::deposit_stake_with_authority_and_slippage(
+                    &spl_stake_pool::id(),
+                    stake_pool_address,
+                    &stake_pool.validator_list,
+                    &stake_deposit_authority.pubkey(),
+                    &pool_withdraw_authority,
+                    stake,
+                    &withdraw_authority.pubkey(),
+                    &validator_stake_account,
+                    &stake_pool.reserve_stake,
+                    &pool_token_receiver_account,
+                    &stake_pool.manager_fee_account,
+                    &referrer_token_account,
+                    &stake_pool.pool_mint,
+                    &spl_token::id(),
+                    minimum_pool_tokens_out,
+                )
+            } else {
+                spl_stake_pool::instruction::deposit_stake_with_authority(
+                    &spl_stake_pool::id(),
+                    stake_pool_address,
+                    &stake_pool.validator_list,
+                    &stake_deposit_authority.pubkey(),
+                    &pool_withdraw_authority,
+                    stake,
+                    &withdraw_authority.pubkey(),
+                    &validator_stake_account,
+                    &stake_pool.reserve_stake,
+                    &pool_token_receiver_account,
+                    &stake_pool.manager_fee_account,
+                    &referrer_token_account,
+                    &stake_pool.pool_mint,
+                    &spl_token::id(),
+                )
+            }
+        } else if let Some(minimum_pool_tokens_out) = minimum_pool_tokens_out {
+            spl_stake_pool::instruction::deposit_stake_with_slippage(
                 &spl_stake_pool::id(),
                 stake_pool_address,
                 &stake_pool.validator_list,
-                &stake_deposit_authority.pubkey(),
                 &pool_withdraw_authority,
                 stake,
                 &withdraw_authority.pubkey(),
@@ -1076,6 +1529,7 @@ fn command_deposit_stake(
                 &referrer_token_account,
                 &stake_pool.pool_mint,
                 &spl_token::id(),
+                minimum_pool_tokens_out,
             )
         } else {
             spl_stake_pool::instruction::deposit_stake(
@@ -1246,6 +1700,7 @@ fn command_deposit_sol(
     pool_token_receiver_account: &Option<Pubkey>,
     referrer_token_account: &Option<Pubkey>,
     amount: f64,
+    minimum_pool_tokens_out: Option<f64>,
 ) -> CommandResult {
     if !config.no_update {
         command_update(config, stake_pool_address, false, false, false)?;
@@ -1268,6 +1723,8 @@ fn command_deposit_sol(
     }
 
     let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    let minimum_pool_tokens_out =
+        pool_tokens_to_amount(config, &stake_pool, minimum_pool_tokens_out)?;
 
     let mut instructions: Vec<Instruction> = vec![];
 
@@ -1316,10 +1773,42 @@ fn command_deposit_sol(
             return Err(error.into());
         }
 
-        spl_stake_pool::instruction::deposit_sol_with_authority(
+        if let Some(minimum_pool_tokens_out) = minimum_pool_tokens_out {
+            spl_stake_pool::instruction::deposit_sol_with_authority_and_slippage(
+                &spl_stake_pool::id(),
+                stake_pool_address,
+                &deposit_authority.pubkey(),
+                &pool_withdraw_authority,
+                &stake_pool.reserve_stake,
+                &user_sol_transfer.pubkey(),
+                &pool_token_receiver_account,
+                &stake_pool.manager_fee_account,
+                &referrer_token_account,
+                &stake_pool.pool_mint,
+                &spl_token::id(),
+                amount,
+                minimum_pool_tokens_out,
+            )
+        } else {
+            spl_stake_pool::instruction::deposit_sol_with_authority(
+                &spl_stake_pool::id(),
+                stake_pool_address,
+                &deposit_authority.pubkey(),
+                &pool_withdraw_authority,
+                &stake_pool.reserve_stake,
+                &user_sol_transfer.pubkey(),
+                &pool_token_receiver_account,
+                &stake_pool.manager_fee_account,
+                &referrer_token_account,
+                &stake_pool.pool_mint,
+                &spl_token::id(),
+                amount,
+            )
+        }
+    } else if let Some(minimum_pool_tokens_out) = minimum_pool_tokens_out {
+        spl_stake_pool::instruction::deposit_sol_with_slippage(
             &spl_stake_pool::id(),
             stake_pool_address,
-            &deposit_authority.pubkey(),
             &pool_withdraw_authority,
             &stake_pool.reserve_stake,
             &user_sol_transfer.pubkey(),
@@ -1329,6 +1818,7 @@ fn command_deposit_sol(
             &stake_pool.pool_mint,
             &spl_token::id(),
             amount,
+            minimum_pool_tokens_out,
         )
     } else {
         spl_stake_pool::instruction::deposit_sol(
@@ -1376,7 +1866,17 @@ fn command_list(config: &Config, stake_pool_address: &Pubkey) -> CommandResult {
         .rpc_client
         .get_minimum_balance_for_rent_exemption(STAKE_STATE_LEN)?
         + MINIMUM_RESERVE_LAMPORTS;
-    let cli_stake_pool_stake_account_infos = validator_list
+    let validator_reward_history =
+        match get_validator_reward_history(&config.rpc_client, stake_pool_address) {
+            Ok(validator_reward_history) => validator_reward_history.map(|(_, history)| history),
+            Err(err) => {
+                eprintln!("Unable to fetch validator reward history: {}", err);
+                None
+            }
+        };
+    let epochs_per_year = epochs_per_year(config.rpc_client.get_epoch_schedule()?.slots_per_epoch);
+    let fee_split = get_fee_split(&config.rpc_client, stake_pool_address)?;
+    let cli_stake_pool_stake_account_infos: Vec<CliStakePoolStakeAccountInfo> = validator_list
         .validators
         .iter()
         .map(|validator| {
@@ -1394,6 +1894,20 @@ fn command_list(config: &Config, stake_pool_address: &Pubkey) -> CommandResult {
                 validator.transient_seed_suffix.into(),
             );
             let update_required = u64::from(validator.last_update_epoch) != epoch_info.epoch;
+            let rewards = validator_reward_history
+                .as_ref()
+                .and_then(|history| history.find(&validator.vote_account_address))
+                .map(|reward_info| CliValidatorRewards {
+                    first_update_epoch: reward_info.first_update_epoch.into(),
+                    last_update_epoch: reward_info.last_update_epoch.into(),
+                    last_epochs: reward_info.last_epochs.into(),
+                    last_stake_lamports: reward_info.last_stake_lamports.into(),
+                    last_rewards_lamports: reward_info.last_rewards_lamports.into(),
+                    last_mev_lamports: reward_info.last_mev_lamports.into(),
+                    total_rewards_lamports: reward_info.total_rewards_lamports.into(),
+                    total_mev_lamports: reward_info.total_mev_lamports.into(),
+                    apy: validator_apy(reward_info, epochs_per_year),
+                });
             CliStakePoolStakeAccountInfo {
                 vote_account_address: validator.vote_account_address.to_string(),
                 stake_account_address: stake_account_address.to_string(),
@@ -1404,9 +1918,18 @@ fn command_list(config: &Config, stake_pool_address: &Pubkey) -> CommandResult {
                     .to_string(),
                 validator_transient_stake_lamports: validator.transient_stake_lamports.into(),
                 update_required,
+                rewards,
             }
         })
         .collect();
+    let apy = stake_weighted_apy(
+        cli_stake_pool_stake_account_infos
+            .iter()
+            .filter_map(|info| {
+                let rewards = info.rewards.as_ref()?;
+                Some((rewards.last_stake_lamports, rewards.apy?))
+            }),
+    );
     let total_pool_tokens =
         spl_token::amount_to_ui_amount(stake_pool.pool_token_supply, pool_mint.decimals);
     let mut cli_stake_pool = CliStakePool::from((
@@ -1426,16 +1949,98 @@ fn command_list(config: &Config, stake_pool_address: &Pubkey) -> CommandResult {
         current_number_of_validators: current_number_of_validators as u32,
         max_number_of_validators,
         update_required,
+        apy,
+        fee_recipients: fee_split
+            .as_ref()
+            .map(|fee_split| fee_split.recipients.iter().map(Into::into).collect())
+            .unwrap_or_default(),
+        next_fee_recipients: fee_split.and_then(|fee_split| {
+            Option::<Vec<FeeRecipient>>::from(fee_split.next_recipients)
+                .map(|recipients| recipients.iter().map(Into::into).collect())
+        }),
     };
     cli_stake_pool.details = Some(cli_stake_pool_details);
     println!("{}", config.output_format.formatted_string(&cli_stake_pool));
     Ok(())
 }
 
-fn command_update(
-    config: &Config,
-    stake_pool_address: &Pubkey,
-    force: bool,
+fn command_verify(config: &Config, stake_pool_address: &Pubkey) -> CommandResult {
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    let validator_list = get_validator_list(&config.rpc_client, &stake_pool.validator_list)?;
+    let pool_mint = get_token_mint(&config.rpc_client, &stake_pool.pool_mint)?;
+    let epoch_info = config.rpc_client.get_epoch_info()?;
+    let (withdraw_authority, _) =
+        find_withdraw_authority_program_address(&spl_stake_pool::id(), stake_pool_address);
+    let unfunded_ticket_lamports =
+        get_unstake_ticket_ledger(&config.rpc_client, stake_pool_address)?
+            .map(|unstake_ticket_ledger| unstake_ticket_ledger.unfunded_lamports())
+            .unwrap_or_default();
+
+    // reserve first, then the validator and transient stake of every validator
+    let mut stake_addresses = vec![stake_pool.reserve_stake];
+    for validator in validator_list.validators.iter() {
+        let (validator_stake_address, _) = find_stake_program_address(
+            &spl_stake_pool::id(),
+            &validator.vote_account_address,
+            stake_pool_address,
+            NonZeroU32::new(validator.validator_seed_suffix.into()),
+        );
+        let (transient_stake_address, _) = find_transient_stake_program_address(
+            &spl_stake_pool::id(),
+            &validator.vote_account_address,
+            stake_pool_address,
+            validator.transient_seed_suffix.into(),
+        );
+        stake_addresses.push(validator_stake_address);
+        stake_addresses.push(transient_stake_address);
+    }
+    let mut stake_accounts = get_stake_accounts(&config.rpc_client, &stake_addresses)?
+        .into_iter()
+        .map(|account| account.map(|(lamports, state)| StakeAccount { lamports, state }));
+    let reserve_stake = stake_accounts.next().flatten();
+    let validators = stake_addresses[1..]
+        .chunks_exact(2)
+        .map(|addresses| ValidatorAccounts {
+            validator_stake_address: addresses[0],
+            validator_stake: stake_accounts.next().flatten(),
+            transient_stake_address: addresses[1],
+            transient_stake: stake_accounts.next().flatten(),
+        })
+        .collect();
+
+    let discrepancies = verify_pool(&PoolAccounts {
+        stake_pool_address: *stake_pool_address,
+        stake_pool: &stake_pool,
+        validator_list: &validator_list,
+        withdraw_authority,
+        reserve_stake,
+        validators,
+        pool_mint_supply: pool_mint.supply,
+        unfunded_ticket_lamports,
+        epoch: epoch_info.epoch,
+    });
+
+    let cli_verify_report = CliVerifyReport {
+        stake_pool_address: stake_pool_address.to_string(),
+        epoch: epoch_info.epoch,
+        validators_checked: validator_list.validators.len(),
+        discrepancies: discrepancies.iter().map(Into::into).collect(),
+    };
+    println!(
+        "{}",
+        config.output_format.formatted_string(&cli_verify_report)
+    );
+    if discrepancies.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Found {} discrepancies", discrepancies.len()).into())
+    }
+}
+
+fn command_update(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    force: bool,
     no_merge: bool,
     stale_only: bool,
 ) -> CommandResult {
@@ -1457,12 +2062,28 @@ fn command_update(
 
     let validator_list = get_validator_list(&config.rpc_client, &stake_pool.validator_list)?;
 
-    let (mut update_list_instructions, final_instructions) = if stale_only {
+    // validators updated under an enabled policy reject the plain update, so
+    // the policy account goes along as long as it exists
+    let validator_policy = get_validator_policy(&config.rpc_client, stake_pool_address)?;
+    let fee_split = get_fee_split(&config.rpc_client, stake_pool_address)?;
+
+    let (mut update_list_instructions, mut final_instructions) = if validator_policy.is_some() {
+        spl_stake_pool::instruction::update_stake_pool_with_policy(
+            &spl_stake_pool::id(),
+            &stake_pool,
+            &validator_list,
+            stake_pool_address,
+            fee_split.as_ref(),
+            no_merge,
+            stale_only.then_some(epoch_info.epoch),
+        )
+    } else if stale_only {
         spl_stake_pool::instruction::update_stale_stake_pool(
             &spl_stake_pool::id(),
             &stake_pool,
             &validator_list,
             stake_pool_address,
+            fee_split.as_ref(),
             no_merge,
             epoch_info.epoch,
         )
@@ -1472,10 +2093,35 @@ fn command_update(
             &stake_pool,
             &validator_list,
             stake_pool_address,
+            fee_split.as_ref(),
             no_merge,
         )
     };
 
+    // rewards are recorded by the update, before it withdraws MEV to the reserve
+    match get_validator_reward_history(&config.rpc_client, stake_pool_address) {
+        Ok(Some((validator_reward_history_address, _))) => {
+            for instruction in update_list_instructions.iter_mut() {
+                spl_stake_pool::instruction::add_validator_reward_history(
+                    instruction,
+                    &validator_reward_history_address,
+                );
+            }
+            // the cleanup comes last, and drops the rewards of removed validators
+            if let Some(cleanup_instruction) = final_instructions.last_mut() {
+                spl_stake_pool::instruction::add_validator_reward_history(
+                    cleanup_instruction,
+                    &validator_reward_history_address,
+                );
+            }
+        }
+        Ok(None) => {}
+        Err(err) => eprintln!(
+            "Unable to fetch validator reward history, rewards are not recorded: {}",
+            err
+        ),
+    }
+
     let update_list_instructions_len = update_list_instructions.len();
     if update_list_instructions_len > 0 {
         let last_instruction = update_list_instructions.split_off(update_list_instructions_len - 1);
@@ -1659,6 +2305,7 @@ fn prepare_withdraw_accounts(
     Ok(withdraw_from)
 }
 
+#[allow(clippy::too_many_arguments)]
 fn command_withdraw_stake(
     config: &Config,
     stake_pool_address: &Pubkey,
@@ -1667,6 +2314,7 @@ fn command_withdraw_stake(
     stake_receiver_param: &Option<Pubkey>,
     pool_token_account: &Option<Pubkey>,
     pool_amount: f64,
+    minimum_lamports_out: Option<f64>,
 ) -> CommandResult {
     if !config.no_update {
         command_update(config, stake_pool_address, false, false, false)?;
@@ -1675,6 +2323,7 @@ fn command_withdraw_stake(
     let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
     let pool_mint = get_token_mint(&config.rpc_client, &stake_pool.pool_mint)?;
     let pool_amount = spl_token::ui_amount_to_amount(pool_amount, pool_mint.decimals);
+    let minimum_lamports_out = minimum_lamports_out.map(native_token::sol_to_lamports);
 
     let pool_withdraw_authority =
         find_withdraw_authority_program_address(&spl_stake_pool::id(), stake_pool_address).0;
@@ -1835,8 +2484,8 @@ fn command_withdraw_stake(
 
     instructions.push(
         // Approve spending token
-        spl_token::instruction::approve(
-            &spl_token::id(),
+        spl_token_2022::instruction::approve(
+            &stake_pool.token_program_id,
             &pool_token_account,
             &user_transfer_authority.pubkey(),
             &config.token_owner.pubkey(),
@@ -1885,21 +2534,63 @@ fn command_withdraw_stake(
                 stake_receiver_param.unwrap()
             };
 
-        instructions.push(spl_stake_pool::instruction::withdraw_stake(
-            &spl_stake_pool::id(),
-            stake_pool_address,
-            &stake_pool.validator_list,
-            &pool_withdraw_authority,
-            &withdraw_account.stake_address,
-            &stake_receiver,
-            &config.staker.pubkey(),
-            &user_transfer_authority.pubkey(),
+        let mut withdraw_instruction = if let Some(minimum_lamports_out) = minimum_lamports_out {
+            // split the minimum across the withdrawn stake accounts in
+            // proportion to their pool tokens
+            let minimum_lamports_out = (minimum_lamports_out as u128)
+                .checked_mul(withdraw_account.pool_amount as u128)
+                .and_then(|product| product.checked_div(pool_amount as u128))
+                .and_then(|minimum| u64::try_from(minimum).ok())
+                .ok_or("Invalid minimum lamports out")?;
+            spl_stake_pool::instruction::withdraw_stake_with_slippage(
+                &spl_stake_pool::id(),
+                stake_pool_address,
+                &stake_pool.validator_list,
+                &pool_withdraw_authority,
+                &withdraw_account.stake_address,
+                &stake_receiver,
+                &config.staker.pubkey(),
+                &user_transfer_authority.pubkey(),
+                &pool_token_account,
+                &stake_pool.manager_fee_account,
+                &stake_pool.pool_mint,
+                &stake_pool.token_program_id,
+                withdraw_account.pool_amount,
+                minimum_lamports_out,
+            )
+        } else {
+            spl_stake_pool::instruction::withdraw_stake(
+                &spl_stake_pool::id(),
+                stake_pool_address,
+                &stake_pool.validator_list,
+                &pool_withdraw_authority,
+                &withdraw_account.stake_address,
+                &stake_receiver,
+                &config.staker.pubkey(),
+                &user_transfer_authority.pubkey(),
+                &pool_token_account,
+                &stake_pool.manager_fee_account,
+                &stake_pool.pool_mint,
+                &stake_pool.token_program_id,
+                withdraw_account.pool_amount,
+            )
+        };
+        let pool_tokens_fee = if pool_token_account == stake_pool.manager_fee_account {
+            0
+        } else {
+            stake_pool
+                .calc_pool_tokens_stake_withdrawal_fee(withdraw_account.pool_amount)
+                .ok_or("Invalid withdrawal fee")?
+        };
+        add_transfer_hook_accounts(
+            &config.rpc_client,
+            &mut withdraw_instruction,
+            &stake_pool,
             &pool_token_account,
-            &stake_pool.manager_fee_account,
-            &stake_pool.pool_mint,
-            &spl_token::id(),
-            withdraw_account.pool_amount,
-        ));
+            &user_transfer_authority.pubkey(),
+            pool_tokens_fee,
+        )?;
+        instructions.push(withdraw_instruction);
     }
 
     // Merging the stake with account provided by user
@@ -1927,12 +2618,229 @@ fn command_withdraw_stake(
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
+fn command_swap_pool_tokens(
+    config: &Config,
+    source_stake_pool_address: &Pubkey,
+    destination_stake_pool_address: &Pubkey,
+    vote_account_address: &Option<Pubkey>,
+    pool_token_account: &Option<Pubkey>,
+    destination_pool_token_account: &Option<Pubkey>,
+    pool_amount: f64,
+    minimum_pool_tokens_out: Option<f64>,
+) -> CommandResult {
+    if source_stake_pool_address == destination_stake_pool_address {
+        return Err("Source and destination stake pools must be different".into());
+    }
+    if !config.no_update {
+        command_update(config, source_stake_pool_address, false, false, false)?;
+        command_update(config, destination_stake_pool_address, false, false, false)?;
+    }
+
+    let source_stake_pool = get_stake_pool(&config.rpc_client, source_stake_pool_address)?;
+    let destination_stake_pool =
+        get_stake_pool(&config.rpc_client, destination_stake_pool_address)?;
+    let pool_mint = get_token_mint(&config.rpc_client, &source_stake_pool.pool_mint)?;
+    let pool_amount = spl_token::ui_amount_to_amount(pool_amount, pool_mint.decimals);
+    let minimum_pool_tokens_out =
+        pool_tokens_to_amount(config, &destination_stake_pool, minimum_pool_tokens_out)?;
+
+    let pool_token_account = pool_token_account.unwrap_or(get_associated_token_address(
+        &config.token_owner.pubkey(),
+        &source_stake_pool.pool_mint,
+    ));
+    let token_account = get_token_account(
+        &config.rpc_client,
+        &pool_token_account,
+        &source_stake_pool.pool_mint,
+    )?;
+    if token_account.amount < pool_amount {
+        return Err(format!(
+            "Not enough token balance to swap {} pool tokens.\nMaximum swap amount is {} pool tokens.",
+            spl_token::amount_to_ui_amount(pool_amount, pool_mint.decimals),
+            spl_token::amount_to_ui_amount(token_account.amount, pool_mint.decimals)
+        )
+        .into());
+    }
+
+    // The withdrawn stake can only be deposited into a validator stake account
+    // that is already active in the destination pool, so a validator missing
+    // there must be added by its staker an epoch before swapping through it
+    let source_validator_list =
+        get_validator_list(&config.rpc_client, &source_stake_pool.validator_list)?;
+    let destination_validator_list =
+        get_validator_list(&config.rpc_client, &destination_stake_pool.validator_list)?;
+    let stake_account_rent_exemption = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(STAKE_STATE_LEN)?;
+    let stake_pool_minimum_delegation =
+        minimum_delegation(config.rpc_client.get_stake_minimum_delegation()?);
+    let lamports_needed = source_stake_pool
+        .calc_lamports_withdraw_amount(pool_amount)
+        .ok_or("Invalid pool token amount")?;
+
+    let mut candidates = source_validator_list
+        .validators
+        .iter()
+        .filter(|source| source.status == StakeStatus::Active.into())
+        .filter(|source| {
+            vote_account_address.map_or(true, |address| address == source.vote_account_address)
+        })
+        .filter(|source| {
+            destination_stake_pool
+                .preferred_deposit_validator_vote_address
+                .map_or(true, |address| address == source.vote_account_address)
+        })
+        .filter(|source| {
+            u64::from(source.active_stake_lamports)
+                .saturating_sub(stake_pool_minimum_delegation)
+                .saturating_sub(stake_account_rent_exemption)
+                >= lamports_needed
+        })
+        .filter_map(|source| {
+            destination_validator_list
+                .find(&source.vote_account_address)
+                .filter(|destination| destination.status == StakeStatus::Active.into())
+                .map(|destination| (source, destination))
+        })
+        .collect::<Vec<_>>();
+    candidates.sort_by(|(left, _), (right, _)| {
+        if Some(left.vote_account_address)
+            == source_stake_pool.preferred_withdraw_validator_vote_address
+        {
+            Ordering::Less
+        } else if Some(right.vote_account_address)
+            == source_stake_pool.preferred_withdraw_validator_vote_address
+        {
+            Ordering::Greater
+        } else {
+            u64::from(right.active_stake_lamports).cmp(&u64::from(left.active_stake_lamports))
+        }
+    });
+    let (source_validator, destination_validator) = candidates.first().ok_or_else(|| {
+        if let Some(vote_account_address) = vote_account_address {
+            format!(
+                "Validator {} cannot be used to swap {} pool tokens. It must be active in both pools, \
+                 have enough stake in the source pool, and match any preferred deposit validator \
+                 of the destination pool",
+                vote_account_address,
+                spl_token::amount_to_ui_amount(pool_amount, pool_mint.decimals),
+            )
+        } else {
+            format!(
+                "No validator shared by both pools has enough stake to swap {} pool tokens. \
+                 The destination pool's staker can add one with `add-validator`, usable from \
+                 the next epoch",
+                spl_token::amount_to_ui_amount(pool_amount, pool_mint.decimals),
+            )
+        }
+    })?;
+    let swap_vote_account_address = source_validator.vote_account_address;
+
+    let mut instructions: Vec<Instruction> = vec![];
+    let mut signers = vec![config.fee_payer.as_ref(), config.token_owner.as_ref()];
+    let mut total_rent_free_balances = 0;
+
+    let (default_deposit_authority, _) = find_deposit_authority_program_address(
+        &spl_stake_pool::id(),
+        destination_stake_pool_address,
+    );
+    if destination_stake_pool.stake_deposit_authority != default_deposit_authority {
+        let stake_deposit_authority = config
+            .funding_authority
+            .as_ref()
+            .ok_or("The destination stake pool requires its stake deposit authority to sign")?;
+        if stake_deposit_authority.pubkey() != destination_stake_pool.stake_deposit_authority {
+            return Err(format!(
+                "Invalid deposit authority specified, expected {}, received {}",
+                destination_stake_pool.stake_deposit_authority,
+                stake_deposit_authority.pubkey()
+            )
+            .into());
+        }
+        signers.push(stake_deposit_authority.as_ref());
+    }
+
+    let destination_pool_token_account = destination_pool_token_account.unwrap_or_else(|| {
+        add_associated_token_account(
+            config,
+            &destination_stake_pool.pool_mint,
+            &config.token_owner.pubkey(),
+            &mut instructions,
+            &mut total_rent_free_balances,
+        )
+    });
+
+    let stake_receiver = new_stake_account(
+        &config.fee_payer.pubkey(),
+        &mut instructions,
+        stake_account_rent_exemption,
+    );
+    total_rent_free_balances += stake_account_rent_exemption;
+
+    println!(
+        "Swapping {} pool tokens, or about {}, from stake pool {} to stake pool {} through validator {}",
+        spl_token::amount_to_ui_amount(pool_amount, pool_mint.decimals),
+        Sol(lamports_needed),
+        source_stake_pool_address,
+        destination_stake_pool_address,
+        swap_vote_account_address,
+    );
+
+    let mut swap_instructions = spl_stake_pool::instruction::swap_pool_tokens(
+        &spl_stake_pool::id(),
+        &source_stake_pool,
+        source_stake_pool_address,
+        NonZeroU32::new(source_validator.validator_seed_suffix.into()),
+        &destination_stake_pool,
+        destination_stake_pool_address,
+        NonZeroU32::new(destination_validator.validator_seed_suffix.into()),
+        &swap_vote_account_address,
+        &stake_receiver.pubkey(),
+        &config.token_owner.pubkey(),
+        &pool_token_account,
+        &destination_pool_token_account,
+        pool_amount,
+        minimum_pool_tokens_out,
+    );
+    // The first swap instruction withdraws from the source pool and transfers
+    // its pool tokens, so it needs any transfer hook accounts of that mint
+    let pool_tokens_fee = if pool_token_account == source_stake_pool.manager_fee_account {
+        0
+    } else {
+        source_stake_pool
+            .calc_pool_tokens_stake_withdrawal_fee(pool_amount)
+            .ok_or("Invalid withdrawal fee")?
+    };
+    add_transfer_hook_accounts(
+        &config.rpc_client,
+        &mut swap_instructions[0],
+        &source_stake_pool,
+        &pool_token_account,
+        &config.token_owner.pubkey(),
+        pool_tokens_fee,
+    )?;
+    instructions.extend(swap_instructions);
+
+    signers.push(&stake_receiver);
+    unique_signers!(signers);
+    let transaction = checked_transaction_with_signers_and_additional_fee(
+        config,
+        &instructions,
+        &signers,
+        total_rent_free_balances,
+    )?;
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
 fn command_withdraw_sol(
     config: &Config,
     stake_pool_address: &Pubkey,
     pool_token_account: &Option<Pubkey>,
     sol_receiver: &Pubkey,
     pool_amount: f64,
+    minimum_lamports_out: Option<f64>,
 ) -> CommandResult {
     if !config.no_update {
         command_update(config, stake_pool_address, false, false, false)?;
@@ -1941,6 +2849,7 @@ fn command_withdraw_sol(
     let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
     let pool_mint = get_token_mint(&config.rpc_client, &stake_pool.pool_mint)?;
     let pool_amount = spl_token::ui_amount_to_amount(pool_amount, pool_mint.decimals);
+    let minimum_lamports_out = minimum_lamports_out.map(native_token::sol_to_lamports);
 
     let pool_token_account = pool_token_account.unwrap_or(get_associated_token_address(
         &config.token_owner.pubkey(),
@@ -1972,8 +2881,8 @@ fn command_withdraw_sol(
 
     let mut instructions = vec![
         // Approve spending token
-        spl_token::instruction::approve(
-            &spl_token::id(),
+        spl_token_2022::instruction::approve(
+            &stake_pool.token_program_id,
             &pool_token_account,
             &user_transfer_authority.pubkey(),
             &config.token_owner.pubkey(),
@@ -1985,7 +2894,9 @@ fn command_withdraw_sol(
     let pool_withdraw_authority =
         find_withdraw_authority_program_address(&spl_stake_pool::id(), stake_pool_address).0;
 
-    let withdraw_instruction = if let Some(withdraw_authority) = config.funding_authority.as_ref() {
+    let mut withdraw_instruction = if let Some(withdraw_authority) =
+        config.funding_authority.as_ref()
+    {
         let expected_sol_withdraw_authority =
             stake_pool.sol_withdraw_authority.ok_or_else(|| {
                 "SOL withdraw authority specified in arguments but stake pool has none".to_string()
@@ -2000,10 +2911,42 @@ fn command_withdraw_sol(
             return Err(error.into());
         }
 
-        spl_stake_pool::instruction::withdraw_sol_with_authority(
+        if let Some(minimum_lamports_out) = minimum_lamports_out {
+            spl_stake_pool::instruction::withdraw_sol_with_authority_and_slippage(
+                &spl_stake_pool::id(),
+                stake_pool_address,
+                &withdraw_authority.pubkey(),
+                &pool_withdraw_authority,
+                &user_transfer_authority.pubkey(),
+                &pool_token_account,
+                &stake_pool.reserve_stake,
+                sol_receiver,
+                &stake_pool.manager_fee_account,
+                &stake_pool.pool_mint,
+                &stake_pool.token_program_id,
+                pool_amount,
+                minimum_lamports_out,
+            )
+        } else {
+            spl_stake_pool::instruction::withdraw_sol_with_authority(
+                &spl_stake_pool::id(),
+                stake_pool_address,
+                &withdraw_authority.pubkey(),
+                &pool_withdraw_authority,
+                &user_transfer_authority.pubkey(),
+                &pool_token_account,
+                &stake_pool.reserve_stake,
+                sol_receiver,
+                &stake_pool.manager_fee_account,
+                &stake_pool.pool_mint,
+                &stake_pool.token_program_id,
+                pool_amount,
+            )
+        }
+    } else if let Some(minimum_lamports_out) = minimum_lamports_out {
+        spl_stake_pool::instruction::withdraw_sol_with_slippage(
             &spl_stake_pool::id(),
             stake_pool_address,
-            &withdraw_authority.pubkey(),
             &pool_withdraw_authority,
             &user_transfer_authority.pubkey(),
             &pool_token_account,
@@ -2011,8 +2954,9 @@ fn command_withdraw_sol(
             sol_receiver,
             &stake_pool.manager_fee_account,
             &stake_pool.pool_mint,
-            &spl_token::id(),
+            &stake_pool.token_program_id,
             pool_amount,
+            minimum_lamports_out,
         )
     } else {
         spl_stake_pool::instruction::withdraw_sol(
@@ -2025,10 +2969,25 @@ fn command_withdraw_sol(
             sol_receiver,
             &stake_pool.manager_fee_account,
             &stake_pool.pool_mint,
-            &spl_token::id(),
+            &stake_pool.token_program_id,
             pool_amount,
         )
     };
+    let pool_tokens_fee = if pool_token_account == stake_pool.manager_fee_account {
+        0
+    } else {
+        stake_pool
+            .calc_pool_tokens_sol_withdrawal_fee(pool_amount)
+            .ok_or("Invalid withdrawal fee")?
+    };
+    add_transfer_hook_accounts(
+        &config.rpc_client,
+        &mut withdraw_instruction,
+        &stake_pool,
+        &pool_token_account,
+        &user_transfer_authority.pubkey(),
+        pool_tokens_fee,
+    )?;
 
     instructions.push(withdraw_instruction);
 
@@ -2038,41 +2997,393 @@ fn command_withdraw_sol(
     Ok(())
 }
 
-fn command_set_manager(
+fn command_create_unstake_ticket(
     config: &Config,
     stake_pool_address: &Pubkey,
-    new_manager: &Option<Box<dyn Signer>>,
-    new_fee_receiver: &Option<Pubkey>,
+    pool_token_account: &Option<Pubkey>,
+    ticket_owner: &Option<Pubkey>,
+    pool_amount: f64,
 ) -> CommandResult {
     if !config.no_update {
         command_update(config, stake_pool_address, false, false, false)?;
     }
+
     let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    let pool_mint = get_token_mint(&config.rpc_client, &stake_pool.pool_mint)?;
+    let pool_amount = spl_token::ui_amount_to_amount(pool_amount, pool_mint.decimals);
+    let ticket_owner = ticket_owner.unwrap_or(config.token_owner.pubkey());
 
-    // If new accounts are missing in the arguments use the old ones
-    let (new_manager_pubkey, mut signers): (Pubkey, Vec<&dyn Signer>) = match new_manager {
-        None => (stake_pool.manager, vec![]),
-        Some(value) => (value.pubkey(), vec![value.as_ref()]),
-    };
+    let pool_token_account = pool_token_account.unwrap_or(get_associated_token_address(
+        &config.token_owner.pubkey(),
+        &stake_pool.pool_mint,
+    ));
+    let token_account = get_token_account(
+        &config.rpc_client,
+        &pool_token_account,
+        &stake_pool.pool_mint,
+    )?;
 
-    let new_fee_receiver = match new_fee_receiver {
-        None => stake_pool.manager_fee_account,
-        Some(value) => {
-            // Check for fee receiver being a valid token account and have to same mint as
-            // the stake pool
-            let token_account =
-                get_token_account(&config.rpc_client, value, &stake_pool.pool_mint)?;
-            if token_account.mint != stake_pool.pool_mint {
-                return Err("Fee receiver account belongs to a different mint"
-                    .to_string()
-                    .into());
-            }
-            *value
-        }
-    };
+    // Check burn_from balance
+    if token_account.amount < pool_amount {
+        return Err(format!(
+            "Not enough token balance to unstake {} pool tokens.\nMaximum unstake amount is {} pool tokens.",
+            spl_token::amount_to_ui_amount(pool_amount, pool_mint.decimals),
+            spl_token::amount_to_ui_amount(token_account.amount, pool_mint.decimals)
+        )
+        .into());
+    }
 
-    signers.append(&mut vec![
-        config.fee_payer.as_ref(),
+    let unstake_ticket = Keypair::new();
+    println!("Creating unstake ticket {}", unstake_ticket.pubkey());
+
+    let user_transfer_authority = Keypair::new(); // ephemeral keypair just to do the transfer
+    let mut signers = vec![
+        config.fee_payer.as_ref(),
+        config.token_owner.as_ref(),
+        &user_transfer_authority,
+        &unstake_ticket,
+    ];
+
+    let unstake_ticket_len = get_packed_len::<UnstakeTicket>();
+    let unstake_ticket_rent = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(unstake_ticket_len)?;
+    let mut instructions = vec![
+        system_instruction::create_account(
+            &config.fee_payer.pubkey(),
+            &unstake_ticket.pubkey(),
+            unstake_ticket_rent,
+            unstake_ticket_len as u64,
+            &spl_stake_pool::id(),
+        ),
+        // Approve spending token
+        spl_token_2022::instruction::approve(
+            &stake_pool.token_program_id,
+            &pool_token_account,
+            &user_transfer_authority.pubkey(),
+            &config.token_owner.pubkey(),
+            &[],
+            pool_amount,
+        )?,
+    ];
+
+    let mut unstake_instruction = if let Some(withdraw_authority) =
+        config.funding_authority.as_ref()
+    {
+        let expected_sol_withdraw_authority =
+            stake_pool.sol_withdraw_authority.ok_or_else(|| {
+                "SOL withdraw authority specified in arguments but stake pool has none".to_string()
+            })?;
+        signers.push(withdraw_authority.as_ref());
+        if withdraw_authority.pubkey() != expected_sol_withdraw_authority {
+            let error = format!(
+                "Invalid deposit withdraw specified, expected {}, received {}",
+                expected_sol_withdraw_authority,
+                withdraw_authority.pubkey()
+            );
+            return Err(error.into());
+        }
+
+        spl_stake_pool::instruction::create_unstake_ticket_with_authority(
+            &spl_stake_pool::id(),
+            stake_pool_address,
+            &withdraw_authority.pubkey(),
+            &unstake_ticket.pubkey(),
+            &ticket_owner,
+            &user_transfer_authority.pubkey(),
+            &pool_token_account,
+            &stake_pool.manager_fee_account,
+            &stake_pool.pool_mint,
+            &config.fee_payer.pubkey(),
+            &stake_pool.token_program_id,
+            pool_amount,
+        )
+    } else {
+        spl_stake_pool::instruction::create_unstake_ticket(
+            &spl_stake_pool::id(),
+            stake_pool_address,
+            &unstake_ticket.pubkey(),
+            &ticket_owner,
+            &user_transfer_authority.pubkey(),
+            &pool_token_account,
+            &stake_pool.manager_fee_account,
+            &stake_pool.pool_mint,
+            &config.fee_payer.pubkey(),
+            &stake_pool.token_program_id,
+            pool_amount,
+        )
+    };
+    let pool_tokens_fee = if pool_token_account == stake_pool.manager_fee_account {
+        0
+    } else {
+        stake_pool
+            .calc_pool_tokens_sol_withdrawal_fee(pool_amount)
+            .ok_or("Invalid withdrawal fee")?
+    };
+    add_transfer_hook_accounts(
+        &config.rpc_client,
+        &mut unstake_instruction,
+        &stake_pool,
+        &pool_token_account,
+        &user_transfer_authority.pubkey(),
+        pool_tokens_fee,
+    )?;
+    instructions.push(unstake_instruction);
+
+    unique_signers!(signers);
+    let transaction = checked_transaction_with_signers(config, &instructions, &signers)?;
+    send_transaction(config, transaction)?;
+
+    if !config.dry_run {
+        let unstake_ticket = get_unstake_ticket(&config.rpc_client, &unstake_ticket.pubkey())?;
+        println!(
+            "Unstake ticket for {} claimable from epoch {}",
+            Sol(unstake_ticket.lamports),
+            unstake_ticket.claimable_epoch
+        );
+    }
+    Ok(())
+}
+
+fn command_claim_unstake_ticket(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    unstake_ticket_address: &Pubkey,
+    lamports_to: &Option<Pubkey>,
+) -> CommandResult {
+    let unstake_ticket = get_unstake_ticket(&config.rpc_client, unstake_ticket_address)?;
+    if unstake_ticket.stake_pool != *stake_pool_address {
+        return Err(format!(
+            "Unstake ticket {} belongs to stake pool {}",
+            unstake_ticket_address, unstake_ticket.stake_pool
+        )
+        .into());
+    }
+    if unstake_ticket.owner != config.token_owner.pubkey() {
+        return Err(format!(
+            "Unstake ticket {} is owned by {}, received {}",
+            unstake_ticket_address,
+            unstake_ticket.owner,
+            config.token_owner.pubkey()
+        )
+        .into());
+    }
+    let epoch_info = config.rpc_client.get_epoch_info()?;
+    if !unstake_ticket.is_claimable(epoch_info.epoch) {
+        return Err(format!(
+            "Unstake ticket {} is claimable from epoch {}, current epoch is {}",
+            unstake_ticket_address, unstake_ticket.claimable_epoch, epoch_info.epoch
+        )
+        .into());
+    }
+    let lamports_to = lamports_to.unwrap_or(config.token_owner.pubkey());
+
+    let mut signers = vec![config.fee_payer.as_ref(), config.token_owner.as_ref()];
+    unique_signers!(signers);
+    let transaction = checked_transaction_with_signers(
+        config,
+        &[spl_stake_pool::instruction::claim_unstake_ticket(
+            &spl_stake_pool::id(),
+            stake_pool_address,
+            unstake_ticket_address,
+            &config.token_owner.pubkey(),
+            &lamports_to,
+        )],
+        &signers,
+    )?;
+    send_transaction(config, transaction)?;
+    println!(
+        "Claimed {} from unstake ticket {} to {}",
+        Sol(unstake_ticket.lamports),
+        unstake_ticket_address,
+        lamports_to
+    );
+    Ok(())
+}
+
+fn command_list_unstake_tickets(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    owner: &Option<Pubkey>,
+) -> CommandResult {
+    let (unstake_ticket_ledger_address, _) =
+        find_unstake_ticket_ledger_program_address(&spl_stake_pool::id(), stake_pool_address);
+    let unstake_ticket_ledger =
+        get_unstake_ticket_ledger(&config.rpc_client, stake_pool_address)?.unwrap_or_default();
+    let epoch_info = config.rpc_client.get_epoch_info()?;
+    let mut unstake_tickets =
+        get_unstake_tickets(&config.rpc_client, stake_pool_address, owner.as_ref())?;
+    unstake_tickets.sort_by_key(|(_, unstake_ticket)| unstake_ticket.claimable_epoch);
+
+    let cli_unstake_tickets = CliUnstakeTickets {
+        ledger_address: unstake_ticket_ledger_address.to_string(),
+        owed_lamports: unstake_ticket_ledger.owed_lamports,
+        funded_lamports: unstake_ticket_ledger.funded_lamports,
+        tickets: unstake_tickets
+            .into_iter()
+            .map(|(address, unstake_ticket)| CliUnstakeTicket {
+                address: address.to_string(),
+                owner: unstake_ticket.owner.to_string(),
+                lamports: unstake_ticket.lamports,
+                claimable_epoch: unstake_ticket.claimable_epoch,
+                claimable: unstake_ticket.is_claimable(epoch_info.epoch),
+            })
+            .collect(),
+    };
+    println!(
+        "{}",
+        config.output_format.formatted_string(&cli_unstake_tickets)
+    );
+    Ok(())
+}
+
+fn command_crank_unstake_tickets(config: &Config, stake_pool_address: &Pubkey) -> CommandResult {
+    if !config.no_update {
+        command_update(config, stake_pool_address, false, false, false)?;
+    }
+
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    let validator_list = get_validator_list(&config.rpc_client, &stake_pool.validator_list)?;
+    let unfunded_lamports = get_unstake_ticket_ledger(&config.rpc_client, stake_pool_address)?
+        .map(|unstake_ticket_ledger| unstake_ticket_ledger.unfunded_lamports())
+        .unwrap_or_default();
+    let pool = get_pool_balances(config, stake_pool_address, &stake_pool, &validator_list)?;
+
+    // Stake already deactivating returns to the reserve on a later update,
+    // and funds the ledger then
+    let mut deactivating_lamports = 0;
+    for validator in validator_list.validators.iter() {
+        if validator.status != StakeStatus::Active.into() {
+            deactivating_lamports += validator.stake_lamports()?;
+            continue;
+        }
+        if u64::from(validator.transient_stake_lamports) == 0 {
+            continue;
+        }
+        let (transient_stake_address, _) = find_transient_stake_program_address(
+            &spl_stake_pool::id(),
+            &validator.vote_account_address,
+            stake_pool_address,
+            validator.transient_seed_suffix.into(),
+        );
+        if let stake::state::StakeStateV2::Stake(_, stake, _) =
+            get_stake_state(&config.rpc_client, &transient_stake_address)?
+        {
+            if stake.delegation.deactivation_epoch != u64::MAX {
+                deactivating_lamports += u64::from(validator.transient_stake_lamports);
+            }
+        }
+    }
+    let reserve_available_lamports = pool
+        .reserve_lamports
+        .saturating_sub(pool.stake_rent)
+        .saturating_sub(MINIMUM_RESERVE_LAMPORTS);
+    let shortfall_lamports = unfunded_lamports
+        .saturating_sub(reserve_available_lamports)
+        .saturating_sub(deactivating_lamports);
+
+    let steps = plan_unstake_ticket_decreases(&pool, shortfall_lamports);
+    let cli_unstake_ticket_crank = CliUnstakeTicketCrank {
+        unfunded_lamports,
+        reserve_available_lamports,
+        deactivating_lamports,
+        shortfall_lamports,
+        steps: steps.iter().map(CliRebalanceStep::from).collect(),
+    };
+    println!(
+        "{}",
+        config
+            .output_format
+            .formatted_string(&cli_unstake_ticket_crank)
+    );
+
+    let mut signers = vec![config.fee_payer.as_ref(), config.staker.as_ref()];
+    unique_signers!(signers);
+    for steps in batch_steps(&steps, MAX_TRANSIENT_STAKE_ACCOUNTS) {
+        let mut instructions: Vec<Instruction> = vec![];
+        for step in steps {
+            let RebalanceStep::Decrease {
+                vote_account_address,
+                ..
+            } = step
+            else {
+                unreachable!();
+            };
+            let validator = validator_list
+                .find(vote_account_address)
+                .ok_or("Vote account not found in validator list")?;
+            let (transient_stake_address, _) = find_transient_stake_program_address(
+                &spl_stake_pool::id(),
+                vote_account_address,
+                stake_pool_address,
+                validator.transient_seed_suffix.into(),
+            );
+            // the reserve may not hold the transient stake rent anymore, so the
+            // fee payer pre-funds it
+            instructions.push(system_instruction::transfer(
+                &config.fee_payer.pubkey(),
+                &transient_stake_address,
+                pool.stake_rent,
+            ));
+            instructions.push(rebalance_instruction(
+                &stake_pool,
+                stake_pool_address,
+                &validator_list,
+                step,
+            )?);
+            if !fits_in_transaction(config, &instructions, signers.len()) {
+                // flush everything but the new step, which starts the next
+                // transaction
+                let step_instructions = instructions.split_off(instructions.len() - 2);
+                let transaction =
+                    checked_transaction_with_signers(config, &instructions, &signers)?;
+                send_transaction(config, transaction)?;
+                instructions = step_instructions;
+            }
+        }
+        if !instructions.is_empty() {
+            let transaction = checked_transaction_with_signers(config, &instructions, &signers)?;
+            send_transaction(config, transaction)?;
+        }
+    }
+    Ok(())
+}
+
+fn command_set_manager(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    new_manager: &Option<Box<dyn Signer>>,
+    new_fee_receiver: &Option<Pubkey>,
+) -> CommandResult {
+    if !config.no_update {
+        command_update(config, stake_pool_address, false, false, false)?;
+    }
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+
+    // If new accounts are missing in the arguments use the old ones
+    let (new_manager_pubkey, mut signers): (Pubkey, Vec<&dyn Signer>) = match new_manager {
+        None => (stake_pool.manager, vec![]),
+        Some(value) => (value.pubkey(), vec![value.as_ref()]),
+    };
+
+    let new_fee_receiver = match new_fee_receiver {
+        None => stake_pool.manager_fee_account,
+        Some(value) => {
+            // Check for fee receiver being a valid token account and have to same mint as
+            // the stake pool
+            let token_account =
+                get_token_account(&config.rpc_client, value, &stake_pool.pool_mint)?;
+            if token_account.mint != stake_pool.pool_mint {
+                return Err("Fee receiver account belongs to a different mint"
+                    .to_string()
+                    .into());
+            }
+            *value
+        }
+    };
+
+    signers.append(&mut vec![
+        config.fee_payer.as_ref(),
         config.manager.as_ref(),
     ]);
     unique_signers!(signers);
@@ -2115,6 +3426,147 @@ fn command_set_staker(
     Ok(())
 }
 
+fn command_set_validator_policy(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    max_commission: u8,
+    min_epoch_credits_bps: u16,
+    max_delinquent_slots: u64,
+) -> CommandResult {
+    let mut signers = vec![config.fee_payer.as_ref(), config.staker.as_ref()];
+    unique_signers!(signers);
+    let transaction = checked_transaction_with_signers(
+        config,
+        &[spl_stake_pool::instruction::set_validator_policy(
+            &spl_stake_pool::id(),
+            stake_pool_address,
+            &config.staker.pubkey(),
+            &config.fee_payer.pubkey(),
+            max_commission,
+            min_epoch_credits_bps,
+            max_delinquent_slots,
+        )],
+        &signers,
+    )?;
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
+fn command_set_cluster_epoch_credits(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    epoch: Option<u64>,
+    epoch_credits: Option<u64>,
+) -> CommandResult {
+    if get_validator_policy(&config.rpc_client, stake_pool_address)?.is_none() {
+        return Err(format!(
+            "Stake pool {} has no validator policy, set it up with set-validator-policy",
+            stake_pool_address
+        )
+        .into());
+    }
+    let epoch = match epoch {
+        Some(epoch) => epoch,
+        None => config
+            .rpc_client
+            .get_epoch_info()?
+            .epoch
+            .checked_sub(1)
+            .ok_or("No epoch completed yet")?,
+    };
+    let epoch_credits = match epoch_credits {
+        Some(epoch_credits) => epoch_credits,
+        None => get_cluster_epoch_credits(&config.rpc_client, epoch)?.ok_or_else(|| {
+            format!(
+                "No vote credits found for epoch {}, provide them with --epoch-credits",
+                epoch
+            )
+        })?,
+    };
+    if config.verbose {
+        println!(
+            "Recording {} cluster epoch credits for epoch {}",
+            epoch_credits, epoch
+        );
+    }
+
+    let mut signers = vec![config.fee_payer.as_ref(), config.staker.as_ref()];
+    unique_signers!(signers);
+    let transaction = checked_transaction_with_signers(
+        config,
+        &[spl_stake_pool::instruction::set_cluster_epoch_credits(
+            &spl_stake_pool::id(),
+            stake_pool_address,
+            &config.staker.pubkey(),
+            epoch,
+            epoch_credits,
+        )],
+        &signers,
+    )?;
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
+fn command_create_validator_reward_history(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    validator_reward_history_keypair: Option<Keypair>,
+) -> CommandResult {
+    if let Some((address, _)) =
+        get_validator_reward_history(&config.rpc_client, stake_pool_address)?
+    {
+        return Err(format!(
+            "Stake pool {} already has validator reward history {}",
+            stake_pool_address, address
+        )
+        .into());
+    }
+    let stake_pool = get_stake_pool(&config.rpc_client, stake_pool_address)?;
+    let validator_list = get_validator_list(&config.rpc_client, &stake_pool.validator_list)?;
+    let validator_reward_history_keypair =
+        validator_reward_history_keypair.unwrap_or_else(Keypair::new);
+    let validator_reward_history_size = get_instance_packed_len(&ValidatorRewardHistory::new(
+        *stake_pool_address,
+        validator_list.header.max_validators,
+    ))?;
+    let validator_reward_history_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(validator_reward_history_size)?;
+
+    let mut signers = vec![
+        config.fee_payer.as_ref(),
+        config.staker.as_ref(),
+        &validator_reward_history_keypair,
+    ];
+    unique_signers!(signers);
+    let transaction = checked_transaction_with_signers(
+        config,
+        &[
+            system_instruction::create_account(
+                &config.fee_payer.pubkey(),
+                &validator_reward_history_keypair.pubkey(),
+                validator_reward_history_balance,
+                validator_reward_history_size as u64,
+                &spl_stake_pool::id(),
+            ),
+            spl_stake_pool::instruction::initialize_validator_reward_history(
+                &spl_stake_pool::id(),
+                stake_pool_address,
+                &config.staker.pubkey(),
+                &stake_pool.validator_list,
+                &validator_reward_history_keypair.pubkey(),
+            ),
+        ],
+        &signers,
+    )?;
+    println!(
+        "Creating validator reward history {}",
+        validator_reward_history_keypair.pubkey()
+    );
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
 fn command_set_funding_authority(
     config: &Config,
     stake_pool_address: &Pubkey,
@@ -2165,6 +3617,38 @@ fn command_set_fee(
     Ok(())
 }
 
+fn command_set_fee_split(
+    config: &Config,
+    stake_pool_address: &Pubkey,
+    recipients: Vec<FeeRecipient>,
+) -> CommandResult {
+    FeeSplit::check_recipients(&recipients).map_err(|err| {
+        format!(
+            "Invalid fee recipients, shares must be distinct and add up to {} bps: {}",
+            FeeSplit::TOTAL_BPS,
+            err
+        )
+    })?;
+    if !config.no_update {
+        command_update(config, stake_pool_address, false, false, false)?;
+    }
+    let mut signers = vec![config.fee_payer.as_ref(), config.manager.as_ref()];
+    unique_signers!(signers);
+    let transaction = checked_transaction_with_signers(
+        config,
+        &[spl_stake_pool::instruction::set_fee_split(
+            &spl_stake_pool::id(),
+            stake_pool_address,
+            &config.manager.pubkey(),
+            &config.fee_payer.pubkey(),
+            recipients,
+        )],
+        &signers,
+    )?;
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
 fn command_list_all_pools(config: &Config) -> CommandResult {
     let all_pools = get_stake_pools(&config.rpc_client)?;
     let cli_stake_pool_vec: Vec<CliStakePool> =
@@ -2489,9 +3973,80 @@ fn main() {
             .required(true)
             .help("URI of the token metadata json"),
         )
-    )
-        .subcommand(SubCommand::with_name("add-validator")
-            .about("Add validator account to the stake pool. Must be signed by the pool staker.")
+    )
+        .subcommand(SubCommand::with_name("add-validator")
+            .about("Add validator account to the stake pool. Must be signed by the pool staker.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address"),
+            )
+            .arg(
+                Arg::with_name("vote_account")
+                    .index(2)
+                    .validator(is_pubkey)
+                    .value_name("VOTE_ACCOUNT_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("The validator vote account that the stake is delegated to"),
+            )
+        )
+        .subcommand(SubCommand::with_name("remove-validator")
+            .about("Remove validator account from the stake pool. Must be signed by the pool staker.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address"),
+            )
+            .arg(
+                Arg::with_name("vote_account")
+                    .index(2)
+                    .validator(is_pubkey)
+                    .value_name("VOTE_ACCOUNT_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Vote account for the validator to remove from the pool"),
+            )
+        )
+        .subcommand(SubCommand::with_name("increase-validator-stake")
+            .about("Increase stake to a validator, drawing from the stake pool reserve. Must be signed by the pool staker.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address"),
+            )
+            .arg(
+                Arg::with_name("vote_account")
+                    .index(2)
+                    .validator(is_pubkey)
+                    .value_name("VOTE_ACCOUNT_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Vote account for the validator to increase stake to"),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .index(3)
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .help("Amount in SOL to add to the validator stake account. Must be at least the rent-exempt amount for a stake plus 1 SOL for merging."),
+            )
+        )
+        .subcommand(SubCommand::with_name("decrease-validator-stake")
+            .about("Decrease stake to a validator, splitting from the active stake with the reserve paying the rent of the transient stake. Must be signed by the pool staker.")
             .arg(
                 Arg::with_name("pool")
                     .index(1)
@@ -2508,11 +4063,19 @@ fn main() {
                     .value_name("VOTE_ACCOUNT_ADDRESS")
                     .takes_value(true)
                     .required(true)
-                    .help("The validator vote account that the stake is delegated to"),
+                    .help("Vote account for the validator to decrease stake from"),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .index(3)
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .help("Amount in SOL to remove from the validator stake account. Must be at least the rent-exempt amount for a stake."),
             )
         )
-        .subcommand(SubCommand::with_name("remove-validator")
-            .about("Remove validator account from the stake pool. Must be signed by the pool staker.")
+        .subcommand(SubCommand::with_name("increase-additional-validator-stake")
+            .about("Increase stake to a validator, adding to the stake already activating this epoch. Must be signed by the pool staker.")
             .arg(
                 Arg::with_name("pool")
                     .index(1)
@@ -2529,11 +4092,28 @@ fn main() {
                     .value_name("VOTE_ACCOUNT_ADDRESS")
                     .takes_value(true)
                     .required(true)
-                    .help("Vote account for the validator to remove from the pool"),
+                    .help("Vote account for the validator to increase stake to"),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .index(3)
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount in SOL to add to the validator stake account. The reserve also pays the rent-exempt amount for the ephemeral stake."),
+            )
+            .arg(
+                Arg::with_name("ephemeral_seed")
+                    .long("ephemeral-seed")
+                    .validator(is_parsable::<u64>)
+                    .value_name("SEED")
+                    .takes_value(true)
+                    .help("Seed of the ephemeral stake account used for the increase [default: 0]"),
             )
         )
-        .subcommand(SubCommand::with_name("increase-validator-stake")
-            .about("Increase stake to a validator, drawing from the stake pool reserve. Must be signed by the pool staker.")
+        .subcommand(SubCommand::with_name("decrease-additional-validator-stake")
+            .about("Decrease stake to a validator, adding to the stake already deactivating this epoch. Must be signed by the pool staker.")
             .arg(
                 Arg::with_name("pool")
                     .index(1)
@@ -2550,7 +4130,7 @@ fn main() {
                     .value_name("VOTE_ACCOUNT_ADDRESS")
                     .takes_value(true)
                     .required(true)
-                    .help("Vote account for the validator to increase stake to"),
+                    .help("Vote account for the validator to decrease stake from"),
             )
             .arg(
                 Arg::with_name("amount")
@@ -2558,11 +4138,20 @@ fn main() {
                     .validator(is_amount)
                     .value_name("AMOUNT")
                     .takes_value(true)
-                    .help("Amount in SOL to add to the validator stake account. Must be at least the rent-exempt amount for a stake plus 1 SOL for merging."),
+                    .required(true)
+                    .help("Amount in SOL to remove from the validator stake account. The reserve pays the rent-exempt amount for the ephemeral stake."),
+            )
+            .arg(
+                Arg::with_name("ephemeral_seed")
+                    .long("ephemeral-seed")
+                    .validator(is_parsable::<u64>)
+                    .value_name("SEED")
+                    .takes_value(true)
+                    .help("Seed of the ephemeral stake account used for the decrease [default: 0]"),
             )
         )
-        .subcommand(SubCommand::with_name("decrease-validator-stake")
-            .about("Decrease stake to a validator, splitting from the active stake. Must be signed by the pool staker.")
+        .subcommand(SubCommand::with_name("redelegate")
+            .about("Move stake from one validator to another without going through the reserve. Only works on programs with redelegation enabled. Must be signed by the pool staker.")
             .arg(
                 Arg::with_name("pool")
                     .index(1)
@@ -2573,21 +4162,92 @@ fn main() {
                     .help("Stake pool address"),
             )
             .arg(
-                Arg::with_name("vote_account")
+                Arg::with_name("source_vote_account")
                     .index(2)
                     .validator(is_pubkey)
-                    .value_name("VOTE_ACCOUNT_ADDRESS")
+                    .value_name("SOURCE_VOTE_ACCOUNT_ADDRESS")
                     .takes_value(true)
                     .required(true)
-                    .help("Vote account for the validator to decrease stake from"),
+                    .help("Vote account for the validator to move stake from"),
             )
             .arg(
-                Arg::with_name("amount")
+                Arg::with_name("destination_vote_account")
                     .index(3)
+                    .validator(is_pubkey)
+                    .value_name("DESTINATION_VOTE_ACCOUNT_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Vote account for the validator to move stake to"),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .index(4)
                     .validator(is_amount)
                     .value_name("AMOUNT")
                     .takes_value(true)
-                    .help("Amount in SOL to remove from the validator stake account. Must be at least the rent-exempt amount for a stake."),
+                    .required(true)
+                    .help("Amount in SOL to move. The destination receives the amount minus the rent-exempt amount of a stake, which the reserve covers."),
+            )
+            .arg(
+                Arg::with_name("ephemeral_seed")
+                    .long("ephemeral-seed")
+                    .validator(is_parsable::<u64>)
+                    .value_name("SEED")
+                    .takes_value(true)
+                    .help("Seed of the ephemeral stake account used for the move [default: 0]"),
+            )
+        )
+        .subcommand(SubCommand::with_name("rebalance")
+            .about("Move stake between the reserve and the validators toward target weights. Must be signed by the pool staker. Use --dry-run to only simulate the transactions.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address"),
+            )
+            .arg(
+                Arg::with_name("weight")
+                    .long("weight")
+                    .value_name("VOTE_ACCOUNT_ADDRESS=WEIGHT")
+                    .takes_value(true)
+                    .multiple(true)
+                    .number_of_values(1)
+                    .help("Target weight of a validator, may be specified multiple times. \
+                          Validators without a weight are reduced to the minimum stake. \
+                          Defaults to equal weights for all validators."),
+            )
+            .arg(
+                Arg::with_name("weights_file")
+                    .long("weights-file")
+                    .value_name("PATH")
+                    .takes_value(true)
+                    .conflicts_with("weight")
+                    .help("File with one validator vote account per line, optionally followed by its weight, which defaults to 1"),
+            )
+            .arg(
+                Arg::with_name("reserve_buffer")
+                    .long("reserve-buffer")
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .help("Amount in SOL to keep in the reserve on top of its minimum balance [default: 0]"),
+            )
+            .arg(
+                Arg::with_name("min_move")
+                    .long("min-move")
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .help("Smallest amount in SOL worth moving, smaller differences to the targets are left as they are [default: minimum delegation]"),
+            )
+            .arg(
+                Arg::with_name("use_redelegate")
+                    .long("use-redelegate")
+                    .takes_value(false)
+                    .help("Move stake directly between validators with Redelegate, only works on programs with redelegation enabled"),
             )
         )
         .subcommand(SubCommand::with_name("set-preferred-validator")
@@ -2676,6 +4336,14 @@ fn main() {
                     .help("Pool token account to receive the referral fees for deposits. \
                           Defaults to the token receiver."),
             )
+            .arg(
+                Arg::with_name("min_pool_tokens_out")
+                    .long("min-pool-tokens-out")
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .help("Minimum amount of pool tokens to receive, the deposit fails if fewer are minted"),
+            )
         )
         .subcommand(SubCommand::with_name("deposit-all-stake")
             .about("Deposit all active stake accounts into the stake pool in exchange for pool tokens")
@@ -2769,6 +4437,14 @@ fn main() {
                     .help("Account to receive the referral fees for deposits. \
                           Defaults to the token receiver."),
             )
+            .arg(
+                Arg::with_name("min_pool_tokens_out")
+                    .long("min-pool-tokens-out")
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .help("Minimum amount of pool tokens to receive, the deposit fails if fewer are minted"),
+            )
         )
         .subcommand(SubCommand::with_name("list")
             .about("List stake accounts managed by this pool")
@@ -2781,39 +4457,228 @@ fn main() {
                     .required(true)
                     .help("Stake pool address."),
             )
-        )
-        .subcommand(SubCommand::with_name("update")
-            .about("Updates all balances in the pool after validator stake accounts receive rewards.")
+        )
+        .subcommand(SubCommand::with_name("verify")
+            .about("Check the stake pool, its validator list and stake accounts for discrepancies, and suggest how to fix them")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+        )
+        .subcommand(SubCommand::with_name("update")
+            .about("Updates all balances in the pool after validator stake accounts receive rewards.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("force")
+                    .long("force")
+                    .takes_value(false)
+                    .help("Update balances, even if it has already been performed this epoch."),
+            )
+            .arg(
+                Arg::with_name("no_merge")
+                    .long("no-merge")
+                    .takes_value(false)
+                    .help("Do not automatically merge transient stakes. Useful if the stake pool is in an expected state, but the balances still need to be updated."),
+            )
+            .arg(
+                Arg::with_name("stale_only")
+                    .long("stale-only")
+                    .takes_value(false)
+                    .help("If set, only updates validator list balances that have not been updated for this epoch. Otherwise, updates all validator balances on the validator list."),
+            )
+        )
+        .subcommand(SubCommand::with_name("withdraw-stake")
+            .about("Withdraw active stake from the stake pool in exchange for pool tokens")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .index(2)
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount of pool tokens to withdraw for activated stake."),
+            )
+            .arg(
+                Arg::with_name("pool_account")
+                    .long("pool-account")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Pool token account to withdraw tokens from. Defaults to the token-owner's associated token account."),
+            )
+            .arg(
+                Arg::with_name("stake_receiver")
+                    .long("stake-receiver")
+                    .validator(is_pubkey)
+                    .value_name("STAKE_ACCOUNT_ADDRESS")
+                    .takes_value(true)
+                    .requires("withdraw_from")
+                    .help("Stake account from which to receive a stake from the stake pool. Defaults to a new stake account."),
+            )
+            .arg(
+                Arg::with_name("vote_account")
+                    .long("vote-account")
+                    .validator(is_pubkey)
+                    .value_name("VOTE_ACCOUNT_ADDRESS")
+                    .takes_value(true)
+                    .help("Validator to withdraw from. Defaults to the largest validator stakes in the pool."),
+            )
+            .arg(
+                Arg::with_name("use_reserve")
+                    .long("use-reserve")
+                    .takes_value(false)
+                    .help("Withdraw from the stake pool's reserve. Only possible if all validator stakes are at the minimum possible amount."),
+            )
+            .arg(
+                Arg::with_name("min_lamports_out")
+                    .long("min-lamports-out")
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .help("Minimum amount in SOL to receive, the withdrawal fails if less is withdrawn. Split across the withdrawn stake accounts in proportion to their pool tokens."),
+            )
+            .group(ArgGroup::with_name("withdraw_from")
+                .arg("use_reserve")
+                .arg("vote_account")
+            )
+        )
+        .subcommand(SubCommand::with_name("swap-pool-tokens")
+            .about("Swap pool tokens of one stake pool for those of another in one transaction, \
+                    by withdrawing active stake and depositing it into the other pool")
+            .after_help("The stake moves through a validator already active in both pools. \
+                         This command does not add a missing validator to the destination pool: \
+                         only its staker can, with `add-validator`, and the new validator stake \
+                         account can receive deposits from the next epoch.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address to swap pool tokens from."),
+            )
+            .arg(
+                Arg::with_name("destination_pool")
+                    .index(2)
+                    .validator(is_pubkey)
+                    .value_name("DESTINATION_POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address to swap pool tokens into."),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .index(3)
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount of source pool tokens to swap."),
+            )
+            .arg(
+                Arg::with_name("pool_account")
+                    .long("pool-account")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Source pool token account to swap tokens from. Defaults to the token-owner's associated token account."),
+            )
+            .arg(
+                Arg::with_name("destination_pool_account")
+                    .long("destination-pool-account")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Destination pool token account to receive tokens. Defaults to the token-owner's associated token account."),
+            )
+            .arg(
+                Arg::with_name("vote_account")
+                    .long("vote-account")
+                    .validator(is_pubkey)
+                    .value_name("VOTE_ACCOUNT_ADDRESS")
+                    .takes_value(true)
+                    .help("Validator to move stake through. Defaults to the largest validator stake active in both pools."),
+            )
+            .arg(
+                Arg::with_name("min_pool_tokens_out")
+                    .long("min-pool-tokens-out")
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .help("Minimum amount of destination pool tokens to receive, the swap fails if fewer are minted"),
+            )
+        )
+        .subcommand(SubCommand::with_name("withdraw-sol")
+            .about("Withdraw SOL from the stake pool's reserve in exchange for pool tokens")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
             .arg(
-                Arg::with_name("pool")
-                    .index(1)
-                    .validator(is_pubkey)
-                    .value_name("POOL_ADDRESS")
+                Arg::with_name("sol_receiver")
+                    .index(2)
+                    .validator(is_valid_pubkey)
+                    .value_name("SYSTEM_ACCOUNT_ADDRESS_OR_KEYPAIR")
                     .takes_value(true)
                     .required(true)
-                    .help("Stake pool address."),
+                    .help("System account to receive SOL from the stake pool. Defaults to the payer."),
             )
             .arg(
-                Arg::with_name("force")
-                    .long("force")
-                    .takes_value(false)
-                    .help("Update balances, even if it has already been performed this epoch."),
+                Arg::with_name("amount")
+                    .index(3)
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount of pool tokens to withdraw for SOL."),
             )
             .arg(
-                Arg::with_name("no_merge")
-                    .long("no-merge")
-                    .takes_value(false)
-                    .help("Do not automatically merge transient stakes. Useful if the stake pool is in an expected state, but the balances still need to be updated."),
+                Arg::with_name("pool_account")
+                    .long("pool-account")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Pool token account to withdraw tokens from. Defaults to the token-owner's associated token account."),
             )
             .arg(
-                Arg::with_name("stale_only")
-                    .long("stale-only")
-                    .takes_value(false)
-                    .help("If set, only updates validator list balances that have not been updated for this epoch. Otherwise, updates all validator balances on the validator list."),
+                Arg::with_name("min_lamports_out")
+                    .long("min-lamports-out")
+                    .validator(is_amount)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .help("Minimum amount in SOL to receive, the withdrawal fails if less is withdrawn"),
             )
         )
-        .subcommand(SubCommand::with_name("withdraw-stake")
-            .about("Withdraw active stake from the stake pool in exchange for pool tokens")
+        .subcommand(SubCommand::with_name("create-unstake-ticket")
+            .about("Burn pool tokens for an unstake ticket, claimable for SOL once the staker deactivated enough stake")
             .arg(
                 Arg::with_name("pool")
                     .index(1)
@@ -2830,7 +4695,7 @@ fn main() {
                     .value_name("AMOUNT")
                     .takes_value(true)
                     .required(true)
-                    .help("Amount of pool tokens to withdraw for activated stake."),
+                    .help("Amount of pool tokens to unstake."),
             )
             .arg(
                 Arg::with_name("pool_account")
@@ -2838,38 +4703,19 @@ fn main() {
                     .validator(is_pubkey)
                     .value_name("ADDRESS")
                     .takes_value(true)
-                    .help("Pool token account to withdraw tokens from. Defaults to the token-owner's associated token account."),
-            )
-            .arg(
-                Arg::with_name("stake_receiver")
-                    .long("stake-receiver")
-                    .validator(is_pubkey)
-                    .value_name("STAKE_ACCOUNT_ADDRESS")
-                    .takes_value(true)
-                    .requires("withdraw_from")
-                    .help("Stake account from which to receive a stake from the stake pool. Defaults to a new stake account."),
+                    .help("Pool token account to burn tokens from. Defaults to the token-owner's associated token account."),
             )
             .arg(
-                Arg::with_name("vote_account")
-                    .long("vote-account")
+                Arg::with_name("ticket_owner")
+                    .long("ticket-owner")
                     .validator(is_pubkey)
-                    .value_name("VOTE_ACCOUNT_ADDRESS")
+                    .value_name("ADDRESS")
                     .takes_value(true)
-                    .help("Validator to withdraw from. Defaults to the largest validator stakes in the pool."),
-            )
-            .arg(
-                Arg::with_name("use_reserve")
-                    .long("use-reserve")
-                    .takes_value(false)
-                    .help("Withdraw from the stake pool's reserve. Only possible if all validator stakes are at the minimum possible amount."),
-            )
-            .group(ArgGroup::with_name("withdraw_from")
-                .arg("use_reserve")
-                .arg("vote_account")
+                    .help("Owner allowed to claim the unstake ticket. Defaults to the token-owner."),
             )
         )
-        .subcommand(SubCommand::with_name("withdraw-sol")
-            .about("Withdraw SOL from the stake pool's reserve in exchange for pool tokens")
+        .subcommand(SubCommand::with_name("claim-unstake-ticket")
+            .about("Claim the SOL of an unstake ticket. Must be signed by the ticket owner, given as the token-owner.")
             .arg(
                 Arg::with_name("pool")
                     .index(1)
@@ -2880,30 +4726,55 @@ fn main() {
                     .help("Stake pool address."),
             )
             .arg(
-                Arg::with_name("sol_receiver")
+                Arg::with_name("ticket")
                     .index(2)
-                    .validator(is_valid_pubkey)
-                    .value_name("SYSTEM_ACCOUNT_ADDRESS_OR_KEYPAIR")
+                    .validator(is_pubkey)
+                    .value_name("TICKET_ADDRESS")
                     .takes_value(true)
                     .required(true)
-                    .help("System account to receive SOL from the stake pool. Defaults to the payer."),
+                    .help("Unstake ticket address."),
             )
             .arg(
-                Arg::with_name("amount")
-                    .index(3)
-                    .validator(is_amount)
-                    .value_name("AMOUNT")
+                Arg::with_name("to")
+                    .long("to")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Account to receive the SOL. Defaults to the token-owner."),
+            )
+        )
+        .subcommand(SubCommand::with_name("list-unstake-tickets")
+            .about("List the unstake tickets of a stake pool and the lamports owed to them")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
                     .takes_value(true)
                     .required(true)
-                    .help("Amount of pool tokens to withdraw for SOL."),
+                    .help("Stake pool address."),
             )
             .arg(
-                Arg::with_name("pool_account")
-                    .long("pool-account")
+                Arg::with_name("owner")
+                    .long("owner")
                     .validator(is_pubkey)
                     .value_name("ADDRESS")
                     .takes_value(true)
-                    .help("Pool token account to withdraw tokens from. Defaults to the token-owner's associated token account."),
+                    .help("Only list the unstake tickets of this owner."),
+            )
+        )
+        .subcommand(SubCommand::with_name("crank-unstake-tickets")
+            .about("Deactivate the stake owed to unstake tickets that the reserve cannot cover. \
+                    The fee payer pre-funds the rent of each transient stake account. \
+                    Must be signed by the pool staker.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
             )
         )
         .subcommand(SubCommand::with_name("set-manager")
@@ -2960,6 +4831,100 @@ fn main() {
                     .help("Public key for the new stake pool staker."),
             )
         )
+        .subcommand(SubCommand::with_name("create-reward-history")
+            .about("Create the account recording the rewards and MEV earned by each validator \
+                    of the stake pool, updated by every update. Must be signed by the staker.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("reward_history_keypair")
+                    .long("reward-history-keypair")
+                    .validator(is_keypair_or_ask_keyword)
+                    .value_name("PATH")
+                    .takes_value(true)
+                    .help("Validator reward history keypair [default: new keypair]"),
+            )
+        )
+        .subcommand(SubCommand::with_name("set-validator-policy")
+            .about("Set the validator policy of the stake pool, evaluated on every update. \
+                    Validators violating it are removed from the pool. Must be signed by the staker.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("max_commission")
+                    .long("max-commission")
+                    .validator(is_valid_percentage)
+                    .value_name("PERCENT")
+                    .takes_value(true)
+                    .default_value("100")
+                    .help("Highest commission allowed for a validator. 100 disables the check."),
+            )
+            .arg(
+                Arg::with_name("min_epoch_credits_bps")
+                    .long("min-epoch-credits-bps")
+                    .validator(is_parsable::<u16>)
+                    .value_name("BPS")
+                    .takes_value(true)
+                    .default_value("0")
+                    .help("Minimum credits earned by a validator in the previous epoch, \
+                           in basis points of the cluster epoch credits recorded with \
+                           set-cluster-epoch-credits for that epoch. 0 disables the check."),
+            )
+            .arg(
+                Arg::with_name("max_delinquent_slots")
+                    .long("max-delinquent-slots")
+                    .validator(is_parsable::<u64>)
+                    .value_name("SLOTS")
+                    .takes_value(true)
+                    .default_value("0")
+                    .help("Maximum number of slots since the last vote of a validator. 0 disables the check."),
+            )
+        )
+        .subcommand(SubCommand::with_name("set-cluster-epoch-credits")
+            .about("Record the credits earned by a typical validator of the cluster during an epoch, \
+                    the reference of the minimum epoch credits of the validator policy. \
+                    Must be signed by the staker.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("epoch")
+                    .long("epoch")
+                    .validator(is_parsable::<u64>)
+                    .value_name("EPOCH")
+                    .takes_value(true)
+                    .help("Epoch the credits were earned in. Defaults to the previous epoch."),
+            )
+            .arg(
+                Arg::with_name("epoch_credits")
+                    .long("epoch-credits")
+                    .validator(is_parsable::<u64>)
+                    .value_name("CREDITS")
+                    .takes_value(true)
+                    .help("Credits earned during the epoch. Defaults to the median credits \
+                           of the staked vote accounts of the cluster."),
+            )
+        )
         .subcommand(SubCommand::with_name("set-funding-authority")
             .about("Change one of the funding authorities for the stake pool. Must be signed by the manager.")
             .arg(
@@ -3038,6 +5003,41 @@ fn main() {
                     .help("Fee denominator, fee amount is numerator divided by denominator."),
             )
         )
+        .subcommand(SubCommand::with_name("set-fee-split")
+            .about("Split the epoch fee of the stake pool between several token accounts. \
+                    Like epoch fee changes, the new split takes effect after two epoch boundaries. \
+                    Must be signed by the manager.")
+            .arg(
+                Arg::with_name("pool")
+                    .index(1)
+                    .validator(is_pubkey)
+                    .value_name("POOL_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Stake pool address."),
+            )
+            .arg(
+                Arg::with_name("recipient")
+                    .long("recipient")
+                    .validator(is_fee_recipient)
+                    .value_name("TOKEN_ACCOUNT_ADDRESS=BPS")
+                    .takes_value(true)
+                    .multiple(true)
+                    .help("Pool token account receiving a share of the epoch fee, in basis points. \
+                           Shares of all recipients must add up to 10000."),
+            )
+            .arg(
+                Arg::with_name("clear")
+                    .long("clear")
+                    .takes_value(false)
+                    .help("Remove all recipients, giving the epoch fee back to the manager fee account."),
+            )
+            .group(ArgGroup::with_name("recipients")
+                .arg("recipient")
+                .arg("clear")
+                .required(true)
+            )
+        )
         .subcommand(SubCommand::with_name("set-referral-fee")
             .about("Change the referral fee assessed by the stake pool for stake deposits. Must be signed by the manager.")
             .arg(
@@ -3251,6 +5251,72 @@ fn main() {
             let amount = value_t_or_exit!(arg_matches, "amount", f64);
             command_decrease_validator_stake(&config, &stake_pool_address, &vote_account, amount)
         }
+        ("increase-additional-validator-stake", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let vote_account = pubkey_of(arg_matches, "vote_account").unwrap();
+            let amount = value_t_or_exit!(arg_matches, "amount", f64);
+            let ephemeral_seed = value_t!(arg_matches, "ephemeral_seed", u64).unwrap_or(0);
+            command_increase_additional_validator_stake(
+                &config,
+                &stake_pool_address,
+                &vote_account,
+                amount,
+                ephemeral_seed,
+            )
+        }
+        ("decrease-additional-validator-stake", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let vote_account = pubkey_of(arg_matches, "vote_account").unwrap();
+            let amount = value_t_or_exit!(arg_matches, "amount", f64);
+            let ephemeral_seed = value_t!(arg_matches, "ephemeral_seed", u64).unwrap_or(0);
+            command_decrease_additional_validator_stake(
+                &config,
+                &stake_pool_address,
+                &vote_account,
+                amount,
+                ephemeral_seed,
+            )
+        }
+        ("redelegate", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let source_vote_account = pubkey_of(arg_matches, "source_vote_account").unwrap();
+            let destination_vote_account =
+                pubkey_of(arg_matches, "destination_vote_account").unwrap();
+            let amount = value_t_or_exit!(arg_matches, "amount", f64);
+            let ephemeral_seed = value_t!(arg_matches, "ephemeral_seed", u64).unwrap_or(0);
+            command_redelegate(
+                &config,
+                &stake_pool_address,
+                &source_vote_account,
+                &destination_vote_account,
+                amount,
+                ephemeral_seed,
+            )
+        }
+        ("rebalance", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let weights = if let Some(path) = arg_matches.value_of("weights_file") {
+                TargetWeights::from_file(path)
+            } else if let Some(weights) = arg_matches.values_of("weight") {
+                TargetWeights::from_args(weights)
+            } else {
+                Ok(TargetWeights::Equal)
+            }
+            .unwrap_or_else(|err| {
+                eprintln!("error: {}", err);
+                exit(1);
+            });
+            let options = RebalanceOptions {
+                reserve_buffer_lamports: value_t!(arg_matches, "reserve_buffer", f64)
+                    .map(native_token::sol_to_lamports)
+                    .unwrap_or(0),
+                minimum_move_lamports: value_t!(arg_matches, "min_move", f64)
+                    .map(native_token::sol_to_lamports)
+                    .unwrap_or(0),
+                use_redelegate: arg_matches.is_present("use_redelegate"),
+            };
+            command_rebalance(&config, &stake_pool_address, &weights, &options)
+        }
         ("set-preferred-validator", Some(arg_matches)) => {
             let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
             let preferred_type = match arg_matches.value_of("preferred_type").unwrap() {
@@ -3274,6 +5340,7 @@ fn main() {
             let stake_account = pubkey_of(arg_matches, "stake_account").unwrap();
             let token_receiver: Option<Pubkey> = pubkey_of(arg_matches, "token_receiver");
             let referrer: Option<Pubkey> = pubkey_of(arg_matches, "referrer");
+            let min_pool_tokens_out = value_t!(arg_matches, "min_pool_tokens_out", f64).ok();
             let withdraw_authority = get_signer(
                 arg_matches,
                 "withdraw_authority",
@@ -3290,6 +5357,7 @@ fn main() {
                 withdraw_authority,
                 &token_receiver,
                 &referrer,
+                min_pool_tokens_out,
             )
         }
         ("deposit-sol", Some(arg_matches)) => {
@@ -3298,6 +5366,7 @@ fn main() {
             let referrer: Option<Pubkey> = pubkey_of(arg_matches, "referrer");
             let from = keypair_of(arg_matches, "from");
             let amount = value_t_or_exit!(arg_matches, "amount", f64);
+            let min_pool_tokens_out = value_t!(arg_matches, "min_pool_tokens_out", f64).ok();
             command_deposit_sol(
                 &config,
                 &stake_pool_address,
@@ -3305,12 +5374,17 @@ fn main() {
                 &token_receiver,
                 &referrer,
                 amount,
+                min_pool_tokens_out,
             )
         }
         ("list", Some(arg_matches)) => {
             let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
             command_list(&config, &stake_pool_address)
         }
+        ("verify", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            command_verify(&config, &stake_pool_address)
+        }
         ("update", Some(arg_matches)) => {
             let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
             let no_merge = arg_matches.is_present("no_merge");
@@ -3325,6 +5399,7 @@ fn main() {
             let pool_amount = value_t_or_exit!(arg_matches, "amount", f64);
             let stake_receiver = pubkey_of(arg_matches, "stake_receiver");
             let use_reserve = arg_matches.is_present("use_reserve");
+            let min_lamports_out = value_t!(arg_matches, "min_lamports_out", f64).ok();
             command_withdraw_stake(
                 &config,
                 &stake_pool_address,
@@ -3333,12 +5408,34 @@ fn main() {
                 &stake_receiver,
                 &pool_account,
                 pool_amount,
+                min_lamports_out,
+            )
+        }
+        ("swap-pool-tokens", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let destination_stake_pool_address =
+                pubkey_of(arg_matches, "destination_pool").unwrap();
+            let vote_account = pubkey_of(arg_matches, "vote_account");
+            let pool_account = pubkey_of(arg_matches, "pool_account");
+            let destination_pool_account = pubkey_of(arg_matches, "destination_pool_account");
+            let pool_amount = value_t_or_exit!(arg_matches, "amount", f64);
+            let min_pool_tokens_out = value_t!(arg_matches, "min_pool_tokens_out", f64).ok();
+            command_swap_pool_tokens(
+                &config,
+                &stake_pool_address,
+                &destination_stake_pool_address,
+                &vote_account,
+                &pool_account,
+                &destination_pool_account,
+                pool_amount,
+                min_pool_tokens_out,
             )
         }
         ("withdraw-sol", Some(arg_matches)) => {
             let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
             let pool_account = pubkey_of(arg_matches, "pool_account");
             let pool_amount = value_t_or_exit!(arg_matches, "amount", f64);
+            let min_lamports_out = value_t!(arg_matches, "min_lamports_out", f64).ok();
             let sol_receiver = get_signer(
                 arg_matches,
                 "sol_receiver",
@@ -3355,8 +5452,42 @@ fn main() {
                 &pool_account,
                 &sol_receiver,
                 pool_amount,
+                min_lamports_out,
             )
         }
+        ("create-unstake-ticket", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let pool_account = pubkey_of(arg_matches, "pool_account");
+            let ticket_owner = pubkey_of(arg_matches, "ticket_owner");
+            let pool_amount = value_t_or_exit!(arg_matches, "amount", f64);
+            command_create_unstake_ticket(
+                &config,
+                &stake_pool_address,
+                &pool_account,
+                &ticket_owner,
+                pool_amount,
+            )
+        }
+        ("claim-unstake-ticket", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let unstake_ticket_address = pubkey_of(arg_matches, "ticket").unwrap();
+            let lamports_to = pubkey_of(arg_matches, "to");
+            command_claim_unstake_ticket(
+                &config,
+                &stake_pool_address,
+                &unstake_ticket_address,
+                &lamports_to,
+            )
+        }
+        ("list-unstake-tickets", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let owner = pubkey_of(arg_matches, "owner");
+            command_list_unstake_tickets(&config, &stake_pool_address, &owner)
+        }
+        ("crank-unstake-tickets", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            command_crank_unstake_tickets(&config, &stake_pool_address)
+        }
         ("set-manager", Some(arg_matches)) => {
             let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
 
@@ -3390,6 +5521,43 @@ fn main() {
             let new_staker = pubkey_of(arg_matches, "new_staker").unwrap();
             command_set_staker(&config, &stake_pool_address, &new_staker)
         }
+        ("set-validator-policy", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let max_commission = value_t_or_exit!(arg_matches, "max_commission", u8);
+            let min_epoch_credits_bps = value_t_or_exit!(arg_matches, "min_epoch_credits_bps", u16);
+            let max_delinquent_slots = value_t_or_exit!(arg_matches, "max_delinquent_slots", u64);
+            command_set_validator_policy(
+                &config,
+                &stake_pool_address,
+                max_commission,
+                min_epoch_credits_bps,
+                max_delinquent_slots,
+            )
+        }
+        ("set-cluster-epoch-credits", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let epoch = value_t!(arg_matches, "epoch", u64).ok();
+            let epoch_credits = value_t!(arg_matches, "epoch_credits", u64).ok();
+            command_set_cluster_epoch_credits(&config, &stake_pool_address, epoch, epoch_credits)
+        }
+        ("create-reward-history", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let validator_reward_history_keypair =
+                keypair_of(arg_matches, "reward_history_keypair");
+            command_create_validator_reward_history(
+                &config,
+                &stake_pool_address,
+                validator_reward_history_keypair,
+            )
+        }
+        ("set-fee-split", Some(arg_matches)) => {
+            let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
+            let recipients = arg_matches
+                .values_of("recipient")
+                .map(|values| values.map(|x| parse_fee_recipient(x).unwrap()).collect())
+                .unwrap_or_default();
+            command_set_fee_split(&config, &stake_pool_address, recipients)
+        }
         ("set-funding-authority", Some(arg_matches)) => {
             let stake_pool_address = pubkey_of(arg_matches, "pool").unwrap();
             let new_authority = pubkey_of(arg_matches, "new_authority");
@@ -1,9 +1,14 @@
 use {
+    crate::{
+        rebalance::{RebalanceStep, ValidatorTarget},
+        verify::Discrepancy,
+    },
     serde::{Deserialize, Serialize},
     solana_cli_output::{QuietDisplay, VerboseDisplay},
     solana_sdk::{native_token::Sol, pubkey::Pubkey, stake::state::Lockup},
     spl_stake_pool::state::{
-        Fee, PodStakeStatus, StakePool, StakeStatus, ValidatorList, ValidatorStakeInfo,
+        Fee, FeeRecipient, PodStakeStatus, StakePool, StakeStatus, ValidatorList,
+        ValidatorStakeInfo,
     },
     std::fmt::{Display, Formatter, Result, Write},
 };
@@ -247,6 +252,55 @@ pub(crate) struct CliStakePoolDetails {
     pub current_number_of_validators: u32,
     pub max_number_of_validators: u32,
     pub update_required: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub apy: Option<f64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fee_recipients: Vec<CliFeeRecipient>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_fee_recipients: Option<Vec<CliFeeRecipient>>,
+}
+
+impl CliStakePoolDetails {
+    fn write_fee_recipients(&self, w: &mut dyn Write) -> Result {
+        for recipient in &self.fee_recipients {
+            writeln!(
+                w,
+                "Epoch Fee Recipient: {}\tShare: {}%",
+                recipient.token_account,
+                recipient.bps as f64 / 100.0,
+            )?;
+        }
+        if let Some(next_fee_recipients) = &self.next_fee_recipients {
+            if next_fee_recipients.is_empty() {
+                writeln!(w, "Next Epoch Fee Recipient: manager fee account")?;
+            }
+            for recipient in next_fee_recipients {
+                writeln!(
+                    w,
+                    "Next Epoch Fee Recipient: {}\tShare: {}%",
+                    recipient.token_account,
+                    recipient.bps as f64 / 100.0,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CliFeeRecipient {
+    pub token_account: String,
+    pub bps: u16,
+}
+
+impl From<&FeeRecipient> for CliFeeRecipient {
+    fn from(r: &FeeRecipient) -> Self {
+        Self {
+            token_account: r.token_account.to_string(),
+            bps: r.bps,
+        }
+    }
 }
 
 impl Display for CliStakePoolDetails {
@@ -260,10 +314,16 @@ impl Display for CliStakePoolDetails {
         for stake_account in &self.stake_accounts {
             writeln!(
                 f,
-                "Vote Account: {}\tBalance: {}\tLast Update Epoch: {}",
+                "Vote Account: {}\tBalance: {}\tLast Update Epoch: {}{}",
                 stake_account.vote_account_address,
                 Sol(stake_account.validator_lamports),
                 stake_account.validator_last_update_epoch,
+                stake_account
+                    .rewards
+                    .as_ref()
+                    .and_then(|rewards| rewards.apy)
+                    .map(|apy| format!("\tAPY: {:.2}%", apy * 100.0))
+                    .unwrap_or_default(),
             )?;
         }
         writeln!(
@@ -277,6 +337,10 @@ impl Display for CliStakePoolDetails {
             },
         )?;
         writeln!(f, "Total Pool Tokens: {}", &self.total_pool_tokens,)?;
+        self.write_fee_recipients(f)?;
+        if let Some(apy) = self.apy {
+            writeln!(f, "Pool APY: {:.2}%", apy * 100.0)?;
+        }
         writeln!(
             f,
             "Current Number of Validators: {}",
@@ -318,6 +382,21 @@ impl VerboseDisplay for CliStakePoolDetails {
                     ""
                 },
             )?;
+            if let Some(rewards) = &stake_account.rewards {
+                writeln!(
+                    w,
+                    "    Rewards: {}\tMEV: {}\tEpochs: {}\tTotal Rewards: {}\tTotal MEV: {}\tAPY: {}",
+                    Sol(rewards.last_rewards_lamports),
+                    Sol(rewards.last_mev_lamports),
+                    rewards.last_epochs,
+                    Sol(rewards.total_rewards_lamports),
+                    Sol(rewards.total_mev_lamports),
+                    rewards
+                        .apy
+                        .map(|apy| format!("{:.2}%", apy * 100.0))
+                        .unwrap_or_else(|| "-".to_string()),
+                )?;
+            }
         }
         writeln!(
             w,
@@ -330,6 +409,10 @@ impl VerboseDisplay for CliStakePoolDetails {
             },
         )?;
         writeln!(w, "Total Pool Tokens: {}", &self.total_pool_tokens,)?;
+        self.write_fee_recipients(w)?;
+        if let Some(apy) = self.apy {
+            writeln!(w, "Pool APY: {:.2}%", apy * 100.0)?;
+        }
         writeln!(
             w,
             "Current Number of Validators: {}",
@@ -355,6 +438,23 @@ pub(crate) struct CliStakePoolStakeAccountInfo {
     pub validator_transient_stake_account_address: String,
     pub validator_transient_stake_lamports: u64,
     pub update_required: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rewards: Option<CliValidatorRewards>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CliValidatorRewards {
+    pub first_update_epoch: u64,
+    pub last_update_epoch: u64,
+    pub last_epochs: u64,
+    pub last_stake_lamports: u64,
+    pub last_rewards_lamports: u64,
+    pub last_mev_lamports: u64,
+    pub total_rewards_lamports: u64,
+    pub total_mev_lamports: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub apy: Option<f64>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -503,3 +603,280 @@ impl From<(Pubkey, StakePool, ValidatorList, Pubkey)> for CliStakePool {
         }
     }
 }
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CliRebalancePlan {
+    pub reserve_stake_lamports: u64,
+    pub planned_reserve_stake_lamports: u64,
+    pub validators: Vec<CliRebalanceTarget>,
+    pub steps: Vec<CliRebalanceStep>,
+    pub incomplete: bool,
+}
+
+impl Display for CliRebalancePlan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        writeln!(
+            f,
+            "Reserve Balance: {}\tPlanned Reserve Balance: {}",
+            Sol(self.reserve_stake_lamports),
+            Sol(self.planned_reserve_stake_lamports),
+        )?;
+        for validator in &self.validators {
+            writeln!(
+                f,
+                "Vote Account: {}\tWeight: {}\tBalance: {}\tTarget: {}\tPlanned: {}",
+                validator.vote_account_address,
+                validator.weight,
+                Sol(validator.current_lamports),
+                Sol(validator.target_lamports),
+                Sol(validator.planned_lamports),
+            )?;
+        }
+        if self.steps.is_empty() {
+            writeln!(f, "No stake movements required")?;
+        }
+        for (index, step) in self.steps.iter().enumerate() {
+            match &step.destination_vote_account_address {
+                Some(destination) => writeln!(
+                    f,
+                    "Step {}: {} {} from {} to {}",
+                    index + 1,
+                    step.instruction,
+                    Sol(step.lamports),
+                    step.vote_account_address,
+                    destination,
+                )?,
+                None => writeln!(
+                    f,
+                    "Step {}: {} {} on {}",
+                    index + 1,
+                    step.instruction,
+                    Sol(step.lamports),
+                    step.vote_account_address,
+                )?,
+            }
+        }
+        if self.incomplete {
+            writeln!(
+                f,
+                "Targets cannot all be reached this epoch, rebalance again after the next update"
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl QuietDisplay for CliRebalancePlan {}
+impl VerboseDisplay for CliRebalancePlan {}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CliRebalanceTarget {
+    pub vote_account_address: String,
+    pub weight: u64,
+    pub current_lamports: u64,
+    pub target_lamports: u64,
+    pub planned_lamports: u64,
+}
+
+impl From<&ValidatorTarget> for CliRebalanceTarget {
+    fn from(target: &ValidatorTarget) -> Self {
+        Self {
+            vote_account_address: target.vote_account_address.to_string(),
+            weight: target.weight,
+            current_lamports: target.current_lamports,
+            target_lamports: target.target_lamports,
+            planned_lamports: target.planned_lamports,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CliRebalanceStep {
+    pub instruction: String,
+    pub vote_account_address: String,
+    pub destination_vote_account_address: Option<String>,
+    pub lamports: u64,
+}
+
+impl From<&RebalanceStep> for CliRebalanceStep {
+    fn from(step: &RebalanceStep) -> Self {
+        match step {
+            RebalanceStep::Increase {
+                vote_account_address,
+                lamports,
+            } => Self {
+                instruction: "IncreaseAdditionalValidatorStake".to_string(),
+                vote_account_address: vote_account_address.to_string(),
+                destination_vote_account_address: None,
+                lamports: *lamports,
+            },
+            RebalanceStep::Decrease {
+                vote_account_address,
+                lamports,
+            } => Self {
+                instruction: "DecreaseValidatorStakeWithReserve".to_string(),
+                vote_account_address: vote_account_address.to_string(),
+                destination_vote_account_address: None,
+                lamports: *lamports,
+            },
+            RebalanceStep::Redelegate {
+                source_vote_account_address,
+                destination_vote_account_address,
+                lamports,
+            } => Self {
+                instruction: "Redelegate".to_string(),
+                vote_account_address: source_vote_account_address.to_string(),
+                destination_vote_account_address: Some(
+                    destination_vote_account_address.to_string(),
+                ),
+                lamports: *lamports,
+            },
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CliUnstakeTickets {
+    pub ledger_address: String,
+    pub owed_lamports: u64,
+    pub funded_lamports: u64,
+    pub tickets: Vec<CliUnstakeTicket>,
+}
+
+impl Display for CliUnstakeTickets {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        writeln!(
+            f,
+            "Ledger: {}\tOwed: {}\tFunded: {}",
+            self.ledger_address,
+            Sol(self.owed_lamports),
+            Sol(self.funded_lamports),
+        )?;
+        if self.tickets.is_empty() {
+            writeln!(f, "No unstake tickets")?;
+        }
+        for ticket in &self.tickets {
+            writeln!(
+                f,
+                "Ticket: {}\tOwner: {}\tAmount: {}\tClaimable Epoch: {}{}",
+                ticket.address,
+                ticket.owner,
+                Sol(ticket.lamports),
+                ticket.claimable_epoch,
+                if ticket.claimable { " (claimable)" } else { "" },
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl QuietDisplay for CliUnstakeTickets {}
+impl VerboseDisplay for CliUnstakeTickets {}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CliUnstakeTicket {
+    pub address: String,
+    pub owner: String,
+    pub lamports: u64,
+    pub claimable_epoch: u64,
+    pub claimable: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CliUnstakeTicketCrank {
+    pub unfunded_lamports: u64,
+    pub reserve_available_lamports: u64,
+    pub deactivating_lamports: u64,
+    pub shortfall_lamports: u64,
+    pub steps: Vec<CliRebalanceStep>,
+}
+
+impl Display for CliUnstakeTicketCrank {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        writeln!(
+            f,
+            "Unfunded: {}\tReserve Available: {}\tDeactivating: {}\tShortfall: {}",
+            Sol(self.unfunded_lamports),
+            Sol(self.reserve_available_lamports),
+            Sol(self.deactivating_lamports),
+            Sol(self.shortfall_lamports),
+        )?;
+        if self.steps.is_empty() {
+            writeln!(f, "No stake deactivation required")?;
+        }
+        for (index, step) in self.steps.iter().enumerate() {
+            writeln!(
+                f,
+                "Step {}: {} {} on {}",
+                index + 1,
+                step.instruction,
+                Sol(step.lamports),
+                step.vote_account_address,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl QuietDisplay for CliUnstakeTicketCrank {}
+impl VerboseDisplay for CliUnstakeTicketCrank {}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CliVerifyReport {
+    pub stake_pool_address: String,
+    pub epoch: u64,
+    pub validators_checked: usize,
+    pub discrepancies: Vec<CliDiscrepancy>,
+}
+
+impl Display for CliVerifyReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        writeln!(
+            f,
+            "Stake Pool: {}\tEpoch: {}\tValidators Checked: {}",
+            self.stake_pool_address, self.epoch, self.validators_checked,
+        )?;
+        if self.discrepancies.is_empty() {
+            writeln!(f, "No discrepancies found")?;
+        }
+        for (index, discrepancy) in self.discrepancies.iter().enumerate() {
+            writeln!(
+                f,
+                "Discrepancy {}: {}: {}\n    Suggested fix: {}",
+                index + 1,
+                discrepancy.address,
+                discrepancy.description,
+                discrepancy.suggested_fix,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl QuietDisplay for CliVerifyReport {}
+impl VerboseDisplay for CliVerifyReport {}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CliDiscrepancy {
+    pub address: String,
+    pub description: String,
+    pub suggested_fix: String,
+}
+
+impl From<&Discrepancy> for CliDiscrepancy {
+    fn from(discrepancy: &Discrepancy) -> Self {
+        Self {
+            address: discrepancy.address.to_string(),
+            description: discrepancy.description.clone(),
+            suggested_fix: discrepancy.suggested_fix.to_string(),
+        }
+    }
+}
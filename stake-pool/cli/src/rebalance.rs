@@ -0,0 +1,718 @@
+//! Planning stake movements that reconcile a stake pool toward target
+//! validator weights
+
+use {
+    solana_program::pubkey::Pubkey,
+    spl_stake_pool::MINIMUM_RESERVE_LAMPORTS,
+    std::{cmp::Reverse, collections::HashMap, fs, str::FromStr},
+};
+
+/// Target weights of the validators in a stake pool
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum TargetWeights {
+    /// Every active validator receives the same share
+    Equal,
+    /// Validators receive shares proportional to their weight, validators
+    /// without a weight are reduced to the minimum stake
+    Fixed(HashMap<Pubkey, u64>),
+}
+
+impl TargetWeights {
+    /// Parse weights given as `<VOTE_ACCOUNT_ADDRESS>=<WEIGHT>`
+    pub fn from_args<'a>(args: impl Iterator<Item = &'a str>) -> Result<Self, String> {
+        let mut weights = HashMap::new();
+        for arg in args {
+            let (vote_account, weight) = arg
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid weight {}, expected VOTE_ACCOUNT=WEIGHT", arg))?;
+            insert_weight(&mut weights, vote_account, weight)?;
+        }
+        Ok(Self::Fixed(weights))
+    }
+
+    /// Read weights from a file with one vote account per line, optionally
+    /// followed by its weight, which defaults to 1. Blank lines and lines
+    /// starting with `#` are skipped.
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| format!("Unable to read weights file {}: {}", path, err))?;
+        let mut weights = HashMap::new();
+        for line in contents.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split(|c: char| c.is_whitespace() || c == ',');
+            let vote_account = fields.next().unwrap_or_default();
+            let weight = fields.find(|field| !field.is_empty()).unwrap_or("1");
+            insert_weight(&mut weights, vote_account, weight)?;
+        }
+        Ok(Self::Fixed(weights))
+    }
+
+    fn weight(&self, vote_account_address: &Pubkey) -> u64 {
+        match self {
+            Self::Equal => 1,
+            Self::Fixed(weights) => weights.get(vote_account_address).copied().unwrap_or(0),
+        }
+    }
+}
+
+fn insert_weight(
+    weights: &mut HashMap<Pubkey, u64>,
+    vote_account: &str,
+    weight: &str,
+) -> Result<(), String> {
+    let vote_account = Pubkey::from_str(vote_account.trim())
+        .map_err(|err| format!("Invalid vote account {}: {}", vote_account, err))?;
+    let weight = weight
+        .trim()
+        .parse::<u64>()
+        .map_err(|err| format!("Invalid weight {} for {}: {}", weight, vote_account, err))?;
+    if weights.insert(vote_account, weight).is_some() {
+        return Err(format!(
+            "Duplicate weight for vote account {}",
+            vote_account
+        ));
+    }
+    Ok(())
+}
+
+/// State of the transient stake account of a validator, which decides the
+/// stake movements still possible in the current epoch
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum TransientStake {
+    /// No transient stake account exists
+    None,
+    /// Transient stake activating in the current epoch, which can receive
+    /// additional stake
+    Activating,
+    /// Transient stake deactivating, or left from a previous epoch, which
+    /// blocks stake movements until it is merged by an update
+    Locked,
+}
+
+/// Balances of a validator in the stake pool, as recorded in the validator
+/// list after its update for the current epoch
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ValidatorBalance {
+    /// Validator vote account address
+    pub vote_account_address: Pubkey,
+    /// Lamports on the validator stake account, including rent
+    pub active_stake_lamports: u64,
+    /// Lamports on the transient stake account, including rent
+    pub transient_stake_lamports: u64,
+    /// State of the transient stake account
+    pub transient_stake: TransientStake,
+}
+
+impl ValidatorBalance {
+    fn stake_lamports(&self) -> u64 {
+        self.active_stake_lamports
+            .saturating_add(self.transient_stake_lamports)
+    }
+}
+
+/// Stake pool balances the rebalancing plan starts from
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct PoolBalances {
+    /// Lamports on the reserve stake account
+    pub reserve_lamports: u64,
+    /// Rent-exempt reserve of a stake account
+    pub stake_rent: u64,
+    /// Minimum delegation of stake pool stake accounts
+    pub minimum_delegation: u64,
+    /// Active validators of the pool, validators marked for removal are left
+    /// out
+    pub validators: Vec<ValidatorBalance>,
+}
+
+impl PoolBalances {
+    fn minimum_reserve_lamports(&self) -> u64 {
+        self.stake_rent.saturating_add(MINIMUM_RESERVE_LAMPORTS)
+    }
+
+    fn minimum_validator_lamports(&self) -> u64 {
+        self.stake_rent.saturating_add(self.minimum_delegation)
+    }
+}
+
+/// Options of the rebalancing plan
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct RebalanceOptions {
+    /// Lamports kept undelegated in the reserve on top of its minimum, to
+    /// serve SOL withdrawals
+    pub reserve_buffer_lamports: u64,
+    /// Smallest stake movement worth an instruction, moves are always at least
+    /// the minimum delegation
+    pub minimum_move_lamports: u64,
+    /// Whether stake can move directly between validators with `Redelegate`,
+    /// only for stake pool programs with redelegation enabled
+    pub use_redelegate: bool,
+}
+
+/// A stake movement of the rebalancing plan
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum RebalanceStep {
+    /// `IncreaseAdditionalValidatorStake` from the reserve
+    Increase {
+        vote_account_address: Pubkey,
+        lamports: u64,
+    },
+    /// `DecreaseValidatorStakeWithReserve` back to the reserve after the
+    /// cooldown
+    Decrease {
+        vote_account_address: Pubkey,
+        lamports: u64,
+    },
+    /// `Redelegate` from one validator to another
+    Redelegate {
+        source_vote_account_address: Pubkey,
+        destination_vote_account_address: Pubkey,
+        lamports: u64,
+    },
+}
+
+impl RebalanceStep {
+    /// Number of transient stake accounts created or modified by the step
+    pub fn transient_stake_accounts(&self) -> usize {
+        match self {
+            Self::Increase { .. } | Self::Decrease { .. } => 1,
+            Self::Redelegate { .. } => 2,
+        }
+    }
+}
+
+/// Target stake of a validator and the change planned toward it
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ValidatorTarget {
+    /// Validator vote account address
+    pub vote_account_address: Pubkey,
+    /// Target weight of the validator
+    pub weight: u64,
+    /// Lamports currently staked to the validator, including transient stake
+    pub current_lamports: u64,
+    /// Lamports the validator should have staked
+    pub target_lamports: u64,
+    /// Lamports staked to the validator once the planned steps complete
+    pub planned_lamports: u64,
+}
+
+/// Stake movements reconciling a stake pool toward its target weights in the
+/// current epoch
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct RebalancePlan {
+    /// Steps in the order they should be sent
+    pub steps: Vec<RebalanceStep>,
+    /// Targets of every active validator
+    pub targets: Vec<ValidatorTarget>,
+    /// Reserve lamports left once the planned steps complete, before
+    /// decreased stake returns to the reserve
+    pub reserve_lamports: u64,
+}
+
+impl RebalancePlan {
+    /// Whether validators are left away from their targets by more than the
+    /// minimum move, for another rebalance in a later epoch
+    pub fn is_incomplete(&self, minimum_move_lamports: u64) -> bool {
+        self.targets.iter().any(|target| {
+            target.planned_lamports.abs_diff(target.target_lamports) >= minimum_move_lamports
+        })
+    }
+}
+
+/// Plan the stake movements of the current epoch toward the target weights.
+///
+/// Stake is only increased from lamports already in the reserve, since
+/// decreased stake only returns to the reserve after its cooldown. Each
+/// validator moves stake at most once per epoch, except for additional
+/// increases merged into transient stake activating in the current epoch.
+pub(crate) fn plan_rebalance(
+    pool: &PoolBalances,
+    weights: &TargetWeights,
+    options: &RebalanceOptions,
+) -> Result<RebalancePlan, String> {
+    if let TargetWeights::Fixed(weights) = weights {
+        if let Some(vote_account_address) = weights.keys().find(|vote_account_address| {
+            !pool
+                .validators
+                .iter()
+                .any(|validator| &validator.vote_account_address == *vote_account_address)
+        }) {
+            return Err(format!(
+                "Vote account {} is not an active validator of the stake pool",
+                vote_account_address
+            ));
+        }
+    }
+    let total_weight = pool
+        .validators
+        .iter()
+        .map(|validator| weights.weight(&validator.vote_account_address) as u128)
+        .sum::<u128>();
+    if total_weight == 0 {
+        return Err("No active validator of the stake pool has a positive weight".to_string());
+    }
+
+    // Lamports above the reserve minimum and buffer get delegated, and a
+    // reserve below them gets refilled from the validators
+    let staked_lamports = pool
+        .validators
+        .iter()
+        .map(|validator| validator.stake_lamports() as u128)
+        .sum::<u128>();
+    let reserve_target = pool
+        .minimum_reserve_lamports()
+        .saturating_add(options.reserve_buffer_lamports) as u128;
+    let target_staked_lamports =
+        (staked_lamports + pool.reserve_lamports as u128).saturating_sub(reserve_target);
+
+    let minimum_validator_lamports = pool.minimum_validator_lamports();
+    let mut targets = pool
+        .validators
+        .iter()
+        .map(|validator| {
+            let weight = weights.weight(&validator.vote_account_address);
+            let target_lamports = (target_staked_lamports * weight as u128 / total_weight)
+                .try_into()
+                .unwrap_or(u64::MAX)
+                .max(minimum_validator_lamports);
+            ValidatorTarget {
+                vote_account_address: validator.vote_account_address,
+                weight,
+                current_lamports: validator.stake_lamports(),
+                target_lamports,
+                planned_lamports: validator.stake_lamports(),
+            }
+        })
+        .collect::<Vec<_>>();
+    let mut transient_stakes = pool
+        .validators
+        .iter()
+        .map(|validator| validator.transient_stake)
+        .collect::<Vec<_>>();
+
+    let minimum_move_lamports = options.minimum_move_lamports.max(pool.minimum_delegation);
+    let minimum_reserve_lamports = pool.minimum_reserve_lamports();
+    let mut reserve_lamports = pool.reserve_lamports;
+    let mut steps = vec![];
+
+    // Lamports a validator can give up, keeping the minimum stake on its
+    // validator stake account
+    let surplus = |index: usize, targets: &[ValidatorTarget]| -> u64 {
+        let target = &targets[index];
+        let validator = &pool.validators[index];
+        target
+            .planned_lamports
+            .saturating_sub(target.target_lamports)
+            .min(
+                validator
+                    .active_stake_lamports
+                    .saturating_sub(minimum_validator_lamports),
+            )
+    };
+    let deficit = |index: usize, targets: &[ValidatorTarget]| -> u64 {
+        targets[index]
+            .target_lamports
+            .saturating_sub(targets[index].planned_lamports)
+    };
+    let by_largest = |amount: &dyn Fn(usize) -> u64| -> Vec<usize> {
+        let mut indexes = (0..pool.validators.len())
+            .filter(|index| amount(*index) > 0)
+            .collect::<Vec<_>>();
+        indexes.sort_by_key(|index| Reverse(amount(*index)));
+        indexes
+    };
+
+    // Redelegations move stake between validators without a cooldown through
+    // the reserve, the destination transient stake rent is not delegated
+    if options.use_redelegate {
+        let sources = by_largest(&|index| {
+            if transient_stakes[index] == TransientStake::None {
+                surplus(index, &targets)
+            } else {
+                0
+            }
+        });
+        let destinations = by_largest(&|index| {
+            if transient_stakes[index] == TransientStake::None {
+                deficit(index, &targets)
+            } else {
+                0
+            }
+        });
+        for (source, destination) in sources.into_iter().zip(destinations) {
+            if reserve_lamports.saturating_sub(pool.stake_rent) < minimum_reserve_lamports {
+                break;
+            }
+            let lamports = surplus(source, &targets)
+                .min(deficit(destination, &targets).saturating_add(pool.stake_rent));
+            if lamports.saturating_sub(pool.stake_rent) < minimum_move_lamports {
+                continue;
+            }
+            reserve_lamports -= pool.stake_rent;
+            targets[source].planned_lamports -= lamports;
+            targets[destination].planned_lamports += lamports - pool.stake_rent;
+            transient_stakes[source] = TransientStake::Locked;
+            transient_stakes[destination] = TransientStake::Activating;
+            steps.push(RebalanceStep::Redelegate {
+                source_vote_account_address: pool.validators[source].vote_account_address,
+                destination_vote_account_address: pool.validators[destination].vote_account_address,
+                lamports,
+            });
+        }
+    }
+
+    // Decreases only need the transient stake rent from the reserve
+    for index in by_largest(&|index| {
+        if transient_stakes[index] == TransientStake::None {
+            surplus(index, &targets)
+        } else {
+            0
+        }
+    }) {
+        if reserve_lamports.saturating_sub(pool.stake_rent) < minimum_reserve_lamports {
+            break;
+        }
+        let lamports = surplus(index, &targets);
+        if lamports < minimum_move_lamports {
+            continue;
+        }
+        reserve_lamports -= pool.stake_rent;
+        targets[index].planned_lamports -= lamports;
+        transient_stakes[index] = TransientStake::Locked;
+        steps.push(RebalanceStep::Decrease {
+            vote_account_address: pool.validators[index].vote_account_address,
+            lamports,
+        });
+    }
+
+    // Increases fund the stake and its transient stake rent from the reserve,
+    // keeping the reserve buffer, largest deficits first
+    let reserve_floor = reserve_target.try_into().unwrap_or(u64::MAX);
+    for index in by_largest(&|index| {
+        if transient_stakes[index] == TransientStake::Locked {
+            0
+        } else {
+            deficit(index, &targets)
+        }
+    }) {
+        let available = reserve_lamports
+            .saturating_sub(reserve_floor)
+            .saturating_sub(pool.stake_rent);
+        let lamports = deficit(index, &targets).min(available);
+        if lamports < minimum_move_lamports {
+            continue;
+        }
+        reserve_lamports -= lamports + pool.stake_rent;
+        targets[index].planned_lamports += lamports;
+        transient_stakes[index] = TransientStake::Activating;
+        steps.push(RebalanceStep::Increase {
+            vote_account_address: pool.validators[index].vote_account_address,
+            lamports,
+        });
+    }
+
+    Ok(RebalancePlan {
+        steps,
+        targets,
+        reserve_lamports,
+    })
+}
+
+/// Split the steps into batches touching at most `max_transient_stake_accounts`
+/// transient stake accounts each
+pub(crate) fn batch_steps(
+    steps: &[RebalanceStep],
+    max_transient_stake_accounts: usize,
+) -> Vec<&[RebalanceStep]> {
+    let mut batches = vec![];
+    let mut start = 0;
+    let mut transient_stake_accounts = 0;
+    for (index, step) in steps.iter().enumerate() {
+        if index > start
+            && transient_stake_accounts + step.transient_stake_accounts()
+                > max_transient_stake_accounts
+        {
+            batches.push(&steps[start..index]);
+            start = index;
+            transient_stake_accounts = 0;
+        }
+        transient_stake_accounts += step.transient_stake_accounts();
+    }
+    if start < steps.len() {
+        batches.push(&steps[start..]);
+    }
+    batches
+}
+
+/// Plan the stake decreases deactivating `shortfall_lamports` for unstake
+/// tickets, largest validators first.
+///
+/// Funding the tickets can leave the reserve at its minimum, so the transient
+/// stake rent is not taken from the reserve and each transient stake account
+/// must be pre-funded by the caller.
+pub(crate) fn plan_unstake_ticket_decreases(
+    pool: &PoolBalances,
+    shortfall_lamports: u64,
+) -> Vec<RebalanceStep> {
+    let minimum_validator_lamports = pool.minimum_validator_lamports();
+    let available = |validator: &ValidatorBalance| -> u64 {
+        if validator.transient_stake == TransientStake::None {
+            validator
+                .active_stake_lamports
+                .saturating_sub(minimum_validator_lamports)
+        } else {
+            0
+        }
+    };
+    let mut validators = pool
+        .validators
+        .iter()
+        .filter(|validator| available(validator) >= pool.minimum_delegation)
+        .collect::<Vec<_>>();
+    validators.sort_by_key(|validator| Reverse(available(validator)));
+
+    let mut remaining_lamports = shortfall_lamports;
+    let mut steps = vec![];
+    for validator in validators {
+        if remaining_lamports == 0 {
+            break;
+        }
+        let lamports = remaining_lamports
+            .max(pool.minimum_delegation)
+            .min(available(validator));
+        remaining_lamports = remaining_lamports.saturating_sub(lamports);
+        steps.push(RebalanceStep::Decrease {
+            vote_account_address: validator.vote_account_address,
+            lamports,
+        });
+    }
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STAKE_RENT: u64 = 2_282_880;
+    const MINIMUM_DELEGATION: u64 = 1_000_000_000;
+    const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
+
+    fn validator(active_sol: u64) -> ValidatorBalance {
+        ValidatorBalance {
+            vote_account_address: Pubkey::new_unique(),
+            active_stake_lamports: active_sol * LAMPORTS_PER_SOL + STAKE_RENT,
+            transient_stake_lamports: 0,
+            transient_stake: TransientStake::None,
+        }
+    }
+
+    fn pool(reserve_sol: u64, validators: Vec<ValidatorBalance>) -> PoolBalances {
+        PoolBalances {
+            reserve_lamports: reserve_sol * LAMPORTS_PER_SOL + STAKE_RENT,
+            stake_rent: STAKE_RENT,
+            minimum_delegation: MINIMUM_DELEGATION,
+            validators,
+        }
+    }
+
+    #[test]
+    fn equal_weights_deploy_reserve() {
+        let pool = pool(100, vec![validator(10), validator(30)]);
+        let plan =
+            plan_rebalance(&pool, &TargetWeights::Equal, &RebalanceOptions::default()).unwrap();
+
+        // 140 SOL split evenly, largest deficit first, each increase paying
+        // its transient stake rent from the reserve
+        let target_lamports = plan.targets[0].target_lamports;
+        assert_eq!(target_lamports, 70 * LAMPORTS_PER_SOL + STAKE_RENT);
+        assert_eq!(target_lamports, plan.targets[1].target_lamports);
+        assert_eq!(
+            plan.steps,
+            vec![
+                RebalanceStep::Increase {
+                    vote_account_address: pool.validators[0].vote_account_address,
+                    lamports: 60 * LAMPORTS_PER_SOL,
+                },
+                RebalanceStep::Increase {
+                    vote_account_address: pool.validators[1].vote_account_address,
+                    lamports: 40 * LAMPORTS_PER_SOL - 2 * STAKE_RENT,
+                },
+            ]
+        );
+        assert_eq!(plan.reserve_lamports, pool.minimum_reserve_lamports());
+        assert!(!plan.is_incomplete(MINIMUM_DELEGATION));
+    }
+
+    #[test]
+    fn increases_limited_by_reserve() {
+        let pool = pool(10, vec![validator(50), validator(10), validator(20)]);
+        let options = RebalanceOptions {
+            reserve_buffer_lamports: 2 * LAMPORTS_PER_SOL,
+            ..RebalanceOptions::default()
+        };
+        let plan = plan_rebalance(&pool, &TargetWeights::Equal, &options).unwrap();
+
+        // decreased stake cannot fund increases in the same epoch, and the
+        // reserve buffer stays undelegated
+        let increased = plan
+            .steps
+            .iter()
+            .filter_map(|step| match step {
+                RebalanceStep::Increase { lamports, .. } => Some(*lamports + STAKE_RENT),
+                _ => None,
+            })
+            .sum::<u64>();
+        assert!(increased <= 8 * LAMPORTS_PER_SOL);
+        assert!(plan.reserve_lamports >= pool.minimum_reserve_lamports() + 2 * LAMPORTS_PER_SOL);
+        assert!(matches!(
+            plan.steps[1],
+            RebalanceStep::Increase { vote_account_address, .. }
+                if vote_account_address == pool.validators[1].vote_account_address
+        ));
+        assert!(plan.is_incomplete(MINIMUM_DELEGATION));
+    }
+
+    #[test]
+    fn transient_stake_constraints() {
+        let mut pool = pool(100, vec![validator(10), validator(10), validator(120)]);
+        pool.validators[0].transient_stake = TransientStake::Locked;
+        pool.validators[0].transient_stake_lamports = LAMPORTS_PER_SOL;
+        pool.validators[1].transient_stake = TransientStake::Activating;
+        pool.validators[1].transient_stake_lamports = LAMPORTS_PER_SOL;
+        pool.validators[2].transient_stake = TransientStake::Activating;
+        let plan =
+            plan_rebalance(&pool, &TargetWeights::Equal, &RebalanceOptions::default()).unwrap();
+
+        // locked transient stake blocks movements, activating transient stake
+        // only takes additional increases
+        assert_eq!(plan.steps.len(), 1);
+        assert!(matches!(
+            plan.steps[0],
+            RebalanceStep::Increase { vote_account_address, .. }
+                if vote_account_address == pool.validators[1].vote_account_address
+        ));
+    }
+
+    #[test]
+    fn fixed_weights_keep_minimum_stake() {
+        let pool = pool(1, vec![validator(10), validator(10)]);
+        let removed = pool.validators[1].vote_account_address;
+        let weights = TargetWeights::Fixed(HashMap::from([(
+            pool.validators[0].vote_account_address,
+            1,
+        )]));
+        let plan = plan_rebalance(&pool, &weights, &RebalanceOptions::default()).unwrap();
+        assert_eq!(
+            plan.steps,
+            vec![RebalanceStep::Decrease {
+                vote_account_address: removed,
+                lamports: 9 * LAMPORTS_PER_SOL,
+            }]
+        );
+        assert_eq!(
+            plan.targets[1].target_lamports,
+            pool.minimum_validator_lamports()
+        );
+
+        let unknown = TargetWeights::Fixed(HashMap::from([(Pubkey::new_unique(), 1)]));
+        assert!(plan_rebalance(&pool, &unknown, &RebalanceOptions::default()).is_err());
+        let zero = TargetWeights::Fixed(HashMap::from([(removed, 0)]));
+        assert!(plan_rebalance(&pool, &zero, &RebalanceOptions::default()).is_err());
+    }
+
+    #[test]
+    fn redelegate_between_validators() {
+        let pool = pool(1, vec![validator(30), validator(10)]);
+        let options = RebalanceOptions {
+            reserve_buffer_lamports: LAMPORTS_PER_SOL,
+            use_redelegate: true,
+            ..RebalanceOptions::default()
+        };
+        let plan = plan_rebalance(&pool, &TargetWeights::Equal, &options).unwrap();
+        assert_eq!(
+            plan.steps,
+            vec![RebalanceStep::Redelegate {
+                source_vote_account_address: pool.validators[0].vote_account_address,
+                destination_vote_account_address: pool.validators[1].vote_account_address,
+                lamports: 10 * LAMPORTS_PER_SOL,
+            }]
+        );
+        // the destination transient stake rent is not delegated
+        assert_eq!(
+            plan.targets[1].planned_lamports,
+            plan.targets[1].target_lamports - STAKE_RENT
+        );
+    }
+
+    #[test]
+    fn batch_transient_stake_accounts() {
+        let increase = RebalanceStep::Increase {
+            vote_account_address: Pubkey::new_unique(),
+            lamports: 1,
+        };
+        let redelegate = RebalanceStep::Redelegate {
+            source_vote_account_address: Pubkey::new_unique(),
+            destination_vote_account_address: Pubkey::new_unique(),
+            lamports: 1,
+        };
+        let steps = vec![
+            increase.clone(),
+            redelegate.clone(),
+            increase.clone(),
+            redelegate,
+            increase,
+        ];
+        let batches = batch_steps(&steps, 3);
+        assert_eq!(batches.len(), 3);
+        assert_eq!(batches[0].len(), 2);
+        assert_eq!(batches[1].len(), 2);
+        assert_eq!(batches[2].len(), 1);
+    }
+
+    #[test]
+    fn unstake_ticket_decreases() {
+        let mut pool = pool(0, vec![validator(5), validator(20), validator(40)]);
+        pool.validators[2].transient_stake = TransientStake::Locked;
+
+        // validators with transient stake are skipped, and every validator
+        // keeps the minimum stake
+        let steps = plan_unstake_ticket_decreases(&pool, 22 * LAMPORTS_PER_SOL);
+        assert_eq!(
+            steps,
+            vec![
+                RebalanceStep::Decrease {
+                    vote_account_address: pool.validators[1].vote_account_address,
+                    lamports: 19 * LAMPORTS_PER_SOL,
+                },
+                RebalanceStep::Decrease {
+                    vote_account_address: pool.validators[0].vote_account_address,
+                    lamports: 3 * LAMPORTS_PER_SOL,
+                },
+            ]
+        );
+
+        // small shortfalls still decrease the minimum delegation
+        let steps = plan_unstake_ticket_decreases(&pool, 1);
+        assert_eq!(
+            steps,
+            vec![RebalanceStep::Decrease {
+                vote_account_address: pool.validators[1].vote_account_address,
+                lamports: MINIMUM_DELEGATION,
+            }]
+        );
+        assert!(plan_unstake_ticket_decreases(&pool, 0).is_empty());
+    }
+
+    #[test]
+    fn parse_weights() {
+        let vote_account = Pubkey::new_unique();
+        let arg = format!("{}=3", vote_account);
+        assert_eq!(
+            TargetWeights::from_args([arg.as_str()].into_iter()).unwrap(),
+            TargetWeights::Fixed(HashMap::from([(vote_account, 3)]))
+        );
+        assert!(TargetWeights::from_args(["invalid"].into_iter()).is_err());
+        assert!(TargetWeights::from_args([arg.as_str(), arg.as_str()].into_iter()).is_err());
+    }
+}
@@ -0,0 +1,78 @@
+//! Estimating validator yields from the rewards recorded in a stake pool's
+//! validator reward history
+
+use {solana_sdk::clock::DEFAULT_MS_PER_SLOT, spl_stake_pool::state::ValidatorRewardInfo};
+
+const MS_PER_YEAR: f64 = 365.25 * 24.0 * 60.0 * 60.0 * 1000.0;
+
+/// Approximate number of epochs in a year, assuming the target slot time
+pub(crate) fn epochs_per_year(slots_per_epoch: u64) -> f64 {
+    MS_PER_YEAR / (slots_per_epoch as f64 * DEFAULT_MS_PER_SLOT as f64)
+}
+
+/// Annualize the lamports earned on `stake_lamports` over `epochs`, compounding
+/// every epoch. Returns `None` if nothing was staked or no epoch elapsed.
+pub(crate) fn estimate_apy(
+    stake_lamports: u64,
+    earned_lamports: u64,
+    epochs: u64,
+    epochs_per_year: f64,
+) -> Option<f64> {
+    if stake_lamports == 0 || epochs == 0 {
+        return None;
+    }
+    let epoch_rate = earned_lamports as f64 / stake_lamports as f64 / epochs as f64;
+    Some((1.0 + epoch_rate).powf(epochs_per_year) - 1.0)
+}
+
+/// Estimate the APY of a validator from its last recorded rewards, including
+/// MEV
+pub(crate) fn validator_apy(
+    reward_info: &ValidatorRewardInfo,
+    epochs_per_year: f64,
+) -> Option<f64> {
+    let earned_lamports = u64::from(reward_info.last_rewards_lamports)
+        .saturating_add(reward_info.last_mev_lamports.into());
+    estimate_apy(
+        reward_info.last_stake_lamports.into(),
+        earned_lamports,
+        reward_info.last_epochs.into(),
+        epochs_per_year,
+    )
+}
+
+/// Weight each validator APY by its stake
+pub(crate) fn stake_weighted_apy(apys: impl Iterator<Item = (u64, f64)>) -> Option<f64> {
+    let (total_stake, weighted_sum) = apys.fold((0u64, 0f64), |(stake, sum), (lamports, apy)| {
+        (stake.saturating_add(lamports), sum + lamports as f64 * apy)
+    });
+    (total_stake > 0).then(|| weighted_sum / total_stake as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apy_compounds_per_epoch() {
+        // 2 epochs per year at 10% per epoch
+        let apy = estimate_apy(1_000, 100, 1, 2.0).unwrap();
+        assert!((apy - 0.21).abs() < 1e-9);
+        // the same rewards over two epochs halve the epoch rate
+        let apy = estimate_apy(1_000, 200, 2, 2.0).unwrap();
+        assert!((apy - 0.21).abs() < 1e-9);
+        assert_eq!(estimate_apy(0, 100, 1, 2.0), None);
+        assert_eq!(estimate_apy(1_000, 100, 0, 2.0), None);
+
+        // mainnet epochs last a little over two days
+        let epochs_per_year = epochs_per_year(432_000);
+        assert!(epochs_per_year > 182.0 && epochs_per_year < 183.0);
+    }
+
+    #[test]
+    fn apy_weighted_by_stake() {
+        let apy = stake_weighted_apy([(3, 0.1), (1, 0.5)].into_iter()).unwrap();
+        assert!((apy - 0.2).abs() < 1e-9);
+        assert_eq!(stake_weighted_apy(std::iter::empty()), None);
+    }
+}
@@ -0,0 +1,671 @@
+//! Verifying the state recorded by a stake pool against the accounts it
+//! manages, to diagnose pools left in an unexpected state
+
+use {
+    solana_program::{
+        pubkey::Pubkey,
+        stake::state::{Meta, StakeStateV2},
+    },
+    spl_stake_pool::{
+        state::{StakePool, StakeStatus, ValidatorList, ValidatorStakeInfo},
+        MINIMUM_RESERVE_LAMPORTS,
+    },
+    std::{collections::HashSet, fmt},
+};
+
+/// A stake account of the pool as found on chain
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct StakeAccount {
+    /// Lamports on the account, including rent
+    pub lamports: u64,
+    /// Deserialized stake state
+    pub state: StakeStateV2,
+}
+
+/// On-chain stake accounts derived for a validator list entry
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ValidatorAccounts {
+    /// Validator stake account address
+    pub validator_stake_address: Pubkey,
+    /// Validator stake account, `None` if missing or not a stake account
+    pub validator_stake: Option<StakeAccount>,
+    /// Transient stake account address
+    pub transient_stake_address: Pubkey,
+    /// Transient stake account, `None` if missing or not a stake account
+    pub transient_stake: Option<StakeAccount>,
+}
+
+/// Everything the verification needs to know about a stake pool
+pub(crate) struct PoolAccounts<'a> {
+    /// Stake pool address
+    pub stake_pool_address: Pubkey,
+    /// Stake pool state
+    pub stake_pool: &'a StakePool,
+    /// Validator list of the stake pool
+    pub validator_list: &'a ValidatorList,
+    /// Stake pool withdraw authority, which must control every stake account
+    pub withdraw_authority: Pubkey,
+    /// Reserve stake account, `None` if missing or not a stake account
+    pub reserve_stake: Option<StakeAccount>,
+    /// Stake accounts of the validators, in validator list order
+    pub validators: Vec<ValidatorAccounts>,
+    /// Supply of the pool mint
+    pub pool_mint_supply: u64,
+    /// Lamports owed to unstake tickets that the reserve has not funded yet
+    pub unfunded_ticket_lamports: u64,
+    /// Current epoch
+    pub epoch: u64,
+}
+
+/// Instruction expected to fix a discrepancy
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum SuggestedFix {
+    /// `UpdateValidatorListBalance` starting from the entry of the validator
+    UpdateValidatorListBalance { start_index: u32 },
+    /// `UpdateStakePoolBalance`
+    UpdateStakePoolBalance,
+    /// `CleanupRemovedValidatorEntries`
+    CleanupRemovedValidatorEntries,
+    /// `RemoveValidatorFromPool`
+    RemoveValidatorFromPool { vote_account_address: Pubkey },
+    /// No instruction fixes the discrepancy, the accounts need to be looked
+    /// at manually
+    Investigate,
+}
+
+impl fmt::Display for SuggestedFix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UpdateValidatorListBalance { start_index } => write!(
+                f,
+                "UpdateValidatorListBalance from index {} (`update --force`)",
+                start_index
+            ),
+            Self::UpdateStakePoolBalance => write!(f, "UpdateStakePoolBalance (`update`)"),
+            Self::CleanupRemovedValidatorEntries => {
+                write!(f, "CleanupRemovedValidatorEntries (`update`)")
+            }
+            Self::RemoveValidatorFromPool {
+                vote_account_address,
+            } => write!(
+                f,
+                "RemoveValidatorFromPool (`remove-validator {}`)",
+                vote_account_address
+            ),
+            Self::Investigate => write!(f, "none, investigate manually"),
+        }
+    }
+}
+
+/// A difference between the state recorded by the stake pool and the state
+/// expected from its accounts
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Discrepancy {
+    /// Account the discrepancy was found on
+    pub address: Pubkey,
+    /// What differs
+    pub description: String,
+    /// Instruction expected to fix it
+    pub suggested_fix: SuggestedFix,
+}
+
+impl Discrepancy {
+    fn new(address: Pubkey, description: String, suggested_fix: SuggestedFix) -> Self {
+        Self {
+            address,
+            description,
+            suggested_fix,
+        }
+    }
+}
+
+/// Balances and status the validator list entry should hold once updated in
+/// the current epoch, recomputed from its stake accounts
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ExpectedValidator {
+    pub active_stake_lamports: u64,
+    pub transient_stake_lamports: u64,
+    pub status: StakeStatus,
+}
+
+fn is_usable_by_pool(meta: &Meta, pool: &PoolAccounts) -> bool {
+    meta.authorized.staker == pool.withdraw_authority
+        && meta.authorized.withdrawer == pool.withdraw_authority
+        && meta.lockup == pool.stake_pool.lockup
+}
+
+/// Recompute a validator list entry the way `UpdateValidatorListBalance`
+/// leaves it after merging, assuming no stake account changed since
+pub(crate) fn expected_validator(
+    validator: &ValidatorStakeInfo,
+    accounts: &ValidatorAccounts,
+    pool: &PoolAccounts,
+) -> ExpectedValidator {
+    let status = StakeStatus::try_from(validator.status).unwrap_or_default();
+    let removing = status != StakeStatus::Active;
+
+    let transient_stake_lamports = match &accounts.transient_stake {
+        Some(StakeAccount {
+            lamports,
+            state: StakeStateV2::Initialized(meta) | StakeStateV2::Stake(meta, _, _),
+        }) if is_usable_by_pool(meta, pool) => *lamports,
+        _ => 0,
+    };
+    let validator_stake_lamports = match &accounts.validator_stake {
+        Some(StakeAccount {
+            lamports,
+            state: StakeStateV2::Stake(_, _, _),
+        }) => *lamports,
+        _ => 0,
+    };
+    let active_stake_lamports = match status {
+        StakeStatus::Active | StakeStatus::DeactivatingValidator | StakeStatus::DeactivatingAll => {
+            validator_stake_lamports
+        }
+        StakeStatus::DeactivatingTransient | StakeStatus::ReadyForRemoval => 0,
+    };
+
+    let has_validator_stake = validator_stake_lamports > 0;
+    let has_transient_stake = transient_stake_lamports > 0;
+    let status = match (removing, has_validator_stake, has_transient_stake) {
+        (false, _, _) => StakeStatus::Active,
+        (true, true, true) => StakeStatus::DeactivatingAll,
+        (true, true, false) => StakeStatus::DeactivatingValidator,
+        (true, false, true) => StakeStatus::DeactivatingTransient,
+        (true, false, false) => StakeStatus::ReadyForRemoval,
+    };
+
+    ExpectedValidator {
+        active_stake_lamports,
+        transient_stake_lamports,
+        status,
+    }
+}
+
+fn verify_validator(
+    index: usize,
+    validator: &ValidatorStakeInfo,
+    accounts: &ValidatorAccounts,
+    pool: &PoolAccounts,
+    discrepancies: &mut Vec<Discrepancy>,
+) {
+    let vote_account_address = validator.vote_account_address;
+    let start_index = index as u32;
+    let status = StakeStatus::try_from(validator.status).unwrap_or_default();
+
+    for (address, account, name) in [
+        (
+            accounts.validator_stake_address,
+            &accounts.validator_stake,
+            "Validator",
+        ),
+        (
+            accounts.transient_stake_address,
+            &accounts.transient_stake,
+            "Transient",
+        ),
+    ] {
+        let (meta, delegation) = match account.as_ref().map(|account| &account.state) {
+            Some(StakeStateV2::Stake(meta, stake, _)) => (meta, Some(&stake.delegation)),
+            Some(StakeStateV2::Initialized(meta)) => (meta, None),
+            _ => continue,
+        };
+        if !is_usable_by_pool(meta, pool) {
+            discrepancies.push(Discrepancy::new(
+                address,
+                format!(
+                    "{} stake account of {} is not controlled by the pool withdraw authority or has a different lockup",
+                    name, vote_account_address
+                ),
+                SuggestedFix::Investigate,
+            ));
+        }
+        if let Some(delegation) = delegation {
+            if delegation.voter_pubkey != vote_account_address {
+                discrepancies.push(Discrepancy::new(
+                    address,
+                    format!(
+                        "{} stake account is delegated to {} instead of {}",
+                        name, delegation.voter_pubkey, vote_account_address
+                    ),
+                    SuggestedFix::Investigate,
+                ));
+            }
+        }
+    }
+
+    if u64::from(validator.last_update_epoch) < pool.epoch {
+        discrepancies.push(Discrepancy::new(
+            vote_account_address,
+            format!(
+                "Validator list entry last updated in epoch {}, current epoch is {}",
+                u64::from(validator.last_update_epoch),
+                pool.epoch
+            ),
+            SuggestedFix::UpdateValidatorListBalance { start_index },
+        ));
+        // balances are only meaningful once updated
+        return;
+    }
+
+    match (status, &accounts.validator_stake) {
+        (StakeStatus::Active, None) => discrepancies.push(Discrepancy::new(
+            accounts.validator_stake_address,
+            format!(
+                "Validator stake account of active validator {} is missing",
+                vote_account_address
+            ),
+            SuggestedFix::Investigate,
+        )),
+        (
+            StakeStatus::Active,
+            Some(StakeAccount {
+                state: StakeStateV2::Stake(_, stake, _),
+                ..
+            }),
+        ) if stake.delegation.deactivation_epoch != u64::MAX => {
+            discrepancies.push(Discrepancy::new(
+                accounts.validator_stake_address,
+                format!(
+                    "Validator stake account of active validator {} was deactivated in epoch {}",
+                    vote_account_address, stake.delegation.deactivation_epoch
+                ),
+                SuggestedFix::RemoveValidatorFromPool {
+                    vote_account_address,
+                },
+            ))
+        }
+        (
+            _,
+            Some(StakeAccount {
+                state: StakeStateV2::Initialized(_),
+                ..
+            }),
+        ) => discrepancies.push(Discrepancy::new(
+            accounts.validator_stake_address,
+            "Validator stake account is inactive and not merged into the reserve".to_string(),
+            SuggestedFix::UpdateValidatorListBalance { start_index },
+        )),
+        _ => {}
+    }
+
+    let expected = expected_validator(validator, accounts, pool);
+    let update = SuggestedFix::UpdateValidatorListBalance { start_index };
+    if u64::from(validator.active_stake_lamports) != expected.active_stake_lamports {
+        discrepancies.push(Discrepancy::new(
+            vote_account_address,
+            format!(
+                "Recorded active stake of {} lamports, validator stake account holds {}",
+                u64::from(validator.active_stake_lamports),
+                expected.active_stake_lamports
+            ),
+            update.clone(),
+        ));
+    }
+    if u64::from(validator.transient_stake_lamports) != expected.transient_stake_lamports {
+        discrepancies.push(Discrepancy::new(
+            vote_account_address,
+            format!(
+                "Recorded transient stake of {} lamports, transient stake account holds {}",
+                u64::from(validator.transient_stake_lamports),
+                expected.transient_stake_lamports
+            ),
+            update.clone(),
+        ));
+    }
+    if status != expected.status {
+        discrepancies.push(Discrepancy::new(
+            vote_account_address,
+            format!(
+                "Recorded status {:?}, expected {:?} from its stake accounts",
+                status, expected.status
+            ),
+            update,
+        ));
+    }
+    if status == StakeStatus::ReadyForRemoval && expected.status == StakeStatus::ReadyForRemoval {
+        discrepancies.push(Discrepancy::new(
+            vote_account_address,
+            "Validator is ready for removal but still in the validator list".to_string(),
+            SuggestedFix::CleanupRemovedValidatorEntries,
+        ));
+    }
+}
+
+/// Check the stake pool, its validator list and their stake accounts, and
+/// report every discrepancy found
+pub(crate) fn verify_pool(pool: &PoolAccounts) -> Vec<Discrepancy> {
+    let mut discrepancies = vec![];
+    let stake_pool = pool.stake_pool;
+    let validator_list = pool.validator_list;
+
+    if !stake_pool.is_valid() {
+        discrepancies.push(Discrepancy::new(
+            pool.stake_pool_address,
+            "Stake pool account is not initialized".to_string(),
+            SuggestedFix::Investigate,
+        ));
+    }
+    if !validator_list.header.is_valid() {
+        discrepancies.push(Discrepancy::new(
+            stake_pool.validator_list,
+            "Validator list account is not initialized".to_string(),
+            SuggestedFix::Investigate,
+        ));
+    }
+    if validator_list.validators.len() > validator_list.header.max_validators as usize {
+        discrepancies.push(Discrepancy::new(
+            stake_pool.validator_list,
+            format!(
+                "Validator list holds {} validators, more than its maximum of {}",
+                validator_list.validators.len(),
+                validator_list.header.max_validators
+            ),
+            SuggestedFix::Investigate,
+        ));
+    }
+    let mut vote_accounts = HashSet::new();
+    for validator in validator_list.validators.iter() {
+        if !vote_accounts.insert(validator.vote_account_address) {
+            discrepancies.push(Discrepancy::new(
+                validator.vote_account_address,
+                "Validator appears more than once in the validator list".to_string(),
+                SuggestedFix::Investigate,
+            ));
+        }
+    }
+
+    let available_reserve_lamports = match &pool.reserve_stake {
+        Some(StakeAccount {
+            lamports,
+            state: StakeStateV2::Initialized(meta),
+        }) => {
+            if !is_usable_by_pool(meta, pool) {
+                discrepancies.push(Discrepancy::new(
+                    stake_pool.reserve_stake,
+                    "Reserve stake account is not controlled by the pool withdraw authority or has a different lockup".to_string(),
+                    SuggestedFix::Investigate,
+                ));
+            }
+            let minimum_reserve_lamports = meta
+                .rent_exempt_reserve
+                .saturating_add(MINIMUM_RESERVE_LAMPORTS);
+            if *lamports < minimum_reserve_lamports {
+                discrepancies.push(Discrepancy::new(
+                    stake_pool.reserve_stake,
+                    format!(
+                        "Reserve stake account holds {} lamports, less than its minimum of {}",
+                        lamports, minimum_reserve_lamports
+                    ),
+                    SuggestedFix::Investigate,
+                ));
+            }
+            Some(lamports.saturating_sub(minimum_reserve_lamports))
+        }
+        _ => {
+            discrepancies.push(Discrepancy::new(
+                stake_pool.reserve_stake,
+                "Reserve stake account is missing or not an initialized stake account".to_string(),
+                SuggestedFix::Investigate,
+            ));
+            None
+        }
+    };
+
+    for (index, (validator, accounts)) in validator_list
+        .validators
+        .iter()
+        .zip(pool.validators.iter())
+        .enumerate()
+    {
+        verify_validator(index, validator, accounts, pool, &mut discrepancies);
+    }
+
+    if stake_pool.pool_token_supply != pool.pool_mint_supply {
+        discrepancies.push(Discrepancy::new(
+            stake_pool.pool_mint,
+            format!(
+                "Recorded pool token supply of {}, pool mint supply is {}",
+                stake_pool.pool_token_supply, pool.pool_mint_supply
+            ),
+            SuggestedFix::UpdateStakePoolBalance,
+        ));
+    }
+
+    let validators_updated = validator_list
+        .validators
+        .iter()
+        .all(|validator| u64::from(validator.last_update_epoch) >= pool.epoch);
+    if stake_pool.last_update_epoch < pool.epoch {
+        discrepancies.push(Discrepancy::new(
+            pool.stake_pool_address,
+            format!(
+                "Stake pool last updated in epoch {}, current epoch is {}",
+                stake_pool.last_update_epoch, pool.epoch
+            ),
+            SuggestedFix::UpdateStakePoolBalance,
+        ));
+    } else if let (Some(available_reserve_lamports), true) =
+        (available_reserve_lamports, validators_updated)
+    {
+        let expected_total_lamports = validator_list
+            .validators
+            .iter()
+            .map(|validator| validator.stake_lamports().unwrap_or(u64::MAX))
+            .fold(available_reserve_lamports, u64::saturating_add)
+            .saturating_sub(pool.unfunded_ticket_lamports);
+        if stake_pool.total_lamports != expected_total_lamports {
+            discrepancies.push(Discrepancy::new(
+                pool.stake_pool_address,
+                format!(
+                    "Recorded total of {} lamports, reserve and validator list add up to {}",
+                    stake_pool.total_lamports, expected_total_lamports
+                ),
+                SuggestedFix::UpdateStakePoolBalance,
+            ));
+        }
+    }
+
+    discrepancies
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_program::stake::{
+            stake_flags::StakeFlags,
+            state::{Authorized, Delegation, Stake},
+        },
+        spl_stake_pool::state::{AccountType, ValidatorListHeader},
+    };
+
+    const STAKE_RENT: u64 = 2_282_880;
+    const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
+    const EPOCH: u64 = 10;
+
+    fn meta(authority: &Pubkey) -> Meta {
+        Meta {
+            rent_exempt_reserve: STAKE_RENT,
+            authorized: Authorized {
+                staker: *authority,
+                withdrawer: *authority,
+            },
+            ..Meta::default()
+        }
+    }
+
+    fn stake_account(authority: &Pubkey, voter: &Pubkey, lamports: u64) -> StakeAccount {
+        StakeAccount {
+            lamports,
+            state: StakeStateV2::Stake(
+                meta(authority),
+                Stake {
+                    delegation: Delegation {
+                        voter_pubkey: *voter,
+                        stake: lamports - STAKE_RENT,
+                        ..Delegation::default()
+                    },
+                    credits_observed: 0,
+                },
+                StakeFlags::empty(),
+            ),
+        }
+    }
+
+    struct Fixture {
+        withdraw_authority: Pubkey,
+        stake_pool: StakePool,
+        validator_list: ValidatorList,
+        validators: Vec<ValidatorAccounts>,
+    }
+
+    impl Fixture {
+        fn new(validator_sol: &[u64]) -> Self {
+            let withdraw_authority = Pubkey::new_unique();
+            let mut validator_list = ValidatorList {
+                header: ValidatorListHeader {
+                    account_type: AccountType::ValidatorList,
+                    max_validators: 10,
+                },
+                validators: vec![],
+            };
+            let mut validators = vec![];
+            for sol in validator_sol {
+                let vote_account_address = Pubkey::new_unique();
+                let lamports = sol * LAMPORTS_PER_SOL + STAKE_RENT;
+                validator_list.validators.push(ValidatorStakeInfo {
+                    active_stake_lamports: lamports.into(),
+                    last_update_epoch: EPOCH.into(),
+                    vote_account_address,
+                    ..ValidatorStakeInfo::default()
+                });
+                validators.push(ValidatorAccounts {
+                    validator_stake_address: Pubkey::new_unique(),
+                    validator_stake: Some(stake_account(
+                        &withdraw_authority,
+                        &vote_account_address,
+                        lamports,
+                    )),
+                    transient_stake_address: Pubkey::new_unique(),
+                    transient_stake: None,
+                });
+            }
+            let total_lamports = validator_list
+                .validators
+                .iter()
+                .map(|validator| validator.stake_lamports().unwrap())
+                .sum::<u64>()
+                + LAMPORTS_PER_SOL;
+            let stake_pool = StakePool {
+                account_type: AccountType::StakePool,
+                total_lamports,
+                pool_token_supply: total_lamports,
+                last_update_epoch: EPOCH,
+                ..StakePool::default()
+            };
+            Self {
+                withdraw_authority,
+                stake_pool,
+                validator_list,
+                validators,
+            }
+        }
+
+        fn pool_accounts(&self) -> PoolAccounts {
+            PoolAccounts {
+                stake_pool_address: Pubkey::new_unique(),
+                stake_pool: &self.stake_pool,
+                validator_list: &self.validator_list,
+                withdraw_authority: self.withdraw_authority,
+                reserve_stake: Some(StakeAccount {
+                    lamports: LAMPORTS_PER_SOL + STAKE_RENT + MINIMUM_RESERVE_LAMPORTS,
+                    state: StakeStateV2::Initialized(meta(&self.withdraw_authority)),
+                }),
+                validators: self.validators.clone(),
+                pool_mint_supply: self.stake_pool.pool_token_supply,
+                unfunded_ticket_lamports: 0,
+                epoch: EPOCH,
+            }
+        }
+    }
+
+    #[test]
+    fn consistent_pool() {
+        let fixture = Fixture::new(&[10, 20]);
+        assert_eq!(verify_pool(&fixture.pool_accounts()), vec![]);
+    }
+
+    #[test]
+    fn balance_and_supply_mismatches() {
+        let mut fixture = Fixture::new(&[10, 20]);
+        fixture.validators[1].validator_stake = Some(stake_account(
+            &fixture.withdraw_authority,
+            &fixture.validator_list.validators[1].vote_account_address,
+            21 * LAMPORTS_PER_SOL + STAKE_RENT,
+        ));
+        // pool tokens burnt outside of the pool
+        let mut pool_accounts = fixture.pool_accounts();
+        pool_accounts.pool_mint_supply -= 1;
+        let discrepancies = verify_pool(&pool_accounts);
+        let fixes: Vec<_> = discrepancies
+            .iter()
+            .map(|discrepancy| (discrepancy.address, discrepancy.suggested_fix.clone()))
+            .collect();
+        assert_eq!(
+            fixes,
+            vec![
+                (
+                    fixture.validator_list.validators[1].vote_account_address,
+                    SuggestedFix::UpdateValidatorListBalance { start_index: 1 }
+                ),
+                (
+                    fixture.stake_pool.pool_mint,
+                    SuggestedFix::UpdateStakePoolBalance
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn hijacked_and_stale_validators() {
+        let mut fixture = Fixture::new(&[10, 20]);
+        let vote_account_address = fixture.validator_list.validators[0].vote_account_address;
+        let hijacker = Pubkey::new_unique();
+        fixture.validators[0].transient_stake = Some(stake_account(
+            &hijacker,
+            &vote_account_address,
+            LAMPORTS_PER_SOL + STAKE_RENT,
+        ));
+        fixture.validator_list.validators[1].last_update_epoch = (EPOCH - 1).into();
+        let discrepancies = verify_pool(&fixture.pool_accounts());
+        assert_eq!(
+            discrepancies
+                .iter()
+                .map(|discrepancy| discrepancy.suggested_fix.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                SuggestedFix::Investigate,
+                SuggestedFix::UpdateValidatorListBalance { start_index: 1 },
+            ]
+        );
+        assert_eq!(
+            discrepancies[0].address,
+            fixture.validators[0].transient_stake_address
+        );
+
+        // a removed validator whose stake was merged is ready for cleanup
+        let mut fixture = Fixture::new(&[10]);
+        fixture.validator_list.validators[0].status = StakeStatus::ReadyForRemoval.into();
+        fixture.validator_list.validators[0].active_stake_lamports = 0.into();
+        fixture.validators[0].validator_stake = None;
+        fixture.stake_pool.total_lamports = LAMPORTS_PER_SOL;
+        assert_eq!(
+            verify_pool(&fixture.pool_accounts())
+                .into_iter()
+                .map(|discrepancy| discrepancy.suggested_fix)
+                .collect::<Vec<_>>(),
+            vec![SuggestedFix::CleanupRemovedValidatorEntries]
+        );
+    }
+}
@@ -19,6 +19,12 @@ export const EPHEMERAL_STAKE_SEED_PREFIX = Buffer.from('ephemeral');
 // Seed used to derive transient stake accounts.
 export const TRANSIENT_STAKE_SEED_PREFIX = Buffer.from('transient');
 
+// Seed used to derive the unstake ticket ledger.
+export const UNSTAKE_TICKET_LEDGER_SEED_PREFIX = Buffer.from('unstake_ticket_ledger');
+
+// Seed used to derive the fee split.
+export const FEE_SPLIT_SEED_PREFIX = Buffer.from('fee_split');
+
 // Minimum amount of staked SOL required in a validator stake account to allow
 // for merges without a mismatch on credits observed
 export const MINIMUM_ACTIVE_STAKE = LAMPORTS_PER_SOL;
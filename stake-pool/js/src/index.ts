@@ -29,9 +29,12 @@ import {
   solToLamports,
   findEphemeralStakeProgramAddress,
   findMetadataAddress,
+  findUnstakeTicketLedgerProgramAddress,
+  findFeeSplitProgramAddress,
 } from './utils';
 import { StakePoolInstruction } from './instructions';
 import {
+  FeeSplitLayout,
   StakeAccount,
   StakePool,
   StakePoolLayout,
@@ -43,10 +46,22 @@ import { MAX_VALIDATORS_TO_UPDATE, MINIMUM_ACTIVE_STAKE, STAKE_POOL_PROGRAM_ID }
 import { create } from 'superstruct';
 import BN from 'bn.js';
 
-export type { StakePool, AccountType, ValidatorList, ValidatorStakeInfo } from './layouts';
+export type {
+  StakePool,
+  AccountType,
+  ValidatorList,
+  ValidatorStakeInfo,
+  FeeSplit,
+  FeeRecipient,
+} from './layouts';
 export { STAKE_POOL_PROGRAM_ID } from './constants';
 export * from './instructions';
-export { StakePoolLayout, ValidatorListLayout, ValidatorStakeInfoLayout } from './layouts';
+export {
+  StakePoolLayout,
+  ValidatorListLayout,
+  ValidatorStakeInfoLayout,
+  FeeSplitLayout,
+} from './layouts';
 
 export interface ValidatorListAccount {
   pubkey: PublicKey;
@@ -1005,6 +1020,12 @@ export async function updateStakePool(
     startIndex += MAX_VALIDATORS_TO_UPDATE;
   }
 
+  const feeSplit = await findFeeSplitProgramAddress(STAKE_POOL_PROGRAM_ID, stakePoolAddress);
+  const feeSplitAccount = await connection.getAccountInfo(feeSplit);
+  const feeRecipients = feeSplitAccount
+    ? FeeSplitLayout.decode(feeSplitAccount.data).recipients.map((x) => x.tokenAccount)
+    : [];
+
   instructions.push(
     StakePoolInstruction.updateStakePoolBalance({
       stakePool: stakePoolAddress,
@@ -1013,6 +1034,12 @@ export async function updateStakePool(
       managerFeeAccount: stakePool.account.data.managerFeeAccount,
       poolMint: stakePool.account.data.poolMint,
       withdrawAuthority,
+      unstakeTicketLedger: await findUnstakeTicketLedgerProgramAddress(
+        STAKE_POOL_PROGRAM_ID,
+        stakePoolAddress,
+      ),
+      feeSplit,
+      feeRecipients,
     }),
   );
 
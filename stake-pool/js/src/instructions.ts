@@ -210,6 +210,12 @@ export type UpdateStakePoolBalanceParams = {
   reserveStake: PublicKey;
   managerFeeAccount: PublicKey;
   poolMint: PublicKey;
+  unstakeTicketLedger: PublicKey;
+  feeSplit: PublicKey;
+  /**
+   * Token accounts of the current fee split recipients, in order
+   */
+  feeRecipients?: PublicKey[];
 };
 
 /**
@@ -493,6 +499,9 @@ export class StakePoolInstruction {
       reserveStake,
       managerFeeAccount,
       poolMint,
+      unstakeTicketLedger,
+      feeSplit,
+      feeRecipients = [],
     } = params;
 
     const type = STAKE_POOL_INSTRUCTION_LAYOUTS.UpdateStakePoolBalance;
@@ -502,10 +511,16 @@ export class StakePoolInstruction {
       { pubkey: stakePool, isSigner: false, isWritable: true },
       { pubkey: withdrawAuthority, isSigner: false, isWritable: false },
       { pubkey: validatorList, isSigner: false, isWritable: true },
-      { pubkey: reserveStake, isSigner: false, isWritable: false },
+      { pubkey: reserveStake, isSigner: false, isWritable: true },
       { pubkey: managerFeeAccount, isSigner: false, isWritable: true },
       { pubkey: poolMint, isSigner: false, isWritable: true },
       { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+      { pubkey: unstakeTicketLedger, isSigner: false, isWritable: true },
+      { pubkey: SYSVAR_CLOCK_PUBKEY, isSigner: false, isWritable: false },
+      { pubkey: SYSVAR_STAKE_HISTORY_PUBKEY, isSigner: false, isWritable: false },
+      { pubkey: StakeProgram.programId, isSigner: false, isWritable: false },
+      { pubkey: feeSplit, isSigner: false, isWritable: true },
+      ...feeRecipients.map((pubkey) => ({ pubkey, isSigner: false, isWritable: true })),
     ];
 
     return new TransactionInstruction({
@@ -1,5 +1,5 @@
 import { Layout, publicKey, u64, option, vec } from './codecs';
-import { struct, Layout as LayoutCls, u8, u32 } from 'buffer-layout';
+import { struct, Layout as LayoutCls, u8, u16, u32 } from 'buffer-layout';
 import { PublicKey } from '@solana/web3.js';
 import BN from 'bn.js';
 import {
@@ -244,3 +244,31 @@ export const ValidatorListLayout = struct<ValidatorList>([
   u32('maxValidators'),
   vec(ValidatorStakeInfoLayout, 'validators'),
 ]);
+
+export interface FeeRecipient {
+  /// Pool token account receiving the share
+  tokenAccount: PublicKey;
+  /// Share of the epoch fee, in basis points
+  bps: number;
+}
+
+export interface FeeSplit {
+  /// Account type, must be FeeSplit currently
+  accountType: number;
+  /// Stake pool the epoch fee is split for
+  stakePool: PublicKey;
+  /// Current recipients of the epoch fee
+  recipients: FeeRecipient[];
+}
+
+export const FeeRecipientLayout = struct<FeeRecipient>([
+  publicKey('tokenAccount'),
+  u16('bps'),
+]);
+
+/// Only decodes the current recipients, the next ones follow them
+export const FeeSplitLayout = struct<FeeSplit>([
+  u8('accountType'),
+  publicKey('stakePool'),
+  vec(FeeRecipientLayout, 'recipients'),
+]);
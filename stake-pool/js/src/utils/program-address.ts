@@ -5,6 +5,8 @@ import {
   METADATA_PROGRAM_ID,
   EPHEMERAL_STAKE_SEED_PREFIX,
   TRANSIENT_STAKE_SEED_PREFIX,
+  UNSTAKE_TICKET_LEDGER_SEED_PREFIX,
+  FEE_SPLIT_SEED_PREFIX,
 } from '../constants';
 
 /**
@@ -77,6 +79,31 @@ export async function findEphemeralStakeProgramAddress(
   return publicKey;
 }
 
+/**
+ * Generates the unstake ticket ledger program address for the stake pool
+ */
+export async function findUnstakeTicketLedgerProgramAddress(
+  programId: PublicKey,
+  stakePoolAddress: PublicKey,
+) {
+  const [publicKey] = await PublicKey.findProgramAddress(
+    [UNSTAKE_TICKET_LEDGER_SEED_PREFIX, stakePoolAddress.toBuffer()],
+    programId,
+  );
+  return publicKey;
+}
+
+/**
+ * Generates the fee split program address for the stake pool
+ */
+export async function findFeeSplitProgramAddress(programId: PublicKey, stakePoolAddress: PublicKey) {
+  const [publicKey] = await PublicKey.findProgramAddress(
+    [FEE_SPLIT_SEED_PREFIX, stakePoolAddress.toBuffer()],
+    programId,
+  );
+  return publicKey;
+}
+
 /**
  * Generates the metadata program address for the stake pool
  */
@@ -164,6 +164,56 @@ pub enum StakePoolError {
     /// Missing required sysvar account
     #[error("Missing required sysvar account")]
     MissingRequiredSysvar,
+    /// Provided validator policy account does not match the one derived for
+    /// the stake pool
+    #[error("InvalidValidatorPolicyAccount")]
+    InvalidValidatorPolicyAccount,
+    /// Validator policy criteria are out of range
+    #[error("InvalidValidatorPolicy")]
+    InvalidValidatorPolicy,
+
+    // 45.
+    /// Provided unstake ticket ledger account does not match the one derived
+    /// for the stake pool
+    #[error("InvalidUnstakeTicketLedgerAccount")]
+    InvalidUnstakeTicketLedgerAccount,
+    /// Unstake ticket is not initialized or does not belong to the stake pool
+    #[error("InvalidUnstakeTicket")]
+    InvalidUnstakeTicket,
+    /// Unstake ticket cannot be claimed yet, the cooldown epoch has not been
+    /// reached or the staker has not deactivated enough stake
+    #[error("UnstakeTicketNotClaimable")]
+    UnstakeTicketNotClaimable,
+    /// Validator reward history is not initialized or does not belong to the
+    /// stake pool
+    #[error("InvalidValidatorRewardHistory")]
+    InvalidValidatorRewardHistory,
+    /// Provided fee split account does not match the one derived for the
+    /// stake pool
+    #[error("InvalidFeeSplitAccount")]
+    InvalidFeeSplitAccount,
+
+    // 50.
+    /// Fee recipients are duplicated, too many, or their shares do not add up
+    /// to the whole fee
+    #[error("InvalidFeeSplit")]
+    InvalidFeeSplit,
+    /// Pool mint does not have a metadata pointer referencing the mint itself
+    #[error("InvalidMetadataPointer")]
+    InvalidMetadataPointer,
+    /// Validator was updated under the validator policy, and must keep being
+    /// updated through `UpdateValidatorListBalanceWithPolicy`
+    #[error("ValidatorPolicyRequired")]
+    ValidatorPolicyRequired,
+    /// Cluster epoch credits can only be recorded for a past epoch
+    #[error("InvalidClusterEpochCredits")]
+    InvalidClusterEpochCredits,
+
+    // 55.
+    /// Pool mint transfer hook can be changed by an authority other than the
+    /// stake pool withdraw authority
+    #[error("InvalidMintTransferHookAuthority")]
+    InvalidMintTransferHookAuthority,
 }
 impl From<StakePoolError> for ProgramError {
     fn from(e: StakePoolError) -> Self {
@@ -8,11 +8,14 @@
 use {
     crate::{
         find_deposit_authority_program_address, find_ephemeral_stake_program_address,
-        find_stake_program_address, find_transient_stake_program_address,
-        find_withdraw_authority_program_address,
+        find_fee_split_program_address, find_stake_program_address,
+        find_transient_stake_program_address, find_unstake_ticket_ledger_program_address,
+        find_validator_policy_program_address, find_withdraw_authority_program_address,
         inline_mpl_token_metadata::{self, pda::find_metadata_account},
-        state::{Fee, FeeType, StakePool, ValidatorList, ValidatorStakeInfo},
-        MAX_VALIDATORS_TO_UPDATE,
+        state::{
+            Fee, FeeRecipient, FeeSplit, FeeType, StakePool, ValidatorList, ValidatorStakeInfo,
+        },
+        MAX_VALIDATORS_TO_UPDATE, MAX_VALIDATORS_TO_UPDATE_WITH_POLICY,
     },
     borsh::{BorshDeserialize, BorshSchema, BorshSerialize},
     solana_program::{
@@ -23,7 +26,8 @@ use {
         stake_history::Epoch,
         system_program, sysvar,
     },
-    std::num::NonZeroU32,
+    spl_token_2022::offchain::{AccountDataResult, AccountFetchError},
+    std::{future::Future, num::NonZeroU32},
 };
 
 /// Defines which validator vote account is set during the
@@ -64,6 +68,7 @@ pub enum StakePoolInstruction {
     ///   5. `[]` Reserve stake account must be initialized, have zero balance,
     ///      and staker / withdrawer authority set to pool withdraw authority.
     ///   6. `[]` Pool token mint. Must have zero supply, owned by withdraw
+    ///      authority. A transfer hook must have no authority, or the withdraw
     ///      authority.
     ///   7. `[]` Pool account to deposit the generated fee for manager.
     ///   8. `[]` Token program id
@@ -234,6 +239,10 @@ pub enum StakePoolInstruction {
     ///  validator stake account. In all other states, nothing is done, and
     ///  the balance is simply added to the canonical stake account balance.
     ///
+    ///  Fails on validators last updated through
+    ///  `UpdateValidatorListBalanceWithPolicy` with an enabled policy, which
+    ///  must keep being updated through it.
+    ///
     ///  0. `[]` Stake pool
     ///  1. `[]` Stake pool withdraw authority
     ///  2. `[w]` Validator stake list storage account
@@ -242,6 +251,8 @@ pub enum StakePoolInstruction {
     ///  5. `[]` Sysvar stake history
     ///  6. `[]` Stake program
     ///  7. ..7+2N ` [] N pairs of validator and transient stake accounts
+    ///  7+2N. `[w]` (Optional) Validator reward history account, recording
+    ///     the rewards of the updated validators
     UpdateValidatorListBalance {
         /// Index to start updating on the validator list
         start_index: u32,
@@ -253,21 +264,45 @@ pub enum StakePoolInstruction {
     },
 
     ///   Updates total pool balance based on balances in the reserve and
-    ///   validator list
+    ///   validator list, minus the lamports owed to unstake tickets.
+    ///
+    ///   If unstake tickets are owed more than the ledger holds, the
+    ///   difference is moved from the reserve to the ledger, as much as
+    ///   possible.
+    ///
+    ///   If the pool has a fee split with recipients, the epoch fee is minted
+    ///   to them instead of the manager fee account, and recipients which are
+    ///   not valid pool token accounts forfeit their share.
+    ///
+    ///   Accounts 7 to 10 and 11 onward are optional, for clients built
+    ///   before unstake tickets and fee splits. Without them, lamports owed to
+    ///   unstake tickets are counted in the pool balance, and the epoch fee is
+    ///   minted to the manager fee account, so pools with either must be
+    ///   updated with all accounts.
     ///
     ///   0. `[w]` Stake pool
     ///   1. `[]` Stake pool withdraw authority
     ///   2. `[w]` Validator stake list storage account
-    ///   3. `[]` Reserve stake account
+    ///   3. `[w]` Reserve stake account, only written with account 7
     ///   4. `[w]` Account to receive pool fee tokens
     ///   5. `[w]` Pool mint account
     ///   6. `[]` Pool token program
+    ///   7. `[w]` (Optional) Unstake ticket ledger account, may be
+    ///      uninitialized
+    ///   8. `[]` (Optional) Sysvar clock, required with account 7
+    ///   9. `[]` (Optional) Sysvar stake history, required with account 7
+    ///  10. `[]` (Optional) Stake program, required with account 7
+    ///  11. `[w]` (Optional) Fee split account, may be uninitialized
+    ///  12. ..12+N `[w]` Token accounts of the N current fee split recipients,
+    ///      in order
     UpdateStakePoolBalance,
 
     ///   Cleans up validator stake account entries marked as `ReadyForRemoval`
     ///
     ///   0. `[]` Stake pool
     ///   1. `[w]` Validator stake list storage account
+    ///   2. `[w]` (Optional) Validator reward history account, dropping the
+    ///      reward entries of the removed validators
     CleanupRemovedValidatorEntries,
 
     ///   Deposit some stake into the pool. The output is a "pool" token
@@ -329,6 +364,8 @@ pub enum StakePoolInstruction {
     ///  10. `[]` Sysvar clock account (required)
     ///  11. `[]` Pool token program id
     ///  12. `[]` Stake program id,
+    ///  13. ..13+N `[]` Accounts required by the transfer hook of the pool
+    ///      mint, if any
     ///  userdata: amount of pool tokens to withdraw
     WithdrawStake(u64),
 
@@ -398,6 +435,8 @@ pub enum StakePoolInstruction {
     ///  10. `[]` Stake program account
     ///  11. `[]` Token program id
     ///  12. `[s]` (Optional) Stake pool sol withdraw authority
+    ///  13. ..13+N `[]` Accounts required by the transfer hook of the pool
+    ///      mint, if any, following the sol withdraw authority if provided
     WithdrawSol(u64),
 
     /// Create token metadata for the stake-pool token in the
@@ -674,6 +713,8 @@ pub enum StakePoolInstruction {
     ///  10. `[]` Sysvar clock account (required)
     ///  11. `[]` Pool token program id
     ///  12. `[]` Stake program id,
+    ///  13. ..13+N `[]` Accounts required by the transfer hook of the pool
+    ///      mint, if any
     ///  userdata: amount of pool tokens to withdraw
     WithdrawStakeWithSlippage {
         /// Pool tokens to burn in exchange for lamports
@@ -723,12 +764,216 @@ pub enum StakePoolInstruction {
     ///  10. `[]` Stake program account
     ///  11. `[]` Token program id
     ///  12. `[s]` (Optional) Stake pool sol withdraw authority
+    ///  13. ..13+N `[]` Accounts required by the transfer hook of the pool
+    ///      mint, if any, following the sol withdraw authority if provided
     WithdrawSolWithSlippage {
         /// Pool tokens to burn in exchange for lamports
         pool_tokens_in: u64,
         /// Minimum amount of lamports that must be received
         minimum_lamports_out: u64,
     },
+
+    ///   (Staker only) Creates or updates the validator policy of the pool,
+    ///   evaluated by `UpdateValidatorListBalanceWithPolicy`
+    ///
+    ///   0. `[]` Stake pool
+    ///   1. `[s]` Staker
+    ///   2. `[w]` Validator policy account, derived from
+    ///      `find_validator_policy_program_address`
+    ///   3. `[ws]` Payer for the validator policy account rent
+    ///   4. `[]` System program
+    SetValidatorPolicy {
+        /// Maximum commission of a validator, 100 disables the check
+        max_commission: u8,
+        /// Minimum vote credits earned by a validator in the previous epoch,
+        /// in basis points of the cluster epoch credits recorded through
+        /// `SetClusterEpochCredits` for that epoch, 0 disables the check
+        min_epoch_credits_bps: u16,
+        /// Maximum number of slots since the last vote of a validator, 0
+        /// disables the check
+        max_delinquent_slots: u64,
+    },
+
+    ///  Updates balances of validator and transient stake accounts in the pool,
+    ///  same as `UpdateValidatorListBalance`, and evaluates the validator
+    ///  policy of the pool on every active validator.
+    ///
+    ///  Validators violating the policy have their stake accounts deactivated
+    ///  and are marked for removal, same as with `RemoveValidatorFromPool`.
+    ///  If the validator policy account is not initialized, this behaves the
+    ///  same as `UpdateValidatorListBalance`. Otherwise, the validators updated
+    ///  under an enabled policy can no longer be updated through
+    ///  `UpdateValidatorListBalance`.
+    ///
+    ///  The epoch credits are only checked once the staker recorded the
+    ///  cluster epoch credits of the previous epoch.
+    ///
+    ///  0. `[w]` Stake pool
+    ///  1. `[]` Stake pool withdraw authority
+    ///  2. `[w]` Validator stake list storage account
+    ///  3. `[w]` Reserve stake account
+    ///  4. `[]` Sysvar clock
+    ///  5. `[]` Sysvar stake history
+    ///  6. `[]` Stake program
+    ///  7. `[]` Validator policy account
+    ///  8. ..8+3N ` [] N triples of validator stake, transient stake and vote
+    ///     accounts
+    ///  8+3N. `[w]` (Optional) Validator reward history account, recording
+    ///     the rewards of the updated validators
+    UpdateValidatorListBalanceWithPolicy {
+        /// Index to start updating on the validator list
+        start_index: u32,
+        /// If true, don't try merging transient stake accounts into the reserve
+        /// or validator stake account
+        no_merge: bool,
+    },
+
+    ///   Burn pool tokens in exchange for an unstake ticket, recording the
+    ///   lamports owed at the current rate. The ticket can be claimed with
+    ///   `ClaimUnstakeTicket` from the next epoch, once the staker
+    ///   deactivated enough stake to fund it.
+    ///
+    ///   The SOL withdrawal fee applies to the pool tokens burnt.
+    ///
+    ///   0. `[w]` Stake pool
+    ///   1. `[w]` Unstake ticket ledger account, created if needed
+    ///   2. `[w]` Unstake ticket account, uninitialized, rent-exempt and owned
+    ///      by the stake pool program
+    ///   3. `[]` Ticket owner, allowed to claim the ticket
+    ///   4. `[s]` User transfer authority, for pool token account
+    ///   5. `[w]` User account to burn pool tokens
+    ///   6. `[w]` Account to receive pool fee tokens
+    ///   7. `[w]` Pool token mint account
+    ///   8. `[ws]` Payer for the unstake ticket ledger rent, if created
+    ///   9. `[]` System program
+    ///  10. `[]` Token program id
+    ///  11. `[s]` (Optional) Stake pool sol withdraw authority
+    ///  12. ..12+N `[]` Accounts required by the transfer hook of the pool
+    ///      mint, if any, following the sol withdraw authority if provided
+    CreateUnstakeTicket {
+        /// Pool tokens to burn in exchange for the ticket
+        pool_tokens_in: u64,
+    },
+
+    ///   Claim the lamports owed by an unstake ticket and close it, returning
+    ///   its rent to the destination.
+    ///
+    ///   Fails until the ticket's claimable epoch, and while the unstake
+    ///   ticket ledger does not hold enough lamports.
+    ///
+    ///   0. `[]` Stake pool
+    ///   1. `[w]` Unstake ticket ledger account
+    ///   2. `[w]` Unstake ticket account
+    ///   3. `[s]` Ticket owner
+    ///   4. `[w]` Account receiving the lamports
+    ClaimUnstakeTicket,
+
+    ///   (Staker only) Initialize the reward history of the stake pool's
+    ///   validators.
+    ///
+    ///   0. `[]` Stake pool
+    ///   1. `[s]` Staker
+    ///   2. `[]` Validator stake list storage account
+    ///   3. `[w]` Uninitialized validator reward history account, rent-exempt,
+    ///      owned by the stake pool program and fitting at least the maximum
+    ///      validators of the validator list
+    InitializeValidatorRewardHistory,
+
+    ///   Record the rewards each validator earned since its last balance
+    ///   update in the validator reward history.
+    ///
+    ///   Staking rewards are the growth of the validator and transient stake
+    ///   lamports over the balances in the validator list, and MEV rewards are
+    ///   the lamports above the delegation and rent of the validator stake
+    ///   account. Both are only visible before `UpdateValidatorListBalance`,
+    ///   so validators already updated in the current epoch are skipped.
+    ///   Passing the reward history to `UpdateValidatorListBalance` records
+    ///   the rewards during the update instead. This instruction remains for
+    ///   pools updated by cranks which do not pass the reward history, as the
+    ///   rewards can still be recorded ahead of their update. Both paths skip
+    ///   validators already recorded in the epoch, so rewards are never
+    ///   counted twice.
+    ///
+    ///   0. `[]` Stake pool
+    ///   1. `[]` Validator stake list storage account
+    ///   2. `[w]` Validator reward history account
+    ///   3. `[]` Sysvar clock
+    ///   4. ..4+2N `[]` N pairs of validator and transient stake accounts
+    UpdateValidatorRewardHistory {
+        /// Index to start updating on the validator list
+        start_index: u32,
+    },
+
+    ///   (Manager only) Creates the fee split of the pool, or updates its
+    ///   recipients. Like epoch fee changes, the new recipients receive the
+    ///   epoch fee after two epoch boundaries. Without recipients, the epoch
+    ///   fee goes back to the manager fee account.
+    ///
+    ///   0. `[]` Stake pool
+    ///   1. `[s]` Manager
+    ///   2. `[w]` Fee split account, derived from
+    ///      `find_fee_split_program_address`
+    ///   3. `[ws]` Payer for the fee split account rent
+    ///   4. `[]` System program
+    ///   5. ..5+N `[]` Token accounts of the N recipients, in order
+    SetFeeSplit {
+        /// Recipients of the epoch fee, with shares adding up to
+        /// `FeeSplit::TOTAL_BPS`
+        recipients: Vec<FeeRecipient>,
+    },
+
+    ///   (Manager only) Create token-2022 metadata for the pool token, stored
+    ///   in the pool mint. The mint must have a metadata pointer to itself.
+    ///   The stake pool withdraw authority is the update authority.
+    ///
+    ///   0. `[]` Stake pool
+    ///   1. `[s]` Manager
+    ///   2. `[]` Stake pool withdraw authority
+    ///   3. `[w]` Pool token mint account
+    ///   4. `[ws]` Payer for the rent of the reallocated mint
+    ///   5. `[]` Token-2022 program id
+    ///   6. `[]` System program id
+    CreateToken2022Metadata {
+        /// Token name
+        name: String,
+        /// Token symbol e.g. stkSOL
+        symbol: String,
+        /// URI of the uploaded metadata of the spl-token
+        uri: String,
+    },
+
+    ///   (Manager only) Update the token-2022 metadata stored in the pool
+    ///   mint.
+    ///
+    ///   0. `[]` Stake pool
+    ///   1. `[s]` Manager
+    ///   2. `[]` Stake pool withdraw authority
+    ///   3. `[w]` Pool token mint account
+    ///   4. `[ws]` Payer for the rent of the reallocated mint
+    ///   5. `[]` Token-2022 program id
+    ///   6. `[]` System program id
+    UpdateToken2022Metadata {
+        /// Token name
+        name: String,
+        /// Token symbol e.g. stkSOL
+        symbol: String,
+        /// URI of the uploaded metadata of the spl-token
+        uri: String,
+    },
+
+    ///   (Staker only) Record the credits earned by a typical validator of
+    ///   the cluster during a past epoch, the reference of the minimum epoch
+    ///   credits of the validator policy.
+    ///
+    ///   0. `[]` Stake pool
+    ///   1. `[s]` Staker
+    ///   2. `[w]` Validator policy account
+    SetClusterEpochCredits {
+        /// Epoch the credits were earned in, must be before the current epoch
+        epoch: u64,
+        /// Credits earned during `epoch` by a typical validator of the cluster
+        epoch_credits: u64,
+    },
 }
 
 /// Creates an 'initialize' instruction.
@@ -1608,15 +1853,55 @@ pub fn update_stake_pool_balance(
     stake_pool_mint: &Pubkey,
     token_program_id: &Pubkey,
 ) -> Instruction {
-    let accounts = vec![
+    update_stake_pool_balance_with_fee_split(
+        program_id,
+        stake_pool,
+        withdraw_authority,
+        validator_list_storage,
+        reserve_stake,
+        manager_fee_account,
+        stake_pool_mint,
+        token_program_id,
+        &[],
+    )
+}
+
+/// Creates `UpdateStakePoolBalance` instruction (pool balance from the stake
+/// account list balances) for a pool splitting its epoch fee between
+/// `fee_recipients`
+pub fn update_stake_pool_balance_with_fee_split(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    withdraw_authority: &Pubkey,
+    validator_list_storage: &Pubkey,
+    reserve_stake: &Pubkey,
+    manager_fee_account: &Pubkey,
+    stake_pool_mint: &Pubkey,
+    token_program_id: &Pubkey,
+    fee_recipients: &[Pubkey],
+) -> Instruction {
+    let (unstake_ticket_ledger, _) =
+        find_unstake_ticket_ledger_program_address(program_id, stake_pool);
+    let (fee_split, _) = find_fee_split_program_address(program_id, stake_pool);
+    let mut accounts = vec![
         AccountMeta::new(*stake_pool, false),
         AccountMeta::new_readonly(*withdraw_authority, false),
         AccountMeta::new(*validator_list_storage, false),
-        AccountMeta::new_readonly(*reserve_stake, false),
+        AccountMeta::new(*reserve_stake, false),
         AccountMeta::new(*manager_fee_account, false),
         AccountMeta::new(*stake_pool_mint, false),
         AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new(unstake_ticket_ledger, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(sysvar::stake_history::id(), false),
+        AccountMeta::new_readonly(stake::program::id(), false),
+        AccountMeta::new(fee_split, false),
     ];
+    accounts.extend(
+        fee_recipients
+            .iter()
+            .map(|fee_recipient| AccountMeta::new(*fee_recipient, false)),
+    );
     Instruction {
         program_id: *program_id,
         accounts,
@@ -1644,11 +1929,15 @@ pub fn cleanup_removed_validator_entries(
 
 /// Creates all `UpdateValidatorListBalance` and `UpdateStakePoolBalance`
 /// instructions for fully updating a stake pool each epoch
+///
+/// `fee_split` is the fee split of the pool, if it has one, whose current
+/// recipients receive the epoch fee
 pub fn update_stake_pool(
     program_id: &Pubkey,
     stake_pool: &StakePool,
     validator_list: &ValidatorList,
     stake_pool_address: &Pubkey,
+    fee_split: Option<&FeeSplit>,
     no_merge: bool,
 ) -> (Vec<Instruction>, Vec<Instruction>) {
     let (withdraw_authority, _) =
@@ -1675,23 +1964,13 @@ pub fn update_stake_pool(
         })
         .collect();
 
-    let final_instructions = vec![
-        update_stake_pool_balance(
-            program_id,
-            stake_pool_address,
-            &withdraw_authority,
-            &stake_pool.validator_list,
-            &stake_pool.reserve_stake,
-            &stake_pool.manager_fee_account,
-            &stake_pool.pool_mint,
-            &stake_pool.token_program_id,
-        ),
-        cleanup_removed_validator_entries(
-            program_id,
-            stake_pool_address,
-            &stake_pool.validator_list,
-        ),
-    ];
+    let final_instructions = update_stake_pool_final_instructions(
+        program_id,
+        stake_pool,
+        stake_pool_address,
+        &withdraw_authority,
+        fee_split,
+    );
     (update_list_instructions, final_instructions)
 }
 
@@ -1706,6 +1985,7 @@ pub fn update_stale_stake_pool(
     stake_pool: &StakePool,
     validator_list: &ValidatorList,
     stake_pool_address: &Pubkey,
+    fee_split: Option<&FeeSplit>,
     no_merge: bool,
     current_epoch: Epoch,
 ) -> (Vec<Instruction>, Vec<Instruction>) {
@@ -1734,77 +2014,315 @@ pub fn update_stale_stake_pool(
         })
         .collect();
 
-    let final_instructions = vec![
-        update_stake_pool_balance(
+    let final_instructions = update_stake_pool_final_instructions(
+        program_id,
+        stake_pool,
+        stake_pool_address,
+        &withdraw_authority,
+        fee_split,
+    );
+    (update_list_instructions, final_instructions)
+}
+
+fn update_stake_pool_final_instructions(
+    program_id: &Pubkey,
+    stake_pool: &StakePool,
+    stake_pool_address: &Pubkey,
+    withdraw_authority: &Pubkey,
+    fee_split: Option<&FeeSplit>,
+) -> Vec<Instruction> {
+    let fee_recipients: Vec<Pubkey> = fee_split
+        .map(|fee_split| {
+            fee_split
+                .recipients
+                .iter()
+                .map(|recipient| recipient.token_account)
+                .collect()
+        })
+        .unwrap_or_default();
+    vec![
+        update_stake_pool_balance_with_fee_split(
             program_id,
             stake_pool_address,
-            &withdraw_authority,
+            withdraw_authority,
             &stake_pool.validator_list,
             &stake_pool.reserve_stake,
             &stake_pool.manager_fee_account,
             &stake_pool.pool_mint,
             &stake_pool.token_program_id,
+            &fee_recipients,
         ),
         cleanup_removed_validator_entries(
             program_id,
             stake_pool_address,
             &stake_pool.validator_list,
         ),
+    ]
+}
+
+/// Creates a `SetValidatorPolicy` instruction
+pub fn set_validator_policy(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    staker: &Pubkey,
+    payer: &Pubkey,
+    max_commission: u8,
+    min_epoch_credits_bps: u16,
+    max_delinquent_slots: u64,
+) -> Instruction {
+    let (validator_policy, _) = find_validator_policy_program_address(program_id, stake_pool);
+    let accounts = vec![
+        AccountMeta::new_readonly(*stake_pool, false),
+        AccountMeta::new_readonly(*staker, true),
+        AccountMeta::new(validator_policy, false),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(system_program::id(), false),
     ];
-    (update_list_instructions, final_instructions)
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&StakePoolInstruction::SetValidatorPolicy {
+            max_commission,
+            min_epoch_credits_bps,
+            max_delinquent_slots,
+        })
+        .unwrap(),
+    }
 }
 
-fn deposit_stake_internal(
+/// Creates a `SetClusterEpochCredits` instruction
+pub fn set_cluster_epoch_credits(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    staker: &Pubkey,
+    epoch: u64,
+    epoch_credits: u64,
+) -> Instruction {
+    let (validator_policy, _) = find_validator_policy_program_address(program_id, stake_pool);
+    let accounts = vec![
+        AccountMeta::new_readonly(*stake_pool, false),
+        AccountMeta::new_readonly(*staker, true),
+        AccountMeta::new(validator_policy, false),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&StakePoolInstruction::SetClusterEpochCredits {
+            epoch,
+            epoch_credits,
+        })
+        .unwrap(),
+    }
+}
+
+/// Creates a `SetFeeSplit` instruction
+pub fn set_fee_split(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+    payer: &Pubkey,
+    recipients: Vec<FeeRecipient>,
+) -> Instruction {
+    let (fee_split, _) = find_fee_split_program_address(program_id, stake_pool);
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+        AccountMeta::new(fee_split, false),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    accounts.extend(
+        recipients
+            .iter()
+            .map(|recipient| AccountMeta::new_readonly(recipient.token_account, false)),
+    );
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&StakePoolInstruction::SetFeeSplit { recipients }).unwrap(),
+    }
+}
+
+/// Creates an `UpdateValidatorListBalanceWithPolicy` instruction (update
+/// validator stake account balances and evaluate the validator policy) to
+/// update `validator_list[start_index..start_index + len]`.
+///
+/// Returns `Err(ProgramError::InvalidInstructionData)` if:
+/// - `start_index..start_index + len` is out of bounds for
+///   `validator_list.validators`
+pub fn update_validator_list_balance_with_policy_chunk(
     program_id: &Pubkey,
     stake_pool: &Pubkey,
-    validator_list_storage: &Pubkey,
-    stake_pool_deposit_authority: Option<&Pubkey>,
     stake_pool_withdraw_authority: &Pubkey,
-    deposit_stake_address: &Pubkey,
-    deposit_stake_withdraw_authority: &Pubkey,
-    validator_stake_account: &Pubkey,
-    reserve_stake_account: &Pubkey,
-    pool_tokens_to: &Pubkey,
-    manager_fee_account: &Pubkey,
-    referrer_pool_tokens_account: &Pubkey,
-    pool_mint: &Pubkey,
-    token_program_id: &Pubkey,
-    minimum_pool_tokens_out: Option<u64>,
-) -> Vec<Instruction> {
-    let mut instructions = vec![];
+    validator_list_address: &Pubkey,
+    reserve_stake: &Pubkey,
+    validator_list: &ValidatorList,
+    len: usize,
+    start_index: usize,
+    no_merge: bool,
+) -> Result<Instruction, ProgramError> {
+    let (validator_policy, _) = find_validator_policy_program_address(program_id, stake_pool);
     let mut accounts = vec![
         AccountMeta::new(*stake_pool, false),
-        AccountMeta::new(*validator_list_storage, false),
+        AccountMeta::new_readonly(*stake_pool_withdraw_authority, false),
+        AccountMeta::new(*validator_list_address, false),
+        AccountMeta::new(*reserve_stake, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(sysvar::stake_history::id(), false),
+        AccountMeta::new_readonly(stake::program::id(), false),
+        AccountMeta::new_readonly(validator_policy, false),
     ];
-    if let Some(stake_pool_deposit_authority) = stake_pool_deposit_authority {
-        accounts.push(AccountMeta::new_readonly(
-            *stake_pool_deposit_authority,
-            true,
-        ));
-        instructions.extend_from_slice(&[
-            stake::instruction::authorize(
-                deposit_stake_address,
-                deposit_stake_withdraw_authority,
-                stake_pool_deposit_authority,
-                stake::state::StakeAuthorize::Staker,
-                None,
-            ),
-            stake::instruction::authorize(
-                deposit_stake_address,
-                deposit_stake_withdraw_authority,
-                stake_pool_deposit_authority,
-                stake::state::StakeAuthorize::Withdrawer,
-                None,
-            ),
-        ]);
-    } else {
-        let stake_pool_deposit_authority =
-            find_deposit_authority_program_address(program_id, stake_pool).0;
-        accounts.push(AccountMeta::new_readonly(
-            stake_pool_deposit_authority,
-            false,
-        ));
-        instructions.extend_from_slice(&[
+    let validator_list_subslice = validator_list
+        .validators
+        .get(start_index..start_index.saturating_add(len))
+        .ok_or(ProgramError::InvalidInstructionData)?;
+    accounts.extend(validator_list_subslice.iter().flat_map(
+        |ValidatorStakeInfo {
+             vote_account_address,
+             validator_seed_suffix,
+             transient_seed_suffix,
+             ..
+         }| {
+            let (validator_stake_account, _) = find_stake_program_address(
+                program_id,
+                vote_account_address,
+                stake_pool,
+                NonZeroU32::new((*validator_seed_suffix).into()),
+            );
+            let (transient_stake_account, _) = find_transient_stake_program_address(
+                program_id,
+                vote_account_address,
+                stake_pool,
+                (*transient_seed_suffix).into(),
+            );
+            [
+                AccountMeta::new(validator_stake_account, false),
+                AccountMeta::new(transient_stake_account, false),
+                AccountMeta::new_readonly(*vote_account_address, false),
+            ]
+        },
+    ));
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(
+            &StakePoolInstruction::UpdateValidatorListBalanceWithPolicy {
+                start_index: start_index.try_into().unwrap(),
+                no_merge,
+            },
+        )
+        .unwrap(),
+    })
+}
+
+/// Creates all `UpdateValidatorListBalanceWithPolicy` and
+/// `UpdateStakePoolBalance` instructions for fully updating a stake pool each
+/// epoch, evaluating its validator policy
+///
+/// If `current_epoch` is given, validators that are already updated for this
+/// epoch are skipped, same as in [`update_stale_stake_pool`]
+pub fn update_stake_pool_with_policy(
+    program_id: &Pubkey,
+    stake_pool: &StakePool,
+    validator_list: &ValidatorList,
+    stake_pool_address: &Pubkey,
+    fee_split: Option<&FeeSplit>,
+    no_merge: bool,
+    current_epoch: Option<Epoch>,
+) -> (Vec<Instruction>, Vec<Instruction>) {
+    let (withdraw_authority, _) =
+        find_withdraw_authority_program_address(program_id, stake_pool_address);
+
+    let update_list_instructions = validator_list
+        .validators
+        .chunks(MAX_VALIDATORS_TO_UPDATE_WITH_POLICY)
+        .enumerate()
+        .filter(|(_, chunk)| {
+            current_epoch.map_or(true, |current_epoch| {
+                chunk.iter().any(|info| {
+                    let last_update_epoch: u64 = info.last_update_epoch.into();
+                    last_update_epoch < current_epoch
+                })
+            })
+        })
+        .map(|(i, chunk)| {
+            // unwrap-safety: chunk len and offset are derived
+            update_validator_list_balance_with_policy_chunk(
+                program_id,
+                stake_pool_address,
+                &withdraw_authority,
+                &stake_pool.validator_list,
+                &stake_pool.reserve_stake,
+                validator_list,
+                chunk.len(),
+                i.saturating_mul(MAX_VALIDATORS_TO_UPDATE_WITH_POLICY),
+                no_merge,
+            )
+            .unwrap()
+        })
+        .collect();
+
+    let final_instructions = update_stake_pool_final_instructions(
+        program_id,
+        stake_pool,
+        stake_pool_address,
+        &withdraw_authority,
+        fee_split,
+    );
+    (update_list_instructions, final_instructions)
+}
+
+fn deposit_stake_internal(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    validator_list_storage: &Pubkey,
+    stake_pool_deposit_authority: Option<&Pubkey>,
+    stake_pool_withdraw_authority: &Pubkey,
+    deposit_stake_address: &Pubkey,
+    deposit_stake_withdraw_authority: &Pubkey,
+    validator_stake_account: &Pubkey,
+    reserve_stake_account: &Pubkey,
+    pool_tokens_to: &Pubkey,
+    manager_fee_account: &Pubkey,
+    referrer_pool_tokens_account: &Pubkey,
+    pool_mint: &Pubkey,
+    token_program_id: &Pubkey,
+    minimum_pool_tokens_out: Option<u64>,
+) -> Vec<Instruction> {
+    let mut instructions = vec![];
+    let mut accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new(*validator_list_storage, false),
+    ];
+    if let Some(stake_pool_deposit_authority) = stake_pool_deposit_authority {
+        accounts.push(AccountMeta::new_readonly(
+            *stake_pool_deposit_authority,
+            true,
+        ));
+        instructions.extend_from_slice(&[
+            stake::instruction::authorize(
+                deposit_stake_address,
+                deposit_stake_withdraw_authority,
+                stake_pool_deposit_authority,
+                stake::state::StakeAuthorize::Staker,
+                None,
+            ),
+            stake::instruction::authorize(
+                deposit_stake_address,
+                deposit_stake_withdraw_authority,
+                stake_pool_deposit_authority,
+                stake::state::StakeAuthorize::Withdrawer,
+                None,
+            ),
+        ]);
+    } else {
+        let stake_pool_deposit_authority =
+            find_deposit_authority_program_address(program_id, stake_pool).0;
+        accounts.push(AccountMeta::new_readonly(
+            stake_pool_deposit_authority,
+            false,
+        ));
+        instructions.extend_from_slice(&[
             stake::instruction::authorize(
                 deposit_stake_address,
                 deposit_stake_withdraw_authority,
@@ -2308,6 +2826,96 @@ pub fn withdraw_stake_with_slippage(
     )
 }
 
+/// Creates instructions to move liquid stake from one stake pool to another in
+/// a single transaction, without unstaking.
+///
+/// Withdraws `pool_tokens_in` source pool tokens as an active stake account
+/// split from the source pool's stake for `vote_account_address`, then
+/// deposits that stake into the destination pool's stake account for the same
+/// validator. Both pools must be updated for the current epoch, and the
+/// validator must be active in both of them.
+///
+/// `stake_receiver` must be an allocated, rent-exempt, uninitialized stake
+/// account. `user_authority` must own `source_pool_tokens_from` and sign, and
+/// becomes the authority of the withdrawn stake until it is deposited. If the
+/// destination pool uses a custom stake deposit authority, it must also sign.
+///
+/// The first returned instruction is the `WithdrawStake` from the source pool.
+/// If the source pool mint has a transfer hook, pass it to
+/// `add_transfer_hook_accounts` before sending.
+pub fn swap_pool_tokens(
+    program_id: &Pubkey,
+    source_stake_pool: &StakePool,
+    source_stake_pool_address: &Pubkey,
+    source_validator_stake_seed: Option<NonZeroU32>,
+    destination_stake_pool: &StakePool,
+    destination_stake_pool_address: &Pubkey,
+    destination_validator_stake_seed: Option<NonZeroU32>,
+    vote_account_address: &Pubkey,
+    stake_receiver: &Pubkey,
+    user_authority: &Pubkey,
+    source_pool_tokens_from: &Pubkey,
+    destination_pool_tokens_to: &Pubkey,
+    pool_tokens_in: u64,
+    minimum_pool_tokens_out: Option<u64>,
+) -> Vec<Instruction> {
+    let (source_withdraw_authority, _) =
+        find_withdraw_authority_program_address(program_id, source_stake_pool_address);
+    let (source_validator_stake, _) = find_stake_program_address(
+        program_id,
+        vote_account_address,
+        source_stake_pool_address,
+        source_validator_stake_seed,
+    );
+    let (destination_withdraw_authority, _) =
+        find_withdraw_authority_program_address(program_id, destination_stake_pool_address);
+    let (destination_validator_stake, _) = find_stake_program_address(
+        program_id,
+        vote_account_address,
+        destination_stake_pool_address,
+        destination_validator_stake_seed,
+    );
+    let (default_deposit_authority, _) =
+        find_deposit_authority_program_address(program_id, destination_stake_pool_address);
+    let destination_deposit_authority = (destination_stake_pool.stake_deposit_authority
+        != default_deposit_authority)
+        .then_some(&destination_stake_pool.stake_deposit_authority);
+
+    let mut instructions = vec![withdraw_stake(
+        program_id,
+        source_stake_pool_address,
+        &source_stake_pool.validator_list,
+        &source_withdraw_authority,
+        &source_validator_stake,
+        stake_receiver,
+        user_authority,
+        user_authority,
+        source_pool_tokens_from,
+        &source_stake_pool.manager_fee_account,
+        &source_stake_pool.pool_mint,
+        &source_stake_pool.token_program_id,
+        pool_tokens_in,
+    )];
+    instructions.extend(deposit_stake_internal(
+        program_id,
+        destination_stake_pool_address,
+        &destination_stake_pool.validator_list,
+        destination_deposit_authority,
+        &destination_withdraw_authority,
+        stake_receiver,
+        user_authority,
+        &destination_validator_stake,
+        &destination_stake_pool.reserve_stake,
+        destination_pool_tokens_to,
+        &destination_stake_pool.manager_fee_account,
+        destination_pool_tokens_to,
+        &destination_stake_pool.pool_mint,
+        &destination_stake_pool.token_program_id,
+        minimum_pool_tokens_out,
+    ));
+    instructions
+}
+
 fn withdraw_sol_internal(
     program_id: &Pubkey,
     stake_pool: &Pubkey,
@@ -2638,3 +3246,350 @@ pub fn create_token_metadata(
             .unwrap(),
     }
 }
+
+/// Creates an instruction to create token-2022 metadata stored in the pool
+/// mint itself
+pub fn create_token_2022_metadata(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+    pool_mint: &Pubkey,
+    payer: &Pubkey,
+    name: String,
+    symbol: String,
+    uri: String,
+) -> Instruction {
+    let (stake_pool_withdraw_authority, _) =
+        find_withdraw_authority_program_address(program_id, stake_pool);
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+        AccountMeta::new_readonly(stake_pool_withdraw_authority, false),
+        AccountMeta::new(*pool_mint, false),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(spl_token_2022::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&StakePoolInstruction::CreateToken2022Metadata { name, symbol, uri })
+            .unwrap(),
+    }
+}
+
+/// Creates an instruction to update the token-2022 metadata stored in the
+/// pool mint
+pub fn update_token_2022_metadata(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    manager: &Pubkey,
+    pool_mint: &Pubkey,
+    payer: &Pubkey,
+    name: String,
+    symbol: String,
+    uri: String,
+) -> Instruction {
+    let (stake_pool_withdraw_authority, _) =
+        find_withdraw_authority_program_address(program_id, stake_pool);
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*stake_pool, false),
+        AccountMeta::new_readonly(*manager, true),
+        AccountMeta::new_readonly(stake_pool_withdraw_authority, false),
+        AccountMeta::new(*pool_mint, false),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(spl_token_2022::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&StakePoolInstruction::UpdateToken2022Metadata { name, symbol, uri })
+            .unwrap(),
+    }
+}
+
+fn create_unstake_ticket_internal(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    unstake_ticket: &Pubkey,
+    ticket_owner: &Pubkey,
+    user_transfer_authority: &Pubkey,
+    pool_tokens_from: &Pubkey,
+    manager_fee_account: &Pubkey,
+    pool_mint: &Pubkey,
+    payer: &Pubkey,
+    token_program_id: &Pubkey,
+    sol_withdraw_authority: Option<&Pubkey>,
+    pool_tokens_in: u64,
+) -> Instruction {
+    let (unstake_ticket_ledger, _) =
+        find_unstake_ticket_ledger_program_address(program_id, stake_pool);
+    let mut accounts = vec![
+        AccountMeta::new(*stake_pool, false),
+        AccountMeta::new(unstake_ticket_ledger, false),
+        AccountMeta::new(*unstake_ticket, false),
+        AccountMeta::new_readonly(*ticket_owner, false),
+        AccountMeta::new_readonly(*user_transfer_authority, true),
+        AccountMeta::new(*pool_tokens_from, false),
+        AccountMeta::new(*manager_fee_account, false),
+        AccountMeta::new(*pool_mint, false),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+    if let Some(sol_withdraw_authority) = sol_withdraw_authority {
+        accounts.push(AccountMeta::new_readonly(*sol_withdraw_authority, true));
+    }
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&StakePoolInstruction::CreateUnstakeTicket { pool_tokens_in }).unwrap(),
+    }
+}
+
+/// Creates instruction required to burn pool tokens in exchange for an unstake
+/// ticket. The ticket account must be created beforehand, owned by the stake
+/// pool program.
+pub fn create_unstake_ticket(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    unstake_ticket: &Pubkey,
+    ticket_owner: &Pubkey,
+    user_transfer_authority: &Pubkey,
+    pool_tokens_from: &Pubkey,
+    manager_fee_account: &Pubkey,
+    pool_mint: &Pubkey,
+    payer: &Pubkey,
+    token_program_id: &Pubkey,
+    pool_tokens_in: u64,
+) -> Instruction {
+    create_unstake_ticket_internal(
+        program_id,
+        stake_pool,
+        unstake_ticket,
+        ticket_owner,
+        user_transfer_authority,
+        pool_tokens_from,
+        manager_fee_account,
+        pool_mint,
+        payer,
+        token_program_id,
+        None,
+        pool_tokens_in,
+    )
+}
+
+/// Creates instruction required to burn pool tokens in exchange for an unstake
+/// ticket.
+/// The difference with `create_unstake_ticket()` is that the sol withdraw
+/// authority must sign this instruction.
+pub fn create_unstake_ticket_with_authority(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    sol_withdraw_authority: &Pubkey,
+    unstake_ticket: &Pubkey,
+    ticket_owner: &Pubkey,
+    user_transfer_authority: &Pubkey,
+    pool_tokens_from: &Pubkey,
+    manager_fee_account: &Pubkey,
+    pool_mint: &Pubkey,
+    payer: &Pubkey,
+    token_program_id: &Pubkey,
+    pool_tokens_in: u64,
+) -> Instruction {
+    create_unstake_ticket_internal(
+        program_id,
+        stake_pool,
+        unstake_ticket,
+        ticket_owner,
+        user_transfer_authority,
+        pool_tokens_from,
+        manager_fee_account,
+        pool_mint,
+        payer,
+        token_program_id,
+        Some(sol_withdraw_authority),
+        pool_tokens_in,
+    )
+}
+
+/// Appends the accounts required by the pool mint's transfer hook, if any, to
+/// a `WithdrawStake`, `WithdrawSol` or `CreateUnstakeTicket` instruction, so
+/// that the withdrawal fee can be transferred to the manager fee account.
+///
+/// `pool_tokens_fee` is the fee that will be transferred, as given by
+/// `StakePool::calc_pool_tokens_stake_withdrawal_fee` or
+/// `StakePool::calc_pool_tokens_sol_withdrawal_fee`.
+pub async fn add_transfer_hook_accounts<F, Fut>(
+    instruction: &mut Instruction,
+    pool_tokens_from: &Pubkey,
+    manager_fee_account: &Pubkey,
+    pool_mint: &Pubkey,
+    user_transfer_authority: &Pubkey,
+    pool_tokens_fee: u64,
+    fetch_account_data_fn: F,
+) -> Result<(), AccountFetchError>
+where
+    F: Fn(Pubkey) -> Fut,
+    Fut: Future<Output = AccountDataResult>,
+{
+    spl_token_2022::offchain::add_extra_account_metas(
+        instruction,
+        pool_tokens_from,
+        pool_mint,
+        manager_fee_account,
+        user_transfer_authority,
+        pool_tokens_fee,
+        fetch_account_data_fn,
+    )
+    .await
+}
+
+/// Creates instruction required to claim the lamports owed by an unstake
+/// ticket
+pub fn claim_unstake_ticket(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    unstake_ticket: &Pubkey,
+    ticket_owner: &Pubkey,
+    lamports_to: &Pubkey,
+) -> Instruction {
+    let (unstake_ticket_ledger, _) =
+        find_unstake_ticket_ledger_program_address(program_id, stake_pool);
+    let accounts = vec![
+        AccountMeta::new_readonly(*stake_pool, false),
+        AccountMeta::new(unstake_ticket_ledger, false),
+        AccountMeta::new(*unstake_ticket, false),
+        AccountMeta::new_readonly(*ticket_owner, true),
+        AccountMeta::new(*lamports_to, false),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&StakePoolInstruction::ClaimUnstakeTicket).unwrap(),
+    }
+}
+
+/// Creates `InitializeValidatorRewardHistory` instruction
+pub fn initialize_validator_reward_history(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    staker: &Pubkey,
+    validator_list: &Pubkey,
+    validator_reward_history: &Pubkey,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*stake_pool, false),
+        AccountMeta::new_readonly(*staker, true),
+        AccountMeta::new_readonly(*validator_list, false),
+        AccountMeta::new(*validator_reward_history, false),
+    ];
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&StakePoolInstruction::InitializeValidatorRewardHistory).unwrap(),
+    }
+}
+
+/// Adds the validator reward history to an `UpdateValidatorListBalance` or
+/// `UpdateValidatorListBalanceWithPolicy` instruction, recording the rewards
+/// of the validators it updates, or to a `CleanupRemovedValidatorEntries`
+/// instruction, dropping the rewards of the validators it removes
+pub fn add_validator_reward_history(
+    instruction: &mut Instruction,
+    validator_reward_history: &Pubkey,
+) {
+    instruction
+        .accounts
+        .push(AccountMeta::new(*validator_reward_history, false));
+}
+
+/// Creates `UpdateValidatorRewardHistory` instruction (record validator
+/// rewards) for a chunk of the validator list
+pub fn update_validator_reward_history_chunk(
+    program_id: &Pubkey,
+    stake_pool: &Pubkey,
+    validator_list_address: &Pubkey,
+    validator_reward_history: &Pubkey,
+    validator_list: &ValidatorList,
+    len: usize,
+    start_index: usize,
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*stake_pool, false),
+        AccountMeta::new_readonly(*validator_list_address, false),
+        AccountMeta::new(*validator_reward_history, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+    let validator_list_subslice = validator_list
+        .validators
+        .get(start_index..start_index.saturating_add(len))
+        .ok_or(ProgramError::InvalidInstructionData)?;
+    accounts.extend(validator_list_subslice.iter().flat_map(
+        |ValidatorStakeInfo {
+             vote_account_address,
+             validator_seed_suffix,
+             transient_seed_suffix,
+             ..
+         }| {
+            let (validator_stake_account, _) = find_stake_program_address(
+                program_id,
+                vote_account_address,
+                stake_pool,
+                NonZeroU32::new((*validator_seed_suffix).into()),
+            );
+            let (transient_stake_account, _) = find_transient_stake_program_address(
+                program_id,
+                vote_account_address,
+                stake_pool,
+                (*transient_seed_suffix).into(),
+            );
+            [
+                AccountMeta::new_readonly(validator_stake_account, false),
+                AccountMeta::new_readonly(transient_stake_account, false),
+            ]
+        },
+    ));
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: borsh::to_vec(&StakePoolInstruction::UpdateValidatorRewardHistory {
+            start_index: start_index.try_into().unwrap(),
+        })
+        .unwrap(),
+    })
+}
+
+/// Creates the `UpdateValidatorRewardHistory` instructions recording the
+/// rewards of every validator, to send before updating the stake pool
+pub fn update_validator_reward_history(
+    program_id: &Pubkey,
+    stake_pool: &StakePool,
+    validator_list: &ValidatorList,
+    stake_pool_address: &Pubkey,
+    validator_reward_history: &Pubkey,
+) -> Vec<Instruction> {
+    validator_list
+        .validators
+        .chunks(MAX_VALIDATORS_TO_UPDATE)
+        .enumerate()
+        .map(|(i, chunk)| {
+            // unwrap-safety: chunk len and offset are derived
+            update_validator_reward_history_chunk(
+                program_id,
+                stake_pool_address,
+                &stake_pool.validator_list,
+                validator_reward_history,
+                validator_list,
+                chunk.len(),
+                i.saturating_mul(MAX_VALIDATORS_TO_UPDATE),
+            )
+            .unwrap()
+        })
+        .collect()
+}
@@ -33,6 +33,15 @@ const TRANSIENT_STAKE_SEED_PREFIX: &[u8] = b"transient";
 /// Seed for ephemeral stake account
 const EPHEMERAL_STAKE_SEED_PREFIX: &[u8] = b"ephemeral";
 
+/// Seed for validator policy account
+const VALIDATOR_POLICY_SEED_PREFIX: &[u8] = b"validator_policy";
+
+/// Seed for unstake ticket ledger account
+const UNSTAKE_TICKET_LEDGER_SEED_PREFIX: &[u8] = b"unstake_ticket_ledger";
+
+/// Seed for fee split account
+const FEE_SPLIT_SEED_PREFIX: &[u8] = b"fee_split";
+
 /// Minimum amount of staked lamports required in a validator stake account to
 /// allow for merges without a mismatch on credits observed
 pub const MINIMUM_ACTIVE_STAKE: u64 = 1_000_000;
@@ -44,6 +53,11 @@ pub const MINIMUM_RESERVE_LAMPORTS: u64 = 0;
 /// `UpdateValidatorListBalance` instruction, based on compute limits
 pub const MAX_VALIDATORS_TO_UPDATE: usize = 5;
 
+/// Maximum amount of validator stake accounts to update per
+/// `UpdateValidatorListBalanceWithPolicy` instruction, which also reads each
+/// validator's vote account
+pub const MAX_VALIDATORS_TO_UPDATE_WITH_POLICY: usize = 3;
+
 /// Maximum factor by which a withdrawal fee can be increased per epoch
 /// protecting stakers from malicious users.
 /// If current fee is 0, WITHDRAWAL_BASELINE_FEE is used as the baseline
@@ -157,6 +171,42 @@ pub fn find_ephemeral_stake_program_address(
     )
 }
 
+/// Generates the validator policy program address for the stake pool
+pub fn find_validator_policy_program_address(
+    program_id: &Pubkey,
+    stake_pool_address: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[VALIDATOR_POLICY_SEED_PREFIX, stake_pool_address.as_ref()],
+        program_id,
+    )
+}
+
+/// Generates the unstake ticket ledger program address for the stake pool
+pub fn find_unstake_ticket_ledger_program_address(
+    program_id: &Pubkey,
+    stake_pool_address: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            UNSTAKE_TICKET_LEDGER_SEED_PREFIX,
+            stake_pool_address.as_ref(),
+        ],
+        program_id,
+    )
+}
+
+/// Generates the fee split program address for the stake pool
+pub fn find_fee_split_program_address(
+    program_id: &Pubkey,
+    stake_pool_address: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[FEE_SPLIT_SEED_PREFIX, stake_pool_address.as_ref()],
+        program_id,
+    )
+}
+
 solana_program::declare_id!("SPoo1Ku8WFXoNDMHPsrGSTSG1Y47rzgn41SLUNakuHy");
 
 #[cfg(test)]
@@ -2,6 +2,7 @@
 
 use {
     crate::{
+        big_vec::BigVec,
         error::StakePoolError,
         find_deposit_authority_program_address,
         inline_mpl_token_metadata::{
@@ -13,35 +14,43 @@ use {
         instruction::{FundingType, PreferredValidatorType, StakePoolInstruction},
         minimum_delegation, minimum_reserve_lamports, minimum_stake_lamports,
         state::{
-            is_extension_supported_for_mint, AccountType, Fee, FeeType, FutureEpoch, StakePool,
-            StakeStatus, StakeWithdrawSource, ValidatorList, ValidatorListHeader,
-            ValidatorStakeInfo,
+            is_extension_supported_for_mint, AccountType, Fee, FeeRecipient, FeeSplit, FeeType,
+            FutureEpoch, StakePool, StakeStatus, StakeWithdrawSource, UnstakeTicket,
+            UnstakeTicketLedger, ValidatorList, ValidatorListHeader, ValidatorPolicy,
+            ValidatorPolicyViolation, ValidatorRewardHistory, ValidatorRewardHistoryHeader,
+            ValidatorRewardInfo, ValidatorStakeInfo,
         },
-        AUTHORITY_DEPOSIT, AUTHORITY_WITHDRAW, EPHEMERAL_STAKE_SEED_PREFIX,
-        TRANSIENT_STAKE_SEED_PREFIX,
+        AUTHORITY_DEPOSIT, AUTHORITY_WITHDRAW, EPHEMERAL_STAKE_SEED_PREFIX, FEE_SPLIT_SEED_PREFIX,
+        TRANSIENT_STAKE_SEED_PREFIX, UNSTAKE_TICKET_LEDGER_SEED_PREFIX,
+        VALIDATOR_POLICY_SEED_PREFIX,
     },
     borsh::BorshDeserialize,
     num_traits::FromPrimitive,
     solana_program::{
         account_info::{next_account_info, AccountInfo},
-        borsh1::try_from_slice_unchecked,
+        borsh1::{get_packed_len, try_from_slice_unchecked},
         clock::{Clock, Epoch},
         decode_error::DecodeError,
         entrypoint::ProgramResult,
         msg,
         program::{invoke, invoke_signed},
         program_error::{PrintProgramError, ProgramError},
-        pubkey::Pubkey,
+        pubkey::{Pubkey, PUBKEY_BYTES},
         rent::Rent,
         stake, system_instruction, system_program,
         sysvar::Sysvar,
+        vote::{self, state::VoteState},
     },
     spl_token_2022::{
         check_spl_token_program_account,
-        extension::{BaseStateWithExtensions, StateWithExtensions},
+        extension::{
+            metadata_pointer::MetadataPointer, transfer_hook::TransferHook,
+            BaseStateWithExtensions, StateWithExtensions,
+        },
         native_mint,
         state::Mint,
     },
+    spl_token_metadata_interface::state::{Field, TokenMetadata},
     std::num::NonZeroU32,
 };
 
@@ -120,6 +129,87 @@ fn check_ephemeral_stake_address(
     }
 }
 
+/// Check address validity for the validator policy account of a stake pool
+fn check_validator_policy_address(
+    program_id: &Pubkey,
+    stake_pool_address: &Pubkey,
+    validator_policy_address: &Pubkey,
+) -> Result<u8, ProgramError> {
+    let (expected_address, bump_seed) =
+        crate::find_validator_policy_program_address(program_id, stake_pool_address);
+    if expected_address != *validator_policy_address {
+        msg!(
+            "Incorrect validator policy account, expected {}, received {}",
+            expected_address,
+            validator_policy_address
+        );
+        Err(StakePoolError::InvalidValidatorPolicyAccount.into())
+    } else {
+        Ok(bump_seed)
+    }
+}
+
+/// Check address validity for the unstake ticket ledger account of a stake
+/// pool
+fn check_unstake_ticket_ledger_address(
+    program_id: &Pubkey,
+    stake_pool_address: &Pubkey,
+    unstake_ticket_ledger_address: &Pubkey,
+) -> Result<u8, ProgramError> {
+    let (expected_address, bump_seed) =
+        crate::find_unstake_ticket_ledger_program_address(program_id, stake_pool_address);
+    if expected_address != *unstake_ticket_ledger_address {
+        msg!(
+            "Incorrect unstake ticket ledger account, expected {}, received {}",
+            expected_address,
+            unstake_ticket_ledger_address
+        );
+        Err(StakePoolError::InvalidUnstakeTicketLedgerAccount.into())
+    } else {
+        Ok(bump_seed)
+    }
+}
+
+/// Check address validity for the fee split account of a stake pool
+fn check_fee_split_address(
+    program_id: &Pubkey,
+    stake_pool_address: &Pubkey,
+    fee_split_address: &Pubkey,
+) -> Result<u8, ProgramError> {
+    let (expected_address, bump_seed) =
+        crate::find_fee_split_program_address(program_id, stake_pool_address);
+    if expected_address != *fee_split_address {
+        msg!(
+            "Incorrect fee split account, expected {}, received {}",
+            expected_address,
+            fee_split_address
+        );
+        Err(StakePoolError::InvalidFeeSplitAccount.into())
+    } else {
+        Ok(bump_seed)
+    }
+}
+
+/// Check that the pool mint points its metadata to itself, so that the
+/// token-2022 metadata is stored in the mint
+fn check_self_metadata_pointer(pool_mint_info: &AccountInfo) -> Result<(), ProgramError> {
+    let mint_data = pool_mint_info.try_borrow_data()?;
+    let mint = StateWithExtensions::<Mint>::unpack(&mint_data)?;
+    let metadata_address = mint
+        .get_extension::<MetadataPointer>()
+        .ok()
+        .and_then(|pointer| Option::<Pubkey>::from(pointer.metadata_address));
+    if metadata_address != Some(*pool_mint_info.key) {
+        msg!(
+            "Pool mint {} must have a metadata pointer to itself",
+            pool_mint_info.key
+        );
+        Err(StakePoolError::InvalidMetadataPointer.into())
+    } else {
+        Ok(())
+    }
+}
+
 /// Check mpl metadata account address for the pool mint
 fn check_mpl_metadata_account_address(
     metadata_address: &Pubkey,
@@ -326,6 +416,83 @@ fn create_stake_account(
     )
 }
 
+/// Create a program-owned account on a PDA, funding its rent from the payer
+fn create_program_account<'a>(
+    program_id: &Pubkey,
+    new_account_info: &AccountInfo<'a>,
+    new_account_signer_seeds: &[&[u8]],
+    space: usize,
+    payer_info: &AccountInfo<'a>,
+    system_program_info: &AccountInfo<'a>,
+) -> Result<(), ProgramError> {
+    let required_lamports = Rent::get()?
+        .minimum_balance(space)
+        .saturating_sub(new_account_info.lamports());
+    if required_lamports > 0 {
+        invoke(
+            &system_instruction::transfer(payer_info.key, new_account_info.key, required_lamports),
+            &[
+                payer_info.clone(),
+                new_account_info.clone(),
+                system_program_info.clone(),
+            ],
+        )?;
+    }
+    invoke_signed(
+        &system_instruction::allocate(new_account_info.key, space as u64),
+        &[new_account_info.clone(), system_program_info.clone()],
+        &[new_account_signer_seeds],
+    )?;
+    invoke_signed(
+        &system_instruction::assign(new_account_info.key, program_id),
+        &[new_account_info.clone(), system_program_info.clone()],
+        &[new_account_signer_seeds],
+    )
+}
+
+/// Get the unstake ticket ledger of a stake pool, if any ticket was ever
+/// created on it
+fn get_unstake_ticket_ledger(
+    program_id: &Pubkey,
+    stake_pool_address: &Pubkey,
+    unstake_ticket_ledger_info: &AccountInfo,
+) -> Result<Option<UnstakeTicketLedger>, ProgramError> {
+    check_unstake_ticket_ledger_address(
+        program_id,
+        stake_pool_address,
+        unstake_ticket_ledger_info.key,
+    )?;
+    if unstake_ticket_ledger_info.data_is_empty() {
+        return Ok(None);
+    }
+    check_account_owner(unstake_ticket_ledger_info, program_id)?;
+    let unstake_ticket_ledger =
+        try_from_slice_unchecked::<UnstakeTicketLedger>(&unstake_ticket_ledger_info.data.borrow())?;
+    if !unstake_ticket_ledger.is_valid() || unstake_ticket_ledger.stake_pool != *stake_pool_address
+    {
+        return Err(StakePoolError::InvalidState.into());
+    }
+    Ok(Some(unstake_ticket_ledger))
+}
+
+/// Deserialize the fee split of a stake pool, if it was ever set
+fn get_fee_split(
+    program_id: &Pubkey,
+    stake_pool_address: &Pubkey,
+    fee_split_info: &AccountInfo,
+) -> Result<Option<FeeSplit>, ProgramError> {
+    check_fee_split_address(program_id, stake_pool_address, fee_split_info.key)?;
+    if fee_split_info.data_is_empty() {
+        return Ok(None);
+    }
+    check_account_owner(fee_split_info, program_id)?;
+    let fee_split = try_from_slice_unchecked::<FeeSplit>(&fee_split_info.data.borrow())?;
+    if !fee_split.is_valid() || fee_split.stake_pool != *stake_pool_address {
+        return Err(StakePoolError::InvalidState.into());
+    }
+    Ok(Some(fee_split))
+}
+
 /// Program state handler.
 pub struct Processor {}
 impl Processor {
@@ -611,7 +778,8 @@ impl Processor {
         invoke_signed(&ix, &[mint, destination, authority], signers)
     }
 
-    /// Issue a spl_token `Transfer` instruction.
+    /// Transfer tokens, resolving the accounts required by the mint's
+    /// transfer hook, if any, from `additional_accounts`
     #[allow(clippy::too_many_arguments)]
     fn token_transfer<'a>(
         token_program: AccountInfo<'a>,
@@ -619,20 +787,21 @@ impl Processor {
         mint: AccountInfo<'a>,
         destination: AccountInfo<'a>,
         authority: AccountInfo<'a>,
+        additional_accounts: &[AccountInfo<'a>],
         amount: u64,
         decimals: u8,
     ) -> Result<(), ProgramError> {
-        let ix = spl_token_2022::instruction::transfer_checked(
+        spl_token_2022::onchain::invoke_transfer_checked(
             token_program.key,
-            source.key,
-            mint.key,
-            destination.key,
-            authority.key,
-            &[],
+            source,
+            mint,
+            destination,
+            authority,
+            additional_accounts,
             amount,
             decimals,
-        )?;
-        invoke(&ix, &[source, mint, destination, authority])
+            &[],
+        )
     }
 
     fn sol_transfer<'a>(
@@ -795,6 +964,15 @@ impl Processor {
             {
                 return Err(StakePoolError::UnsupportedMintExtension.into());
             }
+
+            // a hook swapped in later could block the withdrawal fee transfers
+            if let Ok(transfer_hook) = pool_mint.get_extension::<TransferHook>() {
+                if Option::<Pubkey>::from(transfer_hook.authority)
+                    .is_some_and(|authority| authority != withdraw_authority_key)
+                {
+                    return Err(StakePoolError::InvalidMintTransferHookAuthority.into());
+                }
+            }
         }
         stake_pool.check_manager_fee_info(manager_fee_info)?;
 
@@ -1840,13 +2018,191 @@ impl Processor {
         Ok(())
     }
 
-    /// Processes `UpdateValidatorListBalance` instruction.
+    /// Get the validator policy of a stake pool, if it is set up and enabled
+    fn get_validator_policy(
+        program_id: &Pubkey,
+        stake_pool_address: &Pubkey,
+        validator_policy_info: &AccountInfo,
+    ) -> Result<Option<ValidatorPolicy>, ProgramError> {
+        check_validator_policy_address(program_id, stake_pool_address, validator_policy_info.key)?;
+        if validator_policy_info.data_is_empty() {
+            return Ok(None);
+        }
+        check_account_owner(validator_policy_info, program_id)?;
+        let validator_policy =
+            try_from_slice_unchecked::<ValidatorPolicy>(&validator_policy_info.data.borrow())?;
+        if !validator_policy.is_valid() || validator_policy.stake_pool != *stake_pool_address {
+            return Err(StakePoolError::InvalidState.into());
+        }
+        Ok(Some(validator_policy).filter(ValidatorPolicy::is_enabled))
+    }
+
+    /// Evaluate the validator policy on a vote account
+    #[inline(never)] // needed to keep the vote state off the update stack frame
+    fn check_validator_policy(
+        validator_policy: &ValidatorPolicy,
+        vote_account_info: &AccountInfo,
+        clock: &Clock,
+    ) -> Option<ValidatorPolicyViolation> {
+        let mut vote_state = Box::<VoteState>::default();
+        let vote_state = if *vote_account_info.owner == vote::program::id() {
+            VoteState::deserialize_into(&vote_account_info.data.borrow(), &mut vote_state)
+                .ok()
+                .map(|_| vote_state)
+        } else {
+            None
+        };
+        validator_policy.check_vote_state(vote_state.as_deref(), clock)
+    }
+
+    /// Deactivate the stakes of a validator violating the validator policy,
+    /// marking it for removal as `RemoveValidatorFromPool` does
+    #[allow(clippy::too_many_arguments)]
+    #[inline(never)] // needed to avoid stack size violation
+    fn deactivate_validator_for_policy<'a>(
+        stake_pool_info: &AccountInfo<'a>,
+        stake_pool: &StakePool,
+        withdraw_authority_info: &AccountInfo<'a>,
+        clock_info: &AccountInfo<'a>,
+        validator_stake_info: &AccountInfo<'a>,
+        transient_stake_info: &AccountInfo<'a>,
+        validator_stake_record: &mut ValidatorStakeInfo,
+    ) -> ProgramResult {
+        let new_status = if u64::from(validator_stake_record.transient_stake_lamports) > 0 {
+            match get_stake_state(transient_stake_info) {
+                Ok((meta, stake))
+                    if stake_is_usable_by_pool(
+                        &meta,
+                        withdraw_authority_info.key,
+                        &stake_pool.lockup,
+                    ) =>
+                {
+                    if stake.delegation.deactivation_epoch == Epoch::MAX {
+                        Self::stake_deactivate(
+                            transient_stake_info.clone(),
+                            clock_info.clone(),
+                            withdraw_authority_info.clone(),
+                            stake_pool_info.key,
+                            AUTHORITY_WITHDRAW,
+                            stake_pool.stake_withdraw_bump_seed,
+                        )?;
+                    }
+                    StakeStatus::DeactivatingAll
+                }
+                _ => StakeStatus::DeactivatingValidator,
+            }
+        } else {
+            StakeStatus::DeactivatingValidator
+        };
+
+        match get_stake_state(validator_stake_info) {
+            Ok((meta, stake))
+                if stake_is_usable_by_pool(
+                    &meta,
+                    withdraw_authority_info.key,
+                    &stake_pool.lockup,
+                ) =>
+            {
+                if stake.delegation.deactivation_epoch == Epoch::MAX {
+                    Self::stake_deactivate(
+                        validator_stake_info.clone(),
+                        clock_info.clone(),
+                        withdraw_authority_info.clone(),
+                        stake_pool_info.key,
+                        AUTHORITY_WITHDRAW,
+                        stake_pool.stake_withdraw_bump_seed,
+                    )?;
+                }
+                validator_stake_record.status = new_status.into();
+            }
+            _ => msg!("Validator stake account not usable by the pool, leaving it as is"),
+        }
+        Ok(())
+    }
+
+    /// Record the rewards a validator earned since its last balance update in
+    /// the validator reward history, before the update merges its transient
+    /// stake and withdraws its extra lamports. The stake account addresses
+    /// must be checked by the caller.
+    #[inline(never)] // needed to avoid stack size violation
+    fn record_validator_rewards(
+        validator_reward_history_header: &ValidatorRewardHistoryHeader,
+        reward_big_vec: &mut BigVec,
+        index: usize,
+        validator_stake_record: &ValidatorStakeInfo,
+        validator_stake_info: &AccountInfo,
+        transient_stake_info: &AccountInfo,
+        clock: &Clock,
+    ) -> ProgramResult {
+        // Once updated for the epoch, the transient stake is merged and the
+        // extra lamports are withdrawn, so the rewards cannot be told apart
+        let last_update_epoch = u64::from(validator_stake_record.last_update_epoch);
+        if validator_stake_record.status != StakeStatus::Active.into()
+            || last_update_epoch >= clock.epoch
+        {
+            return Ok(());
+        }
+        let Ok((meta, stake)) = get_stake_state(validator_stake_info) else {
+            return Ok(());
+        };
+
+        // The recorded balances follow every stake movement of the epoch,
+        // so any lamports above them were earned since the last update
+        let transient_stake_lamports =
+            if u64::from(validator_stake_record.transient_stake_lamports) > 0 {
+                transient_stake_info.lamports()
+            } else {
+                0
+            };
+        let stake_lamports = validator_stake_record.stake_lamports()?;
+        let earned_lamports = validator_stake_info
+            .lamports()
+            .saturating_add(transient_stake_lamports)
+            .saturating_sub(stake_lamports);
+        let mev_lamports = validator_stake_info
+            .lamports()
+            .saturating_sub(stake.delegation.stake)
+            .saturating_sub(meta.rent_exempt_reserve)
+            .min(earned_lamports);
+        let rewards_lamports = earned_lamports.saturating_sub(mev_lamports);
+        let epochs = clock.epoch.saturating_sub(last_update_epoch);
+
+        let vote_account_address = validator_stake_record.vote_account_address;
+        match validator_reward_history_header.get_or_insert(
+            reward_big_vec,
+            index,
+            &vote_account_address,
+            last_update_epoch,
+        )? {
+            Some(validator_reward_info)
+                if u64::from(validator_reward_info.last_update_epoch) < clock.epoch =>
+            {
+                validator_reward_info.record(
+                    clock.epoch,
+                    epochs,
+                    stake_lamports,
+                    rewards_lamports,
+                    mev_lamports,
+                );
+            }
+            Some(_) => {}
+            None => msg!(
+                "Validator reward history is full, rewards of {} not recorded",
+                vote_account_address
+            ),
+        }
+        Ok(())
+    }
+
+    /// Processes `UpdateValidatorListBalance` and
+    /// `UpdateValidatorListBalanceWithPolicy` instructions.
     #[inline(always)] // needed to maximize number of validators
     fn process_update_validator_list_balance(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
         start_index: u32,
         no_merge: bool,
+        with_policy: bool,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let stake_pool_info = next_account_info(account_info_iter)?;
@@ -1857,10 +2213,36 @@ impl Processor {
         let clock = &Clock::from_account_info(clock_info)?;
         let stake_history_info = next_account_info(account_info_iter)?;
         let stake_program_info = next_account_info(account_info_iter)?;
+        let validator_policy = if with_policy {
+            let validator_policy_info = next_account_info(account_info_iter)?;
+            Self::get_validator_policy(program_id, stake_pool_info.key, validator_policy_info)?
+        } else {
+            None
+        };
         let validator_stake_accounts = account_info_iter.as_slice();
+        // validator and transient stake accounts, followed by the vote account
+        // when evaluating the validator policy
+        let accounts_per_validator = if with_policy { 3 } else { 2 };
+        // optionally followed by the validator reward history
+        let (validator_stake_accounts, validator_reward_history_info) =
+            match validator_stake_accounts
+                .len()
+                .checked_rem(accounts_per_validator)
+                .ok_or(StakePoolError::CalculationFailure)?
+            {
+                0 => (validator_stake_accounts, None),
+                1 => match validator_stake_accounts.split_last() {
+                    Some((last, rest)) => (rest, Some(last)),
+                    None => (validator_stake_accounts, None),
+                },
+                _ => {
+                    msg!("Wrong number of validator accounts passed in, should be triples of validator stake, transient stake and vote accounts, optionally followed by the validator reward history");
+                    return Err(StakePoolError::UnexpectedValidatorListAccountSize.into());
+                }
+            };
 
         check_account_owner(stake_pool_info, program_id)?;
-        let stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        let mut stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
         if !stake_pool.is_valid() {
             return Err(StakePoolError::InvalidState.into());
         }
@@ -1873,15 +2255,24 @@ impl Processor {
         stake_pool.check_reserve_stake(reserve_stake_info)?;
         check_stake_program(stake_program_info.key)?;
 
-        if validator_stake_accounts
-            .len()
-            .checked_rem(2)
-            .ok_or(StakePoolError::CalculationFailure)?
-            != 0
-        {
-            msg!("Odd number of validator stake accounts passed in, should be pairs of validator stake and transient stake accounts");
-            return Err(StakePoolError::UnexpectedValidatorListAccountSize.into());
-        }
+        let mut validator_reward_history_data = match validator_reward_history_info {
+            Some(validator_reward_history_info) => {
+                check_account_owner(validator_reward_history_info, program_id)?;
+                Some(validator_reward_history_info.data.borrow_mut())
+            }
+            None => None,
+        };
+        let mut validator_reward_history = match validator_reward_history_data.as_mut() {
+            Some(data) => {
+                let (header, reward_big_vec) = ValidatorRewardHistoryHeader::deserialize_vec(data)?;
+                if !header.is_valid() || header.stake_pool != *stake_pool_info.key {
+                    return Err(StakePoolError::InvalidValidatorRewardHistory.into());
+                }
+                Some((header, reward_big_vec))
+            }
+            None => None,
+        };
+        let mut stake_pool_changed = false;
 
         check_account_owner(validator_list_info, program_id)?;
         let mut validator_list_data = validator_list_info.data.borrow_mut();
@@ -1890,7 +2281,7 @@ impl Processor {
         let validator_slice = ValidatorListHeader::deserialize_mut_slice(
             &mut big_vec,
             start_index as usize,
-            validator_stake_accounts.len() / 2,
+            validator_stake_accounts.len() / accounts_per_validator,
         )?;
 
         if !validator_list_header.is_valid() {
@@ -1899,15 +2290,22 @@ impl Processor {
 
         let validator_iter = &mut validator_slice
             .iter_mut()
-            .zip(validator_stake_accounts.chunks_exact(2));
-        for (validator_stake_record, validator_stakes) in validator_iter {
-            // chunks_exact means that we always get 2 elements, making this safe
+            .zip(validator_stake_accounts.chunks_exact(accounts_per_validator))
+            .enumerate();
+        for (index, (validator_stake_record, validator_stakes)) in validator_iter {
+            // chunks_exact means that we always get all elements, making this safe
             let validator_stake_info = validator_stakes
                 .first()
                 .ok_or(ProgramError::InvalidInstructionData)?;
             let transient_stake_info = validator_stakes
-                .last()
+                .get(1)
                 .ok_or(ProgramError::InvalidInstructionData)?;
+            let vote_account_info = validator_stakes.get(2);
+            if vote_account_info
+                .is_some_and(|info| *info.key != validator_stake_record.vote_account_address)
+            {
+                continue;
+            }
             if check_validator_stake_address(
                 program_id,
                 stake_pool_info.key,
@@ -1930,6 +2328,26 @@ impl Processor {
             {
                 continue;
             };
+            if with_policy {
+                validator_stake_record.set_policy_enforced(validator_policy.is_some());
+            } else if validator_stake_record.is_policy_enforced() {
+                msg!(
+                    "Validator {} must be updated under the validator policy",
+                    validator_stake_record.vote_account_address
+                );
+                return Err(StakePoolError::ValidatorPolicyRequired.into());
+            }
+            if let Some((header, reward_big_vec)) = validator_reward_history.as_mut() {
+                Self::record_validator_rewards(
+                    header,
+                    reward_big_vec,
+                    (start_index as usize).saturating_add(index),
+                    validator_stake_record,
+                    validator_stake_info,
+                    transient_stake_info,
+                    clock,
+                )?;
+            }
 
             let mut active_stake_lamports = 0;
             let mut transient_stake_lamports = 0;
@@ -2129,8 +2547,52 @@ impl Processor {
             validator_stake_record.last_update_epoch = clock.epoch.into();
             validator_stake_record.active_stake_lamports = active_stake_lamports.into();
             validator_stake_record.transient_stake_lamports = transient_stake_lamports.into();
+
+            // The balances stay accounted for this epoch, the deactivated stake
+            // is merged into the reserve by the next epoch's update
+            if let (Some(validator_policy), Some(vote_account_info)) =
+                (validator_policy.as_ref(), vote_account_info)
+            {
+                if validator_stake_record.status != StakeStatus::Active.into() {
+                    continue;
+                }
+                if let Some(violation) =
+                    Self::check_validator_policy(validator_policy, vote_account_info, clock)
+                {
+                    let vote_account_address = validator_stake_record.vote_account_address;
+                    msg!(
+                        "Validator {} violates the validator policy: {}",
+                        vote_account_address,
+                        violation
+                    );
+                    Self::deactivate_validator_for_policy(
+                        stake_pool_info,
+                        &stake_pool,
+                        withdraw_authority_info,
+                        clock_info,
+                        validator_stake_info,
+                        transient_stake_info,
+                        validator_stake_record,
+                    )?;
+                    if stake_pool.preferred_deposit_validator_vote_address
+                        == Some(vote_account_address)
+                    {
+                        stake_pool.preferred_deposit_validator_vote_address = None;
+                        stake_pool_changed = true;
+                    }
+                    if stake_pool.preferred_withdraw_validator_vote_address
+                        == Some(vote_account_address)
+                    {
+                        stake_pool.preferred_withdraw_validator_vote_address = None;
+                        stake_pool_changed = true;
+                    }
+                }
+            }
         }
 
+        if stake_pool_changed {
+            borsh::to_writer(&mut stake_pool_info.data.borrow_mut()[..], &stake_pool)?;
+        }
         Ok(())
     }
 
@@ -2148,6 +2610,19 @@ impl Processor {
         let manager_fee_info = next_account_info(account_info_iter)?;
         let pool_mint_info = next_account_info(account_info_iter)?;
         let token_program_info = next_account_info(account_info_iter)?;
+        // the unstake ticket ledger and fee split accounts were added after
+        // this instruction, so older clients may leave them out
+        let unstake_ticket_ledger_accounts = match account_info_iter.next() {
+            Some(unstake_ticket_ledger_info) => {
+                let clock_info = next_account_info(account_info_iter)?;
+                let stake_history_info = next_account_info(account_info_iter)?;
+                let stake_program_info = next_account_info(account_info_iter)?;
+                check_stake_program(stake_program_info.key)?;
+                Some((unstake_ticket_ledger_info, clock_info, stake_history_info))
+            }
+            None => None,
+        };
+        let fee_split_info = account_info_iter.next();
         let clock = Clock::get()?;
 
         check_account_owner(stake_pool_info, program_id)?;
@@ -2192,6 +2667,53 @@ impl Processor {
                 msg!("Reserve stake account in unknown state, aborting");
                 return Err(StakePoolError::WrongStakeStake.into());
             };
+
+        // Lamports owed to unstake tickets are not part of the pool anymore:
+        // fund the ledger with as much as the reserve can, and leave the rest
+        // out of the total until the staker deactivates enough stake
+        let mut unfunded_ticket_lamports = 0;
+        let unstake_ticket_ledger = match unstake_ticket_ledger_accounts {
+            Some((unstake_ticket_ledger_info, _, _)) => get_unstake_ticket_ledger(
+                program_id,
+                stake_pool_info.key,
+                unstake_ticket_ledger_info,
+            )?,
+            None => None,
+        };
+        if let (
+            Some(mut unstake_ticket_ledger),
+            Some((unstake_ticket_ledger_info, clock_info, stake_history_info)),
+        ) = (unstake_ticket_ledger, unstake_ticket_ledger_accounts)
+        {
+            let funding_lamports =
+                std::cmp::min(unstake_ticket_ledger.unfunded_lamports(), total_lamports);
+            if funding_lamports > 0 {
+                Self::stake_withdraw(
+                    stake_pool_info.key,
+                    reserve_stake_info.clone(),
+                    withdraw_info.clone(),
+                    AUTHORITY_WITHDRAW,
+                    stake_pool.stake_withdraw_bump_seed,
+                    unstake_ticket_ledger_info.clone(),
+                    clock_info.clone(),
+                    stake_history_info.clone(),
+                    funding_lamports,
+                )?;
+                total_lamports = total_lamports
+                    .checked_sub(funding_lamports)
+                    .ok_or(StakePoolError::CalculationFailure)?;
+                unstake_ticket_ledger.funded_lamports = unstake_ticket_ledger
+                    .funded_lamports
+                    .checked_add(funding_lamports)
+                    .ok_or(StakePoolError::CalculationFailure)?;
+                borsh::to_writer(
+                    &mut unstake_ticket_ledger_info.data.borrow_mut()[..],
+                    &unstake_ticket_ledger,
+                )?;
+            }
+            unfunded_ticket_lamports = unstake_ticket_ledger.unfunded_lamports();
+        }
+
         for validator_stake_record in validator_list
             .deserialize_slice::<ValidatorStakeInfo>(0, validator_list.len() as usize)?
         {
@@ -2202,32 +2724,76 @@ impl Processor {
                 .checked_add(validator_stake_record.stake_lamports()?)
                 .ok_or(StakePoolError::CalculationFailure)?;
         }
+        let total_lamports = total_lamports
+            .checked_sub(unfunded_ticket_lamports)
+            .ok_or(StakePoolError::CalculationFailure)?;
 
         let reward_lamports = total_lamports.saturating_sub(previous_lamports);
 
-        // If the manager fee info is invalid, they don't deserve to receive the fee.
-        let fee = if stake_pool.check_manager_fee_info(manager_fee_info).is_ok() {
-            stake_pool
-                .calc_epoch_fee_amount(reward_lamports)
-                .ok_or(StakePoolError::CalculationFailure)?
-        } else {
-            0
+        let fee_split = match fee_split_info {
+            Some(fee_split_info) => get_fee_split(program_id, stake_pool_info.key, fee_split_info)?,
+            None => None,
         };
+        let mut fee_accounts = vec![];
+        match fee_split.as_ref().filter(|x| !x.recipients.is_empty()) {
+            Some(fee_split) => {
+                let fee = stake_pool
+                    .calc_epoch_fee_amount(reward_lamports)
+                    .ok_or(StakePoolError::CalculationFailure)?;
+                let shares = fee_split
+                    .fee_shares(fee)
+                    .ok_or(StakePoolError::CalculationFailure)?;
+                for (recipient, share) in fee_split.recipients.iter().zip(shares) {
+                    let recipient_info = next_account_info(account_info_iter)?;
+                    if recipient.token_account != *recipient_info.key {
+                        msg!(
+                            "Incorrect fee recipient, expected {}, received {}",
+                            recipient.token_account,
+                            recipient_info.key
+                        );
+                        return Err(StakePoolError::InvalidFeeAccount.into());
+                    }
+                    // If a recipient is invalid, they don't deserve to receive their share.
+                    if stake_pool.check_manager_fee_info(recipient_info).is_ok() {
+                        fee_accounts.push((recipient_info, share));
+                    }
+                }
+            }
+            None => {
+                // If the manager fee info is invalid, they don't deserve to receive the fee.
+                if stake_pool.check_manager_fee_info(manager_fee_info).is_ok() {
+                    let fee = stake_pool
+                        .calc_epoch_fee_amount(reward_lamports)
+                        .ok_or(StakePoolError::CalculationFailure)?;
+                    fee_accounts.push((manager_fee_info, fee));
+                }
+            }
+        }
 
-        if fee > 0 {
-            Self::token_mint_to(
-                stake_pool_info.key,
-                token_program_info.clone(),
-                pool_mint_info.clone(),
-                manager_fee_info.clone(),
-                withdraw_info.clone(),
-                AUTHORITY_WITHDRAW,
-                stake_pool.stake_withdraw_bump_seed,
-                fee,
-            )?;
+        for (fee_info, fee) in fee_accounts {
+            if fee > 0 {
+                Self::token_mint_to(
+                    stake_pool_info.key,
+                    token_program_info.clone(),
+                    pool_mint_info.clone(),
+                    fee_info.clone(),
+                    withdraw_info.clone(),
+                    AUTHORITY_WITHDRAW,
+                    stake_pool.stake_withdraw_bump_seed,
+                    fee,
+                )?;
+            }
         }
 
         if stake_pool.last_update_epoch < clock.epoch {
+            if let (Some(mut fee_split), Some(fee_split_info)) = (fee_split, fee_split_info) {
+                if let Some(recipients) = fee_split.next_recipients.get() {
+                    fee_split.recipients.clone_from(recipients);
+                }
+                fee_split.next_recipients.update_epoch();
+                borsh::to_writer(&mut fee_split_info.data.borrow_mut()[..], &fee_split)?;
+            }
+
             if let Some(fee) = stake_pool.next_epoch_fee.get() {
                 stake_pool.epoch_fee = *fee;
             }
@@ -2267,6 +2833,7 @@ impl Processor {
         let account_info_iter = &mut accounts.iter();
         let stake_pool_info = next_account_info(account_info_iter)?;
         let validator_list_info = next_account_info(account_info_iter)?;
+        let validator_reward_history_info = account_info_iter.next();
 
         check_account_owner(stake_pool_info, program_id)?;
         let stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
@@ -2283,6 +2850,34 @@ impl Processor {
             return Err(StakePoolError::InvalidState.into());
         }
 
+        // the reward entries of the removed validators are dropped along with
+        // them, so the reward history never fills up with stale validators
+        if let Some(validator_reward_history_info) = validator_reward_history_info {
+            check_account_owner(validator_reward_history_info, program_id)?;
+            let mut validator_reward_history_data = validator_reward_history_info.data.borrow_mut();
+            let (validator_reward_history_header, mut reward_big_vec) =
+                ValidatorRewardHistoryHeader::deserialize_vec(&mut validator_reward_history_data)?;
+            if !validator_reward_history_header.is_valid()
+                || validator_reward_history_header.stake_pool != *stake_pool_info.key
+            {
+                return Err(StakePoolError::InvalidValidatorRewardHistory.into());
+            }
+
+            let removed_vote_account_addresses = validator_list
+                .deserialize_slice::<ValidatorStakeInfo>(0, validator_list.len() as usize)?
+                .iter()
+                .filter(|validator| validator.status == StakeStatus::ReadyForRemoval.into())
+                .map(|validator| validator.vote_account_address)
+                .collect::<Vec<_>>();
+            if !removed_vote_account_addresses.is_empty() {
+                reward_big_vec.retain::<ValidatorRewardInfo, _>(|data| {
+                    !removed_vote_account_addresses
+                        .iter()
+                        .any(|address| address.as_ref() == &data[..PUBKEY_BYTES])
+                })?;
+            }
+        }
+
         validator_list.retain::<ValidatorStakeInfo, _>(ValidatorStakeInfo::is_not_removed)?;
 
         Ok(())
@@ -2724,6 +3319,7 @@ impl Processor {
         let clock = &Clock::from_account_info(clock_info)?;
         let token_program_info = next_account_info(account_info_iter)?;
         let stake_program_info = next_account_info(account_info_iter)?;
+        let transfer_hook_accounts = account_info_iter.as_slice();
 
         check_stake_program(stake_program_info.key)?;
         check_account_owner(stake_pool_info, program_id)?;
@@ -2964,6 +3560,7 @@ impl Processor {
                 pool_mint_info.clone(),
                 manager_fee_info.clone(),
                 user_transfer_authority_info.clone(),
+                transfer_hook_accounts,
                 pool_tokens_fee,
                 decimals,
             )?;
@@ -3037,6 +3634,8 @@ impl Processor {
         let stake_history_info = next_account_info(account_info_iter)?;
         let stake_program_info = next_account_info(account_info_iter)?;
         let token_program_info = next_account_info(account_info_iter)?;
+        // transfer hook accounts are looked up by key, after the optional authority
+        let transfer_hook_accounts = account_info_iter.as_slice();
         let sol_withdraw_authority_info = next_account_info(account_info_iter);
 
         check_account_owner(stake_pool_info, program_id)?;
@@ -3134,6 +3733,7 @@ impl Processor {
                 pool_mint_info.clone(),
                 manager_fee_info.clone(),
                 user_transfer_authority_info.clone(),
+                transfer_hook_accounts,
                 pool_tokens_fee,
                 decimals,
             )?;
@@ -3318,120 +3918,942 @@ impl Processor {
         Ok(())
     }
 
-    /// Processes [SetManager](enum.Instruction.html).
-    #[inline(never)] // needed to avoid stack size violation
-    fn process_set_manager(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
-        let account_info_iter = &mut accounts.iter();
-        let stake_pool_info = next_account_info(account_info_iter)?;
-        let manager_info = next_account_info(account_info_iter)?;
-        let new_manager_info = next_account_info(account_info_iter)?;
-        let new_manager_fee_info = next_account_info(account_info_iter)?;
+    #[inline(never)]
+    fn process_create_pool_token_2022_metadata(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        name: String,
+        symbol: String,
+        uri: String,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
+        let withdraw_authority_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        if !payer_info.is_signer {
+            msg!("Payer did not sign metadata creation");
+            return Err(StakePoolError::SignatureMissing.into());
+        }
+
+        check_system_program(system_program_info.key)?;
+        check_account_owner(stake_pool_info, program_id)?;
+
+        let stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        if !stake_pool.is_valid() {
+            return Err(StakePoolError::InvalidState.into());
+        }
+
+        stake_pool.check_manager(manager_info)?;
+        stake_pool.check_authority_withdraw(
+            withdraw_authority_info.key,
+            program_id,
+            stake_pool_info.key,
+        )?;
+        stake_pool.check_mint(pool_mint_info)?;
+        if stake_pool.token_program_id != *token_program_info.key
+            || *token_program_info.key != spl_token_2022::id()
+        {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        check_self_metadata_pointer(pool_mint_info)?;
+
+        // The withdraw authority mints the pool tokens, and also updates the
+        // metadata
+        let token_metadata = TokenMetadata {
+            update_authority: Some(*withdraw_authority_info.key).try_into()?,
+            mint: *pool_mint_info.key,
+            name,
+            symbol,
+            uri,
+            additional_metadata: vec![],
+        };
+        let new_len = pool_mint_info
+            .data_len()
+            .checked_add(token_metadata.tlv_size_of()?)
+            .ok_or(StakePoolError::CalculationFailure)?;
+        Self::fund_pool_mint_realloc(payer_info, pool_mint_info, new_len)?;
+
+        let initialize_instruction = spl_token_metadata_interface::instruction::initialize(
+            token_program_info.key,
+            pool_mint_info.key,
+            withdraw_authority_info.key,
+            pool_mint_info.key,
+            withdraw_authority_info.key,
+            token_metadata.name,
+            token_metadata.symbol,
+            token_metadata.uri,
+        );
+
+        let token_mint_authority_signer_seeds: &[&[_]] = &[
+            stake_pool_info.key.as_ref(),
+            AUTHORITY_WITHDRAW,
+            &[stake_pool.stake_withdraw_bump_seed],
+        ];
+
+        invoke_signed(
+            &initialize_instruction,
+            &[pool_mint_info.clone(), withdraw_authority_info.clone()],
+            &[token_mint_authority_signer_seeds],
+        )?;
+
+        Ok(())
+    }
+
+    #[inline(never)]
+    fn process_update_pool_token_2022_metadata(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        name: String,
+        symbol: String,
+        uri: String,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
+        let withdraw_authority_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        check_system_program(system_program_info.key)?;
+        check_account_owner(stake_pool_info, program_id)?;
+
+        let stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        if !stake_pool.is_valid() {
+            return Err(StakePoolError::InvalidState.into());
+        }
+
+        stake_pool.check_manager(manager_info)?;
+        stake_pool.check_authority_withdraw(
+            withdraw_authority_info.key,
+            program_id,
+            stake_pool_info.key,
+        )?;
+        stake_pool.check_mint(pool_mint_info)?;
+        if stake_pool.token_program_id != *token_program_info.key
+            || *token_program_info.key != spl_token_2022::id()
+        {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        check_self_metadata_pointer(pool_mint_info)?;
+
+        let mut token_metadata = {
+            let mint_data = pool_mint_info.try_borrow_data()?;
+            let mint = StateWithExtensions::<Mint>::unpack(&mint_data)?;
+            mint.get_variable_len_extension::<TokenMetadata>()?
+        };
+
+        // Every field is updated separately, so the mint must stay rent-exempt
+        // at the largest intermediate size
+        let current_size = token_metadata.tlv_size_of()?;
+        let mut max_size = current_size;
+        let mut updates = vec![];
+        for (field, value) in [
+            (Field::Name, name),
+            (Field::Symbol, symbol),
+            (Field::Uri, uri),
+        ] {
+            let mut updated_metadata = token_metadata.clone();
+            updated_metadata.update(field.clone(), value.clone());
+            if updated_metadata != token_metadata {
+                max_size = max_size.max(updated_metadata.tlv_size_of()?);
+                token_metadata = updated_metadata;
+                updates.push((field, value));
+            }
+        }
+        let new_len = pool_mint_info
+            .data_len()
+            .saturating_sub(current_size)
+            .checked_add(max_size)
+            .ok_or(StakePoolError::CalculationFailure)?;
+        Self::fund_pool_mint_realloc(payer_info, pool_mint_info, new_len)?;
+
+        let token_mint_authority_signer_seeds: &[&[_]] = &[
+            stake_pool_info.key.as_ref(),
+            AUTHORITY_WITHDRAW,
+            &[stake_pool.stake_withdraw_bump_seed],
+        ];
+
+        for (field, value) in updates {
+            let update_field_instruction = spl_token_metadata_interface::instruction::update_field(
+                token_program_info.key,
+                pool_mint_info.key,
+                withdraw_authority_info.key,
+                field,
+                value,
+            );
+            invoke_signed(
+                &update_field_instruction,
+                &[pool_mint_info.clone(), withdraw_authority_info.clone()],
+                &[token_mint_authority_signer_seeds],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Top up the pool mint so that it stays rent-exempt once token-2022
+    /// reallocates it to `new_len`
+    fn fund_pool_mint_realloc<'a>(
+        payer_info: &AccountInfo<'a>,
+        pool_mint_info: &AccountInfo<'a>,
+        new_len: usize,
+    ) -> ProgramResult {
+        let required_lamports = Rent::get()?
+            .minimum_balance(new_len)
+            .saturating_sub(pool_mint_info.lamports());
+        if required_lamports > 0 {
+            Self::sol_transfer(
+                payer_info.clone(),
+                pool_mint_info.clone(),
+                required_lamports,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Processes [SetManager](enum.Instruction.html).
+    #[inline(never)] // needed to avoid stack size violation
+    fn process_set_manager(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
+        let new_manager_info = next_account_info(account_info_iter)?;
+        let new_manager_fee_info = next_account_info(account_info_iter)?;
+
+        check_account_owner(stake_pool_info, program_id)?;
+        let mut stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        check_account_owner(new_manager_fee_info, &stake_pool.token_program_id)?;
+        if !stake_pool.is_valid() {
+            return Err(StakePoolError::InvalidState.into());
+        }
+
+        stake_pool.check_manager(manager_info)?;
+        if !new_manager_info.is_signer {
+            msg!("New manager signature missing");
+            return Err(StakePoolError::SignatureMissing.into());
+        }
+
+        stake_pool.check_manager_fee_info(new_manager_fee_info)?;
+
+        stake_pool.manager = *new_manager_info.key;
+        stake_pool.manager_fee_account = *new_manager_fee_info.key;
+        borsh::to_writer(&mut stake_pool_info.data.borrow_mut()[..], &stake_pool)?;
+        Ok(())
+    }
+
+    /// Processes [SetFee](enum.Instruction.html).
+    #[inline(never)] // needed to avoid stack size violation
+    fn process_set_fee(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        fee: FeeType,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
+        let clock = Clock::get()?;
+
+        check_account_owner(stake_pool_info, program_id)?;
+        let mut stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        if !stake_pool.is_valid() {
+            return Err(StakePoolError::InvalidState.into());
+        }
+        stake_pool.check_manager(manager_info)?;
+
+        if fee.can_only_change_next_epoch() && stake_pool.last_update_epoch < clock.epoch {
+            return Err(StakePoolError::StakeListAndPoolOutOfDate.into());
+        }
+
+        fee.check_too_high()?;
+        stake_pool.update_fee(&fee)?;
+        borsh::to_writer(&mut stake_pool_info.data.borrow_mut()[..], &stake_pool)?;
+        Ok(())
+    }
+
+    /// Processes [SetStaker](enum.Instruction.html).
+    #[inline(never)] // needed to avoid stack size violation
+    fn process_set_staker(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let set_staker_authority_info = next_account_info(account_info_iter)?;
+        let new_staker_info = next_account_info(account_info_iter)?;
+
+        check_account_owner(stake_pool_info, program_id)?;
+        let mut stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        if !stake_pool.is_valid() {
+            return Err(StakePoolError::InvalidState.into());
+        }
+
+        let staker_signed = stake_pool.check_staker(set_staker_authority_info);
+        let manager_signed = stake_pool.check_manager(set_staker_authority_info);
+        if staker_signed.is_err() && manager_signed.is_err() {
+            return Err(StakePoolError::SignatureMissing.into());
+        }
+        stake_pool.staker = *new_staker_info.key;
+        borsh::to_writer(&mut stake_pool_info.data.borrow_mut()[..], &stake_pool)?;
+        Ok(())
+    }
+
+    /// Processes [SetFundingAuthority](enum.Instruction.html).
+    #[inline(never)] // needed to avoid stack size violation
+    fn process_set_funding_authority(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        funding_type: FundingType,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
+
+        let new_authority = next_account_info(account_info_iter)
+            .ok()
+            .map(|new_authority_account_info| *new_authority_account_info.key);
+
+        check_account_owner(stake_pool_info, program_id)?;
+        let mut stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        if !stake_pool.is_valid() {
+            return Err(StakePoolError::InvalidState.into());
+        }
+        stake_pool.check_manager(manager_info)?;
+        match funding_type {
+            FundingType::StakeDeposit => {
+                stake_pool.stake_deposit_authority = new_authority.unwrap_or(
+                    find_deposit_authority_program_address(program_id, stake_pool_info.key).0,
+                );
+            }
+            FundingType::SolDeposit => stake_pool.sol_deposit_authority = new_authority,
+            FundingType::SolWithdraw => stake_pool.sol_withdraw_authority = new_authority,
+        }
+        borsh::to_writer(&mut stake_pool_info.data.borrow_mut()[..], &stake_pool)?;
+        Ok(())
+    }
+
+    /// Processes [SetValidatorPolicy](enum.Instruction.html).
+    #[inline(never)] // needed to avoid stack size violation
+    fn process_set_validator_policy(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        max_commission: u8,
+        min_epoch_credits_bps: u16,
+        max_delinquent_slots: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let staker_info = next_account_info(account_info_iter)?;
+        let validator_policy_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        check_system_program(system_program_info.key)?;
+        check_account_owner(stake_pool_info, program_id)?;
+        let stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        if !stake_pool.is_valid() {
+            return Err(StakePoolError::InvalidState.into());
+        }
+        stake_pool.check_staker(staker_info)?;
+        let bump_seed = check_validator_policy_address(
+            program_id,
+            stake_pool_info.key,
+            validator_policy_info.key,
+        )?;
+
+        if max_commission > 100 || min_epoch_credits_bps > ValidatorPolicy::MAX_EPOCH_CREDITS_BPS {
+            return Err(StakePoolError::InvalidValidatorPolicy.into());
+        }
+
+        let mut validator_policy = if validator_policy_info.data_is_empty() {
+            let validator_policy_signer_seeds: &[&[_]] = &[
+                VALIDATOR_POLICY_SEED_PREFIX,
+                stake_pool_info.key.as_ref(),
+                &[bump_seed],
+            ];
+            create_program_account(
+                program_id,
+                validator_policy_info,
+                validator_policy_signer_seeds,
+                get_packed_len::<ValidatorPolicy>(),
+                payer_info,
+                system_program_info,
+            )?;
+            ValidatorPolicy {
+                account_type: AccountType::ValidatorPolicy,
+                stake_pool: *stake_pool_info.key,
+                ..ValidatorPolicy::default()
+            }
+        } else {
+            check_account_owner(validator_policy_info, program_id)?;
+            let validator_policy =
+                try_from_slice_unchecked::<ValidatorPolicy>(&validator_policy_info.data.borrow())?;
+            if !validator_policy.is_valid() || validator_policy.stake_pool != *stake_pool_info.key {
+                return Err(StakePoolError::InvalidState.into());
+            }
+            validator_policy
+        };
+
+        validator_policy.max_commission = max_commission;
+        validator_policy.min_epoch_credits_bps = min_epoch_credits_bps;
+        validator_policy.max_delinquent_slots = max_delinquent_slots;
+        borsh::to_writer(
+            &mut validator_policy_info.data.borrow_mut()[..],
+            &validator_policy,
+        )?;
+        Ok(())
+    }
+
+    /// Processes [SetClusterEpochCredits](enum.Instruction.html).
+    #[inline(never)] // needed to avoid stack size violation
+    fn process_set_cluster_epoch_credits(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        epoch: u64,
+        epoch_credits: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let staker_info = next_account_info(account_info_iter)?;
+        let validator_policy_info = next_account_info(account_info_iter)?;
+        let clock = Clock::get()?;
+
+        check_account_owner(stake_pool_info, program_id)?;
+        let stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        if !stake_pool.is_valid() {
+            return Err(StakePoolError::InvalidState.into());
+        }
+        stake_pool.check_staker(staker_info)?;
+        check_validator_policy_address(program_id, stake_pool_info.key, validator_policy_info.key)?;
+        check_account_owner(validator_policy_info, program_id)?;
+        let mut validator_policy =
+            try_from_slice_unchecked::<ValidatorPolicy>(&validator_policy_info.data.borrow())?;
+        if !validator_policy.is_valid() || validator_policy.stake_pool != *stake_pool_info.key {
+            return Err(StakePoolError::InvalidState.into());
+        }
+
+        if epoch >= clock.epoch {
+            return Err(StakePoolError::InvalidClusterEpochCredits.into());
+        }
+
+        validator_policy.cluster_credits_epoch = epoch;
+        validator_policy.cluster_epoch_credits = epoch_credits;
+        borsh::to_writer(
+            &mut validator_policy_info.data.borrow_mut()[..],
+            &validator_policy,
+        )?;
+        Ok(())
+    }
+
+    /// Processes [SetFeeSplit](enum.Instruction.html).
+    #[inline(never)] // needed to avoid stack size violation
+    fn process_set_fee_split(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        recipients: Vec<FeeRecipient>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let manager_info = next_account_info(account_info_iter)?;
+        let fee_split_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let clock = Clock::get()?;
+
+        check_system_program(system_program_info.key)?;
+        check_account_owner(stake_pool_info, program_id)?;
+        let stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        if !stake_pool.is_valid() {
+            return Err(StakePoolError::InvalidState.into());
+        }
+        stake_pool.check_manager(manager_info)?;
+        let bump_seed =
+            check_fee_split_address(program_id, stake_pool_info.key, fee_split_info.key)?;
+
+        // Same as epoch fee changes, the countdown only holds on an updated pool
+        if stake_pool.last_update_epoch < clock.epoch {
+            return Err(StakePoolError::StakeListAndPoolOutOfDate.into());
+        }
+
+        FeeSplit::check_recipients(&recipients)?;
+        for recipient in &recipients {
+            let recipient_info = next_account_info(account_info_iter)?;
+            if recipient.token_account != *recipient_info.key {
+                return Err(StakePoolError::InvalidFeeAccount.into());
+            }
+            check_account_owner(recipient_info, &stake_pool.token_program_id)?;
+            stake_pool.check_manager_fee_info(recipient_info)?;
+        }
+
+        let mut fee_split = if fee_split_info.data_is_empty() {
+            let fee_split_signer_seeds: &[&[_]] = &[
+                FEE_SPLIT_SEED_PREFIX,
+                stake_pool_info.key.as_ref(),
+                &[bump_seed],
+            ];
+            create_program_account(
+                program_id,
+                fee_split_info,
+                fee_split_signer_seeds,
+                FeeSplit::LEN,
+                payer_info,
+                system_program_info,
+            )?;
+            FeeSplit {
+                account_type: AccountType::FeeSplit,
+                stake_pool: *stake_pool_info.key,
+                ..FeeSplit::default()
+            }
+        } else {
+            get_fee_split(program_id, stake_pool_info.key, fee_split_info)?
+                .ok_or(StakePoolError::InvalidState)?
+        };
+
+        fee_split.next_recipients = FutureEpoch::new(recipients);
+        borsh::to_writer(&mut fee_split_info.data.borrow_mut()[..], &fee_split)?;
+        Ok(())
+    }
+
+    /// Processes [CreateUnstakeTicket](enum.Instruction.html).
+    #[inline(never)] // needed to avoid stack size violation
+    fn process_create_unstake_ticket(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        pool_tokens: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stake_pool_info = next_account_info(account_info_iter)?;
+        let unstake_ticket_ledger_info = next_account_info(account_info_iter)?;
+        let unstake_ticket_info = next_account_info(account_info_iter)?;
+        let ticket_owner_info = next_account_info(account_info_iter)?;
+        let user_transfer_authority_info = next_account_info(account_info_iter)?;
+        let burn_from_pool_info = next_account_info(account_info_iter)?;
+        let manager_fee_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        // transfer hook accounts are looked up by key, after the optional authority
+        let transfer_hook_accounts = account_info_iter.as_slice();
+        let sol_withdraw_authority_info = next_account_info(account_info_iter);
+
+        check_system_program(system_program_info.key)?;
+        check_account_owner(stake_pool_info, program_id)?;
+        let mut stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        if !stake_pool.is_valid() {
+            return Err(StakePoolError::InvalidState.into());
+        }
+        stake_pool.check_sol_withdraw_authority(sol_withdraw_authority_info)?;
+        let decimals = stake_pool.check_mint(pool_mint_info)?;
+        if stake_pool.token_program_id != *token_program_info.key {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if stake_pool.manager_fee_account != *manager_fee_info.key {
+            return Err(StakePoolError::InvalidFeeAccount.into());
+        }
+
+        let clock = Clock::get()?;
+        // Tickets burn pool tokens at the current rate, same as withdrawals
+        if stake_pool.last_update_epoch < clock.epoch {
+            return Err(StakePoolError::StakeListAndPoolOutOfDate.into());
+        }
 
-        check_account_owner(stake_pool_info, program_id)?;
-        let mut stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
-        check_account_owner(new_manager_fee_info, &stake_pool.token_program_id)?;
-        if !stake_pool.is_valid() {
-            return Err(StakePoolError::InvalidState.into());
+        check_account_owner(unstake_ticket_info, program_id)?;
+        let unstake_ticket_len = get_packed_len::<UnstakeTicket>();
+        if unstake_ticket_info.data_len() != unstake_ticket_len {
+            msg!(
+                "Unstake ticket account has {} bytes, expected {}",
+                unstake_ticket_info.data_len(),
+                unstake_ticket_len
+            );
+            return Err(StakePoolError::InvalidUnstakeTicket.into());
+        }
+        let unstake_ticket =
+            try_from_slice_unchecked::<UnstakeTicket>(&unstake_ticket_info.data.borrow())?;
+        if unstake_ticket.account_type != AccountType::Uninitialized {
+            return Err(StakePoolError::AlreadyInUse.into());
+        }
+        if !Rent::get()?.is_exempt(unstake_ticket_info.lamports(), unstake_ticket_len) {
+            msg!("Unstake ticket account not rent-exempt");
+            return Err(ProgramError::AccountNotRentExempt);
         }
 
-        stake_pool.check_manager(manager_info)?;
-        if !new_manager_info.is_signer {
-            msg!("New manager signature missing");
-            return Err(StakePoolError::SignatureMissing.into());
+        let mut unstake_ticket_ledger = match get_unstake_ticket_ledger(
+            program_id,
+            stake_pool_info.key,
+            unstake_ticket_ledger_info,
+        )? {
+            Some(unstake_ticket_ledger) => unstake_ticket_ledger,
+            None => {
+                let bump_seed = check_unstake_ticket_ledger_address(
+                    program_id,
+                    stake_pool_info.key,
+                    unstake_ticket_ledger_info.key,
+                )?;
+                let unstake_ticket_ledger_signer_seeds: &[&[_]] = &[
+                    UNSTAKE_TICKET_LEDGER_SEED_PREFIX,
+                    stake_pool_info.key.as_ref(),
+                    &[bump_seed],
+                ];
+                create_program_account(
+                    program_id,
+                    unstake_ticket_ledger_info,
+                    unstake_ticket_ledger_signer_seeds,
+                    get_packed_len::<UnstakeTicketLedger>(),
+                    payer_info,
+                    system_program_info,
+                )?;
+                UnstakeTicketLedger {
+                    account_type: AccountType::UnstakeTicketLedger,
+                    stake_pool: *stake_pool_info.key,
+                    owed_lamports: 0,
+                    funded_lamports: 0,
+                }
+            }
+        };
+
+        // To prevent a faulty manager fee account from preventing withdrawals
+        // if the token program does not own the account, or if the account is not
+        // initialized
+        let pool_tokens_fee = if stake_pool.manager_fee_account == *burn_from_pool_info.key
+            || stake_pool.check_manager_fee_info(manager_fee_info).is_err()
+        {
+            0
+        } else {
+            stake_pool
+                .calc_pool_tokens_sol_withdrawal_fee(pool_tokens)
+                .ok_or(StakePoolError::CalculationFailure)?
+        };
+        let pool_tokens_burnt = pool_tokens
+            .checked_sub(pool_tokens_fee)
+            .ok_or(StakePoolError::CalculationFailure)?;
+
+        let ticket_lamports = stake_pool
+            .calc_lamports_withdraw_amount(pool_tokens_burnt)
+            .ok_or(StakePoolError::CalculationFailure)?;
+        if ticket_lamports == 0 {
+            return Err(StakePoolError::WithdrawalTooSmall.into());
         }
 
-        stake_pool.check_manager_fee_info(new_manager_fee_info)?;
+        Self::token_burn(
+            token_program_info.clone(),
+            burn_from_pool_info.clone(),
+            pool_mint_info.clone(),
+            user_transfer_authority_info.clone(),
+            pool_tokens_burnt,
+        )?;
 
-        stake_pool.manager = *new_manager_info.key;
-        stake_pool.manager_fee_account = *new_manager_fee_info.key;
+        if pool_tokens_fee > 0 {
+            Self::token_transfer(
+                token_program_info.clone(),
+                burn_from_pool_info.clone(),
+                pool_mint_info.clone(),
+                manager_fee_info.clone(),
+                user_transfer_authority_info.clone(),
+                transfer_hook_accounts,
+                pool_tokens_fee,
+                decimals,
+            )?;
+        }
+
+        // Stake deactivated during this epoch is withdrawable on the next one
+        let unstake_ticket = UnstakeTicket {
+            account_type: AccountType::UnstakeTicket,
+            stake_pool: *stake_pool_info.key,
+            owner: *ticket_owner_info.key,
+            lamports: ticket_lamports,
+            claimable_epoch: clock
+                .epoch
+                .checked_add(1)
+                .ok_or(StakePoolError::CalculationFailure)?,
+        };
+        borsh::to_writer(
+            &mut unstake_ticket_info.data.borrow_mut()[..],
+            &unstake_ticket,
+        )?;
+
+        unstake_ticket_ledger.owed_lamports = unstake_ticket_ledger
+            .owed_lamports
+            .checked_add(ticket_lamports)
+            .ok_or(StakePoolError::CalculationFailure)?;
+        borsh::to_writer(
+            &mut unstake_ticket_ledger_info.data.borrow_mut()[..],
+            &unstake_ticket_ledger,
+        )?;
+
+        stake_pool.pool_token_supply = stake_pool
+            .pool_token_supply
+            .checked_sub(pool_tokens_burnt)
+            .ok_or(StakePoolError::CalculationFailure)?;
+        stake_pool.total_lamports = stake_pool
+            .total_lamports
+            .checked_sub(ticket_lamports)
+            .ok_or(StakePoolError::CalculationFailure)?;
         borsh::to_writer(&mut stake_pool_info.data.borrow_mut()[..], &stake_pool)?;
+
         Ok(())
     }
 
-    /// Processes [SetFee](enum.Instruction.html).
+    /// Processes [ClaimUnstakeTicket](enum.Instruction.html).
     #[inline(never)] // needed to avoid stack size violation
-    fn process_set_fee(
+    fn process_claim_unstake_ticket(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        fee: FeeType,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let stake_pool_info = next_account_info(account_info_iter)?;
-        let manager_info = next_account_info(account_info_iter)?;
-        let clock = Clock::get()?;
+        let unstake_ticket_ledger_info = next_account_info(account_info_iter)?;
+        let unstake_ticket_info = next_account_info(account_info_iter)?;
+        let ticket_owner_info = next_account_info(account_info_iter)?;
+        let destination_lamports_info = next_account_info(account_info_iter)?;
 
         check_account_owner(stake_pool_info, program_id)?;
-        let mut stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        let stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
         if !stake_pool.is_valid() {
             return Err(StakePoolError::InvalidState.into());
         }
-        stake_pool.check_manager(manager_info)?;
 
-        if fee.can_only_change_next_epoch() && stake_pool.last_update_epoch < clock.epoch {
-            return Err(StakePoolError::StakeListAndPoolOutOfDate.into());
+        let mut unstake_ticket_ledger =
+            get_unstake_ticket_ledger(program_id, stake_pool_info.key, unstake_ticket_ledger_info)?
+                .ok_or(StakePoolError::InvalidUnstakeTicketLedgerAccount)?;
+
+        check_account_owner(unstake_ticket_info, program_id)?;
+        let unstake_ticket =
+            try_from_slice_unchecked::<UnstakeTicket>(&unstake_ticket_info.data.borrow())?;
+        if !unstake_ticket.is_valid() || unstake_ticket.stake_pool != *stake_pool_info.key {
+            return Err(StakePoolError::InvalidUnstakeTicket.into());
+        }
+        if unstake_ticket.owner != *ticket_owner_info.key {
+            msg!(
+                "Incorrect unstake ticket owner provided, expected {}, received {}",
+                unstake_ticket.owner,
+                ticket_owner_info.key
+            );
+            return Err(StakePoolError::InvalidUnstakeTicket.into());
+        }
+        if !ticket_owner_info.is_signer {
+            msg!("Unstake ticket owner signature missing");
+            return Err(StakePoolError::SignatureMissing.into());
         }
 
-        fee.check_too_high()?;
-        stake_pool.update_fee(&fee)?;
-        borsh::to_writer(&mut stake_pool_info.data.borrow_mut()[..], &stake_pool)?;
+        if destination_lamports_info.key == unstake_ticket_info.key
+            || destination_lamports_info.key == unstake_ticket_ledger_info.key
+        {
+            msg!("Unstake ticket cannot be claimed into the ticket or the ledger account");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let clock = Clock::get()?;
+        if !unstake_ticket.is_claimable(clock.epoch) {
+            msg!(
+                "Unstake ticket claimable from epoch {}, current epoch is {}",
+                unstake_ticket.claimable_epoch,
+                clock.epoch
+            );
+            return Err(StakePoolError::UnstakeTicketNotClaimable.into());
+        }
+        if unstake_ticket.lamports > unstake_ticket_ledger.funded_lamports {
+            msg!(
+                "Unstake ticket owed {} lamports, only {} lamports funded, the stake pool must be updated after the staker deactivates more stake",
+                unstake_ticket.lamports,
+                unstake_ticket_ledger.funded_lamports
+            );
+            return Err(StakePoolError::UnstakeTicketNotClaimable.into());
+        }
+
+        unstake_ticket_ledger.owed_lamports = unstake_ticket_ledger
+            .owed_lamports
+            .checked_sub(unstake_ticket.lamports)
+            .ok_or(StakePoolError::CalculationFailure)?;
+        unstake_ticket_ledger.funded_lamports = unstake_ticket_ledger
+            .funded_lamports
+            .checked_sub(unstake_ticket.lamports)
+            .ok_or(StakePoolError::CalculationFailure)?;
+        borsh::to_writer(
+            &mut unstake_ticket_ledger_info.data.borrow_mut()[..],
+            &unstake_ticket_ledger,
+        )?;
+
+        // Pay out the ticket from the ledger, and close the ticket account,
+        // returning its rent to the destination as well
+        let ticket_rent_lamports = unstake_ticket_info.lamports();
+        let claimed_lamports = unstake_ticket
+            .lamports
+            .checked_add(ticket_rent_lamports)
+            .ok_or(StakePoolError::CalculationFailure)?;
+        **unstake_ticket_ledger_info.try_borrow_mut_lamports()? = unstake_ticket_ledger_info
+            .lamports()
+            .checked_sub(unstake_ticket.lamports)
+            .ok_or(StakePoolError::CalculationFailure)?;
+        **unstake_ticket_info.try_borrow_mut_lamports()? = 0;
+        **destination_lamports_info.try_borrow_mut_lamports()? = destination_lamports_info
+            .lamports()
+            .checked_add(claimed_lamports)
+            .ok_or(StakePoolError::CalculationFailure)?;
+        unstake_ticket_info.data.borrow_mut().fill(0);
+
         Ok(())
     }
 
-    /// Processes [SetStaker](enum.Instruction.html).
+    /// Processes `InitializeValidatorRewardHistory` instruction.
     #[inline(never)] // needed to avoid stack size violation
-    fn process_set_staker(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    fn process_initialize_validator_reward_history(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let stake_pool_info = next_account_info(account_info_iter)?;
-        let set_staker_authority_info = next_account_info(account_info_iter)?;
-        let new_staker_info = next_account_info(account_info_iter)?;
+        let staker_info = next_account_info(account_info_iter)?;
+        let validator_list_info = next_account_info(account_info_iter)?;
+        let validator_reward_history_info = next_account_info(account_info_iter)?;
 
         check_account_owner(stake_pool_info, program_id)?;
-        let mut stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        let stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
         if !stake_pool.is_valid() {
             return Err(StakePoolError::InvalidState.into());
         }
+        stake_pool.check_staker(staker_info)?;
+        stake_pool.check_validator_list(validator_list_info)?;
 
-        let staker_signed = stake_pool.check_staker(set_staker_authority_info);
-        let manager_signed = stake_pool.check_manager(set_staker_authority_info);
-        if staker_signed.is_err() && manager_signed.is_err() {
-            return Err(StakePoolError::SignatureMissing.into());
+        check_account_owner(validator_reward_history_info, program_id)?;
+        let mut validator_reward_history_data = validator_reward_history_info.data.borrow_mut();
+        let (header, _) =
+            ValidatorRewardHistoryHeader::deserialize_vec(&mut validator_reward_history_data)?;
+        if !header.is_uninitialized() {
+            msg!("Provided validator reward history already in use");
+            return Err(StakePoolError::AlreadyInUse.into());
         }
-        stake_pool.staker = *new_staker_info.key;
-        borsh::to_writer(&mut stake_pool_info.data.borrow_mut()[..], &stake_pool)?;
+
+        check_account_owner(validator_list_info, program_id)?;
+        let mut validator_list_data = validator_list_info.data.borrow_mut();
+        let (validator_list_header, _) =
+            ValidatorListHeader::deserialize_vec(&mut validator_list_data)?;
+        let max_validators = ValidatorRewardHistory::calculate_max_validators(
+            validator_reward_history_info.data_len(),
+        );
+        if max_validators < validator_list_header.max_validators as usize {
+            msg!(
+                "Incorrect validator reward history size provided, fits {} validators, expected {}",
+                max_validators,
+                validator_list_header.max_validators
+            );
+            return Err(StakePoolError::UnexpectedValidatorListAccountSize.into());
+        }
+
+        if !Rent::get()?.is_exempt(
+            validator_reward_history_info.lamports(),
+            validator_reward_history_info.data_len(),
+        ) {
+            msg!("Validator reward history not rent-exempt");
+            return Err(ProgramError::AccountNotRentExempt);
+        }
+
+        let validator_reward_history = ValidatorRewardHistory {
+            header: ValidatorRewardHistoryHeader {
+                account_type: AccountType::ValidatorRewardHistory,
+                stake_pool: *stake_pool_info.key,
+                max_validators: max_validators
+                    .try_into()
+                    .map_err(|_| StakePoolError::CalculationFailure)?,
+            },
+            validators: vec![],
+        };
+        borsh::to_writer(
+            &mut validator_reward_history_data[..],
+            &validator_reward_history,
+        )?;
         Ok(())
     }
 
-    /// Processes [SetFundingAuthority](enum.Instruction.html).
+    /// Processes `UpdateValidatorRewardHistory` instruction.
     #[inline(never)] // needed to avoid stack size violation
-    fn process_set_funding_authority(
+    fn process_update_validator_reward_history(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        funding_type: FundingType,
+        start_index: u32,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let stake_pool_info = next_account_info(account_info_iter)?;
-        let manager_info = next_account_info(account_info_iter)?;
-
-        let new_authority = next_account_info(account_info_iter)
-            .ok()
-            .map(|new_authority_account_info| *new_authority_account_info.key);
+        let validator_list_info = next_account_info(account_info_iter)?;
+        let validator_reward_history_info = next_account_info(account_info_iter)?;
+        let clock_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::from_account_info(clock_info)?;
+        let validator_stake_accounts = account_info_iter.as_slice();
 
         check_account_owner(stake_pool_info, program_id)?;
-        let mut stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
+        let stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool_info.data.borrow())?;
         if !stake_pool.is_valid() {
             return Err(StakePoolError::InvalidState.into());
         }
-        stake_pool.check_manager(manager_info)?;
-        match funding_type {
-            FundingType::StakeDeposit => {
-                stake_pool.stake_deposit_authority = new_authority.unwrap_or(
-                    find_deposit_authority_program_address(program_id, stake_pool_info.key).0,
-                );
+        stake_pool.check_validator_list(validator_list_info)?;
+        if validator_stake_accounts.len() % 2 != 0 {
+            msg!("Odd number of validator stake accounts passed in, should be pairs of validator stake and transient stake accounts");
+            return Err(StakePoolError::UnexpectedValidatorListAccountSize.into());
+        }
+
+        check_account_owner(validator_reward_history_info, program_id)?;
+        let mut validator_reward_history_data = validator_reward_history_info.data.borrow_mut();
+        let (validator_reward_history_header, mut reward_big_vec) =
+            ValidatorRewardHistoryHeader::deserialize_vec(&mut validator_reward_history_data)?;
+        if !validator_reward_history_header.is_valid()
+            || validator_reward_history_header.stake_pool != *stake_pool_info.key
+        {
+            return Err(StakePoolError::InvalidValidatorRewardHistory.into());
+        }
+
+        check_account_owner(validator_list_info, program_id)?;
+        let mut validator_list_data = validator_list_info.data.borrow_mut();
+        let (validator_list_header, mut big_vec) =
+            ValidatorListHeader::deserialize_vec(&mut validator_list_data)?;
+        if !validator_list_header.is_valid() {
+            return Err(StakePoolError::InvalidState.into());
+        }
+        let validator_slice = ValidatorListHeader::deserialize_mut_slice(
+            &mut big_vec,
+            start_index as usize,
+            validator_stake_accounts.len() / 2,
+        )?;
+
+        for (index, (validator_stake_record, validator_stakes)) in validator_slice
+            .iter()
+            .zip(validator_stake_accounts.chunks_exact(2))
+            .enumerate()
+        {
+            let validator_stake_info = &validator_stakes[0];
+            let transient_stake_info = &validator_stakes[1];
+            let vote_account_address = validator_stake_record.vote_account_address;
+            if check_validator_stake_address(
+                program_id,
+                stake_pool_info.key,
+                validator_stake_info.key,
+                &vote_account_address,
+                NonZeroU32::new(validator_stake_record.validator_seed_suffix.into()),
+            )
+            .is_err()
+                || check_transient_stake_address(
+                    program_id,
+                    stake_pool_info.key,
+                    transient_stake_info.key,
+                    &vote_account_address,
+                    validator_stake_record.transient_seed_suffix.into(),
+                )
+                .is_err()
+            {
+                continue;
             }
-            FundingType::SolDeposit => stake_pool.sol_deposit_authority = new_authority,
-            FundingType::SolWithdraw => stake_pool.sol_withdraw_authority = new_authority,
+            Self::record_validator_rewards(
+                &validator_reward_history_header,
+                &mut reward_big_vec,
+                (start_index as usize).saturating_add(index),
+                validator_stake_record,
+                validator_stake_info,
+                transient_stake_info,
+                clock,
+            )?;
         }
-        borsh::to_writer(&mut stake_pool_info.data.borrow_mut()[..], &stake_pool)?;
         Ok(())
     }
 
@@ -3558,6 +4980,7 @@ impl Processor {
                     accounts,
                     start_index,
                     no_merge,
+                    false,
                 )
             }
             StakePoolInstruction::UpdateStakePoolBalance => {
@@ -3655,6 +5078,72 @@ impl Processor {
                     Some(minimum_lamports_out),
                 )
             }
+            StakePoolInstruction::SetValidatorPolicy {
+                max_commission,
+                min_epoch_credits_bps,
+                max_delinquent_slots,
+            } => {
+                msg!("Instruction: SetValidatorPolicy");
+                Self::process_set_validator_policy(
+                    program_id,
+                    accounts,
+                    max_commission,
+                    min_epoch_credits_bps,
+                    max_delinquent_slots,
+                )
+            }
+            StakePoolInstruction::UpdateValidatorListBalanceWithPolicy {
+                start_index,
+                no_merge,
+            } => {
+                msg!("Instruction: UpdateValidatorListBalanceWithPolicy");
+                Self::process_update_validator_list_balance(
+                    program_id,
+                    accounts,
+                    start_index,
+                    no_merge,
+                    true,
+                )
+            }
+            StakePoolInstruction::CreateUnstakeTicket { pool_tokens_in } => {
+                msg!("Instruction: CreateUnstakeTicket");
+                Self::process_create_unstake_ticket(program_id, accounts, pool_tokens_in)
+            }
+            StakePoolInstruction::ClaimUnstakeTicket => {
+                msg!("Instruction: ClaimUnstakeTicket");
+                Self::process_claim_unstake_ticket(program_id, accounts)
+            }
+            StakePoolInstruction::InitializeValidatorRewardHistory => {
+                msg!("Instruction: InitializeValidatorRewardHistory");
+                Self::process_initialize_validator_reward_history(program_id, accounts)
+            }
+            StakePoolInstruction::UpdateValidatorRewardHistory { start_index } => {
+                msg!("Instruction: UpdateValidatorRewardHistory");
+                Self::process_update_validator_reward_history(program_id, accounts, start_index)
+            }
+            StakePoolInstruction::SetFeeSplit { recipients } => {
+                msg!("Instruction: SetFeeSplit");
+                Self::process_set_fee_split(program_id, accounts, recipients)
+            }
+            StakePoolInstruction::CreateToken2022Metadata { name, symbol, uri } => {
+                msg!("Instruction: CreateToken2022Metadata");
+                Self::process_create_pool_token_2022_metadata(
+                    program_id, accounts, name, symbol, uri,
+                )
+            }
+            StakePoolInstruction::UpdateToken2022Metadata { name, symbol, uri } => {
+                msg!("Instruction: UpdateToken2022Metadata");
+                Self::process_update_pool_token_2022_metadata(
+                    program_id, accounts, name, symbol, uri,
+                )
+            }
+            StakePoolInstruction::SetClusterEpochCredits {
+                epoch,
+                epoch_credits,
+            } => {
+                msg!("Instruction: SetClusterEpochCredits");
+                Self::process_set_cluster_epoch_credits(program_id, accounts, epoch, epoch_credits)
+            }
         }
     }
 }
@@ -3710,6 +5199,18 @@ impl PrintProgramError for StakePoolError {
             StakePoolError::IncorrectMintDecimals => msg!("Error: Provided mint does not have 9 decimals to match SOL"),
             StakePoolError::ReserveDepleted => msg!("Error: Pool reserve does not have enough lamports to fund rent-exempt reserve in split destination. Deposit more SOL in reserve, or pre-fund split destination with the rent-exempt reserve for a stake account."),
             StakePoolError::MissingRequiredSysvar => msg!("Missing required sysvar account"),
+            StakePoolError::InvalidValidatorPolicyAccount => msg!("Error: Provided validator policy account does not match the one derived for the stake pool"),
+            StakePoolError::InvalidValidatorPolicy => msg!("Error: Validator policy criteria are out of range"),
+            StakePoolError::InvalidUnstakeTicketLedgerAccount => msg!("Error: Provided unstake ticket ledger account does not match the one derived for the stake pool"),
+            StakePoolError::InvalidUnstakeTicket => msg!("Error: Unstake ticket is not initialized or does not belong to the stake pool"),
+            StakePoolError::UnstakeTicketNotClaimable => msg!("Error: Unstake ticket cannot be claimed yet"),
+            StakePoolError::InvalidValidatorRewardHistory => msg!("Error: Validator reward history is not initialized or does not belong to the stake pool"),
+            StakePoolError::InvalidFeeSplitAccount => msg!("Error: Provided fee split account does not match the one derived for the stake pool"),
+            StakePoolError::InvalidFeeSplit => msg!("Error: Fee recipients are duplicated, too many, or their shares do not add up to the whole fee"),
+            StakePoolError::InvalidMetadataPointer => msg!("Error: Pool mint does not have a metadata pointer referencing the mint itself"),
+            StakePoolError::ValidatorPolicyRequired => msg!("Error: Validator was updated under the validator policy, use UpdateValidatorListBalanceWithPolicy"),
+            StakePoolError::InvalidClusterEpochCredits => msg!("Error: Cluster epoch credits can only be recorded for a past epoch"),
+            StakePoolError::InvalidMintTransferHookAuthority => msg!("Error: Pool mint transfer hook can be changed by an authority other than the stake pool withdraw authority"),
         }
     }
 }
@@ -12,12 +12,14 @@ use {
     solana_program::{
         account_info::AccountInfo,
         borsh1::get_instance_packed_len,
+        clock::{Clock, Epoch},
         msg,
         program_error::ProgramError,
         program_memory::sol_memcmp,
         program_pack::{Pack, Sealed},
         pubkey::{Pubkey, PUBKEY_BYTES},
         stake::state::Lockup,
+        vote::state::VoteState,
     },
     spl_pod::primitives::{PodU32, PodU64},
     spl_token_2022::{
@@ -37,6 +39,16 @@ pub enum AccountType {
     StakePool,
     /// Validator stake list
     ValidatorList,
+    /// Validator policy
+    ValidatorPolicy,
+    /// Unstake ticket ledger
+    UnstakeTicketLedger,
+    /// Unstake ticket
+    UnstakeTicket,
+    /// Validator reward history
+    ValidatorRewardHistory,
+    /// Epoch fee split
+    FeeSplit,
 }
 
 /// Initialized program details.
@@ -500,7 +512,7 @@ impl StakePool {
 
 /// Checks if the given extension is supported for the stake pool mint
 pub fn is_extension_supported_for_mint(extension_type: &ExtensionType) -> bool {
-    const SUPPORTED_EXTENSIONS: [ExtensionType; 8] = [
+    const SUPPORTED_EXTENSIONS: [ExtensionType; 9] = [
         ExtensionType::Uninitialized,
         ExtensionType::TransferFeeConfig,
         ExtensionType::ConfidentialTransferMint,
@@ -509,6 +521,9 @@ pub fn is_extension_supported_for_mint(extension_type: &ExtensionType) -> bool {
         ExtensionType::InterestBearingConfig,
         ExtensionType::MetadataPointer,
         ExtensionType::TokenMetadata,
+        // ok, the hook accounts are passed along on withdrawal fee transfers,
+        // but only the withdraw authority may change the hook
+        ExtensionType::TransferHook,
     ];
     if !SUPPORTED_EXTENSIONS.contains(extension_type) {
         msg!(
@@ -526,11 +541,12 @@ pub fn is_extension_supported_for_fee_account(extension_type: &ExtensionType) ->
     // Note: this does not include the `ConfidentialTransferAccount` extension
     // because it is possible to block non-confidential transfers with the
     // extension enabled.
-    const SUPPORTED_EXTENSIONS: [ExtensionType; 4] = [
+    const SUPPORTED_EXTENSIONS: [ExtensionType; 5] = [
         ExtensionType::Uninitialized,
         ExtensionType::TransferFeeAmount,
         ExtensionType::ImmutableOwner,
         ExtensionType::CpiGuard,
+        ExtensionType::TransferHookAccount,
     ];
     if !SUPPORTED_EXTENSIONS.contains(extension_type) {
         msg!("Fee account cannot have the {:?} extension", extension_type);
@@ -709,7 +725,9 @@ pub struct ValidatorStakeInfo {
     /// account address
     pub transient_seed_suffix: PodU64,
 
-    /// Unused space, initially meant to specify the end of seed suffixes
+    /// Unused space, initially meant to specify the end of seed suffixes.
+    /// Its lowest bit holds the validator policy flag, see
+    /// `ValidatorStakeInfo::is_policy_enforced`
     pub unused: PodU32,
 
     /// Validator account seed suffix
@@ -723,6 +741,27 @@ pub struct ValidatorStakeInfo {
 }
 
 impl ValidatorStakeInfo {
+    /// Flag set once an `UpdateValidatorListBalanceWithPolicy` evaluated the
+    /// validator policy on the validator, rejecting plain
+    /// `UpdateValidatorListBalance` updates of the validator from then on
+    pub const POLICY_ENFORCED_FLAG: u32 = 1;
+
+    /// Check if updates of this validator must evaluate the validator policy
+    pub fn is_policy_enforced(&self) -> bool {
+        u32::from(self.unused) & Self::POLICY_ENFORCED_FLAG != 0
+    }
+
+    /// Set or clear `POLICY_ENFORCED_FLAG`
+    pub fn set_policy_enforced(&mut self, policy_enforced: bool) {
+        let flags = u32::from(self.unused);
+        self.unused = if policy_enforced {
+            flags | Self::POLICY_ENFORCED_FLAG
+        } else {
+            flags & !Self::POLICY_ENFORCED_FLAG
+        }
+        .into();
+    }
+
     /// Get the total lamports on this validator (active and transient)
     pub fn stake_lamports(&self) -> Result<u64, StakePoolError> {
         u64::from(self.active_stake_lamports)
@@ -1049,6 +1088,540 @@ impl FeeType {
     }
 }
 
+/// Eligibility criteria for the validators of a stake pool, evaluated on
+/// every validator during `UpdateValidatorListBalanceWithPolicy`. Validators
+/// violating any of them have their stake deactivated and are removed from
+/// the pool, just like through `RemoveValidatorFromPool`.
+#[repr(C)]
+#[derive(Clone, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct ValidatorPolicy {
+    /// Account type, must be ValidatorPolicy currently
+    pub account_type: AccountType,
+
+    /// Stake pool the policy applies to
+    pub stake_pool: Pubkey,
+
+    /// Highest commission allowed on the vote account, in percent. 100
+    /// disables the check.
+    pub max_commission: u8,
+
+    /// Minimum credits earned during the previous epoch, in basis points of
+    /// the cluster epoch credits recorded by the staker for that epoch. 0
+    /// disables the check.
+    pub min_epoch_credits_bps: u16,
+
+    /// Maximum number of slots since the last vote of the vote account. 0
+    /// disables the check.
+    pub max_delinquent_slots: u64,
+
+    /// Epoch of `cluster_epoch_credits`
+    pub cluster_credits_epoch: Epoch,
+
+    /// Credits earned by a typical validator of the cluster during
+    /// `cluster_credits_epoch`, recorded by the staker through
+    /// `SetClusterEpochCredits`
+    pub cluster_epoch_credits: u64,
+}
+
+impl ValidatorPolicy {
+    /// Highest value of `min_epoch_credits_bps`, requiring the cluster epoch
+    /// credits
+    pub const MAX_EPOCH_CREDITS_BPS: u16 = 10_000;
+
+    /// Check if ValidatorPolicy is actually initialized as a validator policy
+    pub fn is_valid(&self) -> bool {
+        self.account_type == AccountType::ValidatorPolicy
+    }
+
+    /// Check if any of the criteria is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.max_commission < 100 || self.min_epoch_credits_bps > 0 || self.max_delinquent_slots > 0
+    }
+
+    /// Minimum credits a vote account must have earned during `epoch`, or
+    /// `None` if the staker did not record the cluster epoch credits of
+    /// `epoch`
+    pub fn min_epoch_credits(&self, epoch: Epoch) -> Option<u64> {
+        if self.cluster_credits_epoch != epoch {
+            return None;
+        }
+        let min_epoch_credits = (self.cluster_epoch_credits as u128)
+            .saturating_mul(self.min_epoch_credits_bps as u128)
+            .checked_div(Self::MAX_EPOCH_CREDITS_BPS as u128)
+            .unwrap_or(0);
+        Some(u64::try_from(min_epoch_credits).unwrap_or(u64::MAX))
+    }
+
+    /// Evaluate the policy on a vote account, returning the first violated
+    /// criterion. `vote_state` is `None` if the vote account is closed or
+    /// invalid, which always violates the policy. The epoch credits are only
+    /// checked once the cluster epoch credits of the previous epoch are
+    /// recorded.
+    pub fn check_vote_state(
+        &self,
+        vote_state: Option<&VoteState>,
+        clock: &Clock,
+    ) -> Option<ValidatorPolicyViolation> {
+        let Some(vote_state) = vote_state else {
+            return Some(ValidatorPolicyViolation::InvalidVoteAccount);
+        };
+        if vote_state.commission > self.max_commission {
+            return Some(ValidatorPolicyViolation::Commission(vote_state.commission));
+        }
+        if self.min_epoch_credits_bps > 0 && clock.epoch > 0 {
+            let previous_epoch = clock.epoch.saturating_sub(1);
+            if let Some(min_epoch_credits) = self.min_epoch_credits(previous_epoch) {
+                let epoch_credits = vote_state
+                    .epoch_credits
+                    .iter()
+                    .find(|(epoch, _, _)| *epoch == previous_epoch)
+                    .map(|(_, credits, prev_credits)| credits.saturating_sub(*prev_credits))
+                    .unwrap_or(0);
+                if epoch_credits < min_epoch_credits {
+                    return Some(ValidatorPolicyViolation::EpochCredits(epoch_credits));
+                }
+            }
+        }
+        if self.max_delinquent_slots > 0 {
+            let delinquent_slots = vote_state
+                .last_voted_slot()
+                .map(|slot| clock.slot.saturating_sub(slot))
+                .unwrap_or(u64::MAX);
+            if delinquent_slots > self.max_delinquent_slots {
+                return Some(ValidatorPolicyViolation::Delinquent(delinquent_slots));
+            }
+        }
+        None
+    }
+}
+
+/// Criterion of the validator policy violated by a validator
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ValidatorPolicyViolation {
+    /// The vote account is closed or cannot be deserialized
+    InvalidVoteAccount,
+    /// The commission is above the maximum
+    Commission(u8),
+    /// The credits earned during the previous epoch are below the minimum
+    EpochCredits(u64),
+    /// The vote account has not voted for more than the maximum slots
+    Delinquent(u64),
+}
+
+impl fmt::Display for ValidatorPolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidVoteAccount => write!(f, "invalid vote account"),
+            Self::Commission(commission) => write!(f, "commission of {}%", commission),
+            Self::EpochCredits(credits) => write!(f, "{} credits in the previous epoch", credits),
+            Self::Delinquent(slots) => write!(f, "no vote in {} slots", slots),
+        }
+    }
+}
+
+/// Ledger of the lamports owed to the unclaimed unstake tickets of a stake
+/// pool, at the address derived by `find_unstake_ticket_ledger_program_address`.
+///
+/// The lamports owed but not yet funded are still held by the reserve or the
+/// validator stake accounts, and are left out of the pool's `total_lamports`
+/// by `UpdateStakePoolBalance`, which also funds the ledger from the reserve.
+/// Funded lamports are held by the ledger account itself until claimed.
+#[repr(C)]
+#[derive(Clone, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct UnstakeTicketLedger {
+    /// Account type, must be UnstakeTicketLedger currently
+    pub account_type: AccountType,
+
+    /// Stake pool the tickets were created on
+    pub stake_pool: Pubkey,
+
+    /// Total lamports owed to unclaimed tickets
+    pub owed_lamports: u64,
+
+    /// Lamports owed to unclaimed tickets already held by the ledger account
+    pub funded_lamports: u64,
+}
+
+impl UnstakeTicketLedger {
+    /// Check if UnstakeTicketLedger is actually initialized as a ledger
+    pub fn is_valid(&self) -> bool {
+        self.account_type == AccountType::UnstakeTicketLedger
+    }
+
+    /// Lamports owed to unclaimed tickets still held by the reserve or the
+    /// validator stake accounts
+    pub fn unfunded_lamports(&self) -> u64 {
+        self.owed_lamports.saturating_sub(self.funded_lamports)
+    }
+}
+
+/// Claim on lamports from a stake pool, created by burning pool tokens with
+/// `CreateUnstakeTicket` and redeemed with `ClaimUnstakeTicket` once the
+/// staker deactivated enough stake.
+#[repr(C)]
+#[derive(Clone, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct UnstakeTicket {
+    /// Account type, must be UnstakeTicket currently
+    pub account_type: AccountType,
+
+    /// Stake pool the ticket was created on
+    pub stake_pool: Pubkey,
+
+    /// Owner allowed to claim the ticket
+    pub owner: Pubkey,
+
+    /// Lamports owed to the owner, at the pool's rate when the ticket was
+    /// created
+    pub lamports: u64,
+
+    /// First epoch the ticket can be claimed in, after the cooldown of the
+    /// stake deactivated for it
+    pub claimable_epoch: u64,
+}
+
+impl UnstakeTicket {
+    /// Check if UnstakeTicket is actually initialized as a ticket
+    pub fn is_valid(&self) -> bool {
+        self.account_type == AccountType::UnstakeTicket
+    }
+
+    /// Check if the ticket is past its cooldown epoch
+    pub fn is_claimable(&self, epoch: Epoch) -> bool {
+        epoch >= self.claimable_epoch
+    }
+}
+
+/// Rewards earned by the validators of a stake pool, recorded by the
+/// validator list update of each epoch when given the reward history, or by
+/// `UpdateValidatorRewardHistory` before it. Entries follow the order of the
+/// validator list.
+#[repr(C)]
+#[derive(Clone, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct ValidatorRewardHistory {
+    /// Data outside of the reward entries, separated out for cheaper
+    /// deserializations
+    pub header: ValidatorRewardHistoryHeader,
+
+    /// Rewards of each validator in the pool, until
+    /// `CleanupRemovedValidatorEntries` drops the removed validators
+    pub validators: Vec<ValidatorRewardInfo>,
+}
+
+/// Helper type to deserialize just the start of a ValidatorRewardHistory
+#[repr(C)]
+#[derive(Clone, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct ValidatorRewardHistoryHeader {
+    /// Account type, must be ValidatorRewardHistory currently
+    pub account_type: AccountType,
+
+    /// Stake pool the rewards are recorded for
+    pub stake_pool: Pubkey,
+
+    /// Maximum allowable number of validators
+    pub max_validators: u32,
+}
+
+/// Rewards of a validator over the epochs since its last recording, and
+/// since its first recording
+#[repr(C)]
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Pod,
+    Zeroable,
+    BorshDeserialize,
+    BorshSerialize,
+    BorshSchema,
+)]
+pub struct ValidatorRewardInfo {
+    /// Validator vote account address
+    pub vote_account_address: Pubkey,
+
+    /// Epoch the first recorded rewards started accruing in
+    pub first_update_epoch: PodU64,
+
+    /// Last epoch rewards were recorded in
+    pub last_update_epoch: PodU64,
+
+    /// Epochs covered by the last recording, more than one if the validator
+    /// list was not updated every epoch
+    pub last_epochs: PodU64,
+
+    /// Lamports staked to the validator the last rewards were earned on,
+    /// including transient stake
+    pub last_stake_lamports: PodU64,
+
+    /// Staking rewards of the last recording
+    pub last_rewards_lamports: PodU64,
+
+    /// Lamports above the delegation and rent of the validator stake account
+    /// in the last recording, such as MEV tips, withdrawn to the reserve by
+    /// the update
+    pub last_mev_lamports: PodU64,
+
+    /// Staking rewards since the first recording
+    pub total_rewards_lamports: PodU64,
+
+    /// MEV lamports since the first recording
+    pub total_mev_lamports: PodU64,
+}
+
+impl ValidatorRewardHistory {
+    /// Create an empty instance containing space for `max_validators`
+    pub fn new(stake_pool: Pubkey, max_validators: u32) -> Self {
+        Self {
+            header: ValidatorRewardHistoryHeader {
+                account_type: AccountType::ValidatorRewardHistory,
+                stake_pool,
+                max_validators,
+            },
+            validators: vec![ValidatorRewardInfo::default(); max_validators as usize],
+        }
+    }
+
+    /// Calculate the number of reward entries that fit in the provided length
+    pub fn calculate_max_validators(buffer_length: usize) -> usize {
+        let header_size = ValidatorRewardHistoryHeader::LEN.saturating_add(4);
+        buffer_length
+            .saturating_sub(header_size)
+            .saturating_div(ValidatorRewardInfo::LEN)
+    }
+
+    /// Find the rewards of the validator with a particular vote account
+    pub fn find(&self, vote_account_address: &Pubkey) -> Option<&ValidatorRewardInfo> {
+        self.validators
+            .iter()
+            .find(|x| x.vote_account_address == *vote_account_address)
+    }
+}
+
+impl ValidatorRewardHistoryHeader {
+    const LEN: usize = 1 + 32 + 4;
+
+    /// Check if the reward history is actually initialized as a reward history
+    pub fn is_valid(&self) -> bool {
+        self.account_type == AccountType::ValidatorRewardHistory
+    }
+
+    /// Check if the reward history is uninitialized
+    pub fn is_uninitialized(&self) -> bool {
+        self.account_type == AccountType::Uninitialized
+    }
+
+    /// Extracts the reward history into its header and internal BigVec
+    pub fn deserialize_vec(data: &mut [u8]) -> Result<(Self, BigVec), ProgramError> {
+        let mut data_mut = data.borrow();
+        let header = ValidatorRewardHistoryHeader::deserialize(&mut data_mut)?;
+        let length = get_instance_packed_len(&header)?;
+
+        let big_vec = BigVec {
+            data: &mut data[length..],
+        };
+        Ok((header, big_vec))
+    }
+
+    /// Get the rewards of the validator at `index` of the validator list,
+    /// adding an entry first updated in `epoch` if it has none yet. Returns
+    /// `None` if the reward history is full.
+    ///
+    /// Entries are kept in the order of the validator list, so the lookup
+    /// is constant. Once `CleanupRemovedValidatorEntries` shifted the list,
+    /// entries are searched for and moved back to the index of their
+    /// validator.
+    pub fn get_or_insert<'a>(
+        &self,
+        big_vec: &'a mut BigVec,
+        index: usize,
+        vote_account_address: &Pubkey,
+        epoch: Epoch,
+    ) -> Result<Option<&'a mut ValidatorRewardInfo>, ProgramError> {
+        let len = big_vec.len() as usize;
+        let validators = big_vec.deserialize_mut_slice::<ValidatorRewardInfo>(0, len)?;
+        let position = match validators.get(index) {
+            Some(info) if info.vote_account_address == *vote_account_address => Some(index),
+            _ => validators
+                .iter()
+                .position(|info| info.vote_account_address == *vote_account_address),
+        };
+        let position = match position {
+            Some(position) => position,
+            None if big_vec.len() >= self.max_validators => return Ok(None),
+            None => {
+                big_vec.push(ValidatorRewardInfo::new(*vote_account_address, epoch))?;
+                len
+            }
+        };
+
+        let len = big_vec.len() as usize;
+        let validators = big_vec.deserialize_mut_slice::<ValidatorRewardInfo>(0, len)?;
+        if index < len {
+            validators.swap(index, position);
+            Ok(validators.get_mut(index))
+        } else {
+            Ok(validators.get_mut(position))
+        }
+    }
+}
+
+impl ValidatorRewardInfo {
+    /// Length of a serialized entry
+    pub const LEN: usize = 32 + 8 * 8;
+
+    /// Create the entry of a validator without recorded rewards
+    pub fn new(vote_account_address: Pubkey, epoch: Epoch) -> Self {
+        Self {
+            vote_account_address,
+            first_update_epoch: epoch.into(),
+            last_update_epoch: epoch.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Performs a very cheap comparison, for checking if this reward info
+    /// matches the vote account address
+    pub fn memcmp_pubkey(data: &[u8], vote_address: &Pubkey) -> bool {
+        sol_memcmp(&data[0..PUBKEY_BYTES], vote_address.as_ref(), PUBKEY_BYTES) == 0
+    }
+
+    /// Record the rewards earned over the epochs since `last_update_epoch`
+    pub fn record(
+        &mut self,
+        epoch: Epoch,
+        epochs: u64,
+        stake_lamports: u64,
+        rewards_lamports: u64,
+        mev_lamports: u64,
+    ) {
+        self.last_update_epoch = epoch.into();
+        self.last_epochs = epochs.into();
+        self.last_stake_lamports = stake_lamports.into();
+        self.last_rewards_lamports = rewards_lamports.into();
+        self.last_mev_lamports = mev_lamports.into();
+        self.total_rewards_lamports = u64::from(self.total_rewards_lamports)
+            .saturating_add(rewards_lamports)
+            .into();
+        self.total_mev_lamports = u64::from(self.total_mev_lamports)
+            .saturating_add(mev_lamports)
+            .into();
+    }
+}
+
+/// Split of the epoch fee of a stake pool between several token accounts,
+/// at the address derived by `find_fee_split_program_address`. Without
+/// recipients, the whole epoch fee goes to the manager fee account.
+#[repr(C)]
+#[derive(Clone, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct FeeSplit {
+    /// Account type, must be FeeSplit currently
+    pub account_type: AccountType,
+
+    /// Stake pool the epoch fee is split for
+    pub stake_pool: Pubkey,
+
+    /// Recipients of the epoch fee minted by `UpdateStakePoolBalance`
+    pub recipients: Vec<FeeRecipient>,
+
+    /// Recipients replacing the current ones after two epoch boundaries
+    pub next_recipients: FutureEpoch<Vec<FeeRecipient>>,
+}
+
+/// Share of the epoch fee received by a token account
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, BorshDeserialize, BorshSerialize, BorshSchema)]
+pub struct FeeRecipient {
+    /// Pool token account receiving the share
+    pub token_account: Pubkey,
+
+    /// Share of the epoch fee, in basis points
+    pub bps: u16,
+}
+
+impl FeeSplit {
+    /// Maximum number of recipients of a fee split
+    pub const MAX_RECIPIENTS: usize = 8;
+
+    /// Sum of the shares of all recipients
+    pub const TOTAL_BPS: u16 = 10_000;
+
+    /// Length of the account, fitting the maximum number of recipients for
+    /// both the current and the next split
+    pub const LEN: usize = 1 + 32 + 2 * (4 + Self::MAX_RECIPIENTS * FeeRecipient::LEN) + 1;
+
+    /// Check if FeeSplit is actually initialized as a fee split
+    pub fn is_valid(&self) -> bool {
+        self.account_type == AccountType::FeeSplit
+    }
+
+    /// Check that the recipients are distinct and their shares add up to the
+    /// whole fee. No recipients at all gives the fee back to the manager fee
+    /// account.
+    pub fn check_recipients(recipients: &[FeeRecipient]) -> Result<(), StakePoolError> {
+        if recipients.is_empty() {
+            return Ok(());
+        }
+        if recipients.len() > Self::MAX_RECIPIENTS {
+            msg!(
+                "Too many fee recipients, {} provided, maximum {}",
+                recipients.len(),
+                Self::MAX_RECIPIENTS
+            );
+            return Err(StakePoolError::InvalidFeeSplit);
+        }
+        let mut total_bps: u16 = 0;
+        for (i, recipient) in recipients.iter().enumerate() {
+            if recipient.bps == 0
+                || recipients[..i]
+                    .iter()
+                    .any(|x| x.token_account == recipient.token_account)
+            {
+                return Err(StakePoolError::InvalidFeeSplit);
+            }
+            total_bps = total_bps
+                .checked_add(recipient.bps)
+                .ok_or(StakePoolError::InvalidFeeSplit)?;
+        }
+        if total_bps != Self::TOTAL_BPS {
+            msg!(
+                "Fee recipient shares add up to {} bps, expected {}",
+                total_bps,
+                Self::TOTAL_BPS
+            );
+            return Err(StakePoolError::InvalidFeeSplit);
+        }
+        Ok(())
+    }
+
+    /// Amounts of `fee` received by each recipient. Rounding dust goes to the
+    /// first recipient.
+    pub fn fee_shares(&self, fee: u64) -> Option<Vec<u64>> {
+        let mut shares = self
+            .recipients
+            .iter()
+            .map(|recipient| {
+                u64::try_from(
+                    (fee as u128)
+                        .checked_mul(recipient.bps as u128)?
+                        .checked_div(Self::TOTAL_BPS as u128)?,
+                )
+                .ok()
+            })
+            .collect::<Option<Vec<u64>>>()?;
+        let dust = fee.checked_sub(shares.iter().sum())?;
+        if let Some(first) = shares.first_mut() {
+            *first = first.checked_add(dust)?;
+        }
+        Some(shares)
+    }
+}
+
+impl FeeRecipient {
+    /// Length of a serialized recipient
+    pub const LEN: usize = 32 + 2;
+}
+
 #[cfg(test)]
 mod test {
     #![allow(clippy::arithmetic_side_effects)]
@@ -1057,8 +1630,9 @@ mod test {
         proptest::prelude::*,
         solana_program::{
             borsh1::{get_packed_len, try_from_slice_unchecked},
-            clock::{DEFAULT_SLOTS_PER_EPOCH, DEFAULT_S_PER_SLOT, SECONDS_PER_DAY},
+            clock::{Slot, DEFAULT_SLOTS_PER_EPOCH, DEFAULT_S_PER_SLOT, SECONDS_PER_DAY},
             native_token::LAMPORTS_PER_SOL,
+            vote::state::Lockout,
         },
     };
 
@@ -1463,4 +2037,316 @@ mod test {
         let withdraw_result = stake_pool.calc_lamports_withdraw_amount(1).unwrap();
         assert_eq!(stake_pool.total_lamports, withdraw_result);
     }
+
+    fn test_vote_state(commission: u8, epoch_credits: u64, last_voted_slot: Slot) -> VoteState {
+        let mut vote_state = VoteState::default();
+        vote_state.commission = commission;
+        vote_state.epoch_credits = vec![(9, 1_000 + epoch_credits, 1_000)];
+        vote_state
+            .votes
+            .push_back(Lockout::new(last_voted_slot).into());
+        vote_state
+    }
+
+    #[test]
+    fn validator_policy_min_epoch_credits() {
+        let validator_policy = ValidatorPolicy {
+            min_epoch_credits_bps: 5_000,
+            cluster_credits_epoch: 9,
+            cluster_epoch_credits: 6_000,
+            ..ValidatorPolicy::default()
+        };
+        assert_eq!(validator_policy.min_epoch_credits(9), Some(3_000));
+        assert_eq!(validator_policy.min_epoch_credits(8), None);
+        assert_eq!(validator_policy.min_epoch_credits(10), None);
+        let validator_policy = ValidatorPolicy {
+            cluster_credits_epoch: 9,
+            cluster_epoch_credits: u64::MAX,
+            min_epoch_credits_bps: ValidatorPolicy::MAX_EPOCH_CREDITS_BPS,
+            ..ValidatorPolicy::default()
+        };
+        assert_eq!(validator_policy.min_epoch_credits(9), Some(u64::MAX));
+    }
+
+    #[test]
+    fn validator_policy_check_vote_state() {
+        let clock = Clock {
+            slot: 10_500,
+            epoch: 10,
+            ..Clock::default()
+        };
+        let validator_policy = ValidatorPolicy {
+            account_type: AccountType::ValidatorPolicy,
+            max_commission: 10,
+            min_epoch_credits_bps: 5_000,
+            max_delinquent_slots: 100,
+            cluster_credits_epoch: 9,
+            cluster_epoch_credits: 8_000,
+            ..ValidatorPolicy::default()
+        };
+        assert!(validator_policy.is_enabled());
+        let min_epoch_credits = validator_policy.min_epoch_credits(9).unwrap();
+        assert_eq!(min_epoch_credits, 4_000);
+
+        let vote_state = test_vote_state(10, min_epoch_credits, 10_400);
+        assert_eq!(
+            validator_policy.check_vote_state(Some(&vote_state), &clock),
+            None
+        );
+        assert_eq!(
+            validator_policy.check_vote_state(None, &clock),
+            Some(ValidatorPolicyViolation::InvalidVoteAccount)
+        );
+
+        let vote_state = test_vote_state(11, min_epoch_credits, 10_400);
+        assert_eq!(
+            validator_policy.check_vote_state(Some(&vote_state), &clock),
+            Some(ValidatorPolicyViolation::Commission(11))
+        );
+
+        let vote_state = test_vote_state(10, min_epoch_credits - 1, 10_400);
+        assert_eq!(
+            validator_policy.check_vote_state(Some(&vote_state), &clock),
+            Some(ValidatorPolicyViolation::EpochCredits(
+                min_epoch_credits - 1
+            ))
+        );
+
+        // no credits recorded for the previous epoch
+        let mut vote_state = test_vote_state(10, min_epoch_credits, 10_400);
+        vote_state.epoch_credits = vec![(8, 20_000, 0)];
+        assert_eq!(
+            validator_policy.check_vote_state(Some(&vote_state), &clock),
+            Some(ValidatorPolicyViolation::EpochCredits(0))
+        );
+
+        // no cluster epoch credits recorded for the previous epoch
+        let stale_validator_policy = ValidatorPolicy {
+            cluster_credits_epoch: 8,
+            ..validator_policy.clone()
+        };
+        assert_eq!(
+            stale_validator_policy.check_vote_state(Some(&vote_state), &clock),
+            None
+        );
+
+        let vote_state = test_vote_state(10, min_epoch_credits, 10_399);
+        assert_eq!(
+            validator_policy.check_vote_state(Some(&vote_state), &clock),
+            Some(ValidatorPolicyViolation::Delinquent(101))
+        );
+
+        // disabled criteria are never violated
+        let validator_policy = ValidatorPolicy {
+            account_type: AccountType::ValidatorPolicy,
+            max_commission: 100,
+            ..ValidatorPolicy::default()
+        };
+        assert!(!validator_policy.is_enabled());
+        let vote_state = test_vote_state(100, 0, 0);
+        assert_eq!(
+            validator_policy.check_vote_state(Some(&vote_state), &clock),
+            None
+        );
+    }
+
+    #[test]
+    fn validator_stake_info_policy_enforced() {
+        let mut validator = ValidatorStakeInfo {
+            unused: 0b100.into(),
+            ..ValidatorStakeInfo::default()
+        };
+        assert!(!validator.is_policy_enforced());
+        validator.set_policy_enforced(true);
+        assert!(validator.is_policy_enforced());
+        assert_eq!(u32::from(validator.unused), 0b101);
+        validator.set_policy_enforced(false);
+        assert!(!validator.is_policy_enforced());
+        assert_eq!(u32::from(validator.unused), 0b100);
+    }
+
+    #[test]
+    fn unstake_ticket_ledger_unfunded_lamports() {
+        let mut ledger = UnstakeTicketLedger {
+            account_type: AccountType::UnstakeTicketLedger,
+            stake_pool: Pubkey::new_unique(),
+            owed_lamports: 10 * LAMPORTS_PER_SOL,
+            funded_lamports: 4 * LAMPORTS_PER_SOL,
+        };
+        assert!(ledger.is_valid());
+        assert_eq!(ledger.unfunded_lamports(), 6 * LAMPORTS_PER_SOL);
+        ledger.funded_lamports = 11 * LAMPORTS_PER_SOL;
+        assert_eq!(ledger.unfunded_lamports(), 0);
+
+        let ticket = UnstakeTicket {
+            account_type: AccountType::UnstakeTicket,
+            stake_pool: ledger.stake_pool,
+            owner: Pubkey::new_unique(),
+            lamports: LAMPORTS_PER_SOL,
+            claimable_epoch: 10,
+        };
+        assert!(ticket.is_valid());
+        assert!(!ticket.is_claimable(9));
+        assert!(ticket.is_claimable(10));
+        assert!(ticket.is_claimable(11));
+    }
+
+    #[test]
+    fn validator_reward_history_records_rewards() {
+        let stake_pool = Pubkey::new_unique();
+        let max_validators = 3;
+        let history = ValidatorRewardHistory::new(stake_pool, max_validators);
+        let mut data = vec![0; get_instance_packed_len(&history).unwrap()];
+        assert_eq!(
+            ValidatorRewardHistory::calculate_max_validators(data.len()),
+            max_validators as usize
+        );
+        assert_eq!(
+            get_packed_len::<ValidatorRewardInfo>(),
+            ValidatorRewardInfo::LEN
+        );
+        let empty = ValidatorRewardHistory {
+            header: history.header.clone(),
+            validators: vec![],
+        };
+        borsh::to_writer(&mut data[..], &empty).unwrap();
+
+        let vote_account_address = Pubkey::new_unique();
+        {
+            let (header, mut big_vec) =
+                ValidatorRewardHistoryHeader::deserialize_vec(&mut data).unwrap();
+            assert!(header.is_valid());
+            let mut reward_info = ValidatorRewardInfo::new(vote_account_address, 10);
+            reward_info.record(12, 2, 100 * LAMPORTS_PER_SOL, LAMPORTS_PER_SOL, 1_000);
+            big_vec.push(reward_info).unwrap();
+            let reward_info = big_vec
+                .find_mut::<ValidatorRewardInfo, _>(|x| {
+                    ValidatorRewardInfo::memcmp_pubkey(x, &vote_account_address)
+                })
+                .unwrap();
+            reward_info.record(13, 1, 101 * LAMPORTS_PER_SOL, LAMPORTS_PER_SOL / 2, 0);
+        }
+
+        let history = try_from_slice_unchecked::<ValidatorRewardHistory>(&data).unwrap();
+        assert_eq!(history.header.stake_pool, stake_pool);
+        let reward_info = history.find(&vote_account_address).unwrap();
+        assert_eq!(u64::from(reward_info.first_update_epoch), 10);
+        assert_eq!(u64::from(reward_info.last_update_epoch), 13);
+        assert_eq!(u64::from(reward_info.last_epochs), 1);
+        assert_eq!(
+            u64::from(reward_info.last_rewards_lamports),
+            LAMPORTS_PER_SOL / 2
+        );
+        assert_eq!(
+            u64::from(reward_info.total_rewards_lamports),
+            3 * LAMPORTS_PER_SOL / 2
+        );
+        assert_eq!(u64::from(reward_info.total_mev_lamports), 1_000);
+    }
+
+    #[test]
+    fn validator_reward_history_follows_validator_list() {
+        let stake_pool = Pubkey::new_unique();
+        let history = ValidatorRewardHistory::new(stake_pool, 3);
+        let mut data = vec![0; get_instance_packed_len(&history).unwrap()];
+        let empty = ValidatorRewardHistory {
+            header: history.header.clone(),
+            validators: vec![],
+        };
+        borsh::to_writer(&mut data[..], &empty).unwrap();
+
+        let vote_account_addresses: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+        {
+            let (header, mut big_vec) =
+                ValidatorRewardHistoryHeader::deserialize_vec(&mut data).unwrap();
+            for (index, vote_account_address) in vote_account_addresses[..3].iter().enumerate() {
+                header
+                    .get_or_insert(&mut big_vec, index, vote_account_address, 10)
+                    .unwrap()
+                    .unwrap()
+                    .record(11, 1, LAMPORTS_PER_SOL, index as u64, 0);
+            }
+            // full
+            assert_eq!(
+                header
+                    .get_or_insert(&mut big_vec, 3, &vote_account_addresses[3], 10)
+                    .unwrap(),
+                None
+            );
+
+            // the first validator was cleaned up from the list, shifting the
+            // others down
+            for (index, vote_account_address) in vote_account_addresses[1..3].iter().enumerate() {
+                let reward_info = header
+                    .get_or_insert(&mut big_vec, index, vote_account_address, 11)
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(reward_info.vote_account_address, *vote_account_address);
+                assert_eq!(
+                    u64::from(reward_info.last_rewards_lamports),
+                    index as u64 + 1
+                );
+            }
+        }
+
+        let history = try_from_slice_unchecked::<ValidatorRewardHistory>(&data).unwrap();
+        assert_eq!(
+            history
+                .validators
+                .iter()
+                .map(|info| info.vote_account_address)
+                .collect::<Vec<_>>(),
+            vec![
+                vote_account_addresses[1],
+                vote_account_addresses[2],
+                vote_account_addresses[0]
+            ]
+        );
+    }
+
+    #[test]
+    fn fee_split_shares() {
+        let recipients: Vec<FeeRecipient> = [5_000, 3_333, 1_667]
+            .into_iter()
+            .map(|bps| FeeRecipient {
+                token_account: Pubkey::new_unique(),
+                bps,
+            })
+            .collect();
+        FeeSplit::check_recipients(&recipients).unwrap();
+        FeeSplit::check_recipients(&[]).unwrap();
+        assert_eq!(
+            FeeSplit::check_recipients(&recipients[..2]),
+            Err(StakePoolError::InvalidFeeSplit)
+        );
+        let duplicated = [recipients[0], recipients[0]];
+        assert_eq!(
+            FeeSplit::check_recipients(&duplicated),
+            Err(StakePoolError::InvalidFeeSplit)
+        );
+
+        let fee_split = FeeSplit {
+            account_type: AccountType::FeeSplit,
+            stake_pool: Pubkey::new_unique(),
+            recipients,
+            next_recipients: FutureEpoch::None,
+        };
+        let shares = fee_split.fee_shares(1_000).unwrap();
+        assert_eq!(shares, vec![501, 333, 166]);
+        assert_eq!(shares.iter().sum::<u64>(), 1_000);
+        assert_eq!(fee_split.fee_shares(0).unwrap(), vec![0, 0, 0]);
+        assert_eq!(
+            fee_split.fee_shares(u64::MAX).unwrap().iter().sum::<u64>(),
+            u64::MAX
+        );
+
+        // the account fits the largest splits
+        let full = vec![FeeRecipient::default(); FeeSplit::MAX_RECIPIENTS];
+        let fee_split = FeeSplit {
+            recipients: full.clone(),
+            next_recipients: FutureEpoch::new(full),
+            ..fee_split
+        };
+        assert_eq!(get_instance_packed_len(&fee_split).unwrap(), FeeSplit::LEN);
+    }
 }
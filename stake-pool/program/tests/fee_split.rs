@@ -0,0 +1,299 @@
+#![allow(clippy::arithmetic_side_effects)]
+#![cfg(feature = "test-sbf")]
+
+mod helpers;
+
+use {
+    helpers::*,
+    solana_program_test::*,
+    solana_sdk::{
+        borsh1::try_from_slice_unchecked,
+        hash::Hash,
+        instruction::InstructionError,
+        native_token::LAMPORTS_PER_SOL,
+        pubkey::Pubkey,
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    },
+    spl_stake_pool::{
+        error, find_fee_split_program_address, id, instruction,
+        state::{FeeRecipient, FeeSplit, FutureEpoch},
+        MINIMUM_RESERVE_LAMPORTS,
+    },
+};
+
+async fn setup() -> (ProgramTestContext, StakePoolAccounts, Vec<FeeRecipient>) {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::default();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            MINIMUM_RESERVE_LAMPORTS,
+        )
+        .await
+        .unwrap();
+
+    let mut recipients = vec![];
+    for bps in [7_000, 3_000] {
+        let token_account = Keypair::new();
+        create_token_account(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &stake_pool_accounts.token_program_id,
+            &token_account,
+            &stake_pool_accounts.pool_mint.pubkey(),
+            &Keypair::new(),
+            &[],
+        )
+        .await
+        .unwrap();
+        recipients.push(FeeRecipient {
+            token_account: token_account.pubkey(),
+            bps,
+        });
+    }
+
+    (context, stake_pool_accounts, recipients)
+}
+
+async fn get_fee_split(
+    context: &mut ProgramTestContext,
+    stake_pool_accounts: &StakePoolAccounts,
+) -> FeeSplit {
+    let (fee_split_address, _) =
+        find_fee_split_program_address(&id(), &stake_pool_accounts.stake_pool.pubkey());
+    let fee_split = get_account(&mut context.banks_client, &fee_split_address).await;
+    try_from_slice_unchecked::<FeeSplit>(fee_split.data.as_slice()).unwrap()
+}
+
+async fn update_stake_pool_balance(
+    context: &mut ProgramTestContext,
+    stake_pool_accounts: &StakePoolAccounts,
+    fee_recipients: &[Pubkey],
+    recent_blockhash: &Hash,
+) -> Result<(), BanksClientError> {
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::update_stake_pool_balance_with_fee_split(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.withdraw_authority,
+            &stake_pool_accounts.validator_list.pubkey(),
+            &stake_pool_accounts.reserve_stake.pubkey(),
+            &stake_pool_accounts.pool_fee_account.pubkey(),
+            &stake_pool_accounts.pool_mint.pubkey(),
+            &stake_pool_accounts.token_program_id,
+            fee_recipients,
+        )],
+        Some(&context.payer.pubkey()),
+        &[&context.payer],
+        *recent_blockhash,
+    );
+    context.banks_client.process_transaction(transaction).await
+}
+
+#[tokio::test]
+async fn success() {
+    let (mut context, stake_pool_accounts, recipients) = setup().await;
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::set_fee_split(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.manager.pubkey(),
+            &context.payer.pubkey(),
+            recipients.clone(),
+        )],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &stake_pool_accounts.manager],
+        context.last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let fee_split = get_fee_split(&mut context, &stake_pool_accounts).await;
+    assert!(fee_split.recipients.is_empty());
+    assert_eq!(
+        fee_split.next_recipients,
+        FutureEpoch::Two(recipients.clone())
+    );
+
+    let first_normal_slot = context.genesis_config().epoch_schedule.first_normal_slot;
+    let slots_per_epoch = context.genesis_config().epoch_schedule.slots_per_epoch;
+    let mut slot = first_normal_slot + 1;
+    context.warp_to_slot(slot).unwrap();
+    let last_blockhash = context.last_blockhash;
+    update_stake_pool_balance(&mut context, &stake_pool_accounts, &[], &last_blockhash)
+        .await
+        .unwrap();
+    let fee_split = get_fee_split(&mut context, &stake_pool_accounts).await;
+    assert!(fee_split.recipients.is_empty());
+    assert_eq!(
+        fee_split.next_recipients,
+        FutureEpoch::One(recipients.clone())
+    );
+
+    // rewards of the epoch the split becomes active in still go to the manager
+    transfer(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        LAMPORTS_PER_SOL,
+    )
+    .await;
+    slot += slots_per_epoch;
+    context.warp_to_slot(slot).unwrap();
+    let last_blockhash = context
+        .banks_client
+        .get_new_latest_blockhash(&context.last_blockhash)
+        .await
+        .unwrap();
+    update_stake_pool_balance(&mut context, &stake_pool_accounts, &[], &last_blockhash)
+        .await
+        .unwrap();
+    let fee_split = get_fee_split(&mut context, &stake_pool_accounts).await;
+    assert_eq!(fee_split.recipients, recipients);
+    assert_eq!(fee_split.next_recipients, FutureEpoch::None);
+    let manager_fee = get_token_balance(
+        &mut context.banks_client,
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+    )
+    .await;
+    assert!(manager_fee > 0);
+
+    // following rewards are split
+    transfer(
+        &mut context.banks_client,
+        &context.payer,
+        &last_blockhash,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        LAMPORTS_PER_SOL,
+    )
+    .await;
+    slot += slots_per_epoch;
+    context.warp_to_slot(slot).unwrap();
+    let last_blockhash = context
+        .banks_client
+        .get_new_latest_blockhash(&last_blockhash)
+        .await
+        .unwrap();
+    let fee_recipients: Vec<Pubkey> = recipients.iter().map(|x| x.token_account).collect();
+
+    // recipients must be passed in order
+    let reversed: Vec<Pubkey> = fee_recipients.iter().rev().cloned().collect();
+    let transaction_error = update_stake_pool_balance(
+        &mut context,
+        &stake_pool_accounts,
+        &reversed,
+        &last_blockhash,
+    )
+    .await
+    .unwrap_err()
+    .unwrap();
+    assert_eq!(
+        transaction_error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(error::StakePoolError::InvalidFeeAccount as u32)
+        )
+    );
+
+    let stake_pool = stake_pool_accounts
+        .get_stake_pool(&mut context.banks_client)
+        .await;
+    let expected_fee = stake_pool.calc_epoch_fee_amount(LAMPORTS_PER_SOL).unwrap();
+    update_stake_pool_balance(
+        &mut context,
+        &stake_pool_accounts,
+        &fee_recipients,
+        &last_blockhash,
+    )
+    .await
+    .unwrap();
+
+    let expected_shares = fee_split.fee_shares(expected_fee).unwrap();
+    for (recipient, expected_share) in recipients.iter().zip(expected_shares) {
+        let balance = get_token_balance(&mut context.banks_client, &recipient.token_account).await;
+        assert_eq!(balance, expected_share);
+    }
+    assert_eq!(
+        get_token_balance(
+            &mut context.banks_client,
+            &stake_pool_accounts.pool_fee_account.pubkey(),
+        )
+        .await,
+        manager_fee
+    );
+}
+
+#[tokio::test]
+async fn fail_wrong_manager() {
+    let (mut context, stake_pool_accounts, recipients) = setup().await;
+
+    let wrong_manager = Keypair::new();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::set_fee_split(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &wrong_manager.pubkey(),
+            &context.payer.pubkey(),
+            recipients,
+        )],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &wrong_manager],
+        context.last_blockhash,
+    );
+    let transaction_error = context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .err()
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        transaction_error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(error::StakePoolError::WrongManager as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn fail_shares_not_adding_up() {
+    let (mut context, stake_pool_accounts, mut recipients) = setup().await;
+
+    recipients[1].bps = 2_000;
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::set_fee_split(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.manager.pubkey(),
+            &context.payer.pubkey(),
+            recipients,
+        )],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &stake_pool_accounts.manager],
+        context.last_blockhash,
+    );
+    let transaction_error = context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .err()
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        transaction_error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(error::StakePoolError::InvalidFeeSplit as u32)
+        )
+    );
+}
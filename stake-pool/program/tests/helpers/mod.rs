@@ -34,11 +34,15 @@ use {
         state::{self, FeeType, FutureEpoch, StakePool, ValidatorList},
         MAX_VALIDATORS_TO_UPDATE, MINIMUM_RESERVE_LAMPORTS,
     },
+    spl_tlv_account_resolution::{account::ExtraAccountMeta, state::ExtraAccountMetaList},
     spl_token_2022::{
         extension::{ExtensionType, StateWithExtensionsOwned},
         native_mint,
         state::{Account, Mint},
     },
+    spl_transfer_hook_interface::{
+        get_extra_account_metas_address, instruction::ExecuteInstruction,
+    },
     std::{convert::TryInto, num::NonZeroU32},
 };
 
@@ -49,6 +53,7 @@ pub const DEFAULT_VALIDATOR_STAKE_SEED: Option<NonZeroU32> = NonZeroU32::new(1_0
 pub const DEFAULT_TRANSIENT_STAKE_SEED: u64 = 42;
 pub const STAKE_ACCOUNT_RENT_EXEMPTION: u64 = 2_282_880;
 const ACCOUNT_RENT_EXEMPTION: u64 = 1_000_000_000; // go with something big to be safe
+pub const TRANSFER_HOOK_PROGRAM_ID: Pubkey = Pubkey::new_from_array([7; 32]);
 
 pub fn program_test() -> ProgramTest {
     let mut program_test = ProgramTest::new("spl_stake_pool", id(), processor!(Processor::process));
@@ -74,6 +79,33 @@ pub fn program_test_with_metadata_program() -> ProgramTest {
     program_test
 }
 
+/// Adds the example transfer hook program, along with the validation account
+/// requiring `extra_account_metas` on transfers of `mint`
+pub fn add_transfer_hook_program(
+    program_test: &mut ProgramTest,
+    mint: &Pubkey,
+    extra_account_metas: &[ExtraAccountMeta],
+) {
+    program_test.add_program(
+        "spl_transfer_hook_example",
+        TRANSFER_HOOK_PROGRAM_ID,
+        processor!(spl_transfer_hook_example::processor::process),
+    );
+    let account_size = ExtraAccountMetaList::size_of(extra_account_metas.len()).unwrap();
+    let mut data = vec![0; account_size];
+    ExtraAccountMetaList::init::<ExecuteInstruction>(&mut data, extra_account_metas).unwrap();
+    program_test.add_account(
+        get_extra_account_metas_address(mint, &TRANSFER_HOOK_PROGRAM_ID),
+        SolanaAccount::create(
+            ACCOUNT_RENT_EXEMPTION,
+            data,
+            TRANSFER_HOOK_PROGRAM_ID,
+            false,
+            Epoch::default(),
+        ),
+    );
+}
+
 pub async fn get_account(banks_client: &mut BanksClient, pubkey: &Pubkey) -> SolanaAccount {
     banks_client
         .get_account(*pubkey)
@@ -136,6 +168,18 @@ pub async fn create_mint(
             ),
             ExtensionType::NonTransferable =>
                 spl_token_2022::instruction::initialize_non_transferable_mint(program_id, &mint_pubkey),
+            ExtensionType::MetadataPointer => spl_token_2022::extension::metadata_pointer::instruction::initialize(
+                program_id,
+                &mint_pubkey,
+                Some(*manager),
+                Some(mint_pubkey),
+            ),
+            ExtensionType::TransferHook => spl_token_2022::extension::transfer_hook::instruction::initialize(
+                program_id,
+                &mint_pubkey,
+                Some(*manager),
+                Some(TRANSFER_HOOK_PROGRAM_ID),
+            ),
             _ => unimplemented!(),
         };
         instructions.push(instruction.unwrap());
@@ -249,7 +293,8 @@ pub async fn create_token_account(
             ExtensionType::TransferFeeAmount
             | ExtensionType::MemoTransfer
             | ExtensionType::CpiGuard
-            | ExtensionType::NonTransferableAccount => (),
+            | ExtensionType::NonTransferableAccount
+            | ExtensionType::TransferHookAccount => (),
             _ => unimplemented!(),
         };
     }
@@ -293,7 +338,8 @@ pub async fn create_token_account(
             }
             ExtensionType::ImmutableOwner
             | ExtensionType::TransferFeeAmount
-            | ExtensionType::NonTransferableAccount => (),
+            | ExtensionType::NonTransferableAccount
+            | ExtensionType::TransferHookAccount => (),
             _ => unimplemented!(),
         }
     }
@@ -930,6 +976,24 @@ impl StakePoolAccounts {
         payer: &Keypair,
         recent_blockhash: &Hash,
         reserve_lamports: u64,
+    ) -> Result<(), TransportError> {
+        self.initialize_stake_pool_with_mint_extensions(
+            banks_client,
+            payer,
+            recent_blockhash,
+            reserve_lamports,
+            &[],
+        )
+        .await
+    }
+
+    pub async fn initialize_stake_pool_with_mint_extensions(
+        &self,
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        reserve_lamports: u64,
+        mint_extensions: &[ExtensionType],
     ) -> Result<(), TransportError> {
         create_mint(
             banks_client,
@@ -939,7 +1003,7 @@ impl StakePoolAccounts {
             &self.pool_mint,
             &self.withdraw_authority,
             self.pool_decimals,
-            &[],
+            mint_extensions,
         )
         .await?;
         create_token_account(
@@ -950,7 +1014,7 @@ impl StakePoolAccounts {
             &self.pool_fee_account,
             &self.pool_mint.pubkey(),
             &self.manager,
-            &[],
+            &ExtensionType::get_required_init_account_extensions(mint_extensions),
         )
         .await?;
         create_independent_stake_account(
@@ -508,12 +508,15 @@ async fn fail_with_freeze_authority() {
     );
 }
 
+#[test_case(ExtensionType::TransferFeeConfig; "transfer_fee")]
+#[test_case(ExtensionType::MetadataPointer; "metadata_pointer")]
+#[test_case(ExtensionType::TransferHook; "transfer_hook")]
 #[tokio::test]
-async fn success_with_supported_extensions() {
+async fn success_with_supported_extensions(mint_extension: ExtensionType) {
     let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
     let stake_pool_accounts = StakePoolAccounts::new_with_token_program(spl_token_2022::id());
 
-    let mint_extensions = vec![ExtensionType::TransferFeeConfig];
+    let mint_extensions = vec![mint_extension];
     create_required_accounts(
         &mut banks_client,
         &payer,
@@ -632,6 +635,111 @@ async fn fail_with_unsupported_mint_extension() {
     );
 }
 
+#[tokio::test]
+async fn fail_with_transfer_hook_authority() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let stake_pool_accounts = StakePoolAccounts::new_with_token_program(spl_token_2022::id());
+
+    create_required_accounts(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        &[],
+    )
+    .await;
+
+    // create mint with a transfer hook the manager can change
+    let wrong_mint = Keypair::new();
+    let mint_extensions = vec![ExtensionType::TransferHook];
+    let space =
+        ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(&mint_extensions)
+            .unwrap();
+    let rent = banks_client.get_rent().await.unwrap();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            system_instruction::create_account(
+                &payer.pubkey(),
+                &wrong_mint.pubkey(),
+                rent.minimum_balance(space),
+                space as u64,
+                &spl_token_2022::id(),
+            ),
+            spl_token_2022::extension::transfer_hook::instruction::initialize(
+                &spl_token_2022::id(),
+                &wrong_mint.pubkey(),
+                Some(stake_pool_accounts.manager.pubkey()),
+                Some(TRANSFER_HOOK_PROGRAM_ID),
+            )
+            .unwrap(),
+            spl_token_2022::instruction::initialize_mint(
+                &spl_token_2022::id(),
+                &wrong_mint.pubkey(),
+                &stake_pool_accounts.withdraw_authority,
+                None,
+                stake_pool_accounts.pool_decimals,
+            )
+            .unwrap(),
+        ],
+        Some(&payer.pubkey()),
+        &[&payer, &wrong_mint],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let pool_fee_account = Keypair::new();
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts.token_program_id,
+        &pool_fee_account,
+        &wrong_mint.pubkey(),
+        &stake_pool_accounts.manager,
+        &ExtensionType::get_required_init_account_extensions(&mint_extensions),
+    )
+    .await
+    .unwrap();
+
+    let error = create_stake_pool(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts.stake_pool,
+        &stake_pool_accounts.validator_list,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        &stake_pool_accounts.token_program_id,
+        &wrong_mint.pubkey(),
+        &pool_fee_account.pubkey(),
+        &stake_pool_accounts.manager,
+        &stake_pool_accounts.staker.pubkey(),
+        &stake_pool_accounts.withdraw_authority,
+        &None,
+        &stake_pool_accounts.epoch_fee,
+        &stake_pool_accounts.withdrawal_fee,
+        &stake_pool_accounts.deposit_fee,
+        stake_pool_accounts.referral_fee,
+        &stake_pool_accounts.sol_deposit_fee,
+        stake_pool_accounts.sol_referral_fee,
+        stake_pool_accounts.max_validators,
+    )
+    .await
+    .err()
+    .unwrap()
+    .unwrap();
+
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            2,
+            InstructionError::Custom(
+                error::StakePoolError::InvalidMintTransferHookAuthority as u32
+            ),
+        )
+    );
+}
+
 #[tokio::test]
 async fn fail_with_unsupported_account_extension() {
     let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
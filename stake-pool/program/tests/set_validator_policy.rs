@@ -0,0 +1,472 @@
+#![allow(clippy::arithmetic_side_effects)]
+#![cfg(feature = "test-sbf")]
+
+mod helpers;
+
+use {
+    bincode::deserialize,
+    helpers::*,
+    solana_program::{
+        borsh1::try_from_slice_unchecked, clock::Epoch, hash::Hash, instruction::Instruction, stake,
+    },
+    solana_program_test::*,
+    solana_sdk::{
+        instruction::InstructionError,
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+        transport::TransportError,
+    },
+    spl_stake_pool::{
+        error, find_validator_policy_program_address, id, instruction, state,
+        MINIMUM_RESERVE_LAMPORTS,
+    },
+};
+
+async fn set_validator_policy(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    stake_pool_accounts: &StakePoolAccounts,
+    max_commission: u8,
+    max_delinquent_slots: u64,
+) {
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::set_validator_policy(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.staker.pubkey(),
+            &payer.pubkey(),
+            max_commission,
+            0,
+            max_delinquent_slots,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[payer, &stake_pool_accounts.staker], *recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+}
+
+async fn update_validator_list_balance_with_policy(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    stake_pool_accounts: &StakePoolAccounts,
+) -> Option<TransportError> {
+    let validator_list = stake_pool_accounts.get_validator_list(banks_client).await;
+    let mut instructions: Vec<Instruction> = vec![
+        instruction::update_validator_list_balance_with_policy_chunk(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.withdraw_authority,
+            &stake_pool_accounts.validator_list.pubkey(),
+            &stake_pool_accounts.reserve_stake.pubkey(),
+            &validator_list,
+            validator_list.validators.len(),
+            0,
+            false,
+        )
+        .unwrap(),
+    ];
+    stake_pool_accounts.maybe_add_compute_budget_instruction(&mut instructions);
+    let transaction = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&payer.pubkey()),
+        &[payer],
+        *recent_blockhash,
+    );
+    banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.into())
+        .err()
+}
+
+async fn setup() -> (BanksClient, Keypair, Hash, StakePoolAccounts) {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let stake_pool_accounts = StakePoolAccounts::default();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            MINIMUM_RESERVE_LAMPORTS,
+        )
+        .await
+        .unwrap();
+
+    (banks_client, payer, recent_blockhash, stake_pool_accounts)
+}
+
+#[tokio::test]
+async fn success_create_and_update() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts) = setup().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::set_validator_policy(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.staker.pubkey(),
+            &payer.pubkey(),
+            10,
+            5_000,
+            1_000,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &stake_pool_accounts.staker], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let (validator_policy_address, _) =
+        find_validator_policy_program_address(&id(), &stake_pool_accounts.stake_pool.pubkey());
+    let validator_policy = get_account(&mut banks_client, &validator_policy_address).await;
+    let validator_policy =
+        try_from_slice_unchecked::<state::ValidatorPolicy>(validator_policy.data.as_slice())
+            .unwrap();
+    assert_eq!(
+        validator_policy,
+        state::ValidatorPolicy {
+            account_type: state::AccountType::ValidatorPolicy,
+            stake_pool: stake_pool_accounts.stake_pool.pubkey(),
+            max_commission: 10,
+            min_epoch_credits_bps: 5_000,
+            max_delinquent_slots: 1_000,
+            cluster_credits_epoch: 0,
+            cluster_epoch_credits: 0,
+        }
+    );
+
+    let last_blockhash = banks_client
+        .get_new_latest_blockhash(&recent_blockhash)
+        .await
+        .unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::set_validator_policy(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.staker.pubkey(),
+            &payer.pubkey(),
+            100,
+            0,
+            0,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &stake_pool_accounts.staker], last_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let validator_policy = get_account(&mut banks_client, &validator_policy_address).await;
+    let validator_policy =
+        try_from_slice_unchecked::<state::ValidatorPolicy>(validator_policy.data.as_slice())
+            .unwrap();
+    assert!(!validator_policy.is_enabled());
+}
+
+#[tokio::test]
+async fn fail_wrong_staker() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts) = setup().await;
+
+    let wrong_staker = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::set_validator_policy(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &wrong_staker.pubkey(),
+            &payer.pubkey(),
+            10,
+            0,
+            0,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &wrong_staker], recent_blockhash);
+    let transaction_error = banks_client
+        .process_transaction(transaction)
+        .await
+        .err()
+        .unwrap()
+        .into();
+
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = error::StakePoolError::WrongStaker as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!("Wrong error occurs while malicious try to set validator policy"),
+    }
+}
+
+#[tokio::test]
+async fn fail_invalid_policy() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts) = setup().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::set_validator_policy(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.staker.pubkey(),
+            &payer.pubkey(),
+            10,
+            state::ValidatorPolicy::MAX_EPOCH_CREDITS_BPS + 1,
+            0,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &stake_pool_accounts.staker], recent_blockhash);
+    let transaction_error = banks_client
+        .process_transaction(transaction)
+        .await
+        .err()
+        .unwrap()
+        .into();
+
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = error::StakePoolError::InvalidValidatorPolicy as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!("Wrong error occurs while setting an invalid validator policy"),
+    }
+}
+
+#[tokio::test]
+async fn success_deactivate_violating_validator() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts) = setup().await;
+    let validator_stake = simple_add_validator_to_pool(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        None,
+    )
+    .await;
+
+    // the new vote account never voted, so it is delinquent
+    set_validator_policy(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        100,
+        1_000,
+    )
+    .await;
+
+    let error = update_validator_list_balance_with_policy(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+    )
+    .await;
+    assert!(error.is_none(), "{:?}", error);
+
+    let validator_list = stake_pool_accounts
+        .get_validator_list(&mut banks_client)
+        .await;
+    let validator_stake_info = validator_list.find(&validator_stake.vote.pubkey()).unwrap();
+    assert_eq!(
+        validator_stake_info.status,
+        state::StakeStatus::DeactivatingValidator.into()
+    );
+    assert!(validator_stake_info.is_policy_enforced());
+
+    let stake = get_account(&mut banks_client, &validator_stake.stake_account).await;
+    let stake_state = deserialize::<stake::state::StakeStateV2>(&stake.data).unwrap();
+    assert_ne!(
+        stake_state.stake().unwrap().delegation.deactivation_epoch,
+        Epoch::MAX
+    );
+}
+
+#[tokio::test]
+async fn fail_plain_update_under_policy() {
+    let (mut banks_client, payer, recent_blockhash, stake_pool_accounts) = setup().await;
+    let validator_stake = simple_add_validator_to_pool(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        None,
+    )
+    .await;
+
+    // the new vote account has no commission, so it complies
+    set_validator_policy(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+        10,
+        0,
+    )
+    .await;
+    let error = update_validator_list_balance_with_policy(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &stake_pool_accounts,
+    )
+    .await;
+    assert!(error.is_none(), "{:?}", error);
+
+    let validator_list = stake_pool_accounts
+        .get_validator_list(&mut banks_client)
+        .await;
+    let validator_stake_info = validator_list.find(&validator_stake.vote.pubkey()).unwrap();
+    assert_eq!(
+        validator_stake_info.status,
+        state::StakeStatus::Active.into()
+    );
+    assert!(validator_stake_info.is_policy_enforced());
+
+    let last_blockhash = banks_client
+        .get_new_latest_blockhash(&recent_blockhash)
+        .await
+        .unwrap();
+    let error = stake_pool_accounts
+        .update_validator_list_balance(&mut banks_client, &payer, &last_blockhash, 1, false)
+        .await
+        .unwrap();
+    match error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = error::StakePoolError::ValidatorPolicyRequired as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!("Wrong error occurs while bypassing the validator policy"),
+    }
+
+    // disabling the policy lets the next update clear the flag
+    set_validator_policy(
+        &mut banks_client,
+        &payer,
+        &last_blockhash,
+        &stake_pool_accounts,
+        100,
+        0,
+    )
+    .await;
+    let error = update_validator_list_balance_with_policy(
+        &mut banks_client,
+        &payer,
+        &last_blockhash,
+        &stake_pool_accounts,
+    )
+    .await;
+    assert!(error.is_none(), "{:?}", error);
+
+    let last_blockhash = banks_client
+        .get_new_latest_blockhash(&last_blockhash)
+        .await
+        .unwrap();
+    let error = stake_pool_accounts
+        .update_validator_list_balance(&mut banks_client, &payer, &last_blockhash, 1, false)
+        .await;
+    assert!(error.is_none(), "{:?}", error);
+}
+
+#[tokio::test]
+async fn success_set_cluster_epoch_credits() {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::default();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            MINIMUM_RESERVE_LAMPORTS,
+        )
+        .await
+        .unwrap();
+    set_validator_policy(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts,
+        10,
+        0,
+    )
+    .await;
+
+    let first_normal_slot = context.genesis_config().epoch_schedule.first_normal_slot;
+    let slots_per_epoch = context.genesis_config().epoch_schedule.slots_per_epoch;
+    context
+        .warp_to_slot(first_normal_slot + slots_per_epoch)
+        .unwrap();
+    let epoch = context
+        .banks_client
+        .get_sysvar::<solana_program::clock::Clock>()
+        .await
+        .unwrap()
+        .epoch;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::set_cluster_epoch_credits(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.staker.pubkey(),
+            epoch - 1,
+            6_000,
+        )],
+        Some(&context.payer.pubkey()),
+    );
+    transaction.sign(
+        &[&context.payer, &stake_pool_accounts.staker],
+        context.last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let (validator_policy_address, _) =
+        find_validator_policy_program_address(&id(), &stake_pool_accounts.stake_pool.pubkey());
+    let validator_policy = get_account(&mut context.banks_client, &validator_policy_address).await;
+    let validator_policy =
+        try_from_slice_unchecked::<state::ValidatorPolicy>(validator_policy.data.as_slice())
+            .unwrap();
+    assert_eq!(validator_policy.cluster_credits_epoch, epoch - 1);
+    assert_eq!(validator_policy.cluster_epoch_credits, 6_000);
+
+    // the current epoch is still running
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::set_cluster_epoch_credits(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &stake_pool_accounts.staker.pubkey(),
+            epoch,
+            6_000,
+        )],
+        Some(&context.payer.pubkey()),
+    );
+    transaction.sign(
+        &[&context.payer, &stake_pool_accounts.staker],
+        context.last_blockhash,
+    );
+    let transaction_error = context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .err()
+        .unwrap()
+        .into();
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = error::StakePoolError::InvalidClusterEpochCredits as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!("Wrong error occurs while recording credits of the current epoch"),
+    }
+}
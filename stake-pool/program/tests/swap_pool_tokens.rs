@@ -0,0 +1,301 @@
+#![allow(clippy::arithmetic_side_effects)]
+#![cfg(feature = "test-sbf")]
+
+mod helpers;
+
+use {
+    helpers::*,
+    solana_program::{instruction::InstructionError, pubkey::Pubkey, stake},
+    solana_program_test::*,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    },
+    spl_stake_pool::{
+        error::StakePoolError, find_stake_program_address, id, instruction,
+        MINIMUM_RESERVE_LAMPORTS,
+    },
+};
+
+async fn setup() -> (
+    ProgramTestContext,
+    StakePoolAccounts,
+    StakePoolAccounts,
+    ValidatorStakeAccount,
+    Pubkey,
+    DepositStakeAccount,
+    Keypair,
+) {
+    let mut context = program_test().start_with_context().await;
+
+    let source_pool_accounts = StakePoolAccounts::default();
+    source_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            MINIMUM_RESERVE_LAMPORTS,
+        )
+        .await
+        .unwrap();
+    let destination_pool_accounts = StakePoolAccounts::default();
+    destination_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            MINIMUM_RESERVE_LAMPORTS,
+        )
+        .await
+        .unwrap();
+
+    let validator_stake_account = simple_add_validator_to_pool(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &source_pool_accounts,
+        None,
+    )
+    .await;
+
+    // add the same validator to the destination pool, funding its reserve first
+    let current_minimum_delegation = stake_pool_get_minimum_delegation(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+    )
+    .await;
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let stake_rent = rent.minimum_balance(std::mem::size_of::<stake::state::StakeStateV2>());
+    let reserve_funding_account = Keypair::new();
+    create_token_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &destination_pool_accounts.token_program_id,
+        &reserve_funding_account,
+        &destination_pool_accounts.pool_mint.pubkey(),
+        &context.payer,
+        &[],
+    )
+    .await
+    .unwrap();
+    let error = destination_pool_accounts
+        .deposit_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &reserve_funding_account.pubkey(),
+            stake_rent + current_minimum_delegation,
+            None,
+        )
+        .await;
+    assert!(error.is_none(), "{:?}", error);
+
+    let (destination_validator_stake, _) = find_stake_program_address(
+        &id(),
+        &validator_stake_account.vote.pubkey(),
+        &destination_pool_accounts.stake_pool.pubkey(),
+        DEFAULT_VALIDATOR_STAKE_SEED,
+    );
+    let error = destination_pool_accounts
+        .add_validator_to_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &destination_validator_stake,
+            &validator_stake_account.vote.pubkey(),
+            DEFAULT_VALIDATOR_STAKE_SEED,
+        )
+        .await;
+    assert!(error.is_none(), "{:?}", error);
+
+    let deposit_info = simple_deposit_stake(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &source_pool_accounts,
+        &validator_stake_account,
+        TEST_STAKE_AMOUNT,
+    )
+    .await
+    .unwrap();
+
+    let first_normal_slot = context.genesis_config().epoch_schedule.first_normal_slot;
+    context.warp_to_slot(first_normal_slot + 1).unwrap();
+    for stake_pool_accounts in [&source_pool_accounts, &destination_pool_accounts] {
+        let error = stake_pool_accounts
+            .update_all(
+                &mut context.banks_client,
+                &context.payer,
+                &context.last_blockhash,
+                false,
+            )
+            .await;
+        assert!(error.is_none(), "{:?}", error);
+    }
+
+    let destination_pool_account = Keypair::new();
+    create_token_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &destination_pool_accounts.token_program_id,
+        &destination_pool_account,
+        &destination_pool_accounts.pool_mint.pubkey(),
+        &deposit_info.authority,
+        &[],
+    )
+    .await
+    .unwrap();
+
+    (
+        context,
+        source_pool_accounts,
+        destination_pool_accounts,
+        validator_stake_account,
+        destination_validator_stake,
+        deposit_info,
+        destination_pool_account,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn swap_pool_tokens(
+    context: &mut ProgramTestContext,
+    source_pool_accounts: &StakePoolAccounts,
+    destination_pool_accounts: &StakePoolAccounts,
+    vote_account_address: &Pubkey,
+    deposit_info: &DepositStakeAccount,
+    destination_pool_account: &Pubkey,
+    pool_tokens_in: u64,
+    minimum_pool_tokens_out: Option<u64>,
+) -> Result<(), BanksClientError> {
+    let stake_receiver = Keypair::new();
+    create_blank_stake_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_receiver,
+    )
+    .await;
+
+    let source_stake_pool = source_pool_accounts
+        .get_stake_pool(&mut context.banks_client)
+        .await;
+    let destination_stake_pool = destination_pool_accounts
+        .get_stake_pool(&mut context.banks_client)
+        .await;
+    let instructions = instruction::swap_pool_tokens(
+        &id(),
+        &source_stake_pool,
+        &source_pool_accounts.stake_pool.pubkey(),
+        DEFAULT_VALIDATOR_STAKE_SEED,
+        &destination_stake_pool,
+        &destination_pool_accounts.stake_pool.pubkey(),
+        DEFAULT_VALIDATOR_STAKE_SEED,
+        vote_account_address,
+        &stake_receiver.pubkey(),
+        &deposit_info.authority.pubkey(),
+        &deposit_info.pool_account.pubkey(),
+        destination_pool_account,
+        pool_tokens_in,
+        minimum_pool_tokens_out,
+    );
+    let transaction = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &deposit_info.authority],
+        context.last_blockhash,
+    );
+    context.banks_client.process_transaction(transaction).await
+}
+
+#[tokio::test]
+async fn success() {
+    let (
+        mut context,
+        source_pool_accounts,
+        destination_pool_accounts,
+        validator_stake_account,
+        destination_validator_stake,
+        deposit_info,
+        destination_pool_account,
+    ) = setup().await;
+
+    let destination_stake_before =
+        get_account(&mut context.banks_client, &destination_validator_stake)
+            .await
+            .lamports;
+    let pool_tokens_in = deposit_info.pool_tokens / 2;
+
+    swap_pool_tokens(
+        &mut context,
+        &source_pool_accounts,
+        &destination_pool_accounts,
+        &validator_stake_account.vote.pubkey(),
+        &deposit_info,
+        &destination_pool_account.pubkey(),
+        pool_tokens_in,
+        None,
+    )
+    .await
+    .unwrap();
+
+    let source_tokens = get_token_balance(
+        &mut context.banks_client,
+        &deposit_info.pool_account.pubkey(),
+    )
+    .await;
+    assert_eq!(source_tokens, deposit_info.pool_tokens - pool_tokens_in);
+
+    let destination_tokens = get_token_balance(
+        &mut context.banks_client,
+        &destination_pool_account.pubkey(),
+    )
+    .await;
+    assert!(destination_tokens > 0);
+
+    // the withdrawn stake was merged into the destination pool's validator stake
+    let destination_stake_after =
+        get_account(&mut context.banks_client, &destination_validator_stake)
+            .await
+            .lamports;
+    assert!(destination_stake_after > destination_stake_before);
+}
+
+#[tokio::test]
+async fn fail_with_slippage() {
+    let (
+        mut context,
+        source_pool_accounts,
+        destination_pool_accounts,
+        validator_stake_account,
+        _,
+        deposit_info,
+        destination_pool_account,
+    ) = setup().await;
+
+    let error = swap_pool_tokens(
+        &mut context,
+        &source_pool_accounts,
+        &destination_pool_accounts,
+        &validator_stake_account.vote.pubkey(),
+        &deposit_info,
+        &destination_pool_account.pubkey(),
+        deposit_info.pool_tokens / 2,
+        Some(u64::MAX),
+    )
+    .await
+    .unwrap_err()
+    .unwrap();
+
+    // withdraw, then two authorizes, then the deposit
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            3,
+            InstructionError::Custom(StakePoolError::ExceededSlippage as u32)
+        )
+    );
+}
@@ -0,0 +1,231 @@
+#![allow(clippy::arithmetic_side_effects)]
+#![cfg(feature = "test-sbf")]
+
+mod helpers;
+
+use {
+    helpers::*,
+    solana_program::{instruction::InstructionError, pubkey::Pubkey, stake},
+    solana_program_test::*,
+    solana_sdk::{
+        instruction::Instruction,
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    },
+    spl_stake_pool::{
+        error::StakePoolError::{InvalidMetadataPointer, WrongManager},
+        instruction, MINIMUM_RESERVE_LAMPORTS,
+    },
+    spl_token_2022::{
+        extension::{BaseStateWithExtensions, ExtensionType, StateWithExtensions},
+        state::Mint,
+    },
+    spl_token_metadata_interface::state::TokenMetadata,
+};
+
+async fn setup(mint_extensions: &[ExtensionType]) -> (ProgramTestContext, StakePoolAccounts) {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::new_with_token_program(spl_token_2022::id());
+
+    create_mint(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts.token_program_id,
+        &stake_pool_accounts.pool_mint,
+        &stake_pool_accounts.withdraw_authority,
+        stake_pool_accounts.pool_decimals,
+        mint_extensions,
+    )
+    .await
+    .unwrap();
+    create_token_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts.token_program_id,
+        &stake_pool_accounts.pool_fee_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &stake_pool_accounts.manager,
+        &ExtensionType::get_required_init_account_extensions(mint_extensions),
+    )
+    .await
+    .unwrap();
+    create_independent_stake_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts.reserve_stake,
+        &stake::state::Authorized {
+            staker: stake_pool_accounts.withdraw_authority,
+            withdrawer: stake_pool_accounts.withdraw_authority,
+        },
+        &stake::state::Lockup::default(),
+        MINIMUM_RESERVE_LAMPORTS,
+    )
+    .await;
+    create_stake_pool(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts.stake_pool,
+        &stake_pool_accounts.validator_list,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        &stake_pool_accounts.token_program_id,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+        &stake_pool_accounts.manager,
+        &stake_pool_accounts.staker.pubkey(),
+        &stake_pool_accounts.withdraw_authority,
+        &None,
+        &stake_pool_accounts.epoch_fee,
+        &stake_pool_accounts.withdrawal_fee,
+        &stake_pool_accounts.deposit_fee,
+        stake_pool_accounts.referral_fee,
+        &stake_pool_accounts.sol_deposit_fee,
+        stake_pool_accounts.sol_referral_fee,
+        stake_pool_accounts.max_validators,
+    )
+    .await
+    .unwrap();
+
+    (context, stake_pool_accounts)
+}
+
+async fn get_token_metadata(
+    context: &mut ProgramTestContext,
+    stake_pool_accounts: &StakePoolAccounts,
+) -> TokenMetadata {
+    let mint = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.pool_mint.pubkey(),
+    )
+    .await;
+    let mint = StateWithExtensions::<Mint>::unpack(mint.data.as_slice()).unwrap();
+    mint.get_variable_len_extension::<TokenMetadata>().unwrap()
+}
+
+async fn process_instruction(
+    context: &mut ProgramTestContext,
+    instruction: Instruction,
+    manager: &Keypair,
+) -> Result<(), BanksClientError> {
+    let last_blockhash = context
+        .banks_client
+        .get_new_latest_blockhash(&context.last_blockhash)
+        .await
+        .unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, manager],
+        last_blockhash,
+    );
+    context.banks_client.process_transaction(transaction).await
+}
+
+#[tokio::test]
+async fn success() {
+    let (mut context, stake_pool_accounts) = setup(&[ExtensionType::MetadataPointer]).await;
+
+    let ix = instruction::create_token_2022_metadata(
+        &spl_stake_pool::id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &stake_pool_accounts.manager.pubkey(),
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &context.payer.pubkey(),
+        "test_name".to_string(),
+        "SYM".to_string(),
+        "test_uri".to_string(),
+    );
+    process_instruction(&mut context, ix, &stake_pool_accounts.manager)
+        .await
+        .unwrap();
+
+    let token_metadata = get_token_metadata(&mut context, &stake_pool_accounts).await;
+    assert_eq!(
+        Option::<Pubkey>::from(token_metadata.update_authority),
+        Some(stake_pool_accounts.withdraw_authority)
+    );
+    assert_eq!(token_metadata.mint, stake_pool_accounts.pool_mint.pubkey());
+    assert_eq!(token_metadata.name, "test_name");
+    assert_eq!(token_metadata.symbol, "SYM");
+    assert_eq!(token_metadata.uri, "test_uri");
+
+    // a longer uri grows the mint, topped up by the payer
+    let updated_uri = "https://example.com/a/much/longer/uri/for/the/pool/token.json";
+    let ix = instruction::update_token_2022_metadata(
+        &spl_stake_pool::id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &stake_pool_accounts.manager.pubkey(),
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &context.payer.pubkey(),
+        "updated_name".to_string(),
+        "SYM".to_string(),
+        updated_uri.to_string(),
+    );
+    process_instruction(&mut context, ix, &stake_pool_accounts.manager)
+        .await
+        .unwrap();
+
+    let token_metadata = get_token_metadata(&mut context, &stake_pool_accounts).await;
+    assert_eq!(token_metadata.name, "updated_name");
+    assert_eq!(token_metadata.symbol, "SYM");
+    assert_eq!(token_metadata.uri, updated_uri);
+}
+
+#[tokio::test]
+async fn fail_without_metadata_pointer() {
+    let (mut context, stake_pool_accounts) = setup(&[]).await;
+
+    let ix = instruction::create_token_2022_metadata(
+        &spl_stake_pool::id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &stake_pool_accounts.manager.pubkey(),
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &context.payer.pubkey(),
+        "test_name".to_string(),
+        "SYM".to_string(),
+        "test_uri".to_string(),
+    );
+    let error = process_instruction(&mut context, ix, &stake_pool_accounts.manager)
+        .await
+        .err()
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(InvalidMetadataPointer as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn fail_wrong_manager_signed() {
+    let (mut context, stake_pool_accounts) = setup(&[ExtensionType::MetadataPointer]).await;
+
+    let random_keypair = Keypair::new();
+    let ix = instruction::create_token_2022_metadata(
+        &spl_stake_pool::id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &random_keypair.pubkey(),
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &context.payer.pubkey(),
+        "test_name".to_string(),
+        "SYM".to_string(),
+        "test_uri".to_string(),
+    );
+    let error = process_instruction(&mut context, ix, &random_keypair)
+        .await
+        .err()
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(0, InstructionError::Custom(WrongManager as u32))
+    );
+}
@@ -0,0 +1,263 @@
+#![allow(clippy::arithmetic_side_effects)]
+#![cfg(feature = "test-sbf")]
+
+mod helpers;
+
+use {
+    helpers::*,
+    solana_program::{instruction::Instruction, pubkey::Pubkey},
+    solana_program_test::*,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::Transaction,
+    },
+    spl_stake_pool::{id, instruction, MINIMUM_RESERVE_LAMPORTS},
+    spl_tlv_account_resolution::account::ExtraAccountMeta,
+    spl_token_2022::{extension::ExtensionType, offchain::AccountFetchError},
+};
+
+async fn setup() -> (ProgramTestContext, StakePoolAccounts, Keypair, Pubkey, u64) {
+    let stake_pool_accounts = StakePoolAccounts::new_with_token_program(spl_token_2022::id());
+
+    // the hook requires an extra account on every transfer, so the withdrawal
+    // fee can only be collected if the client resolved it
+    let mut program_test = program_test();
+    add_transfer_hook_program(
+        &mut program_test,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &[ExtraAccountMeta::new_with_pubkey(&Pubkey::new_unique(), false, false).unwrap()],
+    );
+    let mut context = program_test.start_with_context().await;
+
+    let mint_extensions = [ExtensionType::TransferHook];
+    stake_pool_accounts
+        .initialize_stake_pool_with_mint_extensions(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            MINIMUM_RESERVE_LAMPORTS,
+            &mint_extensions,
+        )
+        .await
+        .unwrap();
+
+    let user = Keypair::new();
+
+    // make pool token account for user
+    let pool_token_account = Keypair::new();
+    create_token_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts.token_program_id,
+        &pool_token_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user,
+        &ExtensionType::get_required_init_account_extensions(&mint_extensions),
+    )
+    .await
+    .unwrap();
+
+    let error = stake_pool_accounts
+        .deposit_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &pool_token_account.pubkey(),
+            TEST_STAKE_AMOUNT,
+            None,
+        )
+        .await;
+    assert!(error.is_none(), "{:?}", error);
+
+    let tokens_issued =
+        get_token_balance(&mut context.banks_client, &pool_token_account.pubkey()).await;
+
+    (
+        context,
+        stake_pool_accounts,
+        user,
+        pool_token_account.pubkey(),
+        tokens_issued,
+    )
+}
+
+async fn add_transfer_hook_accounts(
+    banks_client: &BanksClient,
+    instruction: &mut Instruction,
+    stake_pool_accounts: &StakePoolAccounts,
+    pool_token_account: &Pubkey,
+    user_transfer_authority: &Pubkey,
+    pool_tokens_fee: u64,
+) {
+    instruction::add_transfer_hook_accounts(
+        instruction,
+        pool_token_account,
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+        &stake_pool_accounts.pool_mint.pubkey(),
+        user_transfer_authority,
+        pool_tokens_fee,
+        |address| {
+            let mut banks_client = banks_client.clone();
+            async move {
+                banks_client
+                    .get_account(address)
+                    .await
+                    .map(|account| account.map(|account| account.data))
+                    .map_err(AccountFetchError::from)
+            }
+        },
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn success_withdraw_sol() {
+    let (mut context, stake_pool_accounts, user, pool_token_account, pool_tokens) = setup().await;
+
+    let pre_fee_balance = get_token_balance(
+        &mut context.banks_client,
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+    )
+    .await;
+
+    let withdrawal_fee = stake_pool_accounts.calculate_withdrawal_fee(pool_tokens);
+    let mut withdraw_instruction = instruction::withdraw_sol(
+        &id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &stake_pool_accounts.withdraw_authority,
+        &user.pubkey(),
+        &pool_token_account,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        &user.pubkey(),
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &stake_pool_accounts.token_program_id,
+        pool_tokens,
+    );
+    add_transfer_hook_accounts(
+        &context.banks_client,
+        &mut withdraw_instruction,
+        &stake_pool_accounts,
+        &pool_token_account,
+        &user.pubkey(),
+        withdrawal_fee,
+    )
+    .await;
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[withdraw_instruction],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &user],
+        context.last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let user_token_balance =
+        get_token_balance(&mut context.banks_client, &pool_token_account).await;
+    assert_eq!(user_token_balance, 0);
+
+    let post_fee_balance = get_token_balance(
+        &mut context.banks_client,
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+    )
+    .await;
+    assert_eq!(post_fee_balance, pre_fee_balance + withdrawal_fee);
+}
+
+#[tokio::test]
+async fn success_withdraw_stake_from_reserve() {
+    let (mut context, stake_pool_accounts, user, pool_token_account, pool_tokens) = setup().await;
+
+    let pre_fee_balance = get_token_balance(
+        &mut context.banks_client,
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+    )
+    .await;
+
+    let user_stake_recipient = Keypair::new();
+    create_blank_stake_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &user_stake_recipient,
+    )
+    .await;
+
+    let tokens_to_withdraw = pool_tokens / 2;
+    let withdrawal_fee = stake_pool_accounts.calculate_withdrawal_fee(tokens_to_withdraw);
+    let mut withdraw_instruction = instruction::withdraw_stake(
+        &id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &stake_pool_accounts.validator_list.pubkey(),
+        &stake_pool_accounts.withdraw_authority,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        &user_stake_recipient.pubkey(),
+        &user.pubkey(),
+        &user.pubkey(),
+        &pool_token_account,
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &stake_pool_accounts.token_program_id,
+        tokens_to_withdraw,
+    );
+    add_transfer_hook_accounts(
+        &context.banks_client,
+        &mut withdraw_instruction,
+        &stake_pool_accounts,
+        &pool_token_account,
+        &user.pubkey(),
+        withdrawal_fee,
+    )
+    .await;
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[withdraw_instruction],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &user],
+        context.last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let user_token_balance =
+        get_token_balance(&mut context.banks_client, &pool_token_account).await;
+    assert_eq!(user_token_balance, pool_tokens - tokens_to_withdraw);
+
+    let post_fee_balance = get_token_balance(
+        &mut context.banks_client,
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+    )
+    .await;
+    assert_eq!(post_fee_balance, pre_fee_balance + withdrawal_fee);
+}
+
+#[tokio::test]
+async fn fail_withdraw_sol_without_transfer_hook_accounts() {
+    let (mut context, stake_pool_accounts, user, pool_token_account, pool_tokens) = setup().await;
+
+    let error = stake_pool_accounts
+        .withdraw_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &user,
+            &pool_token_account,
+            pool_tokens,
+            None,
+        )
+        .await;
+    assert!(error.is_some());
+
+    let user_token_balance =
+        get_token_balance(&mut context.banks_client, &pool_token_account).await;
+    assert_eq!(user_token_balance, pool_tokens);
+}
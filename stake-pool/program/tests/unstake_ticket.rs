@@ -0,0 +1,410 @@
+#![allow(clippy::arithmetic_side_effects)]
+#![cfg(feature = "test-sbf")]
+
+mod helpers;
+
+use {
+    helpers::*,
+    solana_program::{
+        borsh1::{get_packed_len, try_from_slice_unchecked},
+        instruction::InstructionError,
+        pubkey::Pubkey,
+        system_instruction,
+    },
+    solana_program_test::*,
+    solana_sdk::{
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+        transport::TransportError,
+    },
+    spl_stake_pool::{
+        error::StakePoolError, find_unstake_ticket_ledger_program_address, id, instruction,
+        state, MINIMUM_RESERVE_LAMPORTS,
+    },
+};
+
+async fn setup() -> (ProgramTestContext, StakePoolAccounts, Keypair, Pubkey, u64) {
+    let mut context = program_test().start_with_context().await;
+
+    let stake_pool_accounts = StakePoolAccounts::default();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            MINIMUM_RESERVE_LAMPORTS,
+        )
+        .await
+        .unwrap();
+
+    let user = Keypair::new();
+
+    // make pool token account for user
+    let pool_token_account = Keypair::new();
+    create_token_account(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts.token_program_id,
+        &pool_token_account,
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &user,
+        &[],
+    )
+    .await
+    .unwrap();
+
+    let error = stake_pool_accounts
+        .deposit_sol(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            &pool_token_account.pubkey(),
+            TEST_STAKE_AMOUNT,
+            None,
+        )
+        .await;
+    assert!(error.is_none(), "{:?}", error);
+
+    let tokens_issued =
+        get_token_balance(&mut context.banks_client, &pool_token_account.pubkey()).await;
+
+    (
+        context,
+        stake_pool_accounts,
+        user,
+        pool_token_account.pubkey(),
+        tokens_issued,
+    )
+}
+
+async fn create_unstake_ticket(
+    context: &mut ProgramTestContext,
+    stake_pool_accounts: &StakePoolAccounts,
+    user: &Keypair,
+    pool_token_account: &Pubkey,
+    unstake_ticket: &Keypair,
+    pool_tokens: u64,
+) -> Option<TransportError> {
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let unstake_ticket_len = get_packed_len::<state::UnstakeTicket>();
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            system_instruction::create_account(
+                &context.payer.pubkey(),
+                &unstake_ticket.pubkey(),
+                rent.minimum_balance(unstake_ticket_len),
+                unstake_ticket_len as u64,
+                &id(),
+            ),
+            instruction::create_unstake_ticket(
+                &id(),
+                &stake_pool_accounts.stake_pool.pubkey(),
+                &unstake_ticket.pubkey(),
+                &user.pubkey(),
+                &user.pubkey(),
+                pool_token_account,
+                &stake_pool_accounts.pool_fee_account.pubkey(),
+                &stake_pool_accounts.pool_mint.pubkey(),
+                &context.payer.pubkey(),
+                &stake_pool_accounts.token_program_id,
+                pool_tokens,
+            ),
+        ],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, user, unstake_ticket],
+        context.last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.into())
+        .err()
+}
+
+async fn claim_unstake_ticket(
+    context: &mut ProgramTestContext,
+    stake_pool_accounts: &StakePoolAccounts,
+    user: &Keypair,
+    unstake_ticket: &Pubkey,
+) -> Option<TransportError> {
+    let last_blockhash = context
+        .banks_client
+        .get_new_latest_blockhash(&context.last_blockhash)
+        .await
+        .unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::claim_unstake_ticket(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            unstake_ticket,
+            &user.pubkey(),
+            &user.pubkey(),
+        )],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, user],
+        last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.into())
+        .err()
+}
+
+async fn get_unstake_ticket_ledger(
+    context: &mut ProgramTestContext,
+    stake_pool_accounts: &StakePoolAccounts,
+) -> state::UnstakeTicketLedger {
+    let (unstake_ticket_ledger, _) = find_unstake_ticket_ledger_program_address(
+        &id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+    );
+    let account = get_account(&mut context.banks_client, &unstake_ticket_ledger).await;
+    try_from_slice_unchecked::<state::UnstakeTicketLedger>(account.data.as_slice()).unwrap()
+}
+
+#[tokio::test]
+async fn success() {
+    let (mut context, stake_pool_accounts, user, pool_token_account, pool_tokens) = setup().await;
+
+    let pre_stake_pool = stake_pool_accounts
+        .get_stake_pool(&mut context.banks_client)
+        .await;
+
+    let unstake_ticket = Keypair::new();
+    let error = create_unstake_ticket(
+        &mut context,
+        &stake_pool_accounts,
+        &user,
+        &pool_token_account,
+        &unstake_ticket,
+        pool_tokens,
+    )
+    .await;
+    assert!(error.is_none(), "{:?}", error);
+
+    let ticket_lamports = pool_tokens - stake_pool_accounts.calculate_withdrawal_fee(pool_tokens);
+    let ticket = get_account(&mut context.banks_client, &unstake_ticket.pubkey()).await;
+    let ticket = try_from_slice_unchecked::<state::UnstakeTicket>(ticket.data.as_slice()).unwrap();
+    assert!(ticket.is_valid());
+    assert_eq!(ticket.owner, user.pubkey());
+    assert_eq!(ticket.lamports, ticket_lamports);
+
+    let unstake_ticket_ledger = get_unstake_ticket_ledger(&mut context, &stake_pool_accounts).await;
+    assert_eq!(unstake_ticket_ledger.owed_lamports, ticket_lamports);
+    assert_eq!(unstake_ticket_ledger.funded_lamports, 0);
+
+    let post_stake_pool = stake_pool_accounts
+        .get_stake_pool(&mut context.banks_client)
+        .await;
+    assert_eq!(
+        post_stake_pool.total_lamports,
+        pre_stake_pool.total_lamports - ticket_lamports
+    );
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &pool_token_account).await,
+        0
+    );
+
+    // the reserve funds the ticket right away, the pool total stays the same
+    let error = stake_pool_accounts
+        .update_stake_pool_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await;
+    assert!(error.is_none(), "{:?}", error);
+    let unstake_ticket_ledger = get_unstake_ticket_ledger(&mut context, &stake_pool_accounts).await;
+    assert_eq!(unstake_ticket_ledger.funded_lamports, ticket_lamports);
+    let updated_stake_pool = stake_pool_accounts
+        .get_stake_pool(&mut context.banks_client)
+        .await;
+    assert_eq!(
+        updated_stake_pool.total_lamports,
+        post_stake_pool.total_lamports
+    );
+
+    // not claimable before the cooldown epoch
+    let error = claim_unstake_ticket(
+        &mut context,
+        &stake_pool_accounts,
+        &user,
+        &unstake_ticket.pubkey(),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::UnstakeTicketNotClaimable as u32)
+        )
+    );
+
+    let slot = context.genesis_config().epoch_schedule.first_normal_slot + 1;
+    context.warp_to_slot(slot).unwrap();
+
+    let ticket_rent = get_account(&mut context.banks_client, &unstake_ticket.pubkey())
+        .await
+        .lamports;
+    let error = claim_unstake_ticket(
+        &mut context,
+        &stake_pool_accounts,
+        &user,
+        &unstake_ticket.pubkey(),
+    )
+    .await;
+    assert!(error.is_none(), "{:?}", error);
+
+    let user_lamports = get_account(&mut context.banks_client, &user.pubkey())
+        .await
+        .lamports;
+    assert_eq!(user_lamports, ticket_lamports + ticket_rent);
+    assert!(context
+        .banks_client
+        .get_account(unstake_ticket.pubkey())
+        .await
+        .unwrap()
+        .is_none());
+
+    let unstake_ticket_ledger = get_unstake_ticket_ledger(&mut context, &stake_pool_accounts).await;
+    assert_eq!(unstake_ticket_ledger.owed_lamports, 0);
+    assert_eq!(unstake_ticket_ledger.funded_lamports, 0);
+}
+
+#[tokio::test]
+async fn fail_claim_wrong_owner() {
+    let (mut context, stake_pool_accounts, user, pool_token_account, pool_tokens) = setup().await;
+
+    let unstake_ticket = Keypair::new();
+    let error = create_unstake_ticket(
+        &mut context,
+        &stake_pool_accounts,
+        &user,
+        &pool_token_account,
+        &unstake_ticket,
+        pool_tokens,
+    )
+    .await;
+    assert!(error.is_none(), "{:?}", error);
+
+    let slot = context.genesis_config().epoch_schedule.first_normal_slot + 1;
+    context.warp_to_slot(slot).unwrap();
+
+    let wrong_owner = Keypair::new();
+    let error = claim_unstake_ticket(
+        &mut context,
+        &stake_pool_accounts,
+        &wrong_owner,
+        &unstake_ticket.pubkey(),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::InvalidUnstakeTicket as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn fail_claim_unfunded() {
+    let (mut context, stake_pool_accounts, user, pool_token_account, pool_tokens) = setup().await;
+
+    let unstake_ticket = Keypair::new();
+    let error = create_unstake_ticket(
+        &mut context,
+        &stake_pool_accounts,
+        &user,
+        &pool_token_account,
+        &unstake_ticket,
+        pool_tokens,
+    )
+    .await;
+    assert!(error.is_none(), "{:?}", error);
+
+    // the pool was not updated since the ticket was created, so the ledger
+    // does not hold the lamports yet
+    let slot = context.genesis_config().epoch_schedule.first_normal_slot + 1;
+    context.warp_to_slot(slot).unwrap();
+
+    let error = claim_unstake_ticket(
+        &mut context,
+        &stake_pool_accounts,
+        &user,
+        &unstake_ticket.pubkey(),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::UnstakeTicketNotClaimable as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn fail_create_with_initialized_ticket() {
+    let (mut context, stake_pool_accounts, user, pool_token_account, pool_tokens) = setup().await;
+
+    let unstake_ticket = Keypair::new();
+    let error = create_unstake_ticket(
+        &mut context,
+        &stake_pool_accounts,
+        &user,
+        &pool_token_account,
+        &unstake_ticket,
+        pool_tokens / 2,
+    )
+    .await;
+    assert!(error.is_none(), "{:?}", error);
+
+    let last_blockhash = context
+        .banks_client
+        .get_new_latest_blockhash(&context.last_blockhash)
+        .await
+        .unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::create_unstake_ticket(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &unstake_ticket.pubkey(),
+            &user.pubkey(),
+            &user.pubkey(),
+            &pool_token_account,
+            &stake_pool_accounts.pool_fee_account.pubkey(),
+            &stake_pool_accounts.pool_mint.pubkey(),
+            &context.payer.pubkey(),
+            &stake_pool_accounts.token_program_id,
+            pool_tokens / 2,
+        )],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &user],
+        last_blockhash,
+    );
+    let error = context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .err()
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        error,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(StakePoolError::AlreadyInUse as u32)
+        )
+    );
+}
@@ -9,11 +9,14 @@ use {
     solana_program_test::*,
     solana_sdk::{
         hash::Hash,
+        instruction::AccountMeta,
         signature::{Keypair, Signer},
         stake,
-        transaction::TransactionError,
+        transaction::{Transaction, TransactionError},
+    },
+    spl_stake_pool::{
+        error::StakePoolError, id, instruction, state::StakePool, MINIMUM_RESERVE_LAMPORTS,
     },
-    spl_stake_pool::{error::StakePoolError, state::StakePool, MINIMUM_RESERVE_LAMPORTS},
     std::num::NonZeroU32,
 };
 
@@ -307,6 +310,120 @@ async fn success_absorbing_extra_lamports() {
     assert_eq!(pool_token_supply, pre_token_supply + expected_fee);
 }
 
+#[tokio::test]
+async fn success_legacy_accounts() {
+    let (mut context, mut last_blockhash, stake_pool_accounts, stake_accounts) =
+        setup(NUM_VALIDATORS).await;
+
+    let pre_balance = get_validator_list_sum(
+        &mut context.banks_client,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        &stake_pool_accounts.validator_list.pubkey(),
+    )
+    .await;
+    let stake_pool = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    )
+    .await;
+    let stake_pool = try_from_slice_unchecked::<StakePool>(stake_pool.data.as_slice()).unwrap();
+
+    let pre_token_supply = get_token_supply(
+        &mut context.banks_client,
+        &stake_pool_accounts.pool_mint.pubkey(),
+    )
+    .await;
+
+    const EXTRA_STAKE_AMOUNT: u64 = 1_000_000;
+    for stake_account in &stake_accounts {
+        transfer(
+            &mut context.banks_client,
+            &context.payer,
+            &last_blockhash,
+            &stake_account.stake_account,
+            EXTRA_STAKE_AMOUNT,
+        )
+        .await;
+
+        last_blockhash = context
+            .banks_client
+            .get_new_latest_blockhash(&last_blockhash)
+            .await
+            .unwrap();
+    }
+
+    let extra_lamports = EXTRA_STAKE_AMOUNT * stake_accounts.len() as u64;
+    let expected_fee = stake_pool.calc_epoch_fee_amount(extra_lamports).unwrap();
+
+    // Update epoch
+    let slot = context.genesis_config().epoch_schedule.first_normal_slot + 1;
+    context.warp_to_slot(slot).unwrap();
+    let last_blockhash = context
+        .banks_client
+        .get_new_latest_blockhash(&last_blockhash)
+        .await
+        .unwrap();
+
+    let error = stake_pool_accounts
+        .update_validator_list_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &last_blockhash,
+            stake_accounts.len(),
+            false,
+        )
+        .await;
+    assert!(error.is_none(), "{:?}", error);
+
+    // Update the pool with the accounts expected before unstake tickets and
+    // fee splits
+    let mut update_instruction = instruction::update_stake_pool_balance(
+        &id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &stake_pool_accounts.withdraw_authority,
+        &stake_pool_accounts.validator_list.pubkey(),
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        &stake_pool_accounts.pool_fee_account.pubkey(),
+        &stake_pool_accounts.pool_mint.pubkey(),
+        &stake_pool_accounts.token_program_id,
+    );
+    update_instruction.accounts.truncate(7);
+    update_instruction.accounts[3] =
+        AccountMeta::new_readonly(stake_pool_accounts.reserve_stake.pubkey(), false);
+    let transaction = Transaction::new_signed_with_payer(
+        &[update_instruction],
+        Some(&context.payer.pubkey()),
+        &[&context.payer],
+        last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let post_balance = get_validator_list_sum(
+        &mut context.banks_client,
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        &stake_pool_accounts.validator_list.pubkey(),
+    )
+    .await;
+    assert_eq!(post_balance, pre_balance + extra_lamports);
+    let stake_pool = get_account(
+        &mut context.banks_client,
+        &stake_pool_accounts.stake_pool.pubkey(),
+    )
+    .await;
+    let stake_pool = try_from_slice_unchecked::<StakePool>(stake_pool.data.as_slice()).unwrap();
+    assert_eq!(post_balance, stake_pool.total_lamports);
+    let pool_token_supply = get_token_supply(
+        &mut context.banks_client,
+        &stake_pool_accounts.pool_mint.pubkey(),
+    )
+    .await;
+    assert_eq!(pool_token_supply, pre_token_supply + expected_fee);
+}
+
 #[tokio::test]
 async fn fail_with_wrong_validator_list() {
     let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
@@ -0,0 +1,466 @@
+#![allow(clippy::arithmetic_side_effects)]
+#![cfg(feature = "test-sbf")]
+
+mod helpers;
+
+use {
+    helpers::*,
+    solana_program::borsh1::{get_instance_packed_len, try_from_slice_unchecked},
+    solana_program_test::*,
+    solana_sdk::{
+        instruction::InstructionError,
+        signature::{Keypair, Signer},
+        system_instruction,
+        transaction::{Transaction, TransactionError},
+        transport::TransportError,
+    },
+    spl_stake_pool::{error, id, instruction, state, MINIMUM_RESERVE_LAMPORTS},
+};
+
+async fn setup() -> (
+    ProgramTestContext,
+    StakePoolAccounts,
+    ValidatorStakeAccount,
+    Keypair,
+) {
+    let mut context = program_test().start_with_context().await;
+    let stake_pool_accounts = StakePoolAccounts::default();
+    stake_pool_accounts
+        .initialize_stake_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            MINIMUM_RESERVE_LAMPORTS,
+        )
+        .await
+        .unwrap();
+    let validator_stake_account = simple_add_validator_to_pool(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &stake_pool_accounts,
+        None,
+    )
+    .await;
+
+    let validator_reward_history = Keypair::new();
+    let space = get_instance_packed_len(&state::ValidatorRewardHistory::new(
+        stake_pool_accounts.stake_pool.pubkey(),
+        stake_pool_accounts.max_validators,
+    ))
+    .unwrap();
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[system_instruction::create_account(
+            &context.payer.pubkey(),
+            &validator_reward_history.pubkey(),
+            rent.minimum_balance(space),
+            space as u64,
+            &id(),
+        )],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, &validator_reward_history],
+        context.last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    (
+        context,
+        stake_pool_accounts,
+        validator_stake_account,
+        validator_reward_history,
+    )
+}
+
+async fn initialize_validator_reward_history(
+    context: &mut ProgramTestContext,
+    stake_pool_accounts: &StakePoolAccounts,
+    staker: &Keypair,
+    validator_reward_history: &Keypair,
+) -> Result<(), BanksClientError> {
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::initialize_validator_reward_history(
+            &id(),
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &staker.pubkey(),
+            &stake_pool_accounts.validator_list.pubkey(),
+            &validator_reward_history.pubkey(),
+        )],
+        Some(&context.payer.pubkey()),
+        &[&context.payer, staker],
+        context.last_blockhash,
+    );
+    context.banks_client.process_transaction(transaction).await
+}
+
+#[tokio::test]
+async fn success_records_mev_rewards() {
+    let (mut context, stake_pool_accounts, validator_stake_account, validator_reward_history) =
+        setup().await;
+    initialize_validator_reward_history(
+        &mut context,
+        &stake_pool_accounts,
+        &stake_pool_accounts.staker,
+        &validator_reward_history,
+    )
+    .await
+    .unwrap();
+
+    // tips land on the validator stake account on top of its delegation
+    let mev_lamports = 1_000_000;
+    transfer(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &validator_stake_account.stake_account,
+        mev_lamports,
+    )
+    .await;
+
+    let first_normal_slot = context.genesis_config().epoch_schedule.first_normal_slot;
+    context.warp_to_slot(first_normal_slot + 1).unwrap();
+    let last_blockhash = context
+        .banks_client
+        .get_new_latest_blockhash(&context.last_blockhash)
+        .await
+        .unwrap();
+
+    let stake_pool = stake_pool_accounts
+        .get_stake_pool(&mut context.banks_client)
+        .await;
+    let validator_list = stake_pool_accounts
+        .get_validator_list(&mut context.banks_client)
+        .await;
+    let mut instructions = instruction::update_validator_reward_history(
+        &id(),
+        &stake_pool,
+        &validator_list,
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &validator_reward_history.pubkey(),
+    );
+    // recording twice in the same epoch does not count the rewards again
+    instructions.extend(instruction::update_validator_reward_history(
+        &id(),
+        &stake_pool,
+        &validator_list,
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &validator_reward_history.pubkey(),
+    ));
+    let transaction = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&context.payer.pubkey()),
+        &[&context.payer],
+        last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let validator_reward_history = get_account(
+        &mut context.banks_client,
+        &validator_reward_history.pubkey(),
+    )
+    .await;
+    let validator_reward_history = try_from_slice_unchecked::<state::ValidatorRewardHistory>(
+        validator_reward_history.data.as_slice(),
+    )
+    .unwrap();
+    assert_eq!(
+        validator_reward_history.header.stake_pool,
+        stake_pool_accounts.stake_pool.pubkey()
+    );
+    assert_eq!(validator_reward_history.validators.len(), 1);
+    let reward_info = validator_reward_history
+        .find(&validator_stake_account.vote.pubkey())
+        .unwrap();
+    assert_eq!(u64::from(reward_info.last_mev_lamports), mev_lamports);
+    assert_eq!(u64::from(reward_info.total_mev_lamports), mev_lamports);
+    assert_eq!(
+        u64::from(reward_info.last_stake_lamports),
+        validator_list.validators[0].stake_lamports().unwrap()
+    );
+}
+
+#[tokio::test]
+async fn success_records_rewards_during_update() {
+    let (mut context, stake_pool_accounts, validator_stake_account, validator_reward_history) =
+        setup().await;
+    initialize_validator_reward_history(
+        &mut context,
+        &stake_pool_accounts,
+        &stake_pool_accounts.staker,
+        &validator_reward_history,
+    )
+    .await
+    .unwrap();
+
+    let mev_lamports = 1_000_000;
+    transfer(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &validator_stake_account.stake_account,
+        mev_lamports,
+    )
+    .await;
+
+    let first_normal_slot = context.genesis_config().epoch_schedule.first_normal_slot;
+    context.warp_to_slot(first_normal_slot + 1).unwrap();
+    let last_blockhash = context
+        .banks_client
+        .get_new_latest_blockhash(&context.last_blockhash)
+        .await
+        .unwrap();
+
+    let validator_list = stake_pool_accounts
+        .get_validator_list(&mut context.banks_client)
+        .await;
+    let mut update_instruction = instruction::update_validator_list_balance_chunk(
+        &id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &stake_pool_accounts.withdraw_authority,
+        &stake_pool_accounts.validator_list.pubkey(),
+        &stake_pool_accounts.reserve_stake.pubkey(),
+        &validator_list,
+        validator_list.validators.len(),
+        0,
+        false,
+    )
+    .unwrap();
+    instruction::add_validator_reward_history(
+        &mut update_instruction,
+        &validator_reward_history.pubkey(),
+    );
+    let transaction = Transaction::new_signed_with_payer(
+        &[update_instruction],
+        Some(&context.payer.pubkey()),
+        &[&context.payer],
+        last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    // the update recorded the tips before withdrawing them to the reserve
+    let stake = get_account(
+        &mut context.banks_client,
+        &validator_stake_account.stake_account,
+    )
+    .await;
+    let updated_validator_list = stake_pool_accounts
+        .get_validator_list(&mut context.banks_client)
+        .await;
+    assert_eq!(
+        u64::from(updated_validator_list.validators[0].active_stake_lamports),
+        stake.lamports
+    );
+
+    let validator_reward_history = get_account(
+        &mut context.banks_client,
+        &validator_reward_history.pubkey(),
+    )
+    .await;
+    let validator_reward_history = try_from_slice_unchecked::<state::ValidatorRewardHistory>(
+        validator_reward_history.data.as_slice(),
+    )
+    .unwrap();
+    assert_eq!(validator_reward_history.validators.len(), 1);
+    let reward_info = validator_reward_history
+        .find(&validator_stake_account.vote.pubkey())
+        .unwrap();
+    assert_eq!(u64::from(reward_info.last_mev_lamports), mev_lamports);
+    assert_eq!(u64::from(reward_info.total_mev_lamports), mev_lamports);
+}
+
+#[tokio::test]
+async fn success_drops_removed_validators() {
+    let (mut context, stake_pool_accounts, validator_stake_account, validator_reward_history) =
+        setup().await;
+    initialize_validator_reward_history(
+        &mut context,
+        &stake_pool_accounts,
+        &stake_pool_accounts.staker,
+        &validator_reward_history,
+    )
+    .await
+    .unwrap();
+
+    let first_normal_slot = context.genesis_config().epoch_schedule.first_normal_slot;
+    context.warp_to_slot(first_normal_slot + 1).unwrap();
+    let last_blockhash = context
+        .banks_client
+        .get_new_latest_blockhash(&context.last_blockhash)
+        .await
+        .unwrap();
+
+    let stake_pool = stake_pool_accounts
+        .get_stake_pool(&mut context.banks_client)
+        .await;
+    let validator_list = stake_pool_accounts
+        .get_validator_list(&mut context.banks_client)
+        .await;
+    let transaction = Transaction::new_signed_with_payer(
+        &instruction::update_validator_reward_history(
+            &id(),
+            &stake_pool,
+            &validator_list,
+            &stake_pool_accounts.stake_pool.pubkey(),
+            &validator_reward_history.pubkey(),
+        ),
+        Some(&context.payer.pubkey()),
+        &[&context.payer],
+        last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let error = stake_pool_accounts
+        .update_all(
+            &mut context.banks_client,
+            &context.payer,
+            &last_blockhash,
+            false,
+        )
+        .await;
+    assert!(error.is_none(), "{:?}", error);
+
+    let error = stake_pool_accounts
+        .remove_validator_from_pool(
+            &mut context.banks_client,
+            &context.payer,
+            &last_blockhash,
+            &validator_stake_account.stake_account,
+            &validator_stake_account.transient_stake_account,
+        )
+        .await;
+    assert!(error.is_none(), "{:?}", error);
+
+    let error = stake_pool_accounts
+        .update_validator_list_balance(
+            &mut context.banks_client,
+            &context.payer,
+            &last_blockhash,
+            validator_list.validators.len(),
+            false,
+        )
+        .await;
+    assert!(error.is_none(), "{:?}", error);
+
+    let mut cleanup_instruction = instruction::cleanup_removed_validator_entries(
+        &id(),
+        &stake_pool_accounts.stake_pool.pubkey(),
+        &stake_pool_accounts.validator_list.pubkey(),
+    );
+    instruction::add_validator_reward_history(
+        &mut cleanup_instruction,
+        &validator_reward_history.pubkey(),
+    );
+    let transaction = Transaction::new_signed_with_payer(
+        &[cleanup_instruction],
+        Some(&context.payer.pubkey()),
+        &[&context.payer],
+        last_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    // the removed validator no longer takes up an entry
+    let validator_list = stake_pool_accounts
+        .get_validator_list(&mut context.banks_client)
+        .await;
+    assert!(validator_list.validators.is_empty());
+    let validator_reward_history = get_account(
+        &mut context.banks_client,
+        &validator_reward_history.pubkey(),
+    )
+    .await;
+    let validator_reward_history = try_from_slice_unchecked::<state::ValidatorRewardHistory>(
+        validator_reward_history.data.as_slice(),
+    )
+    .unwrap();
+    assert!(validator_reward_history.validators.is_empty());
+}
+
+#[tokio::test]
+async fn fail_initialize_wrong_staker() {
+    let (mut context, stake_pool_accounts, _, validator_reward_history) = setup().await;
+
+    let wrong_staker = Keypair::new();
+    let transaction_error = initialize_validator_reward_history(
+        &mut context,
+        &stake_pool_accounts,
+        &wrong_staker,
+        &validator_reward_history,
+    )
+    .await
+    .err()
+    .unwrap()
+    .into();
+
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = error::StakePoolError::WrongStaker as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => {
+            panic!("Wrong error occurs while malicious try to initialize validator reward history")
+        }
+    }
+}
+
+#[tokio::test]
+async fn fail_initialize_twice() {
+    let (mut context, stake_pool_accounts, _, validator_reward_history) = setup().await;
+    initialize_validator_reward_history(
+        &mut context,
+        &stake_pool_accounts,
+        &stake_pool_accounts.staker,
+        &validator_reward_history,
+    )
+    .await
+    .unwrap();
+
+    context.last_blockhash = context
+        .banks_client
+        .get_new_latest_blockhash(&context.last_blockhash)
+        .await
+        .unwrap();
+    let transaction_error = initialize_validator_reward_history(
+        &mut context,
+        &stake_pool_accounts,
+        &stake_pool_accounts.staker,
+        &validator_reward_history,
+    )
+    .await
+    .err()
+    .unwrap()
+    .into();
+
+    match transaction_error {
+        TransportError::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(error_index),
+        )) => {
+            let program_error = error::StakePoolError::AlreadyInUse as u32;
+            assert_eq!(error_index, program_error);
+        }
+        _ => panic!("Wrong error occurs while initializing validator reward history twice"),
+    }
+}
@@ -23,8 +23,10 @@ from stake_pool.constants import \
     find_transient_stake_program_address, \
     find_withdraw_authority_program_address, \
     find_metadata_account, \
-    find_ephemeral_stake_program_address
-from stake_pool.state import STAKE_POOL_LAYOUT, ValidatorList, Fee, StakePool
+    find_ephemeral_stake_program_address, \
+    find_unstake_ticket_ledger_program_address, \
+    find_fee_split_program_address
+from stake_pool.state import STAKE_POOL_LAYOUT, ValidatorList, Fee, FeeSplit, StakePool
 import stake_pool.instructions as sp
 
 from stake.actions import create_stake
@@ -402,6 +404,9 @@ async def update_stake_pool(client: AsyncClient, payer: Keypair, stake_pool_addr
     resp = await client.get_account_info(stake_pool.validator_list, commitment=Confirmed)
     data = resp.value.data if resp.value else bytes()
     validator_list = ValidatorList.decode(data)
+    (fee_split_address, _) = find_fee_split_program_address(STAKE_POOL_PROGRAM_ID, stake_pool_address)
+    resp = await client.get_account_info(fee_split_address, commitment=Confirmed)
+    fee_recipients = [x.token_account for x in FeeSplit.decode(resp.value.data).recipients] if resp.value else []
     (withdraw_authority, seed) = find_withdraw_authority_program_address(STAKE_POOL_PROGRAM_ID, stake_pool_address)
     update_list_instructions = []
     validator_chunks = [
@@ -468,6 +473,10 @@ async def update_stake_pool(client: AsyncClient, payer: Keypair, stake_pool_addr
                 manager_fee_account=stake_pool.manager_fee_account,
                 pool_mint=stake_pool.pool_mint,
                 token_program_id=stake_pool.token_program_id,
+                unstake_ticket_ledger=find_unstake_ticket_ledger_program_address(
+                    STAKE_POOL_PROGRAM_ID, stake_pool_address)[0],
+                fee_split=fee_split_address,
+                fee_recipients=fee_recipients,
             )
         )
     )
@@ -95,6 +95,28 @@ def find_ephemeral_stake_program_address(
     )
 
 
+def find_unstake_ticket_ledger_program_address(
+    program_id: Pubkey,
+    stake_pool_address: Pubkey,
+) -> Tuple[Pubkey, int]:
+    """Generates the unstake ticket ledger program address for the stake pool"""
+    return Pubkey.find_program_address(
+        [UNSTAKE_TICKET_LEDGER_SEED_PREFIX, bytes(stake_pool_address)],
+        program_id,
+    )
+
+
+def find_fee_split_program_address(
+    program_id: Pubkey,
+    stake_pool_address: Pubkey,
+) -> Tuple[Pubkey, int]:
+    """Generates the fee split program address for the stake pool"""
+    return Pubkey.find_program_address(
+        [FEE_SPLIT_SEED_PREFIX, bytes(stake_pool_address)],
+        program_id,
+    )
+
+
 def find_metadata_account(
     mint_key: Pubkey
 ) -> Tuple[Pubkey, int]:
@@ -119,3 +141,7 @@ METADATA_SEED_PREFIX = b"metadata"
 """Seed used to avoid certain collision attacks."""
 EPHEMERAL_STAKE_SEED_PREFIX = b'ephemeral'
 """Seed for ephemeral stake account"""
+UNSTAKE_TICKET_LEDGER_SEED_PREFIX = b"unstake_ticket_ledger"
+"""Seed used to derive the unstake ticket ledger."""
+FEE_SPLIT_SEED_PREFIX = b"fee_split"
+"""Seed used to derive the fee split."""
@@ -304,6 +304,12 @@ class UpdateStakePoolBalanceParams(NamedTuple):
     """`[w]` Pool mint account."""
     token_program_id: Pubkey
     """`[]` Pool token program."""
+    unstake_ticket_ledger: Pubkey
+    """`[w]` Unstake ticket ledger, funded from the reserve."""
+    fee_split: Pubkey
+    """`[w]` Fee split of the pool, may be uninitialized."""
+    fee_recipients: List[Pubkey] = []
+    """`[w]` Token accounts of the current fee split recipients, in order."""
 
 
 class CleanupRemovedValidatorEntriesParams(NamedTuple):
@@ -1033,10 +1039,18 @@ def update_stake_pool_balance(params: UpdateStakePoolBalanceParams) -> Instructi
             AccountMeta(pubkey=params.stake_pool, is_signer=False, is_writable=True),
             AccountMeta(pubkey=params.withdraw_authority, is_signer=False, is_writable=False),
             AccountMeta(pubkey=params.validator_list, is_signer=False, is_writable=True),
-            AccountMeta(pubkey=params.reserve_stake, is_signer=False, is_writable=False),
+            AccountMeta(pubkey=params.reserve_stake, is_signer=False, is_writable=True),
             AccountMeta(pubkey=params.manager_fee_account, is_signer=False, is_writable=True),
             AccountMeta(pubkey=params.pool_mint, is_signer=False, is_writable=True),
             AccountMeta(pubkey=params.token_program_id, is_signer=False, is_writable=False),
+            AccountMeta(pubkey=params.unstake_ticket_ledger, is_signer=False, is_writable=True),
+            AccountMeta(pubkey=CLOCK, is_signer=False, is_writable=False),
+            AccountMeta(pubkey=STAKE_HISTORY, is_signer=False, is_writable=False),
+            AccountMeta(pubkey=STAKE_PROGRAM_ID, is_signer=False, is_writable=False),
+            AccountMeta(pubkey=params.fee_split, is_signer=False, is_writable=True),
+        ] + [
+            AccountMeta(pubkey=fee_recipient, is_signer=False, is_writable=True)
+            for fee_recipient in params.fee_recipients
         ],
         program_id=params.program_id,
         data=INSTRUCTIONS_LAYOUT.build(
@@ -2,7 +2,7 @@
 
 from enum import IntEnum
 from typing import List, NamedTuple, Optional
-from construct import Bytes, Container, Struct, Switch, Int8ul, Int32ul, Int64ul, Pass  # type: ignore
+from construct import Bytes, Container, PrefixedArray, Struct, Switch, Int8ul, Int16ul, Int32ul, Int64ul, Pass  # type: ignore
 
 from solders.pubkey import Pubkey
 from stake.state import Lockup, LOCKUP_LAYOUT
@@ -182,6 +182,41 @@ class ValidatorList(NamedTuple):
         )
 
 
+class FeeRecipient(NamedTuple):
+    """Share of the epoch fee received by a token account."""
+
+    token_account: Pubkey
+    """Pool token account receiving the share."""
+
+    bps: int
+    """Share of the epoch fee, in basis points."""
+
+    @classmethod
+    def decode_container(cls, container: Container):
+        return FeeRecipient(
+            token_account=Pubkey(container['token_account']),
+            bps=container['bps'],
+        )
+
+
+class FeeSplit(NamedTuple):
+    """Split of the epoch fee of a stake pool between several token accounts."""
+
+    stake_pool: Pubkey
+    """Stake pool the epoch fee is split for."""
+
+    recipients: List[FeeRecipient]
+    """Current recipients of the epoch fee."""
+
+    @classmethod
+    def decode(cls, data: bytes):
+        parsed = DECODE_FEE_SPLIT_LAYOUT.parse(data)
+        return FeeSplit(
+            stake_pool=Pubkey(parsed['stake_pool']),
+            recipients=[FeeRecipient.decode_container(container) for container in parsed['recipients']],
+        )
+
+
 FEE_LAYOUT = Struct(
     "denominator" / Int64ul,
     "numerator" / Int64ul,
@@ -325,3 +360,14 @@ DECODE_VALIDATOR_LIST_LAYOUT = Struct(
     "validators_len" / Int32ul,
     "validators" / VALIDATOR_INFO_LAYOUT[lambda this: this.validators_len],
 )
+
+FEE_RECIPIENT_LAYOUT = Struct(
+    "token_account" / PUBLIC_KEY_LAYOUT,
+    "bps" / Int16ul,
+)
+
+DECODE_FEE_SPLIT_LAYOUT = Struct(
+    "account_type" / Int8ul,
+    "stake_pool" / PUBLIC_KEY_LAYOUT,
+    "recipients" / PrefixedArray(Int32ul, FEE_RECIPIENT_LAYOUT),
+)
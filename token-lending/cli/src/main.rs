@@ -1,35 +1,65 @@
 #![allow(clippy::arithmetic_side_effects)]
+mod output;
+
 use {
+    crate::output::{CliLendingMarket, CliObligation, CliReserve},
     clap::{
         crate_description, crate_name, crate_version, value_t, App, AppSettings, Arg, ArgMatches,
         SubCommand,
     },
+    solana_account_decoder::UiAccountEncoding,
     solana_clap_utils::{
         fee_payer::fee_payer_arg,
-        input_parsers::{keypair_of, pubkey_of, value_of},
-        input_validators::{is_amount, is_keypair, is_parsable, is_pubkey, is_url},
+        input_parsers::{keypair_of, pubkey_of, pubkeys_of, value_of},
+        input_validators::{
+            is_amount, is_amount_or_all, is_keypair, is_parsable, is_pubkey, is_url,
+        },
         keypair::signer_from_path,
     },
-    solana_client::rpc_client::RpcClient,
+    solana_cli_output::OutputFormat,
+    solana_client::{
+        rpc_client::RpcClient,
+        rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+        rpc_filter::{Memcmp, RpcFilterType},
+    },
     solana_program::{native_token::lamports_to_sol, program_pack::Pack, pubkey::Pubkey},
     solana_sdk::{
         commitment_config::CommitmentConfig,
+        instruction::{AccountMeta, Instruction},
         message::Message,
         signature::{Keypair, Signer},
         system_instruction,
         transaction::Transaction,
     },
     spl_token::{
-        instruction::{approve, revoke},
         state::{Account as Token, Mint},
         ui_amount_to_amount,
     },
+    spl_token_2022::{
+        extension::{BaseStateWithExtensions, ExtensionType, StateWithExtensions},
+        instruction::{approve, revoke},
+    },
     spl_token_lending::{
         self,
-        instruction::{init_lending_market, init_reserve},
+        instruction::{
+            apply_lending_market_changes, apply_reserve_config, borrow_obligation_liquidity,
+            cancel_reserve_config, deposit_obligation_collateral, deposit_reserve_liquidity,
+            flash_loan, init_lending_market, init_obligation, init_reserve, modify_reserve_config,
+            modify_reserve_config_and_oracle, queue_reserve_config,
+            queue_reserve_config_and_oracle, redeem_reserve_collateral, refresh_obligation,
+            refresh_obligation_chunk, refresh_reserve_with_oracle_accounts,
+            repay_obligation_liquidity, set_config_timelock, set_lending_market_guardian,
+            set_liquidation_close_factor, tighten_reserve_config, withdraw_obligation_collateral,
+            withdraw_protocol_fees,
+        },
         math::WAD,
-        state::{LendingMarket, Reserve, ReserveConfig, ReserveFees},
+        state::{
+            ElevationGroup, InterestRateModel, LendingMarket, Obligation, OracleConfig,
+            OracleSource, PendingReserveConfig, RateCurve, RateCurvePoint, Reserve, ReserveCaps,
+            ReserveConfig, ReserveFees, SecondaryOracle, MAX_OBLIGATION_RESERVES,
+        },
     },
+    spl_token_swap::state::SwapVersion,
     std::{borrow::Borrow, process::exit, str::FromStr},
     system_instruction::create_account,
 };
@@ -39,6 +69,7 @@ struct Config {
     fee_payer: Box<dyn Signer>,
     lending_program_id: Pubkey,
     verbose: bool,
+    output_format: OutputFormat,
     dry_run: bool,
 }
 
@@ -47,6 +78,10 @@ type CommandResult = Result<(), Error>;
 
 const PYTH_PROGRAM_ID: &str = "gSbePebfvPy7tRqimPoVecS2UsBvYv46ynrzWocc92s";
 
+/// Offset of the lending market address in reserve accounts, after the
+/// version and last update
+const RESERVE_LENDING_MARKET_OFFSET: usize = 10; // 1 + 8 + 1
+
 fn main() {
     solana_logger::setup_with_default("solana=info");
 
@@ -101,6 +136,15 @@ fn main() {
                 .global(true)
                 .help("Show additional information"),
         )
+        .arg(
+            Arg::with_name("output_format")
+                .long("output")
+                .value_name("FORMAT")
+                .global(true)
+                .takes_value(true)
+                .possible_values(&["json", "json-compact"])
+                .help("Return information in specified output format"),
+        )
         .arg(
             Arg::with_name("dry_run")
                 .long("dry-run")
@@ -198,7 +242,7 @@ fn main() {
                         .value_name("PUBKEY")
                         .takes_value(true)
                         .required(true)
-                        .help("Pyth product account: https://pyth.network/developers/consumers/accounts"),
+                        .help("Pyth product account: https://pyth.network/developers/consumers/accounts, unused by other oracle sources"),
                 )
                 .arg(
                     Arg::with_name("pyth_price")
@@ -207,7 +251,7 @@ fn main() {
                         .value_name("PUBKEY")
                         .takes_value(true)
                         .required(true)
-                        .help("Pyth price account: https://pyth.network/developers/consumers/accounts"),
+                        .help("Pyth price account: https://pyth.network/developers/consumers/accounts, or the oracle account of other oracle sources"),
                 )
                 .arg(
                     Arg::with_name("optimal_utilization_rate")
@@ -239,6 +283,16 @@ fn main() {
                         .default_value("5")
                         .help("Bonus a liquidator gets when repaying part of an unhealthy obligation: [0, 100]"),
                 )
+                .arg(
+                    Arg::with_name("max_liquidation_bonus")
+                        .long("max-liquidation-bonus")
+                        .validator(is_parsable::<u8>)
+                        .value_name("INTEGER_PERCENT")
+                        .takes_value(true)
+                        .required(true)
+                        .default_value("0")
+                        .help("Bonus a liquidator gets as the obligation falls further below its liquidation threshold, 0 for a static bonus: [liquidation bonus, 100]"),
+                )
                 .arg(
                     Arg::with_name("liquidation_threshold")
                         .long("liquidation-threshold")
@@ -279,6 +333,14 @@ fn main() {
                         .default_value("30")
                         .help("Max borrow APY: min <= optimal <= max"),
                 )
+                .arg(
+                    Arg::with_name("borrow_rate_curve")
+                        .long("borrow-rate-curve")
+                        .validator(is_rate_curve)
+                        .value_name("UTILIZATION_BPS:RATE_BPS,...")
+                        .takes_value(true)
+                        .help("Piecewise-linear borrow APY curve, replacing the min, optimal and max borrow rates, e.g. 0:0,8000:400,10000:30000"),
+                )
                 .arg(
                     Arg::with_name("borrow_fee")
                         .long("borrow-fee")
@@ -309,175 +371,1516 @@ fn main() {
                         .default_value("20")
                         .help("Amount of fee going to host account: [0, 100]"),
                 )
+                .arg(
+                    Arg::with_name("protocol_take_rate")
+                        .long("protocol-take-rate")
+                        .validator(is_parsable::<u8>)
+                        .value_name("INTEGER_PERCENT")
+                        .takes_value(true)
+                        .required(true)
+                        .default_value("0")
+                        .help("Share of accrued interest kept as protocol fees: [0, 100]"),
+                )
+                .arg(
+                    Arg::with_name("oracle_source")
+                        .long("oracle-source")
+                        .value_name("SOURCE")
+                        .takes_value(true)
+                        .possible_values(&["pyth", "swap-pool-twap", "fixed-price"])
+                        .default_value("pyth")
+                        .help("Kind of feed the --pyth-price account is; for swap-pool-twap it is a token swap pool"),
+                )
+                .arg(
+                    Arg::with_name("max_staleness_slots")
+                        .long("max-staleness-slots")
+                        .validator(is_parsable::<u32>)
+                        .value_name("SLOTS")
+                        .takes_value(true)
+                        .default_value("0")
+                        .help("Max slots since the oracle price was published, 0 for the program default"),
+                )
+                .arg(
+                    Arg::with_name("max_confidence_bps")
+                        .long("max-confidence-bps")
+                        .validator(is_parsable::<u16>)
+                        .value_name("BPS")
+                        .takes_value(true)
+                        .default_value("0")
+                        .help("Max oracle confidence interval relative to the price, 0 if unchecked: [0, 10000]"),
+                )
+                .arg(
+                    Arg::with_name("twap_window_slots")
+                        .long("twap-window-slots")
+                        .validator(is_parsable::<u32>)
+                        .value_name("SLOTS")
+                        .takes_value(true)
+                        .default_value("0")
+                        .help("Slots over which swap pool spot prices are averaged, for swap-pool-twap oracles"),
+                )
+                .arg(
+                    Arg::with_name("fixed_price")
+                        .long("fixed-price")
+                        .validator(is_parsable::<f64>)
+                        .value_name("DECIMAL_PRICE")
+                        .takes_value(true)
+                        .required_if("oracle_source", "fixed-price")
+                        .help("Price in the quote currency, for fixed-price oracles"),
+                )
+                .arg(
+                    Arg::with_name("secondary_oracle")
+                        .long("secondary-oracle")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .requires("max_divergence_bps")
+                        .help("Oracle whose price must agree with the primary oracle price"),
+                )
+                .arg(
+                    Arg::with_name("secondary_oracle_source")
+                        .long("secondary-oracle-source")
+                        .value_name("SOURCE")
+                        .takes_value(true)
+                        .possible_values(&["pyth", "swap-pool-twap"])
+                        .default_value("pyth")
+                        .help("Kind of feed the --secondary-oracle account is"),
+                )
+                .arg(
+                    Arg::with_name("max_divergence_bps")
+                        .long("max-divergence-bps")
+                        .validator(is_parsable::<u16>)
+                        .value_name("BPS")
+                        .takes_value(true)
+                        .help("Max difference between the primary and secondary oracle prices relative to the primary price: (0, 10000]"),
+                )
+                .args(&reserve_caps_args())
+                .arg(
+                    Arg::with_name("isolated_collateral")
+                        .long("isolated-collateral")
+                        .takes_value(false)
+                        .help("Only allow collateral from this reserve to back a single borrowed asset"),
+                )
+                .arg(
+                    Arg::with_name("elevation_group")
+                        .long("elevation-group")
+                        .validator(is_parsable::<u8>)
+                        .value_name("ID")
+                        .takes_value(true)
+                        .requires_all(&["elevation_group_loan_to_value_ratio", "elevation_group_liquidation_threshold"])
+                        .help("Elevation group of correlated reserves with raised ratios when every deposit and borrow is in it: [1, 255]"),
+                )
+                .arg(
+                    Arg::with_name("elevation_group_loan_to_value_ratio")
+                        .long("elevation-group-ltv")
+                        .validator(is_parsable::<u8>)
+                        .value_name("INTEGER_PERCENT")
+                        .takes_value(true)
+                        .requires("elevation_group")
+                        .help("Loan to value ratio inside the elevation group: [LTV, 100)"),
+                )
+                .arg(
+                    Arg::with_name("elevation_group_liquidation_threshold")
+                        .long("elevation-group-liquidation-threshold")
+                        .validator(is_parsable::<u8>)
+                        .value_name("INTEGER_PERCENT")
+                        .takes_value(true)
+                        .requires("elevation_group")
+                        .help("Liquidation threshold inside the elevation group: (elevation group LTV, 100]"),
+                )
         )
-        .get_matches();
-
-    let mut wallet_manager = None;
-    let config = {
-        let cli_config = if let Some(config_file) = matches.value_of("config_file") {
-            solana_cli_config::Config::load(config_file).unwrap_or_default()
-        } else {
-            solana_cli_config::Config::default()
-        };
-        let json_rpc_url = value_t!(matches, "json_rpc_url", String)
-            .unwrap_or_else(|_| cli_config.json_rpc_url.clone());
-
-        let fee_payer = signer_from_path(
-            &matches,
-            matches
-                .value_of("fee_payer")
-                .unwrap_or(&cli_config.keypair_path),
-            "fee_payer",
-            &mut wallet_manager,
+        .subcommand(
+            SubCommand::with_name("set-reserve-caps")
+                .about("Change the deposit and borrow limits of a reserve")
+                // @TODO: use is_valid_signer
+                .arg(
+                    Arg::with_name("lending_market_owner")
+                        .long("market-owner")
+                        .validator(is_keypair)
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Owner of the lending market"),
+                )
+                .arg(
+                    Arg::with_name("reserve")
+                        .long("reserve")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Reserve address"),
+                )
+                .arg(
+                    Arg::with_name("pending_reserve_config")
+                        .long("pending-config")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .help("Pending reserve config account to queue the config in while the lending market has a config timelock [default: a new account]"),
+                )
+                .args(&reserve_caps_args())
         )
-        .unwrap_or_else(|e| {
-            eprintln!("error: {}", e);
-            exit(1);
-        });
-
-        let lending_program_id = pubkey_of(&matches, "lending_program_id").unwrap();
-        let verbose = matches.is_present("verbose");
-        let dry_run = matches.is_present("dry_run");
-
-        Config {
-            rpc_client: RpcClient::new_with_commitment(json_rpc_url, CommitmentConfig::confirmed()),
-            fee_payer,
-            lending_program_id,
-            verbose,
-            dry_run,
-        }
-    };
-
-    let _ = match matches.subcommand() {
-        ("create-market", Some(arg_matches)) => {
-            let lending_market_owner = pubkey_of(arg_matches, "lending_market_owner").unwrap();
-            let quote_currency = quote_currency_of(arg_matches, "quote_currency").unwrap();
-            let oracle_program_id = pubkey_of(arg_matches, "oracle_program_id").unwrap();
-            command_create_lending_market(
-                &config,
-                lending_market_owner,
-                quote_currency,
-                oracle_program_id,
-            )
-        }
-        ("add-reserve", Some(arg_matches)) => {
-            let lending_market_owner_keypair =
-                keypair_of(arg_matches, "lending_market_owner").unwrap();
-            let source_liquidity_owner_keypair =
-                keypair_of(arg_matches, "source_liquidity_owner").unwrap();
-            let lending_market_pubkey = pubkey_of(arg_matches, "lending_market").unwrap();
-            let source_liquidity_pubkey = pubkey_of(arg_matches, "source_liquidity").unwrap();
-            let ui_amount = value_of(arg_matches, "liquidity_amount").unwrap();
-            let pyth_product_pubkey = pubkey_of(arg_matches, "pyth_product").unwrap();
-            let pyth_price_pubkey = pubkey_of(arg_matches, "pyth_price").unwrap();
-            let optimal_utilization_rate =
-                value_of(arg_matches, "optimal_utilization_rate").unwrap();
-            let loan_to_value_ratio = value_of(arg_matches, "loan_to_value_ratio").unwrap();
-            let liquidation_bonus = value_of(arg_matches, "liquidation_bonus").unwrap();
-            let liquidation_threshold = value_of(arg_matches, "liquidation_threshold").unwrap();
-            let min_borrow_rate = value_of(arg_matches, "min_borrow_rate").unwrap();
-            let optimal_borrow_rate = value_of(arg_matches, "optimal_borrow_rate").unwrap();
-            let max_borrow_rate = value_of(arg_matches, "max_borrow_rate").unwrap();
-            let borrow_fee = value_of::<f64>(arg_matches, "borrow_fee").unwrap();
-            let flash_loan_fee = value_of::<f64>(arg_matches, "flash_loan_fee").unwrap();
-            let host_fee_percentage = value_of(arg_matches, "host_fee_percentage").unwrap();
-
-            let borrow_fee_wad = (borrow_fee * WAD as f64) as u64;
-            let flash_loan_fee_wad = (flash_loan_fee * WAD as f64) as u64;
-
-            command_add_reserve(
-                &config,
-                ui_amount,
-                ReserveConfig {
-                    optimal_utilization_rate,
-                    loan_to_value_ratio,
-                    liquidation_bonus,
-                    liquidation_threshold,
-                    min_borrow_rate,
-                    optimal_borrow_rate,
-                    max_borrow_rate,
-                    fees: ReserveFees {
-                        borrow_fee_wad,
-                        flash_loan_fee_wad,
-                        host_fee_percentage,
-                    },
-                },
-                source_liquidity_pubkey,
-                source_liquidity_owner_keypair,
-                lending_market_pubkey,
-                lending_market_owner_keypair,
-                pyth_product_pubkey,
-                pyth_price_pubkey,
-            )
-        }
-        _ => unreachable!(),
-    }
-    .map_err(|err| {
-        eprintln!("{}", err);
-        exit(1);
-    });
-}
-
-// COMMANDS
-
-fn command_create_lending_market(
-    config: &Config,
-    lending_market_owner: Pubkey,
-    quote_currency: [u8; 32],
-    oracle_program_id: Pubkey,
-) -> CommandResult {
-    let lending_market_keypair = Keypair::new();
-    println!(
-        "Creating lending market {}",
-        lending_market_keypair.pubkey()
-    );
-
-    let lending_market_balance = config
-        .rpc_client
-        .get_minimum_balance_for_rent_exemption(LendingMarket::LEN)?;
-
-    let recent_blockhash = config.rpc_client.get_latest_blockhash()?;
-    let message = Message::new_with_blockhash(
-        &[
-            // Account for the lending market
-            create_account(
-                &config.fee_payer.pubkey(),
-                &lending_market_keypair.pubkey(),
-                lending_market_balance,
-                LendingMarket::LEN as u64,
-                &config.lending_program_id,
-            ),
-            // Initialize lending market account
-            init_lending_market(
-                config.lending_program_id,
-                lending_market_owner,
-                quote_currency,
-                lending_market_keypair.pubkey(),
-                oracle_program_id,
-            ),
-        ],
-        Some(&config.fee_payer.pubkey()),
-        &recent_blockhash,
-    );
-
-    check_fee_payer_balance(
-        config,
-        lending_market_balance + config.rpc_client.get_fee_for_message(&message)?,
-    )?;
-    let transaction = Transaction::new(
-        &vec![config.fee_payer.as_ref(), &lending_market_keypair],
-        message,
-        recent_blockhash,
-    );
-    send_transaction(config, transaction)?;
-    Ok(())
-}
+        .subcommand(
+            SubCommand::with_name("set-close-factor")
+                .about("Change the share of an obligation that can be repaid in each liquidation")
+                // @TODO: use is_valid_signer
+                .arg(
+                    Arg::with_name("lending_market_owner")
+                        .long("market-owner")
+                        .validator(is_keypair)
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Owner of the lending market"),
+                )
+                .arg(
+                    Arg::with_name("lending_market")
+                        .long("market")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Lending market address"),
+                )
+                .arg(
+                    Arg::with_name("liquidation_close_factor")
+                        .long("close-factor")
+                        .validator(is_parsable::<u8>)
+                        .value_name("INTEGER_PERCENT")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Percentage of an obligation borrowed value that can be repaid in each liquidation: (0, 100]"),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("withdraw-protocol-fees")
+                .about("Withdraw the protocol fees accumulated by a reserve")
+                // @TODO: use is_valid_signer
+                .arg(
+                    Arg::with_name("lending_market_owner")
+                        .long("market-owner")
+                        .validator(is_keypair)
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Owner of the lending market"),
+                )
+                .arg(
+                    Arg::with_name("reserve")
+                        .long("reserve")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Reserve address"),
+                )
+                .arg(
+                    Arg::with_name("destination")
+                        .long("destination")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("SPL Token account to receive the protocol fees"),
+                )
+                .arg(
+                    Arg::with_name("liquidity_amount")
+                        .long("amount")
+                        .validator(is_amount)
+                        .value_name("DECIMAL_AMOUNT")
+                        .takes_value(true)
+                        .help("Amount of protocol fees to withdraw [default: all available]"),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("update-reserve")
+                .about("Change the configuration of a reserve, keeping values not provided")
+                // @TODO: use is_valid_signer
+                .arg(
+                    Arg::with_name("lending_market_owner")
+                        .long("market-owner")
+                        .validator(is_keypair)
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Owner of the lending market"),
+                )
+                .arg(
+                    Arg::with_name("reserve")
+                        .long("reserve")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Reserve address"),
+                )
+                .arg(
+                    Arg::with_name("optimal_utilization_rate")
+                        .long("optimal-utilization-rate")
+                        .validator(is_parsable::<u8>)
+                        .value_name("INTEGER_PERCENT")
+                        .takes_value(true)
+                        .help("Optimal utilization rate: [0, 100]"),
+                )
+                .arg(
+                    Arg::with_name("loan_to_value_ratio")
+                        .long("loan-to-value-ratio")
+                        .validator(is_parsable::<u8>)
+                        .value_name("INTEGER_PERCENT")
+                        .takes_value(true)
+                        .help("Target ratio of the value of borrows to deposits: [0, 100)"),
+                )
+                .arg(
+                    Arg::with_name("liquidation_bonus")
+                        .long("liquidation-bonus")
+                        .validator(is_parsable::<u8>)
+                        .value_name("INTEGER_PERCENT")
+                        .takes_value(true)
+                        .help("Bonus a liquidator gets when repaying part of an unhealthy obligation: [0, 100]"),
+                )
+                .arg(
+                    Arg::with_name("max_liquidation_bonus")
+                        .long("max-liquidation-bonus")
+                        .validator(is_parsable::<u8>)
+                        .value_name("INTEGER_PERCENT")
+                        .takes_value(true)
+                        .help("Bonus a liquidator gets as the obligation falls further below its liquidation threshold, 0 for a static bonus: [liquidation bonus, 100]"),
+                )
+                .arg(
+                    Arg::with_name("liquidation_threshold")
+                        .long("liquidation-threshold")
+                        .validator(is_parsable::<u8>)
+                        .value_name("INTEGER_PERCENT")
+                        .takes_value(true)
+                        .help("Loan to value ratio at which an obligation can be liquidated: (LTV, 100]"),
+                )
+                .arg(
+                    Arg::with_name("min_borrow_rate")
+                        .long("min-borrow-rate")
+                        .validator(is_parsable::<u8>)
+                        .value_name("INTEGER_PERCENT")
+                        .takes_value(true)
+                        .help("Min borrow APY: min <= optimal <= max"),
+                )
+                .arg(
+                    Arg::with_name("optimal_borrow_rate")
+                        .long("optimal-borrow-rate")
+                        .validator(is_parsable::<u8>)
+                        .value_name("INTEGER_PERCENT")
+                        .takes_value(true)
+                        .help("Optimal (utilization) borrow APY: min <= optimal <= max"),
+                )
+                .arg(
+                    Arg::with_name("max_borrow_rate")
+                        .long("max-borrow-rate")
+                        .validator(is_parsable::<u8>)
+                        .value_name("INTEGER_PERCENT")
+                        .takes_value(true)
+                        .help("Max borrow APY: min <= optimal <= max"),
+                )
+                .arg(
+                    Arg::with_name("borrow_rate_curve")
+                        .long("borrow-rate-curve")
+                        .validator(is_rate_curve)
+                        .value_name("UTILIZATION_BPS:RATE_BPS,...")
+                        .takes_value(true)
+                        .help("Piecewise-linear borrow APY curve, replacing the interest rate model, e.g. 0:0,8000:400,10000:30000"),
+                )
+                .arg(
+                    Arg::with_name("borrow_fee")
+                        .long("borrow-fee")
+                        .validator(is_parsable::<f64>)
+                        .value_name("DECIMAL_PERCENT")
+                        .takes_value(true)
+                        .help("Fee assessed on borrow, expressed as a percentage: [0, 1)"),
+                )
+                .arg(
+                    Arg::with_name("flash_loan_fee")
+                        .long("flash-loan-fee")
+                        .validator(is_parsable::<f64>)
+                        .value_name("DECIMAL_PERCENT")
+                        .takes_value(true)
+                        .help("Fee assessed for flash loans, expressed as a percentage: [0, 1)"),
+                )
+                .arg(
+                    Arg::with_name("host_fee_percentage")
+                        .long("host-fee-percentage")
+                        .validator(is_parsable::<u8>)
+                        .value_name("INTEGER_PERCENT")
+                        .takes_value(true)
+                        .help("Amount of fee going to host account: [0, 100]"),
+                )
+                .arg(
+                    Arg::with_name("protocol_take_rate")
+                        .long("protocol-take-rate")
+                        .validator(is_parsable::<u8>)
+                        .value_name("INTEGER_PERCENT")
+                        .takes_value(true)
+                        .help("Share of accrued interest kept as protocol fees: [0, 100]"),
+                )
+                .arg(
+                    Arg::with_name("oracle_source")
+                        .long("oracle-source")
+                        .value_name("SOURCE")
+                        .takes_value(true)
+                        .possible_values(&["pyth", "swap-pool-twap", "fixed-price"])
+                        .requires_all(&["pyth_product", "pyth_price"])
+                        .help("Kind of feed the --pyth-price account is; for swap-pool-twap it is a token swap pool"),
+                )
+                .arg(
+                    Arg::with_name("pyth_product")
+                        .long("pyth-product")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .requires("pyth_price")
+                        .help("New Pyth product account, unused by other oracle sources"),
+                )
+                .arg(
+                    Arg::with_name("pyth_price")
+                        .long("pyth-price")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .requires("pyth_product")
+                        .help("New Pyth price account, or the oracle account of other oracle sources"),
+                )
+                .arg(
+                    Arg::with_name("max_staleness_slots")
+                        .long("max-staleness-slots")
+                        .validator(is_parsable::<u32>)
+                        .value_name("SLOTS")
+                        .takes_value(true)
+                        .help("Max slots since the oracle price was published, 0 for the program default"),
+                )
+                .arg(
+                    Arg::with_name("max_confidence_bps")
+                        .long("max-confidence-bps")
+                        .validator(is_parsable::<u16>)
+                        .value_name("BPS")
+                        .takes_value(true)
+                        .help("Max oracle confidence interval relative to the price, 0 if unchecked: [0, 10000]"),
+                )
+                .arg(
+                    Arg::with_name("twap_window_slots")
+                        .long("twap-window-slots")
+                        .validator(is_parsable::<u32>)
+                        .value_name("SLOTS")
+                        .takes_value(true)
+                        .help("Slots over which swap pool spot prices are averaged, for swap-pool-twap oracles"),
+                )
+                .arg(
+                    Arg::with_name("fixed_price")
+                        .long("fixed-price")
+                        .validator(is_parsable::<f64>)
+                        .value_name("DECIMAL_PRICE")
+                        .takes_value(true)
+                        .help("Price in the quote currency, for fixed-price oracles"),
+                )
+                .arg(
+                    Arg::with_name("pending_reserve_config")
+                        .long("pending-config")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .help("Pending reserve config account to queue the config in while the lending market has a config timelock [default: a new account]"),
+                )
+                .args(&reserve_caps_args())
+        )
+        .subcommand(
+            SubCommand::with_name("apply-reserve-config")
+                .about("Apply a reserve config queued behind the config timelock once its slot is reached")
+                .arg(
+                    Arg::with_name("pending_reserve_config")
+                        .long("pending-config")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Pending reserve config address"),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("cancel-reserve-config")
+                .about("Cancel a reserve config queued behind the config timelock")
+                // @TODO: use is_valid_signer
+                .arg(
+                    Arg::with_name("risk_authority")
+                        .long("authority")
+                        .validator(is_keypair)
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Owner or guardian of the lending market"),
+                )
+                .arg(
+                    Arg::with_name("pending_reserve_config")
+                        .long("pending-config")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Pending reserve config address"),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("tighten-reserve")
+                .about("Lower the loan to value ratio or caps of a reserve, or disable borrowing or pause it, without the config timelock")
+                // @TODO: use is_valid_signer
+                .arg(
+                    Arg::with_name("risk_authority")
+                        .long("authority")
+                        .validator(is_keypair)
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Owner or guardian of the lending market"),
+                )
+                .arg(
+                    Arg::with_name("reserve")
+                        .long("reserve")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Reserve address"),
+                )
+                .arg(
+                    Arg::with_name("loan_to_value_ratio")
+                        .long("loan-to-value-ratio")
+                        .validator(is_parsable::<u8>)
+                        .value_name("INTEGER_PERCENT")
+                        .takes_value(true)
+                        .help("Target ratio of the value of borrows to deposits, at most the current ratio"),
+                )
+                .arg(
+                    Arg::with_name("disable_borrowing")
+                        .long("disable-borrowing")
+                        .takes_value(false)
+                        .help("Reject new borrows of the reserve liquidity"),
+                )
+                .arg(
+                    Arg::with_name("pause")
+                        .long("pause")
+                        .takes_value(false)
+                        .help("Reject deposits, borrows and flash loans of the reserve liquidity"),
+                )
+                .args(&reserve_caps_args())
+        )
+        .subcommand(
+            SubCommand::with_name("set-guardian")
+                .about("Change the guardian which can tighten reserves and cancel pending reserve configs")
+                // @TODO: use is_valid_signer
+                .arg(
+                    Arg::with_name("lending_market_owner")
+                        .long("market-owner")
+                        .validator(is_keypair)
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Owner of the lending market"),
+                )
+                .arg(
+                    Arg::with_name("lending_market")
+                        .long("market")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Lending market address"),
+                )
+                .arg(
+                    Arg::with_name("guardian")
+                        .long("guardian")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .help("Guardian address [default: remove the guardian]"),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("set-config-timelock")
+                .about("Change the slots reserve config and market changes wait before they can be applied")
+                // @TODO: use is_valid_signer
+                .arg(
+                    Arg::with_name("lending_market_owner")
+                        .long("market-owner")
+                        .validator(is_keypair)
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Owner of the lending market"),
+                )
+                .arg(
+                    Arg::with_name("lending_market")
+                        .long("market")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Lending market address"),
+                )
+                .arg(
+                    Arg::with_name("config_timelock_slots")
+                        .long("slots")
+                        .validator(is_parsable::<u64>)
+                        .value_name("SLOTS")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Slots config changes wait, 0 to apply them immediately"),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("apply-market-changes")
+                .about("Apply the owner, config timelock and close factor changes of a lending market once their slots are reached")
+                .arg(
+                    Arg::with_name("lending_market")
+                        .long("market")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Lending market address"),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("create-obligation")
+                .about("Create an obligation to deposit collateral and borrow liquidity with")
+                // @TODO: use is_valid_signer
+                .arg(
+                    Arg::with_name("obligation_owner")
+                        .long("owner")
+                        .validator(is_keypair)
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Owner of the obligation"),
+                )
+                .arg(
+                    Arg::with_name("lending_market")
+                        .long("market")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Lending market address"),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("deposit")
+                .about("Deposit liquidity into a reserve in exchange for collateral")
+                // @TODO: use is_valid_signer
+                .arg(
+                    Arg::with_name("source_liquidity_owner")
+                        .long("owner")
+                        .validator(is_keypair)
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Owner of the SPL Token account to deposit liquidity from"),
+                )
+                .arg(
+                    Arg::with_name("reserve")
+                        .long("reserve")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Reserve address"),
+                )
+                .arg(
+                    Arg::with_name("source_liquidity")
+                        .long("source")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("SPL Token account to deposit liquidity from"),
+                )
+                .arg(
+                    Arg::with_name("destination_collateral")
+                        .long("destination")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("SPL Token account to receive collateral"),
+                )
+                .arg(
+                    Arg::with_name("liquidity_amount")
+                        .long("amount")
+                        .validator(is_amount)
+                        .value_name("DECIMAL_AMOUNT")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Amount of liquidity to deposit"),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("redeem")
+                .about("Redeem reserve collateral in exchange for liquidity")
+                // @TODO: use is_valid_signer
+                .arg(
+                    Arg::with_name("source_collateral_owner")
+                        .long("owner")
+                        .validator(is_keypair)
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Owner of the SPL Token account to redeem collateral from"),
+                )
+                .arg(
+                    Arg::with_name("reserve")
+                        .long("reserve")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Reserve address"),
+                )
+                .arg(
+                    Arg::with_name("source_collateral")
+                        .long("source")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("SPL Token account to redeem collateral from"),
+                )
+                .arg(
+                    Arg::with_name("destination_liquidity")
+                        .long("destination")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("SPL Token account to receive liquidity"),
+                )
+                .arg(
+                    Arg::with_name("collateral_amount")
+                        .long("amount")
+                        .validator(is_amount_or_all)
+                        .value_name("DECIMAL_AMOUNT")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Amount of collateral to redeem, or ALL for the whole source balance"),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("deposit-collateral")
+                .about("Deposit reserve collateral into an obligation")
+                // @TODO: use is_valid_signer
+                .arg(
+                    Arg::with_name("obligation_owner")
+                        .long("owner")
+                        .validator(is_keypair)
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Owner of the obligation and of the SPL Token account to deposit collateral from"),
+                )
+                .arg(
+                    Arg::with_name("obligation")
+                        .long("obligation")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Obligation address"),
+                )
+                .arg(
+                    Arg::with_name("reserve")
+                        .long("reserve")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Reserve of the collateral"),
+                )
+                .arg(
+                    Arg::with_name("source_collateral")
+                        .long("source")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("SPL Token account to deposit collateral from"),
+                )
+                .arg(
+                    Arg::with_name("collateral_amount")
+                        .long("amount")
+                        .validator(is_amount)
+                        .value_name("DECIMAL_AMOUNT")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Amount of collateral to deposit"),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("withdraw-collateral")
+                .about("Withdraw collateral from an obligation")
+                // @TODO: use is_valid_signer
+                .arg(
+                    Arg::with_name("obligation_owner")
+                        .long("owner")
+                        .validator(is_keypair)
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Owner of the obligation"),
+                )
+                .arg(
+                    Arg::with_name("obligation")
+                        .long("obligation")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Obligation address"),
+                )
+                .arg(
+                    Arg::with_name("reserve")
+                        .long("reserve")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Reserve of the collateral"),
+                )
+                .arg(
+                    Arg::with_name("destination_collateral")
+                        .long("destination")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("SPL Token account to receive collateral"),
+                )
+                .arg(
+                    Arg::with_name("collateral_amount")
+                        .long("amount")
+                        .validator(is_amount_or_all)
+                        .value_name("DECIMAL_AMOUNT")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Amount of collateral to withdraw, or ALL for as much as the obligation allows"),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("borrow")
+                .about("Borrow liquidity against the collateral of an obligation")
+                // @TODO: use is_valid_signer
+                .arg(
+                    Arg::with_name("obligation_owner")
+                        .long("owner")
+                        .validator(is_keypair)
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Owner of the obligation"),
+                )
+                .arg(
+                    Arg::with_name("obligation")
+                        .long("obligation")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Obligation address"),
+                )
+                .arg(
+                    Arg::with_name("reserve")
+                        .long("reserve")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Reserve to borrow from"),
+                )
+                .arg(
+                    Arg::with_name("destination_liquidity")
+                        .long("destination")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("SPL Token account to receive liquidity"),
+                )
+                .arg(
+                    Arg::with_name("liquidity_amount")
+                        .long("amount")
+                        .validator(is_amount_or_all)
+                        .value_name("DECIMAL_AMOUNT")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Amount of liquidity to borrow, or ALL for the whole remaining borrowing power"),
+                )
+                .arg(
+                    Arg::with_name("host_fee_receiver")
+                        .long("host-fee-receiver")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .help("SPL Token account to receive the host share of the borrow fee"),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("repay")
+                .about("Repay liquidity borrowed by an obligation")
+                // @TODO: use is_valid_signer
+                .arg(
+                    Arg::with_name("source_liquidity_owner")
+                        .long("owner")
+                        .validator(is_keypair)
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Owner of the SPL Token account to repay liquidity from"),
+                )
+                .arg(
+                    Arg::with_name("obligation")
+                        .long("obligation")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Obligation address"),
+                )
+                .arg(
+                    Arg::with_name("reserve")
+                        .long("reserve")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Reserve the liquidity was borrowed from"),
+                )
+                .arg(
+                    Arg::with_name("source_liquidity")
+                        .long("source")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("SPL Token account to repay liquidity from"),
+                )
+                .arg(
+                    Arg::with_name("liquidity_amount")
+                        .long("amount")
+                        .validator(is_amount_or_all)
+                        .value_name("DECIMAL_AMOUNT")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Amount of liquidity to repay, or ALL for the whole borrowed amount"),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("flash-loan")
+                .about("Flash borrow liquidity from a reserve through a flash loan receiver program")
+                // @TODO: use is_valid_signer
+                .arg(
+                    Arg::with_name("destination_liquidity_owner")
+                        .long("owner")
+                        .validator(is_keypair)
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Owner of the destination SPL Token account, passed as the first receiver program account to return the loan"),
+                )
+                .arg(
+                    Arg::with_name("reserve")
+                        .long("reserve")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Reserve to borrow from"),
+                )
+                .arg(
+                    Arg::with_name("destination_liquidity")
+                        .long("destination")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("SPL Token account to receive the loan, holding enough to also pay the flash loan fee"),
+                )
+                .arg(
+                    Arg::with_name("flash_loan_receiver_program_id")
+                        .long("receiver-program")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Flash loan receiver program, returning the loan and fee to the reserve"),
+                )
+                .arg(
+                    Arg::with_name("flash_loan_receiver_account")
+                        .long("receiver-account")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .multiple(true)
+                        .help("Additional writable account passed to the flash loan receiver program, after the owner"),
+                )
+                .arg(
+                    Arg::with_name("host_fee_receiver")
+                        .long("host-fee-receiver")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .help("SPL Token account to receive the host share of the flash loan fee [default: the destination]"),
+                )
+                .arg(
+                    Arg::with_name("liquidity_amount")
+                        .long("amount")
+                        .validator(is_amount)
+                        .value_name("DECIMAL_AMOUNT")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Amount of liquidity to flash borrow"),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("show-market")
+                .about("Show a lending market and its reserves")
+                .arg(
+                    Arg::with_name("lending_market")
+                        .long("market")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Lending market address"),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("show-reserve")
+                .about("Show a reserve")
+                .arg(
+                    Arg::with_name("reserve")
+                        .long("reserve")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Reserve address"),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("show-obligation")
+                .about("Show an obligation, with values as of its last refresh")
+                .arg(
+                    Arg::with_name("obligation")
+                        .long("obligation")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Obligation address"),
+                )
+        )
+        .get_matches();
+
+    let mut wallet_manager = None;
+    let config = {
+        let cli_config = if let Some(config_file) = matches.value_of("config_file") {
+            solana_cli_config::Config::load(config_file).unwrap_or_default()
+        } else {
+            solana_cli_config::Config::default()
+        };
+        let json_rpc_url = value_t!(matches, "json_rpc_url", String)
+            .unwrap_or_else(|_| cli_config.json_rpc_url.clone());
+
+        let fee_payer = signer_from_path(
+            &matches,
+            matches
+                .value_of("fee_payer")
+                .unwrap_or(&cli_config.keypair_path),
+            "fee_payer",
+            &mut wallet_manager,
+        )
+        .unwrap_or_else(|e| {
+            eprintln!("error: {}", e);
+            exit(1);
+        });
+
+        let lending_program_id = pubkey_of(&matches, "lending_program_id").unwrap();
+        let verbose = matches.is_present("verbose");
+        let output_format = matches
+            .value_of("output_format")
+            .map(|value| match value {
+                "json" => OutputFormat::Json,
+                "json-compact" => OutputFormat::JsonCompact,
+                _ => unreachable!(),
+            })
+            .unwrap_or(if verbose {
+                OutputFormat::DisplayVerbose
+            } else {
+                OutputFormat::Display
+            });
+        let dry_run = matches.is_present("dry_run");
+
+        Config {
+            rpc_client: RpcClient::new_with_commitment(json_rpc_url, CommitmentConfig::confirmed()),
+            fee_payer,
+            lending_program_id,
+            verbose,
+            output_format,
+            dry_run,
+        }
+    };
+
+    let _ = match matches.subcommand() {
+        ("create-market", Some(arg_matches)) => {
+            let lending_market_owner = pubkey_of(arg_matches, "lending_market_owner").unwrap();
+            let quote_currency = quote_currency_of(arg_matches, "quote_currency").unwrap();
+            let oracle_program_id = pubkey_of(arg_matches, "oracle_program_id").unwrap();
+            command_create_lending_market(
+                &config,
+                lending_market_owner,
+                quote_currency,
+                oracle_program_id,
+            )
+        }
+        ("add-reserve", Some(arg_matches)) => {
+            let lending_market_owner_keypair =
+                keypair_of(arg_matches, "lending_market_owner").unwrap();
+            let source_liquidity_owner_keypair =
+                keypair_of(arg_matches, "source_liquidity_owner").unwrap();
+            let lending_market_pubkey = pubkey_of(arg_matches, "lending_market").unwrap();
+            let source_liquidity_pubkey = pubkey_of(arg_matches, "source_liquidity").unwrap();
+            let ui_amount = value_of(arg_matches, "liquidity_amount").unwrap();
+            let pyth_product_pubkey = pubkey_of(arg_matches, "pyth_product").unwrap();
+            let pyth_price_pubkey = pubkey_of(arg_matches, "pyth_price").unwrap();
+            let optimal_utilization_rate =
+                value_of(arg_matches, "optimal_utilization_rate").unwrap();
+            let loan_to_value_ratio = value_of(arg_matches, "loan_to_value_ratio").unwrap();
+            let liquidation_bonus = value_of(arg_matches, "liquidation_bonus").unwrap();
+            let max_liquidation_bonus = value_of(arg_matches, "max_liquidation_bonus").unwrap();
+            let liquidation_threshold = value_of(arg_matches, "liquidation_threshold").unwrap();
+            let min_borrow_rate = value_of(arg_matches, "min_borrow_rate").unwrap();
+            let optimal_borrow_rate = value_of(arg_matches, "optimal_borrow_rate").unwrap();
+            let max_borrow_rate = value_of(arg_matches, "max_borrow_rate").unwrap();
+            let interest_rate_model = rate_curve_of(arg_matches, "borrow_rate_curve").map_or(
+                InterestRateModel::Kinked,
+                InterestRateModel::PiecewiseLinear,
+            );
+            let borrow_fee = value_of::<f64>(arg_matches, "borrow_fee").unwrap();
+            let flash_loan_fee = value_of::<f64>(arg_matches, "flash_loan_fee").unwrap();
+            let host_fee_percentage = value_of(arg_matches, "host_fee_percentage").unwrap();
+            let protocol_take_rate = value_of(arg_matches, "protocol_take_rate").unwrap();
+            let isolated_collateral = arg_matches.is_present("isolated_collateral");
+            let elevation_group = value_of(arg_matches, "elevation_group")
+                .map(|id| ElevationGroup {
+                    id,
+                    loan_to_value_ratio: value_of(
+                        arg_matches,
+                        "elevation_group_loan_to_value_ratio",
+                    )
+                    .unwrap(),
+                    liquidation_threshold: value_of(
+                        arg_matches,
+                        "elevation_group_liquidation_threshold",
+                    )
+                    .unwrap(),
+                })
+                .unwrap_or_default();
+
+            let borrow_fee_wad = (borrow_fee * WAD as f64) as u64;
+            let flash_loan_fee_wad = (flash_loan_fee * WAD as f64) as u64;
+            let oracle = OracleConfig {
+                source: oracle_source_of(arg_matches, "oracle_source").unwrap(),
+                max_staleness_slots: value_of(arg_matches, "max_staleness_slots").unwrap(),
+                max_confidence_bps: value_of(arg_matches, "max_confidence_bps").unwrap(),
+                twap_window_slots: value_of(arg_matches, "twap_window_slots").unwrap(),
+                fixed_price_wads: value_of::<f64>(arg_matches, "fixed_price")
+                    .map_or(0, |price| (price * WAD as f64) as u128),
+                secondary: pubkey_of(arg_matches, "secondary_oracle").map(|oracle_pubkey| {
+                    SecondaryOracle {
+                        source: oracle_source_of(arg_matches, "secondary_oracle_source").unwrap(),
+                        oracle_pubkey,
+                        max_divergence_bps: value_of(arg_matches, "max_divergence_bps").unwrap(),
+                    }
+                }),
+            };
+
+            command_add_reserve(
+                &config,
+                ui_amount,
+                ReserveConfig {
+                    optimal_utilization_rate,
+                    loan_to_value_ratio,
+                    liquidation_bonus,
+                    max_liquidation_bonus,
+                    liquidation_threshold,
+                    min_borrow_rate,
+                    optimal_borrow_rate,
+                    max_borrow_rate,
+                    interest_rate_model,
+                    fees: ReserveFees {
+                        borrow_fee_wad,
+                        flash_loan_fee_wad,
+                        host_fee_percentage,
+                        protocol_take_rate,
+                    },
+                    oracle,
+                    caps: ReserveCaps::default(),
+                    isolated_collateral,
+                    elevation_group,
+                    borrowing_disabled: false,
+                    paused: false,
+                },
+                UiReserveCaps::of(arg_matches),
+                source_liquidity_pubkey,
+                source_liquidity_owner_keypair,
+                lending_market_pubkey,
+                lending_market_owner_keypair,
+                pyth_product_pubkey,
+                pyth_price_pubkey,
+            )
+        }
+        ("set-reserve-caps", Some(arg_matches)) => {
+            let lending_market_owner_keypair =
+                keypair_of(arg_matches, "lending_market_owner").unwrap();
+            let reserve_pubkey = pubkey_of(arg_matches, "reserve").unwrap();
+
+            let pending_reserve_config_pubkey = pubkey_of(arg_matches, "pending_reserve_config");
+
+            command_set_reserve_caps(
+                &config,
+                reserve_pubkey,
+                UiReserveCaps::of(arg_matches),
+                pending_reserve_config_pubkey,
+                lending_market_owner_keypair,
+            )
+        }
+        ("set-close-factor", Some(arg_matches)) => {
+            let lending_market_owner_keypair =
+                keypair_of(arg_matches, "lending_market_owner").unwrap();
+            let lending_market_pubkey = pubkey_of(arg_matches, "lending_market").unwrap();
+            let liquidation_close_factor =
+                value_of(arg_matches, "liquidation_close_factor").unwrap();
+
+            command_set_close_factor(
+                &config,
+                lending_market_pubkey,
+                liquidation_close_factor,
+                lending_market_owner_keypair,
+            )
+        }
+        ("withdraw-protocol-fees", Some(arg_matches)) => {
+            let lending_market_owner_keypair =
+                keypair_of(arg_matches, "lending_market_owner").unwrap();
+            let reserve_pubkey = pubkey_of(arg_matches, "reserve").unwrap();
+            let destination_pubkey = pubkey_of(arg_matches, "destination").unwrap();
+            let ui_amount = value_of(arg_matches, "liquidity_amount");
+
+            command_withdraw_protocol_fees(
+                &config,
+                reserve_pubkey,
+                destination_pubkey,
+                ui_amount,
+                lending_market_owner_keypair,
+            )
+        }
+        ("update-reserve", Some(arg_matches)) => {
+            let lending_market_owner_keypair =
+                keypair_of(arg_matches, "lending_market_owner").unwrap();
+            let reserve_pubkey = pubkey_of(arg_matches, "reserve").unwrap();
+            let oracle_pubkeys =
+                pubkey_of(arg_matches, "pyth_product").zip(pubkey_of(arg_matches, "pyth_price"));
+            let pending_reserve_config_pubkey = pubkey_of(arg_matches, "pending_reserve_config");
+
+            command_update_reserve(
+                &config,
+                reserve_pubkey,
+                ReserveConfigUpdate::of(arg_matches),
+                UiReserveCaps::of(arg_matches),
+                oracle_pubkeys,
+                pending_reserve_config_pubkey,
+                lending_market_owner_keypair,
+            )
+        }
+        ("apply-reserve-config", Some(arg_matches)) => {
+            let pending_reserve_config_pubkey =
+                pubkey_of(arg_matches, "pending_reserve_config").unwrap();
+            command_apply_reserve_config(&config, pending_reserve_config_pubkey)
+        }
+        ("cancel-reserve-config", Some(arg_matches)) => {
+            let risk_authority_keypair = keypair_of(arg_matches, "risk_authority").unwrap();
+            let pending_reserve_config_pubkey =
+                pubkey_of(arg_matches, "pending_reserve_config").unwrap();
+
+            command_cancel_reserve_config(
+                &config,
+                pending_reserve_config_pubkey,
+                risk_authority_keypair,
+            )
+        }
+        ("tighten-reserve", Some(arg_matches)) => {
+            let risk_authority_keypair = keypair_of(arg_matches, "risk_authority").unwrap();
+            let reserve_pubkey = pubkey_of(arg_matches, "reserve").unwrap();
+            let loan_to_value_ratio = value_of(arg_matches, "loan_to_value_ratio");
+            let disable_borrowing = arg_matches.is_present("disable_borrowing");
+            let pause = arg_matches.is_present("pause");
+
+            command_tighten_reserve(
+                &config,
+                reserve_pubkey,
+                loan_to_value_ratio,
+                UiReserveCaps::of(arg_matches),
+                disable_borrowing,
+                pause,
+                risk_authority_keypair,
+            )
+        }
+        ("set-guardian", Some(arg_matches)) => {
+            let lending_market_owner_keypair =
+                keypair_of(arg_matches, "lending_market_owner").unwrap();
+            let lending_market_pubkey = pubkey_of(arg_matches, "lending_market").unwrap();
+            let guardian = pubkey_of(arg_matches, "guardian").unwrap_or_default();
+
+            command_set_guardian(
+                &config,
+                lending_market_pubkey,
+                guardian,
+                lending_market_owner_keypair,
+            )
+        }
+        ("set-config-timelock", Some(arg_matches)) => {
+            let lending_market_owner_keypair =
+                keypair_of(arg_matches, "lending_market_owner").unwrap();
+            let lending_market_pubkey = pubkey_of(arg_matches, "lending_market").unwrap();
+            let config_timelock_slots = value_of(arg_matches, "config_timelock_slots").unwrap();
+
+            command_set_config_timelock(
+                &config,
+                lending_market_pubkey,
+                config_timelock_slots,
+                lending_market_owner_keypair,
+            )
+        }
+        ("apply-market-changes", Some(arg_matches)) => {
+            let lending_market_pubkey = pubkey_of(arg_matches, "lending_market").unwrap();
+            command_apply_market_changes(&config, lending_market_pubkey)
+        }
+        ("create-obligation", Some(arg_matches)) => {
+            let obligation_owner_keypair = keypair_of(arg_matches, "obligation_owner").unwrap();
+            let lending_market_pubkey = pubkey_of(arg_matches, "lending_market").unwrap();
+
+            command_create_obligation(&config, lending_market_pubkey, obligation_owner_keypair)
+        }
+        ("deposit", Some(arg_matches)) => {
+            let source_liquidity_owner_keypair =
+                keypair_of(arg_matches, "source_liquidity_owner").unwrap();
+            let reserve_pubkey = pubkey_of(arg_matches, "reserve").unwrap();
+            let source_liquidity_pubkey = pubkey_of(arg_matches, "source_liquidity").unwrap();
+            let destination_collateral_pubkey =
+                pubkey_of(arg_matches, "destination_collateral").unwrap();
+            let ui_amount = value_of(arg_matches, "liquidity_amount").unwrap();
+
+            command_deposit(
+                &config,
+                reserve_pubkey,
+                source_liquidity_pubkey,
+                destination_collateral_pubkey,
+                ui_amount,
+                source_liquidity_owner_keypair,
+            )
+        }
+        ("redeem", Some(arg_matches)) => {
+            let source_collateral_owner_keypair =
+                keypair_of(arg_matches, "source_collateral_owner").unwrap();
+            let reserve_pubkey = pubkey_of(arg_matches, "reserve").unwrap();
+            let source_collateral_pubkey = pubkey_of(arg_matches, "source_collateral").unwrap();
+            let destination_liquidity_pubkey =
+                pubkey_of(arg_matches, "destination_liquidity").unwrap();
+            let ui_amount = ui_amount_or_all_of(arg_matches, "collateral_amount");
+
+            command_redeem(
+                &config,
+                reserve_pubkey,
+                source_collateral_pubkey,
+                destination_liquidity_pubkey,
+                ui_amount,
+                source_collateral_owner_keypair,
+            )
+        }
+        ("deposit-collateral", Some(arg_matches)) => {
+            let obligation_owner_keypair = keypair_of(arg_matches, "obligation_owner").unwrap();
+            let obligation_pubkey = pubkey_of(arg_matches, "obligation").unwrap();
+            let reserve_pubkey = pubkey_of(arg_matches, "reserve").unwrap();
+            let source_collateral_pubkey = pubkey_of(arg_matches, "source_collateral").unwrap();
+            let ui_amount = value_of(arg_matches, "collateral_amount").unwrap();
+
+            command_deposit_collateral(
+                &config,
+                obligation_pubkey,
+                reserve_pubkey,
+                source_collateral_pubkey,
+                ui_amount,
+                obligation_owner_keypair,
+            )
+        }
+        ("withdraw-collateral", Some(arg_matches)) => {
+            let obligation_owner_keypair = keypair_of(arg_matches, "obligation_owner").unwrap();
+            let obligation_pubkey = pubkey_of(arg_matches, "obligation").unwrap();
+            let reserve_pubkey = pubkey_of(arg_matches, "reserve").unwrap();
+            let destination_collateral_pubkey =
+                pubkey_of(arg_matches, "destination_collateral").unwrap();
+            let ui_amount = ui_amount_or_all_of(arg_matches, "collateral_amount");
+
+            command_withdraw_collateral(
+                &config,
+                obligation_pubkey,
+                reserve_pubkey,
+                destination_collateral_pubkey,
+                ui_amount,
+                obligation_owner_keypair,
+            )
+        }
+        ("borrow", Some(arg_matches)) => {
+            let obligation_owner_keypair = keypair_of(arg_matches, "obligation_owner").unwrap();
+            let obligation_pubkey = pubkey_of(arg_matches, "obligation").unwrap();
+            let reserve_pubkey = pubkey_of(arg_matches, "reserve").unwrap();
+            let destination_liquidity_pubkey =
+                pubkey_of(arg_matches, "destination_liquidity").unwrap();
+            let ui_amount = ui_amount_or_all_of(arg_matches, "liquidity_amount");
+            let host_fee_receiver_pubkey = pubkey_of(arg_matches, "host_fee_receiver");
+
+            command_borrow(
+                &config,
+                obligation_pubkey,
+                reserve_pubkey,
+                destination_liquidity_pubkey,
+                ui_amount,
+                host_fee_receiver_pubkey,
+                obligation_owner_keypair,
+            )
+        }
+        ("repay", Some(arg_matches)) => {
+            let source_liquidity_owner_keypair =
+                keypair_of(arg_matches, "source_liquidity_owner").unwrap();
+            let obligation_pubkey = pubkey_of(arg_matches, "obligation").unwrap();
+            let reserve_pubkey = pubkey_of(arg_matches, "reserve").unwrap();
+            let source_liquidity_pubkey = pubkey_of(arg_matches, "source_liquidity").unwrap();
+            let ui_amount = ui_amount_or_all_of(arg_matches, "liquidity_amount");
+
+            command_repay(
+                &config,
+                obligation_pubkey,
+                reserve_pubkey,
+                source_liquidity_pubkey,
+                ui_amount,
+                source_liquidity_owner_keypair,
+            )
+        }
+        ("flash-loan", Some(arg_matches)) => {
+            let destination_liquidity_owner_keypair =
+                keypair_of(arg_matches, "destination_liquidity_owner").unwrap();
+            let reserve_pubkey = pubkey_of(arg_matches, "reserve").unwrap();
+            let destination_liquidity_pubkey =
+                pubkey_of(arg_matches, "destination_liquidity").unwrap();
+            let flash_loan_receiver_program_id =
+                pubkey_of(arg_matches, "flash_loan_receiver_program_id").unwrap();
+            let flash_loan_receiver_pubkeys =
+                pubkeys_of(arg_matches, "flash_loan_receiver_account").unwrap_or_default();
+            let host_fee_receiver_pubkey =
+                pubkey_of(arg_matches, "host_fee_receiver").unwrap_or(destination_liquidity_pubkey);
+            let ui_amount = value_of(arg_matches, "liquidity_amount").unwrap();
+
+            command_flash_loan(
+                &config,
+                reserve_pubkey,
+                destination_liquidity_pubkey,
+                host_fee_receiver_pubkey,
+                flash_loan_receiver_program_id,
+                flash_loan_receiver_pubkeys,
+                ui_amount,
+                destination_liquidity_owner_keypair,
+            )
+        }
+        ("show-market", Some(arg_matches)) => {
+            let lending_market_pubkey = pubkey_of(arg_matches, "lending_market").unwrap();
+            command_show_market(&config, lending_market_pubkey)
+        }
+        ("show-reserve", Some(arg_matches)) => {
+            let reserve_pubkey = pubkey_of(arg_matches, "reserve").unwrap();
+            command_show_reserve(&config, reserve_pubkey)
+        }
+        ("show-obligation", Some(arg_matches)) => {
+            let obligation_pubkey = pubkey_of(arg_matches, "obligation").unwrap();
+            command_show_obligation(&config, obligation_pubkey)
+        }
+        _ => unreachable!(),
+    }
+    .map_err(|err| {
+        eprintln!("{}", err);
+        exit(1);
+    });
+}
+
+// COMMANDS
+
+fn command_create_lending_market(
+    config: &Config,
+    lending_market_owner: Pubkey,
+    quote_currency: [u8; 32],
+    oracle_program_id: Pubkey,
+) -> CommandResult {
+    let lending_market_keypair = Keypair::new();
+    println!(
+        "Creating lending market {}",
+        lending_market_keypair.pubkey()
+    );
+
+    let lending_market_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(LendingMarket::LEN)?;
+
+    let recent_blockhash = config.rpc_client.get_latest_blockhash()?;
+    let message = Message::new_with_blockhash(
+        &[
+            // Account for the lending market
+            create_account(
+                &config.fee_payer.pubkey(),
+                &lending_market_keypair.pubkey(),
+                lending_market_balance,
+                LendingMarket::LEN as u64,
+                &config.lending_program_id,
+            ),
+            // Initialize lending market account
+            init_lending_market(
+                config.lending_program_id,
+                lending_market_owner,
+                quote_currency,
+                lending_market_keypair.pubkey(),
+                oracle_program_id,
+            ),
+        ],
+        Some(&config.fee_payer.pubkey()),
+        &recent_blockhash,
+    );
+
+    check_fee_payer_balance(
+        config,
+        lending_market_balance + config.rpc_client.get_fee_for_message(&message)?,
+    )?;
+    let transaction = Transaction::new(
+        &vec![config.fee_payer.as_ref(), &lending_market_keypair],
+        message,
+        recent_blockhash,
+    );
+    send_transaction(config, transaction)?;
+    Ok(())
+}
 
 #[allow(clippy::too_many_arguments)]
 fn command_add_reserve(
     config: &Config,
     ui_amount: f64,
-    reserve_config: ReserveConfig,
+    mut reserve_config: ReserveConfig,
+    ui_caps: UiReserveCaps,
     source_liquidity_pubkey: Pubkey,
     source_liquidity_owner_keypair: Keypair,
     lending_market_pubkey: Pubkey,
@@ -486,12 +1889,24 @@ fn command_add_reserve(
     pyth_price_pubkey: Pubkey,
 ) -> CommandResult {
     let source_liquidity_account = config.rpc_client.get_account(&source_liquidity_pubkey)?;
-    let source_liquidity = Token::unpack_from_slice(source_liquidity_account.data.borrow())?;
+    let liquidity_token_program_id = source_liquidity_account.owner;
+    let source_liquidity = StateWithExtensions::<spl_token_2022::state::Account>::unpack(
+        source_liquidity_account.data.borrow(),
+    )?
+    .base;
 
     let source_liquidity_mint_account = config.rpc_client.get_account(&source_liquidity.mint)?;
-    let source_liquidity_mint =
-        Mint::unpack_from_slice(source_liquidity_mint_account.data.borrow())?;
-    let liquidity_amount = ui_amount_to_amount(ui_amount, source_liquidity_mint.decimals);
+    let source_liquidity_mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(
+        source_liquidity_mint_account.data.borrow(),
+    )?;
+    let liquidity_decimals = source_liquidity_mint.base.decimals;
+    let liquidity_amount = ui_amount_to_amount(ui_amount, liquidity_decimals);
+    ui_caps.apply(&mut reserve_config.caps, liquidity_decimals);
+    let liquidity_account_len = ExtensionType::try_calculate_account_len::<
+        spl_token_2022::state::Account,
+    >(&ExtensionType::get_required_init_account_extensions(
+        &source_liquidity_mint.get_extension_types()?,
+    ))?;
 
     let reserve_keypair = Keypair::new();
     let collateral_mint_keypair = Keypair::new();
@@ -502,6 +1917,14 @@ fn command_add_reserve(
     let user_transfer_authority_keypair = Keypair::new();
 
     println!("Adding reserve {}", reserve_keypair.pubkey());
+    let lending_market_account = config.rpc_client.get_account(&lending_market_pubkey)?;
+    let lending_market = LendingMarket::unpack(lending_market_account.data.borrow())?;
+    if lending_market.config_timelock_slots > 0 {
+        println!(
+            "Borrowing is disabled on the reserve until its config is loosened with update-reserve, queued for {} slots",
+            lending_market.config_timelock_slots
+        );
+    }
     if config.verbose {
         println!(
             "Adding collateral mint {}",
@@ -540,8 +1963,11 @@ fn command_add_reserve(
         .get_minimum_balance_for_rent_exemption(Token::LEN)?;
     let collateral_supply_balance = token_account_balance;
     let user_collateral_balance = token_account_balance;
-    let liquidity_supply_balance = token_account_balance;
-    let liquidity_fee_receiver_balance = token_account_balance;
+    let liquidity_account_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(liquidity_account_len)?;
+    let liquidity_supply_balance = liquidity_account_balance;
+    let liquidity_fee_receiver_balance = liquidity_account_balance;
 
     let total_balance = reserve_balance
         + collateral_mint_balance
@@ -592,15 +2018,15 @@ fn command_add_reserve(
                 &config.fee_payer.pubkey(),
                 &liquidity_supply_keypair.pubkey(),
                 liquidity_supply_balance,
-                Token::LEN as u64,
-                &spl_token::id(),
+                liquidity_account_len as u64,
+                &liquidity_token_program_id,
             ),
             create_account(
                 &config.fee_payer.pubkey(),
                 &liquidity_fee_receiver_keypair.pubkey(),
                 liquidity_fee_receiver_balance,
-                Token::LEN as u64,
-                &spl_token::id(),
+                liquidity_account_len as u64,
+                &liquidity_token_program_id,
             ),
         ],
         Some(&config.fee_payer.pubkey()),
@@ -610,7 +2036,7 @@ fn command_add_reserve(
     let message_3 = Message::new_with_blockhash(
         &[
             approve(
-                &spl_token::id(),
+                &liquidity_token_program_id,
                 &source_liquidity_pubkey,
                 &user_transfer_authority_keypair.pubkey(),
                 &source_liquidity_owner_keypair.pubkey(),
@@ -633,66 +2059,1071 @@ fn command_add_reserve(
                 pyth_product_pubkey,
                 pyth_price_pubkey,
                 lending_market_pubkey,
-                lending_market_owner_keypair.pubkey(),
-                user_transfer_authority_keypair.pubkey(),
+                lending_market_owner_keypair.pubkey(),
+                user_transfer_authority_keypair.pubkey(),
+                liquidity_token_program_id,
+            ),
+            revoke(
+                &liquidity_token_program_id,
+                &source_liquidity_pubkey,
+                &source_liquidity_owner_keypair.pubkey(),
+                &[],
+            )
+            .unwrap(),
+        ],
+        Some(&config.fee_payer.pubkey()),
+        &recent_blockhash,
+    );
+    check_fee_payer_balance(
+        config,
+        total_balance
+            + config.rpc_client.get_fee_for_message(&message_1)?
+            + config.rpc_client.get_fee_for_message(&message_2)?
+            + config.rpc_client.get_fee_for_message(&message_3)?,
+    )?;
+
+    let transaction_1 = Transaction::new(
+        &vec![
+            config.fee_payer.as_ref(),
+            &reserve_keypair,
+            &collateral_mint_keypair,
+            &collateral_supply_keypair,
+            &user_collateral_keypair,
+        ],
+        message_1,
+        recent_blockhash,
+    );
+    send_transaction(config, transaction_1)?;
+    let transaction_2 = Transaction::new(
+        &vec![
+            config.fee_payer.as_ref(),
+            &liquidity_supply_keypair,
+            &liquidity_fee_receiver_keypair,
+        ],
+        message_2,
+        recent_blockhash,
+    );
+    send_transaction(config, transaction_2)?;
+    let transaction_3 = Transaction::new(
+        &vec![
+            config.fee_payer.as_ref(),
+            &source_liquidity_owner_keypair,
+            &lending_market_owner_keypair,
+            &user_transfer_authority_keypair,
+        ],
+        message_3,
+        recent_blockhash,
+    );
+    send_transaction(config, transaction_3)?;
+    Ok(())
+}
+
+fn command_set_reserve_caps(
+    config: &Config,
+    reserve_pubkey: Pubkey,
+    ui_caps: UiReserveCaps,
+    pending_reserve_config_pubkey: Option<Pubkey>,
+    lending_market_owner_keypair: Keypair,
+) -> CommandResult {
+    let reserve_account = config.rpc_client.get_account(&reserve_pubkey)?;
+    let reserve = Reserve::unpack(reserve_account.data.borrow())?;
+    let mut reserve_config = reserve.config;
+    ui_caps.apply(&mut reserve_config.caps, reserve.liquidity.mint_decimals);
+
+    println!("Setting caps of reserve {}", reserve_pubkey);
+    if config.verbose {
+        println!("Reserve caps {:?}", reserve_config.caps);
+    }
+
+    send_reserve_config(
+        config,
+        reserve_pubkey,
+        &reserve,
+        reserve_config,
+        None,
+        pending_reserve_config_pubkey,
+        lending_market_owner_keypair,
+    )
+}
+
+fn command_set_close_factor(
+    config: &Config,
+    lending_market_pubkey: Pubkey,
+    liquidation_close_factor: u8,
+    lending_market_owner_keypair: Keypair,
+) -> CommandResult {
+    println!(
+        "Setting liquidation close factor of lending market {} to {}%",
+        lending_market_pubkey, liquidation_close_factor
+    );
+    let lending_market_account = config.rpc_client.get_account(&lending_market_pubkey)?;
+    let lending_market = LendingMarket::unpack(lending_market_account.data.borrow())?;
+    if lending_market.config_timelock_slots > 0 {
+        println!(
+            "Queuing the close factor for {} slots, apply it with apply-market-changes",
+            lending_market.config_timelock_slots
+        );
+    }
+
+    let recent_blockhash = config.rpc_client.get_latest_blockhash()?;
+    let message = Message::new_with_blockhash(
+        &[set_liquidation_close_factor(
+            config.lending_program_id,
+            lending_market_pubkey,
+            lending_market_owner_keypair.pubkey(),
+            liquidation_close_factor,
+        )],
+        Some(&config.fee_payer.pubkey()),
+        &recent_blockhash,
+    );
+
+    check_fee_payer_balance(config, config.rpc_client.get_fee_for_message(&message)?)?;
+    let transaction = Transaction::new(
+        &vec![config.fee_payer.as_ref(), &lending_market_owner_keypair],
+        message,
+        recent_blockhash,
+    );
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
+fn command_withdraw_protocol_fees(
+    config: &Config,
+    reserve_pubkey: Pubkey,
+    destination_pubkey: Pubkey,
+    ui_amount: Option<f64>,
+    lending_market_owner_keypair: Keypair,
+) -> CommandResult {
+    let reserve_account = config.rpc_client.get_account(&reserve_pubkey)?;
+    let reserve = Reserve::unpack(reserve_account.data.borrow())?;
+    let liquidity_amount = ui_amount.map_or(u64::MAX, |ui_amount| {
+        ui_amount_to_amount(ui_amount, reserve.liquidity.mint_decimals)
+    });
+
+    println!("Withdrawing protocol fees of reserve {}", reserve_pubkey);
+    if config.verbose {
+        println!(
+            "Accumulated protocol fees {}",
+            reserve.liquidity.accumulated_protocol_fees_wads
+        );
+    }
+
+    let recent_blockhash = config.rpc_client.get_latest_blockhash()?;
+    let message = Message::new_with_blockhash(
+        &[
+            refresh_reserve_instruction(config, reserve_pubkey, &reserve)?,
+            withdraw_protocol_fees(
+                config.lending_program_id,
+                liquidity_amount,
+                reserve_pubkey,
+                reserve.liquidity.supply_pubkey,
+                destination_pubkey,
+                reserve.lending_market,
+                lending_market_owner_keypair.pubkey(),
+                reserve.liquidity.mint_pubkey,
+                reserve.liquidity.token_program_id,
+            ),
+        ],
+        Some(&config.fee_payer.pubkey()),
+        &recent_blockhash,
+    );
+
+    check_fee_payer_balance(config, config.rpc_client.get_fee_for_message(&message)?)?;
+    let transaction = Transaction::new(
+        &vec![config.fee_payer.as_ref(), &lending_market_owner_keypair],
+        message,
+        recent_blockhash,
+    );
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
+fn command_update_reserve(
+    config: &Config,
+    reserve_pubkey: Pubkey,
+    config_update: ReserveConfigUpdate,
+    ui_caps: UiReserveCaps,
+    oracle_pubkeys: Option<(Pubkey, Pubkey)>,
+    pending_reserve_config_pubkey: Option<Pubkey>,
+    lending_market_owner_keypair: Keypair,
+) -> CommandResult {
+    let reserve_account = config.rpc_client.get_account(&reserve_pubkey)?;
+    let reserve = Reserve::unpack(reserve_account.data.borrow())?;
+    let mut reserve_config = reserve.config;
+    config_update.apply(&mut reserve_config);
+    ui_caps.apply(&mut reserve_config.caps, reserve.liquidity.mint_decimals);
+
+    println!("Updating config of reserve {}", reserve_pubkey);
+    if config.verbose {
+        println!("Reserve config {:?}", reserve_config);
+    }
+
+    send_reserve_config(
+        config,
+        reserve_pubkey,
+        &reserve,
+        reserve_config,
+        oracle_pubkeys,
+        pending_reserve_config_pubkey,
+        lending_market_owner_keypair,
+    )
+}
+
+fn command_apply_reserve_config(
+    config: &Config,
+    pending_reserve_config_pubkey: Pubkey,
+) -> CommandResult {
+    let pending_reserve_config_account = config
+        .rpc_client
+        .get_account(&pending_reserve_config_pubkey)?;
+    let pending_reserve_config =
+        PendingReserveConfig::unpack(pending_reserve_config_account.data.borrow())?;
+
+    println!(
+        "Applying pending reserve config {} to reserve {}",
+        pending_reserve_config_pubkey, pending_reserve_config.reserve
+    );
+    if config.verbose {
+        println!(
+            "Effective from slot {}",
+            pending_reserve_config.effective_slot
+        );
+    }
+
+    send_instructions(
+        config,
+        &[apply_reserve_config(
+            config.lending_program_id,
+            pending_reserve_config_pubkey,
+            pending_reserve_config.reserve,
+            pending_reserve_config.lending_market,
+        )],
+        vec![config.fee_payer.as_ref()],
+    )
+}
+
+fn command_cancel_reserve_config(
+    config: &Config,
+    pending_reserve_config_pubkey: Pubkey,
+    risk_authority_keypair: Keypair,
+) -> CommandResult {
+    let pending_reserve_config_account = config
+        .rpc_client
+        .get_account(&pending_reserve_config_pubkey)?;
+    let pending_reserve_config =
+        PendingReserveConfig::unpack(pending_reserve_config_account.data.borrow())?;
+
+    println!(
+        "Canceling pending reserve config {} of reserve {}",
+        pending_reserve_config_pubkey, pending_reserve_config.reserve
+    );
+
+    send_instructions(
+        config,
+        &[cancel_reserve_config(
+            config.lending_program_id,
+            pending_reserve_config_pubkey,
+            pending_reserve_config.lending_market,
+            risk_authority_keypair.pubkey(),
+        )],
+        vec![config.fee_payer.as_ref(), &risk_authority_keypair],
+    )
+}
+
+fn command_tighten_reserve(
+    config: &Config,
+    reserve_pubkey: Pubkey,
+    loan_to_value_ratio: Option<u8>,
+    ui_caps: UiReserveCaps,
+    disable_borrowing: bool,
+    pause: bool,
+    risk_authority_keypair: Keypair,
+) -> CommandResult {
+    let reserve_account = config.rpc_client.get_account(&reserve_pubkey)?;
+    let reserve = Reserve::unpack(reserve_account.data.borrow())?;
+    let mut reserve_config = reserve.config;
+    if let Some(loan_to_value_ratio) = loan_to_value_ratio {
+        reserve_config.loan_to_value_ratio = loan_to_value_ratio;
+    }
+    ui_caps.apply(&mut reserve_config.caps, reserve.liquidity.mint_decimals);
+    reserve_config.borrowing_disabled |= disable_borrowing;
+    reserve_config.paused |= pause;
+
+    println!("Tightening config of reserve {}", reserve_pubkey);
+    if config.verbose {
+        println!("Reserve config {:?}", reserve_config);
+    }
+
+    send_instructions(
+        config,
+        &[tighten_reserve_config(
+            config.lending_program_id,
+            reserve_config,
+            reserve_pubkey,
+            reserve.lending_market,
+            risk_authority_keypair.pubkey(),
+        )],
+        vec![config.fee_payer.as_ref(), &risk_authority_keypair],
+    )
+}
+
+fn command_set_guardian(
+    config: &Config,
+    lending_market_pubkey: Pubkey,
+    guardian: Pubkey,
+    lending_market_owner_keypair: Keypair,
+) -> CommandResult {
+    if guardian == Pubkey::default() {
+        println!(
+            "Removing the guardian of lending market {}",
+            lending_market_pubkey
+        );
+    } else {
+        println!(
+            "Setting guardian of lending market {} to {}",
+            lending_market_pubkey, guardian
+        );
+    }
+
+    send_instructions(
+        config,
+        &[set_lending_market_guardian(
+            config.lending_program_id,
+            lending_market_pubkey,
+            lending_market_owner_keypair.pubkey(),
+            guardian,
+        )],
+        vec![config.fee_payer.as_ref(), &lending_market_owner_keypair],
+    )
+}
+
+fn command_set_config_timelock(
+    config: &Config,
+    lending_market_pubkey: Pubkey,
+    config_timelock_slots: u64,
+    lending_market_owner_keypair: Keypair,
+) -> CommandResult {
+    println!(
+        "Setting config timelock of lending market {} to {} slots",
+        lending_market_pubkey, config_timelock_slots
+    );
+
+    send_instructions(
+        config,
+        &[set_config_timelock(
+            config.lending_program_id,
+            lending_market_pubkey,
+            lending_market_owner_keypair.pubkey(),
+            config_timelock_slots,
+        )],
+        vec![config.fee_payer.as_ref(), &lending_market_owner_keypair],
+    )
+}
+
+fn command_apply_market_changes(config: &Config, lending_market_pubkey: Pubkey) -> CommandResult {
+    println!(
+        "Applying pending changes of lending market {}",
+        lending_market_pubkey
+    );
+
+    send_instructions(
+        config,
+        &[apply_lending_market_changes(
+            config.lending_program_id,
+            lending_market_pubkey,
+        )],
+        vec![config.fee_payer.as_ref()],
+    )
+}
+
+fn command_create_obligation(
+    config: &Config,
+    lending_market_pubkey: Pubkey,
+    obligation_owner_keypair: Keypair,
+) -> CommandResult {
+    let obligation_keypair = Keypair::new();
+    println!("Creating obligation {}", obligation_keypair.pubkey());
+
+    let obligation_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(Obligation::LEN)?;
+
+    let recent_blockhash = config.rpc_client.get_latest_blockhash()?;
+    let message = Message::new_with_blockhash(
+        &[
+            create_account(
+                &config.fee_payer.pubkey(),
+                &obligation_keypair.pubkey(),
+                obligation_balance,
+                Obligation::LEN as u64,
+                &config.lending_program_id,
+            ),
+            init_obligation(
+                config.lending_program_id,
+                obligation_keypair.pubkey(),
+                lending_market_pubkey,
+                obligation_owner_keypair.pubkey(),
             ),
-            revoke(
-                &spl_token::id(),
-                &source_liquidity_pubkey,
-                &source_liquidity_owner_keypair.pubkey(),
-                &[],
-            )
-            .unwrap(),
         ],
         Some(&config.fee_payer.pubkey()),
         &recent_blockhash,
     );
+
     check_fee_payer_balance(
         config,
-        total_balance
-            + config.rpc_client.get_fee_for_message(&message_1)?
-            + config.rpc_client.get_fee_for_message(&message_2)?
-            + config.rpc_client.get_fee_for_message(&message_3)?,
+        obligation_balance + config.rpc_client.get_fee_for_message(&message)?,
     )?;
-
-    let transaction_1 = Transaction::new(
+    let transaction = Transaction::new(
         &vec![
             config.fee_payer.as_ref(),
-            &reserve_keypair,
-            &collateral_mint_keypair,
-            &collateral_supply_keypair,
-            &user_collateral_keypair,
+            &obligation_keypair,
+            &obligation_owner_keypair,
         ],
-        message_1,
+        message,
         recent_blockhash,
     );
-    send_transaction(config, transaction_1)?;
-    let transaction_2 = Transaction::new(
-        &vec![
-            config.fee_payer.as_ref(),
-            &liquidity_supply_keypair,
-            &liquidity_fee_receiver_keypair,
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
+fn command_deposit(
+    config: &Config,
+    reserve_pubkey: Pubkey,
+    source_liquidity_pubkey: Pubkey,
+    destination_collateral_pubkey: Pubkey,
+    ui_amount: f64,
+    source_liquidity_owner_keypair: Keypair,
+) -> CommandResult {
+    let reserve_account = config.rpc_client.get_account(&reserve_pubkey)?;
+    let reserve = Reserve::unpack(reserve_account.data.borrow())?;
+    let liquidity_amount = ui_amount_to_amount(ui_amount, reserve.liquidity.mint_decimals);
+
+    println!(
+        "Depositing {} liquidity into reserve {}",
+        ui_amount, reserve_pubkey
+    );
+
+    send_instructions(
+        config,
+        &[
+            refresh_reserve_instruction(config, reserve_pubkey, &reserve)?,
+            deposit_reserve_liquidity(
+                config.lending_program_id,
+                liquidity_amount,
+                source_liquidity_pubkey,
+                destination_collateral_pubkey,
+                reserve_pubkey,
+                reserve.liquidity.supply_pubkey,
+                reserve.collateral.mint_pubkey,
+                reserve.lending_market,
+                source_liquidity_owner_keypair.pubkey(),
+                reserve.liquidity.mint_pubkey,
+                reserve.liquidity.token_program_id,
+            ),
         ],
-        message_2,
-        recent_blockhash,
+        vec![config.fee_payer.as_ref(), &source_liquidity_owner_keypair],
+    )
+}
+
+fn command_redeem(
+    config: &Config,
+    reserve_pubkey: Pubkey,
+    source_collateral_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
+    ui_amount: Option<f64>,
+    source_collateral_owner_keypair: Keypair,
+) -> CommandResult {
+    let reserve_account = config.rpc_client.get_account(&reserve_pubkey)?;
+    let reserve = Reserve::unpack(reserve_account.data.borrow())?;
+    // collateral mints have the decimals of their liquidity mint
+    let collateral_amount = match ui_amount {
+        Some(ui_amount) => ui_amount_to_amount(ui_amount, reserve.liquidity.mint_decimals),
+        None => {
+            let source_collateral_account =
+                config.rpc_client.get_account(&source_collateral_pubkey)?;
+            Token::unpack(source_collateral_account.data.borrow())?.amount
+        }
+    };
+
+    println!(
+        "Redeeming {} collateral from reserve {}",
+        collateral_amount, reserve_pubkey
     );
-    send_transaction(config, transaction_2)?;
-    let transaction_3 = Transaction::new(
-        &vec![
+
+    send_instructions(
+        config,
+        &[
+            refresh_reserve_instruction(config, reserve_pubkey, &reserve)?,
+            redeem_reserve_collateral(
+                config.lending_program_id,
+                collateral_amount,
+                source_collateral_pubkey,
+                destination_liquidity_pubkey,
+                reserve_pubkey,
+                reserve.collateral.mint_pubkey,
+                reserve.liquidity.supply_pubkey,
+                reserve.lending_market,
+                source_collateral_owner_keypair.pubkey(),
+                reserve.liquidity.mint_pubkey,
+                reserve.liquidity.token_program_id,
+            ),
+        ],
+        vec![config.fee_payer.as_ref(), &source_collateral_owner_keypair],
+    )
+}
+
+fn command_deposit_collateral(
+    config: &Config,
+    obligation_pubkey: Pubkey,
+    reserve_pubkey: Pubkey,
+    source_collateral_pubkey: Pubkey,
+    ui_amount: f64,
+    obligation_owner_keypair: Keypair,
+) -> CommandResult {
+    let reserve_account = config.rpc_client.get_account(&reserve_pubkey)?;
+    let reserve = Reserve::unpack(reserve_account.data.borrow())?;
+    let collateral_amount = ui_amount_to_amount(ui_amount, reserve.liquidity.mint_decimals);
+
+    println!(
+        "Depositing {} collateral of reserve {} into obligation {}",
+        ui_amount, reserve_pubkey, obligation_pubkey
+    );
+
+    send_instructions(
+        config,
+        &[
+            refresh_reserve_instruction(config, reserve_pubkey, &reserve)?,
+            deposit_obligation_collateral(
+                config.lending_program_id,
+                collateral_amount,
+                source_collateral_pubkey,
+                reserve.collateral.supply_pubkey,
+                reserve_pubkey,
+                obligation_pubkey,
+                reserve.lending_market,
+                obligation_owner_keypair.pubkey(),
+                obligation_owner_keypair.pubkey(),
+            ),
+        ],
+        vec![config.fee_payer.as_ref(), &obligation_owner_keypair],
+    )
+}
+
+fn command_withdraw_collateral(
+    config: &Config,
+    obligation_pubkey: Pubkey,
+    reserve_pubkey: Pubkey,
+    destination_collateral_pubkey: Pubkey,
+    ui_amount: Option<f64>,
+    obligation_owner_keypair: Keypair,
+) -> CommandResult {
+    let reserve_account = config.rpc_client.get_account(&reserve_pubkey)?;
+    let reserve = Reserve::unpack(reserve_account.data.borrow())?;
+    let collateral_amount = ui_amount.map_or(u64::MAX, |ui_amount| {
+        ui_amount_to_amount(ui_amount, reserve.liquidity.mint_decimals)
+    });
+
+    println!(
+        "Withdrawing collateral of reserve {} from obligation {}",
+        reserve_pubkey, obligation_pubkey
+    );
+
+    let mut instructions = refresh_obligation_instructions(config, obligation_pubkey, &[])?;
+    instructions.push(withdraw_obligation_collateral(
+        config.lending_program_id,
+        collateral_amount,
+        reserve.collateral.supply_pubkey,
+        destination_collateral_pubkey,
+        reserve_pubkey,
+        obligation_pubkey,
+        reserve.lending_market,
+        obligation_owner_keypair.pubkey(),
+    ));
+    send_instructions(
+        config,
+        &instructions,
+        vec![config.fee_payer.as_ref(), &obligation_owner_keypair],
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn command_borrow(
+    config: &Config,
+    obligation_pubkey: Pubkey,
+    reserve_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
+    ui_amount: Option<f64>,
+    host_fee_receiver_pubkey: Option<Pubkey>,
+    obligation_owner_keypair: Keypair,
+) -> CommandResult {
+    let reserve_account = config.rpc_client.get_account(&reserve_pubkey)?;
+    let reserve = Reserve::unpack(reserve_account.data.borrow())?;
+    let liquidity_amount = ui_amount.map_or(u64::MAX, |ui_amount| {
+        ui_amount_to_amount(ui_amount, reserve.liquidity.mint_decimals)
+    });
+
+    println!(
+        "Borrowing liquidity of reserve {} for obligation {}",
+        reserve_pubkey, obligation_pubkey
+    );
+
+    // the borrow reserve is refreshed even before the obligation borrows from it
+    let mut instructions =
+        refresh_obligation_instructions(config, obligation_pubkey, &[reserve_pubkey])?;
+    instructions.push(borrow_obligation_liquidity(
+        config.lending_program_id,
+        liquidity_amount,
+        None,
+        reserve.liquidity.supply_pubkey,
+        destination_liquidity_pubkey,
+        reserve_pubkey,
+        reserve.liquidity.fee_receiver,
+        obligation_pubkey,
+        reserve.lending_market,
+        obligation_owner_keypair.pubkey(),
+        reserve.liquidity.mint_pubkey,
+        reserve.liquidity.token_program_id,
+        host_fee_receiver_pubkey,
+    ));
+    send_instructions(
+        config,
+        &instructions,
+        vec![config.fee_payer.as_ref(), &obligation_owner_keypair],
+    )
+}
+
+fn command_repay(
+    config: &Config,
+    obligation_pubkey: Pubkey,
+    reserve_pubkey: Pubkey,
+    source_liquidity_pubkey: Pubkey,
+    ui_amount: Option<f64>,
+    source_liquidity_owner_keypair: Keypair,
+) -> CommandResult {
+    let reserve_account = config.rpc_client.get_account(&reserve_pubkey)?;
+    let reserve = Reserve::unpack(reserve_account.data.borrow())?;
+    let liquidity_amount = ui_amount.map_or(u64::MAX, |ui_amount| {
+        ui_amount_to_amount(ui_amount, reserve.liquidity.mint_decimals)
+    });
+
+    println!(
+        "Repaying liquidity of reserve {} for obligation {}",
+        reserve_pubkey, obligation_pubkey
+    );
+
+    let mut instructions = refresh_obligation_instructions(config, obligation_pubkey, &[])?;
+    instructions.push(repay_obligation_liquidity(
+        config.lending_program_id,
+        liquidity_amount,
+        source_liquidity_pubkey,
+        reserve.liquidity.supply_pubkey,
+        reserve_pubkey,
+        obligation_pubkey,
+        reserve.lending_market,
+        source_liquidity_owner_keypair.pubkey(),
+        reserve.liquidity.mint_pubkey,
+        reserve.liquidity.token_program_id,
+    ));
+    send_instructions(
+        config,
+        &instructions,
+        vec![config.fee_payer.as_ref(), &source_liquidity_owner_keypair],
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn command_flash_loan(
+    config: &Config,
+    reserve_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
+    host_fee_receiver_pubkey: Pubkey,
+    flash_loan_receiver_program_id: Pubkey,
+    flash_loan_receiver_pubkeys: Vec<Pubkey>,
+    ui_amount: f64,
+    destination_liquidity_owner_keypair: Keypair,
+) -> CommandResult {
+    let reserve_account = config.rpc_client.get_account(&reserve_pubkey)?;
+    let reserve = Reserve::unpack(reserve_account.data.borrow())?;
+    let liquidity_amount = ui_amount_to_amount(ui_amount, reserve.liquidity.mint_decimals);
+
+    println!(
+        "Flash borrowing {} liquidity of reserve {} through program {}",
+        ui_amount, reserve_pubkey, flash_loan_receiver_program_id
+    );
+
+    let mut flash_loan_receiver_accounts = vec![AccountMeta::new_readonly(
+        destination_liquidity_owner_keypair.pubkey(),
+        true,
+    )];
+    flash_loan_receiver_accounts.extend(
+        flash_loan_receiver_pubkeys
+            .into_iter()
+            .map(|pubkey| AccountMeta::new(pubkey, false)),
+    );
+    send_instructions(
+        config,
+        &[flash_loan(
+            config.lending_program_id,
+            liquidity_amount,
+            reserve.liquidity.supply_pubkey,
+            destination_liquidity_pubkey,
+            reserve_pubkey,
+            reserve.liquidity.fee_receiver,
+            host_fee_receiver_pubkey,
+            reserve.lending_market,
+            reserve.liquidity.mint_pubkey,
+            reserve.liquidity.token_program_id,
+            flash_loan_receiver_program_id,
+            flash_loan_receiver_accounts,
+        )],
+        vec![
             config.fee_payer.as_ref(),
-            &source_liquidity_owner_keypair,
-            &lending_market_owner_keypair,
-            &user_transfer_authority_keypair,
+            &destination_liquidity_owner_keypair,
         ],
-        message_3,
-        recent_blockhash,
+    )
+}
+
+fn command_show_market(config: &Config, lending_market_pubkey: Pubkey) -> CommandResult {
+    let lending_market_account = config.rpc_client.get_account(&lending_market_pubkey)?;
+    let lending_market = LendingMarket::unpack(lending_market_account.data.borrow())?;
+    let mut reserve_pubkeys: Vec<Pubkey> = config
+        .rpc_client
+        .get_program_accounts_with_config(
+            &config.lending_program_id,
+            RpcProgramAccountsConfig {
+                filters: Some(vec![
+                    RpcFilterType::DataSize(Reserve::LEN as u64),
+                    RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                        RESERVE_LENDING_MARKET_OFFSET,
+                        lending_market_pubkey.to_bytes().to_vec(),
+                    )),
+                ]),
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    ..RpcAccountInfoConfig::default()
+                },
+                ..RpcProgramAccountsConfig::default()
+            },
+        )?
+        .into_iter()
+        .map(|(pubkey, _)| pubkey)
+        .collect();
+    reserve_pubkeys.sort();
+
+    let cli_lending_market =
+        CliLendingMarket::new(lending_market_pubkey, lending_market, reserve_pubkeys);
+    println!(
+        "{}",
+        config.output_format.formatted_string(&cli_lending_market)
     );
-    send_transaction(config, transaction_3)?;
+    Ok(())
+}
+
+fn command_show_reserve(config: &Config, reserve_pubkey: Pubkey) -> CommandResult {
+    let reserve_account = config.rpc_client.get_account(&reserve_pubkey)?;
+    let reserve = Reserve::unpack(reserve_account.data.borrow())?;
+    let slot = config.rpc_client.get_slot()?;
+
+    let cli_reserve = CliReserve::new(reserve_pubkey, reserve, slot);
+    println!("{}", config.output_format.formatted_string(&cli_reserve));
+    Ok(())
+}
+
+fn command_show_obligation(config: &Config, obligation_pubkey: Pubkey) -> CommandResult {
+    let obligation_account = config.rpc_client.get_account(&obligation_pubkey)?;
+    let obligation = Obligation::unpack(obligation_account.data.borrow())?;
+    let slot = config.rpc_client.get_slot()?;
+
+    let cli_obligation = CliObligation::new(obligation_pubkey, obligation, slot);
+    println!("{}", config.output_format.formatted_string(&cli_obligation));
     Ok(())
 }
 
 // HELPERS
 
+/// Build the instructions refreshing every reserve of an obligation, and the
+/// other reserves given, followed by the obligation itself, in chunks for
+/// extended obligations with more positions than fit in a single refresh
+fn refresh_obligation_instructions(
+    config: &Config,
+    obligation_pubkey: Pubkey,
+    other_reserve_pubkeys: &[Pubkey],
+) -> Result<Vec<Instruction>, Error> {
+    let obligation_account = config.rpc_client.get_account(&obligation_pubkey)?;
+    let obligation = Obligation::unpack(obligation_account.data.borrow())?;
+    let position_reserve_pubkeys: Vec<Pubkey> = obligation
+        .deposits
+        .iter()
+        .map(|collateral| collateral.deposit_reserve)
+        .chain(
+            obligation
+                .borrows
+                .iter()
+                .map(|liquidity| liquidity.borrow_reserve),
+        )
+        .collect();
+
+    let mut instructions = vec![];
+    let mut refreshed_reserve_pubkeys = vec![];
+    for reserve_pubkey in position_reserve_pubkeys.iter().chain(other_reserve_pubkeys) {
+        if refreshed_reserve_pubkeys.contains(reserve_pubkey) {
+            continue;
+        }
+        let reserve_account = config.rpc_client.get_account(reserve_pubkey)?;
+        let reserve = Reserve::unpack(reserve_account.data.borrow())?;
+        instructions.push(refresh_reserve_instruction(
+            config,
+            *reserve_pubkey,
+            &reserve,
+        )?);
+        refreshed_reserve_pubkeys.push(*reserve_pubkey);
+    }
+
+    if position_reserve_pubkeys.len() <= MAX_OBLIGATION_RESERVES {
+        instructions.push(refresh_obligation(
+            config.lending_program_id,
+            obligation_pubkey,
+            position_reserve_pubkeys,
+        ));
+    } else {
+        for (chunk_index, chunk) in position_reserve_pubkeys
+            .chunks(MAX_OBLIGATION_RESERVES)
+            .enumerate()
+        {
+            instructions.push(refresh_obligation_chunk(
+                config.lending_program_id,
+                obligation_pubkey,
+                (chunk_index * MAX_OBLIGATION_RESERVES) as u8,
+                chunk.to_vec(),
+            ));
+        }
+    }
+    Ok(instructions)
+}
+
+/// Build a `RefreshReserve` instruction with the accounts its primary and
+/// secondary oracle sources read
+fn refresh_reserve_instruction(
+    config: &Config,
+    reserve_pubkey: Pubkey,
+    reserve: &Reserve,
+) -> Result<Instruction, Error> {
+    let oracle = &reserve.config.oracle;
+    let mut additional_oracle_pubkeys = vec![];
+    if oracle.source == OracleSource::SwapPoolTwap {
+        additional_oracle_pubkeys.extend(swap_pool_oracle_accounts(
+            config,
+            &reserve.liquidity.oracle_pubkey,
+            &reserve.liquidity.mint_pubkey,
+        )?);
+    }
+    if let Some(secondary) = oracle.secondary {
+        additional_oracle_pubkeys.push(secondary.oracle_pubkey);
+        if secondary.source == OracleSource::SwapPoolTwap {
+            additional_oracle_pubkeys.extend(swap_pool_oracle_accounts(
+                config,
+                &secondary.oracle_pubkey,
+                &reserve.liquidity.mint_pubkey,
+            )?);
+        }
+    }
+    Ok(refresh_reserve_with_oracle_accounts(
+        config.lending_program_id,
+        reserve_pubkey,
+        reserve.liquidity.oracle_pubkey,
+        &additional_oracle_pubkeys,
+    ))
+}
+
+/// Token accounts and quote mint read from a swap pool oracle
+fn swap_pool_oracle_accounts(
+    config: &Config,
+    swap_pool_pubkey: &Pubkey,
+    liquidity_mint_pubkey: &Pubkey,
+) -> Result<[Pubkey; 3], Error> {
+    let swap_pool_account = config.rpc_client.get_account(swap_pool_pubkey)?;
+    let swap_pool = SwapVersion::unpack(&swap_pool_account.data)?;
+    let quote_mint_pubkey = if swap_pool.token_a_mint() == liquidity_mint_pubkey {
+        swap_pool.token_b_mint()
+    } else {
+        swap_pool.token_a_mint()
+    };
+    Ok([
+        *swap_pool.token_a_account(),
+        *swap_pool.token_b_account(),
+        *quote_mint_pubkey,
+    ])
+}
+
+fn reserve_caps_args() -> [Arg<'static, 'static>; 4] {
+    [
+        Arg::with_name("deposit_limit")
+            .long("deposit-limit")
+            .validator(is_amount)
+            .value_name("DECIMAL_AMOUNT")
+            .takes_value(true)
+            .help("Max total supply of the reserve, in liquidity tokens, 0 for no limit"),
+        Arg::with_name("borrow_limit")
+            .long("borrow-limit")
+            .validator(is_amount)
+            .value_name("DECIMAL_AMOUNT")
+            .takes_value(true)
+            .help("Max total borrows of the reserve, in liquidity tokens, 0 for no limit"),
+        Arg::with_name("deposit_value_limit")
+            .long("deposit-value-limit")
+            .validator(is_parsable::<u64>)
+            .value_name("INTEGER_AMOUNT")
+            .takes_value(true)
+            .help("Max market value of the total supply, in the market quote currency, 0 for no limit"),
+        Arg::with_name("borrow_value_limit")
+            .long("borrow-value-limit")
+            .validator(is_parsable::<u64>)
+            .value_name("INTEGER_AMOUNT")
+            .takes_value(true)
+            .help("Max market value of the total borrows, in the market quote currency, 0 for no limit"),
+    ]
+}
+
+/// Reserve caps provided on the command line, with liquidity token limits in
+/// UI amounts
+struct UiReserveCaps {
+    deposit_limit: Option<f64>,
+    borrow_limit: Option<f64>,
+    deposit_value_limit: Option<u64>,
+    borrow_value_limit: Option<u64>,
+}
+
+impl UiReserveCaps {
+    fn of(matches: &ArgMatches<'_>) -> Self {
+        Self {
+            deposit_limit: value_of(matches, "deposit_limit"),
+            borrow_limit: value_of(matches, "borrow_limit"),
+            deposit_value_limit: value_of(matches, "deposit_value_limit"),
+            borrow_value_limit: value_of(matches, "borrow_value_limit"),
+        }
+    }
+
+    /// Replace the caps provided, keeping the others
+    fn apply(&self, caps: &mut ReserveCaps, decimals: u8) {
+        if let Some(deposit_limit) = self.deposit_limit {
+            caps.deposit_limit = ui_amount_to_amount(deposit_limit, decimals);
+        }
+        if let Some(borrow_limit) = self.borrow_limit {
+            caps.borrow_limit = ui_amount_to_amount(borrow_limit, decimals);
+        }
+        if let Some(deposit_value_limit) = self.deposit_value_limit {
+            caps.deposit_value_limit = deposit_value_limit;
+        }
+        if let Some(borrow_value_limit) = self.borrow_value_limit {
+            caps.borrow_value_limit = borrow_value_limit;
+        }
+    }
+}
+
+/// Reserve configuration values provided on the command line, replacing the
+/// current values of the reserve
+struct ReserveConfigUpdate {
+    optimal_utilization_rate: Option<u8>,
+    loan_to_value_ratio: Option<u8>,
+    liquidation_bonus: Option<u8>,
+    max_liquidation_bonus: Option<u8>,
+    liquidation_threshold: Option<u8>,
+    min_borrow_rate: Option<u8>,
+    optimal_borrow_rate: Option<u8>,
+    max_borrow_rate: Option<u8>,
+    borrow_rate_curve: Option<RateCurve>,
+    borrow_fee: Option<f64>,
+    flash_loan_fee: Option<f64>,
+    host_fee_percentage: Option<u8>,
+    protocol_take_rate: Option<u8>,
+    oracle_source: Option<OracleSource>,
+    max_staleness_slots: Option<u32>,
+    max_confidence_bps: Option<u16>,
+    twap_window_slots: Option<u32>,
+    fixed_price: Option<f64>,
+}
+
+impl ReserveConfigUpdate {
+    fn of(matches: &ArgMatches<'_>) -> Self {
+        Self {
+            optimal_utilization_rate: value_of(matches, "optimal_utilization_rate"),
+            loan_to_value_ratio: value_of(matches, "loan_to_value_ratio"),
+            liquidation_bonus: value_of(matches, "liquidation_bonus"),
+            max_liquidation_bonus: value_of(matches, "max_liquidation_bonus"),
+            liquidation_threshold: value_of(matches, "liquidation_threshold"),
+            min_borrow_rate: value_of(matches, "min_borrow_rate"),
+            optimal_borrow_rate: value_of(matches, "optimal_borrow_rate"),
+            max_borrow_rate: value_of(matches, "max_borrow_rate"),
+            borrow_rate_curve: rate_curve_of(matches, "borrow_rate_curve"),
+            borrow_fee: value_of(matches, "borrow_fee"),
+            flash_loan_fee: value_of(matches, "flash_loan_fee"),
+            host_fee_percentage: value_of(matches, "host_fee_percentage"),
+            protocol_take_rate: value_of(matches, "protocol_take_rate"),
+            oracle_source: oracle_source_of(matches, "oracle_source"),
+            max_staleness_slots: value_of(matches, "max_staleness_slots"),
+            max_confidence_bps: value_of(matches, "max_confidence_bps"),
+            twap_window_slots: value_of(matches, "twap_window_slots"),
+            fixed_price: value_of(matches, "fixed_price"),
+        }
+    }
+
+    /// Replace the values provided, keeping the others
+    fn apply(&self, config: &mut ReserveConfig) {
+        fn replace<T: Copy>(value: &mut T, update: Option<T>) {
+            if let Some(update) = update {
+                *value = update;
+            }
+        }
+        replace(
+            &mut config.optimal_utilization_rate,
+            self.optimal_utilization_rate,
+        );
+        replace(&mut config.loan_to_value_ratio, self.loan_to_value_ratio);
+        replace(&mut config.liquidation_bonus, self.liquidation_bonus);
+        replace(
+            &mut config.max_liquidation_bonus,
+            self.max_liquidation_bonus,
+        );
+        replace(
+            &mut config.liquidation_threshold,
+            self.liquidation_threshold,
+        );
+        replace(&mut config.min_borrow_rate, self.min_borrow_rate);
+        replace(&mut config.optimal_borrow_rate, self.optimal_borrow_rate);
+        replace(&mut config.max_borrow_rate, self.max_borrow_rate);
+        if let Some(borrow_rate_curve) = self.borrow_rate_curve {
+            config.interest_rate_model = InterestRateModel::PiecewiseLinear(borrow_rate_curve);
+        }
+        replace(
+            &mut config.fees.borrow_fee_wad,
+            self.borrow_fee.map(|fee| (fee * WAD as f64) as u64),
+        );
+        replace(
+            &mut config.fees.flash_loan_fee_wad,
+            self.flash_loan_fee.map(|fee| (fee * WAD as f64) as u64),
+        );
+        replace(
+            &mut config.fees.host_fee_percentage,
+            self.host_fee_percentage,
+        );
+        replace(&mut config.fees.protocol_take_rate, self.protocol_take_rate);
+        replace(&mut config.oracle.source, self.oracle_source);
+        replace(
+            &mut config.oracle.max_staleness_slots,
+            self.max_staleness_slots,
+        );
+        replace(
+            &mut config.oracle.max_confidence_bps,
+            self.max_confidence_bps,
+        );
+        replace(&mut config.oracle.twap_window_slots, self.twap_window_slots);
+        replace(
+            &mut config.oracle.fixed_price_wads,
+            self.fixed_price.map(|price| (price * WAD as f64) as u128),
+        );
+    }
+}
+
 fn check_fee_payer_balance(config: &Config, required_balance: u64) -> Result<(), Error> {
     let balance = config.rpc_client.get_balance(&config.fee_payer.pubkey())?;
     if balance < required_balance {
@@ -708,6 +3139,115 @@ fn check_fee_payer_balance(config: &Config, required_balance: u64) -> Result<(),
     }
 }
 
+/// Change the config of a reserve, or queue the change in pending reserve
+/// configs while the lending market has a config timelock
+fn send_reserve_config(
+    config: &Config,
+    reserve_pubkey: Pubkey,
+    reserve: &Reserve,
+    reserve_config: ReserveConfig,
+    oracle_pubkeys: Option<(Pubkey, Pubkey)>,
+    pending_reserve_config_pubkey: Option<Pubkey>,
+    lending_market_owner_keypair: Keypair,
+) -> CommandResult {
+    let lending_market_account = config.rpc_client.get_account(&reserve.lending_market)?;
+    let lending_market = LendingMarket::unpack(lending_market_account.data.borrow())?;
+    if lending_market.config_timelock_slots == 0 {
+        let instruction = match oracle_pubkeys {
+            Some((oracle_product_pubkey, oracle_pubkey)) => modify_reserve_config_and_oracle(
+                config.lending_program_id,
+                reserve_config,
+                reserve_pubkey,
+                reserve.lending_market,
+                lending_market_owner_keypair.pubkey(),
+                oracle_product_pubkey,
+                oracle_pubkey,
+            ),
+            None => modify_reserve_config(
+                config.lending_program_id,
+                reserve_config,
+                reserve_pubkey,
+                reserve.lending_market,
+                lending_market_owner_keypair.pubkey(),
+            ),
+        };
+        return send_instructions(
+            config,
+            &[instruction],
+            vec![config.fee_payer.as_ref(), &lending_market_owner_keypair],
+        );
+    }
+
+    let mut instructions = vec![];
+    let pending_reserve_config_keypair = Keypair::new();
+    let pending_reserve_config_pubkey = match pending_reserve_config_pubkey {
+        Some(pending_reserve_config_pubkey) => pending_reserve_config_pubkey,
+        None => {
+            let pending_reserve_config_balance = config
+                .rpc_client
+                .get_minimum_balance_for_rent_exemption(PendingReserveConfig::LEN)?;
+            instructions.push(create_account(
+                &config.fee_payer.pubkey(),
+                &pending_reserve_config_keypair.pubkey(),
+                pending_reserve_config_balance,
+                PendingReserveConfig::LEN as u64,
+                &config.lending_program_id,
+            ));
+            pending_reserve_config_keypair.pubkey()
+        }
+    };
+    println!(
+        "Queuing the config in pending reserve config {} for {} slots, apply it with apply-reserve-config",
+        pending_reserve_config_pubkey, lending_market.config_timelock_slots
+    );
+
+    instructions.push(match oracle_pubkeys {
+        Some((oracle_product_pubkey, oracle_pubkey)) => queue_reserve_config_and_oracle(
+            config.lending_program_id,
+            reserve_config,
+            pending_reserve_config_pubkey,
+            reserve_pubkey,
+            reserve.lending_market,
+            lending_market_owner_keypair.pubkey(),
+            oracle_product_pubkey,
+            oracle_pubkey,
+        ),
+        None => queue_reserve_config(
+            config.lending_program_id,
+            reserve_config,
+            pending_reserve_config_pubkey,
+            reserve_pubkey,
+            reserve.lending_market,
+            lending_market_owner_keypair.pubkey(),
+        ),
+    });
+    let mut signers: Vec<&dyn Signer> =
+        vec![config.fee_payer.as_ref(), &lending_market_owner_keypair];
+    if instructions.len() > 1 {
+        signers.push(&pending_reserve_config_keypair);
+    }
+    send_instructions(config, &instructions, signers)
+}
+
+/// Send the instructions in a single transaction paid by the fee payer
+fn send_instructions(
+    config: &Config,
+    instructions: &[Instruction],
+    signers: Vec<&dyn Signer>,
+) -> CommandResult {
+    let recent_blockhash = config.rpc_client.get_latest_blockhash()?;
+    let message = Message::new_with_blockhash(
+        instructions,
+        Some(&config.fee_payer.pubkey()),
+        &recent_blockhash,
+    );
+
+    check_fee_payer_balance(config, config.rpc_client.get_fee_for_message(&message)?)?;
+    let transaction = Transaction::new(&signers, message, recent_blockhash);
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
 fn send_transaction(
     config: &Config,
     transaction: Transaction,
@@ -739,3 +3279,46 @@ fn quote_currency_of(matches: &ArgMatches<'_>, name: &str) -> Option<[u8; 32]> {
         None
     }
 }
+
+/// UI amount of an `is_amount_or_all` argument, None for ALL
+fn ui_amount_or_all_of(matches: &ArgMatches<'_>, name: &str) -> Option<f64> {
+    match matches.value_of(name)? {
+        "ALL" => None,
+        value => value.parse().ok(),
+    }
+}
+
+fn is_rate_curve(string: String) -> Result<(), String> {
+    parse_rate_curve(&string)
+        .map(|_| ())
+        .ok_or_else(|| format!("Unable to parse borrow rate curve, provided: {}", string))
+}
+
+fn parse_rate_curve(value: &str) -> Option<RateCurve> {
+    let points = value
+        .split(',')
+        .map(|point| {
+            let (utilization_bps, borrow_rate_bps) = point.split_once(':')?;
+            Some(RateCurvePoint {
+                utilization_bps: utilization_bps.trim().parse().ok()?,
+                borrow_rate_bps: borrow_rate_bps.trim().parse().ok()?,
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let curve = RateCurve::new(&points).ok()?;
+    curve.validate().ok()?;
+    Some(curve)
+}
+
+fn rate_curve_of(matches: &ArgMatches<'_>, name: &str) -> Option<RateCurve> {
+    matches.value_of(name).and_then(parse_rate_curve)
+}
+
+fn oracle_source_of(matches: &ArgMatches<'_>, name: &str) -> Option<OracleSource> {
+    match matches.value_of(name)? {
+        "pyth" => Some(OracleSource::Pyth),
+        "swap-pool-twap" => Some(OracleSource::SwapPoolTwap),
+        "fixed-price" => Some(OracleSource::FixedPrice),
+        _ => None,
+    }
+}
@@ -0,0 +1,379 @@
+use {
+    serde::{Deserialize, Serialize},
+    solana_cli_output::{QuietDisplay, VerboseDisplay},
+    solana_sdk::pubkey::Pubkey,
+    spl_token_lending::state::{
+        LendingMarket, Obligation, ObligationCollateral, ObligationLiquidity, Reserve,
+        ReserveConfig,
+    },
+    std::fmt::{Display, Formatter, Result},
+};
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CliLendingMarket {
+    pub address: String,
+    pub owner: String,
+    pub quote_currency: String,
+    pub token_program_id: String,
+    pub oracle_program_id: String,
+    pub liquidation_close_factor: u8,
+    pub guardian: Option<String>,
+    pub config_timelock_slots: u64,
+    pub reserves: Vec<String>,
+}
+
+impl Display for CliLendingMarket {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        writeln!(f, "Lending market: {}", self.address)?;
+        writeln!(f, "Owner: {}", self.owner)?;
+        writeln!(f, "Quote currency: {}", self.quote_currency)?;
+        writeln!(f, "Token program: {}", self.token_program_id)?;
+        writeln!(f, "Oracle program: {}", self.oracle_program_id)?;
+        writeln!(
+            f,
+            "Liquidation close factor: {}%",
+            self.liquidation_close_factor
+        )?;
+        if let Some(guardian) = &self.guardian {
+            writeln!(f, "Guardian: {}", guardian)?;
+        }
+        writeln!(f, "Config timelock: {} slots", self.config_timelock_slots)?;
+        writeln!(f, "Reserves: {}", self.reserves.len())?;
+        for reserve in &self.reserves {
+            writeln!(f, "  {}", reserve)?;
+        }
+        Ok(())
+    }
+}
+
+impl QuietDisplay for CliLendingMarket {}
+impl VerboseDisplay for CliLendingMarket {}
+
+impl CliLendingMarket {
+    pub fn new(address: Pubkey, lending_market: LendingMarket, reserves: Vec<Pubkey>) -> Self {
+        // quote currencies are either null padded strings or mint addresses
+        let quote_currency = &lending_market.quote_currency;
+        let quote_currency = if quote_currency
+            .iter()
+            .all(|byte| *byte == 0 || byte.is_ascii_graphic())
+        {
+            String::from_utf8_lossy(quote_currency)
+                .trim_end_matches('\0')
+                .to_string()
+        } else {
+            Pubkey::new_from_array(*quote_currency).to_string()
+        };
+        Self {
+            address: address.to_string(),
+            owner: lending_market.owner.to_string(),
+            quote_currency,
+            token_program_id: lending_market.token_program_id.to_string(),
+            oracle_program_id: lending_market.oracle_program_id.to_string(),
+            liquidation_close_factor: lending_market.liquidation_close_factor,
+            guardian: (lending_market.guardian != Pubkey::default())
+                .then(|| lending_market.guardian.to_string()),
+            config_timelock_slots: lending_market.config_timelock_slots,
+            reserves: reserves.iter().map(Pubkey::to_string).collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CliReserve {
+    pub address: String,
+    pub lending_market: String,
+    pub last_update_slot: u64,
+    pub stale: bool,
+    pub liquidity: CliReserveLiquidity,
+    pub collateral: CliReserveCollateral,
+    pub config: CliReserveConfig,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CliReserveLiquidity {
+    pub mint: String,
+    pub mint_decimals: u8,
+    pub token_program_id: String,
+    pub supply: String,
+    pub fee_receiver: String,
+    pub oracle: String,
+    pub available_amount: u64,
+    pub borrowed_amount: String,
+    pub cumulative_borrow_rate: String,
+    pub market_price: String,
+    pub accumulated_protocol_fees: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CliReserveCollateral {
+    pub mint: String,
+    pub mint_total_supply: u64,
+    pub supply: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CliReserveConfig {
+    pub optimal_utilization_rate: u8,
+    pub loan_to_value_ratio: u8,
+    pub liquidation_bonus: u8,
+    pub max_liquidation_bonus: u8,
+    pub liquidation_threshold: u8,
+    pub min_borrow_rate: u8,
+    pub optimal_borrow_rate: u8,
+    pub max_borrow_rate: u8,
+    pub interest_rate_model: String,
+    pub oracle_source: String,
+    pub max_staleness_slots: u32,
+    pub max_confidence_bps: u16,
+    pub twap_window_slots: u32,
+    pub fixed_price_wads: u128,
+    pub secondary_oracle: Option<String>,
+    pub deposit_limit: u64,
+    pub borrow_limit: u64,
+    pub deposit_value_limit: u64,
+    pub borrow_value_limit: u64,
+    pub isolated_collateral: bool,
+    pub elevation_group: u8,
+    pub borrow_fee_wad: u64,
+    pub flash_loan_fee_wad: u64,
+    pub host_fee_percentage: u8,
+    pub protocol_take_rate: u8,
+}
+
+impl Display for CliReserve {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let liquidity = &self.liquidity;
+        let collateral = &self.collateral;
+        let config = &self.config;
+        writeln!(f, "Reserve: {}", self.address)?;
+        writeln!(f, "Lending market: {}", self.lending_market)?;
+        writeln!(
+            f,
+            "Last update slot: {}{}",
+            self.last_update_slot,
+            if self.stale { " (stale)" } else { "" }
+        )?;
+        writeln!(f, "Liquidity mint: {}", liquidity.mint)?;
+        writeln!(f, "Liquidity supply: {}", liquidity.supply)?;
+        writeln!(f, "Oracle: {}", liquidity.oracle)?;
+        writeln!(f, "Market price: {}", liquidity.market_price)?;
+        writeln!(f, "Available amount: {}", liquidity.available_amount)?;
+        writeln!(f, "Borrowed amount: {}", liquidity.borrowed_amount)?;
+        writeln!(
+            f,
+            "Accumulated protocol fees: {}",
+            liquidity.accumulated_protocol_fees
+        )?;
+        writeln!(f, "Collateral mint: {}", collateral.mint)?;
+        writeln!(f, "Collateral supply: {}", collateral.supply)?;
+        writeln!(
+            f,
+            "Collateral mint total supply: {}",
+            collateral.mint_total_supply
+        )?;
+        writeln!(f, "Loan to value ratio: {}%", config.loan_to_value_ratio)?;
+        writeln!(
+            f,
+            "Liquidation threshold: {}%",
+            config.liquidation_threshold
+        )?;
+        writeln!(f, "Liquidation bonus: {}%", config.liquidation_bonus)?;
+        writeln!(f, "Interest rate model: {}", config.interest_rate_model)?;
+        writeln!(f, "Oracle source: {}", config.oracle_source)?;
+        writeln!(
+            f,
+            "Deposit limit: {}, borrow limit: {}",
+            config.deposit_limit, config.borrow_limit
+        )?;
+        Ok(())
+    }
+}
+
+impl QuietDisplay for CliReserve {}
+impl VerboseDisplay for CliReserve {}
+
+impl CliReserve {
+    pub fn new(address: Pubkey, reserve: Reserve, slot: u64) -> Self {
+        let liquidity = &reserve.liquidity;
+        let collateral = &reserve.collateral;
+        Self {
+            address: address.to_string(),
+            lending_market: reserve.lending_market.to_string(),
+            last_update_slot: reserve.last_update.slot,
+            stale: reserve.last_update.is_stale(slot).unwrap_or(true),
+            liquidity: CliReserveLiquidity {
+                mint: liquidity.mint_pubkey.to_string(),
+                mint_decimals: liquidity.mint_decimals,
+                token_program_id: liquidity.token_program_id.to_string(),
+                supply: liquidity.supply_pubkey.to_string(),
+                fee_receiver: liquidity.fee_receiver.to_string(),
+                oracle: liquidity.oracle_pubkey.to_string(),
+                available_amount: liquidity.available_amount,
+                borrowed_amount: liquidity.borrowed_amount_wads.to_string(),
+                cumulative_borrow_rate: liquidity.cumulative_borrow_rate_wads.to_string(),
+                market_price: liquidity.market_price.to_string(),
+                accumulated_protocol_fees: liquidity.accumulated_protocol_fees_wads.to_string(),
+            },
+            collateral: CliReserveCollateral {
+                mint: collateral.mint_pubkey.to_string(),
+                mint_total_supply: collateral.mint_total_supply,
+                supply: collateral.supply_pubkey.to_string(),
+            },
+            config: reserve.config.into(),
+        }
+    }
+}
+
+impl From<ReserveConfig> for CliReserveConfig {
+    fn from(config: ReserveConfig) -> Self {
+        Self {
+            optimal_utilization_rate: config.optimal_utilization_rate,
+            loan_to_value_ratio: config.loan_to_value_ratio,
+            liquidation_bonus: config.liquidation_bonus,
+            max_liquidation_bonus: config.max_liquidation_bonus,
+            liquidation_threshold: config.liquidation_threshold,
+            min_borrow_rate: config.min_borrow_rate,
+            optimal_borrow_rate: config.optimal_borrow_rate,
+            max_borrow_rate: config.max_borrow_rate,
+            interest_rate_model: format!("{:?}", config.interest_rate_model),
+            oracle_source: format!("{:?}", config.oracle.source),
+            max_staleness_slots: config.oracle.max_staleness_slots,
+            max_confidence_bps: config.oracle.max_confidence_bps,
+            twap_window_slots: config.oracle.twap_window_slots,
+            fixed_price_wads: config.oracle.fixed_price_wads,
+            secondary_oracle: config
+                .oracle
+                .secondary
+                .map(|secondary| secondary.oracle_pubkey.to_string()),
+            deposit_limit: config.caps.deposit_limit,
+            borrow_limit: config.caps.borrow_limit,
+            deposit_value_limit: config.caps.deposit_value_limit,
+            borrow_value_limit: config.caps.borrow_value_limit,
+            isolated_collateral: config.isolated_collateral,
+            elevation_group: config.elevation_group.id,
+            borrow_fee_wad: config.fees.borrow_fee_wad,
+            flash_loan_fee_wad: config.fees.flash_loan_fee_wad,
+            host_fee_percentage: config.fees.host_fee_percentage,
+            protocol_take_rate: config.fees.protocol_take_rate,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CliObligation {
+    pub address: String,
+    pub lending_market: String,
+    pub owner: String,
+    pub last_update_slot: u64,
+    pub stale: bool,
+    pub deposits: Vec<CliObligationCollateral>,
+    pub borrows: Vec<CliObligationLiquidity>,
+    pub deposited_value: String,
+    pub borrowed_value: String,
+    pub allowed_borrow_value: String,
+    pub unhealthy_borrow_value: String,
+    pub elevation_group: u8,
+    pub isolated_collateral: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CliObligationCollateral {
+    pub deposit_reserve: String,
+    pub deposited_amount: u64,
+    pub market_value: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CliObligationLiquidity {
+    pub borrow_reserve: String,
+    pub borrowed_amount: String,
+    pub cumulative_borrow_rate: String,
+    pub market_value: String,
+}
+
+impl Display for CliObligation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        writeln!(f, "Obligation: {}", self.address)?;
+        writeln!(f, "Lending market: {}", self.lending_market)?;
+        writeln!(f, "Owner: {}", self.owner)?;
+        writeln!(
+            f,
+            "Last update slot: {}{}",
+            self.last_update_slot,
+            if self.stale { " (stale)" } else { "" }
+        )?;
+        writeln!(f, "Deposits: {}", self.deposits.len())?;
+        for collateral in &self.deposits {
+            writeln!(
+                f,
+                "  Reserve: {}\tAmount: {}\tValue: {}",
+                collateral.deposit_reserve, collateral.deposited_amount, collateral.market_value
+            )?;
+        }
+        writeln!(f, "Borrows: {}", self.borrows.len())?;
+        for liquidity in &self.borrows {
+            writeln!(
+                f,
+                "  Reserve: {}\tAmount: {}\tValue: {}",
+                liquidity.borrow_reserve, liquidity.borrowed_amount, liquidity.market_value
+            )?;
+        }
+        writeln!(f, "Deposited value: {}", self.deposited_value)?;
+        writeln!(f, "Borrowed value: {}", self.borrowed_value)?;
+        writeln!(f, "Allowed borrow value: {}", self.allowed_borrow_value)?;
+        writeln!(f, "Unhealthy borrow value: {}", self.unhealthy_borrow_value)?;
+        Ok(())
+    }
+}
+
+impl QuietDisplay for CliObligation {}
+impl VerboseDisplay for CliObligation {}
+
+impl CliObligation {
+    pub fn new(address: Pubkey, obligation: Obligation, slot: u64) -> Self {
+        Self {
+            address: address.to_string(),
+            lending_market: obligation.lending_market.to_string(),
+            owner: obligation.owner.to_string(),
+            last_update_slot: obligation.last_update.slot,
+            stale: obligation.last_update.is_stale(slot).unwrap_or(true),
+            deposits: obligation.deposits.into_iter().map(Into::into).collect(),
+            borrows: obligation.borrows.into_iter().map(Into::into).collect(),
+            deposited_value: obligation.deposited_value.to_string(),
+            borrowed_value: obligation.borrowed_value.to_string(),
+            allowed_borrow_value: obligation.allowed_borrow_value.to_string(),
+            unhealthy_borrow_value: obligation.unhealthy_borrow_value.to_string(),
+            elevation_group: obligation.elevation_group,
+            isolated_collateral: obligation.isolated_collateral,
+        }
+    }
+}
+
+impl From<ObligationCollateral> for CliObligationCollateral {
+    fn from(collateral: ObligationCollateral) -> Self {
+        Self {
+            deposit_reserve: collateral.deposit_reserve.to_string(),
+            deposited_amount: collateral.deposited_amount,
+            market_value: collateral.market_value.to_string(),
+        }
+    }
+}
+
+impl From<ObligationLiquidity> for CliObligationLiquidity {
+    fn from(liquidity: ObligationLiquidity) -> Self {
+        Self {
+            borrow_reserve: liquidity.borrow_reserve.to_string(),
+            borrowed_amount: liquidity.borrowed_amount_wads.to_string(),
+            cumulative_borrow_rate: liquidity.cumulative_borrow_rate_wads.to_string(),
+            market_value: liquidity.market_value.to_string(),
+        }
+    }
+}
@@ -0,0 +1,16 @@
+#![deny(missing_docs)]
+
+//! Liquidator and obligation health monitor for the SPL Token Lending program
+//!
+//! Obligations are refreshed off-chain with the same `Reserve` and
+//! `Obligation` math the program uses, at the current prices of the reserve
+//! oracles, to find unhealthy obligations and plan their liquidation.
+//! Snapshots are built from plain accounts, so the library works the same
+//! against an RPC node or `solana-program-test`.
+
+pub mod liquidation;
+pub mod market;
+pub mod rpc;
+
+/// Errors loading a market or planning a liquidation
+pub type Error = Box<dyn std::error::Error>;
@@ -0,0 +1,249 @@
+//! Liquidation planning and instructions
+
+use {
+    crate::{market::MarketSnapshot, Error},
+    solana_program::{instruction::Instruction, pubkey::Pubkey},
+    spl_token_lending::{
+        instruction::{
+            liquidate_obligation, refresh_obligation, refresh_obligation_chunk,
+            refresh_reserve_with_oracle_accounts,
+        },
+        state::{Reserve, LIQUIDATION_CLOSE_AMOUNT, MAX_OBLIGATION_RESERVES},
+    },
+};
+
+/// Liquidation of an unhealthy obligation, repaying its largest borrow to
+/// withdraw its largest deposit
+#[derive(Clone, Debug, PartialEq)]
+pub struct LiquidationPlan {
+    /// Obligation address
+    pub obligation_pubkey: Pubkey,
+    /// Reserve of the borrowed liquidity to repay
+    pub repay_reserve_pubkey: Pubkey,
+    /// Reserve of the deposited collateral to withdraw
+    pub withdraw_reserve_pubkey: Pubkey,
+    /// Most liquidity the lending market close factor allows to repay
+    pub liquidity_amount: u64,
+}
+
+/// Token accounts of the liquidator
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LiquidatorAccounts {
+    /// Token account repaying liquidity, minted by the repay reserve
+    /// liquidity mint
+    pub source_liquidity_pubkey: Pubkey,
+    /// Token account receiving collateral, minted by the withdraw reserve
+    /// collateral mint
+    pub destination_collateral_pubkey: Pubkey,
+    /// Authority which can transfer from the source liquidity account
+    pub user_transfer_authority_pubkey: Pubkey,
+}
+
+impl MarketSnapshot {
+    /// Plan the liquidation of a refreshed obligation, None while it is
+    /// healthy
+    pub fn plan_liquidation(
+        &self,
+        obligation_pubkey: &Pubkey,
+    ) -> Result<Option<LiquidationPlan>, Error> {
+        let obligation = &self.obligation(obligation_pubkey)?.obligation;
+        if obligation.borrows.is_empty()
+            || obligation.deposits.is_empty()
+            || obligation.borrowed_value < obligation.unhealthy_borrow_value
+        {
+            return Ok(None);
+        }
+
+        let liquidity = obligation
+            .borrows
+            .iter()
+            .max_by_key(|liquidity| liquidity.market_value)
+            .unwrap();
+        let collateral = obligation
+            .deposits
+            .iter()
+            .max_by_key(|collateral| collateral.market_value)
+            .unwrap();
+
+        let liquidity_amount =
+            if liquidity.borrowed_amount_wads < LIQUIDATION_CLOSE_AMOUNT.into() {
+                liquidity.borrowed_amount_wads
+            } else {
+                obligation.max_liquidation_amount(
+                    liquidity,
+                    self.lending_market.liquidation_close_factor,
+                )?
+            }
+            .try_ceil_u64()?;
+
+        Ok(Some(LiquidationPlan {
+            obligation_pubkey: *obligation_pubkey,
+            repay_reserve_pubkey: liquidity.borrow_reserve,
+            withdraw_reserve_pubkey: collateral.deposit_reserve,
+            liquidity_amount,
+        }))
+    }
+
+    /// Instructions refreshing an obligation and every reserve it uses, in
+    /// chunks for extended obligations with more deposits and borrows than
+    /// fit in a single refresh
+    pub fn refresh_obligation_instructions(
+        &self,
+        obligation_pubkey: &Pubkey,
+    ) -> Result<Vec<Instruction>, Error> {
+        let obligation = &self.obligation(obligation_pubkey)?.obligation;
+        let position_reserves: Vec<Pubkey> = obligation
+            .deposits
+            .iter()
+            .map(|collateral| collateral.deposit_reserve)
+            .chain(
+                obligation
+                    .borrows
+                    .iter()
+                    .map(|liquidity| liquidity.borrow_reserve),
+            )
+            .collect();
+
+        let mut instructions = vec![];
+        let mut refreshed_reserves = vec![];
+        for reserve_pubkey in &position_reserves {
+            if !refreshed_reserves.contains(reserve_pubkey) {
+                instructions.push(self.refresh_reserve_instruction(reserve_pubkey)?);
+                refreshed_reserves.push(*reserve_pubkey);
+            }
+        }
+
+        if position_reserves.len() <= MAX_OBLIGATION_RESERVES {
+            instructions.push(refresh_obligation(
+                self.program_id,
+                *obligation_pubkey,
+                position_reserves,
+            ));
+        } else {
+            for (chunk_index, chunk) in position_reserves
+                .chunks(MAX_OBLIGATION_RESERVES)
+                .enumerate()
+            {
+                instructions.push(refresh_obligation_chunk(
+                    self.program_id,
+                    *obligation_pubkey,
+                    (chunk_index * MAX_OBLIGATION_RESERVES) as u8,
+                    chunk.to_vec(),
+                ));
+            }
+        }
+        Ok(instructions)
+    }
+
+    /// Instructions refreshing the obligation and its reserves, then
+    /// liquidating it as planned
+    pub fn liquidation_instructions(
+        &self,
+        plan: &LiquidationPlan,
+        accounts: &LiquidatorAccounts,
+    ) -> Result<Vec<Instruction>, Error> {
+        let repay_reserve = self.reserve(&plan.repay_reserve_pubkey)?;
+        let withdraw_reserve = self.reserve(&plan.withdraw_reserve_pubkey)?;
+
+        let mut instructions = self.refresh_obligation_instructions(&plan.obligation_pubkey)?;
+        instructions.push(liquidate_obligation(
+            self.program_id,
+            plan.liquidity_amount,
+            accounts.source_liquidity_pubkey,
+            accounts.destination_collateral_pubkey,
+            plan.repay_reserve_pubkey,
+            repay_reserve.liquidity.supply_pubkey,
+            plan.withdraw_reserve_pubkey,
+            withdraw_reserve.collateral.supply_pubkey,
+            plan.obligation_pubkey,
+            self.lending_market_pubkey,
+            accounts.user_transfer_authority_pubkey,
+            repay_reserve.liquidity.mint_pubkey,
+            repay_reserve.liquidity.token_program_id,
+        ));
+        Ok(instructions)
+    }
+
+    fn refresh_reserve_instruction(&self, reserve_pubkey: &Pubkey) -> Result<Instruction, Error> {
+        let reserve = self.reserve(reserve_pubkey)?;
+        Ok(refresh_reserve_with_oracle_accounts(
+            self.program_id,
+            *reserve_pubkey,
+            reserve.liquidity.oracle_pubkey,
+            self.additional_oracle_accounts
+                .get(reserve_pubkey)
+                .map_or(&[], Vec::as_slice),
+        ))
+    }
+
+    fn reserve(&self, reserve_pubkey: &Pubkey) -> Result<&Reserve, Error> {
+        self.reserves
+            .get(reserve_pubkey)
+            .ok_or_else(|| format!("Reserve {} not found", reserve_pubkey).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::market::ObligationAccount,
+        spl_token_lending::{
+            instruction::LendingInstruction,
+            state::{
+                LendingMarket, Obligation, ObligationCollateral, ObligationLiquidity,
+                EXTENDED_OBLIGATION_LEN,
+            },
+        },
+        std::collections::BTreeMap,
+    };
+
+    #[test]
+    fn refresh_extended_obligation_in_chunks() {
+        let reserve_pubkeys: Vec<Pubkey> = (0..12).map(|_| Pubkey::new_unique()).collect();
+        let obligation_pubkey = Pubkey::new_unique();
+        let obligation = Obligation {
+            deposits: reserve_pubkeys[..6]
+                .iter()
+                .map(|pubkey| ObligationCollateral::new(*pubkey))
+                .collect(),
+            borrows: reserve_pubkeys[6..]
+                .iter()
+                .map(|pubkey| ObligationLiquidity::new(*pubkey))
+                .collect(),
+            ..Obligation::default()
+        };
+        let snapshot = MarketSnapshot {
+            program_id: spl_token_lending::id(),
+            lending_market_pubkey: Pubkey::new_unique(),
+            lending_market: LendingMarket::default(),
+            reserves: reserve_pubkeys
+                .iter()
+                .map(|pubkey| (*pubkey, Reserve::default()))
+                .collect(),
+            obligations: BTreeMap::from([(
+                obligation_pubkey,
+                ObligationAccount {
+                    obligation,
+                    data_len: EXTENDED_OBLIGATION_LEN,
+                },
+            )]),
+            additional_oracle_accounts: BTreeMap::new(),
+            oracle_accounts: BTreeMap::new(),
+        };
+
+        let instructions = snapshot
+            .refresh_obligation_instructions(&obligation_pubkey)
+            .unwrap();
+        assert_eq!(instructions.len(), reserve_pubkeys.len() + 2);
+        assert_eq!(
+            LendingInstruction::unpack(&instructions[12].data).unwrap(),
+            LendingInstruction::RefreshObligationChunk { start_index: 0 }
+        );
+        assert_eq!(
+            LendingInstruction::unpack(&instructions[13].data).unwrap(),
+            LendingInstruction::RefreshObligationChunk { start_index: 10 }
+        );
+        assert_eq!(instructions[13].accounts.len(), 4);
+    }
+}
@@ -0,0 +1,378 @@
+#![allow(clippy::arithmetic_side_effects)]
+use {
+    clap::{
+        crate_description, crate_name, crate_version, value_t, App, AppSettings, Arg, SubCommand,
+    },
+    solana_clap_utils::{
+        fee_payer::fee_payer_arg,
+        input_parsers::{pubkey_of, value_of},
+        input_validators::{is_parsable, is_pubkey, is_url},
+        keypair::signer_from_path,
+    },
+    solana_client::rpc_client::RpcClient,
+    solana_program::{native_token::lamports_to_sol, pubkey::Pubkey},
+    solana_sdk::{
+        commitment_config::CommitmentConfig, message::Message, signature::Signer,
+        transaction::Transaction,
+    },
+    spl_associated_token_account_client::{
+        address::get_associated_token_address_with_program_id,
+        instruction::create_associated_token_account_idempotent,
+    },
+    spl_token_2022::{extension::StateWithExtensions, state::Account as Token},
+    spl_token_lending::{self, math::Decimal},
+    spl_token_lending_liquidator::{
+        liquidation::LiquidatorAccounts,
+        market::{MarketSnapshot, ObligationHealth},
+        rpc::load_market,
+    },
+    std::process::exit,
+};
+
+struct Config {
+    rpc_client: RpcClient,
+    fee_payer: Box<dyn Signer>,
+    lending_program_id: Pubkey,
+    lending_market_pubkey: Pubkey,
+    verbose: bool,
+    dry_run: bool,
+}
+
+type Error = Box<dyn std::error::Error>;
+type CommandResult = Result<(), Error>;
+
+fn main() {
+    solana_logger::setup_with_default("solana=info");
+
+    let default_lending_program_id: &str = &spl_token_lending::id().to_string();
+
+    let matches = App::new(crate_name!())
+        .about(crate_description!())
+        .version(crate_version!())
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg({
+            let arg = Arg::with_name("config_file")
+                .short("C")
+                .long("config")
+                .value_name("PATH")
+                .takes_value(true)
+                .global(true)
+                .help("Configuration file to use");
+            if let Some(ref config_file) = *solana_cli_config::CONFIG_FILE {
+                arg.default_value(config_file)
+            } else {
+                arg
+            }
+        })
+        .arg(
+            Arg::with_name("json_rpc_url")
+                .long("url")
+                .value_name("URL")
+                .takes_value(true)
+                .validator(is_url)
+                .help("JSON RPC URL for the cluster.  Default from the configuration file."),
+        )
+        .arg(
+            fee_payer_arg()
+                .short("p")
+                .global(true)
+                .help("Liquidator wallet, paying fees and repaying borrows. Defaults to the client keypair."),
+        )
+        .arg(
+            Arg::with_name("lending_program_id")
+                .long("program")
+                .validator(is_pubkey)
+                .value_name("PUBKEY")
+                .takes_value(true)
+                .required(true)
+                .default_value(default_lending_program_id)
+                .help("Lending program ID"),
+        )
+        .arg(
+            Arg::with_name("lending_market")
+                .long("market")
+                .validator(is_pubkey)
+                .value_name("PUBKEY")
+                .takes_value(true)
+                .required(true)
+                .global(true)
+                .help("Lending market address"),
+        )
+        .arg(
+            Arg::with_name("verbose")
+                .long("verbose")
+                .short("v")
+                .takes_value(false)
+                .global(true)
+                .help("Show additional information"),
+        )
+        .arg(
+            Arg::with_name("dry_run")
+                .long("dry-run")
+                .takes_value(false)
+                .global(true)
+                .help("Simulate transaction instead of executing"),
+        )
+        .subcommand(
+            SubCommand::with_name("report")
+                .about("Report obligations at risk of liquidation")
+                .arg(
+                    Arg::with_name("health_threshold")
+                        .long("health-threshold")
+                        .validator(is_parsable::<f64>)
+                        .value_name("RATIO")
+                        .takes_value(true)
+                        .default_value("1.1")
+                        .help("Report obligations whose unhealthy borrow value is less than this multiple of their borrowed value"),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("liquidate")
+                .about("Liquidate unhealthy obligations from the liquidator wallet token accounts")
+                .arg(
+                    Arg::with_name("obligation")
+                        .long("obligation")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .help("Obligation to liquidate. Defaults to every unhealthy obligation of the market"),
+                )
+        )
+        .get_matches();
+
+    let mut wallet_manager = None;
+    let config = {
+        let cli_config = if let Some(config_file) = matches.value_of("config_file") {
+            solana_cli_config::Config::load(config_file).unwrap_or_default()
+        } else {
+            solana_cli_config::Config::default()
+        };
+        let json_rpc_url = value_t!(matches, "json_rpc_url", String)
+            .unwrap_or_else(|_| cli_config.json_rpc_url.clone());
+
+        let fee_payer = signer_from_path(
+            &matches,
+            matches
+                .value_of("fee_payer")
+                .unwrap_or(&cli_config.keypair_path),
+            "fee_payer",
+            &mut wallet_manager,
+        )
+        .unwrap_or_else(|e| {
+            eprintln!("error: {}", e);
+            exit(1);
+        });
+
+        let lending_program_id = pubkey_of(&matches, "lending_program_id").unwrap();
+        let lending_market_pubkey = pubkey_of(&matches, "lending_market").unwrap();
+        let verbose = matches.is_present("verbose");
+        let dry_run = matches.is_present("dry_run");
+
+        Config {
+            rpc_client: RpcClient::new_with_commitment(json_rpc_url, CommitmentConfig::confirmed()),
+            fee_payer,
+            lending_program_id,
+            lending_market_pubkey,
+            verbose,
+            dry_run,
+        }
+    };
+
+    let _ = match matches.subcommand() {
+        ("report", Some(arg_matches)) => {
+            let health_threshold = value_of::<f64>(arg_matches, "health_threshold").unwrap();
+            command_report(&config, health_threshold)
+        }
+        ("liquidate", Some(arg_matches)) => {
+            let obligation_pubkey = pubkey_of(arg_matches, "obligation");
+            command_liquidate(&config, obligation_pubkey)
+        }
+        _ => unreachable!(),
+    }
+    .map_err(|err| {
+        eprintln!("{}", err);
+        exit(1);
+    });
+}
+
+// COMMANDS
+
+fn command_report(config: &Config, health_threshold: f64) -> CommandResult {
+    let snapshot = load_refreshed_market(config)?;
+    let health_threshold = Decimal::from_scaled_val((health_threshold * 1e18) as u128);
+
+    let at_risk: Vec<_> = snapshot
+        .health()
+        .into_iter()
+        .filter(|health| config.verbose || health.is_at_risk(health_threshold))
+        .collect();
+    if at_risk.is_empty() {
+        println!("No obligations at risk");
+    }
+    for health in &at_risk {
+        print_health(health);
+    }
+    Ok(())
+}
+
+fn command_liquidate(config: &Config, obligation_pubkey: Option<Pubkey>) -> CommandResult {
+    let snapshot = load_refreshed_market(config)?;
+    let obligation_pubkeys = match obligation_pubkey {
+        Some(obligation_pubkey) => vec![obligation_pubkey],
+        None => snapshot
+            .health()
+            .into_iter()
+            .filter(ObligationHealth::is_liquidatable)
+            .map(|health| health.obligation_pubkey)
+            .collect(),
+    };
+    if obligation_pubkeys.is_empty() {
+        println!("No obligations to liquidate");
+    }
+
+    for obligation_pubkey in obligation_pubkeys {
+        let Some(mut plan) = snapshot.plan_liquidation(&obligation_pubkey)? else {
+            println!("Obligation {} is healthy", obligation_pubkey);
+            continue;
+        };
+        let repay_reserve = &snapshot.reserves[&plan.repay_reserve_pubkey];
+        let withdraw_reserve = &snapshot.reserves[&plan.withdraw_reserve_pubkey];
+
+        let liquidator_pubkey = config.fee_payer.pubkey();
+        let accounts = LiquidatorAccounts {
+            source_liquidity_pubkey: get_associated_token_address_with_program_id(
+                &liquidator_pubkey,
+                &repay_reserve.liquidity.mint_pubkey,
+                &repay_reserve.liquidity.token_program_id,
+            ),
+            destination_collateral_pubkey: get_associated_token_address_with_program_id(
+                &liquidator_pubkey,
+                &withdraw_reserve.collateral.mint_pubkey,
+                &snapshot.lending_market.token_program_id,
+            ),
+            user_transfer_authority_pubkey: liquidator_pubkey,
+        };
+
+        let source_liquidity_amount = token_amount(config, &accounts.source_liquidity_pubkey)?;
+        if source_liquidity_amount == 0 {
+            println!(
+                "Skipping obligation {}: liquidator holds no liquidity of mint {} in {}",
+                obligation_pubkey,
+                repay_reserve.liquidity.mint_pubkey,
+                accounts.source_liquidity_pubkey
+            );
+            continue;
+        }
+        plan.liquidity_amount = plan.liquidity_amount.min(source_liquidity_amount);
+
+        if config.verbose {
+            println!(
+                "Liquidating obligation {}: repaying {} of reserve {} for collateral of reserve {}",
+                obligation_pubkey,
+                plan.liquidity_amount,
+                plan.repay_reserve_pubkey,
+                plan.withdraw_reserve_pubkey
+            );
+        }
+
+        let mut instructions = vec![create_associated_token_account_idempotent(
+            &liquidator_pubkey,
+            &liquidator_pubkey,
+            &withdraw_reserve.collateral.mint_pubkey,
+            &snapshot.lending_market.token_program_id,
+        )];
+        instructions.extend(snapshot.liquidation_instructions(&plan, &accounts)?);
+
+        let recent_blockhash = config.rpc_client.get_latest_blockhash()?;
+        let message =
+            Message::new_with_blockhash(&instructions, Some(&liquidator_pubkey), &recent_blockhash);
+        check_fee_payer_balance(config, config.rpc_client.get_fee_for_message(&message)?)?;
+        let transaction =
+            Transaction::new(&vec![config.fee_payer.as_ref()], message, recent_blockhash);
+        send_transaction(config, transaction)?;
+    }
+    Ok(())
+}
+
+// HELPERS
+
+fn load_refreshed_market(config: &Config) -> Result<MarketSnapshot, Error> {
+    let mut snapshot = load_market(
+        &config.rpc_client,
+        &config.lending_program_id,
+        &config.lending_market_pubkey,
+    )?;
+    let slot = config.rpc_client.get_slot()?;
+    snapshot.refresh(slot)?;
+    if config.verbose {
+        println!(
+            "Loaded {} reserves and {} obligations at slot {}",
+            snapshot.reserves.len(),
+            snapshot.obligations.len(),
+            slot
+        );
+    }
+    Ok(snapshot)
+}
+
+fn print_health(health: &ObligationHealth) {
+    println!("Obligation: {}", health.obligation_pubkey);
+    println!("  Owner: {}", health.owner);
+    println!("  Deposited value: {}", health.deposited_value);
+    println!("  Borrowed value: {}", health.borrowed_value);
+    println!("  Allowed borrow value: {}", health.allowed_borrow_value);
+    println!(
+        "  Unhealthy borrow value: {}",
+        health.unhealthy_borrow_value
+    );
+    if let Some(health_factor) = health.health_factor() {
+        println!("  Health factor: {}", health_factor);
+    }
+    if health.is_liquidatable() {
+        println!("  Liquidatable");
+    }
+}
+
+fn token_amount(config: &Config, token_account_pubkey: &Pubkey) -> Result<u64, Error> {
+    let token_account = config
+        .rpc_client
+        .get_account_with_commitment(token_account_pubkey, config.rpc_client.commitment())?
+        .value;
+    match token_account {
+        Some(token_account) => Ok(StateWithExtensions::<Token>::unpack(&token_account.data)?
+            .base
+            .amount),
+        None => Ok(0),
+    }
+}
+
+fn check_fee_payer_balance(config: &Config, required_balance: u64) -> Result<(), Error> {
+    let balance = config.rpc_client.get_balance(&config.fee_payer.pubkey())?;
+    if balance < required_balance {
+        Err(format!(
+            "Fee payer, {}, has insufficient balance: {} required, {} available",
+            config.fee_payer.pubkey(),
+            lamports_to_sol(required_balance),
+            lamports_to_sol(balance)
+        )
+        .into())
+    } else {
+        Ok(())
+    }
+}
+
+fn send_transaction(
+    config: &Config,
+    transaction: Transaction,
+) -> solana_client::client_error::Result<()> {
+    if config.dry_run {
+        let result = config.rpc_client.simulate_transaction(&transaction)?;
+        println!("Simulate result: {:?}", result);
+    } else {
+        let signature = config
+            .rpc_client
+            .send_and_confirm_transaction_with_spinner(&transaction)?;
+        println!("Signature: {}", signature);
+    }
+    Ok(())
+}
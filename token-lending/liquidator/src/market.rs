@@ -0,0 +1,479 @@
+//! Lending market snapshots and off-chain obligation health
+
+use {
+    crate::Error,
+    solana_program::{
+        account_info::{AccountInfo, IntoAccountInfo},
+        clock::Slot,
+        program_pack::Pack,
+        pubkey::Pubkey,
+    },
+    solana_sdk::account::Account,
+    spl_token_lending::{
+        math::{Decimal, TryDiv},
+        oracle::{get_pyth_price, get_swap_pool_spot_price, OraclePrice},
+        state::{
+            LendingMarket, Obligation, OracleSource, Reserve, EXTENDED_OBLIGATION_LEN,
+            LEGACY_OBLIGATION_LEN,
+        },
+    },
+    std::collections::BTreeMap,
+};
+
+/// Offset of the lending market address in reserve and obligation accounts,
+/// after the version and last update
+pub const LENDING_MARKET_OFFSET: usize = 10; // 1 + 8 + 1
+
+/// Obligation state with the length of its account, which decides its
+/// capacity and collateral modes
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObligationAccount {
+    /// Obligation state
+    pub obligation: Obligation,
+    /// Length of the obligation account data
+    pub data_len: usize,
+}
+
+/// Lending market state with its reserves and obligations
+#[derive(Clone, Debug)]
+pub struct MarketSnapshot {
+    /// Lending program id
+    pub program_id: Pubkey,
+    /// Lending market address
+    pub lending_market_pubkey: Pubkey,
+    /// Lending market state
+    pub lending_market: LendingMarket,
+    /// Reserves of the lending market by address
+    pub reserves: BTreeMap<Pubkey, Reserve>,
+    /// Obligations of the lending market by address
+    pub obligations: BTreeMap<Pubkey, ObligationAccount>,
+    /// Accounts following the oracle account when refreshing a reserve, for
+    /// reserves with swap pool or secondary oracles
+    pub additional_oracle_accounts: BTreeMap<Pubkey, Vec<Pubkey>>,
+    /// Oracle accounts read when pricing reserves by address, including the
+    /// additional oracle accounts
+    pub oracle_accounts: BTreeMap<Pubkey, Account>,
+}
+
+impl MarketSnapshot {
+    /// Decode a lending market and the reserves and obligations among the
+    /// accounts provided, skipping accounts of other markets or programs
+    pub fn from_accounts<I>(
+        program_id: Pubkey,
+        lending_market_pubkey: Pubkey,
+        lending_market_account: &Account,
+        accounts: I,
+    ) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = (Pubkey, Account)>,
+    {
+        if lending_market_account.owner != program_id {
+            return Err(format!(
+                "Lending market {} is not owned by the lending program",
+                lending_market_pubkey
+            )
+            .into());
+        }
+        let lending_market = LendingMarket::unpack(&lending_market_account.data)?;
+
+        let mut reserves = BTreeMap::new();
+        let mut obligations = BTreeMap::new();
+        for (pubkey, account) in accounts {
+            if account.owner != program_id {
+                continue;
+            }
+            let data_len = account.data.len();
+            if data_len == Reserve::LEN {
+                if let Ok(reserve) = Reserve::unpack(&account.data) {
+                    if reserve.lending_market == lending_market_pubkey {
+                        reserves.insert(pubkey, reserve);
+                    }
+                }
+            } else if data_len == Obligation::LEN
+                || data_len == LEGACY_OBLIGATION_LEN
+                || data_len == EXTENDED_OBLIGATION_LEN
+            {
+                if let Ok(obligation) = Obligation::unpack(&account.data) {
+                    if obligation.lending_market == lending_market_pubkey {
+                        obligations.insert(
+                            pubkey,
+                            ObligationAccount {
+                                obligation,
+                                data_len,
+                            },
+                        );
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            program_id,
+            lending_market_pubkey,
+            lending_market,
+            reserves,
+            obligations,
+            additional_oracle_accounts: BTreeMap::new(),
+            oracle_accounts: BTreeMap::new(),
+        })
+    }
+
+    /// Obligation of the lending market by address
+    pub fn obligation(&self, obligation_pubkey: &Pubkey) -> Result<&ObligationAccount, Error> {
+        self.obligations
+            .get(obligation_pubkey)
+            .ok_or_else(|| format!("Obligation {} not found", obligation_pubkey).into())
+    }
+
+    /// Oracle accounts passed when refreshing a reserve, in instruction order
+    pub fn reserve_oracle_pubkeys(
+        &self,
+        reserve_pubkey: &Pubkey,
+        reserve: &Reserve,
+    ) -> Vec<Pubkey> {
+        let mut oracle_pubkeys = vec![reserve.liquidity.oracle_pubkey];
+        if let Some(additional_oracle_pubkeys) = self.additional_oracle_accounts.get(reserve_pubkey)
+        {
+            oracle_pubkeys.extend(additional_oracle_pubkeys);
+        }
+        oracle_pubkeys
+    }
+
+    /// Price every reserve from the oracle accounts, accrue reserve interest
+    /// and refresh every obligation in the given slot, the way
+    /// `RefreshReserve` and `RefreshObligation` would
+    pub fn refresh(&mut self, slot: Slot) -> Result<(), Error> {
+        let reserve_oracle_pubkeys: Vec<_> = self
+            .reserves
+            .iter()
+            .map(|(reserve_pubkey, reserve)| self.reserve_oracle_pubkeys(reserve_pubkey, reserve))
+            .collect();
+        for ((reserve_pubkey, reserve), oracle_pubkeys) in
+            self.reserves.iter_mut().zip(reserve_oracle_pubkeys)
+        {
+            refresh_reserve(reserve, &oracle_pubkeys, &self.oracle_accounts, slot).map_err(
+                |err| format!("Reserve {} cannot be refreshed: {}", reserve_pubkey, err),
+            )?;
+        }
+        for (obligation_pubkey, obligation_account) in self.obligations.iter_mut() {
+            refresh_obligation(&self.reserves, obligation_account, slot).map_err(|err| {
+                format!(
+                    "Obligation {} cannot be refreshed: {}",
+                    obligation_pubkey, err
+                )
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Health of every obligation with borrowed value, least healthy first
+    pub fn health(&self) -> Vec<ObligationHealth> {
+        let mut health: Vec<_> = self
+            .obligations
+            .iter()
+            .map(|(obligation_pubkey, account)| {
+                ObligationHealth::new(*obligation_pubkey, &account.obligation)
+            })
+            .filter(|health| health.health_factor().is_some())
+            .collect();
+        health.sort_by_key(|health| health.health_factor());
+        health
+    }
+}
+
+/// Refresh a reserve the way `RefreshReserve` does, reading its oracles from
+/// the accounts loaded. A reserve whose TWAP is still warming up keeps the
+/// price of its last refresh and is marked stale.
+fn refresh_reserve(
+    reserve: &mut Reserve,
+    oracle_pubkeys: &[Pubkey],
+    oracle_accounts: &BTreeMap<Pubkey, Account>,
+    slot: Slot,
+) -> Result<(), Error> {
+    // accounts missing from the snapshot read as empty, like on-chain
+    let mut accounts: Vec<_> = oracle_pubkeys
+        .iter()
+        .map(|pubkey| {
+            (
+                *pubkey,
+                oracle_accounts.get(pubkey).cloned().unwrap_or_default(),
+            )
+        })
+        .collect();
+    let account_infos: Vec<AccountInfo> = accounts
+        .iter_mut()
+        .map(|(pubkey, account)| (&*pubkey, account).into_account_info())
+        .collect();
+    let account_info_iter = &mut account_infos.iter();
+    let oracle_info = next_oracle_info(account_info_iter)?;
+
+    let oracle_config = reserve.config.oracle;
+    let primary_price = oracle_price(
+        reserve,
+        oracle_config.source,
+        oracle_info,
+        account_info_iter,
+        slot,
+    )?;
+    let market_price = if let Some(secondary) = oracle_config.secondary {
+        let secondary_oracle_info = next_oracle_info(account_info_iter)?;
+        let secondary_price = oracle_price(
+            reserve,
+            secondary.source,
+            secondary_oracle_info,
+            account_info_iter,
+            slot,
+        )?;
+        match (primary_price, secondary_price) {
+            (Some(primary_price), Some(secondary_price)) => {
+                secondary.check_divergence(primary_price, secondary_price)?;
+                Some(primary_price)
+            }
+            (Some(_), None) => None,
+            (None, secondary_price) => secondary_price,
+        }
+    } else {
+        primary_price
+    };
+
+    if slot > reserve.last_update.slot {
+        reserve.accrue_interest(slot)?;
+    }
+    reserve.last_update.update_slot(slot);
+    if let Some(market_price) = market_price {
+        reserve.liquidity.market_price = market_price;
+    } else {
+        reserve.last_update.mark_stale();
+    }
+    Ok(())
+}
+
+fn next_oracle_info<'a, 'b>(
+    account_info_iter: &mut impl Iterator<Item = &'a AccountInfo<'b>>,
+) -> Result<&'a AccountInfo<'b>, Error>
+where
+    'b: 'a,
+{
+    account_info_iter
+        .next()
+        .ok_or_else(|| "Oracle accounts not loaded".into())
+}
+
+/// Price read from an oracle the way the program does, None while a TWAP is
+/// warming up
+fn oracle_price<'a, 'b: 'a>(
+    reserve: &mut Reserve,
+    source: OracleSource,
+    oracle_info: &AccountInfo<'b>,
+    account_info_iter: &mut impl Iterator<Item = &'a AccountInfo<'b>>,
+    slot: Slot,
+) -> Result<Option<Decimal>, Error> {
+    let oracle_config = reserve.config.oracle;
+    let price = match source {
+        OracleSource::Pyth => get_pyth_price(oracle_info)?,
+        OracleSource::SwapPoolTwap => {
+            let token_a_info = next_oracle_info(account_info_iter)?;
+            let token_b_info = next_oracle_info(account_info_iter)?;
+            let quote_mint_info = next_oracle_info(account_info_iter)?;
+            let spot_price = get_swap_pool_spot_price(
+                oracle_info,
+                token_a_info,
+                token_b_info,
+                quote_mint_info,
+                &reserve.liquidity.mint_pubkey,
+                reserve.liquidity.mint_decimals,
+            )?;
+            let twap_price = reserve.liquidity.sample_twap(
+                spot_price,
+                slot,
+                oracle_config.twap_window_slots.into(),
+                oracle_config.max_staleness(),
+            )?;
+            if !reserve
+                .liquidity
+                .twap_warmed_up(slot, oracle_config.twap_window_slots.into())
+            {
+                return Ok(None);
+            }
+            OraclePrice {
+                price: twap_price,
+                confidence: Decimal::zero(),
+                publish_slot: slot,
+            }
+        }
+        OracleSource::FixedPrice => OraclePrice {
+            price: oracle_config.fixed_price(),
+            confidence: Decimal::zero(),
+            publish_slot: slot,
+        },
+    };
+    oracle_config.check_price(&price, slot)?;
+    Ok(Some(price.price))
+}
+
+/// Refresh an obligation the way `RefreshObligation` does, with the reserves
+/// already refreshed
+fn refresh_obligation(
+    reserves: &BTreeMap<Pubkey, Reserve>,
+    obligation_account: &mut ObligationAccount,
+    slot: Slot,
+) -> Result<(), Error> {
+    let obligation = &mut obligation_account.obligation;
+    // Obligations created before collateral modes have no room to record them
+    let collateral_modes = obligation_account.data_len != LEGACY_OBLIGATION_LEN;
+    let reserve = |reserve_pubkey: &Pubkey| {
+        reserves
+            .get(reserve_pubkey)
+            .ok_or_else(|| format!("Reserve {} not found", reserve_pubkey))
+    };
+
+    obligation.begin_refresh(slot);
+    for index in 0..obligation.deposits.len() {
+        let deposit_reserve = reserve(&obligation.deposits[index].deposit_reserve)?;
+        let market_value =
+            deposit_reserve.collateral_market_value(obligation.deposits[index].deposited_amount)?;
+        obligation.refresh_collateral(market_value, &deposit_reserve.config, collateral_modes)?;
+    }
+    for index in 0..obligation.borrows.len() {
+        let liquidity = &mut obligation.borrows[index];
+        let borrow_reserve = reserve(&liquidity.borrow_reserve)?;
+        liquidity.accrue_interest(borrow_reserve.liquidity.cumulative_borrow_rate_wads)?;
+        let market_value = borrow_reserve.liquidity_market_value(liquidity.borrowed_amount_wads)?;
+        obligation.refresh_liquidity(market_value, borrow_reserve.config.elevation_group.id)?;
+    }
+    obligation.finish_refresh(slot, collateral_modes)?;
+    Ok(())
+}
+
+/// Values of a refreshed obligation
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObligationHealth {
+    /// Obligation address
+    pub obligation_pubkey: Pubkey,
+    /// Obligation owner
+    pub owner: Pubkey,
+    /// Market value of deposits
+    pub deposited_value: Decimal,
+    /// Market value of borrows
+    pub borrowed_value: Decimal,
+    /// The maximum borrow value at the weighted average loan to value ratio
+    pub allowed_borrow_value: Decimal,
+    /// The dangerous borrow value at the weighted average liquidation
+    /// threshold
+    pub unhealthy_borrow_value: Decimal,
+}
+
+impl ObligationHealth {
+    /// Health of a refreshed obligation
+    pub fn new(obligation_pubkey: Pubkey, obligation: &Obligation) -> Self {
+        Self {
+            obligation_pubkey,
+            owner: obligation.owner,
+            deposited_value: obligation.deposited_value,
+            borrowed_value: obligation.borrowed_value,
+            allowed_borrow_value: obligation.allowed_borrow_value,
+            unhealthy_borrow_value: obligation.unhealthy_borrow_value,
+        }
+    }
+
+    /// Ratio of the unhealthy borrow value to the borrowed value, below 1 once
+    /// the obligation can be liquidated, None without borrowed value
+    pub fn health_factor(&self) -> Option<Decimal> {
+        if self.borrowed_value == Decimal::zero() {
+            None
+        } else {
+            self.unhealthy_borrow_value
+                .try_div(self.borrowed_value)
+                .ok()
+        }
+    }
+
+    /// Whether `LiquidateObligation` accepts the obligation
+    pub fn is_liquidatable(&self) -> bool {
+        self.borrowed_value > Decimal::zero() && self.borrowed_value >= self.unhealthy_borrow_value
+    }
+
+    /// Whether the health factor is below the threshold
+    pub fn is_at_risk(&self, health_factor_threshold: Decimal) -> bool {
+        self.health_factor().map_or(false, |health_factor| {
+            health_factor < health_factor_threshold
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        spl_token_lending::{
+            math::WAD,
+            state::{ObligationCollateral, ObligationLiquidity, OracleConfig, ReserveConfig},
+        },
+    };
+
+    fn fixed_price_reserve(fixed_price: u64) -> Reserve {
+        let mut reserve = Reserve {
+            config: ReserveConfig {
+                liquidation_threshold: 50,
+                oracle: OracleConfig {
+                    source: OracleSource::FixedPrice,
+                    fixed_price_wads: u128::from(fixed_price) * u128::from(WAD),
+                    ..OracleConfig::default()
+                },
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        reserve.liquidity.market_price = Decimal::one();
+        reserve.liquidity.cumulative_borrow_rate_wads = Decimal::one();
+        reserve.liquidity.available_amount = 1_000;
+        reserve.collateral.mint_total_supply = 1_000;
+        reserve
+    }
+
+    #[test]
+    fn refresh_at_current_oracle_prices() {
+        let collateral_reserve_pubkey = Pubkey::new_unique();
+        let borrow_reserve_pubkey = Pubkey::new_unique();
+        let obligation_pubkey = Pubkey::new_unique();
+        let mut collateral = ObligationCollateral::new(collateral_reserve_pubkey);
+        collateral.deposited_amount = 100;
+        let mut liquidity = ObligationLiquidity::new(borrow_reserve_pubkey);
+        liquidity.borrowed_amount_wads = Decimal::from(100u64);
+        let mut snapshot = MarketSnapshot {
+            program_id: spl_token_lending::id(),
+            lending_market_pubkey: Pubkey::new_unique(),
+            lending_market: LendingMarket::default(),
+            // the collateral price halved since the last reserve refresh
+            reserves: BTreeMap::from([
+                (collateral_reserve_pubkey, fixed_price_reserve(1)),
+                (borrow_reserve_pubkey, fixed_price_reserve(2)),
+            ]),
+            obligations: BTreeMap::from([(
+                obligation_pubkey,
+                ObligationAccount {
+                    obligation: Obligation {
+                        deposits: vec![collateral],
+                        borrows: vec![liquidity],
+                        ..Obligation::default()
+                    },
+                    data_len: EXTENDED_OBLIGATION_LEN,
+                },
+            )]),
+            additional_oracle_accounts: BTreeMap::new(),
+            oracle_accounts: BTreeMap::new(),
+        };
+
+        snapshot.refresh(1).unwrap();
+        assert_eq!(
+            snapshot.reserves[&borrow_reserve_pubkey]
+                .liquidity
+                .market_price,
+            Decimal::from(2u64)
+        );
+        let health = snapshot.health();
+        assert_eq!(health[0].deposited_value, Decimal::from(100u64));
+        assert_eq!(health[0].borrowed_value, Decimal::from(200u64));
+        assert!(health[0].is_liquidatable());
+    }
+}
@@ -0,0 +1,126 @@
+//! Loading lending market snapshots from an RPC node
+
+use {
+    crate::{
+        market::{MarketSnapshot, LENDING_MARKET_OFFSET},
+        Error,
+    },
+    solana_account_decoder::UiAccountEncoding,
+    solana_client::{
+        rpc_client::RpcClient,
+        rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+        rpc_filter::{Memcmp, RpcFilterType},
+    },
+    solana_program::pubkey::Pubkey,
+    spl_token_lending::state::{OracleSource, Reserve},
+    spl_token_swap::state::SwapVersion,
+    std::collections::BTreeSet,
+};
+
+/// Maximum number of accounts fetched by one `getMultipleAccounts` request
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// Load a lending market with all its reserves and obligations, and the
+/// oracle accounts needed to price and refresh its reserves
+pub fn load_market(
+    rpc_client: &RpcClient,
+    program_id: &Pubkey,
+    lending_market_pubkey: &Pubkey,
+) -> Result<MarketSnapshot, Error> {
+    let lending_market_account = rpc_client.get_account(lending_market_pubkey)?;
+    let accounts = rpc_client.get_program_accounts_with_config(
+        program_id,
+        RpcProgramAccountsConfig {
+            filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                LENDING_MARKET_OFFSET,
+                lending_market_pubkey.to_bytes().to_vec(),
+            ))]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..RpcAccountInfoConfig::default()
+            },
+            ..RpcProgramAccountsConfig::default()
+        },
+    )?;
+
+    let mut snapshot = MarketSnapshot::from_accounts(
+        *program_id,
+        *lending_market_pubkey,
+        &lending_market_account,
+        accounts,
+    )?;
+    for (reserve_pubkey, reserve) in &snapshot.reserves {
+        let additional_oracle_accounts = additional_oracle_accounts(rpc_client, reserve)?;
+        if !additional_oracle_accounts.is_empty() {
+            snapshot
+                .additional_oracle_accounts
+                .insert(*reserve_pubkey, additional_oracle_accounts);
+        }
+    }
+
+    let oracle_pubkeys: BTreeSet<Pubkey> = snapshot
+        .reserves
+        .iter()
+        .flat_map(|(reserve_pubkey, reserve)| {
+            snapshot.reserve_oracle_pubkeys(reserve_pubkey, reserve)
+        })
+        .collect();
+    let oracle_pubkeys: Vec<Pubkey> = oracle_pubkeys.into_iter().collect();
+    for pubkeys in oracle_pubkeys.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        let accounts = rpc_client.get_multiple_accounts(pubkeys)?;
+        for (pubkey, account) in pubkeys.iter().zip(accounts) {
+            if let Some(account) = account {
+                snapshot.oracle_accounts.insert(*pubkey, account);
+            }
+        }
+    }
+    Ok(snapshot)
+}
+
+/// Accounts following the oracle account when refreshing the reserve, read
+/// by its primary and secondary oracle sources
+fn additional_oracle_accounts(
+    rpc_client: &RpcClient,
+    reserve: &Reserve,
+) -> Result<Vec<Pubkey>, Error> {
+    let oracle = &reserve.config.oracle;
+    let mut additional_oracle_pubkeys = vec![];
+    if oracle.source == OracleSource::SwapPoolTwap {
+        additional_oracle_pubkeys.extend(swap_pool_oracle_accounts(
+            rpc_client,
+            &reserve.liquidity.oracle_pubkey,
+            &reserve.liquidity.mint_pubkey,
+        )?);
+    }
+    if let Some(secondary) = oracle.secondary {
+        additional_oracle_pubkeys.push(secondary.oracle_pubkey);
+        if secondary.source == OracleSource::SwapPoolTwap {
+            additional_oracle_pubkeys.extend(swap_pool_oracle_accounts(
+                rpc_client,
+                &secondary.oracle_pubkey,
+                &reserve.liquidity.mint_pubkey,
+            )?);
+        }
+    }
+    Ok(additional_oracle_pubkeys)
+}
+
+/// Token accounts and quote mint read from a swap pool oracle
+fn swap_pool_oracle_accounts(
+    rpc_client: &RpcClient,
+    swap_pool_pubkey: &Pubkey,
+    liquidity_mint_pubkey: &Pubkey,
+) -> Result<[Pubkey; 3], Error> {
+    let swap_pool_account = rpc_client.get_account(swap_pool_pubkey)?;
+    let swap_pool = SwapVersion::unpack(&swap_pool_account.data)?;
+    let quote_mint_pubkey = if swap_pool.token_a_mint() == liquidity_mint_pubkey {
+        swap_pool.token_b_mint()
+    } else {
+        swap_pool.token_a_mint()
+    };
+    Ok([
+        *swap_pool.token_a_account(),
+        *swap_pool.token_b_account(),
+        *quote_mint_pubkey,
+    ])
+}
@@ -0,0 +1,327 @@
+use {
+    solana_program::{program_option::COption, program_pack::Pack, pubkey::Pubkey},
+    solana_program_test::*,
+    solana_sdk::{
+        account::Account,
+        signature::{Keypair, Signer},
+        transaction::Transaction,
+    },
+    spl_token::state::{Account as Token, AccountState, Mint},
+    spl_token_lending::{
+        math::{Decimal, WAD},
+        processor::process_instruction,
+        state::{
+            InitLendingMarketParams, InitObligationParams, InitReserveParams, LendingMarket,
+            NewReserveCollateralParams, NewReserveLiquidityParams, Obligation,
+            ObligationCollateral, ObligationLiquidity, OracleConfig, OracleSource, Reserve,
+            ReserveCollateral, ReserveConfig, ReserveLiquidity,
+        },
+    },
+    spl_token_lending_liquidator::{liquidation::LiquidatorAccounts, market::MarketSnapshot},
+};
+
+const QUOTE_CURRENCY: [u8; 32] = *b"USD\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0";
+
+struct TestReserve {
+    pubkey: Pubkey,
+    collateral_mint_pubkey: Pubkey,
+    liquidity_mint_pubkey: Pubkey,
+}
+
+fn add_packable_account<T: Pack>(test: &mut ProgramTest, pubkey: Pubkey, data: &T, owner: &Pubkey) {
+    let mut account = Account::new(u32::MAX as u64, T::get_packed_len(), owner);
+    data.pack_into_slice(&mut account.data);
+    test.add_account(pubkey, account);
+}
+
+fn add_token_account(
+    test: &mut ProgramTest,
+    mint_pubkey: Pubkey,
+    owner: Pubkey,
+    amount: u64,
+) -> Pubkey {
+    let pubkey = Pubkey::new_unique();
+    add_packable_account(
+        test,
+        pubkey,
+        &Token {
+            mint: mint_pubkey,
+            owner,
+            amount,
+            state: AccountState::Initialized,
+            ..Token::default()
+        },
+        &spl_token::id(),
+    );
+    pubkey
+}
+
+/// Add a reserve priced at one quote currency unit per token, with all of its
+/// collateral deposited in obligations
+fn add_reserve(
+    test: &mut ProgramTest,
+    lending_market_pubkey: Pubkey,
+    lending_market_authority: Pubkey,
+    liquidity_amount: u64,
+    borrow_amount: u64,
+) -> TestReserve {
+    let liquidity_mint_pubkey = Pubkey::new_unique();
+    add_packable_account(
+        test,
+        liquidity_mint_pubkey,
+        &Mint {
+            is_initialized: true,
+            mint_authority: COption::Some(Pubkey::new_unique()),
+            supply: liquidity_amount,
+            ..Mint::default()
+        },
+        &spl_token::id(),
+    );
+    let collateral_mint_pubkey = Pubkey::new_unique();
+    add_packable_account(
+        test,
+        collateral_mint_pubkey,
+        &Mint {
+            is_initialized: true,
+            mint_authority: COption::Some(lending_market_authority),
+            supply: liquidity_amount,
+            ..Mint::default()
+        },
+        &spl_token::id(),
+    );
+    let liquidity_supply_pubkey = add_token_account(
+        test,
+        liquidity_mint_pubkey,
+        lending_market_authority,
+        liquidity_amount - borrow_amount,
+    );
+    let collateral_supply_pubkey = add_token_account(
+        test,
+        collateral_mint_pubkey,
+        lending_market_authority,
+        liquidity_amount,
+    );
+    let liquidity_fee_receiver_pubkey =
+        add_token_account(test, liquidity_mint_pubkey, Pubkey::new_unique(), 0);
+
+    let fixed_price_wads = WAD as u128;
+    let mut reserve = Reserve::new(InitReserveParams {
+        current_slot: 1,
+        lending_market: lending_market_pubkey,
+        liquidity: ReserveLiquidity::new(NewReserveLiquidityParams {
+            mint_pubkey: liquidity_mint_pubkey,
+            mint_decimals: 0,
+            supply_pubkey: liquidity_supply_pubkey,
+            fee_receiver: liquidity_fee_receiver_pubkey,
+            oracle_pubkey: Pubkey::new_unique(),
+            market_price: Decimal::from_scaled_val(fixed_price_wads),
+            token_program_id: spl_token::id(),
+        }),
+        collateral: ReserveCollateral::new(NewReserveCollateralParams {
+            mint_pubkey: collateral_mint_pubkey,
+            supply_pubkey: collateral_supply_pubkey,
+        }),
+        config: ReserveConfig {
+            optimal_utilization_rate: 80,
+            loan_to_value_ratio: 50,
+            liquidation_bonus: 5,
+            liquidation_threshold: 55,
+            optimal_borrow_rate: 4,
+            max_borrow_rate: 30,
+            oracle: OracleConfig {
+                source: OracleSource::FixedPrice,
+                fixed_price_wads,
+                ..OracleConfig::default()
+            },
+            ..ReserveConfig::default()
+        },
+    });
+    reserve.deposit_liquidity(liquidity_amount).unwrap();
+    reserve.liquidity.borrow(borrow_amount.into()).unwrap();
+    reserve.last_update.update_slot(1);
+
+    let pubkey = Pubkey::new_unique();
+    add_packable_account(test, pubkey, &reserve, &spl_token_lending::id());
+
+    TestReserve {
+        pubkey,
+        collateral_mint_pubkey,
+        liquidity_mint_pubkey,
+    }
+}
+
+async fn get_accounts(
+    banks_client: &mut BanksClient,
+    pubkeys: &[Pubkey],
+) -> Vec<(Pubkey, Account)> {
+    let mut accounts = vec![];
+    for pubkey in pubkeys {
+        let account = banks_client.get_account(*pubkey).await.unwrap().unwrap();
+        accounts.push((*pubkey, account));
+    }
+    accounts
+}
+
+#[tokio::test]
+async fn test_liquidate_unhealthy_obligation() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let lending_market_pubkey = Pubkey::new_unique();
+    let (lending_market_authority, bump_seed) =
+        Pubkey::find_program_address(&[lending_market_pubkey.as_ref()], &spl_token_lending::id());
+    add_packable_account(
+        &mut test,
+        lending_market_pubkey,
+        &LendingMarket::new(InitLendingMarketParams {
+            bump_seed,
+            owner: Pubkey::new_unique(),
+            quote_currency: QUOTE_CURRENCY,
+            token_program_id: spl_token::id(),
+            oracle_program_id: Pubkey::new_unique(),
+        }),
+        &spl_token_lending::id(),
+    );
+
+    let collateral_reserve = add_reserve(
+        &mut test,
+        lending_market_pubkey,
+        lending_market_authority,
+        1_000,
+        0,
+    );
+    let borrow_reserve = add_reserve(
+        &mut test,
+        lending_market_pubkey,
+        lending_market_authority,
+        1_000,
+        600,
+    );
+
+    // 600 borrowed against an unhealthy borrow value of 1000 * 55% = 550
+    let obligation_pubkey = Pubkey::new_unique();
+    let mut collateral = ObligationCollateral::new(collateral_reserve.pubkey);
+    collateral.deposited_amount = 1_000;
+    let mut liquidity = ObligationLiquidity::new(borrow_reserve.pubkey);
+    liquidity.borrowed_amount_wads = Decimal::from(600u64);
+    add_packable_account(
+        &mut test,
+        obligation_pubkey,
+        &Obligation::new(InitObligationParams {
+            current_slot: 1,
+            lending_market: lending_market_pubkey,
+            owner: Pubkey::new_unique(),
+            deposits: vec![collateral],
+            borrows: vec![liquidity],
+        }),
+        &spl_token_lending::id(),
+    );
+
+    let liquidator = Keypair::new();
+    let accounts = LiquidatorAccounts {
+        source_liquidity_pubkey: add_token_account(
+            &mut test,
+            borrow_reserve.liquidity_mint_pubkey,
+            liquidator.pubkey(),
+            1_000,
+        ),
+        destination_collateral_pubkey: add_token_account(
+            &mut test,
+            collateral_reserve.collateral_mint_pubkey,
+            liquidator.pubkey(),
+            0,
+        ),
+        user_transfer_authority_pubkey: liquidator.pubkey(),
+    };
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let lending_market_account = banks_client
+        .get_account(lending_market_pubkey)
+        .await
+        .unwrap()
+        .unwrap();
+    let program_accounts = get_accounts(
+        &mut banks_client,
+        &[
+            collateral_reserve.pubkey,
+            borrow_reserve.pubkey,
+            obligation_pubkey,
+        ],
+    )
+    .await;
+    let mut snapshot = MarketSnapshot::from_accounts(
+        spl_token_lending::id(),
+        lending_market_pubkey,
+        &lending_market_account,
+        program_accounts,
+    )
+    .unwrap();
+    assert_eq!(snapshot.reserves.len(), 2);
+    assert_eq!(snapshot.obligations.len(), 1);
+
+    snapshot.refresh(1).unwrap();
+    let health = snapshot.health();
+    assert_eq!(health.len(), 1);
+    assert!(health[0].is_liquidatable());
+    assert_eq!(health[0].borrowed_value, Decimal::from(600u64));
+    assert_eq!(health[0].unhealthy_borrow_value, Decimal::from(550u64));
+
+    let plan = snapshot
+        .plan_liquidation(&obligation_pubkey)
+        .unwrap()
+        .unwrap();
+    assert_eq!(plan.repay_reserve_pubkey, borrow_reserve.pubkey);
+    assert_eq!(plan.withdraw_reserve_pubkey, collateral_reserve.pubkey);
+    assert_eq!(plan.liquidity_amount, 300);
+
+    let mut transaction = Transaction::new_with_payer(
+        &snapshot.liquidation_instructions(&plan, &accounts).unwrap(),
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &liquidator], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let obligation_account = banks_client
+        .get_account(obligation_pubkey)
+        .await
+        .unwrap()
+        .unwrap();
+    let obligation = Obligation::unpack(&obligation_account.data).unwrap();
+    assert_eq!(
+        obligation.borrows[0].borrowed_amount_wads,
+        Decimal::from(300u64)
+    );
+
+    // 300 repaid for collateral worth 300 plus the 5% liquidation bonus
+    let destination_collateral_account = banks_client
+        .get_account(accounts.destination_collateral_pubkey)
+        .await
+        .unwrap()
+        .unwrap();
+    let destination_collateral = Token::unpack(&destination_collateral_account.data).unwrap();
+    assert_eq!(destination_collateral.amount, 315);
+
+    // the liquidated obligation is healthy again
+    let program_accounts = get_accounts(
+        &mut banks_client,
+        &[
+            collateral_reserve.pubkey,
+            borrow_reserve.pubkey,
+            obligation_pubkey,
+        ],
+    )
+    .await;
+    let mut snapshot = MarketSnapshot::from_accounts(
+        spl_token_lending::id(),
+        lending_market_pubkey,
+        &lending_market_account,
+        program_accounts,
+    )
+    .unwrap();
+    snapshot.refresh(1).unwrap();
+    assert_eq!(snapshot.plan_liquidation(&obligation_pubkey).unwrap(), None);
+}
@@ -168,6 +168,54 @@ pub enum LendingError {
     /// Lending instruction exceeds desired slippage limit
     #[error("Amount smaller than desired slippage limit")]
     ExceededSlippage,
+    /// Oracle price published too long ago
+    #[error("Oracle price is stale")]
+    OraclePriceStale,
+    /// Oracle price confidence interval too wide
+    #[error("Oracle price confidence interval is too wide")]
+    OraclePriceUncertain,
+    /// Primary and secondary oracle prices too far apart
+    #[error("Primary and secondary oracle prices diverge")]
+    OraclePricesDiverge,
+    /// Deposit would exceed the reserve deposit limit
+    #[error("Deposit would exceed the reserve deposit limit")]
+    DepositLimitExceeded,
+    /// Borrow would exceed the reserve borrow limit
+    #[error("Borrow would exceed the reserve borrow limit")]
+    BorrowLimitExceeded,
+    /// Isolated collateral can only back a single borrowed asset
+    #[error("Isolated collateral can only back a single borrowed asset")]
+    IsolatedCollateralBorrow,
+    /// Reserve is outside the obligation elevation group
+    #[error("Reserve is outside the obligation elevation group")]
+    ElevationGroupMismatch,
+    /// Token mint has an extension the lending program cannot support
+    #[error("Token mint extension is not supported")]
+    UnsupportedTokenExtension,
+    /// Obligation still has deposit value to repay its borrows
+    #[error("Obligation deposits have value, borrows cannot be written off")]
+    ObligationNotInsolvent,
+    /// Obligation refresh chunk does not continue the refresh in progress
+    #[error("Obligation refresh chunk is out of order")]
+    InvalidObligationRefresh,
+    /// Obligation account layout cannot be extended
+    #[error("Obligation is already extended")]
+    ObligationAlreadyExtended,
+    /// Reserve config changes must wait for the lending market config timelock
+    #[error("Reserve config changes must be queued behind the config timelock")]
+    ConfigTimelocked,
+    /// Pending config change cannot be applied yet
+    #[error("Pending config change is not ready to be applied")]
+    PendingConfigNotReady,
+    /// Reserve config change would loosen reserve risk
+    #[error("Reserve config change does not only tighten reserve risk")]
+    ConfigNotTightened,
+    /// Reserve is paused
+    #[error("Reserve is paused")]
+    ReservePaused,
+    /// Reserve liquidity cannot be borrowed
+    #[error("Reserve borrowing is disabled")]
+    ReserveBorrowingDisabled,
 }
 
 impl From<LendingError> for ProgramError {
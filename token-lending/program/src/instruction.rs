@@ -3,14 +3,17 @@
 use {
     crate::{
         error::LendingError,
-        state::{ReserveConfig, ReserveFees},
+        state::{
+            ElevationGroup, InterestRateModel, OracleConfig, ReserveCaps, ReserveConfig,
+            ReserveFees, INTEREST_RATE_MODEL_LEN, ORACLE_CONFIG_LEN,
+        },
     },
     solana_program::{
         instruction::{AccountMeta, Instruction},
         msg,
         program_error::ProgramError,
         pubkey::{Pubkey, PUBKEY_BYTES},
-        sysvar,
+        system_program, sysvar,
     },
     std::{convert::TryInto, mem::size_of},
 };
@@ -40,10 +43,16 @@ pub enum LendingInstruction {
     // 1
     /// Sets the new owner of a lending market.
     ///
+    /// While the lending market has a config timelock, the new owner is queued
+    /// and takes over with ApplyLendingMarketChanges once the timelock
+    /// passes.
+    ///
     /// Accounts expected by this instruction:
     ///
     ///   0. `[writable]` Lending market account.
     ///   1. `[signer]` Current owner.
+    ///   2. `[]` Optional: Clock sysvar, required while the lending market
+    ///      has a config timelock.
     SetLendingMarketOwner {
         /// The new owner
         new_owner: Pubkey,
@@ -52,21 +61,28 @@ pub enum LendingInstruction {
     // 2
     /// Initializes a new lending market reserve.
     ///
+    /// While the lending market has a config timelock, the reserve is created
+    /// with a loan to value ratio of 0 and borrowing disabled, and the config
+    /// must be loosened with QueueReserveConfig.
+    ///
     /// Accounts expected by this instruction:
     ///
     ///   0. `[writable]` Source liquidity token account. $authority can
     ///      transfer $liquidity_amount.
     ///   1. `[writable]` Destination collateral token account - uninitialized.
     ///   2. `[writable]` Reserve account - uninitialized.
-    ///   3. `[]` Reserve liquidity SPL Token mint.
+    ///   3. `[]` Reserve liquidity SPL Token or Token-2022 mint. Mints which
+    ///      are non-transferable, have a permanent delegate or transfer hook,
+    ///      or freeze new accounts by default are rejected.
     ///   4. `[writable]` Reserve liquidity supply SPL Token account -
     ///      uninitialized.
     ///   5. `[writable]` Reserve liquidity fee receiver - uninitialized.
     ///   6. `[writable]` Reserve collateral SPL Token mint - uninitialized.
     ///   7. `[writable]` Reserve collateral token supply - uninitialized.
-    ///   8. `[]` Pyth product account.
-    ///   9. `[]` Pyth price account. This will be used as the reserve liquidity
-    ///      oracle account.
+    ///   8. `[]` Pyth product account for Pyth oracles, otherwise unused.
+    ///   9. `[]` Oracle account of the config oracle source: a Pyth price
+    ///      account, a token swap pool, or any account for fixed prices. This
+    ///      will be used as the reserve liquidity oracle account.
     ///   10 `[]` Lending market account.
     ///   11 `[]` Derived lending market authority.
     ///   12 `[signer]` Lending market owner.
@@ -74,6 +90,8 @@ pub enum LendingInstruction {
     ///   14 `[]` Clock sysvar.
     ///   15 `[]` Rent sysvar.
     ///   16 `[]` Token program id.
+    ///   17 `[]` Optional: Reserve liquidity token program id, owner of the
+    ///      liquidity mint. Defaults to the token program id.
     InitReserve {
         /// Initial amount of liquidity to deposit into the new reserve
         liquidity_amount: u64,
@@ -87,14 +105,22 @@ pub enum LendingInstruction {
     /// Accounts expected by this instruction:
     ///
     ///   0. `[writable]` Reserve account.
-    ///   1. `[]` Reserve liquidity oracle account. Must be the Pyth price
-    ///      account specified at InitReserve.
+    ///   1. `[]` Reserve liquidity oracle account. Must be the oracle account
+    ///      specified at InitReserve or ModifyReserveConfig.
     ///   2. `[]` Clock sysvar.
+    ///   3. `[]` Swap pool oracles only: pool token A account.
+    ///   4. `[]` Swap pool oracles only: pool token B account.
+    ///   5. `[]` Swap pool oracles only: mint of the pool token that is not the
+    ///      reserve liquidity.
+    ///   6. `[]` Optional: Secondary oracle account, if configured, followed
+    ///      by the same accounts as 3-5 for swap pool secondary oracles.
     RefreshReserve,
 
     // 4
     /// Deposit liquidity into a reserve in exchange for collateral. Collateral
-    /// represents a share of the reserve liquidity pool.
+    /// represents a share of the reserve liquidity pool. Fails if the reserve
+    /// total supply would exceed its deposit limits. Only the liquidity
+    /// received after any transfer fee is deposited.
     ///
     /// Accounts expected by this instruction:
     ///
@@ -109,6 +135,10 @@ pub enum LendingInstruction {
     ///   7. `[signer]` User transfer authority ($authority).
     ///   8. `[]` Clock sysvar.
     ///   9. `[]` Token program id.
+    ///   10 `[]` Optional: Reserve liquidity mint, required for mints with a
+    ///      transfer fee.
+    ///   11 `[]` Optional: Reserve liquidity token program id, required for
+    ///      Token-2022 reserves. Defaults to the token program id.
     DepositReserveLiquidity {
         /// Amount of liquidity to deposit in exchange for collateral tokens
         liquidity_amount: u64,
@@ -130,6 +160,10 @@ pub enum LendingInstruction {
     ///   7. `[signer]` User transfer authority ($authority).
     ///   8. `[]` Clock sysvar.
     ///   9. `[]` Token program id.
+    ///   10 `[]` Optional: Reserve liquidity mint, required for mints with a
+    ///      transfer fee.
+    ///   11 `[]` Optional: Reserve liquidity token program id, required for
+    ///      Token-2022 reserves. Defaults to the token program id.
     RedeemReserveCollateral {
         /// Amount of collateral tokens to redeem in exchange for liquidity
         collateral_amount: u64,
@@ -140,7 +174,8 @@ pub enum LendingInstruction {
     ///
     /// Accounts expected by this instruction:
     ///
-    ///   0. `[writable]` Obligation account - uninitialized.
+    ///   0. `[writable]` Obligation account - uninitialized, sized for
+    ///      Obligation::LEN or EXTENDED_OBLIGATION_LEN.
     ///   1. `[]` Lending market account.
     ///   2. `[signer]` Obligation owner.
     ///   3. `[]` Clock sysvar.
@@ -154,6 +189,11 @@ pub enum LendingInstruction {
     /// deposit reserves in order, followed by all liquidity borrow reserves
     /// in order.
     ///
+    /// Collateral uses its reserve's elevation group ratios when all deposit
+    /// and borrow reserves share that group, and isolated collateral only
+    /// counts towards the allowed borrow value while a single asset is
+    /// borrowed. Obligations sized before these modes get neither.
+    ///
     /// Accounts expected by this instruction:
     ///
     ///   0. `[writable]` Obligation account.
@@ -165,6 +205,8 @@ pub enum LendingInstruction {
 
     // 8
     /// Deposit collateral to an obligation. Requires a refreshed reserve.
+    /// Collateral of a reserve whose total supply exceeds its deposit limits
+    /// can only be added to existing obligation deposits.
     ///
     /// Accounts expected by this instruction:
     ///
@@ -210,7 +252,8 @@ pub enum LendingInstruction {
 
     // 10
     /// Borrow liquidity from a reserve by depositing collateral tokens.
-    /// Requires a refreshed obligation and reserve.
+    /// Requires a refreshed obligation and reserve. Fails if the reserve total
+    /// borrows would exceed its borrow limits.
     ///
     /// Accounts expected by this instruction:
     ///
@@ -227,7 +270,13 @@ pub enum LendingInstruction {
     ///   7. `[signer]` Obligation owner.
     ///   8. `[]` Clock sysvar.
     ///   9. `[]` Token program id.
-    ///   10 `[optional, writable]` Host fee receiver account.
+    ///   10 `[optional, writable]` Host fee receiver account. Pass the borrow
+    ///      reserve liquidity fee receiver to skip the host fee while still
+    ///      providing 11-12.
+    ///   11 `[]` Optional: Borrow reserve liquidity mint, required for mints
+    ///      with a transfer fee.
+    ///   12 `[]` Optional: Borrow reserve liquidity token program id, required
+    ///      for Token-2022 reserves. Defaults to the token program id.
     BorrowObligationLiquidity {
         /// Amount of liquidity to borrow - u64::MAX for 100% of borrowing power
         liquidity_amount: u64,
@@ -238,7 +287,8 @@ pub enum LendingInstruction {
 
     // 11
     /// Repay borrowed liquidity to a reserve. Requires a refreshed obligation
-    /// and reserve.
+    /// and reserve. The source also pays any transfer fee, so the reserve
+    /// receives the full repay amount.
     ///
     /// Accounts expected by this instruction:
     ///
@@ -252,6 +302,10 @@ pub enum LendingInstruction {
     ///   5. `[signer]` User transfer authority ($authority).
     ///   6. `[]` Clock sysvar.
     ///   7. `[]` Token program id.
+    ///   8. `[]` Optional: Repay reserve liquidity mint, required for mints
+    ///      with a transfer fee.
+    ///   9. `[]` Optional: Repay reserve liquidity token program id, required
+    ///      for Token-2022 reserves. Defaults to the token program id.
     RepayObligationLiquidity {
         /// Amount of liquidity to repay - u64::MAX for 100% of borrowed amount
         liquidity_amount: u64,
@@ -260,7 +314,8 @@ pub enum LendingInstruction {
     // 12
     /// Repay borrowed liquidity to a reserve to receive collateral at a
     /// discount from an unhealthy obligation. Requires a refreshed
-    /// obligation and reserves.
+    /// obligation and reserves. The source also pays any transfer fee, so the
+    /// repay reserve receives the full repay amount.
     ///
     /// Accounts expected by this instruction:
     ///
@@ -278,6 +333,10 @@ pub enum LendingInstruction {
     ///   9. `[signer]` User transfer authority ($authority).
     ///   10 `[]` Clock sysvar.
     ///   11 `[]` Token program id.
+    ///   12 `[]` Optional: Repay reserve liquidity mint, required for mints
+    ///      with a transfer fee.
+    ///   13 `[]` Optional: Repay reserve liquidity token program id, required
+    ///      for Token-2022 reserves. Defaults to the token program id.
     LiquidateObligation {
         /// Amount of liquidity to repay - u64::MAX for up to 100% of borrowed
         /// amount
@@ -299,12 +358,13 @@ pub enum LendingInstruction {
     ///   4. `[writable]` Host fee receiver.
     ///   5. `[]` Lending market account.
     ///   6. `[]` Derived lending market authority.
-    ///   7. `[]` Token program id.
+    ///   7. `[]` Reserve liquidity token program id.
     ///   8. `[]` Flash loan receiver program id. Must implement an instruction
     ///      that has tag of 0 and a signature of `(amount: u64)` This
     ///      instruction must return the amount to the source liquidity account.
     ///   .. `[any]` Additional accounts expected by the receiving program's
-    /// `ReceiveFlashLoan` instruction.
+    /// `ReceiveFlashLoan` instruction. Mints with a transfer fee also need the
+    /// reserve liquidity mint among these accounts.
     ///
     ///   The flash loan receiver program that is to be invoked should contain
     /// an instruction with   tag `0` and accept the total amount (including
@@ -332,17 +392,215 @@ pub enum LendingInstruction {
 
     // 14
     /// Modify the ReserveConfig parameters of an already initialized Reserve
-    /// account
+    /// account. Switching to an adaptive interest rate model starts it from
+    /// its initial rate at target. Changing the oracle config marks the
+    /// reserve stale. Rejected while the lending market has a config
+    /// timelock, use QueueReserveConfig instead.
     ///
     /// Accounts expected by this instruction:
     ///
     ///   0. `[writable]` Reserve account
     ///   1. `[]` Lending market account
     ///   2. `[signer]` Lending market owner
+    ///   3. `[]` Optional: Pyth product account for Pyth oracles, otherwise
+    ///      unused. Required along with 4 to change the oracle source.
+    ///   4. `[]` Optional: New reserve liquidity oracle account, as in
+    ///      InitReserve.
     ModifyReserveConfig {
         /// Reserve configuration updated values
         new_config: ReserveConfig,
     },
+
+    // 15
+    /// Withdraw protocol fees accumulated from the reserve protocol take rate
+    /// of accrued interest. Withdraws at most the protocol fees that are
+    /// currently available liquidity.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Reserve account - refreshed.
+    ///   1. `[writable]` Reserve liquidity supply SPL Token account.
+    ///   2. `[writable]` Destination liquidity token account.
+    ///   3. `[]` Lending market account.
+    ///   4. `[]` Derived lending market authority.
+    ///   5. `[signer]` Lending market owner.
+    ///   6. `[]` Clock sysvar.
+    ///   7. `[]` Token program id.
+    ///   8. `[]` Optional: Reserve liquidity mint, required for mints with a
+    ///      transfer fee.
+    ///   9. `[]` Optional: Reserve liquidity token program id, required for
+    ///      Token-2022 reserves. Defaults to the token program id.
+    WithdrawProtocolFees {
+        /// Amount of protocol fees to withdraw - u64::MAX for all available
+        liquidity_amount: u64,
+    },
+
+    // 16
+    /// Sets the liquidation close factor of a lending market. While the
+    /// lending market has a config timelock, the new close factor is queued
+    /// and applied with ApplyLendingMarketChanges once the timelock passes.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Lending market account.
+    ///   1. `[signer]` Lending market owner.
+    ///   2. `[]` Optional: Clock sysvar, required while the lending market
+    ///      has a config timelock.
+    SetLiquidationCloseFactor {
+        /// Percentage of an obligation borrowed value that can be repaid
+        /// during each liquidation call, in range (0, 100]
+        liquidation_close_factor: u8,
+    },
+
+    // 17
+    /// Write off the borrowed liquidity of an obligation whose deposits have
+    /// all been liquidated, or have no market value left. Worthless deposits
+    /// are dropped from the obligation. The loss is shared by the reserve
+    /// collateral holders through a lower collateral exchange rate.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Reserve account - refreshed.
+    ///   1. `[writable]` Obligation account - refreshed.
+    ///   2. `[]` Lending market account.
+    ///   3. `[]` Clock sysvar.
+    WriteOffBadDebt,
+
+    // 18
+    /// Extend an obligation account to hold up to
+    /// MAX_EXTENDED_OBLIGATION_RESERVES deposits and borrows combined,
+    /// migrating its deposits and borrows to the extended layout. Obligations
+    /// created before collateral modes gain them.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Obligation account.
+    ///   1. `[signer]` Obligation owner.
+    ///   2. `[writable, signer]` Payer for the rent of the extended account.
+    ///   3. `[]` Rent sysvar.
+    ///   4. `[]` System program.
+    ExtendObligation,
+
+    // 19
+    /// Refresh part of an obligation's deposits and borrows, for obligations
+    /// with too many to refresh within a single instruction. Chunks must
+    /// continue from the previous one in the same slot, starting at index 0,
+    /// with deposits counted before borrows. The obligation stays stale until
+    /// the chunk with its last deposit or borrow completes the refresh, with
+    /// the same result as RefreshObligation.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Obligation account - not created before collateral
+    ///      modes.
+    ///   1. `[]` Clock sysvar.
+    ///   .. `[]` Deposit and borrow reserve accounts - refreshed, in order
+    ///      from $start_index.
+    RefreshObligationChunk {
+        /// Index of the first deposit or borrow refreshed, counting deposits
+        /// before borrows
+        start_index: u8,
+    },
+
+    // 20
+    /// Sets the guardian of a lending market, which can tighten reserve risk
+    /// and cancel pending reserve configs without the config timelock.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Lending market account.
+    ///   1. `[signer]` Lending market owner.
+    SetLendingMarketGuardian {
+        /// The new guardian, default pubkey to remove the guardian
+        guardian: Pubkey,
+    },
+
+    // 21
+    /// Sets the number of slots reserve config and owner changes of a lending
+    /// market wait before they can be applied. A longer timelock applies
+    /// immediately, a shorter one is queued behind the current timelock and
+    /// applied with ApplyLendingMarketChanges.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Lending market account.
+    ///   1. `[signer]` Lending market owner.
+    ///   2. `[]` Clock sysvar.
+    SetConfigTimelock {
+        /// Slots config changes wait, 0 to apply them immediately
+        config_timelock_slots: u64,
+    },
+
+    // 22
+    /// Queue new ReserveConfig parameters behind the lending market config
+    /// timelock. Queuing into pending reserve configs of the same reserve
+    /// replaces them and restarts the timelock.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Pending reserve config account - uninitialized or
+    ///      pending for the same reserve.
+    ///   1. `[]` Reserve account.
+    ///   2. `[]` Lending market account.
+    ///   3. `[signer]` Lending market owner.
+    ///   4. `[]` Clock sysvar.
+    ///   5. `[]` Rent sysvar.
+    ///   6. `[]` Optional: Pyth product account for Pyth oracles, otherwise
+    ///      unused. Required along with 7 to change the oracle source.
+    ///   7. `[]` Optional: New reserve liquidity oracle account, as in
+    ///      InitReserve.
+    QueueReserveConfig {
+        /// Reserve configuration values applied once the timelock passes
+        new_config: ReserveConfig,
+    },
+
+    // 23
+    /// Apply pending reserve configs once their effective slot is reached,
+    /// clearing the pending reserve config account for reuse.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Pending reserve config account.
+    ///   1. `[writable]` Reserve account.
+    ///   2. `[]` Lending market account.
+    ///   3. `[]` Clock sysvar.
+    ApplyReserveConfig,
+
+    // 24
+    /// Cancel pending reserve configs, clearing the pending reserve config
+    /// account for reuse.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Pending reserve config account.
+    ///   1. `[]` Lending market account.
+    ///   2. `[signer]` Lending market owner or guardian.
+    CancelReserveConfig,
+
+    // 25
+    /// Modify the ReserveConfig parameters of a reserve without the config
+    /// timelock, when the new parameters only tighten reserve risk: lower
+    /// loan to value ratios, tighter caps, disabled borrowing or a pause.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Reserve account.
+    ///   1. `[]` Lending market account.
+    ///   2. `[signer]` Lending market owner or guardian.
+    TightenReserveConfig {
+        /// Reserve configuration updated values
+        new_config: ReserveConfig,
+    },
+
+    // 26
+    /// Apply the pending owner, config timelock and liquidation close factor
+    /// of a lending market once their slots are reached.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Lending market account.
+    ///   1. `[]` Clock sysvar.
+    ApplyLendingMarketChanges,
 }
 
 impl LendingInstruction {
@@ -416,6 +674,43 @@ impl LendingInstruction {
                 let new_config = Self::unpack_reserve_config(rest)?;
                 Self::ModifyReserveConfig { new_config }
             }
+            15 => {
+                let (liquidity_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::WithdrawProtocolFees { liquidity_amount }
+            }
+            16 => {
+                let (liquidation_close_factor, _rest) = Self::unpack_u8(rest)?;
+                Self::SetLiquidationCloseFactor {
+                    liquidation_close_factor,
+                }
+            }
+            17 => Self::WriteOffBadDebt,
+            18 => Self::ExtendObligation,
+            19 => {
+                let (start_index, _rest) = Self::unpack_u8(rest)?;
+                Self::RefreshObligationChunk { start_index }
+            }
+            20 => {
+                let (guardian, _rest) = Self::unpack_pubkey(rest)?;
+                Self::SetLendingMarketGuardian { guardian }
+            }
+            21 => {
+                let (config_timelock_slots, _rest) = Self::unpack_u64(rest)?;
+                Self::SetConfigTimelock {
+                    config_timelock_slots,
+                }
+            }
+            22 => {
+                let new_config = Self::unpack_reserve_config(rest)?;
+                Self::QueueReserveConfig { new_config }
+            }
+            23 => Self::ApplyReserveConfig,
+            24 => Self::CancelReserveConfig,
+            25 => {
+                let new_config = Self::unpack_reserve_config(rest)?;
+                Self::TightenReserveConfig { new_config }
+            }
+            26 => Self::ApplyLendingMarketChanges,
             _ => {
                 msg!("Instruction cannot be unpacked");
                 return Err(LendingError::InstructionUnpackError.into());
@@ -485,24 +780,176 @@ impl LendingInstruction {
         let (max_borrow_rate, rest) = Self::unpack_u8(rest)?;
         let (borrow_fee_wad, rest) = Self::unpack_u64(rest)?;
         let (flash_loan_fee_wad, rest) = Self::unpack_u64(rest)?;
-        let (host_fee_percentage, _rest) = Self::unpack_u8(rest)?;
+        let (host_fee_percentage, rest) = Self::unpack_u8(rest)?;
+        let (interest_rate_model, rest) = Self::unpack_interest_rate_model(rest)?;
+        let (oracle, rest) = Self::unpack_oracle_config(rest)?;
+        let (caps, rest) = Self::unpack_reserve_caps(rest)?;
+        let ((isolated_collateral, elevation_group), rest) = Self::unpack_collateral_modes(rest)?;
+        let (protocol_take_rate, rest) = Self::unpack_protocol_take_rate(rest)?;
+        let (max_liquidation_bonus, rest) = Self::unpack_max_liquidation_bonus(rest)?;
+        let ((borrowing_disabled, paused), _rest) = Self::unpack_reserve_flags(rest)?;
 
         Ok(ReserveConfig {
             optimal_utilization_rate,
             loan_to_value_ratio,
             liquidation_bonus,
+            max_liquidation_bonus,
             liquidation_threshold,
             min_borrow_rate,
             optimal_borrow_rate,
             max_borrow_rate,
+            interest_rate_model,
+            oracle,
+            caps,
+            isolated_collateral,
+            elevation_group,
+            borrowing_disabled,
+            paused,
             fees: ReserveFees {
                 borrow_fee_wad,
                 flash_loan_fee_wad,
                 host_fee_percentage,
+                protocol_take_rate,
             },
         })
     }
 
+    // Configs packed before interest rate models end without one, and use the
+    // kinked model
+    fn unpack_interest_rate_model(
+        input: &[u8],
+    ) -> Result<(InterestRateModel, &[u8]), ProgramError> {
+        if input.is_empty() {
+            return Ok((InterestRateModel::Kinked, input));
+        }
+        if input.len() < INTEREST_RATE_MODEL_LEN {
+            msg!("Interest rate model cannot be unpacked");
+            return Err(LendingError::InstructionUnpackError.into());
+        }
+        let (bytes, rest) = input.split_at(INTEREST_RATE_MODEL_LEN);
+        let interest_rate_model = InterestRateModel::unpack_from_slice(
+            bytes
+                .try_into()
+                .map_err(|_| LendingError::InstructionUnpackError)?,
+        )
+        .map_err(|_| LendingError::InstructionUnpackError)?;
+        Ok((interest_rate_model, rest))
+    }
+
+    // Configs packed before oracle configs end without one, and read a Pyth
+    // oracle with the default checks
+    fn unpack_oracle_config(input: &[u8]) -> Result<(OracleConfig, &[u8]), ProgramError> {
+        if input.is_empty() {
+            return Ok((OracleConfig::default(), input));
+        }
+        if input.len() < ORACLE_CONFIG_LEN {
+            msg!("Oracle config cannot be unpacked");
+            return Err(LendingError::InstructionUnpackError.into());
+        }
+        let (bytes, rest) = input.split_at(ORACLE_CONFIG_LEN);
+        let oracle_config = OracleConfig::unpack_from_slice(
+            bytes
+                .try_into()
+                .map_err(|_| LendingError::InstructionUnpackError)?,
+        )
+        .map_err(|_| LendingError::InstructionUnpackError)?;
+        Ok((oracle_config, rest))
+    }
+
+    // Configs packed before reserve caps end without them, and are unlimited
+    fn unpack_reserve_caps(input: &[u8]) -> Result<(ReserveCaps, &[u8]), ProgramError> {
+        if input.is_empty() {
+            return Ok((ReserveCaps::default(), input));
+        }
+        let (deposit_limit, rest) = Self::unpack_u64(input)?;
+        let (borrow_limit, rest) = Self::unpack_u64(rest)?;
+        let (deposit_value_limit, rest) = Self::unpack_u64(rest)?;
+        let (borrow_value_limit, rest) = Self::unpack_u64(rest)?;
+        Ok((
+            ReserveCaps {
+                deposit_limit,
+                borrow_limit,
+                deposit_value_limit,
+                borrow_value_limit,
+            },
+            rest,
+        ))
+    }
+
+    // Configs packed before isolated collateral and elevation groups end
+    // without them, and use neither
+    fn unpack_collateral_modes(
+        input: &[u8],
+    ) -> Result<((bool, ElevationGroup), &[u8]), ProgramError> {
+        if input.is_empty() {
+            return Ok(((false, ElevationGroup::default()), input));
+        }
+        let (isolated_collateral, rest) = Self::unpack_u8(input)?;
+        let isolated_collateral = match isolated_collateral {
+            0 => false,
+            1 => true,
+            _ => {
+                msg!("Isolated collateral flag cannot be unpacked");
+                return Err(LendingError::InstructionUnpackError.into());
+            }
+        };
+        let (id, rest) = Self::unpack_u8(rest)?;
+        let (loan_to_value_ratio, rest) = Self::unpack_u8(rest)?;
+        let (liquidation_threshold, rest) = Self::unpack_u8(rest)?;
+        Ok((
+            (
+                isolated_collateral,
+                ElevationGroup {
+                    id,
+                    loan_to_value_ratio,
+                    liquidation_threshold,
+                },
+            ),
+            rest,
+        ))
+    }
+
+    // Configs packed before protocol fees end without a take rate, and keep
+    // all interest for depositors
+    fn unpack_protocol_take_rate(input: &[u8]) -> Result<(u8, &[u8]), ProgramError> {
+        if input.is_empty() {
+            return Ok((0, input));
+        }
+        Self::unpack_u8(input)
+    }
+
+    // Configs packed before dynamic liquidation bonuses end without a max
+    // bonus, and keep a static liquidation bonus
+    fn unpack_max_liquidation_bonus(input: &[u8]) -> Result<(u8, &[u8]), ProgramError> {
+        if input.is_empty() {
+            return Ok((0, input));
+        }
+        Self::unpack_u8(input)
+    }
+
+    // Configs packed before borrowing could be disabled or reserves paused end
+    // without the flags, and allow both
+    fn unpack_reserve_flags(input: &[u8]) -> Result<((bool, bool), &[u8]), ProgramError> {
+        if input.is_empty() {
+            return Ok(((false, false), input));
+        }
+        let (borrowing_disabled, rest) = Self::unpack_bool(input)?;
+        let (paused, rest) = Self::unpack_bool(rest)?;
+        Ok(((borrowing_disabled, paused), rest))
+    }
+
+    fn unpack_bool(input: &[u8]) -> Result<(bool, &[u8]), ProgramError> {
+        let (value, rest) = Self::unpack_u8(input)?;
+        match value {
+            0 => Ok((false, rest)),
+            1 => Ok((true, rest)),
+            _ => {
+                msg!("Boolean cannot be unpacked");
+                Err(LendingError::InstructionUnpackError.into())
+            }
+        }
+    }
+
     /// Packs a [LendingInstruction](enum.LendingInstruction.html) into a byte
     /// buffer.
     pub fn pack(&self) -> Vec<u8> {
@@ -577,6 +1024,53 @@ impl LendingInstruction {
                 buf.push(14);
                 Self::extend_buffer_from_reserve_config(&mut buf, &new_config);
             }
+            Self::WithdrawProtocolFees { liquidity_amount } => {
+                buf.push(15);
+                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+            }
+            Self::SetLiquidationCloseFactor {
+                liquidation_close_factor,
+            } => {
+                buf.push(16);
+                buf.extend_from_slice(&liquidation_close_factor.to_le_bytes());
+            }
+            Self::WriteOffBadDebt => {
+                buf.push(17);
+            }
+            Self::ExtendObligation => {
+                buf.push(18);
+            }
+            Self::RefreshObligationChunk { start_index } => {
+                buf.push(19);
+                buf.extend_from_slice(&start_index.to_le_bytes());
+            }
+            Self::SetLendingMarketGuardian { guardian } => {
+                buf.push(20);
+                buf.extend_from_slice(guardian.as_ref());
+            }
+            Self::SetConfigTimelock {
+                config_timelock_slots,
+            } => {
+                buf.push(21);
+                buf.extend_from_slice(&config_timelock_slots.to_le_bytes());
+            }
+            Self::QueueReserveConfig { new_config } => {
+                buf.push(22);
+                Self::extend_buffer_from_reserve_config(&mut buf, &new_config);
+            }
+            Self::ApplyReserveConfig => {
+                buf.push(23);
+            }
+            Self::CancelReserveConfig => {
+                buf.push(24);
+            }
+            Self::TightenReserveConfig { new_config } => {
+                buf.push(25);
+                Self::extend_buffer_from_reserve_config(&mut buf, &new_config);
+            }
+            Self::ApplyLendingMarketChanges => {
+                buf.push(26);
+            }
         }
         buf
     }
@@ -593,6 +1087,26 @@ impl LendingInstruction {
         buf.extend_from_slice(&config.fees.borrow_fee_wad.to_le_bytes());
         buf.extend_from_slice(&config.fees.flash_loan_fee_wad.to_le_bytes());
         buf.extend_from_slice(&config.fees.host_fee_percentage.to_le_bytes());
+        let mut interest_rate_model = [0; INTEREST_RATE_MODEL_LEN];
+        config
+            .interest_rate_model
+            .pack_into_slice(&mut interest_rate_model);
+        buf.extend_from_slice(&interest_rate_model);
+        let mut oracle_config = [0; ORACLE_CONFIG_LEN];
+        config.oracle.pack_into_slice(&mut oracle_config);
+        buf.extend_from_slice(&oracle_config);
+        buf.extend_from_slice(&config.caps.deposit_limit.to_le_bytes());
+        buf.extend_from_slice(&config.caps.borrow_limit.to_le_bytes());
+        buf.extend_from_slice(&config.caps.deposit_value_limit.to_le_bytes());
+        buf.extend_from_slice(&config.caps.borrow_value_limit.to_le_bytes());
+        buf.extend_from_slice(&(config.isolated_collateral as u8).to_le_bytes());
+        buf.extend_from_slice(&config.elevation_group.id.to_le_bytes());
+        buf.extend_from_slice(&config.elevation_group.loan_to_value_ratio.to_le_bytes());
+        buf.extend_from_slice(&config.elevation_group.liquidation_threshold.to_le_bytes());
+        buf.extend_from_slice(&config.fees.protocol_take_rate.to_le_bytes());
+        buf.extend_from_slice(&config.max_liquidation_bonus.to_le_bytes());
+        buf.extend_from_slice(&(config.borrowing_disabled as u8).to_le_bytes());
+        buf.extend_from_slice(&(config.paused as u8).to_le_bytes());
     }
 }
 
@@ -632,6 +1146,7 @@ pub fn set_lending_market_owner(
         accounts: vec![
             AccountMeta::new(lending_market_pubkey, false),
             AccountMeta::new_readonly(lending_market_owner, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
         ],
         data: LendingInstruction::SetLendingMarketOwner { new_owner }.pack(),
     }
@@ -651,11 +1166,12 @@ pub fn init_reserve(
     reserve_liquidity_fee_receiver_pubkey: Pubkey,
     reserve_collateral_mint_pubkey: Pubkey,
     reserve_collateral_supply_pubkey: Pubkey,
-    pyth_product_pubkey: Pubkey,
-    pyth_price_pubkey: Pubkey,
+    oracle_product_pubkey: Pubkey,
+    oracle_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
     lending_market_owner_pubkey: Pubkey,
     user_transfer_authority_pubkey: Pubkey,
+    liquidity_token_program_id: Pubkey,
 ) -> Instruction {
     let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
         &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
@@ -670,8 +1186,8 @@ pub fn init_reserve(
         AccountMeta::new(reserve_liquidity_fee_receiver_pubkey, false),
         AccountMeta::new(reserve_collateral_mint_pubkey, false),
         AccountMeta::new(reserve_collateral_supply_pubkey, false),
-        AccountMeta::new_readonly(pyth_product_pubkey, false),
-        AccountMeta::new_readonly(pyth_price_pubkey, false),
+        AccountMeta::new_readonly(oracle_product_pubkey, false),
+        AccountMeta::new_readonly(oracle_pubkey, false),
         AccountMeta::new_readonly(lending_market_pubkey, false),
         AccountMeta::new_readonly(lending_market_authority_pubkey, false),
         AccountMeta::new_readonly(lending_market_owner_pubkey, true),
@@ -679,6 +1195,7 @@ pub fn init_reserve(
         AccountMeta::new_readonly(sysvar::clock::id(), false),
         AccountMeta::new_readonly(sysvar::rent::id(), false),
         AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(liquidity_token_program_id, false),
     ];
     Instruction {
         program_id,
@@ -709,6 +1226,24 @@ pub fn refresh_reserve(
     }
 }
 
+/// Creates a `RefreshReserve` instruction for reserves with swap pool or
+/// secondary oracles, taking the accounts following the clock sysvar
+pub fn refresh_reserve_with_oracle_accounts(
+    program_id: Pubkey,
+    reserve_pubkey: Pubkey,
+    reserve_liquidity_oracle_pubkey: Pubkey,
+    additional_oracle_pubkeys: &[Pubkey],
+) -> Instruction {
+    let mut instruction =
+        refresh_reserve(program_id, reserve_pubkey, reserve_liquidity_oracle_pubkey);
+    instruction.accounts.extend(
+        additional_oracle_pubkeys
+            .iter()
+            .map(|pubkey| AccountMeta::new_readonly(*pubkey, false)),
+    );
+    instruction
+}
+
 /// Creates a 'DepositReserveLiquidity' instruction.
 #[allow(clippy::too_many_arguments)]
 pub fn deposit_reserve_liquidity(
@@ -721,6 +1256,8 @@ pub fn deposit_reserve_liquidity(
     reserve_collateral_mint_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
     user_transfer_authority_pubkey: Pubkey,
+    reserve_liquidity_mint_pubkey: Pubkey,
+    liquidity_token_program_id: Pubkey,
 ) -> Instruction {
     let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
         &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
@@ -739,6 +1276,8 @@ pub fn deposit_reserve_liquidity(
             AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
             AccountMeta::new_readonly(sysvar::clock::id(), false),
             AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(reserve_liquidity_mint_pubkey, false),
+            AccountMeta::new_readonly(liquidity_token_program_id, false),
         ],
         data: LendingInstruction::DepositReserveLiquidity { liquidity_amount }.pack(),
     }
@@ -756,6 +1295,8 @@ pub fn redeem_reserve_collateral(
     reserve_liquidity_supply_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
     user_transfer_authority_pubkey: Pubkey,
+    reserve_liquidity_mint_pubkey: Pubkey,
+    liquidity_token_program_id: Pubkey,
 ) -> Instruction {
     let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
         &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
@@ -774,6 +1315,8 @@ pub fn redeem_reserve_collateral(
             AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
             AccountMeta::new_readonly(sysvar::clock::id(), false),
             AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(reserve_liquidity_mint_pubkey, false),
+            AccountMeta::new_readonly(liquidity_token_program_id, false),
         ],
         data: LendingInstruction::RedeemReserveCollateral { collateral_amount }.pack(),
     }
@@ -900,13 +1443,15 @@ pub fn borrow_obligation_liquidity(
     obligation_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
     obligation_owner_pubkey: Pubkey,
+    borrow_reserve_liquidity_mint_pubkey: Pubkey,
+    liquidity_token_program_id: Pubkey,
     host_fee_receiver_pubkey: Option<Pubkey>,
 ) -> Instruction {
     let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
         &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
         &program_id,
     );
-    let mut accounts = vec![
+    let accounts = vec![
         AccountMeta::new(source_liquidity_pubkey, false),
         AccountMeta::new(destination_liquidity_pubkey, false),
         AccountMeta::new(borrow_reserve_pubkey, false),
@@ -917,10 +1462,15 @@ pub fn borrow_obligation_liquidity(
         AccountMeta::new_readonly(obligation_owner_pubkey, true),
         AccountMeta::new_readonly(sysvar::clock::id(), false),
         AccountMeta::new_readonly(spl_token::id(), false),
+        // the fee receiver stands in for a missing host fee receiver, since
+        // the liquidity mint and token program follow it
+        AccountMeta::new(
+            host_fee_receiver_pubkey.unwrap_or(borrow_reserve_liquidity_fee_receiver_pubkey),
+            false,
+        ),
+        AccountMeta::new_readonly(borrow_reserve_liquidity_mint_pubkey, false),
+        AccountMeta::new_readonly(liquidity_token_program_id, false),
     ];
-    if let Some(host_fee_receiver_pubkey) = host_fee_receiver_pubkey {
-        accounts.push(AccountMeta::new(host_fee_receiver_pubkey, false));
-    }
     let slippage_limit = slippage_limit.unwrap_or(0);
     Instruction {
         program_id,
@@ -944,6 +1494,8 @@ pub fn repay_obligation_liquidity(
     obligation_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
     user_transfer_authority_pubkey: Pubkey,
+    repay_reserve_liquidity_mint_pubkey: Pubkey,
+    liquidity_token_program_id: Pubkey,
 ) -> Instruction {
     Instruction {
         program_id,
@@ -956,6 +1508,8 @@ pub fn repay_obligation_liquidity(
             AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
             AccountMeta::new_readonly(sysvar::clock::id(), false),
             AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(repay_reserve_liquidity_mint_pubkey, false),
+            AccountMeta::new_readonly(liquidity_token_program_id, false),
         ],
         data: LendingInstruction::RepayObligationLiquidity { liquidity_amount }.pack(),
     }
@@ -975,6 +1529,8 @@ pub fn liquidate_obligation(
     obligation_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
     user_transfer_authority_pubkey: Pubkey,
+    repay_reserve_liquidity_mint_pubkey: Pubkey,
+    repay_reserve_liquidity_token_program_id: Pubkey,
 ) -> Instruction {
     let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
         &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
@@ -995,6 +1551,8 @@ pub fn liquidate_obligation(
             AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
             AccountMeta::new_readonly(sysvar::clock::id(), false),
             AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(repay_reserve_liquidity_mint_pubkey, false),
+            AccountMeta::new_readonly(repay_reserve_liquidity_token_program_id, false),
         ],
         data: LendingInstruction::LiquidateObligation { liquidity_amount }.pack(),
     }
@@ -1011,6 +1569,8 @@ pub fn flash_loan(
     reserve_liquidity_fee_receiver_pubkey: Pubkey,
     host_fee_receiver_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
+    reserve_liquidity_mint_pubkey: Pubkey,
+    liquidity_token_program_id: Pubkey,
     flash_loan_receiver_program_id: Pubkey,
     flash_loan_receiver_program_accounts: Vec<AccountMeta>,
 ) -> Instruction {
@@ -1026,10 +1586,14 @@ pub fn flash_loan(
         AccountMeta::new(host_fee_receiver_pubkey, false),
         AccountMeta::new_readonly(lending_market_pubkey, false),
         AccountMeta::new_readonly(lending_market_authority_pubkey, false),
-        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(liquidity_token_program_id, false),
         AccountMeta::new_readonly(flash_loan_receiver_program_id, false),
     ];
     accounts.extend(flash_loan_receiver_program_accounts);
+    accounts.push(AccountMeta::new_readonly(
+        reserve_liquidity_mint_pubkey,
+        false,
+    ));
     Instruction {
         program_id,
         accounts,
@@ -1058,52 +1622,364 @@ pub fn modify_reserve_config(
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Creates a 'ModifyReserveConfig` instruction which also replaces the reserve
+/// liquidity oracle account
+pub fn modify_reserve_config_and_oracle(
+    program_id: Pubkey,
+    config: ReserveConfig,
+    reserve_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner_pubkey: Pubkey,
+    oracle_product_pubkey: Pubkey,
+    oracle_pubkey: Pubkey,
+) -> Instruction {
+    let mut instruction = modify_reserve_config(
+        program_id,
+        config,
+        reserve_pubkey,
+        lending_market_pubkey,
+        lending_market_owner_pubkey,
+    );
+    instruction.accounts.extend([
+        AccountMeta::new_readonly(oracle_product_pubkey, false),
+        AccountMeta::new_readonly(oracle_pubkey, false),
+    ]);
+    instruction
+}
 
-    #[test]
-    fn test_init_lending_market() {
-        let program_id = Pubkey::new_unique();
-        let owner = Pubkey::new_unique();
-        let lending_market_pubkey = Pubkey::new_unique();
-        let oracle_program_id = Pubkey::new_unique();
-        let currency = "USD";
-        let mut quote_currency = [0u8; 32];
-        quote_currency[0..currency.len()].clone_from_slice(currency.as_bytes());
-        let instruction = init_lending_market(
-            program_id,
-            owner,
-            quote_currency,
-            lending_market_pubkey,
-            oracle_program_id,
-        );
-        assert_eq!(instruction.program_id, program_id);
-        assert_eq!(instruction.accounts.len(), 4);
-        assert_eq!(
-            instruction.data,
-            LendingInstruction::InitLendingMarket {
-                owner,
-                quote_currency,
-            }
-            .pack()
-        );
+/// Creates a 'WithdrawProtocolFees' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn withdraw_protocol_fees(
+    program_id: Pubkey,
+    liquidity_amount: u64,
+    reserve_pubkey: Pubkey,
+    reserve_liquidity_supply_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner_pubkey: Pubkey,
+    reserve_liquidity_mint_pubkey: Pubkey,
+    liquidity_token_program_id: Pubkey,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(reserve_pubkey, false),
+            AccountMeta::new(reserve_liquidity_supply_pubkey, false),
+            AccountMeta::new(destination_liquidity_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+            AccountMeta::new_readonly(lending_market_owner_pubkey, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(reserve_liquidity_mint_pubkey, false),
+            AccountMeta::new_readonly(liquidity_token_program_id, false),
+        ],
+        data: LendingInstruction::WithdrawProtocolFees { liquidity_amount }.pack(),
     }
+}
 
-    #[test]
-    fn test_set_lending_market_owner() {
-        let program_id = Pubkey::new_unique();
-        let lending_market_pubkey = Pubkey::new_unique();
-        let lending_market_owner = Pubkey::new_unique();
-        let new_owner = Pubkey::new_unique();
-        let instruction = set_lending_market_owner(
-            program_id,
-            lending_market_pubkey,
-            lending_market_owner,
-            new_owner,
-        );
-        assert_eq!(instruction.program_id, program_id);
-        assert_eq!(instruction.accounts.len(), 2);
+/// Creates a 'SetLiquidationCloseFactor' instruction.
+pub fn set_liquidation_close_factor(
+    program_id: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner: Pubkey,
+    liquidation_close_factor: u8,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_owner, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+        data: LendingInstruction::SetLiquidationCloseFactor {
+            liquidation_close_factor,
+        }
+        .pack(),
+    }
+}
+
+/// Creates a 'WriteOffBadDebt' instruction.
+pub fn write_off_bad_debt(
+    program_id: Pubkey,
+    reserve_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(reserve_pubkey, false),
+            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+        data: LendingInstruction::WriteOffBadDebt.pack(),
+    }
+}
+
+/// Creates an 'ExtendObligation' instruction.
+pub fn extend_obligation(
+    program_id: Pubkey,
+    obligation_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+    payer_pubkey: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new_readonly(obligation_owner_pubkey, true),
+            AccountMeta::new(payer_pubkey, true),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: LendingInstruction::ExtendObligation.pack(),
+    }
+}
+
+/// Creates a 'RefreshObligationChunk' instruction.
+pub fn refresh_obligation_chunk(
+    program_id: Pubkey,
+    obligation_pubkey: Pubkey,
+    start_index: u8,
+    reserve_pubkeys: Vec<Pubkey>,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(obligation_pubkey, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+    accounts.extend(
+        reserve_pubkeys
+            .into_iter()
+            .map(|pubkey| AccountMeta::new_readonly(pubkey, false)),
+    );
+    Instruction {
+        program_id,
+        accounts,
+        data: LendingInstruction::RefreshObligationChunk { start_index }.pack(),
+    }
+}
+
+/// Creates a 'SetLendingMarketGuardian' instruction.
+pub fn set_lending_market_guardian(
+    program_id: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner: Pubkey,
+    guardian: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_owner, true),
+        ],
+        data: LendingInstruction::SetLendingMarketGuardian { guardian }.pack(),
+    }
+}
+
+/// Creates a 'SetConfigTimelock' instruction.
+pub fn set_config_timelock(
+    program_id: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner: Pubkey,
+    config_timelock_slots: u64,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_owner, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+        data: LendingInstruction::SetConfigTimelock {
+            config_timelock_slots,
+        }
+        .pack(),
+    }
+}
+
+/// Creates a 'QueueReserveConfig' instruction.
+pub fn queue_reserve_config(
+    program_id: Pubkey,
+    config: ReserveConfig,
+    pending_reserve_config_pubkey: Pubkey,
+    reserve_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner_pubkey: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(pending_reserve_config_pubkey, false),
+            AccountMeta::new_readonly(reserve_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_owner_pubkey, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+        data: LendingInstruction::QueueReserveConfig { new_config: config }.pack(),
+    }
+}
+
+/// Creates a 'QueueReserveConfig' instruction which also replaces the reserve
+/// liquidity oracle account
+#[allow(clippy::too_many_arguments)]
+pub fn queue_reserve_config_and_oracle(
+    program_id: Pubkey,
+    config: ReserveConfig,
+    pending_reserve_config_pubkey: Pubkey,
+    reserve_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner_pubkey: Pubkey,
+    oracle_product_pubkey: Pubkey,
+    oracle_pubkey: Pubkey,
+) -> Instruction {
+    let mut instruction = queue_reserve_config(
+        program_id,
+        config,
+        pending_reserve_config_pubkey,
+        reserve_pubkey,
+        lending_market_pubkey,
+        lending_market_owner_pubkey,
+    );
+    instruction.accounts.extend([
+        AccountMeta::new_readonly(oracle_product_pubkey, false),
+        AccountMeta::new_readonly(oracle_pubkey, false),
+    ]);
+    instruction
+}
+
+/// Creates an 'ApplyReserveConfig' instruction.
+pub fn apply_reserve_config(
+    program_id: Pubkey,
+    pending_reserve_config_pubkey: Pubkey,
+    reserve_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(pending_reserve_config_pubkey, false),
+            AccountMeta::new(reserve_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+        data: LendingInstruction::ApplyReserveConfig.pack(),
+    }
+}
+
+/// Creates a 'CancelReserveConfig' instruction.
+pub fn cancel_reserve_config(
+    program_id: Pubkey,
+    pending_reserve_config_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    risk_authority_pubkey: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(pending_reserve_config_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(risk_authority_pubkey, true),
+        ],
+        data: LendingInstruction::CancelReserveConfig.pack(),
+    }
+}
+
+/// Creates a 'TightenReserveConfig' instruction.
+pub fn tighten_reserve_config(
+    program_id: Pubkey,
+    config: ReserveConfig,
+    reserve_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    risk_authority_pubkey: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(reserve_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(risk_authority_pubkey, true),
+        ],
+        data: LendingInstruction::TightenReserveConfig { new_config: config }.pack(),
+    }
+}
+
+/// Creates an 'ApplyLendingMarketChanges' instruction.
+pub fn apply_lending_market_changes(
+    program_id: Pubkey,
+    lending_market_pubkey: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(lending_market_pubkey, false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ],
+        data: LendingInstruction::ApplyLendingMarketChanges.pack(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::state::{OracleSource, RateCurve, RateCurvePoint, SecondaryOracle},
+    };
+
+    const RESERVE_CAPS_LEN: usize = 32;
+    const COLLATERAL_MODES_LEN: usize = 4;
+    const PROTOCOL_TAKE_RATE_LEN: usize = 1;
+    const MAX_LIQUIDATION_BONUS_LEN: usize = 1;
+    const RESERVE_FLAGS_LEN: usize = 2;
+
+    #[test]
+    fn test_init_lending_market() {
+        let program_id = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let lending_market_pubkey = Pubkey::new_unique();
+        let oracle_program_id = Pubkey::new_unique();
+        let currency = "USD";
+        let mut quote_currency = [0u8; 32];
+        quote_currency[0..currency.len()].clone_from_slice(currency.as_bytes());
+        let instruction = init_lending_market(
+            program_id,
+            owner,
+            quote_currency,
+            lending_market_pubkey,
+            oracle_program_id,
+        );
+        assert_eq!(instruction.program_id, program_id);
+        assert_eq!(instruction.accounts.len(), 4);
+        assert_eq!(
+            instruction.data,
+            LendingInstruction::InitLendingMarket {
+                owner,
+                quote_currency,
+            }
+            .pack()
+        );
+    }
+
+    #[test]
+    fn test_set_lending_market_owner() {
+        let program_id = Pubkey::new_unique();
+        let lending_market_pubkey = Pubkey::new_unique();
+        let lending_market_owner = Pubkey::new_unique();
+        let new_owner = Pubkey::new_unique();
+        let instruction = set_lending_market_owner(
+            program_id,
+            lending_market_pubkey,
+            lending_market_owner,
+            new_owner,
+        );
+        assert_eq!(instruction.program_id, program_id);
+        assert_eq!(instruction.accounts.len(), 3);
         assert_eq!(
             instruction.data,
             LendingInstruction::SetLendingMarketOwner { new_owner }.pack()
@@ -1118,14 +1994,23 @@ mod tests {
             optimal_utilization_rate: 50,
             loan_to_value_ratio: 1,
             liquidation_bonus: 10,
+            max_liquidation_bonus: 0,
             liquidation_threshold: 5,
             min_borrow_rate: 2,
             optimal_borrow_rate: 4,
             max_borrow_rate: 10,
+            interest_rate_model: InterestRateModel::Kinked,
+            oracle: OracleConfig::default(),
+            caps: ReserveCaps::default(),
+            isolated_collateral: false,
+            elevation_group: ElevationGroup::default(),
+            borrowing_disabled: false,
+            paused: false,
             fees: ReserveFees {
                 borrow_fee_wad: 1,
                 flash_loan_fee_wad: 3,
                 host_fee_percentage: 1,
+                protocol_take_rate: 0,
             },
         };
         let source_liquidity_pubkey = Pubkey::new_unique();
@@ -1158,9 +2043,10 @@ mod tests {
             lending_market_pubkey,
             lending_market_owner_pubkey,
             user_transfer_authority_pubkey,
+            spl_token_2022::id(),
         );
         assert_eq!(instruction.program_id, program_id);
-        assert_eq!(instruction.accounts.len(), 17);
+        assert_eq!(instruction.accounts.len(), 18);
         assert_eq!(
             instruction.data,
             LendingInstruction::InitReserve {
@@ -1194,6 +2080,7 @@ mod tests {
         let reserve_collateral_mint_pubkey = Pubkey::new_unique();
         let lending_market_pubkey = Pubkey::new_unique();
         let user_transfer_authority_pubkey = Pubkey::new_unique();
+        let reserve_liquidity_mint_pubkey = Pubkey::new_unique();
         let instruction = deposit_reserve_liquidity(
             program_id,
             liquidity_amount,
@@ -1204,9 +2091,11 @@ mod tests {
             reserve_collateral_mint_pubkey,
             lending_market_pubkey,
             user_transfer_authority_pubkey,
+            reserve_liquidity_mint_pubkey,
+            spl_token_2022::id(),
         );
         assert_eq!(instruction.program_id, program_id);
-        assert_eq!(instruction.accounts.len(), 10);
+        assert_eq!(instruction.accounts.len(), 12);
         assert_eq!(
             instruction.data,
             LendingInstruction::DepositReserveLiquidity { liquidity_amount }.pack()
@@ -1224,6 +2113,7 @@ mod tests {
         let reserve_liquidity_supply_pubkey = Pubkey::new_unique();
         let lending_market_pubkey = Pubkey::new_unique();
         let user_transfer_authority_pubkey = Pubkey::new_unique();
+        let reserve_liquidity_mint_pubkey = Pubkey::new_unique();
         let instruction = redeem_reserve_collateral(
             program_id,
             collateral_amount,
@@ -1234,9 +2124,11 @@ mod tests {
             reserve_liquidity_supply_pubkey,
             lending_market_pubkey,
             user_transfer_authority_pubkey,
+            reserve_liquidity_mint_pubkey,
+            spl_token::id(),
         );
         assert_eq!(instruction.program_id, program_id);
-        assert_eq!(instruction.accounts.len(), 10);
+        assert_eq!(instruction.accounts.len(), 12);
         assert_eq!(
             instruction.data,
             LendingInstruction::RedeemReserveCollateral { collateral_amount }.pack()
@@ -1343,6 +2235,7 @@ mod tests {
         let obligation_pubkey = Pubkey::new_unique();
         let lending_market_pubkey = Pubkey::new_unique();
         let obligation_owner_pubkey = Pubkey::new_unique();
+        let borrow_reserve_liquidity_mint_pubkey = Pubkey::new_unique();
         let host_fee_receiver_pubkey = Some(Pubkey::new_unique());
         let instruction = borrow_obligation_liquidity(
             program_id,
@@ -1355,10 +2248,12 @@ mod tests {
             obligation_pubkey,
             lending_market_pubkey,
             obligation_owner_pubkey,
+            borrow_reserve_liquidity_mint_pubkey,
+            spl_token::id(),
             host_fee_receiver_pubkey,
         );
         assert_eq!(instruction.program_id, program_id);
-        assert_eq!(instruction.accounts.len(), 11);
+        assert_eq!(instruction.accounts.len(), 13);
         assert_eq!(
             instruction.data,
             LendingInstruction::BorrowObligationLiquidity {
@@ -1379,6 +2274,7 @@ mod tests {
         let obligation_pubkey = Pubkey::new_unique();
         let lending_market_pubkey = Pubkey::new_unique();
         let user_transfer_authority_pubkey = Pubkey::new_unique();
+        let repay_reserve_liquidity_mint_pubkey = Pubkey::new_unique();
         let instruction = repay_obligation_liquidity(
             program_id,
             liquidity_amount,
@@ -1388,9 +2284,11 @@ mod tests {
             obligation_pubkey,
             lending_market_pubkey,
             user_transfer_authority_pubkey,
+            repay_reserve_liquidity_mint_pubkey,
+            spl_token::id(),
         );
         assert_eq!(instruction.program_id, program_id);
-        assert_eq!(instruction.accounts.len(), 8);
+        assert_eq!(instruction.accounts.len(), 10);
         assert_eq!(
             instruction.data,
             LendingInstruction::RepayObligationLiquidity { liquidity_amount }.pack()
@@ -1410,6 +2308,7 @@ mod tests {
         let obligation_pubkey = Pubkey::new_unique();
         let lending_market_pubkey = Pubkey::new_unique();
         let user_transfer_authority_pubkey = Pubkey::new_unique();
+        let repay_reserve_liquidity_mint_pubkey = Pubkey::new_unique();
         let instruction = liquidate_obligation(
             program_id,
             liquidity_amount,
@@ -1422,9 +2321,11 @@ mod tests {
             obligation_pubkey,
             lending_market_pubkey,
             user_transfer_authority_pubkey,
+            repay_reserve_liquidity_mint_pubkey,
+            spl_token::id(),
         );
         assert_eq!(instruction.program_id, program_id);
-        assert_eq!(instruction.accounts.len(), 12);
+        assert_eq!(instruction.accounts.len(), 14);
         assert_eq!(
             instruction.data,
             LendingInstruction::LiquidateObligation { liquidity_amount }.pack()
@@ -1441,6 +2342,7 @@ mod tests {
         let reserve_liquidity_fee_receiver_pubkey = Pubkey::new_unique();
         let host_fee_receiver_pubkey = Pubkey::new_unique();
         let lending_market_pubkey = Pubkey::new_unique();
+        let reserve_liquidity_mint_pubkey = Pubkey::new_unique();
         let flash_loan_receiver_program_id = Pubkey::new_unique();
         let account_meta = AccountMeta {
             pubkey: Pubkey::new_unique(),
@@ -1457,11 +2359,13 @@ mod tests {
             reserve_liquidity_fee_receiver_pubkey,
             host_fee_receiver_pubkey,
             lending_market_pubkey,
+            reserve_liquidity_mint_pubkey,
+            spl_token::id(),
             flash_loan_receiver_program_id,
             flash_loan_receiver_program_accounts,
         );
         assert_eq!(instruction.program_id, program_id);
-        assert_eq!(instruction.accounts.len(), 10);
+        assert_eq!(instruction.accounts.len(), 11);
         assert_eq!(
             instruction.data,
             LendingInstruction::FlashLoan { amount }.pack()
@@ -1475,14 +2379,23 @@ mod tests {
             optimal_utilization_rate: 60,
             loan_to_value_ratio: 1,
             liquidation_bonus: 10,
+            max_liquidation_bonus: 0,
             liquidation_threshold: 5,
             min_borrow_rate: 2,
             optimal_borrow_rate: 4,
             max_borrow_rate: 10,
+            interest_rate_model: InterestRateModel::Kinked,
+            oracle: OracleConfig::default(),
+            caps: ReserveCaps::default(),
+            isolated_collateral: false,
+            elevation_group: ElevationGroup::default(),
+            borrowing_disabled: false,
+            paused: false,
             fees: ReserveFees {
                 borrow_fee_wad: 1,
                 flash_loan_fee_wad: 3,
                 host_fee_percentage: 1,
+                protocol_take_rate: 0,
             },
         };
         let reserve_pubkey = Pubkey::new_unique();
@@ -1502,4 +2415,622 @@ mod tests {
             LendingInstruction::ModifyReserveConfig { new_config: config }.pack()
         );
     }
+
+    #[test]
+    fn test_reserve_config_interest_rate_model() {
+        let config = ReserveConfig {
+            optimal_utilization_rate: 60,
+            loan_to_value_ratio: 1,
+            liquidation_bonus: 10,
+            max_liquidation_bonus: 0,
+            liquidation_threshold: 5,
+            min_borrow_rate: 2,
+            optimal_borrow_rate: 4,
+            max_borrow_rate: 10,
+            interest_rate_model: InterestRateModel::PiecewiseLinear(
+                RateCurve::new(&[
+                    RateCurvePoint {
+                        utilization_bps: 0,
+                        borrow_rate_bps: 25,
+                    },
+                    RateCurvePoint {
+                        utilization_bps: 10_000,
+                        borrow_rate_bps: 40_000,
+                    },
+                ])
+                .unwrap(),
+            ),
+            oracle: OracleConfig::default(),
+            caps: ReserveCaps::default(),
+            isolated_collateral: false,
+            elevation_group: ElevationGroup::default(),
+            borrowing_disabled: false,
+            paused: false,
+            fees: ReserveFees {
+                borrow_fee_wad: 1,
+                flash_loan_fee_wad: 3,
+                host_fee_percentage: 1,
+                protocol_take_rate: 0,
+            },
+        };
+        let instruction = LendingInstruction::ModifyReserveConfig { new_config: config };
+        let packed = instruction.pack();
+        assert_eq!(LendingInstruction::unpack(&packed).unwrap(), instruction);
+
+        // configs packed without an interest rate model use the kinked model
+        let legacy = &packed[..packed.len()
+            - INTEREST_RATE_MODEL_LEN
+            - ORACLE_CONFIG_LEN
+            - RESERVE_CAPS_LEN
+            - COLLATERAL_MODES_LEN
+            - PROTOCOL_TAKE_RATE_LEN
+            - MAX_LIQUIDATION_BONUS_LEN
+            - RESERVE_FLAGS_LEN];
+        assert_eq!(
+            LendingInstruction::unpack(legacy).unwrap(),
+            LendingInstruction::ModifyReserveConfig {
+                new_config: ReserveConfig {
+                    interest_rate_model: InterestRateModel::Kinked,
+                    ..config
+                }
+            }
+        );
+        assert_eq!(
+            LendingInstruction::unpack(
+                &packed[..packed.len()
+                    - PROTOCOL_TAKE_RATE_LEN
+                    - MAX_LIQUIDATION_BONUS_LEN
+                    - RESERVE_FLAGS_LEN
+                    - 1]
+            ),
+            Err(LendingError::InstructionUnpackError.into())
+        );
+    }
+
+    #[test]
+    fn test_reserve_config_oracle() {
+        let config = ReserveConfig {
+            optimal_utilization_rate: 60,
+            loan_to_value_ratio: 1,
+            liquidation_bonus: 10,
+            max_liquidation_bonus: 0,
+            liquidation_threshold: 5,
+            min_borrow_rate: 2,
+            optimal_borrow_rate: 4,
+            max_borrow_rate: 10,
+            interest_rate_model: InterestRateModel::Kinked,
+            oracle: OracleConfig {
+                source: OracleSource::SwapPoolTwap,
+                max_staleness_slots: 10,
+                max_confidence_bps: 0,
+                twap_window_slots: 300,
+                fixed_price_wads: 0,
+                secondary: Some(SecondaryOracle {
+                    source: OracleSource::Pyth,
+                    oracle_pubkey: Pubkey::new_unique(),
+                    max_divergence_bps: 200,
+                }),
+            },
+            caps: ReserveCaps::default(),
+            isolated_collateral: false,
+            elevation_group: ElevationGroup::default(),
+            borrowing_disabled: false,
+            paused: false,
+            fees: ReserveFees {
+                borrow_fee_wad: 1,
+                flash_loan_fee_wad: 3,
+                host_fee_percentage: 1,
+                protocol_take_rate: 0,
+            },
+        };
+        let instruction = LendingInstruction::ModifyReserveConfig { new_config: config };
+        let packed = instruction.pack();
+        assert_eq!(LendingInstruction::unpack(&packed).unwrap(), instruction);
+
+        // configs packed without an oracle config read a pyth oracle
+        let legacy = &packed[..packed.len()
+            - ORACLE_CONFIG_LEN
+            - RESERVE_CAPS_LEN
+            - COLLATERAL_MODES_LEN
+            - PROTOCOL_TAKE_RATE_LEN
+            - MAX_LIQUIDATION_BONUS_LEN
+            - RESERVE_FLAGS_LEN];
+        assert_eq!(
+            LendingInstruction::unpack(legacy).unwrap(),
+            LendingInstruction::ModifyReserveConfig {
+                new_config: ReserveConfig {
+                    oracle: OracleConfig::default(),
+                    ..config
+                }
+            }
+        );
+        assert_eq!(
+            LendingInstruction::unpack(
+                &packed[..packed.len()
+                    - PROTOCOL_TAKE_RATE_LEN
+                    - MAX_LIQUIDATION_BONUS_LEN
+                    - RESERVE_FLAGS_LEN
+                    - 1]
+            ),
+            Err(LendingError::InstructionUnpackError.into())
+        );
+    }
+
+    #[test]
+    fn test_reserve_config_caps() {
+        let config = ReserveConfig {
+            optimal_utilization_rate: 60,
+            loan_to_value_ratio: 1,
+            liquidation_bonus: 10,
+            max_liquidation_bonus: 0,
+            liquidation_threshold: 5,
+            min_borrow_rate: 2,
+            optimal_borrow_rate: 4,
+            max_borrow_rate: 10,
+            interest_rate_model: InterestRateModel::Kinked,
+            oracle: OracleConfig::default(),
+            caps: ReserveCaps {
+                deposit_limit: 1_000_000,
+                borrow_limit: 500_000,
+                deposit_value_limit: 20_000_000,
+                borrow_value_limit: 0,
+            },
+            isolated_collateral: false,
+            elevation_group: ElevationGroup::default(),
+            borrowing_disabled: false,
+            paused: false,
+            fees: ReserveFees {
+                borrow_fee_wad: 1,
+                flash_loan_fee_wad: 3,
+                host_fee_percentage: 1,
+                protocol_take_rate: 0,
+            },
+        };
+        let instruction = LendingInstruction::ModifyReserveConfig { new_config: config };
+        let packed = instruction.pack();
+        assert_eq!(LendingInstruction::unpack(&packed).unwrap(), instruction);
+
+        // configs packed without reserve caps are unlimited
+        let legacy = &packed[..packed.len()
+            - RESERVE_CAPS_LEN
+            - COLLATERAL_MODES_LEN
+            - PROTOCOL_TAKE_RATE_LEN
+            - MAX_LIQUIDATION_BONUS_LEN
+            - RESERVE_FLAGS_LEN];
+        assert_eq!(
+            LendingInstruction::unpack(legacy).unwrap(),
+            LendingInstruction::ModifyReserveConfig {
+                new_config: ReserveConfig {
+                    caps: ReserveCaps::default(),
+                    ..config
+                }
+            }
+        );
+        assert_eq!(
+            LendingInstruction::unpack(
+                &packed[..packed.len()
+                    - PROTOCOL_TAKE_RATE_LEN
+                    - MAX_LIQUIDATION_BONUS_LEN
+                    - RESERVE_FLAGS_LEN
+                    - 1]
+            ),
+            Err(LendingError::InstructionUnpackError.into())
+        );
+    }
+
+    #[test]
+    fn test_reserve_config_collateral_modes() {
+        let config = ReserveConfig {
+            optimal_utilization_rate: 60,
+            loan_to_value_ratio: 50,
+            liquidation_bonus: 10,
+            max_liquidation_bonus: 0,
+            liquidation_threshold: 55,
+            min_borrow_rate: 2,
+            optimal_borrow_rate: 4,
+            max_borrow_rate: 10,
+            interest_rate_model: InterestRateModel::Kinked,
+            oracle: OracleConfig::default(),
+            caps: ReserveCaps::default(),
+            isolated_collateral: true,
+            elevation_group: ElevationGroup {
+                id: 1,
+                loan_to_value_ratio: 90,
+                liquidation_threshold: 95,
+            },
+            borrowing_disabled: false,
+            paused: false,
+            fees: ReserveFees {
+                borrow_fee_wad: 1,
+                flash_loan_fee_wad: 3,
+                host_fee_percentage: 1,
+                protocol_take_rate: 0,
+            },
+        };
+        let instruction = LendingInstruction::ModifyReserveConfig { new_config: config };
+        let mut packed = instruction.pack();
+        assert_eq!(LendingInstruction::unpack(&packed).unwrap(), instruction);
+
+        // configs packed without collateral modes use neither
+        let legacy = &packed[..packed.len()
+            - COLLATERAL_MODES_LEN
+            - PROTOCOL_TAKE_RATE_LEN
+            - MAX_LIQUIDATION_BONUS_LEN
+            - RESERVE_FLAGS_LEN];
+        assert_eq!(
+            LendingInstruction::unpack(legacy).unwrap(),
+            LendingInstruction::ModifyReserveConfig {
+                new_config: ReserveConfig {
+                    isolated_collateral: false,
+                    elevation_group: ElevationGroup::default(),
+                    ..config
+                }
+            }
+        );
+
+        let isolated_collateral_index = packed.len()
+            - COLLATERAL_MODES_LEN
+            - PROTOCOL_TAKE_RATE_LEN
+            - MAX_LIQUIDATION_BONUS_LEN
+            - RESERVE_FLAGS_LEN;
+        packed[isolated_collateral_index] = 2;
+        assert_eq!(
+            LendingInstruction::unpack(&packed),
+            Err(LendingError::InstructionUnpackError.into())
+        );
+    }
+
+    #[test]
+    fn test_reserve_config_protocol_take_rate() {
+        let config = ReserveConfig {
+            optimal_utilization_rate: 60,
+            loan_to_value_ratio: 50,
+            liquidation_bonus: 10,
+            max_liquidation_bonus: 0,
+            liquidation_threshold: 55,
+            min_borrow_rate: 2,
+            optimal_borrow_rate: 4,
+            max_borrow_rate: 10,
+            interest_rate_model: InterestRateModel::Kinked,
+            oracle: OracleConfig::default(),
+            caps: ReserveCaps::default(),
+            isolated_collateral: false,
+            elevation_group: ElevationGroup::default(),
+            borrowing_disabled: false,
+            paused: false,
+            fees: ReserveFees {
+                borrow_fee_wad: 1,
+                flash_loan_fee_wad: 3,
+                host_fee_percentage: 1,
+                protocol_take_rate: 10,
+            },
+        };
+        let instruction = LendingInstruction::ModifyReserveConfig { new_config: config };
+        let packed = instruction.pack();
+        assert_eq!(LendingInstruction::unpack(&packed).unwrap(), instruction);
+
+        // configs packed without a protocol take rate keep no protocol fees
+        let legacy = &packed[..packed.len()
+            - PROTOCOL_TAKE_RATE_LEN
+            - MAX_LIQUIDATION_BONUS_LEN
+            - RESERVE_FLAGS_LEN];
+        assert_eq!(
+            LendingInstruction::unpack(legacy).unwrap(),
+            LendingInstruction::ModifyReserveConfig {
+                new_config: ReserveConfig {
+                    fees: ReserveFees {
+                        protocol_take_rate: 0,
+                        ..config.fees
+                    },
+                    ..config
+                }
+            }
+        );
+    }
+
+    #[test]
+    fn test_reserve_config_max_liquidation_bonus() {
+        let config = ReserveConfig {
+            optimal_utilization_rate: 60,
+            loan_to_value_ratio: 50,
+            liquidation_bonus: 5,
+            max_liquidation_bonus: 15,
+            liquidation_threshold: 55,
+            min_borrow_rate: 2,
+            optimal_borrow_rate: 4,
+            max_borrow_rate: 10,
+            interest_rate_model: InterestRateModel::Kinked,
+            oracle: OracleConfig::default(),
+            caps: ReserveCaps::default(),
+            isolated_collateral: false,
+            elevation_group: ElevationGroup::default(),
+            borrowing_disabled: false,
+            paused: false,
+            fees: ReserveFees {
+                borrow_fee_wad: 1,
+                flash_loan_fee_wad: 3,
+                host_fee_percentage: 1,
+                protocol_take_rate: 10,
+            },
+        };
+        let instruction = LendingInstruction::ModifyReserveConfig { new_config: config };
+        let packed = instruction.pack();
+        assert_eq!(LendingInstruction::unpack(&packed).unwrap(), instruction);
+
+        // configs packed without a max liquidation bonus keep a static bonus
+        let legacy = &packed[..packed.len() - MAX_LIQUIDATION_BONUS_LEN - RESERVE_FLAGS_LEN];
+        assert_eq!(
+            LendingInstruction::unpack(legacy).unwrap(),
+            LendingInstruction::ModifyReserveConfig {
+                new_config: ReserveConfig {
+                    max_liquidation_bonus: 0,
+                    ..config
+                }
+            }
+        );
+    }
+
+    #[test]
+    fn test_withdraw_protocol_fees() {
+        let program_id = Pubkey::new_unique();
+        let liquidity_amount = 1;
+        let reserve_pubkey = Pubkey::new_unique();
+        let reserve_liquidity_supply_pubkey = Pubkey::new_unique();
+        let destination_liquidity_pubkey = Pubkey::new_unique();
+        let lending_market_pubkey = Pubkey::new_unique();
+        let lending_market_owner_pubkey = Pubkey::new_unique();
+        let reserve_liquidity_mint_pubkey = Pubkey::new_unique();
+        let instruction = withdraw_protocol_fees(
+            program_id,
+            liquidity_amount,
+            reserve_pubkey,
+            reserve_liquidity_supply_pubkey,
+            destination_liquidity_pubkey,
+            lending_market_pubkey,
+            lending_market_owner_pubkey,
+            reserve_liquidity_mint_pubkey,
+            spl_token::id(),
+        );
+        assert_eq!(instruction.program_id, program_id);
+        assert_eq!(instruction.accounts.len(), 10);
+        assert_eq!(
+            LendingInstruction::unpack(&instruction.data).unwrap(),
+            LendingInstruction::WithdrawProtocolFees { liquidity_amount }
+        );
+    }
+
+    #[test]
+    fn test_refresh_reserve_with_oracle_accounts() {
+        let program_id = Pubkey::new_unique();
+        let reserve_pubkey = Pubkey::new_unique();
+        let reserve_liquidity_oracle_pubkey = Pubkey::new_unique();
+        let additional_oracle_pubkeys = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let instruction = refresh_reserve_with_oracle_accounts(
+            program_id,
+            reserve_pubkey,
+            reserve_liquidity_oracle_pubkey,
+            &additional_oracle_pubkeys,
+        );
+        assert_eq!(instruction.program_id, program_id);
+        assert_eq!(instruction.accounts.len(), 5);
+        assert_eq!(instruction.accounts[2].pubkey, sysvar::clock::id());
+        assert_eq!(instruction.accounts[3].pubkey, additional_oracle_pubkeys[0]);
+        assert_eq!(instruction.accounts[4].pubkey, additional_oracle_pubkeys[1]);
+        assert_eq!(instruction.data, LendingInstruction::RefreshReserve.pack());
+    }
+
+    #[test]
+    fn test_set_liquidation_close_factor() {
+        let program_id = Pubkey::new_unique();
+        let lending_market_pubkey = Pubkey::new_unique();
+        let lending_market_owner = Pubkey::new_unique();
+        let instruction = set_liquidation_close_factor(
+            program_id,
+            lending_market_pubkey,
+            lending_market_owner,
+            20,
+        );
+        assert_eq!(instruction.program_id, program_id);
+        assert_eq!(instruction.accounts.len(), 3);
+        assert_eq!(
+            LendingInstruction::unpack(&instruction.data).unwrap(),
+            LendingInstruction::SetLiquidationCloseFactor {
+                liquidation_close_factor: 20
+            }
+        );
+    }
+
+    #[test]
+    fn test_write_off_bad_debt() {
+        let program_id = Pubkey::new_unique();
+        let reserve_pubkey = Pubkey::new_unique();
+        let obligation_pubkey = Pubkey::new_unique();
+        let lending_market_pubkey = Pubkey::new_unique();
+        let instruction = write_off_bad_debt(
+            program_id,
+            reserve_pubkey,
+            obligation_pubkey,
+            lending_market_pubkey,
+        );
+        assert_eq!(instruction.program_id, program_id);
+        assert_eq!(instruction.accounts.len(), 4);
+        assert_eq!(instruction.data, LendingInstruction::WriteOffBadDebt.pack());
+    }
+
+    #[test]
+    fn test_extend_obligation() {
+        let program_id = Pubkey::new_unique();
+        let obligation_pubkey = Pubkey::new_unique();
+        let obligation_owner_pubkey = Pubkey::new_unique();
+        let payer_pubkey = Pubkey::new_unique();
+        let instruction = extend_obligation(
+            program_id,
+            obligation_pubkey,
+            obligation_owner_pubkey,
+            payer_pubkey,
+        );
+        assert_eq!(instruction.program_id, program_id);
+        assert_eq!(instruction.accounts.len(), 5);
+        assert_eq!(
+            instruction.data,
+            LendingInstruction::ExtendObligation.pack()
+        );
+    }
+
+    #[test]
+    fn test_refresh_obligation_chunk() {
+        let program_id = Pubkey::new_unique();
+        let obligation_pubkey = Pubkey::new_unique();
+        let reserve_pubkeys = vec![Pubkey::new_unique(), Pubkey::new_unique()];
+        let instruction =
+            refresh_obligation_chunk(program_id, obligation_pubkey, 3, reserve_pubkeys);
+        assert_eq!(instruction.program_id, program_id);
+        assert_eq!(instruction.accounts.len(), 4);
+        assert_eq!(
+            LendingInstruction::unpack(&instruction.data).unwrap(),
+            LendingInstruction::RefreshObligationChunk { start_index: 3 }
+        );
+    }
+
+    #[test]
+    fn test_reserve_config_flags() {
+        let config = ReserveConfig {
+            optimal_utilization_rate: 60,
+            loan_to_value_ratio: 50,
+            liquidation_bonus: 5,
+            liquidation_threshold: 55,
+            optimal_borrow_rate: 4,
+            max_borrow_rate: 10,
+            borrowing_disabled: true,
+            paused: true,
+            ..ReserveConfig::default()
+        };
+        let instruction = LendingInstruction::TightenReserveConfig { new_config: config };
+        let mut packed = instruction.pack();
+        assert_eq!(LendingInstruction::unpack(&packed).unwrap(), instruction);
+
+        // configs packed without flags allow borrowing and are not paused
+        let legacy = &packed[..packed.len() - RESERVE_FLAGS_LEN];
+        assert_eq!(
+            LendingInstruction::unpack(legacy).unwrap(),
+            LendingInstruction::TightenReserveConfig {
+                new_config: ReserveConfig {
+                    borrowing_disabled: false,
+                    paused: false,
+                    ..config
+                }
+            }
+        );
+
+        let paused_index = packed.len() - 1;
+        packed[paused_index] = 2;
+        assert_eq!(
+            LendingInstruction::unpack(&packed),
+            Err(LendingError::InstructionUnpackError.into())
+        );
+    }
+
+    #[test]
+    fn test_set_lending_market_guardian() {
+        let program_id = Pubkey::new_unique();
+        let guardian = Pubkey::new_unique();
+        let instruction = set_lending_market_guardian(
+            program_id,
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            guardian,
+        );
+        assert_eq!(instruction.program_id, program_id);
+        assert_eq!(instruction.accounts.len(), 2);
+        assert_eq!(
+            LendingInstruction::unpack(&instruction.data).unwrap(),
+            LendingInstruction::SetLendingMarketGuardian { guardian }
+        );
+    }
+
+    #[test]
+    fn test_set_config_timelock() {
+        let program_id = Pubkey::new_unique();
+        let instruction =
+            set_config_timelock(program_id, Pubkey::new_unique(), Pubkey::new_unique(), 100);
+        assert_eq!(instruction.program_id, program_id);
+        assert_eq!(instruction.accounts.len(), 3);
+        assert_eq!(
+            LendingInstruction::unpack(&instruction.data).unwrap(),
+            LendingInstruction::SetConfigTimelock {
+                config_timelock_slots: 100
+            }
+        );
+    }
+
+    #[test]
+    fn test_queue_reserve_config() {
+        let program_id = Pubkey::new_unique();
+        let config = ReserveConfig {
+            loan_to_value_ratio: 40,
+            liquidation_threshold: 55,
+            ..ReserveConfig::default()
+        };
+        let instruction = queue_reserve_config(
+            program_id,
+            config,
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        assert_eq!(instruction.program_id, program_id);
+        assert_eq!(instruction.accounts.len(), 6);
+        assert_eq!(
+            LendingInstruction::unpack(&instruction.data).unwrap(),
+            LendingInstruction::QueueReserveConfig { new_config: config }
+        );
+
+        let instruction = queue_reserve_config_and_oracle(
+            program_id,
+            config,
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        assert_eq!(instruction.accounts.len(), 8);
+    }
+
+    #[test]
+    fn test_apply_and_cancel_reserve_config() {
+        let program_id = Pubkey::new_unique();
+        let instruction = apply_reserve_config(
+            program_id,
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        assert_eq!(instruction.accounts.len(), 4);
+        assert_eq!(
+            LendingInstruction::unpack(&instruction.data).unwrap(),
+            LendingInstruction::ApplyReserveConfig
+        );
+
+        let instruction = cancel_reserve_config(
+            program_id,
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        assert_eq!(instruction.accounts.len(), 3);
+        assert_eq!(
+            LendingInstruction::unpack(&instruction.data).unwrap(),
+            LendingInstruction::CancelReserveConfig
+        );
+
+        let instruction = apply_lending_market_changes(program_id, Pubkey::new_unique());
+        assert_eq!(instruction.accounts.len(), 2);
+        assert_eq!(
+            LendingInstruction::unpack(&instruction.data).unwrap(),
+            LendingInstruction::ApplyLendingMarketChanges
+        );
+    }
 }
@@ -7,9 +7,11 @@ pub mod entrypoint;
 pub mod error;
 pub mod instruction;
 pub mod math;
+pub mod oracle;
 pub mod processor;
 pub mod pyth;
 pub mod state;
+pub mod token;
 
 // Export current sdk types for downstream users building with a different sdk
 // version
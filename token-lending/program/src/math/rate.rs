@@ -3,7 +3,7 @@
 //! Usages and their ranges include:
 //!   - Collateral exchange ratio <= 5.0
 //!   - Loan to value ratio <= 0.9
-//!   - Max borrow rate <= 2.56
+//!   - Max borrow rate <= 100.0
 //!   - Percentages <= 1.0
 //!
 //! Rates are internally scaled by a WAD (10^18) to preserve
@@ -59,6 +59,11 @@ impl Rate {
         Self(U128::from(percent as u64 * PERCENT_SCALER))
     }
 
+    /// Create scaled decimal from basis points value
+    pub fn from_bps(bps: u32) -> Self {
+        Self(U128::from(bps as u64) * U128::from(BPS_SCALER))
+    }
+
     /// Return raw scaled value
     #[allow(clippy::wrong_self_convention)]
     pub fn to_scaled_val(&self) -> u128 {
@@ -0,0 +1,246 @@
+//! Oracle price readers
+
+use {
+    crate::{
+        error::LendingError,
+        math::{Decimal, TryDiv, TryMul, WAD},
+        pyth,
+        token::unpack_token_amount,
+    },
+    solana_program::{
+        account_info::AccountInfo, clock::Slot, msg, program_error::ProgramError, pubkey::Pubkey,
+    },
+    spl_math::precise_number::PreciseNumber,
+    spl_token_2022::{extension::StateWithExtensions, state::Mint},
+    spl_token_swap::{
+        curve::calculator::TradeDirection,
+        state::{SwapState, SwapVersion},
+    },
+    std::{convert::TryInto, sync::Arc},
+};
+
+/// Price read from an oracle, in the quote currency
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OraclePrice {
+    /// Price of one whole token
+    pub price: Decimal,
+    /// Confidence interval around the price, zero when not provided by the
+    /// oracle
+    pub confidence: Decimal,
+    /// Slot the price was published at
+    pub publish_slot: Slot,
+}
+
+/// Read the aggregate price of a Pyth price account
+pub fn get_pyth_price(pyth_price_info: &AccountInfo) -> Result<OraclePrice, ProgramError> {
+    let pyth_price_data = pyth_price_info.try_borrow_data()?;
+    let pyth_price = pyth::load::<pyth::Price>(&pyth_price_data)
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+
+    if pyth_price.ptype != pyth::PriceType::Price {
+        msg!("Oracle price type is invalid");
+        return Err(LendingError::InvalidOracleConfig.into());
+    }
+
+    if pyth_price.agg.status != pyth::PriceStatus::Trading {
+        msg!("Oracle price status is invalid");
+        return Err(LendingError::InvalidOracleConfig.into());
+    }
+
+    let price: u64 = pyth_price.agg.price.try_into().map_err(|_| {
+        msg!("Oracle price cannot be negative");
+        LendingError::InvalidOracleConfig
+    })?;
+
+    Ok(OraclePrice {
+        price: pyth_decimal(price, pyth_price.expo)?,
+        confidence: pyth_decimal(pyth_price.agg.conf, pyth_price.expo)?,
+        publish_slot: pyth_price.valid_slot,
+    })
+}
+
+fn pyth_decimal(value: u64, expo: i32) -> Result<Decimal, ProgramError> {
+    if expo >= 0 {
+        let exponent = expo.try_into().map_err(|_| LendingError::MathOverflow)?;
+        let zeros = 10u64
+            .checked_pow(exponent)
+            .ok_or(LendingError::MathOverflow)?;
+        Decimal::from(value).try_mul(zeros)
+    } else {
+        let exponent = expo
+            .checked_abs()
+            .ok_or(LendingError::MathOverflow)?
+            .try_into()
+            .map_err(|_| LendingError::MathOverflow)?;
+        let decimals = 10u64
+            .checked_pow(exponent)
+            .ok_or(LendingError::MathOverflow)?;
+        Decimal::from(value).try_div(decimals)
+    }
+}
+
+/// Unpack a token swap pool, checking it is owned by the token swap program
+/// and holds the given mint
+pub fn unpack_swap_pool(
+    swap_pool_info: &AccountInfo,
+    mint_pubkey: &Pubkey,
+) -> Result<Arc<dyn SwapState>, ProgramError> {
+    if swap_pool_info.owner != &spl_token_swap::id() {
+        msg!("Swap pool oracle is not owned by the token swap program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    let swap_pool = SwapVersion::unpack(&swap_pool_info.try_borrow_data()?).map_err(|_| {
+        msg!("Swap pool oracle is not a valid token swap pool");
+        LendingError::InvalidOracleConfig
+    })?;
+    if swap_pool.token_a_mint() != mint_pubkey && swap_pool.token_b_mint() != mint_pubkey {
+        msg!("Swap pool oracle does not hold the reserve liquidity mint");
+        return Err(LendingError::InvalidOracleConfig.into());
+    }
+    Ok(swap_pool)
+}
+
+/// Read the spot price of one whole reserve liquidity token from a token swap
+/// pool, in units of the other token of the pool
+pub fn get_swap_pool_spot_price(
+    swap_pool_info: &AccountInfo,
+    token_a_info: &AccountInfo,
+    token_b_info: &AccountInfo,
+    quote_mint_info: &AccountInfo,
+    liquidity_mint_pubkey: &Pubkey,
+    liquidity_mint_decimals: u8,
+) -> Result<Decimal, ProgramError> {
+    let swap_pool = unpack_swap_pool(swap_pool_info, liquidity_mint_pubkey)?;
+    if swap_pool.token_a_account() != token_a_info.key {
+        msg!("Swap pool token A account does not match the token A account provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if swap_pool.token_b_account() != token_b_info.key {
+        msg!("Swap pool token B account does not match the token B account provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let token_a_amount = unpack_token_amount(token_a_info)?;
+    let token_b_amount = unpack_token_amount(token_b_info)?;
+    let (liquidity_amount, quote_amount, trade_direction, quote_mint_pubkey) =
+        if swap_pool.token_a_mint() == liquidity_mint_pubkey {
+            (
+                token_a_amount,
+                token_b_amount,
+                TradeDirection::AtoB,
+                swap_pool.token_b_mint(),
+            )
+        } else {
+            (
+                token_b_amount,
+                token_a_amount,
+                TradeDirection::BtoA,
+                swap_pool.token_a_mint(),
+            )
+        };
+    if quote_mint_pubkey != quote_mint_info.key {
+        msg!("Swap pool quote mint does not match the quote mint provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    let quote_mint_decimals =
+        StateWithExtensions::<Mint>::unpack(&quote_mint_info.try_borrow_data()?)
+            .map_err(|_| LendingError::InvalidTokenMint)?
+            .base
+            .decimals;
+
+    let spot_price = swap_pool
+        .swap_curve()
+        .calculator
+        .spot_price(
+            u128::from(liquidity_amount),
+            u128::from(quote_amount),
+            trade_direction,
+        )
+        .ok_or_else(|| {
+            msg!("Swap pool oracle has no spot price");
+            LendingError::InvalidOracleConfig
+        })?;
+
+    // convert the price between base units into a price between whole tokens,
+    // scaled as a Wad
+    let scale = |decimals: u8| PreciseNumber::new(10u128.checked_pow(decimals.into())?);
+    let scaled_price = || {
+        spot_price
+            .checked_mul(&PreciseNumber::new(WAD.into())?)?
+            .checked_mul(&scale(liquidity_mint_decimals)?)?
+            .checked_div(&scale(quote_mint_decimals)?)?
+            .to_imprecise()
+    };
+    Ok(Decimal::from_scaled_val(
+        scaled_price().ok_or(LendingError::MathOverflow)?,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        super::*,
+        spl_token_swap::{
+            curve::{base::SwapCurve, fees::Fees},
+            state::SwapV1,
+        },
+    };
+
+    fn swap_pool_data(token_a_mint: Pubkey, token_b_mint: Pubkey) -> Vec<u8> {
+        let mut data = vec![0; SwapVersion::V1_LEN];
+        SwapVersion::pack(
+            SwapVersion::SwapV1(SwapV1 {
+                is_initialized: true,
+                bump_seed: 255,
+                token_program_id: spl_token::id(),
+                token_a: Pubkey::new_unique(),
+                token_b: Pubkey::new_unique(),
+                pool_mint: Pubkey::new_unique(),
+                token_a_mint,
+                token_b_mint,
+                pool_fee_account: Pubkey::new_unique(),
+                fees: Fees::default(),
+                swap_curve: SwapCurve::default(),
+            }),
+            &mut data,
+        )
+        .unwrap();
+        data
+    }
+
+    #[test]
+    fn unpack_swap_pool_checks_owner() {
+        let mint_pubkey = Pubkey::new_unique();
+        let swap_pool_pubkey = Pubkey::new_unique();
+        let mut data = swap_pool_data(mint_pubkey, Pubkey::new_unique());
+        let mut lamports = 0;
+
+        // an identical account owned by another program is rejected
+        let other_program_id = Pubkey::new_unique();
+        let swap_pool_info = AccountInfo::new(
+            &swap_pool_pubkey,
+            false,
+            false,
+            &mut lamports,
+            &mut data,
+            &other_program_id,
+            false,
+            0,
+        );
+        assert_eq!(
+            unpack_swap_pool(&swap_pool_info, &mint_pubkey).err(),
+            Some(LendingError::InvalidAccountOwner.into())
+        );
+
+        let swap_program_id = spl_token_swap::id();
+        let swap_pool_info = AccountInfo {
+            owner: &swap_program_id,
+            ..swap_pool_info
+        };
+        assert!(unpack_swap_pool(&swap_pool_info, &mint_pubkey).is_ok());
+        assert_eq!(
+            unpack_swap_pool(&swap_pool_info, &Pubkey::new_unique()).err(),
+            Some(LendingError::InvalidOracleConfig.into())
+        );
+    }
+}
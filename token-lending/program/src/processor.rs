@@ -4,14 +4,18 @@ use {
     crate::{
         error::LendingError,
         instruction::LendingInstruction,
-        math::{Decimal, Rate, TryAdd, TryDiv, TryMul},
+        math::{Decimal, Rate, TryDiv, TryMul},
+        oracle::{self, OraclePrice},
         pyth,
         state::{
             CalculateBorrowResult, CalculateLiquidationResult, CalculateRepayResult,
-            InitLendingMarketParams, InitObligationParams, InitReserveParams, LendingMarket,
-            NewReserveCollateralParams, NewReserveLiquidityParams, Obligation, Reserve,
-            ReserveCollateral, ReserveConfig, ReserveLiquidity,
+            InitLendingMarketParams, InitObligationParams, InitPendingReserveConfigParams,
+            InitReserveParams, LendingMarket, NewReserveCollateralParams,
+            NewReserveLiquidityParams, Obligation, OracleSource, PendingReserveConfig, Reserve,
+            ReserveCollateral, ReserveConfig, ReserveLiquidity, EXTENDED_OBLIGATION_LEN,
+            LEGACY_OBLIGATION_LEN,
         },
+        token,
     },
     num_traits::FromPrimitive,
     solana_program::{
@@ -24,12 +28,10 @@ use {
         program_error::{PrintProgramError, ProgramError},
         program_pack::{IsInitialized, Pack},
         pubkey::Pubkey,
+        system_instruction,
         sysvar::{clock::Clock, rent::Rent, Sysvar},
     },
-    spl_token::{
-        solana_program::instruction::AccountMeta,
-        state::{Account, Mint},
-    },
+    spl_token::solana_program::instruction::AccountMeta,
     std::convert::TryInto,
 };
 
@@ -115,6 +117,58 @@ pub fn process_instruction(
             msg!("Instruction: Modify Reserve Config");
             process_modify_reserve_config(program_id, new_config, accounts)
         }
+        LendingInstruction::WithdrawProtocolFees { liquidity_amount } => {
+            msg!("Instruction: Withdraw Protocol Fees");
+            process_withdraw_protocol_fees(program_id, liquidity_amount, accounts)
+        }
+        LendingInstruction::SetLiquidationCloseFactor {
+            liquidation_close_factor,
+        } => {
+            msg!("Instruction: Set Liquidation Close Factor");
+            process_set_liquidation_close_factor(program_id, liquidation_close_factor, accounts)
+        }
+        LendingInstruction::WriteOffBadDebt => {
+            msg!("Instruction: Write Off Bad Debt");
+            process_write_off_bad_debt(program_id, accounts)
+        }
+        LendingInstruction::ExtendObligation => {
+            msg!("Instruction: Extend Obligation");
+            process_extend_obligation(program_id, accounts)
+        }
+        LendingInstruction::RefreshObligationChunk { start_index } => {
+            msg!("Instruction: Refresh Obligation Chunk");
+            process_refresh_obligation_chunk(program_id, start_index, accounts)
+        }
+        LendingInstruction::SetLendingMarketGuardian { guardian } => {
+            msg!("Instruction: Set Lending Market Guardian");
+            process_set_lending_market_guardian(program_id, guardian, accounts)
+        }
+        LendingInstruction::SetConfigTimelock {
+            config_timelock_slots,
+        } => {
+            msg!("Instruction: Set Config Timelock");
+            process_set_config_timelock(program_id, config_timelock_slots, accounts)
+        }
+        LendingInstruction::QueueReserveConfig { new_config } => {
+            msg!("Instruction: Queue Reserve Config");
+            process_queue_reserve_config(program_id, new_config, accounts)
+        }
+        LendingInstruction::ApplyReserveConfig => {
+            msg!("Instruction: Apply Reserve Config");
+            process_apply_reserve_config(program_id, accounts)
+        }
+        LendingInstruction::CancelReserveConfig => {
+            msg!("Instruction: Cancel Reserve Config");
+            process_cancel_reserve_config(program_id, accounts)
+        }
+        LendingInstruction::TightenReserveConfig { new_config } => {
+            msg!("Instruction: Tighten Reserve Config");
+            process_tighten_reserve_config(program_id, new_config, accounts)
+        }
+        LendingInstruction::ApplyLendingMarketChanges => {
+            msg!("Instruction: Apply Lending Market Changes");
+            process_apply_lending_market_changes(program_id, accounts)
+        }
     }
 }
 
@@ -173,7 +227,16 @@ fn process_set_lending_market_owner(
         return Err(LendingError::InvalidSigner.into());
     }
 
-    lending_market.owner = new_owner;
+    let current_slot = match account_info_iter.next() {
+        Some(clock_info) => Clock::from_account_info(clock_info)?.slot,
+        None if lending_market.config_timelock_slots == 0 => 0,
+        None => {
+            msg!("Clock sysvar must be provided while the lending market has a config timelock");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+    };
+
+    lending_market.set_owner(new_owner, current_slot)?;
     LendingMarket::pack(lending_market, &mut lending_market_info.data.borrow_mut())?;
 
     Ok(())
@@ -182,7 +245,7 @@ fn process_set_lending_market_owner(
 fn process_init_reserve(
     program_id: &Pubkey,
     liquidity_amount: u64,
-    config: ReserveConfig,
+    mut config: ReserveConfig,
     accounts: &[AccountInfo],
 ) -> ProgramResult {
     if liquidity_amount == 0 {
@@ -201,8 +264,8 @@ fn process_init_reserve(
     let reserve_liquidity_fee_receiver_info = next_account_info(account_info_iter)?;
     let reserve_collateral_mint_info = next_account_info(account_info_iter)?;
     let reserve_collateral_supply_info = next_account_info(account_info_iter)?;
-    let pyth_product_info = next_account_info(account_info_iter)?;
-    let pyth_price_info = next_account_info(account_info_iter)?;
+    let oracle_product_info = next_account_info(account_info_iter)?;
+    let oracle_info = next_account_info(account_info_iter)?;
     let lending_market_info = next_account_info(account_info_iter)?;
     let lending_market_authority_info = next_account_info(account_info_iter)?;
     let lending_market_owner_info = next_account_info(account_info_iter)?;
@@ -211,6 +274,8 @@ fn process_init_reserve(
     let rent_info = next_account_info(account_info_iter)?;
     let rent = &Rent::from_account_info(rent_info)?;
     let token_program_id = next_account_info(account_info_iter)?;
+    let liquidity_token_program_id =
+        next_account_info(account_info_iter).unwrap_or(token_program_id);
 
     assert_rent_exempt(rent, reserve_info)?;
     let mut reserve = assert_uninitialized::<Reserve>(reserve_info)?;
@@ -241,49 +306,33 @@ fn process_init_reserve(
         msg!("Lending market owner provided must be a signer");
         return Err(LendingError::InvalidSigner.into());
     }
-
-    if &lending_market.oracle_program_id != pyth_product_info.owner {
-        msg!("Pyth product account provided is not owned by the lending market oracle program");
-        return Err(LendingError::InvalidOracleConfig.into());
-    }
-    if &lending_market.oracle_program_id != pyth_price_info.owner {
-        msg!("Pyth price account provided is not owned by the lending market oracle program");
-        return Err(LendingError::InvalidOracleConfig.into());
-    }
-
-    let pyth_product_data = pyth_product_info.try_borrow_data()?;
-    let pyth_product = pyth::load::<pyth::Product>(&pyth_product_data)
-        .map_err(|_| ProgramError::InvalidAccountData)?;
-    if pyth_product.magic != pyth::MAGIC {
-        msg!("Pyth product account provided is not a valid Pyth account");
-        return Err(LendingError::InvalidOracleConfig.into());
-    }
-    if pyth_product.ver != pyth::VERSION_2 {
-        msg!("Pyth product account provided has a different version than expected");
-        return Err(LendingError::InvalidOracleConfig.into());
-    }
-    if pyth_product.atype != pyth::AccountType::Product as u32 {
-        msg!("Pyth product account provided is not a valid Pyth product account");
-        return Err(LendingError::InvalidOracleConfig.into());
-    }
-
-    let pyth_price_pubkey_bytes: &[u8; 32] = pyth_price_info
-        .key
-        .as_ref()
-        .try_into()
-        .map_err(|_| LendingError::InvalidAccountInput)?;
-    if &pyth_product.px_acc.val != pyth_price_pubkey_bytes {
-        msg!("Pyth product price account does not match the Pyth price provided");
-        return Err(LendingError::InvalidOracleConfig.into());
-    }
-
-    let quote_currency = get_pyth_product_quote_currency(pyth_product)?;
-    if lending_market.quote_currency != quote_currency {
-        msg!("Lending market quote currency does not match the oracle quote currency");
-        return Err(LendingError::InvalidOracleConfig.into());
-    }
-
-    let market_price = get_pyth_price(pyth_price_info, clock)?;
+    // new reserves cannot be borrowed from or borrowed against until a
+    // queued config loosens them, so the config timelock also covers them
+    if lending_market.config_timelock_slots != 0 {
+        msg!("Lending market has a config timelock, disabling borrowing on the new reserve");
+        config.loan_to_value_ratio = 0;
+        config.elevation_group.loan_to_value_ratio = 0;
+        config.borrowing_disabled = true;
+    }
+
+    validate_oracle(
+        &lending_market,
+        config.oracle.source,
+        oracle_product_info,
+        oracle_info,
+        reserve_liquidity_mint_info.key,
+    )?;
+    // reserves start stale, so swap pool TWAPs and secondary oracles are only
+    // read once the reserve is refreshed
+    let market_price = match config.oracle.source {
+        OracleSource::Pyth => {
+            let price = oracle::get_pyth_price(oracle_info)?;
+            config.oracle.check_price(&price, clock.slot)?;
+            price.price
+        }
+        OracleSource::SwapPoolTwap => Decimal::zero(),
+        OracleSource::FixedPrice => config.oracle.fixed_price(),
+    };
 
     let authority_signer_seeds = &[
         lending_market_info.key.as_ref(),
@@ -298,12 +347,26 @@ fn process_init_reserve(
         return Err(LendingError::InvalidMarketAuthority.into());
     }
 
-    let reserve_liquidity_mint = unpack_mint(&reserve_liquidity_mint_info.data.borrow())?;
-    if reserve_liquidity_mint_info.owner != token_program_id.key {
-        msg!("Reserve liquidity mint is not owned by the token program provided");
+    let reserve_liquidity_mint = token::unpack_liquidity_mint(reserve_liquidity_mint_info)?;
+    if reserve_liquidity_mint_info.owner != liquidity_token_program_id.key {
+        msg!("Reserve liquidity mint is not owned by the liquidity token program provided");
         return Err(LendingError::InvalidTokenOwner.into());
     }
 
+    // transfer fees are withheld from the supply, so only the liquidity
+    // received backs the initial collateral
+    let liquidity_received = liquidity_amount
+        .checked_sub(token::transfer_fee(
+            Some(reserve_liquidity_mint_info),
+            clock.epoch,
+            liquidity_amount,
+        )?)
+        .ok_or(LendingError::MathOverflow)?;
+    if liquidity_received == 0 {
+        msg!("Reserve must be initialized with liquidity after transfer fees");
+        return Err(LendingError::InvalidAmount.into());
+    }
+
     reserve.init(InitReserveParams {
         current_slot: clock.slot,
         lending_market: *lending_market_info.key,
@@ -312,8 +375,9 @@ fn process_init_reserve(
             mint_decimals: reserve_liquidity_mint.decimals,
             supply_pubkey: *reserve_liquidity_supply_info.key,
             fee_receiver: *reserve_liquidity_fee_receiver_info.key,
-            oracle_pubkey: *pyth_price_info.key,
+            oracle_pubkey: *oracle_info.key,
             market_price,
+            token_program_id: *liquidity_token_program_id.key,
         }),
         collateral: ReserveCollateral::new(NewReserveCollateralParams {
             mint_pubkey: *reserve_collateral_mint_info.key,
@@ -322,7 +386,7 @@ fn process_init_reserve(
         config,
     });
 
-    let collateral_amount = reserve.deposit_liquidity(liquidity_amount)?;
+    let collateral_amount = reserve.deposit_liquidity(liquidity_received)?;
     Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
 
     spl_token_init_account(TokenInitializeAccountParams {
@@ -330,7 +394,7 @@ fn process_init_reserve(
         mint: reserve_liquidity_mint_info.clone(),
         owner: lending_market_authority_info.clone(),
         rent: rent_info.clone(),
-        token_program: token_program_id.clone(),
+        token_program: liquidity_token_program_id.clone(),
     })?;
 
     spl_token_init_account(TokenInitializeAccountParams {
@@ -338,7 +402,7 @@ fn process_init_reserve(
         mint: reserve_liquidity_mint_info.clone(),
         owner: lending_market_authority_info.clone(),
         rent: rent_info.clone(),
-        token_program: token_program_id.clone(),
+        token_program: liquidity_token_program_id.clone(),
     })?;
 
     spl_token_init_mint(TokenInitializeMintParams {
@@ -367,11 +431,13 @@ fn process_init_reserve(
 
     spl_token_transfer(TokenTransferParams {
         source: source_liquidity_info.clone(),
+        mint: Some(reserve_liquidity_mint_info.clone()),
         destination: reserve_liquidity_supply_info.clone(),
         amount: liquidity_amount,
+        decimals: reserve_liquidity_mint.decimals,
         authority: user_transfer_authority_info.clone(),
         authority_signer_seeds: &[],
-        token_program: token_program_id.clone(),
+        token_program: liquidity_token_program_id.clone(),
     })?;
 
     spl_token_mint_to(TokenMintToParams {
@@ -402,10 +468,51 @@ fn process_refresh_reserve(program_id: &Pubkey, accounts: &[AccountInfo]) -> Pro
         return Err(LendingError::InvalidAccountInput.into());
     }
 
-    reserve.liquidity.market_price = get_pyth_price(reserve_liquidity_oracle_info, clock)?;
+    let oracle_config = reserve.config.oracle;
+    let primary_price = get_oracle_price(
+        &mut reserve,
+        oracle_config.source,
+        reserve_liquidity_oracle_info,
+        account_info_iter,
+        clock,
+    )?;
+    let market_price = if let Some(secondary) = oracle_config.secondary {
+        let secondary_oracle_info = next_account_info(account_info_iter)?;
+        if &secondary.oracle_pubkey != secondary_oracle_info.key {
+            msg!("Reserve secondary oracle does not match the secondary oracle provided");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+        let secondary_price = get_oracle_price(
+            &mut reserve,
+            secondary.source,
+            secondary_oracle_info,
+            account_info_iter,
+            clock,
+        )?;
+        match (primary_price, secondary_price) {
+            (Some(primary_price), Some(secondary_price)) => {
+                secondary.check_divergence(primary_price, secondary_price)?;
+                Some(primary_price)
+            }
+            // a secondary TWAP still warming up cannot guard the primary price
+            (Some(_), None) => None,
+            // fall back to the secondary price until the primary TWAP is warm
+            (None, secondary_price) => secondary_price,
+        }
+    } else {
+        primary_price
+    };
 
     reserve.accrue_interest(clock.slot)?;
     reserve.last_update.update_slot(clock.slot);
+    if let Some(market_price) = market_price {
+        reserve.liquidity.market_price = market_price;
+    } else {
+        // keep the TWAP sample, but the reserve cannot be used until the TWAP
+        // has been sampled over a whole window
+        msg!("Oracle TWAP is warming up, the reserve remains stale");
+        reserve.last_update.mark_stale();
+    }
     Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
 
     Ok(())
@@ -432,6 +539,8 @@ fn process_deposit_reserve_liquidity(
     let user_transfer_authority_info = next_account_info(account_info_iter)?;
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
     let token_program_id = next_account_info(account_info_iter)?;
+    let reserve_liquidity_mint_info = next_account_info(account_info_iter).ok();
+    let liquidity_token_program_id = next_account_info(account_info_iter).ok();
 
     let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
     if lending_market_info.owner != program_id {
@@ -452,6 +561,12 @@ fn process_deposit_reserve_liquidity(
         msg!("Reserve lending market does not match the lending market provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
+    let liquidity_token_program_id = assert_reserve_liquidity_token(
+        &reserve,
+        reserve_liquidity_mint_info,
+        liquidity_token_program_id,
+        token_program_id,
+    )?;
     if &reserve.liquidity.supply_pubkey != reserve_liquidity_supply_info.key {
         msg!("Reserve liquidity supply does not match the reserve liquidity supply provided");
         return Err(LendingError::InvalidAccountInput.into());
@@ -472,6 +587,10 @@ fn process_deposit_reserve_liquidity(
         msg!("Reserve is stale and must be refreshed in the current slot");
         return Err(LendingError::ReserveStale.into());
     }
+    if reserve.config.paused {
+        msg!("Reserve is paused");
+        return Err(LendingError::ReservePaused.into());
+    }
 
     let authority_signer_seeds = &[
         lending_market_info.key.as_ref(),
@@ -488,17 +607,31 @@ fn process_deposit_reserve_liquidity(
         return Err(LendingError::InvalidMarketAuthority.into());
     }
 
-    let collateral_amount = reserve.deposit_liquidity(liquidity_amount)?;
+    // transfer fees are withheld from the supply, so only the liquidity
+    // received is deposited
+    let liquidity_received = liquidity_amount
+        .checked_sub(token::transfer_fee(
+            reserve_liquidity_mint_info,
+            clock.epoch,
+            liquidity_amount,
+        )?)
+        .ok_or(LendingError::MathOverflow)?;
+
+    reserve.check_deposit_limit(liquidity_received)?;
+    let collateral_amount = reserve.deposit_liquidity(liquidity_received)?;
+    let decimals = reserve.liquidity.mint_decimals;
     reserve.last_update.mark_stale();
     Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
 
     spl_token_transfer(TokenTransferParams {
         source: source_liquidity_info.clone(),
+        mint: reserve_liquidity_mint_info.cloned(),
         destination: reserve_liquidity_supply_info.clone(),
         amount: liquidity_amount,
+        decimals,
         authority: user_transfer_authority_info.clone(),
         authority_signer_seeds: &[],
-        token_program: token_program_id.clone(),
+        token_program: liquidity_token_program_id.clone(),
     })?;
 
     spl_token_mint_to(TokenMintToParams {
@@ -534,6 +667,8 @@ fn process_redeem_reserve_collateral(
     let user_transfer_authority_info = next_account_info(account_info_iter)?;
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
     let token_program_id = next_account_info(account_info_iter)?;
+    let reserve_liquidity_mint_info = next_account_info(account_info_iter).ok();
+    let liquidity_token_program_id = next_account_info(account_info_iter).ok();
 
     let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
     if lending_market_info.owner != program_id {
@@ -554,6 +689,12 @@ fn process_redeem_reserve_collateral(
         msg!("Reserve lending market does not match the lending market provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
+    let liquidity_token_program_id = assert_reserve_liquidity_token(
+        &reserve,
+        reserve_liquidity_mint_info,
+        liquidity_token_program_id,
+        token_program_id,
+    )?;
     if &reserve.collateral.mint_pubkey != reserve_collateral_mint_info.key {
         msg!("Reserve collateral mint does not match the reserve collateral mint provided");
         return Err(LendingError::InvalidAccountInput.into());
@@ -589,6 +730,7 @@ fn process_redeem_reserve_collateral(
     }
 
     let liquidity_amount = reserve.redeem_collateral(collateral_amount)?;
+    let decimals = reserve.liquidity.mint_decimals;
     reserve.last_update.mark_stale();
     Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
 
@@ -603,11 +745,13 @@ fn process_redeem_reserve_collateral(
 
     spl_token_transfer(TokenTransferParams {
         source: reserve_liquidity_supply_info.clone(),
+        mint: reserve_liquidity_mint_info.cloned(),
         destination: destination_liquidity_info.clone(),
         amount: liquidity_amount,
+        decimals,
         authority: lending_market_authority_info.clone(),
         authority_signer_seeds,
-        token_program: token_program_id.clone(),
+        token_program: liquidity_token_program_id.clone(),
     })?;
 
     Ok(())
@@ -658,7 +802,7 @@ fn process_init_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) -> Pro
 }
 
 fn process_refresh_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
-    let account_info_iter = &mut accounts.iter().peekable();
+    let account_info_iter = &mut accounts.iter();
     let obligation_info = next_account_info(account_info_iter)?;
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
 
@@ -668,113 +812,211 @@ fn process_refresh_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) ->
         return Err(LendingError::InvalidAccountOwner.into());
     }
 
-    let mut deposited_value = Decimal::zero();
-    let mut borrowed_value = Decimal::zero();
-    let mut allowed_borrow_value = Decimal::zero();
-    let mut unhealthy_borrow_value = Decimal::zero();
-
-    for (index, collateral) in obligation.deposits.iter_mut().enumerate() {
-        let deposit_reserve_info = next_account_info(account_info_iter)?;
-        if deposit_reserve_info.owner != program_id {
-            msg!(
-                "Deposit reserve provided for collateral {} is not owned by the lending program",
-                index
-            );
-            return Err(LendingError::InvalidAccountOwner.into());
-        }
-        if collateral.deposit_reserve != *deposit_reserve_info.key {
-            msg!(
-                "Deposit reserve of collateral {} does not match the deposit reserve provided",
-                index
-            );
-            return Err(LendingError::InvalidAccountInput.into());
-        }
+    // Obligations created before collateral modes have no room to record them
+    let collateral_modes = obligation_info.data_len() != LEGACY_OBLIGATION_LEN;
 
-        let deposit_reserve = Reserve::unpack(&deposit_reserve_info.data.borrow())?;
-        if deposit_reserve.last_update.is_stale(clock.slot)? {
-            msg!(
-                "Deposit reserve provided for collateral {} is stale and must be refreshed in the current slot",
-                index
-            );
-            return Err(LendingError::ReserveStale.into());
-        }
+    let reserve_infos = account_info_iter.as_slice();
+    if reserve_infos.len() < obligation.positions_len() {
+        msg!("Not enough obligation deposit or borrow reserves provided");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    if reserve_infos.len() > obligation.positions_len() {
+        msg!("Too many obligation deposit or borrow reserves provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
 
-        // @TODO: add lookup table https://git.io/JOCYq
-        let decimals = 10u64
-            .checked_pow(deposit_reserve.liquidity.mint_decimals as u32)
-            .ok_or(LendingError::MathOverflow)?;
+    obligation.begin_refresh(clock.slot);
+    refresh_obligation_positions(
+        program_id,
+        &mut obligation,
+        reserve_infos,
+        clock,
+        collateral_modes,
+    )?;
+    obligation.finish_refresh(clock.slot, collateral_modes)?;
+    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
 
-        let market_value = deposit_reserve
-            .collateral_exchange_rate()?
-            .decimal_collateral_to_liquidity(collateral.deposited_amount.into())?
-            .try_mul(deposit_reserve.liquidity.market_price)?
-            .try_div(decimals)?;
-        collateral.market_value = market_value;
-
-        let loan_to_value_rate = Rate::from_percent(deposit_reserve.config.loan_to_value_ratio);
-        let liquidation_threshold_rate =
-            Rate::from_percent(deposit_reserve.config.liquidation_threshold);
-
-        deposited_value = deposited_value.try_add(market_value)?;
-        allowed_borrow_value =
-            allowed_borrow_value.try_add(market_value.try_mul(loan_to_value_rate)?)?;
-        unhealthy_borrow_value =
-            unhealthy_borrow_value.try_add(market_value.try_mul(liquidation_threshold_rate)?)?;
-    }
-
-    for (index, liquidity) in obligation.borrows.iter_mut().enumerate() {
-        let borrow_reserve_info = next_account_info(account_info_iter)?;
-        if borrow_reserve_info.owner != program_id {
-            msg!(
-                "Borrow reserve provided for liquidity {} is not owned by the lending program",
-                index
-            );
-            return Err(LendingError::InvalidAccountOwner.into());
-        }
-        if liquidity.borrow_reserve != *borrow_reserve_info.key {
-            msg!(
-                "Borrow reserve of liquidity {} does not match the borrow reserve provided",
-                index
-            );
-            return Err(LendingError::InvalidAccountInput.into());
-        }
+    Ok(())
+}
 
-        let borrow_reserve = Reserve::unpack(&borrow_reserve_info.data.borrow())?;
-        if borrow_reserve.last_update.is_stale(clock.slot)? {
-            msg!(
-                "Borrow reserve provided for liquidity {} is stale and must be refreshed in the current slot",
-                index
-            );
-            return Err(LendingError::ReserveStale.into());
-        }
+fn process_refresh_obligation_chunk(
+    program_id: &Pubkey,
+    start_index: u8,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let obligation_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
 
-        liquidity.accrue_interest(borrow_reserve.liquidity.cumulative_borrow_rate_wads)?;
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        msg!("Obligation provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if obligation_info.data_len() == LEGACY_OBLIGATION_LEN {
+        msg!("Obligation has no room to record refresh progress and must be extended");
+        return Err(LendingError::InvalidObligationRefresh.into());
+    }
 
-        // @TODO: add lookup table https://git.io/JOCYq
-        let decimals = 10u64
-            .checked_pow(borrow_reserve.liquidity.mint_decimals as u32)
-            .ok_or(LendingError::MathOverflow)?;
+    if start_index == 0 {
+        obligation.begin_refresh(clock.slot);
+    } else if obligation.refresh_progress.slot != clock.slot
+        || obligation.refresh_progress.next_index != start_index
+    {
+        msg!(
+            "Obligation refresh chunk must start at the next deposit or borrow of a refresh in the current slot"
+        );
+        return Err(LendingError::InvalidObligationRefresh.into());
+    }
 
-        let market_value = liquidity
-            .borrowed_amount_wads
-            .try_mul(borrow_reserve.liquidity.market_price)?
-            .try_div(decimals)?;
-        liquidity.market_value = market_value;
+    let reserve_infos = account_info_iter.as_slice();
+    if reserve_infos.is_empty()
+        || start_index as usize + reserve_infos.len() > obligation.positions_len()
+    {
+        msg!("Obligation refresh chunk must cover deposits or borrows of the obligation");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    refresh_obligation_positions(program_id, &mut obligation, reserve_infos, clock, true)?;
 
-        borrowed_value = borrowed_value.try_add(market_value)?;
+    if obligation.refresh_progress.next_index as usize == obligation.positions_len() {
+        obligation.finish_refresh(clock.slot, true)?;
+    } else {
+        obligation.last_update.mark_stale();
     }
+    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
 
-    if account_info_iter.peek().is_some() {
-        msg!("Too many obligation deposit or borrow reserves provided");
-        return Err(LendingError::InvalidAccountInput.into());
+    Ok(())
+}
+
+/// Record the market values of the obligation deposits and borrows in the
+/// refresh in progress, from its next index with the reserves provided
+fn refresh_obligation_positions(
+    program_id: &Pubkey,
+    obligation: &mut Obligation,
+    reserve_infos: &[AccountInfo],
+    clock: &Clock,
+    collateral_modes: bool,
+) -> ProgramResult {
+    for reserve_info in reserve_infos {
+        let position_index = obligation.refresh_progress.next_index as usize;
+        if position_index < obligation.deposits.len() {
+            let index = position_index;
+            let collateral = &obligation.deposits[index];
+            if reserve_info.owner != program_id {
+                msg!(
+                    "Deposit reserve provided for collateral {} is not owned by the lending program",
+                    index
+                );
+                return Err(LendingError::InvalidAccountOwner.into());
+            }
+            if collateral.deposit_reserve != *reserve_info.key {
+                msg!(
+                    "Deposit reserve of collateral {} does not match the deposit reserve provided",
+                    index
+                );
+                return Err(LendingError::InvalidAccountInput.into());
+            }
+
+            let deposit_reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+            if deposit_reserve.last_update.is_stale(clock.slot)? {
+                msg!(
+                    "Deposit reserve provided for collateral {} is stale and must be refreshed in the current slot",
+                    index
+                );
+                return Err(LendingError::ReserveStale.into());
+            }
+
+            let market_value =
+                deposit_reserve.collateral_market_value(collateral.deposited_amount)?;
+            obligation.refresh_collateral(
+                market_value,
+                &deposit_reserve.config,
+                collateral_modes,
+            )?;
+        } else {
+            let index = position_index - obligation.deposits.len();
+            let liquidity = &mut obligation.borrows[index];
+            if reserve_info.owner != program_id {
+                msg!(
+                    "Borrow reserve provided for liquidity {} is not owned by the lending program",
+                    index
+                );
+                return Err(LendingError::InvalidAccountOwner.into());
+            }
+            if liquidity.borrow_reserve != *reserve_info.key {
+                msg!(
+                    "Borrow reserve of liquidity {} does not match the borrow reserve provided",
+                    index
+                );
+                return Err(LendingError::InvalidAccountInput.into());
+            }
+
+            let borrow_reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+            if borrow_reserve.last_update.is_stale(clock.slot)? {
+                msg!(
+                    "Borrow reserve provided for liquidity {} is stale and must be refreshed in the current slot",
+                    index
+                );
+                return Err(LendingError::ReserveStale.into());
+            }
+
+            liquidity.accrue_interest(borrow_reserve.liquidity.cumulative_borrow_rate_wads)?;
+
+            let market_value =
+                borrow_reserve.liquidity_market_value(liquidity.borrowed_amount_wads)?;
+            obligation.refresh_liquidity(market_value, borrow_reserve.config.elevation_group.id)?;
+        }
     }
 
-    obligation.deposited_value = deposited_value;
-    obligation.borrowed_value = borrowed_value;
-    obligation.allowed_borrow_value = allowed_borrow_value;
-    obligation.unhealthy_borrow_value = unhealthy_borrow_value;
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_extend_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let obligation_info = next_account_info(account_info_iter)?;
+    let obligation_owner_info = next_account_info(account_info_iter)?;
+    let payer_info = next_account_info(account_info_iter)?;
+    let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+    let system_program_info = next_account_info(account_info_iter)?;
 
-    obligation.last_update.update_slot(clock.slot);
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        msg!("Obligation provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &obligation.owner != obligation_owner_info.key {
+        msg!("Obligation owner does not match the obligation owner provided");
+        return Err(LendingError::InvalidObligationOwner.into());
+    }
+    if !obligation_owner_info.is_signer {
+        msg!("Obligation owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+    if obligation_info.data_len() == EXTENDED_OBLIGATION_LEN {
+        msg!("Obligation account is already extended");
+        return Err(LendingError::ObligationAlreadyExtended.into());
+    }
+
+    let lamports = rent
+        .minimum_balance(EXTENDED_OBLIGATION_LEN)
+        .saturating_sub(obligation_info.lamports());
+    if lamports > 0 {
+        invoke(
+            &system_instruction::transfer(payer_info.key, obligation_info.key, lamports),
+            &[
+                payer_info.clone(),
+                obligation_info.clone(),
+                system_program_info.clone(),
+            ],
+        )?;
+    }
+
+    // Deposits and borrows move after the standard layout, so the bytes of
+    // the old layout are cleared before packing
+    obligation_info.realloc(EXTENDED_OBLIGATION_LEN, false)?;
+    obligation_info.data.borrow_mut().fill(0);
+    obligation.mark_stale();
     Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
 
     Ok(())
@@ -858,16 +1100,39 @@ fn process_deposit_obligation_collateral(
         return Err(LendingError::InvalidSigner.into());
     }
 
+    // existing positions can still be topped up when the reserve is over its
+    // deposit limit, such as after the limit was lowered
+    if obligation
+        .find_collateral_in_deposits(*deposit_reserve_info.key)
+        .is_err()
+    {
+        deposit_reserve.check_deposit_limit(0)?;
+
+        // leaving the elevation group would lower the liquidation threshold
+        // of every position at once
+        if obligation.elevation_group != 0
+            && deposit_reserve.config.elevation_group.id != obligation.elevation_group
+        {
+            msg!("Deposit reserve is outside the obligation elevation group");
+            return Err(LendingError::ElevationGroupMismatch.into());
+        }
+    }
+
     obligation
-        .find_or_add_collateral_to_deposits(*deposit_reserve_info.key)?
+        .find_or_add_collateral_to_deposits(
+            *deposit_reserve_info.key,
+            Obligation::max_reserves(obligation_info.data_len()),
+        )?
         .deposit(collateral_amount)?;
-    obligation.last_update.mark_stale();
+    obligation.mark_stale();
     Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
 
     spl_token_transfer(TokenTransferParams {
         source: source_collateral_info.clone(),
+        mint: None,
         destination: destination_collateral_info.clone(),
         amount: collateral_amount,
+        decimals: deposit_reserve.liquidity.mint_decimals,
         authority: user_transfer_authority_info.clone(),
         authority_signer_seeds: &[],
         token_program: token_program_id.clone(),
@@ -982,9 +1247,17 @@ fn process_withdraw_obligation_collateral(
         msg!("Obligation deposited value is zero");
         return Err(LendingError::ObligationDepositsZero.into());
     } else {
-        let max_withdraw_value = obligation.max_withdraw_value(Rate::from_percent(
-            withdraw_reserve.config.loan_to_value_ratio,
-        ))?;
+        let (loan_to_value_ratio, _) = withdraw_reserve
+            .config
+            .collateral_ratios(obligation.elevation_group);
+        let withdraw_loan_to_value_rate = if withdraw_reserve.config.isolated_collateral
+            && (obligation.borrows.len() > 1 || obligation_info.data_len() == LEGACY_OBLIGATION_LEN)
+        {
+            Rate::zero()
+        } else {
+            Rate::from_percent(loan_to_value_ratio)
+        };
+        let max_withdraw_value = obligation.max_withdraw_value(withdraw_loan_to_value_rate)?;
         if max_withdraw_value == Decimal::zero() {
             msg!("Maximum withdraw value is zero");
             return Err(LendingError::WithdrawTooLarge.into());
@@ -1016,13 +1289,15 @@ fn process_withdraw_obligation_collateral(
     };
 
     obligation.withdraw(withdraw_amount, collateral_index)?;
-    obligation.last_update.mark_stale();
+    obligation.mark_stale();
     Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
 
     spl_token_transfer(TokenTransferParams {
         source: source_collateral_info.clone(),
+        mint: None,
         destination: destination_collateral_info.clone(),
         amount: withdraw_amount,
+        decimals: withdraw_reserve.liquidity.mint_decimals,
         authority: lending_market_authority_info.clone(),
         authority_signer_seeds,
         token_program: token_program_id.clone(),
@@ -1054,6 +1329,9 @@ fn process_borrow_obligation_liquidity(
     let obligation_owner_info = next_account_info(account_info_iter)?;
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
     let token_program_id = next_account_info(account_info_iter)?;
+    let host_fee_receiver_info = next_account_info(account_info_iter).ok();
+    let borrow_reserve_liquidity_mint_info = next_account_info(account_info_iter).ok();
+    let liquidity_token_program_id = next_account_info(account_info_iter).ok();
 
     let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
     if lending_market_info.owner != program_id {
@@ -1074,6 +1352,12 @@ fn process_borrow_obligation_liquidity(
         msg!("Borrow reserve lending market does not match the lending market provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
+    let liquidity_token_program_id = assert_reserve_liquidity_token(
+        &borrow_reserve,
+        borrow_reserve_liquidity_mint_info,
+        liquidity_token_program_id,
+        token_program_id,
+    )?;
     if &borrow_reserve.liquidity.supply_pubkey != source_liquidity_info.key {
         msg!("Borrow reserve liquidity supply must be used as the source liquidity provided");
         return Err(LendingError::InvalidAccountInput.into());
@@ -1092,6 +1376,14 @@ fn process_borrow_obligation_liquidity(
         msg!("Borrow reserve is stale and must be refreshed in the current slot");
         return Err(LendingError::ReserveStale.into());
     }
+    if borrow_reserve.config.paused {
+        msg!("Borrow reserve is paused");
+        return Err(LendingError::ReservePaused.into());
+    }
+    if borrow_reserve.config.borrowing_disabled {
+        msg!("Borrow reserve has borrowing disabled");
+        return Err(LendingError::ReserveBorrowingDisabled.into());
+    }
 
     let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
     if obligation_info.owner != program_id {
@@ -1136,6 +1428,22 @@ fn process_borrow_obligation_liquidity(
         return Err(LendingError::InvalidMarketAuthority.into());
     }
 
+    if obligation
+        .find_liquidity_in_borrows(*borrow_reserve_info.key)
+        .is_err()
+    {
+        if obligation.isolated_collateral && !obligation.borrows.is_empty() {
+            msg!("Obligation deposits include isolated collateral which can only back a single borrowed asset");
+            return Err(LendingError::IsolatedCollateralBorrow.into());
+        }
+        if obligation.elevation_group != 0
+            && borrow_reserve.config.elevation_group.id != obligation.elevation_group
+        {
+            msg!("Borrow reserve is outside the obligation elevation group");
+            return Err(LendingError::ElevationGroupMismatch.into());
+        }
+    }
+
     let remaining_borrow_value = obligation.remaining_borrow_value()?;
     if remaining_borrow_value == Decimal::zero() {
         msg!("Remaining borrow value is zero");
@@ -1159,18 +1467,23 @@ fn process_borrow_obligation_liquidity(
         return Err(LendingError::ExceededSlippage.into());
     }
 
+    borrow_reserve.check_borrow_limit(borrow_amount)?;
     borrow_reserve.liquidity.borrow(borrow_amount)?;
+    let decimals = borrow_reserve.liquidity.mint_decimals;
     borrow_reserve.last_update.mark_stale();
     Reserve::pack(borrow_reserve, &mut borrow_reserve_info.data.borrow_mut())?;
 
     obligation
-        .find_or_add_liquidity_to_borrows(*borrow_reserve_info.key)?
+        .find_or_add_liquidity_to_borrows(
+            *borrow_reserve_info.key,
+            Obligation::max_reserves(obligation_info.data_len()),
+        )?
         .borrow(borrow_amount)?;
-    obligation.last_update.mark_stale();
+    obligation.mark_stale();
     Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
 
     let mut owner_fee = borrow_fee;
-    if let Ok(host_fee_receiver_info) = next_account_info(account_info_iter) {
+    if let Some(host_fee_receiver_info) = host_fee_receiver_info {
         if host_fee > 0 {
             owner_fee = owner_fee
                 .checked_sub(host_fee)
@@ -1178,32 +1491,38 @@ fn process_borrow_obligation_liquidity(
 
             spl_token_transfer(TokenTransferParams {
                 source: source_liquidity_info.clone(),
+                mint: borrow_reserve_liquidity_mint_info.cloned(),
                 destination: host_fee_receiver_info.clone(),
                 amount: host_fee,
+                decimals,
                 authority: lending_market_authority_info.clone(),
                 authority_signer_seeds,
-                token_program: token_program_id.clone(),
+                token_program: liquidity_token_program_id.clone(),
             })?;
         }
     }
     if owner_fee > 0 {
         spl_token_transfer(TokenTransferParams {
             source: source_liquidity_info.clone(),
+            mint: borrow_reserve_liquidity_mint_info.cloned(),
             destination: borrow_reserve_liquidity_fee_receiver_info.clone(),
             amount: owner_fee,
+            decimals,
             authority: lending_market_authority_info.clone(),
             authority_signer_seeds,
-            token_program: token_program_id.clone(),
+            token_program: liquidity_token_program_id.clone(),
         })?;
     }
 
     spl_token_transfer(TokenTransferParams {
         source: source_liquidity_info.clone(),
+        mint: borrow_reserve_liquidity_mint_info.cloned(),
         destination: destination_liquidity_info.clone(),
         amount: receive_amount,
+        decimals,
         authority: lending_market_authority_info.clone(),
         authority_signer_seeds,
-        token_program: token_program_id.clone(),
+        token_program: liquidity_token_program_id.clone(),
     })?;
 
     Ok(())
@@ -1229,6 +1548,8 @@ fn process_repay_obligation_liquidity(
     let user_transfer_authority_info = next_account_info(account_info_iter)?;
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
     let token_program_id = next_account_info(account_info_iter)?;
+    let repay_reserve_liquidity_mint_info = next_account_info(account_info_iter).ok();
+    let liquidity_token_program_id = next_account_info(account_info_iter).ok();
 
     let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
     if lending_market_info.owner != program_id {
@@ -1249,6 +1570,12 @@ fn process_repay_obligation_liquidity(
         msg!("Repay reserve lending market does not match the lending market provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
+    let liquidity_token_program_id = assert_reserve_liquidity_token(
+        &repay_reserve,
+        repay_reserve_liquidity_mint_info,
+        liquidity_token_program_id,
+        token_program_id,
+    )?;
     if &repay_reserve.liquidity.supply_pubkey == source_liquidity_info.key {
         msg!("Repay reserve liquidity supply cannot be used as the source liquidity provided");
         return Err(LendingError::InvalidAccountInput.into());
@@ -1293,21 +1620,32 @@ fn process_repay_obligation_liquidity(
         return Err(LendingError::RepayTooSmall.into());
     }
 
+    // the source pays any transfer fee, so the reserve receives the full
+    // repay amount
+    let transfer_amount = token::transfer_amount_with_fee(
+        repay_reserve_liquidity_mint_info,
+        clock.epoch,
+        repay_amount,
+    )?;
+
     repay_reserve.liquidity.repay(repay_amount, settle_amount)?;
+    let decimals = repay_reserve.liquidity.mint_decimals;
     repay_reserve.last_update.mark_stale();
     Reserve::pack(repay_reserve, &mut repay_reserve_info.data.borrow_mut())?;
 
     obligation.repay(settle_amount, liquidity_index)?;
-    obligation.last_update.mark_stale();
+    obligation.mark_stale();
     Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
 
     spl_token_transfer(TokenTransferParams {
         source: source_liquidity_info.clone(),
+        mint: repay_reserve_liquidity_mint_info.cloned(),
         destination: destination_liquidity_info.clone(),
-        amount: repay_amount,
+        amount: transfer_amount,
+        decimals,
         authority: user_transfer_authority_info.clone(),
         authority_signer_seeds: &[],
-        token_program: token_program_id.clone(),
+        token_program: liquidity_token_program_id.clone(),
     })?;
 
     Ok(())
@@ -1337,6 +1675,8 @@ fn process_liquidate_obligation(
     let user_transfer_authority_info = next_account_info(account_info_iter)?;
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
     let token_program_id = next_account_info(account_info_iter)?;
+    let repay_reserve_liquidity_mint_info = next_account_info(account_info_iter).ok();
+    let repay_reserve_liquidity_token_program_id = next_account_info(account_info_iter).ok();
 
     let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
     if lending_market_info.owner != program_id {
@@ -1357,6 +1697,12 @@ fn process_liquidate_obligation(
         msg!("Repay reserve lending market does not match the lending market provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
+    let repay_reserve_liquidity_token_program_id = assert_reserve_liquidity_token(
+        &repay_reserve,
+        repay_reserve_liquidity_mint_info,
+        repay_reserve_liquidity_token_program_id,
+        token_program_id,
+    )?;
     if &repay_reserve.liquidity.supply_pubkey != repay_reserve_liquidity_supply_info.key {
         msg!("Repay reserve liquidity supply does not match the repay reserve liquidity supply provided");
         return Err(LendingError::InvalidAccountInput.into());
@@ -1464,6 +1810,7 @@ fn process_liquidate_obligation(
         &obligation,
         liquidity,
         collateral,
+        lending_market.liquidation_close_factor,
     )?;
 
     if repay_amount == 0 {
@@ -1475,28 +1822,41 @@ fn process_liquidate_obligation(
         return Err(LendingError::LiquidationTooSmall.into());
     }
 
+    // the liquidator pays any transfer fee, so the reserve receives the full
+    // repay amount
+    let transfer_amount = token::transfer_amount_with_fee(
+        repay_reserve_liquidity_mint_info,
+        clock.epoch,
+        repay_amount,
+    )?;
+
     repay_reserve.liquidity.repay(repay_amount, settle_amount)?;
+    let repay_decimals = repay_reserve.liquidity.mint_decimals;
     repay_reserve.last_update.mark_stale();
     Reserve::pack(repay_reserve, &mut repay_reserve_info.data.borrow_mut())?;
 
     obligation.repay(settle_amount, liquidity_index)?;
     obligation.withdraw(withdraw_amount, collateral_index)?;
-    obligation.last_update.mark_stale();
+    obligation.mark_stale();
     Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
 
     spl_token_transfer(TokenTransferParams {
         source: source_liquidity_info.clone(),
+        mint: repay_reserve_liquidity_mint_info.cloned(),
         destination: repay_reserve_liquidity_supply_info.clone(),
-        amount: repay_amount,
+        amount: transfer_amount,
+        decimals: repay_decimals,
         authority: user_transfer_authority_info.clone(),
         authority_signer_seeds: &[],
-        token_program: token_program_id.clone(),
+        token_program: repay_reserve_liquidity_token_program_id.clone(),
     })?;
 
     spl_token_transfer(TokenTransferParams {
         source: withdraw_reserve_collateral_supply_info.clone(),
+        mint: None,
         destination: destination_collateral_info.clone(),
         amount: withdraw_amount,
+        decimals: withdraw_reserve.liquidity.mint_decimals,
         authority: lending_market_authority_info.clone(),
         authority_signer_seeds,
         token_program: token_program_id.clone(),
@@ -1536,10 +1896,6 @@ fn process_flash_loan(
     if lending_market_info.owner != program_id {
         return Err(LendingError::InvalidAccountOwner.into());
     }
-    if &lending_market.token_program_id != token_program_id.key {
-        msg!("Lending market token program does not match the token program provided");
-        return Err(LendingError::InvalidTokenProgram.into());
-    }
 
     let authority_signer_seeds = &[
         lending_market_info.key.as_ref(),
@@ -1563,6 +1919,7 @@ fn process_flash_loan(
         msg!("Invalid reserve lending market account");
         return Err(LendingError::InvalidAccountInput.into());
     }
+    assert_reserve_liquidity_token(&reserve, None, None, token_program_id)?;
     if &reserve.liquidity.supply_pubkey != source_liquidity_info.key {
         msg!("Reserve liquidity supply must be used as the source liquidity provided");
         return Err(LendingError::InvalidAccountInput.into());
@@ -1571,6 +1928,10 @@ fn process_flash_loan(
         msg!("Reserve liquidity fee receiver does not match the reserve liquidity fee receiver provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
+    if reserve.config.paused {
+        msg!("Reserve is paused");
+        return Err(LendingError::ReservePaused.into());
+    }
 
     // @FIXME: if u64::MAX is flash loaned, fees should be inclusive as with
     // ordinary borrows
@@ -1586,7 +1947,14 @@ fn process_flash_loan(
         .fees
         .calculate_flash_loan_fees(flash_loan_amount_decimal)?;
 
-    let balance_before_flash_loan = Account::unpack(&source_liquidity_info.data.borrow())?.amount;
+    // the liquidity mint is only needed for transfer fees, and is passed
+    // along with the receiver program accounts to keep the account layout
+    let reserve_liquidity_mint_info = account_info_iter
+        .as_slice()
+        .iter()
+        .find(|account_info| account_info.key == &reserve.liquidity.mint_pubkey);
+    let decimals = reserve.liquidity.mint_decimals;
+    let balance_before_flash_loan = token::unpack_token_amount(source_liquidity_info)?;
     let expected_balance_after_flash_loan = balance_before_flash_loan
         .checked_add(origination_fee)
         .ok_or(LendingError::MathOverflow)?;
@@ -1619,8 +1987,10 @@ fn process_flash_loan(
 
     spl_token_transfer(TokenTransferParams {
         source: source_liquidity_info.clone(),
+        mint: reserve_liquidity_mint_info.cloned(),
         destination: destination_liquidity_info.clone(),
         amount: flash_loan_amount,
+        decimals,
         authority: lending_market_authority_info.clone(),
         authority_signer_seeds,
         token_program: token_program_id.clone(),
@@ -1649,8 +2019,7 @@ fn process_flash_loan(
         .repay(flash_loan_amount, flash_loan_amount_decimal)?;
     Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
 
-    let actual_balance_after_flash_loan =
-        Account::unpack(&source_liquidity_info.data.borrow())?.amount;
+    let actual_balance_after_flash_loan = token::unpack_token_amount(source_liquidity_info)?;
     if actual_balance_after_flash_loan < expected_balance_after_flash_loan {
         msg!("Insufficient reserve liquidity after flash loan");
         return Err(LendingError::NotEnoughLiquidityAfterFlashLoan.into());
@@ -1663,8 +2032,10 @@ fn process_flash_loan(
             .ok_or(LendingError::MathOverflow)?;
         spl_token_transfer(TokenTransferParams {
             source: source_liquidity_info.clone(),
+            mint: reserve_liquidity_mint_info.cloned(),
             destination: host_fee_receiver_info.clone(),
             amount: host_fee,
+            decimals,
             authority: lending_market_authority_info.clone(),
             authority_signer_seeds,
             token_program: token_program_id.clone(),
@@ -1674,8 +2045,10 @@ fn process_flash_loan(
     if owner_fee > 0 {
         spl_token_transfer(TokenTransferParams {
             source: source_liquidity_info.clone(),
+            mint: reserve_liquidity_mint_info.cloned(),
             destination: reserve_liquidity_fee_receiver_info.clone(),
             amount: owner_fee,
+            decimals,
             authority: lending_market_authority_info.clone(),
             authority_signer_seeds,
             token_program: token_program_id.clone(),
@@ -1715,6 +2088,10 @@ fn process_modify_reserve_config(
         msg!("Lending market owner provided must be a signer");
         return Err(LendingError::InvalidSigner.into());
     }
+    if lending_market.config_timelock_slots != 0 {
+        msg!("Lending market has a config timelock, reserve config changes must be queued");
+        return Err(LendingError::ConfigTimelocked.into());
+    }
 
     let mut reserve = Reserve::unpack(&reserve_info.data.borrow_mut())?;
     // Validate that the reserve account corresponds to the correct lending market,
@@ -1726,45 +2103,604 @@ fn process_modify_reserve_config(
         return Err(LendingError::InvalidAccountInput.into());
     }
 
-    reserve.config = new_config;
+    if let Some(oracle_product_info) = account_info_iter.next() {
+        let oracle_info = next_account_info(account_info_iter)?;
+        validate_oracle(
+            &lending_market,
+            new_config.oracle.source,
+            oracle_product_info,
+            oracle_info,
+            &reserve.liquidity.mint_pubkey,
+        )?;
+        reserve.set_oracle(*oracle_info.key);
+    } else if new_config.oracle.source != reserve.config.oracle.source {
+        msg!("Changing the reserve oracle source requires the new oracle account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    reserve.update_config(new_config);
 
     Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
 
     Ok(())
 }
 
-fn assert_rent_exempt(rent: &Rent, account_info: &AccountInfo) -> ProgramResult {
-    if !rent.is_exempt(account_info.lamports(), account_info.data_len()) {
-        msg!(&rent.minimum_balance(account_info.data_len()).to_string());
-        Err(LendingError::NotRentExempt.into())
-    } else {
-        Ok(())
-    }
-}
-
-fn assert_uninitialized<T: Pack + IsInitialized>(
-    account_info: &AccountInfo,
-) -> Result<T, ProgramError> {
-    let account: T = T::unpack_unchecked(&account_info.data.borrow())?;
-    if account.is_initialized() {
-        Err(LendingError::AlreadyInitialized.into())
-    } else {
-        Ok(account)
+#[inline(never)] // avoid stack frame limit
+fn process_withdraw_protocol_fees(
+    program_id: &Pubkey,
+    liquidity_amount: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if liquidity_amount == 0 {
+        msg!("Liquidity amount provided cannot be zero");
+        return Err(LendingError::InvalidAmount.into());
     }
-}
-
-/// Unpacks a spl_token `Mint`.
-fn unpack_mint(data: &[u8]) -> Result<Mint, LendingError> {
-    Mint::unpack(data).map_err(|_| LendingError::InvalidTokenMint)
-}
 
-fn get_pyth_product_quote_currency(pyth_product: &pyth::Product) -> Result<[u8; 32], ProgramError> {
-    const LEN: usize = 14;
-    const KEY: &[u8; LEN] = b"quote_currency";
+    let account_info_iter = &mut accounts.iter();
+    let reserve_info = next_account_info(account_info_iter)?;
+    let reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let destination_liquidity_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+    let reserve_liquidity_mint_info = next_account_info(account_info_iter).ok();
+    let liquidity_token_program_id = next_account_info(account_info_iter).ok();
 
-    let mut start = 0;
-    while start < pyth::PROD_ATTR_SIZE {
-        let mut length = pyth_product.attr[start] as usize;
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.token_program_id != token_program_id.key {
+        msg!("Lending market token program does not match the token program provided");
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!("Reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    let liquidity_token_program_id = assert_reserve_liquidity_token(
+        &reserve,
+        reserve_liquidity_mint_info,
+        liquidity_token_program_id,
+        token_program_id,
+    )?;
+    if &reserve.liquidity.supply_pubkey != reserve_liquidity_supply_info.key {
+        msg!("Reserve liquidity supply does not match the reserve liquidity supply provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.liquidity.supply_pubkey == destination_liquidity_info.key {
+        msg!("Reserve liquidity supply cannot be used as the destination liquidity provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if reserve.last_update.is_stale(clock.slot)? {
+        msg!("Reserve is stale and must be refreshed in the current slot");
+        return Err(LendingError::ReserveStale.into());
+    }
+
+    let authority_signer_seeds = &[
+        lending_market_info.key.as_ref(),
+        &[lending_market.bump_seed],
+    ];
+    let lending_market_authority_pubkey =
+        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
+    if &lending_market_authority_pubkey != lending_market_authority_info.key {
+        msg!(
+            "Derived lending market authority does not match the lending market authority provided"
+        );
+        return Err(LendingError::InvalidMarketAuthority.into());
+    }
+
+    let withdraw_amount = reserve.liquidity.withdraw_protocol_fees(liquidity_amount)?;
+    let decimals = reserve.liquidity.mint_decimals;
+    reserve.last_update.mark_stale();
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
+    spl_token_transfer(TokenTransferParams {
+        source: reserve_liquidity_supply_info.clone(),
+        mint: reserve_liquidity_mint_info.cloned(),
+        destination: destination_liquidity_info.clone(),
+        amount: withdraw_amount,
+        decimals,
+        authority: lending_market_authority_info.clone(),
+        authority_signer_seeds,
+        token_program: liquidity_token_program_id.clone(),
+    })?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_set_liquidation_close_factor(
+    program_id: &Pubkey,
+    liquidation_close_factor: u8,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    LendingMarket::validate_liquidation_close_factor(liquidation_close_factor)?;
+
+    let account_info_iter = &mut accounts.iter();
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+
+    let mut lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    let current_slot = match account_info_iter.next() {
+        Some(clock_info) => Clock::from_account_info(clock_info)?.slot,
+        None if lending_market.config_timelock_slots == 0 => 0,
+        None => {
+            msg!("Clock sysvar must be provided while the lending market has a config timelock");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+    };
+
+    lending_market.set_liquidation_close_factor(liquidation_close_factor, current_slot)?;
+    LendingMarket::pack(lending_market, &mut lending_market_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_write_off_bad_debt(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let reserve_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!("Reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if reserve.last_update.is_stale(clock.slot)? {
+        msg!("Reserve is stale and must be refreshed in the current slot");
+        return Err(LendingError::ReserveStale.into());
+    }
+
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        msg!("Obligation provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &obligation.lending_market != lending_market_info.key {
+        msg!("Obligation lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if obligation.last_update.is_stale(clock.slot)? {
+        msg!("Obligation is stale and must be refreshed in the current slot");
+        return Err(LendingError::ObligationStale.into());
+    }
+    if obligation.deposited_value != Decimal::zero() {
+        msg!("Obligation deposited value must be zero to write off its borrows");
+        return Err(LendingError::ObligationNotInsolvent.into());
+    }
+    // collateral priced at zero cannot be liquidated, since liquidators
+    // would receive nothing for their repayment, so it is dropped before the
+    // borrows are socialized
+    if !obligation.deposits.is_empty() {
+        msg!(
+            "Dropping {} deposits with no market value",
+            obligation.deposits.len()
+        );
+        obligation.deposits.clear();
+    }
+
+    let (liquidity, liquidity_index) = obligation.find_liquidity_in_borrows(*reserve_info.key)?;
+    let settle_amount = liquidity.borrowed_amount_wads;
+    msg!("Writing off {} of bad debt", settle_amount);
+
+    reserve.liquidity.write_off(settle_amount)?;
+    reserve.last_update.mark_stale();
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
+    obligation.repay(settle_amount, liquidity_index)?;
+    obligation.mark_stale();
+    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_set_lending_market_guardian(
+    program_id: &Pubkey,
+    guardian: Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+
+    let mut lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    lending_market.guardian = guardian;
+    LendingMarket::pack(lending_market, &mut lending_market_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_set_config_timelock(
+    program_id: &Pubkey,
+    config_timelock_slots: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+
+    let mut lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    lending_market.set_config_timelock(config_timelock_slots, clock.slot)?;
+    LendingMarket::pack(lending_market, &mut lending_market_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_queue_reserve_config(
+    program_id: &Pubkey,
+    new_config: ReserveConfig,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    new_config.validate()?;
+
+    let account_info_iter = &mut accounts.iter();
+    let pending_reserve_config_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+    let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    let reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!("Reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    if pending_reserve_config_info.owner != program_id {
+        msg!("Pending reserve config provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    assert_rent_exempt(rent, pending_reserve_config_info)?;
+    let mut pending_reserve_config =
+        PendingReserveConfig::unpack_unchecked(&pending_reserve_config_info.data.borrow())?;
+    if pending_reserve_config.is_initialized()
+        && &pending_reserve_config.reserve != reserve_info.key
+    {
+        msg!("Pending reserve config is already pending for another reserve");
+        return Err(LendingError::AlreadyInitialized.into());
+    }
+
+    let oracle_pubkey = if let Some(oracle_product_info) = account_info_iter.next() {
+        let oracle_info = next_account_info(account_info_iter)?;
+        validate_oracle(
+            &lending_market,
+            new_config.oracle.source,
+            oracle_product_info,
+            oracle_info,
+            &reserve.liquidity.mint_pubkey,
+        )?;
+        *oracle_info.key
+    } else if new_config.oracle.source != reserve.config.oracle.source {
+        msg!("Changing the reserve oracle source requires the new oracle account");
+        return Err(LendingError::InvalidAccountInput.into());
+    } else {
+        Pubkey::default()
+    };
+
+    pending_reserve_config.init(InitPendingReserveConfigParams {
+        lending_market: *lending_market_info.key,
+        reserve: *reserve_info.key,
+        effective_slot: lending_market.config_effective_slot(clock.slot)?,
+        oracle_pubkey,
+        config: new_config,
+    });
+    PendingReserveConfig::pack(
+        pending_reserve_config,
+        &mut pending_reserve_config_info.data.borrow_mut(),
+    )?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_apply_reserve_config(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let pending_reserve_config_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+
+    let pending_reserve_config =
+        PendingReserveConfig::unpack(&pending_reserve_config_info.data.borrow())?;
+    if pending_reserve_config_info.owner != program_id {
+        msg!("Pending reserve config provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &pending_reserve_config.lending_market != lending_market_info.key {
+        msg!("Pending reserve config lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &pending_reserve_config.reserve != reserve_info.key {
+        msg!("Pending reserve config reserve does not match the reserve provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if pending_reserve_config.effective_slot > clock.slot {
+        msg!(
+            "Pending reserve config cannot be applied before slot {}",
+            pending_reserve_config.effective_slot
+        );
+        return Err(LendingError::PendingConfigNotReady.into());
+    }
+
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!("Reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    if pending_reserve_config.oracle_pubkey != Pubkey::default() {
+        reserve.set_oracle(pending_reserve_config.oracle_pubkey);
+    }
+    reserve.update_config(pending_reserve_config.config);
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
+    PendingReserveConfig::pack(
+        PendingReserveConfig::default(),
+        &mut pending_reserve_config_info.data.borrow_mut(),
+    )?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_cancel_reserve_config(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let pending_reserve_config_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let risk_authority_info = next_account_info(account_info_iter)?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if !lending_market.is_risk_authority(risk_authority_info.key) {
+        msg!("Lending market owner or guardian does not match the authority provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !risk_authority_info.is_signer {
+        msg!("Lending market owner or guardian provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    let pending_reserve_config =
+        PendingReserveConfig::unpack(&pending_reserve_config_info.data.borrow())?;
+    if pending_reserve_config_info.owner != program_id {
+        msg!("Pending reserve config provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &pending_reserve_config.lending_market != lending_market_info.key {
+        msg!("Pending reserve config lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    PendingReserveConfig::pack(
+        PendingReserveConfig::default(),
+        &mut pending_reserve_config_info.data.borrow_mut(),
+    )?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_tighten_reserve_config(
+    program_id: &Pubkey,
+    new_config: ReserveConfig,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    new_config.validate()?;
+
+    let account_info_iter = &mut accounts.iter();
+    let reserve_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let risk_authority_info = next_account_info(account_info_iter)?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if !lending_market.is_risk_authority(risk_authority_info.key) {
+        msg!("Lending market owner or guardian does not match the authority provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !risk_authority_info.is_signer {
+        msg!("Lending market owner or guardian provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!("Reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    // Validate the reserve lending market after the authority, so a guardian
+    // of one lending market cannot change reserves of others
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    new_config.validate_tightening(&reserve.config)?;
+    reserve.update_config(new_config);
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_apply_lending_market_changes(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+
+    let mut lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+
+    lending_market.apply_pending_changes(clock.slot)?;
+    LendingMarket::pack(lending_market, &mut lending_market_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+fn assert_rent_exempt(rent: &Rent, account_info: &AccountInfo) -> ProgramResult {
+    if !rent.is_exempt(account_info.lamports(), account_info.data_len()) {
+        msg!(&rent.minimum_balance(account_info.data_len()).to_string());
+        Err(LendingError::NotRentExempt.into())
+    } else {
+        Ok(())
+    }
+}
+
+fn assert_uninitialized<T: Pack + IsInitialized>(
+    account_info: &AccountInfo,
+) -> Result<T, ProgramError> {
+    let account: T = T::unpack_unchecked(&account_info.data.borrow())?;
+    if account.is_initialized() {
+        Err(LendingError::AlreadyInitialized.into())
+    } else {
+        Ok(account)
+    }
+}
+
+/// Check the optional liquidity mint and token program provided match the
+/// reserve, returning the token program which owns the reserve liquidity.
+/// Callers which omit the token program use the lending market token program,
+/// so the legacy account layouts keep working for SPL Token reserves.
+fn assert_reserve_liquidity_token<'a, 'b>(
+    reserve: &Reserve,
+    liquidity_mint_info: Option<&'a AccountInfo<'b>>,
+    liquidity_token_program_id: Option<&'a AccountInfo<'b>>,
+    token_program_id: &'a AccountInfo<'b>,
+) -> Result<&'a AccountInfo<'b>, ProgramError> {
+    if let Some(liquidity_mint_info) = liquidity_mint_info {
+        if &reserve.liquidity.mint_pubkey != liquidity_mint_info.key {
+            msg!("Reserve liquidity mint does not match the reserve liquidity mint provided");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+    }
+    let liquidity_token_program_id = liquidity_token_program_id.unwrap_or(token_program_id);
+    if &reserve.liquidity.token_program_id != liquidity_token_program_id.key {
+        msg!("Reserve liquidity token program does not match the token program provided");
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+    Ok(liquidity_token_program_id)
+}
+
+fn get_pyth_product_quote_currency(pyth_product: &pyth::Product) -> Result<[u8; 32], ProgramError> {
+    const LEN: usize = 14;
+    const KEY: &[u8; LEN] = b"quote_currency";
+
+    let mut start = 0;
+    while start < pyth::PROD_ATTR_SIZE {
+        let mut length = pyth_product.attr[start] as usize;
         start += 1;
 
         if length == LEN {
@@ -1800,60 +2736,129 @@ fn get_pyth_product_quote_currency(pyth_product: &pyth::Product) -> Result<[u8;
     Err(LendingError::InvalidOracleConfig.into())
 }
 
-fn get_pyth_price(pyth_price_info: &AccountInfo, clock: &Clock) -> Result<Decimal, ProgramError> {
-    const STALE_AFTER_SLOTS_ELAPSED: u64 = 5;
+/// Check the oracle account of a reserve matches the oracle source, the oracle
+/// product account is only used by Pyth oracles
+fn validate_oracle(
+    lending_market: &LendingMarket,
+    source: OracleSource,
+    oracle_product_info: &AccountInfo,
+    oracle_info: &AccountInfo,
+    liquidity_mint_pubkey: &Pubkey,
+) -> ProgramResult {
+    match source {
+        OracleSource::Pyth => {
+            validate_pyth_oracle(lending_market, oracle_product_info, oracle_info)
+        }
+        OracleSource::SwapPoolTwap => {
+            oracle::unpack_swap_pool(oracle_info, liquidity_mint_pubkey)?;
+            Ok(())
+        }
+        OracleSource::FixedPrice => Ok(()),
+    }
+}
 
-    let pyth_price_data = pyth_price_info.try_borrow_data()?;
-    let pyth_price = pyth::load::<pyth::Price>(&pyth_price_data)
-        .map_err(|_| ProgramError::InvalidAccountData)?;
+fn validate_pyth_oracle(
+    lending_market: &LendingMarket,
+    pyth_product_info: &AccountInfo,
+    pyth_price_info: &AccountInfo,
+) -> ProgramResult {
+    if &lending_market.oracle_program_id != pyth_product_info.owner {
+        msg!("Pyth product account provided is not owned by the lending market oracle program");
+        return Err(LendingError::InvalidOracleConfig.into());
+    }
+    if &lending_market.oracle_program_id != pyth_price_info.owner {
+        msg!("Pyth price account provided is not owned by the lending market oracle program");
+        return Err(LendingError::InvalidOracleConfig.into());
+    }
 
-    if pyth_price.ptype != pyth::PriceType::Price {
-        msg!("Oracle price type is invalid");
+    let pyth_product_data = pyth_product_info.try_borrow_data()?;
+    let pyth_product = pyth::load::<pyth::Product>(&pyth_product_data)
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+    if pyth_product.magic != pyth::MAGIC {
+        msg!("Pyth product account provided is not a valid Pyth account");
+        return Err(LendingError::InvalidOracleConfig.into());
+    }
+    if pyth_product.ver != pyth::VERSION_2 {
+        msg!("Pyth product account provided has a different version than expected");
+        return Err(LendingError::InvalidOracleConfig.into());
+    }
+    if pyth_product.atype != pyth::AccountType::Product as u32 {
+        msg!("Pyth product account provided is not a valid Pyth product account");
         return Err(LendingError::InvalidOracleConfig.into());
     }
 
-    if pyth_price.agg.status != pyth::PriceStatus::Trading {
-        msg!("Oracle price status is invalid");
+    let pyth_price_pubkey_bytes: &[u8; 32] = pyth_price_info
+        .key
+        .as_ref()
+        .try_into()
+        .map_err(|_| LendingError::InvalidAccountInput)?;
+    if &pyth_product.px_acc.val != pyth_price_pubkey_bytes {
+        msg!("Pyth product price account does not match the Pyth price provided");
         return Err(LendingError::InvalidOracleConfig.into());
     }
 
-    let slots_elapsed = clock
-        .slot
-        .checked_sub(pyth_price.valid_slot)
-        .ok_or(LendingError::MathOverflow)?;
-    if slots_elapsed >= STALE_AFTER_SLOTS_ELAPSED {
-        msg!("Oracle price is stale");
+    let quote_currency = get_pyth_product_quote_currency(pyth_product)?;
+    if lending_market.quote_currency != quote_currency {
+        msg!("Lending market quote currency does not match the oracle quote currency");
         return Err(LendingError::InvalidOracleConfig.into());
     }
 
-    let price: u64 = pyth_price.agg.price.try_into().map_err(|_| {
-        msg!("Oracle price cannot be negative");
-        LendingError::InvalidOracleConfig
-    })?;
+    Ok(())
+}
 
-    let market_price = if pyth_price.expo >= 0 {
-        let exponent = pyth_price
-            .expo
-            .try_into()
-            .map_err(|_| LendingError::MathOverflow)?;
-        let zeros = 10u64
-            .checked_pow(exponent)
-            .ok_or(LendingError::MathOverflow)?;
-        Decimal::from(price).try_mul(zeros)?
-    } else {
-        let exponent = pyth_price
-            .expo
-            .checked_abs()
-            .ok_or(LendingError::MathOverflow)?
-            .try_into()
-            .map_err(|_| LendingError::MathOverflow)?;
-        let decimals = 10u64
-            .checked_pow(exponent)
-            .ok_or(LendingError::MathOverflow)?;
-        Decimal::from(price).try_div(decimals)?
+/// Read and check the price of a reserve oracle, consuming the accounts
+/// following the oracle account for the oracle source.
+///
+/// Returns `None` while a swap pool TWAP has not yet been sampled over a whole
+/// window.
+fn get_oracle_price<'a, 'b: 'a>(
+    reserve: &mut Reserve,
+    source: OracleSource,
+    oracle_info: &AccountInfo<'b>,
+    account_info_iter: &mut impl Iterator<Item = &'a AccountInfo<'b>>,
+    clock: &Clock,
+) -> Result<Option<Decimal>, ProgramError> {
+    let oracle_config = reserve.config.oracle;
+    let price = match source {
+        OracleSource::Pyth => oracle::get_pyth_price(oracle_info)?,
+        OracleSource::SwapPoolTwap => {
+            let token_a_info = next_account_info(account_info_iter)?;
+            let token_b_info = next_account_info(account_info_iter)?;
+            let quote_mint_info = next_account_info(account_info_iter)?;
+            let spot_price = oracle::get_swap_pool_spot_price(
+                oracle_info,
+                token_a_info,
+                token_b_info,
+                quote_mint_info,
+                &reserve.liquidity.mint_pubkey,
+                reserve.liquidity.mint_decimals,
+            )?;
+            let twap_price = reserve.liquidity.sample_twap(
+                spot_price,
+                clock.slot,
+                oracle_config.twap_window_slots.into(),
+                oracle_config.max_staleness(),
+            )?;
+            if !reserve
+                .liquidity
+                .twap_warmed_up(clock.slot, oracle_config.twap_window_slots.into())
+            {
+                return Ok(None);
+            }
+            OraclePrice {
+                price: twap_price,
+                confidence: Decimal::zero(),
+                publish_slot: clock.slot,
+            }
+        }
+        OracleSource::FixedPrice => OraclePrice {
+            price: oracle_config.fixed_price(),
+            confidence: Decimal::zero(),
+            publish_slot: clock.slot,
+        },
     };
-
-    Ok(market_price)
+    oracle_config.check_price(&price, clock.slot)?;
+    Ok(Some(price.price))
 }
 
 /// Issue a spl_token `InitializeAccount` instruction.
@@ -1866,7 +2871,7 @@ fn spl_token_init_account(params: TokenInitializeAccountParams<'_>) -> ProgramRe
         rent,
         token_program,
     } = params;
-    let ix = spl_token::instruction::initialize_account(
+    let ix = spl_token_2022::instruction::initialize_account(
         token_program.key,
         account.key,
         mint.key,
@@ -1911,29 +2916,49 @@ fn invoke_optionally_signed(
     }
 }
 
-/// Issue a spl_token `Transfer` instruction.
+/// Issue a spl_token `TransferChecked` instruction, or a `Transfer`
+/// instruction when no mint is provided.
 #[inline(always)]
 fn spl_token_transfer(params: TokenTransferParams<'_, '_>) -> ProgramResult {
     let TokenTransferParams {
         source,
+        mint,
         destination,
         authority,
         token_program,
         amount,
+        decimals,
         authority_signer_seeds,
     } = params;
-    let result = invoke_optionally_signed(
-        &spl_token::instruction::transfer(
-            token_program.key,
-            source.key,
-            destination.key,
-            authority.key,
-            &[],
-            amount,
-        )?,
-        &[source, destination, authority, token_program],
-        authority_signer_seeds,
-    );
+    let result = match mint {
+        Some(mint) => invoke_optionally_signed(
+            &spl_token_2022::instruction::transfer_checked(
+                token_program.key,
+                source.key,
+                mint.key,
+                destination.key,
+                authority.key,
+                &[],
+                amount,
+                decimals,
+            )?,
+            &[source, mint, destination, authority, token_program],
+            authority_signer_seeds,
+        ),
+        #[allow(deprecated)]
+        None => invoke_optionally_signed(
+            &spl_token_2022::instruction::transfer(
+                token_program.key,
+                source.key,
+                destination.key,
+                authority.key,
+                &[],
+                amount,
+            )?,
+            &[source, destination, authority, token_program],
+            authority_signer_seeds,
+        ),
+    };
     result.map_err(|_| LendingError::TokenTransferFailed.into())
 }
 
@@ -2006,8 +3031,10 @@ struct TokenInitializeAccountParams<'a> {
 
 struct TokenTransferParams<'a: 'b, 'b> {
     source: AccountInfo<'a>,
+    mint: Option<AccountInfo<'a>>,
     destination: AccountInfo<'a>,
     amount: u64,
+    decimals: u8,
     authority: AccountInfo<'a>,
     authority_signer_seeds: &'b [&'b [u8]],
     token_program: AccountInfo<'a>,
@@ -0,0 +1,621 @@
+use {
+    super::*,
+    crate::{
+        error::LendingError,
+        math::{Rate, TryAdd, TryDiv, TryMul, TrySub},
+    },
+    arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs},
+    solana_program::{entrypoint::ProgramResult, msg, program_error::ProgramError},
+};
+
+/// Maximum number of points on a piecewise-linear borrow rate curve
+pub const MAX_RATE_CURVE_POINTS: usize = 6;
+
+/// Maximum borrow rate of any interest rate model, in basis points (10,000%)
+pub const MAX_BORROW_RATE_BPS: u32 = 1_000_000;
+
+/// Utilization rate of 100%, in basis points
+pub const MAX_UTILIZATION_BPS: u16 = 10_000;
+
+/// Packed length of an interest rate model
+pub const INTEREST_RATE_MODEL_LEN: usize = 41; // 1 + 40
+
+const RATE_CURVE_POINT_LEN: usize = 6; // 2 + 4
+
+/// Model used to derive the borrow rate of a reserve from its utilization
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum InterestRateModel {
+    /// Two-slope model given by the optimal utilization rate and the min,
+    /// optimal and max borrow rates of the reserve config, as percentages
+    #[default]
+    Kinked,
+    /// Borrow rates interpolated linearly between points of a curve
+    PiecewiseLinear(RateCurve),
+    /// Borrow rates which shift over time to steer utilization toward a target
+    Adaptive(AdaptiveRateModel),
+}
+
+impl InterestRateModel {
+    /// Validate the model, when initializing or modifying the reserve configs
+    pub fn validate(&self) -> ProgramResult {
+        match self {
+            Self::Kinked => Ok(()),
+            Self::PiecewiseLinear(curve) => curve.validate(),
+            Self::Adaptive(model) => model.validate(),
+        }
+    }
+
+    /// Rate at target utilization for a reserve starting to use this model,
+    /// zero for models without state
+    pub fn initial_rate_at_target(&self) -> Rate {
+        match self {
+            Self::Adaptive(model) => Rate::from_bps(model.initial_rate_at_target_bps),
+            _ => Rate::zero(),
+        }
+    }
+
+    /// Pack the model into a fixed-size buffer, zeroing unused bytes
+    #[allow(clippy::ptr_offset_with_cast)]
+    pub fn pack_into_slice(&self, output: &mut [u8; INTEREST_RATE_MODEL_LEN]) {
+        let (tag, data) = mut_array_refs![output, 1, INTEREST_RATE_MODEL_LEN - 1];
+        *data = [0; INTEREST_RATE_MODEL_LEN - 1];
+        match self {
+            Self::Kinked => tag[0] = 0,
+            Self::PiecewiseLinear(curve) => {
+                tag[0] = 1;
+                let (len, points, _) =
+                    mut_array_refs![data, 1, RATE_CURVE_POINT_LEN * MAX_RATE_CURVE_POINTS, 3];
+                len[0] = curve.len;
+                for (point, dst) in curve
+                    .points
+                    .iter()
+                    .zip(points.chunks_exact_mut(RATE_CURVE_POINT_LEN))
+                {
+                    let dst = array_mut_ref![dst, 0, RATE_CURVE_POINT_LEN];
+                    let (utilization_bps, borrow_rate_bps) = mut_array_refs![dst, 2, 4];
+                    *utilization_bps = point.utilization_bps.to_le_bytes();
+                    *borrow_rate_bps = point.borrow_rate_bps.to_le_bytes();
+                }
+            }
+            Self::Adaptive(model) => {
+                tag[0] = 2;
+                let (
+                    target_utilization_bps,
+                    initial_rate_at_target_bps,
+                    min_rate_at_target_bps,
+                    max_rate_at_target_bps,
+                    adjustment_speed_bps,
+                    curve_steepness,
+                    _,
+                ) = mut_array_refs![data, 2, 4, 4, 4, 4, 1, 21];
+                *target_utilization_bps = model.target_utilization_bps.to_le_bytes();
+                *initial_rate_at_target_bps = model.initial_rate_at_target_bps.to_le_bytes();
+                *min_rate_at_target_bps = model.min_rate_at_target_bps.to_le_bytes();
+                *max_rate_at_target_bps = model.max_rate_at_target_bps.to_le_bytes();
+                *adjustment_speed_bps = model.adjustment_speed_bps.to_le_bytes();
+                *curve_steepness = model.curve_steepness.to_le_bytes();
+            }
+        }
+    }
+
+    /// Unpack a model from a fixed-size buffer
+    #[allow(clippy::ptr_offset_with_cast)]
+    pub fn unpack_from_slice(input: &[u8; INTEREST_RATE_MODEL_LEN]) -> Result<Self, ProgramError> {
+        let (tag, data) = array_refs![input, 1, INTEREST_RATE_MODEL_LEN - 1];
+        match tag[0] {
+            0 => Ok(Self::Kinked),
+            1 => {
+                let (len, points, _) =
+                    array_refs![data, 1, RATE_CURVE_POINT_LEN * MAX_RATE_CURVE_POINTS, 3];
+                let mut curve = RateCurve {
+                    len: len[0],
+                    ..RateCurve::default()
+                };
+                for (point, src) in curve
+                    .points
+                    .iter_mut()
+                    .zip(points.chunks_exact(RATE_CURVE_POINT_LEN))
+                {
+                    let src = array_ref![src, 0, RATE_CURVE_POINT_LEN];
+                    let (utilization_bps, borrow_rate_bps) = array_refs![src, 2, 4];
+                    point.utilization_bps = u16::from_le_bytes(*utilization_bps);
+                    point.borrow_rate_bps = u32::from_le_bytes(*borrow_rate_bps);
+                }
+                Ok(Self::PiecewiseLinear(curve))
+            }
+            2 => {
+                let (
+                    target_utilization_bps,
+                    initial_rate_at_target_bps,
+                    min_rate_at_target_bps,
+                    max_rate_at_target_bps,
+                    adjustment_speed_bps,
+                    curve_steepness,
+                    _,
+                ) = array_refs![data, 2, 4, 4, 4, 4, 1, 21];
+                Ok(Self::Adaptive(AdaptiveRateModel {
+                    target_utilization_bps: u16::from_le_bytes(*target_utilization_bps),
+                    initial_rate_at_target_bps: u32::from_le_bytes(*initial_rate_at_target_bps),
+                    min_rate_at_target_bps: u32::from_le_bytes(*min_rate_at_target_bps),
+                    max_rate_at_target_bps: u32::from_le_bytes(*max_rate_at_target_bps),
+                    adjustment_speed_bps: u32::from_le_bytes(*adjustment_speed_bps),
+                    curve_steepness: u8::from_le_bytes(*curve_steepness),
+                }))
+            }
+            _ => {
+                msg!("Interest rate model cannot be unpacked");
+                Err(ProgramError::InvalidAccountData)
+            }
+        }
+    }
+}
+
+/// Point on a borrow rate curve
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RateCurvePoint {
+    /// Utilization rate, in basis points
+    pub utilization_bps: u16,
+    /// Borrow APY at this utilization rate, in basis points
+    pub borrow_rate_bps: u32,
+}
+
+/// Piecewise-linear borrow rate curve
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RateCurve {
+    /// Number of points used
+    pub len: u8,
+    /// Points of the curve, from 0% to 100% utilization, followed by unused
+    /// points
+    pub points: [RateCurvePoint; MAX_RATE_CURVE_POINTS],
+}
+
+impl RateCurve {
+    /// Create a curve from its points
+    pub fn new(points: &[RateCurvePoint]) -> Result<Self, ProgramError> {
+        if points.len() > MAX_RATE_CURVE_POINTS {
+            msg!(
+                "Borrow rate curve cannot have more than {} points",
+                MAX_RATE_CURVE_POINTS
+            );
+            return Err(LendingError::InvalidConfig.into());
+        }
+        let mut curve = Self {
+            len: points.len() as u8,
+            ..Self::default()
+        };
+        curve.points[..points.len()].copy_from_slice(points);
+        Ok(curve)
+    }
+
+    /// Points used by the curve
+    pub fn points(&self) -> &[RateCurvePoint] {
+        &self.points[..(self.len as usize).min(MAX_RATE_CURVE_POINTS)]
+    }
+
+    /// Validate the curve, when initializing or modifying the reserve configs
+    pub fn validate(&self) -> ProgramResult {
+        if self.len < 2 || self.len as usize > MAX_RATE_CURVE_POINTS {
+            msg!(
+                "Borrow rate curve must have between 2 and {} points",
+                MAX_RATE_CURVE_POINTS
+            );
+            return Err(LendingError::InvalidConfig.into());
+        }
+        let points = self.points();
+        if points[0].utilization_bps != 0
+            || points[points.len() - 1].utilization_bps != MAX_UTILIZATION_BPS
+        {
+            msg!("Borrow rate curve must start at 0% and end at 100% utilization");
+            return Err(LendingError::InvalidConfig.into());
+        }
+        for pair in points.windows(2) {
+            if pair[0].utilization_bps >= pair[1].utilization_bps {
+                msg!("Borrow rate curve utilization rates must be strictly increasing");
+                return Err(LendingError::InvalidConfig.into());
+            }
+            if pair[0].borrow_rate_bps > pair[1].borrow_rate_bps {
+                msg!("Borrow rate curve borrow rates must not decrease");
+                return Err(LendingError::InvalidConfig.into());
+            }
+        }
+        if points[points.len() - 1].borrow_rate_bps > MAX_BORROW_RATE_BPS {
+            msg!(
+                "Borrow rate curve borrow rates must be in range [0, {}] basis points",
+                MAX_BORROW_RATE_BPS
+            );
+            return Err(LendingError::InvalidConfig.into());
+        }
+        Ok(())
+    }
+
+    /// Calculate the borrow rate at a utilization rate
+    pub fn borrow_rate(&self, utilization_rate: Rate) -> Result<Rate, ProgramError> {
+        let points = self.points();
+        let end = points
+            .iter()
+            .position(|point| utilization_rate <= Rate::from_bps(point.utilization_bps.into()))
+            .unwrap_or(points.len() - 1)
+            .max(1);
+        let (start, end) = (points[end - 1], points[end]);
+
+        let start_utilization_rate = Rate::from_bps(start.utilization_bps.into());
+        let start_borrow_rate = Rate::from_bps(start.borrow_rate_bps);
+        let normalized_rate = utilization_rate
+            .min(Rate::from_bps(end.utilization_bps.into()))
+            .try_sub(start_utilization_rate)?
+            .try_div(Rate::from_bps(end.utilization_bps.into()).try_sub(start_utilization_rate)?)?;
+        let rate_range = Rate::from_bps(end.borrow_rate_bps).try_sub(start_borrow_rate)?;
+
+        normalized_rate
+            .try_mul(rate_range)?
+            .try_add(start_borrow_rate)
+    }
+}
+
+/// Borrow rate curve around a rate at target utilization, which rises while
+/// utilization is above target and falls while it is below
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AdaptiveRateModel {
+    /// Utilization rate the model steers toward, in basis points
+    pub target_utilization_bps: u16,
+    /// Borrow APY at target utilization when the model is first used, in
+    /// basis points
+    pub initial_rate_at_target_bps: u32,
+    /// Minimum borrow APY at target utilization, in basis points
+    pub min_rate_at_target_bps: u32,
+    /// Maximum borrow APY at target utilization, in basis points
+    pub max_rate_at_target_bps: u32,
+    /// Yearly rate of change of the rate at target at 0% or 100% utilization,
+    /// in basis points, compounded every slot
+    pub adjustment_speed_bps: u32,
+    /// Ratio of the borrow rate at 100% utilization to the rate at target,
+    /// and of the rate at target to the borrow rate at 0% utilization
+    pub curve_steepness: u8,
+}
+
+impl AdaptiveRateModel {
+    /// Validate the model, when initializing or modifying the reserve configs
+    pub fn validate(&self) -> ProgramResult {
+        if self.target_utilization_bps == 0 || self.target_utilization_bps >= MAX_UTILIZATION_BPS {
+            msg!("Target utilization rate must be in range (0, 10_000) basis points");
+            return Err(LendingError::InvalidConfig.into());
+        }
+        if self.min_rate_at_target_bps == 0
+            || self.min_rate_at_target_bps > self.initial_rate_at_target_bps
+            || self.initial_rate_at_target_bps > self.max_rate_at_target_bps
+        {
+            msg!("Rates at target must satisfy 0 < min <= initial <= max");
+            return Err(LendingError::InvalidConfig.into());
+        }
+        if self.curve_steepness == 0 {
+            msg!("Curve steepness must be at least 1");
+            return Err(LendingError::InvalidConfig.into());
+        }
+        if u64::from(self.max_rate_at_target_bps) * u64::from(self.curve_steepness)
+            > u64::from(MAX_BORROW_RATE_BPS)
+        {
+            msg!(
+                "Max rate at target times curve steepness must be in range [0, {}] basis points",
+                MAX_BORROW_RATE_BPS
+            );
+            return Err(LendingError::InvalidConfig.into());
+        }
+        Ok(())
+    }
+
+    /// Calculate the borrow rate at a utilization rate, given the current rate
+    /// at target utilization
+    pub fn borrow_rate(
+        &self,
+        utilization_rate: Rate,
+        rate_at_target: Rate,
+    ) -> Result<Rate, ProgramError> {
+        let steepness = Rate::one().try_mul(self.curve_steepness as u64)?;
+        let (error, above_target) = self.utilization_error(utilization_rate)?;
+        let multiplier = if above_target {
+            // rate_at_target * (1 + error * (steepness - 1))
+            Rate::one().try_add(error.try_mul(steepness.try_sub(Rate::one())?)?)?
+        } else {
+            // rate_at_target * (1 - error * (1 - 1 / steepness))
+            let range = Rate::one().try_sub(Rate::one().try_div(steepness)?)?;
+            Rate::one().try_sub(error.try_mul(range)?)?
+        };
+        rate_at_target.try_mul(multiplier)
+    }
+
+    /// Adjust the rate at target utilization for the slots elapsed at a
+    /// utilization rate
+    pub fn adjust_rate_at_target(
+        &self,
+        utilization_rate: Rate,
+        rate_at_target: Rate,
+        slots_elapsed: u64,
+    ) -> Result<Rate, ProgramError> {
+        let min_rate_at_target = Rate::from_bps(self.min_rate_at_target_bps);
+        let max_rate_at_target = Rate::from_bps(self.max_rate_at_target_bps);
+        let (error, above_target) = self.utilization_error(utilization_rate)?;
+        let slot_adjustment = Rate::from_bps(self.adjustment_speed_bps)
+            .try_mul(error)?
+            .try_div(SLOTS_PER_YEAR)?;
+
+        let rate_at_target = if above_target {
+            // saturate rather than fail to refresh after a long time
+            Rate::one()
+                .try_add(slot_adjustment)?
+                .try_pow(slots_elapsed)
+                .and_then(|adjustment| rate_at_target.try_mul(adjustment))
+                .unwrap_or(max_rate_at_target)
+        } else {
+            let adjustment = Rate::one()
+                .try_sub(slot_adjustment)?
+                .try_pow(slots_elapsed)?;
+            rate_at_target.try_mul(adjustment)?
+        };
+
+        Ok(rate_at_target.clamp(min_rate_at_target, max_rate_at_target))
+    }
+
+    /// Distance of the utilization rate from target, normalized to [0, 1], and
+    /// whether utilization is above target
+    fn utilization_error(&self, utilization_rate: Rate) -> Result<(Rate, bool), ProgramError> {
+        let target_utilization_rate = Rate::from_bps(self.target_utilization_bps.into());
+        if utilization_rate > target_utilization_rate {
+            let error = utilization_rate
+                .min(Rate::one())
+                .try_sub(target_utilization_rate)?
+                .try_div(Rate::one().try_sub(target_utilization_rate)?)?;
+            Ok((error, true))
+        } else {
+            let error = target_utilization_rate
+                .try_sub(utilization_rate)?
+                .try_div(target_utilization_rate)?;
+            Ok((error, false))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, proptest::prelude::*};
+
+    fn curve() -> RateCurve {
+        RateCurve::new(&[
+            RateCurvePoint {
+                utilization_bps: 0,
+                borrow_rate_bps: 50,
+            },
+            RateCurvePoint {
+                utilization_bps: 8_000,
+                borrow_rate_bps: 800,
+            },
+            RateCurvePoint {
+                utilization_bps: 9_500,
+                borrow_rate_bps: 2_500,
+            },
+            RateCurvePoint {
+                utilization_bps: 10_000,
+                borrow_rate_bps: 50_000,
+            },
+        ])
+        .unwrap()
+    }
+
+    fn adaptive_model() -> AdaptiveRateModel {
+        AdaptiveRateModel {
+            target_utilization_bps: 9_000,
+            initial_rate_at_target_bps: 400,
+            min_rate_at_target_bps: 10,
+            max_rate_at_target_bps: 20_000,
+            adjustment_speed_bps: 500_000,
+            curve_steepness: 4,
+        }
+    }
+
+    #[test]
+    fn rate_curve_interpolates_between_points() {
+        let curve = curve();
+        curve.validate().unwrap();
+        assert_eq!(curve.borrow_rate(Rate::zero()).unwrap(), Rate::from_bps(50));
+        assert_eq!(
+            curve.borrow_rate(Rate::from_bps(4_000)).unwrap(),
+            Rate::from_bps(425)
+        );
+        assert_eq!(
+            curve.borrow_rate(Rate::from_bps(8_000)).unwrap(),
+            Rate::from_bps(800)
+        );
+        assert_eq!(
+            curve.borrow_rate(Rate::from_bps(9_000)).unwrap(),
+            Rate::from_scaled_val(193_333_333_333_333_333)
+        );
+        // rates above 255% are supported
+        assert_eq!(
+            curve.borrow_rate(Rate::one()).unwrap(),
+            Rate::from_bps(50_000)
+        );
+    }
+
+    #[test]
+    fn rate_curve_validation() {
+        let valid = curve();
+        let mut points = valid.points().to_vec();
+
+        points[0].utilization_bps = 1;
+        assert_eq!(
+            RateCurve::new(&points).unwrap().validate(),
+            Err(LendingError::InvalidConfig.into())
+        );
+
+        let mut points = valid.points().to_vec();
+        points[3].utilization_bps = 9_999;
+        assert_eq!(
+            RateCurve::new(&points).unwrap().validate(),
+            Err(LendingError::InvalidConfig.into())
+        );
+
+        let mut points = valid.points().to_vec();
+        points[2].utilization_bps = 8_000;
+        assert_eq!(
+            RateCurve::new(&points).unwrap().validate(),
+            Err(LendingError::InvalidConfig.into())
+        );
+
+        let mut points = valid.points().to_vec();
+        points[2].borrow_rate_bps = 700;
+        assert_eq!(
+            RateCurve::new(&points).unwrap().validate(),
+            Err(LendingError::InvalidConfig.into())
+        );
+
+        let mut points = valid.points().to_vec();
+        points[3].borrow_rate_bps = MAX_BORROW_RATE_BPS + 1;
+        assert_eq!(
+            RateCurve::new(&points).unwrap().validate(),
+            Err(LendingError::InvalidConfig.into())
+        );
+
+        assert_eq!(
+            RateCurve::new(&points[..1]).unwrap().validate(),
+            Err(LendingError::InvalidConfig.into())
+        );
+        assert!(RateCurve::new(&[RateCurvePoint::default(); MAX_RATE_CURVE_POINTS + 1]).is_err());
+    }
+
+    #[test]
+    fn adaptive_model_validation() {
+        adaptive_model().validate().unwrap();
+        for model in [
+            AdaptiveRateModel {
+                target_utilization_bps: 0,
+                ..adaptive_model()
+            },
+            AdaptiveRateModel {
+                target_utilization_bps: MAX_UTILIZATION_BPS,
+                ..adaptive_model()
+            },
+            AdaptiveRateModel {
+                min_rate_at_target_bps: 0,
+                ..adaptive_model()
+            },
+            AdaptiveRateModel {
+                initial_rate_at_target_bps: 20_001,
+                ..adaptive_model()
+            },
+            AdaptiveRateModel {
+                curve_steepness: 0,
+                ..adaptive_model()
+            },
+            AdaptiveRateModel {
+                curve_steepness: 51,
+                ..adaptive_model()
+            },
+        ] {
+            assert_eq!(model.validate(), Err(LendingError::InvalidConfig.into()));
+        }
+    }
+
+    #[test]
+    fn adaptive_model_borrow_rate() {
+        let model = adaptive_model();
+        let rate_at_target = Rate::from_bps(400);
+        assert_eq!(
+            model.borrow_rate(Rate::zero(), rate_at_target).unwrap(),
+            Rate::from_bps(100)
+        );
+        assert_eq!(
+            model
+                .borrow_rate(Rate::from_bps(9_000), rate_at_target)
+                .unwrap(),
+            rate_at_target
+        );
+        assert_eq!(
+            model.borrow_rate(Rate::one(), rate_at_target).unwrap(),
+            Rate::from_bps(1_600)
+        );
+    }
+
+    #[test]
+    fn adaptive_model_adjusts_rate_at_target() {
+        let model = adaptive_model();
+        let rate_at_target = Rate::from_bps(400);
+        let slots_per_day = SLOTS_PER_YEAR / 365;
+
+        let raised = model
+            .adjust_rate_at_target(Rate::from_bps(9_500), rate_at_target, slots_per_day)
+            .unwrap();
+        assert!(raised > rate_at_target);
+        let lowered = model
+            .adjust_rate_at_target(Rate::from_bps(8_000), rate_at_target, slots_per_day)
+            .unwrap();
+        assert!(lowered < rate_at_target);
+        assert_eq!(
+            model
+                .adjust_rate_at_target(Rate::from_bps(9_000), rate_at_target, slots_per_day)
+                .unwrap(),
+            rate_at_target
+        );
+
+        // bounded, even after a very long time
+        assert_eq!(
+            model
+                .adjust_rate_at_target(Rate::one(), rate_at_target, u64::MAX)
+                .unwrap(),
+            Rate::from_bps(model.max_rate_at_target_bps)
+        );
+        assert_eq!(
+            model
+                .adjust_rate_at_target(Rate::zero(), rate_at_target, u64::MAX)
+                .unwrap(),
+            Rate::from_bps(model.min_rate_at_target_bps)
+        );
+    }
+
+    #[test]
+    fn interest_rate_model_packing() {
+        for model in [
+            InterestRateModel::Kinked,
+            InterestRateModel::PiecewiseLinear(curve()),
+            InterestRateModel::Adaptive(adaptive_model()),
+        ] {
+            let mut packed = [0xff; INTEREST_RATE_MODEL_LEN];
+            model.pack_into_slice(&mut packed);
+            assert_eq!(
+                InterestRateModel::unpack_from_slice(&packed).unwrap(),
+                model
+            );
+        }
+
+        let mut packed = [0; INTEREST_RATE_MODEL_LEN];
+        packed[0] = 3;
+        assert_eq!(
+            InterestRateModel::unpack_from_slice(&packed),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn rate_curve_borrow_rate_is_bounded(
+            utilization_bps in 0..=MAX_UTILIZATION_BPS,
+            first_rate_bps in 0..=MAX_BORROW_RATE_BPS,
+            last_rate_bps in 0..=MAX_BORROW_RATE_BPS,
+            kink_bps in 1..MAX_UTILIZATION_BPS,
+        ) {
+            let (min_rate_bps, max_rate_bps) = if first_rate_bps < last_rate_bps {
+                (first_rate_bps, last_rate_bps)
+            } else {
+                (last_rate_bps, first_rate_bps)
+            };
+            let curve = RateCurve::new(&[
+                RateCurvePoint { utilization_bps: 0, borrow_rate_bps: min_rate_bps },
+                RateCurvePoint { utilization_bps: kink_bps, borrow_rate_bps: min_rate_bps },
+                RateCurvePoint { utilization_bps: MAX_UTILIZATION_BPS, borrow_rate_bps: max_rate_bps },
+            ])?;
+            curve.validate()?;
+
+            let borrow_rate = curve.borrow_rate(Rate::from_bps(utilization_bps.into()))?;
+            assert!(borrow_rate >= Rate::from_bps(min_rate_bps));
+            assert!(borrow_rate <= Rate::from_bps(max_rate_bps));
+        }
+    }
+}
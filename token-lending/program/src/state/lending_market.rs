@@ -1,7 +1,10 @@
 use {
     super::*,
+    crate::error::LendingError,
     arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs},
     solana_program::{
+        clock::Slot,
+        entrypoint::ProgramResult,
         msg,
         program_error::ProgramError,
         program_pack::{IsInitialized, Pack, Sealed},
@@ -27,6 +30,30 @@ pub struct LendingMarket {
     pub token_program_id: Pubkey,
     /// Oracle (Pyth) program id
     pub oracle_program_id: Pubkey,
+    /// Percentage of an obligation borrowed value that can be repaid during
+    /// each liquidation call
+    pub liquidation_close_factor: u8,
+    /// Guardian authority which can tighten reserve risk and cancel pending
+    /// reserve configs without the config timelock, default for none
+    pub guardian: Pubkey,
+    /// Slots reserve config and owner changes wait before they can be
+    /// applied, 0 to apply them immediately
+    pub config_timelock_slots: u64,
+    /// Owner taking over once the pending owner slot is reached
+    pub pending_owner: Pubkey,
+    /// Slot from which the pending owner can be applied, 0 for none
+    pub pending_owner_slot: Slot,
+    /// Config timelock taking over once the pending config timelock slot is
+    /// reached
+    pub pending_config_timelock_slots: u64,
+    /// Slot from which the pending config timelock can be applied, 0 for none
+    pub pending_config_timelock_slot: Slot,
+    /// Liquidation close factor taking over once the pending liquidation
+    /// close factor slot is reached
+    pub pending_liquidation_close_factor: u8,
+    /// Slot from which the pending liquidation close factor can be applied, 0
+    /// for none
+    pub pending_liquidation_close_factor_slot: Slot,
 }
 
 impl LendingMarket {
@@ -45,6 +72,112 @@ impl LendingMarket {
         self.quote_currency = params.quote_currency;
         self.token_program_id = params.token_program_id;
         self.oracle_program_id = params.oracle_program_id;
+        self.liquidation_close_factor = LIQUIDATION_CLOSE_FACTOR;
+    }
+
+    /// Validate a liquidation close factor, when setting it on the lending
+    /// market
+    pub fn validate_liquidation_close_factor(liquidation_close_factor: u8) -> ProgramResult {
+        if liquidation_close_factor == 0 || liquidation_close_factor > 100 {
+            msg!("Liquidation close factor must be in range (0, 100]");
+            return Err(LendingError::InvalidConfig.into());
+        }
+        Ok(())
+    }
+
+    /// Whether the authority can tighten reserve risk without the config
+    /// timelock, as the owner or guardian of the lending market
+    pub fn is_risk_authority(&self, authority: &Pubkey) -> bool {
+        &self.owner == authority
+            || (self.guardian != Pubkey::default() && &self.guardian == authority)
+    }
+
+    /// Slot from which a config change queued in the current slot can be
+    /// applied
+    pub fn config_effective_slot(&self, current_slot: Slot) -> Result<Slot, ProgramError> {
+        current_slot
+            .checked_add(self.config_timelock_slots)
+            .ok_or_else(|| LendingError::MathOverflow.into())
+    }
+
+    /// Set the owner, or queue the new owner behind the config timelock
+    pub fn set_owner(&mut self, new_owner: Pubkey, current_slot: Slot) -> ProgramResult {
+        if self.config_timelock_slots == 0 {
+            self.owner = new_owner;
+            self.pending_owner = Pubkey::default();
+            self.pending_owner_slot = 0;
+        } else {
+            self.pending_owner = new_owner;
+            self.pending_owner_slot = self.config_effective_slot(current_slot)?;
+        }
+        Ok(())
+    }
+
+    /// Set the liquidation close factor, or queue it behind the config
+    /// timelock
+    pub fn set_liquidation_close_factor(
+        &mut self,
+        liquidation_close_factor: u8,
+        current_slot: Slot,
+    ) -> ProgramResult {
+        if self.config_timelock_slots == 0 {
+            self.liquidation_close_factor = liquidation_close_factor;
+            self.pending_liquidation_close_factor = 0;
+            self.pending_liquidation_close_factor_slot = 0;
+        } else {
+            self.pending_liquidation_close_factor = liquidation_close_factor;
+            self.pending_liquidation_close_factor_slot =
+                self.config_effective_slot(current_slot)?;
+        }
+        Ok(())
+    }
+
+    /// Set the config timelock. A longer timelock applies immediately, a
+    /// shorter one is queued behind the current timelock.
+    pub fn set_config_timelock(
+        &mut self,
+        config_timelock_slots: u64,
+        current_slot: Slot,
+    ) -> ProgramResult {
+        if config_timelock_slots >= self.config_timelock_slots {
+            self.config_timelock_slots = config_timelock_slots;
+            self.pending_config_timelock_slots = 0;
+            self.pending_config_timelock_slot = 0;
+        } else {
+            self.pending_config_timelock_slots = config_timelock_slots;
+            self.pending_config_timelock_slot = self.config_effective_slot(current_slot)?;
+        }
+        Ok(())
+    }
+
+    /// Apply the pending owner, config timelock and liquidation close factor
+    /// whose slots are reached
+    pub fn apply_pending_changes(&mut self, current_slot: Slot) -> ProgramResult {
+        let apply_owner = self.pending_owner_slot != 0 && self.pending_owner_slot <= current_slot;
+        let apply_config_timelock = self.pending_config_timelock_slot != 0
+            && self.pending_config_timelock_slot <= current_slot;
+        let apply_liquidation_close_factor = self.pending_liquidation_close_factor_slot != 0
+            && self.pending_liquidation_close_factor_slot <= current_slot;
+        if !apply_owner && !apply_config_timelock && !apply_liquidation_close_factor {
+            msg!("Lending market has no pending changes to apply in the current slot");
+            return Err(LendingError::PendingConfigNotReady.into());
+        }
+        if apply_owner {
+            self.owner = self.pending_owner;
+            self.pending_owner = Pubkey::default();
+            self.pending_owner_slot = 0;
+        }
+        if apply_config_timelock {
+            self.config_timelock_slots = self.pending_config_timelock_slots;
+            self.pending_config_timelock_slots = 0;
+            self.pending_config_timelock_slot = 0;
+        }
+        if apply_liquidation_close_factor {
+            self.liquidation_close_factor = self.pending_liquidation_close_factor;
+            self.pending_liquidation_close_factor = 0;
+            self.pending_liquidation_close_factor_slot = 0;
+        }
+        Ok(())
     }
 }
 
@@ -72,7 +205,7 @@ impl IsInitialized for LendingMarket {
     }
 }
 
-const LENDING_MARKET_LEN: usize = 258; // 1 + 1 + 32 + 32 + 32 + 32 + 128
+const LENDING_MARKET_LEN: usize = 258; // 1 + 1 + 32 + 32 + 32 + 32 + 1 + 32 + 8 + 32 + 8 + 8 + 8 + 1 + 8 + 22
 impl Pack for LendingMarket {
     const LEN: usize = LENDING_MARKET_LEN;
 
@@ -86,6 +219,15 @@ impl Pack for LendingMarket {
             quote_currency,
             token_program_id,
             oracle_program_id,
+            liquidation_close_factor,
+            guardian,
+            config_timelock_slots,
+            pending_owner,
+            pending_owner_slot,
+            pending_config_timelock_slots,
+            pending_config_timelock_slot,
+            pending_liquidation_close_factor,
+            pending_liquidation_close_factor_slot,
             _padding,
         ) = mut_array_refs![
             output,
@@ -95,7 +237,16 @@ impl Pack for LendingMarket {
             32,
             PUBKEY_BYTES,
             PUBKEY_BYTES,
-            128
+            1,
+            PUBKEY_BYTES,
+            8,
+            PUBKEY_BYTES,
+            8,
+            8,
+            8,
+            1,
+            8,
+            22
         ];
 
         *version = self.version.to_le_bytes();
@@ -104,6 +255,16 @@ impl Pack for LendingMarket {
         quote_currency.copy_from_slice(self.quote_currency.as_ref());
         token_program_id.copy_from_slice(self.token_program_id.as_ref());
         oracle_program_id.copy_from_slice(self.oracle_program_id.as_ref());
+        *liquidation_close_factor = self.liquidation_close_factor.to_le_bytes();
+        guardian.copy_from_slice(self.guardian.as_ref());
+        *config_timelock_slots = self.config_timelock_slots.to_le_bytes();
+        pending_owner.copy_from_slice(self.pending_owner.as_ref());
+        *pending_owner_slot = self.pending_owner_slot.to_le_bytes();
+        *pending_config_timelock_slots = self.pending_config_timelock_slots.to_le_bytes();
+        *pending_config_timelock_slot = self.pending_config_timelock_slot.to_le_bytes();
+        *pending_liquidation_close_factor = self.pending_liquidation_close_factor.to_le_bytes();
+        *pending_liquidation_close_factor_slot =
+            self.pending_liquidation_close_factor_slot.to_le_bytes();
     }
 
     /// Unpacks a byte buffer into a
@@ -118,6 +279,15 @@ impl Pack for LendingMarket {
             quote_currency,
             token_program_id,
             oracle_program_id,
+            liquidation_close_factor,
+            guardian,
+            config_timelock_slots,
+            pending_owner,
+            pending_owner_slot,
+            pending_config_timelock_slots,
+            pending_config_timelock_slot,
+            pending_liquidation_close_factor,
+            pending_liquidation_close_factor_slot,
             _padding,
         ) = array_refs![
             input,
@@ -127,7 +297,16 @@ impl Pack for LendingMarket {
             32,
             PUBKEY_BYTES,
             PUBKEY_BYTES,
-            128
+            1,
+            PUBKEY_BYTES,
+            8,
+            PUBKEY_BYTES,
+            8,
+            8,
+            8,
+            1,
+            8,
+            22
         ];
 
         let version = u8::from_le_bytes(*version);
@@ -136,6 +315,13 @@ impl Pack for LendingMarket {
             return Err(ProgramError::InvalidAccountData);
         }
 
+        // Lending markets from before configurable close factors left this
+        // byte zeroed and use the default close factor
+        let liquidation_close_factor = match u8::from_le_bytes(*liquidation_close_factor) {
+            0 => LIQUIDATION_CLOSE_FACTOR,
+            liquidation_close_factor => liquidation_close_factor,
+        };
+
         Ok(Self {
             version,
             bump_seed: u8::from_le_bytes(*bump_seed),
@@ -143,6 +329,112 @@ impl Pack for LendingMarket {
             quote_currency: *quote_currency,
             token_program_id: Pubkey::new_from_array(*token_program_id),
             oracle_program_id: Pubkey::new_from_array(*oracle_program_id),
+            liquidation_close_factor,
+            guardian: Pubkey::new_from_array(*guardian),
+            config_timelock_slots: u64::from_le_bytes(*config_timelock_slots),
+            pending_owner: Pubkey::new_from_array(*pending_owner),
+            pending_owner_slot: u64::from_le_bytes(*pending_owner_slot),
+            pending_config_timelock_slots: u64::from_le_bytes(*pending_config_timelock_slots),
+            pending_config_timelock_slot: u64::from_le_bytes(*pending_config_timelock_slot),
+            pending_liquidation_close_factor: u8::from_le_bytes(*pending_liquidation_close_factor),
+            pending_liquidation_close_factor_slot: u64::from_le_bytes(
+                *pending_liquidation_close_factor_slot,
+            ),
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn config_timelock() {
+        let owner = Pubkey::new_unique();
+        let mut lending_market = LendingMarket {
+            owner,
+            ..LendingMarket::default()
+        };
+
+        // without a timelock owner changes apply immediately
+        let new_owner = Pubkey::new_unique();
+        lending_market.set_owner(new_owner, 10).unwrap();
+        assert_eq!(lending_market.owner, new_owner);
+        assert_eq!(
+            lending_market.apply_pending_changes(10),
+            Err(LendingError::PendingConfigNotReady.into())
+        );
+
+        // a longer timelock applies immediately and queues owner changes
+        lending_market.set_config_timelock(100, 10).unwrap();
+        assert_eq!(lending_market.config_timelock_slots, 100);
+        lending_market.set_owner(owner, 10).unwrap();
+        assert_eq!(lending_market.owner, new_owner);
+        assert_eq!(lending_market.pending_owner, owner);
+        assert_eq!(lending_market.pending_owner_slot, 110);
+
+        // a shorter timelock is queued behind the current one
+        lending_market.set_config_timelock(0, 20).unwrap();
+        assert_eq!(lending_market.config_timelock_slots, 100);
+        assert_eq!(lending_market.pending_config_timelock_slot, 120);
+
+        assert_eq!(
+            lending_market.apply_pending_changes(109),
+            Err(LendingError::PendingConfigNotReady.into())
+        );
+        lending_market.apply_pending_changes(110).unwrap();
+        assert_eq!(lending_market.owner, owner);
+        assert_eq!(lending_market.pending_owner_slot, 0);
+        assert_eq!(lending_market.config_timelock_slots, 100);
+        lending_market.apply_pending_changes(120).unwrap();
+        assert_eq!(lending_market.config_timelock_slots, 0);
+        assert_eq!(lending_market.pending_config_timelock_slot, 0);
+    }
+
+    #[test]
+    fn liquidation_close_factor_timelock() {
+        let mut lending_market = LendingMarket {
+            version: PROGRAM_VERSION,
+            liquidation_close_factor: LIQUIDATION_CLOSE_FACTOR,
+            ..LendingMarket::default()
+        };
+        lending_market.set_liquidation_close_factor(40, 10).unwrap();
+        assert_eq!(lending_market.liquidation_close_factor, 40);
+
+        lending_market.set_config_timelock(100, 10).unwrap();
+        lending_market.set_liquidation_close_factor(80, 10).unwrap();
+        assert_eq!(lending_market.liquidation_close_factor, 40);
+        assert_eq!(lending_market.pending_liquidation_close_factor, 80);
+        assert_eq!(lending_market.pending_liquidation_close_factor_slot, 110);
+
+        assert_eq!(
+            lending_market.apply_pending_changes(109),
+            Err(LendingError::PendingConfigNotReady.into())
+        );
+        lending_market.apply_pending_changes(110).unwrap();
+        assert_eq!(lending_market.liquidation_close_factor, 80);
+        assert_eq!(lending_market.pending_liquidation_close_factor, 0);
+        assert_eq!(lending_market.pending_liquidation_close_factor_slot, 0);
+
+        let packed = &mut [0u8; LendingMarket::LEN];
+        lending_market.set_liquidation_close_factor(60, 20).unwrap();
+        LendingMarket::pack(lending_market.clone(), packed).unwrap();
+        assert_eq!(LendingMarket::unpack(packed).unwrap(), lending_market);
+    }
+
+    #[test]
+    fn risk_authority() {
+        let mut lending_market = LendingMarket {
+            owner: Pubkey::new_unique(),
+            ..LendingMarket::default()
+        };
+        let owner = lending_market.owner;
+        assert!(lending_market.is_risk_authority(&owner));
+        assert!(!lending_market.is_risk_authority(&Pubkey::default()));
+
+        let guardian = Pubkey::new_unique();
+        lending_market.guardian = guardian;
+        assert!(lending_market.is_risk_authority(&guardian));
+        assert!(!lending_market.is_risk_authority(&Pubkey::new_unique()));
+    }
+}
@@ -1,8 +1,11 @@
 //! State types
 
+mod interest_rate_model;
 mod last_update;
 mod lending_market;
 mod obligation;
+mod oracle_config;
+mod pending_reserve_config;
 mod reserve;
 
 use {
@@ -13,7 +16,10 @@ use {
         program_error::ProgramError,
     },
 };
-pub use {last_update::*, lending_market::*, obligation::*, reserve::*};
+pub use {
+    interest_rate_model::*, last_update::*, lending_market::*, obligation::*, oracle_config::*,
+    pending_reserve_config::*, reserve::*,
+};
 
 /// Collateral tokens are initially valued at a ratio of 5:1
 /// (collateral:liquidity)
@@ -22,7 +28,7 @@ pub const INITIAL_COLLATERAL_RATIO: u64 = 1;
 const INITIAL_COLLATERAL_RATE: u64 = INITIAL_COLLATERAL_RATIO * WAD;
 
 /// Current version of the program and all new accounts created
-pub const PROGRAM_VERSION: u8 = 1;
+pub const PROGRAM_VERSION: u8 = 2;
 
 /// Accounts are created with data zeroed out, so uninitialized state instances
 /// will have the version set to 0.
@@ -23,6 +23,10 @@ use {
 /// obligation
 pub const MAX_OBLIGATION_RESERVES: usize = 10;
 
+/// Max number of collateral and liquidity reserve accounts combined for an
+/// obligation in an extended account
+pub const MAX_EXTENDED_OBLIGATION_RESERVES: usize = 32;
+
 /// Lending market obligation state
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct Obligation {
@@ -47,6 +51,15 @@ pub struct Obligation {
     pub allowed_borrow_value: Decimal,
     /// The dangerous borrow value at the weighted average liquidation threshold
     pub unhealthy_borrow_value: Decimal,
+    /// Elevation group of all deposits and borrows as of the last refresh, 0
+    /// if none
+    pub elevation_group: u8,
+    /// Whether the deposits included isolated collateral as of the last
+    /// refresh
+    pub isolated_collateral: bool,
+    /// Refresh of the deposits and borrows split across several instructions
+    /// in progress
+    pub refresh_progress: ObligationRefreshProgress,
 }
 
 impl Obligation {
@@ -67,6 +80,142 @@ impl Obligation {
         self.borrows = params.borrows;
     }
 
+    /// Max number of deposits and borrows combined an obligation account of
+    /// the given length has room for
+    pub fn max_reserves(data_len: usize) -> usize {
+        if data_len == EXTENDED_OBLIGATION_LEN {
+            MAX_EXTENDED_OBLIGATION_RESERVES
+        } else {
+            MAX_OBLIGATION_RESERVES
+        }
+    }
+
+    /// Mark the obligation stale, abandoning any refresh in progress
+    pub fn mark_stale(&mut self) {
+        self.last_update.mark_stale();
+        self.refresh_progress = ObligationRefreshProgress::default();
+    }
+
+    /// Number of deposits and borrows combined, in the order they are
+    /// refreshed
+    pub fn positions_len(&self) -> usize {
+        self.deposits.len() + self.borrows.len()
+    }
+
+    /// Start refreshing the market values of deposits and borrows in the
+    /// current slot
+    pub fn begin_refresh(&mut self, current_slot: Slot) {
+        self.deposited_value = Decimal::zero();
+        self.borrowed_value = Decimal::zero();
+        self.allowed_borrow_value = Decimal::zero();
+        self.unhealthy_borrow_value = Decimal::zero();
+        self.refresh_progress = ObligationRefreshProgress {
+            slot: current_slot,
+            single_elevation_group: true,
+            ..ObligationRefreshProgress::default()
+        };
+    }
+
+    /// Record the market value of the next deposit in the refresh in
+    /// progress, with the config of its deposit reserve
+    pub fn refresh_collateral(
+        &mut self,
+        market_value: Decimal,
+        config: &ReserveConfig,
+        collateral_modes: bool,
+    ) -> ProgramResult {
+        let index = self.refresh_progress.next_index as usize;
+        let collateral = self
+            .deposits
+            .get_mut(index)
+            .ok_or(LendingError::InvalidObligationRefresh)?;
+        collateral.market_value = market_value;
+        self.deposited_value = self.deposited_value.try_add(market_value)?;
+        self.refresh_progress
+            .add_elevation_group(config.elevation_group.id);
+
+        // Isolated collateral only counts towards borrowing power when a
+        // single asset is borrowed
+        let isolated_borrowing = collateral_modes && self.borrows.len() <= 1;
+        let collateral_ltv = |loan_to_value_ratio| {
+            if config.isolated_collateral && !isolated_borrowing {
+                Rate::zero()
+            } else {
+                Rate::from_percent(loan_to_value_ratio)
+            }
+        };
+        let progress = &mut self.refresh_progress;
+        progress.isolated_collateral |= config.isolated_collateral;
+
+        let (loan_to_value_ratio, liquidation_threshold) = config.collateral_ratios(0);
+        self.allowed_borrow_value = self
+            .allowed_borrow_value
+            .try_add(market_value.try_mul(collateral_ltv(loan_to_value_ratio))?)?;
+        self.unhealthy_borrow_value = self
+            .unhealthy_borrow_value
+            .try_add(market_value.try_mul(Rate::from_percent(liquidation_threshold))?)?;
+
+        let (loan_to_value_ratio, liquidation_threshold) =
+            config.collateral_ratios(progress.elevation_group);
+        progress.elevated_allowed_borrow_value = progress
+            .elevated_allowed_borrow_value
+            .try_add(market_value.try_mul(collateral_ltv(loan_to_value_ratio))?)?;
+        progress.elevated_unhealthy_borrow_value = progress
+            .elevated_unhealthy_borrow_value
+            .try_add(market_value.try_mul(Rate::from_percent(liquidation_threshold))?)?;
+
+        progress.next_index += 1;
+        Ok(())
+    }
+
+    /// Record the market value of the next borrow in the refresh in progress,
+    /// with the elevation group of its borrow reserve
+    pub fn refresh_liquidity(
+        &mut self,
+        market_value: Decimal,
+        elevation_group: u8,
+    ) -> ProgramResult {
+        let index = (self.refresh_progress.next_index as usize)
+            .checked_sub(self.deposits.len())
+            .ok_or(LendingError::InvalidObligationRefresh)?;
+        let liquidity = self
+            .borrows
+            .get_mut(index)
+            .ok_or(LendingError::InvalidObligationRefresh)?;
+        liquidity.market_value = market_value;
+        self.borrowed_value = self.borrowed_value.try_add(market_value)?;
+        self.refresh_progress.add_elevation_group(elevation_group);
+        self.refresh_progress.next_index += 1;
+        Ok(())
+    }
+
+    /// Finish the refresh in progress once every deposit and borrow has been
+    /// recorded, marking the obligation fresh
+    pub fn finish_refresh(&mut self, current_slot: Slot, collateral_modes: bool) -> ProgramResult {
+        if self.refresh_progress.next_index as usize != self.positions_len() {
+            msg!("Obligation refresh has not recorded every deposit and borrow");
+            return Err(LendingError::InvalidObligationRefresh.into());
+        }
+        let progress = std::mem::take(&mut self.refresh_progress);
+
+        // Elevated ratios apply only while every deposit and borrow reserve
+        // shares the same elevation group
+        self.elevation_group =
+            if collateral_modes && !self.borrows.is_empty() && progress.single_elevation_group {
+                progress.elevation_group
+            } else {
+                0
+            };
+        if self.elevation_group != 0 {
+            self.allowed_borrow_value = progress.elevated_allowed_borrow_value;
+            self.unhealthy_borrow_value = progress.elevated_unhealthy_borrow_value;
+        }
+        self.isolated_collateral = progress.isolated_collateral;
+
+        self.last_update.update_slot(current_slot);
+        Ok(())
+    }
+
     /// Calculate the current ratio of borrowed value to deposited value
     pub fn loan_to_value(&self) -> Result<Decimal, ProgramError> {
         self.borrowed_value.try_div(self.deposited_value)
@@ -115,14 +264,16 @@ impl Obligation {
         self.allowed_borrow_value.try_sub(self.borrowed_value)
     }
 
-    /// Calculate the maximum liquidation amount for a given liquidity
+    /// Calculate the maximum liquidation amount for a given liquidity, with
+    /// the lending market liquidation close factor as a percentage
     pub fn max_liquidation_amount(
         &self,
         liquidity: &ObligationLiquidity,
+        liquidation_close_factor: u8,
     ) -> Result<Decimal, ProgramError> {
         let max_liquidation_value = self
             .borrowed_value
-            .try_mul(Rate::from_percent(LIQUIDATION_CLOSE_FACTOR))?
+            .try_mul(Rate::from_percent(liquidation_close_factor))?
             .min(liquidity.market_value);
         let max_liquidation_pct = max_liquidation_value.try_div(liquidity.market_value)?;
         liquidity.borrowed_amount_wads.try_mul(max_liquidation_pct)
@@ -143,18 +294,20 @@ impl Obligation {
         Ok((&self.deposits[collateral_index], collateral_index))
     }
 
-    /// Find or add collateral by deposit reserve
+    /// Find or add collateral by deposit reserve, with room for at most
+    /// `max_reserves` deposits and borrows combined
     pub fn find_or_add_collateral_to_deposits(
         &mut self,
         deposit_reserve: Pubkey,
+        max_reserves: usize,
     ) -> Result<&mut ObligationCollateral, ProgramError> {
         if let Some(collateral_index) = self._find_collateral_index_in_deposits(deposit_reserve) {
             return Ok(&mut self.deposits[collateral_index]);
         }
-        if self.deposits.len() + self.borrows.len() >= MAX_OBLIGATION_RESERVES {
+        if self.positions_len() >= max_reserves {
             msg!(
                 "Obligation cannot have more than {} deposits and borrows combined",
-                MAX_OBLIGATION_RESERVES
+                max_reserves
             );
             return Err(LendingError::ObligationReserveLimit.into());
         }
@@ -184,18 +337,20 @@ impl Obligation {
         Ok((&self.borrows[liquidity_index], liquidity_index))
     }
 
-    /// Find or add liquidity by borrow reserve
+    /// Find or add liquidity by borrow reserve, with room for at most
+    /// `max_reserves` deposits and borrows combined
     pub fn find_or_add_liquidity_to_borrows(
         &mut self,
         borrow_reserve: Pubkey,
+        max_reserves: usize,
     ) -> Result<&mut ObligationLiquidity, ProgramError> {
         if let Some(liquidity_index) = self._find_liquidity_index_in_borrows(borrow_reserve) {
             return Ok(&mut self.borrows[liquidity_index]);
         }
-        if self.deposits.len() + self.borrows.len() >= MAX_OBLIGATION_RESERVES {
+        if self.positions_len() >= max_reserves {
             msg!(
                 "Obligation cannot have more than {} deposits and borrows combined",
-                MAX_OBLIGATION_RESERVES
+                max_reserves
             );
             return Err(LendingError::ObligationReserveLimit.into());
         }
@@ -233,6 +388,40 @@ impl IsInitialized for Obligation {
     }
 }
 
+/// Progress of an obligation refresh split across several
+/// RefreshObligationChunk instructions in the same slot
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ObligationRefreshProgress {
+    /// Slot the refresh started in
+    pub slot: Slot,
+    /// Index of the next deposit or borrow to refresh, counting deposits
+    /// before borrows, 0 if no refresh is in progress
+    pub next_index: u8,
+    /// Elevation group of the first deposit or borrow refreshed
+    pub elevation_group: u8,
+    /// Whether every deposit and borrow refreshed so far shares the elevation
+    /// group
+    pub single_elevation_group: bool,
+    /// Whether the deposits refreshed so far include isolated collateral
+    pub isolated_collateral: bool,
+    /// Allowed borrow value of the deposits refreshed so far at the elevated
+    /// loan to value ratios
+    pub elevated_allowed_borrow_value: Decimal,
+    /// Unhealthy borrow value of the deposits refreshed so far at the
+    /// elevated liquidation thresholds
+    pub elevated_unhealthy_borrow_value: Decimal,
+}
+
+impl ObligationRefreshProgress {
+    fn add_elevation_group(&mut self, elevation_group: u8) {
+        if self.next_index == 0 {
+            self.elevation_group = elevation_group;
+        } else if self.elevation_group != elevation_group {
+            self.single_elevation_group = false;
+        }
+    }
+}
+
 /// Obligation collateral state
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct ObligationCollateral {
@@ -335,13 +524,99 @@ impl ObligationLiquidity {
 
 const OBLIGATION_COLLATERAL_LEN: usize = 56; // 32 + 8 + 16
 const OBLIGATION_LIQUIDITY_LEN: usize = 80; // 32 + 16 + 16 + 16
-const OBLIGATION_LEN: usize = 916; // 1 + 8 + 1 + 32 + 32 + 16 + 16 + 16 + 16 + 1 + 1 + (56 * 1) + (80 * 9)
-                                   // @TODO: break this up by obligation / collateral / liquidity https://git.io/JOCca
+/// Length of obligations created before isolated collateral and elevation
+/// groups, which are unpacked with neither
+pub const LEGACY_OBLIGATION_LEN: usize = 916; // 1 + 8 + 1 + 32 + 32 + 16 + 16 + 16 + 16 + 1 + 1 + (56 * 1) + (80 * 9)
+const OBLIGATION_DATA_FLAT_LEN: usize =
+    OBLIGATION_COLLATERAL_LEN + (OBLIGATION_LIQUIDITY_LEN * (MAX_OBLIGATION_RESERVES - 1));
+const OBLIGATION_LEN: usize = 980; // 916 + 1 + 1 + 8 + 1 + 1 + 1 + 1 + 16 + 16 + 18
+/// Length of obligations with room for up to
+/// [MAX_EXTENDED_OBLIGATION_RESERVES] deposits and borrows, which keep them
+/// after the standard layout instead of in its data_flat
+pub const EXTENDED_OBLIGATION_LEN: usize =
+    OBLIGATION_LEN + (OBLIGATION_LIQUIDITY_LEN * MAX_EXTENDED_OBLIGATION_RESERVES); // 980 + (80 * 32)
+
+/// Offset and length of the deposits and borrows in an obligation account of
+/// the given length
+fn obligation_data_flat(data_len: usize) -> (usize, usize) {
+    if data_len == EXTENDED_OBLIGATION_LEN {
+        (OBLIGATION_LEN, EXTENDED_OBLIGATION_LEN - OBLIGATION_LEN)
+    } else {
+        (
+            LEGACY_OBLIGATION_LEN - OBLIGATION_DATA_FLAT_LEN,
+            OBLIGATION_DATA_FLAT_LEN,
+        )
+    }
+}
+
+// @TODO: break this up by obligation / collateral / liquidity https://git.io/JOCca
 impl Pack for Obligation {
     const LEN: usize = OBLIGATION_LEN;
 
+    fn unpack_unchecked(input: &[u8]) -> Result<Self, ProgramError> {
+        if input.len() != OBLIGATION_LEN
+            && input.len() != LEGACY_OBLIGATION_LEN
+            && input.len() != EXTENDED_OBLIGATION_LEN
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Self::unpack_from_slice(input)
+    }
+
+    fn pack(src: Self, dst: &mut [u8]) -> Result<(), ProgramError> {
+        if dst.len() != OBLIGATION_LEN
+            && dst.len() != LEGACY_OBLIGATION_LEN
+            && dst.len() != EXTENDED_OBLIGATION_LEN
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        src.pack_into_slice(dst);
+        Ok(())
+    }
+
     fn pack_into_slice(&self, dst: &mut [u8]) {
-        let output = array_mut_ref![dst, 0, OBLIGATION_LEN];
+        if dst.len() >= OBLIGATION_LEN {
+            let output = array_mut_ref![
+                dst,
+                LEGACY_OBLIGATION_LEN,
+                OBLIGATION_LEN - LEGACY_OBLIGATION_LEN
+            ];
+            #[allow(clippy::ptr_offset_with_cast)]
+            let (
+                elevation_group,
+                isolated_collateral,
+                refresh_slot,
+                refresh_next_index,
+                refresh_elevation_group,
+                refresh_single_elevation_group,
+                refresh_isolated_collateral,
+                refresh_elevated_allowed_borrow_value,
+                refresh_elevated_unhealthy_borrow_value,
+                _padding,
+            ) = mut_array_refs![output, 1, 1, 8, 1, 1, 1, 1, 16, 16, 18];
+            *elevation_group = self.elevation_group.to_le_bytes();
+            pack_bool(self.isolated_collateral, isolated_collateral);
+
+            let progress = &self.refresh_progress;
+            *refresh_slot = progress.slot.to_le_bytes();
+            *refresh_next_index = progress.next_index.to_le_bytes();
+            *refresh_elevation_group = progress.elevation_group.to_le_bytes();
+            pack_bool(
+                progress.single_elevation_group,
+                refresh_single_elevation_group,
+            );
+            pack_bool(progress.isolated_collateral, refresh_isolated_collateral);
+            pack_decimal(
+                progress.elevated_allowed_borrow_value,
+                refresh_elevated_allowed_borrow_value,
+            );
+            pack_decimal(
+                progress.elevated_unhealthy_borrow_value,
+                refresh_elevated_unhealthy_borrow_value,
+            );
+        }
+
+        let output = array_mut_ref![dst, 0, LEGACY_OBLIGATION_LEN];
         #[allow(clippy::ptr_offset_with_cast)]
         let (
             version,
@@ -355,7 +630,7 @@ impl Pack for Obligation {
             unhealthy_borrow_value,
             deposits_len,
             borrows_len,
-            data_flat,
+            _data_flat,
         ) = mut_array_refs![
             output,
             1,
@@ -369,7 +644,7 @@ impl Pack for Obligation {
             16,
             1,
             1,
-            OBLIGATION_COLLATERAL_LEN + (OBLIGATION_LIQUIDITY_LEN * (MAX_OBLIGATION_RESERVES - 1))
+            OBLIGATION_DATA_FLAT_LEN
         ];
 
         // obligation
@@ -385,6 +660,8 @@ impl Pack for Obligation {
         *deposits_len = u8::try_from(self.deposits.len()).unwrap().to_le_bytes();
         *borrows_len = u8::try_from(self.borrows.len()).unwrap().to_le_bytes();
 
+        let (data_flat_offset, data_flat_len) = obligation_data_flat(dst.len());
+        let data_flat = &mut dst[data_flat_offset..data_flat_offset + data_flat_len];
         let mut offset = 0;
 
         // deposits
@@ -419,7 +696,48 @@ impl Pack for Obligation {
     /// Unpacks a byte buffer into an
     /// [ObligationInfo](struct.ObligationInfo.html).
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
-        let input = array_ref![src, 0, OBLIGATION_LEN];
+        let (elevation_group, isolated_collateral, refresh_progress) =
+            if src.len() >= OBLIGATION_LEN {
+                let input = array_ref![
+                    src,
+                    LEGACY_OBLIGATION_LEN,
+                    OBLIGATION_LEN - LEGACY_OBLIGATION_LEN
+                ];
+                #[allow(clippy::ptr_offset_with_cast)]
+                let (
+                    elevation_group,
+                    isolated_collateral,
+                    refresh_slot,
+                    refresh_next_index,
+                    refresh_elevation_group,
+                    refresh_single_elevation_group,
+                    refresh_isolated_collateral,
+                    refresh_elevated_allowed_borrow_value,
+                    refresh_elevated_unhealthy_borrow_value,
+                    _padding,
+                ) = array_refs![input, 1, 1, 8, 1, 1, 1, 1, 16, 16, 18];
+                (
+                    u8::from_le_bytes(*elevation_group),
+                    unpack_bool(isolated_collateral)?,
+                    ObligationRefreshProgress {
+                        slot: u64::from_le_bytes(*refresh_slot),
+                        next_index: u8::from_le_bytes(*refresh_next_index),
+                        elevation_group: u8::from_le_bytes(*refresh_elevation_group),
+                        single_elevation_group: unpack_bool(refresh_single_elevation_group)?,
+                        isolated_collateral: unpack_bool(refresh_isolated_collateral)?,
+                        elevated_allowed_borrow_value: unpack_decimal(
+                            refresh_elevated_allowed_borrow_value,
+                        ),
+                        elevated_unhealthy_borrow_value: unpack_decimal(
+                            refresh_elevated_unhealthy_borrow_value,
+                        ),
+                    },
+                )
+            } else {
+                (0, false, ObligationRefreshProgress::default())
+            };
+
+        let input = array_ref![src, 0, LEGACY_OBLIGATION_LEN];
         #[allow(clippy::ptr_offset_with_cast)]
         let (
             version,
@@ -433,7 +751,7 @@ impl Pack for Obligation {
             unhealthy_borrow_value,
             deposits_len,
             borrows_len,
-            data_flat,
+            _data_flat,
         ) = array_refs![
             input,
             1,
@@ -447,7 +765,7 @@ impl Pack for Obligation {
             16,
             1,
             1,
-            OBLIGATION_COLLATERAL_LEN + (OBLIGATION_LIQUIDITY_LEN * (MAX_OBLIGATION_RESERVES - 1))
+            OBLIGATION_DATA_FLAT_LEN
         ];
 
         let version = u8::from_le_bytes(*version);
@@ -458,6 +776,15 @@ impl Pack for Obligation {
 
         let deposits_len = u8::from_le_bytes(*deposits_len);
         let borrows_len = u8::from_le_bytes(*borrows_len);
+        let (data_flat_offset, data_flat_len) = obligation_data_flat(src.len());
+        if (deposits_len as usize * OBLIGATION_COLLATERAL_LEN)
+            + (borrows_len as usize * OBLIGATION_LIQUIDITY_LEN)
+            > data_flat_len
+        {
+            msg!("Obligation deposits and borrows do not fit in the account");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let data_flat = &src[data_flat_offset..data_flat_offset + data_flat_len];
         let mut deposits = Vec::with_capacity(deposits_len as usize + 1);
         let mut borrows = Vec::with_capacity(borrows_len as usize + 1);
 
@@ -502,6 +829,9 @@ impl Pack for Obligation {
             borrowed_value: unpack_decimal(borrowed_value),
             allowed_borrow_value: unpack_decimal(allowed_borrow_value),
             unhealthy_borrow_value: unpack_decimal(unhealthy_borrow_value),
+            elevation_group,
+            isolated_collateral,
+            refresh_progress,
         })
     }
 }
@@ -543,6 +873,175 @@ mod test {
         );
     }
 
+    #[test]
+    fn obligation_legacy_layout() {
+        let obligation = Obligation {
+            version: PROGRAM_VERSION,
+            lending_market: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            deposits: vec![ObligationCollateral::new(Pubkey::new_unique())],
+            elevation_group: 1,
+            isolated_collateral: true,
+            ..Obligation::default()
+        };
+
+        let mut packed = [0u8; Obligation::LEN];
+        Obligation::pack(obligation.clone(), &mut packed).unwrap();
+        assert_eq!(Obligation::unpack(&packed).unwrap(), obligation);
+
+        let mut legacy = [0u8; LEGACY_OBLIGATION_LEN];
+        Obligation::pack(obligation.clone(), &mut legacy).unwrap();
+        assert_eq!(legacy[..], packed[..LEGACY_OBLIGATION_LEN]);
+        assert_eq!(
+            Obligation::unpack(&legacy).unwrap(),
+            Obligation {
+                elevation_group: 0,
+                isolated_collateral: false,
+                ..obligation
+            }
+        );
+
+        assert_eq!(
+            Obligation::unpack(&packed[..LEGACY_OBLIGATION_LEN + 1]),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn obligation_extended_layout() {
+        let obligation = Obligation {
+            version: PROGRAM_VERSION,
+            lending_market: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            deposits: (0..4)
+                .map(|_| ObligationCollateral::new(Pubkey::new_unique()))
+                .collect(),
+            borrows: (0..MAX_EXTENDED_OBLIGATION_RESERVES - 4)
+                .map(|_| ObligationLiquidity::new(Pubkey::new_unique()))
+                .collect(),
+            elevation_group: 1,
+            refresh_progress: ObligationRefreshProgress {
+                slot: 5,
+                next_index: 3,
+                elevation_group: 1,
+                single_elevation_group: true,
+                isolated_collateral: true,
+                elevated_allowed_borrow_value: Decimal::from(8u64),
+                elevated_unhealthy_borrow_value: Decimal::from(9u64),
+            },
+            ..Obligation::default()
+        };
+
+        let mut packed = vec![0u8; EXTENDED_OBLIGATION_LEN];
+        Obligation::pack(obligation.clone(), &mut packed).unwrap();
+        assert_eq!(Obligation::unpack(&packed).unwrap(), obligation);
+
+        // positions live after the standard layout, whose data_flat is unused
+        let (data_flat_offset, data_flat_len) = obligation_data_flat(Obligation::LEN);
+        assert!(packed[data_flat_offset..data_flat_offset + data_flat_len]
+            .iter()
+            .all(|byte| *byte == 0));
+
+        // standard accounts cannot hold the extended positions
+        assert_eq!(
+            Obligation::unpack(&packed[..Obligation::LEN]),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn obligation_reserve_limit() {
+        let mut obligation = Obligation::default();
+        for _ in 0..MAX_OBLIGATION_RESERVES {
+            obligation
+                .find_or_add_collateral_to_deposits(
+                    Pubkey::new_unique(),
+                    Obligation::max_reserves(Obligation::LEN),
+                )
+                .unwrap();
+        }
+        assert_eq!(
+            obligation.find_or_add_liquidity_to_borrows(
+                Pubkey::new_unique(),
+                Obligation::max_reserves(Obligation::LEN)
+            ),
+            Err(LendingError::ObligationReserveLimit.into())
+        );
+        assert!(obligation
+            .find_or_add_liquidity_to_borrows(
+                Pubkey::new_unique(),
+                Obligation::max_reserves(EXTENDED_OBLIGATION_LEN)
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn obligation_refresh() {
+        let config = ReserveConfig {
+            loan_to_value_ratio: 50,
+            liquidation_threshold: 55,
+            elevation_group: ElevationGroup {
+                id: 1,
+                loan_to_value_ratio: 80,
+                liquidation_threshold: 85,
+            },
+            ..ReserveConfig::default()
+        };
+        let mut obligation = Obligation {
+            deposits: vec![ObligationCollateral::default(); 2],
+            borrows: vec![ObligationLiquidity::default()],
+            ..Obligation::default()
+        };
+
+        // every deposit and borrow must be recorded before finishing
+        obligation.begin_refresh(2);
+        obligation
+            .refresh_collateral(Decimal::from(100u64), &config, true)
+            .unwrap();
+        assert_eq!(
+            obligation.finish_refresh(2, true),
+            Err(LendingError::InvalidObligationRefresh.into())
+        );
+        assert_eq!(
+            obligation.refresh_liquidity(Decimal::from(10u64), 1),
+            Err(LendingError::InvalidObligationRefresh.into())
+        );
+
+        obligation
+            .refresh_collateral(Decimal::from(100u64), &config, true)
+            .unwrap();
+        obligation
+            .refresh_liquidity(Decimal::from(10u64), 1)
+            .unwrap();
+        obligation.finish_refresh(2, true).unwrap();
+        assert_eq!(obligation.elevation_group, 1);
+        assert_eq!(obligation.deposited_value, Decimal::from(200u64));
+        assert_eq!(obligation.borrowed_value, Decimal::from(10u64));
+        assert_eq!(obligation.allowed_borrow_value, Decimal::from(160u64));
+        assert_eq!(obligation.unhealthy_borrow_value, Decimal::from(170u64));
+        assert_eq!(
+            obligation.refresh_progress,
+            ObligationRefreshProgress::default()
+        );
+        assert!(!obligation.last_update.stale);
+
+        // a borrow outside the elevation group leaves the base ratios
+        obligation.begin_refresh(3);
+        obligation
+            .refresh_collateral(Decimal::from(100u64), &config, true)
+            .unwrap();
+        obligation
+            .refresh_collateral(Decimal::from(100u64), &config, true)
+            .unwrap();
+        obligation
+            .refresh_liquidity(Decimal::from(10u64), 2)
+            .unwrap();
+        obligation.finish_refresh(3, true).unwrap();
+        assert_eq!(obligation.elevation_group, 0);
+        assert_eq!(obligation.allowed_borrow_value, Decimal::from(100u64));
+        assert_eq!(obligation.unhealthy_borrow_value, Decimal::from(110u64));
+    }
+
     // Creates rates (r1, r2) where 0 < r1 <= r2 <= 100*r1
     prop_compose! {
         fn cumulative_rates()(rate in 1..=u128::MAX)(
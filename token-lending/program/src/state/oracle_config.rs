@@ -0,0 +1,437 @@
+use {
+    crate::{
+        error::LendingError,
+        math::{Decimal, TryMul, TrySub},
+        oracle::OraclePrice,
+    },
+    arrayref::{array_refs, mut_array_refs},
+    solana_program::{
+        clock::Slot,
+        entrypoint::ProgramResult,
+        msg,
+        program_error::ProgramError,
+        pubkey::{Pubkey, PUBKEY_BYTES},
+    },
+};
+
+/// Max staleness used when none is configured, rejecting prices published 5 or
+/// more slots ago
+pub const DEFAULT_MAX_STALENESS_SLOTS: u64 = 4;
+
+/// Confidence and divergence ratio of 100%, in basis points
+pub const MAX_ORACLE_RATIO_BPS: u16 = 10_000;
+
+/// Packed length of an oracle config
+pub const ORACLE_CONFIG_LEN: usize = 62; // 1 + 4 + 2 + 4 + 16 + 1 + 32 + 2
+
+/// Kind of feed a reserve reads its liquidity market price from
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OracleSource {
+    /// Pyth v2 price account owned by the lending market oracle program
+    #[default]
+    Pyth,
+    /// Time-weighted average of the spot price of a token swap pool pairing
+    /// the reserve liquidity with a token worth one unit of the quote
+    /// currency. The oracle account is the pool, and refreshing the reserve
+    /// also takes the pool token A and B accounts and the quote token mint.
+    SwapPoolTwap,
+    /// Price set in the oracle config, for assets pegged to the quote
+    /// currency. The oracle account is recorded but never read.
+    FixedPrice,
+}
+
+impl OracleSource {
+    /// Number of accounts following the oracle account when refreshing a
+    /// reserve
+    pub fn additional_accounts(&self) -> usize {
+        match self {
+            Self::SwapPoolTwap => 3,
+            Self::Pyth | Self::FixedPrice => 0,
+        }
+    }
+
+    fn from_u8(value: u8) -> Result<Self, ProgramError> {
+        match value {
+            0 => Ok(Self::Pyth),
+            1 => Ok(Self::SwapPoolTwap),
+            2 => Ok(Self::FixedPrice),
+            _ => {
+                msg!("Oracle source cannot be unpacked");
+                Err(ProgramError::InvalidAccountData)
+            }
+        }
+    }
+
+    fn to_u8(self) -> u8 {
+        match self {
+            Self::Pyth => 0,
+            Self::SwapPoolTwap => 1,
+            Self::FixedPrice => 2,
+        }
+    }
+}
+
+/// Oracle read alongside the primary oracle, guarding against a faulty or
+/// manipulated primary price
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SecondaryOracle {
+    /// Kind of feed of the secondary oracle, cannot be a fixed price
+    pub source: OracleSource,
+    /// Secondary oracle account
+    pub oracle_pubkey: Pubkey,
+    /// Max difference between the primary and secondary prices, relative to
+    /// the primary price, in basis points
+    pub max_divergence_bps: u16,
+}
+
+impl SecondaryOracle {
+    /// Check the secondary price is close enough to the primary price
+    pub fn check_divergence(
+        &self,
+        primary_price: Decimal,
+        secondary_price: Decimal,
+    ) -> ProgramResult {
+        let divergence = if primary_price > secondary_price {
+            primary_price.try_sub(secondary_price)?
+        } else {
+            secondary_price.try_sub(primary_price)?
+        };
+        if divergence.try_mul(u64::from(MAX_ORACLE_RATIO_BPS))?
+            > primary_price.try_mul(u64::from(self.max_divergence_bps))?
+        {
+            msg!(
+                "Secondary oracle price {} diverges from primary oracle price {}",
+                secondary_price,
+                primary_price
+            );
+            return Err(LendingError::OraclePricesDiverge.into());
+        }
+        Ok(())
+    }
+}
+
+/// Where a reserve reads its liquidity market price from, and the checks
+/// applied to the price
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct OracleConfig {
+    /// Kind of feed of the primary oracle, the reserve liquidity oracle account
+    pub source: OracleSource,
+    /// Max slots since the price was published, 0 for the default of
+    /// `DEFAULT_MAX_STALENESS_SLOTS`
+    pub max_staleness_slots: u32,
+    /// Max ratio of the price confidence interval to the price, in basis
+    /// points, 0 if unchecked
+    pub max_confidence_bps: u16,
+    /// Slots over which swap pool spot prices are averaged
+    pub twap_window_slots: u32,
+    /// Price of fixed-price feeds in the quote currency, expressed as a Wad
+    pub fixed_price_wads: u128,
+    /// Oracle whose price must agree with the primary oracle, if any
+    pub secondary: Option<SecondaryOracle>,
+}
+
+impl OracleConfig {
+    /// Validate the oracle config, when initializing or modifying the reserve
+    /// configs
+    pub fn validate(&self) -> ProgramResult {
+        if self.max_confidence_bps > MAX_ORACLE_RATIO_BPS {
+            msg!("Oracle max confidence must be in range [0, 10_000] bps");
+            return Err(LendingError::InvalidOracleConfig.into());
+        }
+        if self.source == OracleSource::FixedPrice && self.fixed_price_wads == 0 {
+            msg!("Oracle fixed price must be greater than zero");
+            return Err(LendingError::InvalidOracleConfig.into());
+        }
+        if let Some(secondary) = self.secondary {
+            if secondary.source == OracleSource::FixedPrice {
+                msg!("Secondary oracle cannot be a fixed price");
+                return Err(LendingError::InvalidOracleConfig.into());
+            }
+            if secondary.source == OracleSource::SwapPoolTwap
+                && self.source == OracleSource::SwapPoolTwap
+            {
+                msg!("Only one of the primary and secondary oracles can be a swap pool");
+                return Err(LendingError::InvalidOracleConfig.into());
+            }
+            if secondary.oracle_pubkey == Pubkey::default() {
+                msg!("Secondary oracle account must be set");
+                return Err(LendingError::InvalidOracleConfig.into());
+            }
+            if secondary.max_divergence_bps == 0
+                || secondary.max_divergence_bps > MAX_ORACLE_RATIO_BPS
+            {
+                msg!("Secondary oracle max divergence must be in range (0, 10_000] bps");
+                return Err(LendingError::InvalidOracleConfig.into());
+            }
+        }
+        if self.uses_twap() && u64::from(self.twap_window_slots) < self.max_staleness() {
+            msg!("Oracle TWAP window must be at least the max staleness");
+            return Err(LendingError::InvalidOracleConfig.into());
+        }
+        Ok(())
+    }
+
+    /// Max slots since the price was published
+    pub fn max_staleness(&self) -> u64 {
+        match self.max_staleness_slots {
+            0 => DEFAULT_MAX_STALENESS_SLOTS,
+            slots => u64::from(slots),
+        }
+    }
+
+    /// Whether the primary or secondary oracle is a swap pool TWAP
+    pub fn uses_twap(&self) -> bool {
+        self.source == OracleSource::SwapPoolTwap
+            || self.secondary.map_or(false, |secondary| {
+                secondary.source == OracleSource::SwapPoolTwap
+            })
+    }
+
+    /// Price of fixed-price feeds
+    pub fn fixed_price(&self) -> Decimal {
+        Decimal::from_scaled_val(self.fixed_price_wads)
+    }
+
+    /// Check a price read from an oracle is recent and precise enough to use
+    pub fn check_price(&self, price: &OraclePrice, current_slot: Slot) -> ProgramResult {
+        let slots_elapsed = current_slot
+            .checked_sub(price.publish_slot)
+            .ok_or(LendingError::MathOverflow)?;
+        if slots_elapsed > self.max_staleness() {
+            msg!("Oracle price is stale");
+            return Err(LendingError::OraclePriceStale.into());
+        }
+        if self.max_confidence_bps > 0
+            && price.confidence.try_mul(u64::from(MAX_ORACLE_RATIO_BPS))?
+                > price.price.try_mul(u64::from(self.max_confidence_bps))?
+        {
+            msg!(
+                "Oracle price {} has a confidence interval of {}",
+                price.price,
+                price.confidence
+            );
+            return Err(LendingError::OraclePriceUncertain.into());
+        }
+        Ok(())
+    }
+
+    /// Pack the config into a fixed-size buffer
+    #[allow(clippy::ptr_offset_with_cast)]
+    pub fn pack_into_slice(&self, output: &mut [u8; ORACLE_CONFIG_LEN]) {
+        let (
+            source,
+            max_staleness_slots,
+            max_confidence_bps,
+            twap_window_slots,
+            fixed_price_wads,
+            secondary_source,
+            secondary_oracle_pubkey,
+            secondary_max_divergence_bps,
+        ) = mut_array_refs![output, 1, 4, 2, 4, 16, 1, PUBKEY_BYTES, 2];
+        source[0] = self.source.to_u8();
+        *max_staleness_slots = self.max_staleness_slots.to_le_bytes();
+        *max_confidence_bps = self.max_confidence_bps.to_le_bytes();
+        *twap_window_slots = self.twap_window_slots.to_le_bytes();
+        *fixed_price_wads = self.fixed_price_wads.to_le_bytes();
+        // secondary source is offset by one, leaving zero for no secondary oracle
+        let secondary = self.secondary.unwrap_or_default();
+        secondary_source[0] = self
+            .secondary
+            .map_or(0, |secondary| secondary.source.to_u8() + 1);
+        secondary_oracle_pubkey.copy_from_slice(secondary.oracle_pubkey.as_ref());
+        *secondary_max_divergence_bps = secondary.max_divergence_bps.to_le_bytes();
+    }
+
+    /// Unpack a config from a fixed-size buffer
+    #[allow(clippy::ptr_offset_with_cast)]
+    pub fn unpack_from_slice(input: &[u8; ORACLE_CONFIG_LEN]) -> Result<Self, ProgramError> {
+        let (
+            source,
+            max_staleness_slots,
+            max_confidence_bps,
+            twap_window_slots,
+            fixed_price_wads,
+            secondary_source,
+            secondary_oracle_pubkey,
+            secondary_max_divergence_bps,
+        ) = array_refs![input, 1, 4, 2, 4, 16, 1, PUBKEY_BYTES, 2];
+        let secondary = match secondary_source[0] {
+            0 => None,
+            source => Some(SecondaryOracle {
+                source: OracleSource::from_u8(source - 1)?,
+                oracle_pubkey: Pubkey::new_from_array(*secondary_oracle_pubkey),
+                max_divergence_bps: u16::from_le_bytes(*secondary_max_divergence_bps),
+            }),
+        };
+        Ok(Self {
+            source: OracleSource::from_u8(source[0])?,
+            max_staleness_slots: u32::from_le_bytes(*max_staleness_slots),
+            max_confidence_bps: u16::from_le_bytes(*max_confidence_bps),
+            twap_window_slots: u32::from_le_bytes(*twap_window_slots),
+            fixed_price_wads: u128::from_le_bytes(*fixed_price_wads),
+            secondary,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, crate::math::WAD};
+
+    fn price(price: u64, confidence: u64, publish_slot: Slot) -> OraclePrice {
+        OraclePrice {
+            price: Decimal::from(price),
+            confidence: Decimal::from(confidence),
+            publish_slot,
+        }
+    }
+
+    #[test]
+    fn oracle_config_validation() {
+        let config = OracleConfig::default();
+        assert_eq!(config.validate(), Ok(()));
+        assert_eq!(config.max_staleness(), DEFAULT_MAX_STALENESS_SLOTS);
+
+        for invalid in [
+            OracleConfig {
+                max_confidence_bps: MAX_ORACLE_RATIO_BPS + 1,
+                ..config
+            },
+            OracleConfig {
+                source: OracleSource::FixedPrice,
+                ..config
+            },
+            OracleConfig {
+                source: OracleSource::SwapPoolTwap,
+                twap_window_slots: 2,
+                ..config
+            },
+            OracleConfig {
+                secondary: Some(SecondaryOracle {
+                    source: OracleSource::FixedPrice,
+                    oracle_pubkey: Pubkey::new_unique(),
+                    max_divergence_bps: 100,
+                }),
+                ..config
+            },
+            OracleConfig {
+                secondary: Some(SecondaryOracle {
+                    source: OracleSource::Pyth,
+                    oracle_pubkey: Pubkey::new_unique(),
+                    max_divergence_bps: 0,
+                }),
+                ..config
+            },
+            OracleConfig {
+                source: OracleSource::SwapPoolTwap,
+                twap_window_slots: 100,
+                secondary: Some(SecondaryOracle {
+                    source: OracleSource::SwapPoolTwap,
+                    oracle_pubkey: Pubkey::new_unique(),
+                    max_divergence_bps: 100,
+                }),
+                ..config
+            },
+        ] {
+            assert_eq!(
+                invalid.validate(),
+                Err(LendingError::InvalidOracleConfig.into())
+            );
+        }
+
+        let config = OracleConfig {
+            source: OracleSource::FixedPrice,
+            fixed_price_wads: WAD.into(),
+            secondary: Some(SecondaryOracle {
+                source: OracleSource::SwapPoolTwap,
+                oracle_pubkey: Pubkey::new_unique(),
+                max_divergence_bps: 100,
+            }),
+            twap_window_slots: 100,
+            ..config
+        };
+        assert_eq!(config.validate(), Ok(()));
+        assert_eq!(config.fixed_price(), Decimal::one());
+    }
+
+    #[test]
+    fn oracle_config_checks_staleness_and_confidence() {
+        let config = OracleConfig::default();
+        assert_eq!(config.check_price(&price(100, 50, 10), 14), Ok(()));
+        assert_eq!(
+            config.check_price(&price(100, 50, 10), 15),
+            Err(LendingError::OraclePriceStale.into())
+        );
+
+        let config = OracleConfig {
+            max_staleness_slots: 20,
+            max_confidence_bps: 200,
+            ..config
+        };
+        assert_eq!(config.check_price(&price(100, 2, 10), 30), Ok(()));
+        assert_eq!(
+            config.check_price(&price(100, 2, 10), 31),
+            Err(LendingError::OraclePriceStale.into())
+        );
+        assert_eq!(
+            config.check_price(&price(100, 3, 10), 30),
+            Err(LendingError::OraclePriceUncertain.into())
+        );
+    }
+
+    #[test]
+    fn secondary_oracle_checks_divergence() {
+        let secondary = SecondaryOracle {
+            source: OracleSource::Pyth,
+            oracle_pubkey: Pubkey::new_unique(),
+            max_divergence_bps: 500,
+        };
+        for secondary_price in [95u64, 100, 105] {
+            assert_eq!(
+                secondary.check_divergence(Decimal::from(100u64), Decimal::from(secondary_price)),
+                Ok(())
+            );
+        }
+        for secondary_price in [94u64, 106] {
+            assert_eq!(
+                secondary.check_divergence(Decimal::from(100u64), Decimal::from(secondary_price)),
+                Err(LendingError::OraclePricesDiverge.into())
+            );
+        }
+    }
+
+    #[test]
+    fn oracle_config_packing() {
+        for config in [
+            OracleConfig::default(),
+            OracleConfig {
+                source: OracleSource::SwapPoolTwap,
+                max_staleness_slots: 30,
+                max_confidence_bps: 150,
+                twap_window_slots: 150,
+                fixed_price_wads: 0,
+                secondary: Some(SecondaryOracle {
+                    source: OracleSource::Pyth,
+                    oracle_pubkey: Pubkey::new_unique(),
+                    max_divergence_bps: 300,
+                }),
+            },
+        ] {
+            let mut packed = [0; ORACLE_CONFIG_LEN];
+            config.pack_into_slice(&mut packed);
+            assert_eq!(OracleConfig::unpack_from_slice(&packed), Ok(config));
+        }
+
+        // zeroed bytes, as in reserves from before oracle configs, read as pyth
+        assert_eq!(
+            OracleConfig::unpack_from_slice(&[0; ORACLE_CONFIG_LEN]),
+            Ok(OracleConfig::default())
+        );
+        let mut invalid = [0; ORACLE_CONFIG_LEN];
+        invalid[0] = 3;
+        assert_eq!(
+            OracleConfig::unpack_from_slice(&invalid),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use {
+    super::*,
+    arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs},
+    solana_program::{
+        clock::Slot,
+        msg,
+        program_error::ProgramError,
+        program_pack::{IsInitialized, Pack, Sealed},
+        pubkey::{Pubkey, PUBKEY_BYTES},
+    },
+};
+
+/// Reserve configs queued behind the lending market config timelock, giving
+/// depositors notice before they apply
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PendingReserveConfig {
+    /// Version of the struct
+    pub version: u8,
+    /// Lending market address
+    pub lending_market: Pubkey,
+    /// Reserve the configs apply to
+    pub reserve: Pubkey,
+    /// Slot from which the configs can be applied
+    pub effective_slot: Slot,
+    /// New reserve liquidity oracle account, default to keep the current one
+    pub oracle_pubkey: Pubkey,
+    /// Queued reserve configs
+    pub config: ReserveConfig,
+}
+
+impl PendingReserveConfig {
+    /// Create new pending reserve configs
+    pub fn new(params: InitPendingReserveConfigParams) -> Self {
+        let mut pending_reserve_config = Self::default();
+        Self::init(&mut pending_reserve_config, params);
+        pending_reserve_config
+    }
+
+    /// Initialize pending reserve configs
+    pub fn init(&mut self, params: InitPendingReserveConfigParams) {
+        self.version = PROGRAM_VERSION;
+        self.lending_market = params.lending_market;
+        self.reserve = params.reserve;
+        self.effective_slot = params.effective_slot;
+        self.oracle_pubkey = params.oracle_pubkey;
+        self.config = params.config;
+    }
+}
+
+/// Initialize pending reserve configs
+pub struct InitPendingReserveConfigParams {
+    /// Lending market address
+    pub lending_market: Pubkey,
+    /// Reserve the configs apply to
+    pub reserve: Pubkey,
+    /// Slot from which the configs can be applied
+    pub effective_slot: Slot,
+    /// New reserve liquidity oracle account, default to keep the current one
+    pub oracle_pubkey: Pubkey,
+    /// Queued reserve configs
+    pub config: ReserveConfig,
+}
+
+impl Sealed for PendingReserveConfig {}
+impl IsInitialized for PendingReserveConfig {
+    fn is_initialized(&self) -> bool {
+        self.version != UNINITIALIZED_VERSION
+    }
+}
+
+const PENDING_RESERVE_CONFIG_LEN: usize = 336; // 1 + 32 + 32 + 8 + 32 + 167 + 64
+impl Pack for PendingReserveConfig {
+    const LEN: usize = PENDING_RESERVE_CONFIG_LEN;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, PENDING_RESERVE_CONFIG_LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (version, lending_market, reserve, effective_slot, oracle_pubkey, config, _padding) = mut_array_refs![
+            output,
+            1,
+            PUBKEY_BYTES,
+            PUBKEY_BYTES,
+            8,
+            PUBKEY_BYTES,
+            RESERVE_CONFIG_LEN,
+            64
+        ];
+
+        *version = self.version.to_le_bytes();
+        lending_market.copy_from_slice(self.lending_market.as_ref());
+        reserve.copy_from_slice(self.reserve.as_ref());
+        *effective_slot = self.effective_slot.to_le_bytes();
+        oracle_pubkey.copy_from_slice(self.oracle_pubkey.as_ref());
+        self.config.pack_into_slice(config);
+    }
+
+    /// Unpacks a byte buffer into a
+    /// [PendingReserveConfig](struct.PendingReserveConfig.html)
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, PENDING_RESERVE_CONFIG_LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (version, lending_market, reserve, effective_slot, oracle_pubkey, config, _padding) = array_refs![
+            input,
+            1,
+            PUBKEY_BYTES,
+            PUBKEY_BYTES,
+            8,
+            PUBKEY_BYTES,
+            RESERVE_CONFIG_LEN,
+            64
+        ];
+
+        let version = u8::from_le_bytes(*version);
+        if version > PROGRAM_VERSION {
+            msg!("Pending reserve config version does not match lending program version");
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Ok(Self {
+            version,
+            lending_market: Pubkey::new_from_array(*lending_market),
+            reserve: Pubkey::new_from_array(*reserve),
+            effective_slot: u64::from_le_bytes(*effective_slot),
+            oracle_pubkey: Pubkey::new_from_array(*oracle_pubkey),
+            config: ReserveConfig::unpack_from_slice(config)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, crate::math::WAD};
+
+    #[test]
+    fn pack_and_unpack_pending_reserve_config() {
+        let pending_reserve_config = PendingReserveConfig::new(InitPendingReserveConfigParams {
+            lending_market: Pubkey::new_unique(),
+            reserve: Pubkey::new_unique(),
+            effective_slot: 1_000,
+            oracle_pubkey: Pubkey::new_unique(),
+            config: ReserveConfig {
+                optimal_utilization_rate: 80,
+                loan_to_value_ratio: 50,
+                liquidation_bonus: 5,
+                max_liquidation_bonus: 10,
+                liquidation_threshold: 55,
+                min_borrow_rate: 1,
+                optimal_borrow_rate: 4,
+                max_borrow_rate: 30,
+                caps: ReserveCaps {
+                    deposit_limit: 1_000,
+                    borrow_limit: 500,
+                    deposit_value_limit: 2_000,
+                    borrow_value_limit: 1_000,
+                },
+                isolated_collateral: true,
+                elevation_group: ElevationGroup {
+                    id: 1,
+                    loan_to_value_ratio: 90,
+                    liquidation_threshold: 95,
+                },
+                borrowing_disabled: true,
+                paused: true,
+                fees: ReserveFees {
+                    borrow_fee_wad: WAD / 100,
+                    flash_loan_fee_wad: WAD / 1_000,
+                    host_fee_percentage: 20,
+                    protocol_take_rate: 10,
+                },
+                ..ReserveConfig::default()
+            },
+        });
+
+        let mut packed = [0u8; PendingReserveConfig::LEN];
+        PendingReserveConfig::pack(pending_reserve_config.clone(), &mut packed).unwrap();
+        let unpacked = PendingReserveConfig::unpack(&packed).unwrap();
+        assert_eq!(pending_reserve_config, unpacked);
+    }
+}
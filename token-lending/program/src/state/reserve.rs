@@ -19,7 +19,8 @@ use {
     },
 };
 
-/// Percentage of an obligation that can be repaid during each liquidation call
+/// Default percentage of an obligation that can be repaid during each
+/// liquidation call
 pub const LIQUIDATION_CLOSE_FACTOR: u8 = 50;
 
 /// Obligation borrow amount that is small enough to close out
@@ -58,6 +59,42 @@ impl Reserve {
         self.liquidity = params.liquidity;
         self.collateral = params.collateral;
         self.config = params.config;
+        self.liquidity.adaptive_rate_at_target =
+            params.config.interest_rate_model.initial_rate_at_target();
+    }
+
+    /// Replace the reserve configs, restarting the interest rate model state if
+    /// the new model is adaptive and the current one is not, and the swap pool
+    /// TWAP if the oracle config changes
+    pub fn update_config(&mut self, config: ReserveConfig) {
+        match (self.config.interest_rate_model, config.interest_rate_model) {
+            (InterestRateModel::Adaptive(_), InterestRateModel::Adaptive(model)) => {
+                self.liquidity.adaptive_rate_at_target =
+                    self.liquidity.adaptive_rate_at_target.clamp(
+                        Rate::from_bps(model.min_rate_at_target_bps),
+                        Rate::from_bps(model.max_rate_at_target_bps),
+                    );
+            }
+            (_, interest_rate_model) => {
+                self.liquidity.adaptive_rate_at_target =
+                    interest_rate_model.initial_rate_at_target();
+            }
+        }
+        if self.config.oracle != config.oracle {
+            self.liquidity.reset_twap();
+            self.last_update.mark_stale();
+        }
+        self.config = config;
+    }
+
+    /// Replace the reserve liquidity oracle account, requiring a refresh from
+    /// the new oracle before the reserve can be used
+    pub fn set_oracle(&mut self, oracle_pubkey: Pubkey) {
+        if self.liquidity.oracle_pubkey != oracle_pubkey {
+            self.liquidity.oracle_pubkey = oracle_pubkey;
+            self.liquidity.reset_twap();
+            self.last_update.mark_stale();
+        }
     }
 
     /// Record deposited liquidity and return amount of collateral tokens to
@@ -88,6 +125,16 @@ impl Reserve {
     /// Calculate the current borrow rate
     pub fn current_borrow_rate(&self) -> Result<Rate, ProgramError> {
         let utilization_rate = self.liquidity.utilization_rate()?;
+        match self.config.interest_rate_model {
+            InterestRateModel::Kinked => self.kinked_borrow_rate(utilization_rate),
+            InterestRateModel::PiecewiseLinear(curve) => curve.borrow_rate(utilization_rate),
+            InterestRateModel::Adaptive(model) => {
+                model.borrow_rate(utilization_rate, self.liquidity.adaptive_rate_at_target)
+            }
+        }
+    }
+
+    fn kinked_borrow_rate(&self, utilization_rate: Rate) -> Result<Rate, ProgramError> {
         let optimal_utilization_rate = Rate::from_percent(self.config.optimal_utilization_rate);
         let low_utilization = utilization_rate < optimal_utilization_rate;
         if low_utilization || self.config.optimal_utilization_rate == 100 {
@@ -127,13 +174,47 @@ impl Reserve {
         self.collateral.exchange_rate(total_liquidity)
     }
 
+    /// Market value of an amount of liquidity in quote currency
+    pub fn liquidity_market_value(
+        &self,
+        liquidity_amount: Decimal,
+    ) -> Result<Decimal, ProgramError> {
+        // @TODO: add lookup table https://git.io/JOCYq
+        let decimals = 10u64
+            .checked_pow(self.liquidity.mint_decimals as u32)
+            .ok_or(LendingError::MathOverflow)?;
+        liquidity_amount
+            .try_mul(self.liquidity.market_price)?
+            .try_div(decimals)
+    }
+
+    /// Market value of an amount of collateral in quote currency, at the
+    /// current collateral exchange rate
+    pub fn collateral_market_value(&self, collateral_amount: u64) -> Result<Decimal, ProgramError> {
+        self.liquidity_market_value(
+            self.collateral_exchange_rate()?
+                .decimal_collateral_to_liquidity(collateral_amount.into())?,
+        )
+    }
+
     /// Update borrow rate and accrue interest
     pub fn accrue_interest(&mut self, current_slot: Slot) -> ProgramResult {
         let slots_elapsed = self.last_update.slots_elapsed(current_slot)?;
         if slots_elapsed > 0 {
+            let utilization_rate = self.liquidity.utilization_rate()?;
             let current_borrow_rate = self.current_borrow_rate()?;
-            self.liquidity
-                .compound_interest(current_borrow_rate, slots_elapsed)?;
+            self.liquidity.compound_interest(
+                current_borrow_rate,
+                slots_elapsed,
+                Rate::from_percent(self.config.fees.protocol_take_rate),
+            )?;
+            if let InterestRateModel::Adaptive(model) = self.config.interest_rate_model {
+                self.liquidity.adaptive_rate_at_target = model.adjust_rate_at_target(
+                    utilization_rate,
+                    self.liquidity.adaptive_rate_at_target,
+                    slots_elapsed,
+                )?;
+            }
         }
         Ok(())
     }
@@ -149,10 +230,17 @@ impl Reserve {
             .checked_pow(self.liquidity.mint_decimals as u32)
             .ok_or(LendingError::MathOverflow)?;
         if amount_to_borrow == u64::MAX {
-            let borrow_amount = max_borrow_value
+            let mut borrow_amount = max_borrow_value
                 .try_mul(decimals)?
                 .try_div(self.liquidity.market_price)?
                 .min(self.liquidity.available_amount.into());
+            if let Some(remaining_borrow_limit) = self.remaining_borrow_limit()? {
+                if remaining_borrow_limit == Decimal::zero() {
+                    msg!("Reserve total borrows have reached the borrow limit");
+                    return Err(LendingError::BorrowLimitExceeded.into());
+                }
+                borrow_amount = borrow_amount.min(remaining_borrow_limit);
+            }
             let (borrow_fee, host_fee) = self
                 .config
                 .fees
@@ -194,6 +282,69 @@ impl Reserve {
         }
     }
 
+    /// Check depositing liquidity keeps the total supply within the deposit
+    /// limits
+    pub fn check_deposit_limit(&self, liquidity_amount: u64) -> ProgramResult {
+        if let Some(deposit_limit) = self.limit_amount(
+            self.config.caps.deposit_limit,
+            self.config.caps.deposit_value_limit,
+        )? {
+            let total_supply = self
+                .liquidity
+                .total_supply()?
+                .try_add(liquidity_amount.into())?;
+            if total_supply > deposit_limit {
+                msg!("Reserve total supply cannot exceed the deposit limit");
+                return Err(LendingError::DepositLimitExceeded.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Check borrowing liquidity keeps the total borrows within the borrow
+    /// limits
+    pub fn check_borrow_limit(&self, borrow_amount: Decimal) -> ProgramResult {
+        if let Some(remaining_borrow_limit) = self.remaining_borrow_limit()? {
+            if borrow_amount > remaining_borrow_limit {
+                msg!("Reserve total borrows cannot exceed the borrow limit");
+                return Err(LendingError::BorrowLimitExceeded.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Liquidity that can still be borrowed under the borrow limits, if any
+    pub fn remaining_borrow_limit(&self) -> Result<Option<Decimal>, ProgramError> {
+        let borrow_limit = self.limit_amount(
+            self.config.caps.borrow_limit,
+            self.config.caps.borrow_value_limit,
+        )?;
+        Ok(borrow_limit.map(|borrow_limit| {
+            borrow_limit
+                .try_sub(self.liquidity.borrowed_amount_wads)
+                .unwrap_or_else(|_| Decimal::zero())
+        }))
+    }
+
+    // Combine a limit in liquidity tokens with a limit in market value, valued
+    // at the current market price
+    fn limit_amount(&self, limit: u64, value_limit: u64) -> Result<Option<Decimal>, ProgramError> {
+        let mut limit_amount = (limit > 0).then(|| Decimal::from(limit));
+        if value_limit > 0 && self.liquidity.market_price > Decimal::zero() {
+            // @TODO: add lookup table https://git.io/JOCYq
+            let decimals = 10u64
+                .checked_pow(self.liquidity.mint_decimals as u32)
+                .ok_or(LendingError::MathOverflow)?;
+            let value_limit_amount = Decimal::from(value_limit)
+                .try_mul(decimals)?
+                .try_div(self.liquidity.market_price)?;
+            limit_amount = Some(limit_amount.map_or(value_limit_amount, |limit_amount| {
+                limit_amount.min(value_limit_amount)
+            }));
+        }
+        Ok(limit_amount)
+    }
+
     /// Repay liquidity up to the borrowed amount
     pub fn calculate_repay(
         &self,
@@ -220,8 +371,9 @@ impl Reserve {
         obligation: &Obligation,
         liquidity: &ObligationLiquidity,
         collateral: &ObligationCollateral,
+        liquidation_close_factor: u8,
     ) -> Result<CalculateLiquidationResult, ProgramError> {
-        let bonus_rate = Rate::from_percent(self.config.liquidation_bonus).try_add(Rate::one())?;
+        let bonus_rate = self.liquidation_bonus(obligation)?.try_add(Rate::one())?;
 
         let max_amount = if amount_to_liquidate == u64::MAX {
             liquidity.borrowed_amount_wads
@@ -261,7 +413,7 @@ impl Reserve {
             // calculate settle_amount and withdraw_amount, repay_amount is settle_amount
             // rounded
             let liquidation_amount = obligation
-                .max_liquidation_amount(liquidity)?
+                .max_liquidation_amount(liquidity, liquidation_close_factor)?
                 .min(max_amount);
             let liquidation_pct = liquidation_amount.try_div(liquidity.borrowed_amount_wads)?;
             let liquidation_value = liquidity
@@ -298,6 +450,30 @@ impl Reserve {
             withdraw_amount,
         })
     }
+
+    /// Bonus for liquidating the collateral of an unhealthy obligation. With
+    /// a max liquidation bonus, the bonus grows from the liquidation bonus by
+    /// the share of the obligation borrowed value above its unhealthy borrow
+    /// value.
+    pub fn liquidation_bonus(&self, obligation: &Obligation) -> Result<Rate, ProgramError> {
+        let liquidation_bonus = Rate::from_percent(self.config.liquidation_bonus);
+        let max_liquidation_bonus = Rate::from_percent(self.config.max_liquidation_bonus);
+        if max_liquidation_bonus <= liquidation_bonus
+            || obligation.borrowed_value <= obligation.unhealthy_borrow_value
+        {
+            return Ok(liquidation_bonus);
+        }
+
+        let shortfall_pct = Rate::try_from(
+            obligation
+                .borrowed_value
+                .try_sub(obligation.unhealthy_borrow_value)?
+                .try_div(obligation.borrowed_value)?,
+        )?;
+        Ok(liquidation_bonus
+            .try_add(shortfall_pct)?
+            .min(max_liquidation_bonus))
+    }
 }
 
 /// Initialize a reserve
@@ -369,6 +545,20 @@ pub struct ReserveLiquidity {
     pub cumulative_borrow_rate_wads: Decimal,
     /// Reserve liquidity market price in quote currency
     pub market_price: Decimal,
+    /// Borrow rate at target utilization, for the adaptive interest rate model
+    pub adaptive_rate_at_target: Rate,
+    /// Time-weighted average of swap pool prices, for swap pool oracles
+    pub twap_price: Decimal,
+    /// Slot of the last swap pool price sampled into the TWAP
+    pub twap_slot: Slot,
+    /// Slot of the first swap pool price sampled into the TWAP since it was
+    /// last restarted
+    pub twap_start_slot: Slot,
+    /// Protocol share of accrued interest not yet withdrawn, which is not
+    /// owed to depositors
+    pub accumulated_protocol_fees_wads: Decimal,
+    /// Token program owning the liquidity mint, SPL Token or Token-2022
+    pub token_program_id: Pubkey,
 }
 
 impl ReserveLiquidity {
@@ -384,12 +574,82 @@ impl ReserveLiquidity {
             borrowed_amount_wads: Decimal::zero(),
             cumulative_borrow_rate_wads: Decimal::one(),
             market_price: params.market_price,
+            adaptive_rate_at_target: Rate::zero(),
+            twap_price: Decimal::zero(),
+            twap_slot: 0,
+            twap_start_slot: 0,
+            accumulated_protocol_fees_wads: Decimal::zero(),
+            token_program_id: params.token_program_id,
+        }
+    }
+
+    /// Sample a swap pool spot price into the TWAP and return the new TWAP.
+    /// Each sample is weighted by the slots elapsed since the previous one, so
+    /// a single manipulated spot price moves the TWAP by at most
+    /// `max_sample_slots / window_slots` of its deviation.
+    ///
+    /// After a gap of more than `max_sample_slots`, or more than the window,
+    /// the TWAP no longer reflects recent prices, so it restarts from the spot
+    /// price and has to warm up again.
+    pub fn sample_twap(
+        &mut self,
+        spot_price: Decimal,
+        current_slot: Slot,
+        window_slots: u64,
+        max_sample_slots: u64,
+    ) -> Result<Decimal, ProgramError> {
+        let sample_slots = if self.twap_price == Decimal::zero() {
+            None
+        } else {
+            let elapsed_slots = current_slot
+                .checked_sub(self.twap_slot)
+                .ok_or(LendingError::MathOverflow)?;
+            (elapsed_slots <= max_sample_slots.min(window_slots)).then_some(elapsed_slots)
+        };
+        if let Some(sample_slots) = sample_slots {
+            self.twap_price = if spot_price > self.twap_price {
+                let change = spot_price
+                    .try_sub(self.twap_price)?
+                    .try_mul(sample_slots)?
+                    .try_div(window_slots)?;
+                self.twap_price.try_add(change)?
+            } else {
+                let change = self
+                    .twap_price
+                    .try_sub(spot_price)?
+                    .try_mul(sample_slots)?
+                    .try_div(window_slots)?;
+                self.twap_price.try_sub(change)?
+            };
+        } else {
+            self.twap_price = spot_price;
+            self.twap_start_slot = current_slot;
         }
+        self.twap_slot = current_slot;
+        Ok(self.twap_price)
+    }
+
+    /// Whether the TWAP has been sampled over a whole window since it was
+    /// last restarted.  Until then it is still close to its first spot price,
+    /// which may have been manipulated, and cannot be used as a price.
+    pub fn twap_warmed_up(&self, current_slot: Slot, window_slots: u64) -> bool {
+        self.twap_price != Decimal::zero()
+            && current_slot.saturating_sub(self.twap_start_slot) >= window_slots
+    }
+
+    /// Discard the TWAP, restarting it from the next sampled spot price
+    pub fn reset_twap(&mut self) {
+        self.twap_price = Decimal::zero();
+        self.twap_slot = 0;
+        self.twap_start_slot = 0;
     }
 
-    /// Calculate the total reserve supply including active loans
+    /// Calculate the total reserve supply owed to depositors, including active
+    /// loans and excluding protocol fees
     pub fn total_supply(&self) -> Result<Decimal, ProgramError> {
-        Decimal::from(self.available_amount).try_add(self.borrowed_amount_wads)
+        Decimal::from(self.available_amount)
+            .try_add(self.borrowed_amount_wads)?
+            .try_sub(self.accumulated_protocol_fees_wads)
     }
 
     /// Add liquidity to available amount
@@ -443,20 +703,36 @@ impl ReserveLiquidity {
         Ok(())
     }
 
+    /// Write off borrowed liquidity which can no longer be repaid, lowering
+    /// the total supply shared by collateral holders
+    pub fn write_off(&mut self, settle_amount: Decimal) -> ProgramResult {
+        self.borrowed_amount_wads = self.borrowed_amount_wads.try_sub(settle_amount)?;
+        // protocol fees are owed out of the remaining supply
+        self.accumulated_protocol_fees_wads = self
+            .accumulated_protocol_fees_wads
+            .min(Decimal::from(self.available_amount).try_add(self.borrowed_amount_wads)?);
+
+        Ok(())
+    }
+
     /// Calculate the liquidity utilization rate of the reserve
     pub fn utilization_rate(&self) -> Result<Rate, ProgramError> {
-        let total_supply = self.total_supply()?;
+        // protocol fees are lent out like any other liquidity until withdrawn
+        let total_supply =
+            Decimal::from(self.available_amount).try_add(self.borrowed_amount_wads)?;
         if total_supply == Decimal::zero() {
             return Ok(Rate::zero());
         }
         self.borrowed_amount_wads.try_div(total_supply)?.try_into()
     }
 
-    /// Compound current borrow rate over elapsed slots
+    /// Compound current borrow rate over elapsed slots, keeping the protocol
+    /// take rate of the accrued interest as protocol fees
     fn compound_interest(
         &mut self,
         current_borrow_rate: Rate,
         slots_elapsed: u64,
+        protocol_take_rate: Rate,
     ) -> ProgramResult {
         let slot_interest_rate = current_borrow_rate.try_div(SLOTS_PER_YEAR)?;
         let compounded_interest_rate = Rate::one()
@@ -465,11 +741,36 @@ impl ReserveLiquidity {
         self.cumulative_borrow_rate_wads = self
             .cumulative_borrow_rate_wads
             .try_mul(compounded_interest_rate)?;
-        self.borrowed_amount_wads = self
+        let borrowed_amount_wads = self
             .borrowed_amount_wads
             .try_mul(compounded_interest_rate)?;
+        let protocol_fees = borrowed_amount_wads
+            .try_sub(self.borrowed_amount_wads)?
+            .try_mul(protocol_take_rate)?;
+        self.accumulated_protocol_fees_wads =
+            self.accumulated_protocol_fees_wads.try_add(protocol_fees)?;
+        self.borrowed_amount_wads = borrowed_amount_wads;
         Ok(())
     }
+
+    /// Remove up to `liquidity_amount` of accumulated protocol fees from the
+    /// available liquidity, returning the amount withdrawn
+    pub fn withdraw_protocol_fees(&mut self, liquidity_amount: u64) -> Result<u64, ProgramError> {
+        let withdraw_amount = self
+            .accumulated_protocol_fees_wads
+            .try_floor_u64()?
+            .min(liquidity_amount)
+            .min(self.available_amount);
+        if withdraw_amount == 0 {
+            msg!("No protocol fees are available to withdraw");
+            return Err(LendingError::InsufficientLiquidity.into());
+        }
+        self.withdraw(withdraw_amount)?;
+        self.accumulated_protocol_fees_wads = self
+            .accumulated_protocol_fees_wads
+            .try_sub(Decimal::from(withdraw_amount))?;
+        Ok(withdraw_amount)
+    }
 }
 
 /// Create a new reserve liquidity
@@ -486,6 +787,8 @@ pub struct NewReserveLiquidityParams {
     pub oracle_pubkey: Pubkey,
     /// Reserve liquidity market price in quote currency
     pub market_price: Decimal,
+    /// Token program owning the liquidity mint
+    pub token_program_id: Pubkey,
 }
 
 /// Reserve collateral
@@ -591,6 +894,9 @@ impl From<CollateralExchangeRate> for Rate {
     }
 }
 
+/// Length of reserve configs packed apart from their reserve
+pub const RESERVE_CONFIG_LEN: usize = 167; // 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 41 + 62 + 8 + 8 + 8 + 8 + 1 + 1 + 1 + 1 + 1 + 1 + 8 + 8 + 1 + 1
+
 /// Reserve configuration values
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct ReserveConfig {
@@ -602,6 +908,10 @@ pub struct ReserveConfig {
     /// Bonus a liquidator gets when repaying part of an unhealthy obligation,
     /// as a percentage
     pub liquidation_bonus: u8,
+    /// Max bonus a liquidator gets as the obligation falls further below its
+    /// liquidation threshold, as a percentage
+    /// 0 for a static liquidation bonus
+    pub max_liquidation_bonus: u8,
     /// Loan to value ratio at which an obligation can be liquidated, as a
     /// percentage
     pub liquidation_threshold: u8,
@@ -611,6 +921,23 @@ pub struct ReserveConfig {
     pub optimal_borrow_rate: u8,
     /// Max borrow APY
     pub max_borrow_rate: u8,
+    /// Model deriving the borrow APY from utilization, the kinked model uses
+    /// the rates above
+    pub interest_rate_model: InterestRateModel,
+    /// Oracles the liquidity market price is read from
+    pub oracle: OracleConfig,
+    /// Limits on the reserve supply and borrows
+    pub caps: ReserveCaps,
+    /// Whether the collateral can only back borrows of a single liquidity
+    /// asset
+    pub isolated_collateral: bool,
+    /// Group of correlated reserves with raised collateral ratios
+    pub elevation_group: ElevationGroup,
+    /// Whether new borrows of the liquidity are rejected
+    pub borrowing_disabled: bool,
+    /// Whether liquidity deposits, borrows and flash loans are rejected,
+    /// leaving repayments, withdrawals and liquidations open
+    pub paused: bool,
     /// Program owner fees assessed, separate from gains due to interest accrual
     pub fees: ReserveFees,
 }
@@ -631,6 +958,13 @@ impl ReserveConfig {
             msg!("Liquidation bonus must be in range [0, 100]");
             return Err(LendingError::InvalidConfig.into());
         }
+        if self.max_liquidation_bonus != 0
+            && (self.max_liquidation_bonus < self.liquidation_bonus
+                || self.max_liquidation_bonus > 100)
+        {
+            msg!("Max liquidation bonus must be 0 or in range [liquidation bonus, 100]");
+            return Err(LendingError::InvalidConfig.into());
+        }
         if self.liquidation_threshold <= self.loan_to_value_ratio
             || self.liquidation_threshold > 100
         {
@@ -657,9 +991,305 @@ impl ReserveConfig {
             msg!("Host fee percentage must be in range [0, 100]");
             return Err(LendingError::InvalidConfig.into());
         }
+        if self.fees.protocol_take_rate > 100 {
+            msg!("Protocol take rate must be in range [0, 100]");
+            return Err(LendingError::InvalidConfig.into());
+        }
+        if self.elevation_group.id == 0 {
+            if self.elevation_group.loan_to_value_ratio != 0
+                || self.elevation_group.liquidation_threshold != 0
+            {
+                msg!("Elevation group ratios must be 0 for reserves outside of elevation groups");
+                return Err(LendingError::InvalidConfig.into());
+            }
+        } else {
+            if self.elevation_group.loan_to_value_ratio < self.loan_to_value_ratio
+                || self.elevation_group.loan_to_value_ratio >= 100
+            {
+                msg!("Elevation group loan to value ratio must be in range [LTV, 100)");
+                return Err(LendingError::InvalidConfig.into());
+            }
+            if self.elevation_group.liquidation_threshold < self.liquidation_threshold
+                || self.elevation_group.liquidation_threshold
+                    <= self.elevation_group.loan_to_value_ratio
+                || self.elevation_group.liquidation_threshold > 100
+            {
+                msg!("Elevation group liquidation threshold must be in range (elevation group LTV, 100] and at least the liquidation threshold");
+                return Err(LendingError::InvalidConfig.into());
+            }
+        }
+        self.interest_rate_model.validate()?;
+        self.oracle.validate()?;
 
         Ok(())
     }
+
+    /// Loan to value ratio and liquidation threshold of the collateral, as
+    /// percentages, raised in obligations of the reserve elevation group
+    pub fn collateral_ratios(&self, obligation_elevation_group: u8) -> (u8, u8) {
+        if self.elevation_group.id != 0 && self.elevation_group.id == obligation_elevation_group {
+            (
+                self.elevation_group.loan_to_value_ratio,
+                self.elevation_group.liquidation_threshold,
+            )
+        } else {
+            (self.loan_to_value_ratio, self.liquidation_threshold)
+        }
+    }
+
+    /// Validate that the new configs only lower the risk of the reserve
+    /// compared to its current configs, for changes applied without the
+    /// lending market config timelock: lower loan to value ratios, tighter
+    /// caps, disabled borrowing or a pause
+    pub fn validate_tightening(&self, current: &ReserveConfig) -> ProgramResult {
+        let tightened = ReserveConfig {
+            loan_to_value_ratio: self.loan_to_value_ratio,
+            elevation_group: ElevationGroup {
+                loan_to_value_ratio: self.elevation_group.loan_to_value_ratio,
+                ..current.elevation_group
+            },
+            caps: self.caps,
+            borrowing_disabled: self.borrowing_disabled,
+            paused: self.paused,
+            ..*current
+        };
+        if *self != tightened {
+            msg!("Only loan to value ratios, caps, borrowing and pausing can change without the config timelock");
+            return Err(LendingError::ConfigNotTightened.into());
+        }
+
+        // A limit of 0 is unchecked, the loosest limit
+        let tighter_limit = |new: u64, current: u64| {
+            new == current || (new != 0 && (current == 0 || new < current))
+        };
+        if self.loan_to_value_ratio > current.loan_to_value_ratio
+            || self.elevation_group.loan_to_value_ratio
+                > current.elevation_group.loan_to_value_ratio
+            || !tighter_limit(self.caps.deposit_limit, current.caps.deposit_limit)
+            || !tighter_limit(self.caps.borrow_limit, current.caps.borrow_limit)
+            || !tighter_limit(
+                self.caps.deposit_value_limit,
+                current.caps.deposit_value_limit,
+            )
+            || !tighter_limit(
+                self.caps.borrow_value_limit,
+                current.caps.borrow_value_limit,
+            )
+            || (current.borrowing_disabled && !self.borrowing_disabled)
+            || (current.paused && !self.paused)
+        {
+            msg!("Reserve config changes loosening risk must be queued behind the config timelock");
+            return Err(LendingError::ConfigNotTightened.into());
+        }
+        Ok(())
+    }
+
+    /// Pack the reserve configs into a fixed size buffer, for accounts holding
+    /// configs apart from their reserve
+    pub fn pack_into_slice(&self, output: &mut [u8; RESERVE_CONFIG_LEN]) {
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            optimal_utilization_rate,
+            loan_to_value_ratio,
+            liquidation_bonus,
+            max_liquidation_bonus,
+            liquidation_threshold,
+            min_borrow_rate,
+            optimal_borrow_rate,
+            max_borrow_rate,
+            interest_rate_model,
+            oracle,
+            deposit_limit,
+            borrow_limit,
+            deposit_value_limit,
+            borrow_value_limit,
+            isolated_collateral,
+            elevation_group_id,
+            elevation_group_loan_to_value_ratio,
+            elevation_group_liquidation_threshold,
+            borrowing_disabled,
+            paused,
+            borrow_fee_wad,
+            flash_loan_fee_wad,
+            host_fee_percentage,
+            protocol_take_rate,
+        ) = mut_array_refs![
+            output,
+            1,
+            1,
+            1,
+            1,
+            1,
+            1,
+            1,
+            1,
+            INTEREST_RATE_MODEL_LEN,
+            ORACLE_CONFIG_LEN,
+            8,
+            8,
+            8,
+            8,
+            1,
+            1,
+            1,
+            1,
+            1,
+            1,
+            8,
+            8,
+            1,
+            1
+        ];
+
+        *optimal_utilization_rate = self.optimal_utilization_rate.to_le_bytes();
+        *loan_to_value_ratio = self.loan_to_value_ratio.to_le_bytes();
+        *liquidation_bonus = self.liquidation_bonus.to_le_bytes();
+        *max_liquidation_bonus = self.max_liquidation_bonus.to_le_bytes();
+        *liquidation_threshold = self.liquidation_threshold.to_le_bytes();
+        *min_borrow_rate = self.min_borrow_rate.to_le_bytes();
+        *optimal_borrow_rate = self.optimal_borrow_rate.to_le_bytes();
+        *max_borrow_rate = self.max_borrow_rate.to_le_bytes();
+        self.interest_rate_model
+            .pack_into_slice(interest_rate_model);
+        self.oracle.pack_into_slice(oracle);
+        *deposit_limit = self.caps.deposit_limit.to_le_bytes();
+        *borrow_limit = self.caps.borrow_limit.to_le_bytes();
+        *deposit_value_limit = self.caps.deposit_value_limit.to_le_bytes();
+        *borrow_value_limit = self.caps.borrow_value_limit.to_le_bytes();
+        pack_bool(self.isolated_collateral, isolated_collateral);
+        *elevation_group_id = self.elevation_group.id.to_le_bytes();
+        *elevation_group_loan_to_value_ratio =
+            self.elevation_group.loan_to_value_ratio.to_le_bytes();
+        *elevation_group_liquidation_threshold =
+            self.elevation_group.liquidation_threshold.to_le_bytes();
+        pack_bool(self.borrowing_disabled, borrowing_disabled);
+        pack_bool(self.paused, paused);
+        *borrow_fee_wad = self.fees.borrow_fee_wad.to_le_bytes();
+        *flash_loan_fee_wad = self.fees.flash_loan_fee_wad.to_le_bytes();
+        *host_fee_percentage = self.fees.host_fee_percentage.to_le_bytes();
+        *protocol_take_rate = self.fees.protocol_take_rate.to_le_bytes();
+    }
+
+    /// Unpack reserve configs packed apart from their reserve
+    pub fn unpack_from_slice(input: &[u8; RESERVE_CONFIG_LEN]) -> Result<Self, ProgramError> {
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            optimal_utilization_rate,
+            loan_to_value_ratio,
+            liquidation_bonus,
+            max_liquidation_bonus,
+            liquidation_threshold,
+            min_borrow_rate,
+            optimal_borrow_rate,
+            max_borrow_rate,
+            interest_rate_model,
+            oracle,
+            deposit_limit,
+            borrow_limit,
+            deposit_value_limit,
+            borrow_value_limit,
+            isolated_collateral,
+            elevation_group_id,
+            elevation_group_loan_to_value_ratio,
+            elevation_group_liquidation_threshold,
+            borrowing_disabled,
+            paused,
+            borrow_fee_wad,
+            flash_loan_fee_wad,
+            host_fee_percentage,
+            protocol_take_rate,
+        ) = array_refs![
+            input,
+            1,
+            1,
+            1,
+            1,
+            1,
+            1,
+            1,
+            1,
+            INTEREST_RATE_MODEL_LEN,
+            ORACLE_CONFIG_LEN,
+            8,
+            8,
+            8,
+            8,
+            1,
+            1,
+            1,
+            1,
+            1,
+            1,
+            8,
+            8,
+            1,
+            1
+        ];
+
+        Ok(Self {
+            optimal_utilization_rate: u8::from_le_bytes(*optimal_utilization_rate),
+            loan_to_value_ratio: u8::from_le_bytes(*loan_to_value_ratio),
+            liquidation_bonus: u8::from_le_bytes(*liquidation_bonus),
+            max_liquidation_bonus: u8::from_le_bytes(*max_liquidation_bonus),
+            liquidation_threshold: u8::from_le_bytes(*liquidation_threshold),
+            min_borrow_rate: u8::from_le_bytes(*min_borrow_rate),
+            optimal_borrow_rate: u8::from_le_bytes(*optimal_borrow_rate),
+            max_borrow_rate: u8::from_le_bytes(*max_borrow_rate),
+            interest_rate_model: InterestRateModel::unpack_from_slice(interest_rate_model)?,
+            oracle: OracleConfig::unpack_from_slice(oracle)?,
+            caps: ReserveCaps {
+                deposit_limit: u64::from_le_bytes(*deposit_limit),
+                borrow_limit: u64::from_le_bytes(*borrow_limit),
+                deposit_value_limit: u64::from_le_bytes(*deposit_value_limit),
+                borrow_value_limit: u64::from_le_bytes(*borrow_value_limit),
+            },
+            isolated_collateral: unpack_bool(isolated_collateral)?,
+            elevation_group: ElevationGroup {
+                id: u8::from_le_bytes(*elevation_group_id),
+                loan_to_value_ratio: u8::from_le_bytes(*elevation_group_loan_to_value_ratio),
+                liquidation_threshold: u8::from_le_bytes(*elevation_group_liquidation_threshold),
+            },
+            borrowing_disabled: unpack_bool(borrowing_disabled)?,
+            paused: unpack_bool(paused)?,
+            fees: ReserveFees {
+                borrow_fee_wad: u64::from_le_bytes(*borrow_fee_wad),
+                flash_loan_fee_wad: u64::from_le_bytes(*flash_loan_fee_wad),
+                host_fee_percentage: u8::from_le_bytes(*host_fee_percentage),
+                protocol_take_rate: u8::from_le_bytes(*protocol_take_rate),
+            },
+        })
+    }
+}
+
+/// Correlated reserves whose collateral gets raised ratios in obligations
+/// with all their deposits and borrows in the group, e.g. a token and its
+/// liquid staking derivatives
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ElevationGroup {
+    /// Group shared by the correlated reserves, 0 for no group
+    pub id: u8,
+    /// Target ratio of the value of borrows to deposits in the group, as a
+    /// percentage
+    pub loan_to_value_ratio: u8,
+    /// Loan to value ratio at which an obligation in the group can be
+    /// liquidated, as a percentage
+    pub liquidation_threshold: u8,
+}
+
+/// Deposit and borrow limits of a reserve, bounding the exposure of the
+/// lending market to its liquidity. Each limit is unchecked when 0.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ReserveCaps {
+    /// Max total supply, available and borrowed, in liquidity tokens
+    pub deposit_limit: u64,
+    /// Max total borrows, in liquidity tokens
+    pub borrow_limit: u64,
+    /// Max market value of the total supply, in whole units of the lending
+    /// market quote currency
+    pub deposit_value_limit: u64,
+    /// Max market value of the total borrows, in whole units of the lending
+    /// market quote currency
+    pub borrow_value_limit: u64,
 }
 
 /// Additional fee information on a reserve
@@ -681,6 +1311,9 @@ pub struct ReserveFees {
     pub flash_loan_fee_wad: u64,
     /// Amount of fee going to host account, if provided in liquidate and repay
     pub host_fee_percentage: u8,
+    /// Percentage of accrued interest kept as protocol fees instead of going
+    /// to depositors
+    pub protocol_take_rate: u8,
 }
 
 impl ReserveFees {
@@ -770,8 +1403,12 @@ impl IsInitialized for Reserve {
     }
 }
 
+/// Last program version with reserves predating interest rate models
+const RESERVE_VERSION_KINKED_ONLY: u8 = 1;
+
 const RESERVE_LEN: usize = 571; // 1 + 8 + 1 + 32 + 32 + 1 + 32 + 32 + 32 + 8 + 16 + 16 + 16 + 32 + 8 + 32 + 1 +
-                                // 1 + 1 + 1 + 1 + 1 + 1 + 8 + 8 + 1 + 248
+                                // 1 + 1 + 1 + 1 + 1 + 1 + 8 + 8 + 1 + 41 + 16 + 62 +
+                                // 16 + 8 + 8 + 8 + 8 + 8 + 1 + 1 + 1 + 1 + 1 + 16 + 32 + 1 + 1 + 1 + 8 + 9
 impl Pack for Reserve {
     const LEN: usize = RESERVE_LEN;
 
@@ -806,6 +1443,26 @@ impl Pack for Reserve {
             config_fees_borrow_fee_wad,
             config_fees_flash_loan_fee_wad,
             config_fees_host_fee_percentage,
+            config_interest_rate_model,
+            liquidity_adaptive_rate_at_target,
+            config_oracle,
+            liquidity_twap_price,
+            liquidity_twap_slot,
+            config_caps_deposit_limit,
+            config_caps_borrow_limit,
+            config_caps_deposit_value_limit,
+            config_caps_borrow_value_limit,
+            config_isolated_collateral,
+            config_elevation_group_id,
+            config_elevation_group_loan_to_value_ratio,
+            config_elevation_group_liquidation_threshold,
+            config_fees_protocol_take_rate,
+            liquidity_accumulated_protocol_fees_wads,
+            liquidity_token_program_id,
+            config_max_liquidation_bonus,
+            config_borrowing_disabled,
+            config_paused,
+            liquidity_twap_start_slot,
             _padding,
         ) = mut_array_refs![
             output,
@@ -835,7 +1492,27 @@ impl Pack for Reserve {
             8,
             8,
             1,
-            248
+            INTEREST_RATE_MODEL_LEN,
+            16,
+            ORACLE_CONFIG_LEN,
+            16,
+            8,
+            8,
+            8,
+            8,
+            8,
+            1,
+            1,
+            1,
+            1,
+            1,
+            16,
+            PUBKEY_BYTES,
+            1,
+            1,
+            1,
+            8,
+            9
         ];
 
         // reserve
@@ -877,6 +1554,43 @@ impl Pack for Reserve {
         *config_fees_borrow_fee_wad = self.config.fees.borrow_fee_wad.to_le_bytes();
         *config_fees_flash_loan_fee_wad = self.config.fees.flash_loan_fee_wad.to_le_bytes();
         *config_fees_host_fee_percentage = self.config.fees.host_fee_percentage.to_le_bytes();
+        self.config
+            .interest_rate_model
+            .pack_into_slice(config_interest_rate_model);
+        *liquidity_adaptive_rate_at_target = self
+            .liquidity
+            .adaptive_rate_at_target
+            .to_scaled_val()
+            .to_le_bytes();
+        self.config.oracle.pack_into_slice(config_oracle);
+        pack_decimal(self.liquidity.twap_price, liquidity_twap_price);
+        *liquidity_twap_slot = self.liquidity.twap_slot.to_le_bytes();
+        *liquidity_twap_start_slot = self.liquidity.twap_start_slot.to_le_bytes();
+        *config_caps_deposit_limit = self.config.caps.deposit_limit.to_le_bytes();
+        *config_caps_borrow_limit = self.config.caps.borrow_limit.to_le_bytes();
+        *config_caps_deposit_value_limit = self.config.caps.deposit_value_limit.to_le_bytes();
+        *config_caps_borrow_value_limit = self.config.caps.borrow_value_limit.to_le_bytes();
+        pack_bool(self.config.isolated_collateral, config_isolated_collateral);
+        *config_elevation_group_id = self.config.elevation_group.id.to_le_bytes();
+        *config_elevation_group_loan_to_value_ratio = self
+            .config
+            .elevation_group
+            .loan_to_value_ratio
+            .to_le_bytes();
+        *config_elevation_group_liquidation_threshold = self
+            .config
+            .elevation_group
+            .liquidation_threshold
+            .to_le_bytes();
+        *config_fees_protocol_take_rate = self.config.fees.protocol_take_rate.to_le_bytes();
+        pack_decimal(
+            self.liquidity.accumulated_protocol_fees_wads,
+            liquidity_accumulated_protocol_fees_wads,
+        );
+        liquidity_token_program_id.copy_from_slice(self.liquidity.token_program_id.as_ref());
+        *config_max_liquidation_bonus = self.config.max_liquidation_bonus.to_le_bytes();
+        pack_bool(self.config.borrowing_disabled, config_borrowing_disabled);
+        pack_bool(self.config.paused, config_paused);
     }
 
     /// Unpacks a byte buffer into a [ReserveInfo](struct.ReserveInfo.html).
@@ -910,6 +1624,26 @@ impl Pack for Reserve {
             config_fees_borrow_fee_wad,
             config_fees_flash_loan_fee_wad,
             config_fees_host_fee_percentage,
+            config_interest_rate_model,
+            liquidity_adaptive_rate_at_target,
+            config_oracle,
+            liquidity_twap_price,
+            liquidity_twap_slot,
+            config_caps_deposit_limit,
+            config_caps_borrow_limit,
+            config_caps_deposit_value_limit,
+            config_caps_borrow_value_limit,
+            config_isolated_collateral,
+            config_elevation_group_id,
+            config_elevation_group_loan_to_value_ratio,
+            config_elevation_group_liquidation_threshold,
+            config_fees_protocol_take_rate,
+            liquidity_accumulated_protocol_fees_wads,
+            liquidity_token_program_id,
+            config_max_liquidation_bonus,
+            config_borrowing_disabled,
+            config_paused,
+            liquidity_twap_start_slot,
             _padding,
         ) = array_refs![
             input,
@@ -939,7 +1673,27 @@ impl Pack for Reserve {
             8,
             8,
             1,
-            248
+            INTEREST_RATE_MODEL_LEN,
+            16,
+            ORACLE_CONFIG_LEN,
+            16,
+            8,
+            8,
+            8,
+            8,
+            8,
+            1,
+            1,
+            1,
+            1,
+            1,
+            16,
+            PUBKEY_BYTES,
+            1,
+            1,
+            1,
+            8,
+            9
         ];
 
         let version = u8::from_le_bytes(*version);
@@ -948,6 +1702,28 @@ impl Pack for Reserve {
             return Err(ProgramError::InvalidAccountData);
         }
 
+        // Reserves from before interest rate models never wrote these bytes, so
+        // they are migrated to the kinked model and saved with the new layout
+        let (version, interest_rate_model, adaptive_rate_at_target) =
+            if version == RESERVE_VERSION_KINKED_ONLY {
+                (PROGRAM_VERSION, InterestRateModel::Kinked, Rate::zero())
+            } else {
+                (
+                    version,
+                    InterestRateModel::unpack_from_slice(config_interest_rate_model)?,
+                    Rate::try_from(unpack_decimal(liquidity_adaptive_rate_at_target))?,
+                )
+            };
+
+        // Reserves from before Token-2022 support left these bytes zeroed and
+        // always hold SPL Token liquidity
+        let token_program_id = Pubkey::new_from_array(*liquidity_token_program_id);
+        let token_program_id = if token_program_id == Pubkey::default() {
+            spl_token::id()
+        } else {
+            token_program_id
+        };
+
         Ok(Self {
             version,
             last_update: LastUpdate {
@@ -965,6 +1741,14 @@ impl Pack for Reserve {
                 borrowed_amount_wads: unpack_decimal(liquidity_borrowed_amount_wads),
                 cumulative_borrow_rate_wads: unpack_decimal(liquidity_cumulative_borrow_rate_wads),
                 market_price: unpack_decimal(liquidity_market_price),
+                adaptive_rate_at_target,
+                twap_price: unpack_decimal(liquidity_twap_price),
+                twap_slot: u64::from_le_bytes(*liquidity_twap_slot),
+                twap_start_slot: u64::from_le_bytes(*liquidity_twap_start_slot),
+                accumulated_protocol_fees_wads: unpack_decimal(
+                    liquidity_accumulated_protocol_fees_wads,
+                ),
+                token_program_id,
             },
             collateral: ReserveCollateral {
                 mint_pubkey: Pubkey::new_from_array(*collateral_mint_pubkey),
@@ -975,14 +1759,36 @@ impl Pack for Reserve {
                 optimal_utilization_rate: u8::from_le_bytes(*config_optimal_utilization_rate),
                 loan_to_value_ratio: u8::from_le_bytes(*config_loan_to_value_ratio),
                 liquidation_bonus: u8::from_le_bytes(*config_liquidation_bonus),
+                max_liquidation_bonus: u8::from_le_bytes(*config_max_liquidation_bonus),
                 liquidation_threshold: u8::from_le_bytes(*config_liquidation_threshold),
                 min_borrow_rate: u8::from_le_bytes(*config_min_borrow_rate),
                 optimal_borrow_rate: u8::from_le_bytes(*config_optimal_borrow_rate),
                 max_borrow_rate: u8::from_le_bytes(*config_max_borrow_rate),
+                interest_rate_model,
+                oracle: OracleConfig::unpack_from_slice(config_oracle)?,
+                caps: ReserveCaps {
+                    deposit_limit: u64::from_le_bytes(*config_caps_deposit_limit),
+                    borrow_limit: u64::from_le_bytes(*config_caps_borrow_limit),
+                    deposit_value_limit: u64::from_le_bytes(*config_caps_deposit_value_limit),
+                    borrow_value_limit: u64::from_le_bytes(*config_caps_borrow_value_limit),
+                },
+                isolated_collateral: unpack_bool(config_isolated_collateral)?,
+                elevation_group: ElevationGroup {
+                    id: u8::from_le_bytes(*config_elevation_group_id),
+                    loan_to_value_ratio: u8::from_le_bytes(
+                        *config_elevation_group_loan_to_value_ratio,
+                    ),
+                    liquidation_threshold: u8::from_le_bytes(
+                        *config_elevation_group_liquidation_threshold,
+                    ),
+                },
+                borrowing_disabled: unpack_bool(config_borrowing_disabled)?,
+                paused: unpack_bool(config_paused)?,
                 fees: ReserveFees {
                     borrow_fee_wad: u64::from_le_bytes(*config_fees_borrow_fee_wad),
                     flash_loan_fee_wad: u64::from_le_bytes(*config_fees_flash_loan_fee_wad),
                     host_fee_percentage: u8::from_le_bytes(*config_fees_host_fee_percentage),
+                    protocol_take_rate: u8::from_le_bytes(*config_fees_protocol_take_rate),
                 },
             },
         })
@@ -1159,7 +1965,7 @@ mod test {
             // Simulate running for max 1000 years, assuming that interest is
             // compounded at least once a year
             for _ in 0..1000 {
-                reserve.liquidity.compound_interest(borrow_rate, slots_elapsed)?;
+                reserve.liquidity.compound_interest(borrow_rate, slots_elapsed, Rate::zero())?;
                 reserve.liquidity.cumulative_borrow_rate_wads.to_scaled_val()?;
             }
         }
@@ -1205,6 +2011,7 @@ mod test {
                 borrow_fee_wad,
                 flash_loan_fee_wad,
                 host_fee_percentage,
+                protocol_take_rate: 0,
             };
             let (total_fee, host_fee) = fees.calculate_borrow_fees(Decimal::from(borrow_amount), FeeCalculation::Exclusive)?;
 
@@ -1248,6 +2055,7 @@ mod test {
                 borrow_fee_wad,
                 flash_loan_fee_wad,
                 host_fee_percentage,
+                protocol_take_rate: 0,
             };
             let (total_fee, host_fee) = fees.calculate_flash_loan_fees(Decimal::from(borrow_amount))?;
 
@@ -1279,12 +2087,189 @@ mod test {
         }
     }
 
+    fn adaptive_rate_model() -> AdaptiveRateModel {
+        AdaptiveRateModel {
+            target_utilization_bps: 9_000,
+            initial_rate_at_target_bps: 400,
+            min_rate_at_target_bps: 10,
+            max_rate_at_target_bps: 20_000,
+            adjustment_speed_bps: 500_000,
+            curve_steepness: 4,
+        }
+    }
+
+    #[test]
+    fn reserve_migration() {
+        let reserve = Reserve {
+            version: PROGRAM_VERSION,
+            liquidity: ReserveLiquidity {
+                adaptive_rate_at_target: Rate::from_bps(500),
+                twap_price: Decimal::from(3u64),
+                twap_slot: 100,
+                twap_start_slot: 40,
+                accumulated_protocol_fees_wads: Decimal::from(7u64),
+                token_program_id: spl_token_2022::id(),
+                ..ReserveLiquidity::default()
+            },
+            config: ReserveConfig {
+                interest_rate_model: InterestRateModel::Adaptive(adaptive_rate_model()),
+                oracle: OracleConfig {
+                    source: OracleSource::SwapPoolTwap,
+                    twap_window_slots: 100,
+                    ..OracleConfig::default()
+                },
+                caps: ReserveCaps {
+                    deposit_limit: 1_000,
+                    borrow_limit: 500,
+                    deposit_value_limit: 2_000,
+                    borrow_value_limit: 0,
+                },
+                isolated_collateral: true,
+                elevation_group: ElevationGroup {
+                    id: 1,
+                    loan_to_value_ratio: 90,
+                    liquidation_threshold: 95,
+                },
+                fees: ReserveFees {
+                    protocol_take_rate: 10,
+                    ..ReserveFees::default()
+                },
+                max_liquidation_bonus: 20,
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        let mut packed = [0u8; Reserve::LEN];
+        Reserve::pack(reserve.clone(), &mut packed).unwrap();
+        assert_eq!(Reserve::unpack(&packed).unwrap(), reserve);
+
+        // reserves from before interest rate models use the kinked model, and
+        // are packed with the current version
+        packed[0] = RESERVE_VERSION_KINKED_ONLY;
+        let migrated = Reserve::unpack(&packed).unwrap();
+        assert_eq!(migrated.version, PROGRAM_VERSION);
+        assert_eq!(
+            migrated.config.interest_rate_model,
+            InterestRateModel::Kinked
+        );
+        assert_eq!(migrated.liquidity.adaptive_rate_at_target, Rate::zero());
+        Reserve::pack(migrated.clone(), &mut packed).unwrap();
+        assert_eq!(Reserve::unpack(&packed).unwrap(), migrated);
+
+        // reserves from before Token-2022 support hold SPL Token liquidity
+        let legacy_reserve = Reserve {
+            liquidity: ReserveLiquidity {
+                token_program_id: Pubkey::default(),
+                ..migrated.liquidity
+            },
+            ..migrated
+        };
+        Reserve::pack(legacy_reserve, &mut packed).unwrap();
+        assert_eq!(
+            Reserve::unpack(&packed).unwrap().liquidity.token_program_id,
+            spl_token::id()
+        );
+    }
+
+    #[test]
+    fn update_config_interest_rate_model() {
+        let adaptive_model = adaptive_rate_model();
+        let mut reserve = Reserve::default();
+        reserve.update_config(ReserveConfig {
+            interest_rate_model: InterestRateModel::Adaptive(adaptive_model),
+            ..ReserveConfig::default()
+        });
+        assert_eq!(
+            reserve.liquidity.adaptive_rate_at_target,
+            Rate::from_bps(400)
+        );
+
+        // changing the adaptive model keeps the rate at target within bounds
+        reserve.liquidity.adaptive_rate_at_target = Rate::from_bps(1_000);
+        reserve.update_config(ReserveConfig {
+            interest_rate_model: InterestRateModel::Adaptive(AdaptiveRateModel {
+                max_rate_at_target_bps: 800,
+                ..adaptive_model
+            }),
+            ..ReserveConfig::default()
+        });
+        assert_eq!(
+            reserve.liquidity.adaptive_rate_at_target,
+            Rate::from_bps(800)
+        );
+
+        reserve.update_config(ReserveConfig::default());
+        assert_eq!(reserve.liquidity.adaptive_rate_at_target, Rate::zero());
+    }
+
+    #[test]
+    fn accrue_interest_adaptive_rate_model() {
+        let mut reserve = Reserve {
+            liquidity: ReserveLiquidity {
+                available_amount: 50,
+                borrowed_amount_wads: Decimal::from(950u64),
+                cumulative_borrow_rate_wads: Decimal::one(),
+                ..ReserveLiquidity::default()
+            },
+            ..Reserve::default()
+        };
+        reserve.update_config(ReserveConfig {
+            interest_rate_model: InterestRateModel::Adaptive(adaptive_rate_model()),
+            ..ReserveConfig::default()
+        });
+
+        // 95% utilization is above target, so the rate at target rises
+        let borrow_rate = reserve.current_borrow_rate().unwrap();
+        assert_eq!(borrow_rate, Rate::from_bps(1_000));
+        reserve.accrue_interest(SLOTS_PER_YEAR / 365).unwrap();
+        assert!(reserve.liquidity.borrowed_amount_wads > Decimal::from(950u64));
+        assert!(reserve.liquidity.adaptive_rate_at_target > Rate::from_bps(400));
+        assert!(reserve.current_borrow_rate().unwrap() > borrow_rate);
+    }
+
+    #[test]
+    fn current_borrow_rate_piecewise_linear() {
+        let reserve = Reserve {
+            liquidity: ReserveLiquidity {
+                available_amount: 10,
+                borrowed_amount_wads: Decimal::from(990u64),
+                ..ReserveLiquidity::default()
+            },
+            config: ReserveConfig {
+                interest_rate_model: InterestRateModel::PiecewiseLinear(
+                    RateCurve::new(&[
+                        RateCurvePoint {
+                            utilization_bps: 0,
+                            borrow_rate_bps: 0,
+                        },
+                        RateCurvePoint {
+                            utilization_bps: 9_800,
+                            borrow_rate_bps: 1_000,
+                        },
+                        RateCurvePoint {
+                            utilization_bps: 10_000,
+                            borrow_rate_bps: 31_000,
+                        },
+                    ])
+                    .unwrap(),
+                ),
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        assert_eq!(
+            reserve.current_borrow_rate().unwrap(),
+            Rate::from_bps(16_000)
+        );
+    }
+
     #[test]
     fn borrow_fee_calculation_min_host() {
         let fees = ReserveFees {
             borrow_fee_wad: 10_000_000_000_000_000, // 1%
             flash_loan_fee_wad: 0,
             host_fee_percentage: 20,
+            protocol_take_rate: 0,
         };
 
         // only 2 tokens borrowed, get error
@@ -1313,6 +2298,7 @@ mod test {
             borrow_fee_wad: 10_000_000_000_000_000, // 1%
             flash_loan_fee_wad: 0,
             host_fee_percentage: 0,
+            protocol_take_rate: 0,
         };
 
         // only 2 tokens borrowed, ok
@@ -1342,6 +2328,7 @@ mod test {
             borrow_fee_wad: 10_000_000_000_000_000, // 1%
             flash_loan_fee_wad: 0,
             host_fee_percentage: 20,
+            protocol_take_rate: 0,
         };
 
         let (total_fee, host_fee) = fees
@@ -1358,6 +2345,7 @@ mod test {
             borrow_fee_wad: 10_000_000_000_000_000, // 1%
             flash_loan_fee_wad: 0,
             host_fee_percentage: 0,
+            protocol_take_rate: 0,
         };
 
         let (total_fee, host_fee) = fees
@@ -1367,4 +2355,427 @@ mod test {
         assert_eq!(total_fee, 10); // 1% of 1000
         assert_eq!(host_fee, 0); // 0 host fee
     }
+
+    #[test]
+    fn sample_twap() {
+        let mut liquidity = ReserveLiquidity::default();
+
+        // the first sample starts the TWAP
+        let twap = liquidity
+            .sample_twap(Decimal::from(100u64), 10, 100, 20)
+            .unwrap();
+        assert_eq!(twap, Decimal::from(100u64));
+        assert_eq!(liquidity.twap_slot, 10);
+        assert_eq!(liquidity.twap_start_slot, 10);
+        assert!(!liquidity.twap_warmed_up(10, 100));
+
+        // samples are weighted by the slots elapsed over the window
+        let twap = liquidity
+            .sample_twap(Decimal::from(200u64), 20, 100, 20)
+            .unwrap();
+        assert_eq!(twap, Decimal::from(110u64));
+        let twap = liquidity
+            .sample_twap(Decimal::from(10u64), 30, 100, 20)
+            .unwrap();
+        assert_eq!(twap, Decimal::from(100u64));
+        assert_eq!(liquidity.twap_start_slot, 10);
+        assert!(!liquidity.twap_warmed_up(109, 100));
+        assert!(liquidity.twap_warmed_up(110, 100));
+
+        // up to the max sample slots since the last sample
+        let twap = liquidity
+            .sample_twap(Decimal::from(1_100u64), 50, 100, 20)
+            .unwrap();
+        assert_eq!(twap, Decimal::from(300u64));
+        assert!(liquidity.twap_warmed_up(110, 100));
+
+        // a longer gap restarts the TWAP from the spot price, and it requires
+        // a whole window of samples again
+        let twap = liquidity
+            .sample_twap(Decimal::from(1_100u64), 71, 100, 20)
+            .unwrap();
+        assert_eq!(twap, Decimal::from(1_100u64));
+        assert_eq!(liquidity.twap_slot, 71);
+        assert_eq!(liquidity.twap_start_slot, 71);
+        assert!(!liquidity.twap_warmed_up(170, 100));
+        assert!(liquidity.twap_warmed_up(171, 100));
+
+        // as does a gap longer than the window
+        let twap = liquidity
+            .sample_twap(Decimal::from(5u64), 101, 10, 40)
+            .unwrap();
+        assert_eq!(twap, Decimal::from(5u64));
+        assert_eq!(liquidity.twap_start_slot, 101);
+
+        // restarting the TWAP requires a whole window of samples again
+        liquidity.reset_twap();
+        assert!(!liquidity.twap_warmed_up(1_001, 100));
+        let twap = liquidity
+            .sample_twap(Decimal::from(7u64), 1_001, 100, 20)
+            .unwrap();
+        assert_eq!(twap, Decimal::from(7u64));
+        assert!(!liquidity.twap_warmed_up(1_001, 100));
+        assert!(liquidity.twap_warmed_up(1_101, 100));
+    }
+
+    #[test]
+    fn update_config_oracle() {
+        let mut reserve = Reserve::new(InitReserveParams {
+            current_slot: 1,
+            lending_market: Pubkey::new_unique(),
+            liquidity: ReserveLiquidity::new(NewReserveLiquidityParams {
+                mint_pubkey: Pubkey::new_unique(),
+                mint_decimals: 9,
+                supply_pubkey: Pubkey::new_unique(),
+                fee_receiver: Pubkey::new_unique(),
+                oracle_pubkey: Pubkey::new_unique(),
+                market_price: Decimal::one(),
+                token_program_id: spl_token::id(),
+            }),
+            collateral: ReserveCollateral::default(),
+            config: ReserveConfig::default(),
+        });
+        reserve.liquidity.twap_price = Decimal::one();
+        reserve.last_update.update_slot(1);
+
+        // other configs keep the reserve fresh
+        reserve.update_config(ReserveConfig {
+            loan_to_value_ratio: 50,
+            ..reserve.config
+        });
+        assert!(!reserve.last_update.stale);
+        assert_eq!(reserve.liquidity.twap_price, Decimal::one());
+
+        // oracle configs require a refresh and restart the TWAP
+        reserve.update_config(ReserveConfig {
+            oracle: OracleConfig {
+                max_confidence_bps: 100,
+                ..reserve.config.oracle
+            },
+            ..reserve.config
+        });
+        assert!(reserve.last_update.stale);
+        assert_eq!(reserve.liquidity.twap_price, Decimal::zero());
+
+        reserve.liquidity.twap_price = Decimal::one();
+        reserve.last_update.update_slot(2);
+        let oracle_pubkey = reserve.liquidity.oracle_pubkey;
+        reserve.set_oracle(oracle_pubkey);
+        assert!(!reserve.last_update.stale);
+        reserve.set_oracle(Pubkey::new_unique());
+        assert!(reserve.last_update.stale);
+        assert_eq!(reserve.liquidity.twap_price, Decimal::zero());
+    }
+    #[test]
+    fn deposit_limit() {
+        let mut reserve = Reserve {
+            liquidity: ReserveLiquidity {
+                mint_decimals: 6,
+                available_amount: 800_000_000,
+                borrowed_amount_wads: Decimal::from(100_000_000u64),
+                market_price: Decimal::from(2u64),
+                ..ReserveLiquidity::default()
+            },
+            ..Reserve::default()
+        };
+        reserve.check_deposit_limit(u64::MAX / 2).unwrap();
+
+        reserve.config.caps.deposit_limit = 1_000_000_000;
+        reserve.check_deposit_limit(100_000_000).unwrap();
+        assert_eq!(
+            reserve.check_deposit_limit(100_000_001),
+            Err(LendingError::DepositLimitExceeded.into())
+        );
+
+        // 1,800 of the quote currency is 900 tokens at a price of 2
+        reserve.config.caps.deposit_value_limit = 1_800;
+        assert_eq!(
+            reserve.check_deposit_limit(1),
+            Err(LendingError::DepositLimitExceeded.into())
+        );
+        reserve.check_deposit_limit(0).unwrap();
+    }
+
+    #[test]
+    fn borrow_limit() {
+        let mut reserve = Reserve {
+            liquidity: ReserveLiquidity {
+                mint_decimals: 6,
+                available_amount: 800_000_000,
+                borrowed_amount_wads: Decimal::from(100_000_000u64),
+                market_price: Decimal::from(2u64),
+                ..ReserveLiquidity::default()
+            },
+            ..Reserve::default()
+        };
+        assert_eq!(reserve.remaining_borrow_limit(), Ok(None));
+
+        reserve.config.caps.borrow_value_limit = 400;
+        assert_eq!(
+            reserve.remaining_borrow_limit(),
+            Ok(Some(Decimal::from(100_000_000u64)))
+        );
+        reserve.config.caps.borrow_limit = 150_000_000;
+        assert_eq!(
+            reserve.remaining_borrow_limit(),
+            Ok(Some(Decimal::from(50_000_000u64)))
+        );
+        reserve
+            .check_borrow_limit(Decimal::from(50_000_000u64))
+            .unwrap();
+        assert_eq!(
+            reserve.check_borrow_limit(Decimal::from(50_000_001u64)),
+            Err(LendingError::BorrowLimitExceeded.into())
+        );
+
+        // max borrows are reduced to the remaining limit
+        let result = reserve
+            .calculate_borrow(u64::MAX, Decimal::from(1_000u64))
+            .unwrap();
+        assert_eq!(result.borrow_amount, Decimal::from(50_000_000u64));
+
+        // and fail once it is reached
+        reserve.config.caps.borrow_limit = 100_000_000;
+        assert_eq!(reserve.remaining_borrow_limit(), Ok(Some(Decimal::zero())));
+        assert_eq!(
+            reserve
+                .calculate_borrow(u64::MAX, Decimal::from(1_000u64))
+                .err(),
+            Some(LendingError::BorrowLimitExceeded.into())
+        );
+    }
+
+    #[test]
+    fn collateral_ratios() {
+        let mut config = ReserveConfig {
+            loan_to_value_ratio: 50,
+            liquidation_threshold: 55,
+            elevation_group: ElevationGroup {
+                id: 1,
+                loan_to_value_ratio: 80,
+                liquidation_threshold: 85,
+            },
+            ..ReserveConfig::default()
+        };
+        assert_eq!(config.validate(), Ok(()));
+        assert_eq!(config.collateral_ratios(0), (50, 55));
+        assert_eq!(config.collateral_ratios(1), (80, 85));
+        assert_eq!(config.collateral_ratios(2), (50, 55));
+
+        // elevated ratios cannot be lower than the base ratios
+        config.elevation_group.loan_to_value_ratio = 40;
+        assert_eq!(config.validate(), Err(LendingError::InvalidConfig.into()));
+        config.elevation_group.loan_to_value_ratio = 80;
+        config.elevation_group.liquidation_threshold = 80;
+        assert_eq!(config.validate(), Err(LendingError::InvalidConfig.into()));
+
+        // reserves outside of elevation groups never use elevated ratios
+        config.elevation_group = ElevationGroup::default();
+        assert_eq!(config.validate(), Ok(()));
+        assert_eq!(config.collateral_ratios(0), (50, 55));
+    }
+
+    #[test]
+    fn protocol_fees() {
+        let mut reserve = Reserve {
+            liquidity: ReserveLiquidity {
+                available_amount: 1_000,
+                borrowed_amount_wads: Decimal::from(1_000u64),
+                cumulative_borrow_rate_wads: Decimal::one(),
+                ..ReserveLiquidity::default()
+            },
+            collateral: ReserveCollateral {
+                mint_total_supply: 2_000,
+                ..ReserveCollateral::default()
+            },
+            config: ReserveConfig {
+                fees: ReserveFees {
+                    protocol_take_rate: 20,
+                    ..ReserveFees::default()
+                },
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+
+        // a year of 10% interest, 20% of which goes to the protocol
+        reserve
+            .liquidity
+            .compound_interest(
+                Rate::from_percent(10),
+                SLOTS_PER_YEAR,
+                Rate::from_percent(reserve.config.fees.protocol_take_rate),
+            )
+            .unwrap();
+        let interest = reserve
+            .liquidity
+            .borrowed_amount_wads
+            .try_sub(Decimal::from(1_000u64))
+            .unwrap();
+        let protocol_fees = interest.try_mul(Rate::from_percent(20)).unwrap();
+        assert_eq!(
+            reserve.liquidity.accumulated_protocol_fees_wads,
+            protocol_fees
+        );
+        assert_eq!(
+            reserve.liquidity.total_supply().unwrap(),
+            Decimal::from(2_000u64)
+                .try_add(interest)
+                .unwrap()
+                .try_sub(protocol_fees)
+                .unwrap()
+        );
+
+        // withdrawals are limited to whole accumulated fees
+        let accumulated_fees = protocol_fees.try_floor_u64().unwrap();
+        assert_eq!(
+            reserve.liquidity.withdraw_protocol_fees(u64::MAX).unwrap(),
+            accumulated_fees
+        );
+        assert_eq!(reserve.liquidity.available_amount, 1_000 - accumulated_fees);
+        assert!(reserve.liquidity.accumulated_protocol_fees_wads < Decimal::one());
+        assert_eq!(
+            reserve.liquidity.withdraw_protocol_fees(u64::MAX),
+            Err(LendingError::InsufficientLiquidity.into())
+        );
+    }
+
+    #[test]
+    fn dynamic_liquidation_bonus() {
+        let mut reserve = Reserve {
+            config: ReserveConfig {
+                loan_to_value_ratio: 50,
+                liquidation_threshold: 55,
+                liquidation_bonus: 5,
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        let obligation = Obligation {
+            borrowed_value: Decimal::from(100u64),
+            unhealthy_borrow_value: Decimal::from(90u64),
+            ..Obligation::default()
+        };
+
+        // static bonus without a max liquidation bonus
+        assert_eq!(
+            reserve.liquidation_bonus(&obligation).unwrap(),
+            Rate::from_percent(5)
+        );
+
+        // 10% of the borrowed value is above the unhealthy borrow value
+        reserve.config.max_liquidation_bonus = 20;
+        assert_eq!(reserve.config.validate(), Ok(()));
+        assert_eq!(
+            reserve.liquidation_bonus(&obligation).unwrap(),
+            Rate::from_percent(15)
+        );
+
+        // capped by the max liquidation bonus
+        let insolvent_obligation = Obligation {
+            unhealthy_borrow_value: Decimal::from(50u64),
+            ..obligation.clone()
+        };
+        assert_eq!(
+            reserve.liquidation_bonus(&insolvent_obligation).unwrap(),
+            Rate::from_percent(20)
+        );
+
+        // max bonus must be at least the liquidation bonus
+        reserve.config.max_liquidation_bonus = 4;
+        assert_eq!(
+            reserve.config.validate(),
+            Err(LendingError::InvalidConfig.into())
+        );
+    }
+
+    #[test]
+    fn write_off_bad_debt() {
+        let mut liquidity = ReserveLiquidity {
+            available_amount: 20,
+            borrowed_amount_wads: Decimal::from(1_000u64),
+            accumulated_protocol_fees_wads: Decimal::from(50u64),
+            ..ReserveLiquidity::default()
+        };
+        liquidity.write_off(Decimal::from(400u64)).unwrap();
+        assert_eq!(liquidity.borrowed_amount_wads, Decimal::from(600u64));
+        assert_eq!(liquidity.available_amount, 20);
+        assert_eq!(liquidity.total_supply().unwrap(), Decimal::from(570u64));
+
+        // protocol fees cannot exceed the remaining supply
+        liquidity.write_off(Decimal::from(590u64)).unwrap();
+        assert_eq!(
+            liquidity.accumulated_protocol_fees_wads,
+            Decimal::from(30u64)
+        );
+        assert_eq!(liquidity.total_supply().unwrap(), Decimal::zero());
+    }
+
+    #[test]
+    fn tighten_config() {
+        let current = ReserveConfig {
+            loan_to_value_ratio: 50,
+            liquidation_threshold: 55,
+            caps: ReserveCaps {
+                deposit_limit: 1_000,
+                ..ReserveCaps::default()
+            },
+            ..ReserveConfig::default()
+        };
+
+        // lower loan to value ratios, tighter caps, disabled borrowing and a
+        // pause only tighten risk
+        let tightened = ReserveConfig {
+            loan_to_value_ratio: 40,
+            caps: ReserveCaps {
+                deposit_limit: 500,
+                borrow_limit: 100,
+                ..ReserveCaps::default()
+            },
+            borrowing_disabled: true,
+            paused: true,
+            ..current
+        };
+        assert_eq!(tightened.validate_tightening(&current), Ok(()));
+        assert_eq!(current.validate_tightening(&current), Ok(()));
+
+        // loosening any of them must wait for the config timelock
+        for loosened in [
+            ReserveConfig {
+                loan_to_value_ratio: 51,
+                ..current
+            },
+            ReserveConfig {
+                caps: ReserveCaps::default(),
+                ..current
+            },
+            ReserveConfig {
+                caps: ReserveCaps {
+                    deposit_limit: 1_001,
+                    ..ReserveCaps::default()
+                },
+                ..current
+            },
+        ] {
+            assert_eq!(
+                loosened.validate_tightening(&current),
+                Err(LendingError::ConfigNotTightened.into())
+            );
+        }
+        assert_eq!(
+            current.validate_tightening(&tightened),
+            Err(LendingError::ConfigNotTightened.into())
+        );
+
+        // other parameters cannot change without the config timelock, a lower
+        // liquidation threshold would liquidate borrowers without notice
+        let lowered_threshold = ReserveConfig {
+            liquidation_threshold: 52,
+            ..current
+        };
+        assert_eq!(
+            lowered_threshold.validate_tightening(&current),
+            Err(LendingError::ConfigNotTightened.into())
+        );
+    }
 }
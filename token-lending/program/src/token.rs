@@ -0,0 +1,115 @@
+//! Token program helpers for reserve liquidity held in SPL Token or
+//! Token-2022 mints
+
+use {
+    crate::error::LendingError,
+    solana_program::{
+        account_info::AccountInfo, clock::Epoch, msg, program_error::ProgramError, pubkey::Pubkey,
+    },
+    spl_token_2022::{
+        extension::{
+            default_account_state::DefaultAccountState, transfer_fee::TransferFeeConfig,
+            transfer_hook, BaseStateWithExtensions, ExtensionType, StateWithExtensions,
+        },
+        state::{Account, AccountState, Mint},
+    },
+};
+
+/// Check a token program can own reserve liquidity mints
+pub fn is_supported_token_program(program_id: &Pubkey) -> bool {
+    program_id == &spl_token::id() || program_id == &spl_token_2022::id()
+}
+
+/// Unpack a reserve liquidity mint, rejecting Token-2022 extensions which
+/// could move tokens out of the reserve supply without the lending program or
+/// which need accounts the lending instructions cannot provide
+pub fn unpack_liquidity_mint(mint_info: &AccountInfo) -> Result<Mint, ProgramError> {
+    if !is_supported_token_program(mint_info.owner) {
+        msg!("Liquidity mint provided is not owned by a supported token program");
+        return Err(LendingError::InvalidTokenOwner.into());
+    }
+
+    let mint_data = mint_info.try_borrow_data()?;
+    let mint = StateWithExtensions::<Mint>::unpack(&mint_data)
+        .map_err(|_| LendingError::InvalidTokenMint)?;
+    for extension_type in mint.get_extension_types()? {
+        match extension_type {
+            ExtensionType::NonTransferable => {
+                msg!("Liquidity mint cannot be non-transferable");
+                return Err(LendingError::UnsupportedTokenExtension.into());
+            }
+            ExtensionType::PermanentDelegate => {
+                msg!("Liquidity mint cannot have a permanent delegate");
+                return Err(LendingError::UnsupportedTokenExtension.into());
+            }
+            ExtensionType::TransferHook if transfer_hook::get_program_id(&mint).is_some() => {
+                msg!("Liquidity mint cannot have a transfer hook, which needs extra accounts on every transfer");
+                return Err(LendingError::UnsupportedTokenExtension.into());
+            }
+            ExtensionType::DefaultAccountState => {
+                let default_account_state = mint.get_extension::<DefaultAccountState>()?;
+                if default_account_state.state == AccountState::Frozen as u8 {
+                    msg!("Liquidity mint cannot freeze new token accounts by default");
+                    return Err(LendingError::UnsupportedTokenExtension.into());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(mint.base)
+}
+
+/// Amount withheld by the mint transfer fee when transferring `amount` in the
+/// given epoch, zero for mints without transfer fees. Transfers without the
+/// mint cannot charge a fee, since Token-2022 requires the mint for them.
+pub fn transfer_fee(
+    mint_info: Option<&AccountInfo>,
+    epoch: Epoch,
+    amount: u64,
+) -> Result<u64, ProgramError> {
+    let Some(mint_info) = mint_info else {
+        return Ok(0);
+    };
+    let mint_data = mint_info.try_borrow_data()?;
+    let mint = StateWithExtensions::<Mint>::unpack(&mint_data)?;
+    match mint.get_extension::<TransferFeeConfig>() {
+        Ok(transfer_fee_config) => transfer_fee_config
+            .calculate_epoch_fee(epoch, amount)
+            .ok_or_else(|| LendingError::MathOverflow.into()),
+        Err(_) => Ok(0),
+    }
+}
+
+/// Amount to transfer in the given epoch so that `amount` is received after
+/// the mint transfer fee
+pub fn transfer_amount_with_fee(
+    mint_info: Option<&AccountInfo>,
+    epoch: Epoch,
+    amount: u64,
+) -> Result<u64, ProgramError> {
+    let Some(mint_info) = mint_info else {
+        return Ok(amount);
+    };
+    let mint_data = mint_info.try_borrow_data()?;
+    let mint = StateWithExtensions::<Mint>::unpack(&mint_data)?;
+    let fee = match mint.get_extension::<TransferFeeConfig>() {
+        Ok(transfer_fee_config) => transfer_fee_config
+            .calculate_inverse_epoch_fee(epoch, amount)
+            .ok_or(LendingError::MathOverflow)?,
+        Err(_) => 0,
+    };
+    amount
+        .checked_add(fee)
+        .ok_or_else(|| LendingError::MathOverflow.into())
+}
+
+/// Unpack the amount held by an SPL Token or Token-2022 account
+pub fn unpack_token_amount(token_account_info: &AccountInfo) -> Result<u64, ProgramError> {
+    Ok(
+        StateWithExtensions::<Account>::unpack(&token_account_info.try_borrow_data()?)
+            .map_err(|_| LendingError::InvalidTokenAccount)?
+            .base
+            .amount,
+    )
+}
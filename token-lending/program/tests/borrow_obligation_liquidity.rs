@@ -16,7 +16,7 @@ use {
         instruction::{borrow_obligation_liquidity, refresh_obligation},
         math::Decimal,
         processor::process_instruction,
-        state::{FeeCalculation, INITIAL_COLLATERAL_RATIO},
+        state::{ElevationGroup, FeeCalculation, ReserveCaps, INITIAL_COLLATERAL_RATIO},
     },
 };
 
@@ -112,6 +112,8 @@ async fn test_borrow_usdc_fixed_amount() {
                 test_obligation.pubkey,
                 lending_market.pubkey,
                 test_obligation.owner,
+                usdc_test_reserve.liquidity_mint_pubkey,
+                spl_token::id(),
                 Some(usdc_test_reserve.liquidity_host_pubkey),
             ),
         ],
@@ -260,6 +262,8 @@ async fn test_borrow_sol_max_amount() {
                 test_obligation.pubkey,
                 lending_market.pubkey,
                 test_obligation.owner,
+                sol_test_reserve.liquidity_mint_pubkey,
+                spl_token::id(),
                 Some(sol_test_reserve.liquidity_host_pubkey),
             ),
         ],
@@ -392,6 +396,8 @@ async fn test_borrow_too_large() {
                 test_obligation.pubkey,
                 lending_market.pubkey,
                 test_obligation.owner,
+                usdc_test_reserve.liquidity_mint_pubkey,
+                spl_token::id(),
                 Some(usdc_test_reserve.liquidity_host_pubkey),
             ),
         ],
@@ -507,6 +513,8 @@ async fn test_borrow_max_receive_minimum() {
                 test_obligation.pubkey,
                 lending_market.pubkey,
                 test_obligation.owner,
+                sol_test_reserve.liquidity_mint_pubkey,
+                spl_token::id(),
                 Some(sol_test_reserve.liquidity_host_pubkey),
             ),
         ],
@@ -646,6 +654,8 @@ async fn test_borrow_max_receive_less_than_slippage() {
                 test_obligation.pubkey,
                 lending_market.pubkey,
                 test_obligation.owner,
+                sol_test_reserve.liquidity_mint_pubkey,
+                spl_token::id(),
                 Some(sol_test_reserve.liquidity_host_pubkey),
             ),
         ],
@@ -752,6 +762,8 @@ async fn test_borrow_less_than_max_with_slippage() {
                 test_obligation.pubkey,
                 lending_market.pubkey,
                 test_obligation.owner,
+                usdc_test_reserve.liquidity_mint_pubkey,
+                spl_token::id(),
                 Some(usdc_test_reserve.liquidity_host_pubkey),
             ),
         ],
@@ -763,3 +775,511 @@ async fn test_borrow_less_than_max_with_slippage() {
     // check that transaction succeeds
     banks_client.process_transaction(transaction).await.unwrap();
 }
+
+#[tokio::test]
+async fn test_borrow_limit() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const USDC_BORROW_LIMIT_FRACTIONAL: u64 = 500 * FRACTIONAL_TO_USDC;
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+    const SOL_RESERVE_COLLATERAL_LAMPORTS: u64 = 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS;
+    const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 2_000 * FRACTIONAL_TO_USDC;
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.loan_to_value_ratio = 50;
+
+    let sol_oracle = add_sol_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    reserve_config.caps = ReserveCaps {
+        borrow_limit: USDC_BORROW_LIMIT_FRACTIONAL,
+        ..ReserveCaps::default()
+    };
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let borrow = |liquidity_amount: u64| {
+        let mut transaction = Transaction::new_with_payer(
+            &[
+                refresh_obligation(
+                    spl_token_lending::id(),
+                    test_obligation.pubkey,
+                    vec![sol_test_reserve.pubkey],
+                ),
+                borrow_obligation_liquidity(
+                    spl_token_lending::id(),
+                    liquidity_amount,
+                    None,
+                    usdc_test_reserve.liquidity_supply_pubkey,
+                    usdc_test_reserve.user_liquidity_pubkey,
+                    usdc_test_reserve.pubkey,
+                    usdc_test_reserve.liquidity_fee_receiver_pubkey,
+                    test_obligation.pubkey,
+                    lending_market.pubkey,
+                    test_obligation.owner,
+                    usdc_test_reserve.liquidity_mint_pubkey,
+                    spl_token::id(),
+                    Some(usdc_test_reserve.liquidity_host_pubkey),
+                ),
+            ],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+        transaction
+    };
+
+    assert_eq!(
+        banks_client
+            .process_transaction(borrow(USDC_BORROW_LIMIT_FRACTIONAL))
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::BorrowLimitExceeded as u32)
+        )
+    );
+
+    // max borrows stop at the borrow limit, including fees
+    assert!(banks_client
+        .process_transaction(borrow(u64::MAX))
+        .await
+        .is_ok());
+    let usdc_reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+    assert_eq!(
+        usdc_reserve.liquidity.borrowed_amount_wads,
+        Decimal::from(USDC_BORROW_LIMIT_FRACTIONAL)
+    );
+}
+
+#[tokio::test]
+async fn test_isolated_collateral_borrow() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 100 * FRACTIONAL_TO_USDC;
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+    const SOL_RESERVE_COLLATERAL_LAMPORTS: u64 = 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS;
+    const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 2_000 * FRACTIONAL_TO_USDC;
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.loan_to_value_ratio = 50;
+
+    let mut isolated_config = reserve_config;
+    isolated_config.isolated_collateral = true;
+
+    let sol_oracle = add_sol_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: isolated_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            borrow_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+    let other_usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            borrows: &[(&usdc_test_reserve, USDC_BORROW_AMOUNT_FRACTIONAL)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let borrow = |borrow_reserve: &TestReserve| {
+        let mut transaction = Transaction::new_with_payer(
+            &[
+                refresh_obligation(
+                    spl_token_lending::id(),
+                    test_obligation.pubkey,
+                    vec![sol_test_reserve.pubkey, usdc_test_reserve.pubkey],
+                ),
+                borrow_obligation_liquidity(
+                    spl_token_lending::id(),
+                    USDC_BORROW_AMOUNT_FRACTIONAL,
+                    None,
+                    borrow_reserve.liquidity_supply_pubkey,
+                    borrow_reserve.user_liquidity_pubkey,
+                    borrow_reserve.pubkey,
+                    borrow_reserve.liquidity_fee_receiver_pubkey,
+                    test_obligation.pubkey,
+                    lending_market.pubkey,
+                    test_obligation.owner,
+                    borrow_reserve.liquidity_mint_pubkey,
+                    spl_token::id(),
+                    Some(borrow_reserve.liquidity_host_pubkey),
+                ),
+            ],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+        transaction
+    };
+
+    assert_eq!(
+        banks_client
+            .process_transaction(borrow(&other_usdc_test_reserve))
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::IsolatedCollateralBorrow as u32)
+        )
+    );
+
+    // the asset already borrowed can still be borrowed more of
+    assert!(banks_client
+        .process_transaction(borrow(&usdc_test_reserve))
+        .await
+        .is_ok());
+}
+
+#[tokio::test]
+async fn test_elevation_group_mismatch() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 100 * FRACTIONAL_TO_USDC;
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+    const SOL_RESERVE_COLLATERAL_LAMPORTS: u64 = 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS;
+    const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 2_000 * FRACTIONAL_TO_USDC;
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.loan_to_value_ratio = 50;
+
+    let mut elevated_config = reserve_config;
+    elevated_config.elevation_group = ElevationGroup {
+        id: 1,
+        loan_to_value_ratio: 80,
+        liquidation_threshold: 85,
+    };
+
+    let sol_oracle = add_sol_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: elevated_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            borrow_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: elevated_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    // same asset, outside the elevation group
+    let other_usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            borrows: &[(&usdc_test_reserve, USDC_BORROW_AMOUNT_FRACTIONAL)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let borrow = |borrow_reserve: &TestReserve| {
+        let mut transaction = Transaction::new_with_payer(
+            &[
+                refresh_obligation(
+                    spl_token_lending::id(),
+                    test_obligation.pubkey,
+                    vec![sol_test_reserve.pubkey, usdc_test_reserve.pubkey],
+                ),
+                borrow_obligation_liquidity(
+                    spl_token_lending::id(),
+                    USDC_BORROW_AMOUNT_FRACTIONAL,
+                    None,
+                    borrow_reserve.liquidity_supply_pubkey,
+                    borrow_reserve.user_liquidity_pubkey,
+                    borrow_reserve.pubkey,
+                    borrow_reserve.liquidity_fee_receiver_pubkey,
+                    test_obligation.pubkey,
+                    lending_market.pubkey,
+                    test_obligation.owner,
+                    borrow_reserve.liquidity_mint_pubkey,
+                    spl_token::id(),
+                    Some(borrow_reserve.liquidity_host_pubkey),
+                ),
+            ],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+        transaction
+    };
+
+    assert_eq!(
+        banks_client
+            .process_transaction(borrow(&other_usdc_test_reserve))
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::ElevationGroupMismatch as u32)
+        )
+    );
+
+    // the asset already borrowed can still be borrowed more of
+    assert!(banks_client
+        .process_transaction(borrow(&usdc_test_reserve))
+        .await
+        .is_ok());
+}
+
+#[tokio::test]
+async fn test_borrow_legacy_account_layout() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const USDC_TOTAL_BORROW_FRACTIONAL: u64 = 1_000 * FRACTIONAL_TO_USDC;
+    const FEE_AMOUNT: u64 = 100;
+    const HOST_FEE_AMOUNT: u64 = 20;
+
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = USDC_TOTAL_BORROW_FRACTIONAL - FEE_AMOUNT;
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.loan_to_value_ratio = 50;
+
+    let sol_oracle = add_sol_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: 2 * USDC_TOTAL_BORROW_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut borrow_instruction = borrow_obligation_liquidity(
+        spl_token_lending::id(),
+        USDC_BORROW_AMOUNT_FRACTIONAL,
+        None,
+        usdc_test_reserve.liquidity_supply_pubkey,
+        usdc_test_reserve.user_liquidity_pubkey,
+        usdc_test_reserve.pubkey,
+        usdc_test_reserve.liquidity_fee_receiver_pubkey,
+        test_obligation.pubkey,
+        lending_market.pubkey,
+        test_obligation.owner,
+        usdc_test_reserve.liquidity_mint_pubkey,
+        spl_token::id(),
+        Some(usdc_test_reserve.liquidity_host_pubkey),
+    );
+    // clients built before Token-2022 support end with the host fee receiver
+    borrow_instruction.accounts.truncate(11);
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_obligation(
+                spl_token_lending::id(),
+                test_obligation.pubkey,
+                vec![sol_test_reserve.pubkey],
+            ),
+            borrow_instruction,
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let borrow_amount =
+        get_token_balance(&mut banks_client, usdc_test_reserve.user_liquidity_pubkey).await;
+    assert_eq!(borrow_amount, USDC_BORROW_AMOUNT_FRACTIONAL);
+
+    let fee_balance = get_token_balance(
+        &mut banks_client,
+        usdc_test_reserve.liquidity_fee_receiver_pubkey,
+    )
+    .await;
+    assert_eq!(fee_balance, FEE_AMOUNT - HOST_FEE_AMOUNT);
+
+    let host_fee_balance =
+        get_token_balance(&mut banks_client, usdc_test_reserve.liquidity_host_pubkey).await;
+    assert_eq!(host_fee_balance, HOST_FEE_AMOUNT);
+}
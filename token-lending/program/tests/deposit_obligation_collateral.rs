@@ -7,13 +7,16 @@ use {
     helpers::*,
     solana_program_test::*,
     solana_sdk::{
+        instruction::InstructionError,
         signature::{Keypair, Signer},
-        transaction::Transaction,
+        transaction::{Transaction, TransactionError},
     },
     spl_token::instruction::approve,
     spl_token_lending::{
-        instruction::deposit_obligation_collateral, processor::process_instruction,
-        state::INITIAL_COLLATERAL_RATIO,
+        error::LendingError,
+        instruction::deposit_obligation_collateral,
+        processor::process_instruction,
+        state::{ReserveCaps, INITIAL_COLLATERAL_RATIO},
     },
 };
 
@@ -115,3 +118,118 @@ async fn test_success() {
         initial_user_collateral_balance - SOL_DEPOSIT_AMOUNT_LAMPORTS
     );
 }
+
+#[tokio::test]
+async fn test_deposit_limit_exceeded() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 10 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+    const SOL_RESERVE_COLLATERAL_LAMPORTS: u64 = 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS;
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    // the deposit limit was lowered below the reserve total supply
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.caps = ReserveCaps {
+        deposit_limit: SOL_RESERVE_COLLATERAL_LAMPORTS / 2,
+        ..ReserveCaps::default()
+    };
+
+    let sol_oracle = add_sol_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            user_liquidity_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let new_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs::default(),
+    );
+    let existing_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS / 2)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let deposit = |obligation: &TestObligation| {
+        let user_transfer_authority = Keypair::new();
+        let mut transaction = Transaction::new_with_payer(
+            &[
+                approve(
+                    &spl_token::id(),
+                    &sol_test_reserve.user_collateral_pubkey,
+                    &user_transfer_authority.pubkey(),
+                    &user_accounts_owner.pubkey(),
+                    &[],
+                    SOL_DEPOSIT_AMOUNT_LAMPORTS / 2,
+                )
+                .unwrap(),
+                deposit_obligation_collateral(
+                    spl_token_lending::id(),
+                    SOL_DEPOSIT_AMOUNT_LAMPORTS / 2,
+                    sol_test_reserve.user_collateral_pubkey,
+                    sol_test_reserve.collateral_supply_pubkey,
+                    sol_test_reserve.pubkey,
+                    obligation.pubkey,
+                    lending_market.pubkey,
+                    obligation.owner,
+                    user_transfer_authority.pubkey(),
+                ),
+            ],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(
+            &vec![&payer, &user_accounts_owner, &user_transfer_authority],
+            recent_blockhash,
+        );
+        transaction
+    };
+
+    // new positions are rejected
+    assert_eq!(
+        banks_client
+            .process_transaction(deposit(&new_obligation))
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::DepositLimitExceeded as u32)
+        )
+    );
+
+    // existing positions can be topped up
+    assert!(banks_client
+        .process_transaction(deposit(&existing_obligation))
+        .await
+        .is_ok());
+    let obligation = existing_obligation.get_state(&mut banks_client).await;
+    assert_eq!(
+        obligation.deposits[0].deposited_amount,
+        SOL_DEPOSIT_AMOUNT_LAMPORTS
+    );
+}
@@ -4,8 +4,18 @@
 mod helpers;
 
 use {
-    helpers::*, solana_program_test::*, solana_sdk::signature::Keypair,
-    spl_token_lending::processor::process_instruction,
+    helpers::*,
+    solana_program_test::*,
+    solana_sdk::{
+        instruction::InstructionError,
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    },
+    spl_token::instruction::approve,
+    spl_token_lending::{
+        error::LendingError, instruction::deposit_reserve_liquidity,
+        processor::process_instruction, state::ReserveCaps,
+    },
 };
 
 #[tokio::test]
@@ -52,3 +62,182 @@ async fn test_success() {
         )
         .await;
 }
+
+#[tokio::test]
+async fn test_deposit_limit() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const DEPOSIT_LIMIT_FRACTIONAL: u64 = 10_050 * FRACTIONAL_TO_USDC;
+
+    let user_accounts_owner = Keypair::new();
+    let user_transfer_authority = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.caps = ReserveCaps {
+        deposit_limit: DEPOSIT_LIMIT_FRACTIONAL,
+        ..ReserveCaps::default()
+    };
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            user_liquidity_amount: 100 * FRACTIONAL_TO_USDC,
+            liquidity_amount: 10_000 * FRACTIONAL_TO_USDC,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            approve(
+                &spl_token::id(),
+                &usdc_test_reserve.user_liquidity_pubkey,
+                &user_transfer_authority.pubkey(),
+                &user_accounts_owner.pubkey(),
+                &[],
+                100 * FRACTIONAL_TO_USDC,
+            )
+            .unwrap(),
+            deposit_reserve_liquidity(
+                spl_token_lending::id(),
+                100 * FRACTIONAL_TO_USDC,
+                usdc_test_reserve.user_liquidity_pubkey,
+                usdc_test_reserve.user_collateral_pubkey,
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.liquidity_supply_pubkey,
+                usdc_test_reserve.collateral_mint_pubkey,
+                lending_market.pubkey,
+                user_transfer_authority.pubkey(),
+                usdc_test_reserve.liquidity_mint_pubkey,
+                spl_token::id(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[&payer, &user_accounts_owner, &user_transfer_authority],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::DepositLimitExceeded as u32)
+        )
+    );
+
+    // deposits up to the limit succeed
+    lending_market
+        .deposit(
+            &mut banks_client,
+            &user_accounts_owner,
+            &payer,
+            &usdc_test_reserve,
+            50 * FRACTIONAL_TO_USDC,
+        )
+        .await;
+    let usdc_reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+    assert_eq!(
+        usdc_reserve.liquidity.available_amount,
+        DEPOSIT_LIMIT_FRACTIONAL
+    );
+}
+
+#[tokio::test]
+async fn test_legacy_account_layout() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    const DEPOSIT_AMOUNT: u64 = 100 * FRACTIONAL_TO_USDC;
+    const RESERVE_AMOUNT: u64 = 10_000 * FRACTIONAL_TO_USDC;
+
+    let user_accounts_owner = Keypair::new();
+    let user_transfer_authority = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            user_liquidity_amount: DEPOSIT_AMOUNT,
+            liquidity_amount: RESERVE_AMOUNT,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: TEST_RESERVE_CONFIG,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut deposit_instruction = deposit_reserve_liquidity(
+        spl_token_lending::id(),
+        DEPOSIT_AMOUNT,
+        usdc_test_reserve.user_liquidity_pubkey,
+        usdc_test_reserve.user_collateral_pubkey,
+        usdc_test_reserve.pubkey,
+        usdc_test_reserve.liquidity_supply_pubkey,
+        usdc_test_reserve.collateral_mint_pubkey,
+        lending_market.pubkey,
+        user_transfer_authority.pubkey(),
+        usdc_test_reserve.liquidity_mint_pubkey,
+        spl_token::id(),
+    );
+    // clients built before Token-2022 support stop at the token program
+    deposit_instruction.accounts.truncate(10);
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            approve(
+                &spl_token::id(),
+                &usdc_test_reserve.user_liquidity_pubkey,
+                &user_transfer_authority.pubkey(),
+                &user_accounts_owner.pubkey(),
+                &[],
+                DEPOSIT_AMOUNT,
+            )
+            .unwrap(),
+            deposit_instruction,
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[&payer, &user_accounts_owner, &user_transfer_authority],
+        recent_blockhash,
+    );
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let usdc_reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+    assert_eq!(
+        usdc_reserve.liquidity.available_amount,
+        RESERVE_AMOUNT + DEPOSIT_AMOUNT
+    );
+}
@@ -0,0 +1,180 @@
+#![allow(clippy::arithmetic_side_effects)]
+#![cfg(feature = "test-sbf")]
+
+mod helpers;
+
+use {
+    helpers::*,
+    solana_program_test::*,
+    solana_sdk::{
+        account::Account,
+        instruction::InstructionError,
+        program_pack::Pack,
+        pubkey::Pubkey,
+        rent::Rent,
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    },
+    spl_token_lending::{
+        error::LendingError,
+        instruction::extend_obligation,
+        processor::process_instruction,
+        state::{
+            InitObligationParams, Obligation, ObligationCollateral, EXTENDED_OBLIGATION_LEN,
+            LEGACY_OBLIGATION_LEN, MAX_EXTENDED_OBLIGATION_RESERVES,
+        },
+    },
+};
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_compute_max_units(12_000);
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut collateral = ObligationCollateral::new(Pubkey::new_unique());
+    collateral.deposited_amount = 100;
+    let obligation = Obligation::new(InitObligationParams {
+        current_slot: 1,
+        lending_market: lending_market.pubkey,
+        owner: user_accounts_owner.pubkey(),
+        deposits: vec![collateral],
+        borrows: vec![],
+    });
+
+    // obligation created before collateral modes
+    let obligation_pubkey = Pubkey::new_unique();
+    let mut obligation_account = Account::new(
+        Rent::default().minimum_balance(LEGACY_OBLIGATION_LEN),
+        LEGACY_OBLIGATION_LEN,
+        &spl_token_lending::id(),
+    );
+    Obligation::pack(obligation.clone(), &mut obligation_account.data).unwrap();
+    test.add_account(obligation_pubkey, obligation_account);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[extend_obligation(
+            spl_token_lending::id(),
+            obligation_pubkey,
+            user_accounts_owner.pubkey(),
+            payer.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let obligation_account = banks_client
+        .get_account(obligation_pubkey)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(obligation_account.data.len(), EXTENDED_OBLIGATION_LEN);
+    assert_eq!(
+        obligation_account.lamports,
+        Rent::default().minimum_balance(EXTENDED_OBLIGATION_LEN)
+    );
+
+    let extended_obligation = Obligation::unpack(&obligation_account.data).unwrap();
+    assert!(extended_obligation.last_update.stale);
+    assert_eq!(extended_obligation.deposits, obligation.deposits);
+    assert_eq!(extended_obligation.owner, obligation.owner);
+    assert_eq!(
+        Obligation::max_reserves(obligation_account.data.len()),
+        MAX_EXTENDED_OBLIGATION_RESERVES
+    );
+}
+
+#[tokio::test]
+async fn test_invalid_owner() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs::default(),
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let invalid_owner = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[extend_obligation(
+            spl_token_lending::id(),
+            test_obligation.pubkey,
+            invalid_owner.pubkey(),
+            payer.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &invalid_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidObligationOwner as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_already_extended() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs::default(),
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let extend = extend_obligation(
+        spl_token_lending::id(),
+        test_obligation.pubkey,
+        user_accounts_owner.pubkey(),
+        payer.pubkey(),
+    );
+    let mut transaction =
+        Transaction::new_with_payer(&[extend.clone(), extend], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::ObligationAlreadyExtended as u32)
+        )
+    );
+}
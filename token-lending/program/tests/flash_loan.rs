@@ -97,6 +97,8 @@ async fn test_success() {
             usdc_test_reserve.liquidity_fee_receiver_pubkey,
             usdc_test_reserve.liquidity_host_pubkey,
             lending_market.pubkey,
+            usdc_test_reserve.liquidity_mint_pubkey,
+            spl_token::id(),
             receiver_program_id,
             vec![AccountMeta::new_readonly(receiver_authority_pubkey, false)],
         )],
@@ -206,6 +208,8 @@ async fn test_failure() {
             usdc_test_reserve.liquidity_fee_receiver_pubkey,
             usdc_test_reserve.liquidity_host_pubkey,
             lending_market.pubkey,
+            usdc_test_reserve.liquidity_mint_pubkey,
+            spl_token::id(),
             flash_loan_receiver_program_id,
             vec![AccountMeta::new_readonly(receiver_authority_pubkey, false)],
         )],
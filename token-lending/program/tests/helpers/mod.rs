@@ -16,6 +16,7 @@ use {
         instruction::approve,
         state::{Account as Token, AccountState, Mint},
     },
+    spl_token_2022::extension::{BaseStateWithExtensions, ExtensionType, StateWithExtensions},
     spl_token_lending::{
         instruction::{
             borrow_obligation_liquidity, deposit_reserve_liquidity, init_lending_market,
@@ -24,12 +25,17 @@ use {
         math::{Decimal, Rate, TryAdd, TryMul},
         pyth,
         state::{
-            InitLendingMarketParams, InitObligationParams, InitReserveParams, LendingMarket,
-            NewReserveCollateralParams, NewReserveLiquidityParams, Obligation,
-            ObligationCollateral, ObligationLiquidity, Reserve, ReserveCollateral, ReserveConfig,
+            ElevationGroup, InitLendingMarketParams, InitObligationParams, InitReserveParams,
+            InterestRateModel, LendingMarket, NewReserveCollateralParams,
+            NewReserveLiquidityParams, Obligation, ObligationCollateral, ObligationLiquidity,
+            OracleConfig, OracleSource, Reserve, ReserveCaps, ReserveCollateral, ReserveConfig,
             ReserveFees, ReserveLiquidity, INITIAL_COLLATERAL_RATIO, PROGRAM_VERSION,
         },
     },
+    spl_token_swap::{
+        curve::{base::SwapCurve, fees::Fees},
+        state::{SwapV1, SwapVersion},
+    },
     std::{convert::TryInto, str::FromStr},
 };
 
@@ -43,16 +49,41 @@ pub const TEST_RESERVE_CONFIG: ReserveConfig = ReserveConfig {
     optimal_utilization_rate: 80,
     loan_to_value_ratio: 50,
     liquidation_bonus: 5,
+    max_liquidation_bonus: 0,
     liquidation_threshold: 55,
     min_borrow_rate: 0,
     optimal_borrow_rate: 4,
     max_borrow_rate: 30,
+    interest_rate_model: InterestRateModel::Kinked,
+    oracle: OracleConfig {
+        source: OracleSource::Pyth,
+        max_staleness_slots: 0,
+        max_confidence_bps: 0,
+        twap_window_slots: 0,
+        fixed_price_wads: 0,
+        secondary: None,
+    },
+    caps: ReserveCaps {
+        deposit_limit: 0,
+        borrow_limit: 0,
+        deposit_value_limit: 0,
+        borrow_value_limit: 0,
+    },
+    isolated_collateral: false,
+    elevation_group: ElevationGroup {
+        id: 0,
+        loan_to_value_ratio: 0,
+        liquidation_threshold: 0,
+    },
+    borrowing_disabled: false,
+    paused: false,
     fees: ReserveFees {
         // 0.00001% (Aave borrow fee)
         borrow_fee_wad: 100_000_000_000,
         // 0.3% (Aave flash loan fee)
         flash_loan_fee_wad: 3_000_000_000_000_000,
         host_fee_percentage: 20,
+        protocol_take_rate: 0,
     },
 };
 
@@ -223,6 +254,7 @@ pub struct AddReserveArgs {
     pub borrow_amount: u64,
     pub initial_borrow_rate: u8,
     pub collateral_amount: u64,
+    pub protocol_fees: u64,
     pub mark_fresh: bool,
     pub slots_elapsed: u64,
 }
@@ -244,6 +276,7 @@ pub fn add_reserve(
         borrow_amount,
         initial_borrow_rate,
         collateral_amount,
+        protocol_fees,
         mark_fresh,
         slots_elapsed,
     } = args;
@@ -345,6 +378,7 @@ pub fn add_reserve(
             fee_receiver: liquidity_fee_receiver_pubkey,
             oracle_pubkey: oracle.price_pubkey,
             market_price: oracle.price,
+            token_program_id: spl_token::id(),
         }),
         collateral: ReserveCollateral::new(NewReserveCollateralParams {
             mint_pubkey: collateral_mint_pubkey,
@@ -359,6 +393,7 @@ pub fn add_reserve(
         .unwrap();
     reserve.liquidity.cumulative_borrow_rate_wads =
         Decimal::one().try_mul(borrow_rate_multiplier).unwrap();
+    reserve.liquidity.accumulated_protocol_fees_wads = protocol_fees.into();
 
     if mark_fresh {
         reserve.last_update.update_slot(current_slot);
@@ -570,6 +605,8 @@ impl TestLendingMarket {
                     reserve.collateral_mint_pubkey,
                     self.pubkey,
                     user_transfer_authority.pubkey(),
+                    reserve.liquidity_mint_pubkey,
+                    spl_token::id(),
                 ),
             ],
             Some(&payer.pubkey()),
@@ -622,6 +659,8 @@ impl TestLendingMarket {
                     obligation.pubkey,
                     self.pubkey,
                     user_transfer_authority.pubkey(),
+                    repay_reserve.liquidity_mint_pubkey,
+                    spl_token::id(),
                 ),
             ],
             Some(&payer.pubkey()),
@@ -660,6 +699,8 @@ impl TestLendingMarket {
                 obligation.pubkey,
                 self.pubkey,
                 obligation.owner,
+                borrow_reserve.liquidity_mint_pubkey,
+                spl_token::id(),
                 Some(borrow_reserve.liquidity_host_pubkey),
             )],
             Some(&payer.pubkey()),
@@ -736,13 +777,23 @@ impl TestReserve {
             .await
             .unwrap()
             .unwrap();
-        let liquidity_mint = Mint::unpack(&liquidity_mint_account.data[..]).unwrap();
+        let liquidity_token_program_id = liquidity_mint_account.owner;
+        let liquidity_mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(
+            &liquidity_mint_account.data,
+        )
+        .unwrap();
+        let liquidity_account_len = ExtensionType::try_calculate_account_len::<
+            spl_token_2022::state::Account,
+        >(&ExtensionType::get_required_init_account_extensions(
+            &liquidity_mint.get_extension_types().unwrap(),
+        ))
+        .unwrap();
 
         let rent = banks_client.get_rent().await.unwrap();
         let mut transaction = Transaction::new_with_payer(
             &[
-                approve(
-                    &spl_token::id(),
+                spl_token_2022::instruction::approve(
+                    &liquidity_token_program_id,
                     &user_liquidity_pubkey,
                     &user_transfer_authority_keypair.pubkey(),
                     &user_accounts_owner.pubkey(),
@@ -767,23 +818,23 @@ impl TestReserve {
                 create_account(
                     &payer.pubkey(),
                     &liquidity_supply_keypair.pubkey(),
-                    rent.minimum_balance(Token::LEN),
-                    Token::LEN as u64,
-                    &spl_token::id(),
+                    rent.minimum_balance(liquidity_account_len),
+                    liquidity_account_len as u64,
+                    &liquidity_token_program_id,
                 ),
                 create_account(
                     &payer.pubkey(),
                     &liquidity_fee_receiver_keypair.pubkey(),
-                    rent.minimum_balance(Token::LEN),
-                    Token::LEN as u64,
-                    &spl_token::id(),
+                    rent.minimum_balance(liquidity_account_len),
+                    liquidity_account_len as u64,
+                    &liquidity_token_program_id,
                 ),
                 create_account(
                     &payer.pubkey(),
                     &liquidity_host_keypair.pubkey(),
-                    rent.minimum_balance(Token::LEN),
-                    Token::LEN as u64,
-                    &spl_token::id(),
+                    rent.minimum_balance(liquidity_account_len),
+                    liquidity_account_len as u64,
+                    &liquidity_token_program_id,
                 ),
                 create_account(
                     &payer.pubkey(),
@@ -816,6 +867,7 @@ impl TestReserve {
                     lending_market.pubkey,
                     lending_market.owner.pubkey(),
                     user_transfer_authority_keypair.pubkey(),
+                    liquidity_token_program_id,
                 ),
             ],
             Some(&payer.pubkey()),
@@ -848,7 +900,7 @@ impl TestReserve {
                 lending_market_pubkey: lending_market.pubkey,
                 config,
                 liquidity_mint_pubkey,
-                liquidity_mint_decimals: liquidity_mint.decimals,
+                liquidity_mint_decimals: liquidity_mint.base.decimals,
                 liquidity_supply_pubkey: liquidity_supply_keypair.pubkey(),
                 liquidity_fee_receiver_pubkey: liquidity_fee_receiver_keypair.pubkey(),
                 liquidity_host_pubkey: liquidity_host_keypair.pubkey(),
@@ -1095,6 +1147,16 @@ pub fn add_oracle(
     product_pubkey: Pubkey,
     price_pubkey: Pubkey,
     price: Decimal,
+) -> TestOracle {
+    add_oracle_with_confidence(test, product_pubkey, price_pubkey, price, Decimal::zero())
+}
+
+pub fn add_oracle_with_confidence(
+    test: &mut ProgramTest,
+    product_pubkey: Pubkey,
+    price_pubkey: Pubkey,
+    price: Decimal,
+    confidence: Decimal,
 ) -> TestOracle {
     let oracle_program_id = read_keypair_file("tests/fixtures/oracle_program_id.json").unwrap();
 
@@ -1126,6 +1188,11 @@ pub fn add_oracle(
         .unwrap()
         .try_into()
         .unwrap();
+    pyth_price.agg.conf = confidence
+        .try_mul(decimals)
+        .unwrap()
+        .try_round_u64()
+        .unwrap();
 
     test.add_account(
         price_pubkey,
@@ -1145,6 +1212,99 @@ pub fn add_oracle(
     }
 }
 
+pub struct TestSwapPoolOracle {
+    pub pool_pubkey: Pubkey,
+    pub token_a_pubkey: Pubkey,
+    pub token_b_pubkey: Pubkey,
+    pub quote_mint_pubkey: Pubkey,
+}
+
+impl TestSwapPoolOracle {
+    /// Accounts following the pool when refreshing a reserve
+    pub fn additional_accounts(&self) -> [Pubkey; 3] {
+        [
+            self.token_a_pubkey,
+            self.token_b_pubkey,
+            self.quote_mint_pubkey,
+        ]
+    }
+
+    /// Swap pool as the reserve liquidity oracle, with the price it starts at
+    pub fn oracle(&self, price: Decimal) -> TestOracle {
+        TestOracle {
+            product_pubkey: self.pool_pubkey,
+            price_pubkey: self.pool_pubkey,
+            price,
+        }
+    }
+}
+
+/// Add a constant product swap pool with the reserve liquidity as token A
+pub fn add_swap_pool_oracle(
+    test: &mut ProgramTest,
+    liquidity_mint_pubkey: Pubkey,
+    liquidity_amount: u64,
+    quote_mint_pubkey: Pubkey,
+    quote_amount: u64,
+) -> TestSwapPoolOracle {
+    let pool_pubkey = Pubkey::new_unique();
+    let token_a_pubkey = Pubkey::new_unique();
+    let token_b_pubkey = Pubkey::new_unique();
+    for (pubkey, mint, amount) in [
+        (token_a_pubkey, liquidity_mint_pubkey, liquidity_amount),
+        (token_b_pubkey, quote_mint_pubkey, quote_amount),
+    ] {
+        test.add_packable_account(
+            pubkey,
+            u32::MAX as u64,
+            &Token {
+                mint,
+                owner: Pubkey::new_unique(),
+                amount,
+                state: AccountState::Initialized,
+                ..Token::default()
+            },
+            &spl_token::id(),
+        );
+    }
+
+    let mut data = vec![0; SwapVersion::V1_LEN];
+    SwapVersion::pack(
+        SwapVersion::SwapV1(SwapV1 {
+            is_initialized: true,
+            bump_seed: 255,
+            token_program_id: spl_token::id(),
+            token_a: token_a_pubkey,
+            token_b: token_b_pubkey,
+            pool_mint: Pubkey::new_unique(),
+            token_a_mint: liquidity_mint_pubkey,
+            token_b_mint: quote_mint_pubkey,
+            pool_fee_account: Pubkey::new_unique(),
+            fees: Fees::default(),
+            swap_curve: SwapCurve::default(),
+        }),
+        &mut data,
+    )
+    .unwrap();
+    test.add_account(
+        pool_pubkey,
+        Account {
+            lamports: u32::MAX as u64,
+            data,
+            owner: spl_token_swap::id(),
+            executable: false,
+            rent_epoch: 0,
+        },
+    );
+
+    TestSwapPoolOracle {
+        pool_pubkey,
+        token_a_pubkey,
+        token_b_pubkey,
+        quote_mint_pubkey,
+    }
+}
+
 pub async fn create_and_mint_to_token_account(
     banks_client: &mut BanksClient,
     mint_pubkey: Pubkey,
@@ -1251,7 +1411,8 @@ pub async fn mint_to(
 pub async fn get_token_balance(banks_client: &mut BanksClient, pubkey: Pubkey) -> u64 {
     let token: Account = banks_client.get_account(pubkey).await.unwrap().unwrap();
 
-    spl_token::state::Account::unpack(&token.data[..])
+    StateWithExtensions::<spl_token_2022::state::Account>::unpack(&token.data)
         .unwrap()
+        .base
         .amount
 }
@@ -5,20 +5,32 @@ mod helpers;
 
 use {
     helpers::*,
+    solana_program::pubkey::Pubkey,
     solana_program_test::*,
     solana_sdk::{
         instruction::InstructionError,
         signature::{Keypair, Signer},
+        system_instruction::create_account,
         transaction::{Transaction, TransactionError},
     },
+    spl_token_2022::{
+        extension::{transfer_fee::instruction::initialize_transfer_fee_config, ExtensionType},
+        instruction::{
+            initialize_account, initialize_immutable_owner, initialize_mint,
+            initialize_non_transferable_mint, mint_to,
+        },
+        state::{Account, Mint},
+    },
     spl_token_lending::{
         error::LendingError,
-        instruction::init_reserve,
+        instruction::{deposit_reserve_liquidity, init_reserve},
         processor::process_instruction,
         state::{ReserveFees, INITIAL_COLLATERAL_RATIO},
     },
 };
 
+const TRANSFER_FEE_BASIS_POINTS: u16 = 100;
+
 #[tokio::test]
 async fn test_success() {
     let mut test = ProgramTest::new(
@@ -127,6 +139,7 @@ async fn test_already_initialized() {
             lending_market.pubkey,
             lending_market.owner.pubkey(),
             user_transfer_authority.pubkey(),
+            spl_token::id(),
         )],
         Some(&payer.pubkey()),
     );
@@ -180,6 +193,7 @@ async fn test_invalid_fees() {
             borrow_fee_wad: 1_000_000_000_000_000_001,
             flash_loan_fee_wad: 1_000_000_000_000_000_001,
             host_fee_percentage: 0,
+            protocol_take_rate: 0,
         };
 
         assert_eq!(
@@ -211,6 +225,7 @@ async fn test_invalid_fees() {
             borrow_fee_wad: 10_000_000_000_000_000,
             flash_loan_fee_wad: 10_000_000_000_000_000,
             host_fee_percentage: 101,
+            protocol_take_rate: 0,
         };
 
         assert_eq!(
@@ -235,3 +250,269 @@ async fn test_invalid_fees() {
         );
     }
 }
+
+async fn create_token_2022_mint_and_account(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    owner: &Pubkey,
+    mint_extension: ExtensionType,
+    amount: u64,
+) -> (Pubkey, Pubkey) {
+    let mint_keypair = Keypair::new();
+    let account_keypair = Keypair::new();
+    let mint_authority = Keypair::new();
+    let mint_len = ExtensionType::try_calculate_account_len::<Mint>(&[mint_extension]).unwrap();
+    // non-transferable tokens can only be held in accounts with immutable owners
+    let mut account_extensions =
+        ExtensionType::get_required_init_account_extensions(&[mint_extension]);
+    account_extensions.push(ExtensionType::ImmutableOwner);
+    let account_len =
+        ExtensionType::try_calculate_account_len::<Account>(&account_extensions).unwrap();
+    let init_extension = match mint_extension {
+        ExtensionType::TransferFeeConfig => initialize_transfer_fee_config(
+            &spl_token_2022::id(),
+            &mint_keypair.pubkey(),
+            None,
+            None,
+            TRANSFER_FEE_BASIS_POINTS,
+            u64::MAX,
+        )
+        .unwrap(),
+        ExtensionType::NonTransferable => {
+            initialize_non_transferable_mint(&spl_token_2022::id(), &mint_keypair.pubkey()).unwrap()
+        }
+        _ => unreachable!(),
+    };
+
+    let rent = banks_client.get_rent().await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            create_account(
+                &payer.pubkey(),
+                &mint_keypair.pubkey(),
+                rent.minimum_balance(mint_len),
+                mint_len as u64,
+                &spl_token_2022::id(),
+            ),
+            init_extension,
+            initialize_mint(
+                &spl_token_2022::id(),
+                &mint_keypair.pubkey(),
+                &mint_authority.pubkey(),
+                None,
+                6,
+            )
+            .unwrap(),
+            create_account(
+                &payer.pubkey(),
+                &account_keypair.pubkey(),
+                rent.minimum_balance(account_len),
+                account_len as u64,
+                &spl_token_2022::id(),
+            ),
+            initialize_immutable_owner(&spl_token_2022::id(), &account_keypair.pubkey()).unwrap(),
+            initialize_account(
+                &spl_token_2022::id(),
+                &account_keypair.pubkey(),
+                &mint_keypair.pubkey(),
+                owner,
+            )
+            .unwrap(),
+            mint_to(
+                &spl_token_2022::id(),
+                &mint_keypair.pubkey(),
+                &account_keypair.pubkey(),
+                &mint_authority.pubkey(),
+                &[],
+                amount,
+            )
+            .unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    let recent_blockhash = banks_client.get_latest_blockhash().await.unwrap();
+    transaction.sign(
+        &[payer, &mint_keypair, &account_keypair, &mint_authority],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    (mint_keypair.pubkey(), account_keypair.pubkey())
+}
+
+#[tokio::test]
+async fn test_token_2022_transfer_fee() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+    let usdc_oracle = add_usdc_oracle(&mut test);
+
+    let (mut banks_client, payer, _recent_blockhash) = test.start().await;
+
+    const RESERVE_AMOUNT: u64 = 1_000 * FRACTIONAL_TO_USDC;
+    const RESERVE_AMOUNT_RECEIVED: u64 =
+        RESERVE_AMOUNT - RESERVE_AMOUNT * TRANSFER_FEE_BASIS_POINTS as u64 / 10_000;
+
+    let (liquidity_mint_pubkey, user_liquidity_pubkey) = create_token_2022_mint_and_account(
+        &mut banks_client,
+        &payer,
+        &user_accounts_owner.pubkey(),
+        ExtensionType::TransferFeeConfig,
+        RESERVE_AMOUNT,
+    )
+    .await;
+
+    let usdc_reserve = TestReserve::init(
+        "usdc".to_owned(),
+        &mut banks_client,
+        &lending_market,
+        &usdc_oracle,
+        RESERVE_AMOUNT,
+        TEST_RESERVE_CONFIG,
+        liquidity_mint_pubkey,
+        user_liquidity_pubkey,
+        &payer,
+        &user_accounts_owner,
+    )
+    .await
+    .unwrap();
+
+    let reserve = usdc_reserve.get_state(&mut banks_client).await;
+    assert_eq!(reserve.liquidity.token_program_id, spl_token_2022::id());
+    assert_eq!(reserve.liquidity.available_amount, RESERVE_AMOUNT_RECEIVED);
+    assert_eq!(
+        get_token_balance(&mut banks_client, usdc_reserve.liquidity_supply_pubkey).await,
+        RESERVE_AMOUNT_RECEIVED
+    );
+    assert_eq!(
+        get_token_balance(&mut banks_client, usdc_reserve.user_collateral_pubkey).await,
+        RESERVE_AMOUNT_RECEIVED * INITIAL_COLLATERAL_RATIO
+    );
+}
+
+#[tokio::test]
+async fn test_unsupported_mint_extension() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+    let usdc_oracle = add_usdc_oracle(&mut test);
+
+    let (mut banks_client, payer, _recent_blockhash) = test.start().await;
+
+    const RESERVE_AMOUNT: u64 = 42;
+
+    let (liquidity_mint_pubkey, user_liquidity_pubkey) = create_token_2022_mint_and_account(
+        &mut banks_client,
+        &payer,
+        &user_accounts_owner.pubkey(),
+        ExtensionType::NonTransferable,
+        RESERVE_AMOUNT,
+    )
+    .await;
+
+    assert_eq!(
+        TestReserve::init(
+            "usdc".to_owned(),
+            &mut banks_client,
+            &lending_market,
+            &usdc_oracle,
+            RESERVE_AMOUNT,
+            TEST_RESERVE_CONFIG,
+            liquidity_mint_pubkey,
+            user_liquidity_pubkey,
+            &payer,
+            &user_accounts_owner,
+        )
+        .await
+        .unwrap_err(),
+        TransactionError::InstructionError(
+            8,
+            InstructionError::Custom(LendingError::UnsupportedTokenExtension as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_token_2022_requires_liquidity_token_program() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+    let usdc_oracle = add_usdc_oracle(&mut test);
+
+    let (mut banks_client, payer, _recent_blockhash) = test.start().await;
+
+    const RESERVE_AMOUNT: u64 = 1_000 * FRACTIONAL_TO_USDC;
+    const DEPOSIT_AMOUNT: u64 = 100 * FRACTIONAL_TO_USDC;
+
+    let (liquidity_mint_pubkey, user_liquidity_pubkey) = create_token_2022_mint_and_account(
+        &mut banks_client,
+        &payer,
+        &user_accounts_owner.pubkey(),
+        ExtensionType::TransferFeeConfig,
+        RESERVE_AMOUNT + DEPOSIT_AMOUNT,
+    )
+    .await;
+
+    let usdc_reserve = TestReserve::init(
+        "usdc".to_owned(),
+        &mut banks_client,
+        &lending_market,
+        &usdc_oracle,
+        RESERVE_AMOUNT,
+        TEST_RESERVE_CONFIG,
+        liquidity_mint_pubkey,
+        user_liquidity_pubkey,
+        &payer,
+        &user_accounts_owner,
+    )
+    .await
+    .unwrap();
+
+    let mut deposit_instruction = deposit_reserve_liquidity(
+        spl_token_lending::id(),
+        DEPOSIT_AMOUNT,
+        user_liquidity_pubkey,
+        usdc_reserve.user_collateral_pubkey,
+        usdc_reserve.pubkey,
+        usdc_reserve.liquidity_supply_pubkey,
+        usdc_reserve.collateral_mint_pubkey,
+        lending_market.pubkey,
+        user_accounts_owner.pubkey(),
+        liquidity_mint_pubkey,
+        spl_token_2022::id(),
+    );
+    // without the trailing accounts the lending market token program is used,
+    // which does not own the reserve liquidity
+    deposit_instruction.accounts.truncate(10);
+
+    let mut transaction =
+        Transaction::new_with_payer(&[deposit_instruction], Some(&payer.pubkey()));
+    let recent_blockhash = banks_client.get_latest_blockhash().await.unwrap();
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidTokenProgram as u32)
+        )
+    );
+}
@@ -14,11 +14,13 @@ use {
     },
     spl_token_lending::{
         error::LendingError,
-        instruction::modify_reserve_config,
+        instruction::{modify_reserve_config, modify_reserve_config_and_oracle},
+        math::{Rate, WAD},
         processor::process_instruction,
         state::{
-            InitLendingMarketParams, LendingMarket, ReserveConfig, ReserveFees,
-            INITIAL_COLLATERAL_RATIO,
+            AdaptiveRateModel, ElevationGroup, InitLendingMarketParams, InterestRateModel,
+            LendingMarket, OracleConfig, OracleSource, RateCurve, RateCurvePoint, ReserveCaps,
+            ReserveConfig, ReserveFees, SecondaryOracle, INITIAL_COLLATERAL_RATIO,
         },
     },
 };
@@ -65,15 +67,103 @@ async fn modify_reserve_config_success() {
             - OPTIMAL_UTILIZATION_RATE_CHANGE,
         loan_to_value_ratio: 50,
         liquidation_bonus: 5,
+        max_liquidation_bonus: 0,
         liquidation_threshold: 55,
         min_borrow_rate: 0,
         optimal_borrow_rate: 4,
         max_borrow_rate: 30,
+        interest_rate_model: InterestRateModel::Kinked,
+        oracle: OracleConfig::default(),
+        caps: ReserveCaps {
+            deposit_limit: 1_000 * LAMPORTS_TO_SOL,
+            borrow_limit: 500 * LAMPORTS_TO_SOL,
+            deposit_value_limit: 2_000,
+            borrow_value_limit: 0,
+        },
+        isolated_collateral: true,
+        elevation_group: ElevationGroup {
+            id: 1,
+            loan_to_value_ratio: 80,
+            liquidation_threshold: 85,
+        },
+        borrowing_disabled: false,
+        paused: false,
         fees: ReserveFees {
             borrow_fee_wad: 100_000_000_000,
             flash_loan_fee_wad: 3_000_000_000_000_000,
             host_fee_percentage: 20,
+            protocol_take_rate: 10,
+        },
+    };
+
+    let mut transaction = Transaction::new_with_payer(
+        &[modify_reserve_config(
+            spl_token_lending::id(),
+            new_config,
+            sol_test_reserve.pubkey,
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+
+    banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+        .unwrap();
+
+    let reserve_info = sol_test_reserve.get_state(&mut banks_client).await;
+    assert_eq!(reserve_info.config, new_config);
+}
+
+#[tokio::test]
+async fn modify_reserve_config_interest_rate_model() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    test.set_compute_max_units(70_000);
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+    let sol_oracle = add_sol_oracle(&mut test);
+
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 10 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+    const SOL_RESERVE_COLLATERAL_LAMPORTS: u64 = 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS;
+
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            user_liquidity_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            config: TEST_RESERVE_CONFIG,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
         },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let new_config = ReserveConfig {
+        interest_rate_model: InterestRateModel::Adaptive(AdaptiveRateModel {
+            target_utilization_bps: 9_000,
+            initial_rate_at_target_bps: 400,
+            min_rate_at_target_bps: 10,
+            max_rate_at_target_bps: 20_000,
+            adjustment_speed_bps: 500_000,
+            curve_steepness: 4,
+        }),
+        ..TEST_RESERVE_CONFIG
     };
 
     let mut transaction = Transaction::new_with_payer(
@@ -97,6 +187,146 @@ async fn modify_reserve_config_success() {
 
     let reserve_info = sol_test_reserve.get_state(&mut banks_client).await;
     assert_eq!(reserve_info.config, new_config);
+    assert_eq!(
+        reserve_info.liquidity.adaptive_rate_at_target,
+        Rate::from_bps(400)
+    );
+
+    // invalid models are rejected
+    let invalid_config = ReserveConfig {
+        interest_rate_model: InterestRateModel::PiecewiseLinear(
+            RateCurve::new(&[RateCurvePoint {
+                utilization_bps: 0,
+                borrow_rate_bps: 100,
+            }])
+            .unwrap(),
+        ),
+        ..TEST_RESERVE_CONFIG
+    };
+
+    let mut transaction = Transaction::new_with_payer(
+        &[modify_reserve_config(
+            spl_token_lending::id(),
+            invalid_config,
+            sol_test_reserve.pubkey,
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidConfig as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn modify_reserve_config_oracle() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    test.set_compute_max_units(70_000);
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_oracle(&mut test);
+
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: 100 * FRACTIONAL_TO_USDC,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: TEST_RESERVE_CONFIG,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let new_config = ReserveConfig {
+        oracle: OracleConfig {
+            source: OracleSource::FixedPrice,
+            fixed_price_wads: WAD.into(),
+            secondary: Some(SecondaryOracle {
+                source: OracleSource::Pyth,
+                oracle_pubkey: usdc_oracle.price_pubkey,
+                max_divergence_bps: 100,
+            }),
+            ..OracleConfig::default()
+        },
+        ..TEST_RESERVE_CONFIG
+    };
+
+    // changing the oracle source requires the new oracle account
+    let mut transaction = Transaction::new_with_payer(
+        &[modify_reserve_config(
+            spl_token_lending::id(),
+            new_config,
+            usdc_test_reserve.pubkey,
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidAccountInput as u32)
+        )
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[modify_reserve_config_and_oracle(
+            spl_token_lending::id(),
+            new_config,
+            usdc_test_reserve.pubkey,
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+            usdc_mint.pubkey,
+            usdc_mint.pubkey,
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+
+    banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+        .unwrap();
+
+    let reserve_info = usdc_test_reserve.get_state(&mut banks_client).await;
+    assert_eq!(reserve_info.config, new_config);
+    assert_eq!(reserve_info.liquidity.oracle_pubkey, usdc_mint.pubkey);
+    assert!(reserve_info.last_update.stale);
 }
 
 #[tokio::test]
@@ -146,14 +376,23 @@ async fn wrong_signer_of_lending_market_cannot_change_reserve_config() {
             - OPTIMAL_UTILIZATION_RATE_CHANGE,
         loan_to_value_ratio: 50,
         liquidation_bonus: 5,
+        max_liquidation_bonus: 0,
         liquidation_threshold: 55,
         min_borrow_rate: 0,
         optimal_borrow_rate: 4,
         max_borrow_rate: 30,
+        interest_rate_model: InterestRateModel::Kinked,
+        oracle: OracleConfig::default(),
+        caps: ReserveCaps::default(),
+        isolated_collateral: false,
+        elevation_group: ElevationGroup::default(),
+        borrowing_disabled: false,
+        paused: false,
         fees: ReserveFees {
             borrow_fee_wad: 100_000_000_000,
             flash_loan_fee_wad: 3_000_000_000_000_000,
             host_fee_percentage: 20,
+            protocol_take_rate: 0,
         },
     };
 
@@ -260,14 +499,23 @@ async fn owner_of_different_lending_market_cannot_change_reserve_config() {
             - OPTIMAL_UTILIZATION_RATE_CHANGE,
         loan_to_value_ratio: 50,
         liquidation_bonus: 5,
+        max_liquidation_bonus: 0,
         liquidation_threshold: 55,
         min_borrow_rate: 0,
         optimal_borrow_rate: 4,
         max_borrow_rate: 30,
+        interest_rate_model: InterestRateModel::Kinked,
+        oracle: OracleConfig::default(),
+        caps: ReserveCaps::default(),
+        isolated_collateral: false,
+        elevation_group: ElevationGroup::default(),
+        borrowing_disabled: false,
+        paused: false,
         fees: ReserveFees {
             borrow_fee_wad: 100_000_000_000,
             flash_loan_fee_wad: 3_000_000_000_000_000,
             host_fee_percentage: 20,
+            protocol_take_rate: 0,
         },
     };
 
@@ -350,14 +598,23 @@ async fn correct_owner_providing_wrong_lending_market_fails() {
             - OPTIMAL_UTILIZATION_RATE_CHANGE,
         loan_to_value_ratio: 50,
         liquidation_bonus: 5,
+        max_liquidation_bonus: 0,
         liquidation_threshold: 55,
         min_borrow_rate: 0,
         optimal_borrow_rate: 4,
         max_borrow_rate: 30,
+        interest_rate_model: InterestRateModel::Kinked,
+        oracle: OracleConfig::default(),
+        caps: ReserveCaps::default(),
+        isolated_collateral: false,
+        elevation_group: ElevationGroup::default(),
+        borrowing_disabled: false,
+        paused: false,
         fees: ReserveFees {
             borrow_fee_wad: 100_000_000_000,
             flash_loan_fee_wad: 3_000_000_000_000_000,
             host_fee_percentage: 20,
+            protocol_take_rate: 0,
         },
     };
 
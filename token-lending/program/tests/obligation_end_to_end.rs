@@ -176,6 +176,8 @@ async fn test_success() {
                 obligation_pubkey,
                 lending_market.pubkey,
                 user_accounts_owner_pubkey,
+                usdc_test_reserve.liquidity_mint_pubkey,
+                spl_token::id(),
                 Some(usdc_test_reserve.liquidity_host_pubkey),
             ),
             // 8
@@ -210,6 +212,8 @@ async fn test_success() {
                 obligation_pubkey,
                 lending_market.pubkey,
                 user_transfer_authority_pubkey,
+                usdc_test_reserve.liquidity_mint_pubkey,
+                spl_token::id(),
             ),
             // 12
             refresh_obligation(
@@ -0,0 +1,212 @@
+#![allow(clippy::arithmetic_side_effects)]
+#![cfg(feature = "test-sbf")]
+
+mod helpers;
+
+use {
+    helpers::*,
+    solana_program_test::*,
+    solana_sdk::{
+        instruction::InstructionError,
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    },
+    spl_token_lending::{
+        error::LendingError,
+        instruction::refresh_obligation_chunk,
+        math::{Rate, TryMul},
+        processor::process_instruction,
+        state::{ElevationGroup, INITIAL_COLLATERAL_RATIO},
+    },
+};
+
+const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 1_000 * FRACTIONAL_TO_USDC;
+
+struct ChunkTest {
+    test: ProgramTest,
+    sol_test_reserve: TestReserve,
+    usdc_test_reserve: TestReserve,
+    test_obligation: TestObligation,
+}
+
+fn setup() -> ChunkTest {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.loan_to_value_ratio = 50;
+    reserve_config.elevation_group = ElevationGroup {
+        id: 1,
+        loan_to_value_ratio: 80,
+        liquidation_threshold: 85,
+    };
+
+    let sol_oracle = add_sol_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            borrow_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_amount: 2 * USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            borrows: &[(&usdc_test_reserve, USDC_BORROW_AMOUNT_FRACTIONAL)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    ChunkTest {
+        test,
+        sol_test_reserve,
+        usdc_test_reserve,
+        test_obligation,
+    }
+}
+
+#[tokio::test]
+async fn test_success() {
+    let ChunkTest {
+        mut test,
+        sol_test_reserve,
+        usdc_test_reserve,
+        test_obligation,
+    } = setup();
+
+    // limit to track compute unit increase
+    test.set_compute_max_units(45_000);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[refresh_obligation_chunk(
+            spl_token_lending::id(),
+            test_obligation.pubkey,
+            0,
+            vec![sol_test_reserve.pubkey],
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let obligation = test_obligation.get_state(&mut banks_client).await;
+    assert!(obligation.last_update.stale);
+    assert_eq!(obligation.refresh_progress.next_index, 1);
+
+    let mut transaction = Transaction::new_with_payer(
+        &[refresh_obligation_chunk(
+            spl_token_lending::id(),
+            test_obligation.pubkey,
+            1,
+            vec![usdc_test_reserve.pubkey],
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let obligation = test_obligation.get_state(&mut banks_client).await;
+    assert!(!obligation.last_update.stale);
+    assert_eq!(obligation.refresh_progress.next_index, 0);
+    assert_eq!(obligation.elevation_group, 1);
+    assert_eq!(
+        obligation.allowed_borrow_value,
+        obligation
+            .deposited_value
+            .try_mul(Rate::from_percent(80))
+            .unwrap()
+    );
+    assert_eq!(
+        obligation.unhealthy_borrow_value,
+        obligation
+            .deposited_value
+            .try_mul(Rate::from_percent(85))
+            .unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_out_of_order() {
+    let ChunkTest {
+        test,
+        sol_test_reserve,
+        usdc_test_reserve,
+        test_obligation,
+    } = setup();
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_obligation_chunk(
+                spl_token_lending::id(),
+                test_obligation.pubkey,
+                0,
+                vec![sol_test_reserve.pubkey],
+            ),
+            refresh_obligation_chunk(
+                spl_token_lending::id(),
+                test_obligation.pubkey,
+                0,
+                vec![sol_test_reserve.pubkey, usdc_test_reserve.pubkey],
+            ),
+            refresh_obligation_chunk(
+                spl_token_lending::id(),
+                test_obligation.pubkey,
+                2,
+                vec![usdc_test_reserve.pubkey],
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            2,
+            InstructionError::Custom(LendingError::InvalidObligationRefresh as u32)
+        )
+    );
+}
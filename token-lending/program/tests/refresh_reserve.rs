@@ -5,17 +5,21 @@ mod helpers;
 
 use {
     helpers::*,
+    solana_program::pubkey::Pubkey,
     solana_program_test::*,
     solana_sdk::{
+        instruction::InstructionError,
         signature::{Keypair, Signer},
-        transaction::Transaction,
+        transaction::{Transaction, TransactionError},
     },
     spl_token_lending::{
-        instruction::refresh_reserve,
-        math::{Decimal, Rate, TryAdd, TryDiv, TryMul},
+        error::LendingError,
+        instruction::{refresh_reserve, refresh_reserve_with_oracle_accounts},
+        math::{Decimal, Rate, TryAdd, TryDiv, TryMul, WAD},
         processor::process_instruction,
-        state::SLOTS_PER_YEAR,
+        state::{OracleConfig, OracleSource, ReserveConfig, SecondaryOracle, SLOTS_PER_YEAR},
     },
+    std::str::FromStr,
 };
 
 #[tokio::test]
@@ -140,3 +144,400 @@ async fn test_success() {
         usdc_test_reserve.market_price
     );
 }
+
+async fn refresh(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    reserve: &TestReserve,
+    additional_oracle_pubkeys: &[Pubkey],
+) -> Result<(), TransactionError> {
+    let recent_blockhash = banks_client.get_latest_blockhash().await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[refresh_reserve_with_oracle_accounts(
+            spl_token_lending::id(),
+            reserve.pubkey,
+            reserve.liquidity_oracle_pubkey,
+            additional_oracle_pubkeys,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[payer], recent_blockhash);
+    banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+}
+
+fn lending_error(error: LendingError) -> TransactionError {
+    TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+}
+
+#[tokio::test]
+async fn test_swap_pool_twap() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+    test.set_compute_max_units(50_000);
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+    let usdc_mint = add_usdc_mint(&mut test);
+
+    // 100 SOL for 2,000 USDC, or $20 per SOL
+    let swap_pool = add_swap_pool_oracle(
+        &mut test,
+        spl_token::native_mint::id(),
+        100 * LAMPORTS_TO_SOL,
+        usdc_mint.pubkey,
+        2_000 * FRACTIONAL_TO_USDC,
+    );
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &swap_pool.oracle(Decimal::zero()),
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: 100 * LAMPORTS_TO_SOL,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            config: ReserveConfig {
+                oracle: OracleConfig {
+                    source: OracleSource::SwapPoolTwap,
+                    twap_window_slots: 100,
+                    // samples up to a whole window apart keep the TWAP going
+                    max_staleness_slots: 100,
+                    ..OracleConfig::default()
+                },
+                ..TEST_RESERVE_CONFIG
+            },
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let mut test_context = test.start_with_context().await;
+    test_context.warp_to_slot(3).unwrap();
+
+    // pool token accounts must match the pool
+    let [token_a, token_b, quote_mint] = swap_pool.additional_accounts();
+    assert_eq!(
+        refresh(
+            &mut test_context.banks_client,
+            &test_context.payer,
+            &sol_test_reserve,
+            &[token_b, token_a, quote_mint],
+        )
+        .await
+        .unwrap_err(),
+        lending_error(LendingError::InvalidAccountInput)
+    );
+
+    // the first sample starts the TWAP, but the reserve stays stale until the
+    // TWAP has been sampled over a whole window
+    refresh(
+        &mut test_context.banks_client,
+        &test_context.payer,
+        &sol_test_reserve,
+        &swap_pool.additional_accounts(),
+    )
+    .await
+    .unwrap();
+
+    let sol_reserve = sol_test_reserve
+        .get_state(&mut test_context.banks_client)
+        .await;
+    assert_eq!(sol_reserve.liquidity.market_price, Decimal::zero());
+    assert_eq!(sol_reserve.liquidity.twap_price, Decimal::from(20u64));
+    assert_eq!(sol_reserve.liquidity.twap_slot, 3);
+    assert_eq!(sol_reserve.liquidity.twap_start_slot, 3);
+    assert!(sol_reserve.last_update.stale);
+
+    test_context.warp_to_slot(103).unwrap();
+    refresh(
+        &mut test_context.banks_client,
+        &test_context.payer,
+        &sol_test_reserve,
+        &swap_pool.additional_accounts(),
+    )
+    .await
+    .unwrap();
+
+    let sol_reserve = sol_test_reserve
+        .get_state(&mut test_context.banks_client)
+        .await;
+    assert_eq!(sol_reserve.liquidity.market_price, Decimal::from(20u64));
+    assert_eq!(sol_reserve.liquidity.twap_slot, 103);
+    assert_eq!(sol_reserve.liquidity.twap_start_slot, 3);
+    assert!(!sol_reserve.last_update.stale);
+
+    // after a longer gap, the TWAP restarts and has to warm up again
+    test_context.warp_to_slot(204).unwrap();
+    refresh(
+        &mut test_context.banks_client,
+        &test_context.payer,
+        &sol_test_reserve,
+        &swap_pool.additional_accounts(),
+    )
+    .await
+    .unwrap();
+
+    let sol_reserve = sol_test_reserve
+        .get_state(&mut test_context.banks_client)
+        .await;
+    assert_eq!(sol_reserve.liquidity.twap_price, Decimal::from(20u64));
+    assert_eq!(sol_reserve.liquidity.twap_slot, 204);
+    assert_eq!(sol_reserve.liquidity.twap_start_slot, 204);
+    assert!(sol_reserve.last_update.stale);
+}
+
+#[tokio::test]
+async fn test_swap_pool_twap_falls_back_to_secondary_oracle() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+    test.set_compute_max_units(50_000);
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+    let usdc_mint = add_usdc_mint(&mut test);
+    let sol_oracle = add_sol_oracle(&mut test);
+
+    // 100 SOL for 2,000 USDC, or $20 per SOL
+    let swap_pool = add_swap_pool_oracle(
+        &mut test,
+        spl_token::native_mint::id(),
+        100 * LAMPORTS_TO_SOL,
+        usdc_mint.pubkey,
+        2_000 * FRACTIONAL_TO_USDC,
+    );
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &swap_pool.oracle(Decimal::zero()),
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: 100 * LAMPORTS_TO_SOL,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            config: ReserveConfig {
+                oracle: OracleConfig {
+                    source: OracleSource::SwapPoolTwap,
+                    twap_window_slots: 100,
+                    secondary: Some(SecondaryOracle {
+                        source: OracleSource::Pyth,
+                        oracle_pubkey: sol_oracle.price_pubkey,
+                        max_divergence_bps: 100,
+                    }),
+                    ..OracleConfig::default()
+                },
+                ..TEST_RESERVE_CONFIG
+            },
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let mut test_context = test.start_with_context().await;
+    test_context.warp_to_slot(3).unwrap();
+    let ProgramTestContext {
+        mut banks_client,
+        payer,
+        ..
+    } = test_context;
+
+    let [token_a, token_b, quote_mint] = swap_pool.additional_accounts();
+    refresh(
+        &mut banks_client,
+        &payer,
+        &sol_test_reserve,
+        &[token_a, token_b, quote_mint, sol_oracle.price_pubkey],
+    )
+    .await
+    .unwrap();
+
+    // the TWAP has a single sample, so the secondary oracle price is used
+    let sol_reserve = sol_test_reserve.get_state(&mut banks_client).await;
+    assert_eq!(sol_reserve.liquidity.market_price, Decimal::from(20u64));
+    assert_eq!(sol_reserve.liquidity.twap_start_slot, 3);
+    assert!(!sol_reserve.last_update.stale);
+}
+
+#[tokio::test]
+async fn test_fixed_price_with_secondary_oracle() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+    test.set_compute_max_units(50_000);
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_oracle(&mut test);
+
+    let add_pegged_reserve = |test: &mut ProgramTest, fixed_price_wads: u128| {
+        add_reserve(
+            test,
+            &lending_market,
+            &TestOracle {
+                product_pubkey: usdc_mint.pubkey,
+                price_pubkey: usdc_mint.pubkey,
+                price: Decimal::from_scaled_val(fixed_price_wads),
+            },
+            &user_accounts_owner,
+            AddReserveArgs {
+                liquidity_amount: 100 * FRACTIONAL_TO_USDC,
+                liquidity_mint_decimals: usdc_mint.decimals,
+                liquidity_mint_pubkey: usdc_mint.pubkey,
+                config: ReserveConfig {
+                    oracle: OracleConfig {
+                        source: OracleSource::FixedPrice,
+                        fixed_price_wads,
+                        secondary: Some(SecondaryOracle {
+                            source: OracleSource::Pyth,
+                            oracle_pubkey: usdc_oracle.price_pubkey,
+                            max_divergence_bps: 100,
+                        }),
+                        ..OracleConfig::default()
+                    },
+                    ..TEST_RESERVE_CONFIG
+                },
+                ..AddReserveArgs::default()
+            },
+        )
+    };
+    let pegged_test_reserve = add_pegged_reserve(&mut test, WAD.into());
+    let depegged_test_reserve = add_pegged_reserve(&mut test, (WAD + WAD / 2).into());
+
+    let mut test_context = test.start_with_context().await;
+    test_context.warp_to_slot(3).unwrap();
+    let ProgramTestContext {
+        mut banks_client,
+        payer,
+        ..
+    } = test_context;
+
+    // the secondary oracle account is required
+    assert_eq!(
+        refresh(&mut banks_client, &payer, &pegged_test_reserve, &[])
+            .await
+            .unwrap_err(),
+        TransactionError::InstructionError(0, InstructionError::NotEnoughAccountKeys)
+    );
+    assert_eq!(
+        refresh(
+            &mut banks_client,
+            &payer,
+            &pegged_test_reserve,
+            &[Pubkey::from_str(SOL_PYTH_PRICE).unwrap()],
+        )
+        .await
+        .unwrap_err(),
+        lending_error(LendingError::InvalidAccountInput)
+    );
+
+    refresh(
+        &mut banks_client,
+        &payer,
+        &pegged_test_reserve,
+        &[usdc_oracle.price_pubkey],
+    )
+    .await
+    .unwrap();
+    let pegged_reserve = pegged_test_reserve.get_state(&mut banks_client).await;
+    assert_eq!(pegged_reserve.liquidity.market_price, Decimal::one());
+
+    assert_eq!(
+        refresh(
+            &mut banks_client,
+            &payer,
+            &depegged_test_reserve,
+            &[usdc_oracle.price_pubkey],
+        )
+        .await
+        .unwrap_err(),
+        lending_error(LendingError::OraclePricesDiverge)
+    );
+}
+
+#[tokio::test]
+async fn test_oracle_price_checks() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+    test.set_compute_max_units(50_000);
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    // $20 +/- $1, a confidence interval of 5%
+    let sol_oracle = add_oracle_with_confidence(
+        &mut test,
+        Pubkey::from_str(SOL_PYTH_PRODUCT).unwrap(),
+        Pubkey::from_str(SOL_PYTH_PRICE).unwrap(),
+        Decimal::from(20u64),
+        Decimal::one(),
+    );
+    let mut add_sol_reserve = |oracle: OracleConfig| {
+        add_reserve(
+            &mut test,
+            &lending_market,
+            &sol_oracle,
+            &user_accounts_owner,
+            AddReserveArgs {
+                liquidity_amount: 100 * LAMPORTS_TO_SOL,
+                liquidity_mint_decimals: 9,
+                liquidity_mint_pubkey: spl_token::native_mint::id(),
+                config: ReserveConfig {
+                    oracle,
+                    ..TEST_RESERVE_CONFIG
+                },
+                ..AddReserveArgs::default()
+            },
+        )
+    };
+    let uncertain_test_reserve = add_sol_reserve(OracleConfig {
+        max_confidence_bps: 400,
+        ..OracleConfig::default()
+    });
+    let stale_test_reserve = add_sol_reserve(OracleConfig {
+        max_staleness_slots: 2,
+        ..OracleConfig::default()
+    });
+    let sol_test_reserve = add_sol_reserve(OracleConfig {
+        max_staleness_slots: 3,
+        max_confidence_bps: 500,
+        ..OracleConfig::default()
+    });
+
+    let mut test_context = test.start_with_context().await;
+    test_context.warp_to_slot(3).unwrap(); // price published 3 slots ago
+    let ProgramTestContext {
+        mut banks_client,
+        payer,
+        ..
+    } = test_context;
+
+    assert_eq!(
+        refresh(&mut banks_client, &payer, &uncertain_test_reserve, &[])
+            .await
+            .unwrap_err(),
+        lending_error(LendingError::OraclePriceUncertain)
+    );
+    assert_eq!(
+        refresh(&mut banks_client, &payer, &stale_test_reserve, &[])
+            .await
+            .unwrap_err(),
+        lending_error(LendingError::OraclePriceStale)
+    );
+    refresh(&mut banks_client, &payer, &sol_test_reserve, &[])
+        .await
+        .unwrap();
+    let sol_reserve = sol_test_reserve.get_state(&mut banks_client).await;
+    assert_eq!(sol_reserve.liquidity.market_price, Decimal::from(20u64));
+}
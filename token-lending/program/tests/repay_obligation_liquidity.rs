@@ -119,6 +119,8 @@ async fn test_success() {
                 test_obligation.pubkey,
                 lending_market.pubkey,
                 user_transfer_authority.pubkey(),
+                usdc_test_reserve.liquidity_mint_pubkey,
+                spl_token::id(),
             ),
         ],
         Some(&payer.pubkey()),
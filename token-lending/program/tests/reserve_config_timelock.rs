@@ -0,0 +1,607 @@
+#![allow(clippy::arithmetic_side_effects)]
+#![cfg(feature = "test-sbf")]
+
+mod helpers;
+
+use {
+    helpers::*,
+    solana_program::pubkey::Pubkey,
+    solana_program_test::*,
+    solana_sdk::{
+        account::Account,
+        instruction::InstructionError,
+        program_pack::Pack,
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    },
+    spl_token_lending::{
+        error::LendingError,
+        instruction::{
+            apply_lending_market_changes, apply_reserve_config, cancel_reserve_config,
+            modify_reserve_config, queue_reserve_config, set_config_timelock,
+            set_lending_market_guardian, set_lending_market_owner, set_liquidation_close_factor,
+            tighten_reserve_config,
+        },
+        processor::process_instruction,
+        state::{PendingReserveConfig, ReserveCaps, ReserveConfig},
+    },
+};
+
+const CONFIG_TIMELOCK_SLOTS: u64 = 100;
+
+fn add_sol_reserve(test: &mut ProgramTest, lending_market: &TestLendingMarket) -> TestReserve {
+    let user_accounts_owner = Keypair::new();
+    let sol_oracle = add_sol_oracle(test);
+    add_reserve(
+        test,
+        lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: 100 * LAMPORTS_TO_SOL,
+            liquidity_mint_decimals: 9,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            config: TEST_RESERVE_CONFIG,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    )
+}
+
+fn add_pending_reserve_config(test: &mut ProgramTest) -> Pubkey {
+    let pubkey = Pubkey::new_unique();
+    test.add_packable_account(
+        pubkey,
+        u32::MAX as u64,
+        &PendingReserveConfig::default(),
+        &spl_token_lending::id(),
+    );
+    pubkey
+}
+
+async fn get_pending_reserve_config(
+    banks_client: &mut BanksClient,
+    pubkey: Pubkey,
+) -> PendingReserveConfig {
+    let account: Account = banks_client.get_account(pubkey).await.unwrap().unwrap();
+    PendingReserveConfig::unpack_unchecked(&account.data[..]).unwrap()
+}
+
+#[tokio::test]
+async fn test_queue_and_apply() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let lending_market = add_lending_market(&mut test);
+    let sol_test_reserve = add_sol_reserve(&mut test, &lending_market);
+    let pending_reserve_config_pubkey = add_pending_reserve_config(&mut test);
+
+    let mut test_context = test.start_with_context().await;
+    test_context.warp_to_slot(3).unwrap(); // clock.slot = 3
+
+    let mut transaction = Transaction::new_with_payer(
+        &[set_config_timelock(
+            spl_token_lending::id(),
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+            CONFIG_TIMELOCK_SLOTS,
+        )],
+        Some(&test_context.payer.pubkey()),
+    );
+    transaction.sign(
+        &[&test_context.payer, &lending_market.owner],
+        test_context.last_blockhash,
+    );
+    test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+        .unwrap();
+
+    let lending_market_info = lending_market
+        .get_state(&mut test_context.banks_client)
+        .await;
+    assert_eq!(
+        lending_market_info.config_timelock_slots,
+        CONFIG_TIMELOCK_SLOTS
+    );
+
+    let new_config = ReserveConfig {
+        optimal_utilization_rate: 70,
+        loan_to_value_ratio: 60,
+        liquidation_threshold: 65,
+        ..TEST_RESERVE_CONFIG
+    };
+
+    // reserve configs cannot be modified immediately behind a timelock
+    let mut transaction = Transaction::new_with_payer(
+        &[modify_reserve_config(
+            spl_token_lending::id(),
+            new_config,
+            sol_test_reserve.pubkey,
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+        )],
+        Some(&test_context.payer.pubkey()),
+    );
+    transaction.sign(
+        &[&test_context.payer, &lending_market.owner],
+        test_context.last_blockhash,
+    );
+    assert_eq!(
+        test_context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::ConfigTimelocked as u32)
+        )
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[queue_reserve_config(
+            spl_token_lending::id(),
+            new_config,
+            pending_reserve_config_pubkey,
+            sol_test_reserve.pubkey,
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+        )],
+        Some(&test_context.payer.pubkey()),
+    );
+    transaction.sign(
+        &[&test_context.payer, &lending_market.owner],
+        test_context.last_blockhash,
+    );
+    test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+        .unwrap();
+
+    let pending_reserve_config = get_pending_reserve_config(
+        &mut test_context.banks_client,
+        pending_reserve_config_pubkey,
+    )
+    .await;
+    assert_eq!(pending_reserve_config.reserve, sol_test_reserve.pubkey);
+    assert_eq!(pending_reserve_config.config, new_config);
+    assert_eq!(
+        pending_reserve_config.effective_slot,
+        3 + CONFIG_TIMELOCK_SLOTS
+    );
+
+    // pending configs cannot be applied before the timelock passes
+    let mut transaction = Transaction::new_with_payer(
+        &[apply_reserve_config(
+            spl_token_lending::id(),
+            pending_reserve_config_pubkey,
+            sol_test_reserve.pubkey,
+            lending_market.pubkey,
+        )],
+        Some(&test_context.payer.pubkey()),
+    );
+    transaction.sign(&[&test_context.payer], test_context.last_blockhash);
+    assert_eq!(
+        test_context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::PendingConfigNotReady as u32)
+        )
+    );
+
+    test_context
+        .warp_to_slot(3 + CONFIG_TIMELOCK_SLOTS)
+        .unwrap();
+    let recent_blockhash = test_context.get_new_latest_blockhash().await.unwrap();
+
+    let mut transaction = Transaction::new_with_payer(
+        &[apply_reserve_config(
+            spl_token_lending::id(),
+            pending_reserve_config_pubkey,
+            sol_test_reserve.pubkey,
+            lending_market.pubkey,
+        )],
+        Some(&test_context.payer.pubkey()),
+    );
+    transaction.sign(&[&test_context.payer], recent_blockhash);
+    test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+        .unwrap();
+
+    let reserve_info = sol_test_reserve
+        .get_state(&mut test_context.banks_client)
+        .await;
+    assert_eq!(reserve_info.config, new_config);
+
+    let pending_reserve_config = get_pending_reserve_config(
+        &mut test_context.banks_client,
+        pending_reserve_config_pubkey,
+    )
+    .await;
+    assert_eq!(pending_reserve_config, PendingReserveConfig::default());
+}
+
+#[tokio::test]
+async fn test_guardian() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let lending_market = add_lending_market(&mut test);
+    let sol_test_reserve = add_sol_reserve(&mut test, &lending_market);
+    let pending_reserve_config_pubkey = add_pending_reserve_config(&mut test);
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let guardian = Keypair::new();
+    let loosened_config = ReserveConfig {
+        loan_to_value_ratio: 54,
+        ..TEST_RESERVE_CONFIG
+    };
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            set_lending_market_guardian(
+                spl_token_lending::id(),
+                lending_market.pubkey,
+                lending_market.owner.pubkey(),
+                guardian.pubkey(),
+            ),
+            set_config_timelock(
+                spl_token_lending::id(),
+                lending_market.pubkey,
+                lending_market.owner.pubkey(),
+                CONFIG_TIMELOCK_SLOTS,
+            ),
+            queue_reserve_config(
+                spl_token_lending::id(),
+                loosened_config,
+                pending_reserve_config_pubkey,
+                sol_test_reserve.pubkey,
+                lending_market.pubkey,
+                lending_market.owner.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+    banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+        .unwrap();
+
+    // the guardian tightens reserve risk without the timelock
+    let tightened_config = ReserveConfig {
+        loan_to_value_ratio: 40,
+        caps: ReserveCaps {
+            borrow_limit: LAMPORTS_TO_SOL,
+            ..ReserveCaps::default()
+        },
+        borrowing_disabled: true,
+        paused: true,
+        ..TEST_RESERVE_CONFIG
+    };
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            tighten_reserve_config(
+                spl_token_lending::id(),
+                tightened_config,
+                sol_test_reserve.pubkey,
+                lending_market.pubkey,
+                guardian.pubkey(),
+            ),
+            cancel_reserve_config(
+                spl_token_lending::id(),
+                pending_reserve_config_pubkey,
+                lending_market.pubkey,
+                guardian.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &guardian], recent_blockhash);
+    banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+        .unwrap();
+
+    let reserve_info = sol_test_reserve.get_state(&mut banks_client).await;
+    assert_eq!(reserve_info.config, tightened_config);
+    let pending_reserve_config =
+        get_pending_reserve_config(&mut banks_client, pending_reserve_config_pubkey).await;
+    assert_eq!(pending_reserve_config, PendingReserveConfig::default());
+
+    // the guardian cannot loosen reserve risk
+    let mut transaction = Transaction::new_with_payer(
+        &[tighten_reserve_config(
+            spl_token_lending::id(),
+            TEST_RESERVE_CONFIG,
+            sol_test_reserve.pubkey,
+            lending_market.pubkey,
+            guardian.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &guardian], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::ConfigNotTightened as u32)
+        )
+    );
+
+    // only the owner or guardian can tighten reserve risk
+    let invalid_authority = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[tighten_reserve_config(
+            spl_token_lending::id(),
+            tightened_config,
+            sol_test_reserve.pubkey,
+            lending_market.pubkey,
+            invalid_authority.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &invalid_authority], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidMarketOwner as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_set_owner_timelocked() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let lending_market = add_lending_market(&mut test);
+
+    let mut test_context = test.start_with_context().await;
+    test_context.warp_to_slot(3).unwrap(); // clock.slot = 3
+
+    let new_owner = Pubkey::new_unique();
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            set_config_timelock(
+                spl_token_lending::id(),
+                lending_market.pubkey,
+                lending_market.owner.pubkey(),
+                CONFIG_TIMELOCK_SLOTS,
+            ),
+            set_lending_market_owner(
+                spl_token_lending::id(),
+                lending_market.pubkey,
+                lending_market.owner.pubkey(),
+                new_owner,
+            ),
+        ],
+        Some(&test_context.payer.pubkey()),
+    );
+    transaction.sign(
+        &[&test_context.payer, &lending_market.owner],
+        test_context.last_blockhash,
+    );
+    test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+        .unwrap();
+
+    let lending_market_info = lending_market
+        .get_state(&mut test_context.banks_client)
+        .await;
+    assert_eq!(lending_market_info.owner, lending_market.owner.pubkey());
+    assert_eq!(lending_market_info.pending_owner, new_owner);
+
+    test_context
+        .warp_to_slot(3 + CONFIG_TIMELOCK_SLOTS)
+        .unwrap();
+    let recent_blockhash = test_context.get_new_latest_blockhash().await.unwrap();
+
+    let mut transaction = Transaction::new_with_payer(
+        &[apply_lending_market_changes(
+            spl_token_lending::id(),
+            lending_market.pubkey,
+        )],
+        Some(&test_context.payer.pubkey()),
+    );
+    transaction.sign(&[&test_context.payer], recent_blockhash);
+    test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+        .unwrap();
+
+    let lending_market_info = lending_market
+        .get_state(&mut test_context.banks_client)
+        .await;
+    assert_eq!(lending_market_info.owner, new_owner);
+    assert_eq!(lending_market_info.pending_owner, Pubkey::default());
+}
+
+#[tokio::test]
+async fn test_set_liquidation_close_factor_timelocked() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let lending_market = add_lending_market(&mut test);
+
+    let mut test_context = test.start_with_context().await;
+    test_context.warp_to_slot(3).unwrap(); // clock.slot = 3
+
+    let initial_lending_market_info = lending_market
+        .get_state(&mut test_context.banks_client)
+        .await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            set_config_timelock(
+                spl_token_lending::id(),
+                lending_market.pubkey,
+                lending_market.owner.pubkey(),
+                CONFIG_TIMELOCK_SLOTS,
+            ),
+            set_liquidation_close_factor(
+                spl_token_lending::id(),
+                lending_market.pubkey,
+                lending_market.owner.pubkey(),
+                100,
+            ),
+        ],
+        Some(&test_context.payer.pubkey()),
+    );
+    transaction.sign(
+        &[&test_context.payer, &lending_market.owner],
+        test_context.last_blockhash,
+    );
+    test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+        .unwrap();
+
+    let lending_market_info = lending_market
+        .get_state(&mut test_context.banks_client)
+        .await;
+    assert_eq!(
+        lending_market_info.liquidation_close_factor,
+        initial_lending_market_info.liquidation_close_factor
+    );
+    assert_eq!(lending_market_info.pending_liquidation_close_factor, 100);
+
+    test_context
+        .warp_to_slot(3 + CONFIG_TIMELOCK_SLOTS)
+        .unwrap();
+    let recent_blockhash = test_context.get_new_latest_blockhash().await.unwrap();
+
+    let mut transaction = Transaction::new_with_payer(
+        &[apply_lending_market_changes(
+            spl_token_lending::id(),
+            lending_market.pubkey,
+        )],
+        Some(&test_context.payer.pubkey()),
+    );
+    transaction.sign(&[&test_context.payer], recent_blockhash);
+    test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+        .unwrap();
+
+    let lending_market_info = lending_market
+        .get_state(&mut test_context.banks_client)
+        .await;
+    assert_eq!(lending_market_info.liquidation_close_factor, 100);
+    assert_eq!(lending_market_info.pending_liquidation_close_factor_slot, 0);
+}
+
+#[tokio::test]
+async fn test_init_reserve_timelocked() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+    let sol_oracle = add_sol_oracle(&mut test);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[set_config_timelock(
+            spl_token_lending::id(),
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+            CONFIG_TIMELOCK_SLOTS,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+    banks_client
+        .process_transaction(transaction)
+        .await
+        .map_err(|e| e.unwrap())
+        .unwrap();
+
+    const RESERVE_AMOUNT: u64 = 42;
+
+    let sol_user_liquidity_account = create_and_mint_to_token_account(
+        &mut banks_client,
+        spl_token::native_mint::id(),
+        None,
+        &payer,
+        user_accounts_owner.pubkey(),
+        RESERVE_AMOUNT,
+    )
+    .await;
+
+    let sol_reserve = TestReserve::init(
+        "sol".to_owned(),
+        &mut banks_client,
+        &lending_market,
+        &sol_oracle,
+        RESERVE_AMOUNT,
+        TEST_RESERVE_CONFIG,
+        spl_token::native_mint::id(),
+        sol_user_liquidity_account,
+        &payer,
+        &user_accounts_owner,
+    )
+    .await
+    .unwrap();
+
+    // the reserve cannot back or serve borrows until a queued config
+    // loosens it
+    let reserve_info = sol_reserve.get_state(&mut banks_client).await;
+    assert_eq!(
+        reserve_info.config,
+        ReserveConfig {
+            loan_to_value_ratio: 0,
+            borrowing_disabled: true,
+            ..TEST_RESERVE_CONFIG
+        }
+    );
+}
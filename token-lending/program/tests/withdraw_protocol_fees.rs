@@ -0,0 +1,113 @@
+#![allow(clippy::arithmetic_side_effects)]
+#![cfg(feature = "test-sbf")]
+
+mod helpers;
+
+use {
+    helpers::*,
+    solana_program_test::*,
+    solana_sdk::{
+        instruction::InstructionError,
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    },
+    spl_token_lending::{
+        error::LendingError, instruction::withdraw_protocol_fees, math::Decimal,
+        processor::process_instruction,
+    },
+};
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = ProgramTest::new(
+        "spl_token_lending",
+        spl_token_lending::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_compute_max_units(30_000);
+
+    const PROTOCOL_FEES_FRACTIONAL: u64 = 10 * FRACTIONAL_TO_USDC;
+    const WITHDRAW_AMOUNT_FRACTIONAL: u64 = 4 * FRACTIONAL_TO_USDC;
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = TEST_RESERVE_CONFIG;
+    reserve_config.fees.protocol_take_rate = 10;
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: 1_000 * FRACTIONAL_TO_USDC,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            protocol_fees: PROTOCOL_FEES_FRACTIONAL,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let withdraw = |liquidity_amount: u64, owner: &Keypair| {
+        let mut transaction = Transaction::new_with_payer(
+            &[withdraw_protocol_fees(
+                spl_token_lending::id(),
+                liquidity_amount,
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.liquidity_supply_pubkey,
+                usdc_test_reserve.liquidity_fee_receiver_pubkey,
+                lending_market.pubkey,
+                owner.pubkey(),
+                usdc_test_reserve.liquidity_mint_pubkey,
+                spl_token::id(),
+            )],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[&payer, owner], recent_blockhash);
+        transaction
+    };
+
+    assert_eq!(
+        banks_client
+            .process_transaction(withdraw(u64::MAX, &user_accounts_owner))
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidMarketOwner as u32)
+        )
+    );
+
+    assert!(banks_client
+        .process_transaction(withdraw(WITHDRAW_AMOUNT_FRACTIONAL, &lending_market.owner))
+        .await
+        .is_ok());
+
+    let usdc_reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+    assert_eq!(
+        usdc_reserve.liquidity.accumulated_protocol_fees_wads,
+        Decimal::from(PROTOCOL_FEES_FRACTIONAL - WITHDRAW_AMOUNT_FRACTIONAL)
+    );
+    assert_eq!(
+        usdc_reserve.liquidity.available_amount,
+        1_000 * FRACTIONAL_TO_USDC - WITHDRAW_AMOUNT_FRACTIONAL
+    );
+    assert_eq!(
+        get_token_balance(
+            &mut banks_client,
+            usdc_test_reserve.liquidity_fee_receiver_pubkey
+        )
+        .await,
+        WITHDRAW_AMOUNT_FRACTIONAL
+    );
+}
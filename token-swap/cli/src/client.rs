@@ -0,0 +1,172 @@
+use {
+    solana_client::rpc_client::RpcClient,
+    solana_program::{clock::Epoch, program_pack::Pack, pubkey::Pubkey},
+    spl_token_2022::{
+        extension::{
+            transfer_fee::{TransferFee, TransferFeeConfig},
+            BaseStateWithExtensions, ExtensionType, StateWithExtensionsOwned,
+        },
+        state::{Account, Mint},
+    },
+    spl_token_swap::{
+        processor::Processor,
+        state::{ConcentratedPool, Position, SwapState, SwapVersion, CONCENTRATED_POOL_TAG},
+    },
+    std::sync::Arc,
+};
+
+pub(crate) type Error = Box<dyn std::error::Error>;
+
+/// Token account along with its address
+pub(crate) struct TokenAccount {
+    pub address: Pubkey,
+    pub account: Account,
+}
+
+/// Mint along with the program owning it and its current transfer fee
+pub(crate) struct TokenMint {
+    pub address: Pubkey,
+    pub program_id: Pubkey,
+    pub mint: Mint,
+    pub transfer_fee: Option<TransferFee>,
+    pub extension_types: Vec<ExtensionType>,
+}
+
+impl TokenMint {
+    /// Length of a token account for this mint, including any required
+    /// extensions
+    pub fn account_len(&self) -> Result<usize, Error> {
+        let extension_types =
+            ExtensionType::get_required_init_account_extensions(&self.extension_types);
+        Ok(ExtensionType::try_calculate_account_len::<Account>(
+            &extension_types,
+        )?)
+    }
+}
+
+/// Swap state and all the token accounts and mints it refers to
+pub(crate) struct Pool {
+    pub address: Pubkey,
+    pub authority: Pubkey,
+    pub swap: Arc<dyn SwapState>,
+    pub token_a: TokenAccount,
+    pub token_b: TokenAccount,
+    pub token_a_mint: TokenMint,
+    pub token_b_mint: TokenMint,
+    pub pool_mint: TokenMint,
+    pub pool_fee_account: Option<TokenAccount>,
+}
+
+/// Concentrated liquidity pool state and the token accounts and mints it
+/// refers to
+pub(crate) struct ConcentratedPoolAccounts {
+    pub address: Pubkey,
+    pub authority: Pubkey,
+    pub pool: ConcentratedPool,
+    pub token_a: TokenAccount,
+    pub token_b: TokenAccount,
+    pub token_a_mint: TokenMint,
+    pub token_b_mint: TokenMint,
+}
+
+pub(crate) fn get_token_account(
+    rpc_client: &RpcClient,
+    token_account_address: &Pubkey,
+) -> Result<TokenAccount, Error> {
+    let account = rpc_client.get_account(token_account_address)?;
+    let token_account = StateWithExtensionsOwned::<Account>::unpack(account.data)
+        .map_err(|err| format!("Invalid token account {}: {}", token_account_address, err))?;
+    Ok(TokenAccount {
+        address: *token_account_address,
+        account: token_account.base,
+    })
+}
+
+pub(crate) fn get_token_mint(
+    rpc_client: &RpcClient,
+    token_mint_address: &Pubkey,
+    epoch: Epoch,
+) -> Result<TokenMint, Error> {
+    let account = rpc_client.get_account(token_mint_address)?;
+    let mint = StateWithExtensionsOwned::<Mint>::unpack(account.data)
+        .map_err(|err| format!("Invalid token mint {}: {}", token_mint_address, err))?;
+    let transfer_fee = mint
+        .get_extension::<TransferFeeConfig>()
+        .ok()
+        .map(|config| *config.get_epoch_fee(epoch));
+    Ok(TokenMint {
+        address: *token_mint_address,
+        program_id: account.owner,
+        mint: mint.base,
+        transfer_fee,
+        extension_types: mint.get_extension_types()?,
+    })
+}
+
+pub(crate) fn get_pool(
+    rpc_client: &RpcClient,
+    program_id: &Pubkey,
+    pool_address: &Pubkey,
+) -> Result<Pool, Error> {
+    let account_data = rpc_client.get_account_data(pool_address)?;
+    let swap = SwapVersion::unpack(&account_data)
+        .map_err(|err| format!("Invalid swap {}: {}", pool_address, err))?;
+    let authority = Processor::authority_id(program_id, pool_address, swap.bump_seed())?;
+    let epoch = rpc_client.get_epoch_info()?.epoch;
+    let pool_fee_account = get_token_account(rpc_client, swap.pool_fee_account()).ok();
+    Ok(Pool {
+        address: *pool_address,
+        authority,
+        token_a: get_token_account(rpc_client, swap.token_a_account())?,
+        token_b: get_token_account(rpc_client, swap.token_b_account())?,
+        token_a_mint: get_token_mint(rpc_client, swap.token_a_mint(), epoch)?,
+        token_b_mint: get_token_mint(rpc_client, swap.token_b_mint(), epoch)?,
+        pool_mint: get_token_mint(rpc_client, swap.pool_mint(), epoch)?,
+        pool_fee_account,
+        swap,
+    })
+}
+
+pub(crate) fn get_concentrated_pool(
+    rpc_client: &RpcClient,
+    program_id: &Pubkey,
+    pool_address: &Pubkey,
+) -> Result<ConcentratedPoolAccounts, Error> {
+    let account_data = rpc_client.get_account_data(pool_address)?;
+    let pool = ConcentratedPool::unpack(&account_data).map_err(|err| {
+        format!(
+            "Invalid concentrated liquidity pool {}: {}",
+            pool_address, err
+        )
+    })?;
+    let authority = Processor::authority_id(program_id, pool_address, pool.bump_seed)?;
+    let epoch = rpc_client.get_epoch_info()?.epoch;
+    Ok(ConcentratedPoolAccounts {
+        address: *pool_address,
+        authority,
+        token_a: get_token_account(rpc_client, &pool.token_a)?,
+        token_b: get_token_account(rpc_client, &pool.token_b)?,
+        token_a_mint: get_token_mint(rpc_client, &pool.token_a_mint, epoch)?,
+        token_b_mint: get_token_mint(rpc_client, &pool.token_b_mint, epoch)?,
+        pool,
+    })
+}
+
+pub(crate) fn get_position(
+    rpc_client: &RpcClient,
+    position_address: &Pubkey,
+) -> Result<Position, Error> {
+    let account_data = rpc_client.get_account_data(position_address)?;
+    Ok(Position::unpack(&account_data)
+        .map_err(|err| format!("Invalid position {}: {}", position_address, err))?)
+}
+
+/// Whether the account holds a concentrated liquidity pool, rather than a
+/// swap pool
+pub(crate) fn is_concentrated_pool(
+    rpc_client: &RpcClient,
+    pool_address: &Pubkey,
+) -> Result<bool, Error> {
+    let account_data = rpc_client.get_account_data(pool_address)?;
+    Ok(account_data.first() == Some(&CONCENTRATED_POOL_TAG))
+}
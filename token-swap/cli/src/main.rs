@@ -0,0 +1,2068 @@
+#![allow(clippy::arithmetic_side_effects)]
+mod client;
+
+use {
+    crate::client::*,
+    clap::{
+        crate_description, crate_name, crate_version, value_t, App, AppSettings, Arg, ArgMatches,
+        SubCommand,
+    },
+    solana_clap_utils::{
+        fee_payer::fee_payer_arg,
+        input_parsers::{pubkey_of, value_of},
+        input_validators::{is_amount, is_parsable, is_pubkey, is_url, is_valid_signer},
+        keypair::signer_from_path,
+    },
+    solana_client::rpc_client::RpcClient,
+    solana_program::{
+        instruction::Instruction, native_token::lamports_to_sol, program_pack::Pack, pubkey::Pubkey,
+    },
+    solana_sdk::{
+        commitment_config::CommitmentConfig,
+        message::Message,
+        signature::{Keypair, Signer},
+        system_instruction,
+        transaction::Transaction,
+    },
+    spl_token_2022::{
+        instruction::{initialize_account3, initialize_mint2, transfer_checked},
+        state::{Account as TokenAccountState, Mint},
+    },
+    spl_token_swap::{
+        constraints::{SwapConstraints, SWAP_CONSTRAINTS},
+        curve::{
+            base::{CurveType, SwapCurve},
+            calculator::{RoundDirection, TradeDirection, TradingTokenResult},
+            concentrated_liquidity::{
+                amounts_for_liquidity, sqrt_price_at_tick, tick_at_sqrt_price,
+            },
+            constant_price::ConstantPriceCurve,
+            constant_product::ConstantProductCurve,
+            fees::Fees,
+            offset::OffsetCurve,
+        },
+        instruction::{
+            close_position, decrease_liquidity, deposit_all_token_types,
+            deposit_single_token_type_exact_amount_in, increase_liquidity, initialize,
+            initialize_concentrated_pool, open_position, swap, withdraw_all_token_types,
+            withdraw_single_token_type_exact_amount_out, DecreaseLiquidity, DepositAllTokenTypes,
+            DepositSingleTokenTypeExactAmountIn, IncreaseLiquidity, InitializeConcentratedPool,
+            OpenPosition, Swap, WithdrawAllTokenTypes, WithdrawSingleTokenTypeExactAmountOut,
+        },
+        quote::{maximum_with_slippage, minimum_with_slippage, PoolQuoter},
+        state::{ConcentratedPool, Position, SwapVersion, MAX_TICKS},
+    },
+    std::{process::exit, str::FromStr, sync::Arc},
+    system_instruction::create_account,
+};
+
+struct Config {
+    rpc_client: RpcClient,
+    fee_payer: Box<dyn Signer>,
+    owner: Box<dyn Signer>,
+    swap_program_id: Pubkey,
+    verbose: bool,
+    dry_run: bool,
+}
+
+type CommandResult = Result<(), Error>;
+
+const CURVE_NAMES: &[&str] = &[
+    "constant-product",
+    "constant-price",
+    "offset",
+    "concentrated-liquidity",
+];
+/// Curves whose pools are funded on creation
+const CURVE_POOLS: &[(&str, &str)] = &[
+    ("curve", "constant-product"),
+    ("curve", "constant-price"),
+    ("curve", "offset"),
+];
+/// Q64.64 representation of 1
+const Q64: f64 = 18_446_744_073_709_551_616.0;
+
+macro_rules! unique_signers {
+    ($vec:ident) => {
+        $vec.sort_by_key(|l| l.pubkey());
+        $vec.dedup();
+    };
+}
+
+fn fee_arg<'a, 'b>(name: &'a str, long: &'a str, default: &'a str, help: &'a str) -> Arg<'a, 'b> {
+    Arg::with_name(name)
+        .long(long)
+        .validator(is_fraction)
+        .value_name("NUMERATOR/DENOMINATOR")
+        .takes_value(true)
+        .default_value(default)
+        .help(help)
+}
+
+fn slippage_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("slippage")
+        .long("slippage")
+        .validator(is_parsable::<u64>)
+        .value_name("BASIS_POINTS")
+        .takes_value(true)
+        .default_value("50")
+        .help("Maximum price movement accepted before the transaction fails, in basis points")
+}
+
+fn pubkey_arg<'a, 'b>(name: &'a str, long: &'a str, help: &'a str) -> Arg<'a, 'b> {
+    Arg::with_name(name)
+        .long(long)
+        .validator(is_pubkey)
+        .value_name("PUBKEY")
+        .takes_value(true)
+        .required(true)
+        .help(help)
+}
+
+fn amount_arg(help: &str) -> Arg<'_, '_> {
+    Arg::with_name("amount")
+        .long("amount")
+        .validator(is_amount)
+        .value_name("DECIMAL_AMOUNT")
+        .takes_value(true)
+        .required(true)
+        .help(help)
+}
+
+fn tick_arg<'a, 'b>(name: &'a str, long: &'a str, help: &'a str) -> Arg<'a, 'b> {
+    Arg::with_name(name)
+        .long(long)
+        .validator(is_parsable::<i32>)
+        .value_name("TICK")
+        .takes_value(true)
+        .allow_hyphen_values(true)
+        .required(true)
+        .help(help)
+}
+
+fn main() {
+    solana_logger::setup_with_default("solana=info");
+
+    let default_swap_program_id: &str = &spl_token_swap::id().to_string();
+    let default_pool_token_program_id: &str = &spl_token::id().to_string();
+
+    let matches = App::new(crate_name!())
+        .about(crate_description!())
+        .version(crate_version!())
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg({
+            let arg = Arg::with_name("config_file")
+                .short("C")
+                .long("config")
+                .value_name("PATH")
+                .takes_value(true)
+                .global(true)
+                .help("Configuration file to use");
+            if let Some(ref config_file) = *solana_cli_config::CONFIG_FILE {
+                arg.default_value(config_file)
+            } else {
+                arg
+            }
+        })
+        .arg(
+            Arg::with_name("json_rpc_url")
+                .long("url")
+                .value_name("URL")
+                .takes_value(true)
+                .validator(is_url)
+                .help("JSON RPC URL for the cluster.  Default from the configuration file."),
+        )
+        .arg(
+            fee_payer_arg()
+                .short("p")
+                .global(true)
+        )
+        .arg(
+            Arg::with_name("owner")
+                .long("owner")
+                .value_name("KEYPAIR")
+                .validator(is_valid_signer)
+                .takes_value(true)
+                .global(true)
+                .help("Owner of the token accounts used.  Defaults to the client keypair."),
+        )
+        .arg(
+            Arg::with_name("swap_program_id")
+                .long("program")
+                .validator(is_pubkey)
+                .value_name("PUBKEY")
+                .takes_value(true)
+                .required(true)
+                .default_value(default_swap_program_id)
+                .help("Token swap program ID"),
+        )
+        .arg(
+            Arg::with_name("verbose")
+                .long("verbose")
+                .short("v")
+                .takes_value(false)
+                .global(true)
+                .help("Show additional information"),
+        )
+        .arg(
+            Arg::with_name("dry_run")
+                .long("dry-run")
+                .takes_value(false)
+                .global(true)
+                .help("Simulate transaction instead of executing"),
+        )
+        .subcommand(
+            SubCommand::with_name("create-pool")
+                .about("Create a new swap pool, funded from the owner's token accounts.  Concentrated liquidity pools start out empty, liquidity is added through positions.")
+                .arg(pubkey_arg("token_a", "token-a", "Owner's token A account to fund the pool from"))
+                .arg(pubkey_arg("token_b", "token-b", "Owner's token B account to fund the pool from"))
+                .arg(
+                    Arg::with_name("amount_a")
+                        .long("amount-a")
+                        .validator(is_amount)
+                        .value_name("DECIMAL_AMOUNT")
+                        .takes_value(true)
+                        .required_ifs(CURVE_POOLS)
+                        .help("Initial amount of token A"),
+                )
+                .arg(
+                    Arg::with_name("amount_b")
+                        .long("amount-b")
+                        .validator(is_amount)
+                        .value_name("DECIMAL_AMOUNT")
+                        .takes_value(true)
+                        .required_ifs(CURVE_POOLS)
+                        .help("Initial amount of token B"),
+                )
+                .arg(
+                    Arg::with_name("curve")
+                        .long("curve")
+                        .value_name("CURVE")
+                        .takes_value(true)
+                        .possible_values(CURVE_NAMES)
+                        .default_value("constant-product")
+                        .help("Curve used for pricing trades"),
+                )
+                .arg(
+                    Arg::with_name("token_b_price")
+                        .long("token-b-price")
+                        .validator(is_parsable::<u64>)
+                        .value_name("AMOUNT")
+                        .takes_value(true)
+                        .required_if("curve", "constant-price")
+                        .help("Constant price curve only: amount of token A paid for 1 token B, in base units"),
+                )
+                .arg(
+                    Arg::with_name("token_b_offset")
+                        .long("token-b-offset")
+                        .validator(is_parsable::<u64>)
+                        .value_name("AMOUNT")
+                        .takes_value(true)
+                        .required_if("curve", "offset")
+                        .help("Offset curve only: amount of token B added to the pool's real amount, in base units"),
+                )
+                .arg(
+                    Arg::with_name("tick_spacing")
+                        .long("tick-spacing")
+                        .validator(is_parsable::<u16>)
+                        .value_name("TICKS")
+                        .takes_value(true)
+                        .required_if("curve", "concentrated-liquidity")
+                        .help("Concentrated liquidity only: position bounds must be a multiple of the tick spacing"),
+                )
+                .arg(
+                    Arg::with_name("initial_price")
+                        .long("initial-price")
+                        .validator(is_parsable::<f64>)
+                        .value_name("PRICE")
+                        .takes_value(true)
+                        .required_if("curve", "concentrated-liquidity")
+                        .help("Concentrated liquidity only: initial price of token A, in token B"),
+                )
+                .arg(
+                    Arg::with_name("min_position_liquidity")
+                        .long("min-position-liquidity")
+                        .validator(is_parsable::<u128>)
+                        .value_name("LIQUIDITY")
+                        .takes_value(true)
+                        .default_value("0")
+                        .help("Concentrated liquidity only: minimum liquidity of a non-empty position"),
+                )
+                .arg(fee_arg("trade_fee", "trade-fee", "25/10000", "Trade fee, kept by the pool for liquidity providers"))
+                .arg(fee_arg("owner_trade_fee", "owner-trade-fee", "5/10000", "Owner trade fee, minted as pool tokens to the fee account, or collected by the fee authority of concentrated liquidity pools"))
+                .arg(fee_arg("owner_withdraw_fee", "owner-withdraw-fee", "0/0", "Owner withdraw fee, taken in pool tokens on withdrawals"))
+                .arg(fee_arg("host_fee", "host-fee", "20/100", "Host fee, as a portion of the owner trade fee"))
+                .arg(
+                    Arg::with_name("fee_owner")
+                        .long("fee-owner")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .help("Owner of the pool fee account, or fee authority of concentrated liquidity pools.  Defaults to the program's constraints owner, or the owner."),
+                )
+                .arg(
+                    Arg::with_name("pool_token_program_id")
+                        .long("pool-token-program")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .default_value(default_pool_token_program_id)
+                        .help("Token program for the pool token mint"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("deposit-all")
+                .about("Deposit both tokens in exchange for an exact amount of pool tokens")
+                .arg(pubkey_arg("pool", "pool", "Swap pool address"))
+                .arg(pubkey_arg("token_a", "token-a", "Owner's token A account to deposit from"))
+                .arg(pubkey_arg("token_b", "token-b", "Owner's token B account to deposit from"))
+                .arg(pubkey_arg("pool_account", "pool-account", "Pool token account to receive pool tokens"))
+                .arg(amount_arg("Amount of pool tokens to receive"))
+                .arg(slippage_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("withdraw-all")
+                .about("Burn pool tokens in exchange for both tokens")
+                .arg(pubkey_arg("pool", "pool", "Swap pool address"))
+                .arg(pubkey_arg("pool_account", "pool-account", "Owner's pool token account to burn from"))
+                .arg(pubkey_arg("token_a", "token-a", "Token A account to receive tokens"))
+                .arg(pubkey_arg("token_b", "token-b", "Token B account to receive tokens"))
+                .arg(amount_arg("Amount of pool tokens to burn"))
+                .arg(slippage_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("deposit-single")
+                .about("Deposit an exact amount of token A or B in exchange for pool tokens")
+                .arg(pubkey_arg("pool", "pool", "Swap pool address"))
+                .arg(pubkey_arg("source", "source", "Owner's token A or B account to deposit from"))
+                .arg(pubkey_arg("pool_account", "pool-account", "Pool token account to receive pool tokens"))
+                .arg(amount_arg("Amount of token A or B to deposit"))
+                .arg(slippage_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("withdraw-single")
+                .about("Burn pool tokens in exchange for an exact amount of token A or B")
+                .arg(pubkey_arg("pool", "pool", "Swap pool address"))
+                .arg(pubkey_arg("pool_account", "pool-account", "Owner's pool token account to burn from"))
+                .arg(pubkey_arg("destination", "destination", "Token A or B account to receive tokens"))
+                .arg(amount_arg("Amount of token A or B to withdraw"))
+                .arg(slippage_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("swap")
+                .about("Trade token A for token B, or B for A")
+                .arg(pubkey_arg("pool", "pool", "Swap pool address"))
+                .arg(pubkey_arg("source", "source", "Owner's token account to trade from"))
+                .arg(pubkey_arg("destination", "destination", "Token account to receive the other token"))
+                .arg(amount_arg("Amount of source tokens to trade"))
+                .arg(slippage_arg())
+                .arg(
+                    Arg::with_name("host_fee_account")
+                        .long("host-fee-account")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .help("Pool token account to receive the host fee"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("show")
+                .about("Show the state, reserves and price of a swap pool")
+                .arg(pubkey_arg("pool", "pool", "Swap pool address")),
+        )
+        .subcommand(
+            SubCommand::with_name("validate")
+                .about("Check a swap pool against swap constraints.  Defaults to the constraints the program was built with, if any.")
+                .arg(pubkey_arg("pool", "pool", "Swap pool address"))
+                .arg(
+                    Arg::with_name("fee_owner")
+                        .long("fee-owner")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .help("Required owner of the pool fee account"),
+                )
+                .arg(
+                    Arg::with_name("curve")
+                        .long("curve")
+                        .value_name("CURVE")
+                        .takes_value(true)
+                        .multiple(true)
+                        .possible_values(CURVE_NAMES)
+                        .help("Allowed curve, may be specified multiple times"),
+                )
+                .arg(fee_arg("trade_fee", "trade-fee", "0/0", "Minimum trade fee"))
+                .arg(fee_arg("owner_trade_fee", "owner-trade-fee", "0/0", "Minimum owner trade fee"))
+                .arg(fee_arg("owner_withdraw_fee", "owner-withdraw-fee", "0/0", "Minimum owner withdraw fee"))
+                .arg(fee_arg("host_fee", "host-fee", "0/0", "Required host fee")),
+        )
+        .subcommand(
+            SubCommand::with_name("open-position")
+                .about("Open a position providing liquidity to a concentrated liquidity pool between two ticks")
+                .arg(pubkey_arg("pool", "pool", "Concentrated liquidity pool address"))
+                .arg(tick_arg("tick_lower", "tick-lower", "Lower tick bound of the position, inclusive"))
+                .arg(tick_arg("tick_upper", "tick-upper", "Upper tick bound of the position, exclusive"))
+                .arg(
+                    Arg::with_name("liquidity")
+                        .long("liquidity")
+                        .validator(is_parsable::<u128>)
+                        .value_name("LIQUIDITY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Liquidity to provide.  The token amounts deposited depend on the current price and the position's range."),
+                )
+                .arg(pubkey_arg("token_a", "token-a", "Owner's token A account to deposit from"))
+                .arg(pubkey_arg("token_b", "token-b", "Owner's token B account to deposit from"))
+                .arg(slippage_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("close-position")
+                .about("Withdraw all liquidity and fees from a position, and close it")
+                .arg(pubkey_arg("position", "position", "Position address"))
+                .arg(pubkey_arg("token_a", "token-a", "Token A account to receive tokens"))
+                .arg(pubkey_arg("token_b", "token-b", "Token B account to receive tokens"))
+                .arg(slippage_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("show-position")
+                .about("Show the range, liquidity and fees of a concentrated liquidity position")
+                .arg(pubkey_arg("position", "position", "Position address")),
+        )
+        .get_matches();
+
+    let mut wallet_manager = None;
+    let config = {
+        let cli_config = if let Some(config_file) = matches.value_of("config_file") {
+            solana_cli_config::Config::load(config_file).unwrap_or_default()
+        } else {
+            solana_cli_config::Config::default()
+        };
+        let json_rpc_url = value_t!(matches, "json_rpc_url", String)
+            .unwrap_or_else(|_| cli_config.json_rpc_url.clone());
+
+        let fee_payer = signer_from_path(
+            &matches,
+            matches
+                .value_of("fee_payer")
+                .unwrap_or(&cli_config.keypair_path),
+            "fee_payer",
+            &mut wallet_manager,
+        )
+        .unwrap_or_else(|e| {
+            eprintln!("error: {}", e);
+            exit(1);
+        });
+        let owner = signer_from_path(
+            &matches,
+            matches
+                .value_of("owner")
+                .unwrap_or(&cli_config.keypair_path),
+            "owner",
+            &mut wallet_manager,
+        )
+        .unwrap_or_else(|e| {
+            eprintln!("error: {}", e);
+            exit(1);
+        });
+
+        let swap_program_id = pubkey_of(&matches, "swap_program_id").unwrap();
+        let verbose = matches.is_present("verbose");
+        let dry_run = matches.is_present("dry_run");
+
+        Config {
+            rpc_client: RpcClient::new_with_commitment(json_rpc_url, CommitmentConfig::confirmed()),
+            fee_payer,
+            owner,
+            swap_program_id,
+            verbose,
+            dry_run,
+        }
+    };
+
+    let _ = match matches.subcommand() {
+        ("create-pool", Some(arg_matches))
+            if arg_matches.value_of("curve") == Some("concentrated-liquidity") =>
+        {
+            let token_a = pubkey_of(arg_matches, "token_a").unwrap();
+            let token_b = pubkey_of(arg_matches, "token_b").unwrap();
+            let tick_spacing = value_of(arg_matches, "tick_spacing").unwrap();
+            let initial_price = value_of(arg_matches, "initial_price").unwrap();
+            let min_position_liquidity = value_of(arg_matches, "min_position_liquidity").unwrap();
+            let fees = fees_of(arg_matches);
+            let fee_authority = pubkey_of(arg_matches, "fee_owner");
+            command_create_concentrated_pool(
+                &config,
+                token_a,
+                token_b,
+                tick_spacing,
+                initial_price,
+                min_position_liquidity,
+                fees,
+                fee_authority,
+            )
+        }
+        ("create-pool", Some(arg_matches)) => {
+            let token_a = pubkey_of(arg_matches, "token_a").unwrap();
+            let token_b = pubkey_of(arg_matches, "token_b").unwrap();
+            let amount_a = value_of(arg_matches, "amount_a").unwrap();
+            let amount_b = value_of(arg_matches, "amount_b").unwrap();
+            let swap_curve = swap_curve_of(arg_matches);
+            let fees = fees_of(arg_matches);
+            let fee_owner = pubkey_of(arg_matches, "fee_owner");
+            let pool_token_program_id = pubkey_of(arg_matches, "pool_token_program_id").unwrap();
+            command_create_pool(
+                &config,
+                token_a,
+                token_b,
+                amount_a,
+                amount_b,
+                swap_curve,
+                fees,
+                fee_owner,
+                pool_token_program_id,
+            )
+        }
+        ("deposit-all", Some(arg_matches)) => {
+            let pool = pubkey_of(arg_matches, "pool").unwrap();
+            let token_a = pubkey_of(arg_matches, "token_a").unwrap();
+            let token_b = pubkey_of(arg_matches, "token_b").unwrap();
+            let pool_account = pubkey_of(arg_matches, "pool_account").unwrap();
+            let ui_amount = value_of(arg_matches, "amount").unwrap();
+            let slippage = value_of(arg_matches, "slippage").unwrap();
+            command_deposit_all(
+                &config,
+                pool,
+                token_a,
+                token_b,
+                pool_account,
+                ui_amount,
+                slippage,
+            )
+        }
+        ("withdraw-all", Some(arg_matches)) => {
+            let pool = pubkey_of(arg_matches, "pool").unwrap();
+            let pool_account = pubkey_of(arg_matches, "pool_account").unwrap();
+            let token_a = pubkey_of(arg_matches, "token_a").unwrap();
+            let token_b = pubkey_of(arg_matches, "token_b").unwrap();
+            let ui_amount = value_of(arg_matches, "amount").unwrap();
+            let slippage = value_of(arg_matches, "slippage").unwrap();
+            command_withdraw_all(
+                &config,
+                pool,
+                pool_account,
+                token_a,
+                token_b,
+                ui_amount,
+                slippage,
+            )
+        }
+        ("deposit-single", Some(arg_matches)) => {
+            let pool = pubkey_of(arg_matches, "pool").unwrap();
+            let source = pubkey_of(arg_matches, "source").unwrap();
+            let pool_account = pubkey_of(arg_matches, "pool_account").unwrap();
+            let ui_amount = value_of(arg_matches, "amount").unwrap();
+            let slippage = value_of(arg_matches, "slippage").unwrap();
+            command_deposit_single(&config, pool, source, pool_account, ui_amount, slippage)
+        }
+        ("withdraw-single", Some(arg_matches)) => {
+            let pool = pubkey_of(arg_matches, "pool").unwrap();
+            let pool_account = pubkey_of(arg_matches, "pool_account").unwrap();
+            let destination = pubkey_of(arg_matches, "destination").unwrap();
+            let ui_amount = value_of(arg_matches, "amount").unwrap();
+            let slippage = value_of(arg_matches, "slippage").unwrap();
+            command_withdraw_single(
+                &config,
+                pool,
+                pool_account,
+                destination,
+                ui_amount,
+                slippage,
+            )
+        }
+        ("swap", Some(arg_matches)) => {
+            let pool = pubkey_of(arg_matches, "pool").unwrap();
+            let source = pubkey_of(arg_matches, "source").unwrap();
+            let destination = pubkey_of(arg_matches, "destination").unwrap();
+            let ui_amount = value_of(arg_matches, "amount").unwrap();
+            let slippage = value_of(arg_matches, "slippage").unwrap();
+            let host_fee_account = pubkey_of(arg_matches, "host_fee_account");
+            command_swap(
+                &config,
+                pool,
+                source,
+                destination,
+                ui_amount,
+                slippage,
+                host_fee_account,
+            )
+        }
+        ("show", Some(arg_matches)) => {
+            let pool = pubkey_of(arg_matches, "pool").unwrap();
+            command_show(&config, pool)
+        }
+        ("validate", Some(arg_matches)) => {
+            let pool = pubkey_of(arg_matches, "pool").unwrap();
+            command_validate(&config, pool, arg_matches)
+        }
+        ("open-position", Some(arg_matches)) => {
+            let pool = pubkey_of(arg_matches, "pool").unwrap();
+            let tick_lower = value_of(arg_matches, "tick_lower").unwrap();
+            let tick_upper = value_of(arg_matches, "tick_upper").unwrap();
+            let liquidity = value_of(arg_matches, "liquidity").unwrap();
+            let token_a = pubkey_of(arg_matches, "token_a").unwrap();
+            let token_b = pubkey_of(arg_matches, "token_b").unwrap();
+            let slippage = value_of(arg_matches, "slippage").unwrap();
+            command_open_position(
+                &config, pool, tick_lower, tick_upper, liquidity, token_a, token_b, slippage,
+            )
+        }
+        ("close-position", Some(arg_matches)) => {
+            let position = pubkey_of(arg_matches, "position").unwrap();
+            let token_a = pubkey_of(arg_matches, "token_a").unwrap();
+            let token_b = pubkey_of(arg_matches, "token_b").unwrap();
+            let slippage = value_of(arg_matches, "slippage").unwrap();
+            command_close_position(&config, position, token_a, token_b, slippage)
+        }
+        ("show-position", Some(arg_matches)) => {
+            let position = pubkey_of(arg_matches, "position").unwrap();
+            command_show_position(&config, position)
+        }
+        _ => unreachable!(),
+    }
+    .map_err(|err| {
+        eprintln!("{}", err);
+        exit(1);
+    });
+}
+
+// COMMANDS
+
+#[allow(clippy::too_many_arguments)]
+fn command_create_pool(
+    config: &Config,
+    token_a: Pubkey,
+    token_b: Pubkey,
+    ui_amount_a: f64,
+    ui_amount_b: f64,
+    swap_curve: SwapCurve,
+    fees: Fees,
+    fee_owner: Option<Pubkey>,
+    pool_token_program_id: Pubkey,
+) -> CommandResult {
+    let epoch = config.rpc_client.get_epoch_info()?.epoch;
+    let source_a = get_token_account(&config.rpc_client, &token_a)?;
+    let source_b = get_token_account(&config.rpc_client, &token_b)?;
+    let mint_a = get_token_mint(&config.rpc_client, &source_a.account.mint, epoch)?;
+    let mint_b = get_token_mint(&config.rpc_client, &source_b.account.mint, epoch)?;
+    let amount_a = spl_token::ui_amount_to_amount(ui_amount_a, mint_a.mint.decimals);
+    let amount_b = spl_token::ui_amount_to_amount(ui_amount_b, mint_b.mint.decimals);
+
+    fees.validate()?;
+    swap_curve.calculator.validate()?;
+    // the curve checks the amounts received by the pool
+    swap_curve.calculator.validate_supply(
+        amount_a - transfer_fee(&mint_a, amount_a)?,
+        amount_b - transfer_fee(&mint_b, amount_b)?,
+    )?;
+    let constraints_owner = SWAP_CONSTRAINTS
+        .as_ref()
+        .map(|constraints| Pubkey::from_str(constraints.owner_key))
+        .transpose()?;
+    if let Some(swap_constraints) = &SWAP_CONSTRAINTS {
+        swap_constraints.validate_curve(&swap_curve)?;
+        swap_constraints.validate_fees(&fees)?;
+        if fee_owner.is_some() && fee_owner != constraints_owner {
+            return Err(format!(
+                "Pool fee account must be owned by {}",
+                swap_constraints.owner_key
+            )
+            .into());
+        }
+    }
+    let fee_owner = fee_owner
+        .or(constraints_owner)
+        .unwrap_or_else(|| config.owner.pubkey());
+
+    let swap_keypair = Keypair::new();
+    let pool_mint_keypair = Keypair::new();
+    let token_a_keypair = Keypair::new();
+    let token_b_keypair = Keypair::new();
+    let pool_fee_keypair = Keypair::new();
+    let destination_keypair = Keypair::new();
+    let (authority, _bump_seed) = Pubkey::find_program_address(
+        &[&swap_keypair.pubkey().to_bytes()],
+        &config.swap_program_id,
+    );
+
+    println!("Creating swap pool {}", swap_keypair.pubkey());
+    if config.verbose {
+        println!("Swap authority {}", authority);
+        println!("Pool token mint {}", pool_mint_keypair.pubkey());
+        println!("Swap token A account {}", token_a_keypair.pubkey());
+        println!("Swap token B account {}", token_b_keypair.pubkey());
+        println!("Pool fee account {}", pool_fee_keypair.pubkey());
+        println!("Pool token account {}", destination_keypair.pubkey());
+    }
+
+    let swap_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(SwapVersion::LATEST_LEN)?;
+    let pool_mint_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(Mint::LEN)?;
+    let token_a_len = mint_a.account_len()?;
+    let token_a_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(token_a_len)?;
+    let token_b_len = mint_b.account_len()?;
+    let token_b_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(token_b_len)?;
+    let pool_account_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(TokenAccountState::LEN)?;
+    let total_balance = swap_balance
+        + pool_mint_balance
+        + token_a_balance
+        + token_b_balance
+        + 2 * pool_account_balance;
+
+    let instructions_1 = vec![
+        create_account(
+            &config.fee_payer.pubkey(),
+            &swap_keypair.pubkey(),
+            swap_balance,
+            SwapVersion::LATEST_LEN as u64,
+            &config.swap_program_id,
+        ),
+        create_account(
+            &config.fee_payer.pubkey(),
+            &pool_mint_keypair.pubkey(),
+            pool_mint_balance,
+            Mint::LEN as u64,
+            &pool_token_program_id,
+        ),
+        initialize_mint2(
+            &pool_token_program_id,
+            &pool_mint_keypair.pubkey(),
+            &authority,
+            None,
+            2,
+        )?,
+        create_account(
+            &config.fee_payer.pubkey(),
+            &token_a_keypair.pubkey(),
+            token_a_balance,
+            token_a_len as u64,
+            &mint_a.program_id,
+        ),
+        initialize_account3(
+            &mint_a.program_id,
+            &token_a_keypair.pubkey(),
+            &mint_a.address,
+            &authority,
+        )?,
+        create_account(
+            &config.fee_payer.pubkey(),
+            &token_b_keypair.pubkey(),
+            token_b_balance,
+            token_b_len as u64,
+            &mint_b.program_id,
+        ),
+        initialize_account3(
+            &mint_b.program_id,
+            &token_b_keypair.pubkey(),
+            &mint_b.address,
+            &authority,
+        )?,
+    ];
+    let instructions_2 = vec![
+        create_account(
+            &config.fee_payer.pubkey(),
+            &pool_fee_keypair.pubkey(),
+            pool_account_balance,
+            TokenAccountState::LEN as u64,
+            &pool_token_program_id,
+        ),
+        initialize_account3(
+            &pool_token_program_id,
+            &pool_fee_keypair.pubkey(),
+            &pool_mint_keypair.pubkey(),
+            &fee_owner,
+        )?,
+        create_account(
+            &config.fee_payer.pubkey(),
+            &destination_keypair.pubkey(),
+            pool_account_balance,
+            TokenAccountState::LEN as u64,
+            &pool_token_program_id,
+        ),
+        initialize_account3(
+            &pool_token_program_id,
+            &destination_keypair.pubkey(),
+            &pool_mint_keypair.pubkey(),
+            &config.owner.pubkey(),
+        )?,
+        transfer_checked(
+            &mint_a.program_id,
+            &token_a,
+            &mint_a.address,
+            &token_a_keypair.pubkey(),
+            &config.owner.pubkey(),
+            &[],
+            amount_a,
+            mint_a.mint.decimals,
+        )?,
+        transfer_checked(
+            &mint_b.program_id,
+            &token_b,
+            &mint_b.address,
+            &token_b_keypair.pubkey(),
+            &config.owner.pubkey(),
+            &[],
+            amount_b,
+            mint_b.mint.decimals,
+        )?,
+        initialize(
+            &config.swap_program_id,
+            &pool_token_program_id,
+            &swap_keypair.pubkey(),
+            &authority,
+            &token_a_keypair.pubkey(),
+            &token_b_keypair.pubkey(),
+            &pool_mint_keypair.pubkey(),
+            &pool_fee_keypair.pubkey(),
+            &destination_keypair.pubkey(),
+            fees,
+            swap_curve,
+        )?,
+    ];
+
+    let recent_blockhash = config.rpc_client.get_latest_blockhash()?;
+    let message_1 = Message::new_with_blockhash(
+        &instructions_1,
+        Some(&config.fee_payer.pubkey()),
+        &recent_blockhash,
+    );
+    let message_2 = Message::new_with_blockhash(
+        &instructions_2,
+        Some(&config.fee_payer.pubkey()),
+        &recent_blockhash,
+    );
+    check_fee_payer_balance(
+        config,
+        total_balance
+            + config.rpc_client.get_fee_for_message(&message_1)?
+            + config.rpc_client.get_fee_for_message(&message_2)?,
+    )?;
+
+    let transaction_1 = Transaction::new(
+        &vec![
+            config.fee_payer.as_ref(),
+            &swap_keypair,
+            &pool_mint_keypair,
+            &token_a_keypair,
+            &token_b_keypair,
+        ],
+        message_1,
+        recent_blockhash,
+    );
+    send_transaction(config, transaction_1)?;
+    let mut signers = vec![
+        config.fee_payer.as_ref(),
+        config.owner.as_ref(),
+        &pool_fee_keypair,
+        &destination_keypair,
+        &swap_keypair,
+    ];
+    unique_signers!(signers);
+    let transaction_2 = Transaction::new(&signers, message_2, recent_blockhash);
+    send_transaction(config, transaction_2)?;
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn command_create_concentrated_pool(
+    config: &Config,
+    token_a: Pubkey,
+    token_b: Pubkey,
+    tick_spacing: u16,
+    initial_price: f64,
+    min_position_liquidity: u128,
+    fees: Fees,
+    fee_authority: Option<Pubkey>,
+) -> CommandResult {
+    let epoch = config.rpc_client.get_epoch_info()?.epoch;
+    let source_a = get_token_account(&config.rpc_client, &token_a)?;
+    let source_b = get_token_account(&config.rpc_client, &token_b)?;
+    let mint_a = get_token_mint(&config.rpc_client, &source_a.account.mint, epoch)?;
+    let mint_b = get_token_mint(&config.rpc_client, &source_b.account.mint, epoch)?;
+    let initial_sqrt_price =
+        sqrt_price_of(initial_price, mint_a.mint.decimals, mint_b.mint.decimals)
+            .ok_or_else(|| format!("Initial price {} is out of range", initial_price))?;
+
+    fees.validate()?;
+    if tick_spacing == 0 {
+        return Err("Tick spacing must be greater than 0".into());
+    }
+    let constraints_owner = SWAP_CONSTRAINTS
+        .as_ref()
+        .map(|constraints| Pubkey::from_str(constraints.owner_key))
+        .transpose()?;
+    if let Some(swap_constraints) = &SWAP_CONSTRAINTS {
+        swap_constraints.validate_curve_type(CurveType::ConcentratedLiquidity)?;
+        swap_constraints.validate_fees(&fees)?;
+        if fee_authority.is_some() && fee_authority != constraints_owner {
+            return Err(format!("Fee authority must be {}", swap_constraints.owner_key).into());
+        }
+    }
+    let fee_authority = fee_authority
+        .or(constraints_owner)
+        .unwrap_or_else(|| config.owner.pubkey());
+
+    let pool_keypair = Keypair::new();
+    let token_a_keypair = Keypair::new();
+    let token_b_keypair = Keypair::new();
+    let (authority, _bump_seed) = Pubkey::find_program_address(
+        &[&pool_keypair.pubkey().to_bytes()],
+        &config.swap_program_id,
+    );
+
+    println!(
+        "Creating concentrated liquidity pool {}",
+        pool_keypair.pubkey()
+    );
+    if config.verbose {
+        println!("Pool authority {}", authority);
+        println!("Pool token A account {}", token_a_keypair.pubkey());
+        println!("Pool token B account {}", token_b_keypair.pubkey());
+        println!("Fee authority {}", fee_authority);
+    }
+
+    let pool_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(ConcentratedPool::LEN)?;
+    let token_a_len = mint_a.account_len()?;
+    let token_a_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(token_a_len)?;
+    let token_b_len = mint_b.account_len()?;
+    let token_b_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(token_b_len)?;
+    let total_balance = pool_balance + token_a_balance + token_b_balance;
+
+    let instructions = vec![
+        create_account(
+            &config.fee_payer.pubkey(),
+            &pool_keypair.pubkey(),
+            pool_balance,
+            ConcentratedPool::LEN as u64,
+            &config.swap_program_id,
+        ),
+        create_account(
+            &config.fee_payer.pubkey(),
+            &token_a_keypair.pubkey(),
+            token_a_balance,
+            token_a_len as u64,
+            &mint_a.program_id,
+        ),
+        initialize_account3(
+            &mint_a.program_id,
+            &token_a_keypair.pubkey(),
+            &mint_a.address,
+            &authority,
+        )?,
+        create_account(
+            &config.fee_payer.pubkey(),
+            &token_b_keypair.pubkey(),
+            token_b_balance,
+            token_b_len as u64,
+            &mint_b.program_id,
+        ),
+        initialize_account3(
+            &mint_b.program_id,
+            &token_b_keypair.pubkey(),
+            &mint_b.address,
+            &authority,
+        )?,
+        initialize_concentrated_pool(
+            &config.swap_program_id,
+            &pool_keypair.pubkey(),
+            &authority,
+            &token_a_keypair.pubkey(),
+            &token_b_keypair.pubkey(),
+            &fee_authority,
+            InitializeConcentratedPool {
+                fees,
+                tick_spacing,
+                initial_sqrt_price,
+                min_position_liquidity,
+            },
+        )?,
+    ];
+
+    let recent_blockhash = config.rpc_client.get_latest_blockhash()?;
+    let message = Message::new_with_blockhash(
+        &instructions,
+        Some(&config.fee_payer.pubkey()),
+        &recent_blockhash,
+    );
+    check_fee_payer_balance(
+        config,
+        total_balance + config.rpc_client.get_fee_for_message(&message)?,
+    )?;
+    let transaction = Transaction::new(
+        &vec![
+            config.fee_payer.as_ref(),
+            &pool_keypair,
+            &token_a_keypair,
+            &token_b_keypair,
+        ],
+        message,
+        recent_blockhash,
+    );
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn command_open_position(
+    config: &Config,
+    pool_address: Pubkey,
+    tick_lower: i32,
+    tick_upper: i32,
+    liquidity: u128,
+    token_a: Pubkey,
+    token_b: Pubkey,
+    slippage: u64,
+) -> CommandResult {
+    let pool = get_concentrated_pool(&config.rpc_client, &config.swap_program_id, &pool_address)?;
+    pool.pool.validate_tick(tick_lower)?;
+    pool.pool.validate_tick(tick_upper)?;
+    if tick_lower >= tick_upper {
+        return Err("Lower tick must be less than the upper tick".into());
+    }
+    let results = position_amounts(
+        &pool.pool,
+        tick_lower,
+        tick_upper,
+        liquidity,
+        RoundDirection::Ceiling,
+    )?;
+    // the pool checks the maximums against the amounts sent, including the
+    // transfer fees
+    let maximum_token_a_amount = maximum_with_slippage(
+        amount_with_transfer_fee(&pool.token_a_mint, u64::try_from(results.token_a_amount)?)?,
+        slippage,
+    )?;
+    let maximum_token_b_amount = maximum_with_slippage(
+        amount_with_transfer_fee(&pool.token_b_mint, u64::try_from(results.token_b_amount)?)?,
+        slippage,
+    )?;
+
+    let position_keypair = Keypair::new();
+    println!(
+        "Opening position {}, depositing at most {} token A and {} token B",
+        position_keypair.pubkey(),
+        amount_to_ui(maximum_token_a_amount, &pool.token_a_mint),
+        amount_to_ui(maximum_token_b_amount, &pool.token_b_mint),
+    );
+
+    let position_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(Position::LEN)?;
+    let mut instructions = vec![
+        create_account(
+            &config.fee_payer.pubkey(),
+            &position_keypair.pubkey(),
+            position_balance,
+            Position::LEN as u64,
+            &config.swap_program_id,
+        ),
+        open_position(
+            &config.swap_program_id,
+            &pool.address,
+            &position_keypair.pubkey(),
+            &config.owner.pubkey(),
+            OpenPosition {
+                tick_lower,
+                tick_upper,
+            },
+        )?,
+    ];
+    if liquidity > 0 {
+        instructions.push(increase_liquidity(
+            &config.swap_program_id,
+            &pool.token_a_mint.program_id,
+            &pool.token_b_mint.program_id,
+            &pool.address,
+            &pool.authority,
+            &position_keypair.pubkey(),
+            &config.owner.pubkey(),
+            &config.owner.pubkey(),
+            &token_a,
+            &token_b,
+            &pool.token_a.address,
+            &pool.token_b.address,
+            &pool.token_a_mint.address,
+            &pool.token_b_mint.address,
+            IncreaseLiquidity {
+                liquidity,
+                maximum_token_a_amount,
+                maximum_token_b_amount,
+            },
+        )?);
+    }
+
+    let recent_blockhash = config.rpc_client.get_latest_blockhash()?;
+    let message = Message::new_with_blockhash(
+        &instructions,
+        Some(&config.fee_payer.pubkey()),
+        &recent_blockhash,
+    );
+    check_fee_payer_balance(
+        config,
+        position_balance + config.rpc_client.get_fee_for_message(&message)?,
+    )?;
+    let mut signers = vec![
+        config.fee_payer.as_ref(),
+        config.owner.as_ref(),
+        &position_keypair,
+    ];
+    unique_signers!(signers);
+    let transaction = Transaction::new(&signers, message, recent_blockhash);
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
+fn command_close_position(
+    config: &Config,
+    position_address: Pubkey,
+    token_a: Pubkey,
+    token_b: Pubkey,
+    slippage: u64,
+) -> CommandResult {
+    let position = get_position(&config.rpc_client, &position_address)?;
+    let pool = get_concentrated_pool(&config.rpc_client, &config.swap_program_id, &position.pool)?;
+    let results = position_amounts(
+        &pool.pool,
+        position.tick_lower,
+        position.tick_upper,
+        position.liquidity,
+        RoundDirection::Floor,
+    )?;
+    let minimum_token_a_amount =
+        minimum_with_slippage(u64::try_from(results.token_a_amount)?, slippage)?;
+    let minimum_token_b_amount =
+        minimum_with_slippage(u64::try_from(results.token_b_amount)?, slippage)?;
+    println!(
+        "Closing position {}, withdrawing at least {} token A and {} token B, plus fees",
+        position_address,
+        amount_to_ui(minimum_token_a_amount, &pool.token_a_mint),
+        amount_to_ui(minimum_token_b_amount, &pool.token_b_mint),
+    );
+
+    // fees are paid out along with the liquidity, so the position is emptied
+    // even if it no longer holds any liquidity
+    let instructions = [
+        decrease_liquidity(
+            &config.swap_program_id,
+            &pool.token_a_mint.program_id,
+            &pool.token_b_mint.program_id,
+            &pool.address,
+            &pool.authority,
+            &position_address,
+            &config.owner.pubkey(),
+            &pool.token_a.address,
+            &pool.token_b.address,
+            &token_a,
+            &token_b,
+            &pool.token_a_mint.address,
+            &pool.token_b_mint.address,
+            DecreaseLiquidity {
+                liquidity: position.liquidity,
+                minimum_token_a_amount,
+                minimum_token_b_amount,
+            },
+        )?,
+        close_position(
+            &config.swap_program_id,
+            &pool.address,
+            &position_address,
+            &config.owner.pubkey(),
+            &config.owner.pubkey(),
+        )?,
+    ];
+    send_owner_instructions(config, &instructions)
+}
+
+fn command_show_position(config: &Config, position_address: Pubkey) -> CommandResult {
+    let mut position = get_position(&config.rpc_client, &position_address)?;
+    let mut pool =
+        get_concentrated_pool(&config.rpc_client, &config.swap_program_id, &position.pool)?;
+    let results = position_amounts(
+        &pool.pool,
+        position.tick_lower,
+        position.tick_upper,
+        position.liquidity,
+        RoundDirection::Floor,
+    )?;
+    let in_range = (position.tick_lower..position.tick_upper).contains(&pool.pool.current_tick);
+    // credit the fees earned since the position was last modified
+    pool.pool.modify_position(&mut position, 0)?;
+
+    let price = |tick| {
+        sqrt_price_at_tick(tick).map_or(0.0, |sqrt_price| {
+            price_of(
+                sqrt_price,
+                pool.token_a_mint.mint.decimals,
+                pool.token_b_mint.mint.decimals,
+            )
+        })
+    };
+    println!("Position: {}", position_address);
+    println!("Pool: {}", position.pool);
+    println!("Owner: {}", position.owner);
+    println!(
+        "Range: ticks {} to {}, {} to {} token B per token A, {}",
+        position.tick_lower,
+        position.tick_upper,
+        price(position.tick_lower),
+        price(position.tick_upper),
+        if in_range { "in range" } else { "out of range" },
+    );
+    println!("Liquidity: {}", position.liquidity);
+    println!(
+        "Token A: {}",
+        amount_to_ui(u64::try_from(results.token_a_amount)?, &pool.token_a_mint)
+    );
+    println!(
+        "Token B: {}",
+        amount_to_ui(u64::try_from(results.token_b_amount)?, &pool.token_b_mint)
+    );
+    println!(
+        "Fees earned: {} token A, {} token B",
+        amount_to_ui(position.fees_owed_a, &pool.token_a_mint),
+        amount_to_ui(position.fees_owed_b, &pool.token_b_mint),
+    );
+    Ok(())
+}
+
+fn command_deposit_all(
+    config: &Config,
+    pool_address: Pubkey,
+    token_a: Pubkey,
+    token_b: Pubkey,
+    pool_account: Pubkey,
+    ui_amount: f64,
+    slippage: u64,
+) -> CommandResult {
+    let pool = get_pool(&config.rpc_client, &config.swap_program_id, &pool_address)?;
+    let pool_token_amount = spl_token::ui_amount_to_amount(ui_amount, pool.pool_mint.mint.decimals);
+    let results = pool
+        .swap
+        .swap_curve()
+        .calculator
+        .pool_tokens_to_trading_tokens(
+            u128::from(pool_token_amount),
+            u128::from(pool.pool_mint.mint.supply),
+            u128::from(pool.token_a.account.amount),
+            u128::from(pool.token_b.account.amount),
+            RoundDirection::Ceiling,
+        )
+        .ok_or("Pool token amount is too large for the pool")?;
+    let maximum_token_a_amount =
+        maximum_with_slippage(u64::try_from(results.token_a_amount)?, slippage)?;
+    let maximum_token_b_amount =
+        maximum_with_slippage(u64::try_from(results.token_b_amount)?, slippage)?;
+    println!(
+        "Depositing at most {} token A and {} token B for {} pool tokens",
+        amount_to_ui(maximum_token_a_amount, &pool.token_a_mint),
+        amount_to_ui(maximum_token_b_amount, &pool.token_b_mint),
+        ui_amount,
+    );
+
+    let instruction = deposit_all_token_types(
+        &config.swap_program_id,
+        &pool.token_a_mint.program_id,
+        &pool.token_b_mint.program_id,
+        &pool.pool_mint.program_id,
+        &pool.address,
+        &pool.authority,
+        &config.owner.pubkey(),
+        &token_a,
+        &token_b,
+        &pool.token_a.address,
+        &pool.token_b.address,
+        &pool.pool_mint.address,
+        &pool_account,
+        &pool.token_a_mint.address,
+        &pool.token_b_mint.address,
+        DepositAllTokenTypes {
+            pool_token_amount,
+            maximum_token_a_amount,
+            maximum_token_b_amount,
+        },
+    )?;
+    send_owner_instructions(config, &[instruction])
+}
+
+fn command_withdraw_all(
+    config: &Config,
+    pool_address: Pubkey,
+    pool_account: Pubkey,
+    token_a: Pubkey,
+    token_b: Pubkey,
+    ui_amount: f64,
+    slippage: u64,
+) -> CommandResult {
+    let pool = get_pool(&config.rpc_client, &config.swap_program_id, &pool_address)?;
+    let pool_token_amount = spl_token::ui_amount_to_amount(ui_amount, pool.pool_mint.mint.decimals);
+    let withdraw_fee = if pool_account == *pool.swap.pool_fee_account() {
+        0
+    } else {
+        pool.swap
+            .fees()
+            .owner_withdraw_fee(u128::from(pool_token_amount))
+            .ok_or("Withdraw fee calculation failed")?
+    };
+    let results = pool
+        .swap
+        .swap_curve()
+        .calculator
+        .pool_tokens_to_trading_tokens(
+            u128::from(pool_token_amount) - withdraw_fee,
+            u128::from(pool.pool_mint.mint.supply),
+            u128::from(pool.token_a.account.amount),
+            u128::from(pool.token_b.account.amount),
+            RoundDirection::Floor,
+        )
+        .ok_or("Pool token amount is too large for the pool")?;
+    let minimum_token_a_amount =
+        minimum_with_slippage(u64::try_from(results.token_a_amount)?, slippage)?;
+    let minimum_token_b_amount =
+        minimum_with_slippage(u64::try_from(results.token_b_amount)?, slippage)?;
+    println!(
+        "Withdrawing at least {} token A and {} token B for {} pool tokens",
+        amount_to_ui(minimum_token_a_amount, &pool.token_a_mint),
+        amount_to_ui(minimum_token_b_amount, &pool.token_b_mint),
+        ui_amount,
+    );
+
+    let instruction = withdraw_all_token_types(
+        &config.swap_program_id,
+        &pool.pool_mint.program_id,
+        &pool.token_a_mint.program_id,
+        &pool.token_b_mint.program_id,
+        &pool.address,
+        &pool.authority,
+        &config.owner.pubkey(),
+        &pool.pool_mint.address,
+        pool.swap.pool_fee_account(),
+        &pool_account,
+        &pool.token_a.address,
+        &pool.token_b.address,
+        &token_a,
+        &token_b,
+        &pool.token_a_mint.address,
+        &pool.token_b_mint.address,
+        WithdrawAllTokenTypes {
+            pool_token_amount,
+            minimum_token_a_amount,
+            minimum_token_b_amount,
+        },
+    )?;
+    send_owner_instructions(config, &[instruction])
+}
+
+fn command_deposit_single(
+    config: &Config,
+    pool_address: Pubkey,
+    source: Pubkey,
+    pool_account: Pubkey,
+    ui_amount: f64,
+    slippage: u64,
+) -> CommandResult {
+    let pool = get_pool(&config.rpc_client, &config.swap_program_id, &pool_address)?;
+    let source_account = get_token_account(&config.rpc_client, &source)?;
+    let (trade_direction, source_mint) = trade_direction_of(&pool, &source_account.account.mint)?;
+    let source_token_amount = spl_token::ui_amount_to_amount(ui_amount, source_mint.mint.decimals);
+    let quote = quoter(&pool).quote_deposit_single_token_type_exact_amount_in(
+        source_token_amount,
+        trade_direction,
+        slippage,
+    )?;
+    println!(
+        "Depositing {} for at least {} pool tokens",
+        ui_amount,
+        amount_to_ui(quote.minimum_pool_token_amount, &pool.pool_mint),
+    );
+    if config.verbose {
+        println!("{:#?}", quote);
+    }
+
+    let instruction = deposit_single_token_type_exact_amount_in(
+        &config.swap_program_id,
+        &source_mint.program_id,
+        &pool.pool_mint.program_id,
+        &pool.address,
+        &pool.authority,
+        &config.owner.pubkey(),
+        &source,
+        &pool.token_a.address,
+        &pool.token_b.address,
+        &pool.pool_mint.address,
+        &pool_account,
+        &source_mint.address,
+        DepositSingleTokenTypeExactAmountIn {
+            source_token_amount,
+            minimum_pool_token_amount: quote.minimum_pool_token_amount,
+        },
+    )?;
+    send_owner_instructions(config, &[instruction])
+}
+
+fn command_withdraw_single(
+    config: &Config,
+    pool_address: Pubkey,
+    pool_account: Pubkey,
+    destination: Pubkey,
+    ui_amount: f64,
+    slippage: u64,
+) -> CommandResult {
+    let pool = get_pool(&config.rpc_client, &config.swap_program_id, &pool_address)?;
+    let destination_account = get_token_account(&config.rpc_client, &destination)?;
+    let (trade_direction, destination_mint) =
+        trade_direction_of(&pool, &destination_account.account.mint)?;
+    let destination_token_amount =
+        spl_token::ui_amount_to_amount(ui_amount, destination_mint.mint.decimals);
+    let mut quote = quoter(&pool).quote_withdraw_single_token_type_exact_amount_out(
+        destination_token_amount,
+        trade_direction,
+        slippage,
+    )?;
+    if pool_account == *pool.swap.pool_fee_account() {
+        // no withdraw fee is charged to the pool fee account
+        quote.pool_token_amount -= quote.withdraw_fee;
+        quote.maximum_pool_token_amount = maximum_with_slippage(quote.pool_token_amount, slippage)?;
+    }
+    println!(
+        "Withdrawing {} for at most {} pool tokens",
+        ui_amount,
+        amount_to_ui(quote.maximum_pool_token_amount, &pool.pool_mint),
+    );
+    if config.verbose {
+        println!("{:#?}", quote);
+    }
+
+    let instruction = withdraw_single_token_type_exact_amount_out(
+        &config.swap_program_id,
+        &pool.pool_mint.program_id,
+        &destination_mint.program_id,
+        &pool.address,
+        &pool.authority,
+        &config.owner.pubkey(),
+        &pool.pool_mint.address,
+        pool.swap.pool_fee_account(),
+        &pool_account,
+        &pool.token_a.address,
+        &pool.token_b.address,
+        &destination,
+        &destination_mint.address,
+        WithdrawSingleTokenTypeExactAmountOut {
+            destination_token_amount,
+            maximum_pool_token_amount: quote.maximum_pool_token_amount,
+        },
+    )?;
+    send_owner_instructions(config, &[instruction])
+}
+
+fn command_swap(
+    config: &Config,
+    pool_address: Pubkey,
+    source: Pubkey,
+    destination: Pubkey,
+    ui_amount: f64,
+    slippage: u64,
+    host_fee_account: Option<Pubkey>,
+) -> CommandResult {
+    let pool = get_pool(&config.rpc_client, &config.swap_program_id, &pool_address)?;
+    let source_account = get_token_account(&config.rpc_client, &source)?;
+    let (trade_direction, source_mint) = trade_direction_of(&pool, &source_account.account.mint)?;
+    let (swap_source, swap_destination, destination_mint) = match trade_direction {
+        TradeDirection::AtoB => (&pool.token_a, &pool.token_b, &pool.token_b_mint),
+        TradeDirection::BtoA => (&pool.token_b, &pool.token_a, &pool.token_a_mint),
+    };
+    let amount_in = spl_token::ui_amount_to_amount(ui_amount, source_mint.mint.decimals);
+    let quote = quoter(&pool).quote_swap(amount_in, trade_direction, slippage)?;
+    println!(
+        "Swapping {} for at least {}, with a price impact of {:.2}%",
+        amount_to_ui(quote.amount_in, source_mint),
+        amount_to_ui(quote.minimum_amount_out, destination_mint),
+        quote.price_impact_bps as f64 / 100.0,
+    );
+    if config.verbose {
+        println!("{:#?}", quote);
+    }
+
+    let instruction = swap(
+        &config.swap_program_id,
+        &source_mint.program_id,
+        &destination_mint.program_id,
+        &pool.pool_mint.program_id,
+        &pool.address,
+        &pool.authority,
+        &config.owner.pubkey(),
+        &source,
+        &swap_source.address,
+        &swap_destination.address,
+        &destination,
+        &pool.pool_mint.address,
+        pool.swap.pool_fee_account(),
+        &source_mint.address,
+        &destination_mint.address,
+        host_fee_account.as_ref(),
+        Swap {
+            amount_in,
+            minimum_amount_out: quote.minimum_amount_out,
+        },
+    )?;
+    send_owner_instructions(config, &[instruction])
+}
+
+fn command_show(config: &Config, pool_address: Pubkey) -> CommandResult {
+    if is_concentrated_pool(&config.rpc_client, &pool_address)? {
+        return command_show_concentrated_pool(config, pool_address);
+    }
+    let pool = get_pool(&config.rpc_client, &config.swap_program_id, &pool_address)?;
+    let swap_curve = pool.swap.swap_curve();
+    let fees = pool.swap.fees();
+    println!("Swap pool: {}", pool.address);
+    println!("Authority: {}", pool.authority);
+    println!("Curve: {}", curve_name(swap_curve));
+    println!(
+        "Paused: {}",
+        match pool.swap.pause_authority() {
+            Some(pause_authority) => format!(
+                "{}, pause authority {}",
+                pool.swap.is_paused(),
+                pause_authority
+            ),
+            None => "false, cannot be paused".to_string(),
+        }
+    );
+    println!(
+        "Token A: {} ({}), mint {}",
+        amount_to_ui(pool.token_a.account.amount, &pool.token_a_mint),
+        pool.token_a.address,
+        pool.token_a_mint.address,
+    );
+    println!(
+        "Token B: {} ({}), mint {}",
+        amount_to_ui(pool.token_b.account.amount, &pool.token_b_mint),
+        pool.token_b.address,
+        pool.token_b_mint.address,
+    );
+    println!(
+        "Pool token supply: {}, mint {}",
+        amount_to_ui(pool.pool_mint.mint.supply, &pool.pool_mint),
+        pool.pool_mint.address,
+    );
+    println!("Pool fee account: {}", pool.swap.pool_fee_account());
+    if let Some(price) = implied_price(&pool) {
+        println!("Price: {} token B per token A", price);
+        if price > 0.0 {
+            println!("Price: {} token A per token B", 1.0 / price);
+        }
+    }
+    print_fees(fees, &pool.token_a_mint, &pool.token_b_mint);
+    Ok(())
+}
+
+fn command_show_concentrated_pool(config: &Config, pool_address: Pubkey) -> CommandResult {
+    let pool = get_concentrated_pool(&config.rpc_client, &config.swap_program_id, &pool_address)?;
+    println!("Concentrated liquidity pool: {}", pool.address);
+    println!("Authority: {}", pool.authority);
+    println!("Fee authority: {}", pool.pool.fee_authority);
+    println!(
+        "Paused: {}, pause authority {}",
+        pool.pool.is_paused, pool.pool.pause_authority
+    );
+    println!(
+        "Token A: {} ({}), mint {}",
+        amount_to_ui(pool.token_a.account.amount, &pool.token_a_mint),
+        pool.token_a.address,
+        pool.token_a_mint.address,
+    );
+    println!(
+        "Token B: {} ({}), mint {}",
+        amount_to_ui(pool.token_b.account.amount, &pool.token_b_mint),
+        pool.token_b.address,
+        pool.token_b_mint.address,
+    );
+    println!(
+        "Price: {} token B per token A, tick {}",
+        price_of(
+            pool.pool.sqrt_price,
+            pool.token_a_mint.mint.decimals,
+            pool.token_b_mint.mint.decimals,
+        ),
+        pool.pool.current_tick,
+    );
+    println!("Active liquidity: {}", pool.pool.liquidity);
+    println!(
+        "Tick spacing: {}, {} of {} ticks initialized",
+        pool.pool.tick_spacing,
+        pool.pool.ticks.len(),
+        MAX_TICKS,
+    );
+    println!(
+        "Minimum position liquidity: {}",
+        pool.pool.min_position_liquidity
+    );
+    println!(
+        "Uncollected owner fees: {} token A, {} token B",
+        amount_to_ui(pool.pool.protocol_fees_a, &pool.token_a_mint),
+        amount_to_ui(pool.pool.protocol_fees_b, &pool.token_b_mint),
+    );
+    print_fees(&pool.pool.fees, &pool.token_a_mint, &pool.token_b_mint);
+    Ok(())
+}
+
+fn command_validate(
+    config: &Config,
+    pool_address: Pubkey,
+    arg_matches: &ArgMatches<'_>,
+) -> CommandResult {
+    let fee_owner = pubkey_of(arg_matches, "fee_owner").map(|owner| owner.to_string());
+    let curve_types = arg_matches.values_of("curve").map(|curves| {
+        curves
+            .map(|curve| match curve {
+                "constant-product" => CurveType::ConstantProduct,
+                "constant-price" => CurveType::ConstantPrice,
+                "offset" => CurveType::Offset,
+                _ => CurveType::ConcentratedLiquidity,
+            })
+            .collect::<Vec<_>>()
+    });
+    let fees = fees_of(arg_matches);
+    let swap_constraints = match (&fee_owner, &curve_types, &SWAP_CONSTRAINTS) {
+        (Some(owner_key), Some(valid_curve_types), _) => SwapConstraints {
+            owner_key,
+            valid_curve_types,
+            fees: &fees,
+        },
+        (None, None, Some(swap_constraints)) => SwapConstraints {
+            owner_key: swap_constraints.owner_key,
+            valid_curve_types: swap_constraints.valid_curve_types,
+            fees: swap_constraints.fees,
+        },
+        _ => {
+            return Err(
+                "Both --fee-owner and --curve are required, unless built with the program's production constraints"
+                    .into(),
+            )
+        }
+    };
+
+    let checks = if is_concentrated_pool(&config.rpc_client, &pool_address)? {
+        let pool =
+            get_concentrated_pool(&config.rpc_client, &config.swap_program_id, &pool_address)?;
+        let fee_authority = pool.pool.fee_authority.to_string();
+        [
+            (
+                "Curve type",
+                swap_constraints
+                    .validate_curve_type(CurveType::ConcentratedLiquidity)
+                    .map_err(|err| err.to_string()),
+            ),
+            (
+                "Fees",
+                swap_constraints
+                    .validate_fees(&pool.pool.fees)
+                    .map_err(|err| err.to_string()),
+            ),
+            (
+                "Fee authority",
+                if fee_authority == swap_constraints.owner_key {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "{}, expected {}",
+                        fee_authority, swap_constraints.owner_key
+                    ))
+                },
+            ),
+        ]
+    } else {
+        let pool = get_pool(&config.rpc_client, &config.swap_program_id, &pool_address)?;
+        let fee_account_owner = pool
+            .pool_fee_account
+            .as_ref()
+            .map(|account| account.account.owner.to_string());
+        [
+            (
+                "Curve type",
+                swap_constraints
+                    .validate_curve(pool.swap.swap_curve())
+                    .map_err(|err| err.to_string()),
+            ),
+            (
+                "Fees",
+                swap_constraints
+                    .validate_fees(pool.swap.fees())
+                    .map_err(|err| err.to_string()),
+            ),
+            (
+                "Pool fee account owner",
+                match fee_account_owner {
+                    Some(owner) if owner == swap_constraints.owner_key => Ok(()),
+                    Some(owner) => Err(format!(
+                        "owned by {}, expected {}",
+                        owner, swap_constraints.owner_key
+                    )),
+                    None => Err("pool fee account does not exist".to_string()),
+                },
+            ),
+        ]
+    };
+    let mut valid = true;
+    for (name, result) in checks {
+        match result {
+            Ok(()) => println!("{}: ok", name),
+            Err(err) => {
+                valid = false;
+                println!("{}: invalid, {}", name, err);
+            }
+        }
+    }
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("Swap pool {} does not meet the constraints", pool_address).into())
+    }
+}
+
+// HELPERS
+
+fn check_fee_payer_balance(config: &Config, required_balance: u64) -> Result<(), Error> {
+    let balance = config.rpc_client.get_balance(&config.fee_payer.pubkey())?;
+    if balance < required_balance {
+        Err(format!(
+            "Fee payer, {}, has insufficient balance: {} required, {} available",
+            config.fee_payer.pubkey(),
+            lamports_to_sol(required_balance),
+            lamports_to_sol(balance)
+        )
+        .into())
+    } else {
+        Ok(())
+    }
+}
+
+fn send_transaction(
+    config: &Config,
+    transaction: Transaction,
+) -> solana_client::client_error::Result<()> {
+    if config.dry_run {
+        let result = config.rpc_client.simulate_transaction(&transaction)?;
+        println!("Simulate result: {:?}", result);
+    } else {
+        let signature = config
+            .rpc_client
+            .send_and_confirm_transaction_with_spinner(&transaction)?;
+        println!("Signature: {}", signature);
+    }
+    Ok(())
+}
+
+fn send_owner_instructions(config: &Config, instructions: &[Instruction]) -> CommandResult {
+    let recent_blockhash = config.rpc_client.get_latest_blockhash()?;
+    let message = Message::new_with_blockhash(
+        instructions,
+        Some(&config.fee_payer.pubkey()),
+        &recent_blockhash,
+    );
+    check_fee_payer_balance(config, config.rpc_client.get_fee_for_message(&message)?)?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+    unique_signers!(signers);
+    let transaction = Transaction::new(&signers, message, recent_blockhash);
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
+fn quoter(pool: &Pool) -> PoolQuoter {
+    PoolQuoter {
+        swap: pool.swap.as_ref(),
+        token_a_amount: pool.token_a.account.amount,
+        token_b_amount: pool.token_b.account.amount,
+        pool_token_supply: pool.pool_mint.mint.supply,
+        token_a_transfer_fee: pool.token_a_mint.transfer_fee.as_ref(),
+        token_b_transfer_fee: pool.token_b_mint.transfer_fee.as_ref(),
+    }
+}
+
+/// Direction of a trade starting from the given mint, along with the mint
+fn trade_direction_of<'a>(
+    pool: &'a Pool,
+    mint: &Pubkey,
+) -> Result<(TradeDirection, &'a TokenMint), Error> {
+    if *mint == pool.token_a_mint.address {
+        Ok((TradeDirection::AtoB, &pool.token_a_mint))
+    } else if *mint == pool.token_b_mint.address {
+        Ok((TradeDirection::BtoA, &pool.token_b_mint))
+    } else {
+        Err(format!("Mint {} is not traded by swap pool {}", mint, pool.address).into())
+    }
+}
+
+fn transfer_fee(mint: &TokenMint, amount: u64) -> Result<u64, Error> {
+    Ok(mint
+        .transfer_fee
+        .map_or(Some(0), |transfer_fee| transfer_fee.calculate_fee(amount))
+        .ok_or("Transfer fee calculation failed")?)
+}
+
+fn amount_to_ui(amount: u64, mint: &TokenMint) -> f64 {
+    spl_token::amount_to_ui_amount(amount, mint.mint.decimals)
+}
+
+/// Marginal price of token A, in token B, adjusted for the mint decimals
+fn implied_price(pool: &Pool) -> Option<f64> {
+    const PRECISION: u128 = 1_000_000_000_000;
+    let spot_price = pool.swap.swap_curve().calculator.spot_price(
+        u128::from(pool.token_a.account.amount),
+        u128::from(pool.token_b.account.amount),
+        TradeDirection::AtoB,
+    )?;
+    let scaled_price = spot_price
+        .checked_mul(&spl_math::precise_number::PreciseNumber::new(PRECISION)?)?
+        .to_imprecise()?;
+    let decimals =
+        i32::from(pool.token_a_mint.mint.decimals) - i32::from(pool.token_b_mint.mint.decimals);
+    Some(scaled_price as f64 / PRECISION as f64 * 10f64.powi(decimals))
+}
+
+fn curve_name(swap_curve: &SwapCurve) -> String {
+    match swap_curve.curve_type {
+        CurveType::ConstantProduct => "constant product".to_string(),
+        CurveType::ConstantPrice => {
+            let mut packed = [0u8; 32];
+            swap_curve.calculator.pack_into_slice(&mut packed);
+            let curve = ConstantPriceCurve::unpack_from_slice(&packed).ok();
+            format!(
+                "constant price, {} token A per token B",
+                curve.map_or(0, |curve| curve.token_b_price)
+            )
+        }
+        CurveType::Offset => {
+            let mut packed = [0u8; 32];
+            swap_curve.calculator.pack_into_slice(&mut packed);
+            let curve = OffsetCurve::unpack_from_slice(&packed).ok();
+            format!(
+                "offset, {} token B offset",
+                curve.map_or(0, |curve| curve.token_b_offset)
+            )
+        }
+        CurveType::ConcentratedLiquidity => "concentrated liquidity".to_string(),
+    }
+}
+
+fn amount_with_transfer_fee(mint: &TokenMint, amount: u64) -> Result<u64, Error> {
+    let fee = mint
+        .transfer_fee
+        .map_or(Some(0), |transfer_fee| {
+            transfer_fee.calculate_inverse_fee(amount)
+        })
+        .ok_or("Transfer fee calculation failed")?;
+    Ok(amount + fee)
+}
+
+fn print_fees(fees: &Fees, token_a_mint: &TokenMint, token_b_mint: &TokenMint) {
+    println!("Fees:");
+    println!(
+        "  Trade fee: {}/{}",
+        fees.trade_fee_numerator, fees.trade_fee_denominator
+    );
+    println!(
+        "  Owner trade fee: {}/{}",
+        fees.owner_trade_fee_numerator, fees.owner_trade_fee_denominator
+    );
+    println!(
+        "  Owner withdraw fee: {}/{}",
+        fees.owner_withdraw_fee_numerator, fees.owner_withdraw_fee_denominator
+    );
+    println!(
+        "  Host fee: {}/{}",
+        fees.host_fee_numerator, fees.host_fee_denominator
+    );
+    for (name, mint) in [("A", token_a_mint), ("B", token_b_mint)] {
+        if let Some(transfer_fee) = mint.transfer_fee {
+            println!(
+                "  Token {} transfer fee: {} basis points, maximum {}",
+                name,
+                u16::from(transfer_fee.transfer_fee_basis_points),
+                amount_to_ui(u64::from(transfer_fee.maximum_fee), mint),
+            );
+        }
+    }
+}
+
+/// Q64.64 square root price of token A in token B, from a price adjusted for
+/// the mint decimals
+fn sqrt_price_of(price: f64, token_a_decimals: u8, token_b_decimals: u8) -> Option<u128> {
+    let decimals = i32::from(token_b_decimals) - i32::from(token_a_decimals);
+    let sqrt_price = ((price * 10f64.powi(decimals)).sqrt() * Q64) as u128;
+    // the pool only accepts prices with a tick
+    tick_at_sqrt_price(sqrt_price).map(|_| sqrt_price)
+}
+
+/// Price of token A in token B, adjusted for the mint decimals, from a Q64.64
+/// square root price
+fn price_of(sqrt_price: u128, token_a_decimals: u8, token_b_decimals: u8) -> f64 {
+    let decimals = i32::from(token_a_decimals) - i32::from(token_b_decimals);
+    let sqrt_price = sqrt_price as f64 / Q64;
+    sqrt_price * sqrt_price * 10f64.powi(decimals)
+}
+
+/// Amounts of token A and B held by `liquidity` between two ticks, at the
+/// pool's current price
+fn position_amounts(
+    pool: &ConcentratedPool,
+    tick_lower: i32,
+    tick_upper: i32,
+    liquidity: u128,
+    round_direction: RoundDirection,
+) -> Result<TradingTokenResult, Error> {
+    let sqrt_price_lower = sqrt_price_at_tick(tick_lower).ok_or("Invalid lower tick")?;
+    let sqrt_price_upper = sqrt_price_at_tick(tick_upper).ok_or("Invalid upper tick")?;
+    Ok(amounts_for_liquidity(
+        pool.sqrt_price,
+        sqrt_price_lower,
+        sqrt_price_upper,
+        liquidity,
+        round_direction,
+    )
+    .ok_or("Liquidity is too large for the position")?)
+}
+
+fn is_fraction(string: String) -> Result<(), String> {
+    fraction_of(&string)
+        .map(|_| ())
+        .ok_or_else(|| format!("Unable to parse fraction, provided: {}", string))
+}
+
+fn fraction_of(value: &str) -> Option<(u64, u64)> {
+    let (numerator, denominator) = value.split_once('/')?;
+    Some((numerator.parse().ok()?, denominator.parse().ok()?))
+}
+
+fn fees_of(matches: &ArgMatches<'_>) -> Fees {
+    let fee = |name| fraction_of(matches.value_of(name).unwrap()).unwrap();
+    let (trade_fee_numerator, trade_fee_denominator) = fee("trade_fee");
+    let (owner_trade_fee_numerator, owner_trade_fee_denominator) = fee("owner_trade_fee");
+    let (owner_withdraw_fee_numerator, owner_withdraw_fee_denominator) = fee("owner_withdraw_fee");
+    let (host_fee_numerator, host_fee_denominator) = fee("host_fee");
+    Fees {
+        trade_fee_numerator,
+        trade_fee_denominator,
+        owner_trade_fee_numerator,
+        owner_trade_fee_denominator,
+        owner_withdraw_fee_numerator,
+        owner_withdraw_fee_denominator,
+        host_fee_numerator,
+        host_fee_denominator,
+    }
+}
+
+fn swap_curve_of(matches: &ArgMatches<'_>) -> SwapCurve {
+    match matches.value_of("curve").unwrap() {
+        "constant-price" => SwapCurve {
+            curve_type: CurveType::ConstantPrice,
+            calculator: Arc::new(ConstantPriceCurve {
+                token_b_price: value_of(matches, "token_b_price").unwrap(),
+            }),
+        },
+        "offset" => SwapCurve {
+            curve_type: CurveType::Offset,
+            calculator: Arc::new(OffsetCurve {
+                token_b_offset: value_of(matches, "token_b_offset").unwrap(),
+            }),
+        },
+        _ => SwapCurve {
+            curve_type: CurveType::ConstantProduct,
+            calculator: Arc::new(ConstantProductCurve),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_fraction() {
+        assert_eq!(fraction_of("25/10000"), Some((25, 10000)));
+        assert_eq!(fraction_of("0/0"), Some((0, 0)));
+        assert_eq!(fraction_of("25"), None);
+        assert_eq!(fraction_of("25/"), None);
+        assert_eq!(fraction_of("/10000"), None);
+        assert_eq!(fraction_of("-1/100"), None);
+        assert_eq!(fraction_of("1/2/3"), None);
+        assert_eq!(fraction_of("0.5/1"), None);
+        assert!(is_fraction("1/100".to_string()).is_ok());
+        assert!(is_fraction("1%".to_string()).is_err());
+    }
+
+    #[test]
+    fn parse_fees() {
+        let app = App::new("test")
+            .arg(fee_arg("trade_fee", "trade-fee", "25/10000", ""))
+            .arg(fee_arg("owner_trade_fee", "owner-trade-fee", "5/10000", ""))
+            .arg(fee_arg(
+                "owner_withdraw_fee",
+                "owner-withdraw-fee",
+                "0/0",
+                "",
+            ))
+            .arg(fee_arg("host_fee", "host-fee", "20/100", ""));
+
+        let matches =
+            app.clone()
+                .get_matches_from(["test", "--trade-fee", "3/1000", "--host-fee", "1/5"]);
+        assert_eq!(
+            fees_of(&matches),
+            Fees {
+                trade_fee_numerator: 3,
+                trade_fee_denominator: 1000,
+                owner_trade_fee_numerator: 5,
+                owner_trade_fee_denominator: 10000,
+                owner_withdraw_fee_numerator: 0,
+                owner_withdraw_fee_denominator: 0,
+                host_fee_numerator: 1,
+                host_fee_denominator: 5,
+            }
+        );
+
+        assert!(app
+            .get_matches_from_safe(["test", "--trade-fee", "3"])
+            .is_err());
+    }
+
+    #[test]
+    fn price_conversions() {
+        assert_eq!(sqrt_price_of(1.0, 6, 6), Some(1 << 64));
+        assert_eq!(sqrt_price_of(4.0, 6, 6), Some(2 << 64));
+        // one token A with 9 decimals buys 100 token B with 6 decimals, so
+        // one base unit of token A is worth 0.1 base units of token B
+        let sqrt_price = sqrt_price_of(100.0, 9, 6).unwrap();
+        assert!((price_of(sqrt_price, 9, 6) - 100.0).abs() < 1e-9);
+        assert!((price_of(sqrt_price, 6, 6) - 0.1).abs() < 1e-12);
+
+        assert_eq!(sqrt_price_of(0.0, 6, 6), None);
+        assert_eq!(sqrt_price_of(-1.0, 6, 6), None);
+        assert_eq!(sqrt_price_of(f64::NAN, 6, 6), None);
+        assert_eq!(sqrt_price_of(1e40, 6, 6), None);
+    }
+}
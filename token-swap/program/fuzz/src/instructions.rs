@@ -2,6 +2,7 @@
 use {
     arbitrary::Arbitrary,
     honggfuzz::fuzz,
+    solana_program::{program_error::ProgramError, program_pack::Pack},
     spl_math::precise_number::PreciseNumber,
     spl_token::error::TokenError,
     spl_token_swap::{
@@ -15,12 +16,15 @@ use {
         },
         error::SwapError,
         instruction::{
-            DepositAllTokenTypes, DepositSingleTokenTypeExactAmountIn, Swap, WithdrawAllTokenTypes,
-            WithdrawSingleTokenTypeExactAmountOut,
+            DecreaseLiquidity, DepositAllTokenTypes, DepositSingleTokenTypeExactAmountIn,
+            IncreaseLiquidity, OpenPosition, SetPauseAuthority, SetPaused, Swap,
+            WithdrawAllTokenTypes, WithdrawSingleTokenTypeExactAmountOut,
         },
+        state::Position,
     },
     spl_token_swap_fuzz::{
         native_account_data::NativeAccountData,
+        native_concentrated_pool::NativeConcentratedPool,
         native_token::{get_token_balance, transfer},
         native_token_swap::NativeTokenSwap,
     },
@@ -68,6 +72,32 @@ enum FuzzInstruction {
         pool_token_id: AccountId,
         instruction: WithdrawSingleTokenTypeExactAmountOut,
     },
+    SetPaused {
+        instruction: SetPaused,
+    },
+    SetPauseAuthority {
+        instruction: SetPauseAuthority,
+    },
+    OpenPosition {
+        position_id: AccountId,
+        instruction: OpenPosition,
+    },
+    IncreaseLiquidity {
+        position_id: AccountId,
+        token_a_id: AccountId,
+        token_b_id: AccountId,
+        instruction: IncreaseLiquidity,
+    },
+    DecreaseLiquidity {
+        position_id: AccountId,
+        token_a_id: AccountId,
+        token_b_id: AccountId,
+        instruction: DecreaseLiquidity,
+    },
+    ClosePosition {
+        position_id: AccountId,
+    },
+    CollectProtocolFees,
 }
 
 /// Use u8 as an account id to simplify the address space and re-use accounts
@@ -80,6 +110,15 @@ const INITIAL_SWAP_TOKEN_B_AMOUNT: u64 = 300_000_000_000;
 const INITIAL_USER_TOKEN_A_AMOUNT: u64 = 1_000_000_000;
 const INITIAL_USER_TOKEN_B_AMOUNT: u64 = 3_000_000_000;
 
+const TICK_SPACING: u16 = 10;
+/// Price of 1, as a Q64.64 square root
+const INITIAL_SQRT_PRICE: u128 = 1 << 64;
+const MIN_POSITION_LIQUIDITY: u128 = 1_000;
+/// Keeps fuzzed positions close enough to the price to be swapped through
+const MAX_POSITION_TICK_OFFSET: i32 = 200;
+/// Keeps fuzzed liquidity within reach of the user token balances
+const MAX_POSITION_LIQUIDITY: u128 = 100_000_000_000;
+
 fn main() {
     loop {
         fuzz!(|fuzz_data: FuzzData| { run_fuzz(fuzz_data) });
@@ -105,7 +144,11 @@ fn run_fuzz(fuzz_data: FuzzData) {
         host_fee_numerator,
         host_fee_denominator,
     };
-    let swap_curve = get_swap_curve(fuzz_data.curve_type);
+    // concentrated liquidity pools do not use a swap curve
+    let Some(swap_curve) = get_swap_curve(fuzz_data.curve_type) else {
+        run_concentrated_fuzz(fees, fuzz_data.instructions);
+        return;
+    };
     let mut token_swap = NativeTokenSwap::new(
         fees,
         swap_curve.clone(),
@@ -160,6 +203,23 @@ fn run_fuzz(fuzz_data: FuzzData) {
                 TradeDirection::AtoB => (Some(token_account_id), None, Some(pool_token_id)),
                 TradeDirection::BtoA => (None, Some(token_account_id), Some(pool_token_id)),
             },
+
+            FuzzInstruction::IncreaseLiquidity {
+                token_a_id,
+                token_b_id,
+                ..
+            }
+            | FuzzInstruction::DecreaseLiquidity {
+                token_a_id,
+                token_b_id,
+                ..
+            } => (Some(token_a_id), Some(token_b_id), None),
+
+            FuzzInstruction::SetPaused { .. }
+            | FuzzInstruction::SetPauseAuthority { .. }
+            | FuzzInstruction::OpenPosition { .. }
+            | FuzzInstruction::ClosePosition { .. }
+            | FuzzInstruction::CollectProtocolFees => (None, None, None),
         };
         if let Some(token_a_id) = token_a_id {
             token_a_accounts
@@ -378,6 +438,16 @@ fn run_fuzz_instruction(
                 instruction,
             )
         }
+        FuzzInstruction::SetPaused { instruction } => token_swap.set_paused(instruction),
+        FuzzInstruction::SetPauseAuthority { instruction } => {
+            token_swap.set_pause_authority(instruction)
+        }
+        // positions only exist in concentrated liquidity pools
+        FuzzInstruction::OpenPosition { .. }
+        | FuzzInstruction::IncreaseLiquidity { .. }
+        | FuzzInstruction::DecreaseLiquidity { .. }
+        | FuzzInstruction::ClosePosition { .. }
+        | FuzzInstruction::CollectProtocolFees => Ok(()),
     };
     result
         .map_err(|e| {
@@ -387,6 +457,272 @@ fn run_fuzz_instruction(
                 || e == SwapError::ExceededSlippage.into()
                 || e == SwapError::ZeroTradingTokens.into()
                 || e == SwapError::UnsupportedCurveOperation.into()
+                || e == SwapError::SwapPaused.into()
+                || e == TokenError::InsufficientFunds.into())
+            {
+                println!("{:?}", e);
+                panic!("{:?}", e)
+            }
+        })
+        .ok();
+}
+
+fn run_concentrated_fuzz(fees: Fees, fuzz_instructions: Vec<FuzzInstruction>) {
+    let mut pool = NativeConcentratedPool::new(
+        fees,
+        TICK_SPACING,
+        INITIAL_SQRT_PRICE,
+        MIN_POSITION_LIQUIDITY,
+    );
+
+    // keep track of all accounts, the pool starts out empty
+    let mut token_a_accounts: HashMap<AccountId, NativeAccountData> = HashMap::new();
+    let mut token_b_accounts: HashMap<AccountId, NativeAccountData> = HashMap::new();
+    let mut position_accounts: HashMap<AccountId, NativeAccountData> = HashMap::new();
+    let mut fee_token_a_account = pool.create_token_a_account(0);
+    let mut fee_token_b_account = pool.create_token_b_account(0);
+
+    // add all the token and position accounts that will be needed
+    for fuzz_instruction in &fuzz_instructions {
+        let (token_a_id, token_b_id, position_id) = match fuzz_instruction.clone() {
+            FuzzInstruction::Swap {
+                token_a_id,
+                token_b_id,
+                ..
+            } => (Some(token_a_id), Some(token_b_id), None),
+
+            FuzzInstruction::IncreaseLiquidity {
+                position_id,
+                token_a_id,
+                token_b_id,
+                ..
+            }
+            | FuzzInstruction::DecreaseLiquidity {
+                position_id,
+                token_a_id,
+                token_b_id,
+                ..
+            } => (Some(token_a_id), Some(token_b_id), Some(position_id)),
+
+            FuzzInstruction::OpenPosition { position_id, .. }
+            | FuzzInstruction::ClosePosition { position_id } => (None, None, Some(position_id)),
+
+            _ => (None, None, None),
+        };
+        if let Some(token_a_id) = token_a_id {
+            token_a_accounts
+                .entry(token_a_id)
+                .or_insert_with(|| pool.create_token_a_account(INITIAL_USER_TOKEN_A_AMOUNT));
+        }
+        if let Some(token_b_id) = token_b_id {
+            token_b_accounts
+                .entry(token_b_id)
+                .or_insert_with(|| pool.create_token_b_account(INITIAL_USER_TOKEN_B_AMOUNT));
+        }
+        if let Some(position_id) = position_id {
+            position_accounts
+                .entry(position_id)
+                .or_insert_with(|| pool.create_position_account());
+        }
+    }
+
+    // to ensure that we never create or remove base tokens
+    let before_total_token_a = token_a_accounts
+        .values()
+        .map(get_token_balance)
+        .sum::<u64>();
+    let before_total_token_b = token_b_accounts
+        .values()
+        .map(get_token_balance)
+        .sum::<u64>();
+
+    for fuzz_instruction in fuzz_instructions {
+        run_concentrated_fuzz_instruction(
+            fuzz_instruction,
+            &mut pool,
+            &mut token_a_accounts,
+            &mut token_b_accounts,
+            &mut position_accounts,
+            &mut fee_token_a_account,
+            &mut fee_token_b_account,
+        );
+    }
+
+    // check total token a and b amounts
+    let after_total_token_a = token_a_accounts
+        .values()
+        .map(get_token_balance)
+        .sum::<u64>()
+        + get_token_balance(&fee_token_a_account)
+        + get_token_balance(&pool.token_a_account);
+    assert_eq!(before_total_token_a, after_total_token_a);
+    let after_total_token_b = token_b_accounts
+        .values()
+        .map(get_token_balance)
+        .sum::<u64>()
+        + get_token_balance(&fee_token_b_account)
+        + get_token_balance(&pool.token_b_account);
+    assert_eq!(before_total_token_b, after_total_token_b);
+
+    // Final check to make sure that withdrawing everything works, even if the
+    // pool was left paused
+    let mut withdrawn_token_a_account = pool.create_token_a_account(0);
+    let mut withdrawn_token_b_account = pool.create_token_b_account(0);
+    for position_account in position_accounts.values_mut() {
+        let Ok(position) = Position::unpack(&position_account.data) else {
+            continue;
+        };
+        pool.decrease_liquidity(
+            position_account,
+            &mut withdrawn_token_a_account,
+            &mut withdrawn_token_b_account,
+            DecreaseLiquidity {
+                liquidity: position.liquidity,
+                minimum_token_a_amount: 0,
+                minimum_token_b_amount: 0,
+            },
+        )
+        .unwrap();
+        pool.close_position(position_account).unwrap();
+    }
+    pool.collect_protocol_fees(&mut fee_token_a_account, &mut fee_token_b_account)
+        .unwrap();
+
+    let after_total_token_a = token_a_accounts
+        .values()
+        .map(get_token_balance)
+        .sum::<u64>()
+        + get_token_balance(&withdrawn_token_a_account)
+        + get_token_balance(&fee_token_a_account)
+        + get_token_balance(&pool.token_a_account);
+    assert_eq!(before_total_token_a, after_total_token_a);
+    let after_total_token_b = token_b_accounts
+        .values()
+        .map(get_token_balance)
+        .sum::<u64>()
+        + get_token_balance(&withdrawn_token_b_account)
+        + get_token_balance(&fee_token_b_account)
+        + get_token_balance(&pool.token_b_account);
+    assert_eq!(before_total_token_b, after_total_token_b);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_concentrated_fuzz_instruction(
+    fuzz_instruction: FuzzInstruction,
+    pool: &mut NativeConcentratedPool,
+    token_a_accounts: &mut HashMap<AccountId, NativeAccountData>,
+    token_b_accounts: &mut HashMap<AccountId, NativeAccountData>,
+    position_accounts: &mut HashMap<AccountId, NativeAccountData>,
+    fee_token_a_account: &mut NativeAccountData,
+    fee_token_b_account: &mut NativeAccountData,
+) {
+    let result = match fuzz_instruction {
+        FuzzInstruction::Swap {
+            token_a_id,
+            token_b_id,
+            trade_direction,
+            instruction,
+        } => {
+            let token_a_account = token_a_accounts.get_mut(&token_a_id).unwrap();
+            let token_b_account = token_b_accounts.get_mut(&token_b_id).unwrap();
+            match trade_direction {
+                TradeDirection::AtoB => pool.swap(
+                    trade_direction,
+                    token_a_account,
+                    token_b_account,
+                    instruction,
+                ),
+                TradeDirection::BtoA => pool.swap(
+                    trade_direction,
+                    token_b_account,
+                    token_a_account,
+                    instruction,
+                ),
+            }
+        }
+        FuzzInstruction::OpenPosition {
+            position_id,
+            instruction,
+        } => {
+            let position_account = position_accounts.get_mut(&position_id).unwrap();
+            let tick_spacing = i32::from(TICK_SPACING);
+            pool.open_position(
+                position_account,
+                OpenPosition {
+                    tick_lower: instruction.tick_lower % MAX_POSITION_TICK_OFFSET * tick_spacing,
+                    tick_upper: instruction.tick_upper % MAX_POSITION_TICK_OFFSET * tick_spacing,
+                },
+            )
+        }
+        FuzzInstruction::IncreaseLiquidity {
+            position_id,
+            token_a_id,
+            token_b_id,
+            instruction,
+        } => {
+            let position_account = position_accounts.get_mut(&position_id).unwrap();
+            let token_a_account = token_a_accounts.get_mut(&token_a_id).unwrap();
+            let token_b_account = token_b_accounts.get_mut(&token_b_id).unwrap();
+            pool.increase_liquidity(
+                position_account,
+                token_a_account,
+                token_b_account,
+                IncreaseLiquidity {
+                    liquidity: instruction.liquidity % MAX_POSITION_LIQUIDITY,
+                    ..instruction
+                },
+            )
+        }
+        FuzzInstruction::DecreaseLiquidity {
+            position_id,
+            token_a_id,
+            token_b_id,
+            instruction,
+        } => {
+            let position_account = position_accounts.get_mut(&position_id).unwrap();
+            let token_a_account = token_a_accounts.get_mut(&token_a_id).unwrap();
+            let token_b_account = token_b_accounts.get_mut(&token_b_id).unwrap();
+            pool.decrease_liquidity(
+                position_account,
+                token_a_account,
+                token_b_account,
+                DecreaseLiquidity {
+                    liquidity: instruction.liquidity % MAX_POSITION_LIQUIDITY,
+                    ..instruction
+                },
+            )
+        }
+        FuzzInstruction::ClosePosition { position_id } => {
+            let position_account = position_accounts.get_mut(&position_id).unwrap();
+            pool.close_position(position_account)
+        }
+        FuzzInstruction::CollectProtocolFees => {
+            pool.collect_protocol_fees(fee_token_a_account, fee_token_b_account)
+        }
+        FuzzInstruction::SetPaused { instruction } => pool.set_paused(instruction),
+        FuzzInstruction::SetPauseAuthority { instruction } => pool.set_pause_authority(instruction),
+        // concentrated liquidity pools do not have pool tokens
+        FuzzInstruction::DepositAllTokenTypes { .. }
+        | FuzzInstruction::WithdrawAllTokenTypes { .. }
+        | FuzzInstruction::DepositSingleTokenTypeExactAmountIn { .. }
+        | FuzzInstruction::WithdrawSingleTokenTypeExactAmountOut { .. } => Ok(()),
+    };
+    result
+        .map_err(|e| {
+            if !(e == SwapError::CalculationFailure.into()
+                || e == SwapError::ConversionFailure.into()
+                || e == SwapError::FeeCalculationFailure.into()
+                || e == SwapError::ExceededSlippage.into()
+                || e == SwapError::ZeroTradingTokens.into()
+                || e == SwapError::SwapPaused.into()
+                || e == SwapError::InvalidInput.into()
+                || e == SwapError::InvalidTick.into()
+                || e == SwapError::TooManyTicks.into()
+                || e == SwapError::InsufficientLiquidity.into()
+                || e == SwapError::PositionLiquidityTooLow.into()
+                || e == SwapError::PositionNotEmpty.into()
+                || e == SwapError::AlreadyInUse.into()
+                || e == ProgramError::UninitializedAccount
                 || e == TokenError::InsufficientFunds.into())
             {
                 println!("{:?}", e);
@@ -407,6 +743,10 @@ fn get_total_token_a_amount(fuzz_instructions: &[FuzzInstruction]) -> u64 {
             FuzzInstruction::WithdrawAllTokenTypes { token_a_id, .. } => {
                 token_a_ids.insert(token_a_id)
             }
+            FuzzInstruction::IncreaseLiquidity { token_a_id, .. }
+            | FuzzInstruction::DecreaseLiquidity { token_a_id, .. } => {
+                token_a_ids.insert(token_a_id)
+            }
             FuzzInstruction::DepositSingleTokenTypeExactAmountIn {
                 token_account_id,
                 trade_direction,
@@ -423,6 +763,11 @@ fn get_total_token_a_amount(fuzz_instructions: &[FuzzInstruction]) -> u64 {
                 TradeDirection::AtoB => token_a_ids.insert(token_account_id),
                 _ => false,
             },
+            FuzzInstruction::SetPaused { .. }
+            | FuzzInstruction::SetPauseAuthority { .. }
+            | FuzzInstruction::OpenPosition { .. }
+            | FuzzInstruction::ClosePosition { .. }
+            | FuzzInstruction::CollectProtocolFees => false,
         };
     }
     (token_a_ids.len() as u64) * INITIAL_USER_TOKEN_A_AMOUNT
@@ -439,6 +784,10 @@ fn get_total_token_b_amount(fuzz_instructions: &[FuzzInstruction]) -> u64 {
             FuzzInstruction::WithdrawAllTokenTypes { token_b_id, .. } => {
                 token_b_ids.insert(token_b_id)
             }
+            FuzzInstruction::IncreaseLiquidity { token_b_id, .. }
+            | FuzzInstruction::DecreaseLiquidity { token_b_id, .. } => {
+                token_b_ids.insert(token_b_id)
+            }
             FuzzInstruction::DepositSingleTokenTypeExactAmountIn {
                 token_account_id,
                 trade_direction,
@@ -455,13 +804,18 @@ fn get_total_token_b_amount(fuzz_instructions: &[FuzzInstruction]) -> u64 {
                 TradeDirection::BtoA => token_b_ids.insert(token_account_id),
                 _ => false,
             },
+            FuzzInstruction::SetPaused { .. }
+            | FuzzInstruction::SetPauseAuthority { .. }
+            | FuzzInstruction::OpenPosition { .. }
+            | FuzzInstruction::ClosePosition { .. }
+            | FuzzInstruction::CollectProtocolFees => false,
         };
     }
     (token_b_ids.len() as u64) * INITIAL_USER_TOKEN_B_AMOUNT
 }
 
-fn get_swap_curve(curve_type: CurveType) -> SwapCurve {
-    SwapCurve {
+fn get_swap_curve(curve_type: CurveType) -> Option<SwapCurve> {
+    Some(SwapCurve {
         curve_type,
         calculator: match curve_type {
             CurveType::ConstantProduct => Arc::new(ConstantProductCurve),
@@ -471,6 +825,7 @@ fn get_swap_curve(curve_type: CurveType) -> SwapCurve {
             CurveType::Offset => Arc::new(OffsetCurve {
                 token_b_offset: 100_000_000_000,
             }),
+            CurveType::ConcentratedLiquidity => return None,
         },
-    }
+    })
 }
@@ -1,5 +1,6 @@
 #![allow(clippy::arithmetic_side_effects)]
 pub mod native_account_data;
+pub mod native_concentrated_pool;
 pub mod native_processor;
 pub mod native_token;
 pub mod native_token_swap;
@@ -0,0 +1,403 @@
+//! Helpers for working with concentrated liquidity pools in a fuzzing
+//! environment
+
+use {
+    crate::{
+        native_account_data::NativeAccountData, native_processor::do_process_instruction,
+        native_token, native_token_swap::create_program_account,
+    },
+    solana_program::{
+        entrypoint::ProgramResult, program_pack::Pack, pubkey::Pubkey, system_program,
+    },
+    spl_token_swap::{
+        curve::{calculator::TradeDirection, fees::Fees},
+        instruction::{
+            self, DecreaseLiquidity, IncreaseLiquidity, InitializeConcentratedPool, OpenPosition,
+            SetPauseAuthority, SetPaused, Swap,
+        },
+        state::{ConcentratedPool, Position},
+    },
+};
+
+pub struct NativeConcentratedPool {
+    pub user_account: NativeAccountData,
+    pub pause_authority_account: NativeAccountData,
+    pub authority_account: NativeAccountData,
+    pub pool_account: NativeAccountData,
+    pub token_a_account: NativeAccountData,
+    pub token_a_mint_account: NativeAccountData,
+    pub token_b_account: NativeAccountData,
+    pub token_b_mint_account: NativeAccountData,
+    pub token_program_account: NativeAccountData,
+}
+
+impl NativeConcentratedPool {
+    pub fn new(
+        fees: Fees,
+        tick_spacing: u16,
+        initial_sqrt_price: u128,
+        min_position_liquidity: u128,
+    ) -> Self {
+        let mut user_account = NativeAccountData::new(0, system_program::id());
+        user_account.is_signer = true;
+        let mut pool_account = NativeAccountData::new(ConcentratedPool::LEN, spl_token_swap::id());
+        let (authority_key, _bump_seed) = Pubkey::find_program_address(
+            &[&pool_account.key.to_bytes()[..]],
+            &spl_token_swap::id(),
+        );
+        let mut authority_account = create_program_account(authority_key);
+        let token_program_account = create_program_account(spl_token::id());
+
+        let mut token_a_mint_account = native_token::create_mint(&user_account.key);
+        let mut token_a_account =
+            native_token::create_token_account(&mut token_a_mint_account, &authority_key, 0);
+        let mut token_b_mint_account = native_token::create_mint(&user_account.key);
+        let mut token_b_account =
+            native_token::create_token_account(&mut token_b_mint_account, &authority_key, 0);
+
+        let init_instruction = instruction::initialize_concentrated_pool(
+            &spl_token_swap::id(),
+            &pool_account.key,
+            &authority_account.key,
+            &token_a_account.key,
+            &token_b_account.key,
+            &user_account.key,
+            InitializeConcentratedPool {
+                fees,
+                tick_spacing,
+                initial_sqrt_price,
+                min_position_liquidity,
+            },
+        )
+        .unwrap();
+
+        do_process_instruction(
+            init_instruction,
+            &[
+                pool_account.as_account_info(),
+                authority_account.as_account_info(),
+                token_a_account.as_account_info(),
+                token_b_account.as_account_info(),
+                user_account.as_account_info(),
+            ],
+        )
+        .unwrap();
+
+        Self {
+            pause_authority_account: user_account.clone(),
+            user_account,
+            authority_account,
+            pool_account,
+            token_a_account,
+            token_a_mint_account,
+            token_b_account,
+            token_b_mint_account,
+            token_program_account,
+        }
+    }
+
+    pub fn create_token_a_account(&mut self, amount: u64) -> NativeAccountData {
+        native_token::create_token_account(
+            &mut self.token_a_mint_account,
+            &self.user_account.key,
+            amount,
+        )
+    }
+
+    pub fn create_token_b_account(&mut self, amount: u64) -> NativeAccountData {
+        native_token::create_token_account(
+            &mut self.token_b_mint_account,
+            &self.user_account.key,
+            amount,
+        )
+    }
+
+    pub fn create_position_account(&self) -> NativeAccountData {
+        NativeAccountData::new(Position::LEN, spl_token_swap::id())
+    }
+
+    pub fn open_position(
+        &mut self,
+        position_account: &mut NativeAccountData,
+        instruction: OpenPosition,
+    ) -> ProgramResult {
+        let open_position_instruction = instruction::open_position(
+            &spl_token_swap::id(),
+            &self.pool_account.key,
+            &position_account.key,
+            &self.user_account.key,
+            instruction,
+        )
+        .unwrap();
+
+        do_process_instruction(
+            open_position_instruction,
+            &[
+                self.pool_account.as_account_info(),
+                position_account.as_account_info(),
+                self.user_account.as_account_info(),
+            ],
+        )
+    }
+
+    pub fn increase_liquidity(
+        &mut self,
+        position_account: &mut NativeAccountData,
+        token_a_account: &mut NativeAccountData,
+        token_b_account: &mut NativeAccountData,
+        instruction: IncreaseLiquidity,
+    ) -> ProgramResult {
+        let increase_liquidity_instruction = instruction::increase_liquidity(
+            &spl_token_swap::id(),
+            &spl_token::id(),
+            &spl_token::id(),
+            &self.pool_account.key,
+            &self.authority_account.key,
+            &position_account.key,
+            &self.user_account.key,
+            &self.user_account.key,
+            &token_a_account.key,
+            &token_b_account.key,
+            &self.token_a_account.key,
+            &self.token_b_account.key,
+            &self.token_a_mint_account.key,
+            &self.token_b_mint_account.key,
+            instruction,
+        )
+        .unwrap();
+
+        // the owner also signs the transfers
+        let mut user_transfer_account = self.user_account.clone();
+        let mut token_b_program_account = self.token_program_account.clone();
+        do_process_instruction(
+            increase_liquidity_instruction,
+            &[
+                self.pool_account.as_account_info(),
+                self.authority_account.as_account_info(),
+                position_account.as_account_info(),
+                self.user_account.as_account_info(),
+                user_transfer_account.as_account_info(),
+                token_a_account.as_account_info(),
+                token_b_account.as_account_info(),
+                self.token_a_account.as_account_info(),
+                self.token_b_account.as_account_info(),
+                self.token_a_mint_account.as_account_info(),
+                self.token_b_mint_account.as_account_info(),
+                self.token_program_account.as_account_info(),
+                token_b_program_account.as_account_info(),
+            ],
+        )
+    }
+
+    pub fn decrease_liquidity(
+        &mut self,
+        position_account: &mut NativeAccountData,
+        token_a_account: &mut NativeAccountData,
+        token_b_account: &mut NativeAccountData,
+        instruction: DecreaseLiquidity,
+    ) -> ProgramResult {
+        let decrease_liquidity_instruction = instruction::decrease_liquidity(
+            &spl_token_swap::id(),
+            &spl_token::id(),
+            &spl_token::id(),
+            &self.pool_account.key,
+            &self.authority_account.key,
+            &position_account.key,
+            &self.user_account.key,
+            &self.token_a_account.key,
+            &self.token_b_account.key,
+            &token_a_account.key,
+            &token_b_account.key,
+            &self.token_a_mint_account.key,
+            &self.token_b_mint_account.key,
+            instruction,
+        )
+        .unwrap();
+
+        let mut token_b_program_account = self.token_program_account.clone();
+        do_process_instruction(
+            decrease_liquidity_instruction,
+            &[
+                self.pool_account.as_account_info(),
+                self.authority_account.as_account_info(),
+                position_account.as_account_info(),
+                self.user_account.as_account_info(),
+                self.token_a_account.as_account_info(),
+                self.token_b_account.as_account_info(),
+                token_a_account.as_account_info(),
+                token_b_account.as_account_info(),
+                self.token_a_mint_account.as_account_info(),
+                self.token_b_mint_account.as_account_info(),
+                self.token_program_account.as_account_info(),
+                token_b_program_account.as_account_info(),
+            ],
+        )
+    }
+
+    pub fn swap(
+        &mut self,
+        trade_direction: TradeDirection,
+        source_account: &mut NativeAccountData,
+        destination_account: &mut NativeAccountData,
+        instruction: Swap,
+    ) -> ProgramResult {
+        let (pool_source_account, pool_destination_account, source_mint, destination_mint) =
+            match trade_direction {
+                TradeDirection::AtoB => (
+                    &mut self.token_a_account,
+                    &mut self.token_b_account,
+                    &mut self.token_a_mint_account,
+                    &mut self.token_b_mint_account,
+                ),
+                TradeDirection::BtoA => (
+                    &mut self.token_b_account,
+                    &mut self.token_a_account,
+                    &mut self.token_b_mint_account,
+                    &mut self.token_a_mint_account,
+                ),
+            };
+        let swap_instruction = instruction::concentrated_swap(
+            &spl_token_swap::id(),
+            &spl_token::id(),
+            &spl_token::id(),
+            &self.pool_account.key,
+            &self.authority_account.key,
+            &self.user_account.key,
+            &source_account.key,
+            &pool_source_account.key,
+            &pool_destination_account.key,
+            &destination_account.key,
+            &source_mint.key,
+            &destination_mint.key,
+            None,
+            instruction,
+        )
+        .unwrap();
+
+        let mut destination_program_account = self.token_program_account.clone();
+        do_process_instruction(
+            swap_instruction,
+            &[
+                self.pool_account.as_account_info(),
+                self.authority_account.as_account_info(),
+                self.user_account.as_account_info(),
+                source_account.as_account_info(),
+                pool_source_account.as_account_info(),
+                pool_destination_account.as_account_info(),
+                destination_account.as_account_info(),
+                source_mint.as_account_info(),
+                destination_mint.as_account_info(),
+                self.token_program_account.as_account_info(),
+                destination_program_account.as_account_info(),
+            ],
+        )
+    }
+
+    pub fn collect_protocol_fees(
+        &mut self,
+        token_a_account: &mut NativeAccountData,
+        token_b_account: &mut NativeAccountData,
+    ) -> ProgramResult {
+        let collect_instruction = instruction::collect_protocol_fees(
+            &spl_token_swap::id(),
+            &spl_token::id(),
+            &spl_token::id(),
+            &self.pool_account.key,
+            &self.authority_account.key,
+            &self.user_account.key,
+            &self.token_a_account.key,
+            &self.token_b_account.key,
+            &token_a_account.key,
+            &token_b_account.key,
+            &self.token_a_mint_account.key,
+            &self.token_b_mint_account.key,
+        )
+        .unwrap();
+
+        let mut token_b_program_account = self.token_program_account.clone();
+        do_process_instruction(
+            collect_instruction,
+            &[
+                self.pool_account.as_account_info(),
+                self.authority_account.as_account_info(),
+                self.user_account.as_account_info(),
+                self.token_a_account.as_account_info(),
+                self.token_b_account.as_account_info(),
+                token_a_account.as_account_info(),
+                token_b_account.as_account_info(),
+                self.token_a_mint_account.as_account_info(),
+                self.token_b_mint_account.as_account_info(),
+                self.token_program_account.as_account_info(),
+                token_b_program_account.as_account_info(),
+            ],
+        )
+    }
+
+    pub fn close_position(&mut self, position_account: &mut NativeAccountData) -> ProgramResult {
+        let close_position_instruction = instruction::close_position(
+            &spl_token_swap::id(),
+            &self.pool_account.key,
+            &position_account.key,
+            &self.user_account.key,
+            &self.user_account.key,
+        )
+        .unwrap();
+
+        // the owner also receives the rent
+        let mut destination_account = self.user_account.clone();
+        do_process_instruction(
+            close_position_instruction,
+            &[
+                self.pool_account.as_account_info(),
+                position_account.as_account_info(),
+                self.user_account.as_account_info(),
+                destination_account.as_account_info(),
+            ],
+        )
+    }
+
+    pub fn set_paused(&mut self, instruction: SetPaused) -> ProgramResult {
+        let set_paused_instruction = instruction::set_paused(
+            &spl_token_swap::id(),
+            &self.pool_account.key,
+            &self.pause_authority_account.key,
+            instruction,
+        )
+        .unwrap();
+
+        do_process_instruction(
+            set_paused_instruction,
+            &[
+                self.pool_account.as_account_info(),
+                self.pause_authority_account.as_account_info(),
+            ],
+        )
+    }
+
+    pub fn set_pause_authority(&mut self, instruction: SetPauseAuthority) -> ProgramResult {
+        let new_pause_authority = instruction.new_pause_authority;
+        let set_pause_authority_instruction = instruction::set_pause_authority(
+            &spl_token_swap::id(),
+            &self.pool_account.key,
+            &self.pause_authority_account.key,
+            instruction,
+        )
+        .unwrap();
+
+        do_process_instruction(
+            set_pause_authority_instruction,
+            &[
+                self.pool_account.as_account_info(),
+                self.pause_authority_account.as_account_info(),
+            ],
+        )?;
+
+        // keep signing for the new authority, so later pause transitions
+        // can still succeed
+        let mut pause_authority_account = NativeAccountData::new(0, system_program::id());
+        pause_authority_account.key = new_pause_authority;
+        pause_authority_account.is_signer = true;
+        self.pause_authority_account = pause_authority_account;
+        Ok(())
+    }
+}
@@ -10,8 +10,8 @@ use {
     spl_token_swap::{
         curve::{base::SwapCurve, calculator::TradeDirection, fees::Fees},
         instruction::{
-            self, DepositAllTokenTypes, DepositSingleTokenTypeExactAmountIn, Swap,
-            WithdrawAllTokenTypes, WithdrawSingleTokenTypeExactAmountOut,
+            self, DepositAllTokenTypes, DepositSingleTokenTypeExactAmountIn, SetPauseAuthority,
+            SetPaused, Swap, WithdrawAllTokenTypes, WithdrawSingleTokenTypeExactAmountOut,
         },
         state::SwapVersion,
     },
@@ -19,6 +19,7 @@ use {
 
 pub struct NativeTokenSwap {
     pub user_account: NativeAccountData,
+    pub pause_authority_account: NativeAccountData,
     pub bump_seed: u8,
     pub authority_account: NativeAccountData,
     pub fees: Fees,
@@ -111,6 +112,7 @@ impl NativeTokenSwap {
         .unwrap();
 
         Self {
+            pause_authority_account: user_account.clone(),
             user_account,
             bump_seed,
             authority_account,
@@ -610,6 +612,51 @@ impl NativeTokenSwap {
         )
     }
 
+    pub fn set_paused(&mut self, instruction: SetPaused) -> ProgramResult {
+        let set_paused_instruction = instruction::set_paused(
+            &spl_token_swap::id(),
+            &self.swap_account.key,
+            &self.pause_authority_account.key,
+            instruction,
+        )
+        .unwrap();
+
+        do_process_instruction(
+            set_paused_instruction,
+            &[
+                self.swap_account.as_account_info(),
+                self.pause_authority_account.as_account_info(),
+            ],
+        )
+    }
+
+    pub fn set_pause_authority(&mut self, instruction: SetPauseAuthority) -> ProgramResult {
+        let new_pause_authority = instruction.new_pause_authority;
+        let set_pause_authority_instruction = instruction::set_pause_authority(
+            &spl_token_swap::id(),
+            &self.swap_account.key,
+            &self.pause_authority_account.key,
+            instruction,
+        )
+        .unwrap();
+
+        do_process_instruction(
+            set_pause_authority_instruction,
+            &[
+                self.swap_account.as_account_info(),
+                self.pause_authority_account.as_account_info(),
+            ],
+        )?;
+
+        // keep signing for the new authority, so later pause transitions
+        // can still succeed
+        let mut pause_authority_account = NativeAccountData::new(0, system_program::id());
+        pause_authority_account.key = new_pause_authority;
+        pause_authority_account.is_signer = true;
+        self.pause_authority_account = pause_authority_account;
+        Ok(())
+    }
+
     pub fn withdraw_all(
         &mut self,
         pool_account: &mut NativeAccountData,
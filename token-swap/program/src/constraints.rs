@@ -31,11 +31,12 @@ pub struct SwapConstraints<'a> {
 impl<'a> SwapConstraints<'a> {
     /// Checks that the provided curve is valid for the given constraints
     pub fn validate_curve(&self, swap_curve: &SwapCurve) -> Result<(), ProgramError> {
-        if self
-            .valid_curve_types
-            .iter()
-            .any(|x| *x == swap_curve.curve_type)
-        {
+        self.validate_curve_type(swap_curve.curve_type)
+    }
+
+    /// Checks that the provided curve type is valid for the given constraints
+    pub fn validate_curve_type(&self, curve_type: CurveType) -> Result<(), ProgramError> {
+        if self.valid_curve_types.iter().any(|x| *x == curve_type) {
             Ok(())
         } else {
             Err(SwapError::UnsupportedCurveType.into())
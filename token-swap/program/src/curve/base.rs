@@ -3,12 +3,15 @@
 #[cfg(feature = "fuzz")]
 use arbitrary::Arbitrary;
 use {
-    crate::curve::{
-        calculator::{CurveCalculator, RoundDirection, SwapWithoutFeesResult, TradeDirection},
-        constant_price::ConstantPriceCurve,
-        constant_product::ConstantProductCurve,
-        fees::Fees,
-        offset::OffsetCurve,
+    crate::{
+        curve::{
+            calculator::{CurveCalculator, RoundDirection, SwapWithoutFeesResult, TradeDirection},
+            constant_price::ConstantPriceCurve,
+            constant_product::ConstantProductCurve,
+            fees::Fees,
+            offset::OffsetCurve,
+        },
+        error::SwapError,
     },
     arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs},
     solana_program::{
@@ -34,6 +37,10 @@ pub enum CurveType {
     ConstantPrice,
     /// Offset curve, like Uniswap, but the token B side has a faked offset
     Offset,
+    /// Concentrated liquidity, where liquidity is provided over price ranges.
+    /// Only used by concentrated liquidity pools, which do not store a
+    /// `SwapCurve`.
+    ConcentratedLiquidity,
 }
 
 /// Encodes all results of swapping from a source token to a destination token
@@ -229,6 +236,9 @@ impl Pack for SwapCurve {
                     Arc::new(ConstantPriceCurve::unpack_from_slice(calculator)?)
                 }
                 CurveType::Offset => Arc::new(OffsetCurve::unpack_from_slice(calculator)?),
+                CurveType::ConcentratedLiquidity => {
+                    return Err(SwapError::UnsupportedCurveType.into())
+                }
             },
         })
     }
@@ -258,6 +268,7 @@ impl TryFrom<u8> for CurveType {
             0 => Ok(CurveType::ConstantProduct),
             1 => Ok(CurveType::ConstantPrice),
             2 => Ok(CurveType::Offset),
+            3 => Ok(CurveType::ConcentratedLiquidity),
             _ => Err(ProgramError::InvalidAccountData),
         }
     }
@@ -149,6 +149,22 @@ pub trait CurveCalculator: Debug + DynPack {
         round_direction: RoundDirection,
     ) -> Option<u128>;
 
+    /// Get the marginal price of the source token, in destination tokens,
+    /// given the current amounts in the swap.
+    ///
+    /// This is the rate received for an infinitesimally small trade, and is
+    /// used to calculate the price impact of larger trades.  The default
+    /// implementation is the constant product ratio of the two amounts.
+    fn spot_price(
+        &self,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        _trade_direction: TradeDirection,
+    ) -> Option<PreciseNumber> {
+        PreciseNumber::new(swap_destination_amount)?
+            .checked_div(&PreciseNumber::new(swap_source_amount)?)
+    }
+
     /// Validate that the given curve has no invalid parameters
     fn validate(&self) -> Result<(), SwapError>;
 
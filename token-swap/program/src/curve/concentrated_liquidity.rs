@@ -0,0 +1,532 @@
+//! Concentrated liquidity calculations, where liquidity is provided within
+//! tick ranges instead of across the whole price curve.
+//!
+//! Prices are expressed as the square root of the price of token A in terms of
+//! token B, stored as Q64.64 fixed-point numbers.  Tick `i` corresponds to a
+//! price of `1.0001^i`.
+
+use {
+    crate::curve::calculator::{RoundDirection, TradeDirection, TradingTokenResult},
+    spl_math::uint::U256,
+    std::convert::TryFrom,
+};
+
+/// Minimum tick index supported by concentrated liquidity pools
+pub const MIN_TICK: i32 = -443_636;
+/// Maximum tick index supported by concentrated liquidity pools
+pub const MAX_TICK: i32 = 443_636;
+/// Square root price at `MIN_TICK`, as a Q64.64 number
+pub const MIN_SQRT_PRICE: u128 = 4_295_048_016;
+/// Square root price at `MAX_TICK`, as a Q64.64 number
+pub const MAX_SQRT_PRICE: u128 = 79_226_673_515_401_279_992_447_579_061;
+
+/// Number of fractional bits in a Q64.64 number
+const Q64_RESOLUTION: usize = 64;
+
+/// `1 / sqrt(1.0001)^(2^i)` as Q128.128 numbers, used to build the square root
+/// price of a tick from the bits of its absolute value
+const TICK_FACTORS: [u128; 19] = [
+    0xfff97272373d413259a46990580e2139,
+    0xfff2e50f5f656932ef12357cf3c7fdcb,
+    0xffe5caca7e10e4e61c3624eaa0941ccf,
+    0xffcb9843d60f6159c9db58835c926643,
+    0xff973b41fa98c081472e6896dfb254bf,
+    0xff2ea16466c96a3843ec78b326b52860,
+    0xfe5dee046a99a2a811c461f1969c3052,
+    0xfcbe86c7900a88aedcffc83b479aa3a3,
+    0xf987a7253ac413176f2b074cf7815e53,
+    0xf3392b0822b70005940c7a398e4b70f2,
+    0xe7159475a2c29b7443b29c7fa6e889d8,
+    0xd097f3bdfd2022b8845ad8f792aa5825,
+    0xa9f746462d870fdf8a65dc1f90e061e4,
+    0x70d869a156d2a1b890bb3df62baf32f6,
+    0x31be135f97d08fd981231505542fcfa5,
+    0x09aa508b5b7a84e1c677de54f3e99bc8,
+    0x005d6af8dedb81196699c329225ee604,
+    0x00002216e584f5fa1ea926041bedfe97,
+    0x00000000048a170391f7dc42444e8fa2,
+];
+/// `1 / sqrt(1.0001)` as a Q128.128 number, for the lowest bit of the tick
+const TICK_FACTOR_ONE: u128 = 0xfffcb933bd6fad37aa2d162d1a594001;
+
+/// Encodes the results of a single swap step within one tick range
+#[derive(Debug, PartialEq)]
+pub struct SwapStep {
+    /// Square root price after the step
+    pub sqrt_price_next: u128,
+    /// Amount of source token consumed by the step
+    pub amount_in: u128,
+    /// Amount of destination token produced by the step
+    pub amount_out: u128,
+}
+
+fn to_u128(value: U256) -> Option<u128> {
+    u128::try_from(value).ok()
+}
+
+fn div_round(numerator: U256, denominator: U256, round_direction: RoundDirection) -> Option<U256> {
+    let quotient = numerator.checked_div(denominator)?;
+    match round_direction {
+        RoundDirection::Floor => Some(quotient),
+        RoundDirection::Ceiling => {
+            if numerator.checked_rem(denominator)?.is_zero() {
+                Some(quotient)
+            } else {
+                quotient.checked_add(U256::one())
+            }
+        }
+    }
+}
+
+/// Calculate the Q64.64 square root price at the given tick, ie.
+/// `sqrt(1.0001^tick) * 2^64`
+pub fn sqrt_price_at_tick(tick: i32) -> Option<u128> {
+    if !(MIN_TICK..=MAX_TICK).contains(&tick) {
+        return None;
+    }
+    let abs_tick = tick.unsigned_abs();
+    let mut ratio = if abs_tick & 1 != 0 {
+        U256::from(TICK_FACTOR_ONE)
+    } else {
+        U256::one() << 128
+    };
+    for (bit, factor) in TICK_FACTORS.iter().enumerate() {
+        if abs_tick & (2 << bit) != 0 {
+            ratio = ratio.checked_mul(U256::from(*factor))? >> 128;
+        }
+    }
+    if tick > 0 {
+        ratio = U256::MAX.checked_div(ratio)?;
+    }
+    // Q128.128 to Q64.64
+    to_u128(ratio >> Q64_RESOLUTION)
+}
+
+/// Calculate the greatest tick whose square root price is less than or equal
+/// to the given Q64.64 square root price
+pub fn tick_at_sqrt_price(sqrt_price: u128) -> Option<i32> {
+    tick_at_sqrt_price_between(sqrt_price, MIN_TICK, MAX_TICK)
+}
+
+/// Calculate the greatest tick whose square root price is less than or equal
+/// to the given Q64.64 square root price, knowing that the tick lies between
+/// `tick_low` and `tick_high`, inclusive.
+///
+/// Swaps only search between the two initialized ticks around the price,
+/// which is much cheaper than searching the whole tick range.
+pub fn tick_at_sqrt_price_between(sqrt_price: u128, tick_low: i32, tick_high: i32) -> Option<i32> {
+    if !(MIN_SQRT_PRICE..=MAX_SQRT_PRICE).contains(&sqrt_price)
+        || tick_low < MIN_TICK
+        || tick_high > MAX_TICK
+        || tick_low > tick_high
+        || sqrt_price_at_tick(tick_low)? > sqrt_price
+    {
+        return None;
+    }
+    if tick_high < MAX_TICK && sqrt_price_at_tick(tick_high + 1)? <= sqrt_price {
+        return None;
+    }
+    let mut low = tick_low;
+    let mut high = tick_high;
+    while low < high {
+        // round up to always make progress
+        let middle = low + (high - low + 1) / 2;
+        if sqrt_price_at_tick(middle)? <= sqrt_price {
+            low = middle;
+        } else {
+            high = middle - 1;
+        }
+    }
+    Some(low)
+}
+
+/// Amount of token A represented by `liquidity` between two square root
+/// prices, ie. `liquidity * (upper - lower) / (upper * lower)`
+pub fn token_a_delta(
+    sqrt_price_lower: u128,
+    sqrt_price_upper: u128,
+    liquidity: u128,
+    round_direction: RoundDirection,
+) -> Option<u128> {
+    if sqrt_price_lower == 0 || sqrt_price_lower > sqrt_price_upper {
+        return None;
+    }
+    let numerator = (U256::from(liquidity) << Q64_RESOLUTION)
+        .checked_mul(U256::from(sqrt_price_upper - sqrt_price_lower))?;
+    let amount = div_round(numerator, U256::from(sqrt_price_upper), round_direction)?;
+    to_u128(div_round(
+        amount,
+        U256::from(sqrt_price_lower),
+        round_direction,
+    )?)
+}
+
+/// Amount of token B represented by `liquidity` between two square root
+/// prices, ie. `liquidity * (upper - lower)`
+pub fn token_b_delta(
+    sqrt_price_lower: u128,
+    sqrt_price_upper: u128,
+    liquidity: u128,
+    round_direction: RoundDirection,
+) -> Option<u128> {
+    if sqrt_price_lower > sqrt_price_upper {
+        return None;
+    }
+    let numerator =
+        U256::from(liquidity).checked_mul(U256::from(sqrt_price_upper - sqrt_price_lower))?;
+    to_u128(div_round(
+        numerator,
+        U256::one() << Q64_RESOLUTION,
+        round_direction,
+    )?)
+}
+
+/// Calculate the square root price after adding `amount_in` of the source
+/// token at the given liquidity.  The price is always rounded in favor of the
+/// pool.
+pub fn next_sqrt_price_from_input(
+    sqrt_price: u128,
+    liquidity: u128,
+    amount_in: u128,
+    trade_direction: TradeDirection,
+) -> Option<u128> {
+    if liquidity == 0 {
+        return None;
+    }
+    match trade_direction {
+        // adding token A pushes the price down:
+        // liquidity * price / (liquidity + amount * price)
+        TradeDirection::AtoB => {
+            let liquidity = U256::from(liquidity) << Q64_RESOLUTION;
+            let numerator = liquidity.checked_mul(U256::from(sqrt_price))?;
+            let denominator = liquidity
+                .checked_add(U256::from(amount_in).checked_mul(U256::from(sqrt_price))?)?;
+            to_u128(div_round(numerator, denominator, RoundDirection::Ceiling)?)
+        }
+        // adding token B pushes the price up: price + amount / liquidity
+        TradeDirection::BtoA => {
+            let delta = div_round(
+                U256::from(amount_in) << Q64_RESOLUTION,
+                U256::from(liquidity),
+                RoundDirection::Floor,
+            )?;
+            sqrt_price.checked_add(to_u128(delta)?)
+        }
+    }
+}
+
+/// Swap as much of `amount_remaining` as possible between the current square
+/// root price and the target, using a constant amount of liquidity.
+pub fn compute_swap_step(
+    sqrt_price_current: u128,
+    sqrt_price_target: u128,
+    liquidity: u128,
+    amount_remaining: u128,
+    trade_direction: TradeDirection,
+) -> Option<SwapStep> {
+    if liquidity == 0 {
+        // nothing to trade against, move straight to the target
+        return Some(SwapStep {
+            sqrt_price_next: sqrt_price_target,
+            amount_in: 0,
+            amount_out: 0,
+        });
+    }
+    match trade_direction {
+        TradeDirection::AtoB => {
+            if sqrt_price_target > sqrt_price_current {
+                return None;
+            }
+            let max_amount_in = token_a_delta(
+                sqrt_price_target,
+                sqrt_price_current,
+                liquidity,
+                RoundDirection::Ceiling,
+            )?;
+            let (sqrt_price_next, amount_in) = if amount_remaining >= max_amount_in {
+                (sqrt_price_target, max_amount_in)
+            } else {
+                (
+                    next_sqrt_price_from_input(
+                        sqrt_price_current,
+                        liquidity,
+                        amount_remaining,
+                        trade_direction,
+                    )?,
+                    amount_remaining,
+                )
+            };
+            let amount_out = token_b_delta(
+                sqrt_price_next,
+                sqrt_price_current,
+                liquidity,
+                RoundDirection::Floor,
+            )?;
+            Some(SwapStep {
+                sqrt_price_next,
+                amount_in,
+                amount_out,
+            })
+        }
+        TradeDirection::BtoA => {
+            if sqrt_price_target < sqrt_price_current {
+                return None;
+            }
+            let max_amount_in = token_b_delta(
+                sqrt_price_current,
+                sqrt_price_target,
+                liquidity,
+                RoundDirection::Ceiling,
+            )?;
+            let (sqrt_price_next, amount_in) = if amount_remaining >= max_amount_in {
+                (sqrt_price_target, max_amount_in)
+            } else {
+                (
+                    next_sqrt_price_from_input(
+                        sqrt_price_current,
+                        liquidity,
+                        amount_remaining,
+                        trade_direction,
+                    )?,
+                    amount_remaining,
+                )
+            };
+            let amount_out = token_a_delta(
+                sqrt_price_current,
+                sqrt_price_next,
+                liquidity,
+                RoundDirection::Floor,
+            )?;
+            Some(SwapStep {
+                sqrt_price_next,
+                amount_in,
+                amount_out,
+            })
+        }
+    }
+}
+
+/// Get the amounts of token A and B represented by `liquidity` in the range
+/// between two square root prices, given the current square root price
+pub fn amounts_for_liquidity(
+    sqrt_price: u128,
+    sqrt_price_lower: u128,
+    sqrt_price_upper: u128,
+    liquidity: u128,
+    round_direction: RoundDirection,
+) -> Option<TradingTokenResult> {
+    if sqrt_price <= sqrt_price_lower {
+        Some(TradingTokenResult {
+            token_a_amount: token_a_delta(
+                sqrt_price_lower,
+                sqrt_price_upper,
+                liquidity,
+                round_direction,
+            )?,
+            token_b_amount: 0,
+        })
+    } else if sqrt_price < sqrt_price_upper {
+        Some(TradingTokenResult {
+            token_a_amount: token_a_delta(
+                sqrt_price,
+                sqrt_price_upper,
+                liquidity,
+                round_direction,
+            )?,
+            token_b_amount: token_b_delta(
+                sqrt_price_lower,
+                sqrt_price,
+                liquidity,
+                round_direction,
+            )?,
+        })
+    } else {
+        Some(TradingTokenResult {
+            token_a_amount: 0,
+            token_b_amount: token_b_delta(
+                sqrt_price_lower,
+                sqrt_price_upper,
+                liquidity,
+                round_direction,
+            )?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, proptest::prelude::*};
+
+    const ONE: u128 = 1 << 64;
+
+    #[test]
+    fn sqrt_price_bounds() {
+        assert_eq!(sqrt_price_at_tick(0), Some(ONE));
+        assert_eq!(sqrt_price_at_tick(MIN_TICK), Some(MIN_SQRT_PRICE));
+        assert_eq!(sqrt_price_at_tick(MAX_TICK), Some(MAX_SQRT_PRICE));
+        assert_eq!(sqrt_price_at_tick(MIN_TICK - 1), None);
+        assert_eq!(sqrt_price_at_tick(MAX_TICK + 1), None);
+        assert_eq!(tick_at_sqrt_price(MIN_SQRT_PRICE), Some(MIN_TICK));
+        assert_eq!(tick_at_sqrt_price(MAX_SQRT_PRICE), Some(MAX_TICK));
+        assert_eq!(tick_at_sqrt_price(MIN_SQRT_PRICE - 1), None);
+        assert_eq!(tick_at_sqrt_price(MAX_SQRT_PRICE + 1), None);
+    }
+
+    #[test]
+    fn sqrt_price_known_values() {
+        // sqrt(1.0001^10000) ~= 1.64868
+        let price = sqrt_price_at_tick(10_000).unwrap();
+        assert_eq!(price / (ONE / 10_000), 16_486);
+        // sqrt(1.0001^-10000) ~= 0.6065
+        let price = sqrt_price_at_tick(-10_000).unwrap();
+        assert_eq!(price / (ONE / 10_000), 6_065);
+    }
+
+    #[test]
+    fn swap_step_a_to_b() {
+        let liquidity = 1_000_000_000;
+        let target = sqrt_price_at_tick(-100).unwrap();
+
+        // small amount stays within the range
+        let step = compute_swap_step(ONE, target, liquidity, 1_000, TradeDirection::AtoB).unwrap();
+        assert_eq!(step.amount_in, 1_000);
+        assert!(step.sqrt_price_next < ONE && step.sqrt_price_next > target);
+        // roughly 1:1 near a price of 1
+        assert!(step.amount_out <= 1_000 && step.amount_out >= 998);
+
+        // large amount stops at the target
+        let step = compute_swap_step(
+            ONE,
+            target,
+            liquidity,
+            u64::MAX as u128,
+            TradeDirection::AtoB,
+        )
+        .unwrap();
+        assert_eq!(step.sqrt_price_next, target);
+        assert_eq!(
+            step.amount_in,
+            token_a_delta(target, ONE, liquidity, RoundDirection::Ceiling).unwrap()
+        );
+
+        // wrong direction
+        assert_eq!(
+            compute_swap_step(target, ONE, liquidity, 1_000, TradeDirection::AtoB),
+            None
+        );
+    }
+
+    #[test]
+    fn swap_step_b_to_a() {
+        let liquidity = 1_000_000_000;
+        let target = sqrt_price_at_tick(100).unwrap();
+        let step = compute_swap_step(ONE, target, liquidity, 1_000, TradeDirection::BtoA).unwrap();
+        assert_eq!(step.amount_in, 1_000);
+        assert!(step.sqrt_price_next > ONE && step.sqrt_price_next < target);
+        assert!(step.amount_out <= 1_000 && step.amount_out >= 998);
+
+        let step = compute_swap_step(ONE, target, 0, 1_000, TradeDirection::BtoA).unwrap();
+        assert_eq!(
+            step,
+            SwapStep {
+                sqrt_price_next: target,
+                amount_in: 0,
+                amount_out: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn amounts_for_range() {
+        let liquidity = 1_000_000;
+        let lower = sqrt_price_at_tick(-1_000).unwrap();
+        let upper = sqrt_price_at_tick(1_000).unwrap();
+
+        let below =
+            amounts_for_liquidity(lower - 1, lower, upper, liquidity, RoundDirection::Floor)
+                .unwrap();
+        assert!(below.token_a_amount > 0);
+        assert_eq!(below.token_b_amount, 0);
+
+        let inside =
+            amounts_for_liquidity(ONE, lower, upper, liquidity, RoundDirection::Floor).unwrap();
+        assert!(inside.token_a_amount > 0);
+        assert!(inside.token_b_amount > 0);
+        // symmetric range around a price of 1
+        assert!(inside.token_a_amount.abs_diff(inside.token_b_amount) <= 1);
+
+        let above =
+            amounts_for_liquidity(upper, lower, upper, liquidity, RoundDirection::Floor).unwrap();
+        assert_eq!(above.token_a_amount, 0);
+        assert!(above.token_b_amount > 0);
+    }
+
+    #[test]
+    fn tick_search_between_bounds() {
+        let sqrt_price = sqrt_price_at_tick(1_234).unwrap() + 1;
+        assert_eq!(
+            tick_at_sqrt_price_between(sqrt_price, 1_000, 2_000),
+            Some(1_234)
+        );
+        assert_eq!(
+            tick_at_sqrt_price_between(sqrt_price, 1_234, 1_234),
+            Some(1_234)
+        );
+        // the tick must be within the bounds
+        assert_eq!(tick_at_sqrt_price_between(sqrt_price, 1_235, 2_000), None);
+        assert_eq!(tick_at_sqrt_price_between(sqrt_price, 1_000, 1_233), None);
+        assert_eq!(tick_at_sqrt_price_between(sqrt_price, 2_000, 1_000), None);
+        assert_eq!(
+            tick_at_sqrt_price_between(MAX_SQRT_PRICE, MAX_TICK - 10, MAX_TICK),
+            Some(MAX_TICK)
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn tick_round_trip(tick in MIN_TICK..=MAX_TICK) {
+            let sqrt_price = sqrt_price_at_tick(tick).unwrap();
+            prop_assert_eq!(tick_at_sqrt_price(sqrt_price).unwrap(), tick);
+            if tick < MAX_TICK {
+                let next = sqrt_price_at_tick(tick + 1).unwrap();
+                prop_assert!(next > sqrt_price);
+                prop_assert_eq!(tick_at_sqrt_price(next - 1).unwrap(), tick);
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn swap_step_never_loses_value(
+            tick in -10_000i32..10_000,
+            liquidity in 1_000u128..u64::MAX as u128,
+            amount in 1u128..u64::MAX as u128,
+        ) {
+            let current = sqrt_price_at_tick(tick).unwrap();
+            for trade_direction in [TradeDirection::AtoB, TradeDirection::BtoA] {
+                let target = match trade_direction {
+                    TradeDirection::AtoB => MIN_SQRT_PRICE,
+                    TradeDirection::BtoA => MAX_SQRT_PRICE,
+                };
+                let step =
+                    compute_swap_step(current, target, liquidity, amount, trade_direction).unwrap();
+                prop_assert!(step.amount_in <= amount);
+                // the reserves needed after the step, rounded up, must be
+                // covered by the reserves before the step plus the input
+                let (lower, upper) = (current.min(step.sqrt_price_next), current.max(step.sqrt_price_next));
+                let (max_in, min_out) = match trade_direction {
+                    TradeDirection::AtoB => (
+                        token_a_delta(lower, upper, liquidity, RoundDirection::Ceiling).unwrap(),
+                        token_b_delta(lower, upper, liquidity, RoundDirection::Floor).unwrap(),
+                    ),
+                    TradeDirection::BtoA => (
+                        token_b_delta(lower, upper, liquidity, RoundDirection::Ceiling).unwrap(),
+                        token_a_delta(lower, upper, liquidity, RoundDirection::Floor).unwrap(),
+                    ),
+                };
+                prop_assert!(step.amount_in + 1 >= max_in);
+                prop_assert!(step.amount_out <= min_out);
+            }
+        }
+    }
+}
@@ -186,6 +186,21 @@ impl CurveCalculator for ConstantPriceCurve {
         )
     }
 
+    /// The spot price is fixed by the curve, regardless of the amounts in the
+    /// swap
+    fn spot_price(
+        &self,
+        _swap_source_amount: u128,
+        _swap_destination_amount: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<PreciseNumber> {
+        let token_b_price = PreciseNumber::new(self.token_b_price as u128)?;
+        match trade_direction {
+            TradeDirection::AtoB => PreciseNumber::new(1)?.checked_div(&token_b_price),
+            TradeDirection::BtoA => Some(token_b_price),
+        }
+    }
+
     fn validate(&self) -> Result<(), SwapError> {
         if self.token_b_price == 0 {
             Err(SwapError::InvalidCurve)
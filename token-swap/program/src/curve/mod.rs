@@ -2,6 +2,7 @@
 
 pub mod base;
 pub mod calculator;
+pub mod concentrated_liquidity;
 pub mod constant_price;
 pub mod constant_product;
 pub mod fees;
@@ -118,6 +118,29 @@ impl CurveCalculator for OffsetCurve {
         )
     }
 
+    /// The spot price is the constant product ratio, with the offset added to
+    /// the token B side
+    fn spot_price(
+        &self,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<PreciseNumber> {
+        let token_b_offset = self.token_b_offset as u128;
+        let (swap_source_amount, swap_destination_amount) = match trade_direction {
+            TradeDirection::AtoB => (
+                swap_source_amount,
+                swap_destination_amount.checked_add(token_b_offset)?,
+            ),
+            TradeDirection::BtoA => (
+                swap_source_amount.checked_add(token_b_offset)?,
+                swap_destination_amount,
+            ),
+        };
+        PreciseNumber::new(swap_destination_amount)?
+            .checked_div(&PreciseNumber::new(swap_source_amount)?)
+    }
+
     fn validate(&self) -> Result<(), SwapError> {
         if self.token_b_offset == 0 {
             Err(SwapError::InvalidCurve)
@@ -117,6 +117,40 @@ pub enum SwapError {
     /// The pool fee account is invalid.
     #[error("The pool fee account is invalid")]
     InvalidFeeAccount,
+    /// The swap is paused, only withdrawals of all token types are allowed
+    #[error("The swap is paused")]
+    SwapPaused,
+
+    // 30.
+    /// The provided pause authority does not match the swap's pause authority
+    #[error("The provided pause authority does not match the swap's pause authority")]
+    IncorrectPauseAuthority,
+    /// The operation is not supported by this version of the swap account
+    #[error("The operation is not supported by this version of the swap account")]
+    UnsupportedSwapVersion,
+    /// The tick is out of bounds, or not a multiple of the pool's tick spacing
+    #[error("The tick is out of bounds, or not a multiple of the pool's tick spacing")]
+    InvalidTick,
+    /// The pool has no room left to initialize another tick
+    #[error("The pool has no room left to initialize another tick")]
+    TooManyTicks,
+    /// The pool does not have enough liquidity to fill the trade
+    #[error("The pool does not have enough liquidity to fill the trade")]
+    InsufficientLiquidity,
+
+    // 35.
+    /// The position does not belong to the given pool or owner
+    #[error("The position does not belong to the given pool or owner")]
+    IncorrectPosition,
+    /// The provided fee authority does not match the pool's fee authority
+    #[error("The provided fee authority does not match the pool's fee authority")]
+    IncorrectFeeAuthority,
+    /// The position's liquidity would be below the pool's minimum
+    #[error("The position's liquidity would be below the pool's minimum")]
+    PositionLiquidityTooLow,
+    /// The position still holds liquidity or uncollected fees
+    #[error("The position still holds liquidity or uncollected fees")]
+    PositionNotEmpty,
 }
 impl From<SwapError> for ProgramError {
     fn from(e: SwapError) -> Self {
@@ -200,6 +234,36 @@ impl PrintProgramError for SwapError {
             SwapError::InvalidFeeAccount => {
                 msg!("Error: The pool fee account is invalid")
             }
+            SwapError::SwapPaused => msg!("Error: The swap is paused"),
+            SwapError::IncorrectPauseAuthority => {
+                msg!(
+                    "Error: The provided pause authority does not match the swap's pause authority"
+                )
+            }
+            SwapError::UnsupportedSwapVersion => {
+                msg!("Error: The operation is not supported by this version of the swap account")
+            }
+            SwapError::InvalidTick => {
+                msg!("Error: The tick is out of bounds, or not a multiple of the pool's tick spacing")
+            }
+            SwapError::TooManyTicks => {
+                msg!("Error: The pool has no room left to initialize another tick")
+            }
+            SwapError::InsufficientLiquidity => {
+                msg!("Error: The pool does not have enough liquidity to fill the trade")
+            }
+            SwapError::IncorrectPosition => {
+                msg!("Error: The position does not belong to the given pool or owner")
+            }
+            SwapError::IncorrectFeeAuthority => {
+                msg!("Error: The provided fee authority does not match the pool's fee authority")
+            }
+            SwapError::PositionLiquidityTooLow => {
+                msg!("Error: The position's liquidity would be below the pool's minimum")
+            }
+            SwapError::PositionNotEmpty => {
+                msg!("Error: The position still holds liquidity or uncollected fees")
+            }
         }
     }
 }
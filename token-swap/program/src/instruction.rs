@@ -94,6 +94,85 @@ pub struct WithdrawSingleTokenTypeExactAmountOut {
     pub maximum_pool_token_amount: u64,
 }
 
+/// SetPaused instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetPaused {
+    /// If true, pause the swap, allowing only withdrawals of all token types.
+    /// If false, resume normal operation.
+    pub paused: bool,
+}
+
+/// SetPauseAuthority instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetPauseAuthority {
+    /// The new authority allowed to pause and resume the swap
+    #[cfg_attr(feature = "fuzz", arbitrary(with = arbitrary_pubkey))]
+    pub new_pause_authority: Pubkey,
+}
+
+#[cfg(feature = "fuzz")]
+fn arbitrary_pubkey(u: &mut arbitrary::Unstructured) -> arbitrary::Result<Pubkey> {
+    Ok(Pubkey::new_from_array(u.arbitrary()?))
+}
+
+/// InitializeConcentratedPool instruction data
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct InitializeConcentratedPool {
+    /// all pool fees, the owner withdraw fee is unused
+    pub fees: Fees,
+    /// position bounds must be a multiple of the tick spacing
+    pub tick_spacing: u16,
+    /// initial square root price of token A in terms of token B, as a Q64.64
+    /// number
+    pub initial_sqrt_price: u128,
+    /// minimum liquidity of a non-empty position
+    pub min_position_liquidity: u128,
+}
+
+/// OpenPosition instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct OpenPosition {
+    /// Lower tick bound of the position, inclusive
+    pub tick_lower: i32,
+    /// Upper tick bound of the position, exclusive
+    pub tick_upper: i32,
+}
+
+/// IncreaseLiquidity instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct IncreaseLiquidity {
+    /// Liquidity to add to the position.  The token A and B amounts are set by
+    /// the current price and the position's range
+    pub liquidity: u128,
+    /// Maximum token A amount to deposit, prevents excessive slippage
+    pub maximum_token_a_amount: u64,
+    /// Maximum token B amount to deposit, prevents excessive slippage
+    pub maximum_token_b_amount: u64,
+}
+
+/// DecreaseLiquidity instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecreaseLiquidity {
+    /// Liquidity to remove from the position.  Zero only collects the fees
+    /// earned by the position
+    pub liquidity: u128,
+    /// Minimum amount of token A to receive, prevents excessive slippage
+    pub minimum_token_a_amount: u64,
+    /// Minimum amount of token B to receive, prevents excessive slippage
+    pub minimum_token_b_amount: u64,
+}
+
 /// Instructions supported by the token swap program.
 #[repr(C)]
 #[derive(Debug, PartialEq)]
@@ -217,6 +296,130 @@ pub enum SwapInstruction {
     ///   10. `[]` Pool Token program id
     ///   11. `[]` Token (A|B) DESTINATION program id
     WithdrawSingleTokenTypeExactAmountOut(WithdrawSingleTokenTypeExactAmountOut),
+
+    ///   Pause or resume the swap.  While paused, `Swap`, the deposit
+    ///   instructions, and `WithdrawSingleTokenTypeExactAmountOut` fail, but
+    ///   `WithdrawAllTokenTypes` remains available.
+    ///
+    ///   Concentrated liquidity pools can be paused as well, in which case
+    ///   `ConcentratedSwap`, `OpenPosition` and `IncreaseLiquidity` fail, while
+    ///   `DecreaseLiquidity`, `ClosePosition` and `CollectProtocolFees` remain
+    ///   available.
+    ///
+    ///   0. `[writable]` Token-swap or concentrated liquidity pool
+    ///   1. `[signer]` Pause authority
+    SetPaused(SetPaused),
+
+    ///   Initializes a new concentrated liquidity pool, where liquidity is
+    ///   provided through positions over price ranges.
+    ///
+    ///   0. `[writable]` New concentrated liquidity pool to create.
+    ///   1. `[]` pool authority derived from
+    ///      `create_program_address(&[pool account])`
+    ///   2. `[]` token_a Account. Must be owned by pool authority.
+    ///   3. `[]` token_b Account. Must be owned by pool authority.
+    ///   4. `[]` Fee authority, allowed to collect owner trading fees, which
+    ///      is also the initial pause authority
+    InitializeConcentratedPool(InitializeConcentratedPool),
+
+    ///   Opens an empty position over a range of ticks in a concentrated
+    ///   liquidity pool.
+    ///
+    ///   0. `[]` Concentrated liquidity pool
+    ///   1. `[writable]` New position to create.
+    ///   2. `[signer]` Position owner
+    OpenPosition(OpenPosition),
+
+    ///   Add liquidity to a position.  Inputs are converted using the current
+    ///   price and the position's range.
+    ///
+    ///   0. `[writable]` Concentrated liquidity pool
+    ///   1. `[]` pool authority
+    ///   2. `[writable]` Position
+    ///   3. `[signer]` Position owner
+    ///   4. `[signer]` user transfer authority
+    ///   5. `[writable]` token_a user transfer authority can transfer amount
+    ///   6. `[writable]` token_b user transfer authority can transfer amount
+    ///   7. `[writable]` token_a pool Account to deposit into.
+    ///   8. `[writable]` token_b pool Account to deposit into.
+    ///   9. `[]` Token A mint
+    ///   10. `[]` Token B mint
+    ///   11. `[]` Token A program id
+    ///   12. `[]` Token B program id
+    IncreaseLiquidity(IncreaseLiquidity),
+
+    ///   Remove liquidity from a position, also paying out all fees earned by
+    ///   the position.
+    ///
+    ///   0. `[writable]` Concentrated liquidity pool
+    ///   1. `[]` pool authority
+    ///   2. `[writable]` Position
+    ///   3. `[signer]` Position owner
+    ///   4. `[writable]` token_a pool Account to withdraw FROM.
+    ///   5. `[writable]` token_b pool Account to withdraw FROM.
+    ///   6. `[writable]` token_a user Account to credit.
+    ///   7. `[writable]` token_b user Account to credit.
+    ///   8. `[]` Token A mint
+    ///   9. `[]` Token B mint
+    ///   10. `[]` Token A program id
+    ///   11. `[]` Token B program id
+    DecreaseLiquidity(DecreaseLiquidity),
+
+    ///   Swap the tokens in a concentrated liquidity pool.
+    ///
+    ///   0. `[writable]` Concentrated liquidity pool
+    ///   1. `[]` pool authority
+    ///   2. `[signer]` user transfer authority
+    ///   3. `[writable]` token_(A|B) SOURCE Account, amount is transferable by
+    ///      user transfer authority,
+    ///   4. `[writable]` token_(A|B) pool Account to swap INTO.  Must be the
+    ///      SOURCE token.
+    ///   5. `[writable]` token_(A|B) pool Account to swap FROM.  Must be the
+    ///      DESTINATION token.
+    ///   6. `[writable]` token_(A|B) DESTINATION Account assigned to USER as
+    ///      the owner.
+    ///   7. `[]` Token (A|B) SOURCE mint
+    ///   8. `[]` Token (A|B) DESTINATION mint
+    ///   9. `[]` Token (A|B) SOURCE program id
+    ///   10. `[]` Token (A|B) DESTINATION program id
+    ///   11. `[optional, writable]` Host fee account for the SOURCE token, to
+    ///       receive a portion of the owner trading fee
+    ConcentratedSwap(Swap),
+
+    ///   Transfer the owner trading fees collected by a concentrated liquidity
+    ///   pool to the fee authority's accounts.
+    ///
+    ///   0. `[writable]` Concentrated liquidity pool
+    ///   1. `[]` pool authority
+    ///   2. `[signer]` Fee authority
+    ///   3. `[writable]` token_a pool Account to withdraw FROM.
+    ///   4. `[writable]` token_b pool Account to withdraw FROM.
+    ///   5. `[writable]` token_a Account to credit.
+    ///   6. `[writable]` token_b Account to credit.
+    ///   7. `[]` Token A mint
+    ///   8. `[]` Token B mint
+    ///   9. `[]` Token A program id
+    ///   10. `[]` Token B program id
+    CollectProtocolFees,
+
+    ///   Transfer the right to pause and resume the swap to a new authority.
+    ///   Only available on swaps created with a pause authority and on
+    ///   concentrated liquidity pools; swaps still using the original account
+    ///   layout can never be paused.
+    ///
+    ///   0. `[writable]` Token-swap or concentrated liquidity pool
+    ///   1. `[signer]` Current pause authority
+    SetPauseAuthority(SetPauseAuthority),
+
+    ///   Close an empty position of a concentrated liquidity pool, returning
+    ///   its rent.  All liquidity and fees must have been withdrawn with
+    ///   `DecreaseLiquidity` first.
+    ///
+    ///   0. `[]` Concentrated liquidity pool
+    ///   1. `[writable]` Position to close
+    ///   2. `[signer]` Position owner
+    ///   3. `[writable]` Account to receive the position's lamports
+    ClosePosition,
 }
 
 impl SwapInstruction {
@@ -279,6 +482,76 @@ impl SwapInstruction {
                     maximum_pool_token_amount,
                 })
             }
+            6 => {
+                let (&paused, _rest) = rest.split_first().ok_or(SwapError::InvalidInstruction)?;
+                let paused = match paused {
+                    0 => false,
+                    1 => true,
+                    _ => return Err(SwapError::InvalidInstruction.into()),
+                };
+                Self::SetPaused(SetPaused { paused })
+            }
+            7 => {
+                if rest.len() >= Fees::LEN {
+                    let (fees, rest) = rest.split_at(Fees::LEN);
+                    let fees = Fees::unpack_unchecked(fees)?;
+                    let (tick_spacing, rest) = Self::unpack_u16(rest)?;
+                    let (initial_sqrt_price, rest) = Self::unpack_u128(rest)?;
+                    let (min_position_liquidity, _rest) = Self::unpack_u128(rest)?;
+                    Self::InitializeConcentratedPool(InitializeConcentratedPool {
+                        fees,
+                        tick_spacing,
+                        initial_sqrt_price,
+                        min_position_liquidity,
+                    })
+                } else {
+                    return Err(SwapError::InvalidInstruction.into());
+                }
+            }
+            8 => {
+                let (tick_lower, rest) = Self::unpack_i32(rest)?;
+                let (tick_upper, _rest) = Self::unpack_i32(rest)?;
+                Self::OpenPosition(OpenPosition {
+                    tick_lower,
+                    tick_upper,
+                })
+            }
+            9 => {
+                let (liquidity, rest) = Self::unpack_u128(rest)?;
+                let (maximum_token_a_amount, rest) = Self::unpack_u64(rest)?;
+                let (maximum_token_b_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::IncreaseLiquidity(IncreaseLiquidity {
+                    liquidity,
+                    maximum_token_a_amount,
+                    maximum_token_b_amount,
+                })
+            }
+            10 => {
+                let (liquidity, rest) = Self::unpack_u128(rest)?;
+                let (minimum_token_a_amount, rest) = Self::unpack_u64(rest)?;
+                let (minimum_token_b_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::DecreaseLiquidity(DecreaseLiquidity {
+                    liquidity,
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                })
+            }
+            11 => {
+                let (amount_in, rest) = Self::unpack_u64(rest)?;
+                let (minimum_amount_out, _rest) = Self::unpack_u64(rest)?;
+                Self::ConcentratedSwap(Swap {
+                    amount_in,
+                    minimum_amount_out,
+                })
+            }
+            12 => Self::CollectProtocolFees,
+            13 => {
+                let (new_pause_authority, _rest) = Self::unpack_pubkey(rest)?;
+                Self::SetPauseAuthority(SetPauseAuthority {
+                    new_pause_authority,
+                })
+            }
+            14 => Self::ClosePosition,
             _ => return Err(SwapError::InvalidInstruction.into()),
         })
     }
@@ -297,6 +570,58 @@ impl SwapInstruction {
         }
     }
 
+    fn unpack_u16(input: &[u8]) -> Result<(u16, &[u8]), ProgramError> {
+        if input.len() >= 2 {
+            let (value, rest) = input.split_at(2);
+            let value = value
+                .try_into()
+                .map(u16::from_le_bytes)
+                .map_err(|_| SwapError::InvalidInstruction)?;
+            Ok((value, rest))
+        } else {
+            Err(SwapError::InvalidInstruction.into())
+        }
+    }
+
+    fn unpack_i32(input: &[u8]) -> Result<(i32, &[u8]), ProgramError> {
+        if input.len() >= 4 {
+            let (value, rest) = input.split_at(4);
+            let value = value
+                .try_into()
+                .map(i32::from_le_bytes)
+                .map_err(|_| SwapError::InvalidInstruction)?;
+            Ok((value, rest))
+        } else {
+            Err(SwapError::InvalidInstruction.into())
+        }
+    }
+
+    fn unpack_u128(input: &[u8]) -> Result<(u128, &[u8]), ProgramError> {
+        if input.len() >= 16 {
+            let (value, rest) = input.split_at(16);
+            let value = value
+                .try_into()
+                .map(u128::from_le_bytes)
+                .map_err(|_| SwapError::InvalidInstruction)?;
+            Ok((value, rest))
+        } else {
+            Err(SwapError::InvalidInstruction.into())
+        }
+    }
+
+    fn unpack_pubkey(input: &[u8]) -> Result<(Pubkey, &[u8]), ProgramError> {
+        if input.len() >= 32 {
+            let (key, rest) = input.split_at(32);
+            let key = key
+                .try_into()
+                .map(Pubkey::new_from_array)
+                .map_err(|_| SwapError::InvalidInstruction)?;
+            Ok((key, rest))
+        } else {
+            Err(SwapError::InvalidInstruction.into())
+        }
+    }
+
     /// Packs a [SwapInstruction](enum.SwapInstruction.html) into a byte buffer.
     pub fn pack(&self) -> Vec<u8> {
         let mut buf = Vec::with_capacity(size_of::<Self>());
@@ -356,6 +681,72 @@ impl SwapInstruction {
                 buf.extend_from_slice(&destination_token_amount.to_le_bytes());
                 buf.extend_from_slice(&maximum_pool_token_amount.to_le_bytes());
             }
+            Self::SetPaused(SetPaused { paused }) => {
+                buf.push(6);
+                buf.push(*paused as u8);
+            }
+            Self::InitializeConcentratedPool(InitializeConcentratedPool {
+                fees,
+                tick_spacing,
+                initial_sqrt_price,
+                min_position_liquidity,
+            }) => {
+                buf.push(7);
+                let mut fees_slice = [0u8; Fees::LEN];
+                Pack::pack_into_slice(fees, &mut fees_slice[..]);
+                buf.extend_from_slice(&fees_slice);
+                buf.extend_from_slice(&tick_spacing.to_le_bytes());
+                buf.extend_from_slice(&initial_sqrt_price.to_le_bytes());
+                buf.extend_from_slice(&min_position_liquidity.to_le_bytes());
+            }
+            Self::OpenPosition(OpenPosition {
+                tick_lower,
+                tick_upper,
+            }) => {
+                buf.push(8);
+                buf.extend_from_slice(&tick_lower.to_le_bytes());
+                buf.extend_from_slice(&tick_upper.to_le_bytes());
+            }
+            Self::IncreaseLiquidity(IncreaseLiquidity {
+                liquidity,
+                maximum_token_a_amount,
+                maximum_token_b_amount,
+            }) => {
+                buf.push(9);
+                buf.extend_from_slice(&liquidity.to_le_bytes());
+                buf.extend_from_slice(&maximum_token_a_amount.to_le_bytes());
+                buf.extend_from_slice(&maximum_token_b_amount.to_le_bytes());
+            }
+            Self::DecreaseLiquidity(DecreaseLiquidity {
+                liquidity,
+                minimum_token_a_amount,
+                minimum_token_b_amount,
+            }) => {
+                buf.push(10);
+                buf.extend_from_slice(&liquidity.to_le_bytes());
+                buf.extend_from_slice(&minimum_token_a_amount.to_le_bytes());
+                buf.extend_from_slice(&minimum_token_b_amount.to_le_bytes());
+            }
+            Self::ConcentratedSwap(Swap {
+                amount_in,
+                minimum_amount_out,
+            }) => {
+                buf.push(11);
+                buf.extend_from_slice(&amount_in.to_le_bytes());
+                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
+            }
+            Self::CollectProtocolFees => {
+                buf.push(12);
+            }
+            Self::SetPauseAuthority(SetPauseAuthority {
+                new_pause_authority,
+            }) => {
+                buf.push(13);
+                buf.extend_from_slice(new_pause_authority.as_ref());
+            }
+            Self::ClosePosition => {
+                buf.push(14);
+            }
         }
         buf
     }
@@ -617,6 +1008,287 @@ pub fn swap(
     })
 }
 
+/// Creates a 'set_paused' instruction.
+pub fn set_paused(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    pause_authority_pubkey: &Pubkey,
+    instruction: SetPaused,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::SetPaused(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*pause_authority_pubkey, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an 'initialize_concentrated_pool' instruction.
+pub fn initialize_concentrated_pool(
+    program_id: &Pubkey,
+    pool_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    token_a_pubkey: &Pubkey,
+    token_b_pubkey: &Pubkey,
+    fee_authority_pubkey: &Pubkey,
+    instruction: InitializeConcentratedPool,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::InitializeConcentratedPool(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*pool_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*token_a_pubkey, false),
+        AccountMeta::new_readonly(*token_b_pubkey, false),
+        AccountMeta::new_readonly(*fee_authority_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an 'open_position' instruction.
+pub fn open_position(
+    program_id: &Pubkey,
+    pool_pubkey: &Pubkey,
+    position_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    instruction: OpenPosition,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::OpenPosition(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*pool_pubkey, false),
+        AccountMeta::new(*position_pubkey, false),
+        AccountMeta::new_readonly(*owner_pubkey, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an 'increase_liquidity' instruction.
+pub fn increase_liquidity(
+    program_id: &Pubkey,
+    token_a_program_id: &Pubkey,
+    token_b_program_id: &Pubkey,
+    pool_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    position_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    deposit_token_a_pubkey: &Pubkey,
+    deposit_token_b_pubkey: &Pubkey,
+    pool_token_a_pubkey: &Pubkey,
+    pool_token_b_pubkey: &Pubkey,
+    token_a_mint_pubkey: &Pubkey,
+    token_b_mint_pubkey: &Pubkey,
+    instruction: IncreaseLiquidity,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::IncreaseLiquidity(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*pool_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new(*position_pubkey, false),
+        AccountMeta::new_readonly(*owner_pubkey, true),
+        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
+        AccountMeta::new(*deposit_token_a_pubkey, false),
+        AccountMeta::new(*deposit_token_b_pubkey, false),
+        AccountMeta::new(*pool_token_a_pubkey, false),
+        AccountMeta::new(*pool_token_b_pubkey, false),
+        AccountMeta::new_readonly(*token_a_mint_pubkey, false),
+        AccountMeta::new_readonly(*token_b_mint_pubkey, false),
+        AccountMeta::new_readonly(*token_a_program_id, false),
+        AccountMeta::new_readonly(*token_b_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'decrease_liquidity' instruction.
+pub fn decrease_liquidity(
+    program_id: &Pubkey,
+    token_a_program_id: &Pubkey,
+    token_b_program_id: &Pubkey,
+    pool_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    position_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    pool_token_a_pubkey: &Pubkey,
+    pool_token_b_pubkey: &Pubkey,
+    destination_token_a_pubkey: &Pubkey,
+    destination_token_b_pubkey: &Pubkey,
+    token_a_mint_pubkey: &Pubkey,
+    token_b_mint_pubkey: &Pubkey,
+    instruction: DecreaseLiquidity,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::DecreaseLiquidity(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*pool_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new(*position_pubkey, false),
+        AccountMeta::new_readonly(*owner_pubkey, true),
+        AccountMeta::new(*pool_token_a_pubkey, false),
+        AccountMeta::new(*pool_token_b_pubkey, false),
+        AccountMeta::new(*destination_token_a_pubkey, false),
+        AccountMeta::new(*destination_token_b_pubkey, false),
+        AccountMeta::new_readonly(*token_a_mint_pubkey, false),
+        AccountMeta::new_readonly(*token_b_mint_pubkey, false),
+        AccountMeta::new_readonly(*token_a_program_id, false),
+        AccountMeta::new_readonly(*token_b_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'concentrated_swap' instruction.
+pub fn concentrated_swap(
+    program_id: &Pubkey,
+    source_token_program_id: &Pubkey,
+    destination_token_program_id: &Pubkey,
+    pool_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    pool_source_pubkey: &Pubkey,
+    pool_destination_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    source_mint_pubkey: &Pubkey,
+    destination_mint_pubkey: &Pubkey,
+    host_fee_pubkey: Option<&Pubkey>,
+    instruction: Swap,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::ConcentratedSwap(instruction).pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(*pool_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
+        AccountMeta::new(*source_pubkey, false),
+        AccountMeta::new(*pool_source_pubkey, false),
+        AccountMeta::new(*pool_destination_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new_readonly(*source_mint_pubkey, false),
+        AccountMeta::new_readonly(*destination_mint_pubkey, false),
+        AccountMeta::new_readonly(*source_token_program_id, false),
+        AccountMeta::new_readonly(*destination_token_program_id, false),
+    ];
+    if let Some(host_fee_pubkey) = host_fee_pubkey {
+        accounts.push(AccountMeta::new(*host_fee_pubkey, false));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'collect_protocol_fees' instruction.
+pub fn collect_protocol_fees(
+    program_id: &Pubkey,
+    token_a_program_id: &Pubkey,
+    token_b_program_id: &Pubkey,
+    pool_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    fee_authority_pubkey: &Pubkey,
+    pool_token_a_pubkey: &Pubkey,
+    pool_token_b_pubkey: &Pubkey,
+    destination_token_a_pubkey: &Pubkey,
+    destination_token_b_pubkey: &Pubkey,
+    token_a_mint_pubkey: &Pubkey,
+    token_b_mint_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::CollectProtocolFees.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*pool_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*fee_authority_pubkey, true),
+        AccountMeta::new(*pool_token_a_pubkey, false),
+        AccountMeta::new(*pool_token_b_pubkey, false),
+        AccountMeta::new(*destination_token_a_pubkey, false),
+        AccountMeta::new(*destination_token_b_pubkey, false),
+        AccountMeta::new_readonly(*token_a_mint_pubkey, false),
+        AccountMeta::new_readonly(*token_b_mint_pubkey, false),
+        AccountMeta::new_readonly(*token_a_program_id, false),
+        AccountMeta::new_readonly(*token_b_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'set_pause_authority' instruction.
+pub fn set_pause_authority(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    pause_authority_pubkey: &Pubkey,
+    instruction: SetPauseAuthority,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::SetPauseAuthority(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*pause_authority_pubkey, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'close_position' instruction.
+pub fn close_position(
+    program_id: &Pubkey,
+    pool_pubkey: &Pubkey,
+    position_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::ClosePosition.pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*pool_pubkey, false),
+        AccountMeta::new(*position_pubkey, false),
+        AccountMeta::new_readonly(*owner_pubkey, true),
+        AccountMeta::new(*destination_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
 /// Unpacks a reference from a bytes buffer.
 /// TODO actually pack / unpack instead of relying on normal memory layout.
 pub fn unpack<T>(input: &[u8]) -> Result<&T, ProgramError> {
@@ -776,4 +1448,145 @@ mod tests {
         let unpacked = SwapInstruction::unpack(&expect).unwrap();
         assert_eq!(unpacked, check);
     }
+
+    #[test]
+    fn pack_set_paused() {
+        for paused in [true, false] {
+            let check = SwapInstruction::SetPaused(SetPaused { paused });
+            let packed = check.pack();
+            let expect = vec![6, paused as u8];
+            assert_eq!(packed, expect);
+            let unpacked = SwapInstruction::unpack(&expect).unwrap();
+            assert_eq!(unpacked, check);
+        }
+        assert_eq!(
+            SwapInstruction::unpack(&[6, 2]).unwrap_err(),
+            SwapError::InvalidInstruction.into()
+        );
+    }
+
+    #[test]
+    fn pack_set_pause_authority() {
+        let new_pause_authority = Pubkey::new_from_array([9u8; 32]);
+        let check = SwapInstruction::SetPauseAuthority(SetPauseAuthority {
+            new_pause_authority,
+        });
+        let packed = check.pack();
+        let mut expect = vec![13];
+        expect.extend_from_slice(new_pause_authority.as_ref());
+        assert_eq!(packed, expect);
+        let unpacked = SwapInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+        assert_eq!(
+            SwapInstruction::unpack(&expect[..32]).unwrap_err(),
+            SwapError::InvalidInstruction.into()
+        );
+    }
+
+    #[test]
+    fn pack_initialize_concentrated_pool() {
+        let fees = Fees {
+            trade_fee_numerator: 3,
+            trade_fee_denominator: 1000,
+            owner_trade_fee_numerator: 1,
+            owner_trade_fee_denominator: 1000,
+            owner_withdraw_fee_numerator: 0,
+            owner_withdraw_fee_denominator: 0,
+            host_fee_numerator: 20,
+            host_fee_denominator: 100,
+        };
+        let tick_spacing: u16 = 64;
+        let initial_sqrt_price: u128 = 1 << 64;
+        let min_position_liquidity: u128 = 1_000_000;
+        let check = SwapInstruction::InitializeConcentratedPool(InitializeConcentratedPool {
+            fees: fees.clone(),
+            tick_spacing,
+            initial_sqrt_price,
+            min_position_liquidity,
+        });
+        let packed = check.pack();
+        let mut expect = vec![7u8];
+        let mut fees_slice = [0u8; Fees::LEN];
+        fees.pack_into_slice(&mut fees_slice);
+        expect.extend_from_slice(&fees_slice);
+        expect.extend_from_slice(&tick_spacing.to_le_bytes());
+        expect.extend_from_slice(&initial_sqrt_price.to_le_bytes());
+        expect.extend_from_slice(&min_position_liquidity.to_le_bytes());
+        assert_eq!(packed, expect);
+        let unpacked = SwapInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[test]
+    fn pack_open_position() {
+        let tick_lower: i32 = -128;
+        let tick_upper: i32 = 256;
+        let check = SwapInstruction::OpenPosition(OpenPosition {
+            tick_lower,
+            tick_upper,
+        });
+        let packed = check.pack();
+        let mut expect = vec![8];
+        expect.extend_from_slice(&tick_lower.to_le_bytes());
+        expect.extend_from_slice(&tick_upper.to_le_bytes());
+        assert_eq!(packed, expect);
+        let unpacked = SwapInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = SwapInstruction::ClosePosition;
+        assert_eq!(check.pack(), vec![14]);
+        assert_eq!(SwapInstruction::unpack(&[14]).unwrap(), check);
+    }
+
+    #[test]
+    fn pack_modify_liquidity() {
+        let liquidity: u128 = 1_000_000_000_000_000_000_000;
+        let token_a_amount: u64 = 102198761982612;
+        let token_b_amount: u64 = 2011239855213;
+        let check = SwapInstruction::IncreaseLiquidity(IncreaseLiquidity {
+            liquidity,
+            maximum_token_a_amount: token_a_amount,
+            maximum_token_b_amount: token_b_amount,
+        });
+        let packed = check.pack();
+        let mut expect = vec![9];
+        expect.extend_from_slice(&liquidity.to_le_bytes());
+        expect.extend_from_slice(&token_a_amount.to_le_bytes());
+        expect.extend_from_slice(&token_b_amount.to_le_bytes());
+        assert_eq!(packed, expect);
+        let unpacked = SwapInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = SwapInstruction::DecreaseLiquidity(DecreaseLiquidity {
+            liquidity,
+            minimum_token_a_amount: token_a_amount,
+            minimum_token_b_amount: token_b_amount,
+        });
+        let packed = check.pack();
+        expect[0] = 10;
+        assert_eq!(packed, expect);
+        let unpacked = SwapInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+    }
+
+    #[test]
+    fn pack_concentrated_swap() {
+        let amount_in: u64 = 2;
+        let minimum_amount_out: u64 = 10;
+        let check = SwapInstruction::ConcentratedSwap(Swap {
+            amount_in,
+            minimum_amount_out,
+        });
+        let packed = check.pack();
+        let mut expect = vec![11];
+        expect.extend_from_slice(&amount_in.to_le_bytes());
+        expect.extend_from_slice(&minimum_amount_out.to_le_bytes());
+        assert_eq!(packed, expect);
+        let unpacked = SwapInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = SwapInstruction::CollectProtocolFees;
+        assert_eq!(check.pack(), vec![12]);
+        assert_eq!(SwapInstruction::unpack(&[12]).unwrap(), check);
+    }
 }
@@ -8,6 +8,7 @@ pub mod curve;
 pub mod error;
 pub mod instruction;
 pub mod processor;
+pub mod quote;
 pub mod state;
 
 #[cfg(not(feature = "no-entrypoint"))]
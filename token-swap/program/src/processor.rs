@@ -4,16 +4,22 @@ use {
     crate::{
         constraints::{SwapConstraints, SWAP_CONSTRAINTS},
         curve::{
-            base::SwapCurve,
+            base::{CurveType, SwapCurve},
             calculator::{RoundDirection, TradeDirection},
+            concentrated_liquidity::tick_at_sqrt_price,
             fees::Fees,
         },
         error::SwapError,
         instruction::{
-            DepositAllTokenTypes, DepositSingleTokenTypeExactAmountIn, Initialize, Swap,
-            SwapInstruction, WithdrawAllTokenTypes, WithdrawSingleTokenTypeExactAmountOut,
+            DecreaseLiquidity, DepositAllTokenTypes, DepositSingleTokenTypeExactAmountIn,
+            IncreaseLiquidity, Initialize, InitializeConcentratedPool, OpenPosition,
+            SetPauseAuthority, SetPaused, Swap, SwapInstruction, WithdrawAllTokenTypes,
+            WithdrawSingleTokenTypeExactAmountOut,
+        },
+        state::{
+            ConcentratedPool, Position, SwapState, SwapV1, SwapV2, SwapVersion,
+            CONCENTRATED_POOL_TAG,
         },
-        state::{SwapState, SwapV1, SwapVersion},
     },
     num_traits::FromPrimitive,
     solana_program::{
@@ -26,6 +32,7 @@ use {
         program::invoke_signed,
         program_error::{PrintProgramError, ProgramError},
         program_option::COption,
+        program_pack::Pack,
         pubkey::Pubkey,
         sysvar::Sysvar,
     },
@@ -38,7 +45,10 @@ use {
         },
         state::{Account, Mint},
     },
-    std::{convert::TryInto, error::Error},
+    std::{
+        convert::{TryFrom, TryInto},
+        error::Error,
+    },
 };
 
 /// Program state handler.
@@ -361,19 +371,45 @@ impl Processor {
             to_u64(initial_amount)?,
         )?;
 
-        let obj = SwapVersion::SwapV1(SwapV1 {
-            is_initialized: true,
-            bump_seed,
-            token_program_id,
-            token_a: *token_a_info.key,
-            token_b: *token_b_info.key,
-            pool_mint: *pool_mint_info.key,
-            token_a_mint: token_a.mint,
-            token_b_mint: token_b.mint,
-            pool_fee_account: *fee_account_info.key,
-            fees,
-            swap_curve,
-        });
+        // Accounts allocated for the original layout have no room for the
+        // pause authority, so they are initialized as unpausable swaps
+        let obj = if swap_info.data_len() == SwapVersion::V1_LEN {
+            SwapVersion::SwapV1(SwapV1 {
+                is_initialized: true,
+                bump_seed,
+                token_program_id,
+                token_a: *token_a_info.key,
+                token_b: *token_b_info.key,
+                pool_mint: *pool_mint_info.key,
+                token_a_mint: token_a.mint,
+                token_b_mint: token_b.mint,
+                pool_fee_account: *fee_account_info.key,
+                fees,
+                swap_curve,
+            })
+        } else if swap_info.data_len() == SwapVersion::LATEST_LEN {
+            SwapVersion::SwapV2(SwapV2 {
+                is_initialized: true,
+                bump_seed,
+                token_program_id,
+                token_a: *token_a_info.key,
+                token_b: *token_b_info.key,
+                pool_mint: *pool_mint_info.key,
+                token_a_mint: token_a.mint,
+                token_b_mint: token_b.mint,
+                pool_fee_account: *fee_account_info.key,
+                fees,
+                swap_curve,
+                pause_authority: fee_account.owner,
+                is_paused: false,
+            })
+        } else if swap_info.data_len() > SwapVersion::LATEST_LEN {
+            // the state is unpacked from the exact layout length, so an
+            // oversized account could never be used again after initializing
+            return Err(ProgramError::InvalidAccountData);
+        } else {
+            return Err(ProgramError::AccountDataTooSmall);
+        };
         SwapVersion::pack(obj, &mut swap_info.data.borrow_mut())?;
         Ok(())
     }
@@ -405,6 +441,9 @@ impl Processor {
             return Err(ProgramError::IncorrectProgramId);
         }
         let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+        if token_swap.is_paused() {
+            return Err(SwapError::SwapPaused.into());
+        }
 
         if *authority_info.key
             != Self::authority_id(program_id, swap_info.key, token_swap.bump_seed())?
@@ -651,6 +690,9 @@ impl Processor {
         let pool_token_program_info = next_account_info(account_info_iter)?;
 
         let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+        if token_swap.is_paused() {
+            return Err(SwapError::SwapPaused.into());
+        }
         let calculator = &token_swap.swap_curve().calculator;
         if !calculator.allows_deposits() {
             return Err(SwapError::UnsupportedCurveOperation.into());
@@ -903,6 +945,9 @@ impl Processor {
         let pool_token_program_info = next_account_info(account_info_iter)?;
 
         let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+        if token_swap.is_paused() {
+            return Err(SwapError::SwapPaused.into());
+        }
         let calculator = &token_swap.swap_curve().calculator;
         if !calculator.allows_deposits() {
             return Err(SwapError::UnsupportedCurveOperation.into());
@@ -1033,6 +1078,9 @@ impl Processor {
         let destination_token_program_info = next_account_info(account_info_iter)?;
 
         let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+        if token_swap.is_paused() {
+            return Err(SwapError::SwapPaused.into());
+        }
         let destination_account =
             Self::unpack_token_account(destination_info, token_swap.token_program_id())?;
         let swap_token_a =
@@ -1165,4104 +1213,3647 @@ impl Processor {
         Ok(())
     }
 
-    /// Processes an [Instruction](enum.Instruction.html).
-    pub fn process(program_id: &Pubkey, accounts: &[AccountInfo], input: &[u8]) -> ProgramResult {
-        Self::process_with_constraints(program_id, accounts, input, &SWAP_CONSTRAINTS)
-    }
-
-    /// Processes an instruction given extra constraint
-    pub fn process_with_constraints(
+    /// Unpacks the latest version of a swap, checking that its pause
+    /// authority signed
+    fn unpack_swap_for_pause_authority(
         program_id: &Pubkey,
-        accounts: &[AccountInfo],
-        input: &[u8],
-        swap_constraints: &Option<SwapConstraints>,
-    ) -> ProgramResult {
-        let instruction = SwapInstruction::unpack(input)?;
-        match instruction {
-            SwapInstruction::Initialize(Initialize { fees, swap_curve }) => {
-                msg!("Instruction: Init");
-                Self::process_initialize(program_id, fees, swap_curve, accounts, swap_constraints)
-            }
-            SwapInstruction::Swap(Swap {
-                amount_in,
-                minimum_amount_out,
-            }) => {
-                msg!("Instruction: Swap");
-                Self::process_swap(program_id, amount_in, minimum_amount_out, accounts)
-            }
-            SwapInstruction::DepositAllTokenTypes(DepositAllTokenTypes {
-                pool_token_amount,
-                maximum_token_a_amount,
-                maximum_token_b_amount,
-            }) => {
-                msg!("Instruction: DepositAllTokenTypes");
-                Self::process_deposit_all_token_types(
-                    program_id,
-                    pool_token_amount,
-                    maximum_token_a_amount,
-                    maximum_token_b_amount,
-                    accounts,
-                )
-            }
-            SwapInstruction::WithdrawAllTokenTypes(WithdrawAllTokenTypes {
-                pool_token_amount,
-                minimum_token_a_amount,
-                minimum_token_b_amount,
-            }) => {
-                msg!("Instruction: WithdrawAllTokenTypes");
-                Self::process_withdraw_all_token_types(
-                    program_id,
-                    pool_token_amount,
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
-                    accounts,
-                )
-            }
-            SwapInstruction::DepositSingleTokenTypeExactAmountIn(
-                DepositSingleTokenTypeExactAmountIn {
-                    source_token_amount,
-                    minimum_pool_token_amount,
-                },
-            ) => {
-                msg!("Instruction: DepositSingleTokenTypeExactAmountIn");
-                Self::process_deposit_single_token_type_exact_amount_in(
-                    program_id,
-                    source_token_amount,
-                    minimum_pool_token_amount,
-                    accounts,
-                )
-            }
-            SwapInstruction::WithdrawSingleTokenTypeExactAmountOut(
-                WithdrawSingleTokenTypeExactAmountOut {
-                    destination_token_amount,
-                    maximum_pool_token_amount,
-                },
-            ) => {
-                msg!("Instruction: WithdrawSingleTokenTypeExactAmountOut");
-                Self::process_withdraw_single_token_type_exact_amount_out(
-                    program_id,
-                    destination_token_amount,
-                    maximum_pool_token_amount,
-                    accounts,
-                )
-            }
+        swap_info: &AccountInfo,
+        pause_authority_info: &AccountInfo,
+    ) -> Result<SwapV2, ProgramError> {
+        if swap_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let token_swap = SwapVersion::unpack_latest(&swap_info.data.borrow())?;
+        if *pause_authority_info.key != token_swap.pause_authority {
+            return Err(SwapError::IncorrectPauseAuthority.into());
         }
+        if !pause_authority_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        Ok(token_swap)
     }
-}
-
-fn to_u64(val: u128) -> Result<u64, SwapError> {
-    val.try_into().map_err(|_| SwapError::ConversionFailure)
-}
 
-fn invoke_signed_wrapper<T>(
-    instruction: &Instruction,
-    account_infos: &[AccountInfo],
-    signers_seeds: &[&[&[u8]]],
-) -> Result<(), ProgramError>
-where
-    T: 'static + PrintProgramError + DecodeError<T> + FromPrimitive + Error,
-{
-    invoke_signed(instruction, account_infos, signers_seeds).map_err(|err| {
-        err.print::<T>();
-        err
-    })
-}
-
-#[cfg(test)]
-mod tests {
-    use {
-        super::*,
-        crate::{
-            curve::{
-                base::CurveType,
-                calculator::{CurveCalculator, INITIAL_SWAP_POOL_AMOUNT},
-                constant_price::ConstantPriceCurve,
-                constant_product::ConstantProductCurve,
-                offset::OffsetCurve,
-            },
-            instruction::{
-                deposit_all_token_types, deposit_single_token_type_exact_amount_in, initialize,
-                swap, withdraw_all_token_types, withdraw_single_token_type_exact_amount_out,
-            },
-        },
-        solana_program::{
-            clock::Clock, entrypoint::SUCCESS, instruction::Instruction, program_pack::Pack,
-            program_stubs, rent::Rent,
-        },
-        solana_sdk::account::{
-            create_account_for_test, create_is_signer_account_infos, Account as SolanaAccount,
-        },
-        spl_token_2022::{
-            error::TokenError,
-            extension::{
-                transfer_fee::{instruction::initialize_transfer_fee_config, TransferFee},
-                ExtensionType,
-            },
-            instruction::{
-                approve, close_account, freeze_account, initialize_account,
-                initialize_immutable_owner, initialize_mint, initialize_mint_close_authority,
-                mint_to, revoke, set_authority, AuthorityType,
-            },
-        },
-        std::sync::Arc,
-        test_case::test_case,
-    };
+    /// Checks whether the account holds a concentrated liquidity pool rather
+    /// than a swap
+    fn is_concentrated_pool(swap_info: &AccountInfo) -> bool {
+        swap_info.data.borrow().first() == Some(&CONCENTRATED_POOL_TAG)
+    }
 
-    // Test program id for the swap program.
-    const SWAP_PROGRAM_ID: Pubkey = Pubkey::new_from_array([2u8; 32]);
+    /// Unpacks a concentrated liquidity pool, checking that its pause
+    /// authority signed
+    fn unpack_concentrated_pool_for_pause_authority(
+        program_id: &Pubkey,
+        pool_info: &AccountInfo,
+        pause_authority_info: &AccountInfo,
+    ) -> Result<ConcentratedPool, ProgramError> {
+        if pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let pool = ConcentratedPool::unpack(&pool_info.data.borrow())?;
+        if *pause_authority_info.key != pool.pause_authority {
+            return Err(SwapError::IncorrectPauseAuthority.into());
+        }
+        if !pause_authority_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        Ok(pool)
+    }
 
-    struct TestSyscallStubs {}
-    impl program_stubs::SyscallStubs for TestSyscallStubs {
-        fn sol_invoke_signed(
-            &self,
-            instruction: &Instruction,
-            account_infos: &[AccountInfo],
-            signers_seeds: &[&[&[u8]]],
-        ) -> ProgramResult {
-            msg!("TestSyscallStubs::sol_invoke_signed()");
+    /// Processes a [SetPaused](enum.Instruction.html).
+    pub fn process_set_paused(
+        program_id: &Pubkey,
+        paused: bool,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let pause_authority_info = next_account_info(account_info_iter)?;
 
-            let mut new_account_infos = vec![];
+        if Self::is_concentrated_pool(swap_info) {
+            let mut pool = Self::unpack_concentrated_pool_for_pause_authority(
+                program_id,
+                swap_info,
+                pause_authority_info,
+            )?;
+            pool.is_paused = paused;
+            ConcentratedPool::pack(pool, &mut swap_info.data.borrow_mut())?;
+            return Ok(());
+        }
+        let mut token_swap =
+            Self::unpack_swap_for_pause_authority(program_id, swap_info, pause_authority_info)?;
+        token_swap.is_paused = paused;
+        SwapVersion::pack(
+            SwapVersion::SwapV2(token_swap),
+            &mut swap_info.data.borrow_mut(),
+        )?;
+        Ok(())
+    }
 
-            // mimic check for token program in accounts
-            if !account_infos
-                .iter()
-                .any(|x| *x.key == spl_token::id() || *x.key == spl_token_2022::id())
-            {
-                return Err(ProgramError::InvalidAccountData);
-            }
+    /// Processes a [SetPauseAuthority](enum.Instruction.html).
+    pub fn process_set_pause_authority(
+        program_id: &Pubkey,
+        new_pause_authority: Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let pause_authority_info = next_account_info(account_info_iter)?;
 
-            for meta in instruction.accounts.iter() {
-                for account_info in account_infos.iter() {
-                    if meta.pubkey == *account_info.key {
-                        let mut new_account_info = account_info.clone();
-                        for seeds in signers_seeds.iter() {
-                            let signer =
-                                Pubkey::create_program_address(seeds, &SWAP_PROGRAM_ID).unwrap();
-                            if *account_info.key == signer {
-                                new_account_info.is_signer = true;
-                            }
-                        }
-                        new_account_infos.push(new_account_info);
-                    }
-                }
-            }
+        if Self::is_concentrated_pool(swap_info) {
+            let mut pool = Self::unpack_concentrated_pool_for_pause_authority(
+                program_id,
+                swap_info,
+                pause_authority_info,
+            )?;
+            pool.pause_authority = new_pause_authority;
+            ConcentratedPool::pack(pool, &mut swap_info.data.borrow_mut())?;
+            return Ok(());
+        }
+        let mut token_swap =
+            Self::unpack_swap_for_pause_authority(program_id, swap_info, pause_authority_info)?;
+        token_swap.pause_authority = new_pause_authority;
+        SwapVersion::pack(
+            SwapVersion::SwapV2(token_swap),
+            &mut swap_info.data.borrow_mut(),
+        )?;
+        Ok(())
+    }
 
-            if instruction.program_id == spl_token::id() {
-                spl_token::processor::Processor::process(
-                    &instruction.program_id,
-                    &new_account_infos,
-                    &instruction.data,
-                )
-            } else if instruction.program_id == spl_token_2022::id() {
-                spl_token_2022::processor::Processor::process(
-                    &instruction.program_id,
-                    &new_account_infos,
-                    &instruction.data,
-                )
-            } else {
-                Err(ProgramError::IncorrectProgramId)
-            }
+    /// Unpacks a concentrated liquidity pool, checking its pool authority
+    fn unpack_concentrated_pool(
+        program_id: &Pubkey,
+        pool_info: &AccountInfo,
+        authority_info: &AccountInfo,
+    ) -> Result<ConcentratedPool, ProgramError> {
+        if pool_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
         }
-
-        fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
-            unsafe {
-                *(var_addr as *mut _ as *mut Clock) = Clock::default();
-            }
-            SUCCESS
+        let pool = ConcentratedPool::unpack(&pool_info.data.borrow())?;
+        if *authority_info.key != Self::authority_id(program_id, pool_info.key, pool.bump_seed)? {
+            return Err(SwapError::InvalidProgramAddress.into());
         }
+        Ok(pool)
     }
 
-    fn test_syscall_stubs() {
-        use std::sync::Once;
-        static ONCE: Once = Once::new();
+    /// Unpacks a concentrated liquidity position, checking that it belongs to
+    /// the pool and that its owner signed
+    fn unpack_position(
+        program_id: &Pubkey,
+        position_info: &AccountInfo,
+        pool_key: &Pubkey,
+        owner_info: &AccountInfo,
+    ) -> Result<Position, ProgramError> {
+        if position_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let position = Position::unpack(&position_info.data.borrow())?;
+        if position.pool != *pool_key || position.owner != *owner_info.key {
+            return Err(SwapError::IncorrectPosition.into());
+        }
+        if !owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        Ok(position)
+    }
 
-        ONCE.call_once(|| {
-            program_stubs::set_syscall_stubs(Box::new(TestSyscallStubs {}));
-        });
+    /// Checks that a pool token account is the expected one, and owned by the
+    /// given token program
+    fn check_pool_token_account(
+        token_info: &AccountInfo,
+        expected_key: &Pubkey,
+        token_program_info: &AccountInfo,
+    ) -> ProgramResult {
+        if token_info.key != expected_key {
+            return Err(SwapError::IncorrectSwapAccount.into());
+        }
+        if token_info.owner != token_program_info.key {
+            return Err(SwapError::IncorrectTokenProgramId.into());
+        }
+        Ok(())
     }
 
-    #[derive(Default)]
-    struct SwapTransferFees {
-        pool_token: TransferFee,
-        token_a: TransferFee,
-        token_b: TransferFee,
+    /// Gets the amount to transfer so that the destination receives `amount`
+    /// after the mint's transfer fee, along with the mint decimals
+    fn transfer_amount_with_fee(
+        mint_info: &AccountInfo,
+        token_program_id: &Pubkey,
+        amount: u64,
+    ) -> Result<(u64, u8), ProgramError> {
+        let mint_data = mint_info.data.borrow();
+        let mint =
+            Self::unpack_mint_with_extensions(&mint_data, mint_info.owner, token_program_id)?;
+        let amount = if let Ok(transfer_fee_config) = mint.get_extension::<TransferFeeConfig>() {
+            amount.saturating_add(
+                transfer_fee_config
+                    .calculate_inverse_epoch_fee(Clock::get()?.epoch, amount)
+                    .ok_or(SwapError::FeeCalculationFailure)?,
+            )
+        } else {
+            amount
+        };
+        Ok((amount, mint.base.decimals))
     }
 
-    struct SwapAccountInfo {
-        bump_seed: u8,
-        authority_key: Pubkey,
-        fees: Fees,
-        transfer_fees: SwapTransferFees,
-        swap_curve: SwapCurve,
-        swap_key: Pubkey,
-        swap_account: SolanaAccount,
-        pool_mint_key: Pubkey,
-        pool_mint_account: SolanaAccount,
-        pool_fee_key: Pubkey,
-        pool_fee_account: SolanaAccount,
-        pool_token_key: Pubkey,
-        pool_token_account: SolanaAccount,
-        token_a_key: Pubkey,
-        token_a_account: SolanaAccount,
-        token_a_mint_key: Pubkey,
-        token_a_mint_account: SolanaAccount,
-        token_b_key: Pubkey,
-        token_b_account: SolanaAccount,
-        token_b_mint_key: Pubkey,
-        token_b_mint_account: SolanaAccount,
-        pool_token_program_id: Pubkey,
-        token_a_program_id: Pubkey,
-        token_b_program_id: Pubkey,
+    /// Gets the amount received after the mint's transfer fee when
+    /// transferring `amount`, along with the mint decimals
+    fn amount_received_after_fee(
+        mint_info: &AccountInfo,
+        token_program_id: &Pubkey,
+        amount: u64,
+    ) -> Result<(u64, u8), ProgramError> {
+        let mint_data = mint_info.data.borrow();
+        let mint =
+            Self::unpack_mint_with_extensions(&mint_data, mint_info.owner, token_program_id)?;
+        let amount = if let Ok(transfer_fee_config) = mint.get_extension::<TransferFeeConfig>() {
+            amount.saturating_sub(
+                transfer_fee_config
+                    .calculate_epoch_fee(Clock::get()?.epoch, amount)
+                    .ok_or(SwapError::FeeCalculationFailure)?,
+            )
+        } else {
+            amount
+        };
+        Ok((amount, mint.base.decimals))
     }
 
-    impl SwapAccountInfo {
-        #[allow(clippy::too_many_arguments)]
-        pub fn new(
-            user_key: &Pubkey,
-            fees: Fees,
-            transfer_fees: SwapTransferFees,
-            swap_curve: SwapCurve,
-            token_a_amount: u64,
-            token_b_amount: u64,
-            pool_token_program_id: &Pubkey,
-            token_a_program_id: &Pubkey,
-            token_b_program_id: &Pubkey,
-        ) -> Self {
-            let swap_key = Pubkey::new_unique();
-            let swap_account = SolanaAccount::new(0, SwapVersion::LATEST_LEN, &SWAP_PROGRAM_ID);
-            let (authority_key, bump_seed) =
-                Pubkey::find_program_address(&[&swap_key.to_bytes()[..]], &SWAP_PROGRAM_ID);
-
-            let (pool_mint_key, mut pool_mint_account) = create_mint(
-                pool_token_program_id,
-                &authority_key,
-                None,
-                None,
-                &transfer_fees.pool_token,
-            );
-            let (pool_token_key, pool_token_account) = mint_token(
-                pool_token_program_id,
-                &pool_mint_key,
-                &mut pool_mint_account,
-                &authority_key,
-                user_key,
-                0,
-            );
-            let (pool_fee_key, pool_fee_account) = mint_token(
-                pool_token_program_id,
-                &pool_mint_key,
-                &mut pool_mint_account,
-                &authority_key,
-                user_key,
-                0,
-            );
-            let (token_a_mint_key, mut token_a_mint_account) = create_mint(
-                token_a_program_id,
-                user_key,
-                None,
-                None,
-                &transfer_fees.token_a,
-            );
-            let (token_a_key, token_a_account) = mint_token(
-                token_a_program_id,
-                &token_a_mint_key,
-                &mut token_a_mint_account,
-                user_key,
-                &authority_key,
-                token_a_amount,
-            );
-            let (token_b_mint_key, mut token_b_mint_account) = create_mint(
-                token_b_program_id,
-                user_key,
-                None,
-                None,
-                &transfer_fees.token_b,
-            );
-            let (token_b_key, token_b_account) = mint_token(
-                token_b_program_id,
-                &token_b_mint_key,
-                &mut token_b_mint_account,
-                user_key,
-                &authority_key,
-                token_b_amount,
-            );
+    /// Processes an [InitializeConcentratedPool](enum.Instruction.html).
+    pub fn process_initialize_concentrated_pool(
+        program_id: &Pubkey,
+        fees: Fees,
+        tick_spacing: u16,
+        initial_sqrt_price: u128,
+        min_position_liquidity: u128,
+        accounts: &[AccountInfo],
+        swap_constraints: &Option<SwapConstraints>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let token_a_info = next_account_info(account_info_iter)?;
+        let token_b_info = next_account_info(account_info_iter)?;
+        let fee_authority_info = next_account_info(account_info_iter)?;
 
-            SwapAccountInfo {
-                bump_seed,
-                authority_key,
-                fees,
-                transfer_fees,
-                swap_curve,
-                swap_key,
-                swap_account,
-                pool_mint_key,
-                pool_mint_account,
-                pool_fee_key,
-                pool_fee_account,
-                pool_token_key,
-                pool_token_account,
-                token_a_key,
-                token_a_account,
-                token_a_mint_key,
-                token_a_mint_account,
-                token_b_key,
-                token_b_account,
-                token_b_mint_key,
-                token_b_mint_account,
-                pool_token_program_id: *pool_token_program_id,
-                token_a_program_id: *token_a_program_id,
-                token_b_program_id: *token_b_program_id,
-            }
+        if SwapVersion::is_initialized(&pool_info.data.borrow()) {
+            return Err(SwapError::AlreadyInUse.into());
         }
 
-        pub fn initialize_swap(&mut self) -> ProgramResult {
-            do_process_instruction(
-                initialize(
-                    &SWAP_PROGRAM_ID,
-                    &self.pool_token_program_id,
-                    &self.swap_key,
-                    &self.authority_key,
-                    &self.token_a_key,
-                    &self.token_b_key,
-                    &self.pool_mint_key,
-                    &self.pool_fee_key,
-                    &self.pool_token_key,
-                    self.fees.clone(),
-                    self.swap_curve.clone(),
-                )
-                .unwrap(),
-                vec![
-                    &mut self.swap_account,
-                    &mut SolanaAccount::default(),
-                    &mut self.token_a_account,
-                    &mut self.token_b_account,
-                    &mut self.pool_mint_account,
-                    &mut self.pool_fee_account,
-                    &mut self.pool_token_account,
-                    &mut SolanaAccount::default(),
-                ],
-            )
+        let (pool_authority, bump_seed) =
+            Pubkey::find_program_address(&[&pool_info.key.to_bytes()], program_id);
+        if *authority_info.key != pool_authority {
+            return Err(SwapError::InvalidProgramAddress.into());
         }
-
-        pub fn setup_token_accounts(
-            &mut self,
-            mint_owner: &Pubkey,
-            account_owner: &Pubkey,
-            a_amount: u64,
-            b_amount: u64,
-            pool_amount: u64,
-        ) -> (
-            Pubkey,
-            SolanaAccount,
-            Pubkey,
-            SolanaAccount,
-            Pubkey,
-            SolanaAccount,
-        ) {
-            let (token_a_key, token_a_account) = mint_token(
-                &self.token_a_program_id,
-                &self.token_a_mint_key,
-                &mut self.token_a_mint_account,
-                mint_owner,
-                account_owner,
-                a_amount,
-            );
-            let (token_b_key, token_b_account) = mint_token(
-                &self.token_b_program_id,
-                &self.token_b_mint_key,
-                &mut self.token_b_mint_account,
-                mint_owner,
-                account_owner,
-                b_amount,
-            );
-            let (pool_key, pool_account) = mint_token(
-                &self.pool_token_program_id,
-                &self.pool_mint_key,
-                &mut self.pool_mint_account,
-                &self.authority_key,
-                account_owner,
-                pool_amount,
-            );
-            (
-                token_a_key,
-                token_a_account,
-                token_b_key,
-                token_b_account,
-                pool_key,
-                pool_account,
-            )
+        check_spl_token_program_account(token_a_info.owner)
+            .map_err(|_| SwapError::IncorrectTokenProgramId)?;
+        check_spl_token_program_account(token_b_info.owner)
+            .map_err(|_| SwapError::IncorrectTokenProgramId)?;
+        let token_a = Self::unpack_token_account(token_a_info, token_a_info.owner)?;
+        let token_b = Self::unpack_token_account(token_b_info, token_b_info.owner)?;
+        if *authority_info.key != token_a.owner {
+            return Err(SwapError::InvalidOwner.into());
         }
-
-        fn get_swap_key(&self, mint_key: &Pubkey) -> &Pubkey {
-            if *mint_key == self.token_a_mint_key {
-                &self.token_a_key
-            } else if *mint_key == self.token_b_mint_key {
-                &self.token_b_key
-            } else {
-                panic!("Could not find matching swap token account");
-            }
+        if *authority_info.key != token_b.owner {
+            return Err(SwapError::InvalidOwner.into());
         }
-
-        fn get_token_program_id(&self, account_key: &Pubkey) -> &Pubkey {
-            if *account_key == self.token_a_key {
-                &self.token_a_program_id
-            } else if *account_key == self.token_b_key {
-                &self.token_b_program_id
-            } else {
-                panic!("Could not find matching swap token account");
-            }
+        if token_a.mint == token_b.mint {
+            return Err(SwapError::RepeatedMint.into());
         }
-
-        fn get_token_mint(&self, account_key: &Pubkey) -> (Pubkey, SolanaAccount) {
-            if *account_key == self.token_a_key {
-                (self.token_a_mint_key, self.token_a_mint_account.clone())
-            } else if *account_key == self.token_b_key {
-                (self.token_b_mint_key, self.token_b_mint_account.clone())
-            } else {
-                panic!("Could not find matching swap token account");
-            }
+        if token_a.delegate.is_some() {
+            return Err(SwapError::InvalidDelegate.into());
+        }
+        if token_b.delegate.is_some() {
+            return Err(SwapError::InvalidDelegate.into());
+        }
+        if token_a.close_authority.is_some() {
+            return Err(SwapError::InvalidCloseAuthority.into());
+        }
+        if token_b.close_authority.is_some() {
+            return Err(SwapError::InvalidCloseAuthority.into());
         }
 
-        fn get_token_account(&self, account_key: &Pubkey) -> &SolanaAccount {
-            if *account_key == self.token_a_key {
-                &self.token_a_account
-            } else if *account_key == self.token_b_key {
-                &self.token_b_account
-            } else {
-                panic!("Could not find matching swap token account");
+        if let Some(swap_constraints) = swap_constraints {
+            let owner_key = swap_constraints
+                .owner_key
+                .parse::<Pubkey>()
+                .map_err(|_| SwapError::InvalidOwner)?;
+            if *fee_authority_info.key != owner_key {
+                return Err(SwapError::InvalidOwner.into());
             }
+            swap_constraints.validate_curve_type(CurveType::ConcentratedLiquidity)?;
+            swap_constraints.validate_fees(&fees)?;
         }
+        fees.validate()?;
 
-        fn set_token_account(&mut self, account_key: &Pubkey, account: SolanaAccount) {
-            if *account_key == self.token_a_key {
-                self.token_a_account = account;
-                return;
-            } else if *account_key == self.token_b_key {
-                self.token_b_account = account;
-                return;
-            }
-            panic!("Could not find matching swap token account");
+        if tick_spacing == 0 {
+            return Err(SwapError::InvalidTick.into());
         }
+        let current_tick = tick_at_sqrt_price(initial_sqrt_price).ok_or(SwapError::InvalidTick)?;
 
-        #[allow(clippy::too_many_arguments)]
-        pub fn swap(
-            &mut self,
-            user_key: &Pubkey,
-            user_source_key: &Pubkey,
-            user_source_account: &mut SolanaAccount,
-            swap_source_key: &Pubkey,
-            swap_destination_key: &Pubkey,
-            user_destination_key: &Pubkey,
-            user_destination_account: &mut SolanaAccount,
-            amount_in: u64,
-            minimum_amount_out: u64,
-        ) -> ProgramResult {
-            let user_transfer_key = Pubkey::new_unique();
-            let source_token_program_id = self.get_token_program_id(swap_source_key);
-            let destination_token_program_id = self.get_token_program_id(swap_destination_key);
-            // approve moving from user source account
-            do_process_instruction(
-                approve(
-                    source_token_program_id,
-                    user_source_key,
-                    &user_transfer_key,
-                    user_key,
-                    &[],
-                    amount_in,
-                )
-                .unwrap(),
-                vec![
-                    user_source_account,
-                    &mut SolanaAccount::default(),
-                    &mut SolanaAccount::default(),
-                ],
-            )
-            .unwrap();
+        let pool = ConcentratedPool {
+            is_initialized: true,
+            bump_seed,
+            token_a: *token_a_info.key,
+            token_b: *token_b_info.key,
+            token_a_mint: token_a.mint,
+            token_b_mint: token_b.mint,
+            fee_authority: *fee_authority_info.key,
+            pause_authority: *fee_authority_info.key,
+            fees,
+            tick_spacing,
+            min_position_liquidity,
+            current_tick,
+            sqrt_price: initial_sqrt_price,
+            ..ConcentratedPool::default()
+        };
+        ConcentratedPool::pack(pool, &mut pool_info.data.borrow_mut())?;
+        Ok(())
+    }
 
-            let (source_mint_key, mut source_mint_account) = self.get_token_mint(swap_source_key);
-            let (destination_mint_key, mut destination_mint_account) =
-                self.get_token_mint(swap_destination_key);
-            let mut swap_source_account = self.get_token_account(swap_source_key).clone();
-            let mut swap_destination_account = self.get_token_account(swap_destination_key).clone();
+    /// Processes an [OpenPosition](enum.Instruction.html).
+    pub fn process_open_position(
+        program_id: &Pubkey,
+        tick_lower: i32,
+        tick_upper: i32,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_info = next_account_info(account_info_iter)?;
+        let position_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
 
-            // perform the swap
-            do_process_instruction(
-                swap(
-                    &SWAP_PROGRAM_ID,
-                    source_token_program_id,
-                    destination_token_program_id,
-                    &self.pool_token_program_id,
-                    &self.swap_key,
-                    &self.authority_key,
-                    &user_transfer_key,
-                    user_source_key,
-                    swap_source_key,
-                    swap_destination_key,
-                    user_destination_key,
-                    &self.pool_mint_key,
-                    &self.pool_fee_key,
-                    &source_mint_key,
-                    &destination_mint_key,
-                    None,
-                    Swap {
-                        amount_in,
-                        minimum_amount_out,
-                    },
-                )
-                .unwrap(),
-                vec![
-                    &mut self.swap_account,
-                    &mut SolanaAccount::default(),
-                    &mut SolanaAccount::default(),
-                    user_source_account,
-                    &mut swap_source_account,
-                    &mut swap_destination_account,
-                    user_destination_account,
-                    &mut self.pool_mint_account,
-                    &mut self.pool_fee_account,
-                    &mut source_mint_account,
-                    &mut destination_mint_account,
-                    &mut SolanaAccount::default(),
-                    &mut SolanaAccount::default(),
-                    &mut SolanaAccount::default(),
-                ],
-            )?;
+        if pool_info.owner != program_id || position_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let pool = ConcentratedPool::unpack(&pool_info.data.borrow())?;
+        if pool.is_paused {
+            return Err(SwapError::SwapPaused.into());
+        }
+        if SwapVersion::is_initialized(&position_info.data.borrow()) {
+            return Err(SwapError::AlreadyInUse.into());
+        }
+        if !owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        pool.validate_tick(tick_lower)?;
+        pool.validate_tick(tick_upper)?;
+        if tick_lower >= tick_upper {
+            return Err(SwapError::InvalidTick.into());
+        }
 
-            self.set_token_account(swap_source_key, swap_source_account);
-            self.set_token_account(swap_destination_key, swap_destination_account);
+        let position = Position {
+            is_initialized: true,
+            pool: *pool_info.key,
+            owner: *owner_info.key,
+            tick_lower,
+            tick_upper,
+            ..Position::default()
+        };
+        Position::pack(position, &mut position_info.data.borrow_mut())?;
+        Ok(())
+    }
 
-            Ok(())
+    /// Processes an [IncreaseLiquidity](enum.Instruction.html).
+    pub fn process_increase_liquidity(
+        program_id: &Pubkey,
+        liquidity: u128,
+        maximum_token_a_amount: u64,
+        maximum_token_b_amount: u64,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let position_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+        let user_transfer_authority_info = next_account_info(account_info_iter)?;
+        let source_a_info = next_account_info(account_info_iter)?;
+        let source_b_info = next_account_info(account_info_iter)?;
+        let token_a_info = next_account_info(account_info_iter)?;
+        let token_b_info = next_account_info(account_info_iter)?;
+        let token_a_mint_info = next_account_info(account_info_iter)?;
+        let token_b_mint_info = next_account_info(account_info_iter)?;
+        let token_a_program_info = next_account_info(account_info_iter)?;
+        let token_b_program_info = next_account_info(account_info_iter)?;
+
+        let mut pool = Self::unpack_concentrated_pool(program_id, pool_info, authority_info)?;
+        if pool.is_paused {
+            return Err(SwapError::SwapPaused.into());
+        }
+        let mut position =
+            Self::unpack_position(program_id, position_info, pool_info.key, owner_info)?;
+        Self::check_pool_token_account(token_a_info, &pool.token_a, token_a_program_info)?;
+        Self::check_pool_token_account(token_b_info, &pool.token_b, token_b_program_info)?;
+        if source_a_info.key == token_a_info.key || source_b_info.key == token_b_info.key {
+            return Err(SwapError::InvalidInput.into());
+        }
+        if liquidity == 0 {
+            return Err(SwapError::InvalidInput.into());
         }
 
-        #[allow(clippy::too_many_arguments)]
-        pub fn deposit_all_token_types(
-            &mut self,
-            depositor_key: &Pubkey,
-            depositor_token_a_key: &Pubkey,
-            depositor_token_a_account: &mut SolanaAccount,
-            depositor_token_b_key: &Pubkey,
-            depositor_token_b_account: &mut SolanaAccount,
-            depositor_pool_key: &Pubkey,
-            depositor_pool_account: &mut SolanaAccount,
-            pool_token_amount: u64,
-            maximum_token_a_amount: u64,
-            maximum_token_b_amount: u64,
-        ) -> ProgramResult {
-            let user_transfer_authority = Pubkey::new_unique();
-            let token_a_program_id = depositor_token_a_account.owner;
-            do_process_instruction(
-                approve(
-                    &token_a_program_id,
-                    depositor_token_a_key,
-                    &user_transfer_authority,
-                    depositor_key,
-                    &[],
-                    maximum_token_a_amount,
-                )
-                .unwrap(),
-                vec![
-                    depositor_token_a_account,
-                    &mut SolanaAccount::default(),
-                    &mut SolanaAccount::default(),
-                ],
-            )
-            .unwrap();
+        let liquidity_delta =
+            i128::try_from(liquidity).map_err(|_| SwapError::ConversionFailure)?;
+        let results = pool.modify_position(&mut position, liquidity_delta)?;
 
-            let token_b_program_id = depositor_token_b_account.owner;
-            do_process_instruction(
-                approve(
-                    &token_b_program_id,
-                    depositor_token_b_key,
-                    &user_transfer_authority,
-                    depositor_key,
-                    &[],
-                    maximum_token_b_amount,
-                )
-                .unwrap(),
-                vec![
-                    depositor_token_b_account,
-                    &mut SolanaAccount::default(),
-                    &mut SolanaAccount::default(),
-                ],
-            )
-            .unwrap();
+        let (token_a_amount, token_a_decimals) = Self::transfer_amount_with_fee(
+            token_a_mint_info,
+            token_a_program_info.key,
+            to_u64(results.token_a_amount)?,
+        )?;
+        if token_a_amount > maximum_token_a_amount {
+            return Err(SwapError::ExceededSlippage.into());
+        }
+        let (token_b_amount, token_b_decimals) = Self::transfer_amount_with_fee(
+            token_b_mint_info,
+            token_b_program_info.key,
+            to_u64(results.token_b_amount)?,
+        )?;
+        if token_b_amount > maximum_token_b_amount {
+            return Err(SwapError::ExceededSlippage.into());
+        }
 
-            let pool_token_program_id = depositor_pool_account.owner;
-            do_process_instruction(
-                deposit_all_token_types(
-                    &SWAP_PROGRAM_ID,
-                    &token_a_program_id,
-                    &token_b_program_id,
-                    &pool_token_program_id,
-                    &self.swap_key,
-                    &self.authority_key,
-                    &user_transfer_authority,
-                    depositor_token_a_key,
-                    depositor_token_b_key,
-                    &self.token_a_key,
-                    &self.token_b_key,
-                    &self.pool_mint_key,
-                    depositor_pool_key,
-                    &self.token_a_mint_key,
-                    &self.token_b_mint_key,
-                    DepositAllTokenTypes {
-                        pool_token_amount,
-                        maximum_token_a_amount,
-                        maximum_token_b_amount,
-                    },
-                )
-                .unwrap(),
-                vec![
-                    &mut self.swap_account,
-                    &mut SolanaAccount::default(),
-                    &mut SolanaAccount::default(),
-                    depositor_token_a_account,
-                    depositor_token_b_account,
-                    &mut self.token_a_account,
-                    &mut self.token_b_account,
-                    &mut self.pool_mint_account,
-                    depositor_pool_account,
-                    &mut self.token_a_mint_account,
-                    &mut self.token_b_mint_account,
-                    &mut SolanaAccount::default(),
-                    &mut SolanaAccount::default(),
-                    &mut SolanaAccount::default(),
-                ],
-            )
+        let bump_seed = pool.bump_seed;
+        ConcentratedPool::pack(pool, &mut pool_info.data.borrow_mut())?;
+        Position::pack(position, &mut position_info.data.borrow_mut())?;
+
+        if token_a_amount > 0 {
+            Self::token_transfer(
+                pool_info.key,
+                token_a_program_info.clone(),
+                source_a_info.clone(),
+                token_a_mint_info.clone(),
+                token_a_info.clone(),
+                user_transfer_authority_info.clone(),
+                bump_seed,
+                token_a_amount,
+                token_a_decimals,
+            )?;
+        }
+        if token_b_amount > 0 {
+            Self::token_transfer(
+                pool_info.key,
+                token_b_program_info.clone(),
+                source_b_info.clone(),
+                token_b_mint_info.clone(),
+                token_b_info.clone(),
+                user_transfer_authority_info.clone(),
+                bump_seed,
+                token_b_amount,
+                token_b_decimals,
+            )?;
         }
+        Ok(())
+    }
 
-        #[allow(clippy::too_many_arguments)]
-        pub fn withdraw_all_token_types(
-            &mut self,
-            user_key: &Pubkey,
-            pool_key: &Pubkey,
-            pool_account: &mut SolanaAccount,
-            token_a_key: &Pubkey,
-            token_a_account: &mut SolanaAccount,
-            token_b_key: &Pubkey,
-            token_b_account: &mut SolanaAccount,
-            pool_token_amount: u64,
-            minimum_token_a_amount: u64,
-            minimum_token_b_amount: u64,
-        ) -> ProgramResult {
-            let user_transfer_authority_key = Pubkey::new_unique();
-            let pool_token_program_id = pool_account.owner;
-            // approve user transfer authority to take out pool tokens
-            do_process_instruction(
-                approve(
-                    &pool_token_program_id,
-                    pool_key,
-                    &user_transfer_authority_key,
-                    user_key,
-                    &[],
-                    pool_token_amount,
-                )
-                .unwrap(),
-                vec![
-                    pool_account,
-                    &mut SolanaAccount::default(),
-                    &mut SolanaAccount::default(),
-                ],
-            )
-            .unwrap();
+    /// Processes a [DecreaseLiquidity](enum.Instruction.html).
+    pub fn process_decrease_liquidity(
+        program_id: &Pubkey,
+        liquidity: u128,
+        minimum_token_a_amount: u64,
+        minimum_token_b_amount: u64,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let position_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+        let token_a_info = next_account_info(account_info_iter)?;
+        let token_b_info = next_account_info(account_info_iter)?;
+        let dest_token_a_info = next_account_info(account_info_iter)?;
+        let dest_token_b_info = next_account_info(account_info_iter)?;
+        let token_a_mint_info = next_account_info(account_info_iter)?;
+        let token_b_mint_info = next_account_info(account_info_iter)?;
+        let token_a_program_info = next_account_info(account_info_iter)?;
+        let token_b_program_info = next_account_info(account_info_iter)?;
 
-            // withdraw token a and b correctly
-            let token_a_program_id = token_a_account.owner;
-            let token_b_program_id = token_b_account.owner;
-            do_process_instruction(
-                withdraw_all_token_types(
-                    &SWAP_PROGRAM_ID,
-                    &pool_token_program_id,
-                    &token_a_program_id,
-                    &token_b_program_id,
-                    &self.swap_key,
-                    &self.authority_key,
-                    &user_transfer_authority_key,
-                    &self.pool_mint_key,
-                    &self.pool_fee_key,
-                    pool_key,
-                    &self.token_a_key,
-                    &self.token_b_key,
-                    token_a_key,
-                    token_b_key,
-                    &self.token_a_mint_key,
-                    &self.token_b_mint_key,
-                    WithdrawAllTokenTypes {
-                        pool_token_amount,
-                        minimum_token_a_amount,
-                        minimum_token_b_amount,
-                    },
-                )
-                .unwrap(),
-                vec![
-                    &mut self.swap_account,
-                    &mut SolanaAccount::default(),
-                    &mut SolanaAccount::default(),
-                    &mut self.pool_mint_account,
-                    pool_account,
-                    &mut self.token_a_account,
-                    &mut self.token_b_account,
-                    token_a_account,
-                    token_b_account,
-                    &mut self.pool_fee_account,
-                    &mut self.token_a_mint_account,
-                    &mut self.token_b_mint_account,
-                    &mut SolanaAccount::default(),
-                    &mut SolanaAccount::default(),
-                    &mut SolanaAccount::default(),
-                ],
-            )
+        let mut pool = Self::unpack_concentrated_pool(program_id, pool_info, authority_info)?;
+        let mut position =
+            Self::unpack_position(program_id, position_info, pool_info.key, owner_info)?;
+        Self::check_pool_token_account(token_a_info, &pool.token_a, token_a_program_info)?;
+        Self::check_pool_token_account(token_b_info, &pool.token_b, token_b_program_info)?;
+        if dest_token_a_info.key == token_a_info.key || dest_token_b_info.key == token_b_info.key {
+            return Err(SwapError::InvalidInput.into());
+        }
+        if liquidity > position.liquidity {
+            return Err(SwapError::InsufficientLiquidity.into());
         }
 
-        #[allow(clippy::too_many_arguments)]
-        pub fn deposit_single_token_type_exact_amount_in(
-            &mut self,
-            depositor_key: &Pubkey,
-            deposit_account_key: &Pubkey,
-            deposit_token_account: &mut SolanaAccount,
-            deposit_pool_key: &Pubkey,
-            deposit_pool_account: &mut SolanaAccount,
-            source_token_amount: u64,
-            minimum_pool_token_amount: u64,
-        ) -> ProgramResult {
-            let user_transfer_authority_key = Pubkey::new_unique();
-            let source_token_program_id = deposit_token_account.owner;
-            do_process_instruction(
-                approve(
-                    &source_token_program_id,
-                    deposit_account_key,
-                    &user_transfer_authority_key,
-                    depositor_key,
-                    &[],
-                    source_token_amount,
-                )
-                .unwrap(),
-                vec![
-                    deposit_token_account,
-                    &mut SolanaAccount::default(),
-                    &mut SolanaAccount::default(),
-                ],
-            )
-            .unwrap();
+        let liquidity_delta = i128::try_from(liquidity)
+            .ok()
+            .and_then(i128::checked_neg)
+            .ok_or(SwapError::ConversionFailure)?;
+        let results = pool.modify_position(&mut position, liquidity_delta)?;
+        let token_a_amount = to_u64(results.token_a_amount)?;
+        if token_a_amount < minimum_token_a_amount {
+            return Err(SwapError::ExceededSlippage.into());
+        }
+        let token_b_amount = to_u64(results.token_b_amount)?;
+        if token_b_amount < minimum_token_b_amount {
+            return Err(SwapError::ExceededSlippage.into());
+        }
 
-            let source_mint_key =
-                StateWithExtensions::<Account>::unpack(&deposit_token_account.data)
-                    .unwrap()
-                    .base
-                    .mint;
-            let swap_source_key = self.get_swap_key(&source_mint_key);
-            let (source_mint_key, mut source_mint_account) = self.get_token_mint(swap_source_key);
+        // all fees earned by the position are paid out with the liquidity
+        let token_a_amount = token_a_amount
+            .checked_add(position.fees_owed_a)
+            .ok_or(SwapError::CalculationFailure)?;
+        let token_b_amount = token_b_amount
+            .checked_add(position.fees_owed_b)
+            .ok_or(SwapError::CalculationFailure)?;
+        position.fees_owed_a = 0;
+        position.fees_owed_b = 0;
 
-            let pool_token_program_id = deposit_pool_account.owner;
-            do_process_instruction(
-                deposit_single_token_type_exact_amount_in(
-                    &SWAP_PROGRAM_ID,
-                    &source_token_program_id,
-                    &pool_token_program_id,
-                    &self.swap_key,
-                    &self.authority_key,
-                    &user_transfer_authority_key,
-                    deposit_account_key,
-                    &self.token_a_key,
-                    &self.token_b_key,
-                    &self.pool_mint_key,
-                    deposit_pool_key,
-                    &source_mint_key,
-                    DepositSingleTokenTypeExactAmountIn {
-                        source_token_amount,
-                        minimum_pool_token_amount,
-                    },
-                )
-                .unwrap(),
-                vec![
-                    &mut self.swap_account,
-                    &mut SolanaAccount::default(),
-                    &mut SolanaAccount::default(),
-                    deposit_token_account,
-                    &mut self.token_a_account,
-                    &mut self.token_b_account,
-                    &mut self.pool_mint_account,
-                    deposit_pool_account,
-                    &mut source_mint_account,
-                    &mut SolanaAccount::default(),
-                    &mut SolanaAccount::default(),
-                ],
-            )
+        let bump_seed = pool.bump_seed;
+        ConcentratedPool::pack(pool, &mut pool_info.data.borrow_mut())?;
+        Position::pack(position, &mut position_info.data.borrow_mut())?;
+
+        if token_a_amount > 0 {
+            let token_a_decimals =
+                Self::unpack_mint(token_a_mint_info, token_a_program_info.key)?.decimals;
+            Self::token_transfer(
+                pool_info.key,
+                token_a_program_info.clone(),
+                token_a_info.clone(),
+                token_a_mint_info.clone(),
+                dest_token_a_info.clone(),
+                authority_info.clone(),
+                bump_seed,
+                token_a_amount,
+                token_a_decimals,
+            )?;
+        }
+        if token_b_amount > 0 {
+            let token_b_decimals =
+                Self::unpack_mint(token_b_mint_info, token_b_program_info.key)?.decimals;
+            Self::token_transfer(
+                pool_info.key,
+                token_b_program_info.clone(),
+                token_b_info.clone(),
+                token_b_mint_info.clone(),
+                dest_token_b_info.clone(),
+                authority_info.clone(),
+                bump_seed,
+                token_b_amount,
+                token_b_decimals,
+            )?;
         }
+        Ok(())
+    }
 
-        #[allow(clippy::too_many_arguments)]
-        pub fn withdraw_single_token_type_exact_amount_out(
-            &mut self,
-            user_key: &Pubkey,
-            pool_key: &Pubkey,
-            pool_account: &mut SolanaAccount,
-            destination_key: &Pubkey,
-            destination_account: &mut SolanaAccount,
-            destination_token_amount: u64,
-            maximum_pool_token_amount: u64,
-        ) -> ProgramResult {
-            let user_transfer_authority_key = Pubkey::new_unique();
-            let pool_token_program_id = pool_account.owner;
-            // approve user transfer authority to take out pool tokens
-            do_process_instruction(
-                approve(
-                    &pool_token_program_id,
-                    pool_key,
-                    &user_transfer_authority_key,
-                    user_key,
-                    &[],
-                    maximum_pool_token_amount,
-                )
-                .unwrap(),
-                vec![
-                    pool_account,
-                    &mut SolanaAccount::default(),
-                    &mut SolanaAccount::default(),
-                ],
-            )
-            .unwrap();
-
-            let destination_mint_key =
-                StateWithExtensions::<Account>::unpack(&destination_account.data)
-                    .unwrap()
-                    .base
-                    .mint;
-            let swap_destination_key = self.get_swap_key(&destination_mint_key);
-            let (destination_mint_key, mut destination_mint_account) =
-                self.get_token_mint(swap_destination_key);
+    /// Processes a [ConcentratedSwap](enum.Instruction.html).
+    pub fn process_concentrated_swap(
+        program_id: &Pubkey,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let user_transfer_authority_info = next_account_info(account_info_iter)?;
+        let source_info = next_account_info(account_info_iter)?;
+        let swap_source_info = next_account_info(account_info_iter)?;
+        let swap_destination_info = next_account_info(account_info_iter)?;
+        let destination_info = next_account_info(account_info_iter)?;
+        let source_token_mint_info = next_account_info(account_info_iter)?;
+        let destination_token_mint_info = next_account_info(account_info_iter)?;
+        let source_token_program_info = next_account_info(account_info_iter)?;
+        let destination_token_program_info = next_account_info(account_info_iter)?;
 
-            let destination_token_program_id = destination_account.owner;
-            do_process_instruction(
-                withdraw_single_token_type_exact_amount_out(
-                    &SWAP_PROGRAM_ID,
-                    &pool_token_program_id,
-                    &destination_token_program_id,
-                    &self.swap_key,
-                    &self.authority_key,
-                    &user_transfer_authority_key,
-                    &self.pool_mint_key,
-                    &self.pool_fee_key,
-                    pool_key,
-                    &self.token_a_key,
-                    &self.token_b_key,
-                    destination_key,
-                    &destination_mint_key,
-                    WithdrawSingleTokenTypeExactAmountOut {
-                        destination_token_amount,
-                        maximum_pool_token_amount,
-                    },
-                )
-                .unwrap(),
-                vec![
-                    &mut self.swap_account,
-                    &mut SolanaAccount::default(),
-                    &mut SolanaAccount::default(),
-                    &mut self.pool_mint_account,
-                    pool_account,
-                    &mut self.token_a_account,
-                    &mut self.token_b_account,
-                    destination_account,
-                    &mut self.pool_fee_account,
-                    &mut destination_mint_account,
-                    &mut SolanaAccount::default(),
-                    &mut SolanaAccount::default(),
-                ],
-            )
+        let mut pool = Self::unpack_concentrated_pool(program_id, pool_info, authority_info)?;
+        if pool.is_paused {
+            return Err(SwapError::SwapPaused.into());
+        }
+        let trade_direction = if *swap_source_info.key == pool.token_a {
+            Self::check_pool_token_account(
+                swap_destination_info,
+                &pool.token_b,
+                destination_token_program_info,
+            )?;
+            TradeDirection::AtoB
+        } else {
+            Self::check_pool_token_account(
+                swap_destination_info,
+                &pool.token_a,
+                destination_token_program_info,
+            )?;
+            Self::check_pool_token_account(
+                swap_source_info,
+                &pool.token_b,
+                source_token_program_info,
+            )?;
+            TradeDirection::BtoA
+        };
+        if swap_source_info.owner != source_token_program_info.key {
+            return Err(SwapError::IncorrectTokenProgramId.into());
+        }
+        if swap_source_info.key == source_info.key {
+            return Err(SwapError::InvalidInput.into());
+        }
+        if swap_destination_info.key == destination_info.key {
+            return Err(SwapError::InvalidInput.into());
         }
-    }
 
-    fn mint_minimum_balance() -> u64 {
-        Rent::default().minimum_balance(spl_token::state::Mint::get_packed_len())
-    }
+        let source_account =
+            Self::unpack_token_account(swap_source_info, source_token_program_info.key)?;
+        let dest_account =
+            Self::unpack_token_account(swap_destination_info, destination_token_program_info.key)?;
 
-    fn account_minimum_balance() -> u64 {
-        Rent::default().minimum_balance(spl_token::state::Account::get_packed_len())
-    }
+        // Take transfer fees into account for actual amount transferred in
+        let (actual_amount_in, source_mint_decimals) = Self::amount_received_after_fee(
+            source_token_mint_info,
+            source_token_program_info.key,
+            amount_in,
+        )?;
 
-    fn do_process_instruction_with_fee_constraints(
-        instruction: Instruction,
-        accounts: Vec<&mut SolanaAccount>,
-        swap_constraints: &Option<SwapConstraints>,
-    ) -> ProgramResult {
-        test_syscall_stubs();
+        let result = pool.swap(
+            u128::from(actual_amount_in),
+            u128::from(source_account.amount),
+            u128::from(dest_account.amount),
+            trade_direction,
+        )?;
 
-        // approximate the logic in the actual runtime which runs the instruction
-        // and only updates accounts if the instruction is successful
-        let mut account_clones = accounts.iter().map(|x| (*x).clone()).collect::<Vec<_>>();
-        let mut meta = instruction
-            .accounts
-            .iter()
-            .zip(account_clones.iter_mut())
-            .map(|(account_meta, account)| (&account_meta.pubkey, account_meta.is_signer, account))
-            .collect::<Vec<_>>();
-        let mut account_infos = create_is_signer_account_infos(&mut meta);
-        let res = if instruction.program_id == SWAP_PROGRAM_ID {
-            Processor::process_with_constraints(
-                &instruction.program_id,
-                &account_infos,
-                &instruction.data,
-                swap_constraints,
-            )
-        } else if instruction.program_id == spl_token::id() {
-            spl_token::processor::Processor::process(
-                &instruction.program_id,
-                &account_infos,
-                &instruction.data,
-            )
-        } else if instruction.program_id == spl_token_2022::id() {
-            spl_token_2022::processor::Processor::process(
-                &instruction.program_id,
-                &account_infos,
-                &instruction.data,
-            )
-        } else {
-            Err(ProgramError::IncorrectProgramId)
-        };
+        let destination_amount = to_u64(result.destination_amount_swapped)?;
+        let (amount_received, destination_mint_decimals) = Self::amount_received_after_fee(
+            destination_token_mint_info,
+            destination_token_program_info.key,
+            destination_amount,
+        )?;
+        if amount_received < minimum_amount_out {
+            return Err(SwapError::ExceededSlippage.into());
+        }
 
-        if res.is_ok() {
-            let mut account_metas = instruction
-                .accounts
-                .iter()
-                .zip(accounts)
-                .map(|(account_meta, account)| (&account_meta.pubkey, account))
-                .collect::<Vec<_>>();
-            for account_info in account_infos.iter_mut() {
-                for account_meta in account_metas.iter_mut() {
-                    if account_info.key == account_meta.0 {
-                        let account = &mut account_meta.1;
-                        account.owner = *account_info.owner;
-                        account.lamports = **account_info.lamports.borrow();
-                        account.data = account_info.data.borrow().to_vec();
-                    }
-                }
+        let mut protocol_fee = result.owner_fee;
+        let mut host_fee = 0;
+        // Allow error to fall through
+        let host_fee_account_info = next_account_info(account_info_iter).ok();
+        if let Some(host_fee_account_info) = host_fee_account_info {
+            let host_fee_account =
+                Self::unpack_token_account(host_fee_account_info, source_token_program_info.key)?;
+            if host_fee_account.mint != source_account.mint {
+                return Err(SwapError::InvalidInput.into());
+            }
+            host_fee = pool
+                .fees
+                .host_fee(protocol_fee)
+                .ok_or(SwapError::FeeCalculationFailure)?;
+            protocol_fee = protocol_fee
+                .checked_sub(host_fee)
+                .ok_or(SwapError::FeeCalculationFailure)?;
+        }
+        let protocol_fee = to_u64(protocol_fee)?;
+        match trade_direction {
+            TradeDirection::AtoB => {
+                pool.protocol_fees_a = pool
+                    .protocol_fees_a
+                    .checked_add(protocol_fee)
+                    .ok_or(SwapError::CalculationFailure)?
+            }
+            TradeDirection::BtoA => {
+                pool.protocol_fees_b = pool
+                    .protocol_fees_b
+                    .checked_add(protocol_fee)
+                    .ok_or(SwapError::CalculationFailure)?
             }
         }
-        res
-    }
 
-    fn do_process_instruction(
-        instruction: Instruction,
-        accounts: Vec<&mut SolanaAccount>,
-    ) -> ProgramResult {
-        do_process_instruction_with_fee_constraints(instruction, accounts, &SWAP_CONSTRAINTS)
+        let bump_seed = pool.bump_seed;
+        ConcentratedPool::pack(pool, &mut pool_info.data.borrow_mut())?;
+
+        Self::token_transfer(
+            pool_info.key,
+            source_token_program_info.clone(),
+            source_info.clone(),
+            source_token_mint_info.clone(),
+            swap_source_info.clone(),
+            user_transfer_authority_info.clone(),
+            bump_seed,
+            amount_in,
+            source_mint_decimals,
+        )?;
+
+        if let Some(host_fee_account_info) = host_fee_account_info {
+            if host_fee > 0 {
+                Self::token_transfer(
+                    pool_info.key,
+                    source_token_program_info.clone(),
+                    swap_source_info.clone(),
+                    source_token_mint_info.clone(),
+                    host_fee_account_info.clone(),
+                    authority_info.clone(),
+                    bump_seed,
+                    to_u64(host_fee)?,
+                    source_mint_decimals,
+                )?;
+            }
+        }
+
+        Self::token_transfer(
+            pool_info.key,
+            destination_token_program_info.clone(),
+            swap_destination_info.clone(),
+            destination_token_mint_info.clone(),
+            destination_info.clone(),
+            authority_info.clone(),
+            bump_seed,
+            destination_amount,
+            destination_mint_decimals,
+        )?;
+
+        Ok(())
     }
 
-    fn mint_token(
-        program_id: &Pubkey,
-        mint_key: &Pubkey,
-        mint_account: &mut SolanaAccount,
-        mint_authority_key: &Pubkey,
-        account_owner_key: &Pubkey,
-        amount: u64,
-    ) -> (Pubkey, SolanaAccount) {
-        let account_key = Pubkey::new_unique();
-        let space = if *program_id == spl_token_2022::id() {
-            ExtensionType::try_calculate_account_len::<Account>(&[
-                ExtensionType::ImmutableOwner,
-                ExtensionType::TransferFeeAmount,
-            ])
-            .unwrap()
-        } else {
-            Account::get_packed_len()
-        };
-        let minimum_balance = Rent::default().minimum_balance(space);
-        let mut account_account = SolanaAccount::new(minimum_balance, space, program_id);
-        let mut mint_authority_account = SolanaAccount::default();
-        let mut rent_sysvar_account = create_account_for_test(&Rent::free());
+    /// Processes a [ClosePosition](enum.Instruction.html).
+    pub fn process_close_position(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_info = next_account_info(account_info_iter)?;
+        let position_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+        let destination_info = next_account_info(account_info_iter)?;
 
-        // no-ops in normal token, so we're good to run it either way
-        do_process_instruction(
-            initialize_immutable_owner(program_id, &account_key).unwrap(),
-            vec![&mut account_account],
-        )
-        .unwrap();
+        let position = Self::unpack_position(program_id, position_info, pool_info.key, owner_info)?;
+        if position.liquidity != 0 || position.fees_owed_a != 0 || position.fees_owed_b != 0 {
+            return Err(SwapError::PositionNotEmpty.into());
+        }
+        if destination_info.key == position_info.key {
+            return Err(SwapError::InvalidInput.into());
+        }
 
-        do_process_instruction(
-            initialize_account(program_id, &account_key, mint_key, account_owner_key).unwrap(),
-            vec![
-                &mut account_account,
-                mint_account,
-                &mut mint_authority_account,
-                &mut rent_sysvar_account,
-            ],
-        )
-        .unwrap();
+        let destination_starting_lamports = destination_info.lamports();
+        **destination_info.lamports.borrow_mut() = destination_starting_lamports
+            .checked_add(position_info.lamports())
+            .ok_or(SwapError::CalculationFailure)?;
+        **position_info.lamports.borrow_mut() = 0;
+        position_info.data.borrow_mut().fill(0);
+        Ok(())
+    }
 
-        if amount > 0 {
-            do_process_instruction(
-                mint_to(
-                    program_id,
-                    mint_key,
-                    &account_key,
-                    mint_authority_key,
-                    &[],
-                    amount,
-                )
-                .unwrap(),
-                vec![
-                    mint_account,
-                    &mut account_account,
-                    &mut mint_authority_account,
-                ],
-            )
-            .unwrap();
+    /// Processes a [CollectProtocolFees](enum.Instruction.html).
+    pub fn process_collect_protocol_fees(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let pool_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let fee_authority_info = next_account_info(account_info_iter)?;
+        let token_a_info = next_account_info(account_info_iter)?;
+        let token_b_info = next_account_info(account_info_iter)?;
+        let dest_token_a_info = next_account_info(account_info_iter)?;
+        let dest_token_b_info = next_account_info(account_info_iter)?;
+        let token_a_mint_info = next_account_info(account_info_iter)?;
+        let token_b_mint_info = next_account_info(account_info_iter)?;
+        let token_a_program_info = next_account_info(account_info_iter)?;
+        let token_b_program_info = next_account_info(account_info_iter)?;
+
+        let mut pool = Self::unpack_concentrated_pool(program_id, pool_info, authority_info)?;
+        if *fee_authority_info.key != pool.fee_authority {
+            return Err(SwapError::IncorrectFeeAuthority.into());
         }
+        if !fee_authority_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        Self::check_pool_token_account(token_a_info, &pool.token_a, token_a_program_info)?;
+        Self::check_pool_token_account(token_b_info, &pool.token_b, token_b_program_info)?;
 
-        (account_key, account_account)
+        let token_a_amount = pool.protocol_fees_a;
+        let token_b_amount = pool.protocol_fees_b;
+        pool.protocol_fees_a = 0;
+        pool.protocol_fees_b = 0;
+        let bump_seed = pool.bump_seed;
+        ConcentratedPool::pack(pool, &mut pool_info.data.borrow_mut())?;
+
+        if token_a_amount > 0 {
+            let token_a_decimals =
+                Self::unpack_mint(token_a_mint_info, token_a_program_info.key)?.decimals;
+            Self::token_transfer(
+                pool_info.key,
+                token_a_program_info.clone(),
+                token_a_info.clone(),
+                token_a_mint_info.clone(),
+                dest_token_a_info.clone(),
+                authority_info.clone(),
+                bump_seed,
+                token_a_amount,
+                token_a_decimals,
+            )?;
+        }
+        if token_b_amount > 0 {
+            let token_b_decimals =
+                Self::unpack_mint(token_b_mint_info, token_b_program_info.key)?.decimals;
+            Self::token_transfer(
+                pool_info.key,
+                token_b_program_info.clone(),
+                token_b_info.clone(),
+                token_b_mint_info.clone(),
+                dest_token_b_info.clone(),
+                authority_info.clone(),
+                bump_seed,
+                token_b_amount,
+                token_b_decimals,
+            )?;
+        }
+        Ok(())
     }
 
-    fn create_mint(
+    /// Processes an [Instruction](enum.Instruction.html).
+    pub fn process(program_id: &Pubkey, accounts: &[AccountInfo], input: &[u8]) -> ProgramResult {
+        Self::process_with_constraints(program_id, accounts, input, &SWAP_CONSTRAINTS)
+    }
+
+    /// Processes an instruction given extra constraint
+    pub fn process_with_constraints(
         program_id: &Pubkey,
-        authority_key: &Pubkey,
-        freeze_authority: Option<&Pubkey>,
-        close_authority: Option<&Pubkey>,
-        fees: &TransferFee,
-    ) -> (Pubkey, SolanaAccount) {
-        let mint_key = Pubkey::new_unique();
-        let space = if *program_id == spl_token_2022::id() {
-            if close_authority.is_some() {
-                ExtensionType::try_calculate_account_len::<Mint>(&[
-                    ExtensionType::MintCloseAuthority,
-                    ExtensionType::TransferFeeConfig,
-                ])
-                .unwrap()
-            } else {
-                ExtensionType::try_calculate_account_len::<Mint>(&[
-                    ExtensionType::TransferFeeConfig,
-                ])
-                .unwrap()
+        accounts: &[AccountInfo],
+        input: &[u8],
+        swap_constraints: &Option<SwapConstraints>,
+    ) -> ProgramResult {
+        let instruction = SwapInstruction::unpack(input)?;
+        match instruction {
+            SwapInstruction::Initialize(Initialize { fees, swap_curve }) => {
+                msg!("Instruction: Init");
+                Self::process_initialize(program_id, fees, swap_curve, accounts, swap_constraints)
             }
-        } else {
-            Mint::get_packed_len()
-        };
-        let minimum_balance = Rent::default().minimum_balance(space);
-        let mut mint_account = SolanaAccount::new(minimum_balance, space, program_id);
-        let mut rent_sysvar_account = create_account_for_test(&Rent::free());
-
-        if *program_id == spl_token_2022::id() {
-            if close_authority.is_some() {
-                do_process_instruction(
-                    initialize_mint_close_authority(program_id, &mint_key, close_authority)
-                        .unwrap(),
-                    vec![&mut mint_account],
+            SwapInstruction::Swap(Swap {
+                amount_in,
+                minimum_amount_out,
+            }) => {
+                msg!("Instruction: Swap");
+                Self::process_swap(program_id, amount_in, minimum_amount_out, accounts)
+            }
+            SwapInstruction::DepositAllTokenTypes(DepositAllTokenTypes {
+                pool_token_amount,
+                maximum_token_a_amount,
+                maximum_token_b_amount,
+            }) => {
+                msg!("Instruction: DepositAllTokenTypes");
+                Self::process_deposit_all_token_types(
+                    program_id,
+                    pool_token_amount,
+                    maximum_token_a_amount,
+                    maximum_token_b_amount,
+                    accounts,
                 )
-                .unwrap();
             }
-            do_process_instruction(
-                initialize_transfer_fee_config(
+            SwapInstruction::WithdrawAllTokenTypes(WithdrawAllTokenTypes {
+                pool_token_amount,
+                minimum_token_a_amount,
+                minimum_token_b_amount,
+            }) => {
+                msg!("Instruction: WithdrawAllTokenTypes");
+                Self::process_withdraw_all_token_types(
                     program_id,
-                    &mint_key,
-                    freeze_authority,
-                    freeze_authority,
-                    fees.transfer_fee_basis_points.into(),
-                    fees.maximum_fee.into(),
+                    pool_token_amount,
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                    accounts,
                 )
-                .unwrap(),
-                vec![&mut mint_account],
-            )
-            .unwrap();
+            }
+            SwapInstruction::DepositSingleTokenTypeExactAmountIn(
+                DepositSingleTokenTypeExactAmountIn {
+                    source_token_amount,
+                    minimum_pool_token_amount,
+                },
+            ) => {
+                msg!("Instruction: DepositSingleTokenTypeExactAmountIn");
+                Self::process_deposit_single_token_type_exact_amount_in(
+                    program_id,
+                    source_token_amount,
+                    minimum_pool_token_amount,
+                    accounts,
+                )
+            }
+            SwapInstruction::WithdrawSingleTokenTypeExactAmountOut(
+                WithdrawSingleTokenTypeExactAmountOut {
+                    destination_token_amount,
+                    maximum_pool_token_amount,
+                },
+            ) => {
+                msg!("Instruction: WithdrawSingleTokenTypeExactAmountOut");
+                Self::process_withdraw_single_token_type_exact_amount_out(
+                    program_id,
+                    destination_token_amount,
+                    maximum_pool_token_amount,
+                    accounts,
+                )
+            }
+            SwapInstruction::SetPaused(SetPaused { paused }) => {
+                msg!("Instruction: SetPaused");
+                Self::process_set_paused(program_id, paused, accounts)
+            }
+            SwapInstruction::InitializeConcentratedPool(InitializeConcentratedPool {
+                fees,
+                tick_spacing,
+                initial_sqrt_price,
+                min_position_liquidity,
+            }) => {
+                msg!("Instruction: InitializeConcentratedPool");
+                Self::process_initialize_concentrated_pool(
+                    program_id,
+                    fees,
+                    tick_spacing,
+                    initial_sqrt_price,
+                    min_position_liquidity,
+                    accounts,
+                    swap_constraints,
+                )
+            }
+            SwapInstruction::OpenPosition(OpenPosition {
+                tick_lower,
+                tick_upper,
+            }) => {
+                msg!("Instruction: OpenPosition");
+                Self::process_open_position(program_id, tick_lower, tick_upper, accounts)
+            }
+            SwapInstruction::IncreaseLiquidity(IncreaseLiquidity {
+                liquidity,
+                maximum_token_a_amount,
+                maximum_token_b_amount,
+            }) => {
+                msg!("Instruction: IncreaseLiquidity");
+                Self::process_increase_liquidity(
+                    program_id,
+                    liquidity,
+                    maximum_token_a_amount,
+                    maximum_token_b_amount,
+                    accounts,
+                )
+            }
+            SwapInstruction::DecreaseLiquidity(DecreaseLiquidity {
+                liquidity,
+                minimum_token_a_amount,
+                minimum_token_b_amount,
+            }) => {
+                msg!("Instruction: DecreaseLiquidity");
+                Self::process_decrease_liquidity(
+                    program_id,
+                    liquidity,
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                    accounts,
+                )
+            }
+            SwapInstruction::ConcentratedSwap(Swap {
+                amount_in,
+                minimum_amount_out,
+            }) => {
+                msg!("Instruction: ConcentratedSwap");
+                Self::process_concentrated_swap(program_id, amount_in, minimum_amount_out, accounts)
+            }
+            SwapInstruction::CollectProtocolFees => {
+                msg!("Instruction: CollectProtocolFees");
+                Self::process_collect_protocol_fees(program_id, accounts)
+            }
+            SwapInstruction::SetPauseAuthority(SetPauseAuthority {
+                new_pause_authority,
+            }) => {
+                msg!("Instruction: SetPauseAuthority");
+                Self::process_set_pause_authority(program_id, new_pause_authority, accounts)
+            }
+            SwapInstruction::ClosePosition => {
+                msg!("Instruction: ClosePosition");
+                Self::process_close_position(program_id, accounts)
+            }
         }
-        do_process_instruction(
-            initialize_mint(program_id, &mint_key, authority_key, freeze_authority, 2).unwrap(),
-            vec![&mut mint_account, &mut rent_sysvar_account],
-        )
-        .unwrap();
-
-        (mint_key, mint_account)
     }
+}
 
-    #[test_case(spl_token::id(); "token")]
-    #[test_case(spl_token_2022::id(); "token-2022")]
-    fn test_token_program_id_error(token_program_id: Pubkey) {
-        test_syscall_stubs();
-        let swap_key = Pubkey::new_unique();
-        let mut mint = (Pubkey::new_unique(), SolanaAccount::default());
-        let mut destination = (Pubkey::new_unique(), SolanaAccount::default());
-        let token_program = (token_program_id, SolanaAccount::default());
-        let (authority_key, bump_seed) =
-            Pubkey::find_program_address(&[&swap_key.to_bytes()[..]], &SWAP_PROGRAM_ID);
-        let mut authority = (authority_key, SolanaAccount::default());
-        let swap_bytes = swap_key.to_bytes();
-        let authority_signature_seeds = [&swap_bytes[..32], &[bump_seed]];
-        let signers = &[&authority_signature_seeds[..]];
-        let ix = mint_to(
-            &token_program.0,
-            &mint.0,
-            &destination.0,
-            &authority.0,
-            &[],
-            10,
-        )
-        .unwrap();
-        let mint = (&mut mint).into();
-        let destination = (&mut destination).into();
-        let authority = (&mut authority).into();
-
-        let err = invoke_signed(&ix, &[mint, destination, authority], signers).unwrap_err();
-        assert_eq!(err, ProgramError::InvalidAccountData);
-    }
+fn to_u64(val: u128) -> Result<u64, SwapError> {
+    val.try_into().map_err(|_| SwapError::ConversionFailure)
+}
 
-    #[test_case(spl_token::id(); "token")]
-    #[test_case(spl_token_2022::id(); "token-2022")]
-    fn test_token_error(token_program_id: Pubkey) {
-        test_syscall_stubs();
-        let swap_key = Pubkey::new_unique();
-        let mut mint = (
-            Pubkey::new_unique(),
-            SolanaAccount::new(
-                mint_minimum_balance(),
-                spl_token::state::Mint::get_packed_len(),
-                &token_program_id,
-            ),
-        );
-        let mut destination = (
-            Pubkey::new_unique(),
-            SolanaAccount::new(
-                account_minimum_balance(),
-                spl_token::state::Account::get_packed_len(),
-                &token_program_id,
-            ),
-        );
-        let mut token_program = (token_program_id, SolanaAccount::default());
-        let (authority_key, bump_seed) =
-            Pubkey::find_program_address(&[&swap_key.to_bytes()[..]], &SWAP_PROGRAM_ID);
-        let mut authority = (authority_key, SolanaAccount::default());
-        let swap_bytes = swap_key.to_bytes();
-        let authority_signature_seeds = [&swap_bytes[..32], &[bump_seed]];
-        let signers = &[&authority_signature_seeds[..]];
-        let mut rent_sysvar = (
-            Pubkey::new_unique(),
-            create_account_for_test(&Rent::default()),
-        );
-        do_process_instruction(
-            initialize_mint(
-                &token_program.0,
-                &mint.0,
-                &authority.0,
-                Some(&authority.0),
-                2,
-            )
-            .unwrap(),
-            vec![&mut mint.1, &mut rent_sysvar.1],
-        )
-        .unwrap();
-        do_process_instruction(
-            initialize_account(&token_program.0, &destination.0, &mint.0, &authority.0).unwrap(),
-            vec![
-                &mut destination.1,
-                &mut mint.1,
-                &mut authority.1,
-                &mut rent_sysvar.1,
-                &mut token_program.1,
-            ],
-        )
-        .unwrap();
-        do_process_instruction(
-            freeze_account(&token_program.0, &destination.0, &mint.0, &authority.0, &[]).unwrap(),
-            vec![
-                &mut destination.1,
-                &mut mint.1,
-                &mut authority.1,
-                &mut token_program.1,
-            ],
-        )
-        .unwrap();
-        let ix = mint_to(
-            &token_program.0,
-            &mint.0,
-            &destination.0,
-            &authority.0,
-            &[],
-            10,
-        )
-        .unwrap();
-        let mint_info = (&mut mint).into();
-        let destination_info = (&mut destination).into();
-        let authority_info = (&mut authority).into();
-        let token_program_info = (&mut token_program).into();
+fn invoke_signed_wrapper<T>(
+    instruction: &Instruction,
+    account_infos: &[AccountInfo],
+    signers_seeds: &[&[&[u8]]],
+) -> Result<(), ProgramError>
+where
+    T: 'static + PrintProgramError + DecodeError<T> + FromPrimitive + Error,
+{
+    invoke_signed(instruction, account_infos, signers_seeds).map_err(|err| {
+        err.print::<T>();
+        err
+    })
+}
 
-        let err = invoke_signed_wrapper::<TokenError>(
-            &ix,
-            &[
-                mint_info,
-                destination_info,
-                authority_info,
-                token_program_info,
-            ],
-            signers,
-        )
-        .unwrap_err();
-        assert_eq!(err, ProgramError::Custom(TokenError::AccountFrozen as u32));
-    }
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            curve::{
+                calculator::{CurveCalculator, INITIAL_SWAP_POOL_AMOUNT},
+                concentrated_liquidity::MAX_TICK,
+                constant_price::ConstantPriceCurve,
+                constant_product::ConstantProductCurve,
+                offset::OffsetCurve,
+            },
+            instruction::{
+                close_position, collect_protocol_fees, concentrated_swap, decrease_liquidity,
+                deposit_all_token_types, deposit_single_token_type_exact_amount_in,
+                increase_liquidity, initialize, initialize_concentrated_pool, open_position,
+                set_pause_authority, set_paused, swap, withdraw_all_token_types,
+                withdraw_single_token_type_exact_amount_out,
+            },
+            quote::PoolQuoter,
+        },
+        solana_program::{
+            clock::Clock, entrypoint::SUCCESS, instruction::Instruction, program_pack::Pack,
+            program_stubs, rent::Rent,
+        },
+        solana_sdk::account::{
+            create_account_for_test, create_is_signer_account_infos, Account as SolanaAccount,
+        },
+        spl_token_2022::{
+            error::TokenError,
+            extension::{
+                transfer_fee::{instruction::initialize_transfer_fee_config, TransferFee},
+                ExtensionType,
+            },
+            instruction::{
+                approve, close_account, freeze_account, initialize_account,
+                initialize_immutable_owner, initialize_mint, initialize_mint_close_authority,
+                mint_to, revoke, set_authority, AuthorityType,
+            },
+        },
+        std::sync::Arc,
+        test_case::test_case,
+    };
 
-    #[test_case(spl_token::id(), spl_token::id(), spl_token::id(); "all-token")]
-    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token_2022::id(); "all-token-2022")]
-    #[test_case(spl_token::id(), spl_token_2022::id(), spl_token_2022::id(); "mixed-pool-token")]
-    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token::id(); "mixed-pool-token-2022")]
-    fn test_initialize(
-        pool_token_program_id: Pubkey,
-        token_a_program_id: Pubkey,
-        token_b_program_id: Pubkey,
-    ) {
-        let user_key = Pubkey::new_unique();
-        let trade_fee_numerator = 1;
-        let trade_fee_denominator = 2;
-        let owner_trade_fee_numerator = 1;
-        let owner_trade_fee_denominator = 10;
-        let owner_withdraw_fee_numerator = 1;
-        let owner_withdraw_fee_denominator = 5;
-        let host_fee_numerator = 20;
-        let host_fee_denominator = 100;
-        let fees = Fees {
-            trade_fee_numerator,
-            trade_fee_denominator,
-            owner_trade_fee_numerator,
-            owner_trade_fee_denominator,
-            owner_withdraw_fee_numerator,
-            owner_withdraw_fee_denominator,
-            host_fee_numerator,
-            host_fee_denominator,
-        };
+    // Test program id for the swap program.
+    const SWAP_PROGRAM_ID: Pubkey = Pubkey::new_from_array([2u8; 32]);
 
-        let token_a_amount = 1000;
-        let token_b_amount = 2000;
-        let pool_token_amount = 10;
-        let curve_type = CurveType::ConstantProduct;
-        let swap_curve = SwapCurve {
-            curve_type,
-            calculator: Arc::new(ConstantProductCurve {}),
-        };
+    struct TestSyscallStubs {}
+    impl program_stubs::SyscallStubs for TestSyscallStubs {
+        fn sol_invoke_signed(
+            &self,
+            instruction: &Instruction,
+            account_infos: &[AccountInfo],
+            signers_seeds: &[&[&[u8]]],
+        ) -> ProgramResult {
+            msg!("TestSyscallStubs::sol_invoke_signed()");
 
-        let mut accounts = SwapAccountInfo::new(
-            &user_key,
-            fees,
-            SwapTransferFees::default(),
-            swap_curve,
-            token_a_amount,
-            token_b_amount,
-            &pool_token_program_id,
-            &token_a_program_id,
-            &token_b_program_id,
-        );
+            let mut new_account_infos = vec![];
 
-        // uninitialized token a account
-        {
-            let old_account = accounts.token_a_account;
-            accounts.token_a_account = SolanaAccount::new(0, 0, &token_a_program_id);
-            assert_eq!(
-                Err(SwapError::ExpectedAccount.into()),
-                accounts.initialize_swap()
-            );
-            accounts.token_a_account = old_account;
-        }
+            // mimic check for token program in accounts
+            if !account_infos
+                .iter()
+                .any(|x| *x.key == spl_token::id() || *x.key == spl_token_2022::id())
+            {
+                return Err(ProgramError::InvalidAccountData);
+            }
 
-        // uninitialized token b account
-        {
-            let old_account = accounts.token_b_account;
-            accounts.token_b_account = SolanaAccount::new(0, 0, &token_b_program_id);
-            assert_eq!(
-                Err(SwapError::ExpectedAccount.into()),
-                accounts.initialize_swap()
-            );
-            accounts.token_b_account = old_account;
+            for meta in instruction.accounts.iter() {
+                for account_info in account_infos.iter() {
+                    if meta.pubkey == *account_info.key {
+                        let mut new_account_info = account_info.clone();
+                        for seeds in signers_seeds.iter() {
+                            let signer =
+                                Pubkey::create_program_address(seeds, &SWAP_PROGRAM_ID).unwrap();
+                            if *account_info.key == signer {
+                                new_account_info.is_signer = true;
+                            }
+                        }
+                        new_account_infos.push(new_account_info);
+                    }
+                }
+            }
+
+            if instruction.program_id == spl_token::id() {
+                spl_token::processor::Processor::process(
+                    &instruction.program_id,
+                    &new_account_infos,
+                    &instruction.data,
+                )
+            } else if instruction.program_id == spl_token_2022::id() {
+                spl_token_2022::processor::Processor::process(
+                    &instruction.program_id,
+                    &new_account_infos,
+                    &instruction.data,
+                )
+            } else {
+                Err(ProgramError::IncorrectProgramId)
+            }
         }
 
-        // uninitialized pool mint
-        {
-            let old_account = accounts.pool_mint_account;
-            accounts.pool_mint_account = SolanaAccount::new(0, 0, &pool_token_program_id);
-            assert_eq!(
-                Err(SwapError::ExpectedMint.into()),
-                accounts.initialize_swap()
-            );
-            accounts.pool_mint_account = old_account;
+        fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+            unsafe {
+                *(var_addr as *mut _ as *mut Clock) = Clock::default();
+            }
+            SUCCESS
         }
+    }
 
-        // token A account owner is not swap authority
-        {
-            let (_token_a_key, token_a_account) = mint_token(
-                &token_a_program_id,
-                &accounts.token_a_mint_key,
-                &mut accounts.token_a_mint_account,
-                &user_key,
-                &user_key,
-                0,
-            );
-            let old_account = accounts.token_a_account;
-            accounts.token_a_account = token_a_account;
-            assert_eq!(
-                Err(SwapError::InvalidOwner.into()),
-                accounts.initialize_swap()
-            );
-            accounts.token_a_account = old_account;
-        }
+    fn test_syscall_stubs() {
+        use std::sync::Once;
+        static ONCE: Once = Once::new();
 
-        // token B account owner is not swap authority
-        {
-            let (_token_b_key, token_b_account) = mint_token(
-                &token_b_program_id,
-                &accounts.token_b_mint_key,
-                &mut accounts.token_b_mint_account,
-                &user_key,
-                &user_key,
-                0,
-            );
-            let old_account = accounts.token_b_account;
-            accounts.token_b_account = token_b_account;
-            assert_eq!(
-                Err(SwapError::InvalidOwner.into()),
-                accounts.initialize_swap()
-            );
-            accounts.token_b_account = old_account;
-        }
+        ONCE.call_once(|| {
+            program_stubs::set_syscall_stubs(Box::new(TestSyscallStubs {}));
+        });
+    }
 
-        // pool token account owner is swap authority
-        {
-            let (_pool_token_key, pool_token_account) = mint_token(
-                &pool_token_program_id,
-                &accounts.pool_mint_key,
-                &mut accounts.pool_mint_account,
-                &accounts.authority_key,
-                &accounts.authority_key,
-                0,
-            );
-            let old_account = accounts.pool_token_account;
-            accounts.pool_token_account = pool_token_account;
-            assert_eq!(
-                Err(SwapError::InvalidOutputOwner.into()),
-                accounts.initialize_swap()
-            );
-            accounts.pool_token_account = old_account;
-        }
+    #[derive(Default)]
+    struct SwapTransferFees {
+        pool_token: TransferFee,
+        token_a: TransferFee,
+        token_b: TransferFee,
+    }
 
-        // pool fee account owner is swap authority
-        {
-            let (_pool_fee_key, pool_fee_account) = mint_token(
-                &pool_token_program_id,
-                &accounts.pool_mint_key,
-                &mut accounts.pool_mint_account,
-                &accounts.authority_key,
-                &accounts.authority_key,
-                0,
-            );
-            let old_account = accounts.pool_fee_account;
-            accounts.pool_fee_account = pool_fee_account;
-            assert_eq!(
-                Err(SwapError::InvalidOutputOwner.into()),
-                accounts.initialize_swap()
-            );
-            accounts.pool_fee_account = old_account;
-        }
+    struct SwapAccountInfo {
+        bump_seed: u8,
+        authority_key: Pubkey,
+        fees: Fees,
+        transfer_fees: SwapTransferFees,
+        swap_curve: SwapCurve,
+        swap_key: Pubkey,
+        swap_account: SolanaAccount,
+        pool_mint_key: Pubkey,
+        pool_mint_account: SolanaAccount,
+        pool_fee_key: Pubkey,
+        pool_fee_account: SolanaAccount,
+        pool_token_key: Pubkey,
+        pool_token_account: SolanaAccount,
+        token_a_key: Pubkey,
+        token_a_account: SolanaAccount,
+        token_a_mint_key: Pubkey,
+        token_a_mint_account: SolanaAccount,
+        token_b_key: Pubkey,
+        token_b_account: SolanaAccount,
+        token_b_mint_key: Pubkey,
+        token_b_mint_account: SolanaAccount,
+        pool_token_program_id: Pubkey,
+        token_a_program_id: Pubkey,
+        token_b_program_id: Pubkey,
+    }
 
-        // pool mint authority is not swap authority
-        {
-            let (_pool_mint_key, pool_mint_account) = create_mint(
-                &pool_token_program_id,
-                &user_key,
+    impl SwapAccountInfo {
+        #[allow(clippy::too_many_arguments)]
+        pub fn new(
+            user_key: &Pubkey,
+            fees: Fees,
+            transfer_fees: SwapTransferFees,
+            swap_curve: SwapCurve,
+            token_a_amount: u64,
+            token_b_amount: u64,
+            pool_token_program_id: &Pubkey,
+            token_a_program_id: &Pubkey,
+            token_b_program_id: &Pubkey,
+        ) -> Self {
+            let swap_key = Pubkey::new_unique();
+            let swap_account = SolanaAccount::new(0, SwapVersion::LATEST_LEN, &SWAP_PROGRAM_ID);
+            let (authority_key, bump_seed) =
+                Pubkey::find_program_address(&[&swap_key.to_bytes()[..]], &SWAP_PROGRAM_ID);
+
+            let (pool_mint_key, mut pool_mint_account) = create_mint(
+                pool_token_program_id,
+                &authority_key,
                 None,
                 None,
-                &TransferFee::default(),
-            );
-            let old_mint = accounts.pool_mint_account;
-            accounts.pool_mint_account = pool_mint_account;
-            assert_eq!(
-                Err(SwapError::InvalidOwner.into()),
-                accounts.initialize_swap()
+                &transfer_fees.pool_token,
             );
-            accounts.pool_mint_account = old_mint;
-        }
-
-        // pool mint token has freeze authority
-        {
-            let (_pool_mint_key, pool_mint_account) = create_mint(
-                &pool_token_program_id,
-                &accounts.authority_key,
-                Some(&user_key),
-                None,
-                &TransferFee::default(),
+            let (pool_token_key, pool_token_account) = mint_token(
+                pool_token_program_id,
+                &pool_mint_key,
+                &mut pool_mint_account,
+                &authority_key,
+                user_key,
+                0,
             );
-            let old_mint = accounts.pool_mint_account;
-            accounts.pool_mint_account = pool_mint_account;
-            assert_eq!(
-                Err(SwapError::InvalidFreezeAuthority.into()),
-                accounts.initialize_swap()
+            let (pool_fee_key, pool_fee_account) = mint_token(
+                pool_token_program_id,
+                &pool_mint_key,
+                &mut pool_mint_account,
+                &authority_key,
+                user_key,
+                0,
             );
-            accounts.pool_mint_account = old_mint;
-        }
-
-        // pool mint token has close authority, only available in token-2022
-        if pool_token_program_id == spl_token_2022::id() {
-            let (_pool_mint_key, pool_mint_account) = create_mint(
-                &pool_token_program_id,
-                &accounts.authority_key,
+            let (token_a_mint_key, mut token_a_mint_account) = create_mint(
+                token_a_program_id,
+                user_key,
                 None,
-                Some(&user_key),
-                &TransferFee::default(),
-            );
-            let old_mint = accounts.pool_mint_account;
-            accounts.pool_mint_account = pool_mint_account;
-            assert_eq!(
-                Err(SwapError::InvalidCloseAuthority.into()),
-                accounts.initialize_swap()
+                None,
+                &transfer_fees.token_a,
             );
-            accounts.pool_mint_account = old_mint;
-        }
-
-        // token A account owned by wrong program
-        {
-            let (_token_a_key, mut token_a_account) = mint_token(
-                &token_a_program_id,
-                &accounts.token_a_mint_key,
-                &mut accounts.token_a_mint_account,
-                &user_key,
-                &accounts.authority_key,
+            let (token_a_key, token_a_account) = mint_token(
+                token_a_program_id,
+                &token_a_mint_key,
+                &mut token_a_mint_account,
+                user_key,
+                &authority_key,
                 token_a_amount,
             );
-            token_a_account.owner = SWAP_PROGRAM_ID;
-            let old_account = accounts.token_a_account;
-            accounts.token_a_account = token_a_account;
-            assert_eq!(
-                Err(SwapError::IncorrectTokenProgramId.into()),
-                accounts.initialize_swap()
+            let (token_b_mint_key, mut token_b_mint_account) = create_mint(
+                token_b_program_id,
+                user_key,
+                None,
+                None,
+                &transfer_fees.token_b,
             );
-            accounts.token_a_account = old_account;
-        }
-
-        // token B account owned by wrong program
-        {
-            let (_token_b_key, mut token_b_account) = mint_token(
-                &token_b_program_id,
-                &accounts.token_b_mint_key,
-                &mut accounts.token_b_mint_account,
-                &user_key,
-                &accounts.authority_key,
+            let (token_b_key, token_b_account) = mint_token(
+                token_b_program_id,
+                &token_b_mint_key,
+                &mut token_b_mint_account,
+                user_key,
+                &authority_key,
                 token_b_amount,
             );
-            token_b_account.owner = SWAP_PROGRAM_ID;
-            let old_account = accounts.token_b_account;
-            accounts.token_b_account = token_b_account;
-            assert_eq!(
-                Err(SwapError::IncorrectTokenProgramId.into()),
-                accounts.initialize_swap()
-            );
-            accounts.token_b_account = old_account;
-        }
-
-        // empty token A account
-        {
-            let (_token_a_key, token_a_account) = mint_token(
-                &token_a_program_id,
-                &accounts.token_a_mint_key,
-                &mut accounts.token_a_mint_account,
-                &user_key,
-                &accounts.authority_key,
-                0,
-            );
-            let old_account = accounts.token_a_account;
-            accounts.token_a_account = token_a_account;
-            assert_eq!(
-                Err(SwapError::EmptySupply.into()),
-                accounts.initialize_swap()
-            );
-            accounts.token_a_account = old_account;
-        }
-
-        // empty token B account
-        {
-            let (_token_b_key, token_b_account) = mint_token(
-                &token_b_program_id,
-                &accounts.token_b_mint_key,
-                &mut accounts.token_b_mint_account,
-                &user_key,
-                &accounts.authority_key,
-                0,
-            );
-            let old_account = accounts.token_b_account;
-            accounts.token_b_account = token_b_account;
-            assert_eq!(
-                Err(SwapError::EmptySupply.into()),
-                accounts.initialize_swap()
-            );
-            accounts.token_b_account = old_account;
-        }
-
-        // invalid pool tokens
-        {
-            let old_mint = accounts.pool_mint_account;
-            let old_pool_account = accounts.pool_token_account;
-
-            let (_pool_mint_key, pool_mint_account) = create_mint(
-                &pool_token_program_id,
-                &accounts.authority_key,
-                None,
-                None,
-                &TransferFee::default(),
-            );
-            accounts.pool_mint_account = pool_mint_account;
-
-            let (_empty_pool_token_key, empty_pool_token_account) = mint_token(
-                &pool_token_program_id,
-                &accounts.pool_mint_key,
-                &mut accounts.pool_mint_account,
-                &accounts.authority_key,
-                &user_key,
-                0,
-            );
-
-            let (_pool_token_key, pool_token_account) = mint_token(
-                &pool_token_program_id,
-                &accounts.pool_mint_key,
-                &mut accounts.pool_mint_account,
-                &accounts.authority_key,
-                &user_key,
-                pool_token_amount,
-            );
-
-            // non-empty pool token account
-            accounts.pool_token_account = pool_token_account;
-            assert_eq!(
-                Err(SwapError::InvalidSupply.into()),
-                accounts.initialize_swap()
-            );
-
-            // pool tokens already in circulation
-            accounts.pool_token_account = empty_pool_token_account;
-            assert_eq!(
-                Err(SwapError::InvalidSupply.into()),
-                accounts.initialize_swap()
-            );
-
-            accounts.pool_mint_account = old_mint;
-            accounts.pool_token_account = old_pool_account;
-        }
 
-        // pool fee account has wrong mint
-        {
-            let (_pool_fee_key, pool_fee_account) = mint_token(
-                &token_a_program_id,
-                &accounts.token_a_mint_key,
-                &mut accounts.token_a_mint_account,
-                &user_key,
-                &user_key,
-                0,
-            );
-            let old_account = accounts.pool_fee_account;
-            accounts.pool_fee_account = pool_fee_account;
-            assert_eq!(
-                Err(SwapError::IncorrectPoolMint.into()),
-                accounts.initialize_swap()
-            );
-            accounts.pool_fee_account = old_account;
+            SwapAccountInfo {
+                bump_seed,
+                authority_key,
+                fees,
+                transfer_fees,
+                swap_curve,
+                swap_key,
+                swap_account,
+                pool_mint_key,
+                pool_mint_account,
+                pool_fee_key,
+                pool_fee_account,
+                pool_token_key,
+                pool_token_account,
+                token_a_key,
+                token_a_account,
+                token_a_mint_key,
+                token_a_mint_account,
+                token_b_key,
+                token_b_account,
+                token_b_mint_key,
+                token_b_mint_account,
+                pool_token_program_id: *pool_token_program_id,
+                token_a_program_id: *token_a_program_id,
+                token_b_program_id: *token_b_program_id,
+            }
         }
 
-        // token A account is delegated
-        {
+        pub fn initialize_swap(&mut self) -> ProgramResult {
             do_process_instruction(
-                approve(
-                    &token_a_program_id,
-                    &accounts.token_a_key,
-                    &user_key,
-                    &accounts.authority_key,
-                    &[],
-                    1,
+                initialize(
+                    &SWAP_PROGRAM_ID,
+                    &self.pool_token_program_id,
+                    &self.swap_key,
+                    &self.authority_key,
+                    &self.token_a_key,
+                    &self.token_b_key,
+                    &self.pool_mint_key,
+                    &self.pool_fee_key,
+                    &self.pool_token_key,
+                    self.fees.clone(),
+                    self.swap_curve.clone(),
                 )
                 .unwrap(),
                 vec![
-                    &mut accounts.token_a_account,
+                    &mut self.swap_account,
                     &mut SolanaAccount::default(),
+                    &mut self.token_a_account,
+                    &mut self.token_b_account,
+                    &mut self.pool_mint_account,
+                    &mut self.pool_fee_account,
+                    &mut self.pool_token_account,
                     &mut SolanaAccount::default(),
                 ],
             )
-            .unwrap();
-            assert_eq!(
-                Err(SwapError::InvalidDelegate.into()),
-                accounts.initialize_swap()
-            );
-
-            do_process_instruction(
-                revoke(
-                    &token_a_program_id,
-                    &accounts.token_a_key,
-                    &accounts.authority_key,
-                    &[],
-                )
-                .unwrap(),
-                vec![&mut accounts.token_a_account, &mut SolanaAccount::default()],
-            )
-            .unwrap();
         }
 
-        // token B account is delegated
-        {
-            do_process_instruction(
-                approve(
-                    &token_b_program_id,
-                    &accounts.token_b_key,
-                    &user_key,
-                    &accounts.authority_key,
-                    &[],
-                    1,
-                )
-                .unwrap(),
-                vec![
-                    &mut accounts.token_b_account,
-                    &mut SolanaAccount::default(),
-                    &mut SolanaAccount::default(),
-                ],
-            )
-            .unwrap();
-            assert_eq!(
-                Err(SwapError::InvalidDelegate.into()),
-                accounts.initialize_swap()
+        pub fn setup_token_accounts(
+            &mut self,
+            mint_owner: &Pubkey,
+            account_owner: &Pubkey,
+            a_amount: u64,
+            b_amount: u64,
+            pool_amount: u64,
+        ) -> (
+            Pubkey,
+            SolanaAccount,
+            Pubkey,
+            SolanaAccount,
+            Pubkey,
+            SolanaAccount,
+        ) {
+            let (token_a_key, token_a_account) = mint_token(
+                &self.token_a_program_id,
+                &self.token_a_mint_key,
+                &mut self.token_a_mint_account,
+                mint_owner,
+                account_owner,
+                a_amount,
+            );
+            let (token_b_key, token_b_account) = mint_token(
+                &self.token_b_program_id,
+                &self.token_b_mint_key,
+                &mut self.token_b_mint_account,
+                mint_owner,
+                account_owner,
+                b_amount,
+            );
+            let (pool_key, pool_account) = mint_token(
+                &self.pool_token_program_id,
+                &self.pool_mint_key,
+                &mut self.pool_mint_account,
+                &self.authority_key,
+                account_owner,
+                pool_amount,
             );
+            (
+                token_a_key,
+                token_a_account,
+                token_b_key,
+                token_b_account,
+                pool_key,
+                pool_account,
+            )
+        }
 
-            do_process_instruction(
-                revoke(
-                    &token_b_program_id,
-                    &accounts.token_b_key,
-                    &accounts.authority_key,
+        fn get_swap_key(&self, mint_key: &Pubkey) -> &Pubkey {
+            if *mint_key == self.token_a_mint_key {
+                &self.token_a_key
+            } else if *mint_key == self.token_b_mint_key {
+                &self.token_b_key
+            } else {
+                panic!("Could not find matching swap token account");
+            }
+        }
+
+        fn get_token_program_id(&self, account_key: &Pubkey) -> &Pubkey {
+            if *account_key == self.token_a_key {
+                &self.token_a_program_id
+            } else if *account_key == self.token_b_key {
+                &self.token_b_program_id
+            } else {
+                panic!("Could not find matching swap token account");
+            }
+        }
+
+        fn get_token_mint(&self, account_key: &Pubkey) -> (Pubkey, SolanaAccount) {
+            if *account_key == self.token_a_key {
+                (self.token_a_mint_key, self.token_a_mint_account.clone())
+            } else if *account_key == self.token_b_key {
+                (self.token_b_mint_key, self.token_b_mint_account.clone())
+            } else {
+                panic!("Could not find matching swap token account");
+            }
+        }
+
+        fn get_token_account(&self, account_key: &Pubkey) -> &SolanaAccount {
+            if *account_key == self.token_a_key {
+                &self.token_a_account
+            } else if *account_key == self.token_b_key {
+                &self.token_b_account
+            } else {
+                panic!("Could not find matching swap token account");
+            }
+        }
+
+        fn set_token_account(&mut self, account_key: &Pubkey, account: SolanaAccount) {
+            if *account_key == self.token_a_key {
+                self.token_a_account = account;
+                return;
+            } else if *account_key == self.token_b_key {
+                self.token_b_account = account;
+                return;
+            }
+            panic!("Could not find matching swap token account");
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        pub fn swap(
+            &mut self,
+            user_key: &Pubkey,
+            user_source_key: &Pubkey,
+            user_source_account: &mut SolanaAccount,
+            swap_source_key: &Pubkey,
+            swap_destination_key: &Pubkey,
+            user_destination_key: &Pubkey,
+            user_destination_account: &mut SolanaAccount,
+            amount_in: u64,
+            minimum_amount_out: u64,
+        ) -> ProgramResult {
+            let user_transfer_key = Pubkey::new_unique();
+            let source_token_program_id = self.get_token_program_id(swap_source_key);
+            let destination_token_program_id = self.get_token_program_id(swap_destination_key);
+            // approve moving from user source account
+            do_process_instruction(
+                approve(
+                    source_token_program_id,
+                    user_source_key,
+                    &user_transfer_key,
+                    user_key,
                     &[],
+                    amount_in,
                 )
                 .unwrap(),
-                vec![&mut accounts.token_b_account, &mut SolanaAccount::default()],
+                vec![
+                    user_source_account,
+                    &mut SolanaAccount::default(),
+                    &mut SolanaAccount::default(),
+                ],
             )
             .unwrap();
-        }
 
-        // token A account has close authority
-        {
+            let (source_mint_key, mut source_mint_account) = self.get_token_mint(swap_source_key);
+            let (destination_mint_key, mut destination_mint_account) =
+                self.get_token_mint(swap_destination_key);
+            let mut swap_source_account = self.get_token_account(swap_source_key).clone();
+            let mut swap_destination_account = self.get_token_account(swap_destination_key).clone();
+
+            // perform the swap
             do_process_instruction(
-                set_authority(
-                    &token_a_program_id,
-                    &accounts.token_a_key,
-                    Some(&user_key),
-                    AuthorityType::CloseAccount,
-                    &accounts.authority_key,
-                    &[],
+                swap(
+                    &SWAP_PROGRAM_ID,
+                    source_token_program_id,
+                    destination_token_program_id,
+                    &self.pool_token_program_id,
+                    &self.swap_key,
+                    &self.authority_key,
+                    &user_transfer_key,
+                    user_source_key,
+                    swap_source_key,
+                    swap_destination_key,
+                    user_destination_key,
+                    &self.pool_mint_key,
+                    &self.pool_fee_key,
+                    &source_mint_key,
+                    &destination_mint_key,
+                    None,
+                    Swap {
+                        amount_in,
+                        minimum_amount_out,
+                    },
                 )
                 .unwrap(),
-                vec![&mut accounts.token_a_account, &mut SolanaAccount::default()],
-            )
-            .unwrap();
-            assert_eq!(
-                Err(SwapError::InvalidCloseAuthority.into()),
-                accounts.initialize_swap()
-            );
+                vec![
+                    &mut self.swap_account,
+                    &mut SolanaAccount::default(),
+                    &mut SolanaAccount::default(),
+                    user_source_account,
+                    &mut swap_source_account,
+                    &mut swap_destination_account,
+                    user_destination_account,
+                    &mut self.pool_mint_account,
+                    &mut self.pool_fee_account,
+                    &mut source_mint_account,
+                    &mut destination_mint_account,
+                    &mut SolanaAccount::default(),
+                    &mut SolanaAccount::default(),
+                    &mut SolanaAccount::default(),
+                ],
+            )?;
+
+            self.set_token_account(swap_source_key, swap_source_account);
+            self.set_token_account(swap_destination_key, swap_destination_account);
 
+            Ok(())
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        pub fn deposit_all_token_types(
+            &mut self,
+            depositor_key: &Pubkey,
+            depositor_token_a_key: &Pubkey,
+            depositor_token_a_account: &mut SolanaAccount,
+            depositor_token_b_key: &Pubkey,
+            depositor_token_b_account: &mut SolanaAccount,
+            depositor_pool_key: &Pubkey,
+            depositor_pool_account: &mut SolanaAccount,
+            pool_token_amount: u64,
+            maximum_token_a_amount: u64,
+            maximum_token_b_amount: u64,
+        ) -> ProgramResult {
+            let user_transfer_authority = Pubkey::new_unique();
+            let token_a_program_id = depositor_token_a_account.owner;
             do_process_instruction(
-                set_authority(
+                approve(
                     &token_a_program_id,
-                    &accounts.token_a_key,
-                    None,
-                    AuthorityType::CloseAccount,
-                    &user_key,
+                    depositor_token_a_key,
+                    &user_transfer_authority,
+                    depositor_key,
                     &[],
+                    maximum_token_a_amount,
                 )
                 .unwrap(),
-                vec![&mut accounts.token_a_account, &mut SolanaAccount::default()],
+                vec![
+                    depositor_token_a_account,
+                    &mut SolanaAccount::default(),
+                    &mut SolanaAccount::default(),
+                ],
             )
             .unwrap();
-        }
 
-        // token B account has close authority
-        {
+            let token_b_program_id = depositor_token_b_account.owner;
             do_process_instruction(
-                set_authority(
+                approve(
                     &token_b_program_id,
-                    &accounts.token_b_key,
-                    Some(&user_key),
-                    AuthorityType::CloseAccount,
-                    &accounts.authority_key,
+                    depositor_token_b_key,
+                    &user_transfer_authority,
+                    depositor_key,
                     &[],
+                    maximum_token_b_amount,
                 )
                 .unwrap(),
-                vec![&mut accounts.token_b_account, &mut SolanaAccount::default()],
+                vec![
+                    depositor_token_b_account,
+                    &mut SolanaAccount::default(),
+                    &mut SolanaAccount::default(),
+                ],
             )
             .unwrap();
-            assert_eq!(
-                Err(SwapError::InvalidCloseAuthority.into()),
-                accounts.initialize_swap()
-            );
 
+            let pool_token_program_id = depositor_pool_account.owner;
             do_process_instruction(
-                set_authority(
+                deposit_all_token_types(
+                    &SWAP_PROGRAM_ID,
+                    &token_a_program_id,
                     &token_b_program_id,
-                    &accounts.token_b_key,
-                    None,
-                    AuthorityType::CloseAccount,
-                    &user_key,
-                    &[],
+                    &pool_token_program_id,
+                    &self.swap_key,
+                    &self.authority_key,
+                    &user_transfer_authority,
+                    depositor_token_a_key,
+                    depositor_token_b_key,
+                    &self.token_a_key,
+                    &self.token_b_key,
+                    &self.pool_mint_key,
+                    depositor_pool_key,
+                    &self.token_a_mint_key,
+                    &self.token_b_mint_key,
+                    DepositAllTokenTypes {
+                        pool_token_amount,
+                        maximum_token_a_amount,
+                        maximum_token_b_amount,
+                    },
                 )
                 .unwrap(),
-                vec![&mut accounts.token_b_account, &mut SolanaAccount::default()],
+                vec![
+                    &mut self.swap_account,
+                    &mut SolanaAccount::default(),
+                    &mut SolanaAccount::default(),
+                    depositor_token_a_account,
+                    depositor_token_b_account,
+                    &mut self.token_a_account,
+                    &mut self.token_b_account,
+                    &mut self.pool_mint_account,
+                    depositor_pool_account,
+                    &mut self.token_a_mint_account,
+                    &mut self.token_b_mint_account,
+                    &mut SolanaAccount::default(),
+                    &mut SolanaAccount::default(),
+                    &mut SolanaAccount::default(),
+                ],
             )
-            .unwrap();
         }
 
-        // wrong token program id
-        {
-            let wrong_program_id = Pubkey::new_unique();
-            assert_eq!(
-                Err(ProgramError::IncorrectProgramId),
-                do_process_instruction(
-                    initialize(
-                        &SWAP_PROGRAM_ID,
-                        &wrong_program_id,
-                        &accounts.swap_key,
-                        &accounts.authority_key,
-                        &accounts.token_a_key,
-                        &accounts.token_b_key,
-                        &accounts.pool_mint_key,
-                        &accounts.pool_fee_key,
-                        &accounts.pool_token_key,
-                        accounts.fees.clone(),
-                        accounts.swap_curve.clone(),
-                    )
-                    .unwrap(),
-                    vec![
-                        &mut accounts.swap_account,
-                        &mut SolanaAccount::default(),
-                        &mut accounts.token_a_account,
-                        &mut accounts.token_b_account,
-                        &mut accounts.pool_mint_account,
-                        &mut accounts.pool_fee_account,
-                        &mut accounts.pool_token_account,
-                        &mut SolanaAccount::default(),
-                    ],
+        #[allow(clippy::too_many_arguments)]
+        pub fn withdraw_all_token_types(
+            &mut self,
+            user_key: &Pubkey,
+            pool_key: &Pubkey,
+            pool_account: &mut SolanaAccount,
+            token_a_key: &Pubkey,
+            token_a_account: &mut SolanaAccount,
+            token_b_key: &Pubkey,
+            token_b_account: &mut SolanaAccount,
+            pool_token_amount: u64,
+            minimum_token_a_amount: u64,
+            minimum_token_b_amount: u64,
+        ) -> ProgramResult {
+            let user_transfer_authority_key = Pubkey::new_unique();
+            let pool_token_program_id = pool_account.owner;
+            // approve user transfer authority to take out pool tokens
+            do_process_instruction(
+                approve(
+                    &pool_token_program_id,
+                    pool_key,
+                    &user_transfer_authority_key,
+                    user_key,
+                    &[],
+                    pool_token_amount,
                 )
-            );
-        }
+                .unwrap(),
+                vec![
+                    pool_account,
+                    &mut SolanaAccount::default(),
+                    &mut SolanaAccount::default(),
+                ],
+            )
+            .unwrap();
 
-        // create swap with same token A and B
-        {
-            let (_token_a_repeat_key, token_a_repeat_account) = mint_token(
-                &token_a_program_id,
-                &accounts.token_a_mint_key,
-                &mut accounts.token_a_mint_account,
-                &user_key,
-                &accounts.authority_key,
-                10,
-            );
-            let old_account = accounts.token_b_account;
-            accounts.token_b_account = token_a_repeat_account;
-            assert_eq!(
-                Err(SwapError::RepeatedMint.into()),
-                accounts.initialize_swap()
-            );
-            accounts.token_b_account = old_account;
+            // withdraw token a and b correctly
+            let token_a_program_id = token_a_account.owner;
+            let token_b_program_id = token_b_account.owner;
+            do_process_instruction(
+                withdraw_all_token_types(
+                    &SWAP_PROGRAM_ID,
+                    &pool_token_program_id,
+                    &token_a_program_id,
+                    &token_b_program_id,
+                    &self.swap_key,
+                    &self.authority_key,
+                    &user_transfer_authority_key,
+                    &self.pool_mint_key,
+                    &self.pool_fee_key,
+                    pool_key,
+                    &self.token_a_key,
+                    &self.token_b_key,
+                    token_a_key,
+                    token_b_key,
+                    &self.token_a_mint_key,
+                    &self.token_b_mint_key,
+                    WithdrawAllTokenTypes {
+                        pool_token_amount,
+                        minimum_token_a_amount,
+                        minimum_token_b_amount,
+                    },
+                )
+                .unwrap(),
+                vec![
+                    &mut self.swap_account,
+                    &mut SolanaAccount::default(),
+                    &mut SolanaAccount::default(),
+                    &mut self.pool_mint_account,
+                    pool_account,
+                    &mut self.token_a_account,
+                    &mut self.token_b_account,
+                    token_a_account,
+                    token_b_account,
+                    &mut self.pool_fee_account,
+                    &mut self.token_a_mint_account,
+                    &mut self.token_b_mint_account,
+                    &mut SolanaAccount::default(),
+                    &mut SolanaAccount::default(),
+                    &mut SolanaAccount::default(),
+                ],
+            )
         }
 
-        // create valid swap
-        accounts.initialize_swap().unwrap();
+        #[allow(clippy::too_many_arguments)]
+        pub fn deposit_single_token_type_exact_amount_in(
+            &mut self,
+            depositor_key: &Pubkey,
+            deposit_account_key: &Pubkey,
+            deposit_token_account: &mut SolanaAccount,
+            deposit_pool_key: &Pubkey,
+            deposit_pool_account: &mut SolanaAccount,
+            source_token_amount: u64,
+            minimum_pool_token_amount: u64,
+        ) -> ProgramResult {
+            let user_transfer_authority_key = Pubkey::new_unique();
+            let source_token_program_id = deposit_token_account.owner;
+            do_process_instruction(
+                approve(
+                    &source_token_program_id,
+                    deposit_account_key,
+                    &user_transfer_authority_key,
+                    depositor_key,
+                    &[],
+                    source_token_amount,
+                )
+                .unwrap(),
+                vec![
+                    deposit_token_account,
+                    &mut SolanaAccount::default(),
+                    &mut SolanaAccount::default(),
+                ],
+            )
+            .unwrap();
 
-        // create invalid flat swap
-        {
-            let token_b_price = 0;
-            let fees = Fees {
-                trade_fee_numerator,
-                trade_fee_denominator,
-                owner_trade_fee_numerator,
-                owner_trade_fee_denominator,
-                owner_withdraw_fee_numerator,
-                owner_withdraw_fee_denominator,
-                host_fee_numerator,
-                host_fee_denominator,
-            };
-            let swap_curve = SwapCurve {
-                curve_type: CurveType::ConstantPrice,
-                calculator: Arc::new(ConstantPriceCurve { token_b_price }),
-            };
-            let mut accounts = SwapAccountInfo::new(
-                &user_key,
-                fees,
-                SwapTransferFees::default(),
-                swap_curve,
-                token_a_amount,
-                token_b_amount,
-                &pool_token_program_id,
-                &token_a_program_id,
-                &token_b_program_id,
-            );
-            assert_eq!(
-                Err(SwapError::InvalidCurve.into()),
-                accounts.initialize_swap()
-            );
+            let source_mint_key =
+                StateWithExtensions::<Account>::unpack(&deposit_token_account.data)
+                    .unwrap()
+                    .base
+                    .mint;
+            let swap_source_key = self.get_swap_key(&source_mint_key);
+            let (source_mint_key, mut source_mint_account) = self.get_token_mint(swap_source_key);
+
+            let pool_token_program_id = deposit_pool_account.owner;
+            do_process_instruction(
+                deposit_single_token_type_exact_amount_in(
+                    &SWAP_PROGRAM_ID,
+                    &source_token_program_id,
+                    &pool_token_program_id,
+                    &self.swap_key,
+                    &self.authority_key,
+                    &user_transfer_authority_key,
+                    deposit_account_key,
+                    &self.token_a_key,
+                    &self.token_b_key,
+                    &self.pool_mint_key,
+                    deposit_pool_key,
+                    &source_mint_key,
+                    DepositSingleTokenTypeExactAmountIn {
+                        source_token_amount,
+                        minimum_pool_token_amount,
+                    },
+                )
+                .unwrap(),
+                vec![
+                    &mut self.swap_account,
+                    &mut SolanaAccount::default(),
+                    &mut SolanaAccount::default(),
+                    deposit_token_account,
+                    &mut self.token_a_account,
+                    &mut self.token_b_account,
+                    &mut self.pool_mint_account,
+                    deposit_pool_account,
+                    &mut source_mint_account,
+                    &mut SolanaAccount::default(),
+                    &mut SolanaAccount::default(),
+                ],
+            )
         }
 
-        // create valid flat swap
-        {
-            let fees = Fees {
-                trade_fee_numerator,
-                trade_fee_denominator,
-                owner_trade_fee_numerator,
-                owner_trade_fee_denominator,
-                owner_withdraw_fee_numerator,
-                owner_withdraw_fee_denominator,
-                host_fee_numerator,
-                host_fee_denominator,
-            };
-            let token_b_price = 10_000;
-            let swap_curve = SwapCurve {
-                curve_type: CurveType::ConstantPrice,
-                calculator: Arc::new(ConstantPriceCurve { token_b_price }),
-            };
-            let mut accounts = SwapAccountInfo::new(
-                &user_key,
-                fees,
-                SwapTransferFees::default(),
-                swap_curve,
-                token_a_amount,
-                token_b_amount,
-                &pool_token_program_id,
-                &token_a_program_id,
-                &token_b_program_id,
-            );
-            accounts.initialize_swap().unwrap();
-        }
+        #[allow(clippy::too_many_arguments)]
+        pub fn withdraw_single_token_type_exact_amount_out(
+            &mut self,
+            user_key: &Pubkey,
+            pool_key: &Pubkey,
+            pool_account: &mut SolanaAccount,
+            destination_key: &Pubkey,
+            destination_account: &mut SolanaAccount,
+            destination_token_amount: u64,
+            maximum_pool_token_amount: u64,
+        ) -> ProgramResult {
+            let user_transfer_authority_key = Pubkey::new_unique();
+            let pool_token_program_id = pool_account.owner;
+            // approve user transfer authority to take out pool tokens
+            do_process_instruction(
+                approve(
+                    &pool_token_program_id,
+                    pool_key,
+                    &user_transfer_authority_key,
+                    user_key,
+                    &[],
+                    maximum_pool_token_amount,
+                )
+                .unwrap(),
+                vec![
+                    pool_account,
+                    &mut SolanaAccount::default(),
+                    &mut SolanaAccount::default(),
+                ],
+            )
+            .unwrap();
 
-        // create invalid offset swap
-        {
-            let token_b_offset = 0;
-            let fees = Fees {
-                trade_fee_numerator,
-                trade_fee_denominator,
-                owner_trade_fee_numerator,
-                owner_trade_fee_denominator,
-                owner_withdraw_fee_numerator,
-                owner_withdraw_fee_denominator,
-                host_fee_numerator,
-                host_fee_denominator,
-            };
-            let swap_curve = SwapCurve {
-                curve_type: CurveType::Offset,
-                calculator: Arc::new(OffsetCurve { token_b_offset }),
-            };
-            let mut accounts = SwapAccountInfo::new(
-                &user_key,
-                fees,
-                SwapTransferFees::default(),
-                swap_curve,
-                token_a_amount,
-                token_b_amount,
-                &pool_token_program_id,
-                &token_a_program_id,
-                &token_b_program_id,
-            );
-            assert_eq!(
-                Err(SwapError::InvalidCurve.into()),
-                accounts.initialize_swap()
-            );
+            let destination_mint_key =
+                StateWithExtensions::<Account>::unpack(&destination_account.data)
+                    .unwrap()
+                    .base
+                    .mint;
+            let swap_destination_key = self.get_swap_key(&destination_mint_key);
+            let (destination_mint_key, mut destination_mint_account) =
+                self.get_token_mint(swap_destination_key);
+
+            let destination_token_program_id = destination_account.owner;
+            do_process_instruction(
+                withdraw_single_token_type_exact_amount_out(
+                    &SWAP_PROGRAM_ID,
+                    &pool_token_program_id,
+                    &destination_token_program_id,
+                    &self.swap_key,
+                    &self.authority_key,
+                    &user_transfer_authority_key,
+                    &self.pool_mint_key,
+                    &self.pool_fee_key,
+                    pool_key,
+                    &self.token_a_key,
+                    &self.token_b_key,
+                    destination_key,
+                    &destination_mint_key,
+                    WithdrawSingleTokenTypeExactAmountOut {
+                        destination_token_amount,
+                        maximum_pool_token_amount,
+                    },
+                )
+                .unwrap(),
+                vec![
+                    &mut self.swap_account,
+                    &mut SolanaAccount::default(),
+                    &mut SolanaAccount::default(),
+                    &mut self.pool_mint_account,
+                    pool_account,
+                    &mut self.token_a_account,
+                    &mut self.token_b_account,
+                    destination_account,
+                    &mut self.pool_fee_account,
+                    &mut destination_mint_account,
+                    &mut SolanaAccount::default(),
+                    &mut SolanaAccount::default(),
+                ],
+            )
         }
 
-        // create valid offset swap
-        {
-            let token_b_offset = 10;
-            let fees = Fees {
-                trade_fee_numerator,
-                trade_fee_denominator,
-                owner_trade_fee_numerator,
-                owner_trade_fee_denominator,
-                owner_withdraw_fee_numerator,
-                owner_withdraw_fee_denominator,
-                host_fee_numerator,
-                host_fee_denominator,
-            };
-            let swap_curve = SwapCurve {
-                curve_type: CurveType::Offset,
-                calculator: Arc::new(OffsetCurve { token_b_offset }),
-            };
-            let mut accounts = SwapAccountInfo::new(
-                &user_key,
-                fees,
-                SwapTransferFees::default(),
-                swap_curve,
-                token_a_amount,
-                token_b_amount,
-                &pool_token_program_id,
-                &token_a_program_id,
-                &token_b_program_id,
-            );
-            accounts.initialize_swap().unwrap();
+        pub fn set_paused(&mut self, pause_authority_key: &Pubkey, paused: bool) -> ProgramResult {
+            do_process_instruction(
+                set_paused(
+                    &SWAP_PROGRAM_ID,
+                    &self.swap_key,
+                    pause_authority_key,
+                    SetPaused { paused },
+                )
+                .unwrap(),
+                vec![&mut self.swap_account, &mut SolanaAccount::default()],
+            )
         }
 
-        // wrong owner key in constraint
-        {
-            let new_key = Pubkey::new_unique();
-            let trade_fee_numerator = 25;
-            let trade_fee_denominator = 10000;
-            let owner_trade_fee_numerator = 5;
-            let owner_trade_fee_denominator = 10000;
-            let host_fee_numerator = 20;
-            let host_fee_denominator = 100;
-            let fees = Fees {
-                trade_fee_numerator,
-                trade_fee_denominator,
-                owner_trade_fee_numerator,
-                owner_trade_fee_denominator,
-                owner_withdraw_fee_numerator,
-                owner_withdraw_fee_denominator,
-                host_fee_numerator,
-                host_fee_denominator,
-            };
-            let curve = ConstantProductCurve {};
-            let swap_curve = SwapCurve {
-                curve_type: CurveType::ConstantProduct,
-                calculator: Arc::new(curve),
-            };
-            let owner_key = &new_key.to_string();
-            let valid_curve_types = &[CurveType::ConstantProduct];
-            let constraints = Some(SwapConstraints {
-                owner_key,
-                valid_curve_types,
-                fees: &fees,
-            });
-            let mut accounts = SwapAccountInfo::new(
-                &user_key,
-                fees.clone(),
-                SwapTransferFees::default(),
-                swap_curve,
-                token_a_amount,
-                token_b_amount,
-                &pool_token_program_id,
-                &token_a_program_id,
-                &token_b_program_id,
-            );
-            assert_eq!(
-                Err(SwapError::InvalidOwner.into()),
-                do_process_instruction_with_fee_constraints(
-                    initialize(
-                        &SWAP_PROGRAM_ID,
-                        &pool_token_program_id,
-                        &accounts.swap_key,
-                        &accounts.authority_key,
-                        &accounts.token_a_key,
-                        &accounts.token_b_key,
-                        &accounts.pool_mint_key,
-                        &accounts.pool_fee_key,
-                        &accounts.pool_token_key,
-                        accounts.fees.clone(),
-                        accounts.swap_curve.clone(),
-                    )
-                    .unwrap(),
-                    vec![
-                        &mut accounts.swap_account,
-                        &mut SolanaAccount::default(),
-                        &mut accounts.token_a_account,
-                        &mut accounts.token_b_account,
-                        &mut accounts.pool_mint_account,
-                        &mut accounts.pool_fee_account,
-                        &mut accounts.pool_token_account,
-                        &mut SolanaAccount::default(),
-                    ],
-                    &constraints,
+        pub fn set_pause_authority(
+            &mut self,
+            pause_authority_key: &Pubkey,
+            new_pause_authority: &Pubkey,
+        ) -> ProgramResult {
+            do_process_instruction(
+                set_pause_authority(
+                    &SWAP_PROGRAM_ID,
+                    &self.swap_key,
+                    pause_authority_key,
+                    SetPauseAuthority {
+                        new_pause_authority: *new_pause_authority,
+                    },
                 )
-            );
+                .unwrap(),
+                vec![&mut self.swap_account, &mut SolanaAccount::default()],
+            )
         }
+    }
 
-        // wrong fee in constraint
-        {
-            let trade_fee_numerator = 25;
-            let trade_fee_denominator = 10000;
-            let owner_trade_fee_numerator = 5;
-            let owner_trade_fee_denominator = 10000;
-            let host_fee_numerator = 20;
-            let host_fee_denominator = 100;
-            let fees = Fees {
-                trade_fee_numerator,
-                trade_fee_denominator,
-                owner_trade_fee_numerator,
-                owner_trade_fee_denominator,
-                owner_withdraw_fee_numerator,
-                owner_withdraw_fee_denominator,
-                host_fee_numerator,
-                host_fee_denominator,
-            };
-            let curve = ConstantProductCurve {};
-            let swap_curve = SwapCurve {
-                curve_type: CurveType::ConstantProduct,
-                calculator: Arc::new(curve),
-            };
-            let owner_key = &user_key.to_string();
-            let valid_curve_types = &[CurveType::ConstantProduct];
-            let constraints = Some(SwapConstraints {
-                owner_key,
-                valid_curve_types,
-                fees: &fees,
-            });
-            let mut bad_fees = fees.clone();
-            bad_fees.trade_fee_numerator = trade_fee_numerator - 1;
-            let mut accounts = SwapAccountInfo::new(
-                &user_key,
-                bad_fees,
-                SwapTransferFees::default(),
-                swap_curve,
-                token_a_amount,
-                token_b_amount,
-                &pool_token_program_id,
-                &token_a_program_id,
-                &token_b_program_id,
+    struct ConcentratedPoolInfo {
+        authority_key: Pubkey,
+        fees: Fees,
+        tick_spacing: u16,
+        initial_sqrt_price: u128,
+        min_position_liquidity: u128,
+        pool_key: Pubkey,
+        pool_account: SolanaAccount,
+        fee_authority_key: Pubkey,
+        token_a_key: Pubkey,
+        token_a_account: SolanaAccount,
+        token_a_mint_key: Pubkey,
+        token_a_mint_account: SolanaAccount,
+        token_b_key: Pubkey,
+        token_b_account: SolanaAccount,
+        token_b_mint_key: Pubkey,
+        token_b_mint_account: SolanaAccount,
+        token_a_program_id: Pubkey,
+        token_b_program_id: Pubkey,
+    }
+
+    impl ConcentratedPoolInfo {
+        pub fn new(
+            mint_owner_key: &Pubkey,
+            fees: Fees,
+            tick_spacing: u16,
+            initial_sqrt_price: u128,
+            token_a_program_id: &Pubkey,
+            token_b_program_id: &Pubkey,
+        ) -> Self {
+            let pool_key = Pubkey::new_unique();
+            let pool_account = SolanaAccount::new(0, ConcentratedPool::LEN, &SWAP_PROGRAM_ID);
+            let (authority_key, _bump_seed) =
+                Pubkey::find_program_address(&[&pool_key.to_bytes()[..]], &SWAP_PROGRAM_ID);
+            let (token_a_mint_key, mut token_a_mint_account) = create_mint(
+                token_a_program_id,
+                mint_owner_key,
+                None,
+                None,
+                &TransferFee::default(),
             );
-            assert_eq!(
-                Err(SwapError::InvalidFee.into()),
-                do_process_instruction_with_fee_constraints(
-                    initialize(
-                        &SWAP_PROGRAM_ID,
-                        &pool_token_program_id,
-                        &accounts.swap_key,
-                        &accounts.authority_key,
-                        &accounts.token_a_key,
-                        &accounts.token_b_key,
-                        &accounts.pool_mint_key,
-                        &accounts.pool_fee_key,
-                        &accounts.pool_token_key,
-                        accounts.fees.clone(),
-                        accounts.swap_curve.clone(),
-                    )
-                    .unwrap(),
-                    vec![
-                        &mut accounts.swap_account,
-                        &mut SolanaAccount::default(),
-                        &mut accounts.token_a_account,
-                        &mut accounts.token_b_account,
-                        &mut accounts.pool_mint_account,
-                        &mut accounts.pool_fee_account,
-                        &mut accounts.pool_token_account,
-                        &mut SolanaAccount::default(),
-                    ],
-                    &constraints,
-                )
+            let (token_a_key, token_a_account) = mint_token(
+                token_a_program_id,
+                &token_a_mint_key,
+                &mut token_a_mint_account,
+                mint_owner_key,
+                &authority_key,
+                0,
+            );
+            let (token_b_mint_key, mut token_b_mint_account) = create_mint(
+                token_b_program_id,
+                mint_owner_key,
+                None,
+                None,
+                &TransferFee::default(),
             );
+            let (token_b_key, token_b_account) = mint_token(
+                token_b_program_id,
+                &token_b_mint_key,
+                &mut token_b_mint_account,
+                mint_owner_key,
+                &authority_key,
+                0,
+            );
+
+            ConcentratedPoolInfo {
+                authority_key,
+                fees,
+                tick_spacing,
+                initial_sqrt_price,
+                min_position_liquidity: 0,
+                pool_key,
+                pool_account,
+                fee_authority_key: Pubkey::new_unique(),
+                token_a_key,
+                token_a_account,
+                token_a_mint_key,
+                token_a_mint_account,
+                token_b_key,
+                token_b_account,
+                token_b_mint_key,
+                token_b_mint_account,
+                token_a_program_id: *token_a_program_id,
+                token_b_program_id: *token_b_program_id,
+            }
         }
 
-        // create valid swap with constraints
-        {
-            let trade_fee_numerator = 25;
-            let trade_fee_denominator = 10000;
-            let owner_trade_fee_numerator = 5;
-            let owner_trade_fee_denominator = 10000;
-            let host_fee_numerator = 20;
-            let host_fee_denominator = 100;
-            let fees = Fees {
-                trade_fee_numerator,
-                trade_fee_denominator,
-                owner_trade_fee_numerator,
-                owner_trade_fee_denominator,
-                owner_withdraw_fee_numerator,
-                owner_withdraw_fee_denominator,
-                host_fee_numerator,
-                host_fee_denominator,
-            };
-            let curve = ConstantProductCurve {};
-            let swap_curve = SwapCurve {
-                curve_type: CurveType::ConstantProduct,
-                calculator: Arc::new(curve),
-            };
-            let owner_key = &user_key.to_string();
-            let valid_curve_types = &[CurveType::ConstantProduct];
-            let constraints = Some(SwapConstraints {
-                owner_key,
-                valid_curve_types,
-                fees: &fees,
-            });
-            let mut accounts = SwapAccountInfo::new(
-                &user_key,
-                fees.clone(),
-                SwapTransferFees::default(),
-                swap_curve,
-                token_a_amount,
-                token_b_amount,
-                &pool_token_program_id,
-                &token_a_program_id,
-                &token_b_program_id,
-            );
+        pub fn initialize_pool(&mut self) -> ProgramResult {
+            self.initialize_pool_with_constraints(&None)
+        }
+
+        pub fn initialize_pool_with_constraints(
+            &mut self,
+            swap_constraints: &Option<SwapConstraints>,
+        ) -> ProgramResult {
             do_process_instruction_with_fee_constraints(
-                initialize(
+                initialize_concentrated_pool(
                     &SWAP_PROGRAM_ID,
-                    &pool_token_program_id,
-                    &accounts.swap_key,
-                    &accounts.authority_key,
-                    &accounts.token_a_key,
-                    &accounts.token_b_key,
-                    &accounts.pool_mint_key,
-                    &accounts.pool_fee_key,
-                    &accounts.pool_token_key,
-                    accounts.fees,
-                    accounts.swap_curve.clone(),
+                    &self.pool_key,
+                    &self.authority_key,
+                    &self.token_a_key,
+                    &self.token_b_key,
+                    &self.fee_authority_key,
+                    InitializeConcentratedPool {
+                        fees: self.fees.clone(),
+                        tick_spacing: self.tick_spacing,
+                        initial_sqrt_price: self.initial_sqrt_price,
+                        min_position_liquidity: self.min_position_liquidity,
+                    },
                 )
                 .unwrap(),
                 vec![
-                    &mut accounts.swap_account,
+                    &mut self.pool_account,
                     &mut SolanaAccount::default(),
-                    &mut accounts.token_a_account,
-                    &mut accounts.token_b_account,
-                    &mut accounts.pool_mint_account,
-                    &mut accounts.pool_fee_account,
-                    &mut accounts.pool_token_account,
+                    &mut self.token_a_account,
+                    &mut self.token_b_account,
                     &mut SolanaAccount::default(),
                 ],
-                &constraints,
+                swap_constraints,
             )
-            .unwrap();
         }
 
-        // create again
-        {
-            assert_eq!(
-                Err(SwapError::AlreadyInUse.into()),
-                accounts.initialize_swap()
-            );
+        pub fn pool(&self) -> ConcentratedPool {
+            ConcentratedPool::unpack(&self.pool_account.data).unwrap()
         }
-        let swap_state = SwapVersion::unpack(&accounts.swap_account.data).unwrap();
-        assert!(swap_state.is_initialized());
-        assert_eq!(swap_state.bump_seed(), accounts.bump_seed);
-        assert_eq!(
-            swap_state.swap_curve().curve_type,
-            accounts.swap_curve.curve_type
-        );
-        assert_eq!(*swap_state.token_a_account(), accounts.token_a_key);
-        assert_eq!(*swap_state.token_b_account(), accounts.token_b_key);
-        assert_eq!(*swap_state.pool_mint(), accounts.pool_mint_key);
-        assert_eq!(*swap_state.token_a_mint(), accounts.token_a_mint_key);
-        assert_eq!(*swap_state.token_b_mint(), accounts.token_b_mint_key);
-        assert_eq!(*swap_state.pool_fee_account(), accounts.pool_fee_key);
-        let token_a =
-            StateWithExtensions::<Account>::unpack(&accounts.token_a_account.data).unwrap();
-        assert_eq!(token_a.base.amount, token_a_amount);
-        let token_b =
-            StateWithExtensions::<Account>::unpack(&accounts.token_b_account.data).unwrap();
-        assert_eq!(token_b.base.amount, token_b_amount);
-        let pool_account =
-            StateWithExtensions::<Account>::unpack(&accounts.pool_token_account.data).unwrap();
-        let pool_mint =
-            StateWithExtensions::<Mint>::unpack(&accounts.pool_mint_account.data).unwrap();
-        assert_eq!(pool_mint.base.supply, pool_account.base.amount);
-    }
 
-    #[test_case(spl_token::id(), spl_token::id(), spl_token::id(); "all-token")]
-    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token_2022::id(); "all-token-2022")]
-    #[test_case(spl_token::id(), spl_token_2022::id(), spl_token_2022::id(); "mixed-pool-token")]
-    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token::id(); "mixed-pool-token-2022")]
-    fn test_deposit(
-        pool_token_program_id: Pubkey,
-        token_a_program_id: Pubkey,
-        token_b_program_id: Pubkey,
-    ) {
-        let user_key = Pubkey::new_unique();
-        let depositor_key = Pubkey::new_unique();
-        let trade_fee_numerator = 1;
-        let trade_fee_denominator = 2;
-        let owner_trade_fee_numerator = 1;
-        let owner_trade_fee_denominator = 10;
-        let owner_withdraw_fee_numerator = 1;
-        let owner_withdraw_fee_denominator = 5;
-        let host_fee_numerator = 20;
-        let host_fee_denominator = 100;
+        pub fn setup_token_accounts(
+            &mut self,
+            mint_owner: &Pubkey,
+            account_owner: &Pubkey,
+            a_amount: u64,
+            b_amount: u64,
+        ) -> (Pubkey, SolanaAccount, Pubkey, SolanaAccount) {
+            let (token_a_key, token_a_account) = mint_token(
+                &self.token_a_program_id,
+                &self.token_a_mint_key,
+                &mut self.token_a_mint_account,
+                mint_owner,
+                account_owner,
+                a_amount,
+            );
+            let (token_b_key, token_b_account) = mint_token(
+                &self.token_b_program_id,
+                &self.token_b_mint_key,
+                &mut self.token_b_mint_account,
+                mint_owner,
+                account_owner,
+                b_amount,
+            );
+            (token_a_key, token_a_account, token_b_key, token_b_account)
+        }
 
-        let fees = Fees {
-            trade_fee_numerator,
-            trade_fee_denominator,
-            owner_trade_fee_numerator,
-            owner_trade_fee_denominator,
-            owner_withdraw_fee_numerator,
-            owner_withdraw_fee_denominator,
-            host_fee_numerator,
-            host_fee_denominator,
-        };
-
-        let token_a_amount = 1000;
-        let token_b_amount = 9000;
-        let curve_type = CurveType::ConstantProduct;
-        let swap_curve = SwapCurve {
-            curve_type,
-            calculator: Arc::new(ConstantProductCurve {}),
-        };
-
-        let mut accounts = SwapAccountInfo::new(
-            &user_key,
-            fees,
-            SwapTransferFees::default(),
-            swap_curve,
-            token_a_amount,
-            token_b_amount,
-            &pool_token_program_id,
-            &token_a_program_id,
-            &token_b_program_id,
-        );
-
-        // depositing 10% of the current pool amount in token A and B means
-        // that our pool tokens will be worth 1 / 10 of the current pool amount
-        let pool_amount = INITIAL_SWAP_POOL_AMOUNT / 10;
-        let deposit_a = token_a_amount / 10;
-        let deposit_b = token_b_amount / 10;
+        pub fn open_position(
+            &mut self,
+            owner_key: &Pubkey,
+            tick_lower: i32,
+            tick_upper: i32,
+        ) -> Result<(Pubkey, SolanaAccount), ProgramError> {
+            let position_key = Pubkey::new_unique();
+            let mut position_account = SolanaAccount::new(0, Position::LEN, &SWAP_PROGRAM_ID);
+            do_process_instruction(
+                open_position(
+                    &SWAP_PROGRAM_ID,
+                    &self.pool_key,
+                    &position_key,
+                    owner_key,
+                    OpenPosition {
+                        tick_lower,
+                        tick_upper,
+                    },
+                )
+                .unwrap(),
+                vec![
+                    &mut self.pool_account,
+                    &mut position_account,
+                    &mut SolanaAccount::default(),
+                ],
+            )?;
+            Ok((position_key, position_account))
+        }
 
-        // swap not initialized
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            assert_eq!(
-                Err(ProgramError::UninitializedAccount),
-                accounts.deposit_all_token_types(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    &pool_key,
-                    &mut pool_account,
-                    pool_amount.try_into().unwrap(),
-                    deposit_a,
-                    deposit_b,
+        pub fn close_position(
+            &mut self,
+            owner_key: &Pubkey,
+            position_key: &Pubkey,
+            position_account: &mut SolanaAccount,
+            destination_key: &Pubkey,
+            destination_account: &mut SolanaAccount,
+        ) -> ProgramResult {
+            do_process_instruction(
+                close_position(
+                    &SWAP_PROGRAM_ID,
+                    &self.pool_key,
+                    position_key,
+                    owner_key,
+                    destination_key,
                 )
-            );
+                .unwrap(),
+                vec![
+                    &mut self.pool_account,
+                    position_account,
+                    &mut SolanaAccount::default(),
+                    destination_account,
+                ],
+            )
         }
 
-        accounts.initialize_swap().unwrap();
+        #[allow(clippy::too_many_arguments)]
+        pub fn increase_liquidity(
+            &mut self,
+            owner_key: &Pubkey,
+            position_key: &Pubkey,
+            position_account: &mut SolanaAccount,
+            user_token_a_key: &Pubkey,
+            user_token_a_account: &mut SolanaAccount,
+            user_token_b_key: &Pubkey,
+            user_token_b_account: &mut SolanaAccount,
+            liquidity: u128,
+            maximum_token_a_amount: u64,
+            maximum_token_b_amount: u64,
+        ) -> ProgramResult {
+            do_process_instruction(
+                increase_liquidity(
+                    &SWAP_PROGRAM_ID,
+                    &self.token_a_program_id,
+                    &self.token_b_program_id,
+                    &self.pool_key,
+                    &self.authority_key,
+                    position_key,
+                    owner_key,
+                    owner_key,
+                    user_token_a_key,
+                    user_token_b_key,
+                    &self.token_a_key,
+                    &self.token_b_key,
+                    &self.token_a_mint_key,
+                    &self.token_b_mint_key,
+                    IncreaseLiquidity {
+                        liquidity,
+                        maximum_token_a_amount,
+                        maximum_token_b_amount,
+                    },
+                )
+                .unwrap(),
+                vec![
+                    &mut self.pool_account,
+                    &mut SolanaAccount::default(),
+                    position_account,
+                    &mut SolanaAccount::default(),
+                    &mut SolanaAccount::default(),
+                    user_token_a_account,
+                    user_token_b_account,
+                    &mut self.token_a_account,
+                    &mut self.token_b_account,
+                    &mut self.token_a_mint_account,
+                    &mut self.token_b_mint_account,
+                    &mut SolanaAccount::default(),
+                    &mut SolanaAccount::default(),
+                ],
+            )
+        }
 
-        // wrong owner for swap account
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            let old_swap_account = accounts.swap_account;
-            let mut wrong_swap_account = old_swap_account.clone();
-            wrong_swap_account.owner = pool_token_program_id;
-            accounts.swap_account = wrong_swap_account;
-            assert_eq!(
-                Err(ProgramError::IncorrectProgramId),
-                accounts.deposit_all_token_types(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    &pool_key,
-                    &mut pool_account,
-                    pool_amount.try_into().unwrap(),
-                    deposit_a,
-                    deposit_b,
+        #[allow(clippy::too_many_arguments)]
+        pub fn decrease_liquidity(
+            &mut self,
+            owner_key: &Pubkey,
+            position_key: &Pubkey,
+            position_account: &mut SolanaAccount,
+            user_token_a_key: &Pubkey,
+            user_token_a_account: &mut SolanaAccount,
+            user_token_b_key: &Pubkey,
+            user_token_b_account: &mut SolanaAccount,
+            liquidity: u128,
+            minimum_token_a_amount: u64,
+            minimum_token_b_amount: u64,
+        ) -> ProgramResult {
+            do_process_instruction(
+                decrease_liquidity(
+                    &SWAP_PROGRAM_ID,
+                    &self.token_a_program_id,
+                    &self.token_b_program_id,
+                    &self.pool_key,
+                    &self.authority_key,
+                    position_key,
+                    owner_key,
+                    &self.token_a_key,
+                    &self.token_b_key,
+                    user_token_a_key,
+                    user_token_b_key,
+                    &self.token_a_mint_key,
+                    &self.token_b_mint_key,
+                    DecreaseLiquidity {
+                        liquidity,
+                        minimum_token_a_amount,
+                        minimum_token_b_amount,
+                    },
                 )
-            );
-            accounts.swap_account = old_swap_account;
+                .unwrap(),
+                vec![
+                    &mut self.pool_account,
+                    &mut SolanaAccount::default(),
+                    position_account,
+                    &mut SolanaAccount::default(),
+                    &mut self.token_a_account,
+                    &mut self.token_b_account,
+                    user_token_a_account,
+                    user_token_b_account,
+                    &mut self.token_a_mint_account,
+                    &mut self.token_b_mint_account,
+                    &mut SolanaAccount::default(),
+                    &mut SolanaAccount::default(),
+                ],
+            )
         }
 
-        // wrong bump seed for authority_key
-        {
+        #[allow(clippy::too_many_arguments)]
+        pub fn swap(
+            &mut self,
+            user_key: &Pubkey,
+            trade_direction: TradeDirection,
+            user_source_key: &Pubkey,
+            user_source_account: &mut SolanaAccount,
+            user_destination_key: &Pubkey,
+            user_destination_account: &mut SolanaAccount,
+            host_fee: Option<(&Pubkey, &mut SolanaAccount)>,
+            amount_in: u64,
+            minimum_amount_out: u64,
+        ) -> ProgramResult {
             let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            let old_authority = accounts.authority_key;
-            let (bad_authority_key, _bump_seed) = Pubkey::find_program_address(
-                &[&accounts.swap_key.to_bytes()[..]],
-                &pool_token_program_id,
-            );
-            accounts.authority_key = bad_authority_key;
-            assert_eq!(
-                Err(SwapError::InvalidProgramAddress.into()),
-                accounts.deposit_all_token_types(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    &pool_key,
-                    &mut pool_account,
-                    pool_amount.try_into().unwrap(),
-                    deposit_a,
-                    deposit_b,
+                source_program_id,
+                destination_program_id,
+                pool_source_key,
+                pool_destination_key,
+                source_mint_key,
+                destination_mint_key,
+            ) = match trade_direction {
+                TradeDirection::AtoB => (
+                    self.token_a_program_id,
+                    self.token_b_program_id,
+                    self.token_a_key,
+                    self.token_b_key,
+                    self.token_a_mint_key,
+                    self.token_b_mint_key,
+                ),
+                TradeDirection::BtoA => (
+                    self.token_b_program_id,
+                    self.token_a_program_id,
+                    self.token_b_key,
+                    self.token_a_key,
+                    self.token_b_mint_key,
+                    self.token_a_mint_key,
+                ),
+            };
+            let (pool_source_account, pool_destination_account, source_mint, destination_mint) =
+                match trade_direction {
+                    TradeDirection::AtoB => (
+                        &mut self.token_a_account,
+                        &mut self.token_b_account,
+                        &mut self.token_a_mint_account,
+                        &mut self.token_b_mint_account,
+                    ),
+                    TradeDirection::BtoA => (
+                        &mut self.token_b_account,
+                        &mut self.token_a_account,
+                        &mut self.token_b_mint_account,
+                        &mut self.token_a_mint_account,
+                    ),
+                };
+            let (host_fee_key, host_fee_account) = host_fee.unzip();
+            let mut authority_account = SolanaAccount::default();
+            let mut user_transfer_authority_account = SolanaAccount::default();
+            let mut source_program_account = SolanaAccount::default();
+            let mut destination_program_account = SolanaAccount::default();
+            let mut accounts = vec![
+                &mut self.pool_account,
+                &mut authority_account,
+                &mut user_transfer_authority_account,
+                user_source_account,
+                pool_source_account,
+                pool_destination_account,
+                user_destination_account,
+                source_mint,
+                destination_mint,
+                &mut source_program_account,
+                &mut destination_program_account,
+            ];
+            if let Some(host_fee_account) = host_fee_account {
+                accounts.push(host_fee_account);
+            }
+            do_process_instruction(
+                concentrated_swap(
+                    &SWAP_PROGRAM_ID,
+                    &source_program_id,
+                    &destination_program_id,
+                    &self.pool_key,
+                    &self.authority_key,
+                    user_key,
+                    user_source_key,
+                    &pool_source_key,
+                    &pool_destination_key,
+                    user_destination_key,
+                    &source_mint_key,
+                    &destination_mint_key,
+                    host_fee_key,
+                    Swap {
+                        amount_in,
+                        minimum_amount_out,
+                    },
                 )
-            );
-            accounts.authority_key = old_authority;
+                .unwrap(),
+                accounts,
+            )
         }
 
-        // not enough token A
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &depositor_key,
-                deposit_a / 2,
-                deposit_b,
-                0,
-            );
-            assert_eq!(
-                Err(TokenError::InsufficientFunds.into()),
-                accounts.deposit_all_token_types(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    &pool_key,
-                    &mut pool_account,
-                    pool_amount.try_into().unwrap(),
-                    deposit_a,
-                    deposit_b,
+        pub fn set_paused(&mut self, pause_authority_key: &Pubkey, paused: bool) -> ProgramResult {
+            do_process_instruction(
+                set_paused(
+                    &SWAP_PROGRAM_ID,
+                    &self.pool_key,
+                    pause_authority_key,
+                    SetPaused { paused },
                 )
-            );
+                .unwrap(),
+                vec![&mut self.pool_account, &mut SolanaAccount::default()],
+            )
         }
 
-        // not enough token B
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &depositor_key,
-                deposit_a,
-                deposit_b / 2,
-                0,
-            );
-            assert_eq!(
-                Err(TokenError::InsufficientFunds.into()),
-                accounts.deposit_all_token_types(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    &pool_key,
-                    &mut pool_account,
-                    pool_amount.try_into().unwrap(),
-                    deposit_a,
-                    deposit_b,
+        pub fn set_pause_authority(
+            &mut self,
+            pause_authority_key: &Pubkey,
+            new_pause_authority: &Pubkey,
+        ) -> ProgramResult {
+            do_process_instruction(
+                set_pause_authority(
+                    &SWAP_PROGRAM_ID,
+                    &self.pool_key,
+                    pause_authority_key,
+                    SetPauseAuthority {
+                        new_pause_authority: *new_pause_authority,
+                    },
                 )
-            );
+                .unwrap(),
+                vec![&mut self.pool_account, &mut SolanaAccount::default()],
+            )
         }
 
-        // wrong swap token accounts
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            let expected_error: ProgramError = if token_a_account.owner == token_b_account.owner {
-                TokenError::MintMismatch.into()
-            } else {
-                ProgramError::InvalidAccountData
-            };
-            assert_eq!(
-                Err(expected_error),
-                accounts.deposit_all_token_types(
-                    &depositor_key,
-                    &token_b_key,
-                    &mut token_b_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &pool_key,
-                    &mut pool_account,
-                    pool_amount.try_into().unwrap(),
-                    deposit_a,
-                    deposit_b,
+        pub fn collect_protocol_fees(
+            &mut self,
+            fee_authority_key: &Pubkey,
+            destination_token_a_key: &Pubkey,
+            destination_token_a_account: &mut SolanaAccount,
+            destination_token_b_key: &Pubkey,
+            destination_token_b_account: &mut SolanaAccount,
+        ) -> ProgramResult {
+            do_process_instruction(
+                collect_protocol_fees(
+                    &SWAP_PROGRAM_ID,
+                    &self.token_a_program_id,
+                    &self.token_b_program_id,
+                    &self.pool_key,
+                    &self.authority_key,
+                    fee_authority_key,
+                    &self.token_a_key,
+                    &self.token_b_key,
+                    destination_token_a_key,
+                    destination_token_b_key,
+                    &self.token_a_mint_key,
+                    &self.token_b_mint_key,
                 )
-            );
+                .unwrap(),
+                vec![
+                    &mut self.pool_account,
+                    &mut SolanaAccount::default(),
+                    &mut SolanaAccount::default(),
+                    &mut self.token_a_account,
+                    &mut self.token_b_account,
+                    destination_token_a_account,
+                    destination_token_b_account,
+                    &mut self.token_a_mint_account,
+                    &mut self.token_b_mint_account,
+                    &mut SolanaAccount::default(),
+                    &mut SolanaAccount::default(),
+                ],
+            )
         }
+    }
 
-        // wrong pool token account
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                mut _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            let (
-                wrong_token_key,
-                mut wrong_token_account,
-                _token_b_key,
-                mut _token_b_account,
-                _pool_key,
-                pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            let expected_error: ProgramError = if token_a_account.owner == pool_account.owner {
-                TokenError::MintMismatch.into()
-            } else {
-                SwapError::IncorrectTokenProgramId.into()
-            };
-            assert_eq!(
-                Err(expected_error),
-                accounts.deposit_all_token_types(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    &wrong_token_key,
-                    &mut wrong_token_account,
-                    pool_amount.try_into().unwrap(),
-                    deposit_a,
-                    deposit_b,
-                )
-            );
-        }
+    fn mint_minimum_balance() -> u64 {
+        Rent::default().minimum_balance(spl_token::state::Mint::get_packed_len())
+    }
 
-        // no approval
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            let user_transfer_authority_key = Pubkey::new_unique();
-            assert_eq!(
-                Err(TokenError::OwnerMismatch.into()),
-                do_process_instruction(
-                    deposit_all_token_types(
-                        &SWAP_PROGRAM_ID,
-                        &token_a_program_id,
-                        &token_b_program_id,
-                        &pool_token_program_id,
-                        &accounts.swap_key,
-                        &accounts.authority_key,
-                        &user_transfer_authority_key,
-                        &token_a_key,
-                        &token_b_key,
-                        &accounts.token_a_key,
-                        &accounts.token_b_key,
-                        &accounts.pool_mint_key,
-                        &pool_key,
-                        &accounts.token_a_mint_key,
-                        &accounts.token_b_mint_key,
-                        DepositAllTokenTypes {
-                            pool_token_amount: pool_amount.try_into().unwrap(),
-                            maximum_token_a_amount: deposit_a,
-                            maximum_token_b_amount: deposit_b,
-                        },
-                    )
-                    .unwrap(),
-                    vec![
-                        &mut accounts.swap_account,
-                        &mut SolanaAccount::default(),
-                        &mut SolanaAccount::default(),
-                        &mut token_a_account,
-                        &mut token_b_account,
-                        &mut accounts.token_a_account,
-                        &mut accounts.token_b_account,
-                        &mut accounts.pool_mint_account,
-                        &mut pool_account,
-                        &mut accounts.token_a_mint_account,
-                        &mut accounts.token_b_mint_account,
-                        &mut SolanaAccount::default(),
-                        &mut SolanaAccount::default(),
-                        &mut SolanaAccount::default(),
-                    ],
-                )
-            );
-        }
+    fn account_minimum_balance() -> u64 {
+        Rent::default().minimum_balance(spl_token::state::Account::get_packed_len())
+    }
 
-        // wrong token program id
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            let wrong_key = Pubkey::new_unique();
-            assert_eq!(
-                Err(SwapError::IncorrectTokenProgramId.into()),
-                do_process_instruction(
-                    deposit_all_token_types(
-                        &SWAP_PROGRAM_ID,
-                        &wrong_key,
-                        &wrong_key,
-                        &wrong_key,
-                        &accounts.swap_key,
-                        &accounts.authority_key,
-                        &accounts.authority_key,
-                        &token_a_key,
-                        &token_b_key,
-                        &accounts.token_a_key,
-                        &accounts.token_b_key,
-                        &accounts.pool_mint_key,
-                        &pool_key,
-                        &accounts.token_a_mint_key,
-                        &accounts.token_b_mint_key,
-                        DepositAllTokenTypes {
-                            pool_token_amount: pool_amount.try_into().unwrap(),
-                            maximum_token_a_amount: deposit_a,
-                            maximum_token_b_amount: deposit_b,
-                        },
-                    )
-                    .unwrap(),
-                    vec![
-                        &mut accounts.swap_account,
-                        &mut SolanaAccount::default(),
-                        &mut SolanaAccount::default(),
-                        &mut token_a_account,
-                        &mut token_b_account,
-                        &mut accounts.token_a_account,
-                        &mut accounts.token_b_account,
-                        &mut accounts.pool_mint_account,
-                        &mut pool_account,
-                        &mut accounts.token_a_mint_account,
-                        &mut accounts.token_b_mint_account,
-                        &mut SolanaAccount::default(),
-                        &mut SolanaAccount::default(),
-                        &mut SolanaAccount::default(),
-                    ],
-                )
-            );
-        }
+    fn do_process_instruction_with_fee_constraints(
+        instruction: Instruction,
+        accounts: Vec<&mut SolanaAccount>,
+        swap_constraints: &Option<SwapConstraints>,
+    ) -> ProgramResult {
+        test_syscall_stubs();
 
-        // wrong swap token accounts
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-
-            let old_a_key = accounts.token_a_key;
-            let old_a_account = accounts.token_a_account;
+        // approximate the logic in the actual runtime which runs the instruction
+        // and only updates accounts if the instruction is successful
+        let mut account_clones = accounts.iter().map(|x| (*x).clone()).collect::<Vec<_>>();
+        let mut meta = instruction
+            .accounts
+            .iter()
+            .zip(account_clones.iter_mut())
+            .map(|(account_meta, account)| (&account_meta.pubkey, account_meta.is_signer, account))
+            .collect::<Vec<_>>();
+        let mut account_infos = create_is_signer_account_infos(&mut meta);
+        let res = if instruction.program_id == SWAP_PROGRAM_ID {
+            Processor::process_with_constraints(
+                &instruction.program_id,
+                &account_infos,
+                &instruction.data,
+                swap_constraints,
+            )
+        } else if instruction.program_id == spl_token::id() {
+            spl_token::processor::Processor::process(
+                &instruction.program_id,
+                &account_infos,
+                &instruction.data,
+            )
+        } else if instruction.program_id == spl_token_2022::id() {
+            spl_token_2022::processor::Processor::process(
+                &instruction.program_id,
+                &account_infos,
+                &instruction.data,
+            )
+        } else {
+            Err(ProgramError::IncorrectProgramId)
+        };
 
-            accounts.token_a_key = token_a_key;
-            accounts.token_a_account = token_a_account.clone();
+        if res.is_ok() {
+            let mut account_metas = instruction
+                .accounts
+                .iter()
+                .zip(accounts)
+                .map(|(account_meta, account)| (&account_meta.pubkey, account))
+                .collect::<Vec<_>>();
+            for account_info in account_infos.iter_mut() {
+                for account_meta in account_metas.iter_mut() {
+                    if account_info.key == account_meta.0 {
+                        let account = &mut account_meta.1;
+                        account.owner = *account_info.owner;
+                        account.lamports = **account_info.lamports.borrow();
+                        account.data = account_info.data.borrow().to_vec();
+                    }
+                }
+            }
+        }
+        res
+    }
 
-            // wrong swap token a account
-            assert_eq!(
-                Err(SwapError::IncorrectSwapAccount.into()),
-                accounts.deposit_all_token_types(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    &pool_key,
-                    &mut pool_account,
-                    pool_amount.try_into().unwrap(),
-                    deposit_a,
-                    deposit_b,
-                )
-            );
+    fn do_process_instruction(
+        instruction: Instruction,
+        accounts: Vec<&mut SolanaAccount>,
+    ) -> ProgramResult {
+        do_process_instruction_with_fee_constraints(instruction, accounts, &SWAP_CONSTRAINTS)
+    }
 
-            accounts.token_a_key = old_a_key;
-            accounts.token_a_account = old_a_account;
+    fn mint_token(
+        program_id: &Pubkey,
+        mint_key: &Pubkey,
+        mint_account: &mut SolanaAccount,
+        mint_authority_key: &Pubkey,
+        account_owner_key: &Pubkey,
+        amount: u64,
+    ) -> (Pubkey, SolanaAccount) {
+        let account_key = Pubkey::new_unique();
+        let space = if *program_id == spl_token_2022::id() {
+            ExtensionType::try_calculate_account_len::<Account>(&[
+                ExtensionType::ImmutableOwner,
+                ExtensionType::TransferFeeAmount,
+            ])
+            .unwrap()
+        } else {
+            Account::get_packed_len()
+        };
+        let minimum_balance = Rent::default().minimum_balance(space);
+        let mut account_account = SolanaAccount::new(minimum_balance, space, program_id);
+        let mut mint_authority_account = SolanaAccount::default();
+        let mut rent_sysvar_account = create_account_for_test(&Rent::free());
 
-            let old_b_key = accounts.token_b_key;
-            let old_b_account = accounts.token_b_account;
+        // no-ops in normal token, so we're good to run it either way
+        do_process_instruction(
+            initialize_immutable_owner(program_id, &account_key).unwrap(),
+            vec![&mut account_account],
+        )
+        .unwrap();
 
-            accounts.token_b_key = token_b_key;
-            accounts.token_b_account = token_b_account.clone();
+        do_process_instruction(
+            initialize_account(program_id, &account_key, mint_key, account_owner_key).unwrap(),
+            vec![
+                &mut account_account,
+                mint_account,
+                &mut mint_authority_account,
+                &mut rent_sysvar_account,
+            ],
+        )
+        .unwrap();
 
-            // wrong swap token b account
-            assert_eq!(
-                Err(SwapError::IncorrectSwapAccount.into()),
-                accounts.deposit_all_token_types(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    &pool_key,
-                    &mut pool_account,
-                    pool_amount.try_into().unwrap(),
-                    deposit_a,
-                    deposit_b,
+        if amount > 0 {
+            do_process_instruction(
+                mint_to(
+                    program_id,
+                    mint_key,
+                    &account_key,
+                    mint_authority_key,
+                    &[],
+                    amount,
                 )
-            );
-
-            accounts.token_b_key = old_b_key;
-            accounts.token_b_account = old_b_account;
+                .unwrap(),
+                vec![
+                    mint_account,
+                    &mut account_account,
+                    &mut mint_authority_account,
+                ],
+            )
+            .unwrap();
         }
 
-        // wrong mint
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            let (pool_mint_key, pool_mint_account) = create_mint(
-                &pool_token_program_id,
-                &accounts.authority_key,
-                None,
-                None,
-                &TransferFee::default(),
-            );
-            let old_pool_key = accounts.pool_mint_key;
-            let old_pool_account = accounts.pool_mint_account;
-            accounts.pool_mint_key = pool_mint_key;
-            accounts.pool_mint_account = pool_mint_account;
+        (account_key, account_account)
+    }
 
-            assert_eq!(
-                Err(SwapError::IncorrectPoolMint.into()),
-                accounts.deposit_all_token_types(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    &pool_key,
-                    &mut pool_account,
-                    pool_amount.try_into().unwrap(),
-                    deposit_a,
-                    deposit_b,
-                )
-            );
+    fn create_mint(
+        program_id: &Pubkey,
+        authority_key: &Pubkey,
+        freeze_authority: Option<&Pubkey>,
+        close_authority: Option<&Pubkey>,
+        fees: &TransferFee,
+    ) -> (Pubkey, SolanaAccount) {
+        let mint_key = Pubkey::new_unique();
+        let space = if *program_id == spl_token_2022::id() {
+            if close_authority.is_some() {
+                ExtensionType::try_calculate_account_len::<Mint>(&[
+                    ExtensionType::MintCloseAuthority,
+                    ExtensionType::TransferFeeConfig,
+                ])
+                .unwrap()
+            } else {
+                ExtensionType::try_calculate_account_len::<Mint>(&[
+                    ExtensionType::TransferFeeConfig,
+                ])
+                .unwrap()
+            }
+        } else {
+            Mint::get_packed_len()
+        };
+        let minimum_balance = Rent::default().minimum_balance(space);
+        let mut mint_account = SolanaAccount::new(minimum_balance, space, program_id);
+        let mut rent_sysvar_account = create_account_for_test(&Rent::free());
 
-            accounts.pool_mint_key = old_pool_key;
-            accounts.pool_mint_account = old_pool_account;
+        if *program_id == spl_token_2022::id() {
+            if close_authority.is_some() {
+                do_process_instruction(
+                    initialize_mint_close_authority(program_id, &mint_key, close_authority)
+                        .unwrap(),
+                    vec![&mut mint_account],
+                )
+                .unwrap();
+            }
+            do_process_instruction(
+                initialize_transfer_fee_config(
+                    program_id,
+                    &mint_key,
+                    freeze_authority,
+                    freeze_authority,
+                    fees.transfer_fee_basis_points.into(),
+                    fees.maximum_fee.into(),
+                )
+                .unwrap(),
+                vec![&mut mint_account],
+            )
+            .unwrap();
         }
-
-        // deposit 1 pool token fails because it equates to 0 swap tokens
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+        do_process_instruction(
+            initialize_mint(program_id, &mint_key, authority_key, freeze_authority, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar_account],
+        )
+        .unwrap();
+
+        (mint_key, mint_account)
+    }
+
+    #[test_case(spl_token::id(); "token")]
+    #[test_case(spl_token_2022::id(); "token-2022")]
+    fn test_token_program_id_error(token_program_id: Pubkey) {
+        test_syscall_stubs();
+        let swap_key = Pubkey::new_unique();
+        let mut mint = (Pubkey::new_unique(), SolanaAccount::default());
+        let mut destination = (Pubkey::new_unique(), SolanaAccount::default());
+        let token_program = (token_program_id, SolanaAccount::default());
+        let (authority_key, bump_seed) =
+            Pubkey::find_program_address(&[&swap_key.to_bytes()[..]], &SWAP_PROGRAM_ID);
+        let mut authority = (authority_key, SolanaAccount::default());
+        let swap_bytes = swap_key.to_bytes();
+        let authority_signature_seeds = [&swap_bytes[..32], &[bump_seed]];
+        let signers = &[&authority_signature_seeds[..]];
+        let ix = mint_to(
+            &token_program.0,
+            &mint.0,
+            &destination.0,
+            &authority.0,
+            &[],
+            10,
+        )
+        .unwrap();
+        let mint = (&mut mint).into();
+        let destination = (&mut destination).into();
+        let authority = (&mut authority).into();
+
+        let err = invoke_signed(&ix, &[mint, destination, authority], signers).unwrap_err();
+        assert_eq!(err, ProgramError::InvalidAccountData);
+    }
+
+    #[test_case(spl_token::id(); "token")]
+    #[test_case(spl_token_2022::id(); "token-2022")]
+    fn test_token_error(token_program_id: Pubkey) {
+        test_syscall_stubs();
+        let swap_key = Pubkey::new_unique();
+        let mut mint = (
+            Pubkey::new_unique(),
+            SolanaAccount::new(
+                mint_minimum_balance(),
+                spl_token::state::Mint::get_packed_len(),
+                &token_program_id,
+            ),
+        );
+        let mut destination = (
+            Pubkey::new_unique(),
+            SolanaAccount::new(
+                account_minimum_balance(),
+                spl_token::state::Account::get_packed_len(),
+                &token_program_id,
+            ),
+        );
+        let mut token_program = (token_program_id, SolanaAccount::default());
+        let (authority_key, bump_seed) =
+            Pubkey::find_program_address(&[&swap_key.to_bytes()[..]], &SWAP_PROGRAM_ID);
+        let mut authority = (authority_key, SolanaAccount::default());
+        let swap_bytes = swap_key.to_bytes();
+        let authority_signature_seeds = [&swap_bytes[..32], &[bump_seed]];
+        let signers = &[&authority_signature_seeds[..]];
+        let mut rent_sysvar = (
+            Pubkey::new_unique(),
+            create_account_for_test(&Rent::default()),
+        );
+        do_process_instruction(
+            initialize_mint(
+                &token_program.0,
+                &mint.0,
+                &authority.0,
+                Some(&authority.0),
+                2,
+            )
+            .unwrap(),
+            vec![&mut mint.1, &mut rent_sysvar.1],
+        )
+        .unwrap();
+        do_process_instruction(
+            initialize_account(&token_program.0, &destination.0, &mint.0, &authority.0).unwrap(),
+            vec![
+                &mut destination.1,
+                &mut mint.1,
+                &mut authority.1,
+                &mut rent_sysvar.1,
+                &mut token_program.1,
+            ],
+        )
+        .unwrap();
+        do_process_instruction(
+            freeze_account(&token_program.0, &destination.0, &mint.0, &authority.0, &[]).unwrap(),
+            vec![
+                &mut destination.1,
+                &mut mint.1,
+                &mut authority.1,
+                &mut token_program.1,
+            ],
+        )
+        .unwrap();
+        let ix = mint_to(
+            &token_program.0,
+            &mint.0,
+            &destination.0,
+            &authority.0,
+            &[],
+            10,
+        )
+        .unwrap();
+        let mint_info = (&mut mint).into();
+        let destination_info = (&mut destination).into();
+        let authority_info = (&mut authority).into();
+        let token_program_info = (&mut token_program).into();
+
+        let err = invoke_signed_wrapper::<TokenError>(
+            &ix,
+            &[
+                mint_info,
+                destination_info,
+                authority_info,
+                token_program_info,
+            ],
+            signers,
+        )
+        .unwrap_err();
+        assert_eq!(err, ProgramError::Custom(TokenError::AccountFrozen as u32));
+    }
+
+    #[test_case(spl_token::id(), spl_token::id(), spl_token::id(); "all-token")]
+    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token_2022::id(); "all-token-2022")]
+    #[test_case(spl_token::id(), spl_token_2022::id(), spl_token_2022::id(); "mixed-pool-token")]
+    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token::id(); "mixed-pool-token-2022")]
+    fn test_initialize(
+        pool_token_program_id: Pubkey,
+        token_a_program_id: Pubkey,
+        token_b_program_id: Pubkey,
+    ) {
+        let user_key = Pubkey::new_unique();
+        let trade_fee_numerator = 1;
+        let trade_fee_denominator = 2;
+        let owner_trade_fee_numerator = 1;
+        let owner_trade_fee_denominator = 10;
+        let owner_withdraw_fee_numerator = 1;
+        let owner_withdraw_fee_denominator = 5;
+        let host_fee_numerator = 20;
+        let host_fee_denominator = 100;
+        let fees = Fees {
+            trade_fee_numerator,
+            trade_fee_denominator,
+            owner_trade_fee_numerator,
+            owner_trade_fee_denominator,
+            owner_withdraw_fee_numerator,
+            owner_withdraw_fee_denominator,
+            host_fee_numerator,
+            host_fee_denominator,
+        };
+
+        let token_a_amount = 1000;
+        let token_b_amount = 2000;
+        let pool_token_amount = 10;
+        let curve_type = CurveType::ConstantProduct;
+        let swap_curve = SwapCurve {
+            curve_type,
+            calculator: Arc::new(ConstantProductCurve {}),
+        };
+
+        let mut accounts = SwapAccountInfo::new(
+            &user_key,
+            fees,
+            SwapTransferFees::default(),
+            swap_curve,
+            token_a_amount,
+            token_b_amount,
+            &pool_token_program_id,
+            &token_a_program_id,
+            &token_b_program_id,
+        );
+
+        // uninitialized token a account
+        {
+            let old_account = accounts.token_a_account;
+            accounts.token_a_account = SolanaAccount::new(0, 0, &token_a_program_id);
             assert_eq!(
-                Err(SwapError::ZeroTradingTokens.into()),
-                accounts.deposit_all_token_types(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    &pool_key,
-                    &mut pool_account,
-                    1,
-                    deposit_a,
-                    deposit_b,
-                )
+                Err(SwapError::ExpectedAccount.into()),
+                accounts.initialize_swap()
             );
+            accounts.token_a_account = old_account;
         }
 
-        // slippage exceeded
+        // uninitialized token b account
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            // maximum A amount in too low
-            assert_eq!(
-                Err(SwapError::ExceededSlippage.into()),
-                accounts.deposit_all_token_types(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    &pool_key,
-                    &mut pool_account,
-                    pool_amount.try_into().unwrap(),
-                    deposit_a / 10,
-                    deposit_b,
-                )
-            );
-            // maximum B amount in too low
+            let old_account = accounts.token_b_account;
+            accounts.token_b_account = SolanaAccount::new(0, 0, &token_b_program_id);
             assert_eq!(
-                Err(SwapError::ExceededSlippage.into()),
-                accounts.deposit_all_token_types(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    &pool_key,
-                    &mut pool_account,
-                    pool_amount.try_into().unwrap(),
-                    deposit_a,
-                    deposit_b / 10,
-                )
+                Err(SwapError::ExpectedAccount.into()),
+                accounts.initialize_swap()
             );
+            accounts.token_b_account = old_account;
         }
 
-        // invalid input: can't use swap pool tokens as source
+        // uninitialized pool mint
         {
-            let (
-                _token_a_key,
-                _token_a_account,
-                _token_b_key,
-                _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            let swap_token_a_key = accounts.token_a_key;
-            let mut swap_token_a_account = accounts.get_token_account(&swap_token_a_key).clone();
-            let swap_token_b_key = accounts.token_b_key;
-            let mut swap_token_b_account = accounts.get_token_account(&swap_token_b_key).clone();
-            let authority_key = accounts.authority_key;
+            let old_account = accounts.pool_mint_account;
+            accounts.pool_mint_account = SolanaAccount::new(0, 0, &pool_token_program_id);
             assert_eq!(
-                Err(SwapError::InvalidInput.into()),
-                accounts.deposit_all_token_types(
-                    &authority_key,
-                    &swap_token_a_key,
-                    &mut swap_token_a_account,
-                    &swap_token_b_key,
-                    &mut swap_token_b_account,
-                    &pool_key,
-                    &mut pool_account,
-                    pool_amount.try_into().unwrap(),
-                    deposit_a,
-                    deposit_b,
-                )
+                Err(SwapError::ExpectedMint.into()),
+                accounts.initialize_swap()
             );
+            accounts.pool_mint_account = old_account;
         }
 
-        // correctly deposit
+        // token A account owner is not swap authority
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            accounts
-                .deposit_all_token_types(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    &pool_key,
-                    &mut pool_account,
-                    pool_amount.try_into().unwrap(),
-                    deposit_a,
-                    deposit_b,
-                )
-                .unwrap();
-
-            let swap_token_a =
-                StateWithExtensions::<Account>::unpack(&accounts.token_a_account.data).unwrap();
-            assert_eq!(swap_token_a.base.amount, deposit_a + token_a_amount);
-            let swap_token_b =
-                StateWithExtensions::<Account>::unpack(&accounts.token_b_account.data).unwrap();
-            assert_eq!(swap_token_b.base.amount, deposit_b + token_b_amount);
-            let token_a = StateWithExtensions::<Account>::unpack(&token_a_account.data).unwrap();
-            assert_eq!(token_a.base.amount, 0);
-            let token_b = StateWithExtensions::<Account>::unpack(&token_b_account.data).unwrap();
-            assert_eq!(token_b.base.amount, 0);
-            let pool_account = StateWithExtensions::<Account>::unpack(&pool_account.data).unwrap();
-            let swap_pool_account =
-                StateWithExtensions::<Account>::unpack(&accounts.pool_token_account.data).unwrap();
-            let pool_mint =
-                StateWithExtensions::<Mint>::unpack(&accounts.pool_mint_account.data).unwrap();
-            assert_eq!(
-                pool_mint.base.supply,
-                pool_account.base.amount + swap_pool_account.base.amount
+            let (_token_a_key, token_a_account) = mint_token(
+                &token_a_program_id,
+                &accounts.token_a_mint_key,
+                &mut accounts.token_a_mint_account,
+                &user_key,
+                &user_key,
+                0,
             );
-        }
-    }
-
-    #[test_case(spl_token::id(), spl_token::id(), spl_token::id(); "all-token")]
-    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token_2022::id(); "all-token-2022")]
-    #[test_case(spl_token::id(), spl_token_2022::id(), spl_token_2022::id(); "mixed-pool-token")]
-    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token::id(); "mixed-pool-token-2022")]
-    fn test_withdraw(
-        pool_token_program_id: Pubkey,
-        token_a_program_id: Pubkey,
-        token_b_program_id: Pubkey,
-    ) {
-        let user_key = Pubkey::new_unique();
-        let trade_fee_numerator = 1;
-        let trade_fee_denominator = 2;
-        let owner_trade_fee_numerator = 1;
-        let owner_trade_fee_denominator = 10;
-        let owner_withdraw_fee_numerator = 1;
-        let owner_withdraw_fee_denominator = 5;
-        let host_fee_numerator = 7;
-        let host_fee_denominator = 100;
-
-        let fees = Fees {
-            trade_fee_numerator,
-            trade_fee_denominator,
-            owner_trade_fee_numerator,
-            owner_trade_fee_denominator,
-            owner_withdraw_fee_numerator,
-            owner_withdraw_fee_denominator,
-            host_fee_numerator,
-            host_fee_denominator,
-        };
-
-        let token_a_amount = 1000;
-        let token_b_amount = 2000;
-        let curve_type = CurveType::ConstantProduct;
-        let swap_curve = SwapCurve {
-            curve_type,
-            calculator: Arc::new(ConstantProductCurve {}),
-        };
-
-        let withdrawer_key = Pubkey::new_unique();
-        let initial_a = token_a_amount / 10;
-        let initial_b = token_b_amount / 10;
-        let initial_pool = swap_curve.calculator.new_pool_supply() / 10;
-        let withdraw_amount = initial_pool / 4;
-        let minimum_token_a_amount = initial_a / 40;
-        let minimum_token_b_amount = initial_b / 40;
-
-        let mut accounts = SwapAccountInfo::new(
-            &user_key,
-            fees,
-            SwapTransferFees::default(),
-            swap_curve,
-            token_a_amount,
-            token_b_amount,
-            &pool_token_program_id,
-            &token_a_program_id,
-            &token_b_program_id,
-        );
-
-        // swap not initialized
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
+            let old_account = accounts.token_a_account;
+            accounts.token_a_account = token_a_account;
             assert_eq!(
-                Err(ProgramError::UninitializedAccount),
-                accounts.withdraw_all_token_types(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
-                )
+                Err(SwapError::InvalidOwner.into()),
+                accounts.initialize_swap()
             );
+            accounts.token_a_account = old_account;
         }
 
-        accounts.initialize_swap().unwrap();
-
-        // wrong owner for swap account
+        // token B account owner is not swap authority
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
-            let old_swap_account = accounts.swap_account;
-            let mut wrong_swap_account = old_swap_account.clone();
-            wrong_swap_account.owner = pool_token_program_id;
-            accounts.swap_account = wrong_swap_account;
+            let (_token_b_key, token_b_account) = mint_token(
+                &token_b_program_id,
+                &accounts.token_b_mint_key,
+                &mut accounts.token_b_mint_account,
+                &user_key,
+                &user_key,
+                0,
+            );
+            let old_account = accounts.token_b_account;
+            accounts.token_b_account = token_b_account;
             assert_eq!(
-                Err(ProgramError::IncorrectProgramId),
-                accounts.withdraw_all_token_types(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
-                )
+                Err(SwapError::InvalidOwner.into()),
+                accounts.initialize_swap()
             );
-            accounts.swap_account = old_swap_account;
+            accounts.token_b_account = old_account;
         }
 
-        // wrong bump seed for authority_key
+        // pool token account owner is swap authority
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
-            let old_authority = accounts.authority_key;
-            let (bad_authority_key, _bump_seed) = Pubkey::find_program_address(
-                &[&accounts.swap_key.to_bytes()[..]],
+            let (_pool_token_key, pool_token_account) = mint_token(
                 &pool_token_program_id,
+                &accounts.pool_mint_key,
+                &mut accounts.pool_mint_account,
+                &accounts.authority_key,
+                &accounts.authority_key,
+                0,
             );
-            accounts.authority_key = bad_authority_key;
+            let old_account = accounts.pool_token_account;
+            accounts.pool_token_account = pool_token_account;
             assert_eq!(
-                Err(SwapError::InvalidProgramAddress.into()),
-                accounts.withdraw_all_token_types(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
-                )
+                Err(SwapError::InvalidOutputOwner.into()),
+                accounts.initialize_swap()
             );
-            accounts.authority_key = old_authority;
+            accounts.pool_token_account = old_account;
         }
 
-        // not enough pool tokens
+        // pool fee account owner is swap authority
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                to_u64(withdraw_amount).unwrap() / 2u64,
+            let (_pool_fee_key, pool_fee_account) = mint_token(
+                &pool_token_program_id,
+                &accounts.pool_mint_key,
+                &mut accounts.pool_mint_account,
+                &accounts.authority_key,
+                &accounts.authority_key,
+                0,
             );
+            let old_account = accounts.pool_fee_account;
+            accounts.pool_fee_account = pool_fee_account;
             assert_eq!(
-                Err(TokenError::InsufficientFunds.into()),
-                accounts.withdraw_all_token_types(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount / 2,
-                    minimum_token_b_amount / 2,
-                )
+                Err(SwapError::InvalidOutputOwner.into()),
+                accounts.initialize_swap()
             );
+            accounts.pool_fee_account = old_account;
         }
 
-        // wrong token a / b accounts
+        // pool mint authority is not swap authority
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
+            let (_pool_mint_key, pool_mint_account) = create_mint(
+                &pool_token_program_id,
                 &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                withdraw_amount.try_into().unwrap(),
+                None,
+                None,
+                &TransferFee::default(),
             );
-            let expected_error: ProgramError = if token_a_account.owner == token_b_account.owner {
-                TokenError::MintMismatch.into()
-            } else {
-                ProgramError::InvalidAccountData
-            };
+            let old_mint = accounts.pool_mint_account;
+            accounts.pool_mint_account = pool_mint_account;
             assert_eq!(
-                Err(expected_error),
-                accounts.withdraw_all_token_types(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
-                )
+                Err(SwapError::InvalidOwner.into()),
+                accounts.initialize_swap()
             );
+            accounts.pool_mint_account = old_mint;
         }
 
-        // wrong pool token account
+        // pool mint token has freeze authority
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                withdraw_amount.try_into().unwrap(),
+            let (_pool_mint_key, pool_mint_account) = create_mint(
+                &pool_token_program_id,
+                &accounts.authority_key,
+                Some(&user_key),
+                None,
+                &TransferFee::default(),
             );
-            let (
-                wrong_token_a_key,
-                mut wrong_token_a_account,
-                _token_b_key,
-                _token_b_account,
-                _pool_key,
-                pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                withdraw_amount.try_into().unwrap(),
-                initial_b,
-                withdraw_amount.try_into().unwrap(),
+            let old_mint = accounts.pool_mint_account;
+            accounts.pool_mint_account = pool_mint_account;
+            assert_eq!(
+                Err(SwapError::InvalidFreezeAuthority.into()),
+                accounts.initialize_swap()
             );
-            let expected_error: ProgramError = if token_a_account.owner == pool_account.owner {
-                TokenError::MintMismatch.into()
-            } else {
-                SwapError::IncorrectTokenProgramId.into()
-            };
+            accounts.pool_mint_account = old_mint;
+        }
+
+        // pool mint token has close authority, only available in token-2022
+        if pool_token_program_id == spl_token_2022::id() {
+            let (_pool_mint_key, pool_mint_account) = create_mint(
+                &pool_token_program_id,
+                &accounts.authority_key,
+                None,
+                Some(&user_key),
+                &TransferFee::default(),
+            );
+            let old_mint = accounts.pool_mint_account;
+            accounts.pool_mint_account = pool_mint_account;
             assert_eq!(
-                Err(expected_error),
-                accounts.withdraw_all_token_types(
-                    &withdrawer_key,
-                    &wrong_token_a_key,
-                    &mut wrong_token_a_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
-                )
+                Err(SwapError::InvalidCloseAuthority.into()),
+                accounts.initialize_swap()
             );
+            accounts.pool_mint_account = old_mint;
         }
 
-        // wrong pool fee account
+        // token A account owned by wrong program
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                wrong_pool_key,
-                wrong_pool_account,
-            ) = accounts.setup_token_accounts(
+            let (_token_a_key, mut token_a_account) = mint_token(
+                &token_a_program_id,
+                &accounts.token_a_mint_key,
+                &mut accounts.token_a_mint_account,
                 &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                withdraw_amount.try_into().unwrap(),
+                &accounts.authority_key,
+                token_a_amount,
             );
-            let (
-                _token_a_key,
-                _token_a_account,
-                _token_b_key,
-                _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
+            token_a_account.owner = SWAP_PROGRAM_ID;
+            let old_account = accounts.token_a_account;
+            accounts.token_a_account = token_a_account;
+            assert_eq!(
+                Err(SwapError::IncorrectTokenProgramId.into()),
+                accounts.initialize_swap()
+            );
+            accounts.token_a_account = old_account;
+        }
+
+        // token B account owned by wrong program
+        {
+            let (_token_b_key, mut token_b_account) = mint_token(
+                &token_b_program_id,
+                &accounts.token_b_mint_key,
+                &mut accounts.token_b_mint_account,
                 &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                withdraw_amount.try_into().unwrap(),
+                &accounts.authority_key,
+                token_b_amount,
             );
-            let old_pool_fee_account = accounts.pool_fee_account;
-            let old_pool_fee_key = accounts.pool_fee_key;
-            accounts.pool_fee_account = wrong_pool_account;
-            accounts.pool_fee_key = wrong_pool_key;
+            token_b_account.owner = SWAP_PROGRAM_ID;
+            let old_account = accounts.token_b_account;
+            accounts.token_b_account = token_b_account;
             assert_eq!(
-                Err(SwapError::IncorrectFeeAccount.into()),
-                accounts.withdraw_all_token_types(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
-                ),
+                Err(SwapError::IncorrectTokenProgramId.into()),
+                accounts.initialize_swap()
             );
-            accounts.pool_fee_account = old_pool_fee_account;
-            accounts.pool_fee_key = old_pool_fee_key;
+            accounts.token_b_account = old_account;
         }
 
-        // no approval
+        // empty token A account
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
+            let (_token_a_key, token_a_account) = mint_token(
+                &token_a_program_id,
+                &accounts.token_a_mint_key,
+                &mut accounts.token_a_mint_account,
                 &user_key,
-                &withdrawer_key,
-                0,
+                &accounts.authority_key,
                 0,
-                withdraw_amount.try_into().unwrap(),
             );
-            let user_transfer_authority_key = Pubkey::new_unique();
+            let old_account = accounts.token_a_account;
+            accounts.token_a_account = token_a_account;
             assert_eq!(
-                Err(TokenError::OwnerMismatch.into()),
-                do_process_instruction(
-                    withdraw_all_token_types(
-                        &SWAP_PROGRAM_ID,
-                        &pool_token_program_id,
-                        &token_a_program_id,
-                        &token_b_program_id,
-                        &accounts.swap_key,
-                        &accounts.authority_key,
-                        &user_transfer_authority_key,
-                        &accounts.pool_mint_key,
-                        &accounts.pool_fee_key,
-                        &pool_key,
-                        &accounts.token_a_key,
-                        &accounts.token_b_key,
-                        &token_a_key,
-                        &token_b_key,
-                        &accounts.token_a_mint_key,
-                        &accounts.token_b_mint_key,
-                        WithdrawAllTokenTypes {
-                            pool_token_amount: withdraw_amount.try_into().unwrap(),
-                            minimum_token_a_amount,
-                            minimum_token_b_amount,
-                        }
-                    )
-                    .unwrap(),
-                    vec![
-                        &mut accounts.swap_account,
-                        &mut SolanaAccount::default(),
-                        &mut SolanaAccount::default(),
-                        &mut accounts.pool_mint_account,
-                        &mut pool_account,
-                        &mut accounts.token_a_account,
-                        &mut accounts.token_b_account,
-                        &mut token_a_account,
-                        &mut token_b_account,
-                        &mut accounts.pool_fee_account,
-                        &mut accounts.token_a_mint_account,
-                        &mut accounts.token_b_mint_account,
-                        &mut SolanaAccount::default(),
-                        &mut SolanaAccount::default(),
-                        &mut SolanaAccount::default(),
-                    ],
-                )
+                Err(SwapError::EmptySupply.into()),
+                accounts.initialize_swap()
             );
+            accounts.token_a_account = old_account;
         }
 
-        // wrong token program id
+        // empty token B account
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
+            let (_token_b_key, token_b_account) = mint_token(
+                &token_b_program_id,
+                &accounts.token_b_mint_key,
+                &mut accounts.token_b_mint_account,
                 &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                withdraw_amount.try_into().unwrap(),
+                &accounts.authority_key,
+                0,
             );
-            let wrong_key = Pubkey::new_unique();
+            let old_account = accounts.token_b_account;
+            accounts.token_b_account = token_b_account;
             assert_eq!(
-                Err(SwapError::IncorrectTokenProgramId.into()),
-                do_process_instruction(
-                    withdraw_all_token_types(
-                        &SWAP_PROGRAM_ID,
-                        &wrong_key,
-                        &wrong_key,
-                        &wrong_key,
-                        &accounts.swap_key,
-                        &accounts.authority_key,
-                        &accounts.authority_key,
-                        &accounts.pool_mint_key,
-                        &accounts.pool_fee_key,
-                        &pool_key,
-                        &accounts.token_a_key,
-                        &accounts.token_b_key,
-                        &token_a_key,
-                        &token_b_key,
-                        &accounts.token_a_mint_key,
-                        &accounts.token_b_mint_key,
-                        WithdrawAllTokenTypes {
-                            pool_token_amount: withdraw_amount.try_into().unwrap(),
-                            minimum_token_a_amount,
-                            minimum_token_b_amount,
-                        },
-                    )
-                    .unwrap(),
-                    vec![
-                        &mut accounts.swap_account,
-                        &mut SolanaAccount::default(),
-                        &mut SolanaAccount::default(),
-                        &mut accounts.pool_mint_account,
-                        &mut pool_account,
-                        &mut accounts.token_a_account,
-                        &mut accounts.token_b_account,
-                        &mut token_a_account,
-                        &mut token_b_account,
-                        &mut accounts.pool_fee_account,
-                        &mut accounts.token_a_mint_account,
-                        &mut accounts.token_b_mint_account,
-                        &mut SolanaAccount::default(),
-                        &mut SolanaAccount::default(),
-                        &mut SolanaAccount::default(),
-                    ],
-                )
+                Err(SwapError::EmptySupply.into()),
+                accounts.initialize_swap()
             );
+            accounts.token_b_account = old_account;
         }
 
-        // wrong swap token accounts
+        // invalid pool tokens
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                initial_pool.try_into().unwrap(),
+            let old_mint = accounts.pool_mint_account;
+            let old_pool_account = accounts.pool_token_account;
+
+            let (_pool_mint_key, pool_mint_account) = create_mint(
+                &pool_token_program_id,
+                &accounts.authority_key,
+                None,
+                None,
+                &TransferFee::default(),
             );
+            accounts.pool_mint_account = pool_mint_account;
 
-            let old_a_key = accounts.token_a_key;
-            let old_a_account = accounts.token_a_account;
+            let (_empty_pool_token_key, empty_pool_token_account) = mint_token(
+                &pool_token_program_id,
+                &accounts.pool_mint_key,
+                &mut accounts.pool_mint_account,
+                &accounts.authority_key,
+                &user_key,
+                0,
+            );
 
-            accounts.token_a_key = token_a_key;
-            accounts.token_a_account = token_a_account.clone();
+            let (_pool_token_key, pool_token_account) = mint_token(
+                &pool_token_program_id,
+                &accounts.pool_mint_key,
+                &mut accounts.pool_mint_account,
+                &accounts.authority_key,
+                &user_key,
+                pool_token_amount,
+            );
 
-            // wrong swap token a account
+            // non-empty pool token account
+            accounts.pool_token_account = pool_token_account;
             assert_eq!(
-                Err(SwapError::IncorrectSwapAccount.into()),
-                accounts.withdraw_all_token_types(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
-                )
+                Err(SwapError::InvalidSupply.into()),
+                accounts.initialize_swap()
             );
 
-            accounts.token_a_key = old_a_key;
-            accounts.token_a_account = old_a_account;
-
-            let old_b_key = accounts.token_b_key;
-            let old_b_account = accounts.token_b_account;
-
-            accounts.token_b_key = token_b_key;
-            accounts.token_b_account = token_b_account.clone();
-
-            // wrong swap token b account
+            // pool tokens already in circulation
+            accounts.pool_token_account = empty_pool_token_account;
             assert_eq!(
-                Err(SwapError::IncorrectSwapAccount.into()),
-                accounts.withdraw_all_token_types(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
-                )
+                Err(SwapError::InvalidSupply.into()),
+                accounts.initialize_swap()
             );
 
-            accounts.token_b_key = old_b_key;
-            accounts.token_b_account = old_b_account;
+            accounts.pool_mint_account = old_mint;
+            accounts.pool_token_account = old_pool_account;
         }
 
-        // wrong mint
+        // pool fee account has wrong mint
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
+            let (_pool_fee_key, pool_fee_account) = mint_token(
+                &token_a_program_id,
+                &accounts.token_a_mint_key,
+                &mut accounts.token_a_mint_account,
                 &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                initial_pool.try_into().unwrap(),
-            );
-            let (pool_mint_key, pool_mint_account) = create_mint(
-                &pool_token_program_id,
-                &accounts.authority_key,
-                None,
-                None,
-                &TransferFee::default(),
+                &user_key,
+                0,
             );
-            let old_pool_key = accounts.pool_mint_key;
-            let old_pool_account = accounts.pool_mint_account;
-            accounts.pool_mint_key = pool_mint_key;
-            accounts.pool_mint_account = pool_mint_account;
-
+            let old_account = accounts.pool_fee_account;
+            accounts.pool_fee_account = pool_fee_account;
             assert_eq!(
                 Err(SwapError::IncorrectPoolMint.into()),
-                accounts.withdraw_all_token_types(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
-                )
+                accounts.initialize_swap()
             );
-
-            accounts.pool_mint_key = old_pool_key;
-            accounts.pool_mint_account = old_pool_account;
+            accounts.pool_fee_account = old_account;
         }
 
-        // withdrawing 1 pool token fails because it equates to 0 output tokens
+        // token A account is delegated
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                initial_pool.try_into().unwrap(),
-            );
-            assert_eq!(
-                Err(SwapError::ZeroTradingTokens.into()),
-                accounts.withdraw_all_token_types(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
+            do_process_instruction(
+                approve(
+                    &token_a_program_id,
+                    &accounts.token_a_key,
+                    &user_key,
+                    &accounts.authority_key,
+                    &[],
                     1,
-                    0,
-                    0,
                 )
+                .unwrap(),
+                vec![
+                    &mut accounts.token_a_account,
+                    &mut SolanaAccount::default(),
+                    &mut SolanaAccount::default(),
+                ],
+            )
+            .unwrap();
+            assert_eq!(
+                Err(SwapError::InvalidDelegate.into()),
+                accounts.initialize_swap()
             );
+
+            do_process_instruction(
+                revoke(
+                    &token_a_program_id,
+                    &accounts.token_a_key,
+                    &accounts.authority_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![&mut accounts.token_a_account, &mut SolanaAccount::default()],
+            )
+            .unwrap();
         }
 
-        // slippage exceeded
+        // token B account is delegated
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                initial_pool.try_into().unwrap(),
-            );
-            // minimum A amount out too high
-            assert_eq!(
-                Err(SwapError::ExceededSlippage.into()),
-                accounts.withdraw_all_token_types(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount * 10,
-                    minimum_token_b_amount,
+            do_process_instruction(
+                approve(
+                    &token_b_program_id,
+                    &accounts.token_b_key,
+                    &user_key,
+                    &accounts.authority_key,
+                    &[],
+                    1,
                 )
-            );
-            // minimum B amount out too high
+                .unwrap(),
+                vec![
+                    &mut accounts.token_b_account,
+                    &mut SolanaAccount::default(),
+                    &mut SolanaAccount::default(),
+                ],
+            )
+            .unwrap();
             assert_eq!(
-                Err(SwapError::ExceededSlippage.into()),
-                accounts.withdraw_all_token_types(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount * 10,
-                )
+                Err(SwapError::InvalidDelegate.into()),
+                accounts.initialize_swap()
             );
+
+            do_process_instruction(
+                revoke(
+                    &token_b_program_id,
+                    &accounts.token_b_key,
+                    &accounts.authority_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![&mut accounts.token_b_account, &mut SolanaAccount::default()],
+            )
+            .unwrap();
         }
 
-        // invalid input: can't use swap pool tokens as destination
+        // token A account has close authority
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                initial_pool.try_into().unwrap(),
-            );
-            let swap_token_a_key = accounts.token_a_key;
-            let mut swap_token_a_account = accounts.get_token_account(&swap_token_a_key).clone();
-            assert_eq!(
-                Err(SwapError::InvalidInput.into()),
-                accounts.withdraw_all_token_types(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &swap_token_a_key,
-                    &mut swap_token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
+            do_process_instruction(
+                set_authority(
+                    &token_a_program_id,
+                    &accounts.token_a_key,
+                    Some(&user_key),
+                    AuthorityType::CloseAccount,
+                    &accounts.authority_key,
+                    &[],
                 )
-            );
-            let swap_token_b_key = accounts.token_b_key;
-            let mut swap_token_b_account = accounts.get_token_account(&swap_token_b_key).clone();
+                .unwrap(),
+                vec![&mut accounts.token_a_account, &mut SolanaAccount::default()],
+            )
+            .unwrap();
             assert_eq!(
-                Err(SwapError::InvalidInput.into()),
-                accounts.withdraw_all_token_types(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &swap_token_b_key,
-                    &mut swap_token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
-                )
+                Err(SwapError::InvalidCloseAuthority.into()),
+                accounts.initialize_swap()
             );
+
+            do_process_instruction(
+                set_authority(
+                    &token_a_program_id,
+                    &accounts.token_a_key,
+                    None,
+                    AuthorityType::CloseAccount,
+                    &user_key,
+                    &[],
+                )
+                .unwrap(),
+                vec![&mut accounts.token_a_account, &mut SolanaAccount::default()],
+            )
+            .unwrap();
         }
 
-        // correct withdrawal
+        // token B account has close authority
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                initial_pool.try_into().unwrap(),
-            );
-
-            accounts
-                .withdraw_all_token_types(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    withdraw_amount.try_into().unwrap(),
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
+            do_process_instruction(
+                set_authority(
+                    &token_b_program_id,
+                    &accounts.token_b_key,
+                    Some(&user_key),
+                    AuthorityType::CloseAccount,
+                    &accounts.authority_key,
+                    &[],
                 )
-                .unwrap();
+                .unwrap(),
+                vec![&mut accounts.token_b_account, &mut SolanaAccount::default()],
+            )
+            .unwrap();
+            assert_eq!(
+                Err(SwapError::InvalidCloseAuthority.into()),
+                accounts.initialize_swap()
+            );
 
-            let swap_token_a =
-                StateWithExtensions::<Account>::unpack(&accounts.token_a_account.data).unwrap();
-            let swap_token_b =
-                StateWithExtensions::<Account>::unpack(&accounts.token_b_account.data).unwrap();
-            let pool_mint =
-                StateWithExtensions::<Mint>::unpack(&accounts.pool_mint_account.data).unwrap();
-            let withdraw_fee = accounts.fees.owner_withdraw_fee(withdraw_amount).unwrap();
-            let results = accounts
-                .swap_curve
-                .calculator
-                .pool_tokens_to_trading_tokens(
-                    withdraw_amount - withdraw_fee,
-                    pool_mint.base.supply.into(),
-                    swap_token_a.base.amount.into(),
-                    swap_token_b.base.amount.into(),
-                    RoundDirection::Floor,
+            do_process_instruction(
+                set_authority(
+                    &token_b_program_id,
+                    &accounts.token_b_key,
+                    None,
+                    AuthorityType::CloseAccount,
+                    &user_key,
+                    &[],
                 )
-                .unwrap();
-            assert_eq!(
-                swap_token_a.base.amount,
-                token_a_amount - to_u64(results.token_a_amount).unwrap()
-            );
+                .unwrap(),
+                vec![&mut accounts.token_b_account, &mut SolanaAccount::default()],
+            )
+            .unwrap();
+        }
+
+        // wrong token program id
+        {
+            let wrong_program_id = Pubkey::new_unique();
             assert_eq!(
-                swap_token_b.base.amount,
-                token_b_amount - to_u64(results.token_b_amount).unwrap()
+                Err(ProgramError::IncorrectProgramId),
+                do_process_instruction(
+                    initialize(
+                        &SWAP_PROGRAM_ID,
+                        &wrong_program_id,
+                        &accounts.swap_key,
+                        &accounts.authority_key,
+                        &accounts.token_a_key,
+                        &accounts.token_b_key,
+                        &accounts.pool_mint_key,
+                        &accounts.pool_fee_key,
+                        &accounts.pool_token_key,
+                        accounts.fees.clone(),
+                        accounts.swap_curve.clone(),
+                    )
+                    .unwrap(),
+                    vec![
+                        &mut accounts.swap_account,
+                        &mut SolanaAccount::default(),
+                        &mut accounts.token_a_account,
+                        &mut accounts.token_b_account,
+                        &mut accounts.pool_mint_account,
+                        &mut accounts.pool_fee_account,
+                        &mut accounts.pool_token_account,
+                        &mut SolanaAccount::default(),
+                    ],
+                )
             );
-            let token_a = StateWithExtensions::<Account>::unpack(&token_a_account.data).unwrap();
-            assert_eq!(
-                token_a.base.amount,
-                initial_a + to_u64(results.token_a_amount).unwrap()
+        }
+
+        // create swap with same token A and B
+        {
+            let (_token_a_repeat_key, token_a_repeat_account) = mint_token(
+                &token_a_program_id,
+                &accounts.token_a_mint_key,
+                &mut accounts.token_a_mint_account,
+                &user_key,
+                &accounts.authority_key,
+                10,
             );
-            let token_b = StateWithExtensions::<Account>::unpack(&token_b_account.data).unwrap();
+            let old_account = accounts.token_b_account;
+            accounts.token_b_account = token_a_repeat_account;
             assert_eq!(
-                token_b.base.amount,
-                initial_b + to_u64(results.token_b_amount).unwrap()
+                Err(SwapError::RepeatedMint.into()),
+                accounts.initialize_swap()
             );
-            let pool_account = StateWithExtensions::<Account>::unpack(&pool_account.data).unwrap();
-            assert_eq!(
-                pool_account.base.amount,
-                to_u64(initial_pool - withdraw_amount).unwrap()
+            accounts.token_b_account = old_account;
+        }
+
+        // create valid swap
+        accounts.initialize_swap().unwrap();
+
+        // create invalid flat swap
+        {
+            let token_b_price = 0;
+            let fees = Fees {
+                trade_fee_numerator,
+                trade_fee_denominator,
+                owner_trade_fee_numerator,
+                owner_trade_fee_denominator,
+                owner_withdraw_fee_numerator,
+                owner_withdraw_fee_denominator,
+                host_fee_numerator,
+                host_fee_denominator,
+            };
+            let swap_curve = SwapCurve {
+                curve_type: CurveType::ConstantPrice,
+                calculator: Arc::new(ConstantPriceCurve { token_b_price }),
+            };
+            let mut accounts = SwapAccountInfo::new(
+                &user_key,
+                fees,
+                SwapTransferFees::default(),
+                swap_curve,
+                token_a_amount,
+                token_b_amount,
+                &pool_token_program_id,
+                &token_a_program_id,
+                &token_b_program_id,
             );
-            let fee_account =
-                StateWithExtensions::<Account>::unpack(&accounts.pool_fee_account.data).unwrap();
             assert_eq!(
-                fee_account.base.amount,
-                TryInto::<u64>::try_into(withdraw_fee).unwrap()
+                Err(SwapError::InvalidCurve.into()),
+                accounts.initialize_swap()
             );
         }
 
-        // correct withdrawal from fee account
+        // create valid flat swap
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                mut _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, 0, 0, 0);
-
-            let pool_fee_key = accounts.pool_fee_key;
-            let mut pool_fee_account = accounts.pool_fee_account.clone();
-            let fee_account =
-                StateWithExtensions::<Account>::unpack(&pool_fee_account.data).unwrap();
-            let pool_fee_amount = fee_account.base.amount;
-
-            accounts
-                .withdraw_all_token_types(
-                    &user_key,
-                    &pool_fee_key,
-                    &mut pool_fee_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    pool_fee_amount,
-                    0,
-                    0,
-                )
-                .unwrap();
+            let fees = Fees {
+                trade_fee_numerator,
+                trade_fee_denominator,
+                owner_trade_fee_numerator,
+                owner_trade_fee_denominator,
+                owner_withdraw_fee_numerator,
+                owner_withdraw_fee_denominator,
+                host_fee_numerator,
+                host_fee_denominator,
+            };
+            let token_b_price = 10_000;
+            let swap_curve = SwapCurve {
+                curve_type: CurveType::ConstantPrice,
+                calculator: Arc::new(ConstantPriceCurve { token_b_price }),
+            };
+            let mut accounts = SwapAccountInfo::new(
+                &user_key,
+                fees,
+                SwapTransferFees::default(),
+                swap_curve,
+                token_a_amount,
+                token_b_amount,
+                &pool_token_program_id,
+                &token_a_program_id,
+                &token_b_program_id,
+            );
+            accounts.initialize_swap().unwrap();
+        }
 
-            let swap_token_a =
-                StateWithExtensions::<Account>::unpack(&accounts.token_a_account.data).unwrap();
-            let swap_token_b =
-                StateWithExtensions::<Account>::unpack(&accounts.token_b_account.data).unwrap();
-            let pool_mint =
-                StateWithExtensions::<Mint>::unpack(&accounts.pool_mint_account.data).unwrap();
-            let results = accounts
-                .swap_curve
-                .calculator
-                .pool_tokens_to_trading_tokens(
-                    pool_fee_amount.into(),
-                    pool_mint.base.supply.into(),
-                    swap_token_a.base.amount.into(),
-                    swap_token_b.base.amount.into(),
-                    RoundDirection::Floor,
-                )
-                .unwrap();
-            let token_a = StateWithExtensions::<Account>::unpack(&token_a_account.data).unwrap();
-            assert_eq!(
-                token_a.base.amount,
-                TryInto::<u64>::try_into(results.token_a_amount).unwrap()
+        // create invalid offset swap
+        {
+            let token_b_offset = 0;
+            let fees = Fees {
+                trade_fee_numerator,
+                trade_fee_denominator,
+                owner_trade_fee_numerator,
+                owner_trade_fee_denominator,
+                owner_withdraw_fee_numerator,
+                owner_withdraw_fee_denominator,
+                host_fee_numerator,
+                host_fee_denominator,
+            };
+            let swap_curve = SwapCurve {
+                curve_type: CurveType::Offset,
+                calculator: Arc::new(OffsetCurve { token_b_offset }),
+            };
+            let mut accounts = SwapAccountInfo::new(
+                &user_key,
+                fees,
+                SwapTransferFees::default(),
+                swap_curve,
+                token_a_amount,
+                token_b_amount,
+                &pool_token_program_id,
+                &token_a_program_id,
+                &token_b_program_id,
             );
-            let token_b = StateWithExtensions::<Account>::unpack(&token_b_account.data).unwrap();
             assert_eq!(
-                token_b.base.amount,
-                TryInto::<u64>::try_into(results.token_b_amount).unwrap()
+                Err(SwapError::InvalidCurve.into()),
+                accounts.initialize_swap()
             );
         }
-    }
 
-    #[test_case(spl_token::id(), spl_token::id(), spl_token::id(); "all-token")]
-    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token_2022::id(); "all-token-2022")]
-    #[test_case(spl_token::id(), spl_token_2022::id(), spl_token_2022::id(); "mixed-pool-token")]
-    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token::id(); "mixed-pool-token-2022")]
-    fn test_deposit_one_exact_in(
-        pool_token_program_id: Pubkey,
-        token_a_program_id: Pubkey,
-        token_b_program_id: Pubkey,
-    ) {
-        let user_key = Pubkey::new_unique();
-        let depositor_key = Pubkey::new_unique();
-        let trade_fee_numerator = 1;
-        let trade_fee_denominator = 2;
-        let owner_trade_fee_numerator = 1;
-        let owner_trade_fee_denominator = 10;
-        let owner_withdraw_fee_numerator = 1;
-        let owner_withdraw_fee_denominator = 5;
-        let host_fee_numerator = 20;
-        let host_fee_denominator = 100;
-
-        let fees = Fees {
-            trade_fee_numerator,
-            trade_fee_denominator,
-            owner_trade_fee_numerator,
-            owner_trade_fee_denominator,
-            owner_withdraw_fee_numerator,
-            owner_withdraw_fee_denominator,
-            host_fee_numerator,
-            host_fee_denominator,
-        };
-
-        let token_a_amount = 1000;
-        let token_b_amount = 9000;
-        let curve_type = CurveType::ConstantProduct;
-        let swap_curve = SwapCurve {
-            curve_type,
-            calculator: Arc::new(ConstantProductCurve {}),
-        };
-
-        let mut accounts = SwapAccountInfo::new(
-            &user_key,
-            fees,
-            SwapTransferFees::default(),
-            swap_curve,
-            token_a_amount,
-            token_b_amount,
-            &pool_token_program_id,
-            &token_a_program_id,
-            &token_b_program_id,
-        );
-
-        let deposit_a = token_a_amount / 10;
-        let deposit_b = token_b_amount / 10;
-        let pool_amount = to_u64(INITIAL_SWAP_POOL_AMOUNT / 100).unwrap();
-
-        // swap not initialized
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                _token_b_key,
-                _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            assert_eq!(
-                Err(ProgramError::UninitializedAccount),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &pool_key,
-                    &mut pool_account,
-                    deposit_a,
-                    pool_amount,
-                )
-            );
-        }
-
-        accounts.initialize_swap().unwrap();
-
-        // wrong owner for swap account
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                _token_b_key,
-                _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            let old_swap_account = accounts.swap_account;
-            let mut wrong_swap_account = old_swap_account.clone();
-            wrong_swap_account.owner = pool_token_program_id;
-            accounts.swap_account = wrong_swap_account;
-            assert_eq!(
-                Err(ProgramError::IncorrectProgramId),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &pool_key,
-                    &mut pool_account,
-                    deposit_a,
-                    pool_amount,
-                )
-            );
-            accounts.swap_account = old_swap_account;
-        }
-
-        // wrong bump seed for authority_key
+        // create valid offset swap
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                _token_b_key,
-                _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            let old_authority = accounts.authority_key;
-            let (bad_authority_key, _bump_seed) = Pubkey::find_program_address(
-                &[&accounts.swap_key.to_bytes()[..]],
+            let token_b_offset = 10;
+            let fees = Fees {
+                trade_fee_numerator,
+                trade_fee_denominator,
+                owner_trade_fee_numerator,
+                owner_trade_fee_denominator,
+                owner_withdraw_fee_numerator,
+                owner_withdraw_fee_denominator,
+                host_fee_numerator,
+                host_fee_denominator,
+            };
+            let swap_curve = SwapCurve {
+                curve_type: CurveType::Offset,
+                calculator: Arc::new(OffsetCurve { token_b_offset }),
+            };
+            let mut accounts = SwapAccountInfo::new(
+                &user_key,
+                fees,
+                SwapTransferFees::default(),
+                swap_curve,
+                token_a_amount,
+                token_b_amount,
                 &pool_token_program_id,
+                &token_a_program_id,
+                &token_b_program_id,
             );
-            accounts.authority_key = bad_authority_key;
-            assert_eq!(
-                Err(SwapError::InvalidProgramAddress.into()),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &pool_key,
-                    &mut pool_account,
-                    deposit_a,
-                    pool_amount,
-                )
-            );
-            accounts.authority_key = old_authority;
+            accounts.initialize_swap().unwrap();
         }
 
-        // not enough token A / B
+        // wrong owner key in constraint
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
+            let new_key = Pubkey::new_unique();
+            let trade_fee_numerator = 25;
+            let trade_fee_denominator = 10000;
+            let owner_trade_fee_numerator = 5;
+            let owner_trade_fee_denominator = 10000;
+            let host_fee_numerator = 20;
+            let host_fee_denominator = 100;
+            let fees = Fees {
+                trade_fee_numerator,
+                trade_fee_denominator,
+                owner_trade_fee_numerator,
+                owner_trade_fee_denominator,
+                owner_withdraw_fee_numerator,
+                owner_withdraw_fee_denominator,
+                host_fee_numerator,
+                host_fee_denominator,
+            };
+            let curve = ConstantProductCurve {};
+            let swap_curve = SwapCurve {
+                curve_type: CurveType::ConstantProduct,
+                calculator: Arc::new(curve),
+            };
+            let owner_key = &new_key.to_string();
+            let valid_curve_types = &[CurveType::ConstantProduct];
+            let constraints = Some(SwapConstraints {
+                owner_key,
+                valid_curve_types,
+                fees: &fees,
+            });
+            let mut accounts = SwapAccountInfo::new(
                 &user_key,
-                &depositor_key,
-                deposit_a / 2,
-                deposit_b / 2,
-                0,
-            );
-            assert_eq!(
-                Err(TokenError::InsufficientFunds.into()),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &pool_key,
-                    &mut pool_account,
-                    deposit_a,
-                    0,
-                )
+                fees.clone(),
+                SwapTransferFees::default(),
+                swap_curve,
+                token_a_amount,
+                token_b_amount,
+                &pool_token_program_id,
+                &token_a_program_id,
+                &token_b_program_id,
             );
             assert_eq!(
-                Err(TokenError::InsufficientFunds.into()),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &depositor_key,
-                    &token_b_key,
-                    &mut token_b_account,
-                    &pool_key,
-                    &mut pool_account,
-                    deposit_b,
-                    0,
+                Err(SwapError::InvalidOwner.into()),
+                do_process_instruction_with_fee_constraints(
+                    initialize(
+                        &SWAP_PROGRAM_ID,
+                        &pool_token_program_id,
+                        &accounts.swap_key,
+                        &accounts.authority_key,
+                        &accounts.token_a_key,
+                        &accounts.token_b_key,
+                        &accounts.pool_mint_key,
+                        &accounts.pool_fee_key,
+                        &accounts.pool_token_key,
+                        accounts.fees.clone(),
+                        accounts.swap_curve.clone(),
+                    )
+                    .unwrap(),
+                    vec![
+                        &mut accounts.swap_account,
+                        &mut SolanaAccount::default(),
+                        &mut accounts.token_a_account,
+                        &mut accounts.token_b_account,
+                        &mut accounts.pool_mint_account,
+                        &mut accounts.pool_fee_account,
+                        &mut accounts.pool_token_account,
+                        &mut SolanaAccount::default(),
+                    ],
+                    &constraints,
                 )
             );
         }
 
-        // wrong pool token account
+        // wrong fee in constraint
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            let expected_error: ProgramError = if token_b_account.owner == pool_account.owner {
-                TokenError::MintMismatch.into()
-            } else {
-                SwapError::IncorrectTokenProgramId.into()
+            let trade_fee_numerator = 25;
+            let trade_fee_denominator = 10000;
+            let owner_trade_fee_numerator = 5;
+            let owner_trade_fee_denominator = 10000;
+            let host_fee_numerator = 20;
+            let host_fee_denominator = 100;
+            let fees = Fees {
+                trade_fee_numerator,
+                trade_fee_denominator,
+                owner_trade_fee_numerator,
+                owner_trade_fee_denominator,
+                owner_withdraw_fee_numerator,
+                owner_withdraw_fee_denominator,
+                host_fee_numerator,
+                host_fee_denominator,
             };
-            assert_eq!(
-                Err(expected_error),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    deposit_a,
-                    pool_amount,
-                )
+            let curve = ConstantProductCurve {};
+            let swap_curve = SwapCurve {
+                curve_type: CurveType::ConstantProduct,
+                calculator: Arc::new(curve),
+            };
+            let owner_key = &user_key.to_string();
+            let valid_curve_types = &[CurveType::ConstantProduct];
+            let constraints = Some(SwapConstraints {
+                owner_key,
+                valid_curve_types,
+                fees: &fees,
+            });
+            let mut bad_fees = fees.clone();
+            bad_fees.trade_fee_numerator = trade_fee_numerator - 1;
+            let mut accounts = SwapAccountInfo::new(
+                &user_key,
+                bad_fees,
+                SwapTransferFees::default(),
+                swap_curve,
+                token_a_amount,
+                token_b_amount,
+                &pool_token_program_id,
+                &token_a_program_id,
+                &token_b_program_id,
             );
-        }
-
-        // no approval
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                _token_b_key,
-                _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            let user_transfer_authority_key = Pubkey::new_unique();
             assert_eq!(
-                Err(TokenError::OwnerMismatch.into()),
-                do_process_instruction(
-                    deposit_single_token_type_exact_amount_in(
+                Err(SwapError::InvalidFee.into()),
+                do_process_instruction_with_fee_constraints(
+                    initialize(
                         &SWAP_PROGRAM_ID,
-                        &token_a_program_id,
                         &pool_token_program_id,
                         &accounts.swap_key,
                         &accounts.authority_key,
-                        &user_transfer_authority_key,
-                        &token_a_key,
                         &accounts.token_a_key,
                         &accounts.token_b_key,
                         &accounts.pool_mint_key,
-                        &pool_key,
-                        &accounts.token_a_mint_key,
-                        DepositSingleTokenTypeExactAmountIn {
-                            source_token_amount: deposit_a,
-                            minimum_pool_token_amount: pool_amount,
-                        },
+                        &accounts.pool_fee_key,
+                        &accounts.pool_token_key,
+                        accounts.fees.clone(),
+                        accounts.swap_curve.clone(),
                     )
                     .unwrap(),
                     vec![
                         &mut accounts.swap_account,
                         &mut SolanaAccount::default(),
-                        &mut SolanaAccount::default(),
-                        &mut token_a_account,
                         &mut accounts.token_a_account,
                         &mut accounts.token_b_account,
                         &mut accounts.pool_mint_account,
-                        &mut pool_account,
-                        &mut accounts.token_a_mint_account,
-                        &mut SolanaAccount::default(),
+                        &mut accounts.pool_fee_account,
+                        &mut accounts.pool_token_account,
                         &mut SolanaAccount::default(),
                     ],
+                    &constraints,
                 )
             );
         }
 
-        // wrong token program id
+        // create valid swap with constraints
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                _token_b_key,
-                _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            let wrong_key = Pubkey::new_unique();
-            assert_eq!(
-                Err(SwapError::IncorrectTokenProgramId.into()),
-                do_process_instruction(
-                    deposit_single_token_type_exact_amount_in(
-                        &SWAP_PROGRAM_ID,
-                        &wrong_key,
-                        &wrong_key,
-                        &accounts.swap_key,
-                        &accounts.authority_key,
-                        &accounts.authority_key,
-                        &token_a_key,
-                        &accounts.token_a_key,
-                        &accounts.token_b_key,
-                        &accounts.pool_mint_key,
-                        &pool_key,
-                        &accounts.token_a_mint_key,
-                        DepositSingleTokenTypeExactAmountIn {
-                            source_token_amount: deposit_a,
-                            minimum_pool_token_amount: pool_amount,
-                        },
-                    )
-                    .unwrap(),
-                    vec![
-                        &mut accounts.swap_account,
-                        &mut SolanaAccount::default(),
-                        &mut SolanaAccount::default(),
-                        &mut token_a_account,
-                        &mut accounts.token_a_account,
-                        &mut accounts.token_b_account,
-                        &mut accounts.pool_mint_account,
-                        &mut pool_account,
-                        &mut accounts.token_a_mint_account,
-                        &mut SolanaAccount::default(),
-                        &mut SolanaAccount::default(),
-                    ],
-                )
+            let trade_fee_numerator = 25;
+            let trade_fee_denominator = 10000;
+            let owner_trade_fee_numerator = 5;
+            let owner_trade_fee_denominator = 10000;
+            let host_fee_numerator = 20;
+            let host_fee_denominator = 100;
+            let fees = Fees {
+                trade_fee_numerator,
+                trade_fee_denominator,
+                owner_trade_fee_numerator,
+                owner_trade_fee_denominator,
+                owner_withdraw_fee_numerator,
+                owner_withdraw_fee_denominator,
+                host_fee_numerator,
+                host_fee_denominator,
+            };
+            let curve = ConstantProductCurve {};
+            let swap_curve = SwapCurve {
+                curve_type: CurveType::ConstantProduct,
+                calculator: Arc::new(curve),
+            };
+            let owner_key = &user_key.to_string();
+            let valid_curve_types = &[CurveType::ConstantProduct];
+            let constraints = Some(SwapConstraints {
+                owner_key,
+                valid_curve_types,
+                fees: &fees,
+            });
+            let mut accounts = SwapAccountInfo::new(
+                &user_key,
+                fees.clone(),
+                SwapTransferFees::default(),
+                swap_curve,
+                token_a_amount,
+                token_b_amount,
+                &pool_token_program_id,
+                &token_a_program_id,
+                &token_b_program_id,
             );
+            do_process_instruction_with_fee_constraints(
+                initialize(
+                    &SWAP_PROGRAM_ID,
+                    &pool_token_program_id,
+                    &accounts.swap_key,
+                    &accounts.authority_key,
+                    &accounts.token_a_key,
+                    &accounts.token_b_key,
+                    &accounts.pool_mint_key,
+                    &accounts.pool_fee_key,
+                    &accounts.pool_token_key,
+                    accounts.fees,
+                    accounts.swap_curve.clone(),
+                )
+                .unwrap(),
+                vec![
+                    &mut accounts.swap_account,
+                    &mut SolanaAccount::default(),
+                    &mut accounts.token_a_account,
+                    &mut accounts.token_b_account,
+                    &mut accounts.pool_mint_account,
+                    &mut accounts.pool_fee_account,
+                    &mut accounts.pool_token_account,
+                    &mut SolanaAccount::default(),
+                ],
+                &constraints,
+            )
+            .unwrap();
         }
 
-        // wrong swap token accounts
+        // create again
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-
-            let old_a_key = accounts.token_a_key;
-            let old_a_account = accounts.token_a_account;
-
-            accounts.token_a_key = token_a_key;
-            accounts.token_a_account = token_a_account.clone();
-
-            // wrong swap token a account
             assert_eq!(
-                Err(SwapError::IncorrectSwapAccount.into()),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &pool_key,
-                    &mut pool_account,
-                    deposit_a,
-                    pool_amount,
-                )
+                Err(SwapError::AlreadyInUse.into()),
+                accounts.initialize_swap()
             );
-
-            accounts.token_a_key = old_a_key;
-            accounts.token_a_account = old_a_account;
-
-            let old_b_key = accounts.token_b_key;
-            let old_b_account = accounts.token_b_account;
-
-            accounts.token_b_key = token_b_key;
-            accounts.token_b_account = token_b_account;
-
-            // wrong swap token b account
-            assert_eq!(
-                Err(SwapError::IncorrectSwapAccount.into()),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &depositor_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &pool_key,
-                    &mut pool_account,
-                    deposit_a,
-                    pool_amount,
-                )
-            );
-
-            accounts.token_b_key = old_b_key;
-            accounts.token_b_account = old_b_account;
         }
+        let swap_state = SwapVersion::unpack(&accounts.swap_account.data).unwrap();
+        assert!(swap_state.is_initialized());
+        assert_eq!(swap_state.bump_seed(), accounts.bump_seed);
+        assert_eq!(
+            swap_state.swap_curve().curve_type,
+            accounts.swap_curve.curve_type
+        );
+        assert_eq!(*swap_state.token_a_account(), accounts.token_a_key);
+        assert_eq!(*swap_state.token_b_account(), accounts.token_b_key);
+        assert_eq!(*swap_state.pool_mint(), accounts.pool_mint_key);
+        assert_eq!(*swap_state.token_a_mint(), accounts.token_a_mint_key);
+        assert_eq!(*swap_state.token_b_mint(), accounts.token_b_mint_key);
+        assert_eq!(*swap_state.pool_fee_account(), accounts.pool_fee_key);
+        let token_a =
+            StateWithExtensions::<Account>::unpack(&accounts.token_a_account.data).unwrap();
+        assert_eq!(token_a.base.amount, token_a_amount);
+        let token_b =
+            StateWithExtensions::<Account>::unpack(&accounts.token_b_account.data).unwrap();
+        assert_eq!(token_b.base.amount, token_b_amount);
+        let pool_account =
+            StateWithExtensions::<Account>::unpack(&accounts.pool_token_account.data).unwrap();
+        let pool_mint =
+            StateWithExtensions::<Mint>::unpack(&accounts.pool_mint_account.data).unwrap();
+        assert_eq!(pool_mint.base.supply, pool_account.base.amount);
+    }
 
-        // wrong mint
+    #[test_case(spl_token::id(), spl_token::id(), spl_token::id(); "all-token")]
+    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token_2022::id(); "all-token-2022")]
+    #[test_case(spl_token::id(), spl_token_2022::id(), spl_token_2022::id(); "mixed-pool-token")]
+    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token::id(); "mixed-pool-token-2022")]
+    fn test_deposit(
+        pool_token_program_id: Pubkey,
+        token_a_program_id: Pubkey,
+        token_b_program_id: Pubkey,
+    ) {
+        let user_key = Pubkey::new_unique();
+        let depositor_key = Pubkey::new_unique();
+        let trade_fee_numerator = 1;
+        let trade_fee_denominator = 2;
+        let owner_trade_fee_numerator = 1;
+        let owner_trade_fee_denominator = 10;
+        let owner_withdraw_fee_numerator = 1;
+        let owner_withdraw_fee_denominator = 5;
+        let host_fee_numerator = 20;
+        let host_fee_denominator = 100;
+
+        let fees = Fees {
+            trade_fee_numerator,
+            trade_fee_denominator,
+            owner_trade_fee_numerator,
+            owner_trade_fee_denominator,
+            owner_withdraw_fee_numerator,
+            owner_withdraw_fee_denominator,
+            host_fee_numerator,
+            host_fee_denominator,
+        };
+
+        let token_a_amount = 1000;
+        let token_b_amount = 9000;
+        let curve_type = CurveType::ConstantProduct;
+        let swap_curve = SwapCurve {
+            curve_type,
+            calculator: Arc::new(ConstantProductCurve {}),
+        };
+
+        let mut accounts = SwapAccountInfo::new(
+            &user_key,
+            fees,
+            SwapTransferFees::default(),
+            swap_curve,
+            token_a_amount,
+            token_b_amount,
+            &pool_token_program_id,
+            &token_a_program_id,
+            &token_b_program_id,
+        );
+
+        // depositing 10% of the current pool amount in token A and B means
+        // that our pool tokens will be worth 1 / 10 of the current pool amount
+        let pool_amount = INITIAL_SWAP_POOL_AMOUNT / 10;
+        let deposit_a = token_a_amount / 10;
+        let deposit_b = token_b_amount / 10;
+
+        // swap not initialized
         {
             let (
                 token_a_key,
                 mut token_a_account,
-                _token_b_key,
-                _token_b_account,
+                token_b_key,
+                mut token_b_account,
                 pool_key,
                 mut pool_account,
             ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            let (pool_mint_key, pool_mint_account) = create_mint(
-                &pool_token_program_id,
-                &accounts.authority_key,
-                None,
-                None,
-                &TransferFee::default(),
-            );
-            let old_pool_key = accounts.pool_mint_key;
-            let old_pool_account = accounts.pool_mint_account;
-            accounts.pool_mint_key = pool_mint_key;
-            accounts.pool_mint_account = pool_mint_account;
-
             assert_eq!(
-                Err(SwapError::IncorrectPoolMint.into()),
-                accounts.deposit_single_token_type_exact_amount_in(
+                Err(ProgramError::UninitializedAccount),
+                accounts.deposit_all_token_types(
                     &depositor_key,
                     &token_a_key,
                     &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
                     &pool_key,
                     &mut pool_account,
+                    pool_amount.try_into().unwrap(),
                     deposit_a,
-                    pool_amount,
+                    deposit_b,
                 )
             );
-
-            accounts.pool_mint_key = old_pool_key;
-            accounts.pool_mint_account = old_pool_account;
         }
 
-        // slippage exceeded
+        accounts.initialize_swap().unwrap();
+
+        // wrong owner for swap account
         {
             let (
                 token_a_key,
@@ -5272,76 +4863,63 @@ mod tests {
                 pool_key,
                 mut pool_account,
             ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            // minimum pool amount too high
+            let old_swap_account = accounts.swap_account;
+            let mut wrong_swap_account = old_swap_account.clone();
+            wrong_swap_account.owner = pool_token_program_id;
+            accounts.swap_account = wrong_swap_account;
             assert_eq!(
-                Err(SwapError::ExceededSlippage.into()),
-                accounts.deposit_single_token_type_exact_amount_in(
+                Err(ProgramError::IncorrectProgramId),
+                accounts.deposit_all_token_types(
                     &depositor_key,
                     &token_a_key,
                     &mut token_a_account,
-                    &pool_key,
-                    &mut pool_account,
-                    deposit_a / 10,
-                    pool_amount,
-                )
-            );
-            // minimum pool amount too high
-            assert_eq!(
-                Err(SwapError::ExceededSlippage.into()),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &depositor_key,
                     &token_b_key,
                     &mut token_b_account,
                     &pool_key,
                     &mut pool_account,
-                    deposit_b / 10,
-                    pool_amount,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
+                    deposit_b,
                 )
             );
+            accounts.swap_account = old_swap_account;
         }
 
-        // invalid input: can't use swap pool tokens as source
+        // wrong bump seed for authority_key
         {
             let (
-                _token_a_key,
-                _token_a_account,
-                _token_b_key,
-                _token_b_account,
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
                 pool_key,
                 mut pool_account,
             ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            let swap_token_a_key = accounts.token_a_key;
-            let mut swap_token_a_account = accounts.get_token_account(&swap_token_a_key).clone();
-            let swap_token_b_key = accounts.token_b_key;
-            let mut swap_token_b_account = accounts.get_token_account(&swap_token_b_key).clone();
-            let authority_key = accounts.authority_key;
-            assert_eq!(
-                Err(SwapError::InvalidInput.into()),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &authority_key,
-                    &swap_token_a_key,
-                    &mut swap_token_a_account,
-                    &pool_key,
-                    &mut pool_account,
-                    deposit_a,
-                    pool_amount,
-                )
+            let old_authority = accounts.authority_key;
+            let (bad_authority_key, _bump_seed) = Pubkey::find_program_address(
+                &[&accounts.swap_key.to_bytes()[..]],
+                &pool_token_program_id,
             );
+            accounts.authority_key = bad_authority_key;
             assert_eq!(
-                Err(SwapError::InvalidInput.into()),
-                accounts.deposit_single_token_type_exact_amount_in(
-                    &authority_key,
-                    &swap_token_b_key,
-                    &mut swap_token_b_account,
+                Err(SwapError::InvalidProgramAddress.into()),
+                accounts.deposit_all_token_types(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
                     &pool_key,
                     &mut pool_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
                     deposit_b,
-                    pool_amount,
                 )
             );
+            accounts.authority_key = old_authority;
         }
 
-        // correctly deposit
+        // not enough token A
         {
             let (
                 token_a_key,
@@ -5350,226 +4928,91 @@ mod tests {
                 mut token_b_account,
                 pool_key,
                 mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
-            accounts
-                .deposit_single_token_type_exact_amount_in(
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &depositor_key,
+                deposit_a / 2,
+                deposit_b,
+                0,
+            );
+            assert_eq!(
+                Err(TokenError::InsufficientFunds.into()),
+                accounts.deposit_all_token_types(
                     &depositor_key,
                     &token_a_key,
                     &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
                     &pool_key,
                     &mut pool_account,
+                    pool_amount.try_into().unwrap(),
                     deposit_a,
-                    pool_amount,
+                    deposit_b,
                 )
-                .unwrap();
-
-            let swap_token_a =
-                StateWithExtensions::<Account>::unpack(&accounts.token_a_account.data).unwrap();
-            assert_eq!(swap_token_a.base.amount, deposit_a + token_a_amount);
-
-            let token_a = StateWithExtensions::<Account>::unpack(&token_a_account.data).unwrap();
-            assert_eq!(token_a.base.amount, 0);
+            );
+        }
 
-            accounts
-                .deposit_single_token_type_exact_amount_in(
+        // not enough token B
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &depositor_key,
+                deposit_a,
+                deposit_b / 2,
+                0,
+            );
+            assert_eq!(
+                Err(TokenError::InsufficientFunds.into()),
+                accounts.deposit_all_token_types(
                     &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
                     &token_b_key,
                     &mut token_b_account,
                     &pool_key,
                     &mut pool_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
                     deposit_b,
-                    pool_amount,
-                )
-                .unwrap();
-            let swap_token_b =
-                StateWithExtensions::<Account>::unpack(&accounts.token_b_account.data).unwrap();
-            assert_eq!(swap_token_b.base.amount, deposit_b + token_b_amount);
-
-            let token_b = StateWithExtensions::<Account>::unpack(&token_b_account.data).unwrap();
-            assert_eq!(token_b.base.amount, 0);
-
-            let pool_account = StateWithExtensions::<Account>::unpack(&pool_account.data).unwrap();
-            let swap_pool_account =
-                StateWithExtensions::<Account>::unpack(&accounts.pool_token_account.data).unwrap();
-            let pool_mint =
-                StateWithExtensions::<Mint>::unpack(&accounts.pool_mint_account.data).unwrap();
-            assert_eq!(
-                pool_mint.base.supply,
-                pool_account.base.amount + swap_pool_account.base.amount
-            );
-        }
-    }
-
-    #[test_case(spl_token::id(), spl_token::id(), spl_token::id(); "all-token")]
-    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token_2022::id(); "all-token-2022")]
-    #[test_case(spl_token::id(), spl_token_2022::id(), spl_token_2022::id(); "mixed-pool-token")]
-    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token::id(); "mixed-pool-token-2022")]
-    fn test_withdraw_one_exact_out(
-        pool_token_program_id: Pubkey,
-        token_a_program_id: Pubkey,
-        token_b_program_id: Pubkey,
-    ) {
-        let user_key = Pubkey::new_unique();
-        let trade_fee_numerator = 1;
-        let trade_fee_denominator = 2;
-        let owner_trade_fee_numerator = 1;
-        let owner_trade_fee_denominator = 10;
-        let owner_withdraw_fee_numerator = 1;
-        let owner_withdraw_fee_denominator = 5;
-        let host_fee_numerator = 7;
-        let host_fee_denominator = 100;
-
-        let fees = Fees {
-            trade_fee_numerator,
-            trade_fee_denominator,
-            owner_trade_fee_numerator,
-            owner_trade_fee_denominator,
-            owner_withdraw_fee_numerator,
-            owner_withdraw_fee_denominator,
-            host_fee_numerator,
-            host_fee_denominator,
-        };
-
-        let token_a_amount = 100_000;
-        let token_b_amount = 200_000;
-        let curve_type = CurveType::ConstantProduct;
-        let swap_curve = SwapCurve {
-            curve_type,
-            calculator: Arc::new(ConstantProductCurve {}),
-        };
-
-        let withdrawer_key = Pubkey::new_unique();
-        let initial_a = token_a_amount / 10;
-        let initial_b = token_b_amount / 10;
-        let initial_pool = swap_curve.calculator.new_pool_supply() / 10;
-        let maximum_pool_token_amount = to_u64(initial_pool / 4).unwrap();
-        let destination_a_amount = initial_a / 40;
-        let destination_b_amount = initial_b / 40;
-
-        let mut accounts = SwapAccountInfo::new(
-            &user_key,
-            fees,
-            SwapTransferFees::default(),
-            swap_curve,
-            token_a_amount,
-            token_b_amount,
-            &pool_token_program_id,
-            &token_a_program_id,
-            &token_b_program_id,
-        );
-
-        // swap not initialized
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                _token_b_key,
-                _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
-            assert_eq!(
-                Err(ProgramError::UninitializedAccount),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    destination_a_amount,
-                    maximum_pool_token_amount,
                 )
             );
         }
 
-        accounts.initialize_swap().unwrap();
-
-        // wrong owner for swap account
+        // wrong swap token accounts
         {
             let (
                 token_a_key,
                 mut token_a_account,
-                _token_b_key,
-                _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
-            let old_swap_account = accounts.swap_account;
-            let mut wrong_swap_account = old_swap_account.clone();
-            wrong_swap_account.owner = pool_token_program_id;
-            accounts.swap_account = wrong_swap_account;
-            assert_eq!(
-                Err(ProgramError::IncorrectProgramId),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    destination_a_amount,
-                    maximum_pool_token_amount,
-                )
-            );
-            accounts.swap_account = old_swap_account;
-        }
-
-        // wrong bump seed for authority_key
-        {
-            let (
-                _token_a_key,
-                _token_a_account,
                 token_b_key,
                 mut token_b_account,
                 pool_key,
                 mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
-            let old_authority = accounts.authority_key;
-            let (bad_authority_key, _bump_seed) = Pubkey::find_program_address(
-                &[&accounts.swap_key.to_bytes()[..]],
-                &pool_token_program_id,
-            );
-            accounts.authority_key = bad_authority_key;
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            let expected_error: ProgramError = if token_a_account.owner == token_b_account.owner {
+                TokenError::MintMismatch.into()
+            } else {
+                ProgramError::InvalidAccountData
+            };
             assert_eq!(
-                Err(SwapError::InvalidProgramAddress.into()),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
+                Err(expected_error),
+                accounts.deposit_all_token_types(
+                    &depositor_key,
                     &token_b_key,
                     &mut token_b_account,
-                    destination_b_amount,
-                    maximum_pool_token_amount,
-                )
-            );
-            accounts.authority_key = old_authority;
-        }
-
-        // not enough pool tokens
-        {
-            let (
-                _token_a_key,
-                _token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                maximum_pool_token_amount / 1000,
-            );
-            assert_eq!(
-                Err(TokenError::InsufficientFunds.into()),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
+                    &token_a_key,
+                    &mut token_a_account,
                     &pool_key,
                     &mut pool_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    destination_b_amount,
-                    maximum_pool_token_amount,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
+                    deposit_b,
                 )
             );
         }
@@ -5582,14 +5025,16 @@ mod tests {
                 token_b_key,
                 mut token_b_account,
                 _pool_key,
+                mut _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            let (
+                wrong_token_key,
+                mut wrong_token_account,
+                _token_b_key,
+                mut _token_b_account,
+                _pool_key,
                 pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                maximum_pool_token_amount,
-                initial_b,
-                maximum_pool_token_amount,
-            );
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
             let expected_error: ProgramError = if token_a_account.owner == pool_account.owner {
                 TokenError::MintMismatch.into()
             } else {
@@ -5597,119 +5042,71 @@ mod tests {
             };
             assert_eq!(
                 Err(expected_error),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
+                accounts.deposit_all_token_types(
+                    &depositor_key,
                     &token_a_key,
                     &mut token_a_account,
                     &token_b_key,
                     &mut token_b_account,
-                    destination_b_amount,
-                    maximum_pool_token_amount,
+                    &wrong_token_key,
+                    &mut wrong_token_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
+                    deposit_b,
                 )
             );
         }
 
-        // wrong pool fee account
+        // no approval
         {
             let (
                 token_a_key,
                 mut token_a_account,
-                _token_b_key,
-                _token_b_account,
-                wrong_pool_key,
-                wrong_pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                maximum_pool_token_amount,
-            );
-            let (
-                _token_a_key,
-                _token_a_account,
-                _token_b_key,
-                _token_b_account,
+                token_b_key,
+                mut token_b_account,
                 pool_key,
                 mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                maximum_pool_token_amount,
-            );
-            let old_pool_fee_account = accounts.pool_fee_account;
-            let old_pool_fee_key = accounts.pool_fee_key;
-            accounts.pool_fee_account = wrong_pool_account;
-            accounts.pool_fee_key = wrong_pool_key;
-            assert_eq!(
-                Err(SwapError::IncorrectFeeAccount.into()),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &token_a_key,
-                    &mut token_a_account,
-                    destination_a_amount,
-                    maximum_pool_token_amount,
-                )
-            );
-            accounts.pool_fee_account = old_pool_fee_account;
-            accounts.pool_fee_key = old_pool_fee_key;
-        }
-
-        // no approval
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                _token_b_key,
-                _token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                0,
-                0,
-                maximum_pool_token_amount,
-            );
-            let user_transfer_authority_key = Pubkey::new_unique();
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            let user_transfer_authority_key = Pubkey::new_unique();
             assert_eq!(
                 Err(TokenError::OwnerMismatch.into()),
                 do_process_instruction(
-                    withdraw_single_token_type_exact_amount_out(
+                    deposit_all_token_types(
                         &SWAP_PROGRAM_ID,
-                        &pool_token_program_id,
                         &token_a_program_id,
+                        &token_b_program_id,
+                        &pool_token_program_id,
                         &accounts.swap_key,
                         &accounts.authority_key,
                         &user_transfer_authority_key,
-                        &accounts.pool_mint_key,
-                        &accounts.pool_fee_key,
-                        &pool_key,
+                        &token_a_key,
+                        &token_b_key,
                         &accounts.token_a_key,
                         &accounts.token_b_key,
-                        &token_a_key,
+                        &accounts.pool_mint_key,
+                        &pool_key,
                         &accounts.token_a_mint_key,
-                        WithdrawSingleTokenTypeExactAmountOut {
-                            destination_token_amount: destination_a_amount,
-                            maximum_pool_token_amount,
-                        }
+                        &accounts.token_b_mint_key,
+                        DepositAllTokenTypes {
+                            pool_token_amount: pool_amount.try_into().unwrap(),
+                            maximum_token_a_amount: deposit_a,
+                            maximum_token_b_amount: deposit_b,
+                        },
                     )
                     .unwrap(),
                     vec![
                         &mut accounts.swap_account,
                         &mut SolanaAccount::default(),
                         &mut SolanaAccount::default(),
-                        &mut accounts.pool_mint_account,
-                        &mut pool_account,
+                        &mut token_a_account,
+                        &mut token_b_account,
                         &mut accounts.token_a_account,
                         &mut accounts.token_b_account,
-                        &mut token_a_account,
-                        &mut accounts.pool_fee_account,
+                        &mut accounts.pool_mint_account,
+                        &mut pool_account,
                         &mut accounts.token_a_mint_account,
+                        &mut accounts.token_b_mint_account,
+                        &mut SolanaAccount::default(),
                         &mut SolanaAccount::default(),
                         &mut SolanaAccount::default(),
                     ],
@@ -5722,52 +5119,51 @@ mod tests {
             let (
                 token_a_key,
                 mut token_a_account,
-                _token_b_key,
-                _token_b_account,
+                token_b_key,
+                mut token_b_account,
                 pool_key,
                 mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                maximum_pool_token_amount,
-            );
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
             let wrong_key = Pubkey::new_unique();
             assert_eq!(
                 Err(SwapError::IncorrectTokenProgramId.into()),
                 do_process_instruction(
-                    withdraw_single_token_type_exact_amount_out(
+                    deposit_all_token_types(
                         &SWAP_PROGRAM_ID,
                         &wrong_key,
                         &wrong_key,
+                        &wrong_key,
                         &accounts.swap_key,
                         &accounts.authority_key,
                         &accounts.authority_key,
-                        &accounts.pool_mint_key,
-                        &accounts.pool_fee_key,
-                        &pool_key,
+                        &token_a_key,
+                        &token_b_key,
                         &accounts.token_a_key,
                         &accounts.token_b_key,
-                        &token_a_key,
+                        &accounts.pool_mint_key,
+                        &pool_key,
                         &accounts.token_a_mint_key,
-                        WithdrawSingleTokenTypeExactAmountOut {
-                            destination_token_amount: destination_a_amount,
-                            maximum_pool_token_amount,
-                        }
+                        &accounts.token_b_mint_key,
+                        DepositAllTokenTypes {
+                            pool_token_amount: pool_amount.try_into().unwrap(),
+                            maximum_token_a_amount: deposit_a,
+                            maximum_token_b_amount: deposit_b,
+                        },
                     )
                     .unwrap(),
                     vec![
                         &mut accounts.swap_account,
                         &mut SolanaAccount::default(),
                         &mut SolanaAccount::default(),
-                        &mut accounts.pool_mint_account,
-                        &mut pool_account,
+                        &mut token_a_account,
+                        &mut token_b_account,
                         &mut accounts.token_a_account,
                         &mut accounts.token_b_account,
-                        &mut token_a_account,
-                        &mut accounts.pool_fee_account,
+                        &mut accounts.pool_mint_account,
+                        &mut pool_account,
                         &mut accounts.token_a_mint_account,
+                        &mut accounts.token_b_mint_account,
+                        &mut SolanaAccount::default(),
                         &mut SolanaAccount::default(),
                         &mut SolanaAccount::default(),
                     ],
@@ -5784,13 +5180,7 @@ mod tests {
                 mut token_b_account,
                 pool_key,
                 mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                initial_pool.try_into().unwrap(),
-            );
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
 
             let old_a_key = accounts.token_a_key;
             let old_a_account = accounts.token_a_account;
@@ -5801,14 +5191,17 @@ mod tests {
             // wrong swap token a account
             assert_eq!(
                 Err(SwapError::IncorrectSwapAccount.into()),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
+                accounts.deposit_all_token_types(
+                    &depositor_key,
                     &token_a_key,
                     &mut token_a_account,
-                    destination_a_amount,
-                    maximum_pool_token_amount,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
+                    deposit_b,
                 )
             );
 
@@ -5824,14 +5217,17 @@ mod tests {
             // wrong swap token b account
             assert_eq!(
                 Err(SwapError::IncorrectSwapAccount.into()),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
+                accounts.deposit_all_token_types(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
                     &token_b_key,
                     &mut token_b_account,
-                    destination_b_amount,
-                    maximum_pool_token_amount,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
+                    deposit_b,
                 )
             );
 
@@ -5844,17 +5240,11 @@ mod tests {
             let (
                 token_a_key,
                 mut token_a_account,
-                _token_b_key,
-                _token_b_account,
+                token_b_key,
+                mut token_b_account,
                 pool_key,
                 mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                initial_pool.try_into().unwrap(),
-            );
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
             let (pool_mint_key, pool_mint_account) = create_mint(
                 &pool_token_program_id,
                 &accounts.authority_key,
@@ -5869,14 +5259,17 @@ mod tests {
 
             assert_eq!(
                 Err(SwapError::IncorrectPoolMint.into()),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
+                accounts.deposit_all_token_types(
+                    &depositor_key,
                     &token_a_key,
                     &mut token_a_account,
-                    destination_a_amount,
-                    maximum_pool_token_amount,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
+                    deposit_b,
                 )
             );
 
@@ -5884,7 +5277,7 @@ mod tests {
             accounts.pool_mint_account = old_pool_account;
         }
 
-        // slippage exceeded
+        // deposit 1 pool token fails because it equates to 0 swap tokens
         {
             let (
                 token_a_key,
@@ -5893,42 +5286,69 @@ mod tests {
                 mut token_b_account,
                 pool_key,
                 mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                maximum_pool_token_amount,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            assert_eq!(
+                Err(SwapError::ZeroTradingTokens.into()),
+                accounts.deposit_all_token_types(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    1,
+                    deposit_a,
+                    deposit_b,
+                )
             );
+        }
 
-            // maximum pool token amount too low
+        // slippage exceeded
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            // maximum A amount in too low
             assert_eq!(
                 Err(SwapError::ExceededSlippage.into()),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
+                accounts.deposit_all_token_types(
+                    &depositor_key,
                     &token_a_key,
                     &mut token_a_account,
-                    destination_a_amount,
-                    maximum_pool_token_amount / 1000,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a / 10,
+                    deposit_b,
                 )
             );
+            // maximum B amount in too low
             assert_eq!(
                 Err(SwapError::ExceededSlippage.into()),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
+                accounts.deposit_all_token_types(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
                     &token_b_key,
                     &mut token_b_account,
-                    destination_b_amount,
-                    maximum_pool_token_amount / 1000,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
+                    deposit_b / 10,
                 )
             );
         }
 
-        // invalid input: can't use swap pool tokens as destination
+        // invalid input: can't use swap pool tokens as source
         {
             let (
                 _token_a_key,
@@ -5937,159 +5357,2168 @@ mod tests {
                 _token_b_account,
                 pool_key,
                 mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                maximum_pool_token_amount,
-            );
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
             let swap_token_a_key = accounts.token_a_key;
             let mut swap_token_a_account = accounts.get_token_account(&swap_token_a_key).clone();
-            assert_eq!(
-                Err(SwapError::InvalidInput.into()),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
-                    &swap_token_a_key,
-                    &mut swap_token_a_account,
-                    destination_a_amount,
-                    maximum_pool_token_amount,
-                )
-            );
             let swap_token_b_key = accounts.token_b_key;
             let mut swap_token_b_account = accounts.get_token_account(&swap_token_b_key).clone();
+            let authority_key = accounts.authority_key;
             assert_eq!(
                 Err(SwapError::InvalidInput.into()),
-                accounts.withdraw_single_token_type_exact_amount_out(
-                    &withdrawer_key,
-                    &pool_key,
-                    &mut pool_account,
+                accounts.deposit_all_token_types(
+                    &authority_key,
+                    &swap_token_a_key,
+                    &mut swap_token_a_account,
                     &swap_token_b_key,
                     &mut swap_token_b_account,
-                    destination_b_amount,
-                    maximum_pool_token_amount,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
+                    deposit_b,
                 )
             );
         }
 
-        // correct withdrawal
+        // correctly deposit
         {
             let (
                 token_a_key,
                 mut token_a_account,
-                _token_b_key,
-                _token_b_account,
+                token_b_key,
+                mut token_b_account,
                 pool_key,
                 mut pool_account,
-            ) = accounts.setup_token_accounts(
-                &user_key,
-                &withdrawer_key,
-                initial_a,
-                initial_b,
-                initial_pool.try_into().unwrap(),
-            );
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            accounts
+                .deposit_all_token_types(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_amount.try_into().unwrap(),
+                    deposit_a,
+                    deposit_b,
+                )
+                .unwrap();
 
             let swap_token_a =
                 StateWithExtensions::<Account>::unpack(&accounts.token_a_account.data).unwrap();
+            assert_eq!(swap_token_a.base.amount, deposit_a + token_a_amount);
             let swap_token_b =
                 StateWithExtensions::<Account>::unpack(&accounts.token_b_account.data).unwrap();
+            assert_eq!(swap_token_b.base.amount, deposit_b + token_b_amount);
+            let token_a = StateWithExtensions::<Account>::unpack(&token_a_account.data).unwrap();
+            assert_eq!(token_a.base.amount, 0);
+            let token_b = StateWithExtensions::<Account>::unpack(&token_b_account.data).unwrap();
+            assert_eq!(token_b.base.amount, 0);
+            let pool_account = StateWithExtensions::<Account>::unpack(&pool_account.data).unwrap();
+            let swap_pool_account =
+                StateWithExtensions::<Account>::unpack(&accounts.pool_token_account.data).unwrap();
             let pool_mint =
                 StateWithExtensions::<Mint>::unpack(&accounts.pool_mint_account.data).unwrap();
+            assert_eq!(
+                pool_mint.base.supply,
+                pool_account.base.amount + swap_pool_account.base.amount
+            );
+        }
+    }
 
-            let pool_token_amount = accounts
-                .swap_curve
-                .withdraw_single_token_type_exact_out(
-                    destination_a_amount.into(),
-                    swap_token_a.base.amount.into(),
-                    swap_token_b.base.amount.into(),
-                    pool_mint.base.supply.into(),
-                    TradeDirection::AtoB,
-                    &accounts.fees,
-                )
-                .unwrap();
-            let withdraw_fee = accounts.fees.owner_withdraw_fee(pool_token_amount).unwrap();
+    #[test_case(spl_token::id(), spl_token::id(), spl_token::id(); "all-token")]
+    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token_2022::id(); "all-token-2022")]
+    #[test_case(spl_token::id(), spl_token_2022::id(), spl_token_2022::id(); "mixed-pool-token")]
+    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token::id(); "mixed-pool-token-2022")]
+    fn test_withdraw(
+        pool_token_program_id: Pubkey,
+        token_a_program_id: Pubkey,
+        token_b_program_id: Pubkey,
+    ) {
+        let user_key = Pubkey::new_unique();
+        let trade_fee_numerator = 1;
+        let trade_fee_denominator = 2;
+        let owner_trade_fee_numerator = 1;
+        let owner_trade_fee_denominator = 10;
+        let owner_withdraw_fee_numerator = 1;
+        let owner_withdraw_fee_denominator = 5;
+        let host_fee_numerator = 7;
+        let host_fee_denominator = 100;
 
-            accounts
-                .withdraw_single_token_type_exact_amount_out(
+        let fees = Fees {
+            trade_fee_numerator,
+            trade_fee_denominator,
+            owner_trade_fee_numerator,
+            owner_trade_fee_denominator,
+            owner_withdraw_fee_numerator,
+            owner_withdraw_fee_denominator,
+            host_fee_numerator,
+            host_fee_denominator,
+        };
+
+        let token_a_amount = 1000;
+        let token_b_amount = 2000;
+        let curve_type = CurveType::ConstantProduct;
+        let swap_curve = SwapCurve {
+            curve_type,
+            calculator: Arc::new(ConstantProductCurve {}),
+        };
+
+        let withdrawer_key = Pubkey::new_unique();
+        let initial_a = token_a_amount / 10;
+        let initial_b = token_b_amount / 10;
+        let initial_pool = swap_curve.calculator.new_pool_supply() / 10;
+        let withdraw_amount = initial_pool / 4;
+        let minimum_token_a_amount = initial_a / 40;
+        let minimum_token_b_amount = initial_b / 40;
+
+        let mut accounts = SwapAccountInfo::new(
+            &user_key,
+            fees,
+            SwapTransferFees::default(),
+            swap_curve,
+            token_a_amount,
+            token_b_amount,
+            &pool_token_program_id,
+            &token_a_program_id,
+            &token_b_program_id,
+        );
+
+        // swap not initialized
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
+            assert_eq!(
+                Err(ProgramError::UninitializedAccount),
+                accounts.withdraw_all_token_types(
                     &withdrawer_key,
                     &pool_key,
                     &mut pool_account,
                     &token_a_key,
                     &mut token_a_account,
-                    destination_a_amount,
-                    maximum_pool_token_amount,
+                    &token_b_key,
+                    &mut token_b_account,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
                 )
-                .unwrap();
-
-            let swap_token_a =
-                StateWithExtensions::<Account>::unpack(&accounts.token_a_account.data).unwrap();
-
-            assert_eq!(
-                swap_token_a.base.amount,
-                token_a_amount - destination_a_amount
             );
-            let token_a = StateWithExtensions::<Account>::unpack(&token_a_account.data).unwrap();
-            assert_eq!(token_a.base.amount, initial_a + destination_a_amount);
+        }
 
-            let pool_account = StateWithExtensions::<Account>::unpack(&pool_account.data).unwrap();
+        accounts.initialize_swap().unwrap();
+
+        // wrong owner for swap account
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
+            let old_swap_account = accounts.swap_account;
+            let mut wrong_swap_account = old_swap_account.clone();
+            wrong_swap_account.owner = pool_token_program_id;
+            accounts.swap_account = wrong_swap_account;
             assert_eq!(
-                pool_account.base.amount,
-                to_u64(initial_pool - pool_token_amount - withdraw_fee).unwrap()
+                Err(ProgramError::IncorrectProgramId),
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                )
             );
-            let fee_account =
-                StateWithExtensions::<Account>::unpack(&accounts.pool_fee_account.data).unwrap();
-            assert_eq!(fee_account.base.amount, to_u64(withdraw_fee).unwrap());
+            accounts.swap_account = old_swap_account;
         }
 
-        // correct withdrawal from fee account
+        // wrong bump seed for authority_key
         {
             let (
                 token_a_key,
                 mut token_a_account,
-                _token_b_key,
-                _token_b_account,
-                _pool_key,
-                _pool_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
             ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
-
-            let fee_a_amount = 2;
-            let pool_fee_key = accounts.pool_fee_key;
-            let mut pool_fee_account = accounts.pool_fee_account.clone();
-            let fee_account =
-                StateWithExtensions::<Account>::unpack(&pool_fee_account.data).unwrap();
-            let pool_fee_amount = fee_account.base.amount;
-
-            let swap_token_a =
-                StateWithExtensions::<Account>::unpack(&accounts.token_a_account.data).unwrap();
-
-            let token_a_amount = swap_token_a.base.amount;
-            accounts
-                .withdraw_single_token_type_exact_amount_out(
-                    &user_key,
-                    &pool_fee_key,
-                    &mut pool_fee_account,
+            let old_authority = accounts.authority_key;
+            let (bad_authority_key, _bump_seed) = Pubkey::find_program_address(
+                &[&accounts.swap_key.to_bytes()[..]],
+                &pool_token_program_id,
+            );
+            accounts.authority_key = bad_authority_key;
+            assert_eq!(
+                Err(SwapError::InvalidProgramAddress.into()),
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
                     &token_a_key,
                     &mut token_a_account,
-                    fee_a_amount,
-                    pool_fee_amount,
+                    &token_b_key,
+                    &mut token_b_account,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
                 )
-                .unwrap();
-
-            let swap_token_a =
-                StateWithExtensions::<Account>::unpack(&accounts.token_a_account.data).unwrap();
-
-            assert_eq!(swap_token_a.base.amount, token_a_amount - fee_a_amount);
-            let token_a = StateWithExtensions::<Account>::unpack(&token_a_account.data).unwrap();
-            assert_eq!(token_a.base.amount, initial_a + fee_a_amount);
+            );
+            accounts.authority_key = old_authority;
         }
-    }
 
-    #[allow(clippy::too_many_arguments)]
-    fn check_valid_swap_curve(
-        fees: Fees,
-        transfer_fees: SwapTransferFees,
+        // not enough pool tokens
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                to_u64(withdraw_amount).unwrap() / 2u64,
+            );
+            assert_eq!(
+                Err(TokenError::InsufficientFunds.into()),
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount / 2,
+                    minimum_token_b_amount / 2,
+                )
+            );
+        }
+
+        // wrong token a / b accounts
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                withdraw_amount.try_into().unwrap(),
+            );
+            let expected_error: ProgramError = if token_a_account.owner == token_b_account.owner {
+                TokenError::MintMismatch.into()
+            } else {
+                ProgramError::InvalidAccountData
+            };
+            assert_eq!(
+                Err(expected_error),
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                )
+            );
+        }
+
+        // wrong pool token account
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                withdraw_amount.try_into().unwrap(),
+            );
+            let (
+                wrong_token_a_key,
+                mut wrong_token_a_account,
+                _token_b_key,
+                _token_b_account,
+                _pool_key,
+                pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                withdraw_amount.try_into().unwrap(),
+                initial_b,
+                withdraw_amount.try_into().unwrap(),
+            );
+            let expected_error: ProgramError = if token_a_account.owner == pool_account.owner {
+                TokenError::MintMismatch.into()
+            } else {
+                SwapError::IncorrectTokenProgramId.into()
+            };
+            assert_eq!(
+                Err(expected_error),
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &wrong_token_a_key,
+                    &mut wrong_token_a_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                )
+            );
+        }
+
+        // wrong pool fee account
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                wrong_pool_key,
+                wrong_pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                withdraw_amount.try_into().unwrap(),
+            );
+            let (
+                _token_a_key,
+                _token_a_account,
+                _token_b_key,
+                _token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                withdraw_amount.try_into().unwrap(),
+            );
+            let old_pool_fee_account = accounts.pool_fee_account;
+            let old_pool_fee_key = accounts.pool_fee_key;
+            accounts.pool_fee_account = wrong_pool_account;
+            accounts.pool_fee_key = wrong_pool_key;
+            assert_eq!(
+                Err(SwapError::IncorrectFeeAccount.into()),
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                ),
+            );
+            accounts.pool_fee_account = old_pool_fee_account;
+            accounts.pool_fee_key = old_pool_fee_key;
+        }
+
+        // no approval
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                0,
+                0,
+                withdraw_amount.try_into().unwrap(),
+            );
+            let user_transfer_authority_key = Pubkey::new_unique();
+            assert_eq!(
+                Err(TokenError::OwnerMismatch.into()),
+                do_process_instruction(
+                    withdraw_all_token_types(
+                        &SWAP_PROGRAM_ID,
+                        &pool_token_program_id,
+                        &token_a_program_id,
+                        &token_b_program_id,
+                        &accounts.swap_key,
+                        &accounts.authority_key,
+                        &user_transfer_authority_key,
+                        &accounts.pool_mint_key,
+                        &accounts.pool_fee_key,
+                        &pool_key,
+                        &accounts.token_a_key,
+                        &accounts.token_b_key,
+                        &token_a_key,
+                        &token_b_key,
+                        &accounts.token_a_mint_key,
+                        &accounts.token_b_mint_key,
+                        WithdrawAllTokenTypes {
+                            pool_token_amount: withdraw_amount.try_into().unwrap(),
+                            minimum_token_a_amount,
+                            minimum_token_b_amount,
+                        }
+                    )
+                    .unwrap(),
+                    vec![
+                        &mut accounts.swap_account,
+                        &mut SolanaAccount::default(),
+                        &mut SolanaAccount::default(),
+                        &mut accounts.pool_mint_account,
+                        &mut pool_account,
+                        &mut accounts.token_a_account,
+                        &mut accounts.token_b_account,
+                        &mut token_a_account,
+                        &mut token_b_account,
+                        &mut accounts.pool_fee_account,
+                        &mut accounts.token_a_mint_account,
+                        &mut accounts.token_b_mint_account,
+                        &mut SolanaAccount::default(),
+                        &mut SolanaAccount::default(),
+                        &mut SolanaAccount::default(),
+                    ],
+                )
+            );
+        }
+
+        // wrong token program id
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                withdraw_amount.try_into().unwrap(),
+            );
+            let wrong_key = Pubkey::new_unique();
+            assert_eq!(
+                Err(SwapError::IncorrectTokenProgramId.into()),
+                do_process_instruction(
+                    withdraw_all_token_types(
+                        &SWAP_PROGRAM_ID,
+                        &wrong_key,
+                        &wrong_key,
+                        &wrong_key,
+                        &accounts.swap_key,
+                        &accounts.authority_key,
+                        &accounts.authority_key,
+                        &accounts.pool_mint_key,
+                        &accounts.pool_fee_key,
+                        &pool_key,
+                        &accounts.token_a_key,
+                        &accounts.token_b_key,
+                        &token_a_key,
+                        &token_b_key,
+                        &accounts.token_a_mint_key,
+                        &accounts.token_b_mint_key,
+                        WithdrawAllTokenTypes {
+                            pool_token_amount: withdraw_amount.try_into().unwrap(),
+                            minimum_token_a_amount,
+                            minimum_token_b_amount,
+                        },
+                    )
+                    .unwrap(),
+                    vec![
+                        &mut accounts.swap_account,
+                        &mut SolanaAccount::default(),
+                        &mut SolanaAccount::default(),
+                        &mut accounts.pool_mint_account,
+                        &mut pool_account,
+                        &mut accounts.token_a_account,
+                        &mut accounts.token_b_account,
+                        &mut token_a_account,
+                        &mut token_b_account,
+                        &mut accounts.pool_fee_account,
+                        &mut accounts.token_a_mint_account,
+                        &mut accounts.token_b_mint_account,
+                        &mut SolanaAccount::default(),
+                        &mut SolanaAccount::default(),
+                        &mut SolanaAccount::default(),
+                    ],
+                )
+            );
+        }
+
+        // wrong swap token accounts
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                initial_pool.try_into().unwrap(),
+            );
+
+            let old_a_key = accounts.token_a_key;
+            let old_a_account = accounts.token_a_account;
+
+            accounts.token_a_key = token_a_key;
+            accounts.token_a_account = token_a_account.clone();
+
+            // wrong swap token a account
+            assert_eq!(
+                Err(SwapError::IncorrectSwapAccount.into()),
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                )
+            );
+
+            accounts.token_a_key = old_a_key;
+            accounts.token_a_account = old_a_account;
+
+            let old_b_key = accounts.token_b_key;
+            let old_b_account = accounts.token_b_account;
+
+            accounts.token_b_key = token_b_key;
+            accounts.token_b_account = token_b_account.clone();
+
+            // wrong swap token b account
+            assert_eq!(
+                Err(SwapError::IncorrectSwapAccount.into()),
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                )
+            );
+
+            accounts.token_b_key = old_b_key;
+            accounts.token_b_account = old_b_account;
+        }
+
+        // wrong mint
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                initial_pool.try_into().unwrap(),
+            );
+            let (pool_mint_key, pool_mint_account) = create_mint(
+                &pool_token_program_id,
+                &accounts.authority_key,
+                None,
+                None,
+                &TransferFee::default(),
+            );
+            let old_pool_key = accounts.pool_mint_key;
+            let old_pool_account = accounts.pool_mint_account;
+            accounts.pool_mint_key = pool_mint_key;
+            accounts.pool_mint_account = pool_mint_account;
+
+            assert_eq!(
+                Err(SwapError::IncorrectPoolMint.into()),
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                )
+            );
+
+            accounts.pool_mint_key = old_pool_key;
+            accounts.pool_mint_account = old_pool_account;
+        }
+
+        // withdrawing 1 pool token fails because it equates to 0 output tokens
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                initial_pool.try_into().unwrap(),
+            );
+            assert_eq!(
+                Err(SwapError::ZeroTradingTokens.into()),
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    1,
+                    0,
+                    0,
+                )
+            );
+        }
+
+        // slippage exceeded
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                initial_pool.try_into().unwrap(),
+            );
+            // minimum A amount out too high
+            assert_eq!(
+                Err(SwapError::ExceededSlippage.into()),
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount * 10,
+                    minimum_token_b_amount,
+                )
+            );
+            // minimum B amount out too high
+            assert_eq!(
+                Err(SwapError::ExceededSlippage.into()),
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount * 10,
+                )
+            );
+        }
+
+        // invalid input: can't use swap pool tokens as destination
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                initial_pool.try_into().unwrap(),
+            );
+            let swap_token_a_key = accounts.token_a_key;
+            let mut swap_token_a_account = accounts.get_token_account(&swap_token_a_key).clone();
+            assert_eq!(
+                Err(SwapError::InvalidInput.into()),
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &swap_token_a_key,
+                    &mut swap_token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                )
+            );
+            let swap_token_b_key = accounts.token_b_key;
+            let mut swap_token_b_account = accounts.get_token_account(&swap_token_b_key).clone();
+            assert_eq!(
+                Err(SwapError::InvalidInput.into()),
+                accounts.withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &swap_token_b_key,
+                    &mut swap_token_b_account,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                )
+            );
+        }
+
+        // correct withdrawal
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                initial_pool.try_into().unwrap(),
+            );
+
+            accounts
+                .withdraw_all_token_types(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    withdraw_amount.try_into().unwrap(),
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                )
+                .unwrap();
+
+            let swap_token_a =
+                StateWithExtensions::<Account>::unpack(&accounts.token_a_account.data).unwrap();
+            let swap_token_b =
+                StateWithExtensions::<Account>::unpack(&accounts.token_b_account.data).unwrap();
+            let pool_mint =
+                StateWithExtensions::<Mint>::unpack(&accounts.pool_mint_account.data).unwrap();
+            let withdraw_fee = accounts.fees.owner_withdraw_fee(withdraw_amount).unwrap();
+            let results = accounts
+                .swap_curve
+                .calculator
+                .pool_tokens_to_trading_tokens(
+                    withdraw_amount - withdraw_fee,
+                    pool_mint.base.supply.into(),
+                    swap_token_a.base.amount.into(),
+                    swap_token_b.base.amount.into(),
+                    RoundDirection::Floor,
+                )
+                .unwrap();
+            assert_eq!(
+                swap_token_a.base.amount,
+                token_a_amount - to_u64(results.token_a_amount).unwrap()
+            );
+            assert_eq!(
+                swap_token_b.base.amount,
+                token_b_amount - to_u64(results.token_b_amount).unwrap()
+            );
+            let token_a = StateWithExtensions::<Account>::unpack(&token_a_account.data).unwrap();
+            assert_eq!(
+                token_a.base.amount,
+                initial_a + to_u64(results.token_a_amount).unwrap()
+            );
+            let token_b = StateWithExtensions::<Account>::unpack(&token_b_account.data).unwrap();
+            assert_eq!(
+                token_b.base.amount,
+                initial_b + to_u64(results.token_b_amount).unwrap()
+            );
+            let pool_account = StateWithExtensions::<Account>::unpack(&pool_account.data).unwrap();
+            assert_eq!(
+                pool_account.base.amount,
+                to_u64(initial_pool - withdraw_amount).unwrap()
+            );
+            let fee_account =
+                StateWithExtensions::<Account>::unpack(&accounts.pool_fee_account.data).unwrap();
+            assert_eq!(
+                fee_account.base.amount,
+                TryInto::<u64>::try_into(withdraw_fee).unwrap()
+            );
+        }
+
+        // correct withdrawal from fee account
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                mut _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, 0, 0, 0);
+
+            let pool_fee_key = accounts.pool_fee_key;
+            let mut pool_fee_account = accounts.pool_fee_account.clone();
+            let fee_account =
+                StateWithExtensions::<Account>::unpack(&pool_fee_account.data).unwrap();
+            let pool_fee_amount = fee_account.base.amount;
+
+            accounts
+                .withdraw_all_token_types(
+                    &user_key,
+                    &pool_fee_key,
+                    &mut pool_fee_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    pool_fee_amount,
+                    0,
+                    0,
+                )
+                .unwrap();
+
+            let swap_token_a =
+                StateWithExtensions::<Account>::unpack(&accounts.token_a_account.data).unwrap();
+            let swap_token_b =
+                StateWithExtensions::<Account>::unpack(&accounts.token_b_account.data).unwrap();
+            let pool_mint =
+                StateWithExtensions::<Mint>::unpack(&accounts.pool_mint_account.data).unwrap();
+            let results = accounts
+                .swap_curve
+                .calculator
+                .pool_tokens_to_trading_tokens(
+                    pool_fee_amount.into(),
+                    pool_mint.base.supply.into(),
+                    swap_token_a.base.amount.into(),
+                    swap_token_b.base.amount.into(),
+                    RoundDirection::Floor,
+                )
+                .unwrap();
+            let token_a = StateWithExtensions::<Account>::unpack(&token_a_account.data).unwrap();
+            assert_eq!(
+                token_a.base.amount,
+                TryInto::<u64>::try_into(results.token_a_amount).unwrap()
+            );
+            let token_b = StateWithExtensions::<Account>::unpack(&token_b_account.data).unwrap();
+            assert_eq!(
+                token_b.base.amount,
+                TryInto::<u64>::try_into(results.token_b_amount).unwrap()
+            );
+        }
+    }
+
+    #[test_case(spl_token::id(), spl_token::id(), spl_token::id(); "all-token")]
+    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token_2022::id(); "all-token-2022")]
+    #[test_case(spl_token::id(), spl_token_2022::id(), spl_token_2022::id(); "mixed-pool-token")]
+    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token::id(); "mixed-pool-token-2022")]
+    fn test_deposit_one_exact_in(
+        pool_token_program_id: Pubkey,
+        token_a_program_id: Pubkey,
+        token_b_program_id: Pubkey,
+    ) {
+        let user_key = Pubkey::new_unique();
+        let depositor_key = Pubkey::new_unique();
+        let trade_fee_numerator = 1;
+        let trade_fee_denominator = 2;
+        let owner_trade_fee_numerator = 1;
+        let owner_trade_fee_denominator = 10;
+        let owner_withdraw_fee_numerator = 1;
+        let owner_withdraw_fee_denominator = 5;
+        let host_fee_numerator = 20;
+        let host_fee_denominator = 100;
+
+        let fees = Fees {
+            trade_fee_numerator,
+            trade_fee_denominator,
+            owner_trade_fee_numerator,
+            owner_trade_fee_denominator,
+            owner_withdraw_fee_numerator,
+            owner_withdraw_fee_denominator,
+            host_fee_numerator,
+            host_fee_denominator,
+        };
+
+        let token_a_amount = 1000;
+        let token_b_amount = 9000;
+        let curve_type = CurveType::ConstantProduct;
+        let swap_curve = SwapCurve {
+            curve_type,
+            calculator: Arc::new(ConstantProductCurve {}),
+        };
+
+        let mut accounts = SwapAccountInfo::new(
+            &user_key,
+            fees,
+            SwapTransferFees::default(),
+            swap_curve,
+            token_a_amount,
+            token_b_amount,
+            &pool_token_program_id,
+            &token_a_program_id,
+            &token_b_program_id,
+        );
+
+        let deposit_a = token_a_amount / 10;
+        let deposit_b = token_b_amount / 10;
+        let pool_amount = to_u64(INITIAL_SWAP_POOL_AMOUNT / 100).unwrap();
+
+        // swap not initialized
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                _token_b_key,
+                _token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            assert_eq!(
+                Err(ProgramError::UninitializedAccount),
+                accounts.deposit_single_token_type_exact_amount_in(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &pool_key,
+                    &mut pool_account,
+                    deposit_a,
+                    pool_amount,
+                )
+            );
+        }
+
+        accounts.initialize_swap().unwrap();
+
+        // wrong owner for swap account
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                _token_b_key,
+                _token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            let old_swap_account = accounts.swap_account;
+            let mut wrong_swap_account = old_swap_account.clone();
+            wrong_swap_account.owner = pool_token_program_id;
+            accounts.swap_account = wrong_swap_account;
+            assert_eq!(
+                Err(ProgramError::IncorrectProgramId),
+                accounts.deposit_single_token_type_exact_amount_in(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &pool_key,
+                    &mut pool_account,
+                    deposit_a,
+                    pool_amount,
+                )
+            );
+            accounts.swap_account = old_swap_account;
+        }
+
+        // wrong bump seed for authority_key
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                _token_b_key,
+                _token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            let old_authority = accounts.authority_key;
+            let (bad_authority_key, _bump_seed) = Pubkey::find_program_address(
+                &[&accounts.swap_key.to_bytes()[..]],
+                &pool_token_program_id,
+            );
+            accounts.authority_key = bad_authority_key;
+            assert_eq!(
+                Err(SwapError::InvalidProgramAddress.into()),
+                accounts.deposit_single_token_type_exact_amount_in(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &pool_key,
+                    &mut pool_account,
+                    deposit_a,
+                    pool_amount,
+                )
+            );
+            accounts.authority_key = old_authority;
+        }
+
+        // not enough token A / B
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &depositor_key,
+                deposit_a / 2,
+                deposit_b / 2,
+                0,
+            );
+            assert_eq!(
+                Err(TokenError::InsufficientFunds.into()),
+                accounts.deposit_single_token_type_exact_amount_in(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &pool_key,
+                    &mut pool_account,
+                    deposit_a,
+                    0,
+                )
+            );
+            assert_eq!(
+                Err(TokenError::InsufficientFunds.into()),
+                accounts.deposit_single_token_type_exact_amount_in(
+                    &depositor_key,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    deposit_b,
+                    0,
+                )
+            );
+        }
+
+        // wrong pool token account
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            let expected_error: ProgramError = if token_b_account.owner == pool_account.owner {
+                TokenError::MintMismatch.into()
+            } else {
+                SwapError::IncorrectTokenProgramId.into()
+            };
+            assert_eq!(
+                Err(expected_error),
+                accounts.deposit_single_token_type_exact_amount_in(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    deposit_a,
+                    pool_amount,
+                )
+            );
+        }
+
+        // no approval
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                _token_b_key,
+                _token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            let user_transfer_authority_key = Pubkey::new_unique();
+            assert_eq!(
+                Err(TokenError::OwnerMismatch.into()),
+                do_process_instruction(
+                    deposit_single_token_type_exact_amount_in(
+                        &SWAP_PROGRAM_ID,
+                        &token_a_program_id,
+                        &pool_token_program_id,
+                        &accounts.swap_key,
+                        &accounts.authority_key,
+                        &user_transfer_authority_key,
+                        &token_a_key,
+                        &accounts.token_a_key,
+                        &accounts.token_b_key,
+                        &accounts.pool_mint_key,
+                        &pool_key,
+                        &accounts.token_a_mint_key,
+                        DepositSingleTokenTypeExactAmountIn {
+                            source_token_amount: deposit_a,
+                            minimum_pool_token_amount: pool_amount,
+                        },
+                    )
+                    .unwrap(),
+                    vec![
+                        &mut accounts.swap_account,
+                        &mut SolanaAccount::default(),
+                        &mut SolanaAccount::default(),
+                        &mut token_a_account,
+                        &mut accounts.token_a_account,
+                        &mut accounts.token_b_account,
+                        &mut accounts.pool_mint_account,
+                        &mut pool_account,
+                        &mut accounts.token_a_mint_account,
+                        &mut SolanaAccount::default(),
+                        &mut SolanaAccount::default(),
+                    ],
+                )
+            );
+        }
+
+        // wrong token program id
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                _token_b_key,
+                _token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            let wrong_key = Pubkey::new_unique();
+            assert_eq!(
+                Err(SwapError::IncorrectTokenProgramId.into()),
+                do_process_instruction(
+                    deposit_single_token_type_exact_amount_in(
+                        &SWAP_PROGRAM_ID,
+                        &wrong_key,
+                        &wrong_key,
+                        &accounts.swap_key,
+                        &accounts.authority_key,
+                        &accounts.authority_key,
+                        &token_a_key,
+                        &accounts.token_a_key,
+                        &accounts.token_b_key,
+                        &accounts.pool_mint_key,
+                        &pool_key,
+                        &accounts.token_a_mint_key,
+                        DepositSingleTokenTypeExactAmountIn {
+                            source_token_amount: deposit_a,
+                            minimum_pool_token_amount: pool_amount,
+                        },
+                    )
+                    .unwrap(),
+                    vec![
+                        &mut accounts.swap_account,
+                        &mut SolanaAccount::default(),
+                        &mut SolanaAccount::default(),
+                        &mut token_a_account,
+                        &mut accounts.token_a_account,
+                        &mut accounts.token_b_account,
+                        &mut accounts.pool_mint_account,
+                        &mut pool_account,
+                        &mut accounts.token_a_mint_account,
+                        &mut SolanaAccount::default(),
+                        &mut SolanaAccount::default(),
+                    ],
+                )
+            );
+        }
+
+        // wrong swap token accounts
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+
+            let old_a_key = accounts.token_a_key;
+            let old_a_account = accounts.token_a_account;
+
+            accounts.token_a_key = token_a_key;
+            accounts.token_a_account = token_a_account.clone();
+
+            // wrong swap token a account
+            assert_eq!(
+                Err(SwapError::IncorrectSwapAccount.into()),
+                accounts.deposit_single_token_type_exact_amount_in(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &pool_key,
+                    &mut pool_account,
+                    deposit_a,
+                    pool_amount,
+                )
+            );
+
+            accounts.token_a_key = old_a_key;
+            accounts.token_a_account = old_a_account;
+
+            let old_b_key = accounts.token_b_key;
+            let old_b_account = accounts.token_b_account;
+
+            accounts.token_b_key = token_b_key;
+            accounts.token_b_account = token_b_account;
+
+            // wrong swap token b account
+            assert_eq!(
+                Err(SwapError::IncorrectSwapAccount.into()),
+                accounts.deposit_single_token_type_exact_amount_in(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &pool_key,
+                    &mut pool_account,
+                    deposit_a,
+                    pool_amount,
+                )
+            );
+
+            accounts.token_b_key = old_b_key;
+            accounts.token_b_account = old_b_account;
+        }
+
+        // wrong mint
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                _token_b_key,
+                _token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            let (pool_mint_key, pool_mint_account) = create_mint(
+                &pool_token_program_id,
+                &accounts.authority_key,
+                None,
+                None,
+                &TransferFee::default(),
+            );
+            let old_pool_key = accounts.pool_mint_key;
+            let old_pool_account = accounts.pool_mint_account;
+            accounts.pool_mint_key = pool_mint_key;
+            accounts.pool_mint_account = pool_mint_account;
+
+            assert_eq!(
+                Err(SwapError::IncorrectPoolMint.into()),
+                accounts.deposit_single_token_type_exact_amount_in(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &pool_key,
+                    &mut pool_account,
+                    deposit_a,
+                    pool_amount,
+                )
+            );
+
+            accounts.pool_mint_key = old_pool_key;
+            accounts.pool_mint_account = old_pool_account;
+        }
+
+        // slippage exceeded
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            // minimum pool amount too high
+            assert_eq!(
+                Err(SwapError::ExceededSlippage.into()),
+                accounts.deposit_single_token_type_exact_amount_in(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &pool_key,
+                    &mut pool_account,
+                    deposit_a / 10,
+                    pool_amount,
+                )
+            );
+            // minimum pool amount too high
+            assert_eq!(
+                Err(SwapError::ExceededSlippage.into()),
+                accounts.deposit_single_token_type_exact_amount_in(
+                    &depositor_key,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    deposit_b / 10,
+                    pool_amount,
+                )
+            );
+        }
+
+        // invalid input: can't use swap pool tokens as source
+        {
+            let (
+                _token_a_key,
+                _token_a_account,
+                _token_b_key,
+                _token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            let swap_token_a_key = accounts.token_a_key;
+            let mut swap_token_a_account = accounts.get_token_account(&swap_token_a_key).clone();
+            let swap_token_b_key = accounts.token_b_key;
+            let mut swap_token_b_account = accounts.get_token_account(&swap_token_b_key).clone();
+            let authority_key = accounts.authority_key;
+            assert_eq!(
+                Err(SwapError::InvalidInput.into()),
+                accounts.deposit_single_token_type_exact_amount_in(
+                    &authority_key,
+                    &swap_token_a_key,
+                    &mut swap_token_a_account,
+                    &pool_key,
+                    &mut pool_account,
+                    deposit_a,
+                    pool_amount,
+                )
+            );
+            assert_eq!(
+                Err(SwapError::InvalidInput.into()),
+                accounts.deposit_single_token_type_exact_amount_in(
+                    &authority_key,
+                    &swap_token_b_key,
+                    &mut swap_token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    deposit_b,
+                    pool_amount,
+                )
+            );
+        }
+
+        // correctly deposit
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &depositor_key, deposit_a, deposit_b, 0);
+            accounts
+                .deposit_single_token_type_exact_amount_in(
+                    &depositor_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &pool_key,
+                    &mut pool_account,
+                    deposit_a,
+                    pool_amount,
+                )
+                .unwrap();
+
+            let swap_token_a =
+                StateWithExtensions::<Account>::unpack(&accounts.token_a_account.data).unwrap();
+            assert_eq!(swap_token_a.base.amount, deposit_a + token_a_amount);
+
+            let token_a = StateWithExtensions::<Account>::unpack(&token_a_account.data).unwrap();
+            assert_eq!(token_a.base.amount, 0);
+
+            accounts
+                .deposit_single_token_type_exact_amount_in(
+                    &depositor_key,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    deposit_b,
+                    pool_amount,
+                )
+                .unwrap();
+            let swap_token_b =
+                StateWithExtensions::<Account>::unpack(&accounts.token_b_account.data).unwrap();
+            assert_eq!(swap_token_b.base.amount, deposit_b + token_b_amount);
+
+            let token_b = StateWithExtensions::<Account>::unpack(&token_b_account.data).unwrap();
+            assert_eq!(token_b.base.amount, 0);
+
+            let pool_account = StateWithExtensions::<Account>::unpack(&pool_account.data).unwrap();
+            let swap_pool_account =
+                StateWithExtensions::<Account>::unpack(&accounts.pool_token_account.data).unwrap();
+            let pool_mint =
+                StateWithExtensions::<Mint>::unpack(&accounts.pool_mint_account.data).unwrap();
+            assert_eq!(
+                pool_mint.base.supply,
+                pool_account.base.amount + swap_pool_account.base.amount
+            );
+        }
+    }
+
+    #[test_case(spl_token::id(), spl_token::id(), spl_token::id(); "all-token")]
+    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token_2022::id(); "all-token-2022")]
+    #[test_case(spl_token::id(), spl_token_2022::id(), spl_token_2022::id(); "mixed-pool-token")]
+    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token::id(); "mixed-pool-token-2022")]
+    fn test_withdraw_one_exact_out(
+        pool_token_program_id: Pubkey,
+        token_a_program_id: Pubkey,
+        token_b_program_id: Pubkey,
+    ) {
+        let user_key = Pubkey::new_unique();
+        let trade_fee_numerator = 1;
+        let trade_fee_denominator = 2;
+        let owner_trade_fee_numerator = 1;
+        let owner_trade_fee_denominator = 10;
+        let owner_withdraw_fee_numerator = 1;
+        let owner_withdraw_fee_denominator = 5;
+        let host_fee_numerator = 7;
+        let host_fee_denominator = 100;
+
+        let fees = Fees {
+            trade_fee_numerator,
+            trade_fee_denominator,
+            owner_trade_fee_numerator,
+            owner_trade_fee_denominator,
+            owner_withdraw_fee_numerator,
+            owner_withdraw_fee_denominator,
+            host_fee_numerator,
+            host_fee_denominator,
+        };
+
+        let token_a_amount = 100_000;
+        let token_b_amount = 200_000;
+        let curve_type = CurveType::ConstantProduct;
+        let swap_curve = SwapCurve {
+            curve_type,
+            calculator: Arc::new(ConstantProductCurve {}),
+        };
+
+        let withdrawer_key = Pubkey::new_unique();
+        let initial_a = token_a_amount / 10;
+        let initial_b = token_b_amount / 10;
+        let initial_pool = swap_curve.calculator.new_pool_supply() / 10;
+        let maximum_pool_token_amount = to_u64(initial_pool / 4).unwrap();
+        let destination_a_amount = initial_a / 40;
+        let destination_b_amount = initial_b / 40;
+
+        let mut accounts = SwapAccountInfo::new(
+            &user_key,
+            fees,
+            SwapTransferFees::default(),
+            swap_curve,
+            token_a_amount,
+            token_b_amount,
+            &pool_token_program_id,
+            &token_a_program_id,
+            &token_b_program_id,
+        );
+
+        // swap not initialized
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                _token_b_key,
+                _token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
+            assert_eq!(
+                Err(ProgramError::UninitializedAccount),
+                accounts.withdraw_single_token_type_exact_amount_out(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    destination_a_amount,
+                    maximum_pool_token_amount,
+                )
+            );
+        }
+
+        accounts.initialize_swap().unwrap();
+
+        // wrong owner for swap account
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                _token_b_key,
+                _token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
+            let old_swap_account = accounts.swap_account;
+            let mut wrong_swap_account = old_swap_account.clone();
+            wrong_swap_account.owner = pool_token_program_id;
+            accounts.swap_account = wrong_swap_account;
+            assert_eq!(
+                Err(ProgramError::IncorrectProgramId),
+                accounts.withdraw_single_token_type_exact_amount_out(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    destination_a_amount,
+                    maximum_pool_token_amount,
+                )
+            );
+            accounts.swap_account = old_swap_account;
+        }
+
+        // wrong bump seed for authority_key
+        {
+            let (
+                _token_a_key,
+                _token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
+            let old_authority = accounts.authority_key;
+            let (bad_authority_key, _bump_seed) = Pubkey::find_program_address(
+                &[&accounts.swap_key.to_bytes()[..]],
+                &pool_token_program_id,
+            );
+            accounts.authority_key = bad_authority_key;
+            assert_eq!(
+                Err(SwapError::InvalidProgramAddress.into()),
+                accounts.withdraw_single_token_type_exact_amount_out(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    destination_b_amount,
+                    maximum_pool_token_amount,
+                )
+            );
+            accounts.authority_key = old_authority;
+        }
+
+        // not enough pool tokens
+        {
+            let (
+                _token_a_key,
+                _token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                maximum_pool_token_amount / 1000,
+            );
+            assert_eq!(
+                Err(TokenError::InsufficientFunds.into()),
+                accounts.withdraw_single_token_type_exact_amount_out(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    destination_b_amount,
+                    maximum_pool_token_amount,
+                )
+            );
+        }
+
+        // wrong pool token account
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                maximum_pool_token_amount,
+                initial_b,
+                maximum_pool_token_amount,
+            );
+            let expected_error: ProgramError = if token_a_account.owner == pool_account.owner {
+                TokenError::MintMismatch.into()
+            } else {
+                SwapError::IncorrectTokenProgramId.into()
+            };
+            assert_eq!(
+                Err(expected_error),
+                accounts.withdraw_single_token_type_exact_amount_out(
+                    &withdrawer_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    destination_b_amount,
+                    maximum_pool_token_amount,
+                )
+            );
+        }
+
+        // wrong pool fee account
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                _token_b_key,
+                _token_b_account,
+                wrong_pool_key,
+                wrong_pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                maximum_pool_token_amount,
+            );
+            let (
+                _token_a_key,
+                _token_a_account,
+                _token_b_key,
+                _token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                maximum_pool_token_amount,
+            );
+            let old_pool_fee_account = accounts.pool_fee_account;
+            let old_pool_fee_key = accounts.pool_fee_key;
+            accounts.pool_fee_account = wrong_pool_account;
+            accounts.pool_fee_key = wrong_pool_key;
+            assert_eq!(
+                Err(SwapError::IncorrectFeeAccount.into()),
+                accounts.withdraw_single_token_type_exact_amount_out(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    destination_a_amount,
+                    maximum_pool_token_amount,
+                )
+            );
+            accounts.pool_fee_account = old_pool_fee_account;
+            accounts.pool_fee_key = old_pool_fee_key;
+        }
+
+        // no approval
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                _token_b_key,
+                _token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                0,
+                0,
+                maximum_pool_token_amount,
+            );
+            let user_transfer_authority_key = Pubkey::new_unique();
+            assert_eq!(
+                Err(TokenError::OwnerMismatch.into()),
+                do_process_instruction(
+                    withdraw_single_token_type_exact_amount_out(
+                        &SWAP_PROGRAM_ID,
+                        &pool_token_program_id,
+                        &token_a_program_id,
+                        &accounts.swap_key,
+                        &accounts.authority_key,
+                        &user_transfer_authority_key,
+                        &accounts.pool_mint_key,
+                        &accounts.pool_fee_key,
+                        &pool_key,
+                        &accounts.token_a_key,
+                        &accounts.token_b_key,
+                        &token_a_key,
+                        &accounts.token_a_mint_key,
+                        WithdrawSingleTokenTypeExactAmountOut {
+                            destination_token_amount: destination_a_amount,
+                            maximum_pool_token_amount,
+                        }
+                    )
+                    .unwrap(),
+                    vec![
+                        &mut accounts.swap_account,
+                        &mut SolanaAccount::default(),
+                        &mut SolanaAccount::default(),
+                        &mut accounts.pool_mint_account,
+                        &mut pool_account,
+                        &mut accounts.token_a_account,
+                        &mut accounts.token_b_account,
+                        &mut token_a_account,
+                        &mut accounts.pool_fee_account,
+                        &mut accounts.token_a_mint_account,
+                        &mut SolanaAccount::default(),
+                        &mut SolanaAccount::default(),
+                    ],
+                )
+            );
+        }
+
+        // wrong token program id
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                _token_b_key,
+                _token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                maximum_pool_token_amount,
+            );
+            let wrong_key = Pubkey::new_unique();
+            assert_eq!(
+                Err(SwapError::IncorrectTokenProgramId.into()),
+                do_process_instruction(
+                    withdraw_single_token_type_exact_amount_out(
+                        &SWAP_PROGRAM_ID,
+                        &wrong_key,
+                        &wrong_key,
+                        &accounts.swap_key,
+                        &accounts.authority_key,
+                        &accounts.authority_key,
+                        &accounts.pool_mint_key,
+                        &accounts.pool_fee_key,
+                        &pool_key,
+                        &accounts.token_a_key,
+                        &accounts.token_b_key,
+                        &token_a_key,
+                        &accounts.token_a_mint_key,
+                        WithdrawSingleTokenTypeExactAmountOut {
+                            destination_token_amount: destination_a_amount,
+                            maximum_pool_token_amount,
+                        }
+                    )
+                    .unwrap(),
+                    vec![
+                        &mut accounts.swap_account,
+                        &mut SolanaAccount::default(),
+                        &mut SolanaAccount::default(),
+                        &mut accounts.pool_mint_account,
+                        &mut pool_account,
+                        &mut accounts.token_a_account,
+                        &mut accounts.token_b_account,
+                        &mut token_a_account,
+                        &mut accounts.pool_fee_account,
+                        &mut accounts.token_a_mint_account,
+                        &mut SolanaAccount::default(),
+                        &mut SolanaAccount::default(),
+                    ],
+                )
+            );
+        }
+
+        // wrong swap token accounts
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                initial_pool.try_into().unwrap(),
+            );
+
+            let old_a_key = accounts.token_a_key;
+            let old_a_account = accounts.token_a_account;
+
+            accounts.token_a_key = token_a_key;
+            accounts.token_a_account = token_a_account.clone();
+
+            // wrong swap token a account
+            assert_eq!(
+                Err(SwapError::IncorrectSwapAccount.into()),
+                accounts.withdraw_single_token_type_exact_amount_out(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    destination_a_amount,
+                    maximum_pool_token_amount,
+                )
+            );
+
+            accounts.token_a_key = old_a_key;
+            accounts.token_a_account = old_a_account;
+
+            let old_b_key = accounts.token_b_key;
+            let old_b_account = accounts.token_b_account;
+
+            accounts.token_b_key = token_b_key;
+            accounts.token_b_account = token_b_account.clone();
+
+            // wrong swap token b account
+            assert_eq!(
+                Err(SwapError::IncorrectSwapAccount.into()),
+                accounts.withdraw_single_token_type_exact_amount_out(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    destination_b_amount,
+                    maximum_pool_token_amount,
+                )
+            );
+
+            accounts.token_b_key = old_b_key;
+            accounts.token_b_account = old_b_account;
+        }
+
+        // wrong mint
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                _token_b_key,
+                _token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                initial_pool.try_into().unwrap(),
+            );
+            let (pool_mint_key, pool_mint_account) = create_mint(
+                &pool_token_program_id,
+                &accounts.authority_key,
+                None,
+                None,
+                &TransferFee::default(),
+            );
+            let old_pool_key = accounts.pool_mint_key;
+            let old_pool_account = accounts.pool_mint_account;
+            accounts.pool_mint_key = pool_mint_key;
+            accounts.pool_mint_account = pool_mint_account;
+
+            assert_eq!(
+                Err(SwapError::IncorrectPoolMint.into()),
+                accounts.withdraw_single_token_type_exact_amount_out(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    destination_a_amount,
+                    maximum_pool_token_amount,
+                )
+            );
+
+            accounts.pool_mint_key = old_pool_key;
+            accounts.pool_mint_account = old_pool_account;
+        }
+
+        // slippage exceeded
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                maximum_pool_token_amount,
+            );
+
+            // maximum pool token amount too low
+            assert_eq!(
+                Err(SwapError::ExceededSlippage.into()),
+                accounts.withdraw_single_token_type_exact_amount_out(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    destination_a_amount,
+                    maximum_pool_token_amount / 1000,
+                )
+            );
+            assert_eq!(
+                Err(SwapError::ExceededSlippage.into()),
+                accounts.withdraw_single_token_type_exact_amount_out(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    destination_b_amount,
+                    maximum_pool_token_amount / 1000,
+                )
+            );
+        }
+
+        // invalid input: can't use swap pool tokens as destination
+        {
+            let (
+                _token_a_key,
+                _token_a_account,
+                _token_b_key,
+                _token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                maximum_pool_token_amount,
+            );
+            let swap_token_a_key = accounts.token_a_key;
+            let mut swap_token_a_account = accounts.get_token_account(&swap_token_a_key).clone();
+            assert_eq!(
+                Err(SwapError::InvalidInput.into()),
+                accounts.withdraw_single_token_type_exact_amount_out(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &swap_token_a_key,
+                    &mut swap_token_a_account,
+                    destination_a_amount,
+                    maximum_pool_token_amount,
+                )
+            );
+            let swap_token_b_key = accounts.token_b_key;
+            let mut swap_token_b_account = accounts.get_token_account(&swap_token_b_key).clone();
+            assert_eq!(
+                Err(SwapError::InvalidInput.into()),
+                accounts.withdraw_single_token_type_exact_amount_out(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &swap_token_b_key,
+                    &mut swap_token_b_account,
+                    destination_b_amount,
+                    maximum_pool_token_amount,
+                )
+            );
+        }
+
+        // correct withdrawal
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                _token_b_key,
+                _token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(
+                &user_key,
+                &withdrawer_key,
+                initial_a,
+                initial_b,
+                initial_pool.try_into().unwrap(),
+            );
+
+            let swap_token_a =
+                StateWithExtensions::<Account>::unpack(&accounts.token_a_account.data).unwrap();
+            let swap_token_b =
+                StateWithExtensions::<Account>::unpack(&accounts.token_b_account.data).unwrap();
+            let pool_mint =
+                StateWithExtensions::<Mint>::unpack(&accounts.pool_mint_account.data).unwrap();
+
+            let pool_token_amount = accounts
+                .swap_curve
+                .withdraw_single_token_type_exact_out(
+                    destination_a_amount.into(),
+                    swap_token_a.base.amount.into(),
+                    swap_token_b.base.amount.into(),
+                    pool_mint.base.supply.into(),
+                    TradeDirection::AtoB,
+                    &accounts.fees,
+                )
+                .unwrap();
+            let withdraw_fee = accounts.fees.owner_withdraw_fee(pool_token_amount).unwrap();
+
+            accounts
+                .withdraw_single_token_type_exact_amount_out(
+                    &withdrawer_key,
+                    &pool_key,
+                    &mut pool_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    destination_a_amount,
+                    maximum_pool_token_amount,
+                )
+                .unwrap();
+
+            let swap_token_a =
+                StateWithExtensions::<Account>::unpack(&accounts.token_a_account.data).unwrap();
+
+            assert_eq!(
+                swap_token_a.base.amount,
+                token_a_amount - destination_a_amount
+            );
+            let token_a = StateWithExtensions::<Account>::unpack(&token_a_account.data).unwrap();
+            assert_eq!(token_a.base.amount, initial_a + destination_a_amount);
+
+            let pool_account = StateWithExtensions::<Account>::unpack(&pool_account.data).unwrap();
+            assert_eq!(
+                pool_account.base.amount,
+                to_u64(initial_pool - pool_token_amount - withdraw_fee).unwrap()
+            );
+            let fee_account =
+                StateWithExtensions::<Account>::unpack(&accounts.pool_fee_account.data).unwrap();
+            assert_eq!(fee_account.base.amount, to_u64(withdraw_fee).unwrap());
+        }
+
+        // correct withdrawal from fee account
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                _token_b_key,
+                _token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, initial_a, initial_b, 0);
+
+            let fee_a_amount = 2;
+            let pool_fee_key = accounts.pool_fee_key;
+            let mut pool_fee_account = accounts.pool_fee_account.clone();
+            let fee_account =
+                StateWithExtensions::<Account>::unpack(&pool_fee_account.data).unwrap();
+            let pool_fee_amount = fee_account.base.amount;
+
+            let swap_token_a =
+                StateWithExtensions::<Account>::unpack(&accounts.token_a_account.data).unwrap();
+
+            let token_a_amount = swap_token_a.base.amount;
+            accounts
+                .withdraw_single_token_type_exact_amount_out(
+                    &user_key,
+                    &pool_fee_key,
+                    &mut pool_fee_account,
+                    &token_a_key,
+                    &mut token_a_account,
+                    fee_a_amount,
+                    pool_fee_amount,
+                )
+                .unwrap();
+
+            let swap_token_a =
+                StateWithExtensions::<Account>::unpack(&accounts.token_a_account.data).unwrap();
+
+            assert_eq!(swap_token_a.base.amount, token_a_amount - fee_a_amount);
+            let token_a = StateWithExtensions::<Account>::unpack(&token_a_account.data).unwrap();
+            assert_eq!(token_a.base.amount, initial_a + fee_a_amount);
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn check_valid_swap_curve(
+        fees: Fees,
+        transfer_fees: SwapTransferFees,
         curve_type: CurveType,
         calculator: Arc<dyn CurveCalculator + Send + Sync>,
         token_a_amount: u64,
@@ -6103,225 +7532,1252 @@ mod tests {
 
         let swap_curve = SwapCurve {
             curve_type,
-            calculator,
+            calculator,
+        };
+
+        let mut accounts = SwapAccountInfo::new(
+            &user_key,
+            fees.clone(),
+            transfer_fees,
+            swap_curve.clone(),
+            token_a_amount,
+            token_b_amount,
+            pool_token_program_id,
+            token_a_program_id,
+            token_b_program_id,
+        );
+        let initial_a = token_a_amount / 5;
+        let initial_b = token_b_amount / 5;
+        accounts.initialize_swap().unwrap();
+
+        let swap_token_a_key = accounts.token_a_key;
+        let swap_token_b_key = accounts.token_b_key;
+
+        let (
+            token_a_key,
+            mut token_a_account,
+            token_b_key,
+            mut token_b_account,
+            _pool_key,
+            _pool_account,
+        ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+        // swap one way
+        let a_to_b_amount = initial_a / 10;
+        let minimum_token_b_amount = 0;
+        let pool_mint =
+            StateWithExtensions::<Mint>::unpack(&accounts.pool_mint_account.data).unwrap();
+        let initial_supply = pool_mint.base.supply;
+        accounts
+            .swap(
+                &swapper_key,
+                &token_a_key,
+                &mut token_a_account,
+                &swap_token_a_key,
+                &swap_token_b_key,
+                &token_b_key,
+                &mut token_b_account,
+                a_to_b_amount,
+                minimum_token_b_amount,
+            )
+            .unwrap();
+
+        // tweak values based on transfer fees assessed
+        let token_a_fee = accounts
+            .transfer_fees
+            .token_a
+            .calculate_fee(a_to_b_amount)
+            .unwrap();
+        let actual_a_to_b_amount = a_to_b_amount - token_a_fee;
+        let results = swap_curve
+            .swap(
+                actual_a_to_b_amount.into(),
+                token_a_amount.into(),
+                token_b_amount.into(),
+                TradeDirection::AtoB,
+                &fees,
+            )
+            .unwrap();
+
+        let swap_token_a =
+            StateWithExtensions::<Account>::unpack(&accounts.token_a_account.data).unwrap();
+        let token_a_amount = swap_token_a.base.amount;
+        assert_eq!(
+            token_a_amount,
+            TryInto::<u64>::try_into(results.new_swap_source_amount).unwrap()
+        );
+        let token_a = StateWithExtensions::<Account>::unpack(&token_a_account.data).unwrap();
+        assert_eq!(token_a.base.amount, initial_a - a_to_b_amount);
+
+        let swap_token_b =
+            StateWithExtensions::<Account>::unpack(&accounts.token_b_account.data).unwrap();
+        let token_b_amount = swap_token_b.base.amount;
+        assert_eq!(
+            token_b_amount,
+            TryInto::<u64>::try_into(results.new_swap_destination_amount).unwrap()
+        );
+        let token_b = StateWithExtensions::<Account>::unpack(&token_b_account.data).unwrap();
+        assert_eq!(
+            token_b.base.amount,
+            initial_b + to_u64(results.destination_amount_swapped).unwrap()
+        );
+
+        let first_fee = if results.owner_fee > 0 {
+            swap_curve
+                .calculator
+                .withdraw_single_token_type_exact_out(
+                    results.owner_fee,
+                    token_a_amount.into(),
+                    token_b_amount.into(),
+                    initial_supply.into(),
+                    TradeDirection::AtoB,
+                    RoundDirection::Floor,
+                )
+                .unwrap()
+        } else {
+            0
+        };
+        let fee_account =
+            StateWithExtensions::<Account>::unpack(&accounts.pool_fee_account.data).unwrap();
+        assert_eq!(
+            fee_account.base.amount,
+            TryInto::<u64>::try_into(first_fee).unwrap()
+        );
+
+        let first_swap_amount = results.destination_amount_swapped;
+
+        // swap the other way
+        let pool_mint =
+            StateWithExtensions::<Mint>::unpack(&accounts.pool_mint_account.data).unwrap();
+        let initial_supply = pool_mint.base.supply;
+
+        let b_to_a_amount = initial_b / 10;
+        let minimum_a_amount = 0;
+        accounts
+            .swap(
+                &swapper_key,
+                &token_b_key,
+                &mut token_b_account,
+                &swap_token_b_key,
+                &swap_token_a_key,
+                &token_a_key,
+                &mut token_a_account,
+                b_to_a_amount,
+                minimum_a_amount,
+            )
+            .unwrap();
+
+        let mut results = swap_curve
+            .swap(
+                b_to_a_amount.into(),
+                token_b_amount.into(),
+                token_a_amount.into(),
+                TradeDirection::BtoA,
+                &fees,
+            )
+            .unwrap();
+        // tweak values based on transfer fees assessed
+        let token_a_fee = accounts
+            .transfer_fees
+            .token_a
+            .calculate_fee(results.destination_amount_swapped.try_into().unwrap())
+            .unwrap();
+        results.destination_amount_swapped -= token_a_fee as u128;
+
+        let swap_token_a =
+            StateWithExtensions::<Account>::unpack(&accounts.token_a_account.data).unwrap();
+        let token_a_amount = swap_token_a.base.amount;
+        assert_eq!(
+            token_a_amount,
+            TryInto::<u64>::try_into(results.new_swap_destination_amount).unwrap()
+        );
+        let token_a = StateWithExtensions::<Account>::unpack(&token_a_account.data).unwrap();
+        assert_eq!(
+            token_a.base.amount,
+            initial_a - a_to_b_amount + to_u64(results.destination_amount_swapped).unwrap()
+        );
+
+        let swap_token_b =
+            StateWithExtensions::<Account>::unpack(&accounts.token_b_account.data).unwrap();
+        let token_b_amount = swap_token_b.base.amount;
+        assert_eq!(
+            token_b_amount,
+            TryInto::<u64>::try_into(results.new_swap_source_amount).unwrap()
+        );
+        let token_b = StateWithExtensions::<Account>::unpack(&token_b_account.data).unwrap();
+        assert_eq!(
+            token_b.base.amount,
+            initial_b + to_u64(first_swap_amount).unwrap()
+                - to_u64(results.source_amount_swapped).unwrap()
+        );
+
+        let second_fee = if results.owner_fee > 0 {
+            swap_curve
+                .calculator
+                .withdraw_single_token_type_exact_out(
+                    results.owner_fee,
+                    token_a_amount.into(),
+                    token_b_amount.into(),
+                    initial_supply.into(),
+                    TradeDirection::BtoA,
+                    RoundDirection::Floor,
+                )
+                .unwrap()
+        } else {
+            0
+        };
+        let fee_account =
+            StateWithExtensions::<Account>::unpack(&accounts.pool_fee_account.data).unwrap();
+        assert_eq!(
+            fee_account.base.amount,
+            to_u64(first_fee + second_fee).unwrap()
+        );
+    }
+
+    #[test_case(spl_token::id(), spl_token::id(), spl_token::id(); "all-token")]
+    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token_2022::id(); "all-token-2022")]
+    #[test_case(spl_token::id(), spl_token_2022::id(), spl_token_2022::id(); "mixed-pool-token")]
+    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token::id(); "mixed-pool-token-2022")]
+    fn test_valid_swap_curve_all_fees(
+        pool_token_program_id: Pubkey,
+        token_a_program_id: Pubkey,
+        token_b_program_id: Pubkey,
+    ) {
+        // All fees
+        let trade_fee_numerator = 1;
+        let trade_fee_denominator = 10;
+        let owner_trade_fee_numerator = 1;
+        let owner_trade_fee_denominator = 30;
+        let owner_withdraw_fee_numerator = 1;
+        let owner_withdraw_fee_denominator = 30;
+        let host_fee_numerator = 20;
+        let host_fee_denominator = 100;
+        let fees = Fees {
+            trade_fee_numerator,
+            trade_fee_denominator,
+            owner_trade_fee_numerator,
+            owner_trade_fee_denominator,
+            owner_withdraw_fee_numerator,
+            owner_withdraw_fee_denominator,
+            host_fee_numerator,
+            host_fee_denominator,
+        };
+
+        let token_a_amount = 10_000_000_000;
+        let token_b_amount = 50_000_000_000;
+
+        check_valid_swap_curve(
+            fees.clone(),
+            SwapTransferFees::default(),
+            CurveType::ConstantProduct,
+            Arc::new(ConstantProductCurve {}),
+            token_a_amount,
+            token_b_amount,
+            &pool_token_program_id,
+            &token_a_program_id,
+            &token_b_program_id,
+        );
+        let token_b_price = 1;
+        check_valid_swap_curve(
+            fees.clone(),
+            SwapTransferFees::default(),
+            CurveType::ConstantPrice,
+            Arc::new(ConstantPriceCurve { token_b_price }),
+            token_a_amount,
+            token_b_amount,
+            &pool_token_program_id,
+            &token_a_program_id,
+            &token_b_program_id,
+        );
+        let token_b_offset = 10_000_000_000;
+        check_valid_swap_curve(
+            fees,
+            SwapTransferFees::default(),
+            CurveType::Offset,
+            Arc::new(OffsetCurve { token_b_offset }),
+            token_a_amount,
+            token_b_amount,
+            &pool_token_program_id,
+            &token_a_program_id,
+            &token_b_program_id,
+        );
+    }
+
+    #[test_case(spl_token::id(), spl_token::id(), spl_token::id(); "all-token")]
+    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token_2022::id(); "all-token-2022")]
+    #[test_case(spl_token::id(), spl_token_2022::id(), spl_token_2022::id(); "mixed-pool-token")]
+    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token::id(); "mixed-pool-token-2022")]
+    fn test_valid_swap_curve_trade_fee_only(
+        pool_token_program_id: Pubkey,
+        token_a_program_id: Pubkey,
+        token_b_program_id: Pubkey,
+    ) {
+        let trade_fee_numerator = 1;
+        let trade_fee_denominator = 10;
+        let owner_trade_fee_numerator = 0;
+        let owner_trade_fee_denominator = 0;
+        let owner_withdraw_fee_numerator = 0;
+        let owner_withdraw_fee_denominator = 0;
+        let host_fee_numerator = 0;
+        let host_fee_denominator = 0;
+        let fees = Fees {
+            trade_fee_numerator,
+            trade_fee_denominator,
+            owner_trade_fee_numerator,
+            owner_trade_fee_denominator,
+            owner_withdraw_fee_numerator,
+            owner_withdraw_fee_denominator,
+            host_fee_numerator,
+            host_fee_denominator,
+        };
+
+        let token_a_amount = 10_000_000_000;
+        let token_b_amount = 50_000_000_000;
+
+        check_valid_swap_curve(
+            fees.clone(),
+            SwapTransferFees::default(),
+            CurveType::ConstantProduct,
+            Arc::new(ConstantProductCurve {}),
+            token_a_amount,
+            token_b_amount,
+            &pool_token_program_id,
+            &token_a_program_id,
+            &token_b_program_id,
+        );
+        let token_b_price = 10_000;
+        check_valid_swap_curve(
+            fees.clone(),
+            SwapTransferFees::default(),
+            CurveType::ConstantPrice,
+            Arc::new(ConstantPriceCurve { token_b_price }),
+            token_a_amount,
+            token_b_amount / token_b_price,
+            &pool_token_program_id,
+            &token_a_program_id,
+            &token_b_program_id,
+        );
+        let token_b_offset = 1;
+        check_valid_swap_curve(
+            fees,
+            SwapTransferFees::default(),
+            CurveType::Offset,
+            Arc::new(OffsetCurve { token_b_offset }),
+            token_a_amount,
+            token_b_amount,
+            &pool_token_program_id,
+            &token_a_program_id,
+            &token_b_program_id,
+        );
+    }
+
+    #[test_case(spl_token::id(), spl_token::id(), spl_token::id(); "all-token")]
+    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token_2022::id(); "all-token-2022")]
+    #[test_case(spl_token::id(), spl_token_2022::id(), spl_token_2022::id(); "mixed-pool-token")]
+    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token::id(); "mixed-pool-token-2022")]
+    fn test_valid_swap_with_fee_constraints(
+        pool_token_program_id: Pubkey,
+        token_a_program_id: Pubkey,
+        token_b_program_id: Pubkey,
+    ) {
+        let owner_key = Pubkey::new_unique();
+
+        let trade_fee_numerator = 1;
+        let trade_fee_denominator = 10;
+        let owner_trade_fee_numerator = 1;
+        let owner_trade_fee_denominator = 30;
+        let owner_withdraw_fee_numerator = 1;
+        let owner_withdraw_fee_denominator = 30;
+        let host_fee_numerator = 10;
+        let host_fee_denominator = 100;
+
+        let token_a_amount = 1_000_000;
+        let token_b_amount = 5_000_000;
+
+        let fees = Fees {
+            trade_fee_numerator,
+            trade_fee_denominator,
+            owner_trade_fee_numerator,
+            owner_trade_fee_denominator,
+            owner_withdraw_fee_numerator,
+            owner_withdraw_fee_denominator,
+            host_fee_numerator,
+            host_fee_denominator,
+        };
+
+        let curve = ConstantProductCurve {};
+        let swap_curve = SwapCurve {
+            curve_type: CurveType::ConstantProduct,
+            calculator: Arc::new(curve),
+        };
+
+        let owner_key_str = &owner_key.to_string();
+        let valid_curve_types = &[CurveType::ConstantProduct];
+        let constraints = Some(SwapConstraints {
+            owner_key: owner_key_str,
+            valid_curve_types,
+            fees: &fees,
+        });
+        let mut accounts = SwapAccountInfo::new(
+            &owner_key,
+            fees.clone(),
+            SwapTransferFees::default(),
+            swap_curve,
+            token_a_amount,
+            token_b_amount,
+            &pool_token_program_id,
+            &token_a_program_id,
+            &token_b_program_id,
+        );
+
+        // initialize swap
+        do_process_instruction_with_fee_constraints(
+            initialize(
+                &SWAP_PROGRAM_ID,
+                &pool_token_program_id,
+                &accounts.swap_key,
+                &accounts.authority_key,
+                &accounts.token_a_key,
+                &accounts.token_b_key,
+                &accounts.pool_mint_key,
+                &accounts.pool_fee_key,
+                &accounts.pool_token_key,
+                accounts.fees.clone(),
+                accounts.swap_curve.clone(),
+            )
+            .unwrap(),
+            vec![
+                &mut accounts.swap_account,
+                &mut SolanaAccount::default(),
+                &mut accounts.token_a_account,
+                &mut accounts.token_b_account,
+                &mut accounts.pool_mint_account,
+                &mut accounts.pool_fee_account,
+                &mut accounts.pool_token_account,
+                &mut SolanaAccount::default(),
+            ],
+            &constraints,
+        )
+        .unwrap();
+
+        let authority_key = accounts.authority_key;
+
+        let (
+            token_a_key,
+            mut token_a_account,
+            token_b_key,
+            mut token_b_account,
+            pool_key,
+            mut pool_account,
+        ) = accounts.setup_token_accounts(
+            &owner_key,
+            &authority_key,
+            token_a_amount,
+            token_b_amount,
+            0,
+        );
+
+        let amount_in = token_a_amount / 2;
+        let minimum_amount_out = 0;
+
+        // perform the swap
+        do_process_instruction_with_fee_constraints(
+            swap(
+                &SWAP_PROGRAM_ID,
+                &token_a_program_id,
+                &token_b_program_id,
+                &pool_token_program_id,
+                &accounts.swap_key,
+                &accounts.authority_key,
+                &accounts.authority_key,
+                &token_a_key,
+                &accounts.token_a_key,
+                &accounts.token_b_key,
+                &token_b_key,
+                &accounts.pool_mint_key,
+                &accounts.pool_fee_key,
+                &accounts.token_a_mint_key,
+                &accounts.token_b_mint_key,
+                Some(&pool_key),
+                Swap {
+                    amount_in,
+                    minimum_amount_out,
+                },
+            )
+            .unwrap(),
+            vec![
+                &mut accounts.swap_account,
+                &mut SolanaAccount::default(),
+                &mut SolanaAccount::default(),
+                &mut token_a_account,
+                &mut accounts.token_a_account,
+                &mut accounts.token_b_account,
+                &mut token_b_account,
+                &mut accounts.pool_mint_account,
+                &mut accounts.pool_fee_account,
+                &mut accounts.token_a_mint_account,
+                &mut accounts.token_b_mint_account,
+                &mut SolanaAccount::default(),
+                &mut SolanaAccount::default(),
+                &mut SolanaAccount::default(),
+                &mut pool_account,
+            ],
+            &constraints,
+        )
+        .unwrap();
+
+        // check that fees were taken in the host fee account
+        let host_fee_account = StateWithExtensions::<Account>::unpack(&pool_account.data).unwrap();
+        let owner_fee_account =
+            StateWithExtensions::<Account>::unpack(&accounts.pool_fee_account.data).unwrap();
+        let total_fee = owner_fee_account.base.amount * host_fee_denominator
+            / (host_fee_denominator - host_fee_numerator);
+        assert_eq!(
+            total_fee,
+            host_fee_account.base.amount + owner_fee_account.base.amount
+        );
+    }
+
+    #[test_case(spl_token::id(), spl_token::id(), spl_token::id(); "all-token")]
+    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token_2022::id(); "all-token-2022")]
+    #[test_case(spl_token::id(), spl_token_2022::id(), spl_token_2022::id(); "mixed-pool-token")]
+    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token::id(); "mixed-pool-token-2022")]
+    fn test_invalid_swap(
+        pool_token_program_id: Pubkey,
+        token_a_program_id: Pubkey,
+        token_b_program_id: Pubkey,
+    ) {
+        let user_key = Pubkey::new_unique();
+        let swapper_key = Pubkey::new_unique();
+        let trade_fee_numerator = 1;
+        let trade_fee_denominator = 4;
+        let owner_trade_fee_numerator = 1;
+        let owner_trade_fee_denominator = 10;
+        let owner_withdraw_fee_numerator = 1;
+        let owner_withdraw_fee_denominator = 5;
+        let host_fee_numerator = 9;
+        let host_fee_denominator = 100;
+        let fees = Fees {
+            trade_fee_numerator,
+            trade_fee_denominator,
+            owner_trade_fee_numerator,
+            owner_trade_fee_denominator,
+            owner_withdraw_fee_numerator,
+            owner_withdraw_fee_denominator,
+            host_fee_numerator,
+            host_fee_denominator,
+        };
+
+        let token_a_amount = 1000;
+        let token_b_amount = 5000;
+        let curve_type = CurveType::ConstantProduct;
+        let swap_curve = SwapCurve {
+            curve_type,
+            calculator: Arc::new(ConstantProductCurve {}),
         };
-
         let mut accounts = SwapAccountInfo::new(
             &user_key,
-            fees.clone(),
-            transfer_fees,
-            swap_curve.clone(),
+            fees,
+            SwapTransferFees::default(),
+            swap_curve,
             token_a_amount,
             token_b_amount,
-            pool_token_program_id,
-            token_a_program_id,
-            token_b_program_id,
+            &pool_token_program_id,
+            &token_a_program_id,
+            &token_b_program_id,
         );
+
         let initial_a = token_a_amount / 5;
         let initial_b = token_b_amount / 5;
-        accounts.initialize_swap().unwrap();
+        let minimum_token_b_amount = initial_b / 2;
 
         let swap_token_a_key = accounts.token_a_key;
         let swap_token_b_key = accounts.token_b_key;
 
-        let (
-            token_a_key,
-            mut token_a_account,
-            token_b_key,
-            mut token_b_account,
-            _pool_key,
-            _pool_account,
-        ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-        // swap one way
-        let a_to_b_amount = initial_a / 10;
-        let minimum_token_b_amount = 0;
-        let pool_mint =
-            StateWithExtensions::<Mint>::unpack(&accounts.pool_mint_account.data).unwrap();
-        let initial_supply = pool_mint.base.supply;
-        accounts
-            .swap(
-                &swapper_key,
-                &token_a_key,
-                &mut token_a_account,
-                &swap_token_a_key,
-                &swap_token_b_key,
-                &token_b_key,
-                &mut token_b_account,
-                a_to_b_amount,
-                minimum_token_b_amount,
-            )
-            .unwrap();
+        // swap not initialized
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            assert_eq!(
+                Err(ProgramError::UninitializedAccount),
+                accounts.swap(
+                    &swapper_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &swap_token_a_key,
+                    &swap_token_b_key,
+                    &token_b_key,
+                    &mut token_b_account,
+                    initial_a,
+                    minimum_token_b_amount,
+                )
+            );
+        }
+
+        accounts.initialize_swap().unwrap();
+
+        // wrong swap account program id
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            let old_swap_account = accounts.swap_account;
+            let mut wrong_swap_account = old_swap_account.clone();
+            wrong_swap_account.owner = pool_token_program_id;
+            accounts.swap_account = wrong_swap_account;
+            assert_eq!(
+                Err(ProgramError::IncorrectProgramId),
+                accounts.swap(
+                    &swapper_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &swap_token_a_key,
+                    &swap_token_b_key,
+                    &token_b_key,
+                    &mut token_b_account,
+                    initial_a,
+                    minimum_token_b_amount,
+                )
+            );
+            accounts.swap_account = old_swap_account;
+        }
+
+        // wrong bump seed
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            let old_authority = accounts.authority_key;
+            let (bad_authority_key, _bump_seed) = Pubkey::find_program_address(
+                &[&accounts.swap_key.to_bytes()[..]],
+                &pool_token_program_id,
+            );
+            accounts.authority_key = bad_authority_key;
+            assert_eq!(
+                Err(SwapError::InvalidProgramAddress.into()),
+                accounts.swap(
+                    &swapper_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &swap_token_a_key,
+                    &swap_token_b_key,
+                    &token_b_key,
+                    &mut token_b_account,
+                    initial_a,
+                    minimum_token_b_amount,
+                )
+            );
+            accounts.authority_key = old_authority;
+        }
+
+        // wrong token program id
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            let wrong_program_id = Pubkey::new_unique();
+            assert_eq!(
+                Err(SwapError::IncorrectTokenProgramId.into()),
+                do_process_instruction(
+                    swap(
+                        &SWAP_PROGRAM_ID,
+                        &wrong_program_id,
+                        &wrong_program_id,
+                        &wrong_program_id,
+                        &accounts.swap_key,
+                        &accounts.authority_key,
+                        &accounts.authority_key,
+                        &token_a_key,
+                        &accounts.token_a_key,
+                        &accounts.token_b_key,
+                        &token_b_key,
+                        &accounts.pool_mint_key,
+                        &accounts.pool_fee_key,
+                        &accounts.token_a_mint_key,
+                        &accounts.token_b_mint_key,
+                        None,
+                        Swap {
+                            amount_in: initial_a,
+                            minimum_amount_out: minimum_token_b_amount,
+                        },
+                    )
+                    .unwrap(),
+                    vec![
+                        &mut accounts.swap_account,
+                        &mut SolanaAccount::default(),
+                        &mut SolanaAccount::default(),
+                        &mut token_a_account,
+                        &mut accounts.token_a_account,
+                        &mut accounts.token_b_account,
+                        &mut token_b_account,
+                        &mut accounts.pool_mint_account,
+                        &mut accounts.pool_fee_account,
+                        &mut accounts.token_a_mint_account,
+                        &mut accounts.token_b_mint_account,
+                        &mut SolanaAccount::default(),
+                        &mut SolanaAccount::default(),
+                        &mut SolanaAccount::default(),
+                    ],
+                ),
+            );
+        }
+
+        // not enough token a to swap
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            assert_eq!(
+                Err(TokenError::InsufficientFunds.into()),
+                accounts.swap(
+                    &swapper_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &swap_token_a_key,
+                    &swap_token_b_key,
+                    &token_b_key,
+                    &mut token_b_account,
+                    initial_a * 2,
+                    minimum_token_b_amount * 2,
+                )
+            );
+        }
+
+        // wrong swap token A / B accounts
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            let user_transfer_key = Pubkey::new_unique();
+            assert_eq!(
+                Err(SwapError::IncorrectSwapAccount.into()),
+                do_process_instruction(
+                    swap(
+                        &SWAP_PROGRAM_ID,
+                        &token_a_program_id,
+                        &token_b_program_id,
+                        &pool_token_program_id,
+                        &accounts.swap_key,
+                        &accounts.authority_key,
+                        &user_transfer_key,
+                        &token_a_key,
+                        &token_a_key,
+                        &token_b_key,
+                        &token_b_key,
+                        &accounts.pool_mint_key,
+                        &accounts.pool_fee_key,
+                        &accounts.token_a_mint_key,
+                        &accounts.token_b_mint_key,
+                        None,
+                        Swap {
+                            amount_in: initial_a,
+                            minimum_amount_out: minimum_token_b_amount,
+                        },
+                    )
+                    .unwrap(),
+                    vec![
+                        &mut accounts.swap_account,
+                        &mut SolanaAccount::default(),
+                        &mut SolanaAccount::default(),
+                        &mut token_a_account.clone(),
+                        &mut token_a_account,
+                        &mut token_b_account.clone(),
+                        &mut token_b_account,
+                        &mut accounts.pool_mint_account,
+                        &mut accounts.pool_fee_account,
+                        &mut accounts.token_a_mint_account,
+                        &mut accounts.token_b_mint_account,
+                        &mut SolanaAccount::default(),
+                        &mut SolanaAccount::default(),
+                        &mut SolanaAccount::default(),
+                    ],
+                ),
+            );
+        }
 
-        // tweak values based on transfer fees assessed
-        let token_a_fee = accounts
-            .transfer_fees
-            .token_a
-            .calculate_fee(a_to_b_amount)
-            .unwrap();
-        let actual_a_to_b_amount = a_to_b_amount - token_a_fee;
-        let results = swap_curve
-            .swap(
-                actual_a_to_b_amount.into(),
-                token_a_amount.into(),
-                token_b_amount.into(),
-                TradeDirection::AtoB,
-                &fees,
-            )
-            .unwrap();
+        // wrong user token A / B accounts
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            assert_eq!(
+                Err(TokenError::MintMismatch.into()),
+                accounts.swap(
+                    &swapper_key,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &swap_token_a_key,
+                    &swap_token_b_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    initial_a,
+                    minimum_token_b_amount,
+                )
+            );
+        }
 
-        let swap_token_a =
-            StateWithExtensions::<Account>::unpack(&accounts.token_a_account.data).unwrap();
-        let token_a_amount = swap_token_a.base.amount;
-        assert_eq!(
-            token_a_amount,
-            TryInto::<u64>::try_into(results.new_swap_source_amount).unwrap()
-        );
-        let token_a = StateWithExtensions::<Account>::unpack(&token_a_account.data).unwrap();
-        assert_eq!(token_a.base.amount, initial_a - a_to_b_amount);
+        // swap from a to a
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                _token_b_key,
+                _token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            assert_eq!(
+                Err(SwapError::InvalidInput.into()),
+                accounts.swap(
+                    &swapper_key,
+                    &token_a_key,
+                    &mut token_a_account.clone(),
+                    &swap_token_a_key,
+                    &swap_token_a_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    initial_a,
+                    minimum_token_b_amount,
+                )
+            );
+        }
 
-        let swap_token_b =
-            StateWithExtensions::<Account>::unpack(&accounts.token_b_account.data).unwrap();
-        let token_b_amount = swap_token_b.base.amount;
-        assert_eq!(
-            token_b_amount,
-            TryInto::<u64>::try_into(results.new_swap_destination_amount).unwrap()
-        );
-        let token_b = StateWithExtensions::<Account>::unpack(&token_b_account.data).unwrap();
-        assert_eq!(
-            token_b.base.amount,
-            initial_b + to_u64(results.destination_amount_swapped).unwrap()
-        );
+        // incorrect mint provided
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            let (pool_mint_key, pool_mint_account) = create_mint(
+                &pool_token_program_id,
+                &accounts.authority_key,
+                None,
+                None,
+                &TransferFee::default(),
+            );
+            let old_pool_key = accounts.pool_mint_key;
+            let old_pool_account = accounts.pool_mint_account;
+            accounts.pool_mint_key = pool_mint_key;
+            accounts.pool_mint_account = pool_mint_account;
 
-        let first_fee = if results.owner_fee > 0 {
-            swap_curve
-                .calculator
-                .withdraw_single_token_type_exact_out(
-                    results.owner_fee,
-                    token_a_amount.into(),
-                    token_b_amount.into(),
-                    initial_supply.into(),
-                    TradeDirection::AtoB,
-                    RoundDirection::Floor,
+            assert_eq!(
+                Err(SwapError::IncorrectPoolMint.into()),
+                accounts.swap(
+                    &swapper_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &swap_token_a_key,
+                    &swap_token_b_key,
+                    &token_b_key,
+                    &mut token_b_account,
+                    initial_a,
+                    minimum_token_b_amount,
                 )
-                .unwrap()
-        } else {
-            0
-        };
-        let fee_account =
-            StateWithExtensions::<Account>::unpack(&accounts.pool_fee_account.data).unwrap();
-        assert_eq!(
-            fee_account.base.amount,
-            TryInto::<u64>::try_into(first_fee).unwrap()
-        );
+            );
 
-        let first_swap_amount = results.destination_amount_swapped;
+            accounts.pool_mint_key = old_pool_key;
+            accounts.pool_mint_account = old_pool_account;
+        }
 
-        // swap the other way
-        let pool_mint =
-            StateWithExtensions::<Mint>::unpack(&accounts.pool_mint_account.data).unwrap();
-        let initial_supply = pool_mint.base.supply;
+        // incorrect fee account provided
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                wrong_pool_key,
+                wrong_pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            let old_pool_fee_account = accounts.pool_fee_account;
+            let old_pool_fee_key = accounts.pool_fee_key;
+            accounts.pool_fee_account = wrong_pool_account;
+            accounts.pool_fee_key = wrong_pool_key;
+            assert_eq!(
+                Err(SwapError::IncorrectFeeAccount.into()),
+                accounts.swap(
+                    &swapper_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &swap_token_a_key,
+                    &swap_token_b_key,
+                    &token_b_key,
+                    &mut token_b_account,
+                    initial_a,
+                    minimum_token_b_amount,
+                )
+            );
+            accounts.pool_fee_account = old_pool_fee_account;
+            accounts.pool_fee_key = old_pool_fee_key;
+        }
 
-        let b_to_a_amount = initial_b / 10;
-        let minimum_a_amount = 0;
-        accounts
-            .swap(
-                &swapper_key,
-                &token_b_key,
-                &mut token_b_account,
-                &swap_token_b_key,
-                &swap_token_a_key,
-                &token_a_key,
-                &mut token_a_account,
-                b_to_a_amount,
-                minimum_a_amount,
-            )
-            .unwrap();
+        // no approval
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            let user_transfer_key = Pubkey::new_unique();
+            assert_eq!(
+                Err(TokenError::OwnerMismatch.into()),
+                do_process_instruction(
+                    swap(
+                        &SWAP_PROGRAM_ID,
+                        &token_a_program_id,
+                        &token_b_program_id,
+                        &pool_token_program_id,
+                        &accounts.swap_key,
+                        &accounts.authority_key,
+                        &user_transfer_key,
+                        &token_a_key,
+                        &accounts.token_a_key,
+                        &accounts.token_b_key,
+                        &token_b_key,
+                        &accounts.pool_mint_key,
+                        &accounts.pool_fee_key,
+                        &accounts.token_a_mint_key,
+                        &accounts.token_b_mint_key,
+                        None,
+                        Swap {
+                            amount_in: initial_a,
+                            minimum_amount_out: minimum_token_b_amount,
+                        },
+                    )
+                    .unwrap(),
+                    vec![
+                        &mut accounts.swap_account,
+                        &mut SolanaAccount::default(),
+                        &mut SolanaAccount::default(),
+                        &mut token_a_account,
+                        &mut accounts.token_a_account,
+                        &mut accounts.token_b_account,
+                        &mut token_b_account,
+                        &mut accounts.pool_mint_account,
+                        &mut accounts.pool_fee_account,
+                        &mut accounts.token_a_mint_account,
+                        &mut accounts.token_b_mint_account,
+                        &mut SolanaAccount::default(),
+                        &mut SolanaAccount::default(),
+                        &mut SolanaAccount::default(),
+                    ],
+                ),
+            );
+        }
 
-        let mut results = swap_curve
-            .swap(
-                b_to_a_amount.into(),
-                token_b_amount.into(),
-                token_a_amount.into(),
-                TradeDirection::BtoA,
-                &fees,
-            )
-            .unwrap();
-        // tweak values based on transfer fees assessed
-        let token_a_fee = accounts
-            .transfer_fees
-            .token_a
-            .calculate_fee(results.destination_amount_swapped.try_into().unwrap())
-            .unwrap();
-        results.destination_amount_swapped -= token_a_fee as u128;
+        // output token value 0
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            assert_eq!(
+                Err(SwapError::ZeroTradingTokens.into()),
+                accounts.swap(
+                    &swapper_key,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &swap_token_b_key,
+                    &swap_token_a_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    1,
+                    1,
+                )
+            );
+        }
 
-        let swap_token_a =
-            StateWithExtensions::<Account>::unpack(&accounts.token_a_account.data).unwrap();
-        let token_a_amount = swap_token_a.base.amount;
-        assert_eq!(
-            token_a_amount,
-            TryInto::<u64>::try_into(results.new_swap_destination_amount).unwrap()
-        );
-        let token_a = StateWithExtensions::<Account>::unpack(&token_a_account.data).unwrap();
-        assert_eq!(
-            token_a.base.amount,
-            initial_a - a_to_b_amount + to_u64(results.destination_amount_swapped).unwrap()
-        );
+        // slippage exceeded: minimum out amount too high
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            assert_eq!(
+                Err(SwapError::ExceededSlippage.into()),
+                accounts.swap(
+                    &swapper_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &swap_token_a_key,
+                    &swap_token_b_key,
+                    &token_b_key,
+                    &mut token_b_account,
+                    initial_a,
+                    minimum_token_b_amount * 2,
+                )
+            );
+        }
 
-        let swap_token_b =
-            StateWithExtensions::<Account>::unpack(&accounts.token_b_account.data).unwrap();
-        let token_b_amount = swap_token_b.base.amount;
-        assert_eq!(
-            token_b_amount,
-            TryInto::<u64>::try_into(results.new_swap_source_amount).unwrap()
-        );
-        let token_b = StateWithExtensions::<Account>::unpack(&token_b_account.data).unwrap();
-        assert_eq!(
-            token_b.base.amount,
-            initial_b + to_u64(first_swap_amount).unwrap()
-                - to_u64(results.source_amount_swapped).unwrap()
-        );
+        // invalid input: can't use swap pool as user source / dest
+        {
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            let mut swap_token_a_account = accounts.get_token_account(&swap_token_a_key).clone();
+            let authority_key = accounts.authority_key;
+            assert_eq!(
+                Err(SwapError::InvalidInput.into()),
+                accounts.swap(
+                    &authority_key,
+                    &swap_token_a_key,
+                    &mut swap_token_a_account,
+                    &swap_token_a_key,
+                    &swap_token_b_key,
+                    &token_b_key,
+                    &mut token_b_account,
+                    initial_a,
+                    minimum_token_b_amount,
+                )
+            );
+            let mut swap_token_b_account = accounts.get_token_account(&swap_token_b_key).clone();
+            assert_eq!(
+                Err(SwapError::InvalidInput.into()),
+                accounts.swap(
+                    &swapper_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &swap_token_a_key,
+                    &swap_token_b_key,
+                    &swap_token_b_key,
+                    &mut swap_token_b_account,
+                    initial_a,
+                    minimum_token_b_amount,
+                )
+            );
+        }
 
-        let second_fee = if results.owner_fee > 0 {
-            swap_curve
-                .calculator
-                .withdraw_single_token_type_exact_out(
-                    results.owner_fee,
-                    token_a_amount.into(),
-                    token_b_amount.into(),
-                    initial_supply.into(),
-                    TradeDirection::BtoA,
-                    RoundDirection::Floor,
+        // still correct: constraint specified, no host fee account
+        {
+            let authority_key = accounts.authority_key;
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &authority_key, initial_a, initial_b, 0);
+            let owner_key = &swapper_key.to_string();
+            let fees = Fees {
+                trade_fee_numerator,
+                trade_fee_denominator,
+                owner_trade_fee_numerator,
+                owner_trade_fee_denominator,
+                owner_withdraw_fee_numerator,
+                owner_withdraw_fee_denominator,
+                host_fee_numerator,
+                host_fee_denominator,
+            };
+            let constraints = Some(SwapConstraints {
+                owner_key,
+                valid_curve_types: &[],
+                fees: &fees,
+            });
+            do_process_instruction_with_fee_constraints(
+                swap(
+                    &SWAP_PROGRAM_ID,
+                    &token_a_program_id,
+                    &token_b_program_id,
+                    &pool_token_program_id,
+                    &accounts.swap_key,
+                    &accounts.authority_key,
+                    &accounts.authority_key,
+                    &token_a_key,
+                    &accounts.token_a_key,
+                    &accounts.token_b_key,
+                    &token_b_key,
+                    &accounts.pool_mint_key,
+                    &accounts.pool_fee_key,
+                    &accounts.token_a_mint_key,
+                    &accounts.token_b_mint_key,
+                    None,
+                    Swap {
+                        amount_in: initial_a,
+                        minimum_amount_out: minimum_token_b_amount,
+                    },
                 )
-                .unwrap()
-        } else {
-            0
-        };
-        let fee_account =
-            StateWithExtensions::<Account>::unpack(&accounts.pool_fee_account.data).unwrap();
-        assert_eq!(
-            fee_account.base.amount,
-            to_u64(first_fee + second_fee).unwrap()
-        );
+                .unwrap(),
+                vec![
+                    &mut accounts.swap_account,
+                    &mut SolanaAccount::default(),
+                    &mut SolanaAccount::default(),
+                    &mut token_a_account,
+                    &mut accounts.token_a_account,
+                    &mut accounts.token_b_account,
+                    &mut token_b_account,
+                    &mut accounts.pool_mint_account,
+                    &mut accounts.pool_fee_account,
+                    &mut accounts.token_a_mint_account,
+                    &mut accounts.token_b_mint_account,
+                    &mut SolanaAccount::default(),
+                    &mut SolanaAccount::default(),
+                    &mut SolanaAccount::default(),
+                ],
+                &constraints,
+            )
+            .unwrap();
+        }
+
+        // invalid mint for host fee account
+        {
+            let authority_key = accounts.authority_key;
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &authority_key, initial_a, initial_b, 0);
+            let (
+                bad_token_a_key,
+                mut bad_token_a_account,
+                _token_b_key,
+                mut _token_b_account,
+                _pool_key,
+                _pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &authority_key, initial_a, initial_b, 0);
+            let owner_key = &swapper_key.to_string();
+            let fees = Fees {
+                trade_fee_numerator,
+                trade_fee_denominator,
+                owner_trade_fee_numerator,
+                owner_trade_fee_denominator,
+                owner_withdraw_fee_numerator,
+                owner_withdraw_fee_denominator,
+                host_fee_numerator,
+                host_fee_denominator,
+            };
+            let constraints = Some(SwapConstraints {
+                owner_key,
+                valid_curve_types: &[],
+                fees: &fees,
+            });
+            assert_eq!(
+                Err(SwapError::IncorrectPoolMint.into()),
+                do_process_instruction_with_fee_constraints(
+                    swap(
+                        &SWAP_PROGRAM_ID,
+                        &token_a_program_id,
+                        &token_b_program_id,
+                        &pool_token_program_id,
+                        &accounts.swap_key,
+                        &accounts.authority_key,
+                        &accounts.authority_key,
+                        &token_a_key,
+                        &accounts.token_a_key,
+                        &accounts.token_b_key,
+                        &token_b_key,
+                        &accounts.pool_mint_key,
+                        &accounts.pool_fee_key,
+                        &accounts.token_a_mint_key,
+                        &accounts.token_b_mint_key,
+                        Some(&bad_token_a_key),
+                        Swap {
+                            amount_in: initial_a,
+                            minimum_amount_out: 0,
+                        },
+                    )
+                    .unwrap(),
+                    vec![
+                        &mut accounts.swap_account,
+                        &mut SolanaAccount::default(),
+                        &mut SolanaAccount::default(),
+                        &mut token_a_account,
+                        &mut accounts.token_a_account,
+                        &mut accounts.token_b_account,
+                        &mut token_b_account,
+                        &mut accounts.pool_mint_account,
+                        &mut accounts.pool_fee_account,
+                        &mut accounts.token_a_mint_account,
+                        &mut accounts.token_b_mint_account,
+                        &mut SolanaAccount::default(),
+                        &mut SolanaAccount::default(),
+                        &mut SolanaAccount::default(),
+                        &mut bad_token_a_account,
+                    ],
+                    &constraints,
+                ),
+            );
+        }
     }
 
     #[test_case(spl_token::id(), spl_token::id(), spl_token::id(); "all-token")]
     #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token_2022::id(); "all-token-2022")]
     #[test_case(spl_token::id(), spl_token_2022::id(), spl_token_2022::id(); "mixed-pool-token")]
     #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token::id(); "mixed-pool-token-2022")]
-    fn test_valid_swap_curve_all_fees(
+    fn test_overdraw_offset_curve(
         pool_token_program_id: Pubkey,
         token_a_program_id: Pubkey,
         token_b_program_id: Pubkey,
     ) {
-        // All fees
         let trade_fee_numerator = 1;
         let trade_fee_denominator = 10;
         let owner_trade_fee_numerator = 1;
         let owner_trade_fee_denominator = 30;
         let owner_withdraw_fee_numerator = 1;
         let owner_withdraw_fee_denominator = 30;
-        let host_fee_numerator = 20;
+        let host_fee_numerator = 10;
         let host_fee_denominator = 100;
+
+        let token_a_amount = 1_000_000_000;
+        let token_b_amount = 0;
         let fees = Fees {
             trade_fee_numerator,
             trade_fee_denominator,
@@ -6333,63 +8789,161 @@ mod tests {
             host_fee_denominator,
         };
 
-        let token_a_amount = 10_000_000_000;
-        let token_b_amount = 50_000_000_000;
+        let token_b_offset = 2_000_000;
+        let swap_curve = SwapCurve {
+            curve_type: CurveType::Offset,
+            calculator: Arc::new(OffsetCurve { token_b_offset }),
+        };
+        let user_key = Pubkey::new_unique();
+        let swapper_key = Pubkey::new_unique();
 
-        check_valid_swap_curve(
-            fees.clone(),
+        let mut accounts = SwapAccountInfo::new(
+            &user_key,
+            fees,
             SwapTransferFees::default(),
-            CurveType::ConstantProduct,
-            Arc::new(ConstantProductCurve {}),
+            swap_curve,
             token_a_amount,
             token_b_amount,
             &pool_token_program_id,
             &token_a_program_id,
             &token_b_program_id,
         );
-        let token_b_price = 1;
-        check_valid_swap_curve(
-            fees.clone(),
-            SwapTransferFees::default(),
-            CurveType::ConstantPrice,
-            Arc::new(ConstantPriceCurve { token_b_price }),
-            token_a_amount,
-            token_b_amount,
-            &pool_token_program_id,
-            &token_a_program_id,
-            &token_b_program_id,
+
+        accounts.initialize_swap().unwrap();
+
+        let swap_token_a_key = accounts.token_a_key;
+        let swap_token_b_key = accounts.token_b_key;
+        let initial_a = 500_000;
+        let initial_b = 1_000;
+
+        let (
+            token_a_key,
+            mut token_a_account,
+            token_b_key,
+            mut token_b_account,
+            _pool_key,
+            _pool_account,
+        ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+
+        // swap a to b way, fails, there's no liquidity
+        let a_to_b_amount = initial_a;
+        let minimum_token_b_amount = 0;
+
+        assert_eq!(
+            Err(SwapError::ZeroTradingTokens.into()),
+            accounts.swap(
+                &swapper_key,
+                &token_a_key,
+                &mut token_a_account,
+                &swap_token_a_key,
+                &swap_token_b_key,
+                &token_b_key,
+                &mut token_b_account,
+                a_to_b_amount,
+                minimum_token_b_amount,
+            )
         );
-        let token_b_offset = 10_000_000_000;
-        check_valid_swap_curve(
-            fees,
-            SwapTransferFees::default(),
-            CurveType::Offset,
-            Arc::new(OffsetCurve { token_b_offset }),
-            token_a_amount,
-            token_b_amount,
-            &pool_token_program_id,
-            &token_a_program_id,
-            &token_b_program_id,
+
+        // swap b to a, succeeds at offset price
+        let b_to_a_amount = initial_b;
+        let minimum_token_a_amount = 0;
+        accounts
+            .swap(
+                &swapper_key,
+                &token_b_key,
+                &mut token_b_account,
+                &swap_token_b_key,
+                &swap_token_a_key,
+                &token_a_key,
+                &mut token_a_account,
+                b_to_a_amount,
+                minimum_token_a_amount,
+            )
+            .unwrap();
+
+        // try a to b again, succeeds due to new liquidity
+        accounts
+            .swap(
+                &swapper_key,
+                &token_a_key,
+                &mut token_a_account,
+                &swap_token_a_key,
+                &swap_token_b_key,
+                &token_b_key,
+                &mut token_b_account,
+                a_to_b_amount,
+                minimum_token_b_amount,
+            )
+            .unwrap();
+
+        // try a to b again, fails due to no more liquidity
+        assert_eq!(
+            Err(SwapError::ZeroTradingTokens.into()),
+            accounts.swap(
+                &swapper_key,
+                &token_a_key,
+                &mut token_a_account,
+                &swap_token_a_key,
+                &swap_token_b_key,
+                &token_b_key,
+                &mut token_b_account,
+                a_to_b_amount,
+                minimum_token_b_amount,
+            )
         );
+
+        // Try to deposit, fails because deposits are not allowed for offset
+        // curve swaps
+        {
+            let initial_a = 100;
+            let initial_b = 100;
+            let pool_amount = 100;
+            let (
+                token_a_key,
+                mut token_a_account,
+                token_b_key,
+                mut token_b_account,
+                pool_key,
+                mut pool_account,
+            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
+            assert_eq!(
+                Err(SwapError::UnsupportedCurveOperation.into()),
+                accounts.deposit_all_token_types(
+                    &swapper_key,
+                    &token_a_key,
+                    &mut token_a_account,
+                    &token_b_key,
+                    &mut token_b_account,
+                    &pool_key,
+                    &mut pool_account,
+                    pool_amount,
+                    initial_a,
+                    initial_b,
+                )
+            );
+        }
     }
 
     #[test_case(spl_token::id(), spl_token::id(), spl_token::id(); "all-token")]
     #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token_2022::id(); "all-token-2022")]
     #[test_case(spl_token::id(), spl_token_2022::id(), spl_token_2022::id(); "mixed-pool-token")]
     #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token::id(); "mixed-pool-token-2022")]
-    fn test_valid_swap_curve_trade_fee_only(
+    fn test_withdraw_all_offset_curve(
         pool_token_program_id: Pubkey,
         token_a_program_id: Pubkey,
         token_b_program_id: Pubkey,
     ) {
         let trade_fee_numerator = 1;
         let trade_fee_denominator = 10;
-        let owner_trade_fee_numerator = 0;
-        let owner_trade_fee_denominator = 0;
+        let owner_trade_fee_numerator = 1;
+        let owner_trade_fee_denominator = 30;
         let owner_withdraw_fee_numerator = 0;
-        let owner_withdraw_fee_denominator = 0;
-        let host_fee_numerator = 0;
-        let host_fee_denominator = 0;
+        let owner_withdraw_fee_denominator = 30;
+        let host_fee_numerator = 10;
+        let host_fee_denominator = 100;
+
+        let token_a_amount = 1_000_000_000;
+        let token_b_amount = 10;
         let fees = Fees {
             trade_fee_numerator,
             trade_fee_denominator,
@@ -6401,69 +8955,96 @@ mod tests {
             host_fee_denominator,
         };
 
-        let token_a_amount = 10_000_000_000;
-        let token_b_amount = 50_000_000_000;
+        let token_b_offset = 2_000_000;
+        let swap_curve = SwapCurve {
+            curve_type: CurveType::Offset,
+            calculator: Arc::new(OffsetCurve { token_b_offset }),
+        };
+        let total_pool = swap_curve.calculator.new_pool_supply();
+        let user_key = Pubkey::new_unique();
+        let withdrawer_key = Pubkey::new_unique();
 
-        check_valid_swap_curve(
-            fees.clone(),
-            SwapTransferFees::default(),
-            CurveType::ConstantProduct,
-            Arc::new(ConstantProductCurve {}),
-            token_a_amount,
-            token_b_amount,
-            &pool_token_program_id,
-            &token_a_program_id,
-            &token_b_program_id,
-        );
-        let token_b_price = 10_000;
-        check_valid_swap_curve(
-            fees.clone(),
-            SwapTransferFees::default(),
-            CurveType::ConstantPrice,
-            Arc::new(ConstantPriceCurve { token_b_price }),
-            token_a_amount,
-            token_b_amount / token_b_price,
-            &pool_token_program_id,
-            &token_a_program_id,
-            &token_b_program_id,
-        );
-        let token_b_offset = 1;
-        check_valid_swap_curve(
+        let mut accounts = SwapAccountInfo::new(
+            &user_key,
             fees,
             SwapTransferFees::default(),
-            CurveType::Offset,
-            Arc::new(OffsetCurve { token_b_offset }),
+            swap_curve,
             token_a_amount,
             token_b_amount,
             &pool_token_program_id,
             &token_a_program_id,
             &token_b_program_id,
         );
+
+        accounts.initialize_swap().unwrap();
+
+        let (
+            token_a_key,
+            mut token_a_account,
+            token_b_key,
+            mut token_b_account,
+            _pool_key,
+            _pool_account,
+        ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, 0, 0, 0);
+
+        let pool_key = accounts.pool_token_key;
+        let mut pool_account = accounts.pool_token_account.clone();
+
+        // WithdrawAllTokenTypes takes all tokens for A and B.
+        // The curve's calculation for token B will say to transfer
+        // `token_b_offset + token_b_amount`, but only `token_b_amount` will be
+        // moved.
+        accounts
+            .withdraw_all_token_types(
+                &user_key,
+                &pool_key,
+                &mut pool_account,
+                &token_a_key,
+                &mut token_a_account,
+                &token_b_key,
+                &mut token_b_account,
+                total_pool.try_into().unwrap(),
+                0,
+                0,
+            )
+            .unwrap();
+
+        let token_a = StateWithExtensions::<Account>::unpack(&token_a_account.data).unwrap();
+        assert_eq!(token_a.base.amount, token_a_amount);
+        let token_b = StateWithExtensions::<Account>::unpack(&token_b_account.data).unwrap();
+        assert_eq!(token_b.base.amount, token_b_amount);
+        let swap_token_a =
+            StateWithExtensions::<Account>::unpack(&accounts.token_a_account.data).unwrap();
+        assert_eq!(swap_token_a.base.amount, 0);
+        let swap_token_b =
+            StateWithExtensions::<Account>::unpack(&accounts.token_b_account.data).unwrap();
+        assert_eq!(swap_token_b.base.amount, 0);
     }
 
     #[test_case(spl_token::id(), spl_token::id(), spl_token::id(); "all-token")]
     #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token_2022::id(); "all-token-2022")]
     #[test_case(spl_token::id(), spl_token_2022::id(), spl_token_2022::id(); "mixed-pool-token")]
     #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token::id(); "mixed-pool-token-2022")]
-    fn test_valid_swap_with_fee_constraints(
+    fn test_withdraw_all_constant_price_curve(
         pool_token_program_id: Pubkey,
         token_a_program_id: Pubkey,
         token_b_program_id: Pubkey,
     ) {
-        let owner_key = Pubkey::new_unique();
-
         let trade_fee_numerator = 1;
         let trade_fee_denominator = 10;
         let owner_trade_fee_numerator = 1;
         let owner_trade_fee_denominator = 30;
-        let owner_withdraw_fee_numerator = 1;
+        let owner_withdraw_fee_numerator = 0;
         let owner_withdraw_fee_denominator = 30;
         let host_fee_numerator = 10;
         let host_fee_denominator = 100;
 
-        let token_a_amount = 1_000_000;
-        let token_b_amount = 5_000_000;
-
+        // initialize "unbalanced", so that withdrawing all will have some issues
+        // A: 1_000_000_000
+        // B: 2_000_000_000 (1_000 * 2_000_000)
+        let swap_token_a_amount = 1_000_000_000;
+        let swap_token_b_amount = 1_000;
+        let token_b_price = 2_000_000;
         let fees = Fees {
             trade_fee_numerator,
             trade_fee_denominator,
@@ -6475,898 +9056,699 @@ mod tests {
             host_fee_denominator,
         };
 
-        let curve = ConstantProductCurve {};
         let swap_curve = SwapCurve {
-            curve_type: CurveType::ConstantProduct,
-            calculator: Arc::new(curve),
+            curve_type: CurveType::ConstantPrice,
+            calculator: Arc::new(ConstantPriceCurve { token_b_price }),
         };
+        let total_pool = swap_curve.calculator.new_pool_supply();
+        let user_key = Pubkey::new_unique();
+        let withdrawer_key = Pubkey::new_unique();
 
-        let owner_key_str = &owner_key.to_string();
-        let valid_curve_types = &[CurveType::ConstantProduct];
-        let constraints = Some(SwapConstraints {
-            owner_key: owner_key_str,
-            valid_curve_types,
-            fees: &fees,
-        });
         let mut accounts = SwapAccountInfo::new(
-            &owner_key,
-            fees.clone(),
+            &user_key,
+            fees,
             SwapTransferFees::default(),
             swap_curve,
-            token_a_amount,
-            token_b_amount,
+            swap_token_a_amount,
+            swap_token_b_amount,
             &pool_token_program_id,
             &token_a_program_id,
             &token_b_program_id,
         );
 
-        // initialize swap
-        do_process_instruction_with_fee_constraints(
-            initialize(
-                &SWAP_PROGRAM_ID,
-                &pool_token_program_id,
-                &accounts.swap_key,
-                &accounts.authority_key,
-                &accounts.token_a_key,
-                &accounts.token_b_key,
-                &accounts.pool_mint_key,
-                &accounts.pool_fee_key,
-                &accounts.pool_token_key,
-                accounts.fees.clone(),
-                accounts.swap_curve.clone(),
-            )
-            .unwrap(),
-            vec![
-                &mut accounts.swap_account,
-                &mut SolanaAccount::default(),
-                &mut accounts.token_a_account,
-                &mut accounts.token_b_account,
-                &mut accounts.pool_mint_account,
-                &mut accounts.pool_fee_account,
-                &mut accounts.pool_token_account,
-                &mut SolanaAccount::default(),
-            ],
-            &constraints,
-        )
-        .unwrap();
-
-        let authority_key = accounts.authority_key;
+        accounts.initialize_swap().unwrap();
 
         let (
             token_a_key,
             mut token_a_account,
             token_b_key,
             mut token_b_account,
-            pool_key,
-            mut pool_account,
-        ) = accounts.setup_token_accounts(
-            &owner_key,
-            &authority_key,
-            token_a_amount,
-            token_b_amount,
-            0,
-        );
+            _pool_key,
+            _pool_account,
+        ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, 0, 0, 0);
 
-        let amount_in = token_a_amount / 2;
-        let minimum_amount_out = 0;
+        let pool_key = accounts.pool_token_key;
+        let mut pool_account = accounts.pool_token_account.clone();
 
-        // perform the swap
-        do_process_instruction_with_fee_constraints(
-            swap(
-                &SWAP_PROGRAM_ID,
-                &token_a_program_id,
-                &token_b_program_id,
-                &pool_token_program_id,
-                &accounts.swap_key,
-                &accounts.authority_key,
-                &accounts.authority_key,
+        // WithdrawAllTokenTypes will not take all token A and B, since their
+        // ratio is unbalanced.  It will try to take 1_500_000_000 worth of
+        // each token, which means 1_500_000_000 token A, and 750 token B.
+        // With no slippage, this will leave 250 token B in the pool.
+        assert_eq!(
+            Err(SwapError::ExceededSlippage.into()),
+            accounts.withdraw_all_token_types(
+                &user_key,
+                &pool_key,
+                &mut pool_account,
                 &token_a_key,
-                &accounts.token_a_key,
-                &accounts.token_b_key,
+                &mut token_a_account,
                 &token_b_key,
-                &accounts.pool_mint_key,
-                &accounts.pool_fee_key,
-                &accounts.token_a_mint_key,
-                &accounts.token_b_mint_key,
-                Some(&pool_key),
-                Swap {
-                    amount_in,
-                    minimum_amount_out,
-                },
+                &mut token_b_account,
+                total_pool.try_into().unwrap(),
+                swap_token_a_amount,
+                swap_token_b_amount,
             )
-            .unwrap(),
-            vec![
-                &mut accounts.swap_account,
-                &mut SolanaAccount::default(),
-                &mut SolanaAccount::default(),
+        );
+
+        accounts
+            .withdraw_all_token_types(
+                &user_key,
+                &pool_key,
+                &mut pool_account,
+                &token_a_key,
+                &mut token_a_account,
+                &token_b_key,
+                &mut token_b_account,
+                total_pool.try_into().unwrap(),
+                0,
+                0,
+            )
+            .unwrap();
+
+        let token_a = StateWithExtensions::<Account>::unpack(&token_a_account.data).unwrap();
+        assert_eq!(token_a.base.amount, swap_token_a_amount);
+        let token_b = StateWithExtensions::<Account>::unpack(&token_b_account.data).unwrap();
+        assert_eq!(token_b.base.amount, 750);
+        let swap_token_a =
+            StateWithExtensions::<Account>::unpack(&accounts.token_a_account.data).unwrap();
+        assert_eq!(swap_token_a.base.amount, 0);
+        let swap_token_b =
+            StateWithExtensions::<Account>::unpack(&accounts.token_b_account.data).unwrap();
+        assert_eq!(swap_token_b.base.amount, 250);
+
+        // deposit now, not enough to cover the tokens already in there
+        let token_b_amount = 10;
+        let token_a_amount = token_b_amount * token_b_price;
+        let (
+            token_a_key,
+            mut token_a_account,
+            token_b_key,
+            mut token_b_account,
+            pool_key,
+            mut pool_account,
+        ) = accounts.setup_token_accounts(
+            &user_key,
+            &withdrawer_key,
+            token_a_amount,
+            token_b_amount,
+            0,
+        );
+
+        assert_eq!(
+            Err(SwapError::ExceededSlippage.into()),
+            accounts.deposit_all_token_types(
+                &withdrawer_key,
+                &token_a_key,
                 &mut token_a_account,
-                &mut accounts.token_a_account,
-                &mut accounts.token_b_account,
+                &token_b_key,
                 &mut token_b_account,
-                &mut accounts.pool_mint_account,
-                &mut accounts.pool_fee_account,
-                &mut accounts.token_a_mint_account,
-                &mut accounts.token_b_mint_account,
-                &mut SolanaAccount::default(),
-                &mut SolanaAccount::default(),
-                &mut SolanaAccount::default(),
+                &pool_key,
                 &mut pool_account,
-            ],
-            &constraints,
-        )
-        .unwrap();
-
-        // check that fees were taken in the host fee account
-        let host_fee_account = StateWithExtensions::<Account>::unpack(&pool_account.data).unwrap();
-        let owner_fee_account =
-            StateWithExtensions::<Account>::unpack(&accounts.pool_fee_account.data).unwrap();
-        let total_fee = owner_fee_account.base.amount * host_fee_denominator
-            / (host_fee_denominator - host_fee_numerator);
-        assert_eq!(
-            total_fee,
-            host_fee_account.base.amount + owner_fee_account.base.amount
+                1, // doesn't matter
+                token_a_amount,
+                token_b_amount,
+            )
         );
-    }
-
-    #[test_case(spl_token::id(), spl_token::id(), spl_token::id(); "all-token")]
-    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token_2022::id(); "all-token-2022")]
-    #[test_case(spl_token::id(), spl_token_2022::id(), spl_token_2022::id(); "mixed-pool-token")]
-    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token::id(); "mixed-pool-token-2022")]
-    fn test_invalid_swap(
-        pool_token_program_id: Pubkey,
-        token_a_program_id: Pubkey,
-        token_b_program_id: Pubkey,
-    ) {
-        let user_key = Pubkey::new_unique();
-        let swapper_key = Pubkey::new_unique();
-        let trade_fee_numerator = 1;
-        let trade_fee_denominator = 4;
-        let owner_trade_fee_numerator = 1;
-        let owner_trade_fee_denominator = 10;
-        let owner_withdraw_fee_numerator = 1;
-        let owner_withdraw_fee_denominator = 5;
-        let host_fee_numerator = 9;
-        let host_fee_denominator = 100;
-        let fees = Fees {
-            trade_fee_numerator,
-            trade_fee_denominator,
-            owner_trade_fee_numerator,
-            owner_trade_fee_denominator,
-            owner_withdraw_fee_numerator,
-            owner_withdraw_fee_denominator,
-            host_fee_numerator,
-            host_fee_denominator,
-        };
 
-        let token_a_amount = 1000;
-        let token_b_amount = 5000;
-        let curve_type = CurveType::ConstantProduct;
-        let swap_curve = SwapCurve {
-            curve_type,
-            calculator: Arc::new(ConstantProductCurve {}),
-        };
-        let mut accounts = SwapAccountInfo::new(
+        // deposit enough tokens, success!
+        let token_b_amount = 125;
+        let token_a_amount = token_b_amount * token_b_price;
+        let (
+            token_a_key,
+            mut token_a_account,
+            token_b_key,
+            mut token_b_account,
+            pool_key,
+            mut pool_account,
+        ) = accounts.setup_token_accounts(
             &user_key,
-            fees,
-            SwapTransferFees::default(),
-            swap_curve,
+            &withdrawer_key,
             token_a_amount,
             token_b_amount,
-            &pool_token_program_id,
-            &token_a_program_id,
-            &token_b_program_id,
+            0,
         );
 
-        let initial_a = token_a_amount / 5;
-        let initial_b = token_b_amount / 5;
-        let minimum_token_b_amount = initial_b / 2;
-
-        let swap_token_a_key = accounts.token_a_key;
-        let swap_token_b_key = accounts.token_b_key;
-
-        // swap not initialized
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            assert_eq!(
-                Err(ProgramError::UninitializedAccount),
-                accounts.swap(
-                    &swapper_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &swap_token_a_key,
-                    &swap_token_b_key,
-                    &token_b_key,
-                    &mut token_b_account,
-                    initial_a,
-                    minimum_token_b_amount,
-                )
-            );
-        }
-
-        accounts.initialize_swap().unwrap();
-
-        // wrong swap account program id
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            let old_swap_account = accounts.swap_account;
-            let mut wrong_swap_account = old_swap_account.clone();
-            wrong_swap_account.owner = pool_token_program_id;
-            accounts.swap_account = wrong_swap_account;
-            assert_eq!(
-                Err(ProgramError::IncorrectProgramId),
-                accounts.swap(
-                    &swapper_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &swap_token_a_key,
-                    &swap_token_b_key,
-                    &token_b_key,
-                    &mut token_b_account,
-                    initial_a,
-                    minimum_token_b_amount,
-                )
-            );
-            accounts.swap_account = old_swap_account;
-        }
-
-        // wrong bump seed
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            let old_authority = accounts.authority_key;
-            let (bad_authority_key, _bump_seed) = Pubkey::find_program_address(
-                &[&accounts.swap_key.to_bytes()[..]],
-                &pool_token_program_id,
-            );
-            accounts.authority_key = bad_authority_key;
-            assert_eq!(
-                Err(SwapError::InvalidProgramAddress.into()),
-                accounts.swap(
-                    &swapper_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &swap_token_a_key,
-                    &swap_token_b_key,
-                    &token_b_key,
-                    &mut token_b_account,
-                    initial_a,
-                    minimum_token_b_amount,
-                )
-            );
-            accounts.authority_key = old_authority;
-        }
-
-        // wrong token program id
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            let wrong_program_id = Pubkey::new_unique();
-            assert_eq!(
-                Err(SwapError::IncorrectTokenProgramId.into()),
-                do_process_instruction(
-                    swap(
-                        &SWAP_PROGRAM_ID,
-                        &wrong_program_id,
-                        &wrong_program_id,
-                        &wrong_program_id,
-                        &accounts.swap_key,
-                        &accounts.authority_key,
-                        &accounts.authority_key,
-                        &token_a_key,
-                        &accounts.token_a_key,
-                        &accounts.token_b_key,
-                        &token_b_key,
-                        &accounts.pool_mint_key,
-                        &accounts.pool_fee_key,
-                        &accounts.token_a_mint_key,
-                        &accounts.token_b_mint_key,
-                        None,
-                        Swap {
-                            amount_in: initial_a,
-                            minimum_amount_out: minimum_token_b_amount,
-                        },
-                    )
-                    .unwrap(),
-                    vec![
-                        &mut accounts.swap_account,
-                        &mut SolanaAccount::default(),
-                        &mut SolanaAccount::default(),
-                        &mut token_a_account,
-                        &mut accounts.token_a_account,
-                        &mut accounts.token_b_account,
-                        &mut token_b_account,
-                        &mut accounts.pool_mint_account,
-                        &mut accounts.pool_fee_account,
-                        &mut accounts.token_a_mint_account,
-                        &mut accounts.token_b_mint_account,
-                        &mut SolanaAccount::default(),
-                        &mut SolanaAccount::default(),
-                        &mut SolanaAccount::default(),
-                    ],
-                ),
-            );
-        }
+        accounts
+            .deposit_all_token_types(
+                &withdrawer_key,
+                &token_a_key,
+                &mut token_a_account,
+                &token_b_key,
+                &mut token_b_account,
+                &pool_key,
+                &mut pool_account,
+                1, // doesn't matter
+                token_a_amount,
+                token_b_amount,
+            )
+            .unwrap();
+    }
 
-        // not enough token a to swap
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            assert_eq!(
-                Err(TokenError::InsufficientFunds.into()),
-                accounts.swap(
-                    &swapper_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &swap_token_a_key,
-                    &swap_token_b_key,
-                    &token_b_key,
-                    &mut token_b_account,
-                    initial_a * 2,
-                    minimum_token_b_amount * 2,
-                )
-            );
-        }
+    #[test_case(spl_token::id(), spl_token::id(), spl_token::id(); "all-token")]
+    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token_2022::id(); "all-token-2022")]
+    #[test_case(spl_token::id(), spl_token_2022::id(), spl_token_2022::id(); "mixed-pool-token")]
+    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token::id(); "mixed-pool-token-2022")]
+    fn test_deposits_allowed_single_token(
+        pool_token_program_id: Pubkey,
+        token_a_program_id: Pubkey,
+        token_b_program_id: Pubkey,
+    ) {
+        let trade_fee_numerator = 1;
+        let trade_fee_denominator = 10;
+        let owner_trade_fee_numerator = 1;
+        let owner_trade_fee_denominator = 30;
+        let owner_withdraw_fee_numerator = 0;
+        let owner_withdraw_fee_denominator = 30;
+        let host_fee_numerator = 10;
+        let host_fee_denominator = 100;
 
-        // wrong swap token A / B accounts
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            let user_transfer_key = Pubkey::new_unique();
-            assert_eq!(
-                Err(SwapError::IncorrectSwapAccount.into()),
-                do_process_instruction(
-                    swap(
-                        &SWAP_PROGRAM_ID,
-                        &token_a_program_id,
-                        &token_b_program_id,
-                        &pool_token_program_id,
-                        &accounts.swap_key,
-                        &accounts.authority_key,
-                        &user_transfer_key,
-                        &token_a_key,
-                        &token_a_key,
-                        &token_b_key,
-                        &token_b_key,
-                        &accounts.pool_mint_key,
-                        &accounts.pool_fee_key,
-                        &accounts.token_a_mint_key,
-                        &accounts.token_b_mint_key,
-                        None,
-                        Swap {
-                            amount_in: initial_a,
-                            minimum_amount_out: minimum_token_b_amount,
-                        },
-                    )
-                    .unwrap(),
-                    vec![
-                        &mut accounts.swap_account,
-                        &mut SolanaAccount::default(),
-                        &mut SolanaAccount::default(),
-                        &mut token_a_account.clone(),
-                        &mut token_a_account,
-                        &mut token_b_account.clone(),
-                        &mut token_b_account,
-                        &mut accounts.pool_mint_account,
-                        &mut accounts.pool_fee_account,
-                        &mut accounts.token_a_mint_account,
-                        &mut accounts.token_b_mint_account,
-                        &mut SolanaAccount::default(),
-                        &mut SolanaAccount::default(),
-                        &mut SolanaAccount::default(),
-                    ],
-                ),
-            );
-        }
+        let token_a_amount = 1_000_000;
+        let token_b_amount = 0;
+        let fees = Fees {
+            trade_fee_numerator,
+            trade_fee_denominator,
+            owner_trade_fee_numerator,
+            owner_trade_fee_denominator,
+            owner_withdraw_fee_numerator,
+            owner_withdraw_fee_denominator,
+            host_fee_numerator,
+            host_fee_denominator,
+        };
 
-        // wrong user token A / B accounts
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            assert_eq!(
-                Err(TokenError::MintMismatch.into()),
-                accounts.swap(
-                    &swapper_key,
-                    &token_b_key,
-                    &mut token_b_account,
-                    &swap_token_a_key,
-                    &swap_token_b_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    initial_a,
-                    minimum_token_b_amount,
-                )
-            );
-        }
+        let token_b_offset = 2_000_000;
+        let swap_curve = SwapCurve {
+            curve_type: CurveType::Offset,
+            calculator: Arc::new(OffsetCurve { token_b_offset }),
+        };
+        let creator_key = Pubkey::new_unique();
+        let depositor_key = Pubkey::new_unique();
 
-        // swap from a to a
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                _token_b_key,
-                _token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            assert_eq!(
-                Err(SwapError::InvalidInput.into()),
-                accounts.swap(
-                    &swapper_key,
-                    &token_a_key,
-                    &mut token_a_account.clone(),
-                    &swap_token_a_key,
-                    &swap_token_a_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    initial_a,
-                    minimum_token_b_amount,
-                )
-            );
-        }
+        let mut accounts = SwapAccountInfo::new(
+            &creator_key,
+            fees,
+            SwapTransferFees::default(),
+            swap_curve,
+            token_a_amount,
+            token_b_amount,
+            &pool_token_program_id,
+            &token_a_program_id,
+            &token_b_program_id,
+        );
 
-        // incorrect mint provided
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            let (pool_mint_key, pool_mint_account) = create_mint(
-                &pool_token_program_id,
-                &accounts.authority_key,
-                None,
-                None,
-                &TransferFee::default(),
-            );
-            let old_pool_key = accounts.pool_mint_key;
-            let old_pool_account = accounts.pool_mint_account;
-            accounts.pool_mint_key = pool_mint_key;
-            accounts.pool_mint_account = pool_mint_account;
+        accounts.initialize_swap().unwrap();
+
+        let initial_a = 1_000_000;
+        let initial_b = 2_000_000;
+        let (
+            _depositor_token_a_key,
+            _depositor_token_a_account,
+            depositor_token_b_key,
+            mut depositor_token_b_account,
+            depositor_pool_key,
+            mut depositor_pool_account,
+        ) = accounts.setup_token_accounts(&creator_key, &depositor_key, initial_a, initial_b, 0);
+
+        assert_eq!(
+            Err(SwapError::UnsupportedCurveOperation.into()),
+            accounts.deposit_single_token_type_exact_amount_in(
+                &depositor_key,
+                &depositor_token_b_key,
+                &mut depositor_token_b_account,
+                &depositor_pool_key,
+                &mut depositor_pool_account,
+                initial_b,
+                0,
+            )
+        );
+    }
+
+    #[test_case(spl_token::id(), spl_token::id(), spl_token::id(); "all-token")]
+    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token_2022::id(); "all-token-2022")]
+    #[test_case(spl_token::id(), spl_token_2022::id(), spl_token_2022::id(); "mixed-pool-token")]
+    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token::id(); "mixed-pool-token-2022")]
+    fn test_paused_swap(
+        pool_token_program_id: Pubkey,
+        token_a_program_id: Pubkey,
+        token_b_program_id: Pubkey,
+    ) {
+        let user_key = Pubkey::new_unique();
+        let fees = Fees {
+            trade_fee_numerator: 1,
+            trade_fee_denominator: 10,
+            owner_trade_fee_numerator: 1,
+            owner_trade_fee_denominator: 30,
+            owner_withdraw_fee_numerator: 1,
+            owner_withdraw_fee_denominator: 30,
+            host_fee_numerator: 10,
+            host_fee_denominator: 100,
+        };
+        let token_a_amount = 1_000_000;
+        let token_b_amount = 2_000_000;
+        let swap_curve = SwapCurve {
+            curve_type: CurveType::ConstantProduct,
+            calculator: Arc::new(ConstantProductCurve {}),
+        };
+        let initial_pool = swap_curve.calculator.new_pool_supply() / 10;
 
-            assert_eq!(
-                Err(SwapError::IncorrectPoolMint.into()),
-                accounts.swap(
-                    &swapper_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &swap_token_a_key,
-                    &swap_token_b_key,
-                    &token_b_key,
-                    &mut token_b_account,
-                    initial_a,
-                    minimum_token_b_amount,
-                )
-            );
+        let mut accounts = SwapAccountInfo::new(
+            &user_key,
+            fees,
+            SwapTransferFees::default(),
+            swap_curve,
+            token_a_amount,
+            token_b_amount,
+            &pool_token_program_id,
+            &token_a_program_id,
+            &token_b_program_id,
+        );
 
-            accounts.pool_mint_key = old_pool_key;
-            accounts.pool_mint_account = old_pool_account;
-        }
+        // cannot pause before initialization
+        assert_eq!(
+            Err(ProgramError::UninitializedAccount),
+            accounts.set_paused(&user_key, true)
+        );
 
-        // incorrect fee account provided
+        accounts.initialize_swap().unwrap();
+        let swap_state = SwapVersion::unpack(&accounts.swap_account.data).unwrap();
+        assert!(!swap_state.is_paused());
+        assert_eq!(swap_state.pause_authority(), Some(&user_key));
+
+        let user_account_owner = Pubkey::new_unique();
+        let (
+            token_a_key,
+            mut token_a_account,
+            token_b_key,
+            mut token_b_account,
+            pool_key,
+            mut pool_account,
+        ) = accounts.setup_token_accounts(
+            &user_key,
+            &user_account_owner,
+            token_a_amount,
+            token_b_amount,
+            initial_pool.try_into().unwrap(),
+        );
+
+        // wrong pause authority
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                wrong_pool_key,
-                wrong_pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            let old_pool_fee_account = accounts.pool_fee_account;
-            let old_pool_fee_key = accounts.pool_fee_key;
-            accounts.pool_fee_account = wrong_pool_account;
-            accounts.pool_fee_key = wrong_pool_key;
+            let wrong_key = Pubkey::new_unique();
             assert_eq!(
-                Err(SwapError::IncorrectFeeAccount.into()),
-                accounts.swap(
-                    &swapper_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &swap_token_a_key,
-                    &swap_token_b_key,
-                    &token_b_key,
-                    &mut token_b_account,
-                    initial_a,
-                    minimum_token_b_amount,
-                )
+                Err(SwapError::IncorrectPauseAuthority.into()),
+                accounts.set_paused(&wrong_key, true)
             );
-            accounts.pool_fee_account = old_pool_fee_account;
-            accounts.pool_fee_key = old_pool_fee_key;
         }
 
-        // no approval
+        // pause authority must sign
         {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            let user_transfer_key = Pubkey::new_unique();
+            let mut instruction = set_paused(
+                &SWAP_PROGRAM_ID,
+                &accounts.swap_key,
+                &user_key,
+                SetPaused { paused: true },
+            )
+            .unwrap();
+            instruction.accounts[1].is_signer = false;
             assert_eq!(
-                Err(TokenError::OwnerMismatch.into()),
+                Err(ProgramError::MissingRequiredSignature),
                 do_process_instruction(
-                    swap(
-                        &SWAP_PROGRAM_ID,
-                        &token_a_program_id,
-                        &token_b_program_id,
-                        &pool_token_program_id,
-                        &accounts.swap_key,
-                        &accounts.authority_key,
-                        &user_transfer_key,
-                        &token_a_key,
-                        &accounts.token_a_key,
-                        &accounts.token_b_key,
-                        &token_b_key,
-                        &accounts.pool_mint_key,
-                        &accounts.pool_fee_key,
-                        &accounts.token_a_mint_key,
-                        &accounts.token_b_mint_key,
-                        None,
-                        Swap {
-                            amount_in: initial_a,
-                            minimum_amount_out: minimum_token_b_amount,
-                        },
-                    )
-                    .unwrap(),
-                    vec![
-                        &mut accounts.swap_account,
-                        &mut SolanaAccount::default(),
-                        &mut SolanaAccount::default(),
-                        &mut token_a_account,
-                        &mut accounts.token_a_account,
-                        &mut accounts.token_b_account,
-                        &mut token_b_account,
-                        &mut accounts.pool_mint_account,
-                        &mut accounts.pool_fee_account,
-                        &mut accounts.token_a_mint_account,
-                        &mut accounts.token_b_mint_account,
-                        &mut SolanaAccount::default(),
-                        &mut SolanaAccount::default(),
-                        &mut SolanaAccount::default(),
-                    ],
-                ),
+                    instruction,
+                    vec![&mut accounts.swap_account, &mut SolanaAccount::default()],
+                )
             );
         }
 
-        // output token value 0
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            assert_eq!(
-                Err(SwapError::ZeroTradingTokens.into()),
-                accounts.swap(
-                    &swapper_key,
-                    &token_b_key,
-                    &mut token_b_account,
-                    &swap_token_b_key,
-                    &swap_token_a_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    1,
-                    1,
-                )
-            );
-        }
+        accounts.set_paused(&user_key, true).unwrap();
+        let swap_state = SwapVersion::unpack(&accounts.swap_account.data).unwrap();
+        assert!(swap_state.is_paused());
+
+        let swap_token_a_key = accounts.token_a_key;
+        let swap_token_b_key = accounts.token_b_key;
+        assert_eq!(
+            Err(SwapError::SwapPaused.into()),
+            accounts.swap(
+                &user_account_owner,
+                &token_a_key,
+                &mut token_a_account,
+                &swap_token_a_key,
+                &swap_token_b_key,
+                &token_b_key,
+                &mut token_b_account,
+                1_000,
+                0,
+            )
+        );
+        assert_eq!(
+            Err(SwapError::SwapPaused.into()),
+            accounts.deposit_all_token_types(
+                &user_account_owner,
+                &token_a_key,
+                &mut token_a_account,
+                &token_b_key,
+                &mut token_b_account,
+                &pool_key,
+                &mut pool_account,
+                1_000,
+                token_a_amount,
+                token_b_amount,
+            )
+        );
+        assert_eq!(
+            Err(SwapError::SwapPaused.into()),
+            accounts.deposit_single_token_type_exact_amount_in(
+                &user_account_owner,
+                &token_a_key,
+                &mut token_a_account,
+                &pool_key,
+                &mut pool_account,
+                1_000,
+                0,
+            )
+        );
+        assert_eq!(
+            Err(SwapError::SwapPaused.into()),
+            accounts.withdraw_single_token_type_exact_amount_out(
+                &user_account_owner,
+                &pool_key,
+                &mut pool_account,
+                &token_a_key,
+                &mut token_a_account,
+                1_000,
+                u64::MAX,
+            )
+        );
+
+        // withdrawing all token types is still allowed
+        let withdraw_amount: u64 = (initial_pool / 4).try_into().unwrap();
+        accounts
+            .withdraw_all_token_types(
+                &user_account_owner,
+                &pool_key,
+                &mut pool_account,
+                &token_a_key,
+                &mut token_a_account,
+                &token_b_key,
+                &mut token_b_account,
+                withdraw_amount,
+                0,
+                0,
+            )
+            .unwrap();
+
+        // hand pausing over to a new authority, the old one loses it
+        let new_pause_authority = Pubkey::new_unique();
+        assert_eq!(
+            Err(SwapError::IncorrectPauseAuthority.into()),
+            accounts.set_pause_authority(&new_pause_authority, &new_pause_authority)
+        );
+        accounts
+            .set_pause_authority(&user_key, &new_pause_authority)
+            .unwrap();
+        let swap_state = SwapVersion::unpack(&accounts.swap_account.data).unwrap();
+        assert_eq!(swap_state.pause_authority(), Some(&new_pause_authority));
+        assert!(swap_state.is_paused());
+        assert_eq!(
+            Err(SwapError::IncorrectPauseAuthority.into()),
+            accounts.set_paused(&user_key, false)
+        );
+
+        // resume, trading works again
+        accounts.set_paused(&new_pause_authority, false).unwrap();
+        let swap_state = SwapVersion::unpack(&accounts.swap_account.data).unwrap();
+        assert!(!swap_state.is_paused());
+        accounts
+            .swap(
+                &user_account_owner,
+                &token_a_key,
+                &mut token_a_account,
+                &swap_token_a_key,
+                &swap_token_b_key,
+                &token_b_key,
+                &mut token_b_account,
+                1_000,
+                0,
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_v1_swap_cannot_be_paused() {
+        let user_key = Pubkey::new_unique();
+        let fees = Fees {
+            trade_fee_numerator: 1,
+            trade_fee_denominator: 10,
+            owner_trade_fee_numerator: 1,
+            owner_trade_fee_denominator: 30,
+            owner_withdraw_fee_numerator: 1,
+            owner_withdraw_fee_denominator: 30,
+            host_fee_numerator: 10,
+            host_fee_denominator: 100,
+        };
+        let swap_curve = SwapCurve {
+            curve_type: CurveType::ConstantProduct,
+            calculator: Arc::new(ConstantProductCurve {}),
+        };
+
+        let mut accounts = SwapAccountInfo::new(
+            &user_key,
+            fees,
+            SwapTransferFees::default(),
+            swap_curve,
+            1_000_000,
+            2_000_000,
+            &spl_token::id(),
+            &spl_token::id(),
+            &spl_token::id(),
+        );
+
+        // account too small for any version
+        accounts.swap_account.data = vec![0; SwapVersion::V1_LEN - 1];
+        assert_eq!(
+            Err(ProgramError::AccountDataTooSmall),
+            accounts.initialize_swap()
+        );
+
+        // account larger than the latest layout
+        accounts.swap_account.data = vec![0; SwapVersion::LATEST_LEN + 1];
+        assert_eq!(
+            Err(ProgramError::InvalidAccountData),
+            accounts.initialize_swap()
+        );
+        assert_eq!(accounts.swap_account.data[0], 0);
+
+        // account sized for the original layout
+        accounts.swap_account.data = vec![0; SwapVersion::V1_LEN];
+        accounts.initialize_swap().unwrap();
+        assert_eq!(accounts.swap_account.data[0], 1);
+        let swap_state = SwapVersion::unpack(&accounts.swap_account.data).unwrap();
+        assert!(!swap_state.is_paused());
+        assert_eq!(swap_state.pause_authority(), None);
+
+        // legacy swaps are deliberately left without a pause authority, so
+        // neither pausing nor assigning an authority is possible
+        assert_eq!(
+            Err(SwapError::UnsupportedSwapVersion.into()),
+            accounts.set_paused(&user_key, true)
+        );
+        assert_eq!(
+            Err(SwapError::UnsupportedSwapVersion.into()),
+            accounts.set_pause_authority(&user_key, &user_key)
+        );
+        let swap_state = SwapVersion::unpack(&accounts.swap_account.data).unwrap();
+        assert_eq!(swap_state.pause_authority(), None);
+    }
+
+    #[test_case(spl_token::id(), spl_token::id(), spl_token::id(); "all-token")]
+    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token_2022::id(); "all-token-2022")]
+    #[test_case(spl_token::id(), spl_token_2022::id(), spl_token_2022::id(); "mixed-pool-token")]
+    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token::id(); "mixed-pool-token-2022")]
+    fn test_withdraw_with_invalid_fee_account(
+        pool_token_program_id: Pubkey,
+        token_a_program_id: Pubkey,
+        token_b_program_id: Pubkey,
+    ) {
+        let user_key = Pubkey::new_unique();
 
-        // slippage exceeded: minimum out amount too high
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            assert_eq!(
-                Err(SwapError::ExceededSlippage.into()),
-                accounts.swap(
-                    &swapper_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &swap_token_a_key,
-                    &swap_token_b_key,
-                    &token_b_key,
-                    &mut token_b_account,
-                    initial_a,
-                    minimum_token_b_amount * 2,
-                )
-            );
-        }
+        let fees = Fees {
+            trade_fee_numerator: 1,
+            trade_fee_denominator: 2,
+            owner_trade_fee_numerator: 1,
+            owner_trade_fee_denominator: 10,
+            owner_withdraw_fee_numerator: 1,
+            owner_withdraw_fee_denominator: 5,
+            host_fee_numerator: 7,
+            host_fee_denominator: 100,
+        };
 
-        // invalid input: can't use swap pool as user source / dest
-        {
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            let mut swap_token_a_account = accounts.get_token_account(&swap_token_a_key).clone();
-            let authority_key = accounts.authority_key;
-            assert_eq!(
-                Err(SwapError::InvalidInput.into()),
-                accounts.swap(
-                    &authority_key,
-                    &swap_token_a_key,
-                    &mut swap_token_a_account,
-                    &swap_token_a_key,
-                    &swap_token_b_key,
-                    &token_b_key,
-                    &mut token_b_account,
-                    initial_a,
-                    minimum_token_b_amount,
-                )
-            );
-            let mut swap_token_b_account = accounts.get_token_account(&swap_token_b_key).clone();
-            assert_eq!(
-                Err(SwapError::InvalidInput.into()),
-                accounts.swap(
-                    &swapper_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &swap_token_a_key,
-                    &swap_token_b_key,
-                    &swap_token_b_key,
-                    &mut swap_token_b_account,
-                    initial_a,
-                    minimum_token_b_amount,
-                )
-            );
-        }
+        let token_a_amount = 1000;
+        let token_b_amount = 2000;
+        let swap_curve = SwapCurve {
+            curve_type: CurveType::ConstantProduct,
+            calculator: Arc::new(ConstantProductCurve {}),
+        };
 
-        // still correct: constraint specified, no host fee account
-        {
-            let authority_key = accounts.authority_key;
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &authority_key, initial_a, initial_b, 0);
-            let owner_key = &swapper_key.to_string();
-            let fees = Fees {
-                trade_fee_numerator,
-                trade_fee_denominator,
-                owner_trade_fee_numerator,
-                owner_trade_fee_denominator,
-                owner_withdraw_fee_numerator,
-                owner_withdraw_fee_denominator,
-                host_fee_numerator,
-                host_fee_denominator,
-            };
-            let constraints = Some(SwapConstraints {
-                owner_key,
-                valid_curve_types: &[],
-                fees: &fees,
-            });
-            do_process_instruction_with_fee_constraints(
-                swap(
-                    &SWAP_PROGRAM_ID,
-                    &token_a_program_id,
-                    &token_b_program_id,
-                    &pool_token_program_id,
-                    &accounts.swap_key,
-                    &accounts.authority_key,
-                    &accounts.authority_key,
-                    &token_a_key,
-                    &accounts.token_a_key,
-                    &accounts.token_b_key,
-                    &token_b_key,
-                    &accounts.pool_mint_key,
-                    &accounts.pool_fee_key,
-                    &accounts.token_a_mint_key,
-                    &accounts.token_b_mint_key,
-                    None,
-                    Swap {
-                        amount_in: initial_a,
-                        minimum_amount_out: minimum_token_b_amount,
-                    },
-                )
-                .unwrap(),
-                vec![
-                    &mut accounts.swap_account,
-                    &mut SolanaAccount::default(),
-                    &mut SolanaAccount::default(),
-                    &mut token_a_account,
-                    &mut accounts.token_a_account,
-                    &mut accounts.token_b_account,
-                    &mut token_b_account,
-                    &mut accounts.pool_mint_account,
-                    &mut accounts.pool_fee_account,
-                    &mut accounts.token_a_mint_account,
-                    &mut accounts.token_b_mint_account,
-                    &mut SolanaAccount::default(),
-                    &mut SolanaAccount::default(),
-                    &mut SolanaAccount::default(),
-                ],
-                &constraints,
+        let withdrawer_key = Pubkey::new_unique();
+        let initial_a = token_a_amount / 10;
+        let initial_b = token_b_amount / 10;
+        let initial_pool = swap_curve.calculator.new_pool_supply() / 10;
+        let withdraw_amount = initial_pool / 4;
+        let minimum_token_a_amount = initial_a / 40;
+        let minimum_token_b_amount = initial_b / 40;
+
+        let mut accounts = SwapAccountInfo::new(
+            &user_key,
+            fees,
+            SwapTransferFees::default(),
+            swap_curve,
+            token_a_amount,
+            token_b_amount,
+            &pool_token_program_id,
+            &token_a_program_id,
+            &token_b_program_id,
+        );
+
+        accounts.initialize_swap().unwrap();
+
+        let (
+            token_a_key,
+            mut token_a_account,
+            token_b_key,
+            mut token_b_account,
+            pool_key,
+            mut pool_account,
+        ) = accounts.setup_token_accounts(
+            &user_key,
+            &withdrawer_key,
+            initial_a,
+            initial_b,
+            initial_pool.try_into().unwrap(),
+        );
+
+        let destination_key = Pubkey::new_unique();
+        let mut destination = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &withdrawer_key,
+        );
+
+        do_process_instruction(
+            close_account(
+                &pool_token_program_id,
+                &accounts.pool_fee_key,
+                &destination_key,
+                &user_key,
+                &[],
+            )
+            .unwrap(),
+            vec![
+                &mut accounts.pool_fee_account,
+                &mut destination,
+                &mut SolanaAccount::default(),
+            ],
+        )
+        .unwrap();
+
+        let user_transfer_authority_key = Pubkey::new_unique();
+        let pool_token_amount = withdraw_amount.try_into().unwrap();
+
+        do_process_instruction(
+            approve(
+                &pool_token_program_id,
+                &pool_key,
+                &user_transfer_authority_key,
+                &withdrawer_key,
+                &[],
+                pool_token_amount,
             )
-            .unwrap();
-        }
+            .unwrap(),
+            vec![
+                &mut pool_account,
+                &mut SolanaAccount::default(),
+                &mut SolanaAccount::default(),
+            ],
+        )
+        .unwrap();
 
-        // invalid mint for host fee account
-        {
-            let authority_key = accounts.authority_key;
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &authority_key, initial_a, initial_b, 0);
-            let (
-                bad_token_a_key,
-                mut bad_token_a_account,
-                _token_b_key,
-                mut _token_b_account,
-                _pool_key,
-                _pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &authority_key, initial_a, initial_b, 0);
-            let owner_key = &swapper_key.to_string();
-            let fees = Fees {
-                trade_fee_numerator,
-                trade_fee_denominator,
-                owner_trade_fee_numerator,
-                owner_trade_fee_denominator,
-                owner_withdraw_fee_numerator,
-                owner_withdraw_fee_denominator,
-                host_fee_numerator,
-                host_fee_denominator,
-            };
-            let constraints = Some(SwapConstraints {
-                owner_key,
-                valid_curve_types: &[],
-                fees: &fees,
-            });
-            assert_eq!(
-                Err(SwapError::IncorrectPoolMint.into()),
-                do_process_instruction_with_fee_constraints(
-                    swap(
-                        &SWAP_PROGRAM_ID,
-                        &token_a_program_id,
-                        &token_b_program_id,
-                        &pool_token_program_id,
-                        &accounts.swap_key,
-                        &accounts.authority_key,
-                        &accounts.authority_key,
-                        &token_a_key,
-                        &accounts.token_a_key,
-                        &accounts.token_b_key,
-                        &token_b_key,
-                        &accounts.pool_mint_key,
-                        &accounts.pool_fee_key,
-                        &accounts.token_a_mint_key,
-                        &accounts.token_b_mint_key,
-                        Some(&bad_token_a_key),
-                        Swap {
-                            amount_in: initial_a,
-                            minimum_amount_out: 0,
-                        },
-                    )
-                    .unwrap(),
-                    vec![
-                        &mut accounts.swap_account,
-                        &mut SolanaAccount::default(),
-                        &mut SolanaAccount::default(),
-                        &mut token_a_account,
-                        &mut accounts.token_a_account,
-                        &mut accounts.token_b_account,
-                        &mut token_b_account,
-                        &mut accounts.pool_mint_account,
-                        &mut accounts.pool_fee_account,
-                        &mut accounts.token_a_mint_account,
-                        &mut accounts.token_b_mint_account,
-                        &mut SolanaAccount::default(),
-                        &mut SolanaAccount::default(),
-                        &mut SolanaAccount::default(),
-                        &mut bad_token_a_account,
-                    ],
-                    &constraints,
-                ),
-            );
-        }
+        do_process_instruction(
+            withdraw_all_token_types(
+                &SWAP_PROGRAM_ID,
+                &pool_token_program_id,
+                &token_a_program_id,
+                &token_b_program_id,
+                &accounts.swap_key,
+                &accounts.authority_key,
+                &user_transfer_authority_key,
+                &accounts.pool_mint_key,
+                &accounts.pool_fee_key,
+                &pool_key,
+                &accounts.token_a_key,
+                &accounts.token_b_key,
+                &token_a_key,
+                &token_b_key,
+                &accounts.token_a_mint_key,
+                &accounts.token_b_mint_key,
+                WithdrawAllTokenTypes {
+                    pool_token_amount,
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                },
+            )
+            .unwrap(),
+            vec![
+                &mut accounts.swap_account,
+                &mut SolanaAccount::default(),
+                &mut SolanaAccount::default(),
+                &mut accounts.pool_mint_account,
+                &mut pool_account,
+                &mut accounts.token_a_account,
+                &mut accounts.token_b_account,
+                &mut token_a_account,
+                &mut token_b_account,
+                &mut accounts.pool_fee_account,
+                &mut accounts.token_a_mint_account,
+                &mut accounts.token_b_mint_account,
+                &mut SolanaAccount::default(),
+                &mut SolanaAccount::default(),
+                &mut SolanaAccount::default(),
+            ],
+        )
+        .unwrap();
     }
 
     #[test_case(spl_token::id(), spl_token::id(), spl_token::id(); "all-token")]
     #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token_2022::id(); "all-token-2022")]
     #[test_case(spl_token::id(), spl_token_2022::id(), spl_token_2022::id(); "mixed-pool-token")]
     #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token::id(); "mixed-pool-token-2022")]
-    fn test_overdraw_offset_curve(
+    fn test_withdraw_one_exact_out_with_invalid_fee_account(
         pool_token_program_id: Pubkey,
         token_a_program_id: Pubkey,
         token_b_program_id: Pubkey,
     ) {
-        let trade_fee_numerator = 1;
-        let trade_fee_denominator = 10;
-        let owner_trade_fee_numerator = 1;
-        let owner_trade_fee_denominator = 30;
-        let owner_withdraw_fee_numerator = 1;
-        let owner_withdraw_fee_denominator = 30;
-        let host_fee_numerator = 10;
-        let host_fee_denominator = 100;
+        let user_key = Pubkey::new_unique();
 
-        let token_a_amount = 1_000_000_000;
-        let token_b_amount = 0;
         let fees = Fees {
-            trade_fee_numerator,
-            trade_fee_denominator,
-            owner_trade_fee_numerator,
-            owner_trade_fee_denominator,
-            owner_withdraw_fee_numerator,
-            owner_withdraw_fee_denominator,
-            host_fee_numerator,
-            host_fee_denominator,
+            trade_fee_numerator: 1,
+            trade_fee_denominator: 2,
+            owner_trade_fee_numerator: 1,
+            owner_trade_fee_denominator: 10,
+            owner_withdraw_fee_numerator: 1,
+            owner_withdraw_fee_denominator: 5,
+            host_fee_numerator: 7,
+            host_fee_denominator: 100,
         };
 
-        let token_b_offset = 2_000_000;
+        let token_a_amount = 1000;
+        let token_b_amount = 2000;
         let swap_curve = SwapCurve {
-            curve_type: CurveType::Offset,
-            calculator: Arc::new(OffsetCurve { token_b_offset }),
+            curve_type: CurveType::ConstantProduct,
+            calculator: Arc::new(ConstantProductCurve {}),
         };
-        let user_key = Pubkey::new_unique();
-        let swapper_key = Pubkey::new_unique();
+
+        let withdrawer_key = Pubkey::new_unique();
+        let initial_a = token_a_amount / 10;
+        let initial_b = token_b_amount / 10;
+        let initial_pool = swap_curve.calculator.new_pool_supply() / 10;
+        let maximum_pool_token_amount = to_u64(initial_pool / 4).unwrap();
+        let destination_a_amount = initial_a / 40;
 
         let mut accounts = SwapAccountInfo::new(
             &user_key,
@@ -7382,162 +9764,143 @@ mod tests {
 
         accounts.initialize_swap().unwrap();
 
-        let swap_token_a_key = accounts.token_a_key;
-        let swap_token_b_key = accounts.token_b_key;
-        let initial_a = 500_000;
-        let initial_b = 1_000;
-
         let (
             token_a_key,
             mut token_a_account,
-            token_b_key,
-            mut token_b_account,
-            _pool_key,
-            _pool_account,
-        ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-
-        // swap a to b way, fails, there's no liquidity
-        let a_to_b_amount = initial_a;
-        let minimum_token_b_amount = 0;
+            _token_b_key,
+            _token_b_account,
+            pool_key,
+            mut pool_account,
+        ) = accounts.setup_token_accounts(
+            &user_key,
+            &withdrawer_key,
+            initial_a,
+            initial_b,
+            initial_pool.try_into().unwrap(),
+        );
 
-        assert_eq!(
-            Err(SwapError::ZeroTradingTokens.into()),
-            accounts.swap(
-                &swapper_key,
-                &token_a_key,
-                &mut token_a_account,
-                &swap_token_a_key,
-                &swap_token_b_key,
-                &token_b_key,
-                &mut token_b_account,
-                a_to_b_amount,
-                minimum_token_b_amount,
-            )
+        let destination_key = Pubkey::new_unique();
+        let mut destination = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            &withdrawer_key,
         );
 
-        // swap b to a, succeeds at offset price
-        let b_to_a_amount = initial_b;
-        let minimum_token_a_amount = 0;
-        accounts
-            .swap(
-                &swapper_key,
-                &token_b_key,
-                &mut token_b_account,
-                &swap_token_b_key,
-                &swap_token_a_key,
-                &token_a_key,
-                &mut token_a_account,
-                b_to_a_amount,
-                minimum_token_a_amount,
+        do_process_instruction(
+            close_account(
+                &pool_token_program_id,
+                &accounts.pool_fee_key,
+                &destination_key,
+                &user_key,
+                &[],
             )
-            .unwrap();
+            .unwrap(),
+            vec![
+                &mut accounts.pool_fee_account,
+                &mut destination,
+                &mut SolanaAccount::default(),
+            ],
+        )
+        .unwrap();
 
-        // try a to b again, succeeds due to new liquidity
-        accounts
-            .swap(
-                &swapper_key,
-                &token_a_key,
-                &mut token_a_account,
-                &swap_token_a_key,
-                &swap_token_b_key,
-                &token_b_key,
-                &mut token_b_account,
-                a_to_b_amount,
-                minimum_token_b_amount,
+        let user_transfer_authority_key = Pubkey::new_unique();
+
+        do_process_instruction(
+            approve(
+                &pool_token_program_id,
+                &pool_key,
+                &user_transfer_authority_key,
+                &withdrawer_key,
+                &[],
+                maximum_pool_token_amount,
             )
-            .unwrap();
+            .unwrap(),
+            vec![
+                &mut pool_account,
+                &mut SolanaAccount::default(),
+                &mut SolanaAccount::default(),
+            ],
+        )
+        .unwrap();
 
-        // try a to b again, fails due to no more liquidity
-        assert_eq!(
-            Err(SwapError::ZeroTradingTokens.into()),
-            accounts.swap(
-                &swapper_key,
+        do_process_instruction(
+            withdraw_single_token_type_exact_amount_out(
+                &SWAP_PROGRAM_ID,
+                &pool_token_program_id,
+                &token_a_program_id,
+                &accounts.swap_key,
+                &accounts.authority_key,
+                &user_transfer_authority_key,
+                &accounts.pool_mint_key,
+                &accounts.pool_fee_key,
+                &pool_key,
+                &accounts.token_a_key,
+                &accounts.token_b_key,
                 &token_a_key,
-                &mut token_a_account,
-                &swap_token_a_key,
-                &swap_token_b_key,
-                &token_b_key,
-                &mut token_b_account,
-                a_to_b_amount,
-                minimum_token_b_amount,
+                &accounts.token_a_mint_key,
+                WithdrawSingleTokenTypeExactAmountOut {
+                    destination_token_amount: destination_a_amount,
+                    maximum_pool_token_amount,
+                },
             )
-        );
-
-        // Try to deposit, fails because deposits are not allowed for offset
-        // curve swaps
-        {
-            let initial_a = 100;
-            let initial_b = 100;
-            let pool_amount = 100;
-            let (
-                token_a_key,
-                mut token_a_account,
-                token_b_key,
-                mut token_b_account,
-                pool_key,
-                mut pool_account,
-            ) = accounts.setup_token_accounts(&user_key, &swapper_key, initial_a, initial_b, 0);
-            assert_eq!(
-                Err(SwapError::UnsupportedCurveOperation.into()),
-                accounts.deposit_all_token_types(
-                    &swapper_key,
-                    &token_a_key,
-                    &mut token_a_account,
-                    &token_b_key,
-                    &mut token_b_account,
-                    &pool_key,
-                    &mut pool_account,
-                    pool_amount,
-                    initial_a,
-                    initial_b,
-                )
-            );
-        }
+            .unwrap(),
+            vec![
+                &mut accounts.swap_account,
+                &mut SolanaAccount::default(),
+                &mut SolanaAccount::default(),
+                &mut accounts.pool_mint_account,
+                &mut pool_account,
+                &mut accounts.token_a_account,
+                &mut accounts.token_b_account,
+                &mut token_a_account,
+                &mut accounts.pool_fee_account,
+                &mut accounts.token_a_mint_account,
+                &mut SolanaAccount::default(),
+                &mut SolanaAccount::default(),
+            ],
+        )
+        .unwrap();
     }
 
     #[test_case(spl_token::id(), spl_token::id(), spl_token::id(); "all-token")]
     #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token_2022::id(); "all-token-2022")]
     #[test_case(spl_token::id(), spl_token_2022::id(), spl_token_2022::id(); "mixed-pool-token")]
     #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token::id(); "mixed-pool-token-2022")]
-    fn test_withdraw_all_offset_curve(
+    fn test_valid_swap_with_invalid_fee_account(
         pool_token_program_id: Pubkey,
         token_a_program_id: Pubkey,
         token_b_program_id: Pubkey,
     ) {
-        let trade_fee_numerator = 1;
-        let trade_fee_denominator = 10;
-        let owner_trade_fee_numerator = 1;
-        let owner_trade_fee_denominator = 30;
-        let owner_withdraw_fee_numerator = 0;
-        let owner_withdraw_fee_denominator = 30;
-        let host_fee_numerator = 10;
-        let host_fee_denominator = 100;
+        let owner_key = &Pubkey::new_unique();
+
+        let token_a_amount = 1_000_000;
+        let token_b_amount = 5_000_000;
 
-        let token_a_amount = 1_000_000_000;
-        let token_b_amount = 10;
         let fees = Fees {
-            trade_fee_numerator,
-            trade_fee_denominator,
-            owner_trade_fee_numerator,
-            owner_trade_fee_denominator,
-            owner_withdraw_fee_numerator,
-            owner_withdraw_fee_denominator,
-            host_fee_numerator,
-            host_fee_denominator,
+            trade_fee_numerator: 1,
+            trade_fee_denominator: 10,
+            owner_trade_fee_numerator: 1,
+            owner_trade_fee_denominator: 30,
+            owner_withdraw_fee_numerator: 1,
+            owner_withdraw_fee_denominator: 30,
+            host_fee_numerator: 10,
+            host_fee_denominator: 100,
         };
 
-        let token_b_offset = 2_000_000;
         let swap_curve = SwapCurve {
-            curve_type: CurveType::Offset,
-            calculator: Arc::new(OffsetCurve { token_b_offset }),
+            curve_type: CurveType::ConstantProduct,
+            calculator: Arc::new(ConstantProductCurve {}),
         };
-        let total_pool = swap_curve.calculator.new_pool_supply();
-        let user_key = Pubkey::new_unique();
-        let withdrawer_key = Pubkey::new_unique();
 
+        let owner_key_str = &owner_key.to_string();
+        let constraints = Some(SwapConstraints {
+            owner_key: owner_key_str,
+            valid_curve_types: &[CurveType::ConstantProduct],
+            fees: &fees,
+        });
         let mut accounts = SwapAccountInfo::new(
-            &user_key,
-            fees,
+            owner_key,
+            fees.clone(),
             SwapTransferFees::default(),
             swap_curve,
             token_a_amount,
@@ -7547,75 +9910,139 @@ mod tests {
             &token_b_program_id,
         );
 
-        accounts.initialize_swap().unwrap();
+        do_process_instruction_with_fee_constraints(
+            initialize(
+                &SWAP_PROGRAM_ID,
+                &pool_token_program_id,
+                &accounts.swap_key,
+                &accounts.authority_key,
+                &accounts.token_a_key,
+                &accounts.token_b_key,
+                &accounts.pool_mint_key,
+                &accounts.pool_fee_key,
+                &accounts.pool_token_key,
+                accounts.fees.clone(),
+                accounts.swap_curve.clone(),
+            )
+            .unwrap(),
+            vec![
+                &mut accounts.swap_account,
+                &mut SolanaAccount::default(),
+                &mut accounts.token_a_account,
+                &mut accounts.token_b_account,
+                &mut accounts.pool_mint_account,
+                &mut accounts.pool_fee_account,
+                &mut accounts.pool_token_account,
+                &mut SolanaAccount::default(),
+            ],
+            &constraints,
+        )
+        .unwrap();
+
+        let authority_key = accounts.authority_key;
 
         let (
             token_a_key,
             mut token_a_account,
             token_b_key,
             mut token_b_account,
-            _pool_key,
-            _pool_account,
-        ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, 0, 0, 0);
+            pool_key,
+            mut pool_account,
+        ) = accounts.setup_token_accounts(
+            owner_key,
+            &authority_key,
+            token_a_amount,
+            token_b_amount,
+            0,
+        );
 
-        let pool_key = accounts.pool_token_key;
-        let mut pool_account = accounts.pool_token_account.clone();
+        let destination_key = Pubkey::new_unique();
+        let mut destination = SolanaAccount::new(
+            account_minimum_balance(),
+            Account::get_packed_len(),
+            owner_key,
+        );
 
-        // WithdrawAllTokenTypes takes all tokens for A and B.
-        // The curve's calculation for token B will say to transfer
-        // `token_b_offset + token_b_amount`, but only `token_b_amount` will be
-        // moved.
-        accounts
-            .withdraw_all_token_types(
-                &user_key,
-                &pool_key,
-                &mut pool_account,
+        do_process_instruction(
+            close_account(
+                &pool_token_program_id,
+                &accounts.pool_fee_key,
+                &destination_key,
+                owner_key,
+                &[],
+            )
+            .unwrap(),
+            vec![
+                &mut accounts.pool_fee_account,
+                &mut destination,
+                &mut SolanaAccount::default(),
+            ],
+        )
+        .unwrap();
+
+        do_process_instruction_with_fee_constraints(
+            swap(
+                &SWAP_PROGRAM_ID,
+                &token_a_program_id,
+                &token_b_program_id,
+                &pool_token_program_id,
+                &accounts.swap_key,
+                &accounts.authority_key,
+                &accounts.authority_key,
                 &token_a_key,
+                &accounts.token_a_key,
+                &accounts.token_b_key,
+                &token_b_key,
+                &accounts.pool_mint_key,
+                &accounts.pool_fee_key,
+                &accounts.token_a_mint_key,
+                &accounts.token_b_mint_key,
+                Some(&pool_key),
+                Swap {
+                    amount_in: token_a_amount / 2,
+                    minimum_amount_out: 0,
+                },
+            )
+            .unwrap(),
+            vec![
+                &mut accounts.swap_account,
+                &mut SolanaAccount::default(),
+                &mut SolanaAccount::default(),
                 &mut token_a_account,
-                &token_b_key,
+                &mut accounts.token_a_account,
+                &mut accounts.token_b_account,
                 &mut token_b_account,
-                total_pool.try_into().unwrap(),
-                0,
-                0,
-            )
-            .unwrap();
-
-        let token_a = StateWithExtensions::<Account>::unpack(&token_a_account.data).unwrap();
-        assert_eq!(token_a.base.amount, token_a_amount);
-        let token_b = StateWithExtensions::<Account>::unpack(&token_b_account.data).unwrap();
-        assert_eq!(token_b.base.amount, token_b_amount);
-        let swap_token_a =
-            StateWithExtensions::<Account>::unpack(&accounts.token_a_account.data).unwrap();
-        assert_eq!(swap_token_a.base.amount, 0);
-        let swap_token_b =
-            StateWithExtensions::<Account>::unpack(&accounts.token_b_account.data).unwrap();
-        assert_eq!(swap_token_b.base.amount, 0);
+                &mut accounts.pool_mint_account,
+                &mut accounts.pool_fee_account,
+                &mut accounts.token_a_mint_account,
+                &mut accounts.token_b_mint_account,
+                &mut SolanaAccount::default(),
+                &mut SolanaAccount::default(),
+                &mut SolanaAccount::default(),
+                &mut pool_account,
+            ],
+            &constraints,
+        )
+        .unwrap();
     }
 
-    #[test_case(spl_token::id(), spl_token::id(), spl_token::id(); "all-token")]
     #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token_2022::id(); "all-token-2022")]
     #[test_case(spl_token::id(), spl_token_2022::id(), spl_token_2022::id(); "mixed-pool-token")]
     #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token::id(); "mixed-pool-token-2022")]
-    fn test_withdraw_all_constant_price_curve(
+    fn test_swap_curve_with_transfer_fees(
         pool_token_program_id: Pubkey,
         token_a_program_id: Pubkey,
         token_b_program_id: Pubkey,
     ) {
+        // All fees
         let trade_fee_numerator = 1;
         let trade_fee_denominator = 10;
         let owner_trade_fee_numerator = 1;
         let owner_trade_fee_denominator = 30;
-        let owner_withdraw_fee_numerator = 0;
+        let owner_withdraw_fee_numerator = 1;
         let owner_withdraw_fee_denominator = 30;
-        let host_fee_numerator = 10;
+        let host_fee_numerator = 20;
         let host_fee_denominator = 100;
-
-        // initialize "unbalanced", so that withdrawing all will have some issues
-        // A: 1_000_000_000
-        // B: 2_000_000_000 (1_000 * 2_000_000)
-        let swap_token_a_amount = 1_000_000_000;
-        let swap_token_b_amount = 1_000;
-        let token_b_price = 2_000_000;
         let fees = Fees {
             trade_fee_numerator,
             trade_fee_denominator,
@@ -7627,616 +10054,833 @@ mod tests {
             host_fee_denominator,
         };
 
-        let swap_curve = SwapCurve {
-            curve_type: CurveType::ConstantPrice,
-            calculator: Arc::new(ConstantPriceCurve { token_b_price }),
-        };
-        let total_pool = swap_curve.calculator.new_pool_supply();
-        let user_key = Pubkey::new_unique();
-        let withdrawer_key = Pubkey::new_unique();
+        let token_a_amount = 10_000_000_000;
+        let token_b_amount = 50_000_000_000;
 
-        let mut accounts = SwapAccountInfo::new(
-            &user_key,
+        check_valid_swap_curve(
             fees,
-            SwapTransferFees::default(),
-            swap_curve,
-            swap_token_a_amount,
-            swap_token_b_amount,
+            SwapTransferFees {
+                pool_token: TransferFee::default(),
+                token_a: TransferFee {
+                    epoch: 0.into(),
+                    transfer_fee_basis_points: 100.into(),
+                    maximum_fee: 1_000_000_000.into(),
+                },
+                token_b: TransferFee::default(),
+            },
+            CurveType::ConstantProduct,
+            Arc::new(ConstantProductCurve {}),
+            token_a_amount,
+            token_b_amount,
             &pool_token_program_id,
             &token_a_program_id,
             &token_b_program_id,
         );
+    }
 
-        accounts.initialize_swap().unwrap();
-
-        let (
-            token_a_key,
-            mut token_a_account,
-            token_b_key,
-            mut token_b_account,
-            _pool_key,
-            _pool_account,
-        ) = accounts.setup_token_accounts(&user_key, &withdrawer_key, 0, 0, 0);
+    fn token_amount(account: &SolanaAccount) -> u64 {
+        StateWithExtensions::<Account>::unpack(&account.data)
+            .unwrap()
+            .base
+            .amount
+    }
 
-        let pool_key = accounts.pool_token_key;
-        let mut pool_account = accounts.pool_token_account.clone();
+    fn concentrated_fees() -> Fees {
+        Fees {
+            trade_fee_numerator: 30,
+            trade_fee_denominator: 10000,
+            owner_trade_fee_numerator: 5,
+            owner_trade_fee_denominator: 10000,
+            owner_withdraw_fee_numerator: 0,
+            owner_withdraw_fee_denominator: 0,
+            host_fee_numerator: 20,
+            host_fee_denominator: 100,
+        }
+    }
 
-        // WithdrawAllTokenTypes will not take all token A and B, since their
-        // ratio is unbalanced.  It will try to take 1_500_000_000 worth of
-        // each token, which means 1_500_000_000 token A, and 750 token B.
-        // With no slippage, this will leave 250 token B in the pool.
+    #[test_case(spl_token::id(), spl_token::id(); "all-token")]
+    #[test_case(spl_token_2022::id(), spl_token_2022::id(); "all-token-2022")]
+    #[test_case(spl_token::id(), spl_token_2022::id(); "mixed")]
+    fn test_concentrated_pool_lifecycle(token_a_program_id: Pubkey, token_b_program_id: Pubkey) {
+        let user_key = Pubkey::new_unique();
+        let swapper_key = Pubkey::new_unique();
+        let fees = concentrated_fees();
+        let mut accounts = ConcentratedPoolInfo::new(
+            &user_key,
+            fees.clone(),
+            10,
+            1 << 64,
+            &token_a_program_id,
+            &token_b_program_id,
+        );
+        accounts.initialize_pool().unwrap();
         assert_eq!(
-            Err(SwapError::ExceededSlippage.into()),
-            accounts.withdraw_all_token_types(
+            Err(SwapError::AlreadyInUse.into()),
+            accounts.initialize_pool()
+        );
+        let pool = accounts.pool();
+        assert_eq!(pool.current_tick, 0);
+        assert_eq!(pool.liquidity, 0);
+        assert_eq!(pool.fee_authority, accounts.fee_authority_key);
+
+        // provide liquidity around the current price
+        let initial_amount = 100_000_000;
+        let liquidity = 1_000_000_000;
+        let (position_key, mut position_account) =
+            accounts.open_position(&user_key, -1000, 1000).unwrap();
+        let (token_a_key, mut token_a_account, token_b_key, mut token_b_account) =
+            accounts.setup_token_accounts(&user_key, &user_key, initial_amount, initial_amount);
+        accounts
+            .increase_liquidity(
                 &user_key,
-                &pool_key,
-                &mut pool_account,
+                &position_key,
+                &mut position_account,
                 &token_a_key,
                 &mut token_a_account,
                 &token_b_key,
                 &mut token_b_account,
-                total_pool.try_into().unwrap(),
-                swap_token_a_amount,
-                swap_token_b_amount,
+                liquidity,
+                initial_amount,
+                initial_amount,
+            )
+            .unwrap();
+        let deposited_a = token_amount(&accounts.token_a_account);
+        let deposited_b = token_amount(&accounts.token_b_account);
+        assert_eq!(token_amount(&token_a_account), initial_amount - deposited_a);
+        assert_eq!(token_amount(&token_b_account), initial_amount - deposited_b);
+        // symmetric range around a price of 1
+        assert!(deposited_a > 0 && deposited_a.abs_diff(deposited_b) <= 1);
+        let pool = accounts.pool();
+        assert_eq!(pool.liquidity, liquidity);
+        assert_eq!(pool.ticks.len(), 2);
+        let position = Position::unpack(&position_account.data).unwrap();
+        assert_eq!(position.liquidity, liquidity);
+        assert_eq!(position.owner, user_key);
+
+        // swap A to B, paying a host fee
+        let amount_in = 1_000_000;
+        let (swapper_a_key, mut swapper_a_account, swapper_b_key, mut swapper_b_account) =
+            accounts.setup_token_accounts(&user_key, &swapper_key, amount_in, 0);
+        let (host_a_key, mut host_a_account, _host_b_key, _host_b_account) =
+            accounts.setup_token_accounts(&user_key, &Pubkey::new_unique(), 0, 0);
+        assert_eq!(
+            Err(SwapError::ExceededSlippage.into()),
+            accounts.swap(
+                &swapper_key,
+                TradeDirection::AtoB,
+                &swapper_a_key,
+                &mut swapper_a_account,
+                &swapper_b_key,
+                &mut swapper_b_account,
+                None,
+                amount_in,
+                amount_in,
+            )
+        );
+        accounts
+            .swap(
+                &swapper_key,
+                TradeDirection::AtoB,
+                &swapper_a_key,
+                &mut swapper_a_account,
+                &swapper_b_key,
+                &mut swapper_b_account,
+                Some((&host_a_key, &mut host_a_account)),
+                amount_in,
+                0,
+            )
+            .unwrap();
+        let amount_out = token_amount(&swapper_b_account);
+        assert_eq!(token_amount(&swapper_a_account), 0);
+        assert!(amount_out < amount_in && amount_out > amount_in * 99 / 100);
+        assert_eq!(
+            token_amount(&accounts.token_b_account),
+            deposited_b - amount_out
+        );
+        let owner_fee = fees.owner_trading_fee(amount_in.into()).unwrap() as u64;
+        let host_fee = fees.host_fee(owner_fee.into()).unwrap() as u64;
+        assert_eq!(token_amount(&host_a_account), host_fee);
+        let pool = accounts.pool();
+        assert_eq!(pool.protocol_fees_a, owner_fee - host_fee);
+        assert_eq!(pool.protocol_fees_b, 0);
+        assert!(pool.sqrt_price < 1 << 64);
+        assert_eq!(
+            pool.current_tick,
+            tick_at_sqrt_price(pool.sqrt_price).unwrap()
+        );
+
+        // only the fee authority collects the owner trading fees
+        let (fee_a_key, mut fee_a_account, fee_b_key, mut fee_b_account) =
+            accounts.setup_token_accounts(&user_key, &accounts.fee_authority_key.clone(), 0, 0);
+        assert_eq!(
+            Err(SwapError::IncorrectFeeAuthority.into()),
+            accounts.collect_protocol_fees(
+                &user_key,
+                &fee_a_key,
+                &mut fee_a_account,
+                &fee_b_key,
+                &mut fee_b_account,
             )
         );
+        let fee_authority_key = accounts.fee_authority_key;
+        accounts
+            .collect_protocol_fees(
+                &fee_authority_key,
+                &fee_a_key,
+                &mut fee_a_account,
+                &fee_b_key,
+                &mut fee_b_account,
+            )
+            .unwrap();
+        assert_eq!(token_amount(&fee_a_account), owner_fee - host_fee);
+        assert_eq!(accounts.pool().protocol_fees_a, 0);
 
+        // withdraw everything, including the trading fees earned
         accounts
-            .withdraw_all_token_types(
+            .decrease_liquidity(
                 &user_key,
-                &pool_key,
-                &mut pool_account,
+                &position_key,
+                &mut position_account,
                 &token_a_key,
                 &mut token_a_account,
                 &token_b_key,
                 &mut token_b_account,
-                total_pool.try_into().unwrap(),
+                liquidity,
                 0,
                 0,
             )
             .unwrap();
+        let trade_fee = fees.trading_fee(amount_in.into()).unwrap() as u64;
+        let withdrawn_a = token_amount(&token_a_account) - (initial_amount - deposited_a);
+        // everything the pool received, except the owner fee, goes back to
+        // the liquidity provider, up to rounding dust left in the pool
+        assert_eq!(
+            withdrawn_a + token_amount(&accounts.token_a_account),
+            deposited_a + amount_in - owner_fee
+        );
+        assert!(withdrawn_a - deposited_a > trade_fee);
+        // only rounding dust is left in the pool
+        assert!(token_amount(&accounts.token_a_account) <= 2);
+        assert!(token_amount(&accounts.token_b_account) <= 2);
+        let pool = accounts.pool();
+        assert_eq!(pool.liquidity, 0);
+        assert!(pool.ticks.is_empty());
+        let position = Position::unpack(&position_account.data).unwrap();
+        assert_eq!(position.liquidity, 0);
+        assert_eq!(position.fees_owed_a, 0);
+        assert_eq!(position.fees_owed_b, 0);
+
+        // the empty position is closed by its owner, returning the rent
+        let position_rent = 1_000_000;
+        position_account.lamports = position_rent;
+        let destination_key = Pubkey::new_unique();
+        let mut destination_account = SolanaAccount::default();
+        assert_eq!(
+            Err(SwapError::IncorrectPosition.into()),
+            accounts.close_position(
+                &swapper_key,
+                &position_key,
+                &mut position_account,
+                &destination_key,
+                &mut destination_account,
+            )
+        );
+        accounts
+            .close_position(
+                &user_key,
+                &position_key,
+                &mut position_account,
+                &destination_key,
+                &mut destination_account,
+            )
+            .unwrap();
+        assert_eq!(destination_account.lamports, position_rent);
+        assert_eq!(position_account.lamports, 0);
+        assert!(!SwapVersion::is_initialized(&position_account.data));
+    }
 
-        let token_a = StateWithExtensions::<Account>::unpack(&token_a_account.data).unwrap();
-        assert_eq!(token_a.base.amount, swap_token_a_amount);
-        let token_b = StateWithExtensions::<Account>::unpack(&token_b_account.data).unwrap();
-        assert_eq!(token_b.base.amount, 750);
-        let swap_token_a =
-            StateWithExtensions::<Account>::unpack(&accounts.token_a_account.data).unwrap();
-        assert_eq!(swap_token_a.base.amount, 0);
-        let swap_token_b =
-            StateWithExtensions::<Account>::unpack(&accounts.token_b_account.data).unwrap();
-        assert_eq!(swap_token_b.base.amount, 250);
+    #[test]
+    fn test_concentrated_pool_crosses_ticks() {
+        let user_key = Pubkey::new_unique();
+        let swapper_key = Pubkey::new_unique();
+        let token_program_id = spl_token::id();
+        let mut accounts = ConcentratedPoolInfo::new(
+            &user_key,
+            concentrated_fees(),
+            10,
+            1 << 64,
+            &token_program_id,
+            &token_program_id,
+        );
+        accounts.initialize_pool().unwrap();
+
+        // position entirely above the current price only holds token A
+        let initial_amount = 100_000_000;
+        let liquidity = 1_000_000_000;
+        let (position_key, mut position_account) =
+            accounts.open_position(&user_key, 1000, 2000).unwrap();
+        let (token_a_key, mut token_a_account, token_b_key, mut token_b_account) =
+            accounts.setup_token_accounts(&user_key, &user_key, initial_amount, initial_amount);
+        accounts
+            .increase_liquidity(
+                &user_key,
+                &position_key,
+                &mut position_account,
+                &token_a_key,
+                &mut token_a_account,
+                &token_b_key,
+                &mut token_b_account,
+                liquidity,
+                initial_amount,
+                0,
+            )
+            .unwrap();
+        assert!(token_amount(&accounts.token_a_account) > 0);
+        assert_eq!(token_amount(&accounts.token_b_account), 0);
+        assert_eq!(accounts.pool().liquidity, 0);
 
-        // deposit now, not enough to cover the tokens already in there
-        let token_b_amount = 10;
-        let token_a_amount = token_b_amount * token_b_price;
-        let (
-            token_a_key,
-            mut token_a_account,
-            token_b_key,
-            mut token_b_account,
-            pool_key,
-            mut pool_account,
-        ) = accounts.setup_token_accounts(
+        // buying token A moves the price into the range
+        let (swapper_a_key, mut swapper_a_account, swapper_b_key, mut swapper_b_account) =
+            accounts.setup_token_accounts(&user_key, &swapper_key, 0, initial_amount);
+        accounts
+            .swap(
+                &swapper_key,
+                TradeDirection::BtoA,
+                &swapper_b_key,
+                &mut swapper_b_account,
+                &swapper_a_key,
+                &mut swapper_a_account,
+                None,
+                1_000_000,
+                0,
+            )
+            .unwrap();
+        let pool = accounts.pool();
+        assert_eq!(pool.liquidity, liquidity);
+        assert!(pool.current_tick >= 1000 && pool.current_tick < 2000);
+        assert_eq!(
+            pool.current_tick,
+            tick_at_sqrt_price(pool.sqrt_price).unwrap()
+        );
+        // tokens are bought at a price of at least 1.0001^1000
+        let amount_out = token_amount(&swapper_a_account);
+        assert!(amount_out > 0 && amount_out < 1_000_000 * 100 / 110);
+
+        // selling back more than was bought moves the price below the range,
+        // where there is no liquidity left to fill the trade
+        let (seller_a_key, mut seller_a_account, seller_b_key, mut seller_b_account) =
+            accounts.setup_token_accounts(&user_key, &swapper_key, amount_out * 2, 0);
+        assert_eq!(
+            Err(SwapError::InsufficientLiquidity.into()),
+            accounts.swap(
+                &swapper_key,
+                TradeDirection::AtoB,
+                &seller_a_key,
+                &mut seller_a_account,
+                &seller_b_key,
+                &mut seller_b_account,
+                None,
+                amount_out * 2,
+                0,
+            )
+        );
+        accounts
+            .swap(
+                &swapper_key,
+                TradeDirection::AtoB,
+                &swapper_a_key,
+                &mut swapper_a_account,
+                &swapper_b_key,
+                &mut swapper_b_account,
+                None,
+                amount_out / 2,
+                0,
+            )
+            .unwrap();
+        assert_eq!(accounts.pool().liquidity, liquidity);
+    }
+
+    #[test]
+    fn test_invalid_concentrated_positions() {
+        let user_key = Pubkey::new_unique();
+        let token_program_id = spl_token::id();
+        let mut accounts = ConcentratedPoolInfo::new(
             &user_key,
-            &withdrawer_key,
-            token_a_amount,
-            token_b_amount,
-            0,
+            concentrated_fees(),
+            10,
+            1 << 64,
+            &token_program_id,
+            &token_program_id,
+        );
+
+        // positions need an initialized pool
+        assert_eq!(
+            Err(ProgramError::UninitializedAccount),
+            accounts.open_position(&user_key, -100, 100).map(|_| ())
+        );
+        accounts.initialize_pool().unwrap();
+
+        // bounds must be ordered multiples of the tick spacing
+        for (tick_lower, tick_upper) in
+            [(-105, 100), (100, 100), (100, -100), (-100, MAX_TICK + 10)]
+        {
+            assert_eq!(
+                Err(SwapError::InvalidTick.into()),
+                accounts
+                    .open_position(&user_key, tick_lower, tick_upper)
+                    .map(|_| ())
+            );
+        }
+
+        let initial_amount = 100_000_000;
+        let liquidity = 1_000_000_000;
+        let (position_key, mut position_account) =
+            accounts.open_position(&user_key, -100, 100).unwrap();
+        let (token_a_key, mut token_a_account, token_b_key, mut token_b_account) =
+            accounts.setup_token_accounts(&user_key, &user_key, initial_amount, initial_amount);
+
+        // wrong owner
+        let other_key = Pubkey::new_unique();
+        assert_eq!(
+            Err(SwapError::IncorrectPosition.into()),
+            accounts.increase_liquidity(
+                &other_key,
+                &position_key,
+                &mut position_account,
+                &token_a_key,
+                &mut token_a_account,
+                &token_b_key,
+                &mut token_b_account,
+                liquidity,
+                initial_amount,
+                initial_amount,
+            )
         );
 
+        // slippage on deposit
         assert_eq!(
             Err(SwapError::ExceededSlippage.into()),
-            accounts.deposit_all_token_types(
-                &withdrawer_key,
+            accounts.increase_liquidity(
+                &user_key,
+                &position_key,
+                &mut position_account,
                 &token_a_key,
                 &mut token_a_account,
                 &token_b_key,
                 &mut token_b_account,
-                &pool_key,
-                &mut pool_account,
-                1, // doesn't matter
-                token_a_amount,
-                token_b_amount,
+                liquidity,
+                1,
+                initial_amount,
             )
         );
-
-        // deposit enough tokens, success!
-        let token_b_amount = 125;
-        let token_a_amount = token_b_amount * token_b_price;
-        let (
-            token_a_key,
-            mut token_a_account,
-            token_b_key,
-            mut token_b_account,
-            pool_key,
-            mut pool_account,
-        ) = accounts.setup_token_accounts(
-            &user_key,
-            &withdrawer_key,
-            token_a_amount,
-            token_b_amount,
-            0,
-        );
-
         accounts
-            .deposit_all_token_types(
-                &withdrawer_key,
+            .increase_liquidity(
+                &user_key,
+                &position_key,
+                &mut position_account,
                 &token_a_key,
                 &mut token_a_account,
                 &token_b_key,
                 &mut token_b_account,
-                &pool_key,
-                &mut pool_account,
-                1, // doesn't matter
-                token_a_amount,
-                token_b_amount,
+                liquidity,
+                initial_amount,
+                initial_amount,
             )
             .unwrap();
-    }
-
-    #[test_case(spl_token::id(), spl_token::id(), spl_token::id(); "all-token")]
-    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token_2022::id(); "all-token-2022")]
-    #[test_case(spl_token::id(), spl_token_2022::id(), spl_token_2022::id(); "mixed-pool-token")]
-    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token::id(); "mixed-pool-token-2022")]
-    fn test_deposits_allowed_single_token(
-        pool_token_program_id: Pubkey,
-        token_a_program_id: Pubkey,
-        token_b_program_id: Pubkey,
-    ) {
-        let trade_fee_numerator = 1;
-        let trade_fee_denominator = 10;
-        let owner_trade_fee_numerator = 1;
-        let owner_trade_fee_denominator = 30;
-        let owner_withdraw_fee_numerator = 0;
-        let owner_withdraw_fee_denominator = 30;
-        let host_fee_numerator = 10;
-        let host_fee_denominator = 100;
-
-        let token_a_amount = 1_000_000;
-        let token_b_amount = 0;
-        let fees = Fees {
-            trade_fee_numerator,
-            trade_fee_denominator,
-            owner_trade_fee_numerator,
-            owner_trade_fee_denominator,
-            owner_withdraw_fee_numerator,
-            owner_withdraw_fee_denominator,
-            host_fee_numerator,
-            host_fee_denominator,
-        };
-
-        let token_b_offset = 2_000_000;
-        let swap_curve = SwapCurve {
-            curve_type: CurveType::Offset,
-            calculator: Arc::new(OffsetCurve { token_b_offset }),
-        };
-        let creator_key = Pubkey::new_unique();
-        let depositor_key = Pubkey::new_unique();
 
-        let mut accounts = SwapAccountInfo::new(
-            &creator_key,
-            fees,
-            SwapTransferFees::default(),
-            swap_curve,
-            token_a_amount,
-            token_b_amount,
-            &pool_token_program_id,
-            &token_a_program_id,
-            &token_b_program_id,
+        // cannot close a position still holding liquidity
+        let destination_key = Pubkey::new_unique();
+        let mut destination_account = SolanaAccount::default();
+        assert_eq!(
+            Err(SwapError::PositionNotEmpty.into()),
+            accounts.close_position(
+                &user_key,
+                &position_key,
+                &mut position_account,
+                &destination_key,
+                &mut destination_account,
+            )
         );
 
-        accounts.initialize_swap().unwrap();
-
-        let initial_a = 1_000_000;
-        let initial_b = 2_000_000;
-        let (
-            _depositor_token_a_key,
-            _depositor_token_a_account,
-            depositor_token_b_key,
-            mut depositor_token_b_account,
-            depositor_pool_key,
-            mut depositor_pool_account,
-        ) = accounts.setup_token_accounts(&creator_key, &depositor_key, initial_a, initial_b, 0);
+        // cannot remove more than the position holds
+        assert_eq!(
+            Err(SwapError::InsufficientLiquidity.into()),
+            accounts.decrease_liquidity(
+                &user_key,
+                &position_key,
+                &mut position_account,
+                &token_a_key,
+                &mut token_a_account,
+                &token_b_key,
+                &mut token_b_account,
+                liquidity + 1,
+                0,
+                0,
+            )
+        );
 
+        // slippage on withdrawal
         assert_eq!(
-            Err(SwapError::UnsupportedCurveOperation.into()),
-            accounts.deposit_single_token_type_exact_amount_in(
-                &depositor_key,
-                &depositor_token_b_key,
-                &mut depositor_token_b_account,
-                &depositor_pool_key,
-                &mut depositor_pool_account,
-                initial_b,
+            Err(SwapError::ExceededSlippage.into()),
+            accounts.decrease_liquidity(
+                &user_key,
+                &position_key,
+                &mut position_account,
+                &token_a_key,
+                &mut token_a_account,
+                &token_b_key,
+                &mut token_b_account,
+                liquidity,
+                initial_amount,
                 0,
             )
         );
     }
 
-    #[test_case(spl_token::id(), spl_token::id(), spl_token::id(); "all-token")]
-    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token_2022::id(); "all-token-2022")]
-    #[test_case(spl_token::id(), spl_token_2022::id(), spl_token_2022::id(); "mixed-pool-token")]
-    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token::id(); "mixed-pool-token-2022")]
-    fn test_withdraw_with_invalid_fee_account(
-        pool_token_program_id: Pubkey,
-        token_a_program_id: Pubkey,
-        token_b_program_id: Pubkey,
-    ) {
+    #[test]
+    fn test_concentrated_position_minimum_liquidity() {
         let user_key = Pubkey::new_unique();
-
-        let fees = Fees {
-            trade_fee_numerator: 1,
-            trade_fee_denominator: 2,
-            owner_trade_fee_numerator: 1,
-            owner_trade_fee_denominator: 10,
-            owner_withdraw_fee_numerator: 1,
-            owner_withdraw_fee_denominator: 5,
-            host_fee_numerator: 7,
-            host_fee_denominator: 100,
-        };
-
-        let token_a_amount = 1000;
-        let token_b_amount = 2000;
-        let swap_curve = SwapCurve {
-            curve_type: CurveType::ConstantProduct,
-            calculator: Arc::new(ConstantProductCurve {}),
-        };
-
-        let withdrawer_key = Pubkey::new_unique();
-        let initial_a = token_a_amount / 10;
-        let initial_b = token_b_amount / 10;
-        let initial_pool = swap_curve.calculator.new_pool_supply() / 10;
-        let withdraw_amount = initial_pool / 4;
-        let minimum_token_a_amount = initial_a / 40;
-        let minimum_token_b_amount = initial_b / 40;
-
-        let mut accounts = SwapAccountInfo::new(
+        let token_program_id = spl_token::id();
+        let mut accounts = ConcentratedPoolInfo::new(
             &user_key,
-            fees,
-            SwapTransferFees::default(),
-            swap_curve,
-            token_a_amount,
-            token_b_amount,
-            &pool_token_program_id,
-            &token_a_program_id,
-            &token_b_program_id,
+            concentrated_fees(),
+            10,
+            1 << 64,
+            &token_program_id,
+            &token_program_id,
         );
-
-        accounts.initialize_swap().unwrap();
-
-        let (
-            token_a_key,
-            mut token_a_account,
-            token_b_key,
-            mut token_b_account,
-            pool_key,
-            mut pool_account,
-        ) = accounts.setup_token_accounts(
-            &user_key,
-            &withdrawer_key,
-            initial_a,
-            initial_b,
-            initial_pool.try_into().unwrap(),
+        let min_position_liquidity = 1_000_000;
+        accounts.min_position_liquidity = min_position_liquidity;
+        accounts.initialize_pool().unwrap();
+        assert_eq!(
+            accounts.pool().min_position_liquidity,
+            min_position_liquidity
         );
 
-        let destination_key = Pubkey::new_unique();
-        let mut destination = SolanaAccount::new(
-            account_minimum_balance(),
-            Account::get_packed_len(),
-            &withdrawer_key,
-        );
+        let initial_amount = 100_000_000;
+        let (position_key, mut position_account) =
+            accounts.open_position(&user_key, -100, 100).unwrap();
+        let (token_a_key, mut token_a_account, token_b_key, mut token_b_account) =
+            accounts.setup_token_accounts(&user_key, &user_key, initial_amount, initial_amount);
 
-        do_process_instruction(
-            close_account(
-                &pool_token_program_id,
-                &accounts.pool_fee_key,
-                &destination_key,
+        // dust positions would use up the pool's ticks
+        assert_eq!(
+            Err(SwapError::PositionLiquidityTooLow.into()),
+            accounts.increase_liquidity(
                 &user_key,
-                &[],
-            )
-            .unwrap(),
-            vec![
-                &mut accounts.pool_fee_account,
-                &mut destination,
-                &mut SolanaAccount::default(),
-            ],
-        )
-        .unwrap();
-
-        let user_transfer_authority_key = Pubkey::new_unique();
-        let pool_token_amount = withdraw_amount.try_into().unwrap();
-
-        do_process_instruction(
-            approve(
-                &pool_token_program_id,
-                &pool_key,
-                &user_transfer_authority_key,
-                &withdrawer_key,
-                &[],
-                pool_token_amount,
+                &position_key,
+                &mut position_account,
+                &token_a_key,
+                &mut token_a_account,
+                &token_b_key,
+                &mut token_b_account,
+                min_position_liquidity - 1,
+                initial_amount,
+                initial_amount,
             )
-            .unwrap(),
-            vec![
-                &mut pool_account,
-                &mut SolanaAccount::default(),
-                &mut SolanaAccount::default(),
-            ],
-        )
-        .unwrap();
-
-        do_process_instruction(
-            withdraw_all_token_types(
-                &SWAP_PROGRAM_ID,
-                &pool_token_program_id,
-                &token_a_program_id,
-                &token_b_program_id,
-                &accounts.swap_key,
-                &accounts.authority_key,
-                &user_transfer_authority_key,
-                &accounts.pool_mint_key,
-                &accounts.pool_fee_key,
-                &pool_key,
-                &accounts.token_a_key,
-                &accounts.token_b_key,
+        );
+        accounts
+            .increase_liquidity(
+                &user_key,
+                &position_key,
+                &mut position_account,
+                &token_a_key,
+                &mut token_a_account,
+                &token_b_key,
+                &mut token_b_account,
+                min_position_liquidity,
+                initial_amount,
+                initial_amount,
+            )
+            .unwrap();
+
+        // a partial withdrawal cannot leave dust behind, but a full one can
+        assert_eq!(
+            Err(SwapError::PositionLiquidityTooLow.into()),
+            accounts.decrease_liquidity(
+                &user_key,
+                &position_key,
+                &mut position_account,
                 &token_a_key,
+                &mut token_a_account,
                 &token_b_key,
-                &accounts.token_a_mint_key,
-                &accounts.token_b_mint_key,
-                WithdrawAllTokenTypes {
-                    pool_token_amount,
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
-                },
+                &mut token_b_account,
+                1,
+                0,
+                0,
             )
-            .unwrap(),
-            vec![
-                &mut accounts.swap_account,
-                &mut SolanaAccount::default(),
-                &mut SolanaAccount::default(),
-                &mut accounts.pool_mint_account,
-                &mut pool_account,
-                &mut accounts.token_a_account,
-                &mut accounts.token_b_account,
+        );
+        accounts
+            .decrease_liquidity(
+                &user_key,
+                &position_key,
+                &mut position_account,
+                &token_a_key,
                 &mut token_a_account,
+                &token_b_key,
                 &mut token_b_account,
-                &mut accounts.pool_fee_account,
-                &mut accounts.token_a_mint_account,
-                &mut accounts.token_b_mint_account,
-                &mut SolanaAccount::default(),
-                &mut SolanaAccount::default(),
-                &mut SolanaAccount::default(),
-            ],
-        )
-        .unwrap();
+                min_position_liquidity,
+                0,
+                0,
+            )
+            .unwrap();
+        assert!(accounts.pool().ticks.is_empty());
     }
 
-    #[test_case(spl_token::id(), spl_token::id(), spl_token::id(); "all-token")]
-    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token_2022::id(); "all-token-2022")]
-    #[test_case(spl_token::id(), spl_token_2022::id(), spl_token_2022::id(); "mixed-pool-token")]
-    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token::id(); "mixed-pool-token-2022")]
-    fn test_withdraw_one_exact_out_with_invalid_fee_account(
-        pool_token_program_id: Pubkey,
-        token_a_program_id: Pubkey,
-        token_b_program_id: Pubkey,
-    ) {
+    #[test]
+    fn test_concentrated_pool_curve_type_constraint() {
         let user_key = Pubkey::new_unique();
-
-        let fees = Fees {
-            trade_fee_numerator: 1,
-            trade_fee_denominator: 2,
-            owner_trade_fee_numerator: 1,
-            owner_trade_fee_denominator: 10,
-            owner_withdraw_fee_numerator: 1,
-            owner_withdraw_fee_denominator: 5,
-            host_fee_numerator: 7,
-            host_fee_denominator: 100,
-        };
-
-        let token_a_amount = 1000;
-        let token_b_amount = 2000;
-        let swap_curve = SwapCurve {
-            curve_type: CurveType::ConstantProduct,
-            calculator: Arc::new(ConstantProductCurve {}),
-        };
-
-        let withdrawer_key = Pubkey::new_unique();
-        let initial_a = token_a_amount / 10;
-        let initial_b = token_b_amount / 10;
-        let initial_pool = swap_curve.calculator.new_pool_supply() / 10;
-        let maximum_pool_token_amount = to_u64(initial_pool / 4).unwrap();
-        let destination_a_amount = initial_a / 40;
-
-        let mut accounts = SwapAccountInfo::new(
+        let token_program_id = spl_token::id();
+        let fees = concentrated_fees();
+        let mut accounts = ConcentratedPoolInfo::new(
             &user_key,
-            fees,
-            SwapTransferFees::default(),
-            swap_curve,
-            token_a_amount,
-            token_b_amount,
-            &pool_token_program_id,
-            &token_a_program_id,
-            &token_b_program_id,
+            fees.clone(),
+            10,
+            1 << 64,
+            &token_program_id,
+            &token_program_id,
+        );
+        let owner_key = accounts.fee_authority_key.to_string();
+        let swap_constraints = Some(SwapConstraints {
+            owner_key: &owner_key,
+            valid_curve_types: &[CurveType::ConstantProduct],
+            fees: &fees,
+        });
+        assert_eq!(
+            Err(SwapError::UnsupportedCurveType.into()),
+            accounts.initialize_pool_with_constraints(&swap_constraints)
         );
 
-        accounts.initialize_swap().unwrap();
+        let swap_constraints = Some(SwapConstraints {
+            owner_key: &owner_key,
+            valid_curve_types: &[CurveType::ConstantProduct, CurveType::ConcentratedLiquidity],
+            fees: &fees,
+        });
+        accounts
+            .initialize_pool_with_constraints(&swap_constraints)
+            .unwrap();
+    }
 
-        let (
-            token_a_key,
-            mut token_a_account,
-            _token_b_key,
-            _token_b_account,
-            pool_key,
-            mut pool_account,
-        ) = accounts.setup_token_accounts(
+    #[test]
+    fn test_concentrated_pool_pause() {
+        let user_key = Pubkey::new_unique();
+        let swapper_key = Pubkey::new_unique();
+        let token_program_id = spl_token::id();
+        let mut accounts = ConcentratedPoolInfo::new(
             &user_key,
-            &withdrawer_key,
-            initial_a,
-            initial_b,
-            initial_pool.try_into().unwrap(),
+            concentrated_fees(),
+            10,
+            1 << 64,
+            &token_program_id,
+            &token_program_id,
         );
+        accounts.initialize_pool().unwrap();
+        let fee_authority_key = accounts.fee_authority_key;
+        let pool = accounts.pool();
+        assert_eq!(pool.pause_authority, fee_authority_key);
+        assert!(!pool.is_paused);
+
+        let initial_amount = 100_000_000;
+        let liquidity = 1_000_000_000;
+        let (position_key, mut position_account) =
+            accounts.open_position(&user_key, -1000, 1000).unwrap();
+        let (token_a_key, mut token_a_account, token_b_key, mut token_b_account) =
+            accounts.setup_token_accounts(&user_key, &user_key, initial_amount, initial_amount);
+        accounts
+            .increase_liquidity(
+                &user_key,
+                &position_key,
+                &mut position_account,
+                &token_a_key,
+                &mut token_a_account,
+                &token_b_key,
+                &mut token_b_account,
+                liquidity,
+                initial_amount,
+                initial_amount,
+            )
+            .unwrap();
 
-        let destination_key = Pubkey::new_unique();
-        let mut destination = SolanaAccount::new(
-            account_minimum_balance(),
-            Account::get_packed_len(),
-            &withdrawer_key,
+        // only the pause authority can pause the pool
+        assert_eq!(
+            Err(SwapError::IncorrectPauseAuthority.into()),
+            accounts.set_paused(&user_key, true)
+        );
+        let new_pause_authority = Pubkey::new_unique();
+        accounts
+            .set_pause_authority(&fee_authority_key, &new_pause_authority)
+            .unwrap();
+        assert_eq!(
+            Err(SwapError::IncorrectPauseAuthority.into()),
+            accounts.set_paused(&fee_authority_key, true)
         );
+        accounts.set_paused(&new_pause_authority, true).unwrap();
+        assert!(accounts.pool().is_paused);
 
-        do_process_instruction(
-            close_account(
-                &pool_token_program_id,
-                &accounts.pool_fee_key,
-                &destination_key,
+        // swaps and new liquidity are rejected while paused
+        let amount_in = 1_000_000;
+        let (swapper_a_key, mut swapper_a_account, swapper_b_key, mut swapper_b_account) =
+            accounts.setup_token_accounts(&user_key, &swapper_key, amount_in, 0);
+        assert_eq!(
+            Err(SwapError::SwapPaused.into()),
+            accounts.swap(
+                &swapper_key,
+                TradeDirection::AtoB,
+                &swapper_a_key,
+                &mut swapper_a_account,
+                &swapper_b_key,
+                &mut swapper_b_account,
+                None,
+                amount_in,
+                0,
+            )
+        );
+        assert_eq!(
+            Err(SwapError::SwapPaused.into()),
+            accounts.open_position(&user_key, -100, 100).map(|_| ())
+        );
+        assert_eq!(
+            Err(SwapError::SwapPaused.into()),
+            accounts.increase_liquidity(
                 &user_key,
-                &[],
+                &position_key,
+                &mut position_account,
+                &token_a_key,
+                &mut token_a_account,
+                &token_b_key,
+                &mut token_b_account,
+                liquidity,
+                initial_amount,
+                initial_amount,
             )
-            .unwrap(),
-            vec![
-                &mut accounts.pool_fee_account,
-                &mut destination,
-                &mut SolanaAccount::default(),
-            ],
-        )
-        .unwrap();
-
-        let user_transfer_authority_key = Pubkey::new_unique();
+        );
 
-        do_process_instruction(
-            approve(
-                &pool_token_program_id,
-                &pool_key,
-                &user_transfer_authority_key,
-                &withdrawer_key,
-                &[],
-                maximum_pool_token_amount,
+        // positions can still be withdrawn and closed
+        accounts
+            .decrease_liquidity(
+                &user_key,
+                &position_key,
+                &mut position_account,
+                &token_a_key,
+                &mut token_a_account,
+                &token_b_key,
+                &mut token_b_account,
+                liquidity,
+                0,
+                0,
             )
-            .unwrap(),
-            vec![
-                &mut pool_account,
-                &mut SolanaAccount::default(),
-                &mut SolanaAccount::default(),
-            ],
-        )
-        .unwrap();
+            .unwrap();
+        let destination_key = Pubkey::new_unique();
+        let mut destination_account = SolanaAccount::default();
+        accounts
+            .close_position(
+                &user_key,
+                &position_key,
+                &mut position_account,
+                &destination_key,
+                &mut destination_account,
+            )
+            .unwrap();
 
-        do_process_instruction(
-            withdraw_single_token_type_exact_amount_out(
-                &SWAP_PROGRAM_ID,
-                &pool_token_program_id,
-                &token_a_program_id,
-                &accounts.swap_key,
-                &accounts.authority_key,
-                &user_transfer_authority_key,
-                &accounts.pool_mint_key,
-                &accounts.pool_fee_key,
-                &pool_key,
-                &accounts.token_a_key,
-                &accounts.token_b_key,
+        // resume, trading works again
+        accounts.set_paused(&new_pause_authority, false).unwrap();
+        assert!(!accounts.pool().is_paused);
+        let (position_key, mut position_account) =
+            accounts.open_position(&user_key, -1000, 1000).unwrap();
+        accounts
+            .increase_liquidity(
+                &user_key,
+                &position_key,
+                &mut position_account,
                 &token_a_key,
-                &accounts.token_a_mint_key,
-                WithdrawSingleTokenTypeExactAmountOut {
-                    destination_token_amount: destination_a_amount,
-                    maximum_pool_token_amount,
-                },
-            )
-            .unwrap(),
-            vec![
-                &mut accounts.swap_account,
-                &mut SolanaAccount::default(),
-                &mut SolanaAccount::default(),
-                &mut accounts.pool_mint_account,
-                &mut pool_account,
-                &mut accounts.token_a_account,
-                &mut accounts.token_b_account,
                 &mut token_a_account,
-                &mut accounts.pool_fee_account,
-                &mut accounts.token_a_mint_account,
-                &mut SolanaAccount::default(),
-                &mut SolanaAccount::default(),
-            ],
-        )
-        .unwrap();
-    }
-
-    #[test_case(spl_token::id(), spl_token::id(), spl_token::id(); "all-token")]
-    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token_2022::id(); "all-token-2022")]
-    #[test_case(spl_token::id(), spl_token_2022::id(), spl_token_2022::id(); "mixed-pool-token")]
-    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token::id(); "mixed-pool-token-2022")]
-    fn test_valid_swap_with_invalid_fee_account(
-        pool_token_program_id: Pubkey,
-        token_a_program_id: Pubkey,
-        token_b_program_id: Pubkey,
-    ) {
-        let owner_key = &Pubkey::new_unique();
+                &token_b_key,
+                &mut token_b_account,
+                liquidity,
+                initial_amount,
+                initial_amount,
+            )
+            .unwrap();
+        accounts
+            .swap(
+                &swapper_key,
+                TradeDirection::AtoB,
+                &swapper_a_key,
+                &mut swapper_a_account,
+                &swapper_b_key,
+                &mut swapper_b_account,
+                None,
+                amount_in,
+                0,
+            )
+            .unwrap();
+    }
 
-        let token_a_amount = 1_000_000;
-        let token_b_amount = 5_000_000;
+    fn quote_accounts<'a>(accounts: &SwapAccountInfo, swap: &'a dyn SwapState) -> PoolQuoter<'a> {
+        PoolQuoter {
+            swap,
+            token_a_amount: token_amount(&accounts.token_a_account),
+            token_b_amount: token_amount(&accounts.token_b_account),
+            pool_token_supply: StateWithExtensions::<Mint>::unpack(
+                &accounts.pool_mint_account.data,
+            )
+            .unwrap()
+            .base
+            .supply,
+            token_a_transfer_fee: None,
+            token_b_transfer_fee: None,
+        }
+    }
 
+    #[test]
+    fn test_quotes_match_instructions() {
+        let user_key = Pubkey::new_unique();
+        let depositor_key = Pubkey::new_unique();
         let fees = Fees {
-            trade_fee_numerator: 1,
-            trade_fee_denominator: 10,
-            owner_trade_fee_numerator: 1,
-            owner_trade_fee_denominator: 30,
+            trade_fee_numerator: 25,
+            trade_fee_denominator: 10_000,
+            owner_trade_fee_numerator: 5,
+            owner_trade_fee_denominator: 10_000,
             owner_withdraw_fee_numerator: 1,
-            owner_withdraw_fee_denominator: 30,
-            host_fee_numerator: 10,
+            owner_withdraw_fee_denominator: 100,
+            host_fee_numerator: 20,
             host_fee_denominator: 100,
         };
-
-        let swap_curve = SwapCurve {
-            curve_type: CurveType::ConstantProduct,
-            calculator: Arc::new(ConstantProductCurve {}),
+        let transfer_fees = SwapTransferFees {
+            pool_token: TransferFee::default(),
+            token_a: TransferFee {
+                epoch: 0.into(),
+                transfer_fee_basis_points: 100.into(),
+                maximum_fee: 1_000_000_000.into(),
+            },
+            token_b: TransferFee {
+                epoch: 0.into(),
+                transfer_fee_basis_points: 50.into(),
+                maximum_fee: 1_000_000_000.into(),
+            },
         };
-
-        let owner_key_str = &owner_key.to_string();
-        let constraints = Some(SwapConstraints {
-            owner_key: owner_key_str,
-            valid_curve_types: &[CurveType::ConstantProduct],
-            fees: &fees,
-        });
+        let token_a_amount = 10_000_000_000;
+        let token_b_amount = 50_000_000_000;
+        let program_id = spl_token_2022::id();
         let mut accounts = SwapAccountInfo::new(
-            owner_key,
-            fees.clone(),
-            SwapTransferFees::default(),
-            swap_curve,
+            &user_key,
+            fees,
+            transfer_fees,
+            SwapCurve {
+                curve_type: CurveType::ConstantProduct,
+                calculator: Arc::new(ConstantProductCurve {}),
+            },
             token_a_amount,
             token_b_amount,
-            &pool_token_program_id,
-            &token_a_program_id,
-            &token_b_program_id,
+            &program_id,
+            &program_id,
+            &program_id,
         );
-
-        do_process_instruction_with_fee_constraints(
-            initialize(
-                &SWAP_PROGRAM_ID,
-                &pool_token_program_id,
-                &accounts.swap_key,
-                &accounts.authority_key,
-                &accounts.token_a_key,
-                &accounts.token_b_key,
-                &accounts.pool_mint_key,
-                &accounts.pool_fee_key,
-                &accounts.pool_token_key,
-                accounts.fees.clone(),
-                accounts.swap_curve.clone(),
-            )
-            .unwrap(),
-            vec![
-                &mut accounts.swap_account,
-                &mut SolanaAccount::default(),
-                &mut accounts.token_a_account,
-                &mut accounts.token_b_account,
-                &mut accounts.pool_mint_account,
-                &mut accounts.pool_fee_account,
-                &mut accounts.pool_token_account,
-                &mut SolanaAccount::default(),
-            ],
-            &constraints,
-        )
-        .unwrap();
-
-        let authority_key = accounts.authority_key;
-
+        accounts.initialize_swap().unwrap();
+        let token_a_transfer_fee = accounts.transfer_fees.token_a;
+        let token_b_transfer_fee = accounts.transfer_fees.token_b;
+        let initial_a = token_a_amount / 5;
+        let initial_b = token_b_amount / 5;
         let (
             token_a_key,
             mut token_a_account,
@@ -8244,133 +10888,125 @@ mod tests {
             mut token_b_account,
             pool_key,
             mut pool_account,
-        ) = accounts.setup_token_accounts(
-            owner_key,
-            &authority_key,
-            token_a_amount,
-            token_b_amount,
-            0,
-        );
-
-        let destination_key = Pubkey::new_unique();
-        let mut destination = SolanaAccount::new(
-            account_minimum_balance(),
-            Account::get_packed_len(),
-            owner_key,
-        );
+        ) = accounts.setup_token_accounts(&user_key, &depositor_key, initial_a, initial_b, 0);
 
-        do_process_instruction(
-            close_account(
-                &pool_token_program_id,
-                &accounts.pool_fee_key,
-                &destination_key,
-                owner_key,
-                &[],
-            )
-            .unwrap(),
-            vec![
-                &mut accounts.pool_fee_account,
-                &mut destination,
-                &mut SolanaAccount::default(),
-            ],
-        )
+        // swap
+        let swap = SwapVersion::unpack(&accounts.swap_account.data).unwrap();
+        let quote = PoolQuoter {
+            token_a_transfer_fee: Some(&token_a_transfer_fee),
+            token_b_transfer_fee: Some(&token_b_transfer_fee),
+            ..quote_accounts(&accounts, swap.as_ref())
+        }
+        .quote_swap(initial_a / 10, TradeDirection::AtoB, 50)
         .unwrap();
-
-        do_process_instruction_with_fee_constraints(
-            swap(
-                &SWAP_PROGRAM_ID,
-                &token_a_program_id,
-                &token_b_program_id,
-                &pool_token_program_id,
-                &accounts.swap_key,
-                &accounts.authority_key,
-                &accounts.authority_key,
+        assert!(quote.price_impact_bps > 0);
+        let fee_account_amount = token_amount(&accounts.pool_fee_account);
+        let swap_token_a_key = accounts.token_a_key;
+        let swap_token_b_key = accounts.token_b_key;
+        assert_eq!(
+            Err(SwapError::ExceededSlippage.into()),
+            accounts.swap(
+                &depositor_key,
                 &token_a_key,
-                &accounts.token_a_key,
-                &accounts.token_b_key,
+                &mut token_a_account,
+                &swap_token_a_key,
+                &swap_token_b_key,
                 &token_b_key,
-                &accounts.pool_mint_key,
-                &accounts.pool_fee_key,
-                &accounts.token_a_mint_key,
-                &accounts.token_b_mint_key,
-                Some(&pool_key),
-                Swap {
-                    amount_in: token_a_amount / 2,
-                    minimum_amount_out: 0,
-                },
+                &mut token_b_account,
+                initial_a / 10,
+                quote.amount_received + 1,
             )
-            .unwrap(),
-            vec![
-                &mut accounts.swap_account,
-                &mut SolanaAccount::default(),
-                &mut SolanaAccount::default(),
+        );
+        accounts
+            .swap(
+                &depositor_key,
+                &token_a_key,
                 &mut token_a_account,
-                &mut accounts.token_a_account,
-                &mut accounts.token_b_account,
+                &swap_token_a_key,
+                &swap_token_b_key,
+                &token_b_key,
                 &mut token_b_account,
-                &mut accounts.pool_mint_account,
-                &mut accounts.pool_fee_account,
-                &mut accounts.token_a_mint_account,
-                &mut accounts.token_b_mint_account,
-                &mut SolanaAccount::default(),
-                &mut SolanaAccount::default(),
-                &mut SolanaAccount::default(),
-                &mut pool_account,
-            ],
-            &constraints,
-        )
-        .unwrap();
-    }
-
-    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token_2022::id(); "all-token-2022")]
-    #[test_case(spl_token::id(), spl_token_2022::id(), spl_token_2022::id(); "mixed-pool-token")]
-    #[test_case(spl_token_2022::id(), spl_token_2022::id(), spl_token::id(); "mixed-pool-token-2022")]
-    fn test_swap_curve_with_transfer_fees(
-        pool_token_program_id: Pubkey,
-        token_a_program_id: Pubkey,
-        token_b_program_id: Pubkey,
-    ) {
-        // All fees
-        let trade_fee_numerator = 1;
-        let trade_fee_denominator = 10;
-        let owner_trade_fee_numerator = 1;
-        let owner_trade_fee_denominator = 30;
-        let owner_withdraw_fee_numerator = 1;
-        let owner_withdraw_fee_denominator = 30;
-        let host_fee_numerator = 20;
-        let host_fee_denominator = 100;
-        let fees = Fees {
-            trade_fee_numerator,
-            trade_fee_denominator,
-            owner_trade_fee_numerator,
-            owner_trade_fee_denominator,
-            owner_withdraw_fee_numerator,
-            owner_withdraw_fee_denominator,
-            host_fee_numerator,
-            host_fee_denominator,
-        };
+                initial_a / 10,
+                quote.minimum_amount_out,
+            )
+            .unwrap();
+        assert_eq!(token_amount(&token_a_account), initial_a - quote.amount_in);
+        assert_eq!(
+            token_amount(&token_b_account),
+            initial_b + quote.amount_received
+        );
+        assert_eq!(
+            token_amount(&accounts.token_b_account),
+            token_b_amount - quote.amount_out
+        );
+        assert_eq!(
+            token_amount(&accounts.pool_fee_account),
+            fee_account_amount + quote.owner_fee_pool_tokens
+        );
 
-        let token_a_amount = 10_000_000_000;
-        let token_b_amount = 50_000_000_000;
+        // deposit token B
+        let swap = SwapVersion::unpack(&accounts.swap_account.data).unwrap();
+        let deposit = PoolQuoter {
+            token_b_transfer_fee: Some(&token_b_transfer_fee),
+            ..quote_accounts(&accounts, swap.as_ref())
+        }
+        .quote_deposit_single_token_type_exact_amount_in(initial_b / 10, TradeDirection::BtoA, 50)
+        .unwrap();
+        let token_b_before = token_amount(&token_b_account);
+        let swap_token_b_before = token_amount(&accounts.token_b_account);
+        accounts
+            .deposit_single_token_type_exact_amount_in(
+                &depositor_key,
+                &token_b_key,
+                &mut token_b_account,
+                &pool_key,
+                &mut pool_account,
+                initial_b / 10,
+                deposit.minimum_pool_token_amount,
+            )
+            .unwrap();
+        assert_eq!(
+            token_amount(&token_b_account),
+            token_b_before - deposit.source_token_amount
+        );
+        assert_eq!(
+            token_amount(&accounts.token_b_account),
+            swap_token_b_before + deposit.source_token_amount - deposit.source_transfer_fee
+        );
+        assert_eq!(token_amount(&pool_account), deposit.pool_token_amount);
 
-        check_valid_swap_curve(
-            fees,
-            SwapTransferFees {
-                pool_token: TransferFee::default(),
-                token_a: TransferFee {
-                    epoch: 0.into(),
-                    transfer_fee_basis_points: 100.into(),
-                    maximum_fee: 1_000_000_000.into(),
-                },
-                token_b: TransferFee::default(),
-            },
-            CurveType::ConstantProduct,
-            Arc::new(ConstantProductCurve {}),
-            token_a_amount,
-            token_b_amount,
-            &pool_token_program_id,
-            &token_a_program_id,
-            &token_b_program_id,
+        // withdraw token A
+        let swap = SwapVersion::unpack(&accounts.swap_account.data).unwrap();
+        let withdraw = PoolQuoter {
+            token_a_transfer_fee: Some(&token_a_transfer_fee),
+            ..quote_accounts(&accounts, swap.as_ref())
+        }
+        .quote_withdraw_single_token_type_exact_amount_out(initial_a / 20, TradeDirection::AtoB, 50)
+        .unwrap();
+        let token_a_before = token_amount(&token_a_account);
+        let fee_account_amount = token_amount(&accounts.pool_fee_account);
+        accounts
+            .withdraw_single_token_type_exact_amount_out(
+                &depositor_key,
+                &pool_key,
+                &mut pool_account,
+                &token_a_key,
+                &mut token_a_account,
+                initial_a / 20,
+                withdraw.maximum_pool_token_amount,
+            )
+            .unwrap();
+        assert_eq!(
+            token_amount(&token_a_account),
+            token_a_before + withdraw.amount_received
+        );
+        assert_eq!(
+            token_amount(&pool_account),
+            deposit.pool_token_amount - withdraw.pool_token_amount
+        );
+        assert_eq!(
+            token_amount(&accounts.pool_fee_account),
+            fee_account_amount + withdraw.withdraw_fee
         );
     }
 }
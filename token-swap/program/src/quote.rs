@@ -0,0 +1,673 @@
+//! Off-chain quotes for swaps, deposits and withdrawals
+//!
+//! Every calculation mirrors the processor exactly, using the pool's
+//! `SwapCurve` and `Fees`, so a quote shows what the matching instruction will
+//! do against the same pool state.  Nothing here reads accounts or sysvars, and
+//! only `core` is used, so the functions can be called from any environment
+//! holding a copy of the pool data.
+
+use {
+    crate::{
+        curve::calculator::{RoundDirection, TradeDirection},
+        error::SwapError,
+        state::SwapState,
+    },
+    core::cmp,
+    spl_math::precise_number::PreciseNumber,
+    spl_token_2022::extension::transfer_fee::TransferFee,
+};
+
+/// Denominator for all basis point values
+pub const BASIS_POINTS_DENOMINATOR: u64 = 10_000;
+
+/// Snapshot of a pool, containing everything needed to quote against it
+pub struct PoolQuoter<'a> {
+    /// Swap state, providing the curve and fees
+    pub swap: &'a dyn SwapState,
+    /// Amount of token A held by the swap
+    pub token_a_amount: u64,
+    /// Amount of token B held by the swap
+    pub token_b_amount: u64,
+    /// Supply of the pool token mint
+    pub pool_token_supply: u64,
+    /// Token-2022 transfer fee on token A for the current epoch, if any
+    pub token_a_transfer_fee: Option<&'a TransferFee>,
+    /// Token-2022 transfer fee on token B for the current epoch, if any
+    pub token_b_transfer_fee: Option<&'a TransferFee>,
+}
+
+/// Expected result of a `Swap` instruction
+#[derive(Clone, Debug, PartialEq)]
+pub struct SwapQuote {
+    /// Amount debited from the user's source account, including the transfer
+    /// fee.  This may be less than the requested input if the curve does not
+    /// use all of it.
+    pub amount_in: u64,
+    /// Transfer fee withheld on the source token
+    pub source_transfer_fee: u64,
+    /// Amount sent out of the swap's destination account
+    pub amount_out: u64,
+    /// Transfer fee withheld on the destination token
+    pub destination_transfer_fee: u64,
+    /// Amount credited to the user's destination account
+    pub amount_received: u64,
+    /// Trading fee in source tokens, kept by the pool for liquidity providers
+    pub trade_fee: u64,
+    /// Owner trading fee in source tokens, kept by the pool
+    pub owner_fee: u64,
+    /// Pool tokens minted for the owner trading fee, including the host fee
+    pub owner_fee_pool_tokens: u64,
+    /// Part of `owner_fee_pool_tokens` minted to the host fee account, if one
+    /// is provided to the instruction
+    pub host_fee_pool_tokens: u64,
+    /// Difference between the spot price and the price received before fees,
+    /// in basis points
+    pub price_impact_bps: u64,
+    /// `amount_received` less the allowed slippage, to be used as the
+    /// instruction's `minimum_amount_out`
+    pub minimum_amount_out: u64,
+}
+
+/// Expected result of a `DepositSingleTokenTypeExactAmountIn` instruction
+#[derive(Clone, Debug, PartialEq)]
+pub struct DepositQuote {
+    /// Amount debited from the user's source account
+    pub source_token_amount: u64,
+    /// Transfer fee withheld on the source token, and never received by the
+    /// swap
+    pub source_transfer_fee: u64,
+    /// Pool tokens minted to the user
+    pub pool_token_amount: u64,
+    /// `pool_token_amount` less the allowed slippage, to be used as the
+    /// instruction's `minimum_pool_token_amount`
+    pub minimum_pool_token_amount: u64,
+}
+
+/// Expected result of a `WithdrawSingleTokenTypeExactAmountOut` instruction
+#[derive(Clone, Debug, PartialEq)]
+pub struct WithdrawQuote {
+    /// Amount sent out of the swap's token account
+    pub destination_token_amount: u64,
+    /// Transfer fee withheld on the destination token
+    pub destination_transfer_fee: u64,
+    /// Amount credited to the user's destination account
+    pub amount_received: u64,
+    /// Pool tokens taken from the user, including the withdraw fee
+    pub pool_token_amount: u64,
+    /// Part of `pool_token_amount` sent to the pool fee account.  No fee is
+    /// charged when withdrawing from the pool fee account itself.
+    pub withdraw_fee: u64,
+    /// `pool_token_amount` plus the allowed slippage, to be used as the
+    /// instruction's `maximum_pool_token_amount`
+    pub maximum_pool_token_amount: u64,
+}
+
+fn to_u64(val: u128) -> Result<u64, SwapError> {
+    u64::try_from(val).map_err(|_| SwapError::ConversionFailure)
+}
+
+fn transfer_fee(fee: Option<&TransferFee>, pre_fee_amount: u64) -> Result<u64, SwapError> {
+    fee.map_or(Some(0), |fee| fee.calculate_fee(pre_fee_amount))
+        .ok_or(SwapError::FeeCalculationFailure)
+}
+
+fn inverse_transfer_fee(fee: Option<&TransferFee>, post_fee_amount: u64) -> Result<u64, SwapError> {
+    fee.map_or(Some(0), |fee| fee.calculate_inverse_fee(post_fee_amount))
+        .ok_or(SwapError::FeeCalculationFailure)
+}
+
+fn check_slippage(slippage_bps: u64) -> Result<(), SwapError> {
+    if slippage_bps > BASIS_POINTS_DENOMINATOR {
+        Err(SwapError::InvalidInput)
+    } else {
+        Ok(())
+    }
+}
+
+/// Reduce an amount by the given slippage, rounding down
+pub fn minimum_with_slippage(amount: u64, slippage_bps: u64) -> Result<u64, SwapError> {
+    check_slippage(slippage_bps)?;
+    let amount = u128::from(amount)
+        .checked_mul(u128::from(BASIS_POINTS_DENOMINATOR - slippage_bps))
+        .ok_or(SwapError::CalculationFailure)?
+        / u128::from(BASIS_POINTS_DENOMINATOR);
+    to_u64(amount)
+}
+
+/// Increase an amount by the given slippage, rounding up
+pub fn maximum_with_slippage(amount: u64, slippage_bps: u64) -> Result<u64, SwapError> {
+    check_slippage(slippage_bps)?;
+    let denominator = u128::from(BASIS_POINTS_DENOMINATOR);
+    let amount = u128::from(amount)
+        .checked_mul(denominator + u128::from(slippage_bps))
+        .ok_or(SwapError::CalculationFailure)?
+        .div_ceil(denominator);
+    Ok(u64::try_from(amount).unwrap_or(u64::MAX))
+}
+
+impl PoolQuoter<'_> {
+    fn check_not_paused(&self) -> Result<(), SwapError> {
+        if self.swap.is_paused() {
+            Err(SwapError::SwapPaused)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn transfer_fees(
+        &self,
+        trade_direction: TradeDirection,
+    ) -> (Option<&TransferFee>, Option<&TransferFee>) {
+        match trade_direction {
+            TradeDirection::AtoB => (self.token_a_transfer_fee, self.token_b_transfer_fee),
+            TradeDirection::BtoA => (self.token_b_transfer_fee, self.token_a_transfer_fee),
+        }
+    }
+
+    /// Quote a swap of `amount_in` source tokens, as given to the `Swap`
+    /// instruction
+    pub fn quote_swap(
+        &self,
+        amount_in: u64,
+        trade_direction: TradeDirection,
+        slippage_bps: u64,
+    ) -> Result<SwapQuote, SwapError> {
+        self.check_not_paused()?;
+        let swap_curve = self.swap.swap_curve();
+        let fees = self.swap.fees();
+        let (source_transfer_fee, destination_transfer_fee) = self.transfer_fees(trade_direction);
+        let (swap_source_amount, swap_destination_amount) = match trade_direction {
+            TradeDirection::AtoB => (self.token_a_amount, self.token_b_amount),
+            TradeDirection::BtoA => (self.token_b_amount, self.token_a_amount),
+        };
+        let (swap_source_amount, swap_destination_amount) = (
+            u128::from(swap_source_amount),
+            u128::from(swap_destination_amount),
+        );
+
+        let actual_amount_in =
+            amount_in.saturating_sub(transfer_fee(source_transfer_fee, amount_in)?);
+        let result = swap_curve
+            .swap(
+                u128::from(actual_amount_in),
+                swap_source_amount,
+                swap_destination_amount,
+                trade_direction,
+                fees,
+            )
+            .ok_or(SwapError::ZeroTradingTokens)?;
+
+        let source_amount_swapped = to_u64(result.source_amount_swapped)?;
+        let source_transfer_fee = inverse_transfer_fee(source_transfer_fee, source_amount_swapped)?;
+        let amount_in = source_amount_swapped.saturating_add(source_transfer_fee);
+
+        let amount_out = to_u64(result.destination_amount_swapped)?;
+        let destination_transfer_fee = transfer_fee(destination_transfer_fee, amount_out)?;
+        let amount_received = amount_out.saturating_sub(destination_transfer_fee);
+
+        let (owner_fee_pool_tokens, host_fee_pool_tokens) = if result.owner_fee > 0 {
+            let (swap_token_a_amount, swap_token_b_amount) = match trade_direction {
+                TradeDirection::AtoB => (
+                    result.new_swap_source_amount,
+                    result.new_swap_destination_amount,
+                ),
+                TradeDirection::BtoA => (
+                    result.new_swap_destination_amount,
+                    result.new_swap_source_amount,
+                ),
+            };
+            let pool_token_amount = swap_curve
+                .calculator
+                .withdraw_single_token_type_exact_out(
+                    result.owner_fee,
+                    swap_token_a_amount,
+                    swap_token_b_amount,
+                    u128::from(self.pool_token_supply),
+                    trade_direction,
+                    RoundDirection::Floor,
+                )
+                .ok_or(SwapError::FeeCalculationFailure)?;
+            let host_fee = fees
+                .host_fee(pool_token_amount)
+                .ok_or(SwapError::FeeCalculationFailure)?;
+            (to_u64(pool_token_amount)?, to_u64(host_fee)?)
+        } else {
+            (0, 0)
+        };
+
+        // compare the curve's output, before fees, to the same input at the
+        // spot price
+        let source_amount_less_fees = result
+            .source_amount_swapped
+            .checked_sub(result.trade_fee)
+            .and_then(|amount| amount.checked_sub(result.owner_fee))
+            .ok_or(SwapError::CalculationFailure)?;
+        let price_impact_bps = swap_curve
+            .calculator
+            .spot_price(swap_source_amount, swap_destination_amount, trade_direction)
+            .and_then(|spot_price| {
+                let spot_amount_out =
+                    PreciseNumber::new(source_amount_less_fees)?.checked_mul(&spot_price)?;
+                let amount_out = PreciseNumber::new(result.destination_amount_swapped)?;
+                if amount_out.greater_than_or_equal(&spot_amount_out) {
+                    return Some(0);
+                }
+                spot_amount_out
+                    .checked_sub(&amount_out)?
+                    .checked_mul(&PreciseNumber::new(BASIS_POINTS_DENOMINATOR.into())?)?
+                    .checked_div(&spot_amount_out)?
+                    .floor()?
+                    .to_imprecise()
+            })
+            .ok_or(SwapError::CalculationFailure)?;
+
+        Ok(SwapQuote {
+            amount_in,
+            source_transfer_fee,
+            amount_out,
+            destination_transfer_fee,
+            amount_received,
+            trade_fee: to_u64(result.trade_fee)?,
+            owner_fee: to_u64(result.owner_fee)?,
+            owner_fee_pool_tokens,
+            host_fee_pool_tokens,
+            price_impact_bps: to_u64(cmp::min(
+                price_impact_bps,
+                u128::from(BASIS_POINTS_DENOMINATOR),
+            ))?,
+            minimum_amount_out: minimum_with_slippage(amount_received, slippage_bps)?,
+        })
+    }
+
+    /// Quote a deposit of `source_token_amount` of token A or B, as given to
+    /// the `DepositSingleTokenTypeExactAmountIn` instruction
+    pub fn quote_deposit_single_token_type_exact_amount_in(
+        &self,
+        source_token_amount: u64,
+        trade_direction: TradeDirection,
+        slippage_bps: u64,
+    ) -> Result<DepositQuote, SwapError> {
+        self.check_not_paused()?;
+        let swap_curve = self.swap.swap_curve();
+        if !swap_curve.calculator.allows_deposits() {
+            return Err(SwapError::UnsupportedCurveOperation);
+        }
+        let (source_transfer_fee, _) = self.transfer_fees(trade_direction);
+        let pool_token_amount = if self.pool_token_supply > 0 {
+            swap_curve
+                .deposit_single_token_type(
+                    u128::from(source_token_amount),
+                    u128::from(self.token_a_amount),
+                    u128::from(self.token_b_amount),
+                    u128::from(self.pool_token_supply),
+                    trade_direction,
+                    self.swap.fees(),
+                )
+                .ok_or(SwapError::ZeroTradingTokens)?
+        } else {
+            swap_curve.calculator.new_pool_supply()
+        };
+        let pool_token_amount = to_u64(pool_token_amount)?;
+        if pool_token_amount == 0 {
+            return Err(SwapError::ZeroTradingTokens);
+        }
+        Ok(DepositQuote {
+            source_token_amount,
+            source_transfer_fee: transfer_fee(source_transfer_fee, source_token_amount)?,
+            pool_token_amount,
+            minimum_pool_token_amount: minimum_with_slippage(pool_token_amount, slippage_bps)?,
+        })
+    }
+
+    /// Quote a withdrawal of exactly `destination_token_amount` of token A or
+    /// B, as given to the `WithdrawSingleTokenTypeExactAmountOut` instruction
+    ///
+    /// The trade direction is from the token withdrawn to the other token, so
+    /// `AtoB` withdraws token A.
+    pub fn quote_withdraw_single_token_type_exact_amount_out(
+        &self,
+        destination_token_amount: u64,
+        trade_direction: TradeDirection,
+        slippage_bps: u64,
+    ) -> Result<WithdrawQuote, SwapError> {
+        self.check_not_paused()?;
+        let fees = self.swap.fees();
+        let (destination_transfer_fee, _) = self.transfer_fees(trade_direction);
+        let burn_pool_token_amount = self
+            .swap
+            .swap_curve()
+            .withdraw_single_token_type_exact_out(
+                u128::from(destination_token_amount),
+                u128::from(self.token_a_amount),
+                u128::from(self.token_b_amount),
+                u128::from(self.pool_token_supply),
+                trade_direction,
+                fees,
+            )
+            .ok_or(SwapError::ZeroTradingTokens)?;
+        let withdraw_fee = fees
+            .owner_withdraw_fee(burn_pool_token_amount)
+            .ok_or(SwapError::FeeCalculationFailure)?;
+        let pool_token_amount = to_u64(
+            burn_pool_token_amount
+                .checked_add(withdraw_fee)
+                .ok_or(SwapError::CalculationFailure)?,
+        )?;
+        if pool_token_amount == 0 {
+            return Err(SwapError::ZeroTradingTokens);
+        }
+        let destination_transfer_fee =
+            transfer_fee(destination_transfer_fee, destination_token_amount)?;
+        Ok(WithdrawQuote {
+            destination_token_amount,
+            destination_transfer_fee,
+            amount_received: destination_token_amount.saturating_sub(destination_transfer_fee),
+            pool_token_amount,
+            withdraw_fee: to_u64(withdraw_fee)?,
+            maximum_pool_token_amount: maximum_with_slippage(pool_token_amount, slippage_bps)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            curve::{
+                base::{CurveType, SwapCurve},
+                constant_price::ConstantPriceCurve,
+                constant_product::ConstantProductCurve,
+                fees::Fees,
+                offset::OffsetCurve,
+            },
+            state::SwapV2,
+        },
+        solana_program::pubkey::Pubkey,
+        std::sync::Arc,
+    };
+
+    fn test_fees() -> Fees {
+        Fees {
+            trade_fee_numerator: 25,
+            trade_fee_denominator: 10_000,
+            owner_trade_fee_numerator: 5,
+            owner_trade_fee_denominator: 10_000,
+            owner_withdraw_fee_numerator: 1,
+            owner_withdraw_fee_denominator: 100,
+            host_fee_numerator: 20,
+            host_fee_denominator: 100,
+        }
+    }
+
+    fn test_swap(swap_curve: SwapCurve) -> SwapV2 {
+        SwapV2 {
+            is_initialized: true,
+            bump_seed: 255,
+            token_program_id: spl_token_2022::id(),
+            token_a: Pubkey::new_unique(),
+            token_b: Pubkey::new_unique(),
+            pool_mint: Pubkey::new_unique(),
+            token_a_mint: Pubkey::new_unique(),
+            token_b_mint: Pubkey::new_unique(),
+            pool_fee_account: Pubkey::new_unique(),
+            fees: test_fees(),
+            swap_curve,
+            pause_authority: Pubkey::new_unique(),
+            is_paused: false,
+        }
+    }
+
+    fn constant_product_swap() -> SwapV2 {
+        test_swap(SwapCurve {
+            curve_type: CurveType::ConstantProduct,
+            calculator: Arc::new(ConstantProductCurve),
+        })
+    }
+
+    fn test_quoter(swap: &SwapV2) -> PoolQuoter {
+        PoolQuoter {
+            swap,
+            token_a_amount: 1_000_000_000,
+            token_b_amount: 5_000_000_000,
+            pool_token_supply: 1_000_000_000,
+            token_a_transfer_fee: None,
+            token_b_transfer_fee: None,
+        }
+    }
+
+    #[test]
+    fn slippage_bounds() {
+        assert_eq!(minimum_with_slippage(1_000, 0).unwrap(), 1_000);
+        assert_eq!(minimum_with_slippage(1_000, 50).unwrap(), 995);
+        assert_eq!(minimum_with_slippage(999, 1).unwrap(), 998);
+        assert_eq!(minimum_with_slippage(1_000, 10_000).unwrap(), 0);
+        assert_eq!(maximum_with_slippage(1_000, 50).unwrap(), 1_005);
+        assert_eq!(maximum_with_slippage(999, 1).unwrap(), 1_000);
+        assert_eq!(maximum_with_slippage(u64::MAX, 1).unwrap(), u64::MAX);
+        assert_eq!(
+            minimum_with_slippage(1_000, 10_001),
+            Err(SwapError::InvalidInput)
+        );
+    }
+
+    #[test]
+    fn swap_quote_matches_curve() {
+        let swap = constant_product_swap();
+        let quoter = test_quoter(&swap);
+        let amount_in = 10_000_000;
+        let quote = quoter
+            .quote_swap(amount_in, TradeDirection::AtoB, 100)
+            .unwrap();
+        let result = swap
+            .swap_curve
+            .swap(
+                amount_in.into(),
+                1_000_000_000,
+                5_000_000_000,
+                TradeDirection::AtoB,
+                &swap.fees,
+            )
+            .unwrap();
+        assert_eq!(u128::from(quote.amount_in), result.source_amount_swapped);
+        assert_eq!(
+            u128::from(quote.amount_out),
+            result.destination_amount_swapped
+        );
+        assert_eq!(quote.amount_received, quote.amount_out);
+        assert_eq!(quote.source_transfer_fee, 0);
+        assert_eq!(quote.destination_transfer_fee, 0);
+        assert_eq!(quote.trade_fee, 25_000);
+        assert_eq!(quote.owner_fee, 5_000);
+        assert!(quote.owner_fee_pool_tokens > 0);
+        assert_eq!(
+            quote.host_fee_pool_tokens,
+            quote.owner_fee_pool_tokens * 20 / 100
+        );
+        assert_eq!(quote.minimum_amount_out, quote.amount_received * 99 / 100);
+        // 1% of the pool moves the price by roughly 1%
+        assert!((95..=100).contains(&quote.price_impact_bps));
+
+        let larger_quote = quoter
+            .quote_swap(amount_in * 10, TradeDirection::AtoB, 100)
+            .unwrap();
+        assert!(larger_quote.price_impact_bps > quote.price_impact_bps * 9);
+    }
+
+    #[test]
+    fn swap_quote_spot_prices() {
+        let swap = test_swap(SwapCurve {
+            curve_type: CurveType::ConstantPrice,
+            calculator: Arc::new(ConstantPriceCurve { token_b_price: 5 }),
+        });
+        let quote = test_quoter(&swap)
+            .quote_swap(10_000_000, TradeDirection::BtoA, 0)
+            .unwrap();
+        assert_eq!(quote.price_impact_bps, 0);
+        assert_eq!(quote.amount_out, (10_000_000 - 25_000 - 5_000) * 5);
+        assert_eq!(quote.minimum_amount_out, quote.amount_out);
+
+        let swap = test_swap(SwapCurve {
+            curve_type: CurveType::Offset,
+            calculator: Arc::new(OffsetCurve {
+                token_b_offset: 5_000_000_000,
+            }),
+        });
+        let quoter = PoolQuoter {
+            token_b_amount: 0,
+            ..test_quoter(&swap)
+        };
+        // no token B is actually present
+        assert_eq!(
+            quoter.quote_swap(1_000_000, TradeDirection::AtoB, 0),
+            Err(SwapError::ZeroTradingTokens)
+        );
+        // selling B against the offset moves the price like a constant product
+        // pool with 5,000,000,000 token B
+        let quote = quoter
+            .quote_swap(10_000_000, TradeDirection::BtoA, 0)
+            .unwrap();
+        assert!((19..=20).contains(&quote.price_impact_bps));
+    }
+
+    #[test]
+    fn swap_quote_transfer_fees() {
+        let swap = constant_product_swap();
+        let token_a_transfer_fee = TransferFee {
+            epoch: 0.into(),
+            maximum_fee: u64::MAX.into(),
+            transfer_fee_basis_points: 100.into(),
+        };
+        let token_b_transfer_fee = TransferFee {
+            epoch: 0.into(),
+            maximum_fee: 1_000.into(),
+            transfer_fee_basis_points: 500.into(),
+        };
+        let quoter = PoolQuoter {
+            token_a_transfer_fee: Some(&token_a_transfer_fee),
+            token_b_transfer_fee: Some(&token_b_transfer_fee),
+            ..test_quoter(&swap)
+        };
+        let amount_in = 10_000_000;
+        let quote = quoter
+            .quote_swap(amount_in, TradeDirection::AtoB, 0)
+            .unwrap();
+        assert_eq!(quote.amount_in, amount_in);
+        assert_eq!(quote.source_transfer_fee, 100_000);
+        assert_eq!(quote.destination_transfer_fee, 1_000);
+        assert_eq!(quote.amount_received, quote.amount_out - 1_000);
+        assert_eq!(quote.minimum_amount_out, quote.amount_received);
+        let result = swap
+            .swap_curve
+            .swap(
+                (amount_in - 100_000).into(),
+                1_000_000_000,
+                5_000_000_000,
+                TradeDirection::AtoB,
+                &swap.fees,
+            )
+            .unwrap();
+        assert_eq!(
+            u128::from(quote.amount_out),
+            result.destination_amount_swapped
+        );
+
+        let withdraw = quoter
+            .quote_withdraw_single_token_type_exact_amount_out(1_000_000, TradeDirection::AtoB, 0)
+            .unwrap();
+        assert_eq!(withdraw.destination_transfer_fee, 10_000);
+        assert_eq!(withdraw.amount_received, 990_000);
+
+        let deposit = quoter
+            .quote_deposit_single_token_type_exact_amount_in(1_000_000, TradeDirection::BtoA, 0)
+            .unwrap();
+        assert_eq!(deposit.source_transfer_fee, 1_000);
+    }
+
+    #[test]
+    fn single_token_quotes() {
+        let swap = constant_product_swap();
+        let quoter = test_quoter(&swap);
+        let deposit = quoter
+            .quote_deposit_single_token_type_exact_amount_in(10_000_000, TradeDirection::AtoB, 50)
+            .unwrap();
+        let pool_token_amount = swap
+            .swap_curve
+            .deposit_single_token_type(
+                10_000_000,
+                1_000_000_000,
+                5_000_000_000,
+                1_000_000_000,
+                TradeDirection::AtoB,
+                &swap.fees,
+            )
+            .unwrap();
+        assert_eq!(u128::from(deposit.pool_token_amount), pool_token_amount);
+        assert_eq!(
+            deposit.minimum_pool_token_amount,
+            deposit.pool_token_amount * 995 / 1000
+        );
+
+        let withdraw = quoter
+            .quote_withdraw_single_token_type_exact_amount_out(10_000_000, TradeDirection::BtoA, 50)
+            .unwrap();
+        let burn_pool_token_amount = swap
+            .swap_curve
+            .withdraw_single_token_type_exact_out(
+                10_000_000,
+                1_000_000_000,
+                5_000_000_000,
+                1_000_000_000,
+                TradeDirection::BtoA,
+                &swap.fees,
+            )
+            .unwrap();
+        assert_eq!(
+            u128::from(withdraw.pool_token_amount - withdraw.withdraw_fee),
+            burn_pool_token_amount
+        );
+        assert_eq!(
+            u128::from(withdraw.withdraw_fee),
+            burn_pool_token_amount / 100
+        );
+        assert!(withdraw.maximum_pool_token_amount > withdraw.pool_token_amount);
+
+        let swap = test_swap(SwapCurve {
+            curve_type: CurveType::Offset,
+            calculator: Arc::new(OffsetCurve {
+                token_b_offset: 1_000,
+            }),
+        });
+        assert_eq!(
+            test_quoter(&swap).quote_deposit_single_token_type_exact_amount_in(
+                10_000_000,
+                TradeDirection::AtoB,
+                0
+            ),
+            Err(SwapError::UnsupportedCurveOperation)
+        );
+    }
+
+    #[test]
+    fn paused_swap_quotes() {
+        let mut swap = constant_product_swap();
+        swap.is_paused = true;
+        let quoter = test_quoter(&swap);
+        assert_eq!(
+            quoter.quote_swap(1_000, TradeDirection::AtoB, 0),
+            Err(SwapError::SwapPaused)
+        );
+        assert_eq!(
+            quoter.quote_deposit_single_token_type_exact_amount_in(1_000, TradeDirection::AtoB, 0),
+            Err(SwapError::SwapPaused)
+        );
+        assert_eq!(
+            quoter.quote_withdraw_single_token_type_exact_amount_out(
+                1_000,
+                TradeDirection::AtoB,
+                0
+            ),
+            Err(SwapError::SwapPaused)
+        );
+    }
+}
@@ -2,7 +2,15 @@
 
 use {
     crate::{
-        curve::{base::SwapCurve, fees::Fees},
+        curve::{
+            base::{SwapCurve, SwapResult},
+            calculator::{RoundDirection, TradeDirection, TradingTokenResult},
+            concentrated_liquidity::{
+                amounts_for_liquidity, compute_swap_step, sqrt_price_at_tick,
+                tick_at_sqrt_price_between, MAX_SQRT_PRICE, MAX_TICK, MIN_SQRT_PRICE, MIN_TICK,
+            },
+            fees::Fees,
+        },
         error::SwapError,
     },
     arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs},
@@ -14,11 +22,12 @@ use {
         program_pack::{IsInitialized, Pack, Sealed},
         pubkey::Pubkey,
     },
+    spl_math::uint::U256,
     spl_token_2022::{
         extension::StateWithExtensions,
         state::{Account, AccountState},
     },
-    std::sync::Arc,
+    std::{convert::TryFrom, sync::Arc},
 };
 
 /// Trait representing access to program state across all versions
@@ -52,13 +61,20 @@ pub trait SwapState {
     fn fees(&self) -> &Fees;
     /// Curve associated with swap
     fn swap_curve(&self) -> &SwapCurve;
+
+    /// Authority allowed to pause and resume the swap, if any
+    fn pause_authority(&self) -> Option<&Pubkey>;
+    /// Is the swap paused, only allowing withdrawals of all token types
+    fn is_paused(&self) -> bool;
 }
 
 /// All versions of SwapState
 #[enum_dispatch(SwapState)]
 pub enum SwapVersion {
-    /// Latest version, used for all new swaps
+    /// Original version, without a pause authority
     SwapV1,
+    /// Latest version, used for all new swaps
+    SwapV2,
 }
 
 /// SwapVersion does not implement program_pack::Pack because there are size
@@ -66,14 +82,22 @@ pub enum SwapVersion {
 /// special implementations are provided here
 impl SwapVersion {
     /// Size of the latest version of the SwapState
-    pub const LATEST_LEN: usize = 1 + SwapV1::LEN; // add one for the version enum
+    pub const LATEST_LEN: usize = 1 + SwapV2::LEN; // add one for the version enum
+
+    /// Size of the original version of the SwapState, still accepted on
+    /// initialization for accounts allocated by older clients
+    pub const V1_LEN: usize = 1 + SwapV1::LEN;
 
     /// Pack a swap into a byte array, based on its version
     pub fn pack(src: Self, dst: &mut [u8]) -> Result<(), ProgramError> {
         match src {
             Self::SwapV1(swap_info) => {
                 dst[0] = 1;
-                SwapV1::pack(swap_info, &mut dst[1..])
+                SwapV1::pack(swap_info, &mut dst[1..1 + SwapV1::LEN])
+            }
+            Self::SwapV2(swap_info) => {
+                dst[0] = 2;
+                SwapV2::pack(swap_info, &mut dst[1..1 + SwapV2::LEN])
             }
         }
     }
@@ -85,14 +109,37 @@ impl SwapVersion {
             .split_first()
             .ok_or(ProgramError::InvalidAccountData)?;
         match version {
-            1 => Ok(Arc::new(SwapV1::unpack(rest)?)),
+            1 => Ok(Arc::new(SwapV1::unpack(
+                rest.get(..SwapV1::LEN)
+                    .ok_or(ProgramError::InvalidAccountData)?,
+            )?)),
+            2 => Ok(Arc::new(SwapV2::unpack(rest)?)),
+            _ => Err(ProgramError::UninitializedAccount),
+        }
+    }
+
+    /// Unpack the latest version of the swap account, for instructions that
+    /// need to modify state only available in the latest version.
+    ///
+    /// Legacy `SwapV1` accounts are rejected rather than migrated, so they
+    /// never gain a pause authority.
+    pub fn unpack_latest(input: &[u8]) -> Result<SwapV2, ProgramError> {
+        let (&version, rest) = input
+            .split_first()
+            .ok_or(ProgramError::InvalidAccountData)?;
+        match version {
+            2 => SwapV2::unpack(rest),
+            1 => Err(SwapError::UnsupportedSwapVersion.into()),
             _ => Err(ProgramError::UninitializedAccount),
         }
     }
 
     /// Special check to be done before any instruction processing, works for
-    /// all versions
+    /// all versions, as well as concentrated liquidity pools and positions
     pub fn is_initialized(input: &[u8]) -> bool {
+        if let Some(&CONCENTRATED_POOL_TAG | &POSITION_TAG) = input.first() {
+            return true;
+        }
         match Self::unpack(input) {
             Ok(swap) => swap.is_initialized(),
             Err(_) => false,
@@ -100,6 +147,29 @@ impl SwapVersion {
     }
 }
 
+/// Check that the pool fee info is a valid token account for the pool mint
+fn check_pool_fee_info(
+    pool_fee_info: &AccountInfo,
+    token_program_id: &Pubkey,
+    pool_mint: &Pubkey,
+) -> Result<(), ProgramError> {
+    let data = &pool_fee_info.data.borrow();
+    let token_account = StateWithExtensions::<Account>::unpack(data).map_err(|err| match err {
+        ProgramError::InvalidAccountData | ProgramError::UninitializedAccount => {
+            SwapError::InvalidFeeAccount.into()
+        }
+        _ => err,
+    })?;
+    if pool_fee_info.owner != token_program_id
+        || token_account.base.state != AccountState::Initialized
+        || token_account.base.mint != *pool_mint
+    {
+        msg!("Pool fee account is not owned by token program, is not initialized, or does not match stake pool's mint");
+        return Err(SwapError::InvalidFeeAccount.into());
+    }
+    Ok(())
+}
+
 /// Program states.
 #[repr(C)]
 #[derive(Debug, Default, PartialEq)]
@@ -179,22 +249,7 @@ impl SwapState for SwapV1 {
     }
 
     fn check_pool_fee_info(&self, pool_fee_info: &AccountInfo) -> Result<(), ProgramError> {
-        let data = &pool_fee_info.data.borrow();
-        let token_account =
-            StateWithExtensions::<Account>::unpack(data).map_err(|err| match err {
-                ProgramError::InvalidAccountData | ProgramError::UninitializedAccount => {
-                    SwapError::InvalidFeeAccount.into()
-                }
-                _ => err,
-            })?;
-        if pool_fee_info.owner != &self.token_program_id
-            || token_account.base.state != AccountState::Initialized
-            || token_account.base.mint != self.pool_mint
-        {
-            msg!("Pool fee account is not owned by token program, is not initialized, or does not match stake pool's mint");
-            return Err(SwapError::InvalidFeeAccount.into());
-        }
-        Ok(())
+        check_pool_fee_info(pool_fee_info, &self.token_program_id, &self.pool_mint)
     }
 
     fn fees(&self) -> &Fees {
@@ -204,6 +259,14 @@ impl SwapState for SwapV1 {
     fn swap_curve(&self) -> &SwapCurve {
         &self.swap_curve
     }
+
+    fn pause_authority(&self) -> Option<&Pubkey> {
+        None
+    }
+
+    fn is_paused(&self) -> bool {
+        false
+    }
 }
 
 impl Sealed for SwapV1 {}
@@ -281,6 +344,972 @@ impl Pack for SwapV1 {
     }
 }
 
+/// Program states, with an authority able to pause the swap.
+#[repr(C)]
+#[derive(Debug, Default, PartialEq)]
+pub struct SwapV2 {
+    /// Initialized state.
+    pub is_initialized: bool,
+    /// Bump seed used in program address.
+    /// The program address is created deterministically with the bump seed,
+    /// swap program id, and swap account pubkey.  This program address has
+    /// authority over the swap's token A account, token B account, and pool
+    /// token mint.
+    pub bump_seed: u8,
+
+    /// Program ID of the tokens being exchanged.
+    pub token_program_id: Pubkey,
+
+    /// Token A
+    pub token_a: Pubkey,
+    /// Token B
+    pub token_b: Pubkey,
+
+    /// Pool tokens are issued when A or B tokens are deposited.
+    /// Pool tokens can be withdrawn back to the original A or B token.
+    pub pool_mint: Pubkey,
+
+    /// Mint information for token A
+    pub token_a_mint: Pubkey,
+    /// Mint information for token B
+    pub token_b_mint: Pubkey,
+
+    /// Pool token account to receive trading and / or withdrawal fees
+    pub pool_fee_account: Pubkey,
+
+    /// All fee information
+    pub fees: Fees,
+
+    /// Swap curve parameters, to be unpacked and used by the SwapCurve, which
+    /// calculates swaps, deposits, and withdrawals
+    pub swap_curve: SwapCurve,
+
+    /// Authority allowed to pause and resume the swap.  Set to the owner of
+    /// the pool fee account on initialization.
+    pub pause_authority: Pubkey,
+
+    /// Paused state.  While paused, only withdrawals of all token types are
+    /// allowed, so that liquidity providers can always exit the pool.
+    pub is_paused: bool,
+}
+
+impl SwapState for SwapV2 {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+
+    fn bump_seed(&self) -> u8 {
+        self.bump_seed
+    }
+
+    fn token_program_id(&self) -> &Pubkey {
+        &self.token_program_id
+    }
+
+    fn token_a_account(&self) -> &Pubkey {
+        &self.token_a
+    }
+
+    fn token_b_account(&self) -> &Pubkey {
+        &self.token_b
+    }
+
+    fn pool_mint(&self) -> &Pubkey {
+        &self.pool_mint
+    }
+
+    fn token_a_mint(&self) -> &Pubkey {
+        &self.token_a_mint
+    }
+
+    fn token_b_mint(&self) -> &Pubkey {
+        &self.token_b_mint
+    }
+
+    fn pool_fee_account(&self) -> &Pubkey {
+        &self.pool_fee_account
+    }
+
+    fn check_pool_fee_info(&self, pool_fee_info: &AccountInfo) -> Result<(), ProgramError> {
+        check_pool_fee_info(pool_fee_info, &self.token_program_id, &self.pool_mint)
+    }
+
+    fn fees(&self) -> &Fees {
+        &self.fees
+    }
+
+    fn swap_curve(&self) -> &SwapCurve {
+        &self.swap_curve
+    }
+
+    fn pause_authority(&self) -> Option<&Pubkey> {
+        Some(&self.pause_authority)
+    }
+
+    fn is_paused(&self) -> bool {
+        self.is_paused
+    }
+}
+
+impl Sealed for SwapV2 {}
+impl IsInitialized for SwapV2 {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for SwapV2 {
+    const LEN: usize = 356;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 356];
+        let (
+            is_initialized,
+            bump_seed,
+            token_program_id,
+            token_a,
+            token_b,
+            pool_mint,
+            token_a_mint,
+            token_b_mint,
+            pool_fee_account,
+            fees,
+            swap_curve,
+            pause_authority,
+            is_paused,
+        ) = mut_array_refs![output, 1, 1, 32, 32, 32, 32, 32, 32, 32, 64, 33, 32, 1];
+        is_initialized[0] = self.is_initialized as u8;
+        bump_seed[0] = self.bump_seed;
+        token_program_id.copy_from_slice(self.token_program_id.as_ref());
+        token_a.copy_from_slice(self.token_a.as_ref());
+        token_b.copy_from_slice(self.token_b.as_ref());
+        pool_mint.copy_from_slice(self.pool_mint.as_ref());
+        token_a_mint.copy_from_slice(self.token_a_mint.as_ref());
+        token_b_mint.copy_from_slice(self.token_b_mint.as_ref());
+        pool_fee_account.copy_from_slice(self.pool_fee_account.as_ref());
+        self.fees.pack_into_slice(&mut fees[..]);
+        self.swap_curve.pack_into_slice(&mut swap_curve[..]);
+        pause_authority.copy_from_slice(self.pause_authority.as_ref());
+        is_paused[0] = self.is_paused as u8;
+    }
+
+    /// Unpacks a byte buffer into a [SwapV2](struct.SwapV2.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, 356];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            is_initialized,
+            bump_seed,
+            token_program_id,
+            token_a,
+            token_b,
+            pool_mint,
+            token_a_mint,
+            token_b_mint,
+            pool_fee_account,
+            fees,
+            swap_curve,
+            pause_authority,
+            is_paused,
+        ) = array_refs![input, 1, 1, 32, 32, 32, 32, 32, 32, 32, 64, 33, 32, 1];
+        Ok(Self {
+            is_initialized: match is_initialized {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            bump_seed: bump_seed[0],
+            token_program_id: Pubkey::new_from_array(*token_program_id),
+            token_a: Pubkey::new_from_array(*token_a),
+            token_b: Pubkey::new_from_array(*token_b),
+            pool_mint: Pubkey::new_from_array(*pool_mint),
+            token_a_mint: Pubkey::new_from_array(*token_a_mint),
+            token_b_mint: Pubkey::new_from_array(*token_b_mint),
+            pool_fee_account: Pubkey::new_from_array(*pool_fee_account),
+            fees: Fees::unpack_from_slice(fees)?,
+            swap_curve: SwapCurve::unpack_from_slice(swap_curve)?,
+            pause_authority: Pubkey::new_from_array(*pause_authority),
+            is_paused: match is_paused {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+        })
+    }
+}
+
+/// Account tag of concentrated liquidity pools, following the swap versions
+pub const CONCENTRATED_POOL_TAG: u8 = 3;
+/// Account tag of concentrated liquidity positions
+pub const POSITION_TAG: u8 = 4;
+/// Maximum number of initialized ticks tracked by a concentrated liquidity
+/// pool
+pub const MAX_TICKS: usize = 64;
+
+fn unpack_tag(tag: u8, expected: u8) -> Result<bool, ProgramError> {
+    match tag {
+        0 => Ok(false),
+        tag if tag == expected => Ok(true),
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
+
+fn add_liquidity_delta(liquidity: u128, liquidity_delta: i128) -> Result<u128, SwapError> {
+    if liquidity_delta >= 0 {
+        liquidity.checked_add(liquidity_delta.unsigned_abs())
+    } else {
+        liquidity.checked_sub(liquidity_delta.unsigned_abs())
+    }
+    .ok_or(SwapError::CalculationFailure)
+}
+
+/// Fees earned by `liquidity` given a Q64.64 growth in fees per unit of
+/// liquidity
+fn fees_for_growth(fee_growth: u128, liquidity: u128) -> Result<u64, SwapError> {
+    let fees = U256::from(fee_growth)
+        .checked_mul(U256::from(liquidity))
+        .ok_or(SwapError::CalculationFailure)?
+        >> 64;
+    u64::try_from(fees).map_err(|_| SwapError::ConversionFailure)
+}
+
+/// Q64.64 growth in fees per unit of liquidity from `fee` earned by
+/// `liquidity`
+fn growth_for_fees(fee: u128, liquidity: u128) -> Result<u128, SwapError> {
+    let growth = (U256::from(fee) << 64)
+        .checked_div(U256::from(liquidity))
+        .ok_or(SwapError::CalculationFailure)?;
+    u128::try_from(growth).map_err(|_| SwapError::CalculationFailure)
+}
+
+/// Initialized tick of a concentrated liquidity pool, where the pool's active
+/// liquidity changes as the price crosses it.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TickInfo {
+    /// Tick index
+    pub index: i32,
+    /// Liquidity added to the pool when the price crosses the tick going up,
+    /// and removed when going down
+    pub liquidity_net: i128,
+    /// Total liquidity of all positions using the tick as a bound, the tick is
+    /// no longer tracked once this reaches zero
+    pub liquidity_gross: u128,
+    /// Q64.64 fee growth of token A per unit of liquidity on the other side of
+    /// the tick from the current price
+    pub fee_growth_outside_a: u128,
+    /// Q64.64 fee growth of token B per unit of liquidity on the other side of
+    /// the tick from the current price
+    pub fee_growth_outside_b: u128,
+}
+
+impl Sealed for TickInfo {}
+impl IsInitialized for TickInfo {
+    fn is_initialized(&self) -> bool {
+        self.liquidity_gross > 0
+    }
+}
+
+impl Pack for TickInfo {
+    const LEN: usize = 68;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 68];
+        let (index, liquidity_net, liquidity_gross, fee_growth_outside_a, fee_growth_outside_b) =
+            mut_array_refs![output, 4, 16, 16, 16, 16];
+        *index = self.index.to_le_bytes();
+        *liquidity_net = self.liquidity_net.to_le_bytes();
+        *liquidity_gross = self.liquidity_gross.to_le_bytes();
+        *fee_growth_outside_a = self.fee_growth_outside_a.to_le_bytes();
+        *fee_growth_outside_b = self.fee_growth_outside_b.to_le_bytes();
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, 68];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (index, liquidity_net, liquidity_gross, fee_growth_outside_a, fee_growth_outside_b) =
+            array_refs![input, 4, 16, 16, 16, 16];
+        Ok(Self {
+            index: i32::from_le_bytes(*index),
+            liquidity_net: i128::from_le_bytes(*liquidity_net),
+            liquidity_gross: u128::from_le_bytes(*liquidity_gross),
+            fee_growth_outside_a: u128::from_le_bytes(*fee_growth_outside_a),
+            fee_growth_outside_b: u128::from_le_bytes(*fee_growth_outside_b),
+        })
+    }
+}
+
+/// Concentrated liquidity pool state, where liquidity providers choose the
+/// price range of their liquidity through positions.
+#[repr(C)]
+#[derive(Debug, Default, PartialEq)]
+pub struct ConcentratedPool {
+    /// Initialized state.
+    pub is_initialized: bool,
+    /// Bump seed used in program address.
+    /// The program address is created deterministically with the bump seed,
+    /// swap program id, and pool account pubkey.  This program address has
+    /// authority over the pool's token A and token B accounts.
+    pub bump_seed: u8,
+
+    /// Token A
+    pub token_a: Pubkey,
+    /// Token B
+    pub token_b: Pubkey,
+
+    /// Mint information for token A
+    pub token_a_mint: Pubkey,
+    /// Mint information for token B
+    pub token_b_mint: Pubkey,
+
+    /// Authority allowed to collect the owner trading fees
+    pub fee_authority: Pubkey,
+
+    /// Authority allowed to pause and resume the pool
+    pub pause_authority: Pubkey,
+    /// If true, the pool is in withdraw-only mode: swaps and new liquidity
+    /// are rejected, while positions can still be withdrawn and closed
+    pub is_paused: bool,
+
+    /// All fee information.  The trading fee goes to the positions providing
+    /// liquidity to the trade, and the owner trading fee to the fee authority.
+    pub fees: Fees,
+
+    /// Position bounds must be a multiple of the tick spacing
+    pub tick_spacing: u16,
+    /// Minimum liquidity of a non-empty position.  Since the pool can only
+    /// track `MAX_TICKS` ticks, this keeps dust positions from using them up.
+    pub min_position_liquidity: u128,
+    /// Greatest tick whose square root price is less than or equal to the
+    /// current square root price
+    pub current_tick: i32,
+    /// Current square root price of token A in terms of token B, as a Q64.64
+    /// number
+    pub sqrt_price: u128,
+    /// Liquidity of all positions containing the current price
+    pub liquidity: u128,
+
+    /// Q64.64 fee growth of token A per unit of liquidity over the life of the
+    /// pool
+    pub fee_growth_global_a: u128,
+    /// Q64.64 fee growth of token B per unit of liquidity over the life of the
+    /// pool
+    pub fee_growth_global_b: u128,
+
+    /// Owner trading fees in token A waiting to be collected
+    pub protocol_fees_a: u64,
+    /// Owner trading fees in token B waiting to be collected
+    pub protocol_fees_b: u64,
+
+    /// Initialized ticks, sorted by index
+    pub ticks: Vec<TickInfo>,
+}
+
+impl ConcentratedPool {
+    /// Check that the tick can be used as a position bound in the pool
+    pub fn validate_tick(&self, tick: i32) -> Result<(), SwapError> {
+        if self.tick_spacing == 0
+            || !(MIN_TICK..=MAX_TICK).contains(&tick)
+            || tick % i32::from(self.tick_spacing) != 0
+        {
+            Err(SwapError::InvalidTick)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Get an initialized tick
+    pub fn tick(&self, index: i32) -> Option<&TickInfo> {
+        self.ticks
+            .binary_search_by_key(&index, |tick| tick.index)
+            .ok()
+            .map(|position| &self.ticks[position])
+    }
+
+    /// Position in `ticks` of the next initialized tick the price reaches when
+    /// trading in the given direction, if any
+    fn next_initialized_tick(&self, trade_direction: TradeDirection) -> Option<usize> {
+        let above = self
+            .ticks
+            .partition_point(|tick| tick.index <= self.current_tick);
+        match trade_direction {
+            TradeDirection::AtoB => above.checked_sub(1),
+            TradeDirection::BtoA => (above < self.ticks.len()).then_some(above),
+        }
+    }
+
+    /// Add liquidity to, or remove liquidity from, a position bound,
+    /// initializing the tick if needed
+    fn update_tick(
+        &mut self,
+        index: i32,
+        liquidity_delta: i128,
+        is_upper: bool,
+    ) -> Result<(), SwapError> {
+        let position = match self.ticks.binary_search_by_key(&index, |tick| tick.index) {
+            Ok(position) => position,
+            Err(position) => {
+                if self.ticks.len() >= MAX_TICKS {
+                    return Err(SwapError::TooManyTicks);
+                }
+                // By convention, all fees so far were earned below the tick
+                let (fee_growth_outside_a, fee_growth_outside_b) = if index <= self.current_tick {
+                    (self.fee_growth_global_a, self.fee_growth_global_b)
+                } else {
+                    (0, 0)
+                };
+                self.ticks.insert(
+                    position,
+                    TickInfo {
+                        index,
+                        fee_growth_outside_a,
+                        fee_growth_outside_b,
+                        ..TickInfo::default()
+                    },
+                );
+                position
+            }
+        };
+        let tick = &mut self.ticks[position];
+        tick.liquidity_gross = add_liquidity_delta(tick.liquidity_gross, liquidity_delta)?;
+        tick.liquidity_net = if is_upper {
+            tick.liquidity_net.checked_sub(liquidity_delta)
+        } else {
+            tick.liquidity_net.checked_add(liquidity_delta)
+        }
+        .ok_or(SwapError::CalculationFailure)?;
+        Ok(())
+    }
+
+    /// Move the price across the initialized tick at the given position in
+    /// `ticks`, updating the active liquidity
+    fn cross_tick(
+        &mut self,
+        position: usize,
+        trade_direction: TradeDirection,
+    ) -> Result<(), SwapError> {
+        let (fee_growth_global_a, fee_growth_global_b) =
+            (self.fee_growth_global_a, self.fee_growth_global_b);
+        let tick = self.ticks.get_mut(position).ok_or(SwapError::InvalidTick)?;
+        let index = tick.index;
+        tick.fee_growth_outside_a = fee_growth_global_a.wrapping_sub(tick.fee_growth_outside_a);
+        tick.fee_growth_outside_b = fee_growth_global_b.wrapping_sub(tick.fee_growth_outside_b);
+        let liquidity_net = tick.liquidity_net;
+        match trade_direction {
+            TradeDirection::AtoB => {
+                self.liquidity = add_liquidity_delta(
+                    self.liquidity,
+                    liquidity_net
+                        .checked_neg()
+                        .ok_or(SwapError::CalculationFailure)?,
+                )?;
+                self.current_tick = index - 1;
+            }
+            TradeDirection::BtoA => {
+                self.liquidity = add_liquidity_delta(self.liquidity, liquidity_net)?;
+                self.current_tick = index;
+            }
+        }
+        Ok(())
+    }
+
+    /// Q64.64 fee growth of tokens A and B per unit of liquidity between two
+    /// initialized ticks
+    pub fn fee_growth_inside(&self, tick_lower: i32, tick_upper: i32) -> Option<(u128, u128)> {
+        let lower = self.tick(tick_lower)?;
+        let upper = self.tick(tick_upper)?;
+        let (below_a, below_b) = if self.current_tick >= tick_lower {
+            (lower.fee_growth_outside_a, lower.fee_growth_outside_b)
+        } else {
+            (
+                self.fee_growth_global_a
+                    .wrapping_sub(lower.fee_growth_outside_a),
+                self.fee_growth_global_b
+                    .wrapping_sub(lower.fee_growth_outside_b),
+            )
+        };
+        let (above_a, above_b) = if self.current_tick < tick_upper {
+            (upper.fee_growth_outside_a, upper.fee_growth_outside_b)
+        } else {
+            (
+                self.fee_growth_global_a
+                    .wrapping_sub(upper.fee_growth_outside_a),
+                self.fee_growth_global_b
+                    .wrapping_sub(upper.fee_growth_outside_b),
+            )
+        };
+        Some((
+            self.fee_growth_global_a
+                .wrapping_sub(below_a)
+                .wrapping_sub(above_a),
+            self.fee_growth_global_b
+                .wrapping_sub(below_b)
+                .wrapping_sub(above_b),
+        ))
+    }
+
+    /// Add liquidity to, or remove liquidity from, a position, crediting the
+    /// position with the fees earned since it was last modified.
+    ///
+    /// Returns the amounts of tokens A and B to deposit, rounded up, or to
+    /// withdraw, rounded down.
+    pub fn modify_position(
+        &mut self,
+        position: &mut Position,
+        liquidity_delta: i128,
+    ) -> Result<TradingTokenResult, SwapError> {
+        let position_liquidity = add_liquidity_delta(position.liquidity, liquidity_delta)?;
+        if liquidity_delta != 0
+            && position_liquidity != 0
+            && position_liquidity < self.min_position_liquidity
+        {
+            return Err(SwapError::PositionLiquidityTooLow);
+        }
+        if liquidity_delta != 0 {
+            self.update_tick(position.tick_lower, liquidity_delta, false)?;
+            self.update_tick(position.tick_upper, liquidity_delta, true)?;
+        }
+
+        if position.liquidity > 0 || liquidity_delta != 0 {
+            let (fee_growth_inside_a, fee_growth_inside_b) = self
+                .fee_growth_inside(position.tick_lower, position.tick_upper)
+                .ok_or(SwapError::InvalidTick)?;
+            let fees_a = fees_for_growth(
+                fee_growth_inside_a.wrapping_sub(position.fee_growth_inside_last_a),
+                position.liquidity,
+            )?;
+            let fees_b = fees_for_growth(
+                fee_growth_inside_b.wrapping_sub(position.fee_growth_inside_last_b),
+                position.liquidity,
+            )?;
+            position.fees_owed_a = position
+                .fees_owed_a
+                .checked_add(fees_a)
+                .ok_or(SwapError::CalculationFailure)?;
+            position.fees_owed_b = position
+                .fees_owed_b
+                .checked_add(fees_b)
+                .ok_or(SwapError::CalculationFailure)?;
+            position.fee_growth_inside_last_a = fee_growth_inside_a;
+            position.fee_growth_inside_last_b = fee_growth_inside_b;
+        }
+
+        position.liquidity = position_liquidity;
+        if (position.tick_lower..position.tick_upper).contains(&self.current_tick) {
+            self.liquidity = add_liquidity_delta(self.liquidity, liquidity_delta)?;
+        }
+        self.ticks.retain(|tick| tick.liquidity_gross > 0);
+
+        let (round_direction, liquidity) = if liquidity_delta > 0 {
+            (RoundDirection::Ceiling, liquidity_delta.unsigned_abs())
+        } else {
+            (RoundDirection::Floor, liquidity_delta.unsigned_abs())
+        };
+        amounts_for_liquidity(
+            self.sqrt_price,
+            sqrt_price_at_tick(position.tick_lower).ok_or(SwapError::InvalidTick)?,
+            sqrt_price_at_tick(position.tick_upper).ok_or(SwapError::InvalidTick)?,
+            liquidity,
+            round_direction,
+        )
+        .ok_or(SwapError::CalculationFailure)
+    }
+
+    /// Swap `source_amount` through the initialized ticks of the pool.  The
+    /// trade must be filled completely.
+    ///
+    /// The trading fee is credited to the liquidity used by each step of the
+    /// trade, and the owner trading fee is returned for the caller to assign.
+    pub fn swap(
+        &mut self,
+        source_amount: u128,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        trade_direction: TradeDirection,
+    ) -> Result<SwapResult, SwapError> {
+        let trade_fee = self
+            .fees
+            .trading_fee(source_amount)
+            .ok_or(SwapError::FeeCalculationFailure)?;
+        let owner_fee = self
+            .fees
+            .owner_trading_fee(source_amount)
+            .ok_or(SwapError::FeeCalculationFailure)?;
+        let source_amount_less_fees = trade_fee
+            .checked_add(owner_fee)
+            .and_then(|total_fees| source_amount.checked_sub(total_fees))
+            .ok_or(SwapError::FeeCalculationFailure)?;
+        if source_amount_less_fees == 0 {
+            return Err(SwapError::ZeroTradingTokens);
+        }
+
+        let mut amount_remaining = source_amount_less_fees;
+        let mut trade_fee_remaining = trade_fee;
+        let mut destination_amount_swapped = 0u128;
+        while amount_remaining > 0 {
+            // step from one initialized tick to the next, the price only
+            // lands between ticks on the last step
+            let next_tick = self.next_initialized_tick(trade_direction);
+            let next_tick_index = next_tick.map(|position| self.ticks[position].index);
+            let (sqrt_price_target, tick_low, tick_high) = match (next_tick_index, trade_direction)
+            {
+                (Some(index), TradeDirection::AtoB) => (
+                    sqrt_price_at_tick(index).ok_or(SwapError::InvalidTick)?,
+                    index,
+                    self.current_tick,
+                ),
+                (Some(index), TradeDirection::BtoA) => (
+                    sqrt_price_at_tick(index).ok_or(SwapError::InvalidTick)?,
+                    self.current_tick,
+                    index - 1,
+                ),
+                (None, TradeDirection::AtoB) => (MIN_SQRT_PRICE, MIN_TICK, self.current_tick),
+                (None, TradeDirection::BtoA) => (MAX_SQRT_PRICE, self.current_tick, MAX_TICK),
+            };
+            let step = compute_swap_step(
+                self.sqrt_price,
+                sqrt_price_target,
+                self.liquidity,
+                amount_remaining,
+                trade_direction,
+            )
+            .ok_or(SwapError::CalculationFailure)?;
+            amount_remaining = amount_remaining
+                .checked_sub(step.amount_in)
+                .ok_or(SwapError::CalculationFailure)?;
+            destination_amount_swapped = destination_amount_swapped
+                .checked_add(step.amount_out)
+                .ok_or(SwapError::CalculationFailure)?;
+
+            // share of the trading fee for the liquidity used in this step,
+            // with the last step taking any rounding remainder
+            let step_trade_fee = if amount_remaining == 0 {
+                trade_fee_remaining
+            } else {
+                U256::from(trade_fee)
+                    .checked_mul(U256::from(step.amount_in))
+                    .and_then(|fee| fee.checked_div(U256::from(source_amount_less_fees)))
+                    .and_then(|fee| u128::try_from(fee).ok())
+                    .ok_or(SwapError::FeeCalculationFailure)?
+            };
+            if step_trade_fee > 0 && self.liquidity > 0 {
+                trade_fee_remaining = trade_fee_remaining
+                    .checked_sub(step_trade_fee)
+                    .ok_or(SwapError::FeeCalculationFailure)?;
+                let fee_growth = growth_for_fees(step_trade_fee, self.liquidity)?;
+                match trade_direction {
+                    TradeDirection::AtoB => {
+                        self.fee_growth_global_a = self.fee_growth_global_a.wrapping_add(fee_growth)
+                    }
+                    TradeDirection::BtoA => {
+                        self.fee_growth_global_b = self.fee_growth_global_b.wrapping_add(fee_growth)
+                    }
+                }
+            }
+
+            let sqrt_price_start = self.sqrt_price;
+            self.sqrt_price = step.sqrt_price_next;
+            if step.sqrt_price_next == sqrt_price_target {
+                match next_tick {
+                    Some(position) => self.cross_tick(position, trade_direction)?,
+                    None if amount_remaining > 0 => return Err(SwapError::InsufficientLiquidity),
+                    None => {
+                        self.current_tick = match trade_direction {
+                            TradeDirection::AtoB => MIN_TICK,
+                            TradeDirection::BtoA => MAX_TICK,
+                        }
+                    }
+                }
+            } else if step.sqrt_price_next != sqrt_price_start {
+                self.current_tick =
+                    tick_at_sqrt_price_between(self.sqrt_price, tick_low, tick_high)
+                        .ok_or(SwapError::CalculationFailure)?;
+            }
+        }
+        if destination_amount_swapped == 0 {
+            return Err(SwapError::ZeroTradingTokens);
+        }
+
+        Ok(SwapResult {
+            new_swap_source_amount: swap_source_amount
+                .checked_add(source_amount)
+                .ok_or(SwapError::CalculationFailure)?,
+            new_swap_destination_amount: swap_destination_amount
+                .checked_sub(destination_amount_swapped)
+                .ok_or(SwapError::InsufficientLiquidity)?,
+            source_amount_swapped: source_amount,
+            destination_amount_swapped,
+            trade_fee,
+            owner_fee,
+        })
+    }
+}
+
+impl Sealed for ConcentratedPool {}
+impl IsInitialized for ConcentratedPool {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for ConcentratedPool {
+    const LEN: usize = 361 + MAX_TICKS * TickInfo::LEN;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, ConcentratedPool::LEN];
+        let (
+            tag,
+            bump_seed,
+            token_a,
+            token_b,
+            token_a_mint,
+            token_b_mint,
+            fee_authority,
+            pause_authority,
+            is_paused,
+            fees,
+            tick_spacing,
+            min_position_liquidity,
+            current_tick,
+            sqrt_price,
+            liquidity,
+            fee_growth_global_a,
+            fee_growth_global_b,
+            protocol_fees_a,
+            protocol_fees_b,
+            ticks,
+        ) = mut_array_refs![
+            output,
+            1,
+            1,
+            32,
+            32,
+            32,
+            32,
+            32,
+            32,
+            1,
+            64,
+            2,
+            16,
+            4,
+            16,
+            16,
+            16,
+            16,
+            8,
+            8,
+            MAX_TICKS * TickInfo::LEN
+        ];
+        tag[0] = if self.is_initialized {
+            CONCENTRATED_POOL_TAG
+        } else {
+            0
+        };
+        bump_seed[0] = self.bump_seed;
+        token_a.copy_from_slice(self.token_a.as_ref());
+        token_b.copy_from_slice(self.token_b.as_ref());
+        token_a_mint.copy_from_slice(self.token_a_mint.as_ref());
+        token_b_mint.copy_from_slice(self.token_b_mint.as_ref());
+        fee_authority.copy_from_slice(self.fee_authority.as_ref());
+        pause_authority.copy_from_slice(self.pause_authority.as_ref());
+        is_paused[0] = u8::from(self.is_paused);
+        self.fees.pack_into_slice(&mut fees[..]);
+        *tick_spacing = self.tick_spacing.to_le_bytes();
+        *min_position_liquidity = self.min_position_liquidity.to_le_bytes();
+        *current_tick = self.current_tick.to_le_bytes();
+        *sqrt_price = self.sqrt_price.to_le_bytes();
+        *liquidity = self.liquidity.to_le_bytes();
+        *fee_growth_global_a = self.fee_growth_global_a.to_le_bytes();
+        *fee_growth_global_b = self.fee_growth_global_b.to_le_bytes();
+        *protocol_fees_a = self.protocol_fees_a.to_le_bytes();
+        *protocol_fees_b = self.protocol_fees_b.to_le_bytes();
+        let mut pool_ticks = self.ticks.iter();
+        for dst in ticks.chunks_exact_mut(TickInfo::LEN) {
+            match pool_ticks.next() {
+                Some(tick) => tick.pack_into_slice(dst),
+                None => dst.fill(0),
+            }
+        }
+    }
+
+    /// Unpacks a byte buffer into a [ConcentratedPool](struct.ConcentratedPool.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, ConcentratedPool::LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            tag,
+            bump_seed,
+            token_a,
+            token_b,
+            token_a_mint,
+            token_b_mint,
+            fee_authority,
+            pause_authority,
+            is_paused,
+            fees,
+            tick_spacing,
+            min_position_liquidity,
+            current_tick,
+            sqrt_price,
+            liquidity,
+            fee_growth_global_a,
+            fee_growth_global_b,
+            protocol_fees_a,
+            protocol_fees_b,
+            ticks,
+        ) = array_refs![
+            input,
+            1,
+            1,
+            32,
+            32,
+            32,
+            32,
+            32,
+            32,
+            1,
+            64,
+            2,
+            16,
+            4,
+            16,
+            16,
+            16,
+            16,
+            8,
+            8,
+            MAX_TICKS * TickInfo::LEN
+        ];
+        Ok(Self {
+            is_initialized: unpack_tag(tag[0], CONCENTRATED_POOL_TAG)?,
+            bump_seed: bump_seed[0],
+            token_a: Pubkey::new_from_array(*token_a),
+            token_b: Pubkey::new_from_array(*token_b),
+            token_a_mint: Pubkey::new_from_array(*token_a_mint),
+            token_b_mint: Pubkey::new_from_array(*token_b_mint),
+            fee_authority: Pubkey::new_from_array(*fee_authority),
+            pause_authority: Pubkey::new_from_array(*pause_authority),
+            is_paused: match is_paused {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            fees: Fees::unpack_from_slice(fees)?,
+            tick_spacing: u16::from_le_bytes(*tick_spacing),
+            min_position_liquidity: u128::from_le_bytes(*min_position_liquidity),
+            current_tick: i32::from_le_bytes(*current_tick),
+            sqrt_price: u128::from_le_bytes(*sqrt_price),
+            liquidity: u128::from_le_bytes(*liquidity),
+            fee_growth_global_a: u128::from_le_bytes(*fee_growth_global_a),
+            fee_growth_global_b: u128::from_le_bytes(*fee_growth_global_b),
+            protocol_fees_a: u64::from_le_bytes(*protocol_fees_a),
+            protocol_fees_b: u64::from_le_bytes(*protocol_fees_b),
+            ticks: ticks
+                .chunks_exact(TickInfo::LEN)
+                .map(TickInfo::unpack_from_slice)
+                .filter(|tick| tick.as_ref().map_or(true, |tick| tick.is_initialized()))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+/// Liquidity provided by an owner to a concentrated liquidity pool, between
+/// two ticks.
+#[repr(C)]
+#[derive(Debug, Default, PartialEq)]
+pub struct Position {
+    /// Initialized state.
+    pub is_initialized: bool,
+    /// Concentrated liquidity pool
+    pub pool: Pubkey,
+    /// Owner allowed to modify the position
+    pub owner: Pubkey,
+    /// Lower tick bound, inclusive
+    pub tick_lower: i32,
+    /// Upper tick bound, exclusive
+    pub tick_upper: i32,
+    /// Liquidity provided in the range
+    pub liquidity: u128,
+    /// Q64.64 fee growth of token A inside the range, as of the last
+    /// modification of the position
+    pub fee_growth_inside_last_a: u128,
+    /// Q64.64 fee growth of token B inside the range, as of the last
+    /// modification of the position
+    pub fee_growth_inside_last_b: u128,
+    /// Token A fees earned by the position, not yet collected
+    pub fees_owed_a: u64,
+    /// Token B fees earned by the position, not yet collected
+    pub fees_owed_b: u64,
+}
+
+impl Sealed for Position {}
+impl IsInitialized for Position {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for Position {
+    const LEN: usize = 137;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 137];
+        let (
+            tag,
+            pool,
+            owner,
+            tick_lower,
+            tick_upper,
+            liquidity,
+            fee_growth_inside_last_a,
+            fee_growth_inside_last_b,
+            fees_owed_a,
+            fees_owed_b,
+        ) = mut_array_refs![output, 1, 32, 32, 4, 4, 16, 16, 16, 8, 8];
+        tag[0] = if self.is_initialized { POSITION_TAG } else { 0 };
+        pool.copy_from_slice(self.pool.as_ref());
+        owner.copy_from_slice(self.owner.as_ref());
+        *tick_lower = self.tick_lower.to_le_bytes();
+        *tick_upper = self.tick_upper.to_le_bytes();
+        *liquidity = self.liquidity.to_le_bytes();
+        *fee_growth_inside_last_a = self.fee_growth_inside_last_a.to_le_bytes();
+        *fee_growth_inside_last_b = self.fee_growth_inside_last_b.to_le_bytes();
+        *fees_owed_a = self.fees_owed_a.to_le_bytes();
+        *fees_owed_b = self.fees_owed_b.to_le_bytes();
+    }
+
+    /// Unpacks a byte buffer into a [Position](struct.Position.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, 137];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            tag,
+            pool,
+            owner,
+            tick_lower,
+            tick_upper,
+            liquidity,
+            fee_growth_inside_last_a,
+            fee_growth_inside_last_b,
+            fees_owed_a,
+            fees_owed_b,
+        ) = array_refs![input, 1, 32, 32, 4, 4, 16, 16, 16, 8, 8];
+        Ok(Self {
+            is_initialized: unpack_tag(tag[0], POSITION_TAG)?,
+            pool: Pubkey::new_from_array(*pool),
+            owner: Pubkey::new_from_array(*owner),
+            tick_lower: i32::from_le_bytes(*tick_lower),
+            tick_upper: i32::from_le_bytes(*tick_upper),
+            liquidity: u128::from_le_bytes(*liquidity),
+            fee_growth_inside_last_a: u128::from_le_bytes(*fee_growth_inside_last_a),
+            fee_growth_inside_last_b: u128::from_le_bytes(*fee_growth_inside_last_b),
+            fees_owed_a: u64::from_le_bytes(*fees_owed_a),
+            fees_owed_b: u64::from_le_bytes(*fees_owed_b),
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use {super::*, crate::curve::offset::OffsetCurve, std::convert::TryInto};
@@ -304,6 +1333,7 @@ mod tests {
     const TEST_TOKEN_A_MINT: Pubkey = Pubkey::new_from_array([5u8; 32]);
     const TEST_TOKEN_B_MINT: Pubkey = Pubkey::new_from_array([6u8; 32]);
     const TEST_POOL_FEE_ACCOUNT: Pubkey = Pubkey::new_from_array([7u8; 32]);
+    const TEST_PAUSE_AUTHORITY: Pubkey = Pubkey::new_from_array([8u8; 32]);
 
     const TEST_CURVE_TYPE: u8 = 2;
     const TEST_TOKEN_B_OFFSET: u64 = 1_000_000_000;
@@ -406,4 +1436,84 @@ mod tests {
         let err = SwapV1::unpack(&packed).unwrap_err();
         assert_eq!(err, ProgramError::UninitializedAccount);
     }
+
+    #[test]
+    fn swap_v2_pack() {
+        let curve_type = TEST_CURVE_TYPE.try_into().unwrap();
+        let calculator = Arc::new(TEST_CURVE);
+        let swap_curve = SwapCurve {
+            curve_type,
+            calculator,
+        };
+        let swap_info = SwapV2 {
+            is_initialized: true,
+            bump_seed: TEST_BUMP_SEED,
+            token_program_id: TEST_TOKEN_PROGRAM_ID,
+            token_a: TEST_TOKEN_A,
+            token_b: TEST_TOKEN_B,
+            pool_mint: TEST_POOL_MINT,
+            token_a_mint: TEST_TOKEN_A_MINT,
+            token_b_mint: TEST_TOKEN_B_MINT,
+            pool_fee_account: TEST_POOL_FEE_ACCOUNT,
+            fees: TEST_FEES,
+            swap_curve,
+            pause_authority: TEST_PAUSE_AUTHORITY,
+            is_paused: true,
+        };
+
+        let mut packed = [0u8; SwapV2::LEN];
+        SwapV2::pack_into_slice(&swap_info, &mut packed);
+        let unpacked = SwapV2::unpack(&packed).unwrap();
+        assert_eq!(swap_info, unpacked);
+
+        let mut v1_packed = [0u8; SwapV1::LEN];
+        v1_packed.copy_from_slice(&packed[..SwapV1::LEN]);
+        let mut expect = v1_packed.to_vec();
+        expect.extend_from_slice(&TEST_PAUSE_AUTHORITY.to_bytes());
+        expect.push(1);
+        assert_eq!(packed.to_vec(), expect);
+
+        let mut versioned = [0u8; SwapVersion::LATEST_LEN];
+        SwapVersion::pack(SwapVersion::SwapV2(swap_info), &mut versioned).unwrap();
+        let unpacked = SwapVersion::unpack(&versioned).unwrap();
+        assert!(unpacked.is_paused());
+        assert_eq!(unpacked.pause_authority(), Some(&TEST_PAUSE_AUTHORITY));
+        assert!(SwapVersion::unpack_latest(&versioned).unwrap().is_paused);
+
+        let mut invalid = packed;
+        invalid[SwapV2::LEN - 1] = 2;
+        let err = SwapV2::unpack(&invalid).unwrap_err();
+        assert_eq!(err, ProgramError::InvalidAccountData);
+    }
+
+    #[test]
+    fn swap_v1_cannot_be_unpacked_as_latest() {
+        let curve_type = TEST_CURVE_TYPE.try_into().unwrap();
+        let calculator = Arc::new(TEST_CURVE);
+        let swap_curve = SwapCurve {
+            curve_type,
+            calculator,
+        };
+        let swap_info = SwapVersion::SwapV1(SwapV1 {
+            is_initialized: true,
+            bump_seed: TEST_BUMP_SEED,
+            token_program_id: TEST_TOKEN_PROGRAM_ID,
+            token_a: TEST_TOKEN_A,
+            token_b: TEST_TOKEN_B,
+            pool_mint: TEST_POOL_MINT,
+            token_a_mint: TEST_TOKEN_A_MINT,
+            token_b_mint: TEST_TOKEN_B_MINT,
+            pool_fee_account: TEST_POOL_FEE_ACCOUNT,
+            fees: TEST_FEES,
+            swap_curve,
+        });
+
+        let mut packed = [0u8; SwapVersion::V1_LEN];
+        SwapVersion::pack(swap_info, &mut packed).unwrap();
+        let unpacked = SwapVersion::unpack(&packed).unwrap();
+        assert!(!unpacked.is_paused());
+        assert_eq!(unpacked.pause_authority(), None);
+        let err = SwapVersion::unpack_latest(&packed).unwrap_err();
+        assert_eq!(err, SwapError::UnsupportedSwapVersion.into());
+    }
 }